@@ -3,6 +3,7 @@
 
 import { Button } from "@/components/ui/button";
 import Link from "next/link";
+import { screenpipeFetch } from "@/lib/api";
 import {
   DropdownMenu,
   DropdownMenuTrigger,
@@ -35,7 +36,7 @@ async function queryScreenpipe(params: {
       start_time: params.start_time,
       end_time: params.end_time,
     });
-    const response = await fetch(`http://localhost:3030/search?${queryParams}`);
+    const response = await screenpipeFetch(`/search?${queryParams}`);
     if (!response.ok) {
       throw new Error(`HTTP error! status: ${response.status}`);
     }
@@ -16,6 +16,7 @@ import { Switch } from "./ui/switch";
 import { Label } from "./ui/label";
 import { Badge } from "./ui/badge";
 import { usePostHog } from "posthog-js/react";
+import { screenpipeFetch } from "@/lib/api";
 
 const screenpipeQuery = z.object({
   q: z
@@ -75,7 +76,7 @@ async function queryScreenpipe(params: z.infer<typeof screenpipeQuery>) {
       }).filter(([_, v]) => v != null) as [string, string][]
     );
     console.log("calling screenpipe", JSON.stringify(params));
-    const response = await fetch(`http://localhost:3030/search?${queryParams}`);
+    const response = await screenpipeFetch(`/search?${queryParams}`);
     if (!response.ok) {
       const text = await response.text();
       throw new Error(`HTTP error! status: ${response.status} ${text}`);
@@ -24,6 +24,7 @@ import { Settings, useSettings } from "@/lib/hooks/use-settings";
 import { useToast } from "@/components/ui/use-toast";
 import { useHealthCheck } from "@/lib/hooks/use-health-check";
 import { invoke } from "@tauri-apps/api/core";
+import { screenpipeFetch } from "@/lib/api";
 import { Badge } from "./ui/badge";
 
 interface AudioDevice {
@@ -64,8 +65,8 @@ export function RecordingSettings({
     const loadDevices = async () => {
       try {
         // Fetch monitors
-        const monitorsResponse = await fetch(
-          "http://localhost:3030/vision/list",
+        const monitorsResponse = await screenpipeFetch(
+          "/vision/list",
           {
             method: "POST",
           }
@@ -78,8 +79,8 @@ export function RecordingSettings({
         setAvailableMonitors(monitors);
 
         // Fetch audio devices
-        const audioDevicesResponse = await fetch(
-          "http://localhost:3030/audio/list"
+        const audioDevicesResponse = await screenpipeFetch(
+          "/audio/list"
         );
         if (!audioDevicesResponse.ok) {
           throw new Error("Failed to fetch audio devices");
@@ -0,0 +1,21 @@
+import { invoke } from "@tauri-apps/api/core";
+
+export const SCREENPIPE_URL = "http://localhost:3030";
+
+let appToken: Promise<string> | undefined;
+
+// the token the app starts screenpipe with, without it the app gets redacted text and no tools
+function getAppToken(): Promise<string> {
+  appToken ??= invoke<string>("get_app_token");
+  return appToken;
+}
+
+// fetch of the screenpipe api, as the app
+export async function screenpipeFetch(
+  path: string,
+  init: RequestInit = {}
+): Promise<Response> {
+  const headers = new Headers(init.headers);
+  headers.set("Authorization", `Bearer ${await getAppToken()}`);
+  return fetch(`${SCREENPIPE_URL}${path}`, { ...init, headers });
+}
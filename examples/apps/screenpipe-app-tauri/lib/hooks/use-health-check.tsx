@@ -1,4 +1,5 @@
 import { useState, useEffect } from "react";
+import { screenpipeFetch } from "@/lib/api";
 
 interface HealthCheckResponse {
   status: string;
@@ -15,7 +16,7 @@ export function useHealthCheck() {
 
   const fetchHealth = async () => {
     try {
-      const response = await fetch("http://localhost:3030/health");
+      const response = await screenpipeFetch("/health");
       if (!response.ok) {
         const text = await response.text();
         throw new Error(`HTTP error! status: ${response.status} ${text}`);
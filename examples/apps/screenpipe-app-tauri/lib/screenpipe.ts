@@ -1,4 +1,5 @@
 import { z } from "zod";
+import { screenpipeFetch } from "./api";
 
 export const screenpipeQuery = z.object({
   q: z
@@ -89,7 +90,7 @@ export async function queryScreenpipe(params: z.infer<typeof screenpipeQuery>) {
       }).filter(([_, v]) => v != null) as [string, string][]
     );
     console.log("calling screenpipe", JSON.stringify(params));
-    const response = await fetch(`http://localhost:3030/search?${queryParams}`);
+    const response = await screenpipeFetch(`/search?${queryParams}`);
     if (!response.ok) {
       const text = await response.text();
       throw new Error(`HTTP error! status: ${response.status} ${text}`);
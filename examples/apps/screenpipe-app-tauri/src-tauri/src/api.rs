@@ -0,0 +1,51 @@
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use std::sync::OnceLock;
+
+/// Read by screenpipe at start, the token it takes for the app's (see --app-token)
+pub const APP_TOKEN_ENV: &str = "SCREENPIPE_APP_TOKEN";
+
+static APP_TOKEN: OnceLock<String> = OnceLock::new();
+
+/// The token the sidecar is started with. The one screenpipe saved in its default data
+/// directory is kept, so a screenpipe started by hand with the defaults knows the app too
+pub fn app_token() -> &'static str {
+    APP_TOKEN.get_or_init(|| {
+        dirs::home_dir()
+            .and_then(|home| {
+                std::fs::read_to_string(home.join(".screenpipe").join("app_token")).ok()
+            })
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+            .unwrap_or_else(|| {
+                format!(
+                    "{}{}",
+                    uuid::Uuid::new_v4().simple(),
+                    uuid::Uuid::new_v4().simple()
+                )
+            })
+    })
+}
+
+/// Client of the screenpipe API sending the app's token. Without it the server takes the app
+/// for an unknown caller: answers are redacted, /ask has no tools and consent is refused
+pub fn client() -> reqwest::Client {
+    static CLIENT: OnceLock<reqwest::Client> = OnceLock::new();
+    CLIENT
+        .get_or_init(|| {
+            let mut authorization = HeaderValue::from_str(&format!("Bearer {}", app_token()))
+                .expect("the app token is a valid header value");
+            authorization.set_sensitive(true);
+            let headers = HeaderMap::from_iter([(AUTHORIZATION, authorization)]);
+            reqwest::Client::builder()
+                .default_headers(headers)
+                .build()
+                .expect("the http client is built with default settings")
+        })
+        .clone()
+}
+
+/// For the web views, which call the API too
+#[tauri::command]
+pub fn get_app_token() -> String {
+    app_token().to_string()
+}
@@ -0,0 +1,111 @@
+use crate::{api, pause};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::AppHandle;
+use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+use tauri_plugin_notification::NotificationExt;
+
+const COMPLIANCE_URL: &str = "http://localhost:3030/compliance";
+/// The sidecar takes a while to come up at login
+const STARTUP_RETRY: Duration = Duration::from_secs(2);
+const STARTUP_ATTEMPTS: u32 = 60;
+
+#[derive(Deserialize)]
+struct ComplianceStatus {
+    enabled: bool,
+    organization: Option<String>,
+    consent_text: Option<String>,
+    #[serde(default)]
+    allowed_apps: Vec<String>,
+    consent_given: bool,
+}
+
+/// Set on machines with a compliance policy, the tray then always shows that capture is on
+static ORGANIZATION: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn organization() -> Option<String> {
+    ORGANIZATION.lock().unwrap().clone()
+}
+
+async fn fetch_status() -> anyhow::Result<ComplianceStatus> {
+    Ok(api::client()
+        .get(COMPLIANCE_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+async fn send_consent(app: &AppHandle, accepted: bool) -> anyhow::Result<()> {
+    // only the app can give consent, with its token
+    api::client()
+        .post(format!("{}/consent", COMPLIANCE_URL))
+        .json(&json!({ "accepted": accepted }))
+        .send()
+        .await?
+        .error_for_status()?;
+    if !accepted {
+        let _ = app
+            .notification()
+            .builder()
+            .title("capture is off")
+            .body(
+                "screenpipe won't record until you agree, you'll be asked again at your next login",
+            )
+            .show();
+    }
+    pause::refresh_tray(app).await;
+    Ok(())
+}
+
+/// Asks for consent once the sidecar is up when the machine has a compliance policy,
+/// capture doesn't start before the user agrees
+pub fn start_consent_prompt(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut status = None;
+        for _ in 0..STARTUP_ATTEMPTS {
+            if let Ok(s) = fetch_status().await {
+                status = Some(s);
+                break;
+            }
+            tokio::time::sleep(STARTUP_RETRY).await;
+        }
+        let Some(status) = status.filter(|status| status.enabled) else {
+            return;
+        };
+        let organization = status.organization.unwrap_or_default();
+        *ORGANIZATION.lock().unwrap() = Some(organization.clone());
+        pause::refresh_tray(&app).await;
+        if status.consent_given {
+            return;
+        }
+
+        info!(
+            "Asking for consent to the capture policy of {}",
+            organization
+        );
+        let message = format!(
+            "{}\n\nOnly these apps are recorded: {}",
+            status.consent_text.unwrap_or_default(),
+            status.allowed_apps.join(", ")
+        );
+        let dialog_app = app.clone();
+        app.dialog()
+            .message(message)
+            .title(format!("screen recording for {}", organization))
+            .kind(MessageDialogKind::Info)
+            .ok_button_label("I agree")
+            .cancel_button_label("Don't record")
+            .show(move |accepted| {
+                tauri::async_runtime::spawn(async move {
+                    if let Err(e) = send_consent(&dialog_app, accepted).await {
+                        error!("Failed to send consent: {}", e);
+                    }
+                });
+            });
+    });
+}
@@ -0,0 +1,67 @@
+use crate::api;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tauri::AppHandle;
+use tauri_plugin_clipboard_manager::ClipboardExt;
+use tauri_plugin_global_shortcut::Shortcut;
+use tauri_plugin_notification::NotificationExt;
+
+const COPY_URL: &str = "http://localhost:3030/ocr/copy";
+pub const DEFAULT_COPY_SHORTCUT: &str = "CommandOrControl+Alt+Shift+C";
+
+static COPY_SHORTCUT: OnceLock<Shortcut> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct ScreenText {
+    text: String,
+    app_name: String,
+}
+
+/// Remembers which registered shortcut copies the text of the screen
+pub fn set_shortcut(shortcut: &str) -> anyhow::Result<Shortcut> {
+    let shortcut = Shortcut::from_str(shortcut)?;
+    let _ = COPY_SHORTCUT.set(shortcut);
+    Ok(shortcut)
+}
+
+pub fn is_copy_shortcut(shortcut: &Shortcut) -> bool {
+    COPY_SHORTCUT.get() == Some(shortcut)
+}
+
+/// What the hotkey does, puts the text of the focused window on the clipboard
+pub async fn copy(app: &AppHandle) -> anyhow::Result<()> {
+    let response = api::client()
+        .post(COPY_URL)
+        .json(&json!({"clean": true}))
+        .send()
+        .await?;
+    let body = if response.status() == reqwest::StatusCode::NOT_FOUND {
+        "there is no text in the focused window".to_string()
+    } else {
+        let copied: ScreenText = response.error_for_status()?.json().await?;
+        app.clipboard().write_text(copied.text.clone())?;
+        info!(
+            "Copied {} characters of {}",
+            copied.text.len(),
+            copied.app_name
+        );
+        format!(
+            "{} lines of {} copied",
+            copied.text.lines().count(),
+            copied.app_name
+        )
+    };
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title("screen text")
+        .body(body)
+        .show()
+    {
+        error!("Failed to show notification: {}", e);
+    }
+    Ok(())
+}
@@ -23,6 +23,7 @@ use tauri::{
 };
 use tauri_plugin_autostart::MacosLauncher;
 use tauri_plugin_autostart::ManagerExt;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, ShortcutState};
 use tauri_plugin_shell::process::CommandChild;
 use tauri_plugin_shell::process::CommandEvent;
 use tauri_plugin_shell::ShellExt;
@@ -30,9 +31,15 @@ use tauri_plugin_store::{with_store, StoreCollection};
 use tokio::time::sleep;
 use uuid::Uuid;
 mod analytics;
+mod api;
 
 use crate::analytics::start_analytics;
+mod compliance;
+mod copy;
 mod logs;
+mod markers;
+mod pause;
+mod watches;
 
 struct SidecarState(Arc<Mutex<Option<CommandChild>>>);
 
@@ -93,6 +100,69 @@ async fn kill_all_sreenpipes(
     Err("Failed to kill screenpipe processes after multiple attempts".to_string())
 }
 
+#[tauri::command]
+async fn pause_capture(
+    app: tauri::AppHandle,
+    duration_secs: Option<u64>,
+) -> Result<pause::PauseStatus, String> {
+    pause::pause(&app, duration_secs.map(Duration::from_secs))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+async fn resume_capture(app: tauri::AppHandle) -> Result<pause::PauseStatus, String> {
+    pause::resume(&app).await.map_err(|e| e.to_string())
+}
+
+/// Restarts screenpipe recording to another profile, None for the one bound to the OS user
+#[tauri::command]
+async fn switch_profile(
+    state: State<'_, SidecarState>,
+    app: tauri::AppHandle,
+    profile: Option<String>,
+) -> Result<(), String> {
+    let stores = app.state::<StoreCollection<Wry>>();
+    let base_dir = get_base_dir(&app, None).map_err(|e| e.to_string())?;
+    with_store(app.clone(), stores, base_dir.join("store.bin"), |store| {
+        match &profile {
+            Some(profile) => store.insert("profile".to_string(), Value::String(profile.clone()))?,
+            None => {
+                store.delete("profile")?;
+            }
+        }
+        store.save()
+    })
+    .map_err(|e| e.to_string())?;
+
+    kill_all_sreenpipes(state.clone(), app.clone()).await?;
+    let child = spawn_sidecar(&app)?;
+    state.0.lock().unwrap().replace(child);
+    info!("Switched to profile {:?}", profile);
+    Ok(())
+}
+
+/// The checks of `screenpipe --doctor`, for the setup screens to show what is left to fix
+#[tauri::command]
+async fn run_doctor(app: tauri::AppHandle) -> Result<Value, String> {
+    let output = app
+        .shell()
+        .sidecar("screenpipe")
+        .map_err(|e| e.to_string())?
+        .args(["--doctor"])
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+    // the report is the last line, after the logs; it exits with 1 when a check failed
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report = stdout
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .ok_or_else(|| String::from_utf8_lossy(&output.stderr).into_owned())?;
+    serde_json::from_str(report).map_err(|e| format!("invalid doctor report: {}", e))
+}
+
 #[tauri::command]
 async fn spawn_screenpipe(
     state: State<'_, SidecarState>,
@@ -110,7 +180,12 @@ async fn spawn_screenpipe(
 }
 
 fn spawn_sidecar(app: &tauri::AppHandle) -> Result<CommandChild, String> {
-    let sidecar = app.shell().sidecar("screenpipe").unwrap();
+    // the server takes requests with this token for the app's
+    let sidecar = app
+        .shell()
+        .sidecar("screenpipe")
+        .unwrap()
+        .env(api::APP_TOKEN_ENV, api::app_token());
     // Get the current settings
     let stores = app.state::<StoreCollection<Wry>>();
     let base_dir = get_base_dir(app, None).expect("Failed to ensure local data directory");
@@ -151,6 +226,13 @@ fn spawn_sidecar(app: &tauri::AppHandle) -> Result<CommandChild, String> {
     .map_err(|e| e.to_string())?
     .unwrap_or_default();
 
+    let profile = with_store(app.clone(), stores.clone(), path.clone(), |store| {
+        Ok(store
+            .get("profile")
+            .and_then(|v| v.as_str().map(String::from)))
+    })
+    .map_err(|e| e.to_string())?;
+
     let _data_dir_str = base_dir.to_string_lossy();
     let mut args = vec!["--port", "3030"];
     // if macos do --fps 0.2
@@ -170,6 +252,12 @@ fn spawn_sidecar(app: &tauri::AppHandle) -> Result<CommandChild, String> {
         let model = ocr_engine.as_str();
         args.push(model);
     }
+    // without one the server picks the profile bound to the OS user
+    if let Some(profile) = &profile {
+        args.push("--profile");
+        args.push(profile.as_str());
+    }
+
     if monitor_id != "default" {
         args.push("--monitor-id");
         let id = monitor_id.as_str();
@@ -263,14 +351,44 @@ async fn main() {
         .plugin(tauri_plugin_notification::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_process::init())
+        .plugin(tauri_plugin_clipboard_manager::init())
         .plugin(tauri_plugin_autostart::init(
             MacosLauncher::LaunchAgent,
             None,
         ))
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(|app, shortcut, event| {
+                    if event.state() == ShortcutState::Pressed {
+                        let app = app.clone();
+                        let marker = markers::is_marker_shortcut(shortcut);
+                        let copy = copy::is_copy_shortcut(shortcut);
+                        tauri::async_runtime::spawn(async move {
+                            if marker {
+                                if let Err(e) = markers::capture(&app).await {
+                                    error!("Failed to capture the moment: {}", e);
+                                }
+                            } else if copy {
+                                if let Err(e) = copy::copy(&app).await {
+                                    error!("Failed to copy the text of the screen: {}", e);
+                                }
+                            } else if let Err(e) = pause::toggle(&app).await {
+                                error!("Failed to toggle capture pause: {}", e);
+                            }
+                        });
+                    }
+                })
+                .build(),
+        )
         .manage(sidecar_state)
         .invoke_handler(tauri::generate_handler![
             spawn_screenpipe,
             kill_all_sreenpipes,
+            switch_profile,
+            run_doctor,
+            pause_capture,
+            resume_capture,
+            api::get_app_token,
         ])
         .setup(move |app| {
             // run this on windows only
@@ -344,17 +462,44 @@ async fn main() {
 
             if let Some(main_tray) = app.tray_by_id("screenpipe_main") {
                 // Add System Tray
+                let pause_item = MenuItemBuilder::with_id("pause", "Pause capture").build(app)?;
+                let timed_pause_item =
+                    MenuItemBuilder::with_id("pause_timed", "Pause capture for 15 minutes")
+                        .build(app)?;
+                let resume_item =
+                    MenuItemBuilder::with_id("resume", "Resume capture").build(app)?;
                 let toggle = MenuItemBuilder::with_id("quit", "Quit screenpipe").build(app)?;
-                let menu = MenuBuilder::new(app).items(&[&toggle]).build()?;
+                let menu = MenuBuilder::new(app)
+                    .items(&[&pause_item, &timed_pause_item, &resume_item])
+                    .separator()
+                    .items(&[&toggle])
+                    .build()?;
 
                 let _ = main_tray.set_menu(Some(menu));
                 main_tray.on_menu_event(move |app, event| match event.id().as_ref() {
+                    "pause" | "pause_timed" | "resume" => {
+                        let app = app.clone();
+                        let id = event.id().as_ref().to_string();
+                        tauri::async_runtime::spawn(async move {
+                            let result = match id.as_str() {
+                                "pause" => pause::pause(&app, None).await,
+                                "pause_timed" => pause::pause(&app, Some(pause::TIMED_PAUSE)).await,
+                                _ => pause::resume(&app).await,
+                            };
+                            if let Err(e) = result {
+                                error!("Failed to {} capture: {}", id, e);
+                            }
+                        });
+                    }
                     "quit" => {
                         println!("quit clicked");
                         app.exit(0);
                     }
                     _ => (),
                 });
+                pause::start_status_updates(app.handle().clone());
+                compliance::start_consent_prompt(app.handle().clone());
+                watches::start_alert_notifications(app.handle().clone());
                 main_tray.on_tray_icon_event(move |_tray, event| match event {
                     tauri::tray::TrayIconEvent::Click {
                         button,
@@ -441,16 +586,54 @@ async fn main() {
             );
 
             let mut use_dev_mode = false;
+            let mut pause_shortcut = pause::DEFAULT_PAUSE_SHORTCUT.to_string();
+            let mut marker_shortcut = markers::DEFAULT_MARKER_SHORTCUT.to_string();
+            let mut copy_shortcut = copy::DEFAULT_COPY_SHORTCUT.to_string();
             let _ = with_store(app.app_handle().clone(), stores, path, |store| {
                 use_dev_mode = store
                     .get("devMode")
                     .unwrap_or(&Value::Bool(false))
                     .as_bool()
                     .unwrap_or(false);
+                if let Some(shortcut) = store.get("pauseShortcut").and_then(|v| v.as_str()) {
+                    pause_shortcut = shortcut.to_string();
+                }
+                if let Some(shortcut) = store.get("markerShortcut").and_then(|v| v.as_str()) {
+                    marker_shortcut = shortcut.to_string();
+                }
+                if let Some(shortcut) = store.get("copyShortcut").and_then(|v| v.as_str()) {
+                    copy_shortcut = shortcut.to_string();
+                }
 
                 Ok(())
             });
 
+            if let Err(e) = app.global_shortcut().register(pause_shortcut.as_str()) {
+                error!(
+                    "Failed to register pause shortcut {}: {}",
+                    pause_shortcut, e
+                );
+            }
+            match markers::set_shortcut(&marker_shortcut) {
+                Ok(shortcut) => {
+                    if let Err(e) = app.global_shortcut().register(shortcut) {
+                        error!(
+                            "Failed to register marker shortcut {}: {}",
+                            marker_shortcut, e
+                        );
+                    }
+                }
+                Err(e) => error!("Invalid marker shortcut {}: {}", marker_shortcut, e),
+            }
+            match copy::set_shortcut(&copy_shortcut) {
+                Ok(shortcut) => {
+                    if let Err(e) = app.global_shortcut().register(shortcut) {
+                        error!("Failed to register copy shortcut {}: {}", copy_shortcut, e);
+                    }
+                }
+                Err(e) => error!("Invalid copy shortcut {}: {}", copy_shortcut, e),
+            }
+
             if !use_dev_mode {
                 // Spawn the sidecar initially
                 let sidecar_state = app.state::<SidecarState>();
@@ -0,0 +1,64 @@
+use crate::api;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::Shortcut;
+use tauri_plugin_notification::NotificationExt;
+
+const MARKERS_URL: &str = "http://localhost:3030/markers";
+/// Seconds of what is said after the hotkey kept as the marker's voice memo
+const MEMO_SECS: u32 = 30;
+pub const DEFAULT_MARKER_SHORTCUT: &str = "CommandOrControl+Alt+Shift+M";
+
+static MARKER_SHORTCUT: OnceLock<Shortcut> = OnceLock::new();
+
+#[derive(Deserialize)]
+struct Marker {
+    id: i64,
+    image_path: Option<String>,
+}
+
+/// Remembers which registered shortcut sets markers, the others pause capture
+pub fn set_shortcut(shortcut: &str) -> anyhow::Result<Shortcut> {
+    let shortcut = Shortcut::from_str(shortcut)?;
+    let _ = MARKER_SHORTCUT.set(shortcut);
+    Ok(shortcut)
+}
+
+pub fn is_marker_shortcut(shortcut: &Shortcut) -> bool {
+    MARKER_SHORTCUT.get() == Some(shortcut)
+}
+
+/// What the hotkey does, pins the screen and what is said next
+pub async fn capture(app: &AppHandle) -> anyhow::Result<()> {
+    let marker: Marker = api::client()
+        .post(MARKERS_URL)
+        .json(&json!({"memo_secs": MEMO_SECS}))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    info!("Marker {} set", marker.id);
+    let body = match marker.image_path {
+        Some(_) => format!(
+            "screenshot saved, the next {} seconds are kept as a voice memo",
+            MEMO_SECS
+        ),
+        // capture is paused or the screen isn't recorded
+        None => "marker saved without a screenshot".to_string(),
+    };
+    if let Err(e) = app
+        .notification()
+        .builder()
+        .title("moment captured")
+        .body(body)
+        .show()
+    {
+        error!("Failed to show notification: {}", e);
+    }
+    Ok(())
+}
@@ -0,0 +1,123 @@
+use crate::{api, compliance};
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+const CAPTURE_URL: &str = "http://localhost:3030/capture";
+/// Picks up auto resumes and pauses made through the API
+const STATUS_INTERVAL: Duration = Duration::from_secs(5);
+pub const TIMED_PAUSE: Duration = Duration::from_secs(15 * 60);
+pub const DEFAULT_PAUSE_SHORTCUT: &str = "CommandOrControl+Alt+Shift+P";
+
+#[derive(Deserialize, Clone, PartialEq)]
+pub struct PauseStatus {
+    pub paused: bool,
+    pub resume_at: Option<String>,
+}
+
+async fn fetch_status() -> anyhow::Result<PauseStatus> {
+    Ok(api::client()
+        .get(format!("{}/pause", CAPTURE_URL))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+pub async fn pause(app: &AppHandle, duration: Option<Duration>) -> anyhow::Result<PauseStatus> {
+    let status: PauseStatus = api::client()
+        .post(format!("{}/pause", CAPTURE_URL))
+        .json(&json!({"duration_secs": duration.map(|d| d.as_secs())}))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let body = match duration {
+        Some(duration) => format!(
+            "screen and audio capture paused for {} minutes",
+            duration.as_secs() / 60
+        ),
+        None => "screen and audio capture paused until you resume it".to_string(),
+    };
+    notify(app, "capture paused", &body);
+    update_tray(app, &status);
+    Ok(status)
+}
+
+pub async fn resume(app: &AppHandle) -> anyhow::Result<PauseStatus> {
+    let status: PauseStatus = api::client()
+        .post(format!("{}/resume", CAPTURE_URL))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    notify(app, "capture resumed", "screenpipe is recording again");
+    update_tray(app, &status);
+    Ok(status)
+}
+
+/// What the hotkey does, an indefinite pause or a resume
+pub async fn toggle(app: &AppHandle) -> anyhow::Result<PauseStatus> {
+    if fetch_status().await?.paused {
+        resume(app).await
+    } else {
+        pause(app, None).await
+    }
+}
+
+fn notify(app: &AppHandle, title: &str, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        error!("Failed to show notification: {}", e);
+    }
+}
+
+fn update_tray(app: &AppHandle, status: &PauseStatus) {
+    let Some(tray) = app.tray_by_id("screenpipe_main") else {
+        return;
+    };
+    let organization = compliance::organization();
+    let tooltip = match (status.paused, &status.resume_at, &organization) {
+        (true, Some(resume_at), _) => format!("screenpipe - paused until {}", resume_at),
+        (true, None, _) => "screenpipe - paused".to_string(),
+        (false, _, Some(organization)) => format!("screenpipe - recording for {}", organization),
+        (false, _, None) => "screenpipe - recording".to_string(),
+    };
+    let _ = tray.set_tooltip(Some(tooltip));
+    // shown next to the icon in the macos menubar, always on a machine with a compliance policy
+    let title = match (status.paused, &organization) {
+        (true, _) => Some("paused"),
+        (false, Some(_)) => Some("● recording"),
+        (false, None) => None,
+    };
+    let _ = tray.set_title(title);
+}
+
+/// After something else than a pause changed what the tray shows
+pub async fn refresh_tray(app: &AppHandle) {
+    if let Ok(status) = fetch_status().await {
+        update_tray(app, &status);
+    }
+}
+
+/// Keeps the tray in sync with the server, the sidecar can restart or resume on its own
+pub fn start_status_updates(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_status = None;
+        loop {
+            if let Ok(status) = fetch_status().await {
+                if last_status.as_ref() != Some(&status) {
+                    info!("Capture paused: {}", status.paused);
+                    update_tray(&app, &status);
+                    last_status = Some(status);
+                }
+            }
+            tokio::time::sleep(STATUS_INTERVAL).await;
+        }
+    });
+}
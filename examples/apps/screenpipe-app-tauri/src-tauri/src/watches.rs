@@ -0,0 +1,85 @@
+use crate::api;
+use log::error;
+use serde::Deserialize;
+use std::time::Duration;
+use tauri::AppHandle;
+use tauri_plugin_notification::NotificationExt;
+
+const ALERTS_URL: &str = "http://localhost:3030/watches/alerts";
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// More alerts than this between two polls are summed up in one notification
+const MAX_NOTIFICATIONS: usize = 3;
+
+#[derive(Deserialize)]
+struct Alert {
+    id: i64,
+    watch_name: String,
+    app_name: Option<String>,
+    window_name: Option<String>,
+    excerpt: String,
+}
+
+#[derive(Deserialize)]
+struct Alerts {
+    data: Vec<Alert>,
+}
+
+/// Newest first
+async fn fetch_alerts() -> anyhow::Result<Vec<Alert>> {
+    let alerts: Alerts = api::client()
+        .get(format!("{}?limit=20", ALERTS_URL))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(alerts.data)
+}
+
+fn notify(app: &AppHandle, title: &str, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        error!("Failed to show notification: {}", e);
+    }
+}
+
+/// Shows a notification for each new match of a watch, the alerts from before the app
+/// started aren't shown
+pub fn start_alert_notifications(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let mut last_seen: Option<i64> = None;
+        loop {
+            if let Ok(alerts) = fetch_alerts().await {
+                let newest = alerts.first().map(|alert| alert.id);
+                if let Some(last_seen) = last_seen {
+                    let new: Vec<_> = alerts.iter().filter(|alert| alert.id > last_seen).collect();
+                    if new.len() > MAX_NOTIFICATIONS {
+                        notify(
+                            &app,
+                            "watchlist",
+                            &format!("{} new matches of your watches", new.len()),
+                        );
+                    } else {
+                        for alert in new.iter().rev() {
+                            // transcripts have the device as window
+                            let place = match (&alert.app_name, &alert.window_name) {
+                                (Some(app_name), Some(window)) => {
+                                    format!("{} - {}", app_name, window)
+                                }
+                                (app_name, window) => {
+                                    app_name.clone().or(window.clone()).unwrap_or_default()
+                                }
+                            };
+                            notify(
+                                &app,
+                                &alert.watch_name,
+                                &format!("{}\n{}", place, alert.excerpt),
+                            );
+                        }
+                    }
+                }
+                last_seen = last_seen.max(newest).or(Some(0));
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    });
+}
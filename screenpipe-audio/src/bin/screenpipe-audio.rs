@@ -13,6 +13,7 @@ use std::path::PathBuf;
 use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Parser, Debug)]
 #[clap(author, version, about, long_about = None)]
@@ -79,7 +80,11 @@ async fn main() -> Result<()> {
     let chunk_duration = Duration::from_secs(5);
     let output_path = PathBuf::from("output.mp4");
     let (whisper_sender, mut whisper_receiver) =
-        create_whisper_channel(Arc::new(AudioTranscriptionEngine::WhisperTiny)).await?;
+        create_whisper_channel(
+            Arc::new(AudioTranscriptionEngine::WhisperTiny),
+            CancellationToken::new(),
+        )
+        .await?;
     // Spawn threads for each device
     let recording_threads: Vec<_> = devices
         .into_iter()
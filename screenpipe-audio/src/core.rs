@@ -2,20 +2,24 @@ use anyhow::{anyhow, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::StreamError;
 use log::{debug, error, info, warn};
-use screenpipe_core::find_ffmpeg_path;
+use screenpipe_core::{
+    channel_stats, AudioCodec, Container, FfmpegCommand, FfmpegInput, FfmpegOutput,
+    OverflowPolicy,
+};
 use serde::Serialize;
 use std::path::PathBuf;
-use std::process::Stdio;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
 use std::{fmt, thread};
-use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
-use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::mpsc::{self, Sender};
 
 use crate::AudioInput;
 
+/// Buffers of samples from the device waiting for ffmpeg
+const SAMPLES_CAPACITY: usize = 1000;
+
 async fn get_macos_version() -> Option<f32> {
     let output = Command::new("sw_vers")
         .arg("-productVersion")
@@ -31,6 +35,8 @@ pub enum AudioTranscriptionEngine {
     Deepgram,
     WhisperTiny,
     WhisperDistilLargeV3,
+    /// A worker on another machine, see `--remote-worker`
+    Remote,
 }
 
 impl fmt::Display for AudioTranscriptionEngine {
@@ -39,6 +45,7 @@ impl fmt::Display for AudioTranscriptionEngine {
             AudioTranscriptionEngine::Deepgram => write!(f, "Deepgram"),
             AudioTranscriptionEngine::WhisperTiny => write!(f, "WhisperTiny"),
             AudioTranscriptionEngine::WhisperDistilLargeV3 => write!(f, "WhisperLarge"),
+            AudioTranscriptionEngine::Remote => write!(f, "Remote"),
         }
     }
 }
@@ -189,35 +196,19 @@ async fn run_ffmpeg(
     duration: Duration,
 ) -> Result<()> {
     debug!("Starting FFmpeg process");
-    let mut command = Command::new(find_ffmpeg_path().unwrap());
-    command
-        .args(&[
-            "-f",
-            "f32le",
-            "-ar",
-            &sample_rate.to_string(),
-            "-ac",
-            &channels.to_string(),
-            "-i",
-            "pipe:0",
-            "-c:a",
-            "aac",
-            "-b:a",
-            "128k",
-            "-f",
-            "mp4",
-            output_path.to_str().unwrap(),
-        ])
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-
-    debug!("FFmpeg command: {:?}", command);
-
-    let mut ffmpeg: tokio::process::Child =
-        command.spawn().expect("Failed to spawn FFmpeg process");
-    debug!("FFmpeg process spawned");
-    let mut stdin = ffmpeg.stdin.take().expect("Failed to open stdin");
+    let mut ffmpeg = FfmpegCommand::new()
+        .input(
+            FfmpegInput::pipe()
+                .format(Container::F32le)
+                .sample_rate(sample_rate)
+                .channels(channels),
+        )
+        .output(
+            FfmpegOutput::file(output_path.to_str().unwrap())
+                .audio_codec(AudioCodec::Aac { bitrate_kbps: 128 })
+                .format(Container::Mp4),
+        )
+        .spawn("audio")?;
     let start_time = std::time::Instant::now();
 
     while is_running.load(Ordering::Relaxed) {
@@ -227,7 +218,7 @@ async fn run_ffmpeg(
                     debug!("Duration exceeded, breaking loop");
                     break;
                 }
-                if let Err(e) = stdin.write_all(&data).await {
+                if let Err(e) = ffmpeg.write(&data).await {
                     error!("Failed to write audio data to FFmpeg: {}", e);
                     break;
                 }
@@ -241,23 +232,9 @@ async fn run_ffmpeg(
         }
     }
 
-    debug!("Dropping stdin");
-    drop(stdin);
     debug!("Waiting for FFmpeg process to exit");
-    let output = ffmpeg.wait_with_output().await?;
-    let status = output.status;
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    let stderr = String::from_utf8_lossy(&output.stderr);
-
+    let status = ffmpeg.finish().await?;
     debug!("FFmpeg process exited with status: {}", status);
-    debug!("FFmpeg stdout: {}", stdout);
-    debug!("FFmpeg stderr: {}", stderr);
-
-    if !status.success() {
-        error!("FFmpeg process failed with status: {}", status);
-        error!("FFmpeg stderr: {}", stderr);
-        return Err(anyhow!("FFmpeg process failed"));
-    }
 
     Ok(())
 }
@@ -266,7 +243,7 @@ pub async fn record_and_transcribe(
     audio_device: Arc<AudioDevice>,
     duration: Duration,
     output_path: PathBuf,
-    whisper_sender: UnboundedSender<AudioInput>,
+    whisper_sender: Sender<AudioInput>,
     is_running: Arc<AtomicBool>,
 ) -> Result<PathBuf> {
     let (cpal_audio_device, config) = get_device_and_config(&audio_device).await?;
@@ -278,7 +255,13 @@ pub async fn record_and_transcribe(
     );
 
     // TODO: consider a lock-free ring buffer like crossbeam_queue::ArrayQueue (ask AI why)
-    let (tx, rx) = mpsc::channel(1000); // For audio data
+    let (tx, rx) = mpsc::channel(SAMPLES_CAPACITY); // For audio data
+    // the device callback must not block, samples are lost if ffmpeg falls behind
+    let samples_stats = channel_stats(
+        "audio.samples",
+        OverflowPolicy::DropNewest,
+        SAMPLES_CAPACITY,
+    );
     let is_running_clone = Arc::clone(&is_running);
     let is_running_clone_2 = is_running.clone();
     let is_running_clone_3 = is_running.clone();
@@ -302,7 +285,7 @@ pub async fn record_and_transcribe(
                 &config.into(),
                 move |data: &[i8], _: &_| {
                     if is_running_clone_3.load(Ordering::Relaxed) {
-                        let _ = tx.blocking_send(bytemuck::cast_slice(data).to_vec());
+                        samples_stats.try_send(&tx, bytemuck::cast_slice(data).to_vec());
                     }
                 },
                 error_callback,
@@ -312,7 +295,7 @@ pub async fn record_and_transcribe(
                 &config.into(),
                 move |data: &[i16], _: &_| {
                     if is_running_clone_3.load(Ordering::Relaxed) {
-                        let _ = tx.blocking_send(bytemuck::cast_slice(data).to_vec());
+                        samples_stats.try_send(&tx, bytemuck::cast_slice(data).to_vec());
                     }
                 },
                 error_callback,
@@ -322,7 +305,7 @@ pub async fn record_and_transcribe(
                 &config.into(),
                 move |data: &[i32], _: &_| {
                     if is_running_clone_3.load(Ordering::Relaxed) {
-                        let _ = tx.blocking_send(bytemuck::cast_slice(data).to_vec());
+                        samples_stats.try_send(&tx, bytemuck::cast_slice(data).to_vec());
                     }
                 },
                 error_callback,
@@ -332,7 +315,7 @@ pub async fn record_and_transcribe(
                 &config.into(),
                 move |data: &[f32], _: &_| {
                     if is_running_clone_3.load(Ordering::Relaxed) {
-                        let _ = tx.blocking_send(bytemuck::cast_slice(data).to_vec());
+                        samples_stats.try_send(&tx, bytemuck::cast_slice(data).to_vec());
                     }
                 },
                 error_callback,
@@ -384,10 +367,16 @@ pub async fn record_and_transcribe(
     is_running.store(false, Ordering::Relaxed); // TODO: could also just kill the trhead..
 
     debug!("Sending audio to audio model");
-    if let Err(e) = whisper_sender.send(AudioInput {
+    let input = AudioInput {
         path: output_path_clone_2.to_str().unwrap().to_string(),
         device: audio_device.to_string(),
-    }) {
+    };
+    let chunks_stats = channel_stats(
+        "audio.chunks",
+        OverflowPolicy::Wait,
+        whisper_sender.max_capacity(),
+    );
+    if let Err(e) = chunks_stats.send(&whisper_sender, input).await {
         error!("Failed to send audio to audio model: {}", e);
     }
     debug!("Sent audio to audio model");
@@ -7,4 +7,7 @@ pub use core::{
     record_and_transcribe, AudioDevice, AudioTranscriptionEngine, DeviceControl,
 };
 pub use pcm_decode::pcm_decode;
-pub use stt::{create_whisper_channel, stt, AudioInput, TranscriptionResult, WhisperModel};
+pub use stt::{
+    create_whisper_channel, set_stt_engine_override, stt, AudioInput, TranscriptionResult,
+    WhisperModel,
+};
@@ -1,16 +1,16 @@
 use std::{
-    sync::Arc,
+    sync::{Arc, RwLock},
     time::{SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{Error as E, Result};
 use candle::{Device, IndexOp, Tensor};
 use candle_nn::{ops::softmax, VarBuilder};
-use hf_hub::{api::sync::Api, Repo, RepoType};
 use log::{debug, error, info};
 use rand::{distributions::Distribution, SeedableRng};
 use tokenizers::Tokenizer;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_util::sync::CancellationToken;
 
 use candle_transformers::models::whisper::{self as m, audio, Config};
 use rubato::{
@@ -19,6 +19,11 @@ use rubato::{
 
 use crate::{multilingual, pcm_decode::pcm_decode, AudioTranscriptionEngine};
 
+use screenpipe_core::{
+    channel_stats, model_device, AcceleratorLease, LocalModel, OverflowPolicy,
+    WHISPER_DISTIL_LARGE_V3, WHISPER_TINY,
+};
+use screenpipe_integrations::remote_worker::transcribe_remote;
 use webrtc_vad::{Vad, VadMode};
 
 use hound::{WavSpec, WavWriter};
@@ -29,39 +34,29 @@ pub struct WhisperModel {
     pub model: Model,
     pub tokenizer: Tokenizer,
     pub device: Device,
+    _lease: Arc<AcceleratorLease>,
 }
 
 impl WhisperModel {
     pub fn new(engine: Arc<AudioTranscriptionEngine>) -> Result<Self> {
         debug!("Initializing WhisperModel");
-        let device = Device::new_metal(0).unwrap_or(Device::new_cuda(0).unwrap_or(Device::Cpu));
-        info!("device = {:?}", device);
+        // megabytes the weights take in f32
+        let memory_mb = match engine.as_ref() {
+            AudioTranscriptionEngine::WhisperDistilLargeV3 => 3000,
+            _ => 150,
+        };
+        let (device, lease) = model_device(LocalModel::Transcription, memory_mb);
+        info!("device = {}", lease.accelerator);
 
         debug!("Fetching model files");
         let (config_filename, tokenizer_filename, weights_filename) = {
-            let api = Api::new()?;
-            let repo = match engine.as_ref() {
-                AudioTranscriptionEngine::WhisperTiny => Repo::with_revision(
-                    "openai/whisper-tiny".to_string(),
-                    RepoType::Model,
-                    "main".to_string(),
-                ),
-                AudioTranscriptionEngine::WhisperDistilLargeV3 => Repo::with_revision(
-                    "distil-whisper/distil-large-v3".to_string(),
-                    RepoType::Model,
-                    "main".to_string(),
-                ),
-                _ => Repo::with_revision(
-                    "openai/whisper-tiny".to_string(),
-                    RepoType::Model,
-                    "main".to_string(),
-                ),
-                // ... other engine options ...
+            let model = match engine.as_ref() {
+                AudioTranscriptionEngine::WhisperDistilLargeV3 => WHISPER_DISTIL_LARGE_V3,
+                _ => WHISPER_TINY,
             };
-            let api_repo = api.repo(repo);
-            let config = api_repo.get("config.json")?;
-            let tokenizer = api_repo.get("tokenizer.json")?;
-            let model = api_repo.get("model.safetensors")?;
+            let config = model.file("config.json")?;
+            let tokenizer = model.file("tokenizer.json")?;
+            let model = model.file("model.safetensors")?;
             (config, tokenizer, model)
         };
 
@@ -78,6 +73,7 @@ impl WhisperModel {
             model,
             tokenizer,
             device,
+            _lease: Arc::new(lease),
         })
     }
 }
@@ -704,6 +700,14 @@ fn resample(input: Vec<f32>, from_sample_rate: u32, to_sample_rate: u32) -> Resu
     Ok(waves_out.into_iter().next().unwrap())
 }
 
+/// Replaces the engine transcription started with, set by the power profiles. The whisper
+/// model loaded at start is used for any of the local engines
+static STT_ENGINE_OVERRIDE: RwLock<Option<AudioTranscriptionEngine>> = RwLock::new(None);
+
+pub fn set_stt_engine_override(engine: Option<AudioTranscriptionEngine>) {
+    *STT_ENGINE_OVERRIDE.write().unwrap() = engine;
+}
+
 #[derive(Debug, Clone)]
 pub struct AudioInput {
     pub path: String,
@@ -717,21 +721,34 @@ pub struct TranscriptionResult {
     pub timestamp: u64,
     pub error: Option<String>,
 }
+/// Recorded chunks waiting for transcription, and transcripts waiting for the database.
+/// Neither is dropped, recording waits when they are full
+pub const WHISPER_CHANNEL_CAPACITY: usize = 64;
+
+/// Transcribes the chunks sent until `cancel` is cancelled, the transcription in flight is
+/// dropped then and the receiver closed
 pub async fn create_whisper_channel(
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
-) -> Result<(
-    UnboundedSender<AudioInput>,
-    UnboundedReceiver<TranscriptionResult>,
-)> {
-    let whisper_model = WhisperModel::new(audio_transcription_engine.clone())?;
-    let (input_sender, mut input_receiver): (
-        UnboundedSender<AudioInput>,
-        UnboundedReceiver<AudioInput>,
-    ) = unbounded_channel();
+    cancel: CancellationToken,
+) -> Result<(Sender<AudioInput>, Receiver<TranscriptionResult>)> {
+    // the remote worker has its own model
+    let whisper_model = match *audio_transcription_engine {
+        AudioTranscriptionEngine::Remote => None,
+        _ => Some(Arc::new(WhisperModel::new(
+            audio_transcription_engine.clone(),
+        )?)),
+    };
+    let (input_sender, mut input_receiver): (Sender<AudioInput>, Receiver<AudioInput>) =
+        channel(WHISPER_CHANNEL_CAPACITY);
     let (output_sender, output_receiver): (
-        UnboundedSender<TranscriptionResult>,
-        UnboundedReceiver<TranscriptionResult>,
-    ) = unbounded_channel();
+        Sender<TranscriptionResult>,
+        Receiver<TranscriptionResult>,
+    ) = channel(WHISPER_CHANNEL_CAPACITY);
+    let transcripts_stats = channel_stats(
+        "audio.transcripts",
+        OverflowPolicy::Wait,
+        WHISPER_CHANNEL_CAPACITY,
+    );
 
     tokio::spawn(async move {
         loop {
@@ -742,7 +759,30 @@ pub async fn create_whisper_channel(
                         .expect("Time went backwards")
                         .as_secs();
 
-                    let transcription_result = match stt(&input.path, &whisper_model, audio_transcription_engine.clone()) {
+                    let transcription = match &whisper_model {
+                        Some(whisper_model) => {
+                            let engine = STT_ENGINE_OVERRIDE
+                                .read()
+                                .unwrap()
+                                .clone()
+                                .map_or_else(|| audio_transcription_engine.clone(), Arc::new);
+                            let whisper_model = Arc::clone(whisper_model);
+                            let path = input.path.clone();
+                            // off the runtime so a cancellation isn't held up by the decoding
+                            let transcription = tokio::task::spawn_blocking(move || {
+                                stt(&path, &whisper_model, engine)
+                            });
+                            tokio::select! {
+                                result = transcription => result.map_err(E::from).and_then(|r| r),
+                                _ = cancel.cancelled() => break,
+                            }
+                        }
+                        None => tokio::select! {
+                            result = transcribe_remote(&input.path) => result,
+                            _ = cancel.cancelled() => break,
+                        },
+                    };
+                    let transcription_result = match transcription {
                         Ok(transcription) => TranscriptionResult {
                             input: input.clone(),
                             transcription: Some(transcription),
@@ -760,13 +800,19 @@ pub async fn create_whisper_channel(
                         },
                     };
 
-                    if output_sender.send(transcription_result).is_err() {
+                    if transcripts_stats
+                        .send(&output_sender, transcription_result)
+                        .await
+                        .is_err()
+                    {
                         break;
                     }
                 }
+                _ = cancel.cancelled() => break,
                 else => break,
             }
         }
+        debug!("Transcription stopped");
     });
 
     Ok((input_sender, output_receiver))
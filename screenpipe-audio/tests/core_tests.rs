@@ -12,7 +12,7 @@ mod tests {
     use std::sync::atomic::{AtomicBool, Ordering};
     use std::sync::Arc;
     use std::time::{Duration, Instant};
-    use tokio::sync::mpsc::unbounded_channel;
+    use tokio::sync::mpsc::channel;
 
     fn setup() {
         // Initialize the logger with an info level filter
@@ -74,7 +74,7 @@ mod tests {
         let duration = Duration::from_secs(30); // Record for 3 seconds
         let time = Utc::now().timestamp_millis();
         let output_path = PathBuf::from(format!("test_output_{}.mp4", time));
-        let (sender, mut receiver) = unbounded_channel();
+        let (sender, mut receiver) = channel(64);
         let is_running = Arc::new(AtomicBool::new(true));
 
         // Act
@@ -130,7 +130,7 @@ mod tests {
         let duration = Duration::from_secs(30);
         let time = Utc::now().timestamp_millis();
         let output_path = PathBuf::from(format!("test_output_interrupt_{}.mp4", time));
-        let (sender, mut receiver) = unbounded_channel();
+        let (sender, mut receiver) = channel(64);
         let is_running = Arc::new(AtomicBool::new(true));
         let is_running_clone = Arc::clone(&is_running);
 
@@ -220,6 +220,7 @@ mod tests {
         use std::sync::Arc;
         use std::time::Duration;
         use tokio::time::timeout;
+        use tokio_util::sync::CancellationToken;
 
         // 1. start listening to https://music.youtube.com/watch?v=B6WAlAzuJb4&si=775sYWLG0b7XhQIH&t=50
         // 2. run the test
@@ -230,10 +231,12 @@ mod tests {
         let output_path =
             PathBuf::from(format!("test_output_{}.mp4", Utc::now().timestamp_millis()));
         let output_path_2 = output_path.clone();
-        let (whisper_sender, mut whisper_receiver) =
-            create_whisper_channel(Arc::new(AudioTranscriptionEngine::WhisperTiny))
-                .await
-                .unwrap();
+        let (whisper_sender, mut whisper_receiver) = create_whisper_channel(
+            Arc::new(AudioTranscriptionEngine::WhisperTiny),
+            CancellationToken::new(),
+        )
+        .await
+        .unwrap();
         let is_running = Arc::new(AtomicBool::new(true));
         // Start recording in a separate thread
         let recording_thread = tokio::spawn(async move {
@@ -295,4 +298,24 @@ mod tests {
         let _ = recording_thread.abort();
         std::fs::remove_file(output_path_2).unwrap_or_default();
     }
+
+    #[tokio::test]
+    async fn test_whisper_channel_stops_when_cancelled() {
+        use screenpipe_audio::create_whisper_channel;
+        use tokio::time::timeout;
+        use tokio_util::sync::CancellationToken;
+
+        // the remote engine loads no model
+        let cancel = CancellationToken::new();
+        let (_whisper_sender, mut whisper_receiver) =
+            create_whisper_channel(Arc::new(AudioTranscriptionEngine::Remote), cancel.clone())
+                .await
+                .unwrap();
+
+        cancel.cancel();
+        let closed = timeout(Duration::from_secs(1), whisper_receiver.recv())
+            .await
+            .expect("the transcription task kept running after it was cancelled");
+        assert!(closed.is_none());
+    }
 }
@@ -25,7 +25,7 @@ mod tests {
         // Set up for recording
         let duration = Duration::from_secs(5);
         let output_path = PathBuf::from("test_output.mp4");
-        let (tx, _rx) = mpsc::unbounded_channel();
+        let (tx, _rx) = mpsc::channel(64);
         let is_running = Arc::new(AtomicBool::new(true));
 
         // Record from the virtual device
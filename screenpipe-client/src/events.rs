@@ -0,0 +1,68 @@
+use crate::models::Event;
+use anyhow::Result;
+use futures::stream::{self, Stream, StreamExt};
+use serde_json::Value;
+
+/// Splits the server-sent events of /events as the bytes arrive
+#[derive(Debug, Default)]
+pub struct EventParser {
+    buffer: Vec<u8>,
+}
+
+impl EventParser {
+    /// The events completed by `chunk`, the rest is kept for the next one. Works on bytes as a
+    /// character can be split between chunks
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer
+            .extend(chunk.iter().filter(|byte| **byte != b'\r'));
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|bytes| bytes == b"\n\n") {
+            let block: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            if let Some(event) = parse_block(&String::from_utf8_lossy(&block)) {
+                events.push(event);
+            }
+        }
+        events
+    }
+}
+
+/// None for keep-alive comments
+fn parse_block(block: &str) -> Option<Event> {
+    let mut name = None;
+    let mut data = Vec::new();
+    for line in block.lines() {
+        if let Some(value) = line.strip_prefix("event:") {
+            name = Some(value.trim_start().to_string());
+        } else if let Some(value) = line.strip_prefix("data:") {
+            data.push(value.strip_prefix(' ').unwrap_or(value));
+        }
+    }
+    if name.is_none() && data.is_empty() {
+        return None;
+    }
+    let data = data.join("\n");
+    Some(Event {
+        name: name.unwrap_or_else(|| "message".to_string()),
+        // the server always sends json, anything else is kept as text
+        data: serde_json::from_str(&data).unwrap_or(Value::String(data)),
+    })
+}
+
+pub(crate) fn event_stream(response: reqwest::Response) -> impl Stream<Item = Result<Event>> {
+    let bytes = response.bytes_stream();
+    stream::unfold(
+        (Box::pin(bytes), EventParser::default(), Vec::new()),
+        |(mut bytes, mut parser, mut pending)| async move {
+            loop {
+                if !pending.is_empty() {
+                    let event = pending.remove(0);
+                    return Some((Ok(event), (bytes, parser, pending)));
+                }
+                match bytes.next().await? {
+                    Ok(chunk) => pending = parser.push(&chunk),
+                    Err(e) => return Some((Err(e.into()), (bytes, parser, pending))),
+                }
+            }
+        },
+    )
+}
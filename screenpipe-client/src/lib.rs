@@ -0,0 +1,262 @@
+//! Typed client for the screenpipe HTTP API and its event stream.
+//!
+//! ```no_run
+//! use futures::StreamExt;
+//! use screenpipe_client::{Client, SearchQuery};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let client = Client::local();
+//! let page = client.search(&SearchQuery::text("invoice")).await?;
+//! println!("{} results", page.pagination.total);
+//!
+//! let mut events = Box::pin(client.events().await?);
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event?);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+mod events;
+mod models;
+
+pub use events::EventParser;
+pub use models::{
+    ActionItem, AudioContent, ContentItem, ContentType, Event, FtsContent, Health, Marker,
+    MarkerRequest, MeetingSummary, OcrContent, Paginated, Pagination, PauseStatus, SearchQuery,
+};
+
+use anyhow::Result;
+use futures::stream::{self, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::time::Duration;
+use thiserror::Error;
+
+pub const DEFAULT_URL: &str = "http://localhost:3030";
+/// Header naming the pipe making the request, see `screenpipe_core::PIPE_HEADER`
+const PIPE_HEADER: &str = "x-screenpipe-pipe";
+/// The pipe's token, without it the server doesn't trust the name
+const PIPE_TOKEN_HEADER: &str = "x-screenpipe-pipe-token";
+
+/// A request the server refused or failed, what the `anyhow::Error` of a failed request
+/// downcasts to
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{status}: {message}")]
+pub struct ApiError {
+    pub status: u16,
+    /// Stable name of the cause like `frame_not_found` or `media_locked`, on the endpoints
+    /// that tell them apart
+    pub code: Option<String>,
+    pub message: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    token: Option<String>,
+    pipe: Option<String>,
+    pipe_token: Option<String>,
+}
+
+impl Client {
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Client {
+            http: reqwest::Client::new(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            token: None,
+            pipe: None,
+            pipe_token: None,
+        }
+    }
+
+    /// The server running on this machine
+    pub fn local() -> Self {
+        Client::new(DEFAULT_URL)
+    }
+
+    /// Sent as a bearer token: the app's of app_token in the data directory, or one of the
+    /// server's --api-token for a remote consumer
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Requests are audited and redacted as made by this pipe
+    pub fn with_pipe(mut self, pipe: impl Into<String>) -> Self {
+        self.pipe = Some(pipe.into());
+        self
+    }
+
+    /// The pipe's token of pipe_tokens.json in the data directory, needed for its grants
+    pub fn with_pipe_token(mut self, token: impl Into<String>) -> Self {
+        self.pipe_token = Some(token.into());
+        self
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+        let mut request = self
+            .http
+            .request(method, format!("{}{}", self.base_url, path));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(pipe) = &self.pipe {
+            request = request.header(PIPE_HEADER, pipe);
+        }
+        if let Some(token) = &self.pipe_token {
+            request = request.header(PIPE_TOKEN_HEADER, token);
+        }
+        request
+    }
+
+    /// The server's `{"error": ..., "code": ...}` as an `ApiError` when the request fails
+    async fn send<T: DeserializeOwned>(&self, request: reqwest::RequestBuilder) -> Result<T> {
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_success() {
+            let body: Value = response.json().await.unwrap_or(Value::Null);
+            let message = body
+                .get("error")
+                .and_then(|error| error.as_str())
+                .unwrap_or_else(|| status.canonical_reason().unwrap_or("request failed"));
+            return Err(ApiError {
+                status: status.as_u16(),
+                code: body
+                    .get("code")
+                    .and_then(|code| code.as_str())
+                    .map(str::to_string),
+                message: message.to_string(),
+            }
+            .into());
+        }
+        Ok(response.json().await?)
+    }
+
+    /// Any GET endpoint, for the ones without a typed method
+    pub async fn get<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &(impl Serialize + ?Sized),
+    ) -> Result<T> {
+        self.send(self.request(reqwest::Method::GET, path).query(query))
+            .await
+    }
+
+    /// Any POST endpoint taking a json body
+    pub async fn post<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &(impl Serialize + ?Sized),
+    ) -> Result<T> {
+        self.send(self.request(reqwest::Method::POST, path).json(body))
+            .await
+    }
+
+    pub async fn health(&self) -> Result<Health> {
+        self.get("/health", &()).await
+    }
+
+    /// What this build and machine support, see /capabilities
+    pub async fn capabilities(&self) -> Result<Value> {
+        self.get("/capabilities", &()).await
+    }
+
+    pub async fn search(&self, query: &SearchQuery) -> Result<Paginated<ContentItem>> {
+        self.get("/search", query).await
+    }
+
+    /// Every result of the query, fetching the pages as the stream is read
+    pub fn search_all(&self, query: SearchQuery) -> impl Stream<Item = Result<ContentItem>> + '_ {
+        paginate(query.offset.unwrap_or(0), move |offset| {
+            let query = SearchQuery {
+                offset: Some(offset),
+                ..query.clone()
+            };
+            async move { self.search(&query).await }
+        })
+    }
+
+    /// Matched against the summaries and their calendar events
+    pub async fn meetings(
+        &self,
+        q: Option<&str>,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Paginated<MeetingSummary>> {
+        let mut query = vec![("limit", limit.to_string()), ("offset", offset.to_string())];
+        if let Some(q) = q {
+            query.push(("q", q.to_string()));
+        }
+        self.get("/meetings", &query).await
+    }
+
+    pub async fn meeting(&self, id: i64) -> Result<MeetingSummary> {
+        self.get(&format!("/meetings/{}", id), &()).await
+    }
+
+    pub async fn markers(&self, limit: u32, offset: u32) -> Result<Paginated<Marker>> {
+        self.get("/markers", &[("limit", limit), ("offset", offset)])
+            .await
+    }
+
+    pub async fn create_marker(&self, request: &MarkerRequest) -> Result<Marker> {
+        self.post("/markers", request).await
+    }
+
+    pub async fn pause_status(&self) -> Result<PauseStatus> {
+        self.get("/capture/pause", &()).await
+    }
+
+    /// Until resumed when there is no `duration`
+    pub async fn pause(&self, duration: Option<Duration>) -> Result<PauseStatus> {
+        self.post(
+            "/capture/pause",
+            &json!({"duration_secs": duration.map(|d| d.as_secs())}),
+        )
+        .await
+    }
+
+    pub async fn resume(&self) -> Result<PauseStatus> {
+        self.post("/capture/resume", &json!({})).await
+    }
+
+    /// Subscribes to /events, the stream ends when the server closes the connection
+    pub async fn events(&self) -> Result<impl Stream<Item = Result<Event>>> {
+        let response = self
+            .request(reqwest::Method::GET, "/events")
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(events::event_stream(response))
+    }
+}
+
+/// Items of the pages returned by `fetch` for each offset, until the last page
+pub fn paginate<'a, T, F, Fut>(offset: u32, fetch: F) -> impl Stream<Item = Result<T>> + 'a
+where
+    T: 'a,
+    F: Fn(u32) -> Fut + 'a,
+    Fut: std::future::Future<Output = Result<Paginated<T>>> + 'a,
+{
+    stream::unfold(Some(offset), move |offset| {
+        let page = offset.map(&fetch);
+        async move {
+            let page = match page?.await {
+                Ok(page) => page,
+                Err(e) => return Some((stream::iter(vec![Err(e)]), None)),
+            };
+            // an empty page ends it too, in case items were deleted while paging
+            let next = page
+                .pagination
+                .next_offset()
+                .filter(|_| !page.data.is_empty());
+            Some((
+                stream::iter(page.data.into_iter().map(Ok).collect::<Vec<_>>()),
+                next,
+            ))
+        }
+    })
+    .flatten()
+}
@@ -0,0 +1,193 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A page of a list endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Paginated<T> {
+    pub data: Vec<T>,
+    pub pagination: Pagination,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Pagination {
+    pub limit: u32,
+    pub offset: u32,
+    /// Items matching the query, on every page
+    pub total: i64,
+}
+
+impl Pagination {
+    /// Offset of the next page, None on the last one
+    pub fn next_offset(&self) -> Option<u32> {
+        let next = self.offset + self.limit;
+        (self.limit > 0 && (next as i64) < self.total).then_some(next)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    #[default]
+    All,
+    Ocr,
+    Audio,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SearchQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>,
+    pub content_type: ContentType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub window_name: Option<String>,
+    /// Page size, the server defaults to 20
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub offset: Option<u32>,
+}
+
+impl SearchQuery {
+    pub fn text(q: impl Into<String>) -> Self {
+        SearchQuery {
+            q: Some(q.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "content")]
+pub enum ContentItem {
+    OCR(OcrContent),
+    Audio(AudioContent),
+    FTS(FtsContent),
+}
+
+impl ContentItem {
+    pub fn timestamp(&self) -> DateTime<Utc> {
+        match self {
+            ContentItem::OCR(ocr) => ocr.timestamp,
+            ContentItem::Audio(audio) => audio.timestamp,
+            ContentItem::FTS(fts) => fts.timestamp,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        match self {
+            ContentItem::OCR(ocr) => &ocr.text,
+            ContentItem::Audio(audio) => &audio.transcription,
+            ContentItem::FTS(fts) => &fts.matched_text,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OcrContent {
+    pub frame_id: i64,
+    pub text: String,
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+    pub offset_index: i64,
+    pub app_name: String,
+    pub window_name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AudioContent {
+    pub chunk_id: i64,
+    pub transcription: String,
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+    pub offset_index: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FtsContent {
+    pub text_id: i64,
+    pub matched_text: String,
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: String,
+    pub file_path: String,
+    pub original_frame_text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Health {
+    /// "Healthy", "Unhealthy", "Loading" or "Paused"
+    pub status: String,
+    pub last_frame_timestamp: Option<DateTime<Utc>>,
+    pub last_audio_timestamp: Option<DateTime<Utc>>,
+    pub frame_status: String,
+    pub audio_status: String,
+    pub message: String,
+    pub verbose_instructions: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActionItem {
+    pub description: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingSummary {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub summary: String,
+    pub decisions: Vec<String>,
+    pub action_items: Vec<ActionItem>,
+    pub llm_model: String,
+    pub prompt_version: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    #[serde(default)]
+    pub calendar_title: Option<String>,
+    #[serde(default)]
+    pub calendar_attendees: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MarkerRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+    /// Seconds of speech after the marker kept as a voice memo, 120 at most
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub memo_secs: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Marker {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub note: Option<String>,
+    pub image_path: Option<String>,
+    pub frame_id: Option<i64>,
+    pub memo_secs: Option<u32>,
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PauseStatus {
+    pub paused: bool,
+    pub resume_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub awaiting_consent: bool,
+}
+
+/// What the server emits on /events, like "marker.created" or "capture.paused"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub name: String,
+    pub data: Value,
+}
@@ -0,0 +1,151 @@
+use axum::extract::Query;
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::IntoResponse;
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use futures::StreamExt;
+use screenpipe_client::{ApiError, Client, ContentItem, EventParser, MarkerRequest, SearchQuery};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+fn ocr_item(frame_id: i64) -> Value {
+    json!({
+        "type": "OCR",
+        "content": {
+            "frame_id": frame_id,
+            "text": format!("invoice {}", frame_id),
+            "timestamp": "2024-09-01T10:00:00Z",
+            "file_path": "monitor_1.mp4",
+            "offset_index": frame_id,
+            "app_name": "Mail",
+            "window_name": "Inbox",
+        }
+    })
+}
+
+/// Five results, at most two a page
+async fn search(Query(query): Query<HashMap<String, String>>) -> Json<Value> {
+    let offset: i64 = query["offset"].parse().unwrap();
+    let data: Vec<Value> = (offset..(offset + 2).min(5)).map(ocr_item).collect();
+    Json(json!({"data": data, "pagination": {"limit": 2, "offset": offset, "total": 5}}))
+}
+
+async fn create_marker(headers: HeaderMap, Json(body): Json<Value>) -> impl IntoResponse {
+    if headers.get("x-screenpipe-pipe").is_none() {
+        return (StatusCode::FORBIDDEN, Json(json!({"error": "pipes only"})));
+    }
+    (
+        StatusCode::CREATED,
+        Json(json!({
+            "id": 1,
+            "timestamp": "2024-09-01T10:00:00Z",
+            "note": body["note"],
+            "image_path": null,
+            "frame_id": null,
+            "memo_secs": null,
+            "memo": null,
+        })),
+    )
+}
+
+async fn export_frames_pdf() -> impl IntoResponse {
+    (
+        StatusCode::NOT_FOUND,
+        Json(json!({"error": "frame 3 not found", "code": "frame_not_found"})),
+    )
+}
+
+async fn events() -> &'static str {
+    ": keep-alive\n\nevent: marker.created\ndata: {\"id\":1}\n\nevent: capture.paused\ndata: {}\n\n"
+}
+
+async fn start_server() -> String {
+    let app = Router::new()
+        .route("/search", get(search))
+        .route("/markers", post(create_marker))
+        .route("/frames/pdf", post(export_frames_pdf))
+        .route("/events", get(events));
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+#[tokio::test]
+async fn test_search_all_reads_every_page() {
+    let client = Client::new(start_server().await);
+
+    let items: Vec<ContentItem> = client
+        .search_all(SearchQuery::text("invoice"))
+        .map(|item| item.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(items.len(), 5);
+    assert_eq!(items[4].text(), "invoice 4");
+}
+
+#[tokio::test]
+async fn test_errors_carry_the_server_message() {
+    let url = start_server().await;
+    let request = MarkerRequest {
+        note: Some("pricing".to_string()),
+        memo_secs: None,
+    };
+
+    let error = Client::new(&url).create_marker(&request).await.unwrap_err();
+    assert_eq!(error.to_string(), "403: pipes only");
+    assert_eq!(error.downcast_ref::<ApiError>().unwrap().code, None);
+
+    let marker = Client::new(&url)
+        .with_pipe("daily-digest")
+        .create_marker(&request)
+        .await
+        .unwrap();
+    assert_eq!(marker.note.as_deref(), Some("pricing"));
+}
+
+#[tokio::test]
+async fn test_errors_carry_the_code_of_their_cause() {
+    let client = Client::new(start_server().await);
+
+    let error = client
+        .post::<Value>("/frames/pdf", &json!({"frame_ids": [3]}))
+        .await
+        .unwrap_err();
+    let error = error.downcast::<ApiError>().unwrap();
+    assert_eq!(error.status, 404);
+    assert_eq!(error.code.as_deref(), Some("frame_not_found"));
+    assert_eq!(error.message, "frame 3 not found");
+}
+
+#[tokio::test]
+async fn test_events_skip_keep_alives() {
+    let client = Client::new(start_server().await);
+
+    let events: Vec<_> = client
+        .events()
+        .await
+        .unwrap()
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(events.len(), 2);
+    assert_eq!(events[0].name, "marker.created");
+    assert_eq!(events[0].data, json!({"id": 1}));
+    assert_eq!(events[1].name, "capture.paused");
+}
+
+#[test]
+fn test_events_split_between_chunks() {
+    let mut parser = EventParser::default();
+    let data = "event: file.activity\r\ndata: {\"path\":\"caf\u{e9}.md\"}\r\n\r\n".as_bytes();
+    // in the middle of the é
+    let split = data.iter().position(|byte| *byte == 0xc3).unwrap() + 1;
+
+    assert!(parser.push(&data[..split]).is_empty());
+    let events = parser.push(&data[split..]);
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].data, json!({"path": "café.md"}));
+}
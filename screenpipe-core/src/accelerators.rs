@@ -0,0 +1,358 @@
+use candle::Device;
+use log::{info, warn};
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// CUDA devices looked for, the first one that can't be opened ends the search
+const MAX_CUDA_DEVICES: usize = 8;
+
+/// A device the local models run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(into = "String")]
+pub enum Accelerator {
+    Cuda(usize),
+    Metal(usize),
+    Cpu,
+}
+
+impl fmt::Display for Accelerator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Accelerator::Cuda(ordinal) => write!(f, "cuda:{}", ordinal),
+            Accelerator::Metal(ordinal) => write!(f, "metal:{}", ordinal),
+            Accelerator::Cpu => write!(f, "cpu"),
+        }
+    }
+}
+
+impl From<Accelerator> for String {
+    fn from(accelerator: Accelerator) -> Self {
+        accelerator.to_string()
+    }
+}
+
+/// `cpu`, `cuda`, `cuda:1`, `metal` or `metal:0`
+impl FromStr for Accelerator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, ordinal) = match s.trim().to_lowercase().split_once(':') {
+            Some((kind, ordinal)) => (
+                kind.to_string(),
+                ordinal
+                    .parse::<usize>()
+                    .map_err(|_| format!("{} isn't a device number", ordinal))?,
+            ),
+            None => (s.trim().to_lowercase(), 0),
+        };
+        match kind.as_str() {
+            "cuda" => Ok(Accelerator::Cuda(ordinal)),
+            "metal" => Ok(Accelerator::Metal(ordinal)),
+            "cpu" if ordinal == 0 => Ok(Accelerator::Cpu),
+            _ => Err(format!("{} isn't cpu, cuda:N or metal:N", s)),
+        }
+    }
+}
+
+impl Accelerator {
+    pub fn open(&self) -> candle::Result<Device> {
+        match self {
+            Accelerator::Cuda(ordinal) => Device::new_cuda(*ordinal),
+            Accelerator::Metal(ordinal) => Device::new_metal(*ordinal),
+            Accelerator::Cpu => Ok(Device::Cpu),
+        }
+    }
+}
+
+/// The local models the scheduler places
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LocalModel {
+    /// TrOCR, reading handwriting
+    Ocr,
+    /// The jina embeddings chunking text by similarity
+    Embeddings,
+    /// Whisper
+    Transcription,
+}
+
+impl LocalModel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocalModel::Ocr => "ocr",
+            LocalModel::Embeddings => "embeddings",
+            LocalModel::Transcription => "transcription",
+        }
+    }
+}
+
+impl FromStr for LocalModel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "ocr" => Ok(LocalModel::Ocr),
+            "embeddings" => Ok(LocalModel::Embeddings),
+            "transcription" => Ok(LocalModel::Transcription),
+            _ => Err(format!("{} isn't ocr, embeddings or transcription", s)),
+        }
+    }
+}
+
+/// `--model-device ocr=cuda:1`, the device a model always runs on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelDeviceOverride {
+    pub model: LocalModel,
+    pub accelerator: Accelerator,
+}
+
+impl FromStr for ModelDeviceOverride {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (model, accelerator) = s
+            .split_once('=')
+            .ok_or_else(|| format!("{} isn't model=device", s))?;
+        Ok(Self {
+            model: model.parse()?,
+            accelerator: accelerator.parse()?,
+        })
+    }
+}
+
+/// `--accelerator-memory cuda:0=4096`, the megabytes the models may take on a device
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AcceleratorMemory {
+    pub accelerator: Accelerator,
+    pub memory_mb: u64,
+}
+
+impl FromStr for AcceleratorMemory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (accelerator, memory_mb) = s
+            .split_once('=')
+            .ok_or_else(|| format!("{} isn't device=megabytes", s))?;
+        Ok(Self {
+            accelerator: accelerator.parse()?,
+            memory_mb: memory_mb
+                .trim()
+                .parse()
+                .map_err(|_| format!("{} isn't a number of megabytes", memory_mb))?,
+        })
+    }
+}
+
+/// What's running on an accelerator
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AcceleratorLoad {
+    pub accelerator: Accelerator,
+    /// None when it's not limited
+    pub memory_mb: Option<u64>,
+    pub used_mb: u64,
+    pub models: Vec<LocalModel>,
+}
+
+impl AcceleratorLoad {
+    fn fits(&self, memory_mb: u64) -> bool {
+        self.memory_mb
+            .is_none_or(|limit| self.used_mb + memory_mb <= limit)
+    }
+
+    fn free_mb(&self) -> u64 {
+        self.memory_mb
+            .map_or(u64::MAX, |limit| limit.saturating_sub(self.used_mb))
+    }
+}
+
+/// Places each model on the accelerator running the fewest models that has memory left for it,
+/// the one with the most memory left first, or on the cpu when none has. Overrides win
+pub struct AcceleratorScheduler {
+    loads: Vec<AcceleratorLoad>,
+    overrides: HashMap<LocalModel, Accelerator>,
+}
+
+impl AcceleratorScheduler {
+    /// `accelerators` are the gpus in the order they're preferred, the cpu is always there
+    pub fn new(
+        accelerators: &[Accelerator],
+        memory: &[AcceleratorMemory],
+        overrides: &[ModelDeviceOverride],
+    ) -> Self {
+        let mut loads: Vec<AcceleratorLoad> = accelerators
+            .iter()
+            .filter(|accelerator| **accelerator != Accelerator::Cpu)
+            .map(|accelerator| AcceleratorLoad {
+                accelerator: *accelerator,
+                memory_mb: memory
+                    .iter()
+                    .find(|memory| memory.accelerator == *accelerator)
+                    .map(|memory| memory.memory_mb),
+                used_mb: 0,
+                models: Vec::new(),
+            })
+            .collect();
+        loads.push(AcceleratorLoad {
+            accelerator: Accelerator::Cpu,
+            memory_mb: None,
+            used_mb: 0,
+            models: Vec::new(),
+        });
+        Self {
+            loads,
+            overrides: overrides.iter().map(|o| (o.model, o.accelerator)).collect(),
+        }
+    }
+
+    pub fn place(&mut self, model: LocalModel, memory_mb: u64) -> Accelerator {
+        let accelerator = match self.overrides.get(&model) {
+            Some(accelerator) => *accelerator,
+            None => self
+                .loads
+                .iter()
+                .filter(|load| load.accelerator != Accelerator::Cpu && load.fits(memory_mb))
+                .min_by_key(|load| (load.models.len(), Reverse(load.free_mb())))
+                .map_or(Accelerator::Cpu, |load| load.accelerator),
+        };
+        self.assign(model, accelerator, memory_mb);
+        accelerator
+    }
+
+    fn assign(&mut self, model: LocalModel, accelerator: Accelerator, memory_mb: u64) {
+        let index = match self
+            .loads
+            .iter()
+            .position(|load| load.accelerator == accelerator)
+        {
+            Some(index) => index,
+            None => {
+                // an override to a device that wasn't found, opening it tells whether it's there
+                self.loads.push(AcceleratorLoad {
+                    accelerator,
+                    memory_mb: None,
+                    used_mb: 0,
+                    models: Vec::new(),
+                });
+                self.loads.len() - 1
+            }
+        };
+        let load = &mut self.loads[index];
+        load.used_mb += memory_mb;
+        load.models.push(model);
+    }
+
+    pub fn release(&mut self, model: LocalModel, accelerator: Accelerator, memory_mb: u64) {
+        if let Some(load) = self
+            .loads
+            .iter_mut()
+            .find(|load| load.accelerator == accelerator)
+        {
+            load.used_mb = load.used_mb.saturating_sub(memory_mb);
+            if let Some(index) = load.models.iter().position(|m| *m == model) {
+                load.models.remove(index);
+            }
+        }
+    }
+
+    pub fn loads(&self) -> &[AcceleratorLoad] {
+        &self.loads
+    }
+}
+
+/// The gpus of this machine candle can open, metal first then each cuda device
+pub fn discover_accelerators() -> Vec<Accelerator> {
+    let mut accelerators = Vec::new();
+    if Device::new_metal(0).is_ok() {
+        accelerators.push(Accelerator::Metal(0));
+    }
+    for ordinal in 0..MAX_CUDA_DEVICES {
+        if Device::new_cuda(ordinal).is_err() {
+            break;
+        }
+        accelerators.push(Accelerator::Cuda(ordinal));
+    }
+    accelerators
+}
+
+/// Shared by the models of the process, set up on the first model placed when it isn't
+/// configured at startup
+static SCHEDULER: Mutex<Option<AcceleratorScheduler>> = Mutex::new(None);
+
+/// Looks for the gpus and sets the memory limits and the overrides the models are placed with
+pub fn configure_accelerators(memory: &[AcceleratorMemory], overrides: &[ModelDeviceOverride]) {
+    let accelerators = discover_accelerators();
+    info!(
+        "local models can run on {}",
+        accelerators
+            .iter()
+            .chain([&Accelerator::Cpu])
+            .map(Accelerator::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    *SCHEDULER.lock().unwrap() = Some(AcceleratorScheduler::new(&accelerators, memory, overrides));
+}
+
+/// What runs where in this process, empty until a model is placed or the scheduler configured
+pub fn accelerator_loads() -> Vec<AcceleratorLoad> {
+    SCHEDULER
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map(|scheduler| scheduler.loads().to_vec())
+        .unwrap_or_default()
+}
+
+/// A model's place on an accelerator, given back when dropped
+#[derive(Debug)]
+pub struct AcceleratorLease {
+    pub model: LocalModel,
+    pub accelerator: Accelerator,
+    memory_mb: u64,
+}
+
+impl Drop for AcceleratorLease {
+    fn drop(&mut self) {
+        if let Some(scheduler) = SCHEDULER.lock().unwrap().as_mut() {
+            scheduler.release(self.model, self.accelerator, self.memory_mb);
+        }
+    }
+}
+
+/// The device to load a model of about `memory_mb` megabytes on, the cpu when the one it's
+/// placed on can't be opened. Keep the lease as long as the model is loaded
+pub fn model_device(model: LocalModel, memory_mb: u64) -> (Device, AcceleratorLease) {
+    let mut scheduler = SCHEDULER.lock().unwrap();
+    let scheduler = scheduler
+        .get_or_insert_with(|| AcceleratorScheduler::new(&discover_accelerators(), &[], &[]));
+    let mut accelerator = scheduler.place(model, memory_mb);
+    let device = match accelerator.open() {
+        Ok(device) => device,
+        Err(e) => {
+            warn!(
+                "{} can't be opened for the {} model, running it on the cpu: {}",
+                accelerator,
+                model.as_str(),
+                e
+            );
+            scheduler.release(model, accelerator, memory_mb);
+            accelerator = Accelerator::Cpu;
+            scheduler.assign(model, accelerator, memory_mb);
+            Device::Cpu
+        }
+    };
+    info!("the {} model runs on {}", model.as_str(), accelerator);
+    (
+        device,
+        AcceleratorLease {
+            model,
+            accelerator,
+            memory_mb,
+        },
+    )
+}
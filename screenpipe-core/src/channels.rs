@@ -0,0 +1,131 @@
+use serde::Serialize;
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use tokio::sync::mpsc::error::{SendError, TrySendError};
+use tokio::sync::mpsc::Sender;
+
+/// What a bounded channel or queue does when its consumer falls behind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// The oldest items are dropped for the new ones, for frames where only the latest matters
+    KeepLatest,
+    /// New items are dropped until there is room again
+    DropNewest,
+    /// Nothing is dropped, the producer waits for the consumer, for transcripts
+    Wait,
+}
+
+/// Counters of a bounded channel, shared by its producers
+#[derive(Debug)]
+pub struct ChannelStats {
+    name: &'static str,
+    policy: OverflowPolicy,
+    capacity: usize,
+    dropped: AtomicU64,
+    waited: AtomicU64,
+}
+
+/// What /channels reports for each channel
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChannelReport {
+    pub name: String,
+    pub policy: OverflowPolicy,
+    pub capacity: usize,
+    /// Items lost since the start
+    pub dropped: u64,
+    /// Times a producer had to wait for room
+    pub waited: u64,
+}
+
+impl ChannelStats {
+    pub fn record_dropped(&self, count: u64) {
+        self.dropped.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub fn record_waited(&self) {
+        self.waited.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    pub fn report(&self) -> ChannelReport {
+        ChannelReport {
+            name: self.name.to_string(),
+            policy: self.policy,
+            capacity: self.capacity,
+            dropped: self.dropped(),
+            waited: self.waited.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Keep-latest push, the front of the queue is dropped once it holds `capacity` items
+    pub fn push_latest<T>(&self, queue: &mut VecDeque<T>, item: T) {
+        while queue.len() >= self.capacity.max(1) {
+            queue.pop_front();
+            self.record_dropped(1);
+        }
+        queue.push_back(item);
+    }
+
+    /// Drops the item when the channel is full, false once the receiver is gone
+    pub fn try_send<T>(&self, sender: &Sender<T>, item: T) -> bool {
+        match sender.try_send(item) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                self.record_dropped(1);
+                true
+            }
+            Err(TrySendError::Closed(_)) => false,
+        }
+    }
+
+    /// Waits for room when the channel is full, counting how often the consumer was behind
+    pub async fn send<T>(&self, sender: &Sender<T>, item: T) -> Result<(), SendError<T>> {
+        match sender.try_send(item) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(item)) => {
+                self.record_waited();
+                sender.send(item).await
+            }
+            Err(TrySendError::Closed(item)) => Err(SendError(item)),
+        }
+    }
+}
+
+fn registry() -> &'static Mutex<BTreeMap<&'static str, Arc<ChannelStats>>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<&'static str, Arc<ChannelStats>>>> = OnceLock::new();
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// The counters of the channel called `name`, channels created under the same name, like the
+/// frames of each monitor, share them
+pub fn channel_stats(
+    name: &'static str,
+    policy: OverflowPolicy,
+    capacity: usize,
+) -> Arc<ChannelStats> {
+    let mut registry = registry().lock().unwrap();
+    Arc::clone(registry.entry(name).or_insert_with(|| {
+        Arc::new(ChannelStats {
+            name,
+            policy,
+            capacity,
+            dropped: AtomicU64::new(0),
+            waited: AtomicU64::new(0),
+        })
+    }))
+}
+
+/// Every channel created so far, by name
+pub fn channel_reports() -> Vec<ChannelReport> {
+    registry()
+        .lock()
+        .unwrap()
+        .values()
+        .map(|stats| stats.report())
+        .collect()
+}
@@ -0,0 +1,39 @@
+use crate::channels::{channel_stats, ChannelStats, OverflowPolicy};
+use serde::Serialize;
+use std::sync::{Arc, OnceLock};
+use tokio::sync::broadcast;
+
+const EVENTS_CAPACITY: usize = 1024;
+
+/// Something that happened in screenpipe that other parts (api, pipes...) may want to react to.
+/// `name` is namespaced with dots, e.g. `extraction.record`
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    pub name: String,
+    pub data: serde_json::Value,
+}
+
+fn sender() -> &'static broadcast::Sender<Event> {
+    static SENDER: OnceLock<broadcast::Sender<Event>> = OnceLock::new();
+    SENDER.get_or_init(|| {
+        events_stats();
+        broadcast::channel(EVENTS_CAPACITY).0
+    })
+}
+
+/// A subscriber that falls behind misses the oldest events, counted here when it notices
+pub fn events_stats() -> Arc<ChannelStats> {
+    channel_stats("events", OverflowPolicy::KeepLatest, EVENTS_CAPACITY)
+}
+
+/// Broadcasts an event to all subscribers, events are dropped if nobody listens
+pub fn emit_event(name: &str, data: serde_json::Value) {
+    let _ = sender().send(Event {
+        name: name.to_string(),
+        data,
+    });
+}
+
+pub fn subscribe_events() -> broadcast::Receiver<Event> {
+    sender().subscribe()
+}
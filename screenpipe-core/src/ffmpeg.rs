@@ -1,10 +1,13 @@
-use log::{debug, error};
-use std::path::PathBuf;
+use crate::models::sha256_file;
+use anyhow::Result;
+use flate2::read::GzDecoder;
+use log::{debug, error, info, warn};
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
 use which::which;
 
-#[cfg(windows)]
-use std::os::windows::process::CommandExt;
-
 #[cfg(not(windows))]
 const EXECUTABLE_NAME: &str = "ffmpeg";
 
@@ -12,9 +15,160 @@ const EXECUTABLE_NAME: &str = "ffmpeg";
 const EXECUTABLE_NAME: &str = "ffmpeg.exe";
 
 #[cfg(windows)]
-const CREATE_NO_WINDOW: u32 = 0x08000000;
+pub(crate) const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+/// Release of https://github.com/eugeneware/ffmpeg-static we download when asked to
+pub const PINNED_FFMPEG_VERSION: &str = "b6.0";
+
+struct FfmpegBuild {
+    os: &'static str,
+    arch: &'static str,
+    asset: &'static str,
+    /// sha256 of the gzipped asset, a build without checksum is never installed
+    sha256: &'static str,
+}
+
+/// Static builds per platform. When bumping the version, update the checksums with
+/// `sha256sum ffmpeg-*.gz` on the release assets.
+const PINNED_FFMPEG_BUILDS: &[FfmpegBuild] = &[
+    FfmpegBuild {
+        os: "macos",
+        arch: "aarch64",
+        asset: "ffmpeg-darwin-arm64.gz",
+        sha256: "",
+    },
+    FfmpegBuild {
+        os: "macos",
+        arch: "x86_64",
+        asset: "ffmpeg-darwin-x64.gz",
+        sha256: "",
+    },
+    FfmpegBuild {
+        os: "linux",
+        arch: "x86_64",
+        asset: "ffmpeg-linux-x64.gz",
+        sha256: "",
+    },
+    FfmpegBuild {
+        os: "linux",
+        arch: "aarch64",
+        asset: "ffmpeg-linux-arm64.gz",
+        sha256: "",
+    },
+    FfmpegBuild {
+        os: "windows",
+        arch: "x86_64",
+        asset: "ffmpeg-win32-x64.gz",
+        sha256: "",
+    },
+];
+
+static FFMPEG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Makes `find_ffmpeg_path` return this path, e.g. after installing the pinned build
+pub fn set_ffmpeg_path(path: PathBuf) {
+    if FFMPEG_PATH_OVERRIDE.set(path).is_err() {
+        error!("ffmpeg path was already set");
+    }
+}
+
+fn pinned_build() -> Option<&'static FfmpegBuild> {
+    PINNED_FFMPEG_BUILDS
+        .iter()
+        .find(|b| b.os == std::env::consts::OS && b.arch == std::env::consts::ARCH)
+}
+
+/// Where the pinned build lives in the data dir, versioned so a bump downloads the new one
+pub fn pinned_ffmpeg_path(data_dir: &Path) -> PathBuf {
+    data_dir
+        .join("ffmpeg")
+        .join(PINNED_FFMPEG_VERSION)
+        .join(EXECUTABLE_NAME)
+}
+
+pub fn verify_sha256(bytes: &[u8], expected: &str) -> Result<()> {
+    let actual = format!("{:x}", Sha256::digest(bytes));
+    if !actual.eq_ignore_ascii_case(expected) {
+        anyhow::bail!("checksum mismatch, expected {} got {}", expected, actual);
+    }
+    Ok(())
+}
+
+/// Downloads the pinned static ffmpeg build for this platform into the data dir,
+/// unless it was already installed, and returns its path
+pub async fn install_pinned_ffmpeg(data_dir: &Path) -> Result<PathBuf> {
+    let path = pinned_ffmpeg_path(data_dir);
+    let installed_sha256 = path.with_extension("sha256");
+    if path.exists() {
+        // the checksum of the binary, recorded once the archive it came from was verified
+        let recorded = std::fs::read_to_string(&installed_sha256).unwrap_or_default();
+        if !recorded.is_empty() && sha256_file(&path)?.eq_ignore_ascii_case(recorded.trim()) {
+            debug!("Pinned ffmpeg already installed at {:?}", path);
+            return Ok(path);
+        }
+        warn!(
+            "Pinned ffmpeg at {:?} was modified, installing it again",
+            path
+        );
+        std::fs::remove_file(&path)?;
+    }
+
+    let build = pinned_build().ok_or_else(|| {
+        anyhow::anyhow!(
+            "no pinned ffmpeg build for {} {}",
+            std::env::consts::OS,
+            std::env::consts::ARCH
+        )
+    })?;
+    if build.sha256.is_empty() {
+        anyhow::bail!(
+            "no checksum pinned for {}, refusing to install it",
+            build.asset
+        );
+    }
+
+    let url = format!(
+        "https://github.com/eugeneware/ffmpeg-static/releases/download/{}/{}",
+        PINNED_FFMPEG_VERSION, build.asset
+    );
+    info!("Downloading ffmpeg {} from {}", PINNED_FFMPEG_VERSION, url);
+    let archive = reqwest::get(&url)
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    verify_sha256(&archive, build.sha256)?;
+
+    let mut binary = Vec::new();
+    GzDecoder::new(archive.as_ref()).read_to_end(&mut binary)?;
+
+    // write next to the final path and rename so an interrupted download is never used
+    let dir = path.parent().expect("pinned ffmpeg path has a parent");
+    std::fs::create_dir_all(dir)?;
+    let partial = dir.join(format!("{}.partial", EXECUTABLE_NAME));
+    std::fs::write(&partial, &binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&partial, std::fs::Permissions::from_mode(0o755))?;
+    }
+    std::fs::write(&installed_sha256, format!("{:x}", Sha256::digest(&binary)))?;
+    std::fs::rename(&partial, &path)?;
+
+    info!("Installed ffmpeg {} at {:?}", PINNED_FFMPEG_VERSION, path);
+    Ok(path)
+}
 
 pub fn find_ffmpeg_path() -> Option<PathBuf> {
+    if let Some(path) = FFMPEG_PATH_OVERRIDE.get() {
+        return Some(path.clone());
+    }
+
+    find_system_ffmpeg_path()
+}
+
+/// Looks for an ffmpeg installed on the system or shipped next to the executable
+pub fn find_system_ffmpeg_path() -> Option<PathBuf> {
     debug!("Starting search for ffmpeg executable");
 
     // Check if `ffmpeg` is in the PATH environment variable
@@ -0,0 +1,491 @@
+use crate::{emit_event, find_ffmpeg_path};
+use anyhow::Result;
+use log::{debug, error, warn};
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
+use std::process::{ExitStatus, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::process::{Child, ChildStdin, ChildStdout, Command};
+
+/// Muxer / demuxer passed to `-f`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Container {
+    Mp4,
+    Image2Pipe,
+    F32le,
+    Other(String),
+}
+
+impl Container {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Container::Mp4 => "mp4",
+            Container::Image2Pipe => "image2pipe",
+            Container::F32le => "f32le",
+            Container::Other(name) => name,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoCodec {
+    /// `crf` from 0 (lossless) to 51, `preset` from ultrafast to veryslow
+    Libx264 {
+        preset: String,
+        crf: u8,
+    },
+    /// `quality` from 1 (best) to 31
+    Mpeg4 {
+        quality: u8,
+    },
+    Png,
+    Copy,
+    Other(String),
+}
+
+impl VideoCodec {
+    fn name(&self) -> &str {
+        match self {
+            VideoCodec::Libx264 { .. } => "libx264",
+            VideoCodec::Mpeg4 { .. } => "mpeg4",
+            VideoCodec::Png => "png",
+            VideoCodec::Copy => "copy",
+            VideoCodec::Other(name) => name,
+        }
+    }
+
+    fn args(&self) -> Vec<String> {
+        let mut args = vec!["-vcodec".to_string(), self.name().to_string()];
+        match self {
+            VideoCodec::Libx264 { preset, crf } => {
+                args.extend(["-preset".to_string(), preset.clone()]);
+                args.extend(["-crf".to_string(), crf.to_string()]);
+            }
+            VideoCodec::Mpeg4 { quality } => {
+                args.extend(["-q:v".to_string(), quality.to_string()]);
+            }
+            _ => {}
+        }
+        args
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AudioCodec {
+    Aac { bitrate_kbps: u32 },
+    Copy,
+    Other(String),
+}
+
+impl AudioCodec {
+    fn args(&self) -> Vec<String> {
+        match self {
+            AudioCodec::Aac { bitrate_kbps } => vec![
+                "-c:a".to_string(),
+                "aac".to_string(),
+                "-b:a".to_string(),
+                format!("{}k", bitrate_kbps),
+            ],
+            AudioCodec::Copy => vec!["-c:a".to_string(), "copy".to_string()],
+            AudioCodec::Other(name) => vec!["-c:a".to_string(), name.clone()],
+        }
+    }
+}
+
+/// Options of an input, they go before its `-i`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FfmpegInput {
+    source: String,
+    args: Vec<String>,
+}
+
+impl FfmpegInput {
+    /// Reads from the stdin of the process
+    pub fn pipe() -> Self {
+        FfmpegInput {
+            source: "pipe:0".to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn file(path: &str) -> Self {
+        FfmpegInput {
+            source: path.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn format(self, format: Container) -> Self {
+        self.arg("-f", format.as_str())
+    }
+
+    /// Decoder of the input frames
+    pub fn video_codec(self, codec: VideoCodec) -> Self {
+        let name = codec.name().to_string();
+        self.arg("-vcodec", &name)
+    }
+
+    pub fn frame_rate(self, fps: f64) -> Self {
+        self.arg("-r", &fps.to_string())
+    }
+
+    pub fn sample_rate(self, sample_rate: u32) -> Self {
+        self.arg("-ar", &sample_rate.to_string())
+    }
+
+    pub fn channels(self, channels: u16) -> Self {
+        self.arg("-ac", &channels.to_string())
+    }
+
+    pub fn arg(mut self, key: &str, value: &str) -> Self {
+        self.args.extend([key.to_string(), value.to_string()]);
+        self
+    }
+}
+
+/// Options of an output, they go before its path
+#[derive(Debug, Clone, PartialEq)]
+pub struct FfmpegOutput {
+    target: String,
+    args: Vec<String>,
+}
+
+impl FfmpegOutput {
+    /// Writes to the stdout of the process
+    pub fn pipe() -> Self {
+        FfmpegOutput {
+            target: "-".to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn file(path: &str) -> Self {
+        FfmpegOutput {
+            target: path.to_string(),
+            args: Vec::new(),
+        }
+    }
+
+    pub fn format(self, format: Container) -> Self {
+        self.arg("-f", format.as_str())
+    }
+
+    pub fn video_codec(mut self, codec: VideoCodec) -> Self {
+        self.args.extend(codec.args());
+        self
+    }
+
+    pub fn audio_codec(mut self, codec: AudioCodec) -> Self {
+        self.args.extend(codec.args());
+        self
+    }
+
+    pub fn pixel_format(self, pixel_format: &str) -> Self {
+        self.arg("-pix_fmt", pixel_format)
+    }
+
+    /// `-vf` filtergraph, e.g. `scale=1280:-2`
+    pub fn video_filter(self, filter: &str) -> Self {
+        self.arg("-vf", filter)
+    }
+
+    /// Stop after this many frames
+    pub fn frames(self, frames: u32) -> Self {
+        self.arg("-vframes", &frames.to_string())
+    }
+
+    pub fn arg(mut self, key: &str, value: &str) -> Self {
+        self.args.extend([key.to_string(), value.to_string()]);
+        self
+    }
+}
+
+/// Typed ffmpeg invocation, so callers don't assemble argument lists by hand
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FfmpegCommand {
+    global_args: Vec<String>,
+    inputs: Vec<FfmpegInput>,
+    outputs: Vec<FfmpegOutput>,
+}
+
+impl FfmpegCommand {
+    pub fn new() -> Self {
+        FfmpegCommand::default()
+    }
+
+    /// Overwrite output files without asking
+    pub fn overwrite(mut self) -> Self {
+        self.global_args.push("-y".to_string());
+        self
+    }
+
+    /// Option placed before the inputs, e.g. a hardware device
+    pub fn global_arg(mut self, key: &str, value: &str) -> Self {
+        self.global_args.push(key.to_string());
+        self.global_args.push(value.to_string());
+        self
+    }
+
+    pub fn input(mut self, input: FfmpegInput) -> Self {
+        self.inputs.push(input);
+        self
+    }
+
+    pub fn output(mut self, output: FfmpegOutput) -> Self {
+        self.outputs.push(output);
+        self
+    }
+
+    pub fn args(&self) -> Vec<String> {
+        let mut args = self.global_args.clone();
+        for input in &self.inputs {
+            args.extend(input.args.iter().cloned());
+            args.extend(["-i".to_string(), input.source.clone()]);
+        }
+        for output in &self.outputs {
+            args.extend(output.args.iter().cloned());
+            args.push(output.target.clone());
+        }
+        args
+    }
+
+    /// The process with stdin, stdout and stderr piped
+    pub fn to_command(&self) -> Result<Command> {
+        let ffmpeg_path: PathBuf =
+            find_ffmpeg_path().ok_or_else(|| anyhow::anyhow!("ffmpeg not found"))?;
+        let mut command = Command::new(ffmpeg_path);
+        command
+            .args(self.args())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        #[cfg(windows)]
+        command.creation_flags(crate::ffmpeg::CREATE_NO_WINDOW);
+        debug!("FFmpeg command: {:?}", command);
+        Ok(command)
+    }
+
+    /// Runs to completion and returns what ffmpeg wrote, for short jobs like extracting a frame
+    pub async fn run(&self) -> Result<Output> {
+        let mut command = self.to_command()?;
+        command.stdin(Stdio::null());
+        Ok(command.output().await?)
+    }
+
+    /// Starts a long running process whose stderr is turned into events
+    pub fn spawn(&self, label: &str) -> Result<FfmpegProcess> {
+        FfmpegProcess::spawn(label, self)
+    }
+}
+
+/// What ffmpeg reports on stderr
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FfmpegEvent {
+    Progress {
+        frame: Option<u64>,
+        fps: Option<f64>,
+        /// Position in the output, e.g. `00:00:04.00`
+        time: Option<String>,
+        /// Encoding speed relative to real time
+        speed: Option<f64>,
+    },
+    Warning {
+        message: String,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl FfmpegEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            FfmpegEvent::Progress { .. } => "ffmpeg.progress",
+            FfmpegEvent::Warning { .. } => "ffmpeg.warning",
+            FfmpegEvent::Error { .. } => "ffmpeg.error",
+        }
+    }
+}
+
+/// Parses `key=value` pairs of a progress line, ffmpeg pads values so `frame=  120` is one pair
+fn parse_progress(line: &str) -> FfmpegEvent {
+    let mut pairs = Vec::new();
+    let mut tokens = line.split_whitespace();
+    while let Some(token) = tokens.next() {
+        let Some((key, value)) = token.split_once('=') else {
+            continue;
+        };
+        let value = if value.is_empty() {
+            tokens.next().unwrap_or_default()
+        } else {
+            value
+        };
+        pairs.push((key, value));
+    }
+    let get = |name: &str| pairs.iter().find(|(k, _)| *k == name).map(|(_, v)| *v);
+
+    FfmpegEvent::Progress {
+        frame: get("frame").and_then(|v| v.parse().ok()),
+        fps: get("fps").and_then(|v| v.parse().ok()),
+        time: get("time").map(|v| v.to_string()),
+        speed: get("speed").and_then(|v| v.trim_end_matches('x').parse().ok()),
+    }
+}
+
+/// Informational lines (banner, stream mapping...) are not events
+pub fn parse_ffmpeg_stderr_line(line: &str) -> Option<FfmpegEvent> {
+    let line = line.trim();
+    if line.starts_with("frame=") || line.starts_with("size=") {
+        return Some(parse_progress(line));
+    }
+
+    let lowercase = line.to_lowercase();
+    if ["error", "invalid", "failed", "could not", "unable to"]
+        .iter()
+        .any(|word| lowercase.contains(word))
+    {
+        return Some(FfmpegEvent::Error {
+            message: line.to_string(),
+        });
+    }
+    if lowercase.contains("warning") || lowercase.contains("deprecated") {
+        return Some(FfmpegEvent::Warning {
+            message: line.to_string(),
+        });
+    }
+    None
+}
+
+/// Progress lines end with `\r` to be redrawn in a terminal, so split on both
+async fn read_lines(mut reader: impl AsyncRead + Unpin, mut on_line: impl FnMut(&str)) {
+    let mut pending = Vec::new();
+    let mut buffer = [0u8; 4096];
+    while let Ok(read) = reader.read(&mut buffer).await {
+        if read == 0 {
+            break;
+        }
+        for byte in &buffer[..read] {
+            if *byte == b'\n' || *byte == b'\r' {
+                if !pending.is_empty() {
+                    on_line(&String::from_utf8_lossy(&pending));
+                    pending.clear();
+                }
+            } else {
+                pending.push(*byte);
+            }
+        }
+    }
+    if !pending.is_empty() {
+        on_line(&String::from_utf8_lossy(&pending));
+    }
+}
+
+/// A supervised ffmpeg process. Its stderr is parsed into `ffmpeg.*` events tagged with `label`
+/// and the last error is kept to explain why it died.
+pub struct FfmpegProcess {
+    label: String,
+    child: Child,
+    stdin: Option<ChildStdin>,
+    last_error: Arc<Mutex<Option<String>>>,
+}
+
+impl FfmpegProcess {
+    fn spawn(label: &str, command: &FfmpegCommand) -> Result<Self> {
+        let mut child = command.to_command()?.spawn()?;
+        let stdin = child.stdin.take();
+        let last_error = Arc::new(Mutex::new(None));
+
+        if let Some(stderr) = child.stderr.take() {
+            let label = label.to_string();
+            let last_error = last_error.clone();
+            tokio::spawn(async move {
+                read_lines(stderr, |line| {
+                    debug!("FFmpeg {}: {}", label, line);
+                    let Some(event) = parse_ffmpeg_stderr_line(line) else {
+                        return;
+                    };
+                    if let FfmpegEvent::Error { message } = &event {
+                        *last_error.lock().unwrap() = Some(message.clone());
+                    }
+                    let mut data = json!(event);
+                    data["process"] = json!(label);
+                    emit_event(event.name(), data);
+                })
+                .await;
+            });
+        }
+
+        debug!("FFmpeg process {} spawned", label);
+        Ok(FfmpegProcess {
+            label: label.to_string(),
+            child,
+            stdin,
+            last_error,
+        })
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    /// Can only be taken once, the caller is then responsible for reading it
+    pub fn take_stdout(&mut self) -> Option<ChildStdout> {
+        self.child.stdout.take()
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.lock().unwrap().clone()
+    }
+
+    pub async fn write(&mut self, data: &[u8]) -> Result<()> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| anyhow::anyhow!("ffmpeg {} stdin is closed", self.label))?;
+        stdin.write_all(data).await?;
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        if let Some(stdin) = self.stdin.as_mut() {
+            stdin.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// True if the process died on its own, e.g. crashed mid-recording
+    pub fn has_exited(&mut self) -> bool {
+        matches!(self.child.try_wait(), Ok(Some(_)))
+    }
+
+    /// Closes stdin so ffmpeg finalizes the output, then waits for it to exit
+    pub async fn finish(mut self) -> Result<ExitStatus> {
+        drop(self.stdin.take());
+        let status = self.child.wait().await?;
+        emit_event(
+            "ffmpeg.exited",
+            json!({"process": self.label, "code": status.code(), "success": status.success()}),
+        );
+        if !status.success() {
+            let reason = self.last_error().unwrap_or_default();
+            error!(
+                "FFmpeg {} exited with status {}: {}",
+                self.label, status, reason
+            );
+            anyhow::bail!("ffmpeg {} exited with {}: {}", self.label, status, reason);
+        }
+        Ok(status)
+    }
+}
+
+impl Drop for FfmpegProcess {
+    fn drop(&mut self) {
+        if self.stdin.is_some() && !self.has_exited() {
+            warn!("FFmpeg {} dropped while running, killing it", self.label);
+            let _ = self.child.start_kill();
+        }
+    }
+}
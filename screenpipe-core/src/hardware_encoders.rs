@@ -0,0 +1,183 @@
+use crate::{Container, FfmpegCommand, FfmpegInput, FfmpegOutput, VideoCodec};
+use log::{debug, info};
+use serde::{Deserialize, Serialize};
+
+/// H.264 encoder used for the video chunks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoEncoder {
+    /// libx264, or mpeg4 on windows
+    Software,
+    /// Apple VideoToolbox (macos)
+    VideoToolbox,
+    /// NVIDIA NVENC
+    Nvenc,
+    /// Intel Quick Sync Video
+    Qsv,
+    /// VA-API (linux, Intel and AMD)
+    Vaapi,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EncoderQuality {
+    /// Smallest files, text can get blurry
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+const VAAPI_DEVICE: &str = "/dev/dri/renderD128";
+
+impl VideoEncoder {
+    /// Hardware encoders, best first
+    pub const HARDWARE: [VideoEncoder; 4] = [
+        VideoEncoder::VideoToolbox,
+        VideoEncoder::Nvenc,
+        VideoEncoder::Qsv,
+        VideoEncoder::Vaapi,
+    ];
+
+    pub fn codec_name(&self) -> &'static str {
+        match self {
+            VideoEncoder::Software if cfg!(windows) => "mpeg4",
+            VideoEncoder::Software => "libx264",
+            VideoEncoder::VideoToolbox => "h264_videotoolbox",
+            VideoEncoder::Nvenc => "h264_nvenc",
+            VideoEncoder::Qsv => "h264_qsv",
+            VideoEncoder::Vaapi => "h264_vaapi",
+        }
+    }
+
+    /// Whether the encoder can exist on this os at all, the others are not probed
+    pub fn supported_on_this_os(&self) -> bool {
+        match self {
+            VideoEncoder::Software => true,
+            VideoEncoder::VideoToolbox => cfg!(target_os = "macos"),
+            VideoEncoder::Nvenc | VideoEncoder::Qsv => {
+                cfg!(any(target_os = "linux", target_os = "windows"))
+            }
+            VideoEncoder::Vaapi => cfg!(target_os = "linux"),
+        }
+    }
+
+    /// Adds the encoder, its quality preset and the pixel format it needs to the output
+    pub fn encode_to(
+        &self,
+        command: FfmpegCommand,
+        output: FfmpegOutput,
+        quality: EncoderQuality,
+    ) -> FfmpegCommand {
+        let pick = |low: &str, medium: &str, high: &str| -> String {
+            match quality {
+                EncoderQuality::Low => low.to_string(),
+                EncoderQuality::Medium => medium.to_string(),
+                EncoderQuality::High => high.to_string(),
+            }
+        };
+        let codec = VideoCodec::Other(self.codec_name().to_string());
+
+        match self {
+            VideoEncoder::Software if cfg!(windows) => {
+                // TODO switch back to libx264 when ffmpeg is updated in pre_build.js
+                let quality = match quality {
+                    EncoderQuality::Low => 8,
+                    EncoderQuality::Medium => 5,
+                    EncoderQuality::High => 3,
+                };
+                command.output(
+                    output
+                        .video_codec(VideoCodec::Mpeg4 { quality })
+                        .pixel_format("yuv420p"),
+                )
+            }
+            VideoEncoder::Software => command.output(
+                output
+                    .video_codec(VideoCodec::Libx264 {
+                        preset: "ultrafast".to_string(),
+                        crf: match quality {
+                            EncoderQuality::Low => 28,
+                            EncoderQuality::Medium => 23,
+                            EncoderQuality::High => 18,
+                        },
+                    })
+                    .pixel_format("yuv420p"),
+            ),
+            VideoEncoder::VideoToolbox => command.output(
+                output
+                    .video_codec(codec)
+                    .arg("-realtime", "1")
+                    .arg("-q:v", &pick("40", "55", "70"))
+                    .pixel_format("yuv420p"),
+            ),
+            VideoEncoder::Nvenc => command.output(
+                output
+                    .video_codec(codec)
+                    .arg("-preset", "p2")
+                    .arg("-rc", "vbr")
+                    .arg("-cq", &pick("32", "26", "20"))
+                    .pixel_format("yuv420p"),
+            ),
+            VideoEncoder::Qsv => command.output(
+                output
+                    .video_codec(codec)
+                    .arg("-preset", "veryfast")
+                    .arg("-global_quality", &pick("32", "26", "20"))
+                    .pixel_format("nv12"),
+            ),
+            VideoEncoder::Vaapi => command.global_arg("-vaapi_device", VAAPI_DEVICE).output(
+                output
+                    .video_filter("format=nv12,hwupload")
+                    .video_codec(codec)
+                    .arg("-qp", &pick("32", "26", "20")),
+            ),
+        }
+    }
+}
+
+/// Encodes one synthetic frame, listing the encoder in `ffmpeg -encoders` doesn't mean
+/// the hardware (or its driver) is there
+pub async fn probe_encoder(encoder: VideoEncoder) -> bool {
+    let command = FfmpegCommand::new().input(
+        FfmpegInput::file("color=black:s=256x256:d=0.1")
+            .format(Container::Other("lavfi".to_string())),
+    );
+    let output = FfmpegOutput::pipe()
+        .frames(1)
+        .format(Container::Other("null".to_string()));
+
+    let available = match encoder
+        .encode_to(command, output, EncoderQuality::Medium)
+        .run()
+        .await
+    {
+        Ok(output) => output.status.success(),
+        Err(e) => {
+            debug!("Failed to probe {:?}: {}", encoder, e);
+            false
+        }
+    };
+    debug!("Encoder {:?} available: {}", encoder, available);
+    available
+}
+
+/// Hardware encoders that work on this machine, best first
+pub async fn probe_hardware_encoders() -> Vec<VideoEncoder> {
+    let mut available = Vec::new();
+    for encoder in VideoEncoder::HARDWARE {
+        if encoder.supported_on_this_os() && probe_encoder(encoder).await {
+            available.push(encoder);
+        }
+    }
+    info!("Available hardware encoders: {:?}", available);
+    available
+}
+
+/// The best hardware encoder available, software otherwise
+pub fn best_encoder(available: &[VideoEncoder]) -> VideoEncoder {
+    VideoEncoder::HARDWARE
+        .into_iter()
+        .find(|encoder| available.contains(encoder))
+        .unwrap_or(VideoEncoder::Software)
+}
@@ -1,8 +1,36 @@
+#[cfg(feature = "local-models")]
+pub mod accelerators;
+#[cfg(feature = "local-models")]
+pub use accelerators::*;
+pub mod channels;
+pub use channels::*;
+pub mod events;
+pub use events::*;
 pub mod ffmpeg;
-pub use ffmpeg::find_ffmpeg_path;
+pub use ffmpeg::{
+    find_ffmpeg_path, find_system_ffmpeg_path, install_pinned_ffmpeg, set_ffmpeg_path,
+    PINNED_FFMPEG_VERSION,
+};
+pub mod ffmpeg_command;
+pub use ffmpeg_command::*;
+pub mod hardware_encoders;
+pub use hardware_encoders::*;
+#[cfg(feature = "llm")]
 pub mod llm;
+#[cfg(feature = "llm")]
 pub use llm::*;
+pub mod models;
+pub use models::*;
+#[cfg(feature = "llm")]
+pub mod prompts;
+#[cfg(feature = "llm")]
+pub use prompts::{PromptLibrary, PromptTemplate};
 pub mod pipes;
 pub use pipes::*;
 #[cfg(feature = "security")]
-mod pii_removal;
+pub mod pii_removal;
+#[cfg(feature = "security")]
+pub use pii_removal::{
+    find_pii, find_secrets, luhn_valid, redact_matches, remove_pii, NerModel, PiiEntityKind,
+    PiiMatch, PiiRedactor, SecretKind, DEFAULT_NER_MODEL,
+};
@@ -3,15 +3,21 @@ use candle::{DType, Device, Tensor};
 use candle_nn::VarBuilder;
 use candle_transformers::models::phi3::{Config as Phi3Config, Model as Phi3};
 
-use hf_hub::{api::sync::Api, Repo, RepoType};
+use base64::{engine::general_purpose, Engine as _};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use tokenizers::Tokenizer;
 
+use crate::model_file_path;
+
 /// Loads the safetensors files for a model from the hub based on a json index file.
 pub fn hub_load_safetensors(
-    repo: &hf_hub::api::sync::ApiRepo,
+    model_id: &str,
+    revision: &str,
     json_file: &str,
 ) -> Result<Vec<std::path::PathBuf>> {
-    let json_file = repo.get(json_file).map_err(candle::Error::wrap)?;
+    let json_file = model_file_path(model_id, revision, json_file)?;
     let json_file = std::fs::File::open(json_file)?;
     let json: serde_json::Value =
         serde_json::from_reader(&json_file).map_err(candle::Error::wrap)?;
@@ -28,23 +34,17 @@ pub fn hub_load_safetensors(
     }
     let safetensors_files = safetensors_files
         .iter()
-        .map(|v| repo.get(v).map_err(anyhow::Error::from))
+        .map(|v| model_file_path(model_id, revision, v))
         .collect::<Result<Vec<_>, anyhow::Error>>()?;
     Ok(safetensors_files)
 }
 
 pub fn load_llama_model(device: &Device) -> Result<(Phi3, Tokenizer)> {
-    let api = Api::new()?;
     let model_id = "microsoft/Phi-3-mini-4k-instruct";
     let revision = "main";
 
-    let api = api.repo(Repo::with_revision(
-        model_id.to_string(),
-        RepoType::Model,
-        revision.to_string(),
-    ));
-    let tokenizer_filename = api.get("tokenizer.json")?;
-    let config_filename = api.get("config.json")?;
+    let tokenizer_filename = model_file_path(model_id, revision, "tokenizer.json")?;
+    let config_filename = model_file_path(model_id, revision, "config.json")?;
 
     let config: Phi3Config = serde_json::from_slice(&std::fs::read(config_filename)?)?;
 
@@ -52,7 +52,7 @@ pub fn load_llama_model(device: &Device) -> Result<(Phi3, Tokenizer)> {
     // let dtype = DType::BF16;
     let dtype = DType::F32;
 
-    let filenames = hub_load_safetensors(&api, "model.safetensors.index.json")?;
+    let filenames = hub_load_safetensors(model_id, revision, "model.safetensors.index.json")?;
     let vb = unsafe { VarBuilder::from_mmaped_safetensors(&filenames, dtype, device)? };
 
     let model = Phi3::new(&config, vb)?;
@@ -118,8 +118,361 @@ where
             .decode(&[next_token], true)
             .map_err(anyhow::Error::msg)?;
         callback(text)?;
-
     }
 
     Ok(())
 }
+
+/// Connection settings for an OpenAI compatible chat completion API
+/// (OpenAI, Ollama, LM Studio, llama.cpp server...)
+#[derive(Debug, Clone)]
+pub struct LlmConfig {
+    pub api_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        LlmConfig {
+            // ollama exposes an openai compatible api by default
+            api_url: "http://localhost:11434/v1".to_string(),
+            api_key: None,
+            model: "llama3.1".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    /// Data urls of the images attached to the message, see `encode_image_for_llm`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+    /// The tools an assistant message asked to call
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ToolCall>,
+    /// The call a "tool" message is the result of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+/// A function the model can call, its parameters described by a JSON schema
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    /// As the model wrote them, not checked against the schema
+    pub arguments: serde_json::Value,
+}
+
+impl ChatMessage {
+    fn new(role: &str, content: impl Into<String>) -> Self {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.into(),
+            images: vec![],
+            tool_calls: vec![],
+            tool_call_id: None,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        ChatMessage::new("system", content)
+    }
+
+    pub fn user(content: impl Into<String>) -> Self {
+        ChatMessage::new("user", content)
+    }
+
+    pub fn user_with_images(content: impl Into<String>, images: Vec<String>) -> Self {
+        ChatMessage {
+            images,
+            ..ChatMessage::new("user", content)
+        }
+    }
+
+    pub fn assistant(content: impl Into<String>) -> Self {
+        ChatMessage::new("assistant", content)
+    }
+
+    /// The answer of a model asking for tools, sent back with their results
+    pub fn assistant_with_tool_calls(
+        content: impl Into<String>,
+        tool_calls: Vec<ToolCall>,
+    ) -> Self {
+        ChatMessage {
+            tool_calls,
+            ..ChatMessage::new("assistant", content)
+        }
+    }
+
+    /// What a tool the model called returned, usually JSON
+    pub fn tool_result(tool_call_id: impl Into<String>, content: impl Into<String>) -> Self {
+        ChatMessage {
+            tool_call_id: Some(tool_call_id.into()),
+            ..ChatMessage::new("tool", content)
+        }
+    }
+
+    /// OpenAI format, also understood by Ollama and most compatible servers
+    fn to_api_json(&self) -> serde_json::Value {
+        if let Some(tool_call_id) = &self.tool_call_id {
+            return json!({"role": self.role, "tool_call_id": tool_call_id, "content": self.content});
+        }
+        if !self.tool_calls.is_empty() {
+            let tool_calls: Vec<serde_json::Value> = self
+                .tool_calls
+                .iter()
+                .map(|call| {
+                    json!({
+                        "id": call.id,
+                        "type": "function",
+                        "function": {"name": call.name, "arguments": call.arguments.to_string()},
+                    })
+                })
+                .collect();
+            return json!({"role": self.role, "content": self.content, "tool_calls": tool_calls});
+        }
+        if self.images.is_empty() {
+            return json!({"role": self.role, "content": self.content});
+        }
+        let mut content = vec![json!({"type": "text", "text": self.content})];
+        content.extend(
+            self.images
+                .iter()
+                .map(|url| json!({"type": "image_url", "image_url": {"url": url}})),
+        );
+        json!({"role": self.role, "content": content})
+    }
+}
+
+/// Part of an image, in pixels
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct ImageRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Vision models bill per image tile, screens are shrunk to this by default
+pub const DEFAULT_LLM_IMAGE_MAX_DIMENSION: u32 = 1024;
+
+/// Crops the image to the region (clamped to the image), downscales it so its largest side
+/// is at most `max_dimension` and encodes it as a jpeg data url
+pub fn encode_image_for_llm(
+    image: &DynamicImage,
+    region: Option<ImageRegion>,
+    max_dimension: u32,
+) -> Result<String> {
+    let image = match region {
+        Some(region) => {
+            let x = region.x.min(image.width().saturating_sub(1));
+            let y = region.y.min(image.height().saturating_sub(1));
+            let width = region.width.min(image.width() - x).max(1);
+            let height = region.height.min(image.height() - y).max(1);
+            image.crop_imm(x, y, width, height)
+        }
+        None => image.clone(),
+    };
+
+    let image = if image.width() > max_dimension || image.height() > max_dimension {
+        image.resize(max_dimension, max_dimension, FilterType::Triangle)
+    } else {
+        image
+    };
+
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, 80).encode_image(&image.to_rgb8())?;
+    Ok(format!(
+        "data:image/jpeg;base64,{}",
+        general_purpose::STANDARD.encode(jpeg)
+    ))
+}
+
+/// Tokens billed for a call, as reported by the API (or estimated if it doesn't report them)
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChatCompletion {
+    /// Empty when the model only asks for tools
+    pub content: String,
+    pub tool_calls: Vec<ToolCall>,
+    pub usage: TokenUsage,
+}
+
+/// What a downscaled screenshot costs on most vision models
+const ESTIMATED_IMAGE_TOKENS: u64 = 765;
+
+/// Rough estimation used when the API doesn't report usage, ~4 characters per token
+pub fn estimate_tokens(text: &str) -> u64 {
+    (text.chars().count() as u64).div_ceil(4)
+}
+
+fn parse_usage(response: &serde_json::Value) -> Option<TokenUsage> {
+    let usage = response.get("usage")?;
+    Some(TokenUsage {
+        prompt_tokens: usage["prompt_tokens"].as_u64()?,
+        completion_tokens: usage["completion_tokens"].as_u64().unwrap_or(0),
+    })
+}
+
+async fn post_json(
+    config: &LlmConfig,
+    path: &str,
+    body: &serde_json::Value,
+) -> Result<serde_json::Value> {
+    let url = format!("{}/{}", config.api_url.trim_end_matches('/'), path);
+    let client = reqwest::Client::new();
+    let mut request = client.post(&url).json(body);
+    if let Some(api_key) = &config.api_key {
+        request = request.bearer_auth(api_key);
+    }
+
+    let response = request.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        anyhow::bail!("{} failed with status {}: {}", path, status, text);
+    }
+    Ok(response.json().await?)
+}
+
+/// The tool calls of the first choice of a chat completion response. Their arguments are a
+/// JSON string in OpenAI's format, an object in some compatible servers
+pub fn parse_tool_calls(response: &serde_json::Value) -> Vec<ToolCall> {
+    let Some(calls) = response["choices"][0]["message"]["tool_calls"].as_array() else {
+        return Vec::new();
+    };
+    calls
+        .iter()
+        .enumerate()
+        .filter_map(|(index, call)| {
+            let function = &call["function"];
+            let arguments = match &function["arguments"] {
+                serde_json::Value::String(arguments) if arguments.trim().is_empty() => json!({}),
+                serde_json::Value::String(arguments) => serde_json::from_str(arguments)
+                    .unwrap_or_else(|_| serde_json::Value::String(arguments.clone())),
+                serde_json::Value::Null => json!({}),
+                arguments => arguments.clone(),
+            };
+            Some(ToolCall {
+                // ollama leaves the id out
+                id: call["id"]
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("call_{}", index)),
+                name: function["name"].as_str()?.to_string(),
+                arguments,
+            })
+        })
+        .collect()
+}
+
+/// Sends the messages to the chat completion endpoint and returns the content of the first choice
+pub async fn chat_completion(
+    config: &LlmConfig,
+    messages: &[ChatMessage],
+) -> Result<ChatCompletion> {
+    chat_completion_with_tools(config, messages, &[]).await
+}
+
+/// Like `chat_completion`, the model may answer with calls of the tools rather than content
+pub async fn chat_completion_with_tools(
+    config: &LlmConfig,
+    messages: &[ChatMessage],
+    tools: &[ToolDefinition],
+) -> Result<ChatCompletion> {
+    let api_messages: Vec<serde_json::Value> = messages.iter().map(|m| m.to_api_json()).collect();
+    let mut body = json!({
+        "model": config.model,
+        "messages": api_messages,
+        "temperature": 0.2,
+        "stream": false,
+    });
+    if !tools.is_empty() {
+        body["tools"] = tools
+            .iter()
+            .map(|tool| {
+                json!({
+                    "type": "function",
+                    "function": {
+                        "name": tool.name,
+                        "description": tool.description,
+                        "parameters": tool.parameters,
+                    },
+                })
+            })
+            .collect();
+    }
+    let response = post_json(config, "chat/completions", &body).await?;
+
+    let tool_calls = parse_tool_calls(&response);
+    let content = match response["choices"][0]["message"]["content"].as_str() {
+        Some(content) => content.to_string(),
+        None if !tool_calls.is_empty() => String::new(),
+        None => anyhow::bail!("no content in chat completion response: {}", response),
+    };
+    let usage = parse_usage(&response).unwrap_or_else(|| TokenUsage {
+        prompt_tokens: messages
+            .iter()
+            .map(|m| estimate_tokens(&m.content) + m.images.len() as u64 * ESTIMATED_IMAGE_TOKENS)
+            .sum(),
+        completion_tokens: estimate_tokens(&content),
+    });
+
+    Ok(ChatCompletion {
+        content,
+        tool_calls,
+        usage,
+    })
+}
+
+/// Embeds the inputs with the `/embeddings` endpoint, in the same order
+pub async fn create_embeddings(
+    config: &LlmConfig,
+    inputs: &[String],
+) -> Result<(Vec<Vec<f32>>, TokenUsage)> {
+    let body = json!({
+        "model": config.model,
+        "input": inputs,
+    });
+    let response = post_json(config, "embeddings", &body).await?;
+
+    let embeddings = response["data"]
+        .as_array()
+        .ok_or_else(|| anyhow::anyhow!("no data in embeddings response"))?
+        .iter()
+        .map(|item| {
+            item["embedding"]
+                .as_array()
+                .map(|values| {
+                    values
+                        .iter()
+                        .filter_map(|v| v.as_f64())
+                        .map(|v| v as f32)
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+        .collect();
+    let usage = parse_usage(&response).unwrap_or_else(|| TokenUsage {
+        prompt_tokens: inputs.iter().map(|i| estimate_tokens(i)).sum(),
+        completion_tokens: 0,
+    });
+
+    Ok((embeddings, usage))
+}
@@ -0,0 +1,596 @@
+use anyhow::{Context, Result};
+use log::{info, warn};
+use reqwest::blocking::Client;
+use reqwest::header::{CONTENT_LENGTH, RANGE};
+use reqwest::redirect::Policy;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Directory of the data dir the models are cached in, shared by its profiles and users
+pub const MODELS_DIR: &str = "models";
+const MANIFEST_FILE: &str = "manifest.json";
+const HUB_URL: &str = "https://huggingface.co";
+
+/// A file of a Hugging Face repo
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct ModelFile {
+    pub repo: &'static str,
+    pub revision: &'static str,
+    pub file: &'static str,
+}
+
+/// The files a local model loads, to prefetch them by name
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct KnownModel {
+    pub name: &'static str,
+    pub files: &'static [ModelFile],
+}
+
+impl KnownModel {
+    /// Path of one of its files in the cache, downloaded when it isn't there
+    pub fn file(&self, file: &str) -> Result<PathBuf> {
+        let model_file = self
+            .files
+            .iter()
+            .find(|model_file| model_file.file == file)
+            .ok_or_else(|| anyhow::anyhow!("{} has no file {}", self.name, file))?;
+        model_file_path(model_file.repo, model_file.revision, model_file.file)
+    }
+}
+
+const fn hub_file(repo: &'static str, revision: &'static str, file: &'static str) -> ModelFile {
+    ModelFile {
+        repo,
+        revision,
+        file,
+    }
+}
+
+pub const TROCR_HANDWRITTEN: KnownModel = KnownModel {
+    name: "trocr-handwritten",
+    files: &[
+        // the revision the weights are in as safetensors
+        hub_file(
+            "microsoft/trocr-base-handwritten",
+            "refs/pr/3",
+            "config.json",
+        ),
+        hub_file(
+            "microsoft/trocr-base-handwritten",
+            "refs/pr/3",
+            "model.safetensors",
+        ),
+        hub_file(
+            "ToluClassics/candle-trocr-tokenizer",
+            "main",
+            "tokenizer.json",
+        ),
+    ],
+};
+
+pub const WHISPER_TINY: KnownModel = KnownModel {
+    name: "whisper-tiny",
+    files: &[
+        hub_file("openai/whisper-tiny", "main", "config.json"),
+        hub_file("openai/whisper-tiny", "main", "tokenizer.json"),
+        hub_file("openai/whisper-tiny", "main", "model.safetensors"),
+    ],
+};
+
+pub const WHISPER_DISTIL_LARGE_V3: KnownModel = KnownModel {
+    name: "whisper-distil-large-v3",
+    files: &[
+        hub_file("distil-whisper/distil-large-v3", "main", "config.json"),
+        hub_file("distil-whisper/distil-large-v3", "main", "tokenizer.json"),
+        hub_file(
+            "distil-whisper/distil-large-v3",
+            "main",
+            "model.safetensors",
+        ),
+    ],
+};
+
+pub const JINA_EMBEDDINGS: KnownModel = KnownModel {
+    name: "jina-embeddings",
+    files: &[
+        hub_file(
+            "jinaai/jina-embeddings-v2-base-en",
+            "main",
+            "model.safetensors",
+        ),
+        hub_file(
+            "jinaai/jina-embeddings-v2-base-en",
+            "main",
+            "tokenizer.json",
+        ),
+    ],
+};
+
+/// The default model of `NerModel`, which ships only the WordPiece vocabulary
+pub const BERT_NER: KnownModel = KnownModel {
+    name: "bert-ner",
+    files: &[
+        hub_file("dslim/bert-base-NER", "main", "config.json"),
+        hub_file("dslim/bert-base-NER", "main", "model.safetensors"),
+        hub_file("dslim/bert-base-NER", "main", "vocab.txt"),
+    ],
+};
+
+/// What `models prefetch` knows by name
+pub const KNOWN_MODELS: [KnownModel; 5] = [
+    TROCR_HANDWRITTEN,
+    WHISPER_TINY,
+    WHISPER_DISTIL_LARGE_V3,
+    JINA_EMBEDDINGS,
+    BERT_NER,
+];
+
+pub fn known_model(name: &str) -> Option<&'static KnownModel> {
+    KNOWN_MODELS.iter().find(|model| model.name == name)
+}
+
+/// A file of the cache, as the manifest records it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CachedFile {
+    pub file: String,
+    pub size_bytes: u64,
+    pub sha256: String,
+    /// The sha256 matched the one the hub has for the file. Files the hub keeps in git have
+    /// none, their checksum is the one computed when they were downloaded
+    pub verified: bool,
+    /// Seconds since the epoch
+    pub downloaded_at: u64,
+    pub last_used: u64,
+}
+
+/// The files downloaded from a repo at a revision
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CachedModel {
+    /// `repo@revision`, what `remove` takes
+    pub id: String,
+    pub repo: String,
+    pub revision: String,
+    pub size_bytes: u64,
+    pub last_used: u64,
+    pub files: Vec<CachedFile>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Manifest {
+    /// By `repo@revision`, then by file
+    models: BTreeMap<String, BTreeMap<String, CachedFile>>,
+}
+
+fn model_id(repo: &str, revision: &str) -> String {
+    format!("{}@{}", repo, revision)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn sha256_file(path: &Path) -> Result<String> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 20];
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// What the hub says of a file before it's downloaded
+struct HubFile {
+    size_bytes: Option<u64>,
+    /// Only the files stored with LFS, the weights, have one
+    sha256: Option<String>,
+}
+
+/// Downloads the models once into one directory, checks their sha256 and keeps them within the
+/// quota by removing the ones used least recently
+pub struct ModelManager {
+    dir: PathBuf,
+    quota_bytes: Option<u64>,
+    hub_url: String,
+    /// Held while the manifest is read and written and while downloading, one file at a time
+    lock: Mutex<()>,
+}
+
+impl ModelManager {
+    pub fn new(dir: PathBuf, quota_bytes: Option<u64>) -> Self {
+        Self {
+            dir,
+            quota_bytes,
+            hub_url: std::env::var("HF_ENDPOINT").unwrap_or_else(|_| HUB_URL.to_string()),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Downloads from another hub, a mirror or a test server
+    pub fn with_hub_url(mut self, hub_url: impl Into<String>) -> Self {
+        self.hub_url = hub_url.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn quota_bytes(&self) -> Option<u64> {
+        self.quota_bytes
+    }
+
+    fn manifest(&self) -> Manifest {
+        match std::fs::read(self.dir.join(MANIFEST_FILE)) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                warn!(
+                    "The models manifest can't be read, starting a new one: {}",
+                    e
+                );
+                Manifest::default()
+            }),
+            Err(_) => Manifest::default(),
+        }
+    }
+
+    fn save_manifest(&self, manifest: &Manifest) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let partial = self.dir.join(format!("{}.partial", MANIFEST_FILE));
+        std::fs::write(&partial, serde_json::to_vec_pretty(manifest)?)?;
+        std::fs::rename(partial, self.dir.join(MANIFEST_FILE))?;
+        Ok(())
+    }
+
+    fn repo_dir(&self, repo: &str, revision: &str) -> PathBuf {
+        self.dir
+            .join(repo.replace('/', "--"))
+            .join(revision.replace('/', "--"))
+    }
+
+    /// Path of a file of a repo in the cache, downloaded when it isn't there or doesn't have the
+    /// size it was downloaded with. Blocks while downloading
+    pub fn file(&self, repo: &str, revision: &str, file: &str) -> Result<PathBuf> {
+        let _lock = self.lock.lock().unwrap();
+        let id = model_id(repo, revision);
+        let path = self.repo_dir(repo, revision).join(file);
+        let mut manifest = self.manifest();
+        let cached = manifest
+            .models
+            .get_mut(&id)
+            .and_then(|files| files.get_mut(file));
+        if let Some(cached) = cached {
+            let size = std::fs::metadata(&path).map(|m| m.len()).ok();
+            if size == Some(cached.size_bytes) {
+                cached.last_used = now();
+                self.save_manifest(&manifest)?;
+                return Ok(path);
+            }
+            warn!(
+                "{} of {} changed in the cache, downloading it again",
+                file, id
+            );
+        }
+
+        let cached = match self.take_from_hf_cache(repo, revision, file, &path)? {
+            Some(cached) => cached,
+            None => self.download(&mut manifest, repo, revision, file, &path)?,
+        };
+        manifest
+            .models
+            .entry(id)
+            .or_default()
+            .insert(file.to_string(), cached);
+        self.save_manifest(&manifest)?;
+        Ok(path)
+    }
+
+    /// Takes the file from the cache of the hf-hub crate, where the models were downloaded before
+    #[cfg(feature = "local-models")]
+    fn take_from_hf_cache(
+        &self,
+        repo: &str,
+        revision: &str,
+        file: &str,
+        path: &Path,
+    ) -> Result<Option<CachedFile>> {
+        let repo_cache = hf_hub::Cache::default().repo(hf_hub::Repo::with_revision(
+            repo.to_string(),
+            hf_hub::RepoType::Model,
+            revision.to_string(),
+        ));
+        let Some(existing) = repo_cache.get(file) else {
+            return Ok(None);
+        };
+        std::fs::create_dir_all(path.parent().expect("a file of a repo has a directory"))?;
+        let _ = std::fs::remove_file(path);
+        if std::fs::hard_link(&existing, path).is_err() {
+            std::fs::copy(&existing, path)?;
+        }
+        log::debug!("{} of {} taken from the hf-hub cache", file, repo);
+        let now = now();
+        Ok(Some(CachedFile {
+            file: file.to_string(),
+            size_bytes: std::fs::metadata(path)?.len(),
+            sha256: sha256_file(path)?,
+            verified: false,
+            downloaded_at: now,
+            last_used: now,
+        }))
+    }
+
+    /// Nothing used hf-hub to download before without the local models
+    #[cfg(not(feature = "local-models"))]
+    fn take_from_hf_cache(
+        &self,
+        _repo: &str,
+        _revision: &str,
+        _file: &str,
+        _path: &Path,
+    ) -> Result<Option<CachedFile>> {
+        Ok(None)
+    }
+
+    fn url(&self, repo: &str, revision: &str, file: &str) -> String {
+        format!(
+            "{}/{}/resolve/{}/{}",
+            self.hub_url,
+            repo,
+            revision.replace('/', "%2F"),
+            file
+        )
+    }
+
+    /// The size and sha256 the hub answers with before redirecting to the file
+    fn hub_file(&self, url: &str) -> Result<HubFile> {
+        let client = Client::builder().redirect(Policy::none()).build()?;
+        let response = client.head(url).send()?;
+        if response.status().is_client_error() || response.status().is_server_error() {
+            anyhow::bail!("{} answered {}", url, response.status());
+        }
+        let header = |name: &str| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.trim_matches('"').to_string())
+        };
+        let sha256 = header("x-linked-etag")
+            .filter(|etag| etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit()));
+        let size_bytes = header("x-linked-size")
+            .or_else(|| header(CONTENT_LENGTH.as_str()))
+            .and_then(|size| size.parse().ok());
+        Ok(HubFile { size_bytes, sha256 })
+    }
+
+    /// Removes the models used least recently until `needed` more bytes fit in the quota,
+    /// never the one being downloaded
+    fn make_room(&self, manifest: &mut Manifest, needed: u64, keep: &str) -> Result<()> {
+        let Some(quota) = self.quota_bytes else {
+            return Ok(());
+        };
+        if needed > quota {
+            anyhow::bail!(
+                "{} needs {} MB, more than the models quota of {} MB",
+                keep,
+                needed / 1_000_000,
+                quota / 1_000_000
+            );
+        }
+        loop {
+            let used: u64 = manifest
+                .models
+                .values()
+                .flat_map(|files| files.values())
+                .map(|cached| cached.size_bytes)
+                .sum();
+            if used + needed <= quota {
+                return Ok(());
+            }
+            let oldest = manifest
+                .models
+                .iter()
+                .filter(|(id, _)| id.as_str() != keep)
+                .min_by_key(|(_, files)| files.values().map(|f| f.last_used).max().unwrap_or(0))
+                .map(|(id, _)| id.clone());
+            let Some(oldest) = oldest else {
+                anyhow::bail!(
+                    "{} needs {} MB, more than what the models quota of {} MB leaves",
+                    keep,
+                    needed / 1_000_000,
+                    quota / 1_000_000
+                );
+            };
+            info!("Removing {} to stay within the models quota", oldest);
+            self.remove_from(manifest, &oldest)?;
+        }
+    }
+
+    fn download(
+        &self,
+        manifest: &mut Manifest,
+        repo: &str,
+        revision: &str,
+        file: &str,
+        path: &Path,
+    ) -> Result<CachedFile> {
+        let url = self.url(repo, revision, file);
+        let url_for_thread = url.clone();
+        let partial = path.with_file_name(format!(
+            "{}.partial",
+            path.file_name()
+                .expect("a file of a repo has a name")
+                .to_string_lossy()
+        ));
+        std::fs::create_dir_all(path.parent().expect("a file of a repo has a directory"))?;
+        let hub_file = self.hub_file(&url)?;
+        let id = model_id(repo, revision);
+        self.make_room(manifest, hub_file.size_bytes.unwrap_or(0), &id)?;
+
+        // reqwest's blocking client can't run on the async runtime, the loaders can be called
+        // from it
+        let partial_for_thread = partial.clone();
+        let resumed = std::thread::spawn(move || download_to(&url_for_thread, &partial_for_thread))
+            .join()
+            .map_err(|_| anyhow::anyhow!("the download of {} panicked", url))??;
+        if resumed > 0 {
+            info!("Resumed {} of {} after {} bytes", file, id, resumed);
+        }
+
+        let sha256 = sha256_file(&partial)?;
+        if let Some(expected) = &hub_file.sha256 {
+            if !sha256.eq_ignore_ascii_case(expected) {
+                let _ = std::fs::remove_file(&partial);
+                anyhow::bail!(
+                    "{} of {} doesn't have the checksum of the hub, expected {} got {}",
+                    file,
+                    id,
+                    expected,
+                    sha256
+                );
+            }
+        }
+        std::fs::rename(&partial, path)?;
+        info!("Downloaded {} of {} to {:?}", file, id, path);
+        let now = now();
+        Ok(CachedFile {
+            file: file.to_string(),
+            size_bytes: std::fs::metadata(path)?.len(),
+            sha256,
+            verified: hub_file.sha256.is_some(),
+            downloaded_at: now,
+            last_used: now,
+        })
+    }
+
+    /// The models of the cache, the ones used most recently first
+    pub fn list(&self) -> Vec<CachedModel> {
+        let _lock = self.lock.lock().unwrap();
+        let mut models: Vec<CachedModel> = self
+            .manifest()
+            .models
+            .into_iter()
+            .filter(|(_, files)| !files.is_empty())
+            .map(|(id, files)| {
+                let (repo, revision) = id.rsplit_once('@').unwrap_or((id.as_str(), "main"));
+                let files: Vec<CachedFile> = files.into_values().collect();
+                CachedModel {
+                    repo: repo.to_string(),
+                    revision: revision.to_string(),
+                    size_bytes: files.iter().map(|f| f.size_bytes).sum(),
+                    last_used: files.iter().map(|f| f.last_used).max().unwrap_or(0),
+                    files,
+                    id,
+                }
+            })
+            .collect();
+        models.sort_by_key(|model| Reverse(model.last_used));
+        models
+    }
+
+    pub fn used_bytes(&self) -> u64 {
+        self.list().iter().map(|model| model.size_bytes).sum()
+    }
+
+    fn remove_from(&self, manifest: &mut Manifest, id: &str) -> Result<u64> {
+        let files = manifest
+            .models
+            .remove(id)
+            .ok_or_else(|| anyhow::anyhow!("{} isn't in the models cache", id))?;
+        let (repo, revision) = id.rsplit_once('@').unwrap_or((id, "main"));
+        let dir = self.repo_dir(repo, revision);
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).with_context(|| format!("removing {:?}", dir))?;
+        }
+        self.save_manifest(manifest)?;
+        Ok(files.values().map(|f| f.size_bytes).sum())
+    }
+
+    /// Removes a model of the cache by its `repo@revision`, returns the bytes freed. It's
+    /// downloaded again the next time it's loaded
+    pub fn remove(&self, id: &str) -> Result<u64> {
+        let _lock = self.lock.lock().unwrap();
+        let mut manifest = self.manifest();
+        let freed = self.remove_from(&mut manifest, id)?;
+        info!("Removed {} from the models cache", id);
+        Ok(freed)
+    }
+
+    /// Downloads every file of a known model, so it's loaded without waiting the first time
+    pub fn prefetch(&self, model: &KnownModel) -> Result<Vec<PathBuf>> {
+        model
+            .files
+            .iter()
+            .map(|f| self.file(f.repo, f.revision, f.file))
+            .collect()
+    }
+}
+
+/// Downloads to `partial`, after what it already has when the server can send the rest.
+/// Returns the bytes resumed after
+fn download_to(url: &str, partial: &Path) -> Result<u64> {
+    let client = Client::builder().timeout(None).build()?;
+    let offset = std::fs::metadata(partial).map(|m| m.len()).unwrap_or(0);
+    let mut request = client.get(url);
+    if offset > 0 {
+        request = request.header(RANGE, format!("bytes={}-", offset));
+    }
+    let response = request.send()?;
+    if offset > 0 && response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // the partial file is already complete
+        return Ok(offset);
+    }
+    let mut response = response.error_for_status()?;
+    let resumed = if response.status() == StatusCode::PARTIAL_CONTENT {
+        offset
+    } else {
+        0
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed > 0)
+        .truncate(resumed == 0)
+        .open(partial)?;
+    response.copy_to(&mut file)?;
+    Ok(resumed)
+}
+
+static MODEL_MANAGER: OnceLock<ModelManager> = OnceLock::new();
+
+/// Sets where the models are cached and their quota, before the first one is loaded
+pub fn set_model_manager(manager: ModelManager) {
+    if MODEL_MANAGER.set(manager).is_err() {
+        warn!("The models cache was already set up, keeping it");
+    }
+}
+
+/// The manager the local models are loaded with, caching in ~/.screenpipe/models unless set
+pub fn model_manager() -> &'static ModelManager {
+    MODEL_MANAGER.get_or_init(|| {
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir);
+        ModelManager::new(home.join(".screenpipe").join(MODELS_DIR), None)
+    })
+}
+
+/// Path of a file of a Hugging Face repo in the models cache, downloaded when it isn't there
+pub fn model_file_path(repo: &str, revision: &str, file: &str) -> Result<PathBuf> {
+    model_manager().file(repo, revision, file)
+}
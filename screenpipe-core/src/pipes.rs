@@ -1,3 +1,10 @@
+/// Sent with the requests pipes make so the server knows which pipe read what
+pub const PIPE_HEADER: &str = "x-screenpipe-pipe";
+/// The pipe's own token, sent with its name. Without it the server doesn't trust the name
+pub const PIPE_TOKEN_HEADER: &str = "x-screenpipe-pipe-token";
+/// Where the server gives the pipe runner the token of the pipe it runs
+pub const PIPE_TOKEN_ENV: &str = "SCREENPIPE_PIPE_TOKEN";
+
 #[cfg(feature = "pipes")]
 mod pipes {
     use deno_ast::MediaType;
@@ -9,9 +16,36 @@ mod pipes {
     use deno_core::ModuleLoadResponse;
     use deno_core::ModuleSourceCode;
     use log::error;
+    use std::cell::RefCell;
     use std::env;
     use std::rc::Rc;
 
+    use super::{PIPE_HEADER, PIPE_TOKEN_ENV, PIPE_TOKEN_HEADER};
+
+    thread_local! {
+        /// Pipe run by the runtime of this thread, the runtime and its ops stay on it
+        static PIPE_NAME: RefCell<String> = const { RefCell::new(String::new()) };
+    }
+
+    /// Only what a header value can hold
+    fn pipe_name() -> String {
+        PIPE_NAME.with(|name| {
+            name.borrow()
+                .chars()
+                .filter(|c| c.is_ascii() && !c.is_ascii_control())
+                .collect()
+        })
+    }
+
+    /// The name and token of the pipe on its requests
+    fn pipe_headers(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        let request = request.header(PIPE_HEADER, pipe_name());
+        match env::var(PIPE_TOKEN_ENV) {
+            Ok(token) => request.header(PIPE_TOKEN_HEADER, token),
+            Err(_) => request,
+        }
+    }
+
     #[op2(async)]
     #[string]
     async fn op_read_file(#[string] path: String) -> Result<String, AnyError> {
@@ -36,7 +70,9 @@ mod pipes {
     #[op2(async)]
     #[string]
     async fn op_fetch_get(#[string] url: String) -> Result<String, AnyError> {
-        let response = reqwest::get(&url).await?;
+        let response = pipe_headers(reqwest::Client::new().get(&url))
+            .send()
+            .await?;
         let status = response.status();
         let text = response.text().await?;
 
@@ -58,7 +94,10 @@ mod pipes {
         #[string] body: String,
     ) -> Result<String, AnyError> {
         let client = reqwest::Client::new();
-        let response = client.post(&url).body(body).send().await?;
+        let response = pipe_headers(client.post(&url))
+            .body(body)
+            .send()
+            .await?;
         let status = response.status();
         let text = response.text().await?;
 
@@ -173,6 +212,7 @@ mod pipes {
 
     pub async fn run_js(file_path: &str) -> Result<(), AnyError> {
         let main_module = deno_core::resolve_path(file_path, env::current_dir()?.as_path())?;
+        PIPE_NAME.with(|name| *name.borrow_mut() = file_path.to_string());
         let mut js_runtime = deno_core::JsRuntime::new(deno_core::RuntimeOptions {
             module_loader: Some(Rc::new(TsModuleLoader)),
             startup_snapshot: Some(RUNTIME_SNAPSHOT),
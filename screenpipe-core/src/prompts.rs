@@ -0,0 +1,190 @@
+use crate::ChatMessage;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// A named prompt used by a built-in task. `{{variable}}` placeholders are replaced on render.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PromptTemplate {
+    /// Bumped whenever the wording changes so outputs can be traced back to the prompt that produced them
+    pub version: u32,
+    pub system: String,
+    pub user: String,
+}
+
+/// Variables each built-in task provides. Templates can't use anything else.
+const TASK_VARIABLES: &[(&str, &[&str])] = &[
+    ("summarize", &["start_time", "end_time", "timeline"]),
+    ("extract", &["schema", "text"]),
+    ("digest", &["start_time", "end_time", "content"]),
+    ("ask", &["question", "context"]),
+    ("describe", &["instruction"]),
+];
+
+const SUMMARIZE_SYSTEM: &str = r#"You summarize meetings from a timeline of audio transcriptions and the text visible on screen.
+Answer ONLY with a JSON object of this shape:
+{"summary": "short paragraph", "decisions": ["decision"], "action_items": [{"description": "what to do", "owner": "who, or null if unknown"}]}"#;
+
+const EXTRACT_SYSTEM: &str = "You extract structured data from text visible on a screen. \
+Answer ONLY with a JSON array of objects matching this JSON schema, \
+or an empty array if the text contains nothing relevant:\n{{schema}}";
+
+const DIGEST_SYSTEM: &str = "You write a concise digest of what the user did, based on what was on their screen and what was said. \
+Group by topic, mention apps and people when relevant, and keep it short.";
+
+const ASK_SYSTEM: &str = "You answer questions about the user's past activity from the screen text and audio transcriptions provided. \
+Use the tools you're given to search further or read a frame, and if nothing you find contains the answer, say so.";
+
+const DESCRIBE_SYSTEM: &str = "You look at screenshots of the user's screen. \
+Describe charts, diagrams and the state of the user interface precisely, text is already available through OCR.";
+
+fn builtin_templates() -> HashMap<String, PromptTemplate> {
+    let templates = [
+        (
+            "summarize",
+            SUMMARIZE_SYSTEM,
+            "Meeting from {{start_time}} to {{end_time}}:\n{{timeline}}",
+        ),
+        ("extract", EXTRACT_SYSTEM, "{{text}}"),
+        (
+            "digest",
+            DIGEST_SYSTEM,
+            "Activity from {{start_time}} to {{end_time}}:\n{{content}}",
+        ),
+        (
+            "ask",
+            ASK_SYSTEM,
+            "Context:\n{{context}}\n\nQuestion: {{question}}",
+        ),
+        ("describe", DESCRIBE_SYSTEM, "{{instruction}}"),
+    ];
+
+    templates
+        .into_iter()
+        .map(|(name, system, user)| {
+            (
+                name.to_string(),
+                PromptTemplate {
+                    version: 1,
+                    system: system.to_string(),
+                    user: user.to_string(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// Returns the `{{variable}}` names used in a template
+pub fn template_variables(template: &str) -> HashSet<String> {
+    let mut variables = HashSet::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        variables.insert(rest[start + 2..start + end].trim().to_string());
+        rest = &rest[start + end + 2..];
+    }
+    variables
+}
+
+/// Replaces the placeholders in a single pass so values containing `{{...}}` are left untouched
+fn render(template: &str, variables: &[(&str, &str)]) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        let name = rest[start + 2..start + end].trim();
+        rendered.push_str(&rest[..start]);
+        match variables.iter().find(|(n, _)| *n == name) {
+            Some((_, value)) => rendered.push_str(value),
+            None => rendered.push_str(&rest[start..start + end + 2]),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+impl PromptTemplate {
+    /// Ensures the template only uses the variables the task provides, and uses all of them
+    pub fn validate(&self, task: &str) -> Result<()> {
+        let expected: HashSet<String> = TASK_VARIABLES
+            .iter()
+            .find(|(name, _)| *name == task)
+            .map(|(_, variables)| variables.iter().map(|v| v.to_string()).collect())
+            .ok_or_else(|| anyhow::anyhow!("unknown prompt task: {}", task))?;
+
+        let used: HashSet<String> = template_variables(&self.system)
+            .union(&template_variables(&self.user))
+            .cloned()
+            .collect();
+
+        if let Some(unknown) = used.difference(&expected).next() {
+            anyhow::bail!("prompt {} uses unknown variable {{{{{}}}}}", task, unknown);
+        }
+        if let Some(missing) = expected.difference(&used).next() {
+            anyhow::bail!("prompt {} is missing variable {{{{{}}}}}", task, missing);
+        }
+        Ok(())
+    }
+
+    pub fn render(&self, variables: &[(&str, &str)]) -> Vec<ChatMessage> {
+        vec![
+            ChatMessage::system(render(&self.system, variables)),
+            ChatMessage::user(render(&self.user, variables)),
+        ]
+    }
+}
+
+/// Templates for the built-in tasks, with the user overrides applied
+#[derive(Debug, Clone)]
+pub struct PromptLibrary {
+    templates: HashMap<String, PromptTemplate>,
+}
+
+impl Default for PromptLibrary {
+    fn default() -> Self {
+        PromptLibrary {
+            templates: builtin_templates(),
+        }
+    }
+}
+
+impl PromptLibrary {
+    /// Applies overrides from a json file like
+    /// `{"summarize": {"version": 2, "system": "...", "user": "... {{timeline}}"}}`.
+    /// A missing file means no override.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut library = PromptLibrary::default();
+        if !path.exists() {
+            return Ok(library);
+        }
+
+        let overrides: HashMap<String, PromptTemplate> =
+            serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        for (task, template) in overrides {
+            template.validate(&task)?;
+            let builtin_version = library.templates[&task].version;
+            if template.version <= builtin_version {
+                log::warn!(
+                    "prompt override {} has version {} which is not newer than the built-in version {}",
+                    task,
+                    template.version,
+                    builtin_version
+                );
+            }
+            library.templates.insert(task, template);
+        }
+        Ok(library)
+    }
+
+    pub fn get(&self, task: &str) -> &PromptTemplate {
+        self.templates
+            .get(task)
+            .unwrap_or_else(|| panic!("no prompt template for task {}", task))
+    }
+}
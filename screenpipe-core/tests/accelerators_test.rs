@@ -0,0 +1,95 @@
+#![cfg(feature = "local-models")]
+
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::{
+        Accelerator, AcceleratorMemory, AcceleratorScheduler, LocalModel, ModelDeviceOverride,
+    };
+
+    #[test]
+    fn test_overrides_and_memory_are_parsed() {
+        assert_eq!(
+            "ocr=cuda:1".parse::<ModelDeviceOverride>().unwrap(),
+            ModelDeviceOverride {
+                model: LocalModel::Ocr,
+                accelerator: Accelerator::Cuda(1),
+            }
+        );
+        assert_eq!(
+            "Embeddings=CPU".parse::<ModelDeviceOverride>().unwrap(),
+            ModelDeviceOverride {
+                model: LocalModel::Embeddings,
+                accelerator: Accelerator::Cpu,
+            }
+        );
+        assert_eq!(
+            "metal=4096".parse::<AcceleratorMemory>().unwrap(),
+            AcceleratorMemory {
+                accelerator: Accelerator::Metal(0),
+                memory_mb: 4096,
+            }
+        );
+        assert!("ocr".parse::<ModelDeviceOverride>().is_err());
+        assert!("whisper=cuda".parse::<ModelDeviceOverride>().is_err());
+        assert!("cuda:x=1024".parse::<AcceleratorMemory>().is_err());
+        assert!("tpu=1024".parse::<AcceleratorMemory>().is_err());
+        assert_eq!(Accelerator::Cuda(1).to_string(), "cuda:1");
+    }
+
+    #[test]
+    fn test_models_are_spread_over_the_gpus_that_fit_them() {
+        let mut scheduler = AcceleratorScheduler::new(
+            &[Accelerator::Cuda(0), Accelerator::Cuda(1)],
+            &[
+                "cuda:0=2000".parse().unwrap(),
+                "cuda:1=8000".parse().unwrap(),
+            ],
+            &[],
+        );
+        // the most memory left first, then the least loaded that fits
+        assert_eq!(
+            scheduler.place(LocalModel::Transcription, 3000),
+            Accelerator::Cuda(1)
+        );
+        assert_eq!(scheduler.place(LocalModel::Ocr, 1400), Accelerator::Cuda(0));
+        assert_eq!(
+            scheduler.place(LocalModel::Embeddings, 550),
+            Accelerator::Cuda(1)
+        );
+        assert_eq!(scheduler.loads()[1].used_mb, 3550);
+        // nothing has room for it any more
+        assert_eq!(
+            scheduler.place(LocalModel::Transcription, 5000),
+            Accelerator::Cpu
+        );
+
+        scheduler.release(LocalModel::Ocr, Accelerator::Cuda(0), 1400);
+        assert!(scheduler.loads()[0].models.is_empty());
+        assert_eq!(scheduler.place(LocalModel::Ocr, 1400), Accelerator::Cuda(0));
+    }
+
+    #[test]
+    fn test_overrides_win_and_no_gpu_means_cpu() {
+        let mut scheduler = AcceleratorScheduler::new(
+            &[Accelerator::Cuda(0)],
+            &[],
+            &[
+                "embeddings=cpu".parse().unwrap(),
+                "ocr=cuda:3".parse().unwrap(),
+            ],
+        );
+        assert_eq!(
+            scheduler.place(LocalModel::Embeddings, 550),
+            Accelerator::Cpu
+        );
+        assert_eq!(scheduler.place(LocalModel::Ocr, 1400), Accelerator::Cuda(3));
+        assert_eq!(
+            scheduler.place(LocalModel::Transcription, 150),
+            Accelerator::Cuda(0)
+        );
+
+        let mut cpu_only = AcceleratorScheduler::new(&[], &[], &[]);
+        assert_eq!(cpu_only.place(LocalModel::Ocr, 1400), Accelerator::Cpu);
+        assert_eq!(cpu_only.loads().len(), 1);
+    }
+}
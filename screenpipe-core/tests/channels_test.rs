@@ -0,0 +1,58 @@
+use screenpipe_core::{channel_reports, channel_stats, OverflowPolicy};
+use std::collections::VecDeque;
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+#[test]
+fn test_keep_latest_drops_the_oldest() {
+    let stats = channel_stats("test.keep_latest", OverflowPolicy::KeepLatest, 3);
+    let mut queue = VecDeque::new();
+    for frame in 0..5 {
+        stats.push_latest(&mut queue, frame);
+    }
+    assert_eq!(queue, [2, 3, 4]);
+    assert_eq!(stats.dropped(), 2);
+
+    let report = channel_reports()
+        .into_iter()
+        .find(|report| report.name == "test.keep_latest")
+        .unwrap();
+    assert_eq!(report.capacity, 3);
+    assert_eq!(report.dropped, 2);
+    assert_eq!(
+        serde_json::to_value(&report).unwrap()["policy"],
+        "keep_latest"
+    );
+}
+
+#[tokio::test]
+async fn test_drop_newest_counts_what_did_not_fit() {
+    let stats = channel_stats("test.drop_newest", OverflowPolicy::DropNewest, 2);
+    let (tx, mut rx) = mpsc::channel(2);
+    for event in 0..4 {
+        assert!(stats.try_send(&tx, event));
+    }
+    assert_eq!(stats.dropped(), 2);
+    assert_eq!(rx.recv().await, Some(0));
+    assert_eq!(rx.recv().await, Some(1));
+
+    drop(rx);
+    assert!(!stats.try_send(&tx, 4));
+}
+
+#[tokio::test]
+async fn test_wait_never_drops() {
+    let stats = channel_stats("test.wait", OverflowPolicy::Wait, 1);
+    let (tx, mut rx) = mpsc::channel(1);
+    stats.send(&tx, "first").await.unwrap();
+
+    // the consumer is behind, the second transcript waits for it
+    let consumer = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        vec![rx.recv().await, rx.recv().await]
+    });
+    stats.send(&tx, "second").await.unwrap();
+    assert_eq!(consumer.await.unwrap(), [Some("first"), Some("second")]);
+    assert_eq!(stats.dropped(), 0);
+    assert_eq!(stats.report().waited, 1);
+}
@@ -0,0 +1,103 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::{
+        parse_ffmpeg_stderr_line, AudioCodec, Container, FfmpegCommand, FfmpegEvent, FfmpegInput,
+        FfmpegOutput, VideoCodec,
+    };
+
+    #[test]
+    fn test_builder_orders_input_and_output_options() {
+        let command = FfmpegCommand::new()
+            .overwrite()
+            .input(
+                FfmpegInput::pipe()
+                    .format(Container::Image2Pipe)
+                    .video_codec(VideoCodec::Png)
+                    .frame_rate(0.5),
+            )
+            .output(
+                FfmpegOutput::file("out.mp4")
+                    .video_codec(VideoCodec::Libx264 {
+                        preset: "ultrafast".to_string(),
+                        crf: 23,
+                    })
+                    .pixel_format("yuv420p"),
+            );
+
+        assert_eq!(
+            command.args(),
+            vec![
+                "-y",
+                "-f",
+                "image2pipe",
+                "-vcodec",
+                "png",
+                "-r",
+                "0.5",
+                "-i",
+                "pipe:0",
+                "-vcodec",
+                "libx264",
+                "-preset",
+                "ultrafast",
+                "-crf",
+                "23",
+                "-pix_fmt",
+                "yuv420p",
+                "out.mp4",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_builder_audio_output() {
+        let command = FfmpegCommand::new()
+            .input(
+                FfmpegInput::pipe()
+                    .format(Container::F32le)
+                    .sample_rate(48000)
+                    .channels(2),
+            )
+            .output(
+                FfmpegOutput::file("out.mp4")
+                    .audio_codec(AudioCodec::Aac { bitrate_kbps: 128 })
+                    .format(Container::Mp4),
+            );
+
+        assert_eq!(
+            command.args().join(" "),
+            "-f f32le -ar 48000 -ac 2 -i pipe:0 -c:a aac -b:a 128k -f mp4 out.mp4"
+        );
+    }
+
+    #[test]
+    fn test_parse_progress_line() {
+        let line = "frame=  120 fps= 30 q=28.0 size=     256kB time=00:00:04.00 bitrate= 524.3kbits/s speed=1.02x";
+
+        assert_eq!(
+            parse_ffmpeg_stderr_line(line),
+            Some(FfmpegEvent::Progress {
+                frame: Some(120),
+                fps: Some(30.0),
+                time: Some("00:00:04.00".to_string()),
+                speed: Some(1.02),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors_warnings_and_noise() {
+        assert!(matches!(
+            parse_ffmpeg_stderr_line("pipe:0: Invalid data found when processing input"),
+            Some(FfmpegEvent::Error { .. })
+        ));
+        assert!(matches!(
+            parse_ffmpeg_stderr_line("[swscaler @ 0x7f] deprecated pixel format used"),
+            Some(FfmpegEvent::Warning { .. })
+        ));
+        assert_eq!(
+            parse_ffmpeg_stderr_line("  Stream #0:0: Video: png, rgba, 1920x1080"),
+            None
+        );
+    }
+}
@@ -0,0 +1,22 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::ffmpeg::{pinned_ffmpeg_path, verify_sha256};
+    use screenpipe_core::PINNED_FFMPEG_VERSION;
+    use std::path::Path;
+
+    #[test]
+    fn test_verify_sha256() {
+        let expected = "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+        assert!(verify_sha256(b"hello", expected).is_ok());
+        assert!(verify_sha256(b"hello", &expected.to_uppercase()).is_ok());
+        assert!(verify_sha256(b"tampered", expected).is_err());
+    }
+
+    #[test]
+    fn test_pinned_ffmpeg_path_is_versioned() {
+        let path = pinned_ffmpeg_path(Path::new("/data"));
+
+        assert!(path.starts_with(Path::new("/data/ffmpeg").join(PINNED_FFMPEG_VERSION)));
+    }
+}
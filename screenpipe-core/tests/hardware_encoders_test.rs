@@ -0,0 +1,53 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::{
+        best_encoder, Container, EncoderQuality, FfmpegCommand, FfmpegInput, FfmpegOutput,
+        VideoCodec, VideoEncoder,
+    };
+
+    fn encode_args(encoder: VideoEncoder, quality: EncoderQuality) -> String {
+        let command = FfmpegCommand::new().input(
+            FfmpegInput::pipe()
+                .format(Container::Image2Pipe)
+                .video_codec(VideoCodec::Png),
+        );
+        encoder
+            .encode_to(command, FfmpegOutput::file("out.mp4"), quality)
+            .args()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_best_encoder_prefers_hardware_in_order() {
+        assert_eq!(best_encoder(&[]), VideoEncoder::Software);
+        assert_eq!(
+            best_encoder(&[VideoEncoder::Vaapi, VideoEncoder::Nvenc]),
+            VideoEncoder::Nvenc
+        );
+        assert_eq!(best_encoder(&[VideoEncoder::Qsv]), VideoEncoder::Qsv);
+    }
+
+    #[test]
+    fn test_nvenc_quality_presets() {
+        assert_eq!(
+            encode_args(VideoEncoder::Nvenc, EncoderQuality::High),
+            "-f image2pipe -vcodec png -i pipe:0 -vcodec h264_nvenc -preset p2 -rc vbr -cq 20 -pix_fmt yuv420p out.mp4"
+        );
+        assert!(encode_args(VideoEncoder::Nvenc, EncoderQuality::Low).contains("-cq 32"));
+    }
+
+    #[test]
+    fn test_vaapi_uploads_frames_to_device() {
+        assert_eq!(
+            encode_args(VideoEncoder::Vaapi, EncoderQuality::Medium),
+            "-vaapi_device /dev/dri/renderD128 -f image2pipe -vcodec png -i pipe:0 -vf format=nv12,hwupload -vcodec h264_vaapi -qp 26 out.mp4"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_software_medium_matches_previous_defaults() {
+        assert!(encode_args(VideoEncoder::Software, EncoderQuality::Medium)
+            .ends_with("-vcodec libx264 -preset ultrafast -crf 23 -pix_fmt yuv420p out.mp4"));
+    }
+}
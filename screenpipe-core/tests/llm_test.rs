@@ -1,3 +1,5 @@
+#![cfg(feature = "llm")]
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
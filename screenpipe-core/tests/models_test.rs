@@ -0,0 +1,222 @@
+#[cfg(test)]
+mod tests {
+    use screenpipe_core::ModelManager;
+    use sha2::{Digest, Sha256};
+    use std::collections::HashMap;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex};
+
+    struct HubFile {
+        body: Vec<u8>,
+        /// The sha256 the hub says the file has, none for the files kept in git
+        sha256: Option<String>,
+    }
+
+    /// The GETs the hub answered, with the range asked for
+    type Requests = Arc<Mutex<Vec<(String, Option<String>)>>>;
+
+    fn sha256(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    fn lfs(body: &[u8]) -> HubFile {
+        HubFile {
+            body: body.to_vec(),
+            sha256: Some(sha256(body)),
+        }
+    }
+
+    /// A hub answering one request per connection, like /org/model/resolve/main/model.safetensors
+    fn serve_hub(files: HashMap<&'static str, HubFile>) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests: Requests = Arc::default();
+        let seen = requests.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut range = None;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("range") {
+                            range = Some(value.trim().to_string());
+                        }
+                    }
+                }
+                let mut parts = request_line.split_whitespace();
+                let (method, path) = (parts.next().unwrap(), parts.next().unwrap());
+                let Some(file) = files.get(path) else {
+                    let _ = stream.write_all(
+                        b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    );
+                    continue;
+                };
+                let mut head = String::new();
+                if let Some(sha256) = &file.sha256 {
+                    head.push_str(&format!(
+                        "x-linked-etag: \"{}\"\r\nx-linked-size: {}\r\n",
+                        sha256,
+                        file.body.len()
+                    ));
+                }
+                if method == "HEAD" {
+                    let _ = stream.write_all(
+                        format!(
+                            "HTTP/1.1 200 OK\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n",
+                            head,
+                            file.body.len()
+                        )
+                        .as_bytes(),
+                    );
+                    continue;
+                }
+                seen.lock().unwrap().push((path.to_string(), range.clone()));
+                let offset: usize = range
+                    .as_deref()
+                    .and_then(|range| range.strip_prefix("bytes="))
+                    .and_then(|range| range.trim_end_matches('-').parse().ok())
+                    .unwrap_or(0);
+                let status = if offset > 0 {
+                    "206 Partial Content"
+                } else {
+                    "200 OK"
+                };
+                let body = &file.body[offset..];
+                let _ = stream.write_all(
+                    format!(
+                        "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                        status,
+                        body.len()
+                    )
+                    .as_bytes(),
+                );
+                let _ = stream.write_all(body);
+            }
+        });
+        (url, requests)
+    }
+
+    fn models_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("screenpipe-models-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_models_are_verified_and_downloaded_once() {
+        let weights = vec![7u8; 4096];
+        let (url, requests) = serve_hub(HashMap::from([
+            ("/org/model/resolve/main/model.safetensors", lfs(&weights)),
+            (
+                "/org/model/resolve/main/config.json",
+                HubFile {
+                    body: b"{}".to_vec(),
+                    sha256: None,
+                },
+            ),
+        ]));
+        let manager = ModelManager::new(models_dir("cached"), None).with_hub_url(url);
+
+        let path = manager
+            .file("org/model", "main", "model.safetensors")
+            .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), weights);
+        let again = manager
+            .file("org/model", "main", "model.safetensors")
+            .unwrap();
+        assert_eq!(again, path);
+        manager.file("org/model", "main", "config.json").unwrap();
+        assert_eq!(requests.lock().unwrap().len(), 2);
+
+        let models = manager.list();
+        assert_eq!(models.len(), 1);
+        assert_eq!(models[0].id, "org/model@main");
+        assert_eq!(models[0].size_bytes, 4098);
+        let files: HashMap<&str, bool> = models[0]
+            .files
+            .iter()
+            .map(|f| (f.file.as_str(), f.verified))
+            .collect();
+        assert_eq!(
+            files,
+            HashMap::from([("model.safetensors", true), ("config.json", false)])
+        );
+        assert_eq!(models[0].files[1].sha256, sha256(&weights));
+
+        assert_eq!(manager.remove("org/model@main").unwrap(), 4098);
+        assert!(manager.list().is_empty());
+        assert!(!path.exists());
+        assert!(manager.remove("org/model@main").is_err());
+    }
+
+    #[test]
+    fn test_interrupted_downloads_are_resumed_and_checked() {
+        let weights: Vec<u8> = (0..10_000u32).map(|i| (i % 251) as u8).collect();
+        let mut tampered = lfs(&weights);
+        tampered.sha256 = Some(sha256(b"something else"));
+        let (url, requests) = serve_hub(HashMap::from([
+            ("/org/model/resolve/main/model.safetensors", lfs(&weights)),
+            ("/org/tampered/resolve/main/model.safetensors", tampered),
+        ]));
+        let dir = models_dir("resumed");
+        let manager = ModelManager::new(dir.clone(), None).with_hub_url(url);
+
+        let partial = dir.join("org--model/main/model.safetensors.partial");
+        std::fs::create_dir_all(partial.parent().unwrap()).unwrap();
+        std::fs::write(&partial, &weights[..6000]).unwrap();
+        let path = manager
+            .file("org/model", "main", "model.safetensors")
+            .unwrap();
+        assert_eq!(std::fs::read(path).unwrap(), weights);
+        assert!(!partial.exists());
+        assert_eq!(
+            requests.lock().unwrap()[0].1.as_deref(),
+            Some("bytes=6000-")
+        );
+
+        let error = manager
+            .file("org/tampered", "main", "model.safetensors")
+            .unwrap_err();
+        assert!(error.to_string().contains("checksum"), "{}", error);
+        assert!(!dir.join("org--tampered/main/model.safetensors").exists());
+        assert!(!dir
+            .join("org--tampered/main/model.safetensors.partial")
+            .exists());
+    }
+
+    #[test]
+    fn test_least_recently_used_models_make_room_for_new_ones() {
+        let (url, _) = serve_hub(HashMap::from([
+            ("/org/first/resolve/main/model.safetensors", lfs(&[1; 600])),
+            ("/org/second/resolve/main/model.safetensors", lfs(&[2; 600])),
+            ("/org/huge/resolve/main/model.safetensors", lfs(&[3; 2000])),
+        ]));
+        let manager = ModelManager::new(models_dir("quota"), Some(1000)).with_hub_url(url);
+
+        manager
+            .file("org/first", "main", "model.safetensors")
+            .unwrap();
+        manager
+            .file("org/second", "main", "model.safetensors")
+            .unwrap();
+        let ids: Vec<String> = manager.list().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec!["org/second@main"]);
+
+        let error = manager
+            .file("org/huge", "main", "model.safetensors")
+            .unwrap_err();
+        assert!(error.to_string().contains("quota"), "{}", error);
+        assert_eq!(manager.used_bytes(), 600);
+    }
+}
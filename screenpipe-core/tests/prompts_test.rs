@@ -0,0 +1,56 @@
+#![cfg(feature = "llm")]
+
+use screenpipe_core::{PromptLibrary, PromptTemplate};
+
+#[test]
+fn test_builtin_prompts_are_valid() {
+    let library = PromptLibrary::default();
+    for task in ["summarize", "extract", "digest", "ask", "describe"] {
+        library.get(task).validate(task).unwrap();
+    }
+}
+
+#[test]
+fn test_render_replaces_variables_once() {
+    let template = PromptTemplate {
+        version: 1,
+        system: "Schema: {{schema}}".to_string(),
+        user: "{{text}}".to_string(),
+    };
+    let messages = template.render(&[("schema", "{}"), ("text", "literal {{schema}}")]);
+    assert_eq!(messages[0].content, "Schema: {}");
+    assert_eq!(messages[1].content, "literal {{schema}}");
+}
+
+#[test]
+fn test_override_validation() {
+    let missing = PromptTemplate {
+        version: 2,
+        system: "Summarize".to_string(),
+        user: "{{timeline}}".to_string(),
+    };
+    assert!(missing.validate("summarize").is_err());
+
+    let unknown = PromptTemplate {
+        version: 2,
+        system: "Summarize in {{language}}".to_string(),
+        user: "{{start_time}} {{end_time}} {{timeline}}".to_string(),
+    };
+    assert!(unknown.validate("summarize").is_err());
+}
+
+#[test]
+fn test_load_overrides() {
+    let path = std::env::temp_dir().join("screenpipe_prompts_test.json");
+    std::fs::write(
+        &path,
+        r#"{"ask": {"version": 2, "system": "Be brief", "user": "{{question}} {{context}}"}}"#,
+    )
+    .unwrap();
+
+    let library = PromptLibrary::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(library.get("ask").version, 2);
+    assert_eq!(library.get("ask").system, "Be brief");
+    assert_eq!(library.get("summarize").version, 1);
+}
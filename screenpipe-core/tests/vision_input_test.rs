@@ -0,0 +1,40 @@
+#![cfg(feature = "llm")]
+
+#[cfg(test)]
+mod tests {
+    use base64::{engine::general_purpose, Engine as _};
+    use image::{DynamicImage, GenericImageView};
+    use screenpipe_core::{encode_image_for_llm, ImageRegion};
+
+    fn decode(data_url: &str) -> DynamicImage {
+        let encoded = data_url
+            .strip_prefix("data:image/jpeg;base64,")
+            .expect("jpeg data url");
+        let bytes = general_purpose::STANDARD.decode(encoded).unwrap();
+        image::load_from_memory(&bytes).unwrap()
+    }
+
+    #[test]
+    fn test_encode_image_downscales_to_max_dimension() {
+        let image = DynamicImage::new_rgb8(2048, 1024);
+
+        let encoded = encode_image_for_llm(&image, None, 512).unwrap();
+
+        assert_eq!(decode(&encoded).dimensions(), (512, 256));
+    }
+
+    #[test]
+    fn test_encode_image_crops_region_clamped_to_image() {
+        let image = DynamicImage::new_rgb8(800, 600);
+        let region = ImageRegion {
+            x: 600,
+            y: 500,
+            width: 400,
+            height: 400,
+        };
+
+        let encoded = encode_image_for_llm(&image, Some(region), 1024).unwrap();
+
+        assert_eq!(decode(&encoded).dimensions(), (200, 100));
+    }
+}
@@ -0,0 +1,16 @@
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    // the header is committed, a build that can't regenerate it keeps the last one
+    match cbindgen::generate(&crate_dir) {
+        Ok(bindings) => {
+            bindings.write_to_file(crate_dir.join("include/screenpipe.h"));
+        }
+        Err(e) => println!("cargo:warning=include/screenpipe.h not regenerated: {}", e),
+    }
+}
@@ -0,0 +1,116 @@
+#ifndef SCREENPIPE_H
+#define SCREENPIPE_H
+
+/* Generated from screenpipe-ffi/src/lib.rs by cbindgen, don't edit it by hand */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Bumped when a function of this header changes, not when one is added
+#define SCREENPIPE_ABI_VERSION 1
+
+// An engine opened on a data dir, shared between threads
+typedef struct ScreenpipeEngine ScreenpipeEngine;
+
+// Called with the name of the event, e.g. "watch.alert", its data as json and the
+// `user_data` given to `screenpipe_subscribe`. Both strings are only valid during the call.
+typedef void (*ScreenpipeEventCallback)(const char *name, const char *data_json, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// The `SCREENPIPE_ABI_VERSION` of the library loaded, to compare with the header's
+uint32_t screenpipe_abi_version(void);
+
+// Why the last call of this thread failed, NULL if none did. Valid until the next failure
+// on this thread, not to be freed.
+const char *screenpipe_last_error(void);
+
+// Opens the database of `data_dir` without recording anything yet, e.g.
+// `{"data_dir": "/Users/me/.screenpipe", "fps": 0.5, "disable_audio": true}`.
+// The other fields are `monitor_id`, `audio_chunk_duration`, `ocr_engine`,
+// `audio_transcription_engine`, `video_quality` and `save_text_files`, as the cli takes them.
+//
+// # Safety
+//
+// `config_json` is a nul terminated utf-8 string. The engine returned is freed with
+// `screenpipe_engine_free`.
+struct ScreenpipeEngine *screenpipe_engine_new(const char *config_json);
+
+// Stops the capture and the subscriptions of the engine, and closes it
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't used after, nor freed twice. Not to be
+// called from a `ScreenpipeEventCallback`.
+void screenpipe_engine_free(struct ScreenpipeEngine *engine);
+
+// Starts recording the screen, and the audio unless `disable_audio`. 0 once started, -1 if it
+// couldn't be or already is.
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+int32_t screenpipe_capture_start(const struct ScreenpipeEngine *engine);
+
+// Stops the recording once the frames and audio in flight are written. 0 once stopped, -1 if
+// it wasn't running.
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+int32_t screenpipe_capture_stop(const struct ScreenpipeEngine *engine);
+
+// 1 while the engine records, 0 otherwise and -1 for a NULL engine
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+int32_t screenpipe_capture_running(const struct ScreenpipeEngine *engine);
+
+// Searches what was recorded, `query_json` has the fields of `/search` (`q`, `content_type`,
+// `limit`, `offset`, `start_time`, `end_time`, `app_name` and `window_name`). Returns a json
+// array of `{"OCR": {...}}`, `{"Audio": {...}}` and `{"FTS": {...}}`, freed with
+// `screenpipe_string_free`.
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't freed yet, `query_json` is a nul
+// terminated utf-8 string.
+char *screenpipe_search(const struct ScreenpipeEngine *engine, const char *query_json);
+
+// Calls `callback` for each event of the engine until `screenpipe_unsubscribe`, from a thread
+// of the engine. Returns the id of the subscription, 0 on failure.
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't freed yet. `callback` and `user_data`
+// can be used from another thread until the subscription ends.
+uint64_t screenpipe_subscribe(const struct ScreenpipeEngine *engine,
+                              ScreenpipeEventCallback callback,
+                              void *user_data);
+
+// Ends a subscription, its callback isn't called anymore once this returns. 0 once ended, -1
+// for an unknown id.
+//
+// # Safety
+//
+// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+int32_t screenpipe_unsubscribe(const struct ScreenpipeEngine *engine, uint64_t subscription);
+
+// Frees a string returned by screenpipe
+//
+// # Safety
+//
+// `s` was returned by a screenpipe function, and isn't freed twice.
+void screenpipe_string_free(char *s);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* SCREENPIPE_H */
@@ -0,0 +1,346 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{Parser, ValueEnum};
+use crossbeam::queue::SegQueue;
+use image::DynamicImage;
+use log::{info, warn};
+use screenpipe_audio::{
+    default_input_device, default_output_device, AudioTranscriptionEngine, DeviceControl,
+};
+use screenpipe_core::{
+    best_encoder, probe_hardware_encoders, subscribe_events, EncoderQuality, Event,
+};
+use screenpipe_server::cli::{Cli, CliAudioTranscriptionEngine, CliOcrEngine};
+use screenpipe_server::{
+    extract_frame, start_continuous_recording, CapturePause, ContentType, DatabaseManager, LiveOcr,
+    SearchResult, StorageError, Watchlist,
+};
+use screenpipe_vision::monitor::list_monitors;
+use screenpipe_vision::OcrEngine;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::{Handle, Runtime};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// Same as the cli, the OCR and transcriptions in flight are given that long to finish
+const CAPTURE_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// What the app embedding screenpipe sets, the other fields take the defaults of the cli
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EngineConfig {
+    /// Where the database and the recordings are, required
+    pub data_dir: Option<PathBuf>,
+    /// The primary monitor when none is set
+    pub monitor_id: Option<u32>,
+    pub fps: f64,
+    /// Seconds
+    pub audio_chunk_duration: u64,
+    pub disable_audio: bool,
+    /// A value of `--ocr-engine`, e.g. "tesseract"
+    pub ocr_engine: Option<String>,
+    /// A value of `--audio-transcription-engine`, e.g. "whisper-tiny"
+    pub audio_transcription_engine: Option<String>,
+    pub video_quality: EncoderQuality,
+    pub save_text_files: bool,
+}
+
+impl Default for EngineConfig {
+    fn default() -> Self {
+        let cli = Cli::parse_from(["screenpipe"]);
+        EngineConfig {
+            data_dir: None,
+            monitor_id: None,
+            fps: cli.fps,
+            audio_chunk_duration: cli.audio_chunk_duration,
+            disable_audio: cli.disable_audio,
+            ocr_engine: None,
+            audio_transcription_engine: None,
+            video_quality: EncoderQuality::default(),
+            save_text_files: cli.save_text_files,
+        }
+    }
+}
+
+impl EngineConfig {
+    fn ocr_engine(&self) -> Result<OcrEngine> {
+        Ok(match &self.ocr_engine {
+            Some(name) => CliOcrEngine::from_str(name, true)
+                .map_err(|e| anyhow!("unknown ocr engine {}: {}", name, e))?
+                .into(),
+            None => Cli::parse_from(["screenpipe"]).ocr_engine.into(),
+        })
+    }
+
+    fn audio_transcription_engine(&self) -> Result<AudioTranscriptionEngine> {
+        Ok(match &self.audio_transcription_engine {
+            Some(name) => CliAudioTranscriptionEngine::from_str(name, true)
+                .map_err(|e| anyhow!("unknown audio transcription engine {}: {}", name, e))?
+                .into(),
+            None => Cli::parse_from(["screenpipe"])
+                .audio_transcription_engine
+                .into(),
+        })
+    }
+}
+
+/// The fields of `/search`
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub q: String,
+    pub content_type: ContentType,
+    pub limit: u32,
+    pub offset: u32,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        SearchQuery {
+            q: String::new(),
+            content_type: ContentType::All,
+            limit: 20,
+            offset: 0,
+            start_time: None,
+            end_time: None,
+            app_name: None,
+            window_name: None,
+        }
+    }
+}
+
+struct Task {
+    cancel: CancellationToken,
+    handle: JoinHandle<()>,
+}
+
+/// The database and the recording of one data dir, with its own runtime
+pub struct Engine {
+    runtime: Runtime,
+    db: Arc<DatabaseManager>,
+    config: EngineConfig,
+    data_dir: PathBuf,
+    capture: Mutex<Option<Task>>,
+    subscriptions: Mutex<HashMap<u64, Task>>,
+    next_subscription: AtomicU64,
+}
+
+impl Engine {
+    pub fn new(config: EngineConfig) -> Result<Self> {
+        let data_dir = config.data_dir.clone().context("data_dir is required")?;
+        std::fs::create_dir_all(data_dir.join("data"))
+            .with_context(|| format!("failed to create {}", data_dir.display()))?;
+        // checked before anything starts rather than when the capture does
+        config.ocr_engine()?;
+        config.audio_transcription_engine()?;
+
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_all()
+            .thread_name("screenpipe")
+            .build()?;
+        let database_path = data_dir.join("db.sqlite");
+        let db = runtime
+            .block_on(DatabaseManager::new(&database_path.to_string_lossy()))
+            .with_context(|| format!("failed to open {}", database_path.display()))?;
+        Ok(Engine {
+            runtime,
+            db: Arc::new(db),
+            config,
+            data_dir,
+            capture: Mutex::new(None),
+            subscriptions: Mutex::new(HashMap::new()),
+            next_subscription: AtomicU64::new(1),
+        })
+    }
+
+    pub fn is_capturing(&self) -> bool {
+        self.capture
+            .lock()
+            .unwrap()
+            .as_ref()
+            .is_some_and(|capture| !capture.handle.is_finished())
+    }
+
+    /// Records the screen, and the default microphone and speakers unless `disable_audio`
+    pub fn start_capture(&self) -> Result<()> {
+        let mut capture = self.capture.lock().unwrap();
+        if capture
+            .as_ref()
+            .is_some_and(|capture| !capture.handle.is_finished())
+        {
+            bail!("the capture is already running");
+        }
+        let config = &self.config;
+        let ocr_engine = Arc::new(config.ocr_engine()?);
+        let audio_transcription_engine = Arc::new(config.audio_transcription_engine()?);
+        let db = Arc::clone(&self.db);
+        let output_path = Arc::new(self.data_dir.join("data").to_string_lossy().into_owned());
+        let slides_dir = self.data_dir.join("slides");
+
+        let (monitor_id, video_encoder, watchlist) = self.runtime.block_on(async {
+            let monitor_id = match config.monitor_id {
+                Some(id) => id,
+                None => list_monitors()
+                    .await
+                    .first()
+                    .map(|monitor| monitor.id())
+                    .context("no monitor to capture")?,
+            };
+            let video_encoder = best_encoder(&probe_hardware_encoders().await);
+            let watchlist = Watchlist::load(Arc::clone(&db)).await?;
+            anyhow::Ok((monitor_id, video_encoder, watchlist))
+        })?;
+
+        let audio_devices_control = Arc::new(SegQueue::new());
+        if !config.disable_audio {
+            let output = self.runtime.block_on(default_output_device());
+            for device in [default_input_device(), output].into_iter().flatten() {
+                info!("recording {}", device);
+                audio_devices_control.push((
+                    device,
+                    DeviceControl {
+                        is_running: true,
+                        is_paused: false,
+                    },
+                ));
+            }
+        }
+
+        let cancel = CancellationToken::new();
+        let recording = start_continuous_recording(
+            db,
+            output_path,
+            config.fps,
+            Duration::from_secs(config.audio_chunk_duration),
+            Arc::new(AtomicBool::new(true)),
+            audio_devices_control,
+            config.save_text_files,
+            audio_transcription_engine,
+            ocr_engine,
+            None,
+            monitor_id,
+            video_encoder,
+            config.video_quality,
+            None,
+            None,
+            Arc::new(CapturePause::new()),
+            watchlist,
+            Arc::new(LiveOcr::new(monitor_id)),
+            Some(slides_dir),
+            cancel.clone(),
+        );
+        let handle = self.runtime.spawn(async move {
+            if let Err(e) = recording.await {
+                warn!("capture stopped: {:?}", e);
+            }
+        });
+        *capture = Some(Task { cancel, handle });
+        Ok(())
+    }
+
+    pub fn stop_capture(&self) -> Result<()> {
+        let Some(Task { cancel, mut handle }) = self.capture.lock().unwrap().take() else {
+            bail!("the capture isn't running");
+        };
+        cancel.cancel();
+        self.runtime.block_on(async {
+            if tokio::time::timeout(CAPTURE_STOP_TIMEOUT, &mut handle)
+                .await
+                .is_err()
+            {
+                warn!(
+                    "capture didn't stop in {:?}, aborting it",
+                    CAPTURE_STOP_TIMEOUT
+                );
+                handle.abort();
+            }
+        });
+        Ok(())
+    }
+
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<SearchResult>> {
+        Ok(self.runtime.block_on(self.db.search(
+            &query.q,
+            query.content_type,
+            query.limit,
+            query.offset,
+            query.start_time,
+            query.end_time,
+            query.app_name.as_deref(),
+            query.window_name.as_deref(),
+        ))?)
+    }
+
+    /// The image of a frame `search` returned, decoded from its video chunk
+    pub fn frame(&self, frame_id: i64) -> Result<DynamicImage, StorageError> {
+        self.runtime.block_on(async {
+            let (file_path, offset_index) = self
+                .db
+                .get_frame(frame_id)
+                .await?
+                .ok_or(StorageError::FrameNotFound(frame_id))?;
+            extract_frame(&file_path, offset_index).await
+        })
+    }
+
+    /// Calls `on_event` on a thread of the engine for each event until `unsubscribe`
+    pub fn subscribe(&self, on_event: impl Fn(&Event) + Send + 'static) -> u64 {
+        let id = self.next_subscription.fetch_add(1, Ordering::Relaxed);
+        let cancel = CancellationToken::new();
+        let stop = cancel.clone();
+        let mut events = subscribe_events();
+        let handle = self.runtime.spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = stop.cancelled() => break,
+                    event = events.recv() => match event {
+                        Ok(event) => on_event(&event),
+                        Err(RecvError::Lagged(missed)) => {
+                            warn!("subscription {} missed {} events", id, missed)
+                        }
+                        Err(RecvError::Closed) => break,
+                    },
+                }
+            }
+        });
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .insert(id, Task { cancel, handle });
+        id
+    }
+
+    /// Once it returns `on_event` isn't called anymore, unless it's called from `on_event`
+    pub fn unsubscribe(&self, id: u64) -> bool {
+        let Some(Task { cancel, handle }) = self.subscriptions.lock().unwrap().remove(&id) else {
+            return false;
+        };
+        cancel.cancel();
+        if Handle::try_current().is_err() {
+            let _ = self.runtime.block_on(handle);
+        }
+        true
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        let ids: Vec<u64> = self.subscriptions.lock().unwrap().keys().copied().collect();
+        for id in ids {
+            self.unsubscribe(id);
+        }
+        if self.capture.lock().unwrap().is_some() {
+            let _ = self.stop_capture();
+        }
+    }
+}
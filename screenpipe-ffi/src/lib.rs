@@ -0,0 +1,253 @@
+//! C ABI of the screenpipe engine, for the Electron, Swift and C# apps embedding it instead of
+//! running the `screenpipe` binary. `include/screenpipe.h` is generated from this file by the
+//! build.
+//!
+//! The structured arguments and results are json strings, the fields are the ones of the
+//! http api, so new ones don't change the ABI. The functions returning a pointer return NULL
+//! on failure and the others -1, `screenpipe_last_error` then says why.
+mod engine;
+
+pub use engine::{Engine, EngineConfig, SearchQuery};
+
+use anyhow::{anyhow, Result};
+use std::cell::RefCell;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+
+/// Bumped when a function of this header changes, not when one is added
+pub const SCREENPIPE_ABI_VERSION: u32 = 1;
+
+/// An engine opened on a data dir, shared between threads
+pub struct ScreenpipeEngine(Engine);
+
+/// Called with the name of the event, e.g. "watch.alert", its data as json and the
+/// `user_data` given to `screenpipe_subscribe`. Both strings are only valid during the call.
+pub type ScreenpipeEventCallback = Option<
+    unsafe extern "C" fn(name: *const c_char, data_json: *const c_char, user_data: *mut c_void),
+>;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).expect("no nul left");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+}
+
+/// Runs `f` and on its error, or its panic, keeps the message for `screenpipe_last_error`
+fn guard<T>(failed: T, f: impl FnOnce() -> Result<T>) -> T {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => value,
+        Ok(Err(e)) => {
+            set_last_error(format!("{:#}", e));
+            failed
+        }
+        Err(panic) => {
+            let message = panic
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| panic.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            set_last_error(format!("screenpipe panicked: {}", message));
+            failed
+        }
+    }
+}
+
+unsafe fn json_arg<'a>(json: *const c_char, name: &str) -> Result<&'a str> {
+    if json.is_null() {
+        return Err(anyhow!("{} is NULL", name));
+    }
+    Ok(CStr::from_ptr(json).to_str()?)
+}
+
+unsafe fn engine_arg<'a>(engine: *const ScreenpipeEngine) -> Result<&'a Engine> {
+    engine
+        .as_ref()
+        .map(|engine| &engine.0)
+        .ok_or_else(|| anyhow!("engine is NULL"))
+}
+
+fn to_c_string(s: String) -> Result<*mut c_char> {
+    Ok(CString::new(s)?.into_raw())
+}
+
+/// The pointer of `user_data`, the app promised it can be used from the threads of the engine
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+impl UserData {
+    // a method, for the closures to capture the whole of it and not its pointer
+    fn ptr(&self) -> *mut c_void {
+        self.0
+    }
+}
+
+/// The `SCREENPIPE_ABI_VERSION` of the library loaded, to compare with the header's
+#[no_mangle]
+pub extern "C" fn screenpipe_abi_version() -> u32 {
+    SCREENPIPE_ABI_VERSION
+}
+
+/// Why the last call of this thread failed, NULL if none did. Valid until the next failure
+/// on this thread, not to be freed.
+#[no_mangle]
+pub extern "C" fn screenpipe_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Opens the database of `data_dir` without recording anything yet, e.g.
+/// `{"data_dir": "/Users/me/.screenpipe", "fps": 0.5, "disable_audio": true}`.
+/// The other fields are `monitor_id`, `audio_chunk_duration`, `ocr_engine`,
+/// `audio_transcription_engine`, `video_quality` and `save_text_files`, as the cli takes them.
+///
+/// # Safety
+///
+/// `config_json` is a nul terminated utf-8 string. The engine returned is freed with
+/// `screenpipe_engine_free`.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_engine_new(
+    config_json: *const c_char,
+) -> *mut ScreenpipeEngine {
+    guard(ptr::null_mut(), || {
+        let config: EngineConfig = serde_json::from_str(json_arg(config_json, "config_json")?)?;
+        let engine = Engine::new(config)?;
+        Ok(Box::into_raw(Box::new(ScreenpipeEngine(engine))))
+    })
+}
+
+/// Stops the capture and the subscriptions of the engine, and closes it
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't used after, nor freed twice. Not to be
+/// called from a `ScreenpipeEventCallback`.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_engine_free(engine: *mut ScreenpipeEngine) {
+    if !engine.is_null() {
+        guard((), || {
+            drop(Box::from_raw(engine));
+            Ok(())
+        })
+    }
+}
+
+/// Starts recording the screen, and the audio unless `disable_audio`. 0 once started, -1 if it
+/// couldn't be or already is.
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_capture_start(engine: *const ScreenpipeEngine) -> i32 {
+    guard(-1, || {
+        engine_arg(engine)?.start_capture()?;
+        Ok(0)
+    })
+}
+
+/// Stops the recording once the frames and audio in flight are written. 0 once stopped, -1 if
+/// it wasn't running.
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_capture_stop(engine: *const ScreenpipeEngine) -> i32 {
+    guard(-1, || {
+        engine_arg(engine)?.stop_capture()?;
+        Ok(0)
+    })
+}
+
+/// 1 while the engine records, 0 otherwise and -1 for a NULL engine
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_capture_running(engine: *const ScreenpipeEngine) -> i32 {
+    guard(-1, || Ok(engine_arg(engine)?.is_capturing() as i32))
+}
+
+/// Searches what was recorded, `query_json` has the fields of `/search` (`q`, `content_type`,
+/// `limit`, `offset`, `start_time`, `end_time`, `app_name` and `window_name`). Returns a json
+/// array of `{"OCR": {...}}`, `{"Audio": {...}}` and `{"FTS": {...}}`, freed with
+/// `screenpipe_string_free`.
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't freed yet, `query_json` is a nul
+/// terminated utf-8 string.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_search(
+    engine: *const ScreenpipeEngine,
+    query_json: *const c_char,
+) -> *mut c_char {
+    guard(ptr::null_mut(), || {
+        let engine = engine_arg(engine)?;
+        let query: SearchQuery = serde_json::from_str(json_arg(query_json, "query_json")?)?;
+        to_c_string(serde_json::to_string(&engine.search(&query)?)?)
+    })
+}
+
+/// Calls `callback` for each event of the engine until `screenpipe_unsubscribe`, from a thread
+/// of the engine. Returns the id of the subscription, 0 on failure.
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't freed yet. `callback` and `user_data`
+/// can be used from another thread until the subscription ends.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_subscribe(
+    engine: *const ScreenpipeEngine,
+    callback: ScreenpipeEventCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    guard(0, || {
+        let engine = engine_arg(engine)?;
+        let callback = callback.ok_or_else(|| anyhow!("callback is NULL"))?;
+        let user_data = UserData(user_data);
+        Ok(engine.subscribe(move |event| {
+            let data = event.data.to_string();
+            if let (Ok(name), Ok(data)) = (CString::new(event.name.as_str()), CString::new(data)) {
+                unsafe { callback(name.as_ptr(), data.as_ptr(), user_data.ptr()) };
+            }
+        }))
+    })
+}
+
+/// Ends a subscription, its callback isn't called anymore once this returns. 0 once ended, -1
+/// for an unknown id.
+///
+/// # Safety
+///
+/// `engine` comes from `screenpipe_engine_new` and isn't freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_unsubscribe(
+    engine: *const ScreenpipeEngine,
+    subscription: u64,
+) -> i32 {
+    guard(-1, || {
+        if !engine_arg(engine)?.unsubscribe(subscription) {
+            return Err(anyhow!("no subscription {}", subscription));
+        }
+        Ok(0)
+    })
+}
+
+/// Frees a string returned by screenpipe
+///
+/// # Safety
+///
+/// `s` was returned by a screenpipe function, and isn't freed twice.
+#[no_mangle]
+pub unsafe extern "C" fn screenpipe_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
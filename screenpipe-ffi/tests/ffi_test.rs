@@ -0,0 +1,139 @@
+use screenpipe::{
+    screenpipe_abi_version, screenpipe_capture_running, screenpipe_capture_stop,
+    screenpipe_engine_free, screenpipe_engine_new, screenpipe_last_error, screenpipe_search,
+    screenpipe_string_free, screenpipe_subscribe, screenpipe_unsubscribe, ScreenpipeEngine,
+    SCREENPIPE_ABI_VERSION,
+};
+use screenpipe_core::emit_event;
+use serde_json::json;
+use std::ffi::{c_char, c_void, CStr, CString};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::Duration;
+
+fn data_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("screenpipe-ffi-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn open(dir: &PathBuf) -> *mut ScreenpipeEngine {
+    let config = CString::new(json!({"data_dir": dir, "disable_audio": true}).to_string()).unwrap();
+    let engine = unsafe { screenpipe_engine_new(config.as_ptr()) };
+    assert!(!engine.is_null(), "{}", last_error());
+    engine
+}
+
+fn last_error() -> String {
+    let error = screenpipe_last_error();
+    assert!(!error.is_null());
+    unsafe { CStr::from_ptr(error) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+#[test]
+fn test_engines_are_opened_and_searched_through_the_abi() {
+    assert_eq!(screenpipe_abi_version(), SCREENPIPE_ABI_VERSION);
+    let dir = data_dir("search");
+    let engine = open(&dir);
+    assert!(dir.join("db.sqlite").exists());
+
+    let query = CString::new(r#"{"q": "invoice", "content_type": "ocr"}"#).unwrap();
+    let results = unsafe { screenpipe_search(engine, query.as_ptr()) };
+    assert!(!results.is_null(), "{}", last_error());
+    assert_eq!(unsafe { CStr::from_ptr(results) }.to_str().unwrap(), "[]");
+    unsafe { screenpipe_string_free(results) };
+
+    let query = CString::new(r#"{"content_type": "video"}"#).unwrap();
+    assert!(unsafe { screenpipe_search(engine, query.as_ptr()) }.is_null());
+    assert!(last_error().contains("video"), "{}", last_error());
+
+    assert_eq!(unsafe { screenpipe_capture_running(engine) }, 0);
+    assert_eq!(unsafe { screenpipe_capture_stop(engine) }, -1);
+    assert!(last_error().contains("isn't running"), "{}", last_error());
+    unsafe { screenpipe_engine_free(engine) };
+}
+
+#[test]
+fn test_bad_configs_are_told_apart() {
+    assert!(unsafe { screenpipe_engine_new(std::ptr::null()) }.is_null());
+    assert_eq!(last_error(), "config_json is NULL");
+
+    let config = CString::new("{}").unwrap();
+    assert!(unsafe { screenpipe_engine_new(config.as_ptr()) }.is_null());
+    assert_eq!(last_error(), "data_dir is required");
+
+    let config =
+        CString::new(json!({"data_dir": data_dir("engine"), "ocr_engine": "paper"}).to_string())
+            .unwrap();
+    assert!(unsafe { screenpipe_engine_new(config.as_ptr()) }.is_null());
+    assert!(
+        last_error().contains("unknown ocr engine paper"),
+        "{}",
+        last_error()
+    );
+
+    assert_eq!(unsafe { screenpipe_capture_stop(std::ptr::null()) }, -1);
+    assert_eq!(last_error(), "engine is NULL");
+}
+
+unsafe extern "C" fn on_event(
+    name: *const c_char,
+    data_json: *const c_char,
+    user_data: *mut c_void,
+) {
+    let events = &*(user_data as *const mpsc::Sender<(String, String)>);
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let data = CStr::from_ptr(data_json).to_string_lossy().into_owned();
+    let _ = events.send((name, data));
+}
+
+#[test]
+fn test_subscribers_get_the_events_until_they_unsubscribe() {
+    let engine = open(&data_dir("events"));
+    let (events_tx, events_rx) = mpsc::channel::<(String, String)>();
+    let user_data = Box::into_raw(Box::new(events_tx));
+
+    let subscription =
+        unsafe { screenpipe_subscribe(engine, Some(on_event), user_data as *mut c_void) };
+    assert_ne!(subscription, 0, "{}", last_error());
+    // the subscription listens once its task runs
+    let event = loop {
+        emit_event("ffi.test", json!({"frame_id": 7}));
+        if let Ok(event) = events_rx.recv_timeout(Duration::from_millis(100)) {
+            break event;
+        }
+    };
+    assert_eq!(event.0, "ffi.test");
+    assert_eq!(
+        serde_json::from_str::<serde_json::Value>(&event.1).unwrap(),
+        json!({"frame_id": 7})
+    );
+
+    assert_eq!(unsafe { screenpipe_unsubscribe(engine, subscription) }, 0);
+    while events_rx.try_recv().is_ok() {}
+    emit_event("ffi.test", json!({}));
+    assert!(events_rx.recv_timeout(Duration::from_millis(200)).is_err());
+    assert_eq!(unsafe { screenpipe_unsubscribe(engine, subscription) }, -1);
+
+    assert_eq!(
+        unsafe { screenpipe_subscribe(engine, None, std::ptr::null_mut()) },
+        0
+    );
+    assert_eq!(last_error(), "callback is NULL");
+
+    unsafe { screenpipe_engine_free(engine) };
+    drop(unsafe { Box::from_raw(user_data) });
+}
+
+#[test]
+fn test_missing_frames_are_told_apart() {
+    let engine = screenpipe::Engine::new(screenpipe::EngineConfig {
+        data_dir: Some(data_dir("frames")),
+        ..Default::default()
+    })
+    .unwrap();
+    let error = engine.frame(42).unwrap_err();
+    assert_eq!(error.code(), "frame_not_found");
+}
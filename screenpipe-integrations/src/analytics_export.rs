@@ -0,0 +1,359 @@
+use anyhow::{bail, Result};
+use arrow::array::{
+    ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray,
+};
+use arrow::csv::WriterBuilder;
+use arrow::datatypes::{DataType, Field, Schema, TimeUnit};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Utc};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsFormat {
+    Csv,
+    Parquet,
+}
+
+impl AnalyticsFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AnalyticsFormat::Csv => "csv",
+            AnalyticsFormat::Parquet => "parquet",
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            AnalyticsFormat::Csv => "text/csv; charset=utf-8",
+            AnalyticsFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+}
+
+impl FromStr for AnalyticsFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(AnalyticsFormat::Csv),
+            "parquet" => Ok(AnalyticsFormat::Parquet),
+            _ => bail!("unknown analytics format: {}", s),
+        }
+    }
+}
+
+/// The tables offered to analysis, their columns stay the same whatever the database looks like
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnalyticsTable {
+    Sessions,
+    Ocr,
+    Transcripts,
+    /// Hourly totals, what is left of the text in the aggregation only mode
+    Usage,
+}
+
+impl AnalyticsTable {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AnalyticsTable::Sessions => "sessions",
+            AnalyticsTable::Ocr => "ocr",
+            AnalyticsTable::Transcripts => "transcripts",
+            AnalyticsTable::Usage => "usage",
+        }
+    }
+}
+
+impl FromStr for AnalyticsTable {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "sessions" => Ok(AnalyticsTable::Sessions),
+            "ocr" => Ok(AnalyticsTable::Ocr),
+            "transcripts" => Ok(AnalyticsTable::Transcripts),
+            "usage" => Ok(AnalyticsTable::Usage),
+            _ => bail!("unknown analytics table: {}", s),
+        }
+    }
+}
+
+/// Time spent in an app, with the activity it was tagged as
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRow {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub app_name: String,
+    pub category: String,
+    pub confidence: f64,
+}
+
+/// The size of the text read on a frame, the text itself stays in the database
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrRow {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: Option<String>,
+    pub focused: bool,
+    pub ocr_engine: String,
+    pub word_count: i64,
+    pub char_count: i64,
+}
+
+/// The size of a transcribed piece of audio, without the words
+#[derive(Debug, Clone, PartialEq)]
+pub struct TranscriptRow {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub audio_chunk_id: i64,
+    pub transcription_engine: String,
+    pub word_count: i64,
+    pub char_count: i64,
+}
+
+/// What was read in an app, or said, during an hour
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRow {
+    pub hour: DateTime<Utc>,
+    /// "screen" or "audio"
+    pub source: String,
+    /// `None` for audio
+    pub app_name: Option<String>,
+    /// Frames or transcriptions
+    pub count: i64,
+    pub word_count: i64,
+    pub char_count: i64,
+}
+
+/// The rows of one of the tables
+#[derive(Debug, Clone, PartialEq)]
+pub enum AnalyticsRows {
+    Sessions(Vec<SessionRow>),
+    Ocr(Vec<OcrRow>),
+    Transcripts(Vec<TranscriptRow>),
+    Usage(Vec<UsageRow>),
+}
+
+/// An offset rather than "UTC", arrow only knows zone names with chrono-tz
+const TIMEZONE: &str = "+00:00";
+
+fn timestamps(values: impl Iterator<Item = DateTime<Utc>>) -> ArrayRef {
+    Arc::new(
+        TimestampMicrosecondArray::from_iter_values(values.map(|time| time.timestamp_micros()))
+            .with_timezone(TIMEZONE),
+    )
+}
+
+fn timestamp_field(name: &str) -> Field {
+    Field::new(
+        name,
+        DataType::Timestamp(TimeUnit::Microsecond, Some(TIMEZONE.into())),
+        false,
+    )
+}
+
+fn strings<'a>(values: impl Iterator<Item = &'a str>) -> ArrayRef {
+    Arc::new(StringArray::from_iter_values(values))
+}
+
+fn integers(values: impl Iterator<Item = i64>) -> ArrayRef {
+    Arc::new(Int64Array::from_iter_values(values))
+}
+
+fn floats(values: impl Iterator<Item = f64>) -> ArrayRef {
+    Arc::new(Float64Array::from_iter_values(values))
+}
+
+impl AnalyticsRows {
+    pub fn len(&self) -> usize {
+        match self {
+            AnalyticsRows::Sessions(rows) => rows.len(),
+            AnalyticsRows::Ocr(rows) => rows.len(),
+            AnalyticsRows::Transcripts(rows) => rows.len(),
+            AnalyticsRows::Usage(rows) => rows.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The rows as columns, with the schema written in both formats
+    pub fn record_batch(&self) -> Result<RecordBatch> {
+        let (fields, columns): (Vec<Field>, Vec<ArrayRef>) = match self {
+            AnalyticsRows::Sessions(rows) => {
+                let durations = rows
+                    .iter()
+                    .map(|row| (row.end_time - row.start_time).num_milliseconds() as f64 / 1000.0);
+                vec![
+                    (
+                        Field::new("id", DataType::Int64, false),
+                        integers(rows.iter().map(|row| row.id)),
+                    ),
+                    (
+                        timestamp_field("start_time"),
+                        timestamps(rows.iter().map(|row| row.start_time)),
+                    ),
+                    (
+                        timestamp_field("end_time"),
+                        timestamps(rows.iter().map(|row| row.end_time)),
+                    ),
+                    (
+                        Field::new("duration_secs", DataType::Float64, false),
+                        floats(durations),
+                    ),
+                    (
+                        Field::new("app_name", DataType::Utf8, false),
+                        strings(rows.iter().map(|row| row.app_name.as_str())),
+                    ),
+                    (
+                        Field::new("category", DataType::Utf8, false),
+                        strings(rows.iter().map(|row| row.category.as_str())),
+                    ),
+                    (
+                        Field::new("confidence", DataType::Float64, false),
+                        floats(rows.iter().map(|row| row.confidence)),
+                    ),
+                ]
+                .into_iter()
+                .unzip()
+            }
+            AnalyticsRows::Ocr(rows) => vec![
+                (
+                    Field::new("frame_id", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.frame_id)),
+                ),
+                (
+                    timestamp_field("timestamp"),
+                    timestamps(rows.iter().map(|row| row.timestamp)),
+                ),
+                (
+                    Field::new("app_name", DataType::Utf8, false),
+                    strings(rows.iter().map(|row| row.app_name.as_str())),
+                ),
+                (
+                    Field::new("window_name", DataType::Utf8, true),
+                    Arc::new(StringArray::from_iter(
+                        rows.iter().map(|row| row.window_name.as_deref()),
+                    )) as ArrayRef,
+                ),
+                (
+                    Field::new("focused", DataType::Boolean, false),
+                    Arc::new(BooleanArray::from_iter(
+                        rows.iter().map(|row| Some(row.focused)),
+                    )) as ArrayRef,
+                ),
+                (
+                    Field::new("ocr_engine", DataType::Utf8, false),
+                    strings(rows.iter().map(|row| row.ocr_engine.as_str())),
+                ),
+                (
+                    Field::new("word_count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.word_count)),
+                ),
+                (
+                    Field::new("char_count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.char_count)),
+                ),
+            ]
+            .into_iter()
+            .unzip(),
+            AnalyticsRows::Transcripts(rows) => vec![
+                (
+                    Field::new("id", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.id)),
+                ),
+                (
+                    timestamp_field("timestamp"),
+                    timestamps(rows.iter().map(|row| row.timestamp)),
+                ),
+                (
+                    Field::new("audio_chunk_id", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.audio_chunk_id)),
+                ),
+                (
+                    Field::new("transcription_engine", DataType::Utf8, false),
+                    strings(rows.iter().map(|row| row.transcription_engine.as_str())),
+                ),
+                (
+                    Field::new("word_count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.word_count)),
+                ),
+                (
+                    Field::new("char_count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.char_count)),
+                ),
+            ]
+            .into_iter()
+            .unzip(),
+            AnalyticsRows::Usage(rows) => vec![
+                (
+                    timestamp_field("hour"),
+                    timestamps(rows.iter().map(|row| row.hour)),
+                ),
+                (
+                    Field::new("source", DataType::Utf8, false),
+                    strings(rows.iter().map(|row| row.source.as_str())),
+                ),
+                (
+                    Field::new("app_name", DataType::Utf8, true),
+                    Arc::new(StringArray::from_iter(
+                        rows.iter().map(|row| row.app_name.as_deref()),
+                    )) as ArrayRef,
+                ),
+                (
+                    Field::new("count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.count)),
+                ),
+                (
+                    Field::new("word_count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.word_count)),
+                ),
+                (
+                    Field::new("char_count", DataType::Int64, false),
+                    integers(rows.iter().map(|row| row.char_count)),
+                ),
+            ]
+            .into_iter()
+            .unzip(),
+        };
+        Ok(RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?)
+    }
+}
+
+/// Writes the rows as a csv file with a header, or as a snappy compressed parquet file
+pub fn write_analytics(rows: &AnalyticsRows, format: AnalyticsFormat) -> Result<Vec<u8>> {
+    let batch = rows.record_batch()?;
+    let mut file = Vec::new();
+    match format {
+        AnalyticsFormat::Csv => {
+            let mut writer = WriterBuilder::new().with_header(true).build(&mut file);
+            writer.write(&batch)?;
+        }
+        AnalyticsFormat::Parquet => {
+            let properties = WriterProperties::builder()
+                .set_compression(Compression::SNAPPY)
+                .build();
+            let mut writer = ArrowWriter::try_new(&mut file, batch.schema(), Some(properties))?;
+            writer.write(&batch)?;
+            writer.close()?;
+        }
+    }
+    Ok(file)
+}
+
+/// Words as pandas' `str.split()` counts them
+pub fn word_count(text: &str) -> i64 {
+    text.split_whitespace().count() as i64
+}
@@ -0,0 +1,606 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday,
+};
+use chrono_tz::Tz;
+use std::collections::{HashMap, HashSet};
+
+/// A recurring event never yields more occurrences than this, whatever its rule says
+const MAX_OCCURRENCES: usize = 5000;
+
+/// An occurrence of a calendar event, recurring events give one per occurrence
+#[derive(Debug, Clone, PartialEq)]
+pub struct CalendarEvent {
+    pub uid: String,
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Names when the calendar has them, email addresses otherwise
+    pub attendees: Vec<String>,
+    pub location: Option<String>,
+    pub all_day: bool,
+}
+
+/// Reads an iCalendar feed: an http(s) or webcal url, like the secret address of a Google
+/// calendar, a published Outlook calendar or a CalDAV calendar export, or a local .ics file
+pub async fn fetch_calendar(source: &str) -> Result<String> {
+    let url = match source.strip_prefix("webcal://") {
+        Some(rest) => format!("https://{}", rest),
+        None => source.to_string(),
+    };
+    if url.starts_with("http://") || url.starts_with("https://") {
+        Ok(reqwest::get(&url).await?.error_for_status()?.text().await?)
+    } else {
+        tokio::fs::read_to_string(source)
+            .await
+            .with_context(|| format!("failed to read calendar {}", source))
+    }
+}
+
+/// Continuation lines start with a space or a tab
+fn unfold(ics: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.to_string()),
+        }
+    }
+    lines
+}
+
+struct Property {
+    name: String,
+    params: HashMap<String, String>,
+    value: String,
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // the value starts at the first colon outside of quoted parameters
+    let mut quoted = false;
+    let colon = line.char_indices().find_map(|(i, c)| match c {
+        '"' => {
+            quoted = !quoted;
+            None
+        }
+        ':' if !quoted => Some(i),
+        _ => None,
+    })?;
+    let (head, value) = (&line[..colon], &line[colon + 1..]);
+    let mut parts = head.split(';');
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(key, value)| {
+            (
+                key.to_ascii_uppercase(),
+                value.trim_matches('"').to_string(),
+            )
+        })
+        .collect();
+    Some(Property {
+        name,
+        params,
+        value: value.to_string(),
+    })
+}
+
+fn unescape(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => text.push('\n'),
+            Some(other) => text.push(other),
+            None => {}
+        }
+    }
+    text
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Zone {
+    Utc,
+    Named(Tz),
+    /// Floating times and zones we don't know are taken as local
+    Local,
+}
+
+impl Zone {
+    fn to_utc(self, time: NaiveDateTime) -> Option<DateTime<Utc>> {
+        match self {
+            Zone::Utc => Some(Utc.from_utc_datetime(&time)),
+            Zone::Named(tz) => tz
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            Zone::Local => Local
+                .from_local_datetime(&time)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+}
+
+/// Wall time in its zone, so recurrences keep their hour across DST changes
+#[derive(Debug, Clone, Copy)]
+struct IcsTime {
+    time: NaiveDateTime,
+    zone: Zone,
+    all_day: bool,
+}
+
+fn parse_time(property: &Property) -> Option<IcsTime> {
+    let value = property.value.trim();
+    if property.params.get("VALUE").map(String::as_str) == Some("DATE") || value.len() == 8 {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d").ok()?;
+        return Some(IcsTime {
+            time: date.and_hms_opt(0, 0, 0)?,
+            zone: Zone::Local,
+            all_day: true,
+        });
+    }
+    let (value, zone) = match value.strip_suffix('Z') {
+        Some(value) => (value, Zone::Utc),
+        None => (
+            value,
+            property
+                .params
+                .get("TZID")
+                .and_then(|tzid| tzid.parse::<Tz>().ok())
+                .map(Zone::Named)
+                .unwrap_or(Zone::Local),
+        ),
+    };
+    Some(IcsTime {
+        time: NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()?,
+        zone,
+        all_day: false,
+    })
+}
+
+fn parse_times(property: &Property) -> Vec<IcsTime> {
+    property
+        .value
+        .split(',')
+        .filter_map(|value| {
+            parse_time(&Property {
+                name: property.name.clone(),
+                params: property.params.clone(),
+                value: value.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Durations like PT1H30M or P1D
+fn parse_duration(value: &str) -> Option<Duration> {
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(value) => (true, value),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let mut total = Duration::zero();
+    let mut number = String::new();
+    let mut in_time = false;
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => in_time = true,
+            unit => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                total += match (unit, in_time) {
+                    ('W', _) => Duration::weeks(n),
+                    ('D', _) => Duration::days(n),
+                    ('H', true) => Duration::hours(n),
+                    ('M', true) => Duration::minutes(n),
+                    ('S', true) => Duration::seconds(n),
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(if negative { -total } else { total })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+#[derive(Debug, Clone)]
+struct Recurrence {
+    frequency: Frequency,
+    interval: u32,
+    count: Option<usize>,
+    until: Option<DateTime<Utc>>,
+    by_day: Vec<Weekday>,
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    // positions like 1MO of monthly rules aren't supported, the day alone is kept
+    let day = day.trim_start_matches(|c: char| c.is_ascii_digit() || c == '-' || c == '+');
+    Some(match day {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+fn parse_rrule(value: &str, zone: Zone) -> Option<Recurrence> {
+    let parts: HashMap<&str, &str> = value.split(';').filter_map(|p| p.split_once('=')).collect();
+    let frequency = match *parts.get("FREQ")? {
+        "DAILY" => Frequency::Daily,
+        "WEEKLY" => Frequency::Weekly,
+        "MONTHLY" => Frequency::Monthly,
+        "YEARLY" => Frequency::Yearly,
+        _ => return None,
+    };
+    let until = parts.get("UNTIL").and_then(|until| {
+        parse_time(&Property {
+            name: "UNTIL".to_string(),
+            params: HashMap::new(),
+            value: until.to_string(),
+        })
+        .and_then(|until| match until.all_day {
+            true => zone.to_utc(until.time + Duration::days(1)),
+            false => until.zone.to_utc(until.time),
+        })
+    });
+    Some(Recurrence {
+        frequency,
+        interval: parts
+            .get("INTERVAL")
+            .and_then(|i| i.parse().ok())
+            .unwrap_or(1)
+            .max(1),
+        count: parts.get("COUNT").and_then(|c| c.parse().ok()),
+        until,
+        by_day: parts
+            .get("BYDAY")
+            .map(|days| days.split(',').filter_map(parse_weekday).collect())
+            .unwrap_or_default(),
+    })
+}
+
+fn add_months(time: NaiveDateTime, months: u32) -> Option<NaiveDateTime> {
+    let total = time.year() as i64 * 12 + time.month0() as i64 + months as i64;
+    let date = NaiveDate::from_ymd_opt((total / 12) as i32, (total % 12) as u32 + 1, time.day())?;
+    Some(date.and_time(time.time()))
+}
+
+/// Start times of the occurrences, in the wall time of the event, until `window_end`
+fn occurrences(
+    start: NaiveDateTime,
+    zone: Zone,
+    rule: &Recurrence,
+    window_end: DateTime<Utc>,
+) -> Vec<NaiveDateTime> {
+    let mut starts = Vec::new();
+    let mut period = 0u32;
+    // the start always counts as the first occurrence
+    'periods: loop {
+        let candidates: Vec<NaiveDateTime> = match rule.frequency {
+            Frequency::Daily => vec![start + Duration::days((period * rule.interval) as i64)],
+            Frequency::Weekly if !rule.by_day.is_empty() => {
+                let week_start = start
+                    - Duration::days(start.weekday().num_days_from_monday() as i64)
+                    + Duration::weeks((period * rule.interval) as i64);
+                let mut days: Vec<_> = rule
+                    .by_day
+                    .iter()
+                    .map(|day| week_start + Duration::days(day.num_days_from_monday() as i64))
+                    .filter(|time| *time >= start)
+                    .collect();
+                days.sort();
+                days
+            }
+            Frequency::Weekly => vec![start + Duration::weeks((period * rule.interval) as i64)],
+            // days that don't exist in a month are skipped
+            Frequency::Monthly => add_months(start, period * rule.interval)
+                .into_iter()
+                .collect(),
+            Frequency::Yearly => add_months(start, period * rule.interval * 12)
+                .into_iter()
+                .collect(),
+        };
+        period += 1;
+        for candidate in candidates {
+            let Some(utc) = zone.to_utc(candidate) else {
+                continue;
+            };
+            if rule.until.is_some_and(|until| utc > until)
+                || utc >= window_end
+                || rule.count.is_some_and(|count| starts.len() >= count)
+                || starts.len() >= MAX_OCCURRENCES
+            {
+                break 'periods;
+            }
+            starts.push(candidate);
+        }
+        if period as usize > MAX_OCCURRENCES * 4 {
+            break;
+        }
+    }
+    starts
+}
+
+#[derive(Default)]
+struct RawEvent {
+    uid: String,
+    title: String,
+    start: Option<IcsTime>,
+    end: Option<IcsTime>,
+    duration: Option<Duration>,
+    attendees: Vec<String>,
+    location: Option<String>,
+    cancelled: bool,
+    rrule: Option<String>,
+    exdates: Vec<IcsTime>,
+    recurrence_id: Option<IcsTime>,
+}
+
+fn attendee_name(property: &Property) -> String {
+    property.params.get("CN").cloned().unwrap_or_else(|| {
+        let value = property.value.trim();
+        value
+            .strip_prefix("mailto:")
+            .or_else(|| value.strip_prefix("MAILTO:"))
+            .unwrap_or(value)
+            .to_string()
+    })
+}
+
+fn parse_events(ics: &str) -> Result<Vec<RawEvent>> {
+    let lines = unfold(ics);
+    if !lines
+        .iter()
+        .any(|line| line.trim().eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        bail!("not an iCalendar file");
+    }
+    let mut events = Vec::new();
+    let mut current: Option<RawEvent> = None;
+    // alarms and other components nested in events aren't part of them
+    let mut nested = 0;
+    for property in lines.iter().filter_map(|line| parse_property(line)) {
+        let value = property.value.trim();
+        match (property.name.as_str(), value) {
+            ("BEGIN", "VEVENT") => current = Some(RawEvent::default()),
+            ("END", "VEVENT") => events.extend(current.take()),
+            ("BEGIN", _) if current.is_some() => nested += 1,
+            ("END", _) if current.is_some() && nested > 0 => nested -= 1,
+            _ => {}
+        }
+        let Some(event) = current.as_mut().filter(|_| nested == 0) else {
+            continue;
+        };
+        match property.name.as_str() {
+            "UID" => event.uid = value.to_string(),
+            "SUMMARY" => event.title = unescape(value),
+            "LOCATION" if !value.is_empty() => event.location = Some(unescape(value)),
+            "DTSTART" => event.start = parse_time(&property),
+            "DTEND" => event.end = parse_time(&property),
+            "DURATION" => event.duration = parse_duration(value),
+            "ATTENDEE" => event.attendees.push(attendee_name(&property)),
+            "STATUS" => event.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            "RRULE" => event.rrule = Some(value.to_string()),
+            "EXDATE" => event.exdates.extend(parse_times(&property)),
+            "RECURRENCE-ID" => event.recurrence_id = parse_time(&property),
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+/// Occurrences of the calendar's events overlapping the window. Daily, weekly, monthly and
+/// yearly recurrences are expanded, with their exceptions and moved occurrences
+pub fn parse_ics(
+    ics: &str,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+) -> Result<Vec<CalendarEvent>> {
+    let events = parse_events(ics)?;
+    // occurrences that were moved or cancelled are events of their own
+    let moved: HashSet<(String, Option<DateTime<Utc>>)> = events
+        .iter()
+        .filter_map(|event| {
+            let recurrence_id = event.recurrence_id?;
+            Some((
+                event.uid.clone(),
+                recurrence_id.zone.to_utc(recurrence_id.time),
+            ))
+        })
+        .collect();
+
+    let mut occurrences_in_window = Vec::new();
+    for event in &events {
+        let Some(start) = event.start else {
+            continue;
+        };
+        let length = match (event.end, event.duration) {
+            (Some(end), _) => match (start.zone.to_utc(start.time), end.zone.to_utc(end.time)) {
+                (Some(start), Some(end)) => end - start,
+                _ => continue,
+            },
+            (None, Some(duration)) => duration,
+            (None, None) if start.all_day => Duration::days(1),
+            (None, None) => Duration::zero(),
+        };
+        let starts = match event
+            .rrule
+            .as_deref()
+            .filter(|_| event.recurrence_id.is_none())
+            .and_then(|rrule| parse_rrule(rrule, start.zone))
+        {
+            Some(rule) => occurrences(start.time, start.zone, &rule, window_end),
+            None => vec![start.time],
+        };
+        let excluded: HashSet<Option<DateTime<Utc>>> = event
+            .exdates
+            .iter()
+            .map(|exdate| exdate.zone.to_utc(exdate.time))
+            .collect();
+
+        for occurrence in starts {
+            let Some(occurrence_start) = start.zone.to_utc(occurrence) else {
+                continue;
+            };
+            let moved_away = event.recurrence_id.is_none()
+                && moved.contains(&(event.uid.clone(), Some(occurrence_start)));
+            if event.cancelled || moved_away || excluded.contains(&Some(occurrence_start)) {
+                continue;
+            }
+            let occurrence_end = occurrence_start + length;
+            if occurrence_end <= window_start || occurrence_start >= window_end {
+                continue;
+            }
+            occurrences_in_window.push(CalendarEvent {
+                uid: event.uid.clone(),
+                title: event.title.clone(),
+                start: occurrence_start,
+                end: occurrence_end,
+                attendees: event.attendees.clone(),
+                location: event.location.clone(),
+                all_day: start.all_day,
+            });
+        }
+    }
+    occurrences_in_window.sort_by_key(|event| event.start);
+    Ok(occurrences_in_window)
+}
+
+/// The timed event overlapping the range the most, all-day events would match everything
+pub fn best_overlap(
+    events: &[CalendarEvent],
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Option<&CalendarEvent> {
+    events
+        .iter()
+        .filter(|event| !event.all_day)
+        .map(|event| (event, event.end.min(end) - event.start.max(start)))
+        .filter(|(_, overlap)| *overlap > Duration::zero())
+        .max_by_key(|(_, overlap)| *overlap)
+        .map(|(event, _)| event)
+}
+
+/// Events of all the calendars, the ones that can't be read are skipped with an error
+pub async fn fetch_events(
+    sources: &[String],
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+) -> (Vec<CalendarEvent>, Vec<anyhow::Error>) {
+    let mut events = Vec::new();
+    let mut errors = Vec::new();
+    for source in sources {
+        let parsed = fetch_calendar(source)
+            .await
+            .and_then(|ics| parse_ics(&ics, window_start, window_end));
+        match parsed {
+            Ok(parsed) => events.extend(parsed),
+            Err(e) => errors.push(anyhow!("calendar {}: {}", source, e)),
+        }
+    }
+    events.sort_by_key(|event| event.start);
+    (events, errors)
+}
+
+/// An event of a feed we publish
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedEvent {
+    /// Kept across fetches so calendar apps update the event instead of adding another
+    pub uid: String,
+    pub title: String,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub description: Option<String>,
+    pub categories: Vec<String>,
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Lines are at most 75 octets, longer ones continue on the next line after a space
+fn push_line(ics: &mut String, line: &str) {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            ics.push_str("\r\n ");
+            length = 1;
+        }
+        ics.push(c);
+        length += c.len_utf8();
+    }
+    ics.push_str("\r\n");
+}
+
+fn format_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Writes an iCalendar feed calendar apps can subscribe to, `stamp` is when it was generated
+pub fn write_ics(name: &str, events: &[FeedEvent], stamp: DateTime<Utc>) -> String {
+    let mut ics = String::new();
+    for line in [
+        "BEGIN:VCALENDAR",
+        "VERSION:2.0",
+        "PRODID:-//screenpipe//screenpipe//EN",
+        "CALSCALE:GREGORIAN",
+        "METHOD:PUBLISH",
+        // how often subscribed apps should fetch it again
+        "REFRESH-INTERVAL;VALUE=DURATION:PT1H",
+        "X-PUBLISHED-TTL:PT1H",
+    ] {
+        push_line(&mut ics, line);
+    }
+    push_line(&mut ics, &format!("X-WR-CALNAME:{}", escape(name)));
+    for event in events {
+        push_line(&mut ics, "BEGIN:VEVENT");
+        push_line(&mut ics, &format!("UID:{}", escape(&event.uid)));
+        push_line(&mut ics, &format!("DTSTAMP:{}", format_time(stamp)));
+        push_line(&mut ics, &format!("DTSTART:{}", format_time(event.start)));
+        push_line(&mut ics, &format!("DTEND:{}", format_time(event.end)));
+        push_line(&mut ics, &format!("SUMMARY:{}", escape(&event.title)));
+        if let Some(description) = &event.description {
+            push_line(&mut ics, &format!("DESCRIPTION:{}", escape(description)));
+        }
+        if !event.categories.is_empty() {
+            let categories: Vec<String> = event.categories.iter().map(|c| escape(c)).collect();
+            push_line(&mut ics, &format!("CATEGORIES:{}", categories.join(",")));
+        }
+        // the time already happened, it shouldn't show as busy to others
+        push_line(&mut ics, "TRANSP:TRANSPARENT");
+        push_line(&mut ics, "END:VEVENT");
+    }
+    push_line(&mut ics, "END:VCALENDAR");
+    ics
+}
@@ -0,0 +1,834 @@
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode, Url};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fmt;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+/// S3 refuses smaller parts, but for the last one
+pub const MIN_PART_SIZE: usize = 5 << 20;
+/// A part goes in one request, slow links need a while for it
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(600);
+/// Written in the collection of a WebDAV upload once every part is there
+pub const WEBDAV_COMPLETE: &str = "complete";
+
+/// Where the backups are uploaded, credentials are read from environment variables to keep
+/// them out of the file
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase", deny_unknown_fields)]
+pub enum BackupDestination {
+    /// Amazon S3 or a service speaking its API, like Backblaze B2, Cloudflare R2 or MinIO
+    S3 {
+        /// Like `https://s3.us-west-004.backblazeb2.com`, AWS when not given
+        #[serde(default)]
+        endpoint: Option<String>,
+        region: String,
+        bucket: String,
+        /// Put before the name of every object, like `laptop/`
+        #[serde(default)]
+        prefix: String,
+        access_key_id_env: String,
+        secret_access_key_env: String,
+        /// `https://endpoint/bucket/key` rather than `https://bucket.endpoint/key`, MinIO
+        /// needs it
+        #[serde(default)]
+        path_style: bool,
+    },
+    /// A collection of a WebDAV server, like Nextcloud. Each upload is a collection with
+    /// one file per part, put back together by `screenpipe-backup-restore`
+    WebDav {
+        /// Like `https://cloud.example.com/remote.php/dav/files/me/screenpipe/`
+        url: String,
+        #[serde(default)]
+        username: Option<String>,
+        #[serde(default)]
+        password_env: Option<String>,
+    },
+}
+
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name).with_context(|| format!("{} is not set", name))
+}
+
+/// The destination asked to slow down, with 429 or 503
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backpressure {
+    /// From the Retry-After header
+    pub retry_after: Option<Duration>,
+}
+
+impl fmt::Display for Backpressure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.retry_after {
+            Some(retry_after) => write!(
+                f,
+                "the backup destination asked to retry in {}s",
+                retry_after.as_secs()
+            ),
+            None => write!(f, "the backup destination asked to slow down"),
+        }
+    }
+}
+
+impl std::error::Error for Backpressure {}
+
+async fn check_response(response: Response) -> Result<Response> {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS || status == StatusCode::SERVICE_UNAVAILABLE {
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        return Err(Backpressure { retry_after }.into());
+    }
+    if !status.is_success() {
+        let body = response.text().await.unwrap_or_default();
+        bail!(
+            "backup destination answered {}: {}",
+            status,
+            body.chars().take(500).collect::<String>()
+        );
+    }
+    Ok(response)
+}
+
+async fn write_response(mut response: Response, output: &mut tokio::fs::File) -> Result<u64> {
+    let mut written = 0;
+    while let Some(chunk) = response.chunk().await? {
+        output.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    Ok(written)
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac takes any key");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Percent encoding of AWS, everything but letters, digits and `-_.~`, and `/` when
+/// `keep_slash`
+pub fn aws_uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(byte as char)
+            }
+            b'/' if keep_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+#[derive(Debug, Clone)]
+pub struct S3Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub region: String,
+}
+
+/// A request to sign with AWS signature version 4. `path` and `query` are not encoded yet,
+/// `headers` are all signed and hold at least host, x-amz-date and x-amz-content-sha256
+pub struct S3Request<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub query: &'a [(&'a str, &'a str)],
+    pub headers: &'a [(&'a str, &'a str)],
+    pub payload_sha256: &'a str,
+}
+
+/// The Authorization header of `request` signed at `at`
+pub fn sign_s3_request(
+    credentials: &S3Credentials,
+    request: &S3Request,
+    at: DateTime<Utc>,
+) -> String {
+    let date = at.format("%Y%m%d").to_string();
+    let timestamp = at.format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut query: Vec<(String, String)> = request
+        .query
+        .iter()
+        .map(|(key, value)| (aws_uri_encode(key, false), aws_uri_encode(value, false)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&");
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    headers.sort();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method,
+        aws_uri_encode(request.path, true),
+        canonical_query,
+        canonical_headers,
+        signed_headers,
+        request.payload_sha256
+    );
+
+    let scope = format!("{}/{}/s3/aws4_request", date, credentials.region);
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+        timestamp,
+        scope,
+        sha256_hex(canonical_request.as_bytes())
+    );
+    let mut key = hmac_sha256(
+        format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+        &date,
+    );
+    for part in [credentials.region.as_str(), "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part);
+    }
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{},SignedHeaders={},Signature={}",
+        credentials.access_key_id,
+        scope,
+        signed_headers,
+        hex(&hmac_sha256(&key, &string_to_sign))
+    )
+}
+
+/// Text of the `<name>` elements of an S3 or WebDAV answer, whatever their namespace
+/// prefix, like `<d:href>` for `href`
+pub fn xml_elements<'a>(xml: &'a str, name: &str) -> Vec<&'a str> {
+    let mut elements = Vec::new();
+    let mut rest = xml;
+    while let Some(open) = rest.find('<') {
+        rest = &rest[open + 1..];
+        let tag_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let tag = &rest[..tag_end];
+        let local = tag.rsplit(':').next().unwrap_or(tag);
+        if tag.is_empty() || local != name {
+            continue;
+        }
+        let Some(content_start) = rest.find('>') else {
+            break;
+        };
+        if rest[..content_start].ends_with('/') {
+            elements.push("");
+            continue;
+        }
+        let content = &rest[content_start + 1..];
+        let close = format!("</{}>", tag);
+        let Some(content_end) = content.find(&close) else {
+            break;
+        };
+        elements.push(&content[..content_end]);
+        rest = &content[content_end + close.len()..];
+    }
+    elements
+}
+
+/// Text of the first `<name>` element of an S3 answer
+pub fn xml_element<'a>(xml: &'a str, name: &str) -> Option<&'a str> {
+    xml_elements(xml, name).into_iter().next()
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = (bytes[i] == b'%')
+            .then(|| value.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match hex {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Objects of a ListObjectsV2 answer, with the token of the next page when there is one
+pub fn parse_s3_listing(xml: &str) -> (Vec<BackupObject>, Option<String>) {
+    let objects = xml_elements(xml, "Contents")
+        .into_iter()
+        .filter_map(|contents| {
+            Some(BackupObject {
+                key: xml_unescape(xml_element(contents, "Key")?),
+                size: xml_element(contents, "Size")?.parse().ok()?,
+            })
+        })
+        .collect();
+    let next = (xml_element(xml, "IsTruncated") == Some("true"))
+        .then(|| xml_element(xml, "NextContinuationToken").map(xml_unescape))
+        .flatten();
+    (objects, next)
+}
+
+/// Members of a PROPFIND answer as (path, size), collections end with `/`
+pub fn parse_propfind(xml: &str) -> Vec<(String, Option<u64>)> {
+    xml_elements(xml, "response")
+        .into_iter()
+        .filter_map(|response| {
+            let href = xml_unescape(xml_element(response, "href")?.trim());
+            // absolute urls or paths, depending on the server
+            let path = match Url::parse(&href) {
+                Ok(url) => url.path().to_string(),
+                Err(_) => href,
+            };
+            let size =
+                xml_element(response, "getcontentlength").and_then(|size| size.trim().parse().ok());
+            Some((percent_decode(&path), size))
+        })
+        .collect()
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Body of CompleteMultipartUpload, `etags` are of parts 1, 2, ...
+pub fn complete_multipart_body(etags: &[String]) -> String {
+    let mut body = String::from("<CompleteMultipartUpload>");
+    for (i, etag) in etags.iter().enumerate() {
+        body.push_str(&format!(
+            "<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>",
+            i + 1,
+            xml_escape(etag)
+        ));
+    }
+    body.push_str("</CompleteMultipartUpload>");
+    body
+}
+
+enum Target {
+    S3 {
+        credentials: S3Credentials,
+        /// Scheme and authority the requests go to
+        base: Url,
+        /// Before the key in the path, the bucket with path style
+        path_prefix: String,
+        prefix: String,
+    },
+    WebDav {
+        url: Url,
+        username: Option<String>,
+        password: Option<String>,
+    },
+}
+
+/// An object uploaded, `key` is without the prefix of the destination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BackupObject {
+    pub key: String,
+    /// Bytes uploaded
+    pub size: u64,
+}
+
+/// Uploads files in parts that can be resumed after a restart, by keeping the upload id and
+/// what `upload_part` gave back for each part
+pub struct BackupClient {
+    http: Client,
+    target: Target,
+}
+
+impl BackupClient {
+    pub fn new(destination: &BackupDestination) -> Result<Self> {
+        let target = match destination {
+            BackupDestination::S3 {
+                endpoint,
+                region,
+                bucket,
+                prefix,
+                access_key_id_env,
+                secret_access_key_env,
+                path_style,
+            } => {
+                let endpoint = endpoint
+                    .clone()
+                    .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", region));
+                let mut base = Url::parse(&endpoint)
+                    .with_context(|| format!("invalid S3 endpoint: {}", endpoint))?;
+                let path_prefix = if *path_style {
+                    format!("/{}", bucket)
+                } else {
+                    let host = base
+                        .host_str()
+                        .ok_or_else(|| anyhow!("the S3 endpoint has no host: {}", endpoint))?;
+                    base.set_host(Some(&format!("{}.{}", bucket, host)))?;
+                    String::new()
+                };
+                Target::S3 {
+                    credentials: S3Credentials {
+                        access_key_id: env_var(access_key_id_env)?,
+                        secret_access_key: env_var(secret_access_key_env)?,
+                        region: region.clone(),
+                    },
+                    base,
+                    path_prefix,
+                    prefix: prefix.clone(),
+                }
+            }
+            BackupDestination::WebDav {
+                url,
+                username,
+                password_env,
+            } => {
+                let mut url =
+                    Url::parse(url).with_context(|| format!("invalid WebDAV url: {}", url))?;
+                if !url.path().ends_with('/') {
+                    url.set_path(&format!("{}/", url.path()));
+                }
+                Target::WebDav {
+                    url,
+                    username: username.clone(),
+                    password: password_env.as_deref().map(env_var).transpose()?,
+                }
+            }
+        };
+        Ok(BackupClient {
+            http: Client::builder().timeout(REQUEST_TIMEOUT).build()?,
+            target,
+        })
+    }
+
+    /// Path of the object `key` in the bucket, before it's encoded
+    fn s3_path(&self, key: &str) -> String {
+        match &self.target {
+            Target::S3 {
+                path_prefix,
+                prefix,
+                ..
+            } => format!("{}/{}{}", path_prefix, prefix, key),
+            Target::WebDav { .. } => unreachable!("only called for S3"),
+        }
+    }
+
+    async fn s3(
+        &self,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+        body: Vec<u8>,
+    ) -> Result<Response> {
+        let Target::S3 {
+            credentials, base, ..
+        } = &self.target
+        else {
+            unreachable!("only called for S3");
+        };
+        let host = match base.port() {
+            Some(port) => format!("{}:{}", base.host_str().unwrap_or_default(), port),
+            None => base.host_str().unwrap_or_default().to_string(),
+        };
+        let payload_sha256 = sha256_hex(&body);
+        let now = Utc::now();
+        let timestamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let headers = [
+            ("host", host.as_str()),
+            ("x-amz-content-sha256", payload_sha256.as_str()),
+            ("x-amz-date", timestamp.as_str()),
+        ];
+        let authorization = sign_s3_request(
+            credentials,
+            &S3Request {
+                method: method.as_str(),
+                path,
+                query,
+                headers: &headers,
+                payload_sha256: &payload_sha256,
+            },
+            now,
+        );
+        let mut url = base.clone();
+        url.set_path(&aws_uri_encode(path, true));
+        let query = query
+            .iter()
+            .map(|(key, value)| {
+                if value.is_empty() {
+                    aws_uri_encode(key, false)
+                } else {
+                    format!(
+                        "{}={}",
+                        aws_uri_encode(key, false),
+                        aws_uri_encode(value, false)
+                    )
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        url.set_query((!query.is_empty()).then_some(query.as_str()));
+        let response = self
+            .http
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_sha256)
+            .header("x-amz-date", timestamp)
+            .header(reqwest::header::AUTHORIZATION, authorization)
+            .body(body)
+            .send()
+            .await?;
+        check_response(response).await
+    }
+
+    fn webdav(&self, method: Method, path: &str) -> Result<RequestBuilder> {
+        let Target::WebDav {
+            url,
+            username,
+            password,
+        } = &self.target
+        else {
+            unreachable!("only called for WebDAV");
+        };
+        let mut request = self.http.request(method, url.join(path)?);
+        if let Some(username) = username {
+            request = request.basic_auth(username, password.as_ref());
+        }
+        Ok(request)
+    }
+
+    /// Creates the collections `key` goes in, WebDAV doesn't create them on PUT
+    async fn webdav_collections(&self, key: &str) -> Result<()> {
+        let mut path = String::new();
+        for segment in key.split('/').filter(|segment| !segment.is_empty()) {
+            path.push_str(&aws_uri_encode(segment, false));
+            path.push('/');
+            let response = self
+                .webdav(Method::from_bytes(b"MKCOL")?, &path)?
+                .send()
+                .await?;
+            // 405 when it's already there
+            if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+                check_response(response).await?;
+            }
+        }
+        Ok(())
+    }
+
+    fn webdav_part_path(key: &str, name: &str) -> String {
+        format!("{}/{}", aws_uri_encode(key, true), name)
+    }
+
+    /// Starts the upload of `key`, the id given back goes with each of its parts
+    pub async fn start_upload(&self, key: &str) -> Result<String> {
+        match &self.target {
+            Target::S3 { .. } => {
+                let response = self
+                    .s3(
+                        Method::POST,
+                        &self.s3_path(key),
+                        &[("uploads", "")],
+                        Vec::new(),
+                    )
+                    .await?;
+                let body = response.text().await?;
+                xml_element(&body, "UploadId")
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("no upload id in the answer of S3: {}", body))
+            }
+            Target::WebDav { .. } => {
+                self.webdav_collections(key).await?;
+                Ok(String::new())
+            }
+        }
+    }
+
+    /// Uploads part `number`, counted from 1. Gives back what `complete_upload` needs of it
+    pub async fn upload_part(
+        &self,
+        key: &str,
+        upload_id: &str,
+        number: u32,
+        data: Vec<u8>,
+    ) -> Result<String> {
+        let etag = match &self.target {
+            Target::S3 { .. } => {
+                let number = number.to_string();
+                let response = self
+                    .s3(
+                        Method::PUT,
+                        &self.s3_path(key),
+                        &[("partNumber", &number), ("uploadId", upload_id)],
+                        data,
+                    )
+                    .await?;
+                response
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("S3 gave no etag for part {} of {}", number, key))?
+            }
+            Target::WebDav { .. } => {
+                let response = self
+                    .webdav(
+                        Method::PUT,
+                        &Self::webdav_part_path(key, &format!("{:05}", number)),
+                    )?
+                    .body(data)
+                    .send()
+                    .await?;
+                let response = check_response(response).await?;
+                response
+                    .headers()
+                    .get(reqwest::header::ETAG)
+                    .and_then(|etag| etag.to_str().ok())
+                    .unwrap_or_default()
+                    .to_string()
+            }
+        };
+        Ok(etag)
+    }
+
+    /// Ends the upload once every part is there, `parts` are what `upload_part` gave back
+    pub async fn complete_upload(
+        &self,
+        key: &str,
+        upload_id: &str,
+        parts: &[String],
+    ) -> Result<()> {
+        match &self.target {
+            Target::S3 { .. } => {
+                let response = self
+                    .s3(
+                        Method::POST,
+                        &self.s3_path(key),
+                        &[("uploadId", upload_id)],
+                        complete_multipart_body(parts).into_bytes(),
+                    )
+                    .await?;
+                // errors can come with a 200 once S3 started answering
+                let body = response.text().await?;
+                if let Some(message) = xml_element(&body, "Message") {
+                    bail!("S3 failed to complete {}: {}", key, message);
+                }
+            }
+            Target::WebDav { .. } => {
+                let response = self
+                    .webdav(Method::PUT, &Self::webdav_part_path(key, WEBDAV_COMPLETE))?
+                    .body(parts.len().to_string())
+                    .send()
+                    .await?;
+                check_response(response).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Objects whose key starts with `prefix`, WebDAV uploads are listed once completed
+    pub async fn list_objects(&self, prefix: &str) -> Result<Vec<BackupObject>> {
+        match &self.target {
+            Target::S3 {
+                path_prefix,
+                prefix: destination_prefix,
+                ..
+            } => {
+                let full_prefix = format!("{}{}", destination_prefix, prefix);
+                let mut objects = Vec::new();
+                let mut token: Option<String> = None;
+                loop {
+                    let mut query = vec![("list-type", "2"), ("prefix", full_prefix.as_str())];
+                    if let Some(token) = &token {
+                        query.push(("continuation-token", token.as_str()));
+                    }
+                    let response = self
+                        .s3(
+                            Method::GET,
+                            &format!("{}/", path_prefix),
+                            &query,
+                            Vec::new(),
+                        )
+                        .await?;
+                    let (page, next) = parse_s3_listing(&response.text().await?);
+                    objects.extend(page.into_iter().map(|object| {
+                        BackupObject {
+                            key: object
+                                .key
+                                .strip_prefix(destination_prefix.as_str())
+                                .unwrap_or(&object.key)
+                                .to_string(),
+                            size: object.size,
+                        }
+                    }));
+                    match next {
+                        Some(next) => token = Some(next),
+                        None => return Ok(objects),
+                    }
+                }
+            }
+            Target::WebDav { .. } => {
+                let mut objects = Vec::new();
+                for (path, _) in self.propfind(prefix).await? {
+                    let Some(key) = path.strip_suffix('/') else {
+                        continue;
+                    };
+                    if let Some(size) = self.webdav_upload_size(key).await? {
+                        objects.push(BackupObject {
+                            key: key.to_string(),
+                            size,
+                        });
+                    }
+                }
+                Ok(objects)
+            }
+        }
+    }
+
+    /// Members of the collection `path` relative to the url of the destination, without
+    /// the collection itself
+    async fn propfind(&self, path: &str) -> Result<Vec<(String, Option<u64>)>> {
+        let Target::WebDav { url, .. } = &self.target else {
+            unreachable!("only called for WebDAV");
+        };
+        let collection = format!("{}/", aws_uri_encode(path.trim_end_matches('/'), true));
+        let response = self
+            .webdav(Method::from_bytes(b"PROPFIND")?, &collection)?
+            .header("Depth", "1")
+            .send()
+            .await?;
+        // nothing uploaded yet
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        let body = check_response(response).await?.text().await?;
+        let base = percent_decode(url.path());
+        Ok(parse_propfind(&body)
+            .into_iter()
+            .filter_map(|(member, size)| {
+                let relative = member.strip_prefix(&base)?.to_string();
+                (relative.trim_end_matches('/') != path.trim_end_matches('/'))
+                    .then_some((relative, size))
+            })
+            .collect())
+    }
+
+    /// Parts of a WebDAV upload in order with their size, None until it's completed
+    async fn webdav_parts(&self, key: &str) -> Result<Option<Vec<(String, u64)>>> {
+        let members = self.propfind(key).await?;
+        let name = |path: &str| path.rsplit('/').next().unwrap_or_default().to_string();
+        if !members
+            .iter()
+            .any(|(path, _)| name(path) == WEBDAV_COMPLETE)
+        {
+            return Ok(None);
+        }
+        let mut parts: Vec<(String, u64)> = members
+            .into_iter()
+            .filter(|(path, _)| {
+                let name = name(path);
+                !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_digit())
+            })
+            .map(|(path, size)| (path, size.unwrap_or_default()))
+            .collect();
+        parts.sort();
+        Ok(Some(parts))
+    }
+
+    async fn webdav_upload_size(&self, key: &str) -> Result<Option<u64>> {
+        Ok(self
+            .webdav_parts(key)
+            .await?
+            .map(|parts| parts.iter().map(|(_, size)| size).sum()))
+    }
+
+    /// Writes the object `key` to `output` as it was uploaded, its parts one after the
+    /// other. Returns the bytes written
+    pub async fn download(&self, key: &str, output: &mut tokio::fs::File) -> Result<u64> {
+        let mut written = 0;
+        match &self.target {
+            Target::S3 { .. } => {
+                let response = self
+                    .s3(Method::GET, &self.s3_path(key), &[], Vec::new())
+                    .await?;
+                written += write_response(response, output).await?;
+            }
+            Target::WebDav { .. } => {
+                let parts = self
+                    .webdav_parts(key)
+                    .await?
+                    .ok_or_else(|| anyhow!("the upload of {} wasn't completed", key))?;
+                for (path, _) in parts {
+                    let response = self
+                        .webdav(Method::GET, &aws_uri_encode(&path, true))?
+                        .send()
+                        .await?;
+                    written += write_response(check_response(response).await?, output).await?;
+                }
+            }
+        }
+        output.flush().await?;
+        Ok(written)
+    }
+
+    /// Drops the parts of an upload that won't be completed
+    pub async fn abort_upload(&self, key: &str, upload_id: &str) -> Result<()> {
+        match &self.target {
+            Target::S3 { .. } => {
+                self.s3(
+                    Method::DELETE,
+                    &self.s3_path(key),
+                    &[("uploadId", upload_id)],
+                    Vec::new(),
+                )
+                .await?;
+            }
+            Target::WebDav { .. } => {
+                let response = self
+                    .webdav(Method::DELETE, &format!("{}/", aws_uri_encode(key, true)))?
+                    .send()
+                    .await?;
+                // already gone
+                if response.status() != StatusCode::NOT_FOUND {
+                    check_response(response).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
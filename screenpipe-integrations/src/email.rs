@@ -0,0 +1,425 @@
+use crate::vault_export::{format_duration, local_time, NoteContent};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use image::{codecs::jpeg::JpegEncoder, imageops::FilterType, DynamicImage};
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// Frames are shrunk to this width, mail apps show them about this wide anyway
+const THUMBNAIL_WIDTH: u32 = 640;
+const THUMBNAIL_QUALITY: u8 = 75;
+const SMTP_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailSecurity {
+    /// TLS from the start, usually on port 465
+    Tls,
+    /// Plain connection upgraded with STARTTLS, usually on port 587
+    #[default]
+    StartTls,
+    /// No encryption, for a relay on this machine
+    None,
+}
+
+impl EmailSecurity {
+    pub fn default_port(&self) -> u16 {
+        match self {
+            EmailSecurity::Tls => 465,
+            EmailSecurity::StartTls => 587,
+            EmailSecurity::None => 25,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Where emails are sent and which ones, with the SMTP server to send them through
+#[derive(Debug, Clone, Deserialize)]
+pub struct EmailDestination {
+    pub name: String,
+    pub smtp_host: String,
+    /// The usual port of `security` when not given
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub security: EmailSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    /// Environment variable holding the password, to keep it out of the file
+    #[serde(default)]
+    pub password_env: Option<String>,
+    /// Like `screenpipe <me@example.com>`
+    pub from: String,
+    pub to: Vec<String>,
+    /// Local hour the digest of the day is sent at, no digest when not given
+    #[serde(default)]
+    pub digest_hour: Option<u32>,
+    /// Sends the alerts of the watches
+    #[serde(default)]
+    pub alerts: bool,
+    /// Names of the watches whose alerts are sent, all of them when empty
+    #[serde(default)]
+    pub watches: Vec<String>,
+    /// Frames seen in the emails
+    #[serde(default = "default_true")]
+    pub thumbnails: bool,
+}
+
+impl EmailDestination {
+    pub fn validate(&self) -> Result<()> {
+        self.from
+            .parse::<Mailbox>()
+            .with_context(|| format!("invalid from address of {}: {}", self.name, self.from))?;
+        if self.to.is_empty() {
+            bail!("{} has no address to send to", self.name);
+        }
+        for to in &self.to {
+            to.parse::<Mailbox>()
+                .with_context(|| format!("invalid address of {}: {}", self.name, to))?;
+        }
+        if self.digest_hour.is_some_and(|hour| hour > 23) {
+            bail!("digest_hour of {} is not an hour of the day", self.name);
+        }
+        if let Some(env) = &self.password_env {
+            std::env::var(env).with_context(|| format!("{} is not set", env))?;
+        }
+        Ok(())
+    }
+
+    pub fn password(&self) -> Option<String> {
+        self.password_env
+            .as_ref()
+            .and_then(|env| std::env::var(env).ok())
+            .or_else(|| self.password.clone())
+    }
+
+    pub fn sends_alerts_of(&self, watch_name: &str) -> bool {
+        self.alerts
+            && (self.watches.is_empty()
+                || self
+                    .watches
+                    .iter()
+                    .any(|watch| watch.eq_ignore_ascii_case(watch_name)))
+    }
+}
+
+/// A jpeg the html shows with `cid:<content_id>`
+#[derive(Debug, Clone)]
+pub struct InlineImage {
+    pub content_id: String,
+    pub jpeg: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub subject: String,
+    pub html: String,
+    /// For the mail apps that don't show html
+    pub text: String,
+    pub images: Vec<InlineImage>,
+}
+
+/// A match of a watch, the way it's emailed
+#[derive(Debug, Clone)]
+pub struct EmailAlert {
+    pub watch_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub matched_text: String,
+    pub excerpt: String,
+}
+
+fn frame_content_id(frame_id: i64) -> String {
+    format!("frame-{}@screenpipe", frame_id)
+}
+
+pub fn thumbnail_jpeg(image: &DynamicImage) -> Result<Vec<u8>> {
+    let thumbnail = if image.width() > THUMBNAIL_WIDTH {
+        image.resize(THUMBNAIL_WIDTH, u32::MAX, FilterType::Triangle)
+    } else {
+        image.clone()
+    };
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, THUMBNAIL_QUALITY)
+        .encode_image(&thumbnail.to_rgb8())?;
+    Ok(jpeg)
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn html_page(title: &str, body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head><meta charset="utf-8"><title>{title}</title></head>
+<body style="font-family: -apple-system, Segoe UI, Helvetica, Arial, sans-serif; color: #1a1a1a; max-width: 680px; margin: 0 auto; padding: 16px;">
+<h1 style="font-size: 20px;">{title}</h1>
+{body}
+<p style="color: #888; font-size: 12px; margin-top: 32px;">Sent by screenpipe</p>
+</body>
+</html>
+"#,
+        title = escape(title),
+        body = body
+    )
+}
+
+fn image_tag(content_id: &str) -> String {
+    format!(
+        r#"<p><img src="cid:{}" width="{}" style="max-width: 100%; border: 1px solid #ddd; border-radius: 4px;" alt="frame"></p>"#,
+        escape(content_id),
+        THUMBNAIL_WIDTH
+    )
+}
+
+/// The digest of the day, `thumbnails` has the jpeg of the frames of the moments that could
+/// be read
+pub fn render_digest(
+    date: NaiveDate,
+    content: &NoteContent,
+    mut thumbnails: HashMap<i64, Vec<u8>>,
+) -> Email {
+    let subject = format!("screenpipe digest of {}", date.format("%A %-d %B"));
+    let mut html = String::new();
+    let mut text = format!("{}\n", subject);
+    let mut images = Vec::new();
+    if content.is_empty() {
+        html.push_str("<p>Nothing recorded.</p>\n");
+        text.push_str("\nNothing recorded.\n");
+    }
+
+    if !content.digest.is_empty() {
+        html.push_str("<h2 style=\"font-size: 16px;\">Time spent</h2>\n<ul>\n");
+        text.push_str("\nTime spent\n");
+        for entry in &content.digest {
+            let duration = format_duration(entry.duration_secs);
+            let _ = writeln!(
+                html,
+                "<li>{}: <b>{}</b></li>",
+                escape(&entry.label),
+                duration
+            );
+            let _ = writeln!(text, "- {}: {}", entry.label, duration);
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if !content.last_week.is_empty() {
+        html.push_str("<h2 style=\"font-size: 16px;\">Last week</h2>\n<ul>\n");
+        text.push_str("\nLast week\n");
+        for line in &content.last_week {
+            let _ = writeln!(html, "<li>{}</li>", escape(line));
+            let _ = writeln!(text, "- {}", line);
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if !content.meetings.is_empty() {
+        html.push_str("<h2 style=\"font-size: 16px;\">Meetings</h2>\n");
+        text.push_str("\nMeetings\n");
+        for meeting in &content.meetings {
+            let heading = meeting.heading(content.timezone.as_ref());
+            let _ = writeln!(
+                html,
+                "<h3 style=\"font-size: 14px;\">{}</h3>",
+                escape(&heading)
+            );
+            let _ = writeln!(text, "\n{}", heading);
+            if !meeting.attendees.is_empty() {
+                let attendees = meeting.attendees.join(", ");
+                let _ = writeln!(
+                    html,
+                    "<p style=\"color: #555;\">With {}</p>",
+                    escape(&attendees)
+                );
+                let _ = writeln!(text, "With {}", attendees);
+            }
+            let _ = writeln!(html, "<p>{}</p>", escape(meeting.summary.trim()));
+            let _ = writeln!(text, "{}", meeting.summary.trim());
+            for (label, items) in [
+                ("Decisions", &meeting.decisions),
+                ("Action items", &meeting.action_items),
+            ] {
+                if items.is_empty() {
+                    continue;
+                }
+                let _ = writeln!(html, "<p><b>{}</b></p>\n<ul>", label);
+                let _ = writeln!(text, "{}:", label);
+                for item in items {
+                    let _ = writeln!(html, "<li>{}</li>", escape(item));
+                    let _ = writeln!(text, "- {}", item);
+                }
+                html.push_str("</ul>\n");
+            }
+        }
+    }
+
+    if !content.moments.is_empty() {
+        html.push_str("<h2 style=\"font-size: 16px;\">Moments</h2>\n");
+        text.push_str("\nMoments\n");
+        for moment in &content.moments {
+            let heading = format!(
+                "{} {} - {} - {}",
+                local_time(&moment.timestamp, content.timezone.as_ref()),
+                moment.tag,
+                moment.app_name,
+                moment.window_name
+            );
+            let _ = writeln!(
+                html,
+                "<h3 style=\"font-size: 14px;\">{}</h3>",
+                escape(&heading)
+            );
+            let _ = writeln!(text, "\n{}", heading);
+            if !moment.details.is_empty() {
+                html.push_str("<ul>\n");
+                for detail in &moment.details {
+                    let _ = writeln!(html, "<li>{}</li>", escape(detail));
+                    let _ = writeln!(text, "- {}", detail);
+                }
+                html.push_str("</ul>\n");
+            }
+            let Some(frame_id) = moment.frame_id else {
+                continue;
+            };
+            if let Some(jpeg) = thumbnails.remove(&frame_id) {
+                let content_id = frame_content_id(frame_id);
+                html.push_str(&image_tag(&content_id));
+                html.push('\n');
+                images.push(InlineImage { content_id, jpeg });
+            }
+        }
+    }
+
+    Email {
+        html: html_page(&subject, &html),
+        subject,
+        text,
+        images,
+    }
+}
+
+/// `thumbnail` is the jpeg of the frame the text was seen on
+pub fn render_alert(alert: &EmailAlert, thumbnail: Option<(i64, Vec<u8>)>) -> Email {
+    let subject = format!(
+        "screenpipe: {} matched \"{}\"",
+        alert.watch_name, alert.matched_text
+    );
+    let time = alert
+        .timestamp
+        .with_timezone(&Local)
+        .format("%Y-%m-%d %H:%M:%S")
+        .to_string();
+    let place = match (&alert.app_name, &alert.window_name) {
+        (Some(app_name), Some(window_name)) => format!("{} - {}", app_name, window_name),
+        (Some(name), None) | (None, Some(name)) => name.clone(),
+        (None, None) => "audio".to_string(),
+    };
+
+    let mut html = String::new();
+    let _ = writeln!(
+        html,
+        "<p style=\"color: #555;\">{} in {}</p>",
+        escape(&time),
+        escape(&place)
+    );
+    let _ = writeln!(
+        html,
+        "<blockquote style=\"margin: 0; padding: 8px 12px; border-left: 3px solid #e0a800; background: #fff8e1;\">{}</blockquote>",
+        escape(&alert.excerpt)
+    );
+    let images: Vec<InlineImage> = thumbnail
+        .map(|(frame_id, jpeg)| InlineImage {
+            content_id: frame_content_id(frame_id),
+            jpeg,
+        })
+        .into_iter()
+        .collect();
+    for image in &images {
+        html.push_str(&image_tag(&image.content_id));
+        html.push('\n');
+    }
+    let text = format!(
+        "{}\n\n{} in {}\n\n{}\n",
+        subject, time, place, alert.excerpt
+    );
+
+    Email {
+        html: html_page(&subject, &html),
+        subject,
+        text,
+        images,
+    }
+}
+
+/// The email as a message to the destination, html with its images next to the plain text
+pub fn build_message(destination: &EmailDestination, email: &Email) -> Result<Message> {
+    let mut builder = Message::builder()
+        .from(destination.from.parse()?)
+        .subject(email.subject.clone());
+    for to in &destination.to {
+        builder = builder.to(to.parse()?);
+    }
+    let mut related = MultiPart::related().singlepart(SinglePart::html(email.html.clone()));
+    for image in &email.images {
+        related = related.singlepart(
+            Attachment::new_inline(image.content_id.clone())
+                .body(image.jpeg.clone(), ContentType::parse("image/jpeg")?),
+        );
+    }
+    Ok(builder.multipart(
+        MultiPart::alternative()
+            .singlepart(SinglePart::plain(email.text.clone()))
+            .multipart(related),
+    )?)
+}
+
+pub async fn send_email(destination: &EmailDestination, email: &Email) -> Result<()> {
+    let message = build_message(destination, email)?;
+    let host = destination.smtp_host.as_str();
+    let mut transport = match destination.security {
+        EmailSecurity::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(host)?,
+        EmailSecurity::StartTls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?,
+        EmailSecurity::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host),
+    }
+    .port(
+        destination
+            .smtp_port
+            .unwrap_or_else(|| destination.security.default_port()),
+    )
+    .timeout(Some(SMTP_TIMEOUT));
+    if let (Some(username), Some(password)) = (&destination.username, destination.password()) {
+        transport = transport.credentials(Credentials::new(username.clone(), password));
+    }
+    transport
+        .build()
+        .send(message)
+        .await
+        .with_context(|| format!("failed to send email to {}", destination.name))?;
+    Ok(())
+}
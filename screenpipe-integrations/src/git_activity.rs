@@ -0,0 +1,220 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GitEventKind {
+    /// Also amends, cherry-picks and merge commits
+    Commit,
+    /// Another branch or commit was checked out
+    Checkout,
+    Merge,
+    Pull,
+    /// A rebase that finished, its steps aren't kept
+    Rebase,
+    Reset,
+}
+
+impl GitEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            GitEventKind::Commit => "commit",
+            GitEventKind::Checkout => "checkout",
+            GitEventKind::Merge => "merge",
+            GitEventKind::Pull => "pull",
+            GitEventKind::Rebase => "rebase",
+            GitEventKind::Reset => "reset",
+        }
+    }
+}
+
+impl FromStr for GitEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "commit" => Ok(GitEventKind::Commit),
+            "checkout" => Ok(GitEventKind::Checkout),
+            "merge" => Ok(GitEventKind::Merge),
+            "pull" => Ok(GitEventKind::Pull),
+            "rebase" => Ok(GitEventKind::Rebase),
+            "reset" => Ok(GitEventKind::Reset),
+            _ => bail!("unknown git event kind: {}", s),
+        }
+    }
+}
+
+/// A move of HEAD, as git wrote it in the reflog
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReflogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub kind: GitEventKind,
+    /// The commit HEAD was at before, zeros for the first one of the repository
+    pub previous_commit: String,
+    pub commit: String,
+    pub author: String,
+    /// The subject of the commit, or what git says of the checkout, merge or reset
+    pub message: String,
+    /// Branch checked out then, when it could be told
+    pub branch: Option<String>,
+}
+
+/// The kind of move of a reflog action like `commit (amend)`, none for the steps of rebases and
+/// the other moves
+fn classify(action: &str) -> Option<GitEventKind> {
+    let is_finished = !action.contains('(') || action.ends_with("(finish)");
+    if action.starts_with("commit") || action.starts_with("cherry-pick") {
+        Some(GitEventKind::Commit)
+    } else if action == "checkout" {
+        Some(GitEventKind::Checkout)
+    } else if action.starts_with("merge") {
+        Some(GitEventKind::Merge)
+    } else if action.starts_with("pull") && is_finished {
+        Some(GitEventKind::Pull)
+    } else if action.starts_with("rebase") && action.ends_with("(finish)") {
+        Some(GitEventKind::Rebase)
+    } else if action == "reset" {
+        Some(GitEventKind::Reset)
+    } else {
+        None
+    }
+}
+
+/// `<previous> <commit> <name> <<email>> <unix time> <offset>\t<action>: <message>` lines of
+/// `.git/logs/HEAD`, the oldest first. The branch of a commit is the one checked out last
+pub fn parse_reflog(reflog: &str) -> Vec<ReflogEntry> {
+    let mut entries = Vec::new();
+    let mut branch: Option<String> = None;
+    for line in reflog.lines() {
+        let Some((meta, action_message)) = line.split_once('\t') else {
+            continue;
+        };
+        let mut parts = meta.splitn(3, ' ');
+        let (Some(previous_commit), Some(commit), Some(identity)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            continue;
+        };
+        let Some((author, time)) = identity.rsplit_once('>') else {
+            continue;
+        };
+        let Some(timestamp) = time
+            .split_whitespace()
+            .next()
+            .and_then(|seconds| seconds.parse().ok())
+            .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        else {
+            continue;
+        };
+        let (action, message) = action_message
+            .split_once(": ")
+            .unwrap_or((action_message, ""));
+        let Some(kind) = classify(action) else {
+            continue;
+        };
+        if kind == GitEventKind::Checkout {
+            branch = message
+                .rsplit_once(" to ")
+                .map(|(_, to)| to.trim().to_string());
+        }
+        entries.push(ReflogEntry {
+            timestamp,
+            kind,
+            previous_commit: previous_commit.to_string(),
+            commit: commit.to_string(),
+            author: author
+                .split_once(" <")
+                .map_or(author, |(name, _)| name)
+                .trim()
+                .to_string(),
+            message: message.to_string(),
+            branch: branch.clone(),
+        });
+    }
+    entries
+}
+
+/// The git directory of a repository: its `.git`, the one a worktree's `.git` file points to,
+/// or the repository itself when it's bare
+pub fn git_dir(repository: &Path) -> Result<PathBuf> {
+    let dot_git = repository.join(".git");
+    if dot_git.is_dir() {
+        return Ok(dot_git);
+    }
+    if dot_git.is_file() {
+        let content = std::fs::read_to_string(&dot_git)?;
+        let dir = content
+            .trim()
+            .strip_prefix("gitdir: ")
+            .with_context(|| format!("{} doesn't point to a git directory", dot_git.display()))?;
+        return Ok(repository.join(dir));
+    }
+    if repository.join("HEAD").is_file() && repository.join("objects").is_dir() {
+        return Ok(repository.to_path_buf());
+    }
+    bail!("{} is not a git repository", repository.display())
+}
+
+/// The branch each commit was made on, from the reflogs of the branches. Their other lines, like
+/// `branch: Created from main`, point at commits made elsewhere
+fn branch_commits(git_dir: &Path) -> HashMap<String, String> {
+    let heads = git_dir.join("logs/refs/heads");
+    let mut commits = HashMap::new();
+    let mut dirs = vec![heads.clone()];
+    while let Some(dir) = dirs.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                dirs.push(path);
+                continue;
+            }
+            let (Ok(branch), Ok(reflog)) =
+                (path.strip_prefix(&heads), std::fs::read_to_string(&path))
+            else {
+                continue;
+            };
+            // like feature/login, with / on windows as well
+            let branch = branch
+                .components()
+                .map(|part| part.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            for line in reflog.lines() {
+                let Some((meta, action_message)) = line.split_once('\t') else {
+                    continue;
+                };
+                let action = action_message
+                    .split_once(": ")
+                    .map_or(action_message, |(action, _)| action);
+                if let (Some(commit), Some(_)) = (meta.split(' ').nth(1), classify(action)) {
+                    commits.insert(commit.to_string(), branch.clone());
+                }
+            }
+        }
+    }
+    commits
+}
+
+/// The reflog of HEAD of the repository, with the branch of each commit
+pub fn read_git_activity(git_dir: &Path) -> Result<Vec<ReflogEntry>> {
+    let path = git_dir.join("logs/HEAD");
+    let reflog = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    let mut entries = parse_reflog(&reflog);
+    let commits = branch_commits(git_dir);
+    for entry in entries.iter_mut() {
+        if entry.kind != GitEventKind::Checkout {
+            if let Some(branch) = commits.get(&entry.commit) {
+                entry.branch = Some(branch.clone());
+            }
+        }
+    }
+    Ok(entries)
+}
@@ -1,2 +1,13 @@
+pub mod analytics_export;
+pub mod calendar;
+pub mod cloud_backup;
+pub mod email;
 pub mod friend_wearable;
-pub mod unstructured_ocr;
\ No newline at end of file
+pub mod git_activity;
+pub mod notifications;
+pub mod reflink;
+pub mod remote_worker;
+pub mod shell_history;
+pub mod timezone;
+pub mod unstructured_ocr;
+pub mod vault_export;
\ No newline at end of file
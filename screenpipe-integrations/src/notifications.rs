@@ -0,0 +1,249 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Seconds from the unix epoch to 2001-01-01, where Apple's absolute times start
+const MAC_EPOCH_OFFSET: i64 = 978_307_200;
+/// 100 nanosecond ticks from 1601-01-01, where Windows file times start, to the unix epoch
+const FILETIME_UNIX_EPOCH: i64 = 116_444_736_000_000_000;
+
+/// Where the notifications are read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NotificationSource {
+    /// The Notify calls on the session bus of Linux desktops
+    DBus,
+    /// The database of the macOS notification center
+    NotificationCenter,
+    /// The database of the Windows action center
+    ActionCenter,
+}
+
+impl NotificationSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NotificationSource::DBus => "dbus",
+            NotificationSource::NotificationCenter => "notification_center",
+            NotificationSource::ActionCenter => "action_center",
+        }
+    }
+
+    /// The source of the platform, None where notifications can't be read
+    pub fn current() -> Option<Self> {
+        if cfg!(target_os = "macos") {
+            Some(NotificationSource::NotificationCenter)
+        } else if cfg!(target_os = "windows") {
+            Some(NotificationSource::ActionCenter)
+        } else if cfg!(target_os = "linux") {
+            Some(NotificationSource::DBus)
+        } else {
+            None
+        }
+    }
+}
+
+impl FromStr for NotificationSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "dbus" => Ok(NotificationSource::DBus),
+            "notification_center" => Ok(NotificationSource::NotificationCenter),
+            "action_center" => Ok(NotificationSource::ActionCenter),
+            _ => bail!("unknown notification source: {}", s),
+        }
+    }
+}
+
+/// A notification as the platform showed it. `app` is the name the platform knows the app
+/// by: its name on Linux, its bundle id on macOS and its app user model id on Windows
+#[derive(Debug, Clone, PartialEq)]
+pub struct OsNotification {
+    pub timestamp: DateTime<Utc>,
+    pub app: String,
+    pub title: String,
+    /// None when the app sent none or the platform hides it, like previews turned off
+    pub body: Option<String>,
+    pub source: NotificationSource,
+}
+
+/// Reads the notifications out of the output of
+/// `dbus-monitor --session "interface='org.freedesktop.Notifications',member='Notify'"`, a
+/// line at a time
+#[derive(Debug, Default)]
+pub struct DBusNotifyReader {
+    /// Time of the Notify call being read, None outside of one
+    timestamp: Option<DateTime<Utc>>,
+    strings: Vec<String>,
+    /// A string going on over several lines
+    open_string: Option<String>,
+    arguments: usize,
+}
+
+impl DBusNotifyReader {
+    /// The notification once its body is read. Its arguments are the app, the id it
+    /// replaces, the icon, the summary and the body
+    pub fn push_line(&mut self, line: &str) -> Option<OsNotification> {
+        if let Some(open) = &mut self.open_string {
+            open.push('\n');
+            match line.strip_suffix('"') {
+                Some(end) => {
+                    open.push_str(end);
+                    let string = self.open_string.take().unwrap_or_default();
+                    return self.argument(Some(string));
+                }
+                None => {
+                    open.push_str(line);
+                    return None;
+                }
+            }
+        }
+        if !line.starts_with(' ') {
+            *self = DBusNotifyReader::default();
+            if line.starts_with("method call") && line.contains("member=Notify") {
+                self.timestamp = Some(dbus_time(line).unwrap_or_else(Utc::now));
+            }
+            return None;
+        }
+        self.timestamp?;
+        let argument = line.trim_start();
+        // only the first arguments are read, the nested values of the others are left out
+        if line.len() - argument.len() > 3 {
+            return None;
+        }
+        match argument.strip_prefix("string \"") {
+            Some(string) => match string.strip_suffix('"') {
+                Some(string) => self.argument(Some(string.to_string())),
+                None => {
+                    self.open_string = Some(string.to_string());
+                    None
+                }
+            },
+            None => self.argument(None),
+        }
+    }
+
+    fn argument(&mut self, string: Option<String>) -> Option<OsNotification> {
+        self.arguments += 1;
+        if let Some(string) = string {
+            self.strings.push(string);
+        }
+        // app, replaced id, icon, summary and body
+        if self.arguments < 5 {
+            return None;
+        }
+        let timestamp = self.timestamp.take()?;
+        let mut strings = std::mem::take(&mut self.strings).into_iter();
+        let app = strings.next()?;
+        let _icon = strings.next()?;
+        let title = strings.next()?;
+        let body = strings.next().filter(|body| !body.is_empty());
+        Some(OsNotification {
+            timestamp,
+            app,
+            title,
+            body,
+            source: NotificationSource::DBus,
+        })
+    }
+}
+
+/// `time=<seconds>.<micros>` of the header of a message
+fn dbus_time(header: &str) -> Option<DateTime<Utc>> {
+    let time = header
+        .split_whitespace()
+        .find_map(|field| field.strip_prefix("time="))?;
+    let seconds: f64 = time.parse().ok()?;
+    DateTime::from_timestamp_micros((seconds * 1_000_000.0) as i64)
+}
+
+/// The databases the notification center writes to, the newest location first: macOS 15
+/// moved it to a group container, earlier versions keep it in the user's temporary directory
+pub fn notification_center_dbs(home: &Path, darwin_user_dir: Option<&Path>) -> Vec<PathBuf> {
+    let mut dbs = vec![home.join("Library/Group Containers/group.com.apple.usernoted/db2/db")];
+    if let Some(dir) = darwin_user_dir {
+        dbs.push(dir.join("com.apple.notificationcenter/db2/db"));
+    }
+    dbs
+}
+
+/// Seconds since 2001-01-01, as the notification center stores its times
+pub fn from_mac_absolute_time(seconds: f64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_micros(((seconds + MAC_EPOCH_OFFSET as f64) * 1_000_000.0) as i64)
+}
+
+pub fn to_mac_absolute_time(time: DateTime<Utc>) -> f64 {
+    time.timestamp_micros() as f64 / 1_000_000.0 - MAC_EPOCH_OFFSET as f64
+}
+
+/// The title and body of a notification center record, converted to an XML plist with
+/// `plutil -convert xml1`. A subtitle starts the body, the keys are unique in the record
+pub fn parse_notification_plist(xml: &str) -> Option<(String, Option<String>)> {
+    let title = plist_string(xml, "titl")?;
+    let body: Vec<String> = [plist_string(xml, "subt"), plist_string(xml, "body")]
+        .into_iter()
+        .flatten()
+        .filter(|text| !text.is_empty())
+        .collect();
+    Some((title, (!body.is_empty()).then(|| body.join("\n"))))
+}
+
+fn plist_string(xml: &str, key: &str) -> Option<String> {
+    let after_key = &xml[xml.find(&format!("<key>{}</key>", key))?..];
+    let value = after_key[after_key.find("</key>")? + "</key>".len()..].trim_start();
+    let value = value.strip_prefix("<string>")?;
+    Some(xml_unescape(&value[..value.find("</string>")?]))
+}
+
+/// The database of the Windows action center, in %LOCALAPPDATA%
+pub fn action_center_db(local_app_data: &Path) -> PathBuf {
+    local_app_data.join("Microsoft/Windows/Notifications/wpndatabase.db")
+}
+
+/// 100 nanosecond ticks since 1601-01-01, as the action center stores its times
+pub fn from_filetime(ticks: i64) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp_micros((ticks - FILETIME_UNIX_EPOCH) / 10)
+}
+
+pub fn to_filetime(time: DateTime<Utc>) -> i64 {
+    time.timestamp_micros() * 10 + FILETIME_UNIX_EPOCH
+}
+
+/// The title and body of a toast, its first `<text>` and the ones after it
+pub fn parse_toast_payload(xml: &str) -> Option<(String, Option<String>)> {
+    let mut texts = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<text") {
+        rest = &rest[start + "<text".len()..];
+        // like <textBox>, or a <text/> without content
+        let Some(open_end) = rest.find('>') else {
+            break;
+        };
+        let tag = &rest[..open_end];
+        if !(tag.is_empty() || tag.starts_with(char::is_whitespace)) || tag.ends_with('/') {
+            continue;
+        }
+        rest = &rest[open_end + 1..];
+        let Some(end) = rest.find("</text>") else {
+            break;
+        };
+        let text = xml_unescape(rest[..end].trim());
+        if !text.is_empty() {
+            texts.push(text);
+        }
+        rest = &rest[end..];
+    }
+    let mut texts = texts.into_iter();
+    let title = texts.next()?;
+    let body = texts.collect::<Vec<_>>().join("\n");
+    Some((title, (!body.is_empty()).then_some(body)))
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
@@ -0,0 +1,251 @@
+//! Copies that share the blocks of their source where the file system can: a clone on APFS,
+//! a reflink on btrfs and XFS, duplicated extents on ReFS. The copy takes no room and no time
+//! until one of the two is written to. Anywhere else, or from one volume to another, the bytes
+//! are copied.
+use std::io;
+use std::path::Path;
+
+/// How a file was copied
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyMethod {
+    /// Sharing the blocks of the source
+    Cloned,
+    Copied,
+}
+
+/// Copies `source` to `target`, replacing it like `std::fs::copy`, as a clone where the file
+/// system can. Returns the length of the file and how it was copied. A file copied onto
+/// itself is left as it is, and reported as copied
+pub fn clone_or_copy(source: &Path, target: &Path) -> io::Result<(u64, CopyMethod)> {
+    // replacing the target would delete the source first
+    if let (Ok(source_path), Ok(target_path)) =
+        (std::fs::canonicalize(source), std::fs::canonicalize(target))
+    {
+        if source_path == target_path {
+            return Ok((std::fs::metadata(source)?.len(), CopyMethod::Copied));
+        }
+    }
+    match std::fs::remove_file(target) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    match clone_file(source, target) {
+        Ok(()) => Ok((std::fs::metadata(target)?.len(), CopyMethod::Cloned)),
+        Err(e) => {
+            log::debug!("Copying {} rather than cloning it: {}", source.display(), e);
+            Ok((std::fs::copy(source, target)?, CopyMethod::Copied))
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn clone_file(source: &Path, target: &Path) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let source_file = std::fs::File::open(source)?;
+    let target_file = std::fs::File::create_new(target)?;
+    // SAFETY: both descriptors belong to files open until the end of the call, FICLONE reads
+    // the source's as an int. libc has its number for each architecture, and the request type
+    // of glibc or musl
+    let cloned = unsafe {
+        libc::ioctl(
+            target_file.as_raw_fd(),
+            libc::FICLONE as _,
+            source_file.as_raw_fd(),
+        )
+    } == 0;
+    let result = if cloned {
+        target_file.set_permissions(source_file.metadata()?.permissions())
+    } else {
+        Err(io::Error::last_os_error())
+    };
+    if result.is_err() {
+        drop(target_file);
+        let _ = std::fs::remove_file(target);
+    }
+    result
+}
+
+#[cfg(target_os = "macos")]
+fn clone_file(source: &Path, target: &Path) -> io::Result<()> {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_int};
+    use std::os::unix::ffi::OsStrExt;
+
+    extern "C" {
+        fn clonefile(source: *const c_char, target: *const c_char, flags: u32) -> c_int;
+    }
+
+    let source = CString::new(source.as_os_str().as_bytes())?;
+    let target = CString::new(target.as_os_str().as_bytes())?;
+    // SAFETY: both are nul terminated paths living until the end of the call. clonefile
+    // creates nothing when it fails
+    if unsafe { clonefile(source.as_ptr(), target.as_ptr(), 0) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+#[cfg(windows)]
+fn clone_file(source: &Path, target: &Path) -> io::Result<()> {
+    let source_file = std::fs::File::open(source)?;
+    let target_file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create_new(true)
+        .open(target)?;
+    let result = duplicate_extents(source, &source_file, &target_file);
+    if result.is_err() {
+        drop(target_file);
+        let _ = std::fs::remove_file(target);
+    }
+    result
+}
+
+/// Points the clusters of `target` at the ones of `source`, on ReFS
+#[cfg(windows)]
+fn duplicate_extents(
+    source: &Path,
+    source_file: &std::fs::File,
+    target_file: &std::fs::File,
+) -> io::Result<()> {
+    use std::ffi::c_void;
+    use std::os::windows::fs::MetadataExt;
+    use std::os::windows::io::AsRawHandle;
+
+    const FSCTL_SET_SPARSE: u32 = 0x0009_00C4;
+    const FSCTL_DUPLICATE_EXTENTS_TO_FILE: u32 = 0x0009_8344;
+    const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x200;
+    // ReFS duplicates less than 4 GiB per call, this is a multiple of any cluster size
+    const MAX_EXTENTS: u64 = 1 << 31;
+
+    #[repr(C)]
+    struct DuplicateExtentsData {
+        file_handle: *mut c_void,
+        source_file_offset: i64,
+        target_file_offset: i64,
+        byte_count: i64,
+    }
+
+    let metadata = source_file.metadata()?;
+    let target_handle = target_file.as_raw_handle();
+    // the extents of a sparse file only go to another sparse file
+    if metadata.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0 {
+        device_control(target_handle, FSCTL_SET_SPARSE, std::ptr::null(), 0)?;
+    }
+    target_file.set_len(metadata.len())?;
+    // the extents end on a cluster, past the end of the file for its last one
+    let cluster = cluster_size(source)?;
+    let length = metadata.len().div_ceil(cluster) * cluster;
+    let mut offset = 0;
+    while offset < length {
+        let extents = DuplicateExtentsData {
+            file_handle: source_file.as_raw_handle(),
+            source_file_offset: offset as i64,
+            target_file_offset: offset as i64,
+            byte_count: (length - offset).min(MAX_EXTENTS) as i64,
+        };
+        device_control(
+            target_handle,
+            FSCTL_DUPLICATE_EXTENTS_TO_FILE,
+            &extents as *const DuplicateExtentsData as *const c_void,
+            std::mem::size_of::<DuplicateExtentsData>(),
+        )?;
+        offset += MAX_EXTENTS;
+    }
+    Ok(())
+}
+
+/// Sends a control code with `size` bytes of input and no output to the file of `handle`
+#[cfg(windows)]
+fn device_control(
+    handle: std::os::windows::io::RawHandle,
+    code: u32,
+    input: *const std::ffi::c_void,
+    size: usize,
+) -> io::Result<()> {
+    use std::ffi::c_void;
+    use std::ptr::null_mut;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn DeviceIoControl(
+            device: *mut c_void,
+            control_code: u32,
+            in_buffer: *const c_void,
+            in_size: u32,
+            out_buffer: *mut c_void,
+            out_size: u32,
+            returned: *mut u32,
+            overlapped: *mut c_void,
+        ) -> i32;
+    }
+
+    let mut returned = 0u32;
+    // SAFETY: the handle is of a file its caller keeps open, `input` points at `size` bytes
+    // the call only reads, and there's no output nor overlapped io
+    let done = unsafe {
+        DeviceIoControl(
+            handle,
+            code,
+            input,
+            size as u32,
+            null_mut(),
+            0,
+            &mut returned,
+            null_mut(),
+        )
+    };
+    if done != 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// Bytes of a cluster of the volume `path` is on
+#[cfg(windows)]
+fn cluster_size(path: &Path) -> io::Result<u64> {
+    use std::os::windows::ffi::OsStrExt;
+    use std::path::Component;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetDiskFreeSpaceW(
+            root: *const u16,
+            sectors_per_cluster: *mut u32,
+            bytes_per_sector: *mut u32,
+            free_clusters: *mut u32,
+            total_clusters: *mut u32,
+        ) -> i32;
+    }
+
+    let path = std::fs::canonicalize(path)?;
+    let root: std::path::PathBuf = path
+        .components()
+        .take_while(|component| matches!(component, Component::Prefix(_) | Component::RootDir))
+        .collect();
+    let root: Vec<u16> = root.as_os_str().encode_wide().chain(Some(0)).collect();
+    let (mut sectors_per_cluster, mut bytes_per_sector, mut free, mut total) = (0, 0, 0, 0);
+    // SAFETY: `root` is a nul terminated path living until the end of the call, the counts
+    // are u32s to write to
+    let found = unsafe {
+        GetDiskFreeSpaceW(
+            root.as_ptr(),
+            &mut sectors_per_cluster,
+            &mut bytes_per_sector,
+            &mut free,
+            &mut total,
+        )
+    };
+    if found == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(sectors_per_cluster as u64 * bytes_per_sector as u64)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+fn clone_file(_source: &Path, _target: &Path) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
@@ -0,0 +1,566 @@
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use futures::{SinkExt, StreamExt};
+use image::{codecs::jpeg::JpegEncoder, DynamicImage};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot, Semaphore};
+use tokio::time::{timeout, Duration};
+use tokio_native_tls::TlsAcceptor;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::{header, StatusCode};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::Connector;
+
+/// Both ends have to speak the same version
+pub const PROTOCOL_VERSION: u32 = 1;
+/// Frames are sent as JPEG, high enough to not change what OCR reads
+const FRAME_QUALITY: u8 = 90;
+/// A long audio chunk on a busy worker can take a while
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(180);
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+/// Bigger requests are refused by the worker
+const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// Sent by the client as a binary message: the length of the json header as a big endian
+/// u32, the header, then the JPEG frame or the audio file as it was recorded
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorkRequest {
+    Ocr {
+        id: u64,
+    },
+    /// The extension tells the worker how to decode the file
+    Transcribe {
+        id: u64,
+        extension: String,
+    },
+}
+
+impl WorkRequest {
+    pub fn id(&self) -> u64 {
+        match self {
+            WorkRequest::Ocr { id } | WorkRequest::Transcribe { id, .. } => *id,
+        }
+    }
+}
+
+/// Sent by the worker as text messages
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum WorkerMessage {
+    /// First message on a connection, the client never has more than `max_in_flight`
+    /// requests waiting for an answer
+    Hello {
+        version: u32,
+        max_in_flight: usize,
+        ocr: bool,
+        transcription: bool,
+    },
+    Ocr {
+        id: u64,
+        text: String,
+        text_json: String,
+    },
+    Transcription {
+        id: u64,
+        text: String,
+    },
+    Error {
+        id: u64,
+        error: String,
+    },
+}
+
+pub fn encode_request(request: &WorkRequest, payload: &[u8]) -> Vec<u8> {
+    let header = serde_json::to_vec(request).expect("work requests serialize");
+    let mut message = Vec::with_capacity(4 + header.len() + payload.len());
+    message.extend_from_slice(&(header.len() as u32).to_be_bytes());
+    message.extend_from_slice(&header);
+    message.extend_from_slice(payload);
+    message
+}
+
+pub fn decode_request(message: &[u8]) -> Result<(WorkRequest, &[u8])> {
+    let length = message
+        .get(..4)
+        .map(|bytes| u32::from_be_bytes(bytes.try_into().unwrap()) as usize)
+        .ok_or_else(|| anyhow!("request too short"))?;
+    let header = message
+        .get(4..4 + length)
+        .ok_or_else(|| anyhow!("request header cut off"))?;
+    Ok((serde_json::from_slice(header)?, &message[4 + length..]))
+}
+
+fn encode_frame(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut frame = Vec::new();
+    JpegEncoder::new_with_quality(&mut frame, FRAME_QUALITY).encode_image(&image.to_rgb8())?;
+    Ok(frame)
+}
+
+/// What a worker runs, with the engines of the machine it's on
+#[async_trait]
+pub trait WorkHandler: Send + Sync + 'static {
+    fn can_ocr(&self) -> bool;
+    fn can_transcribe(&self) -> bool;
+    /// Text and the engine's json output
+    async fn ocr(&self, image: DynamicImage) -> Result<(String, String)>;
+    async fn transcribe(&self, audio_path: &Path) -> Result<String>;
+}
+
+pub struct WorkerOptions {
+    /// Clients have to send it as a bearer token when set
+    pub token: Option<String>,
+    /// Requests of a connection processed at the same time
+    pub max_in_flight: usize,
+    pub tls: Option<TlsAcceptor>,
+}
+
+/// From the PEM certificate chain and PKCS#8 key of the worker
+pub fn tls_acceptor(cert_path: &Path, key_path: &Path) -> Result<TlsAcceptor> {
+    let cert = std::fs::read(cert_path)
+        .with_context(|| format!("failed to read {}", cert_path.display()))?;
+    let key = std::fs::read(key_path)
+        .with_context(|| format!("failed to read {}", key_path.display()))?;
+    let identity = native_tls::Identity::from_pkcs8(&cert, &key)?;
+    Ok(TlsAcceptor::from(native_tls::TlsAcceptor::new(identity)?))
+}
+
+fn websocket_config() -> WebSocketConfig {
+    WebSocketConfig {
+        max_message_size: Some(MAX_MESSAGE_SIZE),
+        max_frame_size: Some(MAX_MESSAGE_SIZE),
+        ..Default::default()
+    }
+}
+
+/// Takes work from the clients connecting to the listener until it fails
+pub async fn serve_worker(
+    listener: TcpListener,
+    handler: Arc<dyn WorkHandler>,
+    options: WorkerOptions,
+) -> Result<()> {
+    let options = Arc::new(options);
+    loop {
+        let (stream, address) = listener.accept().await?;
+        let handler = handler.clone();
+        let options = options.clone();
+        tokio::spawn(async move {
+            let result = match &options.tls {
+                Some(tls) => match tls.accept(stream).await {
+                    Ok(stream) => serve_connection(stream, handler, &options).await,
+                    Err(e) => Err(e.into()),
+                },
+                None => serve_connection(stream, handler, &options).await,
+            };
+            match result {
+                Ok(()) => debug!("Remote worker client {} disconnected", address),
+                Err(e) => warn!("Remote worker client {} failed: {}", address, e),
+            }
+        });
+    }
+}
+
+async fn serve_connection<S>(
+    stream: S,
+    handler: Arc<dyn WorkHandler>,
+    options: &WorkerOptions,
+) -> Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let expected = options
+        .token
+        .as_ref()
+        .map(|token| format!("Bearer {}", token));
+    // the error type is tungstenite's
+    #[allow(clippy::result_large_err)]
+    let check_token = |request: &Request, response: Response| {
+        let given = request
+            .headers()
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok());
+        match &expected {
+            Some(expected) if given != Some(expected.as_str()) => {
+                let mut refused = ErrorResponse::new(Some("invalid token".to_string()));
+                *refused.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(refused)
+            }
+            _ => Ok(response),
+        }
+    };
+    let websocket = tokio_tungstenite::accept_hdr_async_with_config(
+        stream,
+        check_token,
+        Some(websocket_config()),
+    )
+    .await?;
+    let (mut sink, mut incoming) = websocket.split();
+
+    let (outgoing, mut outgoing_rx) = mpsc::channel::<WorkerMessage>(options.max_in_flight + 1);
+    let writer = tokio::spawn(async move {
+        while let Some(message) = outgoing_rx.recv().await {
+            let text = serde_json::to_string(&message).expect("worker messages serialize");
+            if sink.send(Message::Text(text)).await.is_err() {
+                break;
+            }
+        }
+    });
+    outgoing
+        .send(WorkerMessage::Hello {
+            version: PROTOCOL_VERSION,
+            max_in_flight: options.max_in_flight,
+            ocr: handler.can_ocr(),
+            transcription: handler.can_transcribe(),
+        })
+        .await?;
+
+    let slots = Arc::new(Semaphore::new(options.max_in_flight));
+    while let Some(message) = incoming.next().await {
+        let message = match message? {
+            Message::Binary(message) => message,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+        let (request, payload) = match decode_request(&message) {
+            Ok((request, payload)) => (request, payload.to_vec()),
+            Err(e) => {
+                warn!("Invalid remote worker request: {}", e);
+                continue;
+            }
+        };
+        // clients keep to max_in_flight, one that doesn't is told to slow down
+        let Ok(slot) = slots.clone().try_acquire_owned() else {
+            outgoing
+                .send(WorkerMessage::Error {
+                    id: request.id(),
+                    error: "worker busy".to_string(),
+                })
+                .await?;
+            continue;
+        };
+        let handler = handler.clone();
+        let outgoing = outgoing.clone();
+        tokio::spawn(async move {
+            let id = request.id();
+            let response = handle_request(handler.as_ref(), request, payload)
+                .await
+                .unwrap_or_else(|e| WorkerMessage::Error {
+                    id,
+                    error: e.to_string(),
+                });
+            // free before the client hears back and sends the next one
+            drop(slot);
+            let _ = outgoing.send(response).await;
+        });
+    }
+    drop(outgoing);
+    let _ = writer.await;
+    Ok(())
+}
+
+async fn handle_request(
+    handler: &dyn WorkHandler,
+    request: WorkRequest,
+    payload: Vec<u8>,
+) -> Result<WorkerMessage> {
+    match request {
+        WorkRequest::Ocr { id } => {
+            if !handler.can_ocr() {
+                bail!("this worker doesn't do OCR");
+            }
+            let image = image::load_from_memory(&payload)?;
+            let (text, text_json) = handler.ocr(image).await?;
+            Ok(WorkerMessage::Ocr {
+                id,
+                text,
+                text_json,
+            })
+        }
+        WorkRequest::Transcribe { id, extension } => {
+            if !handler.can_transcribe() {
+                bail!("this worker doesn't transcribe");
+            }
+            let extension: String = extension
+                .chars()
+                .filter(char::is_ascii_alphanumeric)
+                .collect();
+            let mut file = tempfile::Builder::new()
+                .suffix(&format!(".{}", extension))
+                .tempfile()?;
+            file.write_all(&payload)?;
+            let text = handler.transcribe(file.path()).await?;
+            Ok(WorkerMessage::Transcription { id, text })
+        }
+    }
+}
+
+struct Connection {
+    outgoing: mpsc::Sender<Message>,
+    pending: Arc<Mutex<HashMap<u64, oneshot::Sender<WorkerMessage>>>>,
+    /// One per request the worker takes at a time, waiting for one is the backpressure
+    credits: Arc<Semaphore>,
+    closed: Arc<AtomicBool>,
+    ocr: bool,
+    transcription: bool,
+}
+
+impl Connection {
+    async fn open(url: &str, token: Option<&str>, connector: Option<Connector>) -> Result<Self> {
+        let mut request = url.into_client_request()?;
+        if let Some(token) = token {
+            request
+                .headers_mut()
+                .insert(header::AUTHORIZATION, format!("Bearer {}", token).parse()?);
+        }
+        let (websocket, _) = tokio_tungstenite::connect_async_tls_with_config(
+            request,
+            Some(websocket_config()),
+            false,
+            connector,
+        )
+        .await
+        .with_context(|| format!("failed to connect to remote worker {}", url))?;
+        let (mut sink, mut incoming) = websocket.split();
+
+        let hello = match timeout(HELLO_TIMEOUT, incoming.next()).await {
+            Ok(Some(Ok(Message::Text(text)))) => serde_json::from_str(&text)?,
+            _ => bail!("remote worker {} didn't say hello", url),
+        };
+        let WorkerMessage::Hello {
+            version,
+            max_in_flight,
+            ocr,
+            transcription,
+        } = hello
+        else {
+            bail!("remote worker {} didn't say hello", url);
+        };
+        if version != PROTOCOL_VERSION {
+            bail!(
+                "remote worker {} speaks protocol {}, this is {}",
+                url,
+                version,
+                PROTOCOL_VERSION
+            );
+        }
+        info!(
+            "Connected to remote worker {} taking {} requests at a time",
+            url, max_in_flight
+        );
+
+        let (outgoing, mut outgoing_rx) = mpsc::channel::<Message>(max_in_flight.max(1));
+        tokio::spawn(async move {
+            while let Some(message) = outgoing_rx.recv().await {
+                if sink.send(message).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let pending: Arc<Mutex<HashMap<u64, oneshot::Sender<WorkerMessage>>>> = Arc::default();
+        let credits = Arc::new(Semaphore::new(max_in_flight));
+        let closed = Arc::new(AtomicBool::new(false));
+        {
+            let pending = pending.clone();
+            let credits = credits.clone();
+            let closed = closed.clone();
+            let url = url.to_string();
+            tokio::spawn(async move {
+                while let Some(Ok(message)) = incoming.next().await {
+                    let Message::Text(text) = message else {
+                        continue;
+                    };
+                    let message: WorkerMessage = match serde_json::from_str(&text) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            error!("Invalid message from remote worker: {}", e);
+                            continue;
+                        }
+                    };
+                    let id = match &message {
+                        WorkerMessage::Ocr { id, .. }
+                        | WorkerMessage::Transcription { id, .. }
+                        | WorkerMessage::Error { id, .. } => *id,
+                        WorkerMessage::Hello { .. } => continue,
+                    };
+                    if let Some(waiting) = pending.lock().unwrap().remove(&id) {
+                        let _ = waiting.send(message);
+                    }
+                }
+                warn!("Disconnected from remote worker {}", url);
+                closed.store(true, Ordering::SeqCst);
+                credits.close();
+                // the requests waiting get an error
+                pending.lock().unwrap().clear();
+            });
+        }
+
+        Ok(Connection {
+            outgoing,
+            pending,
+            credits,
+            closed,
+            ocr,
+            transcription,
+        })
+    }
+}
+
+/// Client of a worker on another machine, connects on first use and again after the
+/// connection is lost
+pub struct RemoteWorker {
+    url: String,
+    token: Option<String>,
+    connector: Option<Connector>,
+    connection: tokio::sync::Mutex<Option<Arc<Connection>>>,
+    next_id: AtomicU64,
+}
+
+impl RemoteWorker {
+    /// `ca_pem` is trusted on top of the system roots, for workers with their own certificate
+    pub fn new(url: &str, token: Option<String>, ca_pem: Option<&[u8]>) -> Result<Self> {
+        if !url.starts_with("ws://") && !url.starts_with("wss://") {
+            bail!("remote worker url has to start with ws:// or wss://");
+        }
+        if url.starts_with("ws://") {
+            warn!(
+                "Remote worker {} is not using tls, frames and audio are sent in the clear",
+                url
+            );
+        }
+        let connector = match ca_pem {
+            Some(ca_pem) => Some(Connector::NativeTls(
+                native_tls::TlsConnector::builder()
+                    .add_root_certificate(native_tls::Certificate::from_pem(ca_pem)?)
+                    .build()?,
+            )),
+            None => None,
+        };
+        Ok(RemoteWorker {
+            url: url.to_string(),
+            token,
+            connector,
+            connection: tokio::sync::Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        })
+    }
+
+    async fn connection(&self) -> Result<Arc<Connection>> {
+        let mut connection = self.connection.lock().await;
+        if let Some(open) = connection
+            .as_ref()
+            .filter(|c| !c.closed.load(Ordering::SeqCst))
+        {
+            return Ok(open.clone());
+        }
+        let open = Arc::new(
+            Connection::open(&self.url, self.token.as_deref(), self.connector.clone()).await?,
+        );
+        *connection = Some(open.clone());
+        Ok(open)
+    }
+
+    async fn send(
+        &self,
+        connection: &Connection,
+        request: WorkRequest,
+        payload: &[u8],
+    ) -> Result<WorkerMessage> {
+        let _credit = connection
+            .credits
+            .acquire()
+            .await
+            .map_err(|_| anyhow!("disconnected from remote worker"))?;
+        let id = request.id();
+        let (answer_tx, answer_rx) = oneshot::channel();
+        connection.pending.lock().unwrap().insert(id, answer_tx);
+        connection
+            .outgoing
+            .send(Message::Binary(encode_request(&request, payload)))
+            .await
+            .map_err(|_| anyhow!("disconnected from remote worker"))?;
+        match timeout(REQUEST_TIMEOUT, answer_rx).await {
+            Ok(Ok(WorkerMessage::Error { error, .. })) => bail!("remote worker failed: {}", error),
+            Ok(Ok(answer)) => Ok(answer),
+            Ok(Err(_)) => bail!("disconnected from remote worker"),
+            Err(_) => {
+                connection.pending.lock().unwrap().remove(&id);
+                bail!("remote worker didn't answer in {:?}", REQUEST_TIMEOUT)
+            }
+        }
+    }
+
+    fn next_id(&self) -> u64 {
+        self.next_id.fetch_add(1, Ordering::SeqCst)
+    }
+
+    pub async fn ocr(&self, image: &DynamicImage) -> Result<(String, String)> {
+        let connection = self.connection().await?;
+        if !connection.ocr {
+            bail!("remote worker {} doesn't do OCR", self.url);
+        }
+        let frame = encode_frame(image)?;
+        match self
+            .send(&connection, WorkRequest::Ocr { id: self.next_id() }, &frame)
+            .await?
+        {
+            WorkerMessage::Ocr {
+                text, text_json, ..
+            } => Ok((text, text_json)),
+            other => bail!("unexpected answer from remote worker: {:?}", other),
+        }
+    }
+
+    pub async fn transcribe(&self, audio_path: &Path) -> Result<String> {
+        let connection = self.connection().await?;
+        if !connection.transcription {
+            bail!("remote worker {} doesn't transcribe", self.url);
+        }
+        let audio = tokio::fs::read(audio_path).await?;
+        let request = WorkRequest::Transcribe {
+            id: self.next_id(),
+            extension: audio_path
+                .extension()
+                .map(|e| e.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        match self.send(&connection, request, &audio).await? {
+            WorkerMessage::Transcription { text, .. } => Ok(text),
+            other => bail!("unexpected answer from remote worker: {:?}", other),
+        }
+    }
+}
+
+/// Used by the remote OCR and transcription engines
+static REMOTE_WORKER: RwLock<Option<Arc<RemoteWorker>>> = RwLock::new(None);
+
+pub fn set_remote_worker(worker: Option<Arc<RemoteWorker>>) {
+    *REMOTE_WORKER.write().unwrap() = worker;
+}
+
+fn remote_worker() -> Result<Arc<RemoteWorker>> {
+    REMOTE_WORKER
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| anyhow!("no remote worker set"))
+}
+
+pub async fn perform_ocr_remote(image: &Arc<DynamicImage>) -> Result<(String, String), String> {
+    let worker = remote_worker().map_err(|e| e.to_string())?;
+    worker.ocr(image).await.map_err(|e| e.to_string())
+}
+
+pub async fn transcribe_remote(audio_path: &str) -> Result<String> {
+    remote_worker()?.transcribe(Path::new(audio_path)).await
+}
@@ -0,0 +1,234 @@
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Shell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+impl Shell {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Shell::Zsh => "zsh",
+            Shell::Bash => "bash",
+            Shell::Fish => "fish",
+        }
+    }
+}
+
+impl FromStr for Shell {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "zsh" => Ok(Shell::Zsh),
+            "bash" => Ok(Shell::Bash),
+            "fish" => Ok(Shell::Fish),
+            _ => bail!("unknown shell: {}", s),
+        }
+    }
+}
+
+/// A command of a history file. Only the ones the shell wrote a time for are read
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistoryEntry {
+    pub command: String,
+    pub timestamp: DateTime<Utc>,
+    /// zsh writes it with EXTENDED_HISTORY
+    pub duration_secs: Option<i64>,
+}
+
+fn from_unix(seconds: &str) -> Option<DateTime<Utc>> {
+    DateTime::from_timestamp(seconds.trim().parse().ok()?, 0)
+}
+
+/// `: <start>:<duration>;<command>` lines of EXTENDED_HISTORY, a command going on over
+/// several lines ends them with a backslash
+pub fn parse_zsh_history(history: &str) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    let mut continued = false;
+    for line in history.lines() {
+        if continued {
+            if let Some(entry) = entries.last_mut() {
+                entry.command.push('\n');
+                entry
+                    .command
+                    .push_str(line.strip_suffix('\\').unwrap_or(line));
+            }
+            continued = line.ends_with('\\');
+            continue;
+        }
+        let Some((meta, command)) = line
+            .strip_prefix(": ")
+            .and_then(|rest| rest.split_once(';'))
+        else {
+            continue;
+        };
+        let (start, duration) = meta.split_once(':').unwrap_or((meta, ""));
+        let Some(timestamp) = from_unix(start) else {
+            continue;
+        };
+        continued = command.ends_with('\\');
+        entries.push(HistoryEntry {
+            command: command.strip_suffix('\\').unwrap_or(command).to_string(),
+            timestamp,
+            duration_secs: duration.trim().parse().ok(),
+        });
+    }
+    entries
+}
+
+/// The commands preceded by a `#<time>` line, bash writes them when HISTTIMEFORMAT is set
+pub fn parse_bash_history(history: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut timestamp = None;
+    for line in history.lines() {
+        if let Some(time) = line.strip_prefix('#').and_then(from_unix) {
+            timestamp = Some(time);
+            continue;
+        }
+        if let Some(timestamp) = timestamp.take() {
+            if !line.trim().is_empty() {
+                entries.push(HistoryEntry {
+                    command: line.to_string(),
+                    timestamp,
+                    duration_secs: None,
+                });
+            }
+        }
+    }
+    entries
+}
+
+/// The `- cmd:` and `when:` of each entry of fish's yaml-like history
+pub fn parse_fish_history(history: &str) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut command: Option<String> = None;
+    for line in history.lines() {
+        if let Some(cmd) = line.strip_prefix("- cmd: ") {
+            command = Some(unescape_fish(cmd));
+        } else if let Some(when) = line.trim_start().strip_prefix("when: ") {
+            if let (Some(command), Some(timestamp)) = (command.take(), from_unix(when)) {
+                entries.push(HistoryEntry {
+                    command,
+                    timestamp,
+                    duration_secs: None,
+                });
+            }
+        }
+    }
+    entries
+}
+
+fn unescape_fish(command: &str) -> String {
+    let mut unescaped = String::with_capacity(command.len());
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                unescaped.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                unescaped.push('\\');
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+pub fn parse_history(shell: Shell, history: &str) -> Vec<HistoryEntry> {
+    match shell {
+        Shell::Zsh => parse_zsh_history(history),
+        Shell::Bash => parse_bash_history(history),
+        Shell::Fish => parse_fish_history(history),
+    }
+}
+
+/// Where the shells keep their history by default, the ones that exist
+pub fn default_history_files(home: &Path) -> Vec<(Shell, PathBuf)> {
+    let fish_dir = std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| home.join(".local/share"))
+        .join("fish");
+    [
+        (Shell::Zsh, home.join(".zsh_history")),
+        (Shell::Zsh, home.join(".zhistory")),
+        (Shell::Bash, home.join(".bash_history")),
+        (Shell::Fish, fish_dir.join("fish_history")),
+    ]
+    .into_iter()
+    .filter(|(_, path)| path.is_file())
+    .collect()
+}
+
+/// Quoted for zsh, bash and fish, which all end a single quoted string at the next quote
+pub fn shell_quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+/// Hooks sending each command to the API socket at `socket` once it's done, with its
+/// directory, exit code and times, to `eval` in the shell's rc file. The socket only lets in
+/// the processes of the user, so neither a port nor a token is needed. They post in the
+/// background, a prompt never waits for screenpipe
+pub fn hook_script(shell: Shell, socket: &Path) -> String {
+    let curl = format!(
+        "curl -s -m 2 --unix-socket {} -X POST http://localhost/shell/commands",
+        shell_quote(&socket.to_string_lossy())
+    );
+    let now = "$(date -u +%Y-%m-%dT%H:%M:%SZ)";
+    match shell {
+        Shell::Zsh => format!(
+            r#"__screenpipe_preexec() {{
+  __screenpipe_command="$1"
+  __screenpipe_cwd="$PWD"
+  __screenpipe_started_at="{now}"
+}}
+__screenpipe_precmd() {{
+  local exit_code=$?
+  [[ -z "$__screenpipe_command" ]] && return
+  ({curl} --data-urlencode "shell=zsh" --data-urlencode "command=$__screenpipe_command" --data-urlencode "cwd=$__screenpipe_cwd" --data-urlencode "exit_code=$exit_code" --data-urlencode "started_at=$__screenpipe_started_at" --data-urlencode "ended_at={now}" >/dev/null 2>&1 &)
+  __screenpipe_command=""
+}}
+autoload -Uz add-zsh-hook
+add-zsh-hook preexec __screenpipe_preexec
+add-zsh-hook precmd __screenpipe_precmd
+"#
+        ),
+        Shell::Bash => format!(
+            r#"__screenpipe_preexec() {{
+  [[ -n "$COMP_LINE" || -n "$__screenpipe_command" || "$BASH_COMMAND" == __screenpipe_* ]] && return
+  __screenpipe_command="$(HISTTIMEFORMAT= history 1 | sed 's/^ *[0-9]* *//')"
+  __screenpipe_cwd="$PWD"
+  __screenpipe_started_at="{now}"
+}}
+__screenpipe_precmd() {{
+  local exit_code=$?
+  [[ -z "$__screenpipe_command" ]] && return
+  ({curl} --data-urlencode "shell=bash" --data-urlencode "command=$__screenpipe_command" --data-urlencode "cwd=$__screenpipe_cwd" --data-urlencode "exit_code=$exit_code" --data-urlencode "started_at=$__screenpipe_started_at" --data-urlencode "ended_at={now}" >/dev/null 2>&1 &)
+  __screenpipe_command=""
+}}
+trap '__screenpipe_preexec' DEBUG
+PROMPT_COMMAND="__screenpipe_precmd${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+"#
+        ),
+        Shell::Fish => format!(
+            r#"function __screenpipe_preexec --on-event fish_preexec
+    set -g __screenpipe_cwd $PWD
+    set -g __screenpipe_started_at (date -u +%Y-%m-%dT%H:%M:%SZ)
+end
+function __screenpipe_postexec --on-event fish_postexec
+    set -l exit_code $status
+    {curl} --data-urlencode "shell=fish" --data-urlencode "command=$argv[1]" --data-urlencode "cwd=$__screenpipe_cwd" --data-urlencode "exit_code=$exit_code" --data-urlencode "started_at=$__screenpipe_started_at" --data-urlencode "ended_at="(date -u +%Y-%m-%dT%H:%M:%SZ) >/dev/null 2>&1 &
+    disown 2>/dev/null
+end
+"#
+        ),
+    }
+}
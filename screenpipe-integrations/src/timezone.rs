@@ -0,0 +1,46 @@
+//! Local times of a history recorded across timezones. Timestamps are stored in UTC, the
+//! zone they were recorded in is kept apart to show and group them in it.
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+pub use chrono_tz::Tz;
+
+/// The zone the system is set to, None when it isn't an IANA zone chrono-tz knows
+pub fn current_timezone() -> Option<Tz> {
+    iana_time_zone::get_timezone().ok()?.parse().ok()
+}
+
+/// An IANA name like "Europe/Berlin"
+pub fn parse_timezone(name: &str) -> Result<Tz> {
+    name.parse().map_err(|_| {
+        anyhow!(
+            "unknown timezone {}, expected an IANA name like Europe/Berlin",
+            name
+        )
+    })
+}
+
+/// Offset from UTC in minutes, east positive
+pub fn utc_offset_minutes(zone: &Tz, time: DateTime<Utc>) -> i32 {
+    use chrono::Offset;
+    zone.offset_from_utc_datetime(&time.naive_utc())
+        .fix()
+        .local_minus_utc()
+        / 60
+}
+
+/// Start and end of a day in `zone`
+pub fn day_range_in<Z: TimeZone>(
+    date: NaiveDate,
+    zone: &Z,
+) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    let start_of = |date: NaiveDate| {
+        zone.from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+            .earliest()
+            .map(|time| time.with_timezone(&Utc))
+            .ok_or_else(|| anyhow!("{} has no midnight in the timezone", date))
+    };
+    let next_day = date
+        .succ_opt()
+        .ok_or_else(|| anyhow!("{} is the last day there is", date))?;
+    Ok((start_of(date)?, start_of(next_day)?))
+}
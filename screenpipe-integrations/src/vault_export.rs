@@ -0,0 +1,455 @@
+use crate::reflink::clone_or_copy;
+use crate::timezone::{day_range_in, Tz};
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use image::{codecs::jpeg::JpegEncoder, DynamicImage};
+use log::{debug, error, info};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Folder of the notes in an Obsidian vault, so they don't replace the user's own daily notes
+pub const OBSIDIAN_FOLDER: &str = "screenpipe";
+/// Logseq pages are named `screenpipe/<date>`
+pub const LOGSEQ_NAMESPACE: &str = "screenpipe";
+const IMAGE_QUALITY: u8 = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VaultFormat {
+    Obsidian,
+    Logseq,
+}
+
+/// Time spent on something during the day
+#[derive(Debug, Clone)]
+pub struct DigestEntry {
+    pub label: String,
+    pub duration_secs: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct NoteMeeting {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// The calendar event the meeting overlapped
+    pub title: Option<String>,
+    pub attendees: Vec<String>,
+    pub summary: String,
+    pub decisions: Vec<String>,
+    pub action_items: Vec<String>,
+}
+
+impl NoteMeeting {
+    pub(crate) fn heading(&self, timezone: Option<&Tz>) -> String {
+        let times = format!(
+            "{} - {}",
+            local_time(&self.start_time, timezone),
+            local_time(&self.end_time, timezone)
+        );
+        match &self.title {
+            Some(title) => format!("{} {}", times, title),
+            None => times,
+        }
+    }
+}
+
+/// Something screenpipe picked out of the screen, with the frame it was seen on
+#[derive(Debug, Clone)]
+pub struct NoteMoment {
+    pub timestamp: DateTime<Utc>,
+    pub tag: String,
+    pub app_name: String,
+    pub window_name: String,
+    pub details: Vec<String>,
+    pub frame_id: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NoteContent {
+    pub digest: Vec<DigestEntry>,
+    pub meetings: Vec<NoteMeeting>,
+    pub moments: Vec<NoteMoment>,
+    /// Trends and anomalies of the week before, in the notes and digests of mondays
+    pub last_week: Vec<String>,
+    /// The zone its times are written in, the local one when None
+    pub timezone: Option<Tz>,
+}
+
+impl NoteContent {
+    pub fn is_empty(&self) -> bool {
+        self.digest.is_empty() && self.meetings.is_empty() && self.moments.is_empty()
+    }
+}
+
+#[async_trait]
+pub trait VaultNoteSource: Send + Sync {
+    async fn note_content(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<NoteContent>;
+    async fn frame_image(&self, frame_id: i64) -> Result<DynamicImage>;
+}
+
+/// Start and end of a day in the local timezone
+pub fn local_day_range(date: NaiveDate) -> Result<(DateTime<Utc>, DateTime<Utc>)> {
+    day_range_in(date, &Local)
+}
+
+pub fn format_duration(secs: f64) -> String {
+    let minutes = (secs / 60.0).round() as i64;
+    match (minutes / 60, minutes % 60) {
+        (0, 0) => "<1m".to_string(),
+        (0, minutes) => format!("{}m", minutes),
+        (hours, 0) => format!("{}h", hours),
+        (hours, minutes) => format!("{}h {}m", hours, minutes),
+    }
+}
+
+pub(crate) fn local_time(time: &DateTime<Utc>, timezone: Option<&Tz>) -> String {
+    match timezone {
+        Some(timezone) => time.with_timezone(timezone).format("%H:%M").to_string(),
+        None => time.with_timezone(&Local).format("%H:%M").to_string(),
+    }
+}
+
+/// Tags can't have spaces or most punctuation in either app
+fn tag(name: &str) -> String {
+    let tag: String = name
+        .trim()
+        .chars()
+        .map(|c| match c {
+            c if c.is_alphanumeric() || c == '-' || c == '_' || c == '/' => c,
+            _ => '-',
+        })
+        .collect();
+    format!("#{}", tag)
+}
+
+fn image_file_name(frame_id: i64) -> String {
+    format!("screenpipe-frame-{}.jpg", frame_id)
+}
+
+/// Writes daily notes of what was recorded into an Obsidian or Logseq vault, notes are
+/// rewritten on every export and images kept once written
+pub struct VaultExporter {
+    pub vault_dir: PathBuf,
+    pub format: VaultFormat,
+}
+
+impl VaultExporter {
+    pub fn new(vault_dir: PathBuf, format: VaultFormat) -> Self {
+        VaultExporter { vault_dir, format }
+    }
+
+    pub fn note_path(&self, date: NaiveDate) -> PathBuf {
+        match self.format {
+            VaultFormat::Obsidian => self
+                .vault_dir
+                .join(OBSIDIAN_FOLDER)
+                .join(format!("{}.md", date.format("%Y-%m-%d"))),
+            // "___" separates namespaces in Logseq file names
+            VaultFormat::Logseq => self.vault_dir.join("pages").join(format!(
+                "{}___{}.md",
+                LOGSEQ_NAMESPACE,
+                date.format("%Y-%m-%d")
+            )),
+        }
+    }
+
+    pub fn attachments_dir(&self) -> PathBuf {
+        match self.format {
+            VaultFormat::Obsidian => self.vault_dir.join(OBSIDIAN_FOLDER).join("attachments"),
+            VaultFormat::Logseq => self.vault_dir.join("assets"),
+        }
+    }
+
+    fn embed(&self, file_name: &str) -> String {
+        match self.format {
+            VaultFormat::Obsidian => format!("![[{}]]", file_name),
+            VaultFormat::Logseq => format!("![moment](../assets/{})", file_name),
+        }
+    }
+
+    /// `images` has the attachment of the frames that could be written
+    pub fn render(
+        &self,
+        date: NaiveDate,
+        content: &NoteContent,
+        images: &HashMap<i64, String>,
+    ) -> String {
+        match self.format {
+            VaultFormat::Obsidian => self.render_obsidian(date, content, images),
+            VaultFormat::Logseq => self.render_logseq(date, content, images),
+        }
+    }
+
+    fn render_obsidian(
+        &self,
+        date: NaiveDate,
+        content: &NoteContent,
+        images: &HashMap<i64, String>,
+    ) -> String {
+        let mut note = String::new();
+        let date = date.format("%Y-%m-%d");
+        let _ = writeln!(note, "---\ndate: {}\ntags: [screenpipe]\n---\n", date);
+        let _ = writeln!(note, "# {}", date);
+        if content.is_empty() {
+            let _ = writeln!(note, "\nNothing recorded.");
+        }
+
+        if !content.digest.is_empty() {
+            let _ = writeln!(note, "\n## Digest\n");
+            for entry in &content.digest {
+                let _ = writeln!(
+                    note,
+                    "- {}: {}",
+                    entry.label,
+                    format_duration(entry.duration_secs)
+                );
+            }
+        }
+
+        if !content.last_week.is_empty() {
+            let _ = writeln!(note, "\n## Last week\n");
+            for line in &content.last_week {
+                let _ = writeln!(note, "- {}", line);
+            }
+        }
+
+        if !content.meetings.is_empty() {
+            let _ = writeln!(note, "\n## Meetings");
+            for meeting in &content.meetings {
+                let _ = writeln!(
+                    note,
+                    "\n### {}\n",
+                    meeting.heading(content.timezone.as_ref())
+                );
+                if !meeting.attendees.is_empty() {
+                    let _ = writeln!(note, "With {}\n", meeting.attendees.join(", "));
+                }
+                let _ = writeln!(note, "{}", meeting.summary.trim());
+                if !meeting.decisions.is_empty() {
+                    let _ = writeln!(note, "\n**Decisions**\n");
+                    for decision in &meeting.decisions {
+                        let _ = writeln!(note, "- {}", decision);
+                    }
+                }
+                if !meeting.action_items.is_empty() {
+                    let _ = writeln!(note, "\n**Action items**\n");
+                    for item in &meeting.action_items {
+                        let _ = writeln!(note, "- [ ] {}", item);
+                    }
+                }
+            }
+        }
+
+        if !content.moments.is_empty() {
+            let _ = writeln!(note, "\n## Moments");
+            for moment in &content.moments {
+                let _ = writeln!(
+                    note,
+                    "\n### {} {}\n\n{} - {}",
+                    local_time(&moment.timestamp, content.timezone.as_ref()),
+                    tag(&moment.tag),
+                    moment.app_name,
+                    moment.window_name
+                );
+                for detail in &moment.details {
+                    let _ = writeln!(note, "- {}", detail);
+                }
+                if let Some(file_name) = moment.frame_id.and_then(|id| images.get(&id)) {
+                    let _ = writeln!(note, "\n{}", self.embed(file_name));
+                }
+            }
+        }
+        note
+    }
+
+    /// Outline of blocks indented with tabs, page properties in the first block
+    fn render_logseq(
+        &self,
+        date: NaiveDate,
+        content: &NoteContent,
+        images: &HashMap<i64, String>,
+    ) -> String {
+        let mut note = String::new();
+        let _ = writeln!(
+            note,
+            "date:: {}\ntags:: screenpipe\n",
+            date.format("%Y-%m-%d")
+        );
+        if content.is_empty() {
+            let _ = writeln!(note, "- Nothing recorded.");
+        }
+
+        if !content.digest.is_empty() {
+            let _ = writeln!(note, "- ## Digest");
+            for entry in &content.digest {
+                let _ = writeln!(
+                    note,
+                    "\t- {}: {}",
+                    entry.label,
+                    format_duration(entry.duration_secs)
+                );
+            }
+        }
+
+        if !content.last_week.is_empty() {
+            let _ = writeln!(note, "- ## Last week");
+            for line in &content.last_week {
+                let _ = writeln!(note, "\t- {}", line);
+            }
+        }
+
+        if !content.meetings.is_empty() {
+            let _ = writeln!(note, "- ## Meetings");
+            for meeting in &content.meetings {
+                let _ = writeln!(note, "\t- {}", meeting.heading(content.timezone.as_ref()));
+                if !meeting.attendees.is_empty() {
+                    let _ = writeln!(note, "\t\t- With {}", meeting.attendees.join(", "));
+                }
+                for line in meeting.summary.lines().filter(|l| !l.trim().is_empty()) {
+                    let _ = writeln!(note, "\t\t- {}", line.trim());
+                }
+                if !meeting.decisions.is_empty() {
+                    let _ = writeln!(note, "\t\t- Decisions");
+                    for decision in &meeting.decisions {
+                        let _ = writeln!(note, "\t\t\t- {}", decision);
+                    }
+                }
+                if !meeting.action_items.is_empty() {
+                    let _ = writeln!(note, "\t\t- Action items");
+                    for item in &meeting.action_items {
+                        let _ = writeln!(note, "\t\t\t- TODO {}", item);
+                    }
+                }
+            }
+        }
+
+        if !content.moments.is_empty() {
+            let _ = writeln!(note, "- ## Moments");
+            for moment in &content.moments {
+                let _ = writeln!(
+                    note,
+                    "\t- {} {} {} - {}",
+                    local_time(&moment.timestamp, content.timezone.as_ref()),
+                    tag(&moment.tag),
+                    moment.app_name,
+                    moment.window_name
+                );
+                for detail in &moment.details {
+                    let _ = writeln!(note, "\t\t- {}", detail);
+                }
+                if let Some(file_name) = moment.frame_id.and_then(|id| images.get(&id)) {
+                    let _ = writeln!(note, "\t\t- {}", self.embed(file_name));
+                }
+            }
+        }
+        note
+    }
+
+    /// Frames already in the vault aren't extracted again, and a frame looking like one written
+    /// before it is a clone of its file where the file system can
+    async fn write_images(
+        &self,
+        source: &dyn VaultNoteSource,
+        moments: &[NoteMoment],
+    ) -> Result<HashMap<i64, String>> {
+        let dir = self.attachments_dir();
+        tokio::fs::create_dir_all(&dir).await?;
+        let mut images = HashMap::new();
+        let mut written: HashMap<Vec<u8>, PathBuf> = HashMap::new();
+        for frame_id in moments.iter().filter_map(|moment| moment.frame_id) {
+            let file_name = image_file_name(frame_id);
+            let path = dir.join(&file_name);
+            if !path.exists() {
+                let image = match source.frame_image(frame_id).await {
+                    Ok(image) => image,
+                    Err(e) => {
+                        debug!("Leaving frame {} out of the vault: {}", frame_id, e);
+                        continue;
+                    }
+                };
+                let mut encoded = Vec::new();
+                JpegEncoder::new_with_quality(&mut encoded, IMAGE_QUALITY)
+                    .encode_image(&image.to_rgb8())?;
+                let digest = Sha256::digest(&encoded).to_vec();
+                match written.get(&digest) {
+                    Some(same) => {
+                        let (same, target) = (same.clone(), path.clone());
+                        tokio::task::spawn_blocking(move || clone_or_copy(&same, &target))
+                            .await??;
+                    }
+                    None => {
+                        tokio::fs::write(&path, encoded).await?;
+                        written.insert(digest, path);
+                    }
+                }
+            }
+            images.insert(frame_id, file_name);
+        }
+        Ok(images)
+    }
+
+    pub async fn export(&self, source: &dyn VaultNoteSource, date: NaiveDate) -> Result<PathBuf> {
+        self.export_in(source, date, None).await
+    }
+
+    /// The note of the day as it was in `timezone`, its start and end and the times in it,
+    /// the local timezone when None
+    pub async fn export_in(
+        &self,
+        source: &dyn VaultNoteSource,
+        date: NaiveDate,
+        timezone: Option<Tz>,
+    ) -> Result<PathBuf> {
+        let (start, end) = match &timezone {
+            Some(timezone) => day_range_in(date, timezone)?,
+            None => local_day_range(date)?,
+        };
+        let mut content = source.note_content(start, end).await?;
+        content.timezone = timezone;
+        let images = self.write_images(source, &content.moments).await?;
+        let note = self.render(date, &content, &images);
+
+        let path = self.note_path(date);
+        if let Some(dir) = path.parent() {
+            tokio::fs::create_dir_all(dir).await?;
+        }
+        // the apps watch the vault, they shouldn't see a half written note
+        let partial = path.with_extension("md.partial");
+        tokio::fs::write(&partial, note).await?;
+        tokio::fs::rename(&partial, &path).await?;
+        Ok(path)
+    }
+}
+
+/// Keeps today's note up to date, and finishes yesterday's once the day is over
+pub async fn initialize_vault_export_loop(
+    source: Arc<dyn VaultNoteSource>,
+    exporter: Arc<VaultExporter>,
+    interval: Duration,
+) {
+    info!(
+        "Exporting daily notes to {} every {:?}",
+        exporter.vault_dir.display(),
+        interval
+    );
+    let mut last_date: Option<NaiveDate> = None;
+    loop {
+        let today = Local::now().date_naive();
+        let dates = last_date
+            .filter(|date| *date != today)
+            .into_iter()
+            .chain([today]);
+        for date in dates {
+            match exporter.export(source.as_ref(), date).await {
+                Ok(path) => debug!("Exported daily note to {}", path.display()),
+                Err(e) => error!("Failed to export the daily note of {}: {}", date, e),
+            }
+        }
+        last_date = Some(today);
+        tokio::time::sleep(interval).await;
+    }
+}
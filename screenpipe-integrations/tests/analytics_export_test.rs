@@ -0,0 +1,90 @@
+use arrow::array::{Array, Int64Array, StringArray};
+use chrono::{TimeZone, Utc};
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use screenpipe_integrations::analytics_export::{
+    write_analytics, AnalyticsFormat, AnalyticsRows, AnalyticsTable, OcrRow,
+};
+use std::io::Write;
+
+fn rows() -> AnalyticsRows {
+    let at = Utc.with_ymd_and_hms(2024, 9, 12, 14, 0, 0).unwrap();
+    AnalyticsRows::Ocr(vec![
+        OcrRow {
+            frame_id: 1,
+            timestamp: at,
+            app_name: "Code".to_string(),
+            window_name: Some("main.rs, screenpipe".to_string()),
+            focused: true,
+            ocr_engine: "tesseract".to_string(),
+            word_count: 12,
+            char_count: 80,
+        },
+        OcrRow {
+            frame_id: 2,
+            timestamp: at,
+            app_name: "Finder".to_string(),
+            window_name: None,
+            focused: false,
+            ocr_engine: "tesseract".to_string(),
+            word_count: 0,
+            char_count: 0,
+        },
+    ])
+}
+
+#[test]
+fn test_tables_and_formats_are_parsed() {
+    assert_eq!(
+        "ocr".parse::<AnalyticsTable>().unwrap(),
+        AnalyticsTable::Ocr
+    );
+    assert_eq!(
+        "Parquet".parse::<AnalyticsFormat>().unwrap(),
+        AnalyticsFormat::Parquet
+    );
+    assert!("frames".parse::<AnalyticsTable>().is_err());
+    assert!("xlsx".parse::<AnalyticsFormat>().is_err());
+}
+
+#[test]
+fn test_csv_quotes_and_leaves_missing_values_empty() {
+    let csv = String::from_utf8(write_analytics(&rows(), AnalyticsFormat::Csv).unwrap()).unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "frame_id,timestamp,app_name,window_name,focused,ocr_engine,word_count,char_count"
+    );
+    assert!(lines[1].starts_with("1,2024-09-12T14:00:00"));
+    assert!(lines[1].contains(",\"main.rs, screenpipe\",true,"));
+    assert!(lines[2].contains(",Finder,,false,"));
+}
+
+#[test]
+fn test_parquet_reads_back() {
+    let data = write_analytics(&rows(), AnalyticsFormat::Parquet).unwrap();
+    let mut file = tempfile::tempfile().unwrap();
+    file.write_all(&data).unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<_> = reader.map(|batch| batch.unwrap()).collect();
+    assert_eq!(batches.len(), 1);
+    let batch = &batches[0];
+    assert_eq!(batch.num_rows(), 2);
+    let words = batch
+        .column_by_name("word_count")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<Int64Array>()
+        .unwrap();
+    assert_eq!(words.value(0), 12);
+    let windows = batch
+        .column_by_name("window_name")
+        .unwrap()
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert!(windows.is_null(1));
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, TimeZone, Utc};
+use screenpipe_integrations::calendar::{best_overlap, parse_ics, write_ics, FeedEvent};
+
+fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 9, day, hour, minute, 0).unwrap()
+}
+
+const CALENDAR: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:sync\r
+SUMMARY:Sync with Bob\\, weekly\r
+DTSTART:20240902T090000Z\r
+DTEND:20240902T093000Z\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE;COUNT=4\r
+EXDATE:20240904T090000Z\r
+ATTENDEE;CN=Bob Smith;ROLE=REQ-PARTICIPANT:mailto:bob@example.com\r
+ATTENDEE:mailto:ana@exam\r
+ ple.com\r
+BEGIN:VALARM\r
+SUMMARY:reminder\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:sync\r
+RECURRENCE-ID:20240909T090000Z\r
+SUMMARY:Sync with Bob (moved)\r
+DTSTART:20240909T140000Z\r
+DURATION:PT45M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:paris\r
+SUMMARY:Planning\r
+DTSTART;TZID=Europe/Paris:20240903T100000\r
+DTEND;TZID=Europe/Paris:20240903T110000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:holiday\r
+SUMMARY:Offsite\r
+DTSTART;VALUE=DATE:20240903\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:cancelled\r
+SUMMARY:Cancelled review\r
+STATUS:CANCELLED\r
+DTSTART:20240903T080000Z\r
+DTEND:20240903T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+#[test]
+fn test_parse_ics() {
+    let events = parse_ics(CALENDAR, at(1, 0, 0), at(30, 0, 0)).unwrap();
+
+    let occurrences: Vec<_> = events
+        .iter()
+        .filter(|event| !event.all_day)
+        .map(|event| (event.title.as_str(), event.start, event.end))
+        .collect();
+    assert_eq!(
+        occurrences,
+        vec![
+            ("Sync with Bob, weekly", at(2, 9, 0), at(2, 9, 30)),
+            // 10:00 in Paris is 08:00 UTC in summer
+            ("Planning", at(3, 8, 0), at(3, 9, 0)),
+            ("Sync with Bob (moved)", at(9, 14, 0), at(9, 14, 45)),
+            ("Sync with Bob, weekly", at(11, 9, 0), at(11, 9, 30)),
+        ]
+    );
+    let offsite: Vec<_> = events.iter().filter(|event| event.all_day).collect();
+    assert_eq!(offsite.len(), 1);
+    assert_eq!(offsite[0].end - offsite[0].start, chrono::Duration::days(1));
+    assert_eq!(occurrences.len(), 4);
+    let sync = events.iter().find(|event| event.uid == "sync").unwrap();
+    assert_eq!(sync.attendees, vec!["Bob Smith", "ana@example.com"]);
+}
+
+#[test]
+fn test_best_overlap() {
+    let events = parse_ics(CALENDAR, at(1, 0, 0), at(30, 0, 0)).unwrap();
+
+    // a recording starting late and running over still gets the planning
+    let event = best_overlap(&events, at(3, 8, 10), at(3, 9, 20)).unwrap();
+    assert_eq!(event.title, "Planning");
+    assert!(best_overlap(&events, at(3, 12, 0), at(3, 13, 0)).is_none());
+}
+
+#[test]
+fn test_not_a_calendar() {
+    assert!(parse_ics("<html></html>", at(1, 0, 0), at(30, 0, 0)).is_err());
+}
+
+#[test]
+fn test_written_feeds_read_back() {
+    let title = format!("Review; notes, and {}", "a very long title".repeat(5));
+    let event = FeedEvent {
+        uid: "meeting-1@screenpipe".to_string(),
+        title: title.clone(),
+        start: at(12, 14, 0),
+        end: at(12, 15, 0),
+        description: Some("decided:\n- ship it".to_string()),
+        categories: vec!["meeting".to_string()],
+    };
+    let ics = write_ics("screenpipe", &[event], at(13, 0, 0));
+    assert!(ics.lines().all(|line| line.len() <= 76), "lines are folded");
+    assert!(ics.contains("DTSTART:20240912T140000Z\r\n"));
+    assert!(ics.contains("SUMMARY:Review\\; notes\\, and"));
+
+    let events = parse_ics(&ics, at(1, 0, 0), at(30, 0, 0)).unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].uid, "meeting-1@screenpipe");
+    assert_eq!(events[0].title, title);
+    assert_eq!(
+        (events[0].start, events[0].end),
+        (at(12, 14, 0), at(12, 15, 0))
+    );
+}
@@ -0,0 +1,149 @@
+use chrono::{NaiveDate, TimeZone, Utc};
+use image::{DynamicImage, RgbImage};
+use screenpipe_integrations::email::{
+    build_message, render_alert, render_digest, send_email, thumbnail_jpeg, EmailAlert,
+    EmailDestination, EmailSecurity,
+};
+use screenpipe_integrations::vault_export::{DigestEntry, NoteContent, NoteMoment};
+use std::collections::HashMap;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+
+fn destination(port: u16) -> EmailDestination {
+    serde_json::from_value(serde_json::json!({
+        "name": "me",
+        "smtp_host": "127.0.0.1",
+        "smtp_port": port,
+        "security": "none",
+        "from": "screenpipe <screenpipe@example.com>",
+        "to": ["me@example.com"],
+        "digest_hour": 18,
+        "alerts": true,
+        "watches": ["Mentions"],
+    }))
+    .unwrap()
+}
+
+fn content() -> NoteContent {
+    let at = Utc.with_ymd_and_hms(2024, 9, 12, 14, 0, 0).unwrap();
+    NoteContent {
+        digest: vec![DigestEntry {
+            label: "coding".to_string(),
+            duration_secs: 2.0 * 3600.0,
+        }],
+        meetings: Vec::new(),
+        moments: vec![NoteMoment {
+            timestamp: at,
+            tag: "orders".to_string(),
+            app_name: "Salesforce".to_string(),
+            window_name: "Order <1234>".to_string(),
+            details: vec!["order_number: 1234".to_string()],
+            frame_id: Some(7),
+        }],
+        last_week: vec!["Unusual: after hours 6h, 3.0x the usual 2h".to_string()],
+        timezone: None,
+    }
+}
+
+#[test]
+fn test_destinations_are_validated() {
+    let destination = destination(25);
+    assert!(destination.validate().is_ok());
+    assert_eq!(destination.security, EmailSecurity::None);
+    assert!(destination.thumbnails);
+    assert!(destination.sends_alerts_of("mentions"));
+    assert!(!destination.sends_alerts_of("orders"));
+
+    let mut invalid = destination.clone();
+    invalid.to = vec!["not an address".to_string()];
+    assert!(invalid.validate().is_err());
+    let mut invalid = destination;
+    invalid.digest_hour = Some(24);
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn test_digest_embeds_the_frames_of_its_moments() {
+    let image = DynamicImage::ImageRgb8(RgbImage::new(1920, 1080));
+    let jpeg = thumbnail_jpeg(&image).unwrap();
+    assert_eq!(
+        image::load_from_memory(&jpeg).unwrap().width(),
+        640,
+        "frames are shrunk"
+    );
+
+    let date = NaiveDate::from_ymd_opt(2024, 9, 12).unwrap();
+    let email = render_digest(date, &content(), HashMap::from([(7, jpeg)]));
+    assert!(email.subject.contains("Thursday 12 September"));
+    assert!(email.html.contains("coding: <b>2h</b>"));
+    assert!(email.html.contains("Order &lt;1234&gt;"));
+    assert!(email.html.contains("cid:frame-7@screenpipe"));
+    assert!(email.text.contains("order_number: 1234"));
+    assert!(email.text.contains("Last week\n- Unusual: after hours 6h, 3.0x the usual 2h\n"));
+    assert_eq!(email.images.len(), 1);
+
+    let message =
+        String::from_utf8(build_message(&destination(25), &email).unwrap().formatted()).unwrap();
+    assert!(message.contains("multipart/alternative"));
+    assert!(message.contains("multipart/related"));
+    assert!(message.contains("Content-ID: <frame-7@screenpipe>"));
+    assert!(message.contains("Content-Type: image/jpeg"));
+}
+
+/// Answers like an SMTP server and returns what was sent in DATA
+async fn fake_smtp_server(listener: TcpListener) -> String {
+    let (stream, _) = listener.accept().await.unwrap();
+    let (read, mut write) = stream.into_split();
+    let mut lines = BufReader::new(read).lines();
+    write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+    let mut data = String::new();
+    let mut in_data = false;
+    while let Some(line) = lines.next_line().await.unwrap() {
+        if in_data {
+            if line == "." {
+                in_data = false;
+                write.write_all(b"250 queued\r\n").await.unwrap();
+            } else {
+                data.push_str(&line);
+                data.push('\n');
+            }
+            continue;
+        }
+        let command = line.to_uppercase();
+        let reply: &[u8] = if command.starts_with("DATA") {
+            in_data = true;
+            b"354 go ahead\r\n"
+        } else if command.starts_with("QUIT") {
+            write.write_all(b"221 bye\r\n").await.unwrap();
+            break;
+        } else {
+            b"250 ok\r\n"
+        };
+        write.write_all(reply).await.unwrap();
+    }
+    data
+}
+
+#[tokio::test]
+async fn test_alerts_are_sent_over_smtp() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let server = tokio::spawn(fake_smtp_server(listener));
+
+    let alert = EmailAlert {
+        watch_name: "mentions".to_string(),
+        timestamp: Utc::now(),
+        app_name: Some("Slack".to_string()),
+        window_name: Some("#general".to_string()),
+        matched_text: "karol".to_string(),
+        excerpt: "@karol can you review the release?".to_string(),
+    };
+    let email = render_alert(&alert, None);
+    send_email(&destination(port), &email).await.unwrap();
+
+    let data = server.await.unwrap();
+    assert!(data.contains("Subject: screenpipe: mentions matched \"karol\""));
+    assert!(data.contains("To: me@example.com"));
+    assert!(data.contains("can you review the release?"));
+    assert!(data.contains("Slack - #general"));
+}
@@ -0,0 +1,113 @@
+use screenpipe_integrations::git_activity::{
+    git_dir, parse_reflog, read_git_activity, GitEventKind,
+};
+
+const ZERO: &str = "0000000000000000000000000000000000000000";
+const A: &str = "1111111111111111111111111111111111111111";
+const B: &str = "2222222222222222222222222222222222222222";
+const C: &str = "3333333333333333333333333333333333333333";
+
+fn line(previous: &str, commit: &str, time: i64, action: &str) -> String {
+    format!(
+        "{} {} Ada Lovelace <ada@example.com> {} +0200\t{}\n",
+        previous, commit, time, action
+    )
+}
+
+#[test]
+fn test_parses_commits_and_branch_switches() {
+    let reflog = [
+        line(ZERO, A, 1725000000, "commit (initial): init"),
+        line(
+            A,
+            A,
+            1725000100,
+            "checkout: moving from main to feature/login",
+        ),
+        line(A, B, 1725000200, "rebase (start): checkout main"),
+        line(A, B, 1725000201, "rebase (pick): add login"),
+        line(
+            B,
+            B,
+            1725000202,
+            "rebase (finish): returning to refs/heads/feature/login",
+        ),
+        line(B, C, 1725000300, "commit (amend): add login form"),
+        line(C, A, 1725000400, "reset: moving to HEAD~1"),
+        "not a reflog line\n".to_string(),
+    ]
+    .concat();
+    let entries = parse_reflog(&reflog);
+    let kinds: Vec<GitEventKind> = entries.iter().map(|entry| entry.kind).collect();
+    assert_eq!(
+        kinds,
+        vec![
+            GitEventKind::Commit,
+            GitEventKind::Checkout,
+            GitEventKind::Rebase,
+            GitEventKind::Commit,
+            GitEventKind::Reset,
+        ]
+    );
+    assert_eq!(entries[0].author, "Ada Lovelace");
+    assert_eq!(entries[0].message, "init");
+    assert_eq!(entries[0].previous_commit, ZERO);
+    assert_eq!(entries[0].branch, None);
+    assert_eq!(entries[0].timestamp.timestamp(), 1725000000);
+    assert_eq!(entries[3].message, "add login form");
+    assert_eq!(entries[3].branch.as_deref(), Some("feature/login"));
+}
+
+#[test]
+fn test_reads_the_branch_of_commits_from_the_branch_reflogs() {
+    let dir = tempfile::tempdir().unwrap();
+    let repository = dir.path();
+    let logs = repository.join(".git/logs");
+    std::fs::create_dir_all(logs.join("refs/heads/feature")).unwrap();
+    std::fs::write(
+        logs.join("HEAD"),
+        [
+            line(ZERO, A, 1725000000, "commit (initial): init"),
+            line(A, B, 1725000100, "commit: fix typo"),
+        ]
+        .concat(),
+    )
+    .unwrap();
+    std::fs::write(
+        logs.join("refs/heads/main"),
+        line(ZERO, A, 1725000000, "commit (initial): init"),
+    )
+    .unwrap();
+    std::fs::write(
+        logs.join("refs/heads/feature/docs"),
+        [
+            line(ZERO, A, 1725000050, "branch: Created from main"),
+            line(A, B, 1725000100, "commit: fix typo"),
+        ]
+        .concat(),
+    )
+    .unwrap();
+
+    let entries = read_git_activity(&git_dir(repository).unwrap()).unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].branch.as_deref(), Some("main"));
+    assert_eq!(entries[1].branch.as_deref(), Some("feature/docs"));
+
+    let not_a_repository = tempfile::tempdir().unwrap();
+    assert!(git_dir(not_a_repository.path()).is_err());
+}
+
+#[test]
+fn test_follows_the_git_file_of_worktrees() {
+    let dir = tempfile::tempdir().unwrap();
+    let main_git = dir.path().join("main/.git/worktrees/wt");
+    std::fs::create_dir_all(&main_git).unwrap();
+    let worktree = dir.path().join("wt");
+    std::fs::create_dir_all(&worktree).unwrap();
+    std::fs::write(
+        worktree.join(".git"),
+        format!("gitdir: {}\n", main_git.display()),
+    )
+    .unwrap();
+    assert_eq!(git_dir(&worktree).unwrap(), main_git);
+}
@@ -0,0 +1,128 @@
+use chrono::{DateTime, TimeZone, Utc};
+use screenpipe_integrations::notifications::{
+    from_filetime, from_mac_absolute_time, parse_notification_plist, parse_toast_payload,
+    to_filetime, to_mac_absolute_time, DBusNotifyReader, NotificationSource, OsNotification,
+};
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+#[test]
+fn test_reads_the_notify_calls_of_dbus_monitor() {
+    let output = r#"signal time=1727000000.000000 sender=org.freedesktop.DBus -> destination=:1.80 serial=2 path=/org/freedesktop/DBus; interface=org.freedesktop.DBus; member=NameAcquired
+   string ":1.80"
+method call time=1727000010.500000 sender=:1.52 -> destination=:1.14 serial=7 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "Slack"
+   uint32 0
+   string "slack"
+   string "Alice in #launch"
+   string "the build is green
+ship it"
+   array [
+      string "default"
+      string "Open"
+   ]
+   array [
+      dict entry(
+         string "urgency"
+         variant             byte 1
+      )
+   ]
+   int32 -1
+method call time=1727000020.000000 sender=:1.60 -> destination=:1.14 serial=9 path=/org/freedesktop/Notifications; interface=org.freedesktop.Notifications; member=Notify
+   string "Firefox"
+   uint32 3
+   string ""
+   string "Download finished"
+   string ""
+   array [
+   ]
+"#;
+    let mut reader = DBusNotifyReader::default();
+    let notifications: Vec<OsNotification> = output
+        .lines()
+        .filter_map(|line| reader.push_line(line))
+        .collect();
+    assert_eq!(
+        notifications,
+        vec![
+            OsNotification {
+                timestamp: at(1727000010) + chrono::Duration::milliseconds(500),
+                app: "Slack".to_string(),
+                title: "Alice in #launch".to_string(),
+                body: Some("the build is green\nship it".to_string()),
+                source: NotificationSource::DBus,
+            },
+            OsNotification {
+                timestamp: at(1727000020),
+                app: "Firefox".to_string(),
+                title: "Download finished".to_string(),
+                body: None,
+                source: NotificationSource::DBus,
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_reads_notification_center_records() {
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<plist version="1.0">
+<dict>
+	<key>app</key>
+	<string>com.apple.MobileSMS</string>
+	<key>date</key>
+	<real>748000000.5</real>
+	<key>req</key>
+	<dict>
+		<key>body</key>
+		<string>Dinner at 8 &amp; bring wine</string>
+		<key>subt</key>
+		<string>Family</string>
+		<key>titl</key>
+		<string>Mom</string>
+	</dict>
+</dict>
+</plist>"#;
+    assert_eq!(
+        parse_notification_plist(xml),
+        Some((
+            "Mom".to_string(),
+            Some("Family\nDinner at 8 & bring wine".to_string())
+        ))
+    );
+    // previews hidden, no body
+    let hidden = "<dict><key>req</key><dict><key>titl</key><string>Messages</string></dict></dict>";
+    assert_eq!(
+        parse_notification_plist(hidden),
+        Some(("Messages".to_string(), None))
+    );
+
+    let time = Utc.with_ymd_and_hms(2024, 9, 22, 10, 0, 0).unwrap();
+    assert_eq!(
+        from_mac_absolute_time(to_mac_absolute_time(time)),
+        Some(time)
+    );
+    assert_eq!(from_mac_absolute_time(0.0), Some(at(978_307_200)));
+}
+
+#[test]
+fn test_reads_action_center_toasts() {
+    let payload = r#"<toast launch="action=open"><visual><binding template="ToastGeneric"><text hint-maxLines="1">Build failed</text><text>main &lt;4f2a1c&gt;: 2 tests</text><text></text><text>Azure Pipelines</text></binding></visual><actions><action content="Open"/></actions></toast>"#;
+    assert_eq!(
+        parse_toast_payload(payload),
+        Some((
+            "Build failed".to_string(),
+            Some("main <4f2a1c>: 2 tests\nAzure Pipelines".to_string())
+        ))
+    );
+    assert_eq!(
+        parse_toast_payload("<toast><visual><binding/></visual></toast>"),
+        None
+    );
+
+    let time = Utc.with_ymd_and_hms(2024, 9, 22, 10, 0, 0).unwrap();
+    assert_eq!(from_filetime(to_filetime(time)), Some(time));
+    assert_eq!(from_filetime(116_444_736_000_000_000), Some(at(0)));
+}
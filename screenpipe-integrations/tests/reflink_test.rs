@@ -0,0 +1,39 @@
+use screenpipe_integrations::reflink::{clone_or_copy, CopyMethod};
+
+#[test]
+fn test_the_copy_replaces_the_target_and_stays_apart_from_the_source() {
+    let dir = tempfile::tempdir().unwrap();
+    let (source, target) = (dir.path().join("chunk.mp4"), dir.path().join("copy.mp4"));
+    std::fs::write(&source, b"frames of the morning").unwrap();
+    std::fs::write(&target, b"an older copy, longer than the chunk").unwrap();
+
+    let (length, method) = clone_or_copy(&source, &target).unwrap();
+
+    assert_eq!(length, 21);
+    assert!(matches!(method, CopyMethod::Cloned | CopyMethod::Copied));
+    assert_eq!(std::fs::read(&target).unwrap(), b"frames of the morning");
+    // a clone shares the blocks until one of them is written to
+    std::fs::write(&target, b"edited").unwrap();
+    assert_eq!(std::fs::read(&source).unwrap(), b"frames of the morning");
+}
+
+#[test]
+fn test_a_missing_source_leaves_no_target() {
+    let dir = tempfile::tempdir().unwrap();
+    let target = dir.path().join("copy.mp4");
+
+    assert!(clone_or_copy(&dir.path().join("gone.mp4"), &target).is_err());
+    assert!(!target.exists());
+}
+
+#[test]
+fn test_a_file_copied_onto_itself_is_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let source = dir.path().join("chunk.mp4");
+    std::fs::write(&source, b"frames of the morning").unwrap();
+
+    let (length, _) = clone_or_copy(&source, &dir.path().join(".").join("chunk.mp4")).unwrap();
+
+    assert_eq!(length, 21);
+    assert_eq!(std::fs::read(&source).unwrap(), b"frames of the morning");
+}
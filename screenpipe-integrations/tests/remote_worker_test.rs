@@ -0,0 +1,124 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use image::{DynamicImage, Rgb, RgbImage};
+use screenpipe_integrations::remote_worker::{
+    decode_request, encode_request, serve_worker, RemoteWorker, WorkHandler, WorkRequest,
+    WorkerOptions,
+};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+
+/// Answers with what it got, slowly enough for requests to pile up
+#[derive(Default)]
+struct EchoHandler {
+    running: AtomicUsize,
+    most_running: AtomicUsize,
+}
+
+#[async_trait]
+impl WorkHandler for EchoHandler {
+    fn can_ocr(&self) -> bool {
+        true
+    }
+
+    fn can_transcribe(&self) -> bool {
+        true
+    }
+
+    async fn ocr(&self, image: DynamicImage) -> Result<(String, String)> {
+        let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        self.most_running.fetch_max(running, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        self.running.fetch_sub(1, Ordering::SeqCst);
+        Ok((
+            format!("{}x{}", image.width(), image.height()),
+            "[]".to_string(),
+        ))
+    }
+
+    async fn transcribe(&self, audio_path: &Path) -> Result<String> {
+        Ok(format!(
+            "{} {}",
+            audio_path.extension().unwrap().to_string_lossy(),
+            std::fs::read_to_string(audio_path)?
+        ))
+    }
+}
+
+async fn start_worker(handler: Arc<EchoHandler>, token: Option<&str>) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let options = WorkerOptions {
+        token: token.map(str::to_string),
+        max_in_flight: 1,
+        tls: None,
+    };
+    tokio::spawn(serve_worker(listener, handler, options));
+    format!("ws://{}", address)
+}
+
+fn frame() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_pixel(64, 32, Rgb([255, 255, 255])))
+}
+
+#[test]
+fn test_request_roundtrip() {
+    let request = WorkRequest::Transcribe {
+        id: 7,
+        extension: "mp4".to_string(),
+    };
+
+    let message = encode_request(&request, b"audio");
+
+    let (decoded, payload) = decode_request(&message).unwrap();
+    assert_eq!(decoded, request);
+    assert_eq!(payload, b"audio");
+    assert!(decode_request(&message[..6]).is_err());
+}
+
+#[tokio::test]
+async fn test_ocr_and_transcription_are_offloaded() {
+    let url = start_worker(Arc::default(), Some("secret")).await;
+    let worker = RemoteWorker::new(&url, Some("secret".to_string()), None).unwrap();
+
+    let (text, text_json) = worker.ocr(&frame()).await.unwrap();
+    assert_eq!(text, "64x32");
+    assert_eq!(text_json, "[]");
+
+    let dir = tempfile::tempdir().unwrap();
+    let audio_path = dir.path().join("chunk.mp4");
+    std::fs::write(&audio_path, "hello").unwrap();
+    assert_eq!(worker.transcribe(&audio_path).await.unwrap(), "mp4 hello");
+}
+
+#[tokio::test]
+async fn test_wrong_token_is_refused() {
+    let url = start_worker(Arc::default(), Some("secret")).await;
+
+    let worker = RemoteWorker::new(&url, Some("guess".to_string()), None).unwrap();
+    assert!(worker.ocr(&frame()).await.is_err());
+    let worker = RemoteWorker::new(&url, None, None).unwrap();
+    assert!(worker.ocr(&frame()).await.is_err());
+}
+
+#[tokio::test]
+async fn test_client_waits_for_the_worker() {
+    let handler = Arc::new(EchoHandler::default());
+    let url = start_worker(handler.clone(), None).await;
+    let worker = Arc::new(RemoteWorker::new(&url, None, None).unwrap());
+
+    let requests: Vec<_> = (0..4)
+        .map(|_| {
+            let worker = worker.clone();
+            tokio::spawn(async move { worker.ocr(&frame()).await })
+        })
+        .collect();
+    for request in requests {
+        // a worker getting more than it takes would answer busy
+        assert_eq!(request.await.unwrap().unwrap().0, "64x32");
+    }
+    assert_eq!(handler.most_running.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,79 @@
+use chrono::{DateTime, Utc};
+use screenpipe_integrations::shell_history::{
+    hook_script, parse_bash_history, parse_fish_history, parse_zsh_history, HistoryEntry, Shell,
+};
+use std::path::Path;
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+#[test]
+fn test_parses_zsh_extended_history() {
+    let history = ": 1725000000:0;cargo build\n: 1725000010:12;cargo test \\\n  --workspace\nnot extended\n: 1725000030:0;ls\n";
+    assert_eq!(
+        parse_zsh_history(history),
+        vec![
+            HistoryEntry {
+                command: "cargo build".to_string(),
+                timestamp: at(1725000000),
+                duration_secs: Some(0),
+            },
+            HistoryEntry {
+                command: "cargo test \n  --workspace".to_string(),
+                timestamp: at(1725000010),
+                duration_secs: Some(12),
+            },
+            HistoryEntry {
+                command: "ls".to_string(),
+                timestamp: at(1725000030),
+                duration_secs: Some(0),
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_parses_timestamped_bash_and_fish_history() {
+    // the first command was written before HISTTIMEFORMAT was set
+    let bash = "make\n#1725000000\ngit status\n#1725000005\ngit push\n";
+    let commands: Vec<(String, DateTime<Utc>)> = parse_bash_history(bash)
+        .into_iter()
+        .map(|entry| (entry.command, entry.timestamp))
+        .collect();
+    assert_eq!(
+        commands,
+        vec![
+            ("git status".to_string(), at(1725000000)),
+            ("git push".to_string(), at(1725000005)),
+        ]
+    );
+
+    let fish = "- cmd: echo \"a\\\\b\"\\nls\n  when: 1725000000\n  paths:\n    - /tmp\n- cmd: npm test\n  when: 1725000020\n";
+    let commands: Vec<(String, DateTime<Utc>)> = parse_fish_history(fish)
+        .into_iter()
+        .map(|entry| (entry.command, entry.timestamp))
+        .collect();
+    assert_eq!(
+        commands,
+        vec![
+            ("echo \"a\\b\"\nls".to_string(), at(1725000000)),
+            ("npm test".to_string(), at(1725000020)),
+        ]
+    );
+}
+
+#[test]
+fn test_hooks_post_to_the_api_socket() {
+    for shell in [Shell::Zsh, Shell::Bash, Shell::Fish] {
+        let script = hook_script(shell, Path::new("/home/me/.screenpipe/screenpipe.sock"));
+        assert!(script.contains(
+            "--unix-socket '/home/me/.screenpipe/screenpipe.sock' -X POST http://localhost/shell/commands"
+        ));
+        assert!(script.contains(&format!("shell={}", shell.as_str())));
+        assert!(script.contains("exit_code="));
+    }
+    // a data directory can't end the quoting
+    let script = hook_script(Shell::Zsh, Path::new("/home/o'brien/$(rm -rf ~)/s.sock"));
+    assert!(script.contains(r"--unix-socket '/home/o'\''brien/$(rm -rf ~)/s.sock' -X POST"));
+}
@@ -0,0 +1,236 @@
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Duration, NaiveDate, TimeZone, Utc};
+use image::{DynamicImage, Rgb, RgbImage};
+use screenpipe_integrations::timezone::{day_range_in, parse_timezone, utc_offset_minutes};
+use screenpipe_integrations::vault_export::{
+    local_day_range, DigestEntry, NoteContent, NoteMeeting, NoteMoment, VaultExporter, VaultFormat,
+    VaultNoteSource,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// One of everything, frame 2 can't be read
+#[derive(Default)]
+struct FakeSource {
+    images_read: AtomicUsize,
+}
+
+#[async_trait]
+impl VaultNoteSource for FakeSource {
+    async fn note_content(&self, start: DateTime<Utc>, _end: DateTime<Utc>) -> Result<NoteContent> {
+        let at = |hours| start + Duration::hours(hours);
+        Ok(NoteContent {
+            digest: vec![DigestEntry {
+                label: "coding".to_string(),
+                duration_secs: 3.0 * 3600.0 + 20.0 * 60.0,
+            }],
+            meetings: vec![NoteMeeting {
+                start_time: at(10),
+                end_time: at(11),
+                title: Some("Release sync".to_string()),
+                attendees: vec!["Ana".to_string(), "Bob".to_string()],
+                summary: "Planned the release.".to_string(),
+                decisions: vec!["Ship on friday".to_string()],
+                action_items: vec!["Write the changelog (ana)".to_string()],
+            }],
+            moments: vec![
+                NoteMoment {
+                    timestamp: at(14),
+                    tag: "big orders".to_string(),
+                    app_name: "Salesforce".to_string(),
+                    window_name: "Order 1234".to_string(),
+                    details: vec!["order_number: 1234".to_string()],
+                    frame_id: Some(1),
+                },
+                NoteMoment {
+                    timestamp: at(15),
+                    tag: "orders".to_string(),
+                    app_name: "Salesforce".to_string(),
+                    window_name: "Order 5678".to_string(),
+                    details: vec![],
+                    frame_id: Some(2),
+                },
+            ],
+            last_week: Vec::new(),
+            timezone: None,
+        })
+    }
+
+    async fn frame_image(&self, frame_id: i64) -> Result<DynamicImage> {
+        self.images_read.fetch_add(1, Ordering::SeqCst);
+        if frame_id == 2 {
+            bail!("video chunk deleted");
+        }
+        Ok(DynamicImage::ImageRgb8(RgbImage::from_pixel(
+            8,
+            8,
+            Rgb([0, 0, 0]),
+        )))
+    }
+}
+
+fn date() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 9, 1).unwrap()
+}
+
+#[test]
+fn test_local_day_range() {
+    let (start, end) = local_day_range(date()).unwrap();
+
+    assert!(end - start >= Duration::hours(23) && end - start <= Duration::hours(25));
+}
+
+#[test]
+fn test_day_range_in_a_timezone() {
+    let tokyo = parse_timezone("Asia/Tokyo").unwrap();
+    let (start, end) = day_range_in(date(), &tokyo).unwrap();
+    assert_eq!(start, Utc.with_ymd_and_hms(2024, 8, 31, 15, 0, 0).unwrap());
+    assert_eq!(end - start, Duration::hours(24));
+    assert_eq!(utc_offset_minutes(&tokyo, start), 9 * 60);
+
+    // the day daylight saving time ends has 25 hours
+    let berlin = parse_timezone("Europe/Berlin").unwrap();
+    let day = NaiveDate::from_ymd_opt(2024, 10, 27).unwrap();
+    let (start, end) = day_range_in(day, &berlin).unwrap();
+    assert_eq!(end - start, Duration::hours(25));
+    assert_eq!(utc_offset_minutes(&berlin, start), 120);
+    assert_eq!(utc_offset_minutes(&berlin, end), 60);
+
+    assert!(parse_timezone("Mars/Olympus_Mons").is_err());
+}
+
+#[tokio::test]
+async fn test_note_in_the_timezone_of_the_day() {
+    let vault = tempfile::tempdir().unwrap();
+    let exporter = VaultExporter::new(vault.path().to_path_buf(), VaultFormat::Obsidian);
+    let tokyo = parse_timezone("Asia/Tokyo").unwrap();
+
+    let path = exporter
+        .export_in(&FakeSource::default(), date(), Some(tokyo))
+        .await
+        .unwrap();
+
+    let note = std::fs::read_to_string(&path).unwrap();
+    assert!(note.contains("### 10:00 - 11:00 Release sync"));
+    assert!(note.contains("### 14:00 #big-orders"));
+}
+
+#[tokio::test]
+async fn test_obsidian_note() {
+    let vault = tempfile::tempdir().unwrap();
+    let exporter = VaultExporter::new(vault.path().to_path_buf(), VaultFormat::Obsidian);
+    let source = FakeSource::default();
+
+    let path = exporter.export(&source, date()).await.unwrap();
+
+    assert_eq!(path, vault.path().join("screenpipe").join("2024-09-01.md"));
+    let note = std::fs::read_to_string(&path).unwrap();
+    assert!(note.starts_with("---\ndate: 2024-09-01\ntags: [screenpipe]\n---\n"));
+    assert!(note.contains("- coding: 3h 20m"));
+    assert!(note.contains(" Release sync\n\nWith Ana, Bob\n\nPlanned the release."));
+    assert!(note.contains("- [ ] Write the changelog (ana)"));
+    assert!(note.contains("#big-orders\n\nSalesforce - Order 1234\n- order_number: 1234"));
+    assert!(note.contains("![[screenpipe-frame-1.jpg]]"));
+    assert!(!note.contains("screenpipe-frame-2.jpg"));
+    assert!(vault
+        .path()
+        .join("screenpipe/attachments/screenpipe-frame-1.jpg")
+        .exists());
+
+    // images written before aren't extracted again
+    exporter.export(&source, date()).await.unwrap();
+    assert_eq!(source.images_read.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_logseq_note() {
+    let vault = tempfile::tempdir().unwrap();
+    let exporter = VaultExporter::new(vault.path().to_path_buf(), VaultFormat::Logseq);
+
+    let path = exporter
+        .export(&FakeSource::default(), date())
+        .await
+        .unwrap();
+
+    assert_eq!(path, vault.path().join("pages/screenpipe___2024-09-01.md"));
+    let note = std::fs::read_to_string(&path).unwrap();
+    assert!(note.starts_with("date:: 2024-09-01\ntags:: screenpipe\n"));
+    assert!(note.contains("- ## Digest\n\t- coding: 3h 20m\n"));
+    assert!(note.contains(" Release sync\n\t\t- With Ana, Bob\n"));
+    assert!(note.contains("\t\t- Decisions\n\t\t\t- Ship on friday\n"));
+    assert!(note.contains("\t\t\t- TODO Write the changelog (ana)\n"));
+    assert!(note.contains("\t\t- ![moment](../assets/screenpipe-frame-1.jpg)\n"));
+    assert!(vault.path().join("assets/screenpipe-frame-1.jpg").exists());
+}
+
+#[tokio::test]
+async fn test_empty_day() {
+    struct EmptySource;
+
+    #[async_trait]
+    impl VaultNoteSource for EmptySource {
+        async fn note_content(&self, _: DateTime<Utc>, _: DateTime<Utc>) -> Result<NoteContent> {
+            Ok(NoteContent::default())
+        }
+
+        async fn frame_image(&self, _: i64) -> Result<DynamicImage> {
+            bail!("no frames")
+        }
+    }
+    let vault = tempfile::tempdir().unwrap();
+    let exporter = VaultExporter::new(vault.path().to_path_buf(), VaultFormat::Obsidian);
+
+    let path = exporter.export(&EmptySource, date()).await.unwrap();
+
+    assert!(std::fs::read_to_string(path)
+        .unwrap()
+        .contains("Nothing recorded."));
+}
+
+#[tokio::test]
+async fn test_identical_frames_are_written_once() {
+    struct StillScreen;
+
+    #[async_trait]
+    impl VaultNoteSource for StillScreen {
+        async fn note_content(
+            &self,
+            start: DateTime<Utc>,
+            _: DateTime<Utc>,
+        ) -> Result<NoteContent> {
+            let moment = |frame_id| NoteMoment {
+                timestamp: start + Duration::hours(frame_id),
+                tag: "orders".to_string(),
+                app_name: "Salesforce".to_string(),
+                window_name: "Orders".to_string(),
+                details: vec![],
+                frame_id: Some(frame_id),
+            };
+            Ok(NoteContent {
+                moments: vec![moment(1), moment(2)],
+                ..Default::default()
+            })
+        }
+
+        async fn frame_image(&self, _: i64) -> Result<DynamicImage> {
+            Ok(DynamicImage::ImageRgb8(RgbImage::from_pixel(
+                8,
+                8,
+                Rgb([255, 255, 255]),
+            )))
+        }
+    }
+    let vault = tempfile::tempdir().unwrap();
+    let exporter = VaultExporter::new(vault.path().to_path_buf(), VaultFormat::Obsidian);
+
+    let path = exporter.export(&StillScreen, date()).await.unwrap();
+
+    let note = std::fs::read_to_string(path).unwrap();
+    assert!(note.contains("![[screenpipe-frame-1.jpg]]"));
+    assert!(note.contains("![[screenpipe-frame-2.jpg]]"));
+    let attachments = exporter.attachments_dir();
+    assert_eq!(
+        std::fs::read(attachments.join("screenpipe-frame-1.jpg")).unwrap(),
+        std::fs::read(attachments.join("screenpipe-frame-2.jpg")).unwrap()
+    );
+}
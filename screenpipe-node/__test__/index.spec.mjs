@@ -0,0 +1,52 @@
+import { test } from 'node:test'
+import assert from 'node:assert/strict'
+import { mkdtempSync } from 'node:fs'
+import { createRequire } from 'node:module'
+import { tmpdir } from 'node:os'
+import { join } from 'node:path'
+
+const { Engine } = createRequire(import.meta.url)('../index.js')
+
+const open = () =>
+  new Engine({ data_dir: mkdtempSync(join(tmpdir(), 'screenpipe-node-')), disable_audio: true })
+
+test('engines are searched in-process', async () => {
+  const engine = open()
+  assert.deepEqual(await engine.search({ q: 'invoice', content_type: 'ocr' }), [])
+  assert.deepEqual(await engine.search(), [])
+  assert.throws(() => engine.search({ content_type: 'video' }), /video/)
+  assert.equal(engine.capturing, false)
+  await assert.rejects(engine.stopCapture(), /isn't running/)
+  await assert.rejects(engine.frame(42), /frame 42 not found/)
+
+  engine.close()
+  assert.throws(() => engine.search(), /the engine is closed/)
+})
+
+test('bad configs are thrown', () => {
+  assert.throws(() => new Engine({}), /data_dir is required/)
+  assert.throws(
+    () => new Engine({ data_dir: tmpdir(), ocr_engine: 'paper' }),
+    /unknown ocr engine paper/,
+  )
+})
+
+test('subscribers get the events until they unsubscribe', async () => {
+  const engine = open()
+  const events = []
+  const subscription = engine.subscribe((name, data) => events.push({ name, data }))
+  // the subscription listens once its task runs
+  while (!events.length) {
+    engine.emit('node.test', { frame_id: 7 })
+    await new Promise((resolve) => setTimeout(resolve, 20))
+  }
+  assert.deepEqual(events[0], { name: 'node.test', data: { frame_id: 7 } })
+
+  assert.equal(engine.unsubscribe(subscription), true)
+  assert.equal(engine.unsubscribe(subscription), false)
+  const seen = events.length
+  engine.emit('node.test', {})
+  await new Promise((resolve) => setTimeout(resolve, 100))
+  assert.equal(events.length, seen)
+  engine.close()
+})
@@ -0,0 +1,40 @@
+/* tslint:disable */
+/* eslint-disable */
+
+/* auto-generated by NAPI-RS */
+
+/** A frame as RGBA pixels, `data` is the memory the frame was decoded into, not a copy */
+export interface Frame {
+  width: number
+  height: number
+  data: Buffer
+}
+/**
+ * An engine opened on a data dir, `new Engine({data_dir: "/Users/me/.screenpipe"})`. The
+ * fields of the config and of the queries are the ones of the C ABI, snake_case like the api.
+ */
+export class Engine {
+  constructor(config: any)
+  startCapture(): Promise<void>
+  /** Resolves once the frames and audio in flight are written */
+  stopCapture(): Promise<void>
+  get capturing(): boolean
+  /**
+   * The fields of `/search`, resolves to `{"OCR": {...}}`, `{"Audio": {...}}` and
+   * `{"FTS": {...}}` results
+   */
+  search(query?: any | undefined | null): Promise<Array<Record<string, any>>>
+  /** The image of a frame of the search results */
+  frame(frameId: number): Promise<Frame>
+  /**
+   * Calls `callback` with each event until `unsubscribe`, which node waits for before
+   * exiting. Returns the id of the subscription.
+   */
+  subscribe(callback: (name: string, data: any) => void): number
+  /** False for an unknown id */
+  unsubscribe(subscription: number): boolean
+  /** Sends an event to the subscribers of the engine, the pipes and the `/events` stream */
+  emit(name: string, data?: any | undefined | null): void
+  /** Stops the capture and the subscriptions once the calls in flight are done */
+  close(): void
+}
@@ -0,0 +1,2 @@
+// the addon `npm run build` puts next to this file
+module.exports = require('./screenpipe.node')
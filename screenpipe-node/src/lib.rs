@@ -0,0 +1,212 @@
+//! Node.js bindings of the screenpipe engine, the same engine as the C ABI of screenpipe-ffi
+//! for Electron and the js tooling of the pipes to use in-process rather than through the
+//! http api.
+//!
+//! The blocking calls (capture, search, frames) run on the libuv thread pool and return
+//! promises, the events are delivered on the js thread.
+use napi::bindgen_prelude::*;
+use napi::threadsafe_function::{
+    ErrorStrategy, ThreadSafeCallContext, ThreadsafeFunction, ThreadsafeFunctionCallMode,
+};
+use napi::{JsFunction, JsUnknown};
+use napi_derive::napi;
+use screenpipe::{EngineConfig, SearchQuery};
+use screenpipe_core::{emit_event, Event};
+use std::sync::Arc;
+
+/// Events waiting for the js thread, the newer ones are dropped past it
+const EVENTS_QUEUE: usize = 1024;
+
+fn to_napi_error(e: impl std::fmt::Display) -> Error {
+    Error::from_reason(e.to_string())
+}
+
+/// A frame as RGBA pixels, `data` is the memory the frame was decoded into, not a copy
+#[napi(object)]
+pub struct Frame {
+    pub width: u32,
+    pub height: u32,
+    pub data: Buffer,
+}
+
+pub struct CaptureTask {
+    engine: Arc<screenpipe::Engine>,
+    start: bool,
+}
+
+#[napi]
+impl Task for CaptureTask {
+    type Output = ();
+    type JsValue = ();
+
+    fn compute(&mut self) -> Result<()> {
+        if self.start {
+            self.engine.start_capture()
+        } else {
+            self.engine.stop_capture()
+        }
+        .map_err(|e| Error::from_reason(format!("{:#}", e)))
+    }
+
+    fn resolve(&mut self, _env: Env, _output: ()) -> Result<()> {
+        Ok(())
+    }
+}
+
+pub struct SearchTask {
+    engine: Arc<screenpipe::Engine>,
+    query: SearchQuery,
+}
+
+#[napi]
+impl Task for SearchTask {
+    type Output = serde_json::Value;
+    type JsValue = JsUnknown;
+
+    fn compute(&mut self) -> Result<serde_json::Value> {
+        let results = self
+            .engine
+            .search(&self.query)
+            .map_err(|e| Error::from_reason(format!("{:#}", e)))?;
+        serde_json::to_value(results).map_err(to_napi_error)
+    }
+
+    fn resolve(&mut self, env: Env, output: serde_json::Value) -> Result<JsUnknown> {
+        env.to_js_value(&output)
+    }
+}
+
+pub struct FrameTask {
+    engine: Arc<screenpipe::Engine>,
+    frame_id: i64,
+}
+
+#[napi]
+impl Task for FrameTask {
+    type Output = image::RgbaImage;
+    type JsValue = Frame;
+
+    fn compute(&mut self) -> Result<image::RgbaImage> {
+        let image = self.engine.frame(self.frame_id).map_err(to_napi_error)?;
+        Ok(image.into_rgba8())
+    }
+
+    fn resolve(&mut self, _env: Env, output: image::RgbaImage) -> Result<Frame> {
+        Ok(Frame {
+            width: output.width(),
+            height: output.height(),
+            // the vec becomes the backing store of the js buffer
+            data: output.into_raw().into(),
+        })
+    }
+}
+
+/// An engine opened on a data dir, `new Engine({data_dir: "/Users/me/.screenpipe"})`. The
+/// fields of the config and of the queries are the ones of the C ABI, snake_case like the api.
+#[napi]
+pub struct Engine {
+    engine: Option<Arc<screenpipe::Engine>>,
+}
+
+#[napi]
+impl Engine {
+    #[napi(constructor)]
+    pub fn new(config: serde_json::Value) -> Result<Self> {
+        let config: EngineConfig = serde_json::from_value(config).map_err(to_napi_error)?;
+        let engine =
+            screenpipe::Engine::new(config).map_err(|e| Error::from_reason(format!("{:#}", e)))?;
+        Ok(Engine {
+            engine: Some(Arc::new(engine)),
+        })
+    }
+
+    fn engine(&self) -> Result<Arc<screenpipe::Engine>> {
+        self.engine
+            .clone()
+            .ok_or_else(|| Error::from_reason("the engine is closed"))
+    }
+
+    #[napi]
+    pub fn start_capture(&self) -> Result<AsyncTask<CaptureTask>> {
+        Ok(AsyncTask::new(CaptureTask {
+            engine: self.engine()?,
+            start: true,
+        }))
+    }
+
+    /// Resolves once the frames and audio in flight are written
+    #[napi]
+    pub fn stop_capture(&self) -> Result<AsyncTask<CaptureTask>> {
+        Ok(AsyncTask::new(CaptureTask {
+            engine: self.engine()?,
+            start: false,
+        }))
+    }
+
+    #[napi(getter)]
+    pub fn capturing(&self) -> Result<bool> {
+        Ok(self.engine()?.is_capturing())
+    }
+
+    /// The fields of `/search`, resolves to `{"OCR": {...}}`, `{"Audio": {...}}` and
+    /// `{"FTS": {...}}` results
+    #[napi(ts_return_type = "Promise<Array<Record<string, any>>>")]
+    pub fn search(&self, query: Option<serde_json::Value>) -> Result<AsyncTask<SearchTask>> {
+        let query = match query {
+            Some(query) => serde_json::from_value(query).map_err(to_napi_error)?,
+            None => SearchQuery::default(),
+        };
+        Ok(AsyncTask::new(SearchTask {
+            engine: self.engine()?,
+            query,
+        }))
+    }
+
+    /// The image of a frame of the search results
+    #[napi]
+    pub fn frame(&self, frame_id: i64) -> Result<AsyncTask<FrameTask>> {
+        Ok(AsyncTask::new(FrameTask {
+            engine: self.engine()?,
+            frame_id,
+        }))
+    }
+
+    /// Calls `callback` with each event until `unsubscribe`, which node waits for before
+    /// exiting. Returns the id of the subscription.
+    #[napi(ts_args_type = "callback: (name: string, data: any) => void")]
+    pub fn subscribe(&self, callback: JsFunction) -> Result<i64> {
+        let engine = self.engine()?;
+        let on_event: ThreadsafeFunction<Event, ErrorStrategy::Fatal> = callback
+            .create_threadsafe_function(
+                EVENTS_QUEUE,
+                |ctx: ThreadSafeCallContext<Event>| -> Result<Vec<JsUnknown>> {
+                    let name = ctx.env.create_string(&ctx.value.name)?.into_unknown();
+                    Ok(vec![name, ctx.env.to_js_value(&ctx.value.data)?])
+                },
+            )?;
+        let id = engine.subscribe(move |event| {
+            on_event.call(event.clone(), ThreadsafeFunctionCallMode::NonBlocking);
+        });
+        Ok(id as i64)
+    }
+
+    /// False for an unknown id
+    #[napi]
+    pub fn unsubscribe(&self, subscription: i64) -> Result<bool> {
+        Ok(self.engine()?.unsubscribe(subscription as u64))
+    }
+
+    /// Sends an event to the subscribers of the engine, the pipes and the `/events` stream
+    #[napi]
+    pub fn emit(&self, name: String, data: Option<serde_json::Value>) -> Result<()> {
+        self.engine()?;
+        emit_event(&name, data.unwrap_or(serde_json::Value::Null));
+        Ok(())
+    }
+
+    /// Stops the capture and the subscriptions once the calls in flight are done
+    #[napi]
+    pub fn close(&mut self) {
+        self.engine = None;
+    }
+}
@@ -0,0 +1,368 @@
+use crate::power::background_jobs_paused;
+use crate::DatabaseManager;
+use anyhow::Result;
+use candle::{Device, Tensor};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Not looking at the screen for longer than this ends the session
+const MAX_IDLE_GAP: ChronoDuration = ChronoDuration::minutes(5);
+/// Quick app switches are noise, not activities
+const MIN_SESSION_DURATION: ChronoDuration = ChronoDuration::seconds(30);
+/// How far back we look for sessions when nothing has been classified yet
+const INITIAL_LOOKBACK: ChronoDuration = ChronoDuration::hours(24);
+const MAX_WINDOW_NAMES_PER_SESSION: usize = 50;
+/// Sharpens the softmax so a single strong keyword is enough
+const LOGIT_SCALE: f64 = 3.0;
+/// Below this the session is tagged as other
+const MIN_CONFIDENCE: f32 = 0.4;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ActivityCategory {
+    Coding,
+    Email,
+    Meetings,
+    Browsing,
+    Entertainment,
+    Other,
+}
+
+impl ActivityCategory {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ActivityCategory::Coding => "coding",
+            ActivityCategory::Email => "email",
+            ActivityCategory::Meetings => "meetings",
+            ActivityCategory::Browsing => "browsing",
+            ActivityCategory::Entertainment => "entertainment",
+            ActivityCategory::Other => "other",
+        }
+    }
+}
+
+impl FromStr for ActivityCategory {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "coding" => Ok(ActivityCategory::Coding),
+            "email" => Ok(ActivityCategory::Email),
+            "meetings" => Ok(ActivityCategory::Meetings),
+            "browsing" => Ok(ActivityCategory::Browsing),
+            "entertainment" => Ok(ActivityCategory::Entertainment),
+            "other" => Ok(ActivityCategory::Other),
+            _ => anyhow::bail!("unknown activity category: {}", s),
+        }
+    }
+}
+
+/// Words of app and window names hinting at each category, with their weight.
+/// Browsers show everything so their names weigh less than what the tab is about.
+#[rustfmt::skip]
+const CATEGORY_KEYWORDS: &[(ActivityCategory, f32, &[&str])] = &[
+    (
+        ActivityCategory::Coding,
+        1.0,
+        &[
+            "code", "vscode", "cursor", "xcode", "intellij", "pycharm", "webstorm", "goland",
+            "clion", "rider", "vim", "nvim", "neovim", "emacs", "zed", "terminal", "iterm",
+            "iterm2", "warp", "alacritty", "kitty", "wezterm", "powershell", "github", "gitlab",
+            "bitbucket", "stackoverflow", "localhost", "docker", "cargo", "npm", "rs", "py",
+            "ts", "tsx", "js", "go", "java", "cpp", "commit", "pull", "merge", "debug",
+        ],
+    ),
+    (
+        ActivityCategory::Email,
+        1.0,
+        &[
+            "mail", "gmail", "outlook", "inbox", "thunderbird", "spark", "superhuman",
+            "protonmail", "airmail", "mimestream", "compose", "unread", "drafts",
+        ],
+    ),
+    (
+        ActivityCategory::Meetings,
+        1.0,
+        &[
+            "zoom", "meet", "meeting", "teams", "webex", "facetime", "huddle", "whereby",
+            "gotomeeting", "call", "standup",
+        ],
+    ),
+    (
+        ActivityCategory::Browsing,
+        0.4,
+        &[
+            "chrome", "safari", "firefox", "arc", "edge", "brave", "opera", "vivaldi", "google",
+            "search", "wikipedia", "reddit", "news",
+        ],
+    ),
+    (
+        ActivityCategory::Entertainment,
+        1.0,
+        &[
+            "youtube", "netflix", "spotify", "twitch", "hulu", "disney", "vlc", "steam", "game",
+            "tiktok", "instagram", "music", "podcast", "primevideo",
+        ],
+    ),
+];
+
+fn tokenize(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+}
+
+/// Linear bag-of-words classifier over app and window names, runs on the cpu with candle.
+/// Everything stays on the machine, no model download and no cloud call.
+pub struct ActivityClassifier {
+    vocabulary: HashMap<String, usize>,
+    /// (vocabulary, categories)
+    weights: Tensor,
+    categories: Vec<ActivityCategory>,
+    device: Device,
+}
+
+impl ActivityClassifier {
+    pub fn new() -> Result<Self> {
+        let device = Device::Cpu;
+        let categories: Vec<ActivityCategory> =
+            CATEGORY_KEYWORDS.iter().map(|(c, _, _)| *c).collect();
+
+        let mut vocabulary = HashMap::new();
+        for (_, _, keywords) in CATEGORY_KEYWORDS {
+            for keyword in keywords.iter() {
+                let next = vocabulary.len();
+                vocabulary.entry(keyword.to_string()).or_insert(next);
+            }
+        }
+
+        let mut weights = vec![0f32; vocabulary.len() * categories.len()];
+        for (category_index, (_, weight, keywords)) in CATEGORY_KEYWORDS.iter().enumerate() {
+            for keyword in keywords.iter() {
+                weights[vocabulary[*keyword] * categories.len() + category_index] = *weight;
+            }
+        }
+        let weights = Tensor::from_vec(weights, (vocabulary.len(), categories.len()), &device)?;
+
+        Ok(ActivityClassifier {
+            vocabulary,
+            weights,
+            categories,
+            device,
+        })
+    }
+
+    /// Returns the most likely category and its probability
+    pub fn classify(&self, text: &str) -> Result<(ActivityCategory, f32)> {
+        let mut features = vec![0f32; self.vocabulary.len()];
+        let mut known_tokens = 0;
+        for token in tokenize(text) {
+            if let Some(index) = self.vocabulary.get(&token) {
+                features[*index] += 1.0;
+                known_tokens += 1;
+            }
+        }
+        if known_tokens == 0 {
+            return Ok((ActivityCategory::Other, 0.0));
+        }
+
+        // normalize so long sessions with many window titles don't get overconfident
+        let features = Tensor::from_vec(features, (1, self.vocabulary.len()), &self.device)?
+            .affine(1.0 / known_tokens as f64, 0.0)?;
+        let logits = features
+            .matmul(&self.weights)?
+            .affine(LOGIT_SCALE * known_tokens.min(4) as f64, 0.0)?;
+        let probabilities = candle_nn::ops::softmax_last_dim(&logits)?
+            .squeeze(0)?
+            .to_vec1::<f32>()?;
+
+        let (index, confidence) = probabilities
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(i, p)| (i, *p))
+            .unwrap_or((0, 0.0));
+        if confidence < MIN_CONFIDENCE {
+            return Ok((ActivityCategory::Other, confidence));
+        }
+        Ok((self.categories[index], confidence))
+    }
+}
+
+/// What sessions are tagged with when the classifier can't run: the category with the most
+/// keyword weight and its share of the weight of all keywords found, other with 0 when there
+/// are none
+pub fn classify_by_keywords(text: &str) -> (ActivityCategory, f32) {
+    let mut scores = [0f32; CATEGORY_KEYWORDS.len()];
+    for token in tokenize(text) {
+        for (score, (_, weight, keywords)) in scores.iter_mut().zip(CATEGORY_KEYWORDS) {
+            if keywords.contains(&token.as_str()) {
+                *score += weight;
+            }
+        }
+    }
+    let total: f32 = scores.iter().sum();
+    if total == 0.0 {
+        return (ActivityCategory::Other, 0.0);
+    }
+    // on a tie the category listed first wins
+    let (index, score) =
+        scores.iter().enumerate().fold(
+            (0, 0f32),
+            |best, (i, score)| if *score > best.1 { (i, *score) } else { best },
+        );
+    let confidence = score / total;
+    if confidence < MIN_CONFIDENCE {
+        return (ActivityCategory::Other, confidence);
+    }
+    (CATEGORY_KEYWORDS[index].0, confidence)
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ActivitySession {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub app_name: String,
+    pub category: ActivityCategory,
+    pub confidence: f64,
+    pub created_at: DateTime<Utc>,
+    /// The focus mode or do not disturb on during the session, the first when there were more
+    pub focus_mode: Option<String>,
+}
+
+/// Time spent per category
+#[derive(Debug, Clone, Serialize)]
+pub struct CategoryDuration {
+    pub category: ActivityCategory,
+    pub sessions: i64,
+    pub duration_secs: f64,
+}
+
+/// Focused app during a session, before classification
+#[derive(Debug, Clone, PartialEq)]
+pub struct AppSession {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub app_name: String,
+    pub window_names: Vec<String>,
+}
+
+impl AppSession {
+    fn classification_text(&self) -> String {
+        format!("{}\n{}", self.app_name, self.window_names.join("\n"))
+    }
+}
+
+/// Groups ascending (timestamp, app, window) of the focused window into sessions,
+/// a new session starts when the app changes or after `max_gap` without frames.
+/// The last session is returned even if it's still ongoing.
+pub fn detect_app_sessions(
+    windows: &[(DateTime<Utc>, String, String)],
+    max_gap: ChronoDuration,
+) -> Vec<AppSession> {
+    let mut sessions: Vec<AppSession> = Vec::new();
+    for (timestamp, app_name, window_name) in windows {
+        match sessions.last_mut() {
+            Some(session)
+                if session.app_name == *app_name && *timestamp - session.end_time <= max_gap =>
+            {
+                session.end_time = *timestamp;
+                if session.window_names.len() < MAX_WINDOW_NAMES_PER_SESSION
+                    && !session.window_names.contains(window_name)
+                {
+                    session.window_names.push(window_name.clone());
+                }
+            }
+            _ => sessions.push(AppSession {
+                start_time: *timestamp,
+                end_time: *timestamp,
+                app_name: app_name.clone(),
+                window_names: vec![window_name.clone()],
+            }),
+        }
+    }
+    sessions
+}
+
+async fn classify_ended_sessions(
+    db: &DatabaseManager,
+    classifier: Option<&ActivityClassifier>,
+) -> Result<()> {
+    let now = Utc::now();
+    let since = db
+        .get_last_activity_session_end_time()
+        .await?
+        .unwrap_or(now - INITIAL_LOOKBACK);
+
+    let windows = db.get_focused_windows_since(since).await?;
+    let mut sessions = detect_app_sessions(&windows, MAX_IDLE_GAP);
+
+    // the user might still be in the last app
+    if sessions
+        .last()
+        .is_some_and(|last| now - last.end_time <= MAX_IDLE_GAP)
+    {
+        sessions.pop();
+    }
+
+    for session in sessions {
+        if session.end_time - session.start_time < MIN_SESSION_DURATION {
+            continue;
+        }
+        let text = session.classification_text();
+        let (category, confidence) = match classifier.map(|c| c.classify(&text)) {
+            Some(Ok(classified)) => classified,
+            Some(Err(e)) => {
+                warn!("Failed to classify a session, using its keywords: {}", e);
+                classify_by_keywords(&text)
+            }
+            None => classify_by_keywords(&text),
+        };
+        debug!(
+            "Session in {} from {} to {} is {} ({:.2})",
+            session.app_name,
+            session.start_time,
+            session.end_time,
+            category.as_str(),
+            confidence
+        );
+        db.insert_activity_session(
+            session.start_time,
+            session.end_time,
+            &session.app_name,
+            category,
+            confidence,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
+/// Periodically tags the sessions that ended with their activity category
+pub async fn start_activity_classifier(db: Arc<DatabaseManager>, interval: Duration) {
+    let classifier = match ActivityClassifier::new() {
+        Ok(classifier) => Some(classifier),
+        Err(e) => {
+            warn!(
+                "Failed to start the activity classifier, tagging sessions by keywords: {}",
+                e
+            );
+            None
+        }
+    };
+    info!("Activity classifier started");
+    loop {
+        if !background_jobs_paused() {
+            if let Err(e) = classify_ended_sessions(&db, classifier.as_ref()).await {
+                error!("Failed to classify activity sessions: {}", e);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
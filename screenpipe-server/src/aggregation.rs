@@ -0,0 +1,55 @@
+use crate::forget::{forget, ForgetFilter};
+use crate::DatabaseManager;
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{debug, info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Raw data has to outlive the idle gap closing an activity session and a pass of the
+/// classifier, or the session would lose its frames before it is tagged
+pub const MIN_RAW_RETENTION: ChronoDuration = ChronoDuration::minutes(10);
+
+/// The totals are counted once past the raw retention, then the frames, images, transcriptions
+/// and audio are deleted like a forget request ending then. Activity sessions and the hourly
+/// totals of /analytics/usage are all that's left
+pub async fn start_aggregation_only(
+    db: Arc<DatabaseManager>,
+    raw_retention: ChronoDuration,
+    interval: Duration,
+) {
+    let raw_retention = if raw_retention < MIN_RAW_RETENTION {
+        warn!(
+            "Raw data is kept at least {} minutes to classify activity sessions",
+            MIN_RAW_RETENTION.num_minutes()
+        );
+        MIN_RAW_RETENTION
+    } else {
+        raw_retention
+    };
+    info!(
+        "Aggregation only mode started, raw data is deleted after {} minutes",
+        raw_retention.num_minutes()
+    );
+    loop {
+        let until = Utc::now() - raw_retention;
+        // nothing is deleted before it's counted
+        match db.aggregate_usage(until).await {
+            Ok(counted) => {
+                debug!(
+                    "Counted {} frames and transcriptions in the usage totals",
+                    counted
+                );
+                let filter = ForgetFilter {
+                    end_time: Some(until),
+                    keep_activity_sessions: true,
+                    ..Default::default()
+                };
+                if let Err(e) = forget(&db, &filter, false).await {
+                    warn!("Failed to delete aggregated raw data: {}", e);
+                }
+            }
+            Err(e) => warn!("Failed to aggregate usage: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,288 @@
+use crate::DatabaseManager;
+use anyhow::{bail, Result};
+use arrow::array::{Array, AsArray};
+use arrow::datatypes::{DataType, Float64Type, Int64Type, TimeUnit, TimestampMicrosecondType};
+use arrow::record_batch::RecordBatch;
+use chrono::{DateTime, Duration as ChronoDuration, SecondsFormat, Utc};
+use futures::TryStreamExt;
+use screenpipe_integrations::analytics_export::{AnalyticsRows, AnalyticsTable, SessionRow};
+use serde::Serialize;
+use sqlx::query::Query;
+use sqlx::sqlite::{Sqlite, SqliteArguments, SqliteConnection, SqliteRow};
+use sqlx::{Column, Connection, Executor, Row, Statement, TypeInfo, ValueRef};
+use std::time::{Duration, Instant};
+
+pub const ANALYTICS_TABLES: [AnalyticsTable; 4] = [
+    AnalyticsTable::Sessions,
+    AnalyticsTable::Ocr,
+    AnalyticsTable::Transcripts,
+    AnalyticsTable::Usage,
+];
+/// Rows returned when the query doesn't ask for less
+pub const DEFAULT_QUERY_ROWS: usize = 1000;
+pub const MAX_QUERY_ROWS: usize = 10_000;
+/// A query still running after this is interrupted
+pub const QUERY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Tables of queries without a start time begin this long ago, copying everything would be slow
+pub const DEFAULT_QUERY_WINDOW: ChronoDuration = ChronoDuration::days(7);
+/// Virtual machine steps between checks of the timeout
+const PROGRESS_STEPS: i32 = 1000;
+
+/// The rows of the table between the two times, oldest first
+pub async fn analytics_rows(
+    db: &DatabaseManager,
+    table: AnalyticsTable,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> Result<AnalyticsRows> {
+    Ok(match table {
+        AnalyticsTable::Sessions => {
+            let mut sessions = db
+                .get_activity_sessions(None, start_time, end_time, u32::MAX, 0, None)
+                .await?;
+            sessions.reverse();
+            AnalyticsRows::Sessions(
+                sessions
+                    .into_iter()
+                    .map(|session| SessionRow {
+                        id: session.id,
+                        start_time: session.start_time,
+                        end_time: session.end_time,
+                        app_name: session.app_name,
+                        category: session.category.as_str().to_string(),
+                        confidence: session.confidence,
+                    })
+                    .collect(),
+            )
+        }
+        AnalyticsTable::Ocr => {
+            AnalyticsRows::Ocr(db.get_ocr_analytics(start_time, end_time).await?)
+        }
+        AnalyticsTable::Transcripts => {
+            AnalyticsRows::Transcripts(db.get_transcript_analytics(start_time, end_time).await?)
+        }
+        AnalyticsTable::Usage => {
+            AnalyticsRows::Usage(db.get_usage_aggregates(start_time, end_time).await?)
+        }
+    })
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AnalyticsQueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// More rows were left out than the limit
+    pub truncated: bool,
+}
+
+/// Skips string literals, quoted names and comments, so a `;` inside them isn't taken as the end
+/// of the statement
+fn statement_end(sql: &str) -> Option<usize> {
+    let bytes = sql.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() && bytes[i] != quote {
+                    i += 1;
+                }
+            }
+            b'[' => {
+                while i < bytes.len() && bytes[i] != b']' {
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                while i < bytes.len() && bytes[i] != b'\n' {
+                    i += 1;
+                }
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i += 2;
+                while i < bytes.len() && !(bytes[i] == b'*' && bytes.get(i + 1) == Some(&b'/')) {
+                    i += 1;
+                }
+                i += 1;
+            }
+            b';' => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    None
+}
+
+fn only_comments(mut sql: &str) -> bool {
+    loop {
+        sql = sql.trim_start();
+        if let Some(rest) = sql.strip_prefix("--") {
+            sql = rest.split_once('\n').map_or("", |(_, rest)| rest);
+        } else if let Some(rest) = sql.strip_prefix("/*") {
+            sql = rest.split_once("*/").map_or("", |(_, rest)| rest);
+        } else {
+            return sql.is_empty();
+        }
+    }
+}
+
+/// The query without its final `;`, refused unless it's a single SELECT. It can only see the
+/// analytics tables anyway, this keeps ATTACH and pragmas out
+pub fn validate_query(sql: &str) -> Result<&str> {
+    let sql = sql.trim();
+    let statement = match statement_end(sql) {
+        Some(end) if only_comments(&sql[end + 1..]) => &sql[..end],
+        Some(_) => bail!("only one statement can be run"),
+        None => sql,
+    };
+    let first_word = statement
+        .split(|c: char| !c.is_ascii_alphabetic())
+        .find(|word| !word.is_empty())
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    if first_word != "SELECT" && first_word != "WITH" {
+        bail!("only SELECT queries can be run");
+    }
+    Ok(statement)
+}
+
+/// Tables named in the query, the others aren't loaded
+fn referenced_tables(sql: &str) -> Vec<AnalyticsTable> {
+    let words: Vec<String> = sql
+        .split(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+        .map(|word| word.to_ascii_lowercase())
+        .collect();
+    ANALYTICS_TABLES
+        .into_iter()
+        .filter(|table| words.iter().any(|word| word == table.as_str()))
+        .collect()
+}
+
+fn bind_value<'q>(
+    query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    column: &'q dyn Array,
+    row: usize,
+) -> Result<Query<'q, Sqlite, SqliteArguments<'q>>> {
+    let valid = column.is_valid(row);
+    Ok(match column.data_type() {
+        DataType::Int64 => {
+            query.bind(Some(column.as_primitive::<Int64Type>().value(row)).filter(|_| valid))
+        }
+        DataType::Float64 => {
+            query.bind(Some(column.as_primitive::<Float64Type>().value(row)).filter(|_| valid))
+        }
+        DataType::Boolean => query.bind(Some(column.as_boolean().value(row)).filter(|_| valid)),
+        DataType::Utf8 => query.bind(Some(column.as_string::<i32>().value(row)).filter(|_| valid)),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            let micros = column.as_primitive::<TimestampMicrosecondType>().value(row);
+            query.bind(
+                DateTime::from_timestamp_micros(micros)
+                    .filter(|_| valid)
+                    .map(|time| time.to_rfc3339_opts(SecondsFormat::Millis, true)),
+            )
+        }
+        other => bail!("can't load {} columns", other),
+    })
+}
+
+fn sql_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Int64 | DataType::Boolean => "INTEGER",
+        DataType::Float64 => "REAL",
+        // timestamps are ISO 8601 text the date functions read
+        _ => "TEXT",
+    }
+}
+
+/// Copies the batch into a table of the scratch database
+async fn load_table(conn: &mut SqliteConnection, name: &str, batch: &RecordBatch) -> Result<()> {
+    let schema = batch.schema();
+    let columns: Vec<String> = schema
+        .fields()
+        .iter()
+        .map(|field| format!("{} {}", field.name(), sql_type(field.data_type())))
+        .collect();
+    conn.execute(format!("CREATE TABLE {} ({})", name, columns.join(", ")).as_str())
+        .await?;
+
+    let placeholders = vec!["?"; batch.num_columns()].join(", ");
+    let insert = format!("INSERT INTO {} VALUES ({})", name, placeholders);
+    let mut tx = conn.begin().await?;
+    for row in 0..batch.num_rows() {
+        let mut query = sqlx::query(&insert);
+        for column in batch.columns() {
+            query = bind_value(query, column.as_ref(), row)?;
+        }
+        query.execute(&mut *tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+fn json_value(row: &SqliteRow, index: usize) -> Result<serde_json::Value> {
+    let value = row.try_get_raw(index)?;
+    if value.is_null() {
+        return Ok(serde_json::Value::Null);
+    }
+    let type_name = value.type_info().name().to_string();
+    Ok(match type_name.as_str() {
+        "INTEGER" => row.try_get::<i64, _>(index)?.into(),
+        "REAL" => row.try_get::<f64, _>(index)?.into(),
+        "BLOB" => serde_json::Value::Null,
+        _ => row.try_get::<String, _>(index)?.into(),
+    })
+}
+
+/// Runs a SELECT over copies of the analytics tables between the two times, in a database of
+/// its own so nothing else can be read or changed. Stops after `max_rows` or `QUERY_TIMEOUT`
+pub async fn query_analytics(
+    db: &DatabaseManager,
+    sql: &str,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+    max_rows: usize,
+) -> Result<AnalyticsQueryResult> {
+    let sql = validate_query(sql)?;
+    let mut conn = SqliteConnection::connect("sqlite::memory:").await?;
+    for table in referenced_tables(sql) {
+        let rows = analytics_rows(db, table, start_time, end_time).await?;
+        load_table(&mut conn, table.as_str(), &rows.record_batch()?).await?;
+    }
+    conn.execute("PRAGMA query_only = ON").await?;
+
+    let deadline = Instant::now() + QUERY_TIMEOUT;
+    conn.lock_handle()
+        .await?
+        .set_progress_handler(PROGRESS_STEPS, move || Instant::now() < deadline);
+
+    let statement = conn.prepare(sql).await?;
+    let columns: Vec<String> = statement
+        .columns()
+        .iter()
+        .map(|column| column.name().to_string())
+        .collect();
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    let mut stream = statement.query().fetch(&mut conn);
+    while let Some(row) = stream.try_next().await.map_err(|e| match e {
+        _ if Instant::now() >= deadline => {
+            anyhow::anyhow!("query took more than {}s", QUERY_TIMEOUT.as_secs())
+        }
+        e => e.into(),
+    })? {
+        if rows.len() == max_rows {
+            truncated = true;
+            break;
+        }
+        rows.push(
+            (0..row.len())
+                .map(|index| json_value(&row, index))
+                .collect::<Result<_>>()?,
+        );
+    }
+    Ok(AnalyticsQueryResult {
+        columns,
+        rows,
+        truncated,
+    })
+}
@@ -0,0 +1,285 @@
+use anyhow::Result;
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+use log::{debug, info, warn};
+use screenpipe_core::subscribe_events;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast::error::RecvError;
+
+/// Side of the stored icons, bigger ones are scaled down
+pub const ICON_SIZE: u32 = 128;
+/// Apps without an icon are looked up again after this long, they may have been installed since
+const MISSING_ICON_RETRY: Duration = Duration::from_secs(60 * 60);
+/// Sizes of the freedesktop icon themes, the first one found is used
+const THEME_ICON_SIZES: [u32; 7] = [256, 128, 512, 96, 64, 48, 32];
+
+/// The icons of the apps, looked up on the machine once and kept as png in `dir`
+pub struct AppIcons {
+    dir: PathBuf,
+    missing: Mutex<HashMap<String, Instant>>,
+}
+
+impl AppIcons {
+    pub fn new(dir: PathBuf) -> Self {
+        AppIcons {
+            dir,
+            missing: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn icon_path(&self, app_name: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", icon_file_stem(app_name)))
+    }
+
+    /// The png of the app's icon, None when the app has no icon screenpipe can read
+    pub async fn icon(&self, app_name: &str) -> Result<Option<Vec<u8>>> {
+        let path = self.icon_path(app_name);
+        if let Ok(png) = tokio::fs::read(&path).await {
+            return Ok(Some(png));
+        }
+        if self
+            .missing
+            .lock()
+            .unwrap()
+            .get(app_name)
+            .is_some_and(|looked_up| looked_up.elapsed() < MISSING_ICON_RETRY)
+        {
+            return Ok(None);
+        }
+
+        let name = app_name.to_string();
+        let Some(icon) = tokio::task::spawn_blocking(move || find_icon(&name)).await? else {
+            debug!("No icon found for {}", app_name);
+            self.missing
+                .lock()
+                .unwrap()
+                .insert(app_name.to_string(), Instant::now());
+            return Ok(None);
+        };
+        let png = encode_icon(&icon)?;
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&path, &png).await?;
+        info!("Cached the icon of {}", app_name);
+        Ok(Some(png))
+    }
+}
+
+fn icon_file_stem(app_name: &str) -> String {
+    let stem: String = app_name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    if stem.is_empty() {
+        "_".to_string()
+    } else {
+        stem
+    }
+}
+
+pub fn encode_icon(icon: &DynamicImage) -> Result<Vec<u8>> {
+    let icon = if icon.width() > ICON_SIZE || icon.height() > ICON_SIZE {
+        icon.resize(ICON_SIZE, ICON_SIZE, FilterType::Lanczos3)
+    } else {
+        icon.clone()
+    };
+    let mut png = Vec::new();
+    icon.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// The icon of the desktop entry of the app, matched on its name, window class, executable or
+/// file name. Only png icons are found, the svg ones of the themes aren't decoded
+pub fn find_desktop_icon(
+    app_name: &str,
+    application_dirs: &[PathBuf],
+    icon_dirs: &[PathBuf],
+) -> Option<PathBuf> {
+    let icon = application_dirs
+        .iter()
+        .flat_map(|dir| std::fs::read_dir(dir).into_iter().flatten().flatten())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "desktop")
+        })
+        .find_map(|path| {
+            let entry = std::fs::read_to_string(&path).ok()?;
+            desktop_entry_icon(app_name, &path, &entry)
+        })?;
+
+    let path = Path::new(&icon);
+    if path.is_absolute() {
+        return path.exists().then(|| path.to_path_buf());
+    }
+    icon_dirs.iter().find_map(|dir| {
+        THEME_ICON_SIZES
+            .iter()
+            .map(|size| {
+                dir.join("hicolor")
+                    .join(format!("{}x{}", size, size))
+                    .join("apps")
+                    .join(format!("{}.png", icon))
+            })
+            // pixmaps
+            .chain(std::iter::once(dir.join(format!("{}.png", icon))))
+            .find(|path| path.exists())
+    })
+}
+
+fn desktop_entry_icon(app_name: &str, path: &Path, entry: &str) -> Option<String> {
+    let mut in_entry = false;
+    let mut matches = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .is_some_and(|stem| stem.eq_ignore_ascii_case(app_name));
+    let mut icon = None;
+    for line in entry.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_entry = line == "[Desktop Entry]";
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|_| in_entry) else {
+            continue;
+        };
+        let value = value.trim();
+        match key.trim() {
+            "Name" | "StartupWMClass" => matches |= value.eq_ignore_ascii_case(app_name),
+            "Exec" => {
+                let executable = value.split_whitespace().next().unwrap_or_default();
+                matches |= Path::new(executable)
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.eq_ignore_ascii_case(app_name));
+            }
+            "Icon" => icon = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    icon.filter(|icon| matches && !icon.is_empty())
+}
+
+#[cfg(target_os = "macos")]
+fn find_icon(app_name: &str) -> Option<DynamicImage> {
+    use std::process::Command;
+
+    let home = std::env::var("HOME").unwrap_or_default();
+    let bundle = [
+        "/Applications".to_string(),
+        "/Applications/Utilities".to_string(),
+        "/System/Applications".to_string(),
+        "/System/Applications/Utilities".to_string(),
+        format!("{}/Applications", home),
+    ]
+    .iter()
+    .map(|dir| Path::new(dir).join(format!("{}.app", app_name)))
+    .find(|bundle| bundle.exists())?;
+
+    let output = Command::new("plutil")
+        .args(["-extract", "CFBundleIconFile", "raw", "-o", "-"])
+        .arg(bundle.join("Contents/Info.plist"))
+        .output()
+        .ok()?;
+    let icon_file = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if !output.status.success() || icon_file.is_empty() {
+        return None;
+    }
+    let icns = bundle
+        .join("Contents/Resources")
+        .join(if icon_file.ends_with(".icns") {
+            icon_file
+        } else {
+            format!("{}.icns", icon_file)
+        });
+
+    let png =
+        std::env::temp_dir().join(format!("screenpipe-icon-{}.png", icon_file_stem(app_name)));
+    Command::new("sips")
+        .args(["-s", "format", "png", "-Z", &ICON_SIZE.to_string()])
+        .arg(&icns)
+        .arg("--out")
+        .arg(&png)
+        .output()
+        .ok()?;
+    let icon = image::open(&png).ok();
+    let _ = std::fs::remove_file(&png);
+    icon
+}
+
+#[cfg(target_os = "linux")]
+fn find_icon(app_name: &str) -> Option<DynamicImage> {
+    let home = std::env::var("HOME").unwrap_or_default();
+    let mut data_dirs = vec![std::env::var("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| Path::new(&home).join(".local/share"))];
+    data_dirs.extend(
+        std::env::var("XDG_DATA_DIRS")
+            .unwrap_or_else(|_| "/usr/local/share:/usr/share".to_string())
+            .split(':')
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from),
+    );
+    data_dirs.push(PathBuf::from("/var/lib/flatpak/exports/share"));
+
+    let application_dirs: Vec<PathBuf> = data_dirs
+        .iter()
+        .map(|dir| dir.join("applications"))
+        .collect();
+    let mut icon_dirs: Vec<PathBuf> = data_dirs.iter().map(|dir| dir.join("icons")).collect();
+    icon_dirs.push(PathBuf::from("/usr/share/pixmaps"));
+    let path = find_desktop_icon(app_name, &application_dirs, &icon_dirs)?;
+    image::open(path).ok()
+}
+
+#[cfg(target_os = "windows")]
+fn find_icon(app_name: &str) -> Option<DynamicImage> {
+    // the icon explorer shows for the executable of a running process of the app
+    let png =
+        std::env::temp_dir().join(format!("screenpipe-icon-{}.png", icon_file_stem(app_name)));
+    let quote = |value: &str| value.replace('\'', "''");
+    let script = format!(
+        "Add-Type -AssemblyName System.Drawing; \
+         $process = Get-Process | Where-Object {{ $_.Path -and ($_.ProcessName -eq '{0}' -or $_.Description -eq '{0}') }} | Select-Object -First 1; \
+         if ($process) {{ [System.Drawing.Icon]::ExtractAssociatedIcon($process.Path).ToBitmap().Save('{1}', [System.Drawing.Imaging.ImageFormat]::Png) }}",
+        quote(app_name),
+        quote(&png.to_string_lossy()),
+    );
+    std::process::Command::new("powershell")
+        .args(["-NoProfile", "-Command", &script])
+        .output()
+        .ok()?;
+    let icon = image::open(&png).ok();
+    let _ = std::fs::remove_file(&png);
+    icon
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+fn find_icon(_app_name: &str) -> Option<DynamicImage> {
+    None
+}
+
+/// Caches the icon of each app as it gets focused, before a timeline asks for it
+pub async fn start_icon_capture(icons: Arc<AppIcons>) {
+    let mut events = subscribe_events();
+    loop {
+        match events.recv().await {
+            Ok(event) if event.name == "window.changed" => {
+                let Some(app_name) = event.data["app_name"].as_str() else {
+                    continue;
+                };
+                if icons.icon_path(app_name).exists() {
+                    continue;
+                }
+                if let Err(e) = icons.icon(app_name).await {
+                    warn!("Failed to cache the icon of {}: {}", app_name, e);
+                }
+            }
+            Ok(_) | Err(RecvError::Lagged(_)) => {}
+            Err(RecvError::Closed) => break,
+        }
+    }
+}
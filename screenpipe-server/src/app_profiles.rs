@@ -0,0 +1,65 @@
+use anyhow::{bail, Context, Result};
+use screenpipe_vision::AppCaptureProfile;
+use std::path::Path;
+
+/// In the screenpipe directory, next to power_profiles.json
+pub const APP_PROFILES_FILE: &str = "app_profiles.json";
+
+/// What app_profiles.json holds, like `[{"app": "Code", "fps": 1}, {"app": "VLC", "fps":
+/// 0.0167, "thumbnail_only": true}, {"app": "Notability", "handwriting": true}, {"app": "Signal",
+/// "text_only": true}]`. Empty when the file doesn't exist
+pub fn load_app_profiles(base_dir: &Path) -> Result<Vec<AppCaptureProfile>> {
+    let path = base_dir.join(APP_PROFILES_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let profiles: Vec<AppCaptureProfile> =
+        serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("invalid {}", path.display()))?;
+    for profile in &profiles {
+        if profile
+            .fps
+            .is_some_and(|fps| fps <= 0.0 || !fps.is_finite())
+        {
+            bail!(
+                "the fps of {} in {} has to be above 0",
+                profile.app,
+                path.display()
+            );
+        }
+        if profile.max_width == Some(0) {
+            bail!(
+                "the max_width of {} in {} has to be above 0",
+                profile.app,
+                path.display()
+            );
+        }
+        if profile.thumbnail_only && profile.handwriting {
+            bail!(
+                "{} in {} is thumbnail only, its windows aren't read with the handwriting model",
+                profile.app,
+                path.display()
+            );
+        }
+        if profile.text_only && profile.thumbnail_only {
+            bail!(
+                "{} in {} is thumbnail only, its windows aren't read to keep their text",
+                profile.app,
+                path.display()
+            );
+        }
+        if profiles
+            .iter()
+            .filter(|other| other.app.eq_ignore_ascii_case(&profile.app))
+            .count()
+            > 1
+        {
+            bail!(
+                "{} has more than one profile in {}",
+                profile.app,
+                path.display()
+            );
+        }
+    }
+    Ok(profiles)
+}
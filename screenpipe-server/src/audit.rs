@@ -0,0 +1,223 @@
+use crate::user_isolation::USER_TOKEN_PARAM;
+use crate::AppState;
+use anyhow::Result;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Query, Request, State},
+    http::{header, HeaderMap, Method, Uri},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use log::error;
+use screenpipe_core::PIPE_HEADER;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// axum's default body limit, handlers refuse anything bigger
+const MAX_BODY: usize = 2 * 1024 * 1024;
+/// Polled by the app and by live view players, nothing gets read
+const UNAUDITED_PREFIXES: [&str; 2] = ["/health", "/live/"];
+/// Query parameters that are credentials and never stored
+const SECRET_PARAMS: [&str; 2] = ["token", USER_TOKEN_PARAM];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditAction {
+    Query,
+    Export,
+    Delete,
+    Write,
+}
+
+impl AuditAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::Query => "query",
+            AuditAction::Export => "export",
+            AuditAction::Delete => "delete",
+            AuditAction::Write => "write",
+        }
+    }
+
+    /// None for requests that are not recorded
+    pub fn of_request(method: &Method, path: &str) -> Option<Self> {
+        if UNAUDITED_PREFIXES
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+        {
+            return None;
+        }
+        let exports = path.ends_with("/video")
+            || (*method == Method::GET && path.starts_with("/analytics/"))
+            || (*method == Method::POST && (path == "/timelapse" || path == "/clips"));
+        let deletes = *method == Method::DELETE || (*method == Method::POST && path == "/forget");
+        Some(match *method {
+            _ if deletes => AuditAction::Delete,
+            _ if exports => AuditAction::Export,
+            Method::GET | Method::HEAD => AuditAction::Query,
+            Method::POST if path == "/analytics/query" => AuditAction::Query,
+            _ => AuditAction::Write,
+        })
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "query" => Ok(AuditAction::Query),
+            "export" => Ok(AuditAction::Export),
+            "delete" => Ok(AuditAction::Delete),
+            "write" => Ok(AuditAction::Write),
+            _ => anyhow::bail!("unknown audit action: {}", s),
+        }
+    }
+}
+
+/// One API request, the audit log can only be appended to
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// `pipe:<name>`, `token:<fingerprint>` or `local`
+    pub actor: String,
+    pub action: AuditAction,
+    pub method: String,
+    pub path: String,
+    /// Without credentials
+    pub query: Option<String>,
+    pub status: u16,
+    /// Time range of the data asked for, when the request had one
+    pub range_start: Option<DateTime<Utc>>,
+    pub range_end: Option<DateTime<Utc>>,
+}
+
+/// Who made the request, tokens are only kept as a fingerprint
+pub fn request_actor(headers: &HeaderMap) -> String {
+    if let Some(pipe) = headers.get(PIPE_HEADER).and_then(|v| v.to_str().ok()) {
+        return format!("pipe:{}", pipe);
+    }
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match token {
+        Some(token) => format!("token:{}", token_fingerprint(token)),
+        None => "local".to_string(),
+    }
+}
+
+fn token_fingerprint(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .take(6)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// The query string with credentials removed, a `?token=` also counts as the actor
+fn split_query(query: Option<&str>) -> (Option<String>, Option<String>) {
+    let Some(query) = query else {
+        return (None, None);
+    };
+    let mut token = None;
+    let kept: Vec<&str> = query
+        .split('&')
+        .filter(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            if SECRET_PARAMS.contains(&name) {
+                token = Some(value.to_string());
+                false
+            } else {
+                true
+            }
+        })
+        .collect();
+    let kept = (!kept.is_empty()).then(|| kept.join("&"));
+    (kept, token)
+}
+
+#[derive(Deserialize, Default)]
+struct TimeRange {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// `start_time` and `end_time` from the query string or a json body
+fn request_range(uri: &Uri, body: Option<&[u8]>) -> TimeRange {
+    let from_query = Query::<TimeRange>::try_from_uri(uri)
+        .map(|Query(range)| range)
+        .unwrap_or_default();
+    if from_query.start_time.is_some() || from_query.end_time.is_some() {
+        return from_query;
+    }
+    body.and_then(|body| serde_json::from_slice::<TimeRange>(body).ok())
+        .unwrap_or_default()
+}
+
+/// Records every API request once it's answered, see `AuditAction::of_request` for
+/// what is left out
+pub async fn audit_requests(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let Some(action) = AuditAction::of_request(request.method(), &path) else {
+        return next.run(request).await;
+    };
+    let method = request.method().to_string();
+    let (query, query_token) = split_query(request.uri().query());
+    let actor = match (request_actor(request.headers()), query_token) {
+        (actor, Some(token)) if actor == "local" => format!("token:{}", token_fingerprint(&token)),
+        (actor, _) => actor,
+    };
+
+    let is_json = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    let (request, body) = if is_json {
+        let (parts, body) = request.into_parts();
+        match to_bytes(body, MAX_BODY).await {
+            Ok(bytes) => (
+                Request::from_parts(parts, Body::from(bytes.clone())),
+                Some(bytes),
+            ),
+            // the handler would have refused it too
+            Err(_) => (Request::from_parts(parts, Body::empty()), None),
+        }
+    } else {
+        (request, None)
+    };
+    let range = request_range(request.uri(), body.as_deref());
+
+    let response = next.run(request).await;
+
+    let entry = AuditEntry {
+        id: 0,
+        timestamp: Utc::now(),
+        actor,
+        action,
+        method,
+        path,
+        query,
+        status: response.status().as_u16(),
+        range_start: range.start_time,
+        range_end: range.end_time,
+    };
+    if let Err(e) = state.db.insert_audit_entry(&entry).await {
+        error!(
+            "Failed to record {} {} in the audit log: {}",
+            entry.method, entry.path, e
+        );
+    }
+    response
+}
@@ -0,0 +1,585 @@
+use crate::DatabaseManager;
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Actions of one request, a pipe acting on what it sees needs a few clicks or keys
+pub const MAX_ACTIONS_PER_REQUEST: usize = 50;
+pub const MAX_TEXT_LEN: usize = 1000;
+/// Actions a minute of the grants without `max_per_minute`
+const DEFAULT_MAX_PER_MINUTE: u32 = 30;
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AutomationKind {
+    Click,
+    Move,
+    Type,
+    Key,
+    Scroll,
+    /// Moving and resizing windows
+    Arrange,
+}
+
+impl AutomationKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AutomationKind::Click => "click",
+            AutomationKind::Move => "move",
+            AutomationKind::Type => "type",
+            AutomationKind::Key => "key",
+            AutomationKind::Scroll => "scroll",
+            AutomationKind::Arrange => "arrange",
+        }
+    }
+}
+
+impl FromStr for AutomationKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "click" => Ok(AutomationKind::Click),
+            "move" => Ok(AutomationKind::Move),
+            "type" => Ok(AutomationKind::Type),
+            "key" => Ok(AutomationKind::Key),
+            "scroll" => Ok(AutomationKind::Scroll),
+            "arrange" => Ok(AutomationKind::Arrange),
+            _ => bail!("unknown automation action: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    #[default]
+    Left,
+    Right,
+    Middle,
+}
+
+/// Input synthesized for a pipe. Coordinates are the desktop ones `/ocr/live` gives
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum AutomationAction {
+    Click {
+        x: i32,
+        y: i32,
+        #[serde(default)]
+        button: MouseButton,
+        #[serde(default)]
+        double: bool,
+    },
+    Move {
+        x: i32,
+        y: i32,
+    },
+    Type {
+        text: String,
+    },
+    /// A key name (`escape`, `enter`, `f5`...) or a single character, pressed with the modifiers
+    /// held (`ctrl`, `shift`, `alt`, `meta`)
+    Key {
+        key: String,
+        #[serde(default)]
+        modifiers: Vec<String>,
+    },
+    /// Lines scrolled, down and right are positive
+    Scroll {
+        #[serde(default)]
+        dx: i32,
+        #[serde(default)]
+        dy: i32,
+    },
+    /// Puts the window of the app with that title, or its first one, at `x`, `y` in that size
+    #[serde(rename = "move_window")]
+    MoveWindow {
+        app_name: String,
+        #[serde(default)]
+        window_name: String,
+        x: i32,
+        y: i32,
+        width: u32,
+        height: u32,
+    },
+}
+
+impl AutomationAction {
+    pub fn kind(&self) -> AutomationKind {
+        match self {
+            AutomationAction::Click { .. } => AutomationKind::Click,
+            AutomationAction::Move { .. } => AutomationKind::Move,
+            AutomationAction::Type { .. } => AutomationKind::Type,
+            AutomationAction::Key { .. } => AutomationKind::Key,
+            AutomationAction::Scroll { .. } => AutomationKind::Scroll,
+            AutomationAction::MoveWindow { .. } => AutomationKind::Arrange,
+        }
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            AutomationAction::Type { text } if text.chars().count() > MAX_TEXT_LEN => {
+                bail!("text typed is longer than {} characters", MAX_TEXT_LEN)
+            }
+            AutomationAction::Key { key, modifiers } => {
+                key.parse::<AutomationKey>()?;
+                for modifier in modifiers {
+                    modifier.parse::<Modifier>()?;
+                }
+                Ok(())
+            }
+            AutomationAction::MoveWindow {
+                app_name,
+                width,
+                height,
+                ..
+            } => {
+                if app_name.is_empty() {
+                    bail!("the app of the window to move is missing");
+                }
+                if *width == 0 || *height == 0 {
+                    bail!("a window can't be moved to an empty size");
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutomationKey {
+    Escape,
+    Enter,
+    Tab,
+    Space,
+    Backspace,
+    Delete,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+    /// F1 to F12
+    Function(u8),
+    Char(char),
+}
+
+impl FromStr for AutomationKey {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return Ok(AutomationKey::Char(c));
+        }
+        let key = s.to_lowercase();
+        Ok(match key.as_str() {
+            "escape" | "esc" => AutomationKey::Escape,
+            "enter" | "return" => AutomationKey::Enter,
+            "tab" => AutomationKey::Tab,
+            "space" => AutomationKey::Space,
+            "backspace" => AutomationKey::Backspace,
+            "delete" => AutomationKey::Delete,
+            "up" => AutomationKey::Up,
+            "down" => AutomationKey::Down,
+            "left" => AutomationKey::Left,
+            "right" => AutomationKey::Right,
+            "home" => AutomationKey::Home,
+            "end" => AutomationKey::End,
+            "pageup" => AutomationKey::PageUp,
+            "pagedown" => AutomationKey::PageDown,
+            _ => match key.strip_prefix('f').and_then(|n| n.parse::<u8>().ok()) {
+                Some(n @ 1..=12) => AutomationKey::Function(n),
+                _ => bail!("unknown key: {}", s),
+            },
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Control,
+    Shift,
+    Alt,
+    /// Command on macOS, the Windows key elsewhere
+    Meta,
+}
+
+impl FromStr for Modifier {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "ctrl" | "control" => Ok(Modifier::Control),
+            "shift" => Ok(Modifier::Shift),
+            "alt" | "option" => Ok(Modifier::Alt),
+            "meta" | "cmd" | "command" | "super" | "win" => Ok(Modifier::Meta),
+            _ => bail!("unknown modifier: {}", s),
+        }
+    }
+}
+
+fn default_max_per_minute() -> u32 {
+    DEFAULT_MAX_PER_MINUTE
+}
+
+/// What a pipe is allowed to do
+#[derive(Debug, Clone, Deserialize)]
+pub struct AutomationGrant {
+    /// As the pipe sends it in the `x-screenpipe-pipe` header, with its token of
+    /// pipe_tokens.json in `x-screenpipe-pipe-token`
+    pub pipe: String,
+    pub actions: HashSet<AutomationKind>,
+    /// Apps that must have the focused window, case insensitive. Any app when empty
+    #[serde(default)]
+    pub apps: Vec<String>,
+    #[serde(default = "default_max_per_minute")]
+    pub max_per_minute: u32,
+}
+
+#[derive(Deserialize)]
+struct AutomationGrantsFile {
+    grants: Vec<AutomationGrant>,
+}
+
+/// Reads the grants of a `{"grants": [...]}` file, one per pipe
+pub fn load_automation_grants(path: &Path) -> Result<Vec<AutomationGrant>> {
+    let file: AutomationGrantsFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut pipes = HashSet::new();
+    for grant in &file.grants {
+        if grant.actions.is_empty() {
+            bail!("the automation grant of {} allows no action", grant.pipe);
+        }
+        if !pipes.insert(grant.pipe.as_str()) {
+            bail!("two automation grants are for {}", grant.pipe);
+        }
+    }
+    Ok(file.grants)
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AutomationStatus {
+    Done,
+    /// Not allowed by the grants, nothing was synthesized
+    Refused,
+    Failed,
+}
+
+impl AutomationStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AutomationStatus::Done => "done",
+            AutomationStatus::Refused => "refused",
+            AutomationStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for AutomationStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "done" => Ok(AutomationStatus::Done),
+            "refused" => Ok(AutomationStatus::Refused),
+            "failed" => Ok(AutomationStatus::Failed),
+            _ => bail!("unknown automation status: {}", s),
+        }
+    }
+}
+
+/// An action asked for by a pipe, whether it was synthesized or not
+#[derive(Debug, Clone, Serialize)]
+pub struct AutomationRecord {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub pipe: String,
+    pub action: AutomationAction,
+    /// Window focused when the action was asked for
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub status: AutomationStatus,
+    /// Why it was refused or failed
+    pub reason: Option<String>,
+}
+
+/// Moves the mouse and presses the keys of the machine
+pub trait InputDevice: Send + Sync {
+    /// Called off the async threads, the actions are done in order
+    fn perform(&self, actions: &[AutomationAction]) -> Result<()>;
+}
+
+#[cfg(feature = "automation")]
+pub use enigo_device::EnigoDevice;
+
+#[cfg(feature = "automation")]
+mod enigo_device {
+    use super::{AutomationAction, AutomationKey, InputDevice, Modifier, MouseButton};
+    use crate::window_layout::move_window;
+    use anyhow::{anyhow, Result};
+    use enigo::{Axis, Button, Coordinate, Direction, Enigo, Key, Keyboard, Mouse, Settings};
+    use std::time::Duration;
+
+    /// Lets the app react to an action before the next one
+    const ACTION_DELAY: Duration = Duration::from_millis(20);
+
+    fn key(key: AutomationKey) -> Key {
+        match key {
+            AutomationKey::Escape => Key::Escape,
+            AutomationKey::Enter => Key::Return,
+            AutomationKey::Tab => Key::Tab,
+            AutomationKey::Space => Key::Space,
+            AutomationKey::Backspace => Key::Backspace,
+            AutomationKey::Delete => Key::Delete,
+            AutomationKey::Up => Key::UpArrow,
+            AutomationKey::Down => Key::DownArrow,
+            AutomationKey::Left => Key::LeftArrow,
+            AutomationKey::Right => Key::RightArrow,
+            AutomationKey::Home => Key::Home,
+            AutomationKey::End => Key::End,
+            AutomationKey::PageUp => Key::PageUp,
+            AutomationKey::PageDown => Key::PageDown,
+            AutomationKey::Function(n) => [
+                Key::F1,
+                Key::F2,
+                Key::F3,
+                Key::F4,
+                Key::F5,
+                Key::F6,
+                Key::F7,
+                Key::F8,
+                Key::F9,
+                Key::F10,
+                Key::F11,
+                Key::F12,
+            ][(n - 1) as usize],
+            AutomationKey::Char(c) => Key::Unicode(c),
+        }
+    }
+
+    fn modifier(modifier: Modifier) -> Key {
+        match modifier {
+            Modifier::Control => Key::Control,
+            Modifier::Shift => Key::Shift,
+            Modifier::Alt => Key::Alt,
+            Modifier::Meta => Key::Meta,
+        }
+    }
+
+    /// The input of the desktop session screenpipe runs in, with enigo
+    pub struct EnigoDevice;
+
+    impl InputDevice for EnigoDevice {
+        fn perform(&self, actions: &[AutomationAction]) -> Result<()> {
+            let input_error = |e: enigo::InputError| anyhow!("failed to synthesize input: {}", e);
+            let mut enigo = Enigo::new(&Settings::default())
+                .map_err(|e| anyhow!("failed to open the input device: {}", e))?;
+            for action in actions {
+                match action {
+                    AutomationAction::Click {
+                        x,
+                        y,
+                        button,
+                        double,
+                    } => {
+                        let button = match button {
+                            MouseButton::Left => Button::Left,
+                            MouseButton::Right => Button::Right,
+                            MouseButton::Middle => Button::Middle,
+                        };
+                        enigo
+                            .move_mouse(*x, *y, Coordinate::Abs)
+                            .map_err(input_error)?;
+                        for _ in 0..if *double { 2 } else { 1 } {
+                            enigo
+                                .button(button, Direction::Click)
+                                .map_err(input_error)?;
+                        }
+                    }
+                    AutomationAction::Move { x, y } => enigo
+                        .move_mouse(*x, *y, Coordinate::Abs)
+                        .map_err(input_error)?,
+                    AutomationAction::Type { text } => enigo.text(text).map_err(input_error)?,
+                    AutomationAction::Key {
+                        key: name,
+                        modifiers,
+                    } => {
+                        let modifiers = modifiers
+                            .iter()
+                            .map(|m| m.parse().map(modifier))
+                            .collect::<Result<Vec<Key>>>()?;
+                        for held in &modifiers {
+                            enigo.key(*held, Direction::Press).map_err(input_error)?;
+                        }
+                        let pressed = enigo.key(key(name.parse()?), Direction::Click);
+                        // released even when the key failed, a stuck modifier breaks the session
+                        for held in modifiers.iter().rev() {
+                            enigo.key(*held, Direction::Release).map_err(input_error)?;
+                        }
+                        pressed.map_err(input_error)?;
+                    }
+                    AutomationAction::Scroll { dx, dy } => {
+                        if *dy != 0 {
+                            enigo.scroll(*dy, Axis::Vertical).map_err(input_error)?;
+                        }
+                        if *dx != 0 {
+                            enigo.scroll(*dx, Axis::Horizontal).map_err(input_error)?;
+                        }
+                    }
+                    // windows are placed by the window manager, not with input
+                    AutomationAction::MoveWindow {
+                        app_name,
+                        window_name,
+                        x,
+                        y,
+                        width,
+                        height,
+                    } => move_window(app_name, window_name, *x, *y, *width, *height)?,
+                }
+                std::thread::sleep(ACTION_DELAY);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Synthesizes the input pipes ask for when their grant allows it, every action asked for is
+/// recorded whether it was done or not
+pub struct Automation {
+    db: Arc<DatabaseManager>,
+    grants: Vec<AutomationGrant>,
+    device: Arc<dyn InputDevice>,
+    /// When each pipe's recent actions were done
+    recent: Mutex<HashMap<String, VecDeque<Instant>>>,
+}
+
+impl Automation {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        grants: Vec<AutomationGrant>,
+        device: Arc<dyn InputDevice>,
+    ) -> Self {
+        Automation {
+            db,
+            grants,
+            device,
+            recent: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Why the grants don't allow the actions, `None` when they do
+    fn refusal(
+        &self,
+        pipe: &str,
+        actions: &[AutomationAction],
+        focused: Option<&(String, String)>,
+    ) -> Option<String> {
+        let Some(grant) = self.grants.iter().find(|grant| grant.pipe == pipe) else {
+            return Some(format!("{} has no automation grant", pipe));
+        };
+        if let Some(action) = actions
+            .iter()
+            .find(|action| !grant.actions.contains(&action.kind()))
+        {
+            return Some(format!(
+                "{} isn't allowed to {}",
+                pipe,
+                action.kind().as_str()
+            ));
+        }
+        if !grant.apps.is_empty() {
+            let allowed = |app_name: &str| {
+                grant
+                    .apps
+                    .iter()
+                    .any(|app| app.eq_ignore_ascii_case(app_name))
+            };
+            if !focused.is_some_and(|(app_name, _)| allowed(app_name)) {
+                let app_name = focused.map_or("no app", |(app_name, _)| app_name.as_str());
+                return Some(format!("{} isn't allowed to act on {}", pipe, app_name));
+            }
+            // the windows moved are acted on too, not just the focused one
+            if let Some(app_name) = actions.iter().find_map(|action| match action {
+                AutomationAction::MoveWindow { app_name, .. } if !allowed(app_name) => {
+                    Some(app_name)
+                }
+                _ => None,
+            }) {
+                return Some(format!("{} isn't allowed to act on {}", pipe, app_name));
+            }
+        }
+        let mut recent = self.recent.lock().unwrap();
+        let done = recent.entry(pipe.to_string()).or_default();
+        let now = Instant::now();
+        while done
+            .front()
+            .is_some_and(|at| now.duration_since(*at) > RATE_WINDOW)
+        {
+            done.pop_front();
+        }
+        if done.len() + actions.len() > grant.max_per_minute as usize {
+            return Some(format!(
+                "{} is allowed {} actions a minute",
+                pipe, grant.max_per_minute
+            ));
+        }
+        done.extend(actions.iter().map(|_| now));
+        None
+    }
+
+    /// Checks the actions against the pipe's grant and synthesizes them all, or none when one
+    /// isn't allowed. `focused` is the app and title of the window in front
+    pub async fn perform(
+        &self,
+        pipe: &str,
+        actions: Vec<AutomationAction>,
+        focused: Option<(String, String)>,
+    ) -> Result<Vec<AutomationRecord>> {
+        let timestamp = Utc::now();
+        let (status, reason) = match self.refusal(pipe, &actions, focused.as_ref()) {
+            Some(reason) => {
+                warn!("Refused automation: {}", reason);
+                (AutomationStatus::Refused, Some(reason))
+            }
+            None => {
+                let device = self.device.clone();
+                let performed = actions.clone();
+                match tokio::task::spawn_blocking(move || device.perform(&performed)).await? {
+                    Ok(()) => {
+                        info!("Synthesized {} actions for {}", actions.len(), pipe);
+                        (AutomationStatus::Done, None)
+                    }
+                    Err(e) => (AutomationStatus::Failed, Some(e.to_string())),
+                }
+            }
+        };
+        let (app_name, window_name) = focused.unzip();
+        let records: Vec<AutomationRecord> = actions
+            .into_iter()
+            .map(|action| AutomationRecord {
+                id: 0,
+                timestamp,
+                pipe: pipe.to_string(),
+                action,
+                app_name: app_name.clone(),
+                window_name: window_name.clone(),
+                status,
+                reason: reason.clone(),
+            })
+            .collect();
+        Ok(self.db.insert_automation_records(records).await?)
+    }
+}
@@ -0,0 +1,626 @@
+use crate::encryption::{open_media, ENCRYPTED_SUFFIX};
+use crate::integrity::ChunkKind;
+use crate::power::{background_jobs_paused, read_power_state, PowerState};
+use crate::DatabaseManager;
+use anyhow::{anyhow, bail, Context, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{error, info, warn};
+use rand::RngCore;
+use screenpipe_core::emit_event;
+use screenpipe_integrations::cloud_backup::{
+    Backpressure, BackupClient, BackupDestination, MIN_PART_SIZE,
+};
+use serde::Deserialize;
+use serde_json::json;
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::io::{Read, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysinfo::{CpuExt, System, SystemExt};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+const MAGIC: &[u8; 8] = b"SPBAK001";
+const SALT_LEN: usize = 16;
+/// The magic, salt, part number, last part flag and length of the ciphertext
+const PART_HEADER_LEN: usize = MAGIC.len() + SALT_LEN + 4 + 1 + 4;
+const NONCE_LEN: usize = 24;
+/// What OWASP advises for PBKDF2-HMAC-SHA256
+pub const KEY_ITERATIONS: u32 = 600_000;
+/// Appended to the name of the objects uploaded
+pub const BACKUP_SUFFIX: &str = ".spbk";
+/// Where the snapshots of the database are uploaded, named after when they were taken
+pub const DATABASE_PREFIX: &str = "database/";
+pub const SNAPSHOT_TIME_FORMAT: &str = "%Y-%m-%dT%H-%M-%SZ";
+/// In the data directory, snapshots of the database wait there until they are uploaded
+const SNAPSHOT_DIR: &str = "backups";
+const CHUNKS_PER_RUN: u32 = 20;
+/// First wait once the destination asks to slow down, doubled each time it asks again
+const FIRST_BACKOFF: Duration = Duration::from_secs(5);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+const MAX_PART_ATTEMPTS: u32 = 6;
+/// How long the cpu is sampled to tell if the machine is idle
+const CPU_SAMPLE: Duration = Duration::from_secs(1);
+
+fn default_database_every_hours() -> u32 {
+    24
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_idle_cpu_percent() -> f32 {
+    30.0
+}
+
+fn default_part_size_mb() -> usize {
+    8
+}
+
+/// What --backup-config holds, like
+/// `{"destination": {"kind": "s3", ...}, "passphrase_env": "SCREENPIPE_BACKUP_PASSPHRASE", "media": ["audio"]}`
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BackupConfig {
+    pub destination: BackupDestination,
+    /// Environment variable holding the passphrase the backups are encrypted with, it's
+    /// needed to restore them
+    pub passphrase_env: String,
+    /// Recordings uploaded along with the database, none when empty
+    #[serde(default)]
+    pub media: Vec<ChunkKind>,
+    #[serde(default = "default_database_every_hours")]
+    pub database_every_hours: u32,
+    /// Kilobits per second shared by all uploads, unlimited when not given
+    #[serde(default)]
+    pub max_upload_kbps: Option<u32>,
+    /// Uploads wait while on battery
+    #[serde(default = "default_true")]
+    pub ac_power_only: bool,
+    /// Uploads wait while the cpu is busier than this, in percent
+    #[serde(default = "default_idle_cpu_percent")]
+    pub idle_cpu_percent: f32,
+    /// Size of the parts uploads resume from, at least 5
+    #[serde(default = "default_part_size_mb")]
+    pub part_size_mb: usize,
+}
+
+impl BackupConfig {
+    pub fn part_size(&self) -> usize {
+        self.part_size_mb << 20
+    }
+}
+
+pub fn load_backup_config(path: &Path) -> Result<BackupConfig> {
+    let config: BackupConfig = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    if config.part_size() < MIN_PART_SIZE {
+        bail!("parts of backups are at least {} MB", MIN_PART_SIZE >> 20);
+    }
+    if config.database_every_hours == 0 {
+        bail!("database_every_hours can't be 0");
+    }
+    if std::env::var(&config.passphrase_env).map_or(true, |passphrase| passphrase.is_empty()) {
+        bail!("{} holds no backup passphrase", config.passphrase_env);
+    }
+    Ok(config)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupSource {
+    Database,
+    Video,
+    Audio,
+}
+
+impl BackupSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BackupSource::Database => "database",
+            BackupSource::Video => "video",
+            BackupSource::Audio => "audio",
+        }
+    }
+
+    pub fn chunk_kind(&self) -> Option<ChunkKind> {
+        match self {
+            BackupSource::Database => None,
+            BackupSource::Video => Some(ChunkKind::Video),
+            BackupSource::Audio => Some(ChunkKind::Audio),
+        }
+    }
+}
+
+impl From<ChunkKind> for BackupSource {
+    fn from(kind: ChunkKind) -> Self {
+        match kind {
+            ChunkKind::Video => BackupSource::Video,
+            ChunkKind::Audio => BackupSource::Audio,
+        }
+    }
+}
+
+impl FromStr for BackupSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "database" => Ok(BackupSource::Database),
+            "video" => Ok(BackupSource::Video),
+            "audio" => Ok(BackupSource::Audio),
+            _ => bail!("unknown backup source: {}", s),
+        }
+    }
+}
+
+/// An upload to the destination, resumed from the part after the last one in `parts`
+#[derive(Debug, Clone)]
+pub struct BackupUpload {
+    pub id: i64,
+    pub source: BackupSource,
+    pub chunk_id: Option<i64>,
+    pub file_path: String,
+    pub object_key: String,
+    /// Of the plaintext, a chunk that changed size was compacted and is uploaded again
+    pub file_size: i64,
+    pub upload_id: String,
+    /// What the destination gave back for each part uploaded
+    pub parts: Vec<String>,
+    pub started_at: DateTime<Utc>,
+}
+
+pub fn pbkdf2_sha256(password: &[u8], salt: &[u8], iterations: u32) -> [u8; 32] {
+    pbkdf2::pbkdf2_hmac_array::<Sha256, 32>(password, salt, iterations)
+}
+
+fn part_header(salt: &[u8; SALT_LEN], number: u32, last: bool, len: usize) -> Vec<u8> {
+    let mut header = Vec::with_capacity(PART_HEADER_LEN);
+    header.extend_from_slice(MAGIC);
+    header.extend_from_slice(salt);
+    header.extend_from_slice(&number.to_be_bytes());
+    header.push(last as u8);
+    header.extend_from_slice(&(len as u32).to_be_bytes());
+    header
+}
+
+/// Key backups are encrypted with, derived from the passphrase so they can be restored
+/// on another machine
+pub struct BackupKey {
+    salt: [u8; SALT_LEN],
+    cipher: XChaCha20Poly1305,
+}
+
+impl BackupKey {
+    /// Slow on purpose, done once at start
+    pub fn derive(passphrase: &str, salt: [u8; SALT_LEN]) -> Self {
+        let key = pbkdf2_sha256(passphrase.as_bytes(), &salt, KEY_ITERATIONS);
+        BackupKey {
+            salt,
+            cipher: XChaCha20Poly1305::new(Key::from_slice(&key)),
+        }
+    }
+
+    /// A part as it's uploaded, decrypted on its own so uploads resume without the parts
+    /// sent before. The header is authenticated, parts can't be reordered and a backup cut
+    /// before its `last` part is noticed
+    pub fn encrypt_part(&self, number: u32, last: bool, plaintext: &[u8]) -> Result<Vec<u8>> {
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let mut output = part_header(&self.salt, number, last, plaintext.len() + 16);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &output,
+                },
+            )
+            .map_err(|_| anyhow!("failed to encrypt"))?;
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        Ok(output)
+    }
+
+    fn decrypt_part(&self, header: &[u8], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+        self.cipher
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| anyhow!("wrong passphrase, or the backup is damaged"))
+    }
+}
+
+/// Decrypts a backup, the object downloaded from S3 or the parts of a WebDAV upload one
+/// after the other. Returns the number of bytes written
+pub fn restore_backup(
+    input: &mut impl Read,
+    output: &mut impl Write,
+    passphrase: &str,
+) -> Result<u64> {
+    let mut keys: HashMap<[u8; SALT_LEN], BackupKey> = HashMap::new();
+    let mut written = 0;
+    for number in 1.. {
+        let mut header = [0u8; PART_HEADER_LEN];
+        input
+            .read_exact(&mut header)
+            .with_context(|| format!("the backup stops before part {}", number))?;
+        if &header[..MAGIC.len()] != MAGIC {
+            bail!("not a screenpipe backup");
+        }
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&header[MAGIC.len()..MAGIC.len() + SALT_LEN]);
+        let rest = &header[MAGIC.len() + SALT_LEN..];
+        let part = u32::from_be_bytes(rest[..4].try_into()?);
+        let last = rest[4] == 1;
+        let len = u32::from_be_bytes(rest[5..9].try_into()?) as usize;
+        if part != number {
+            bail!(
+                "part {} of the backup is where part {} should be",
+                part,
+                number
+            );
+        }
+        let mut nonce = [0u8; NONCE_LEN];
+        input.read_exact(&mut nonce)?;
+        // the length isn't authenticated yet, the buffer only grows with what's really there
+        let mut ciphertext = Vec::new();
+        input
+            .by_ref()
+            .take(len as u64)
+            .read_to_end(&mut ciphertext)?;
+        if ciphertext.len() != len {
+            bail!("part {} of the backup is truncated", number);
+        }
+        let key = keys
+            .entry(salt)
+            .or_insert_with(|| BackupKey::derive(passphrase, salt));
+        let plaintext = key.decrypt_part(&header, &nonce, &ciphertext)?;
+        output.write_all(&plaintext)?;
+        written += plaintext.len() as u64;
+        if last {
+            break;
+        }
+    }
+    if input.read(&mut [0u8; 1])? != 0 {
+        bail!("data follows the last part of the backup");
+    }
+    Ok(written)
+}
+
+/// Parts a file of `size` bytes is uploaded in, an empty file still has one
+pub fn part_count(size: u64, part_size: usize) -> u32 {
+    size.div_ceil(part_size as u64).max(1) as u32
+}
+
+/// How long to wait after sending `sent` bytes in `elapsed` to stay under `max_upload_kbps`
+pub fn throttle_delay(max_upload_kbps: Option<u32>, sent: u64, elapsed: Duration) -> Duration {
+    let Some(kbps) = max_upload_kbps.filter(|kbps| *kbps > 0) else {
+        return Duration::ZERO;
+    };
+    Duration::from_secs_f64(sent as f64 * 8.0 / (kbps as f64 * 1000.0)).saturating_sub(elapsed)
+}
+
+/// Why uploads wait, None when they can go
+pub fn wait_reason(
+    config: &BackupConfig,
+    power: &PowerState,
+    cpu_percent: f32,
+    jobs_paused: bool,
+) -> Option<&'static str> {
+    if jobs_paused {
+        Some("background jobs are paused")
+    } else if config.ac_power_only && power.on_battery {
+        Some("on battery")
+    } else if cpu_percent > config.idle_cpu_percent {
+        Some("the machine is busy")
+    } else {
+        None
+    }
+}
+
+async fn current_wait_reason(config: &BackupConfig) -> Option<&'static str> {
+    let (power, cpu_percent) = tokio::task::spawn_blocking(|| {
+        let mut system = System::new();
+        system.refresh_cpu();
+        std::thread::sleep(CPU_SAMPLE.max(System::MINIMUM_CPU_UPDATE_INTERVAL));
+        system.refresh_cpu();
+        (read_power_state(), system.global_cpu_info().cpu_usage())
+    })
+    .await
+    .unwrap_or_default();
+    wait_reason(config, &power, cpu_percent, background_jobs_paused())
+}
+
+/// Where a chunk is uploaded, the same before and after it's encrypted at rest
+pub fn chunk_object_key(kind: ChunkKind, file_path: &str) -> String {
+    let name = Path::new(file_path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_path.to_string());
+    let name = name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(&name);
+    format!(
+        "{}/{}{}",
+        BackupSource::from(kind).as_str(),
+        name,
+        BACKUP_SUFFIX
+    )
+}
+
+struct Uploader<'a> {
+    db: &'a DatabaseManager,
+    client: &'a BackupClient,
+    key: &'a BackupKey,
+    config: &'a BackupConfig,
+    started: Instant,
+    sent: u64,
+}
+
+impl Uploader<'_> {
+    /// Retries while the destination asks to slow down
+    async fn send_part(&self, upload: &BackupUpload, number: u32, data: Vec<u8>) -> Result<String> {
+        let mut backoff = FIRST_BACKOFF;
+        for attempt in 1.. {
+            let result = self
+                .client
+                .upload_part(&upload.object_key, &upload.upload_id, number, data.clone())
+                .await;
+            let e = match result {
+                Ok(etag) => return Ok(etag),
+                Err(e) => e,
+            };
+            let Some(backpressure) = e.downcast_ref::<Backpressure>() else {
+                return Err(e);
+            };
+            if attempt == MAX_PART_ATTEMPTS {
+                return Err(e);
+            }
+            let wait = backpressure.retry_after.unwrap_or(backoff).min(MAX_BACKOFF);
+            warn!("{}, waiting {}s", backpressure, wait.as_secs());
+            tokio::time::sleep(wait).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+        unreachable!("the last attempt returns")
+    }
+
+    /// Uploads the parts after those in `upload.parts`, false when it stopped to wait
+    async fn upload(&mut self, upload: &mut BackupUpload, path: &str) -> Result<bool> {
+        let part_size = self.config.part_size();
+        let count = part_count(upload.file_size as u64, part_size);
+        let mut file = tokio::fs::File::open(path).await?;
+        for number in upload.parts.len() as u32 + 1..=count {
+            if let Some(reason) = current_wait_reason(self.config).await {
+                info!("Backup of {} waits, {}", upload.object_key, reason);
+                return Ok(false);
+            }
+            file.seek(SeekFrom::Start((number as u64 - 1) * part_size as u64))
+                .await?;
+            let mut plaintext = Vec::with_capacity(part_size);
+            (&mut file)
+                .take(part_size as u64)
+                .read_to_end(&mut plaintext)
+                .await?;
+            let data = self.key.encrypt_part(number, number == count, &plaintext)?;
+            let sent = data.len() as u64;
+            let etag = self.send_part(upload, number, data).await?;
+            upload.parts.push(etag);
+            self.db
+                .set_backup_upload_parts(upload.id, &upload.parts)
+                .await?;
+
+            self.sent += sent;
+            tokio::time::sleep(throttle_delay(
+                self.config.max_upload_kbps,
+                self.sent,
+                self.started.elapsed(),
+            ))
+            .await;
+        }
+        self.client
+            .complete_upload(&upload.object_key, &upload.upload_id, &upload.parts)
+            .await?;
+        self.db.complete_backup_upload(upload.id).await?;
+        info!("Backed up {}", upload.object_key);
+        emit_event(
+            "backup.uploaded",
+            json!({"source": upload.source.as_str(), "object_key": upload.object_key, "size": upload.file_size}),
+        );
+        Ok(true)
+    }
+
+    /// Starts the upload of a file, gives it up when it's gone by the time it's resumed
+    async fn start(
+        &mut self,
+        source: BackupSource,
+        chunk_id: Option<i64>,
+        file_path: &str,
+        object_key: String,
+    ) -> Result<bool> {
+        let media = open_media(file_path).await?;
+        let file_size = tokio::fs::metadata(media.path()).await?.len() as i64;
+        let upload_id = self.client.start_upload(&object_key).await?;
+        let id = self
+            .db
+            .insert_backup_upload(
+                source,
+                chunk_id,
+                file_path,
+                &object_key,
+                file_size,
+                &upload_id,
+            )
+            .await?;
+        let mut upload = BackupUpload {
+            id,
+            source,
+            chunk_id,
+            file_path: file_path.to_string(),
+            object_key,
+            file_size,
+            upload_id,
+            parts: Vec::new(),
+            started_at: Utc::now(),
+        };
+        self.upload(&mut upload, media.path()).await
+    }
+
+    async fn abandon(&self, upload: &BackupUpload, reason: &str) -> Result<()> {
+        warn!("Giving up the backup of {}, {}", upload.object_key, reason);
+        if let Err(e) = self
+            .client
+            .abort_upload(&upload.object_key, &upload.upload_id)
+            .await
+        {
+            warn!("Failed to drop the parts of {}: {}", upload.object_key, e);
+        }
+        self.db.delete_backup_upload(upload.id).await?;
+        Ok(())
+    }
+
+    /// Continues an upload stopped by a restart or a wait, false when it stopped again
+    async fn resume(&mut self, mut upload: BackupUpload) -> Result<bool> {
+        let file_path = match upload.source.chunk_kind() {
+            Some(kind) => match upload.chunk_id {
+                Some(chunk_id) => self.db.get_chunk_file_path(kind, chunk_id).await?,
+                None => None,
+            },
+            None => Path::new(&upload.file_path)
+                .exists()
+                .then(|| upload.file_path.clone()),
+        };
+        let Some(file_path) = file_path else {
+            self.abandon(&upload, "the file was deleted").await?;
+            return Ok(true);
+        };
+        let media = open_media(&file_path).await?;
+        if tokio::fs::metadata(media.path()).await?.len() as i64 != upload.file_size {
+            // compacted since, the chunk is uploaded again
+            self.abandon(&upload, "the file changed").await?;
+            return Ok(true);
+        }
+        let done = self.upload(&mut upload, media.path()).await?;
+        if done && upload.source == BackupSource::Database {
+            let _ = tokio::fs::remove_file(&upload.file_path).await;
+        }
+        Ok(done)
+    }
+}
+
+async fn run_backup(
+    db: &DatabaseManager,
+    client: &BackupClient,
+    key: &BackupKey,
+    config: &BackupConfig,
+    data_dir: &Path,
+) -> Result<()> {
+    if let Some(reason) = current_wait_reason(config).await {
+        info!("Backups wait, {}", reason);
+        return Ok(());
+    }
+    let mut uploader = Uploader {
+        db,
+        client,
+        key,
+        config,
+        started: Instant::now(),
+        sent: 0,
+    };
+    for upload in db.get_unfinished_backup_uploads().await? {
+        if !uploader.resume(upload).await? {
+            return Ok(());
+        }
+    }
+
+    let due = db.get_last_database_backup().await?.is_none_or(|last| {
+        Utc::now() - last >= ChronoDuration::hours(config.database_every_hours as i64)
+    });
+    if due {
+        let snapshots = data_dir.join(SNAPSHOT_DIR);
+        tokio::fs::create_dir_all(&snapshots).await?;
+        let name = format!("{}.sqlite", Utc::now().format(SNAPSHOT_TIME_FORMAT));
+        let snapshot = snapshots.join(&name).to_string_lossy().to_string();
+        db.snapshot_database(&snapshot).await?;
+        let done = uploader
+            .start(
+                BackupSource::Database,
+                None,
+                &snapshot,
+                format!("{}{}{}", DATABASE_PREFIX, name, BACKUP_SUFFIX),
+            )
+            .await?;
+        if !done {
+            return Ok(());
+        }
+        let _ = tokio::fs::remove_file(&snapshot).await;
+    }
+
+    for kind in &config.media {
+        for (chunk_id, file_path) in db.get_chunks_to_back_up(*kind, CHUNKS_PER_RUN).await? {
+            let object_key = chunk_object_key(*kind, &file_path);
+            let done = uploader
+                .start((*kind).into(), Some(chunk_id), &file_path, object_key)
+                .await?;
+            if !done {
+                return Ok(());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Uploads the database every `database_every_hours` and the chunks of `media` once they
+/// are recorded, encrypted with the passphrase. Only while on AC power and the machine is
+/// idle, stopped uploads resume from their last part
+pub async fn start_backup(
+    db: Arc<DatabaseManager>,
+    config: BackupConfig,
+    data_dir: PathBuf,
+    interval: Duration,
+) {
+    let client = match BackupClient::new(&config.destination) {
+        Ok(client) => client,
+        Err(e) => {
+            error!("Backups are off: {}", e);
+            return;
+        }
+    };
+    let passphrase = std::env::var(&config.passphrase_env).unwrap_or_default();
+    let mut new_salt = [0u8; SALT_LEN];
+    rand::rngs::OsRng.fill_bytes(&mut new_salt);
+    let salt: [u8; SALT_LEN] = match db.backup_salt(&new_salt).await {
+        Ok(salt) => match salt.try_into() {
+            Ok(salt) => salt,
+            Err(_) => {
+                error!("Backups are off: the salt of the backup key is damaged");
+                return;
+            }
+        },
+        Err(e) => {
+            error!("Backups are off: {}", e);
+            return;
+        }
+    };
+    let key = match tokio::task::spawn_blocking(move || BackupKey::derive(&passphrase, salt)).await
+    {
+        Ok(key) => key,
+        Err(e) => {
+            error!("Backups are off: {}", e);
+            return;
+        }
+    };
+    info!("Backups started");
+    loop {
+        if let Err(e) = run_backup(&db, &client, &key, &config, &data_dir).await {
+            error!("Backup failed: {}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
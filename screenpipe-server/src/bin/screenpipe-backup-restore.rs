@@ -0,0 +1,151 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use dirs::home_dir;
+use log::LevelFilter;
+use screenpipe_integrations::cloud_backup::{BackupClient, WEBDAV_COMPLETE};
+use screenpipe_server::backup::{load_backup_config, restore_backup};
+use screenpipe_server::restore::{list_snapshots, restore, RestoreOptions};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Restores the backups uploaded by screenpipe --backup-config
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Snapshots of the database at the destination, oldest first
+    List {
+        /// The file given to --backup-config
+        #[arg(long)]
+        backup_config: String,
+    },
+    /// Replaces the database by a snapshot, with the recordings it points at that aren't on
+    /// disk anymore. Stop screenpipe first, the database replaced is kept next to it
+    Restore {
+        /// The file given to --backup-config
+        #[arg(long)]
+        backup_config: String,
+
+        /// Go back to the last snapshot taken at or before this time, e.g.
+        /// 2024-09-16T18:00:00Z. The last snapshot when not given
+        #[arg(long)]
+        at: Option<DateTime<Utc>>,
+
+        /// Only the text layer, the database: the recordings aren't downloaded
+        #[arg(long, default_value_t = false)]
+        text_only: bool,
+
+        /// Data directory of screenpipe. Default to $HOME/.screenpipe
+        #[arg(long)]
+        data_dir: Option<String>,
+    },
+    /// Decrypts a backup downloaded by hand
+    Decrypt {
+        /// The object downloaded from S3, or the directory of a WebDAV upload with its parts
+        input: PathBuf,
+
+        /// Where the database or the recording is written
+        output: PathBuf,
+
+        /// Environment variable holding the passphrase of the backup
+        #[arg(long, default_value = "SCREENPIPE_BACKUP_PASSPHRASE")]
+        passphrase_env: String,
+    },
+}
+
+/// The parts of a WebDAV upload one after the other
+fn webdav_parts(dir: &Path) -> Result<Box<dyn Read>> {
+    if !dir.join(WEBDAV_COMPLETE).exists() {
+        bail!("{} is an upload that wasn't completed", dir.display());
+    }
+    let mut parts: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.bytes().all(|byte| byte.is_ascii_digit()))
+        })
+        .collect();
+    parts.sort();
+    let mut input: Box<dyn Read> = Box::new(std::io::empty());
+    for part in parts {
+        input = Box::new(input.chain(BufReader::new(File::open(part)?)));
+    }
+    Ok(input)
+}
+
+fn decrypt(input: &Path, output: &Path, passphrase_env: &str) -> Result<()> {
+    let passphrase =
+        std::env::var(passphrase_env).with_context(|| format!("{} is not set", passphrase_env))?;
+    let mut input: Box<dyn Read> = if input.is_dir() {
+        webdav_parts(input)?
+    } else {
+        Box::new(BufReader::new(File::open(input)?))
+    };
+    let mut writer = BufWriter::new(File::create(output)?);
+    let written = restore_backup(&mut input, &mut writer, &passphrase)?;
+    writer.flush()?;
+    println!("Restored {} bytes to {}", written, output.display());
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter(None, LevelFilter::Info)
+        .format_timestamp_secs()
+        .init();
+
+    match args.command {
+        Command::List { backup_config } => {
+            let config = load_backup_config(Path::new(&backup_config))?;
+            let client = BackupClient::new(&config.destination)?;
+            for snapshot in list_snapshots(&client).await? {
+                println!(
+                    "{}  {:>10} KB  {}",
+                    snapshot.taken_at.to_rfc3339(),
+                    snapshot.size / 1000,
+                    snapshot.object_key
+                );
+            }
+        }
+        Command::Restore {
+            backup_config,
+            at,
+            text_only,
+            data_dir,
+        } => {
+            let config = load_backup_config(Path::new(&backup_config))?;
+            let client = BackupClient::new(&config.destination)?;
+            let passphrase = std::env::var(&config.passphrase_env)?;
+            let data_dir = match data_dir {
+                Some(dir) => PathBuf::from(dir),
+                None => home_dir()
+                    .ok_or_else(|| anyhow::anyhow!("Failed to get home directory"))?
+                    .join(".screenpipe"),
+            };
+            let report = restore(
+                &client,
+                &passphrase,
+                &data_dir,
+                &RestoreOptions { at, text_only },
+            )
+            .await?;
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Command::Decrypt {
+            input,
+            output,
+            passphrase_env,
+        } => decrypt(&input, &output, &passphrase_env)?,
+    }
+    Ok(())
+}
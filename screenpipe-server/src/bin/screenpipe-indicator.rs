@@ -0,0 +1,186 @@
+// The recording indicator started by `screenpipe --recording-indicator`: reads the capture
+// state on stdin, one line each time it changes, and writes "toggle" on stdout when clicked
+
+use clap::Parser;
+use screenpipe_server::cli::CliIndicatorStyle;
+use screenpipe_server::indicator::{indicator_rects, IndicatorState, TOGGLE_LINE};
+use softbuffer::{Context, Surface};
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::num::NonZeroU32;
+use std::rc::Rc;
+use winit::application::ApplicationHandler;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, MouseButton, WindowEvent};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop, EventLoopProxy};
+use winit::window::{Window, WindowId, WindowLevel};
+
+const CAPTURING_COLOR: u32 = 0x00e5484d;
+const PAUSED_COLOR: u32 = 0x008b8d98;
+
+#[derive(Parser)]
+#[command(about = "Always on top indicator shown while screenpipe captures the screen")]
+struct Args {
+    #[arg(long, value_enum, default_value_t = CliIndicatorStyle::Dot)]
+    style: CliIndicatorStyle,
+}
+
+enum Message {
+    State(IndicatorState),
+    /// screenpipe exited
+    Closed,
+}
+
+// the errors keep the window handle, which can't go to another thread
+fn softbuffer_error(e: softbuffer::SoftBufferError) -> anyhow::Error {
+    anyhow::anyhow!("{}", e)
+}
+
+struct Indicator {
+    style: CliIndicatorStyle,
+    state: IndicatorState,
+    windows: HashMap<WindowId, Surface<Rc<Window>, Rc<Window>>>,
+}
+
+impl Indicator {
+    fn create_windows(&mut self, event_loop: &ActiveEventLoop) -> anyhow::Result<()> {
+        let Some(monitor) = event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next())
+        else {
+            anyhow::bail!("no monitor to show the indicator on");
+        };
+        let position = monitor.position();
+        let size = monitor.size();
+        for (x, y, width, height) in indicator_rects(
+            self.style.clone().into(),
+            (position.x, position.y),
+            (size.width, size.height),
+            monitor.scale_factor(),
+        ) {
+            let attributes = Window::default_attributes()
+                .with_title("screenpipe is recording")
+                .with_decorations(false)
+                .with_resizable(false)
+                .with_active(false)
+                .with_window_level(WindowLevel::AlwaysOnTop)
+                .with_inner_size(PhysicalSize::new(width, height))
+                .with_position(PhysicalPosition::new(x, y));
+            #[cfg(target_os = "windows")]
+            let attributes = {
+                use winit::platform::windows::WindowAttributesExtWindows;
+                attributes.with_skip_taskbar(true)
+            };
+            #[cfg(all(unix, not(target_os = "macos")))]
+            let attributes = {
+                use winit::platform::x11::{WindowAttributesExtX11, WindowType};
+                attributes.with_x11_window_type(vec![WindowType::Utility])
+            };
+            let window = Rc::new(event_loop.create_window(attributes)?);
+            let context = Context::new(window.clone()).map_err(softbuffer_error)?;
+            let surface = Surface::new(&context, window.clone()).map_err(softbuffer_error)?;
+            self.windows.insert(window.id(), surface);
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, id: WindowId) -> anyhow::Result<()> {
+        let Some(surface) = self.windows.get_mut(&id) else {
+            return Ok(());
+        };
+        let size = surface.window().inner_size();
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(size.width), NonZeroU32::new(size.height))
+        else {
+            return Ok(());
+        };
+        surface.resize(width, height).map_err(softbuffer_error)?;
+        let mut buffer = surface.buffer_mut().map_err(softbuffer_error)?;
+        buffer.fill(match self.state {
+            IndicatorState::Capturing => CAPTURING_COLOR,
+            IndicatorState::Paused => PAUSED_COLOR,
+        });
+        buffer.present().map_err(softbuffer_error)?;
+        Ok(())
+    }
+}
+
+impl ApplicationHandler<Message> for Indicator {
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        if !self.windows.is_empty() {
+            return;
+        }
+        if let Err(e) = self.create_windows(event_loop) {
+            eprintln!("failed to show the recording indicator: {}", e);
+            event_loop.exit();
+        }
+    }
+
+    fn window_event(&mut self, _: &ActiveEventLoop, id: WindowId, event: WindowEvent) {
+        match event {
+            WindowEvent::RedrawRequested => {
+                if let Err(e) = self.draw(id) {
+                    eprintln!("failed to draw the recording indicator: {}", e);
+                }
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let mut stdout = std::io::stdout().lock();
+                let _ = writeln!(stdout, "{}", TOGGLE_LINE);
+                let _ = stdout.flush();
+            }
+            _ => {}
+        }
+    }
+
+    fn user_event(&mut self, event_loop: &ActiveEventLoop, message: Message) {
+        match message {
+            Message::State(state) => {
+                self.state = state;
+                for surface in self.windows.values() {
+                    surface.window().request_redraw();
+                }
+            }
+            Message::Closed => event_loop.exit(),
+        }
+    }
+}
+
+fn read_states(proxy: EventLoopProxy<Message>) {
+    for line in std::io::stdin().lock().lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if let Ok(state) = line.trim().parse() {
+            if proxy.send_event(Message::State(state)).is_err() {
+                return;
+            }
+        }
+    }
+    let _ = proxy.send_event(Message::Closed);
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    #[allow(unused_mut)]
+    let mut builder = EventLoop::<Message>::with_user_event();
+    #[cfg(target_os = "macos")]
+    {
+        use winit::platform::macos::{ActivationPolicy, EventLoopBuilderExtMacOS};
+        // no dock icon nor menu bar
+        builder.with_activation_policy(ActivationPolicy::Accessory);
+    }
+    let event_loop = builder.build()?;
+    event_loop.set_control_flow(ControlFlow::Wait);
+    let proxy = event_loop.create_proxy();
+    std::thread::spawn(move || read_states(proxy));
+    event_loop.run_app(&mut Indicator {
+        style: args.style,
+        state: IndicatorState::Capturing,
+        windows: HashMap::new(),
+    })?;
+    Ok(())
+}
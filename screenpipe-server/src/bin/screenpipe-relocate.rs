@@ -0,0 +1,78 @@
+use anyhow::Result;
+use clap::Parser;
+use dirs::home_dir;
+use log::LevelFilter;
+use screenpipe_server::profiles::profile_dir;
+use screenpipe_server::relocate::{relocate_media, RelocateOptions};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Moves the recordings to another directory, an external disk say, while screenpipe keeps
+/// recording. The copies are checked before the database points at them, then the old
+/// directory is replaced by a link to the new one until screenpipe restarts with --media-dir
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Where the recordings go, an empty or new directory
+    to: PathBuf,
+
+    /// Data directory of screenpipe. Default to $HOME/.screenpipe
+    #[arg(long)]
+    data_dir: Option<String>,
+
+    /// Profile of profiles.json whose recordings are moved
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// The --media-dir screenpipe records to. Default to data in the data directory
+    #[arg(long)]
+    media_dir: Option<String>,
+
+    /// Seconds screenpipe gets to finish the chunks it was writing once switched over
+    #[arg(long, default_value_t = 90)]
+    settle_secs: u64,
+
+    /// Keep the old directory once the move was checked
+    #[arg(long, default_value_t = false)]
+    keep_old: bool,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter(None, LevelFilter::Info)
+        .format_timestamp_secs()
+        .init();
+
+    let base_dir = match args.data_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get home directory"))?
+            .join(".screenpipe"),
+    };
+    let data_dir = match &args.profile {
+        Some(name) => profile_dir(&base_dir, name),
+        None => base_dir,
+    };
+    let media_dir = args
+        .media_dir
+        .map(PathBuf::from)
+        .unwrap_or_else(|| data_dir.join("data"));
+    let report = relocate_media(
+        &data_dir,
+        &media_dir,
+        &args.to,
+        &RelocateOptions {
+            settle: Duration::from_secs(args.settle_secs),
+            keep_old: args.keep_old,
+        },
+    )
+    .await?;
+    println!("{}", serde_json::to_string_pretty(&report)?);
+    eprintln!(
+        "Restart screenpipe with --media-dir {} to record there without the link",
+        report.to
+    );
+    Ok(())
+}
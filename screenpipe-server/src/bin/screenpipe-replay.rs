@@ -0,0 +1,55 @@
+use anyhow::Result;
+use clap::Parser;
+use log::LevelFilter;
+use screenpipe_server::cli::CliOcrEngine;
+use screenpipe_vision::{replay_bundle, ReplayOptions};
+use std::path::PathBuf;
+
+/// Feeds a bundle recorded by screenpipe --record-replay through the frame diffing and the OCR
+/// of this build, and prints what each frame gives as JSON. Two builds replaying the same
+/// bundle are compared by diffing their output
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Directory of the bundle
+    bundle: PathBuf,
+
+    /// OCR engine the windows are read with
+    #[cfg_attr(
+        target_os = "macos",
+        arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::AppleNative)
+    )]
+    #[cfg_attr(
+        not(target_os = "macos"),
+        arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::Tesseract)
+    )]
+    ocr_engine: CliOcrEngine,
+
+    /// Only the frame diffing, the windows aren't OCR'd
+    #[arg(long, default_value_t = false)]
+    no_ocr: bool,
+
+    /// Where the report is written, printed when not given
+    #[arg(long)]
+    output: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter(None, LevelFilter::Info)
+        .format_timestamp_secs()
+        .init();
+
+    let options = ReplayOptions {
+        ocr_engine: (!args.no_ocr).then(|| args.ocr_engine.into()),
+    };
+    let report = replay_bundle(&args.bundle, &options).await?;
+    let json = serde_json::to_string_pretty(&report)?;
+    match args.output {
+        Some(path) => std::fs::write(path, json)?,
+        None => println!("{}", json),
+    }
+    Ok(())
+}
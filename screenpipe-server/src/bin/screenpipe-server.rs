@@ -3,7 +3,7 @@ use std::{
     fs::{self, File},
     net::SocketAddr,
     ops::Deref,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{atomic::AtomicBool, Arc},
     time::Duration,
 };
@@ -12,27 +12,69 @@ use std::{
 use colored::Colorize;
 use crossbeam::queue::SegQueue;
 use dirs::home_dir;
-use log::{debug, error, info, LevelFilter};
+use log::{debug, error, info, warn, LevelFilter};
 use screenpipe_audio::{
     default_input_device, default_output_device, list_audio_devices, parse_audio_device,
     AudioDevice, DeviceControl,
 };
 use screenpipe_vision::{
     monitor::{get_monitor_by_id, list_monitors},
+    set_app_capture_profiles, set_ocr_priority, set_symbol_recognition, start_replay_recording,
     OcrEngine,
 };
 use std::io::Write;
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use rand::Rng;
 use screenpipe_audio::AudioTranscriptionEngine as CoreAudioTranscriptionEngine;
-use screenpipe_core::find_ffmpeg_path;
+use screenpipe_core::{
+    best_encoder, configure_accelerators, find_system_ffmpeg_path, install_pinned_ffmpeg,
+    probe_hardware_encoders, set_ffmpeg_path, set_model_manager, EncoderQuality, ModelManager,
+    NerModel, PiiEntityKind, PiiRedactor, PromptLibrary, VideoEncoder, MODELS_DIR,
+    PINNED_FFMPEG_VERSION,
+};
+use screenpipe_integrations::remote_worker::{set_remote_worker, RemoteWorker};
+use screenpipe_integrations::shell_history::{default_history_files, hook_script};
+use screenpipe_integrations::vault_export::{initialize_vault_export_loop, VaultExporter};
 use screenpipe_server::{
-    cli::{Cli, CliAudioTranscriptionEngine, CliOcrEngine},
+    cli::{Cli, CliAudioTranscriptionEngine, CliConsumer, CliFfmpegSource, CliOcrEngine},
+    compliance::{compliance_policy_to_load, default_policy_path},
+    profiles::{login_name, profile_dir},
+    load_compliance_policy, load_email_destinations, load_policy_rules, start_email_alerts,
+    start_email_digests,
     logs::MultiWriter,
-    start_continuous_recording, DatabaseManager, ResourceMonitor, Server,
+    prepare_decrypted_dir, set_engine_selection, set_frame_storage, set_media_keys, set_text_only, set_video_encoder_selection, start_activity_classifier,
+    start_continuous_recording, start_extraction_worker, start_integrity_checker,
+    start_calendar_sync, start_disk_guard, start_file_watcher, start_icon_capture, start_media_encryptor, start_meeting_summarizer, start_storage_compactor,
+    start_power_monitor, start_retention, start_git_activity_watcher, start_notification_capture, start_focus_monitor, start_timezone_tracker, start_trend_analyzer, start_shell_history_import, start_subtitle_muxer, start_window_history, ActiveProfile, AppIcons, CapturePause, ClipRenderer, ComplianceMode,
+    ConsumerRedaction, DatabaseManager, DetectedEntity, DoctorOptions, FrameEncoding, KeyRing,
+    MarkerRecorder,
+    LiveOcr, LiveView, LlmService, load_automation_grants, load_tool_grants, builtin_tools, Automation, PolicyAction, PolicyRule, PowerProfiles, ProfilesConfig, RedactionPolicy, ResourceMonitor,
+    run_doctor, ApiCredentials, Server,
+    TimelapseRenderer, UserInstance, UserIsolation, Watchlist,
 };
+#[cfg(feature = "automation")]
+use screenpipe_server::automation::EnigoDevice;
+use screenpipe_server::aggregation::start_aggregation_only;
+use screenpipe_server::app_profiles::load_app_profiles;
+use screenpipe_server::language::start_text_indexer;
+use screenpipe_server::backup::{load_backup_config, start_backup, BackupSource};
+use screenpipe_server::indicator::start_recording_indicator;
+use screenpipe_server::local_api::default_api_socket;
+#[cfg(all(unix, feature = "timeline-fs"))]
+use screenpipe_server::timeline_fs::mount_timeline;
+use screenpipe_server::tls::{local_certificate, tls_acceptor};
+use screenpipe_server::user_isolation::{bind_user_port, user_data_dir};
+use screenpipe_server::window_history::DEFAULT_WINDOW_POLL_INTERVAL;
+use screenpipe_server::shell_history::SHELL_HISTORY_INTERVAL;
+use screenpipe_server::git_activity::GIT_ACTIVITY_INTERVAL;
+use screenpipe_server::notifications::NOTIFICATION_POLL_INTERVAL;
+use screenpipe_server::focus_mode::FOCUS_POLL_INTERVAL;
+use screenpipe_server::timezones::TIMEZONE_CHECK_INTERVAL;
 use screenpipe_vision::utils::OcrEngine as CoreOcrEngine;
 use tokio::sync::mpsc::channel;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 fn print_devices(devices: &[AudioDevice]) {
     println!("Available audio devices:");
@@ -44,6 +86,9 @@ fn print_devices(devices: &[AudioDevice]) {
     println!("On macOS, it's not intuitive but output devices are your displays");
 }
 
+/// How long a cancelled recording has to finish its chunks before it's aborted
+const RECORDING_STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
 const DISPLAY: &str = r"
                                             _          
    __________________  ___  ____     ____  (_____  ___ 
@@ -68,11 +113,112 @@ fn get_base_dir(custom_path: Option<String>) -> anyhow::Result<PathBuf> {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let mut cli = Cli::parse();
 
-    if find_ffmpeg_path().is_none() {
-        eprintln!("ffmpeg not found. Please install ffmpeg and ensure it is in your PATH.");
-        std::process::exit(1);
+    let base_dir = get_base_dir(cli.data_dir.clone())?;
+    set_model_manager(ModelManager::new(
+        cli.models_dir
+            .as_ref()
+            .map(PathBuf::from)
+            .unwrap_or_else(|| base_dir.join(MODELS_DIR)),
+        cli.models_quota_mb.map(|mb| mb * 1_000_000),
+    ));
+    // users sharing a data directory each record to their own, the default one is in their home
+    let base_dir = match (cli.multi_user, &cli.data_dir) {
+        (true, Some(_)) => {
+            let user = login_name()
+                .ok_or_else(|| anyhow::anyhow!("--multi-user needs the name of the OS user, set USER"))?;
+            let dir = user_data_dir(&base_dir, &user);
+            fs::create_dir_all(dir.join("data"))?;
+            dir
+        }
+        _ => base_dir,
+    };
+    let profiles = ProfilesConfig::load(&base_dir)?;
+    let power_profiles = PowerProfiles::load(&base_dir)?;
+    let app_profiles = load_app_profiles(&base_dir)?;
+    let app_profile_count = app_profiles.len();
+    set_app_capture_profiles(app_profiles);
+    let active_profile = profiles.resolve(cli.profile.as_deref(), login_name().as_deref())?;
+    // each profile has its own database and media, the profiles' settings come before the defaults
+    let local_data_dir = match &active_profile {
+        Some((name, profile)) => {
+            if cli.retention_days.is_none() {
+                cli.retention_days = profile.retention_days;
+            }
+            if cli.redaction_policy.is_none() {
+                cli.redaction_policy = profile
+                    .redaction_policy
+                    .as_ref()
+                    .map(|path| base_dir.join(path).to_string_lossy().into_owned());
+            }
+            let dir = profile_dir(&base_dir, name);
+            fs::create_dir_all(dir.join("data"))?;
+            dir
+        }
+        None => base_dir.clone(),
+    };
+    let api_socket = cli
+        .api_socket
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| default_api_socket(&local_data_dir));
+    if let Some(shell) = cli.print_shell_hook.clone() {
+        if cfg!(windows) {
+            return Err(anyhow::anyhow!(
+                "the shell hooks post to the API socket, which curl can't open on Windows"
+            ));
+        }
+        print!("{}", hook_script(shell.into(), &api_socket));
+        return Ok(());
+    }
+    let media_dir = cli
+        .media_dir
+        .as_ref()
+        .map(PathBuf::from)
+        .unwrap_or_else(|| local_data_dir.join("data"));
+    fs::create_dir_all(&media_dir)?;
+
+    set_frame_storage(cli.frame_format.clone().map(|format| FrameEncoding {
+        format: format.into(),
+        quality: cli.frame_quality,
+    }));
+    set_text_only(cli.text_only);
+    let system_ffmpeg = match cli.ffmpeg_source {
+        CliFfmpegSource::Pinned => None,
+        _ => find_system_ffmpeg_path(),
+    };
+    if system_ffmpeg.is_none() {
+        let installed = if cli.ffmpeg_source == CliFfmpegSource::System {
+            Err(anyhow::anyhow!("ffmpeg not found. Please install ffmpeg and ensure it is in your PATH, or use --ffmpeg-source auto to download it."))
+        } else {
+            println!("Installing ffmpeg {}...", PINNED_FFMPEG_VERSION);
+            install_pinned_ffmpeg(&base_dir).await
+        };
+        match installed {
+            Ok(path) => set_ffmpeg_path(path),
+            // the images of the built-in encoder aren't encrypted
+            Err(e)
+                if cfg!(feature = "native-encoder")
+                    && (cli.encrypt_data || cli.require_encryption)
+                    && !cli.doctor =>
+            {
+                eprintln!("{}", e);
+                eprintln!("Refusing to start: the built-in encoder would store the frames as unencrypted images, encryption needs ffmpeg");
+                std::process::exit(1);
+            }
+            Err(e) if cfg!(feature = "native-encoder") => {
+                eprintln!("{}", e);
+                eprintln!("Falling back to the built-in encoder, frames are stored as images and audio is disabled (see /capabilities).");
+                cli.disable_audio = true;
+            }
+            // the doctor reports it
+            Err(e) if cli.doctor => eprintln!("{}", e),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
     }
 
     // Initialize logging
@@ -92,8 +238,8 @@ async fn main() -> anyhow::Result<()> {
         debug!("Text files will be saved.");
     }
 
-    let local_data_dir = get_base_dir(cli.data_dir)?;
     let local_data_dir_clone = local_data_dir.clone();
+    let api_socket_clone = api_socket.clone();
 
     let log_file = File::create(format!(
         "{}/screenpipe.log",
@@ -124,6 +270,124 @@ async fn main() -> anyhow::Result<()> {
                 .underline()
         );
     }
+
+    // a policy deployed by an admin can't be turned off or replaced from the command line
+    let compliance_policy = match compliance_policy_to_load(
+        &default_policy_path(),
+        cli.compliance_policy.as_deref(),
+        cli.compliance_public_key.as_deref(),
+    ) {
+        Ok(policy) => policy,
+        Err(e) => {
+            eprintln!("Refusing to start: {}", e);
+            return Err(e);
+        }
+    };
+    let compliance_policy = match compliance_policy {
+        Some((path, public_key)) => match load_compliance_policy(&path, public_key) {
+            Ok(policy) => Some(policy),
+            Err(e) => {
+                eprintln!(
+                    "Refusing to start: the compliance policy {} can't be used: {}",
+                    path.display(),
+                    e
+                );
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+    if compliance_policy
+        .as_ref()
+        .is_some_and(|(policy, _)| !policy.allow_audio)
+    {
+        cli.disable_audio = true;
+    }
+
+    let uses_remote_worker = cli.ocr_engine == CliOcrEngine::Remote
+        || cli.audio_transcription_engine == CliAudioTranscriptionEngine::Remote;
+    match &cli.remote_worker {
+        Some(url) => {
+            let ca = cli.remote_worker_ca.as_ref().map(std::fs::read).transpose()?;
+            let worker = RemoteWorker::new(url, cli.remote_worker_token.clone(), ca.as_deref())?;
+            set_remote_worker(Some(Arc::new(worker)));
+        }
+        None if uses_remote_worker => {
+            eprintln!("Refusing to start: the remote engines need a --remote-worker");
+            return Err(anyhow::anyhow!("no remote worker"));
+        }
+        None => {}
+    }
+    // by the names they are picked with on the command line
+    let ocr_engine = cli.ocr_engine.to_possible_value().unwrap();
+    let stt_engine = cli.audio_transcription_engine.to_possible_value().unwrap();
+    set_engine_selection(
+        ocr_engine.get_name(),
+        (!cli.disable_audio).then(|| stt_engine.get_name()),
+        cli.remote_worker.is_some(),
+    );
+
+    if cli.doctor {
+        let report = run_doctor(&DoctorOptions {
+            data_dir: local_data_dir.clone(),
+            ocr_engine: cli.ocr_engine.clone().into(),
+            monitor_id: cli.monitor_id,
+            audio: !cli.disable_audio,
+            smoke_test: Duration::from_secs(cli.doctor_smoke_test_secs),
+        })
+        .await;
+        println!("{}", serde_json::to_string(&report)?);
+        std::process::exit(if report.passed { 0 } else { 1 });
+    }
+
+    let mut user_listener = None;
+    let user_isolation = if cli.multi_user {
+        let user = login_name()
+            .ok_or_else(|| anyhow::anyhow!("--multi-user needs the name of the OS user, set USER"))?;
+        if let Some(instance) = UserInstance::read(&local_data_dir).filter(UserInstance::is_running) {
+            return Err(anyhow::anyhow!(
+                "screenpipe already records {} on port {} (pid {})",
+                instance.user,
+                instance.port,
+                instance.pid
+            ));
+        }
+        let isolation = UserIsolation::load(&local_data_dir, &user)?;
+        // kept bound until the server takes it, another user's server can't get it meanwhile
+        let listener = bind_user_port(cli.port)?;
+        cli.port = listener.local_addr()?.port();
+        user_listener = Some(listener);
+        UserInstance {
+            user,
+            pid: std::process::id(),
+            port: cli.port,
+            started_at: chrono::Utc::now(),
+        }
+        .write(&local_data_dir)?;
+        Some(Arc::new(isolation))
+    } else {
+        None
+    };
+
+    let tls = if cli.tls {
+        let (cert, key) = match (&cli.tls_cert, &cli.tls_key) {
+            (Some(cert), Some(key)) => (PathBuf::from(cert), PathBuf::from(key)),
+            _ => {
+                let certificate = local_certificate(&local_data_dir.join("tls"))?;
+                info!(
+                    "Trust {} in your browser or OS to open https://localhost:{} without warnings",
+                    certificate.ca.display(),
+                    cli.port
+                );
+                (certificate.cert, certificate.key)
+            }
+        };
+        Some(tls_acceptor(&cert, &key)?)
+    } else {
+        None
+    };
+    let scheme = if cli.tls { "https" } else { "http" };
+
     let all_audio_devices = list_audio_devices().await?;
     let mut devices_status = HashMap::new();
     if cli.list_audio_devices {
@@ -214,20 +478,532 @@ async fn main() -> anyhow::Result<()> {
         ResourceMonitor::new(cli.self_healing, Duration::from_secs(60), 3, restart_sender);
     resource_monitor.start_monitoring(Duration::from_secs(10));
 
-    let db = Arc::new(
-        DatabaseManager::new(&format!("{}/db.sqlite", local_data_dir.to_string_lossy()))
-            .await
-            .map_err(|e| {
-                eprintln!("Failed to initialize database: {:?}", e);
-                e
-            })?,
-    );
+    let encryption = if cli.encrypt_data || cli.require_encryption {
+        if cli.require_encryption && !DatabaseManager::supports_encryption() {
+            eprintln!("Refusing to start: encryption is required but this build stores the database in plaintext, build it with the sqlcipher feature");
+            return Err(anyhow::anyhow!("database encryption is not available"));
+        }
+        match KeyRing::load(&local_data_dir).await {
+            Ok(keys) => Some(Arc::new(keys)),
+            Err(e) if cli.require_encryption => {
+                eprintln!(
+                    "Refusing to start: encryption is required but the OS keychain can't be used: {}",
+                    e
+                );
+                return Err(e);
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to load encryption keys from the OS keychain, data stays in plaintext: {}",
+                    e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let database_path = format!("{}/db.sqlite", local_data_dir.to_string_lossy());
+    let db = match &encryption {
+        Some(keys) if DatabaseManager::supports_encryption() => {
+            let previous_key = keys.previous_database_key();
+            let db = DatabaseManager::new_encrypted(
+                &database_path,
+                &keys.database_key(),
+                previous_key.as_deref(),
+            )
+            .await;
+            if db.is_ok() && previous_key.is_some() {
+                keys.database_rekeyed().await?;
+            }
+            db
+        }
+        Some(_) => {
+            warn!("This build can't encrypt the database, only media is encrypted");
+            DatabaseManager::new(&database_path).await
+        }
+        None => DatabaseManager::new(&database_path).await,
+    };
+    let db = Arc::new(db.map_err(|e| {
+        eprintln!("Failed to initialize database: {:?}", e);
+        e
+    })?);
     info!(
         "Database initialized, will store files in {}",
         local_data_dir.to_string_lossy()
     );
     let db_server = db.clone();
 
+    // users can override the built-in prompts in {data_dir}/prompts.json
+    let prompts = Arc::new(
+        PromptLibrary::load(&local_data_dir.join("prompts.json")).map_err(|e| {
+            eprintln!("Failed to load prompts.json: {}", e);
+            e
+        })?,
+    );
+
+    let llm = Arc::new(
+        LlmService::new(
+            db.clone(),
+            cli.llm_config(),
+            cli.llm_fallback_config(),
+            cli.llm_monthly_budget,
+            cli.llm_cache_ttl(),
+        )
+        .with_embedding_models(
+            cli.llm_embedding_model.clone(),
+            cli.llm_fallback_embedding_model.clone(),
+        ),
+    );
+
+    let llm_server = llm.clone();
+    let prompts_server = prompts.clone();
+    let timelapse_server = Arc::new(TimelapseRenderer::new(
+        db.clone(),
+        local_data_dir.join("timelapses"),
+    ));
+    let clips_server = Arc::new(ClipRenderer::new(db.clone(), local_data_dir.join("clips")));
+
+    tokio::spawn(start_extraction_worker(
+        db.clone(),
+        llm.clone(),
+        prompts.clone(),
+        Duration::from_secs(5),
+    ));
+
+    // runs locally, no reason to make it opt-in
+    tokio::spawn(start_activity_classifier(
+        db.clone(),
+        Duration::from_secs(60),
+    ));
+    tokio::spawn(start_trend_analyzer(
+        db.clone(),
+        Duration::from_secs(3600),
+    ));
+
+    if cli.embed_subtitles {
+        tokio::spawn(start_subtitle_muxer(
+            db.clone(),
+            cli.fps,
+            Duration::from_secs(cli.audio_chunk_duration),
+            Duration::from_secs(60),
+        ));
+    }
+
+    tokio::spawn(start_text_indexer(db.clone(), Duration::from_secs(10)));
+
+    if cli.check_chunk_integrity {
+        tokio::spawn(start_integrity_checker(
+            db.clone(),
+            Duration::from_secs(300),
+        ));
+    }
+
+    if let Some(options) = cli.compaction_options() {
+        tokio::spawn(start_storage_compactor(
+            db.clone(),
+            options,
+            Duration::from_secs(3600),
+        ));
+    }
+
+    if let Some(days) = cli.retention_days {
+        tokio::spawn(start_retention(
+            db.clone(),
+            chrono::Duration::days(days as i64),
+            Duration::from_secs(3600),
+        ));
+    }
+
+    if let Some(minutes) = cli.aggregate_only_after {
+        tokio::spawn(start_aggregation_only(
+            db.clone(),
+            chrono::Duration::minutes(minutes as i64),
+            Duration::from_secs(300),
+        ));
+    }
+
+    if cli.disk_guard_gb > 0 {
+        tokio::spawn(start_disk_guard(
+            db.clone(),
+            media_dir.clone(),
+            cli.disk_guard_gb * 1_000_000_000,
+            Duration::from_secs(60),
+        ));
+    }
+
+    if !cli.disable_power_profiles {
+        tokio::spawn(start_power_monitor(
+            power_profiles,
+            cli.fps,
+            Duration::from_secs(30),
+        ));
+    }
+
+    if !cli.disable_focus_mode {
+        tokio::spawn(start_focus_monitor(db.clone(), FOCUS_POLL_INTERVAL));
+    }
+
+    tokio::spawn(start_timezone_tracker(db.clone(), TIMEZONE_CHECK_INTERVAL));
+
+    prepare_decrypted_dir(&local_data_dir)?;
+    if let Some(keys) = &encryption {
+        set_media_keys(keys.clone());
+        tokio::spawn(start_media_encryptor(
+            db.clone(),
+            keys.clone(),
+            Duration::from_secs(60),
+        ));
+    }
+    let encryption_server = encryption.clone();
+
+    let vault_export = cli.vault_dir.as_ref().map(|vault_dir| {
+        Arc::new(VaultExporter::new(
+            PathBuf::from(vault_dir),
+            cli.vault_format.clone().into(),
+        ))
+    });
+    if let Some(exporter) = &vault_export {
+        tokio::spawn(initialize_vault_export_loop(
+            db.clone(),
+            exporter.clone(),
+            Duration::from_secs(cli.vault_export_interval.max(1) * 60),
+        ));
+    }
+
+    let email_destinations = match &cli.email_config {
+        Some(path) => load_email_destinations(Path::new(path)).map_err(|e| {
+            eprintln!("Failed to load email destinations {}: {}", path, e);
+            e
+        })?,
+        None => Vec::new(),
+    };
+    if !email_destinations.is_empty() {
+        tokio::spawn(start_email_digests(db.clone(), email_destinations.clone()));
+        tokio::spawn(start_email_alerts(db.clone(), email_destinations.clone()));
+    }
+
+    let backup_config = match &cli.backup_config {
+        Some(path) => Some(load_backup_config(Path::new(path)).map_err(|e| {
+            eprintln!("Failed to load the backup config {}: {}", path, e);
+            e
+        })?),
+        None => None,
+    };
+    if let Some(config) = &backup_config {
+        tokio::spawn(start_backup(
+            db.clone(),
+            config.clone(),
+            local_data_dir.clone(),
+            Duration::from_secs(600),
+        ));
+    }
+
+    let automation_grants = match &cli.automation_grants {
+        Some(path) => load_automation_grants(Path::new(path)).map_err(|e| {
+            eprintln!("Failed to load automation grants {}: {}", path, e);
+            e
+        })?,
+        None => Vec::new(),
+    };
+    let credentials = Arc::new(ApiCredentials::load(
+        &local_data_dir,
+        cli.app_token.clone(),
+        cli.api_token.clone(),
+    )?);
+    // the pipes granted automation find their token in pipe_tokens.json
+    for grant in &automation_grants {
+        credentials.pipe_token(&grant.pipe)?;
+    }
+    #[cfg(feature = "automation")]
+    let automation = (!automation_grants.is_empty()).then(|| {
+        Arc::new(Automation::new(
+            db.clone(),
+            automation_grants.clone(),
+            Arc::new(EnigoDevice),
+        ))
+    });
+    #[cfg(not(feature = "automation"))]
+    let automation: Option<Arc<Automation>> = {
+        if !automation_grants.is_empty() {
+            warn!("--automation-grants needs screenpipe built with the automation feature");
+        }
+        None
+    };
+
+    let mut llm_tools = builtin_tools(db.clone());
+    if let Some(tools) = &cli.llm_tools {
+        llm_tools.enable(tools).map_err(|e| {
+            eprintln!("Invalid --llm-tools: {}", e);
+            e
+        })?;
+    }
+    if let Some(path) = &cli.llm_tool_grants {
+        let grants = load_tool_grants(Path::new(path)).map_err(|e| {
+            eprintln!("Failed to load llm tool grants {}: {}", path, e);
+            e
+        })?;
+        for grant in &grants {
+            credentials.pipe_token(&grant.pipe)?;
+        }
+        llm_tools.grant(grants).map_err(|e| {
+            eprintln!("Invalid llm tool grants {}: {}", path, e);
+            e
+        })?;
+    }
+    let llm_tools = Arc::new(llm_tools);
+
+    if cli.enable_meeting_summaries {
+        tokio::spawn(start_meeting_summarizer(
+            db.clone(),
+            llm.clone(),
+            prompts.clone(),
+            Duration::from_secs(60),
+        ));
+    }
+
+    if !cli.calendar.is_empty() {
+        if !cli.enable_meeting_summaries {
+            warn!("calendars label meeting summaries, start with --enable-meeting-summaries");
+        }
+        tokio::spawn(start_calendar_sync(
+            db.clone(),
+            cli.calendar.clone(),
+            Duration::from_secs(cli.calendar_sync_interval.max(1) * 60),
+        ));
+    }
+
+    if !cli.watch_dir.is_empty() {
+        let dirs = cli.watch_dir.iter().map(PathBuf::from).collect();
+        let excluded = vec![local_data_dir.clone()];
+        let db = db.clone();
+        tokio::spawn(async move {
+            if let Err(e) = start_file_watcher(db, dirs, excluded).await {
+                error!("Failed to watch file activity: {}", e);
+            }
+        });
+    }
+
+    // unmounted when dropped, at the end of main
+    #[cfg(all(unix, feature = "timeline-fs"))]
+    let _timeline_mount = match &cli.mount_timeline {
+        Some(dir) => Some(mount_timeline(db.clone(), Path::new(dir)).map_err(|e| {
+            eprintln!("Failed to mount the timeline on {}: {}", dir, e);
+            e
+        })?),
+        None => None,
+    };
+    #[cfg(not(all(unix, feature = "timeline-fs")))]
+    if cli.mount_timeline.is_some() {
+        warn!("--mount-timeline needs screenpipe built with the timeline-fs feature, on Linux or macOS");
+    }
+
+    let video_quality: EncoderQuality = cli.video_quality.clone().into();
+    let hardware_encoders = probe_hardware_encoders().await;
+    let video_encoder = match cli.video_encoder.encoder() {
+        None => best_encoder(&hardware_encoders),
+        Some(encoder)
+            if encoder == VideoEncoder::Software || hardware_encoders.contains(&encoder) =>
+        {
+            encoder
+        }
+        Some(encoder) => {
+            warn!(
+                "{} is not available on this machine, using software encoding",
+                encoder.codec_name()
+            );
+            VideoEncoder::Software
+        }
+    };
+    set_video_encoder_selection(hardware_encoders, video_encoder, video_quality);
+    let ocr_priority = cli.ocr_priority();
+    set_ocr_priority(ocr_priority);
+    set_symbol_recognition(cli.recognize_symbols);
+    configure_accelerators(&cli.accelerator_memory, &cli.model_device);
+
+    let live_view = cli.enable_live_view.then(|| {
+        let token = cli.live_view_token.clone().unwrap_or_else(|| {
+            let token: String = rand::thread_rng()
+                .sample_iter(&rand::distributions::Alphanumeric)
+                .take(32)
+                .map(char::from)
+                .collect();
+            // a --multi-user server wants the user's token too
+            let user_token = user_isolation
+                .as_ref()
+                .map(|isolation| format!("&user_token={}", isolation.token()))
+                .unwrap_or_default();
+            println!(
+                "Live view: http://localhost:{}/live?token={}{}",
+                cli.port, token, user_token
+            );
+            token
+        });
+        Arc::new(LiveView::new(
+            local_data_dir.join("live"),
+            token,
+            video_encoder,
+            video_quality,
+        ))
+    });
+    let live_view_server = live_view.clone();
+    let capture_pause = Arc::new(CapturePause::new());
+    let capture_pause_server = capture_pause.clone();
+    if let Some(style) = cli.recording_indicator.clone() {
+        tokio::spawn(start_recording_indicator(capture_pause.clone(), style.into()));
+    }
+    if !cli.disable_window_history {
+        tokio::spawn(start_window_history(
+            db.clone(),
+            capture_pause.clone(),
+            DEFAULT_WINDOW_POLL_INTERVAL,
+        ));
+    }
+    if cli.enable_shell_history {
+        let files = home_dir()
+            .map(|home| default_history_files(&home))
+            .unwrap_or_default();
+        if files.is_empty() {
+            warn!(
+                "No shell history file found, only the hooks of --print-shell-hook send commands"
+            );
+        } else {
+            tokio::spawn(start_shell_history_import(
+                db.clone(),
+                files,
+                capture_pause.clone(),
+                SHELL_HISTORY_INTERVAL,
+            ));
+        }
+    }
+    if !cli.git_repo.is_empty() {
+        let repositories = cli
+            .git_repo
+            .iter()
+            .map(|repository| std::fs::canonicalize(repository).unwrap_or_else(|_| PathBuf::from(repository)))
+            .collect();
+        tokio::spawn(start_git_activity_watcher(
+            db.clone(),
+            repositories,
+            capture_pause.clone(),
+            GIT_ACTIVITY_INTERVAL,
+        ));
+    }
+    if cli.capture_notifications {
+        tokio::spawn(start_notification_capture(
+            db.clone(),
+            capture_pause.clone(),
+            NOTIFICATION_POLL_INTERVAL,
+        ));
+    }
+    let compliance = compliance_policy
+        .map(|(policy, hash)| Arc::new(ComplianceMode::new(policy, hash, capture_pause.clone())));
+    let compliance_summary = match &compliance {
+        Some(compliance) => format!("{} (waiting for consent)", compliance.policy.organization),
+        None => "disabled".to_string(),
+    };
+
+    let mut policy_rules = match &cli.redaction_policy {
+        Some(path) => load_policy_rules(Path::new(path)).map_err(|e| {
+            eprintln!("Failed to load redaction policy {}: {}", path, e);
+            e
+        })?,
+        None => Vec::new(),
+    };
+    if let Some((name, profile)) = &active_profile {
+        policy_rules.extend(profile.blocklist_rules(name)?);
+    }
+    // --redact-pii is a rule redacting what the detectors find everywhere
+    if cli.redact_pii || cli.pii_ner {
+        let mut entities = vec![
+            DetectedEntity::Email,
+            DetectedEntity::CreditCard,
+            DetectedEntity::Ssn,
+        ];
+        if cli.pii_ner {
+            entities.extend(
+                cli.pii_entities
+                    .iter()
+                    .cloned()
+                    .map(|entity| DetectedEntity::from(PiiEntityKind::from(entity))),
+            );
+        }
+        policy_rules.push(PolicyRule::for_entities(
+            "redact-pii",
+            entities,
+            PolicyAction::RedactText,
+        ));
+    }
+    if cli.blur_secrets {
+        policy_rules.push(PolicyRule::for_entities(
+            "blur-secrets",
+            vec![
+                DetectedEntity::CardNumber,
+                DetectedEntity::AwsKey,
+                DetectedEntity::Jwt,
+                DetectedEntity::PrivateKey,
+            ],
+            PolicyAction::BlurText,
+        ));
+    }
+    let mut ner_entities: Vec<PiiEntityKind> = Vec::new();
+    for kind in policy_rules.iter().flat_map(|rule| rule.ner_entities()) {
+        if !ner_entities.contains(&kind) {
+            ner_entities.push(kind);
+        }
+    }
+    let pii_detector = if ner_entities.is_empty() {
+        PiiRedactor::default()
+    } else {
+        let model_id = cli.pii_ner_model.clone();
+        let model = tokio::task::spawn_blocking(move || NerModel::load_from(&model_id))
+            .await
+            .map_err(anyhow::Error::from)
+            .and_then(|model| model);
+        match model {
+            Ok(model) => {
+                PiiRedactor::with_ner(model, ner_entities, cli.pii_min_confidence.clamp(0.0, 1.0))
+            }
+            Err(e) => {
+                warn!(
+                    "Failed to load NER model {}, only finding PII matching patterns: {}",
+                    cli.pii_ner_model, e
+                );
+                PiiRedactor::default()
+            }
+        }
+    };
+    let pii_detector = Arc::new(pii_detector);
+    let redaction_policy = (!policy_rules.is_empty())
+        .then(|| Arc::new(RedactionPolicy::new(policy_rules, pii_detector.clone())));
+    let redacted_consumers: Vec<_> = cli.redact_for.iter().filter_map(CliConsumer::consumer).collect();
+    let consumer_redaction_summary = match redacted_consumers.is_empty() {
+        true => "nobody".to_string(),
+        false => redacted_consumers
+            .iter()
+            .map(|consumer| format!("{:?}", consumer).to_lowercase())
+            .collect::<Vec<_>>()
+            .join(", "),
+    };
+    let consumer_redaction = (!redacted_consumers.is_empty())
+        .then(|| Arc::new(ConsumerRedaction::new(redacted_consumers, pii_detector)));
+    let redaction_policy_summary = match &redaction_policy {
+        Some(policy) if policy.detector().uses_ner() => format!(
+            "{} rules, ner ({})",
+            policy.rules().len(),
+            policy
+                .detector()
+                .entity_types()
+                .iter()
+                .map(|kind| format!("{:?}", kind).to_lowercase())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        Some(policy) => format!("{} rules", policy.rules().len()),
+        None => "disabled".to_string(),
+    };
+
     // Channel for controlling the recorder ! TODO RENAME SHIT
     let vision_control = Arc::new(AtomicBool::new(true));
 
@@ -252,19 +1028,60 @@ async fn main() -> anyhow::Result<()> {
         );
         std::process::exit(1);
     });
+    if let Some(dir) = &cli.record_replay {
+        start_replay_recording(
+            Path::new(dir),
+            monitor_id,
+            Duration::from_secs(cli.record_replay_secs),
+        )?;
+        info!(
+            "Recording a replay bundle of {}s to {}",
+            cli.record_replay_secs, dir
+        );
+    }
     let ocr_engine_clone = cli.ocr_engine.clone();
 
     // Function to start or restart the recording task
-    let _start_recording = tokio::spawn(async move {
+    let markers = Arc::new(MarkerRecorder::new(
+        db.clone(),
+        local_data_dir.join("markers"),
+        Some(monitor_id),
+        Duration::from_secs(cli.audio_chunk_duration),
+    ));
+    let watchlist = Watchlist::load(db.clone()).await?;
+    let watchlist_server = watchlist.clone();
+    let live_ocr = Arc::new(LiveOcr::new(monitor_id));
+    let live_ocr_server = live_ocr.clone();
+    let app_icons = Arc::new(AppIcons::new(local_data_dir.join("icons")));
+    tokio::spawn(start_icon_capture(app_icons.clone()));
+    let profile = active_profile.as_ref().map(|(name, profile)| {
+        Arc::new(ActiveProfile {
+            name: name.clone(),
+            profile: profile.clone(),
+            available: profiles.profiles.keys().cloned().collect(),
+        })
+    });
+
+    // cancelled on ctrl-c, each recording task gets a child token cancelled when it's restarted
+    let shutdown = CancellationToken::new();
+    let recording_shutdown = shutdown.clone();
+    let start_recording = tokio::spawn(async move {
         // hack
         let mut recording_task = tokio::spawn(async move {});
+        let mut recording_cancel = recording_shutdown.child_token();
 
         loop {
             let db_clone = db.clone();
             let local_data_dir = local_data_dir.clone();
+            let media_dir = media_dir.clone();
             let vision_control = vision_control.clone();
             let audio_devices_control = audio_devices_control.clone();
             let friend_wearable_uid_clone = friend_wearable_uid.clone(); // Clone for each iteration
+            let live_view = live_view.clone();
+            let redaction_policy = redaction_policy.clone();
+            let capture_pause = capture_pause.clone();
+            let watchlist = watchlist.clone();
+            let live_ocr = live_ocr.clone();
 
             tokio::select! {
                 _ = &mut recording_task => {
@@ -274,19 +1091,29 @@ async fn main() -> anyhow::Result<()> {
                 Some(_) = restart_receiver.recv() => {
                     // Received restart signal, cancel the current task and restart
                     info!("Received restart signal. Restarting recording task...");
-                    recording_task.abort();
+                    stop_recording(&recording_cancel, &mut recording_task).await;
+                }
+                _ = recording_shutdown.cancelled() => {
+                    stop_recording(&recording_cancel, &mut recording_task).await;
                 }
             }
+            if recording_shutdown.is_cancelled() {
+                info!("Recording stopped");
+                break;
+            }
+            recording_cancel = recording_shutdown.child_token();
+            let cancel = recording_cancel.clone();
             let core_ocr_engine: CoreOcrEngine = cli.ocr_engine.clone().into();
             let ocr_engine = Arc::new(OcrEngine::from(core_ocr_engine));
             let core_audio_transcription_engine: CoreAudioTranscriptionEngine =
                 cli.audio_transcription_engine.clone().into();
             let audio_transcription_engine = Arc::new(core_audio_transcription_engine);
+            let slides_dir = (!cli.disable_slide_decks).then(|| local_data_dir.join("slides"));
 
             recording_task = tokio::spawn(async move {
                 let result = start_continuous_recording(
                     db_clone,
-                    Arc::new(local_data_dir.join("data").to_string_lossy().into_owned()),
+                    Arc::new(media_dir.to_string_lossy().into_owned()),
                     cli.fps,
                     Duration::from_secs(cli.audio_chunk_duration),
                     vision_control,
@@ -296,6 +1123,15 @@ async fn main() -> anyhow::Result<()> {
                     ocr_engine,
                     friend_wearable_uid_clone, // Use the cloned version
                     monitor_id,
+                    video_encoder,
+                    video_quality,
+                    live_view,
+                    redaction_policy,
+                    capture_pause,
+                    watchlist,
+                    live_ocr,
+                    slides_dir,
+                    cancel,
                 )
                 .await;
 
@@ -310,6 +1146,7 @@ async fn main() -> anyhow::Result<()> {
         }
     });
 
+    let credentials_server = credentials.clone();
     tokio::spawn(async move {
         let api_plugin = |req: &axum::http::Request<axum::body::Body>| {
             // Custom plugin logic here
@@ -321,9 +1158,37 @@ async fn main() -> anyhow::Result<()> {
         };
         let server = Server::new(
             db_server,
-            SocketAddr::from(([0, 0, 0, 0], cli.port)),
+            // other users can't reach a multi-user server from another machine either
+            SocketAddr::from(if user_isolation.is_some() {
+                ([127, 0, 0, 1], cli.port)
+            } else {
+                ([0, 0, 0, 0], cli.port)
+            }),
+            user_listener,
             vision_control_server_clone,
             audio_devices_control_server,
+            llm_server,
+            prompts_server,
+            live_view_server,
+            timelapse_server,
+            clips_server,
+            capture_pause_server,
+            encryption_server,
+            compliance,
+            consumer_redaction,
+            vault_export,
+            markers,
+            profile,
+            watchlist_server,
+            app_icons,
+            live_ocr_server,
+            automation,
+            llm_tools,
+            credentials_server,
+            user_isolation,
+            Some(api_socket),
+            !cli.disable_tcp_api,
+            tls,
         );
         server.start(devices_status, api_plugin).await.unwrap();
     });
@@ -332,9 +1197,14 @@ async fn main() -> anyhow::Result<()> {
     if !cli.pipe.is_empty() {
         use tokio::process::Command;
         // ! HACK until we have clean way to store the bin 
+        // the runtime names the pipe after its path
+        let pipe = Path::new(&cli.pipe[0])
+            .canonicalize()
+            .map_or_else(|_| cli.pipe[0].clone(), |path| path.to_string_lossy().to_string());
         let status = Command::new("target/release/screenpipe-pipe-runner")
             .arg("--pipe")
             .args(&cli.pipe)
+            .env(screenpipe_core::PIPE_TOKEN_ENV, credentials.pipe_token(&pipe)?)
             .status()
             .await
             .expect("Failed to start pipe-runner process");
@@ -347,7 +1217,7 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Wait for the server to start
-    info!("Server started on http://localhost:{}", cli.port);
+    info!("Server started on {}://localhost:{}", scheme, cli.port);
 
     // print screenpipe in gradient
     println!("\n\n{}", DISPLAY.truecolor(147, 112, 219).bold());
@@ -371,6 +1241,12 @@ async fn main() -> anyhow::Result<()> {
         format!("{} seconds", cli.audio_chunk_duration)
     );
     println!("│ Port                │ {:<34} │", cli.port);
+    println!("│ Multi User          │ {:<34} │", cli.multi_user);
+    println!("│ TLS                 │ {:<34} │", cli.tls);
+    println!(
+        "│ API Socket          │ {:<34} │",
+        api_socket_clone.display()
+    );
     println!("│ Audio Disabled      │ {:<34} │", cli.disable_audio);
     println!("│ Self Healing        │ {:<34} │", cli.self_healing);
     println!("│ Save Text Files     │ {:<34} │", cli.save_text_files);
@@ -387,7 +1263,138 @@ async fn main() -> anyhow::Result<()> {
         "│ Data Directory      │ {:<34} │",
         local_data_dir_clone.display()
     );
+    println!(
+        "│ Profile             │ {:<34} │",
+        active_profile
+            .as_ref()
+            .map(|(name, _)| name.as_str())
+            .unwrap_or("none")
+    );
+    println!(
+        "│ Retention           │ {:<34} │",
+        cli.retention_days
+            .map(|days| format!("{} days", days))
+            .unwrap_or_else(|| "forever".to_string())
+    );
+    println!(
+        "│ Aggregation Only    │ {:<34} │",
+        cli.aggregate_only_after
+            .map(|minutes| format!("raw data kept {} minutes", minutes))
+            .unwrap_or_else(|| "disabled".to_string())
+    );
+    println!(
+        "│ Disk Guard          │ {:<34} │",
+        if cli.disk_guard_gb > 0 {
+            format!("below {} GB free", cli.disk_guard_gb)
+        } else {
+            "off".to_string()
+        }
+    );
+    println!(
+        "│ Power Profiles      │ {:<34} │",
+        !cli.disable_power_profiles
+    );
+    println!("│ Focus Mode          │ {:<34} │", !cli.disable_focus_mode);
+    println!("│ App Profiles        │ {:<34} │", app_profile_count);
     println!("│ Debug Mode          │ {:<34} │", cli.debug);
+    println!(
+        "│ Meeting Summaries   │ {:<34} │",
+        cli.enable_meeting_summaries
+    );
+    println!("│ Embed Subtitles     │ {:<34} │", cli.embed_subtitles);
+    println!("│ Check Integrity     │ {:<34} │", cli.check_chunk_integrity);
+    println!("│ Redaction Policy    │ {:<34} │", redaction_policy_summary);
+    println!("│ Redacted For        │ {:<34} │", consumer_redaction_summary);
+    println!(
+        "│ Encryption          │ {:<34} │",
+        match &encryption {
+            Some(keys) if DatabaseManager::supports_encryption() =>
+                format!("media and database (key {})", keys.active_key_id()),
+            Some(keys) => format!("media only (key {})", keys.active_key_id()),
+            None => "disabled".to_string(),
+        }
+    );
+    println!("│ Compliance          │ {:<34} │", compliance_summary);
+    println!(
+        "│ Vault               │ {:<34} │",
+        cli.vault_dir.as_deref().unwrap_or("none")
+    );
+    println!(
+        "│ Email Destinations  │ {:<34} │",
+        email_destinations.len()
+    );
+    println!(
+        "│ Backup              │ {:<34} │",
+        backup_config
+            .as_ref()
+            .map(|config| format!(
+                "database{}",
+                config
+                    .media
+                    .iter()
+                    .map(|kind| format!(", {}", BackupSource::from(*kind).as_str()))
+                    .collect::<String>()
+            ))
+            .unwrap_or_else(|| "disabled".to_string())
+    );
+    println!(
+        "│ Calendars           │ {:<34} │",
+        cli.calendar.len()
+    );
+    println!(
+        "│ Watched Dirs        │ {:<34} │",
+        cli.watch_dir.len()
+    );
+    println!(
+        "│ Window History      │ {:<34} │",
+        !cli.disable_window_history
+    );
+    println!(
+        "│ Slide Decks         │ {:<34} │",
+        !cli.disable_slide_decks
+    );
+    println!("│ Shell History       │ {:<34} │", cli.enable_shell_history);
+    println!("│ Git Repositories    │ {:<34} │", cli.git_repo.len());
+    println!("│ Notifications       │ {:<34} │", cli.capture_notifications);
+    println!(
+        "│ Automation Grants   │ {:<34} │",
+        automation_grants.len()
+    );
+    println!(
+        "│ Recording Indicator │ {:<34} │",
+        cli.recording_indicator
+            .as_ref()
+            .map_or("disabled".to_string(), |style| format!("{:?}", style).to_lowercase())
+    );
+    println!(
+        "│ Timeline Mount      │ {:<34} │",
+        cli.mount_timeline.as_deref().unwrap_or("none")
+    );
+    println!(
+        "│ Remote Worker       │ {:<34} │",
+        cli.remote_worker.as_deref().unwrap_or("none")
+    );
+    println!(
+        "│ Compact After Days  │ {:<34} │",
+        cli.compact_after_days
+            .map(|days| format!("{} ({:?})", days, cli.compact_codec))
+            .unwrap_or_else(|| "disabled".to_string())
+    );
+    println!(
+        "│ FFmpeg Source       │ {:<34} │",
+        format!("{:?}", cli.ffmpeg_source)
+    );
+    println!(
+        "│ Video Encoder       │ {:<34} │",
+        format!("{} ({:?})", video_encoder.codec_name(), video_quality)
+    );
+    println!(
+        "│ OCR Priority        │ {:<34} │",
+        format!("{:?}", ocr_priority)
+    );
+    println!("│ Symbol Recognition  │ {:<34} │", cli.recognize_symbols);
+    println!("│ Live View           │ {:<34} │", cli.enable_live_view);
+    println!("│ Text Only           │ {:<34} │", cli.text_only);
     const VALUE_WIDTH: usize = 34;
 
     // Function to truncate and pad strings
@@ -439,7 +1446,24 @@ async fn main() -> anyhow::Result<()> {
     }
 
     // Keep the main thread running
-    loop {
-        tokio::time::sleep(Duration::from_secs(1)).await;
+    tokio::signal::ctrl_c().await?;
+    info!("Shutting down, stopping the recording...");
+    shutdown.cancel();
+    let _ = start_recording.await;
+    Ok(())
+}
+
+/// Cancels the recording task and waits for the work in flight to stop, for so long
+async fn stop_recording(cancel: &CancellationToken, recording_task: &mut JoinHandle<()>) {
+    cancel.cancel();
+    if tokio::time::timeout(RECORDING_STOP_TIMEOUT, &mut *recording_task)
+        .await
+        .is_err()
+    {
+        warn!(
+            "Recording didn't stop within {:?}, aborting it",
+            RECORDING_STOP_TIMEOUT
+        );
+        recording_task.abort();
     }
 }
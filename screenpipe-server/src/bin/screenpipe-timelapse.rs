@@ -0,0 +1,72 @@
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use dirs::home_dir;
+use log::LevelFilter;
+use screenpipe_server::timelapse::{DEFAULT_TIMELAPSE_FPS, DEFAULT_TIMELAPSE_SPEED};
+use screenpipe_server::{render_timelapse, DatabaseManager, TimelapseOptions};
+use std::path::PathBuf;
+
+/// Renders a sped-up video of what was recorded between two times
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Start of the range, e.g. 2024-08-26T08:00:00Z
+    #[arg(long)]
+    start: DateTime<Utc>,
+
+    /// End of the range
+    #[arg(long)]
+    end: DateTime<Utc>,
+
+    /// Recorded seconds per second of video
+    #[arg(long, default_value_t = DEFAULT_TIMELAPSE_SPEED)]
+    speed: f64,
+
+    #[arg(long, default_value_t = DEFAULT_TIMELAPSE_FPS)]
+    fps: f64,
+
+    /// Caption the video with the focused app
+    #[arg(long, default_value_t = false)]
+    app_names: bool,
+
+    /// Caption the video with the time it was recorded at
+    #[arg(long, default_value_t = false)]
+    clock: bool,
+
+    /// Output mp4 file
+    #[arg(short, long, default_value = "timelapse.mp4")]
+    output: PathBuf,
+
+    /// Data directory of screenpipe. Default to $HOME/.screenpipe
+    #[arg(long)]
+    data_dir: Option<String>,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter(None, LevelFilter::Info)
+        .format_timestamp_secs()
+        .init();
+
+    let data_dir = match args.data_dir {
+        Some(dir) => PathBuf::from(dir),
+        None => home_dir()
+            .ok_or_else(|| anyhow::anyhow!("Failed to get home directory"))?
+            .join(".screenpipe"),
+    };
+    let db = DatabaseManager::new(&format!("{}/db.sqlite", data_dir.to_string_lossy())).await?;
+
+    let options = TimelapseOptions {
+        start_time: args.start,
+        end_time: args.end,
+        speed: args.speed,
+        fps: args.fps,
+        app_names: args.app_names,
+        clock: args.clock,
+    };
+    render_timelapse(&db, &options, &args.output).await?;
+    println!("Timelapse written to {}", args.output.display());
+    Ok(())
+}
@@ -3,7 +3,8 @@ use clap::Parser;
 use env_logger::Env;
 use image::GenericImageView;
 use log::info;
-use screenpipe_server::VideoCapture;
+use screenpipe_core::{EncoderQuality, VideoEncoder};
+use screenpipe_server::{CapturePause, VideoCapture};
 use screenpipe_vision::monitor::list_monitors;
 use screenpipe_vision::OcrEngine;
 use serde_json::{json, Value};
@@ -13,6 +14,7 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::Duration;
 use tokio::sync::mpsc::{channel, Receiver, Sender}; // Correct import
+use tokio_util::sync::CancellationToken;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -65,6 +67,12 @@ async fn main() {
         save_text_files,
         Arc::new(OcrEngine::Tesseract),
         monitor_id,
+        VideoEncoder::Software,
+        EncoderQuality::default(),
+        None,
+        Arc::new(CapturePause::new()),
+        None,
+        CancellationToken::new(),
     ); // Pass the cloud_ocr flag
     let (_tx, rx): (Sender<()>, Receiver<()>) = channel(32);
     let rx = Arc::new(Mutex::new(rx));
@@ -0,0 +1,157 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use clap::Parser;
+use image::DynamicImage;
+use log::{info, warn, LevelFilter};
+use screenpipe_audio::{stt, AudioTranscriptionEngine, WhisperModel};
+use screenpipe_core::{configure_accelerators, AcceleratorMemory, ModelDeviceOverride};
+use screenpipe_integrations::remote_worker::{
+    serve_worker, tls_acceptor, WorkHandler, WorkerOptions,
+};
+use screenpipe_server::cli::{CliAudioTranscriptionEngine, CliOcrEngine};
+use screenpipe_vision::{perform_ocr, OcrEngine};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tokio::net::TcpListener;
+
+/// Does OCR and transcription for screenpipe instances started with --remote-worker
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    #[arg(short = 'p', long, default_value_t = 3035)]
+    port: u16,
+
+    /// PEM certificate chain, clients connect with wss:// when set
+    #[arg(long, requires = "tls_key")]
+    tls_cert: Option<PathBuf>,
+
+    /// PKCS#8 PEM key of the certificate
+    #[arg(long, requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Clients have to send it with --remote-worker-token
+    #[arg(long, env = "SCREENPIPE_REMOTE_WORKER_TOKEN")]
+    token: Option<String>,
+
+    /// Requests of a client processed at the same time, it waits for answers beyond that
+    #[arg(long, default_value_t = 2)]
+    max_in_flight: usize,
+
+    #[cfg_attr(
+        target_os = "macos",
+        arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::AppleNative)
+    )]
+    #[cfg_attr(
+        not(target_os = "macos"),
+        arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::Tesseract)
+    )]
+    ocr_engine: CliOcrEngine,
+
+    #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperDistilLargeV3)]
+    audio_transcription_engine: CliAudioTranscriptionEngine,
+
+    #[arg(long, default_value_t = false)]
+    disable_ocr: bool,
+
+    #[arg(long, default_value_t = false)]
+    disable_transcription: bool,
+
+    /// Device a local model runs on, like transcription=cuda:1 (can be specified multiple times)
+    #[arg(long)]
+    model_device: Vec<ModelDeviceOverride>,
+
+    /// Megabytes the local models may take on a gpu, like cuda:0=4096 (can be specified
+    /// multiple times)
+    #[arg(long)]
+    accelerator_memory: Vec<AcceleratorMemory>,
+}
+
+struct LocalEngines {
+    ocr_engine: Option<OcrEngine>,
+    transcription: Option<(Arc<WhisperModel>, Arc<AudioTranscriptionEngine>)>,
+}
+
+#[async_trait]
+impl WorkHandler for LocalEngines {
+    fn can_ocr(&self) -> bool {
+        self.ocr_engine.is_some()
+    }
+
+    fn can_transcribe(&self) -> bool {
+        self.transcription.is_some()
+    }
+
+    async fn ocr(&self, image: DynamicImage) -> Result<(String, String)> {
+        let ocr_engine = self.ocr_engine.as_ref().expect("checked by can_ocr");
+        Ok(perform_ocr(ocr_engine, &Arc::new(image)).await?)
+    }
+
+    async fn transcribe(&self, audio_path: &Path) -> Result<String> {
+        let (whisper_model, engine) = self
+            .transcription
+            .clone()
+            .expect("checked by can_transcribe");
+        let audio_path = audio_path.to_string_lossy().to_string();
+        tokio::task::spawn_blocking(move || stt(&audio_path, &whisper_model, engine)).await?
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter(None, LevelFilter::Info)
+        .format_timestamp_secs()
+        .init();
+
+    if args.ocr_engine == CliOcrEngine::Remote
+        || args.audio_transcription_engine == CliAudioTranscriptionEngine::Remote
+    {
+        anyhow::bail!("a worker can't send its work to another worker");
+    }
+    configure_accelerators(&args.accelerator_memory, &args.model_device);
+    let ocr_engine = (!args.disable_ocr).then(|| OcrEngine::from(args.ocr_engine.clone()));
+    let transcription = match args.disable_transcription {
+        true => None,
+        false => {
+            let engine = Arc::new(AudioTranscriptionEngine::from(
+                args.audio_transcription_engine.clone(),
+            ));
+            Some((Arc::new(WhisperModel::new(engine.clone())?), engine))
+        }
+    };
+    let tls = match (&args.tls_cert, &args.tls_key) {
+        (Some(cert), Some(key)) => Some(tls_acceptor(cert, key)?),
+        _ => None,
+    };
+    if tls.is_none() {
+        warn!("No --tls-cert, frames and audio are received in the clear");
+    }
+    if args.token.is_none() {
+        warn!(
+            "No --token, anyone reaching port {} can use this worker",
+            args.port
+        );
+    }
+
+    let listener = TcpListener::bind(("0.0.0.0", args.port)).await?;
+    info!(
+        "Worker listening on port {} with OCR {} and transcription {}",
+        args.port,
+        ocr_engine.is_some(),
+        transcription.is_some()
+    );
+    serve_worker(
+        listener,
+        Arc::new(LocalEngines {
+            ocr_engine,
+            transcription,
+        }),
+        WorkerOptions {
+            token: args.token,
+            max_in_flight: args.max_in_flight.max(1),
+            tls,
+        },
+    )
+    .await
+}
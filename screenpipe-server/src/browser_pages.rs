@@ -0,0 +1,100 @@
+use screenpipe_vision::core::BrowserPageSource;
+use screenpipe_vision::CaptureResult;
+use serde::Deserialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Stored as the OCR engine of the windows whose text came from the extension
+pub const BROWSER_EXTENSION_ENGINE: &str = "BrowserExtension";
+/// Longest page text accepted, in bytes
+pub const MAX_PAGE_TEXT_LEN: usize = 1_000_000;
+/// A page is matched with the frames taken this long after it was pushed, the extension
+/// pushes again when the page changes or scrolls
+const PAGE_TTL: Duration = Duration::from_secs(30);
+/// Tabs kept at once, a browser window only shows one of them
+const MAX_PAGES: usize = 64;
+
+/// Words of the app names of the browsers' windows, lowercase
+const BROWSERS: [&str; 10] = [
+    "chrome", "chromium", "firefox", "safari", "edge", "brave", "arc", "opera", "vivaldi", "zen",
+];
+
+/// What the browser extension pushes of the tab shown in a window, whenever it's shown,
+/// navigated or scrolled
+#[derive(Debug, Clone, Deserialize)]
+pub struct BrowserPage {
+    pub url: String,
+    /// Title of the tab, the browser window gets it in its title too
+    pub title: String,
+    /// Text of the DOM in the viewport
+    pub text: String,
+    /// Like "chrome" or "firefox", matched with the app name of the window when given
+    #[serde(default)]
+    pub browser: Option<String>,
+    #[serde(default)]
+    pub scroll_y: Option<f64>,
+}
+
+/// Pages pushed by the extension, the newest last
+static PAGES: Mutex<Vec<(Instant, BrowserPage)>> = Mutex::new(Vec::new());
+
+pub fn is_browser(app_name: &str) -> bool {
+    app_name
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .any(|word| BROWSERS.contains(&word))
+}
+
+/// Replaces what was pushed before for the same tab
+pub fn report_browser_page(page: BrowserPage) {
+    let mut pages = PAGES.lock().unwrap();
+    pages.retain(|(pushed, pushed_page)| {
+        pushed.elapsed() < PAGE_TTL
+            && !(pushed_page.url == page.url && pushed_page.title == page.title)
+    });
+    pages.push((Instant::now(), page));
+    if pages.len() > MAX_PAGES {
+        let extra = pages.len() - MAX_PAGES;
+        pages.drain(..extra);
+    }
+}
+
+/// The newest page pushed lately whose title is in the title of the browser window
+pub fn browser_page_for_window(app_name: &str, window_name: &str) -> Option<BrowserPage> {
+    if !is_browser(app_name) {
+        return None;
+    }
+    let app_name = app_name.to_lowercase();
+    PAGES
+        .lock()
+        .unwrap()
+        .iter()
+        .rev()
+        .filter(|(pushed, _)| pushed.elapsed() < PAGE_TTL)
+        .map(|(_, page)| page)
+        .find(|page| {
+            let title = page.title.trim();
+            !title.is_empty()
+                && window_name.contains(title)
+                && page
+                    .browser
+                    .as_ref()
+                    .is_none_or(|browser| app_name.contains(&browser.to_lowercase()))
+        })
+        .cloned()
+}
+
+/// Puts the text the extension read instead of the OCR of the browser windows of the frame.
+/// Done before the redaction policy, the page text is redacted like the OCR would be
+pub fn merge_browser_pages(frame: &mut CaptureResult) {
+    for window in frame.window_ocr_results.iter_mut() {
+        let Some(page) = browser_page_for_window(&window.app_name, &window.window_name) else {
+            continue;
+        };
+        window.text = page.text;
+        window.browser_page = Some(BrowserPageSource {
+            url: page.url,
+            scroll_y: page.scroll_y,
+        });
+    }
+}
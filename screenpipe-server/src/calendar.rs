@@ -0,0 +1,213 @@
+use crate::activity::{ActivityCategory, ActivitySession};
+use crate::meetings::MeetingSummary;
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, info, warn};
+use screenpipe_core::emit_event;
+use screenpipe_integrations::calendar::{best_overlap, fetch_events, write_ics, FeedEvent};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Meetings summarized this long ago still get their event, in case the calendar was late
+const LABEL_LOOKBACK: ChronoDuration = ChronoDuration::days(7);
+/// Activity sessions of these categories are deep work
+const FOCUS_CATEGORIES: [ActivityCategory; 1] = [ActivityCategory::Coding];
+/// Focus sessions this close are the same block, a quick look at the chat doesn't end it
+const FOCUS_MAX_GAP: ChronoDuration = ChronoDuration::minutes(5);
+/// Shorter blocks aren't put in the feed
+pub const MIN_FOCUS_BLOCK: ChronoDuration = ChronoDuration::minutes(25);
+/// Upper bound of the sessions of a category read for the feed
+const MAX_FEED_SESSIONS: u32 = 10_000;
+/// Apps named in the title of a focus block
+const FOCUS_TITLE_APPS: usize = 3;
+
+/// Gives the meetings that don't have one the calendar event they overlapped the most.
+/// Returns how many meetings were labeled
+pub async fn label_meetings(db: &DatabaseManager, calendars: &[String]) -> Result<usize> {
+    let end = Utc::now();
+    let start = end - LABEL_LOOKBACK;
+    let meetings = db.get_unlabeled_meeting_summaries(start, end).await?;
+    if meetings.is_empty() {
+        return Ok(0);
+    }
+
+    let (events, errors) = fetch_events(calendars, start, end).await;
+    for e in errors {
+        warn!("Failed to read calendar: {}", e);
+    }
+    let mut labeled = 0;
+    for meeting in meetings {
+        let Some(event) = best_overlap(&events, meeting.start_time, meeting.end_time) else {
+            continue;
+        };
+        db.set_meeting_calendar_event(meeting.id, &event.title, &event.attendees)
+            .await?;
+        debug!("Meeting {} is \"{}\"", meeting.id, event.title);
+        emit_event(
+            "meeting.labeled",
+            json!({
+                "id": meeting.id,
+                "calendar_title": event.title,
+                "calendar_attendees": event.attendees,
+            }),
+        );
+        labeled += 1;
+    }
+    Ok(labeled)
+}
+
+pub async fn start_calendar_sync(
+    db: Arc<DatabaseManager>,
+    calendars: Vec<String>,
+    interval: Duration,
+) {
+    info!("Calendar sync started with {} calendars", calendars.len());
+    loop {
+        match label_meetings(&db, &calendars).await {
+            Ok(0) => {}
+            Ok(labeled) => info!("Labeled {} meetings with their calendar event", labeled),
+            Err(e) => warn!("Failed to label meetings with calendar events: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Uninterrupted time spent in the focus categories
+#[derive(Debug, Clone, PartialEq)]
+pub struct FocusBlock {
+    /// Id of its first session, so the block keeps it as later sessions extend it
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Seconds spent in each app, the most used first
+    pub apps: Vec<(String, f64)>,
+}
+
+/// Merges the ascending sessions of the focus categories into blocks of at least
+/// `MIN_FOCUS_BLOCK`
+pub fn focus_blocks(sessions: &[ActivitySession]) -> Vec<FocusBlock> {
+    let mut blocks: Vec<(FocusBlock, HashMap<String, f64>)> = Vec::new();
+    for session in sessions
+        .iter()
+        .filter(|session| FOCUS_CATEGORIES.contains(&session.category))
+    {
+        let duration = (session.end_time - session.start_time).num_milliseconds() as f64 / 1000.0;
+        match blocks.last_mut() {
+            Some((block, apps)) if session.start_time - block.end_time <= FOCUS_MAX_GAP => {
+                block.end_time = block.end_time.max(session.end_time);
+                *apps.entry(session.app_name.clone()).or_default() += duration;
+            }
+            _ => blocks.push((
+                FocusBlock {
+                    id: session.id,
+                    start_time: session.start_time,
+                    end_time: session.end_time,
+                    apps: Vec::new(),
+                },
+                HashMap::from([(session.app_name.clone(), duration)]),
+            )),
+        }
+    }
+    blocks
+        .into_iter()
+        .filter(|(block, _)| block.end_time - block.start_time >= MIN_FOCUS_BLOCK)
+        .map(|(mut block, apps)| {
+            block.apps = apps.into_iter().collect();
+            block
+                .apps
+                .sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            block
+        })
+        .collect()
+}
+
+fn meeting_event(meeting: &MeetingSummary) -> FeedEvent {
+    let mut description = meeting.summary.clone();
+    if !meeting.decisions.is_empty() {
+        description.push_str("\n\nDecisions:");
+        for decision in &meeting.decisions {
+            description.push_str(&format!("\n- {}", decision));
+        }
+    }
+    if !meeting.action_items.is_empty() {
+        description.push_str("\n\nAction items:");
+        for item in &meeting.action_items {
+            match &item.owner {
+                Some(owner) => {
+                    description.push_str(&format!("\n- {} ({})", item.description, owner))
+                }
+                None => description.push_str(&format!("\n- {}", item.description)),
+            }
+        }
+    }
+    FeedEvent {
+        uid: format!("meeting-{}@screenpipe", meeting.id),
+        title: meeting
+            .calendar_title
+            .clone()
+            .unwrap_or_else(|| "Meeting".to_string()),
+        start: meeting.start_time,
+        end: meeting.end_time,
+        description: Some(description),
+        categories: vec!["meeting".to_string()],
+    }
+}
+
+fn focus_event(block: &FocusBlock) -> FeedEvent {
+    let apps: Vec<&str> = block
+        .apps
+        .iter()
+        .take(FOCUS_TITLE_APPS)
+        .map(|(app, _)| app.as_str())
+        .collect();
+    let description: Vec<String> = block
+        .apps
+        .iter()
+        .map(|(app, secs)| format!("{}: {} min", app, (secs / 60.0).round()))
+        .collect();
+    FeedEvent {
+        uid: format!("focus-{}@screenpipe", block.id),
+        title: format!("Focus: {}", apps.join(", ")),
+        start: block.start_time,
+        end: block.end_time,
+        description: Some(description.join("\n")),
+        categories: vec!["focus".to_string()],
+    }
+}
+
+/// iCalendar feed of the meetings detected and the focus blocks between `start` and `end`
+pub async fn calendar_feed(
+    db: &DatabaseManager,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<String> {
+    let mut events: Vec<FeedEvent> = db
+        .get_meeting_summaries_between(start, end)
+        .await?
+        .iter()
+        .map(meeting_event)
+        .collect();
+
+    let mut sessions = Vec::new();
+    for category in FOCUS_CATEGORIES {
+        sessions.extend(
+            db.get_activity_sessions(
+                Some(category),
+                Some(start),
+                Some(end),
+                MAX_FEED_SESSIONS,
+                0,
+                None,
+            )
+            .await?,
+        );
+    }
+    sessions.sort_by_key(|session| session.start_time);
+    events.extend(focus_blocks(&sessions).iter().map(focus_event));
+
+    events.sort_by_key(|event| event.start);
+    Ok(write_ics("screenpipe", &events, Utc::now()))
+}
@@ -0,0 +1,274 @@
+use crate::frame_format::{frame_storage, FrameEncoding};
+use crate::text_only::text_only;
+use screenpipe_core::{find_ffmpeg_path, EncoderQuality, VideoEncoder};
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// Set once at startup after probing the hardware
+static VIDEO_ENCODER_SELECTION: OnceLock<VideoEncoderSelection> = OnceLock::new();
+/// Set once at startup from the command line
+static ENGINE_SELECTION: OnceLock<EngineSelection> = OnceLock::new();
+static TESSERACT_INSTALLED: OnceLock<bool> = OnceLock::new();
+
+/// OCR engines compiled for this platform, by their --ocr-engine name
+#[cfg(target_os = "macos")]
+const OCR_ENGINES: &[&str] = &["unstructured", "apple-native", "remote"];
+#[cfg(target_os = "windows")]
+const OCR_ENGINES: &[&str] = &["unstructured", "tesseract", "windows-native", "remote"];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const OCR_ENGINES: &[&str] = &["unstructured", "tesseract", "remote"];
+/// By their --audio-transcription-engine name
+const STT_ENGINES: &[&str] = &["deepgram", "whisper-tiny", "whisper-large", "remote"];
+
+#[cfg(target_os = "macos")]
+const AUDIO_BACKENDS: &[&str] = &["coreaudio", "screencapturekit"];
+#[cfg(target_os = "windows")]
+const AUDIO_BACKENDS: &[&str] = &["wasapi"];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const AUDIO_BACKENDS: &[&str] = &["alsa"];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct VideoEncoderSelection {
+    /// Hardware encoders that passed the probe, best first
+    pub hardware_encoders: Vec<VideoEncoder>,
+    pub encoder: VideoEncoder,
+    pub quality: EncoderQuality,
+}
+
+pub fn set_video_encoder_selection(
+    hardware_encoders: Vec<VideoEncoder>,
+    encoder: VideoEncoder,
+    quality: EncoderQuality,
+) {
+    let _ = VIDEO_ENCODER_SELECTION.set(VideoEncoderSelection {
+        hardware_encoders,
+        encoder,
+        quality,
+    });
+}
+
+#[derive(Debug, Clone)]
+struct EngineSelection {
+    ocr_engine: String,
+    /// None when audio is disabled
+    stt_engine: Option<String>,
+    remote_worker: bool,
+}
+
+pub fn set_engine_selection(ocr_engine: &str, stt_engine: Option<&str>, remote_worker: bool) {
+    let _ = ENGINE_SELECTION.set(EngineSelection {
+        ocr_engine: ocr_engine.to_string(),
+        stt_engine: stt_engine.map(str::to_string),
+        remote_worker,
+    });
+}
+
+/// What encodes the video chunks
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EncoderBackend {
+    Ffmpeg,
+    /// Pure Rust fallback, see `native-encoder` feature
+    Native,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncodingCapabilities {
+    /// None if frames can't be stored at all
+    pub backend: Option<EncoderBackend>,
+    pub ffmpeg_path: Option<String>,
+    pub native_encoder_available: bool,
+    /// None until the encoders have been probed, and with the native backend
+    pub video_encoder: Option<VideoEncoderSelection>,
+    pub video_codecs: Vec<&'static str>,
+    pub containers: Vec<&'static str>,
+    /// Images the frames are stored as rather than video, with --frame-format
+    pub frame_storage: Option<FrameEncoding>,
+    /// Nothing of the frames is stored, with --text-only
+    pub text_only: bool,
+    /// What doesn't work with the current backend
+    pub limitations: Vec<&'static str>,
+}
+
+/// ffmpeg when available, the native encoder otherwise (if compiled in)
+pub fn encoder_backend() -> Option<EncoderBackend> {
+    if find_ffmpeg_path().is_some() {
+        Some(EncoderBackend::Ffmpeg)
+    } else if cfg!(feature = "native-encoder") {
+        Some(EncoderBackend::Native)
+    } else {
+        None
+    }
+}
+
+pub fn encoding_capabilities() -> EncodingCapabilities {
+    let ffmpeg_path = find_ffmpeg_path().map(|p| p.to_string_lossy().into_owned());
+    let backend = encoder_backend();
+    let video_encoder = VIDEO_ENCODER_SELECTION
+        .get()
+        .filter(|_| backend == Some(EncoderBackend::Ffmpeg))
+        .cloned();
+    let frame_storage = frame_storage();
+    let text_only = text_only();
+    let (video_codecs, containers, limitations) = match (backend, frame_storage) {
+        _ if text_only => (
+            vec![],
+            vec![],
+            vec!["the screen is recorded text only, no frames are stored"],
+        ),
+        (Some(EncoderBackend::Ffmpeg), Some(encoding)) => (
+            vec![encoding.format.extension()],
+            vec!["frames"],
+            vec!["chunks are directories of images, not playable video files"],
+        ),
+        (Some(EncoderBackend::Ffmpeg), None) => {
+            let mut video_codecs = vec![VideoEncoder::Software.codec_name()];
+            if let Some(selection) = &video_encoder {
+                video_codecs.extend(selection.hardware_encoders.iter().map(|e| e.codec_name()));
+            }
+            (video_codecs, vec!["mp4"], vec![])
+        }
+        (Some(EncoderBackend::Native), _) => (
+            vec![frame_storage.unwrap_or_default().format.extension()],
+            vec!["frames"],
+            vec![
+                "frames are stored as images, chunks are much bigger than H.264",
+                "audio is not recorded without ffmpeg",
+                "chunks are directories of images, not playable video files",
+            ],
+        ),
+        (None, _) => (
+            vec![],
+            vec![],
+            vec!["frames are not stored, install ffmpeg"],
+        ),
+    };
+
+    EncodingCapabilities {
+        backend,
+        ffmpeg_path,
+        native_encoder_available: cfg!(feature = "native-encoder"),
+        video_encoder,
+        video_codecs,
+        containers,
+        frame_storage,
+        text_only,
+        limitations,
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EngineCapability {
+    pub name: &'static str,
+    /// Can be picked on this machine
+    pub available: bool,
+    pub in_use: bool,
+    /// Why it isn't available, or what it needs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub note: Option<&'static str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureCapabilities {
+    pub screen: Vec<&'static str>,
+    pub audio: Vec<&'static str>,
+}
+
+/// What the running server was started with, for the features that can be turned on
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FeatureCapabilities {
+    /// Media and database keys are kept in the keychain
+    pub encryption: bool,
+    /// Compiled with SQLCipher, see `sqlcipher` feature
+    pub sqlcipher: bool,
+    /// PII is redacted for some consumers of the API
+    pub consumer_redaction: bool,
+    pub compliance: bool,
+    pub live_view: bool,
+    /// Pipes can click and type, see `automation` feature and --automation-grants
+    pub automation: bool,
+    pub vault_export: bool,
+    /// Compiled with the pipes runtime, see `pipes` feature
+    pub pipes: bool,
+    /// Model the summaries, classification and descriptions are asked to
+    pub llm_model: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub os: &'static str,
+    /// What runs the local models
+    pub acceleration: &'static str,
+    pub ocr_engines: Vec<EngineCapability>,
+    pub stt_engines: Vec<EngineCapability>,
+    pub capture: CaptureCapabilities,
+    pub encoding: EncodingCapabilities,
+    pub features: FeatureCapabilities,
+}
+
+fn tesseract_installed() -> bool {
+    *TESSERACT_INSTALLED.get_or_init(|| rusty_tesseract::get_tesseract_version().is_ok())
+}
+
+fn engine_capability(name: &'static str, in_use: bool, remote_worker: bool) -> EngineCapability {
+    let (available, note) = match name {
+        "tesseract" if !tesseract_installed() => (false, Some("tesseract is not installed")),
+        "remote" if !remote_worker => (false, Some("needs a --remote-worker")),
+        "remote" => (true, None),
+        "unstructured" | "deepgram" => (true, Some("sends the data to a cloud api")),
+        _ => (true, None),
+    };
+    EngineCapability {
+        name,
+        available,
+        in_use,
+        note,
+    }
+}
+
+/// Everything this build and machine support, `features` are the ones of the running server
+pub fn machine_capabilities(features: FeatureCapabilities) -> Capabilities {
+    let selection = ENGINE_SELECTION.get();
+    let remote_worker = selection.is_some_and(|selection| selection.remote_worker);
+    let ocr_engines = OCR_ENGINES
+        .iter()
+        .map(|name| {
+            let in_use = selection.is_some_and(|selection| selection.ocr_engine == *name);
+            engine_capability(name, in_use, remote_worker)
+        })
+        .collect();
+    let stt_engines = STT_ENGINES
+        .iter()
+        .map(|name| {
+            let in_use =
+                selection.is_some_and(|selection| selection.stt_engine.as_deref() == Some(*name));
+            engine_capability(name, in_use, remote_worker)
+        })
+        .collect();
+    let acceleration = if cfg!(feature = "cuda") {
+        "cuda"
+    } else if cfg!(feature = "metal") {
+        "metal"
+    } else {
+        "cpu"
+    };
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        acceleration,
+        ocr_engines,
+        stt_engines,
+        capture: CaptureCapabilities {
+            screen: vec!["xcap"],
+            audio: AUDIO_BACKENDS.to_vec(),
+        },
+        encoding: encoding_capabilities(),
+        features: FeatureCapabilities {
+            sqlcipher: cfg!(feature = "sqlcipher"),
+            pipes: cfg!(feature = "pipes"),
+            ..features
+        },
+    }
+}
@@ -1,21 +1,17 @@
 use anyhow::Result;
-use candle::{DType, Device, Tensor};
+use candle::{DType, Tensor};
 use candle_nn::{Module, VarBuilder};
 use candle_transformers::models::jina_bert::{BertModel, Config};
-use hf_hub::{api::sync::Api, Repo, RepoType};
+use screenpipe_core::{model_device, LocalModel, JINA_EMBEDDINGS};
 use tokenizers::Tokenizer;
 
+/// Megabytes the jina embeddings take in f32
+const EMBEDDINGS_MEMORY_MB: u64 = 550;
+
 pub async fn text_chunking_by_similarity(text: &str) -> Result<Vec<String>> {
-    let device =
-        Device::new_metal(0).unwrap_or_else(|_| Device::new_cuda(0).unwrap_or(Device::Cpu));
-    let repo = Repo::with_revision(
-        "jinaai/jina-embeddings-v2-base-en".to_string(),
-        RepoType::Model,
-        "main".to_string(),
-    );
-    let api = Api::new()?;
-    let model_file = api.repo(repo.clone()).get("model.safetensors")?;
-    let tokenizer_file = api.repo(repo).get("tokenizer.json")?;
+    let (device, _lease) = model_device(LocalModel::Embeddings, EMBEDDINGS_MEMORY_MB);
+    let model_file = JINA_EMBEDDINGS.file("model.safetensors")?;
+    let tokenizer_file = JINA_EMBEDDINGS.file("tokenizer.json")?;
 
     let tokenizer = Tokenizer::from_file(tokenizer_file).map_err(anyhow::Error::msg)?;
     let config = Config::v2_base();
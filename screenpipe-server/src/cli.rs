@@ -1,7 +1,19 @@
 use clap::Parser;
 use screenpipe_audio::AudioTranscriptionEngine as CoreAudioTranscriptionEngine;
 use screenpipe_vision::utils::OcrEngine as CoreOcrEngine;
+use screenpipe_vision::{OcrPriority, DEFAULT_BACKGROUND_SAMPLE_EVERY};
 use clap::ValueEnum;
+use screenpipe_core::{
+    AcceleratorMemory, EncoderQuality, LlmConfig, ModelDeviceOverride, PiiEntityKind, VideoEncoder,
+    DEFAULT_NER_MODEL,
+};
+use std::time::Duration;
+use crate::compaction::{CompactionCodec, CompactionOptions};
+use crate::consumer_redaction::Consumer;
+use crate::frame_format::{FrameFormat, DEFAULT_FRAME_QUALITY};
+use crate::indicator::IndicatorStyle;
+use screenpipe_integrations::shell_history::Shell;
+use screenpipe_integrations::vault_export::VaultFormat;
 
 #[derive(Clone, Debug, ValueEnum, PartialEq)]
 pub enum CliAudioTranscriptionEngine {
@@ -11,6 +23,8 @@ pub enum CliAudioTranscriptionEngine {
     WhisperTiny,
     #[clap(name = "whisper-large")]
     WhisperDistilLargeV3,
+    #[clap(name = "remote")]
+    Remote,
 }
 
 impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
@@ -21,6 +35,7 @@ impl From<CliAudioTranscriptionEngine> for CoreAudioTranscriptionEngine {
             CliAudioTranscriptionEngine::WhisperDistilLargeV3 => {
                 CoreAudioTranscriptionEngine::WhisperDistilLargeV3
             }
+            CliAudioTranscriptionEngine::Remote => CoreAudioTranscriptionEngine::Remote,
         }
     }
 }
@@ -34,6 +49,10 @@ pub enum CliOcrEngine {
     WindowsNative,
     #[cfg(target_os = "macos")]
     AppleNative,
+    Remote,
+    /// TrOCR trained on handwriting, downloaded on first use. Slow, for the apps of
+    /// app_profiles.json with "handwriting": true rather than the whole screen
+    Handwriting,
 }
 
 impl From<CliOcrEngine> for CoreOcrEngine {
@@ -46,6 +65,215 @@ impl From<CliOcrEngine> for CoreOcrEngine {
             CliOcrEngine::WindowsNative => CoreOcrEngine::WindowsNative,
             #[cfg(target_os = "macos")]
             CliOcrEngine::AppleNative => CoreOcrEngine::AppleNative,
+            CliOcrEngine::Remote => CoreOcrEngine::Remote,
+            CliOcrEngine::Handwriting => CoreOcrEngine::Handwriting,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliFfmpegSource {
+    /// ffmpeg installed on the system, fails if there is none
+    #[clap(name = "system")]
+    System,
+    /// ffmpeg installed on the system, or the pinned build downloaded on first run
+    #[clap(name = "auto")]
+    Auto,
+    /// Always the pinned build, downloaded on first run into the data dir
+    #[clap(name = "pinned")]
+    Pinned,
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliVideoEncoder {
+    /// Best hardware encoder found at startup, software otherwise
+    #[clap(name = "auto")]
+    Auto,
+    #[clap(name = "software")]
+    Software,
+    #[clap(name = "videotoolbox")]
+    VideoToolbox,
+    #[clap(name = "nvenc")]
+    Nvenc,
+    #[clap(name = "qsv")]
+    Qsv,
+    #[clap(name = "vaapi")]
+    Vaapi,
+}
+
+impl CliVideoEncoder {
+    /// None for auto
+    pub fn encoder(&self) -> Option<VideoEncoder> {
+        match self {
+            CliVideoEncoder::Auto => None,
+            CliVideoEncoder::Software => Some(VideoEncoder::Software),
+            CliVideoEncoder::VideoToolbox => Some(VideoEncoder::VideoToolbox),
+            CliVideoEncoder::Nvenc => Some(VideoEncoder::Nvenc),
+            CliVideoEncoder::Qsv => Some(VideoEncoder::Qsv),
+            CliVideoEncoder::Vaapi => Some(VideoEncoder::Vaapi),
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliVideoQuality {
+    Low,
+    Medium,
+    High,
+}
+
+impl From<CliVideoQuality> for EncoderQuality {
+    fn from(quality: CliVideoQuality) -> Self {
+        match quality {
+            CliVideoQuality::Low => EncoderQuality::Low,
+            CliVideoQuality::Medium => EncoderQuality::Medium,
+            CliVideoQuality::High => EncoderQuality::High,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliFrameFormat {
+    /// Lossless, the biggest
+    Png,
+    /// Lossless, about half the size of PNG
+    Webp,
+    /// Lossy at --frame-quality, the smallest, slow to encode and read back with ffmpeg
+    Avif,
+    /// Lossy at --frame-quality
+    Jpeg,
+}
+
+impl From<CliFrameFormat> for FrameFormat {
+    fn from(format: CliFrameFormat) -> Self {
+        match format {
+            CliFrameFormat::Png => FrameFormat::Png,
+            CliFrameFormat::Webp => FrameFormat::Webp,
+            CliFrameFormat::Avif => FrameFormat::Avif,
+            CliFrameFormat::Jpeg => FrameFormat::Jpeg,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliShell {
+    Zsh,
+    Bash,
+    Fish,
+}
+
+impl From<CliShell> for Shell {
+    fn from(shell: CliShell) -> Self {
+        match shell {
+            CliShell::Zsh => Shell::Zsh,
+            CliShell::Bash => Shell::Bash,
+            CliShell::Fish => Shell::Fish,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliOcrPriority {
+    /// Every window of the frames that are OCR'd
+    All,
+    /// Only the focused window while the OCR is behind
+    Focused,
+    /// The focused window, and the other windows of one frame in --background-ocr-every while the OCR is behind
+    Sample,
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliPiiEntity {
+    Person,
+    Organization,
+    Location,
+    Misc,
+}
+
+impl From<CliPiiEntity> for PiiEntityKind {
+    fn from(entity: CliPiiEntity) -> Self {
+        match entity {
+            CliPiiEntity::Person => PiiEntityKind::Person,
+            CliPiiEntity::Organization => PiiEntityKind::Organization,
+            CliPiiEntity::Location => PiiEntityKind::Location,
+            CliPiiEntity::Misc => PiiEntityKind::Misc,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliConsumer {
+    /// The app, with the token of app_token in the data directory
+    Local,
+    /// Pipes with their token of pipe_tokens.json
+    Pipes,
+    /// Clients with one of the --api-token tokens
+    Remote,
+    /// Nobody, everyone gets the text as stored
+    None,
+}
+
+impl CliConsumer {
+    pub fn consumer(&self) -> Option<Consumer> {
+        match self {
+            CliConsumer::Local => Some(Consumer::Local),
+            CliConsumer::Pipes => Some(Consumer::Pipe),
+            CliConsumer::Remote => Some(Consumer::Remote),
+            CliConsumer::None => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliVaultFormat {
+    /// Notes in a screenpipe folder of the vault, images in screenpipe/attachments
+    Obsidian,
+    /// screenpipe/<date> pages, images in assets
+    Logseq,
+}
+
+impl From<CliVaultFormat> for VaultFormat {
+    fn from(cli_format: CliVaultFormat) -> Self {
+        match cli_format {
+            CliVaultFormat::Obsidian => VaultFormat::Obsidian,
+            CliVaultFormat::Logseq => VaultFormat::Logseq,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliIndicatorStyle {
+    /// A small dot in the top right corner of the main screen
+    Dot,
+    /// A thin frame around the main screen
+    Border,
+}
+
+impl From<CliIndicatorStyle> for IndicatorStyle {
+    fn from(cli_style: CliIndicatorStyle) -> Self {
+        match cli_style {
+            CliIndicatorStyle::Dot => IndicatorStyle::Dot,
+            CliIndicatorStyle::Border => IndicatorStyle::Border,
+        }
+    }
+}
+
+#[derive(Clone, Debug, ValueEnum, PartialEq)]
+pub enum CliCompactionCodec {
+    #[clap(name = "h264")]
+    H264,
+    #[clap(name = "h265")]
+    H265,
+    #[clap(name = "av1")]
+    Av1,
+}
+
+impl From<CliCompactionCodec> for CompactionCodec {
+    fn from(codec: CliCompactionCodec) -> Self {
+        match codec {
+            CliCompactionCodec::H264 => CompactionCodec::H264,
+            CliCompactionCodec::H265 => CompactionCodec::H265,
+            CliCompactionCodec::Av1 => CompactionCodec::Av1,
         }
     }
 }
@@ -93,10 +321,58 @@ pub struct Cli {
     #[arg(long)]
     pub list_audio_devices: bool,
 
+    /// Check permissions, ffmpeg, the OCR engine, disk space and the GPU, then capture and read
+    /// the screen for 10 seconds. Prints the results as one line of json and exits, with 1 when
+    /// a check failed
+    #[arg(long)]
+    pub doctor: bool,
+
+    /// Seconds of the --doctor capture and OCR test, 0 skips it
+    #[arg(long, default_value_t = 10)]
+    pub doctor_smoke_test_secs: u64,
+
     /// Data directory. Default to $HOME/.screenpipe
     #[arg(long)]
     pub data_dir: Option<String>,
 
+    /// Where the recordings go, on an external disk say. The database stays in the data
+    /// directory. Default to the data directory of the profile, in data. screenpipe-relocate
+    /// moves the recordings made so far
+    #[arg(long)]
+    pub media_dir: Option<String>,
+
+    /// Profile of profiles.json in the data directory to record to, each one has its own
+    /// database, media, retention and blocklists. Defaults to the profile bound to the OS user
+    #[arg(long)]
+    pub profile: Option<String>,
+
+    /// Delete what was recorded more than this many days ago. Kept forever if not set
+    #[arg(long)]
+    pub retention_days: Option<u64>,
+
+    /// Aggregation only mode: keep the frames, images, transcriptions and audio this many
+    /// minutes (at least 10), then only their hourly totals of words and characters per app
+    /// (/analytics/usage.csv) and the activity sessions. What's already recorded is aggregated
+    /// and deleted too
+    #[arg(long)]
+    pub aggregate_only_after: Option<u64>,
+
+    /// Free disk space in GB below which recording degrades instead of filling the disk: no
+    /// marker screenshots, then low quality video from half of it, a week of retention from a
+    /// fifth and no media written from a tenth. 0 turns it off
+    #[arg(long, default_value_t = 10)]
+    pub disk_guard_gb: u64,
+
+    /// Don't switch power profiles on battery or when the machine is hot, they lower the fps
+    /// and pause background jobs unless changed in power_profiles.json
+    #[arg(long, default_value_t = false)]
+    pub disable_power_profiles: bool,
+
+    /// Don't read whether do not disturb or a focus mode is on. It tags the search results
+    /// and activity sessions, and the email alerts and digests wait for it to end
+    #[arg(long, default_value_t = false)]
+    pub disable_focus_mode: bool,
+
     /// Enable debug logging for screenpipe modules
     #[arg(long)]
     pub debug: bool,
@@ -109,6 +385,7 @@ pub struct Cli {
     /// Deepgram is a very high quality cloud-based transcription service (free of charge on us for now), recommended for high quality audio.
     /// WhisperTiny is a local, lightweight transcription model, recommended for high data privacy.
     /// WhisperDistilLargeV3 is a local, lightweight transcription model (--a whisper-large), recommended for higher quality audio than tiny.
+    /// Remote sends the audio to the worker at --remote-worker.
     #[arg(short = 'a', long, value_enum, default_value_t = CliAudioTranscriptionEngine::WhisperTiny)]
     pub audio_transcription_engine: CliAudioTranscriptionEngine,
 
@@ -117,6 +394,7 @@ pub struct Cli {
     /// WindowsNative is a local OCR engine for Windows.
     /// Unstructured is a cloud OCR engine (free of charge on us for now), recommended for high quality OCR.
     /// Tesseract is a local OCR engine (not supported on macOS)
+    /// Remote sends the frames to the worker at --remote-worker
     #[cfg_attr(
         target_os = "macos",
         arg(short = 'o', long, value_enum, default_value_t = CliOcrEngine::AppleNative)
@@ -142,4 +420,403 @@ pub struct Cli {
     /// File path for the pipe
     #[arg(long)]
     pub pipe: Vec<String>,
-}
\ No newline at end of file
+
+    /// Summarize meetings (summary, decisions, action items) when they end, using the LLM configured below.
+    /// A meeting is a conversation of at least 5 minutes without more than 5 minutes of silence.
+    #[arg(long, default_value_t = false)]
+    pub enable_meeting_summaries: bool,
+
+    /// OpenAI compatible API used for LLM features. Default to a local Ollama
+    #[arg(long, default_value = "http://localhost:11434/v1")]
+    pub llm_api_url: String,
+
+    /// Model used for LLM features
+    #[arg(long, default_value = "llama3.1")]
+    pub llm_model: String,
+
+    /// API key for the LLM API (not needed for Ollama)
+    #[arg(long, env = "SCREENPIPE_LLM_API_KEY")]
+    pub llm_api_key: Option<String>,
+
+    /// Hard monthly budget in USD for the LLM API, based on the estimated cost of the calls (see /llm/usage).
+    /// Once reached, calls go to --llm-fallback-model or fail.
+    #[arg(long)]
+    pub llm_monthly_budget: Option<f64>,
+
+    /// Local Ollama model used once the monthly budget is reached
+    #[arg(long)]
+    pub llm_fallback_model: Option<String>,
+
+    /// Embedding model of the LLM API, like text-embedding-3-small. Embeddings are computed
+    /// only when one is set
+    #[arg(long)]
+    pub llm_embedding_model: Option<String>,
+
+    /// Local Ollama embedding model used once the monthly budget is reached, like
+    /// nomic-embed-text
+    #[arg(long)]
+    pub llm_fallback_embedding_model: Option<String>,
+
+    /// How long (in seconds) LLM answers are reused for the same model and prompt. 0 disables the cache
+    #[arg(long, default_value_t = 7 * 24 * 60 * 60)]
+    pub llm_cache_ttl: u64,
+
+    /// Which ffmpeg to use. The pinned build is a checksum-verified static ffmpeg downloaded into the data dir
+    #[arg(long, value_enum, default_value_t = CliFfmpegSource::System)]
+    pub ffmpeg_source: CliFfmpegSource,
+
+    /// Video encoder for the chunks. Auto probes the hardware encoders at startup and picks the best one
+    #[arg(long, value_enum, default_value_t = CliVideoEncoder::Auto)]
+    pub video_encoder: CliVideoEncoder,
+
+    /// Quality preset of the video encoder, higher keeps small text sharper but makes bigger chunks
+    #[arg(long, value_enum, default_value_t = CliVideoQuality::Medium)]
+    pub video_quality: CliVideoQuality,
+
+    /// Store the frames as images of this format rather than H.264 video. Bigger, but every
+    /// frame is kept at its own fidelity. The api serves frames in the format each client asks
+    /// for either way. The images aren't encrypted, so it can't be used with --encrypt-data
+    #[arg(long, value_enum, conflicts_with_all = ["encrypt_data", "require_encryption"])]
+    pub frame_format: Option<CliFrameFormat>,
+
+    /// Quality of the AVIF and JPEG frames, from 1 to 100
+    #[arg(long, default_value_t = DEFAULT_FRAME_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    pub frame_quality: u8,
+
+    /// Keep no image of the screen, only its OCR text, the window metadata and the
+    /// transcripts. Apps can be recorded text only on their own with "text_only": true in
+    /// app_profiles.json
+    #[arg(long, default_value_t = false, conflicts_with_all = ["frame_format", "enable_live_view"])]
+    pub text_only: bool,
+
+    /// Which windows are OCR'd when frames come in faster than the OCR keeps up, the focused window always is
+    #[arg(long, value_enum, default_value_t = CliOcrPriority::Sample)]
+    pub ocr_priority: CliOcrPriority,
+
+    /// With --ocr-priority sample, the background windows of one frame in this many are OCR'd while the OCR is behind
+    #[arg(long, default_value_t = DEFAULT_BACKGROUND_SAMPLE_EVERY, value_parser = clap::value_parser!(u32).range(1..))]
+    pub background_ocr_every: u32,
+
+    /// After the OCR, look for the arrows and emoji drawn in the windows and the symbols the OCR
+    /// spelled out (-> for →, :) for 🙂), they're added as a last line of the text so searching
+    /// for → or 🙂 finds them
+    #[arg(long, default_value_t = false)]
+    pub recognize_symbols: bool,
+
+    /// Device a local model runs on, like ocr=cuda:1 or embeddings=cpu, instead of the one the
+    /// scheduler picks (can be specified multiple times). The models are ocr, embeddings and
+    /// transcription
+    #[arg(long)]
+    pub model_device: Vec<ModelDeviceOverride>,
+
+    /// Megabytes the local models may take on a gpu, like cuda:0=4096 (can be specified
+    /// multiple times). Models that don't fit go to another gpu or the cpu
+    #[arg(long)]
+    pub accelerator_memory: Vec<AcceleratorMemory>,
+
+    /// Directory the local models are downloaded to, shared by the profiles and users of the
+    /// data directory. Defaults to its models directory
+    #[arg(long)]
+    pub models_dir: Option<String>,
+
+    /// Megabytes the downloaded models may take, the ones used least recently are removed to
+    /// make room. Unlimited if not set
+    #[arg(long)]
+    pub models_quota_mb: Option<u64>,
+
+    /// Serve a live HLS stream of the screen at /live, protected by --live-view-token
+    #[arg(long, default_value_t = false)]
+    pub enable_live_view: bool,
+
+    /// Token required to watch the live view, a random one is generated and printed if not set
+    #[arg(long, env = "SCREENPIPE_LIVE_VIEW_TOKEN")]
+    pub live_view_token: Option<String>,
+
+    /// Add a subtitle track of focused window titles and transcripts to finished video chunks
+    #[arg(long, default_value_t = false)]
+    pub embed_subtitles: bool,
+
+    /// Decode finished chunks in the background, repair damaged ones and hide unreadable ones from searches
+    #[arg(long, default_value_t = false)]
+    pub check_chunk_integrity: bool,
+
+    /// Re-encode video chunks older than this many days to save disk space. Disabled if not set
+    #[arg(long)]
+    pub compact_after_days: Option<u64>,
+
+    /// Codec of the re-encoded chunks, av1 is the smallest but the slowest to encode
+    #[arg(long, value_enum, default_value_t = CliCompactionCodec::H265)]
+    pub compact_codec: CliCompactionCodec,
+
+    /// Resolution of the re-encoded chunks relative to the original, between 0.1 and 1.0
+    #[arg(long, default_value_t = 1.0)]
+    pub compact_scale: f64,
+
+    /// Remove emails, card and social security numbers from OCR text and transcripts before they are stored.
+    /// Same as a redact_text rule for these entities in --redaction-policy
+    #[arg(long, default_value_t = false)]
+    pub redact_pii: bool,
+
+    /// Also remove the names, organizations and places a local NER model finds. Implies --redact-pii
+    #[arg(long, default_value_t = false)]
+    pub pii_ner: bool,
+
+    /// Entity types removed by the NER model
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [CliPiiEntity::Person, CliPiiEntity::Organization, CliPiiEntity::Location])]
+    pub pii_entities: Vec<CliPiiEntity>,
+
+    /// Confidence from 0 to 1 the NER model needs before removing an entity
+    #[arg(long, default_value_t = 0.8)]
+    pub pii_min_confidence: f32,
+
+    /// Hugging Face repo of the NER model, downloaded on first use
+    #[arg(long, default_value = DEFAULT_NER_MODEL)]
+    pub pii_ner_model: String,
+
+    /// Blur card numbers, AWS keys, JWTs and private keys in the stored frames and remove them from the text
+    #[arg(long, default_value_t = false)]
+    pub blur_secrets: bool,
+
+    /// Remove PII and secrets from what these consumers get from the API and the /events stream, on top
+    /// of what is redacted before storage. Uses the NER model when --pii-ner is set. Callers without
+    /// a token that checks out are redacted too, unless nobody is
+    #[arg(long, value_enum, value_delimiter = ',', default_values_t = [CliConsumer::Pipes, CliConsumer::Remote])]
+    pub redact_for: Vec<CliConsumer>,
+
+    /// Bearer tokens of the remote clients. The app sends the token of app_token in the data
+    /// directory and pipes the one of their name in pipe_tokens.json, with the x-screenpipe-pipe-token
+    /// header. Requests without a token that checks out are redacted and get no tools or automation
+    #[arg(long, env = "SCREENPIPE_API_TOKENS", value_delimiter = ',')]
+    pub api_token: Vec<String>,
+
+    /// Token of the app, which passes its own when it starts screenpipe. Saved to app_token in
+    /// the data directory, a new one is made there when none is given
+    #[arg(long, env = "SCREENPIPE_APP_TOKEN", hide_env_values = true)]
+    pub app_token: Option<String>,
+
+    /// Json file of rules matching apps, window titles, urls, entities or keywords to drop frames,
+    /// redact or blur text, blur windows or skip audio before anything is stored
+    #[arg(long)]
+    pub redaction_policy: Option<String>,
+
+    /// Encrypt finished video and audio chunks with keys kept in the OS keychain, and the database
+    /// too in builds with the sqlcipher feature. Encrypted chunks are not compacted
+    #[arg(long, default_value_t = false)]
+    pub encrypt_data: bool,
+
+    /// Refuse to start if the keychain can't be used or the database would be stored in plaintext.
+    /// Implies --encrypt-data
+    #[arg(long, default_value_t = false)]
+    pub require_encryption: bool,
+
+    /// Signed policy of a shared machine: capture is limited to the apps it allows, shows an indicator
+    /// and waits for the user's consent. A policy in the system location, /etc/screenpipe/compliance.json
+    /// on linux, is always used and screenpipe refuses to start with another one
+    #[arg(long)]
+    pub compliance_policy: Option<String>,
+
+    /// Base64 ed25519 key --compliance-policy is signed with, compliance.pub next to the policy if not set.
+    /// Policies in the system location are always checked against the compliance.pub next to them and
+    /// can't be given another key
+    #[arg(long, env = "SCREENPIPE_COMPLIANCE_PUBLIC_KEY")]
+    pub compliance_public_key: Option<String>,
+
+    /// Worker doing OCR and transcription for the remote engines, e.g. wss://desktop.local:3035,
+    /// run one with screenpipe-worker on a more powerful machine
+    #[arg(long)]
+    pub remote_worker: Option<String>,
+
+    /// Token the remote worker was started with
+    #[arg(long, env = "SCREENPIPE_REMOTE_WORKER_TOKEN")]
+    pub remote_worker_token: Option<String>,
+
+    /// PEM certificate to trust for the remote worker, for workers with a self-signed certificate
+    #[arg(long)]
+    pub remote_worker_ca: Option<String>,
+
+    /// Obsidian or Logseq vault to write daily notes into: the activity digest, meeting summaries
+    /// and what the extraction rules found with the frames it was seen on
+    #[arg(long)]
+    pub vault_dir: Option<String>,
+
+    #[arg(long, value_enum, default_value_t = CliVaultFormat::Obsidian)]
+    pub vault_format: CliVaultFormat,
+
+    /// Minutes between two exports of today's note, POST /vault/export writes it at once
+    #[arg(long, default_value_t = 60)]
+    pub vault_export_interval: u64,
+
+    /// JSON file of the destinations emailed the daily digest and the watch alerts, each with
+    /// its SMTP server: {"destinations": [{"name": "me", "smtp_host": "smtp.example.com",
+    /// "username": "me@example.com", "password_env": "SMTP_PASSWORD", "from": "me@example.com",
+    /// "to": ["me@example.com"], "digest_hour": 18, "alerts": true, "watches": ["mentions"]}]}
+    #[arg(long)]
+    pub email_config: Option<String>,
+
+    /// iCalendar feed labeling meetings with the event they overlap (can be specified multiple times):
+    /// the secret iCal address of a Google calendar, a published Outlook calendar, a CalDAV
+    /// calendar export url or a local .ics file
+    #[arg(long)]
+    pub calendar: Vec<String>,
+
+    /// Minutes between two reads of the calendars
+    #[arg(long, default_value_t = 15)]
+    pub calendar_sync_interval: u64,
+
+    /// Directory where saved files are recorded with the app and window focused at the time,
+    /// recursively (can be specified multiple times)
+    #[arg(long)]
+    pub watch_dir: Vec<String>,
+
+    /// Don't record the focus and title changes of the windows between OCR frames, served on
+    /// /windows/history
+    #[arg(long, default_value_t = false)]
+    pub disable_window_history: bool,
+
+    /// Don't keep the slides of the presentations shown full screen as decks, served on
+    /// /slides/decks and exportable as PDF
+    #[arg(long, default_value_t = false)]
+    pub disable_slide_decks: bool,
+
+    /// Import the commands of the zsh, bash (with HISTTIMEFORMAT) and fish history files as
+    /// they are written. The hooks of --print-shell-hook send them with their directory and
+    /// exit code either way
+    #[arg(long, default_value_t = false)]
+    pub enable_shell_history: bool,
+
+    /// Print the hooks sending the commands of the shell as they finish to the API socket, to
+    /// `eval "$(screenpipe --print-shell-hook zsh)"` in the shell's rc file with the same
+    /// --data-dir, --profile and --api-socket as the server, and exit
+    #[arg(long, value_enum)]
+    pub print_shell_hook: Option<CliShell>,
+
+    /// Git repository whose commits, branch switches, merges and resets are recorded from its
+    /// reflog, served on /git/events (can be specified multiple times)
+    #[arg(long)]
+    pub git_repo: Vec<String>,
+
+    /// Record the notifications the OS shows, served on /notifications: from the session bus
+    /// on Linux (needs dbus-monitor), from the notification center's database on macOS (needs
+    /// Full Disk Access) and from the action center's on Windows
+    #[arg(long, default_value_t = false)]
+    pub capture_notifications: bool,
+
+    /// Empty directory the timeline is mounted on read-only, as <year>/<month>/<day>/<time>_<app>.png
+    /// files and <time>_transcript.txt transcriptions. Needs the timeline-fs feature and FUSE
+    /// (fusermount on Linux, macFUSE on macOS), Windows isn't supported
+    #[arg(long)]
+    pub mount_timeline: Option<String>,
+
+    /// JSON file of the pipes allowed to click and type through POST /automation/actions, with
+    /// the actions and the apps each one may act on: {"grants": [{"pipe": "dismiss-dialog",
+    /// "actions": ["click", "key"], "apps": ["Finder"], "max_per_minute": 10}]}. Needs the
+    /// automation feature, every action asked for is recorded on /automation/actions
+    #[arg(long)]
+    pub automation_grants: Option<String>,
+
+    /// Tools the model can call while answering the app's /ask and /chat: search_history,
+    /// get_frame, create_tag and send_notification. All of them if not set. Callers without
+    /// the app's or a pipe's token get none
+    #[arg(long, value_delimiter = ',')]
+    pub llm_tools: Option<Vec<String>>,
+
+    /// JSON file of the tools each pipe's chats can call, of the ones --llm-tools enables:
+    /// {"grants": [{"pipe": "invoice-filer", "tools": ["search_history", "create_tag"]}]}.
+    /// Pipes without a grant chat without tools
+    #[arg(long)]
+    pub llm_tool_grants: Option<String>,
+
+    /// JSON file of an encrypted backup of the database, and of the recordings in "media", to
+    /// S3, Backblaze B2 (its S3 api) or WebDAV: {"destination": {"kind": "s3", "endpoint":
+    /// "https://s3.us-west-004.backblazeb2.com", "region": "us-west-004", "bucket": "backups",
+    /// "access_key_id_env": "B2_KEY_ID", "secret_access_key_env": "B2_KEY"}, "passphrase_env":
+    /// "SCREENPIPE_BACKUP_PASSPHRASE", "media": ["audio"], "max_upload_kbps": 2000}. Uploads
+    /// only run on AC power while the machine is idle and resume where they stopped, restore
+    /// them with screenpipe-backup-restore
+    #[arg(long)]
+    pub backup_config: Option<String>,
+
+    /// Record the frames and windows the monitor captures for --record-replay-secs to a bundle
+    /// in this directory, before any diffing or OCR. screenpipe-replay feeds them back through
+    /// the pipeline, attach the bundle to bug reports about the OCR or the frame diffing
+    #[arg(long)]
+    pub record_replay: Option<String>,
+
+    /// How long --record-replay records
+    #[arg(long, default_value_t = 30)]
+    pub record_replay_secs: u64,
+
+    /// Show an always on top indicator while the screen is captured, red while capturing and
+    /// grey while paused, clicking it pauses or resumes capture. Needs the indicator feature
+    #[arg(long, value_enum)]
+    pub recording_indicator: Option<CliIndicatorStyle>,
+
+    /// For machines shared by several users, each one running their own screenpipe: the data
+    /// of each user is kept apart (in users/<name> of --data-dir), the server only listens on
+    /// localhost, takes the next free port when another user's server has this one and only
+    /// answers requests with the user's token (the port and token are in instance.json and
+    /// user_token of the user's data directory)
+    #[arg(long, default_value_t = false)]
+    pub multi_user: bool,
+
+    /// Unix socket (named pipe on Windows) the API is also served on, to the processes of the
+    /// same user only. Defaults to screenpipe.sock in the data directory (\\.\pipe\screenpipe-<user>
+    /// on Windows)
+    #[arg(long)]
+    pub api_socket: Option<String>,
+
+    /// Don't open a network port, the API is only served on --api-socket
+    #[arg(long, default_value_t = false)]
+    pub disable_tcp_api: bool,
+
+    /// Serve the API over https, for pipes running on https pages. Without --tls-cert, a
+    /// certificate for localhost is generated in the data directory, the authority it prints
+    /// has to be trusted once for browsers to accept it
+    #[arg(long, default_value_t = false)]
+    pub tls: bool,
+
+    /// Pem certificate chain for --tls, instead of the generated one
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<String>,
+
+    /// Pem private key of --tls-cert
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<String>,
+}
+
+impl Cli {
+    pub fn llm_config(&self) -> LlmConfig {
+        LlmConfig {
+            api_url: self.llm_api_url.clone(),
+            api_key: self.llm_api_key.clone(),
+            model: self.llm_model.clone(),
+        }
+    }
+
+    pub fn llm_cache_ttl(&self) -> Option<Duration> {
+        (self.llm_cache_ttl > 0).then(|| Duration::from_secs(self.llm_cache_ttl))
+    }
+
+    pub fn compaction_options(&self) -> Option<CompactionOptions> {
+        self.compact_after_days.map(|days| CompactionOptions {
+            older_than: chrono::Duration::days(days as i64),
+            codec: self.compact_codec.clone().into(),
+            scale: self.compact_scale.clamp(0.1, 1.0),
+        })
+    }
+
+    pub fn ocr_priority(&self) -> OcrPriority {
+        match self.ocr_priority {
+            CliOcrPriority::All => OcrPriority::AllWindows,
+            CliOcrPriority::Focused => OcrPriority::FocusedOnly,
+            CliOcrPriority::Sample => OcrPriority::SampleBackground(self.background_ocr_every),
+        }
+    }
+
+    pub fn llm_fallback_config(&self) -> Option<LlmConfig> {
+        self.llm_fallback_model.as_ref().map(|model| LlmConfig {
+            model: model.clone(),
+            ..LlmConfig::default()
+        })
+    }
+}
@@ -0,0 +1,404 @@
+use crate::timelapse::{decode_chunk, plan_timelapse, read_frame_png, TimelapseStatus};
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba, RgbaImage};
+use log::{error, info};
+use screenpipe_core::{
+    find_pii, find_secrets, Container, EncoderQuality, FfmpegCommand, FfmpegInput, FfmpegOutput,
+    PiiMatch, VideoCodec, VideoEncoder,
+};
+use screenpipe_vision::{perform_ocr_tesseract_words, OcrWord};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Recorded frames are repeated to this rate so players seek smoothly
+pub const CLIP_FPS: f64 = 5.0;
+const MAX_CLIP_DURATION: ChronoDuration = ChronoDuration::hours(2);
+/// Pixels added around each redacted word so the edges of letters don't show
+const BOX_PADDING: u32 = 4;
+/// How much a blurred region is shrunk before being scaled back, the text is gone at this size
+const BLUR_FACTOR: u32 = 12;
+
+/// Clips go through the same states as timelapses
+pub type ClipStatus = TimelapseStatus;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionStyle {
+    #[default]
+    Black,
+    Blur,
+}
+
+/// Windows whose app and title contain these, case insensitive. Missing fields match anything
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WindowMatch {
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+impl WindowMatch {
+    pub fn matches(&self, app_name: &str, window_name: &str) -> bool {
+        let contains = |pattern: &Option<String>, value: &str| {
+            pattern
+                .as_ref()
+                .is_none_or(|p| value.to_lowercase().contains(&p.to_lowercase()))
+        };
+        contains(&self.app_name, app_name) && contains(&self.window_name, window_name)
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClipOptions {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Cover text that looks like emails, card or social security numbers
+    #[serde(default)]
+    pub redact_pii: bool,
+    /// Window positions aren't recorded, frames showing one of these windows are covered entirely
+    #[serde(default)]
+    pub redact_windows: Vec<WindowMatch>,
+    #[serde(default)]
+    pub redaction: RedactionStyle,
+}
+
+impl ClipOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.end_time <= self.start_time {
+            anyhow::bail!("end_time must be after start_time");
+        }
+        if self.end_time - self.start_time > MAX_CLIP_DURATION {
+            anyhow::bail!(
+                "clips can't be longer than {} hours",
+                MAX_CLIP_DURATION.num_hours()
+            );
+        }
+        if self
+            .redact_windows
+            .iter()
+            .any(|w| w.app_name.is_none() && w.window_name.is_none())
+        {
+            anyhow::bail!("redact_windows entries need an app_name or a window_name");
+        }
+        Ok(())
+    }
+
+    pub fn is_redacted(&self) -> bool {
+        self.redact_pii || !self.redact_windows.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClipJob {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub redacted: bool,
+    pub status: ClipStatus,
+    pub output_path: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ClipSourceFrame {
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+    pub offset_index: i64,
+    /// App and title of every window recognized on the frame
+    pub windows: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RedactionBox {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl RedactionBox {
+    fn around(words: &[&OcrWord]) -> Option<Self> {
+        let left = words.iter().map(|w| w.left).min()?.max(0) as u32;
+        let top = words.iter().map(|w| w.top).min()?.max(0) as u32;
+        let right = words.iter().map(|w| w.left + w.width).max()?.max(0) as u32;
+        let bottom = words.iter().map(|w| w.top + w.height).max()?.max(0) as u32;
+        let x = left.saturating_sub(BOX_PADDING);
+        let y = top.saturating_sub(BOX_PADDING);
+        Some(RedactionBox {
+            x,
+            y,
+            width: right + BOX_PADDING - x,
+            height: bottom + BOX_PADDING - y,
+        })
+    }
+}
+
+/// Boxes around the words making up PII. Numbers are often split over several words,
+/// so patterns are matched on whole lines
+pub fn pii_boxes(words: &[OcrWord]) -> Vec<RedactionBox> {
+    text_boxes(words, |line| {
+        let mut matches = find_pii(line);
+        matches.extend(find_secrets(line));
+        matches
+    })
+}
+
+/// Boxes around the words covered by what `find` matches in each line
+pub fn text_boxes(words: &[OcrWord], find: impl Fn(&str) -> Vec<PiiMatch>) -> Vec<RedactionBox> {
+    let mut boxes = Vec::new();
+    for line in words.chunk_by(|a, b| a.line == b.line) {
+        let mut text = String::new();
+        let mut spans = Vec::with_capacity(line.len());
+        for word in line {
+            if !text.is_empty() {
+                text.push(' ');
+            }
+            spans.push((text.len(), text.len() + word.text.len()));
+            text.push_str(&word.text);
+        }
+        for found in find(&text) {
+            let covered: Vec<&OcrWord> = line
+                .iter()
+                .zip(&spans)
+                .filter(|(_, (start, end))| *start < found.end && *end > found.start)
+                .map(|(word, _)| word)
+                .collect();
+            // detectors often agree on the same words
+            if let Some(redaction) = RedactionBox::around(&covered) {
+                if !boxes.contains(&redaction) {
+                    boxes.push(redaction);
+                }
+            }
+        }
+    }
+    boxes
+}
+
+/// Burns the boxes into the image, boxes reaching outside of it are cut
+pub fn apply_redaction(
+    image: &DynamicImage,
+    boxes: &[RedactionBox],
+    style: RedactionStyle,
+) -> RgbaImage {
+    let mut output = image.to_rgba8();
+    let (image_width, image_height) = image.dimensions();
+    for redaction in boxes {
+        if redaction.x >= image_width || redaction.y >= image_height {
+            continue;
+        }
+        let width = redaction.width.min(image_width - redaction.x);
+        let height = redaction.height.min(image_height - redaction.y);
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let patch = match style {
+            RedactionStyle::Black => RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255])),
+            RedactionStyle::Blur => {
+                let region = image.crop_imm(redaction.x, redaction.y, width, height);
+                let small = region.resize_exact(
+                    (width / BLUR_FACTOR).max(1),
+                    (height / BLUR_FACTOR).max(1),
+                    FilterType::Triangle,
+                );
+                small
+                    .resize_exact(width, height, FilterType::Triangle)
+                    .to_rgba8()
+            }
+        };
+        image::imageops::replace(&mut output, &patch, redaction.x as i64, redaction.y as i64);
+    }
+    output
+}
+
+fn whole_frame(image: &DynamicImage) -> RedactionBox {
+    RedactionBox {
+        x: 0,
+        y: 0,
+        width: image.width(),
+        height: image.height(),
+    }
+}
+
+/// Redacts one recorded frame and returns it as png
+fn redact_frame(png: &[u8], frame: &ClipSourceFrame, options: &ClipOptions) -> Result<Vec<u8>> {
+    let image = image::load_from_memory_with_format(png, ImageFormat::Png)?;
+    let mut boxes = Vec::new();
+    if options.redact_windows.iter().any(|pattern| {
+        frame
+            .windows
+            .iter()
+            .any(|(app, window)| pattern.matches(app, window))
+    }) {
+        boxes.push(whole_frame(&image));
+    } else if options.redact_pii {
+        // the stored OCR has no word positions, the frame is read again
+        boxes.extend(pii_boxes(&perform_ocr_tesseract_words(&image)?));
+    }
+    if boxes.is_empty() {
+        return Ok(png.to_vec());
+    }
+
+    let redacted = DynamicImage::ImageRgba8(apply_redaction(&image, &boxes, options.redaction));
+    let mut buffer = Vec::new();
+    redacted.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)?;
+    Ok(buffer)
+}
+
+/// Cuts the recorded frames between the two times into an mp4 at `output`, redacted as asked
+pub async fn render_clip(db: &DatabaseManager, options: &ClipOptions, output: &Path) -> Result<()> {
+    options.validate()?;
+    let frames = db
+        .get_clip_frames(options.start_time, options.end_time)
+        .await?;
+    if frames.is_empty() {
+        anyhow::bail!(
+            "no frames recorded between {} and {}",
+            options.start_time,
+            options.end_time
+        );
+    }
+
+    let timestamps: Vec<DateTime<Utc>> = frames.iter().map(|f| f.timestamp).collect();
+    let plan = plan_timelapse(&timestamps, 1.0, CLIP_FPS);
+    info!(
+        "Rendering clip of {} frames, redacted: {}",
+        frames.len(),
+        options.is_redacted()
+    );
+
+    let work_dir = output.with_extension("work");
+    tokio::fs::create_dir_all(&work_dir).await?;
+    let result = encode_clip(&frames, &plan, options, output, &work_dir).await;
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    if result.is_err() {
+        // a partial clip could show frames that weren't redacted yet
+        let _ = tokio::fs::remove_file(output).await;
+    }
+    result
+}
+
+async fn encode_clip(
+    frames: &[ClipSourceFrame],
+    plan: &[usize],
+    options: &ClipOptions,
+    output: &Path,
+    work_dir: &Path,
+) -> Result<()> {
+    let command = FfmpegCommand::new().overwrite().input(
+        FfmpegInput::pipe()
+            .format(Container::Image2Pipe)
+            .video_codec(VideoCodec::Png)
+            .frame_rate(CLIP_FPS),
+    );
+    let mut process = VideoEncoder::Software
+        .encode_to(
+            command,
+            FfmpegOutput::file(&output.to_string_lossy())
+                .video_filter("scale=trunc(iw/2)*2:trunc(ih/2)*2"),
+            EncoderQuality::High,
+        )
+        .spawn("clip")?;
+
+    let chunk_dir = work_dir.join("chunk");
+    let mut decoded_chunk: Option<&str> = None;
+    let mut last: Option<(usize, Vec<u8>)> = None;
+    for source in plan {
+        let frame = &frames[*source];
+        if last.as_ref().map(|(index, _)| index) != Some(source) {
+            if decoded_chunk != Some(frame.file_path.as_str()) {
+                if let Err(e) = decode_chunk(&frame.file_path, &chunk_dir).await {
+                    error!("{}", e);
+                }
+                decoded_chunk = Some(frame.file_path.as_str());
+            }
+            match read_frame_png(&frame.file_path, frame.offset_index, &chunk_dir).await {
+                Ok(png) => {
+                    let (frame, options) = (frame.clone(), options.clone());
+                    let png = if options.is_redacted() {
+                        tokio::task::spawn_blocking(move || redact_frame(&png, &frame, &options))
+                            .await??
+                    } else {
+                        png
+                    };
+                    last = Some((*source, png));
+                }
+                Err(e) => error!(
+                    "Frame {} of {} is missing: {}",
+                    frame.offset_index, frame.file_path, e
+                ),
+            }
+        }
+        if let Some((_, png)) = &last {
+            process.write(png).await?;
+        }
+    }
+
+    let status = process.finish().await?;
+    if !status.success() {
+        anyhow::bail!("ffmpeg failed to render the clip ({})", status);
+    }
+    Ok(())
+}
+
+/// Runs clip jobs in the background, their progress is kept in the database
+pub struct ClipRenderer {
+    db: Arc<DatabaseManager>,
+    output_dir: PathBuf,
+}
+
+impl ClipRenderer {
+    pub fn new(db: Arc<DatabaseManager>, output_dir: PathBuf) -> Self {
+        ClipRenderer { db, output_dir }
+    }
+
+    pub async fn start(&self, options: ClipOptions) -> Result<ClipJob> {
+        options.validate()?;
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        let output = self.output_dir.join(format!(
+            "clip_{}_{}.mp4",
+            options.start_time.format("%Y-%m-%d_%H-%M-%S"),
+            Utc::now().timestamp_millis()
+        ));
+
+        let id = self
+            .db
+            .insert_clip_job(
+                options.start_time,
+                options.end_time,
+                options.is_redacted(),
+                &output.to_string_lossy(),
+            )
+            .await?;
+
+        let db = Arc::clone(&self.db);
+        tokio::spawn(async move {
+            let (status, error) = match render_clip(&db, &options, &output).await {
+                Ok(()) => {
+                    info!("Clip {} rendered to {}", id, output.display());
+                    (ClipStatus::Done, None)
+                }
+                Err(e) => {
+                    error!("Clip {} failed: {}", id, e);
+                    (ClipStatus::Failed, Some(e.to_string()))
+                }
+            };
+            if let Err(e) = db.finish_clip_job(id, status, error.as_deref()).await {
+                error!("Failed to update clip job {}: {}", id, e);
+            }
+        });
+
+        self.db
+            .get_clip_job(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("clip job {} not found", id))
+    }
+
+    pub async fn get_job(&self, id: i64) -> Result<Option<ClipJob>> {
+        Ok(self.db.get_clip_job(id).await?)
+    }
+}
@@ -0,0 +1,193 @@
+use crate::power::background_jobs_paused;
+use crate::integrity::decode_errors;
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use screenpipe_core::{
+    emit_event, Container, FfmpegCommand, FfmpegInput, FfmpegOutput, VideoCodec,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Re-encoding is slow, a few chunks per run keeps the cpu free for recording
+const CHUNKS_PER_RUN: u32 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CompactionCodec {
+    H264,
+    H265,
+    Av1,
+}
+
+impl CompactionCodec {
+    fn video_codec(&self) -> VideoCodec {
+        match self {
+            CompactionCodec::H264 => VideoCodec::Libx264 {
+                preset: "slow".to_string(),
+                crf: 32,
+            },
+            CompactionCodec::H265 => VideoCodec::Other("libx265".to_string()),
+            CompactionCodec::Av1 => VideoCodec::Other("libsvtav1".to_string()),
+        }
+    }
+
+    fn apply(&self, output: FfmpegOutput) -> FfmpegOutput {
+        let output = output.video_codec(self.video_codec());
+        match self {
+            CompactionCodec::H264 => output,
+            CompactionCodec::H265 => output
+                .arg("-preset", "medium")
+                .arg("-crf", "32")
+                .arg("-tag:v", "hvc1"),
+            CompactionCodec::Av1 => output.arg("-preset", "8").arg("-crf", "40"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CompactionOptions {
+    /// Chunks whose last frame is older than this are re-encoded
+    pub older_than: ChronoDuration,
+    pub codec: CompactionCodec,
+    /// Resolution of the re-encoded chunk relative to the original, 1.0 keeps it
+    pub scale: f64,
+}
+
+/// Where the re-encoded chunk is written, next to the original
+pub fn compacted_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    path.with_file_name(format!("{}_compact.mp4", stem))
+}
+
+/// Every frame is kept so the stored offsets still point at the same images
+pub fn compact_command(input: &str, output: &str, options: &CompactionOptions) -> FfmpegCommand {
+    let mut output = FfmpegOutput::file(output)
+        .arg("-map", "0:v")
+        .arg("-map", "0:s?")
+        .arg("-c:s", "copy")
+        .arg("-fps_mode", "passthrough");
+    if options.scale < 1.0 {
+        // most encoders need even dimensions
+        output = output.video_filter(&format!(
+            "scale=trunc(iw*{0}/2)*2:trunc(ih*{0}/2)*2",
+            options.scale
+        ));
+    }
+    let output = options
+        .codec
+        .apply(output)
+        .pixel_format("yuv420p")
+        .format(Container::Mp4);
+    FfmpegCommand::new()
+        .overwrite()
+        .input(FfmpegInput::file(input))
+        .output(output)
+}
+
+/// Re-encodes one chunk, then points the database at the new file before removing the old one,
+/// so a crash at any point leaves a readable chunk behind. Returns false if the original was kept
+pub async fn compact_chunk(
+    db: &DatabaseManager,
+    chunk_id: i64,
+    file_path: &str,
+    options: &CompactionOptions,
+) -> Result<bool> {
+    let compacted = compacted_path(file_path);
+    let compacted_str = compacted.to_string_lossy().to_string();
+
+    let output = compact_command(file_path, &compacted_str, options)
+        .run()
+        .await?;
+    let problem = if !output.status.success() {
+        Some(format!(
+            "failed to re-encode {}: {}",
+            file_path,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    } else {
+        let errors = decode_errors(&compacted_str).await?;
+        (!errors.is_empty())
+            .then(|| format!("re-encoded {} is not readable: {}", file_path, errors))
+    };
+    // the original is untouched, don't retry it every run
+    if let Some(problem) = problem {
+        warn!("{}", problem);
+        let _ = tokio::fs::remove_file(&compacted).await;
+        db.mark_video_chunk_compacted(chunk_id, file_path, 1.0)
+            .await?;
+        return Ok(false);
+    }
+
+    let original_size = tokio::fs::metadata(file_path).await?.len();
+    let compacted_size = tokio::fs::metadata(&compacted).await?.len();
+    if compacted_size >= original_size {
+        // already small enough, keep the better looking one
+        debug!("Re-encoding did not shrink {}, keeping it", file_path);
+        tokio::fs::remove_file(&compacted).await?;
+        db.mark_video_chunk_compacted(chunk_id, file_path, 1.0)
+            .await?;
+        return Ok(false);
+    }
+
+    db.mark_video_chunk_compacted(chunk_id, &compacted_str, options.scale.min(1.0))
+        .await?;
+    if let Err(e) = tokio::fs::remove_file(file_path).await {
+        warn!("Failed to remove {} after compaction: {}", file_path, e);
+    }
+    info!(
+        "Compacted {} from {} to {} bytes",
+        file_path, original_size, compacted_size
+    );
+    emit_event(
+        "storage.compacted",
+        json!({"id": chunk_id, "file_path": compacted_str, "original_bytes": original_size, "compacted_bytes": compacted_size}),
+    );
+    Ok(true)
+}
+
+async fn compact_old_chunks(db: &DatabaseManager, options: &CompactionOptions) -> Result<()> {
+    let chunks = db
+        .get_video_chunks_to_compact(Utc::now() - options.older_than, CHUNKS_PER_RUN)
+        .await?;
+    for (chunk_id, file_path) in chunks {
+        // frames stored as images are left as they are
+        if !file_path.ends_with(".mp4") || !Path::new(&file_path).is_file() {
+            db.mark_video_chunk_compacted(chunk_id, &file_path, 1.0)
+                .await?;
+            continue;
+        }
+        compact_chunk(db, chunk_id, &file_path, options).await?;
+    }
+    Ok(())
+}
+
+/// Periodically re-encodes old chunks to a smaller codec and resolution so long histories
+/// take less disk space
+pub async fn start_storage_compactor(
+    db: Arc<DatabaseManager>,
+    options: CompactionOptions,
+    interval: Duration,
+) {
+    info!(
+        "Storage compactor started, re-encoding chunks older than {} days with {:?}",
+        options.older_than.num_days(),
+        options.codec
+    );
+    loop {
+        if !background_jobs_paused() {
+            if let Err(e) = compact_old_chunks(&db, &options).await {
+                error!("Failed to compact old chunks: {}", e);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
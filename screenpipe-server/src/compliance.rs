@@ -0,0 +1,241 @@
+use crate::{CapturePause, DatabaseManager, PauseStatus};
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::{DateTime, Utc};
+use ed25519_dalek::{Signature, VerifyingKey};
+use log::info;
+use screenpipe_core::emit_event;
+use screenpipe_vision::set_allowed_apps;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Name of the admin's public key file, next to the policy when not given
+pub const PUBLIC_KEY_FILE: &str = "compliance.pub";
+/// Appended to the policy path, the detached signature of the file
+pub const SIGNATURE_SUFFIX: &str = ".sig";
+
+/// Where admins deploy the policy, only they can write there. Compliance mode is on
+/// whenever a policy is found
+pub fn default_policy_path() -> PathBuf {
+    #[cfg(target_os = "macos")]
+    let dir = PathBuf::from("/Library/Application Support/screenpipe");
+    #[cfg(target_os = "windows")]
+    let dir = PathBuf::from(
+        std::env::var("PROGRAMDATA").unwrap_or_else(|_| "C:\\ProgramData".to_string()),
+    )
+    .join("screenpipe");
+    #[cfg(not(any(target_os = "macos", target_os = "windows")))]
+    let dir = PathBuf::from("/etc/screenpipe");
+    dir.join("compliance.json")
+}
+
+/// Rules of the shared machine, signed by its admin
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CompliancePolicy {
+    /// Shown in the capture indicator and the consent prompt
+    pub organization: String,
+    /// What the user agrees to at login
+    pub consent_text: String,
+    /// Only the windows of these apps are captured, case insensitive
+    pub allowed_apps: Vec<String>,
+    /// Audio isn't tied to an app and stays off unless allowed
+    #[serde(default)]
+    pub allow_audio: bool,
+}
+
+/// A user's answer to the consent prompt, these are never removed
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsentRecord {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub user: String,
+    pub organization: String,
+    /// Of the policy file the user was shown
+    pub policy_hash: String,
+    pub accepted: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ComplianceStatus {
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub policy: Option<CompliancePolicy>,
+    pub policy_hash: Option<String>,
+    pub consent_given: bool,
+    pub consented_at: Option<DateTime<Utc>>,
+}
+
+impl ComplianceStatus {
+    pub fn disabled() -> Self {
+        ComplianceStatus {
+            enabled: false,
+            policy: None,
+            policy_hash: None,
+            consent_given: false,
+            consented_at: None,
+        }
+    }
+}
+
+fn decode_base64_file(path: &Path) -> Result<Vec<u8>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    STANDARD
+        .decode(content.trim())
+        .with_context(|| format!("{} is not base64", path.display()))
+}
+
+/// Checks the ed25519 signature in `<path>.sig` against the base64 public key, the policy
+/// is only used if it was signed by the admin
+pub fn load_compliance_policy(
+    path: &Path,
+    public_key: Option<&str>,
+) -> Result<(CompliancePolicy, String)> {
+    let policy_file = std::fs::read(path)
+        .with_context(|| format!("failed to read compliance policy {}", path.display()))?;
+    let public_key = match public_key {
+        Some(public_key) => STANDARD
+            .decode(public_key.trim())
+            .context("compliance public key is not base64")?,
+        None => decode_base64_file(&path.with_file_name(PUBLIC_KEY_FILE))?,
+    };
+    let public_key: [u8; 32] = public_key
+        .try_into()
+        .map_err(|_| anyhow::anyhow!("compliance public key is not an ed25519 key"))?;
+    let signature_path = PathBuf::from(format!("{}{}", path.display(), SIGNATURE_SUFFIX));
+    let signature = Signature::from_slice(&decode_base64_file(&signature_path)?)
+        .context("compliance policy signature is not an ed25519 signature")?;
+    VerifyingKey::from_bytes(&public_key)?
+        .verify_strict(&policy_file, &signature)
+        .context(
+            "compliance policy signature doesn't match, it was changed or signed with another key",
+        )?;
+
+    let policy: CompliancePolicy = serde_json::from_slice(&policy_file)?;
+    if policy.allowed_apps.is_empty() {
+        anyhow::bail!("compliance policy doesn't allow any app");
+    }
+    let hash = Sha256::digest(&policy_file)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+    Ok((policy, hash))
+}
+
+/// The policy to load and the key to check it with. A policy deployed by an admin at
+/// `system_policy` can't be turned off or replaced from the command line: it's checked
+/// against its own key and another policy or key is refused
+pub fn compliance_policy_to_load<'a>(
+    system_policy: &Path,
+    policy: Option<&str>,
+    public_key: Option<&'a str>,
+) -> Result<Option<(PathBuf, Option<&'a str>)>> {
+    if !system_policy.exists() {
+        return Ok(policy.map(|path| (PathBuf::from(path), public_key)));
+    }
+    let replaced = policy.is_some_and(|path| {
+        std::fs::canonicalize(path).ok() != std::fs::canonicalize(system_policy).ok()
+    });
+    if replaced || public_key.is_some() {
+        anyhow::bail!(
+            "the compliance policy {} is deployed on this machine, --compliance-policy and --compliance-public-key can't replace it",
+            system_policy.display()
+        );
+    }
+    Ok(Some((system_policy.to_path_buf(), None)))
+}
+
+fn current_user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "default".to_string())
+}
+
+/// Capture of a shared machine, restricted to the policy's apps and held until the user
+/// consents. Consent is asked again every time screenpipe starts, which is at login
+pub struct ComplianceMode {
+    pub policy: CompliancePolicy,
+    pub policy_hash: String,
+    user: String,
+    consented_at: Mutex<Option<DateTime<Utc>>>,
+    capture_pause: Arc<CapturePause>,
+}
+
+impl ComplianceMode {
+    /// Holds capture until consent is given
+    pub fn new(
+        policy: CompliancePolicy,
+        policy_hash: String,
+        capture_pause: Arc<CapturePause>,
+    ) -> Self {
+        set_allowed_apps(Some(policy.allowed_apps.clone()));
+        capture_pause.require_consent();
+        info!(
+            "Compliance mode of {}, capturing {} once the user consents",
+            policy.organization,
+            policy.allowed_apps.join(", ")
+        );
+        ComplianceMode {
+            policy,
+            policy_hash,
+            user: current_user(),
+            consented_at: Mutex::new(None),
+            capture_pause,
+        }
+    }
+
+    pub fn status(&self) -> ComplianceStatus {
+        let consented_at = *self.consented_at.lock().unwrap();
+        ComplianceStatus {
+            enabled: true,
+            policy: Some(self.policy.clone()),
+            policy_hash: Some(self.policy_hash.clone()),
+            consent_given: consented_at.is_some(),
+            consented_at,
+        }
+    }
+
+    /// Capture starts once accepted, declining stops it again
+    pub async fn record_consent(
+        &self,
+        db: &DatabaseManager,
+        accepted: bool,
+    ) -> Result<PauseStatus> {
+        let record = ConsentRecord {
+            id: 0,
+            timestamp: Utc::now(),
+            user: self.user.clone(),
+            organization: self.policy.organization.clone(),
+            policy_hash: self.policy_hash.clone(),
+            accepted,
+        };
+        db.insert_consent_record(&record).await?;
+
+        let status = if accepted {
+            *self.consented_at.lock().unwrap() = Some(record.timestamp);
+            self.capture_pause.give_consent()
+        } else {
+            *self.consented_at.lock().unwrap() = None;
+            self.capture_pause.require_consent()
+        };
+        info!(
+            "{} {} the capture policy of {}",
+            self.user,
+            if accepted { "accepted" } else { "declined" },
+            self.policy.organization
+        );
+        emit_event(
+            if accepted {
+                "compliance.consent_given"
+            } else {
+                "compliance.consent_declined"
+            },
+            json!({"user": self.user, "policy_hash": self.policy_hash}),
+        );
+        Ok(status)
+    }
+}
@@ -0,0 +1,138 @@
+use crate::credentials::{ApiCredentials, Caller};
+use crate::AppState;
+use axum::{
+    body::{to_bytes, Body},
+    extract::{Request, State},
+    http::{header, HeaderMap},
+    middleware::Next,
+    response::Response,
+};
+use log::error;
+use screenpipe_core::PiiRedactor;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+/// Bigger json answers are dropped rather than sent without redaction
+const MAX_REDACTED_BODY: usize = 64 * 1024 * 1024;
+
+/// Who reads the API, from the credentials the server checked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Consumer {
+    /// The app, with its token, and the processes of the user on the API socket
+    Local,
+    /// Pipes with their token
+    Pipe,
+    /// Clients with one of the --api-token tokens
+    Remote,
+    /// Anything else, it could be any of the others
+    Unknown,
+}
+
+impl Consumer {
+    pub fn of_caller(caller: &Caller) -> Self {
+        match caller {
+            Caller::App => Consumer::Local,
+            Caller::Pipe(_) => Consumer::Pipe,
+            Caller::Remote => Consumer::Remote,
+            Caller::Unknown => Consumer::Unknown,
+        }
+    }
+
+    pub fn of_request(credentials: &ApiCredentials, headers: &HeaderMap) -> Self {
+        Consumer::of_caller(&credentials.caller(headers))
+    }
+}
+
+/// PII and secrets removed from what some consumers get from the API and the event
+/// stream, whatever is stored. Frames and other media are sent as they are
+pub struct ConsumerRedaction {
+    consumers: Vec<Consumer>,
+    detector: Arc<PiiRedactor>,
+}
+
+impl ConsumerRedaction {
+    pub fn new(consumers: Vec<Consumer>, detector: Arc<PiiRedactor>) -> Self {
+        ConsumerRedaction {
+            consumers,
+            detector,
+        }
+    }
+
+    /// Unknown callers get redacted answers whenever some consumer does, a pipe leaving out
+    /// its name or token doesn't get around it
+    pub fn applies_to(&self, consumer: Consumer) -> bool {
+        consumer == Consumer::Unknown || self.consumers.contains(&consumer)
+    }
+
+    /// Every string in the value, object keys are left alone.
+    /// Runs the NER model if there is one, call it off the async threads
+    pub fn redact_value(&self, value: &mut Value) {
+        match value {
+            Value::String(text) => *text = self.detector.redact(text),
+            Value::Array(values) => values.iter_mut().for_each(|value| self.redact_value(value)),
+            Value::Object(fields) => fields
+                .values_mut()
+                .for_each(|value| self.redact_value(value)),
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+
+    pub async fn redact_owned(self: &Arc<Self>, mut value: Value) -> Value {
+        let redaction = Arc::clone(self);
+        tokio::task::spawn_blocking(move || {
+            redaction.redact_value(&mut value);
+            value
+        })
+        .await
+        .unwrap_or_else(|e| {
+            error!("Failed to redact response: {}", e);
+            Value::Null
+        })
+    }
+}
+
+/// Redacts the json answers of the consumers the redaction applies to
+pub async fn redact_responses(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let redaction = match &state.consumer_redaction {
+        Some(redaction)
+            if redaction.applies_to(Consumer::of_request(
+                &state.credentials,
+                request.headers(),
+            )) =>
+        {
+            Arc::clone(redaction)
+        }
+        _ => return next.run(request).await,
+    };
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match to_bytes(body, MAX_REDACTED_BODY).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to read response to redact, not sending it: {}", e);
+            parts.headers.remove(header::CONTENT_LENGTH);
+            return Response::from_parts(parts, Body::empty());
+        }
+    };
+    let Ok(value) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let value = redaction.redact_owned(value).await;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(value.to_string()))
+}
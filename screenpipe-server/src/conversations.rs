@@ -0,0 +1,191 @@
+//! Chats with the history kept in the database, so they survive restarts and can be resumed
+//! or exported. Each answer keeps the frames and audio it drew on and the tools called for it.
+use crate::llm::ToolRun;
+use crate::llm_tools::{GET_FRAME_TOOL, SEARCH_HISTORY_TOOL};
+use crate::SearchResult;
+use chrono::{DateTime, Utc};
+use screenpipe_core::ChatMessage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt::Write;
+
+/// Of the question, when a conversation is started without a title
+const MAX_TITLE_CHARS: usize = 80;
+/// Earlier messages sent with a new question, the oldest are left out of long conversations
+pub const MAX_HISTORY_MESSAGES: usize = 20;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Conversation {
+    pub id: i64,
+    pub title: String,
+    /// The pipe that started it, None for the user's own
+    pub pipe: Option<String>,
+    pub created_at: DateTime<Utc>,
+    /// When the last message was added
+    pub updated_at: DateTime<Utc>,
+    pub message_count: i64,
+}
+
+/// Something of the history an answer drew on
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Citation {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub frame_id: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audio_chunk_id: Option<i64>,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConversationMessage {
+    pub id: i64,
+    pub conversation_id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// "user" or "assistant"
+    pub role: String,
+    pub content: String,
+    pub citations: Vec<Citation>,
+    pub tool_calls: Vec<ToolRun>,
+    /// Of the answers
+    pub model: Option<String>,
+}
+
+/// The question, shortened, for a conversation started without a title
+pub fn title_from(question: &str) -> String {
+    let question = question.trim();
+    match question.char_indices().nth(MAX_TITLE_CHARS) {
+        Some((end, _)) => format!("{}…", question[..end].trim_end()),
+        None => question.to_string(),
+    }
+}
+
+fn push_citation(citations: &mut Vec<Citation>, citation: Citation) {
+    let known = citations.iter().any(|known| {
+        known.frame_id == citation.frame_id && known.audio_chunk_id == citation.audio_chunk_id
+    });
+    if !known && (citation.frame_id.is_some() || citation.audio_chunk_id.is_some()) {
+        citations.push(citation);
+    }
+}
+
+/// The frames and audio of the search results the question was asked with, and of what the
+/// searches and frame reads of the model returned, each once
+pub fn collect_citations(results: &[SearchResult], tool_runs: &[ToolRun]) -> Vec<Citation> {
+    let mut citations = Vec::new();
+    for result in results {
+        let citation = match result {
+            SearchResult::OCR(ocr) => Citation {
+                frame_id: Some(ocr.frame_id),
+                audio_chunk_id: None,
+                timestamp: ocr.timestamp,
+                app_name: Some(ocr.app_name.clone()),
+            },
+            SearchResult::Audio(audio) => Citation {
+                frame_id: None,
+                audio_chunk_id: Some(audio.audio_chunk_id),
+                timestamp: audio.timestamp,
+                app_name: None,
+            },
+            SearchResult::FTS(fts) => Citation {
+                frame_id: Some(fts.frame_id),
+                audio_chunk_id: None,
+                timestamp: fts.frame_timestamp,
+                app_name: Some(fts.app_name.clone()),
+            },
+        };
+        push_citation(&mut citations, citation);
+    }
+    for run in tool_runs {
+        let Some(result) = &run.result else {
+            continue;
+        };
+        let found: Vec<&Value> = match run.name.as_str() {
+            SEARCH_HISTORY_TOOL => result["results"]
+                .as_array()
+                .map(|results| results.iter().collect())
+                .unwrap_or_default(),
+            GET_FRAME_TOOL => vec![result],
+            _ => continue,
+        };
+        for value in found {
+            if let Ok(citation) = serde_json::from_value(value.clone()) {
+                push_citation(&mut citations, citation);
+            }
+        }
+    }
+    citations
+}
+
+/// The last messages of the conversation for the model, their questions and answers without
+/// the tool calls in between
+pub fn chat_history(messages: &[ConversationMessage]) -> Vec<ChatMessage> {
+    let skipped = messages.len().saturating_sub(MAX_HISTORY_MESSAGES);
+    messages[skipped..]
+        .iter()
+        .map(|message| match message.role.as_str() {
+            "assistant" => ChatMessage::assistant(&message.content),
+            _ => ChatMessage::user(&message.content),
+        })
+        .collect()
+}
+
+fn citation_line(citation: &Citation) -> String {
+    let source = match (citation.frame_id, citation.audio_chunk_id) {
+        (Some(frame_id), _) => format!("frame {}", frame_id),
+        (None, Some(audio_chunk_id)) => format!("audio chunk {}", audio_chunk_id),
+        (None, None) => "unknown".to_string(),
+    };
+    match &citation.app_name {
+        Some(app_name) => format!(
+            "- {}, {} at {}",
+            source,
+            app_name,
+            citation.timestamp.to_rfc3339()
+        ),
+        None => format!("- {} at {}", source, citation.timestamp.to_rfc3339()),
+    }
+}
+
+/// The conversation as a markdown document, with the sources and tools of each answer
+pub fn to_markdown(conversation: &Conversation, messages: &[ConversationMessage]) -> String {
+    let mut markdown = format!(
+        "# {}\n\nStarted {}",
+        conversation.title,
+        conversation.created_at.to_rfc3339()
+    );
+    if let Some(pipe) = &conversation.pipe {
+        let _ = write!(markdown, " by {}", pipe);
+    }
+    markdown.push('\n');
+    for message in messages {
+        let speaker = match (message.role.as_str(), &message.model) {
+            ("assistant", Some(model)) => model.as_str(),
+            ("assistant", None) => "Assistant",
+            _ => "You",
+        };
+        let _ = write!(
+            markdown,
+            "\n## {}, {}\n\n{}\n",
+            speaker,
+            message.timestamp.to_rfc3339(),
+            message.content.trim()
+        );
+        if !message.tool_calls.is_empty() {
+            markdown.push_str("\nTools:\n");
+            for run in &message.tool_calls {
+                let outcome = if run.error.is_some() { " (failed)" } else { "" };
+                let _ = writeln!(markdown, "- {} {}{}", run.name, run.arguments, outcome);
+            }
+        }
+        if !message.citations.is_empty() {
+            markdown.push_str("\nSources:\n");
+            for citation in &message.citations {
+                markdown.push_str(&citation_line(citation));
+                markdown.push('\n');
+            }
+        }
+    }
+    markdown
+}
@@ -1,21 +1,37 @@
-use crate::{DatabaseManager, VideoCapture};
+use crate::browser_pages::BROWSER_EXTENSION_ENGINE;
+use crate::disk_guard::media_writing_paused;
+use crate::live_ocr::LiveOcr;
+use crate::scene::SceneTracker;
+use crate::screen_regions::window_boxes;
+use crate::similarity::perceptual_hash;
+use crate::slides::SlideRecorder;
+use crate::window_layout::LayoutRecorder;
+use crate::text_changes::TextChangeTracker;
+use crate::text_only::text_only;
+use crate::thumbnails::Thumbnails;
+use crate::watchlist::Watchlist;
+use crate::write_coalescer::{PendingWrite, WriteCoalescer, DEFAULT_FLUSH_INTERVAL};
+use crate::{CapturePause, DatabaseManager, LiveView, RedactionPolicy, VideoCapture};
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use crossbeam::queue::SegQueue;
 use log::{debug, error, info, warn};
+use screenpipe_audio::stt::WHISPER_CHANNEL_CAPACITY;
 use screenpipe_audio::{
     create_whisper_channel, record_and_transcribe, AudioDevice, AudioInput,
     AudioTranscriptionEngine, DeviceControl, TranscriptionResult,
 };
+use screenpipe_core::{EncoderQuality, VideoEncoder};
 use screenpipe_integrations::friend_wearable::initialize_friend_wearable_loop;
-use screenpipe_vision::OcrEngine;
+use screenpipe_vision::{CaptureResult, OcrEngine};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
 
 pub enum RecorderControl {
     Pause,
@@ -56,12 +72,23 @@ pub async fn start_continuous_recording(
     ocr_engine: Arc<OcrEngine>,
     friend_wearable_uid: Option<String>,
     monitor_id: u32,
+    video_encoder: VideoEncoder,
+    video_quality: EncoderQuality,
+    live_view: Option<Arc<LiveView>>,
+    redaction_policy: Option<Arc<RedactionPolicy>>,
+    capture_pause: Arc<CapturePause>,
+    watchlist: Arc<Watchlist>,
+    live_ocr: Arc<LiveOcr>,
+    slides_dir: Option<PathBuf>,
+    // stops the recording, the OCR, transcription and encoding in flight with it
+    cancel: CancellationToken,
 ) -> Result<()> {
     let (whisper_sender, whisper_receiver) =
-        create_whisper_channel(audio_transcription_engine.clone()).await?;
+        create_whisper_channel(audio_transcription_engine.clone(), cancel.clone()).await?;
 
-    let db_manager_video = Arc::clone(&db);
-    let db_manager_audio = Arc::clone(&db);
+    // OCR text and transcripts of this recorder are written in batches
+    let writes = WriteCoalescer::start(Arc::clone(&db), DEFAULT_FLUSH_INTERVAL);
+    let writes_video = writes.clone();
 
     let is_running_video = Arc::clone(&vision_control);
 
@@ -69,6 +96,12 @@ pub async fn start_continuous_recording(
     let output_path_audio = Arc::clone(&output_path);
 
     let friend_wearable_uid_video = friend_wearable_uid.clone();
+    let redaction_policy_video = redaction_policy.clone();
+    let capture_pause_video = Arc::clone(&capture_pause);
+    let watchlist_video = Arc::clone(&watchlist);
+    let slides = slides_dir.map(|dir| SlideRecorder::new(Arc::clone(&db), dir, monitor_id));
+    let layouts = LayoutRecorder::new(Arc::clone(&db), monitor_id);
+    let cancel_video = cancel.clone();
 
     // Initialize friend wearable loop
     if let Some(uid) = &friend_wearable_uid {
@@ -78,9 +111,11 @@ pub async fn start_continuous_recording(
         ));
     }
 
+    let db_video = Arc::clone(&db);
     let video_handle = tokio::spawn(async move {
         record_video(
-            db_manager_video,
+            db_video,
+            writes_video,
             output_path_video,
             fps,
             is_running_video,
@@ -88,13 +123,24 @@ pub async fn start_continuous_recording(
             ocr_engine,
             friend_wearable_uid_video,
             monitor_id,
+            video_encoder,
+            video_quality,
+            live_view,
+            redaction_policy_video,
+            capture_pause_video,
+            watchlist_video,
+            live_ocr,
+            slides,
+            layouts,
+            cancel_video,
         )
         .await
     });
 
     let audio_handle = tokio::spawn(async move {
         record_audio(
-            db_manager_audio,
+            db,
+            writes,
             output_path_audio,
             audio_chunk_duration,
             whisper_sender,
@@ -102,6 +148,10 @@ pub async fn start_continuous_recording(
             audio_devices_control,
             friend_wearable_uid,
             audio_transcription_engine,
+            redaction_policy,
+            capture_pause,
+            watchlist,
+            cancel,
         )
         .await
     });
@@ -122,6 +172,7 @@ pub async fn start_continuous_recording(
 
 async fn record_video(
     db: Arc<DatabaseManager>,
+    writes: WriteCoalescer,
     output_path: Arc<String>,
     fps: f64,
     is_running: Arc<AtomicBool>,
@@ -129,18 +180,28 @@ async fn record_video(
     ocr_engine: Arc<OcrEngine>,
     _friend_wearable_uid: Option<String>,
     monitor_id: u32,
+    video_encoder: VideoEncoder,
+    video_quality: EncoderQuality,
+    live_view: Option<Arc<LiveView>>,
+    redaction_policy: Option<Arc<RedactionPolicy>>,
+    capture_pause: Arc<CapturePause>,
+    watchlist: Arc<Watchlist>,
+    live_ocr: Arc<LiveOcr>,
+    mut slides: Option<SlideRecorder>,
+    mut layouts: LayoutRecorder,
+    cancel: CancellationToken,
 ) -> Result<()> {
     debug!("record_video: Starting");
-    let db_chunk_callback = Arc::clone(&db);
+    let chunk_writes = writes.clone();
     let rt = tokio::runtime::Handle::current();
     let new_chunk_callback = move |file_path: &str| {
-        let db_chunk_callback = Arc::clone(&db_chunk_callback);
+        let chunk_writes = chunk_writes.clone();
         let file_path = file_path.to_string();
         rt.spawn(async move {
-            if let Err(e) = db_chunk_callback.insert_video_chunk(&file_path).await {
-                error!("Failed to insert new video chunk: {}", e);
-            }
-            debug!("record_video: Inserted new video chunk: {}", file_path);
+            debug!("record_video: Queued new video chunk: {}", file_path);
+            chunk_writes
+                .write(PendingWrite::VideoChunk { file_path })
+                .await;
         });
     };
 
@@ -151,62 +212,116 @@ async fn record_video(
         save_text_files,
         Arc::clone(&ocr_engine),
         monitor_id,
+        video_encoder,
+        video_quality,
+        redaction_policy,
+        capture_pause,
+        Some(writes.clone()),
+        cancel.clone(),
     );
 
-    while is_running.load(Ordering::SeqCst) {
+    let thumbnails = Thumbnails::new(Path::new(output_path.as_str()));
+    let mut text_changes = TextChangeTracker::default();
+    let mut scenes = SceneTracker::new(monitor_id);
+    while is_running.load(Ordering::SeqCst) && !cancel.is_cancelled() {
         if let Some(frame) = video_capture.ocr_frame_queue.lock().await.pop_front() {
-            for window_result in &frame.window_ocr_results {
-                match db.insert_frame().await {
-                    Ok(frame_id) => {
-                        let text_json =
-                            serde_json::to_string(&window_result.text_json).unwrap_or_default();
-
-                        if let Err(e) = db
-                            .insert_ocr_text(
-                                frame_id,
-                                &window_result.text,
-                                &text_json,
-                                &window_result.app_name,
-                                &window_result.window_name,
-                                Arc::clone(&ocr_engine),
-                                window_result.focused, // Add this line
-                            )
-                            .await
-                        {
-                            error!(
-                                "Failed to insert OCR text: {}, skipping window {} of frame {}",
-                                e, window_result.window_name, frame_id
-                            );
-                            continue;
-                        }
-                    }
-                    Err(e) => {
-                        warn!("Failed to insert frame: {}", e);
-                        tokio::time::sleep(Duration::from_millis(100)).await;
-                        continue;
+            if let Some(live_view) = &live_view {
+                live_view.publish_frame(&frame);
+            }
+            live_ocr.publish_frame(&frame);
+            let timestamp = Utc::now();
+            if !media_writing_paused() && !text_only() {
+                let (thumbnails, image) = (thumbnails.clone(), Arc::clone(&frame.image));
+                let db = Arc::clone(&db);
+                tokio::spawn(async move {
+                    if let Err(e) = thumbnails.write(&db, image, timestamp).await {
+                        warn!("Failed to write the thumbnails of a frame: {}", e);
                     }
-                }
+                });
+            }
+            text_changes.observe(&frame, timestamp);
+            scenes.observe(&frame, timestamp);
+            if let Some(slides) = &mut slides {
+                slides.observe(&frame, timestamp).await;
+            }
+            layouts.observe(&frame, timestamp).await;
+            watchlist.check_frame(&frame, timestamp).await;
+            for write in frame_writes(&frame, timestamp, &ocr_engine, Some(monitor_id)) {
+                writes.write(write).await;
             }
         }
-        tokio::time::sleep(Duration::from_secs_f64(1.0 / fps)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs_f64(1.0 / fps)) => {}
+            _ = cancel.cancelled() => {}
+        }
     }
 
     Ok(())
 }
 
+/// Each window of a captured frame is stored as its own frame, followed by the boxes of its
+/// text when the engine placed it
+pub(crate) fn frame_writes(
+    frame: &CaptureResult,
+    timestamp: DateTime<Utc>,
+    ocr_engine: &OcrEngine,
+    monitor_id: Option<u32>,
+) -> Vec<PendingWrite> {
+    let mut writes = Vec::new();
+    for window_result in &frame.window_ocr_results {
+        writes.push(PendingWrite::Ocr {
+            timestamp,
+            text: window_result.text.clone(),
+            text_json: serde_json::to_string(&window_result.text_json).unwrap_or_default(),
+            app_name: window_result.app_name.clone(),
+            window_name: window_result.window_name.clone(),
+            ocr_engine: match window_result.browser_page {
+                Some(_) => BROWSER_EXTENSION_ENGINE.to_string(),
+                None => format!("{:?}", ocr_engine),
+            },
+            focused: window_result.focused,
+            perceptual_hash: Some(perceptual_hash(&window_result.image)),
+            browser_url: window_result
+                .browser_page
+                .as_ref()
+                .map(|page| page.url.clone()),
+            scroll_y: window_result
+                .browser_page
+                .as_ref()
+                .and_then(|page| page.scroll_y),
+        });
+        // the handwriting engine of an app profile reads lines without boxes
+        let boxes = window_boxes(window_result, ocr_engine);
+        if !boxes.is_empty() {
+            writes.push(PendingWrite::OcrBoxes { monitor_id, boxes });
+        }
+    }
+    writes
+}
+
 async fn record_audio(
     db: Arc<DatabaseManager>,
+    writes: WriteCoalescer,
     output_path: Arc<String>,
     chunk_duration: Duration,
-    whisper_sender: UnboundedSender<AudioInput>,
-    mut whisper_receiver: UnboundedReceiver<TranscriptionResult>,
+    whisper_sender: Sender<AudioInput>,
+    mut whisper_receiver: Receiver<TranscriptionResult>,
     audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
     friend_wearable_uid: Option<String>,
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    redaction_policy: Option<Arc<RedactionPolicy>>,
+    capture_pause: Arc<CapturePause>,
+    watchlist: Arc<Watchlist>,
+    cancel: CancellationToken,
 ) -> Result<()> {
     let mut handles: HashMap<String, JoinHandle<()>> = HashMap::new();
+    tokio::spawn(resume_pending_transcriptions(
+        Arc::clone(&db),
+        whisper_sender.clone(),
+    ));
+    let whisper_sender = journal_audio_chunks(Arc::clone(&db), whisper_sender);
 
-    loop {
+    while !cancel.is_cancelled() {
         while let Some((audio_device, device_control)) = audio_devices_control.pop() {
             debug!("Received audio device: {}", &audio_device);
             let device_id = audio_device.to_string();
@@ -222,6 +337,8 @@ async fn record_audio(
 
             let output_path_clone = Arc::clone(&output_path);
             let whisper_sender_clone = whisper_sender.clone();
+            let capture_pause = Arc::clone(&capture_pause);
+            let cancel = cancel.clone();
 
             let audio_device = Arc::new(audio_device);
             let device_control = Arc::new(device_control);
@@ -235,7 +352,11 @@ async fn record_audio(
                 );
 
                 let mut iteration = 0;
-                loop {
+                while !cancel.is_cancelled() {
+                    if capture_pause.is_paused() || media_writing_paused() {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue;
+                    }
                     iteration += 1;
                     debug!(
                         "Starting iteration {} for device {}",
@@ -258,14 +379,34 @@ async fn record_audio(
                         "Starting record_and_transcribe for device {} (iteration {})",
                         audio_device_clone, iteration
                     );
+                    let is_running = Arc::new(AtomicBool::new(device_control_clone.is_running));
+                    // a pause, the disk filling up or the recording being cancelled cuts the chunk
+                    // short, only what was said before it is kept
+                    let stop_on_pause = {
+                        let is_running = Arc::clone(&is_running);
+                        let capture_pause = Arc::clone(&capture_pause);
+                        let cancel = cancel.clone();
+                        tokio::spawn(async move {
+                            while is_running.load(Ordering::Relaxed) {
+                                if capture_pause.is_paused()
+                                    || media_writing_paused()
+                                    || cancel.is_cancelled()
+                                {
+                                    is_running.store(false, Ordering::Relaxed);
+                                }
+                                tokio::time::sleep(Duration::from_millis(100)).await;
+                            }
+                        })
+                    };
                     let result = record_and_transcribe(
                         audio_device_clone,
                         chunk_duration,
                         file_path.into(),
                         whisper_sender,
-                        Arc::new(AtomicBool::new(device_control_clone.is_running)),
+                        is_running,
                     )
                     .await;
+                    stop_on_pause.abort();
                     info!(
                         "Finished record_and_transcribe for device {} (iteration {})",
                         audio_device_clone_2, iteration
@@ -313,9 +454,12 @@ async fn record_audio(
             // avoiding crashing the audio processing if one fails
             if let Err(e) = process_audio_result(
                 &db,
+                &writes,
                 transcription,
                 friend_wearable_uid.as_deref(),
                 audio_transcription_engine.clone(),
+                redaction_policy.as_ref(),
+                Some(&watchlist),
             )
             .await
             {
@@ -324,58 +468,150 @@ async fn record_audio(
             }
         }
 
-        tokio::time::sleep(Duration::from_millis(100)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+            _ = cancel.cancelled() => {}
+        }
+    }
+
+    // the device threads stop once their chunk is cut short
+    for (device_id, handle) in handles {
+        if let Err(e) = handle.await {
+            error!(
+                "Audio capture thread for device {} failed: {}",
+                device_id, e
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Journals each recorded chunk before passing it on to whisper, the journal entry is removed
+/// with the transcript so a chunk is neither lost nor transcribed twice across restarts
+pub(crate) fn journal_audio_chunks(
+    db: Arc<DatabaseManager>,
+    whisper_sender: Sender<AudioInput>,
+) -> Sender<AudioInput> {
+    let (sender, mut receiver) = channel::<AudioInput>(WHISPER_CHANNEL_CAPACITY);
+    tokio::spawn(async move {
+        while let Some(input) = receiver.recv().await {
+            if let Err(e) = db
+                .insert_pending_transcription(&input.path, &input.device)
+                .await
+            {
+                warn!("Failed to journal audio chunk {}: {}", input.path, e);
+            }
+            if whisper_sender.send(input).await.is_err() {
+                break;
+            }
+        }
+    });
+    sender
+}
+
+/// Transcribes the chunks recorded before the last stop that never got a transcript
+async fn resume_pending_transcriptions(
+    db: Arc<DatabaseManager>,
+    whisper_sender: Sender<AudioInput>,
+) {
+    let pending = match db.get_pending_transcriptions().await {
+        Ok(pending) => pending,
+        Err(e) => {
+            error!("Failed to get pending transcriptions: {}", e);
+            return;
+        }
+    };
+    if pending.is_empty() {
+        return;
+    }
+    info!("Resuming {} untranscribed audio chunks", pending.len());
+    for (path, device) in pending {
+        if !Path::new(&path).exists() {
+            warn!("Audio chunk {} is gone, skipping its transcription", path);
+            if let Err(e) = db.remove_pending_transcription(&path).await {
+                error!("Failed to remove pending transcription {}: {}", path, e);
+            }
+            continue;
+        }
+        if whisper_sender
+            .send(AudioInput { path, device })
+            .await
+            .is_err()
+        {
+            break;
+        }
     }
 }
 
-async fn process_audio_result(
+pub(crate) async fn process_audio_result(
     db: &DatabaseManager,
+    writes: &WriteCoalescer,
     result: TranscriptionResult,
     _friend_wearable_uid: Option<&str>,
     audio_transcription_engine: Arc<AudioTranscriptionEngine>,
+    redaction_policy: Option<&Arc<RedactionPolicy>>,
+    watchlist: Option<&Watchlist>,
 ) -> Result<(), anyhow::Error> {
     if result.error.is_some() || result.transcription.is_none() {
         error!(
             "Error in audio recording: {}. Not inserting audio result",
             result.error.unwrap_or_default()
         );
+        db.remove_pending_transcription(&result.input.path).await?;
         return Ok(());
     }
     let transcription = result.transcription.unwrap();
-    let transcription_engine = audio_transcription_engine.to_string();
-
-    info!("Inserting audio chunk: {:?}", result.input.path);
-    match db.insert_audio_chunk(&result.input.path).await {
-        Ok(audio_chunk_id) => {
-            if transcription.is_empty() {
-                return Ok(());
-            }
-
-            if let Err(e) = db
-                .insert_audio_transcription(
-                    audio_chunk_id,
-                    &transcription,
-                    0,
-                    &transcription_engine,
-                )
-                .await
-            {
-                error!(
-                    "Failed to insert audio transcription for device {}: {}",
-                    result.input.device, e
-                );
-                return Ok(());
-            } else {
-                debug!(
-                    "Inserted audio transcription for chunk {} from device {} using {}",
-                    audio_chunk_id, result.input.device, transcription_engine
-                );
+    let transcription = match redaction_policy {
+        // runs off the async threads, the NER model takes a while on long texts
+        Some(policy) => {
+            let policy = Arc::clone(policy);
+            let (transcription, annotations) =
+                tokio::task::spawn_blocking(move || policy.annotate_transcript(&transcription))
+                    .await?;
+            let timestamp = Utc::now();
+            for annotation in annotations {
+                writes
+                    .write(PendingWrite::Redaction {
+                        timestamp,
+                        annotation,
+                        audio_file: Some(result.input.path.clone()),
+                    })
+                    .await;
             }
+            transcription
         }
-        Err(e) => error!(
-            "Failed to insert audio chunk for device {}: {}",
-            result.input.device, e
-        ),
+        None => Some(transcription),
+    };
+    let Some(transcription) = transcription else {
+        info!(
+            "Redaction policy skips audio from device {}, deleting {}",
+            result.input.device, result.input.path
+        );
+        if let Err(e) = tokio::fs::remove_file(&result.input.path).await {
+            error!("Failed to delete audio chunk {}: {}", result.input.path, e);
+        }
+        db.remove_pending_transcription(&result.input.path).await?;
+        return Ok(());
+    };
+    let transcription_engine = audio_transcription_engine.to_string();
+    let timestamp = Utc::now();
+    if let Some(watchlist) = watchlist {
+        watchlist
+            .check_transcript(&result.input.device, &transcription, timestamp)
+            .await;
     }
+
+    debug!(
+        "Queueing audio chunk {} from device {} using {}",
+        result.input.path, result.input.device, transcription_engine
+    );
+    writes
+        .write(PendingWrite::Transcription {
+            timestamp,
+            file_path: result.input.path,
+            transcription,
+            engine: transcription_engine,
+        })
+        .await;
     Ok(())
 }
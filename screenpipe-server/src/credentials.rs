@@ -0,0 +1,224 @@
+//! Who is calling the API, from credentials the server checks. Each pipe has its own token,
+//! sent with its name, the app has one of its own and remote clients the ones of --api-token.
+//! A name or a bearer token that doesn't check out is the one of an unknown caller
+use crate::user_isolation::{restrict_to_owner, write_private_file};
+use anyhow::{Context, Result};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Json, Response};
+use rand::Rng;
+use screenpipe_core::{PIPE_HEADER, PIPE_TOKEN_HEADER};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The app's token, sent as a bearer token
+pub const APP_TOKEN_FILE: &str = "app_token";
+/// The token of each pipe by name, for the pipes run outside of the pipe runtime
+pub const PIPE_TOKENS_FILE: &str = "pipe_tokens.json";
+const TOKEN_LEN: usize = 48;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Caller {
+    /// The app, with the token of app_token in the data directory, or a process of the user on
+    /// the API socket
+    App,
+    /// A pipe with its token
+    Pipe(String),
+    /// A client with one of the --api-token tokens
+    Remote,
+    /// Anything else, including pipes without their token
+    Unknown,
+}
+
+impl Caller {
+    pub fn pipe(&self) -> Option<&str> {
+        match self {
+            Caller::Pipe(pipe) => Some(pipe),
+            _ => None,
+        }
+    }
+}
+
+pub struct ApiCredentials {
+    /// Where new pipe tokens are kept, None when they only live in memory
+    dir: Option<PathBuf>,
+    app_token: String,
+    remote_tokens: Vec<String>,
+    pipe_tokens: Mutex<HashMap<String, String>>,
+}
+
+fn new_token() -> String {
+    rand::thread_rng()
+        .sample_iter(&rand::distributions::Alphanumeric)
+        .take(TOKEN_LEN)
+        .map(char::from)
+        .collect()
+}
+
+/// Compares the whole token so the time taken doesn't leak the matching prefix
+pub fn tokens_match(token: &str, expected: &str) -> bool {
+    token.len() == expected.len()
+        && token
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+impl ApiCredentials {
+    /// Only in memory, for tests and embedding
+    pub fn new(app_token: impl Into<String>, remote_tokens: Vec<String>) -> Self {
+        ApiCredentials {
+            dir: None,
+            app_token: app_token.into(),
+            remote_tokens,
+            pipe_tokens: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Reads the app and pipe tokens from the data directory, the app's is created the first
+    /// time unless the app started the server with its own. Both files are readable by the
+    /// user only
+    pub fn load(
+        data_dir: &Path,
+        app_token: Option<String>,
+        remote_tokens: Vec<String>,
+    ) -> Result<Self> {
+        let app_path = data_dir.join(APP_TOKEN_FILE);
+        let saved = std::fs::read_to_string(&app_path)
+            .ok()
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty());
+        let app_token = match (app_token.filter(|token| !token.is_empty()), saved) {
+            (None, Some(saved)) => {
+                restrict_to_owner(&app_path)?;
+                saved
+            }
+            (given, _) => {
+                let token = given.unwrap_or_else(new_token);
+                write_private_file(&app_path, token.as_bytes())?;
+                token
+            }
+        };
+        let pipes_path = data_dir.join(PIPE_TOKENS_FILE);
+        let pipe_tokens = match std::fs::read_to_string(&pipes_path) {
+            Ok(tokens) => serde_json::from_str(&tokens)
+                .with_context(|| format!("failed to parse {}", pipes_path.display()))?,
+            Err(_) => HashMap::new(),
+        };
+        Ok(ApiCredentials {
+            dir: Some(data_dir.to_path_buf()),
+            app_token,
+            remote_tokens,
+            pipe_tokens: Mutex::new(pipe_tokens),
+        })
+    }
+
+    pub fn app_token(&self) -> &str {
+        &self.app_token
+    }
+
+    /// The token of the pipe, created and saved the first time it's asked for
+    pub fn pipe_token(&self, pipe: &str) -> Result<String> {
+        let mut tokens = self.pipe_tokens.lock().unwrap();
+        if let Some(token) = tokens.get(pipe) {
+            return Ok(token.clone());
+        }
+        let token = new_token();
+        tokens.insert(pipe.to_string(), token.clone());
+        if let Some(dir) = &self.dir {
+            write_private_file(
+                &dir.join(PIPE_TOKENS_FILE),
+                serde_json::to_string_pretty(&*tokens)?.as_bytes(),
+            )?;
+        }
+        Ok(token)
+    }
+
+    /// The caller the credentials of the request check out as
+    pub fn caller(&self, headers: &HeaderMap) -> Caller {
+        if let Some(pipe) = headers.get(PIPE_HEADER).and_then(|v| v.to_str().ok()) {
+            let token = headers.get(PIPE_TOKEN_HEADER).and_then(|v| v.to_str().ok());
+            let expected = self.pipe_tokens.lock().unwrap().get(pipe).cloned();
+            return match (token, expected) {
+                (Some(token), Some(expected)) if tokens_match(token, &expected) => {
+                    Caller::Pipe(pipe.to_string())
+                }
+                _ => Caller::Unknown,
+            };
+        }
+        let bearer = headers
+            .get(header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "));
+        match bearer {
+            Some(token) if tokens_match(token, &self.app_token) => Caller::App,
+            Some(token)
+                if self
+                    .remote_tokens
+                    .iter()
+                    .any(|expected| tokens_match(token, expected)) =>
+            {
+                Caller::Remote
+            }
+            _ => Caller::Unknown,
+        }
+    }
+}
+
+/// Requests of the API socket, whose peers the OS checked are processes of the user, are the
+/// app's unless they name a pipe, whose token is still checked
+pub async fn local_peer_is_app(
+    State(credentials): State<Arc<ApiCredentials>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !request.headers().contains_key(PIPE_HEADER) {
+        let mut authorization = HeaderValue::from_str(&format!("Bearer {}", credentials.app_token))
+            .expect("tokens are alphanumeric");
+        authorization.set_sensitive(true);
+        request
+            .headers_mut()
+            .insert(header::AUTHORIZATION, authorization);
+    }
+    next.run(request).await
+}
+
+/// A request a web page made to another origin than the server's, browsers send the page's
+/// origin. What isn't a browser sends none
+pub fn is_cross_origin(headers: &HeaderMap) -> bool {
+    let Some(origin) = headers.get(header::ORIGIN) else {
+        return false;
+    };
+    let host = headers.get(header::HOST).and_then(|v| v.to_str().ok());
+    let origin_host = origin
+        .to_str()
+        .ok()
+        .and_then(|origin| origin.split_once("://"))
+        .map(|(_, host)| host);
+    match (origin_host, host) {
+        (Some(origin_host), Some(host)) => !origin_host.eq_ignore_ascii_case(host),
+        _ => true,
+    }
+}
+
+/// Guards the routes that delete, pause or run arbitrary queries: the permissive CORS of the
+/// API lets any web page send them, so one of another origin is refused unless it comes with
+/// the app's token
+pub async fn refuse_cross_origin(
+    State(credentials): State<Arc<ApiCredentials>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_cross_origin(request.headers()) && credentials.caller(request.headers()) != Caller::App {
+        return (
+            StatusCode::FORBIDDEN,
+            Json(json!({"error": "web pages of other origins can't do this"})),
+        )
+            .into_response();
+    }
+    next.run(request).await
+}
@@ -1,24 +1,67 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, DurationRound, NaiveDate, Utc};
 use log::{debug, error, info, warn};
 use serde::{Deserialize, Serialize};
 use sqlx::migrate::MigrateDatabase;
 use sqlx::{
-    sqlite::{SqlitePool, SqlitePoolOptions},
-    FromRow,
+    sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool, SqlitePoolOptions},
+    ConnectOptions, FromRow,
 };
+use std::collections::BTreeMap;
+use std::str::FromStr;
 use std::time::Duration;
 use tokio::time::{timeout, Duration as TokioDuration};
 use crate::chunking::{text_chunking_by_similarity, text_chunking_simple};
+use crate::audit::{AuditAction, AuditEntry};
+use crate::clips::{ClipJob, ClipSourceFrame, ClipStatus};
+use crate::compliance::ConsentRecord;
 use screenpipe_vision::OcrEngine;
 use std::sync::Arc;
 use screenpipe_integrations::friend_wearable::FriendWearableDatabase;
+use screenpipe_integrations::analytics_export::{word_count, OcrRow, TranscriptRow, UsageRow};
+use screenpipe_integrations::git_activity::{GitEventKind, ReflogEntry};
+use screenpipe_integrations::notifications::{NotificationSource, OsNotification};
 use async_trait::async_trait;
 use std::error::Error as StdError;
 use std::fmt;
 use screenpipe_integrations::unstructured_ocr::unstructured_chunking;
 use crate::filtering::filter_texts;
+use crate::language::fts_condition;
+use crate::activity::{ActivityCategory, ActivitySession, CategoryDuration};
+use crate::extraction::{ExtractedRecord, ExtractionKind, ExtractionRule};
+use crate::automation::{AutomationRecord, AutomationStatus};
+use crate::file_activity::{FileEvent, FileEventKind};
+use crate::focus_mode::FocusPeriod;
+use crate::timezones::TimezoneSegment;
+use crate::trends::WeekMetrics;
+use crate::text_only::TEXT_ONLY_CHUNK_EXTENSION;
+use crate::forget::{ForgetFilter, ForgetFrame, ForgetPlan, ForgetReport};
+use crate::backup::{BackupSource, BackupUpload};
+use crate::encryption::ImageKind;
+use crate::integrity::{ChunkIntegrity, ChunkKind};
+use crate::llm::{cache_key, ToolRun};
+use crate::markers::Marker;
+use crate::pagination::{Cursor, CursorPosition};
+use crate::policy::{PolicyAction, RedactionAnnotation, RedactionRecord, RedactionSource};
+use crate::slides::{Slide, SlideDeck};
+use crate::notifications::Notification;
+use crate::conversations::{Citation, Conversation, ConversationMessage};
+use crate::live_ocr::Rect;
+use crate::screen_regions::{OcrBox, RegionFilter, RegionText};
+use crate::editor_context::{EditorBeacon, EditorContext};
+use crate::git_activity::GitEvent;
+use crate::shell_history::{NewShellCommand, ShellCommand, ShellCommandSource};
+use crate::watchlist::{Watch, WatchAlert, WatchKind, WatchSource};
+use crate::window_history::{WindowEvent, WindowEventKind};
+use crate::window_layout::{LayoutWindow, WindowLayout};
+use crate::meetings::{ActionItem, MeetingSummary};
+use crate::subtitles::ChunkFrame;
+use crate::timelapse::{TimelapseJob, TimelapseSourceFrame, TimelapseStatus};
+use crate::write_coalescer::PendingWrite;
+use screenpipe_core::TokenUsage;
 use sqlx::sqlite::SqliteRow;
 use sqlx::Row;
+use sqlx::Connection;
+use serde_json::json;
 
 #[derive(Debug)]
 pub struct DatabaseError(String);
@@ -62,6 +105,11 @@ pub struct OCRResult {
     pub app_name: String,
     pub ocr_engine: String,
     pub window_name: String,
+    /// Of the page when the browser extension pushed the text
+    pub browser_url: Option<String>,
+    pub scroll_y: Option<f64>,
+    /// ISO 639-1, None until the text indexer got to it
+    pub language: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Default, Clone, Copy)]
@@ -73,6 +121,17 @@ pub enum ContentType {
     Audio,
 }
 
+#[derive(Debug, Serialize, FromRow)]
+pub struct LlmUsageSummary {
+    pub day: String,
+    pub source: String,
+    pub model: String,
+    pub calls: i64,
+    pub prompt_tokens: i64,
+    pub completion_tokens: i64,
+    pub cost_usd: f64,
+}
+
 #[derive(Debug, Serialize, FromRow)]
 pub struct AudioResult {
     pub audio_chunk_id: i64,
@@ -81,6 +140,7 @@ pub struct AudioResult {
     pub file_path: String,
     pub offset_index: i64,
     pub transcription_engine: String,
+    pub language: Option<String>,
 }
 
 pub struct DatabaseManager {
@@ -89,6 +149,36 @@ pub struct DatabaseManager {
 
 impl DatabaseManager {
     pub async fn new(database_path: &str) -> Result<Self, sqlx::Error> {
+        Self::connect(database_path, None).await
+    }
+
+    /// Whether this build can encrypt the database, it needs the sqlcipher feature
+    pub fn supports_encryption() -> bool {
+        cfg!(feature = "sqlcipher")
+    }
+
+    /// Database encrypted by SQLCipher with a hex `key`. A plaintext database is encrypted
+    /// first, one still on `previous_key` after a key rotation is re-keyed
+    pub async fn new_encrypted(
+        database_path: &str,
+        key: &str,
+        previous_key: Option<&str>,
+    ) -> Result<Self, sqlx::Error> {
+        if !Self::supports_encryption() {
+            return Err(sqlx::Error::Configuration(
+                "screenpipe was built without the sqlcipher feature".into(),
+            ));
+        }
+        if is_plaintext_database(database_path) {
+            info!("Encrypting the existing plaintext database");
+            encrypt_plaintext_database(database_path, key).await?;
+        } else if let Some(previous_key) = previous_key {
+            rekey_database(database_path, previous_key, key).await?;
+        }
+        Self::connect(database_path, Some(key)).await
+    }
+
+    async fn connect(database_path: &str, key: Option<&str>) -> Result<Self, sqlx::Error> {
         debug!(
             "Initializing DatabaseManager with database path: {}",
             database_path
@@ -100,11 +190,15 @@ impl DatabaseManager {
             sqlx::Sqlite::create_database(&connection_string).await?;
         }
 
+        let mut options = SqliteConnectOptions::from_str(&connection_string)?;
+        if let Some(key) = key {
+            options = options.pragma("key", sqlcipher_key(key));
+        }
         let pool = SqlitePoolOptions::new()
             .max_connections(10)
             .min_connections(3) // Minimum number of idle connections
             .acquire_timeout(Duration::from_secs(10))
-            .connect(&connection_string)
+            .connect_with(options)
             .await?;
 
         // Enable WAL mode
@@ -421,6 +515,184 @@ impl DatabaseManager {
         Ok(())
     }
 
+    /// Writes the batch in a single transaction, in order, and returns the id of the row each
+    /// write added: the video chunk, frame, audio chunk or file event. A frame written before
+    /// any video chunk gets 0 and no OCR text, as with insert_frame, and its boxes are left out.
+    /// Boxes get the id of their frame
+    pub async fn insert_batch(&self, writes: &[PendingWrite]) -> Result<Vec<i64>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut ids = Vec::with_capacity(writes.len());
+        let mut last_frame: Option<(i64, DateTime<Utc>)> = None;
+        for write in writes {
+            let id = match write {
+                PendingWrite::VideoChunk { file_path } => {
+                    sqlx::query("INSERT INTO video_chunks (file_path) VALUES (?1)")
+                        .bind(file_path)
+                        .execute(&mut *tx)
+                        .await?
+                        .last_insert_rowid()
+                }
+                PendingWrite::Ocr {
+                    timestamp,
+                    text,
+                    text_json,
+                    app_name,
+                    window_name,
+                    ocr_engine,
+                    focused,
+                    perceptual_hash,
+                    browser_url,
+                    scroll_y,
+                } => {
+                    let video_chunk_id: Option<i64> =
+                        sqlx::query_scalar("SELECT id FROM video_chunks ORDER BY id DESC LIMIT 1")
+                            .fetch_optional(&mut *tx)
+                            .await?;
+                    let Some(video_chunk_id) = video_chunk_id else {
+                        last_frame = None;
+                        ids.push(0);
+                        continue;
+                    };
+                    let frame_id = sqlx::query(
+                        "INSERT INTO frames (video_chunk_id, offset_index, timestamp, perceptual_hash) VALUES (?1, (SELECT COALESCE(MAX(offset_index), -1) + 1 FROM frames WHERE video_chunk_id = ?1), ?2, ?3)",
+                    )
+                    .bind(video_chunk_id)
+                    .bind(timestamp)
+                    .bind(perceptual_hash.map(|hash| hash as i64))
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid();
+                    sqlx::query("INSERT INTO ocr_text (frame_id, text, text_json, app_name, ocr_engine, window_name, focused, browser_url, scroll_y) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)")
+                        .bind(frame_id)
+                        .bind(text)
+                        .bind(text_json)
+                        .bind(app_name)
+                        .bind(ocr_engine)
+                        .bind(window_name)
+                        .bind(focused)
+                        .bind(browser_url)
+                        .bind(scroll_y)
+                        .execute(&mut *tx)
+                        .await?;
+                    let (chunks, chunking_engine) = chunks_for_index(text);
+                    for chunk in &chunks {
+                        insert_chunked_entry(&mut tx, frame_id, chunk, *timestamp, ocr_engine, chunking_engine, ContentSource::Screen).await?;
+                    }
+                    last_frame = Some((frame_id, *timestamp));
+                    frame_id
+                }
+                PendingWrite::OcrBoxes { monitor_id, boxes } => {
+                    let Some((frame_id, timestamp)) = last_frame else {
+                        ids.push(0);
+                        continue;
+                    };
+                    let seen = unix_seconds(timestamp);
+                    for ocr_box in boxes {
+                        let rect = &ocr_box.rect;
+                        let box_id = sqlx::query("INSERT INTO ocr_boxes (frame_id, monitor_id, text, confidence, x, y, width, height) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)")
+                            .bind(frame_id)
+                            .bind(monitor_id)
+                            .bind(&ocr_box.text)
+                            .bind(ocr_box.confidence)
+                            .bind(rect.x)
+                            .bind(rect.y)
+                            .bind(rect.width)
+                            .bind(rect.height)
+                            .execute(&mut *tx)
+                            .await?
+                            .last_insert_rowid();
+                        sqlx::query("INSERT INTO ocr_boxes_rtree (id, min_x, max_x, min_y, max_y, min_t, max_t) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?6)")
+                            .bind(box_id)
+                            .bind(rect.x)
+                            .bind(rect.x + rect.width)
+                            .bind(rect.y)
+                            .bind(rect.y + rect.height)
+                            .bind(seen)
+                            .execute(&mut *tx)
+                            .await?;
+                    }
+                    frame_id
+                }
+                PendingWrite::Transcription {
+                    timestamp,
+                    file_path,
+                    transcription,
+                    engine,
+                } => {
+                    let audio_chunk_id = sqlx::query("INSERT INTO audio_chunks (file_path, timestamp) VALUES (?1, ?2)")
+                        .bind(file_path)
+                        .bind(timestamp)
+                        .execute(&mut *tx)
+                        .await?
+                        .last_insert_rowid();
+                    // the chunk is done once its transcript is in
+                    sqlx::query("DELETE FROM pending_transcriptions WHERE file_path = ?1")
+                        .bind(file_path)
+                        .execute(&mut *tx)
+                        .await?;
+                    if !transcription.is_empty() {
+                        sqlx::query(
+                            "INSERT INTO audio_transcriptions (audio_chunk_id, transcription, offset_index, timestamp, transcription_engine) VALUES (?1, ?2, 0, ?3, ?4)",
+                        )
+                        .bind(audio_chunk_id)
+                        .bind(transcription)
+                        .bind(timestamp)
+                        .bind(engine)
+                        .execute(&mut *tx)
+                        .await?;
+                        let (chunks, chunking_engine) = chunks_for_index(transcription);
+                        for chunk in &chunks {
+                            insert_chunked_entry(&mut tx, audio_chunk_id, chunk, *timestamp, engine, chunking_engine, ContentSource::Audio).await?;
+                        }
+                    }
+                    audio_chunk_id
+                }
+                PendingWrite::Redaction {
+                    timestamp,
+                    annotation,
+                    audio_file,
+                } => {
+                    sqlx::query(
+                        "INSERT INTO redactions (timestamp, source, rule, action, app_name, entities, regions, audio_file) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                    )
+                    .bind(timestamp)
+                    .bind(annotation.source.as_str())
+                    .bind(&annotation.rule)
+                    .bind(annotation.action.as_str())
+                    .bind(&annotation.app_name)
+                    .bind(serde_json::to_string(&annotation.entities).unwrap_or_default())
+                    .bind(serde_json::to_string(&annotation.regions).unwrap_or_default())
+                    .bind(audio_file)
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid()
+                }
+                PendingWrite::FileEvent {
+                    timestamp,
+                    path,
+                    kind,
+                    app_name,
+                    window_name,
+                } => {
+                    sqlx::query(
+                        "INSERT INTO file_events (timestamp, path, kind, app_name, window_name) VALUES (?1, ?2, ?3, ?4, ?5)",
+                    )
+                    .bind(timestamp)
+                    .bind(path)
+                    .bind(kind.as_str())
+                    .bind(app_name)
+                    .bind(window_name)
+                    .execute(&mut *tx)
+                    .await?
+                    .last_insert_rowid()
+                }
+            };
+            ids.push(id);
+        }
+        tx.commit().await?;
+        Ok(ids)
+    }
+
     pub async fn search(
         &self,
         query: &str,
@@ -432,50 +704,82 @@ impl DatabaseManager {
         app_name: Option<&str>,
         window_name: Option<&str>, // Add window_name parameter
     ) -> Result<Vec<SearchResult>, sqlx::Error> {
-        let mut results = Vec::new();
-    
+        let (results, _) = self
+            .search_after(
+                query,
+                content_type,
+                limit,
+                offset,
+                &Cursor::default(),
+                start_time,
+                end_time,
+                app_name,
+                window_name,
+            )
+            .await?;
+        Ok(results)
+    }
+
+    /// Results after `cursor`, newest first, with the cursor of the last result of each source
+    pub async fn search_after(
+        &self,
+        query: &str,
+        content_type: ContentType,
+        limit: u32,
+        offset: u32,
+        cursor: &Cursor,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+    ) -> Result<(Vec<SearchResult>, Cursor), sqlx::Error> {
+        let mut ocr_results = Vec::new();
+        let mut audio_results = Vec::new();
+
         // If app_name is specified, only search OCR content
         if app_name.is_some() || window_name.is_some() {
-            let ocr_results = self
-                .search_ocr(query, limit, offset, start_time, end_time, app_name, window_name) // Add window_name parameter
+            ocr_results = self
+                .search_ocr(query, limit, offset, cursor.get("ocr"), start_time, end_time, app_name, window_name)
                 .await?;
-            results.extend(ocr_results.into_iter().map(SearchResult::OCR));
         } else {
             // If no app_name or window_name is specified, proceed with normal search
             if content_type == ContentType::All || content_type == ContentType::OCR {
-                let ocr_results = self
-                    .search_ocr(query, limit, offset, start_time, end_time, None, None) // Add window_name parameter
+                ocr_results = self
+                    .search_ocr(query, limit, offset, cursor.get("ocr"), start_time, end_time, None, None)
                     .await?;
-                results.extend(ocr_results.into_iter().map(SearchResult::OCR));
             }
-    
+
             if content_type == ContentType::All || content_type == ContentType::Audio {
-                let audio_results = self
-                    .search_audio(query, limit, offset, start_time, end_time)
+                audio_results = self
+                    .search_audio(query, limit, offset, cursor.get("audio"), start_time, end_time)
                     .await?;
-                results.extend(audio_results.into_iter().map(SearchResult::Audio));
             }
         }
-    
-        // Sort results by timestamp in descending order
-        results.sort_by(|a, b| {
-            let timestamp_a = match a {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-                SearchResult::FTS(fts) => fts.frame_timestamp,
-            };
-            let timestamp_b = match b {
-                SearchResult::OCR(ocr) => ocr.timestamp,
-                SearchResult::Audio(audio) => audio.timestamp,
-                SearchResult::FTS(fts) => fts.frame_timestamp,
+
+        // both are newest first, merging them keeps what is left of each after the cursor
+        let mut next_cursor = cursor.clone();
+        let mut results = Vec::new();
+        let mut ocr_results = ocr_results.into_iter().peekable();
+        let mut audio_results = audio_results.into_iter().peekable();
+        while results.len() < limit as usize {
+            let take_ocr = match (ocr_results.peek(), audio_results.peek()) {
+                (Some(ocr), Some(audio)) => ocr.timestamp >= audio.timestamp,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => break,
             };
-            timestamp_b.cmp(&timestamp_a)
-        });
-    
-        // Apply limit after combining and sorting
-        results.truncate(limit as usize);
-    
-        Ok(results)
+            if take_ocr {
+                let ocr = ocr_results.next().unwrap();
+                next_cursor.set("ocr", CursorPosition { timestamp: ocr.timestamp, id: ocr.frame_id });
+                results.push(SearchResult::OCR(ocr));
+            } else {
+                let audio = audio_results.next().unwrap();
+                next_cursor.set("audio", CursorPosition { timestamp: audio.timestamp, id: audio.audio_chunk_id });
+                results.push(SearchResult::Audio(audio));
+            }
+        }
+
+        Ok((results, next_cursor))
     }
 
     pub async fn search_fts(
@@ -499,6 +803,7 @@ impl DatabaseManager {
         JOIN video_chunks vc ON f.video_chunk_id = vc.id
         LEFT JOIN ocr_text o ON f.id = o.frame_id
         WHERE fts.text MATCH ?1 COLLATE NOCASE
+            AND vc.integrity IS NOT 'broken'
         ORDER BY fts.rank
         LIMIT ?2
         "#;
@@ -530,12 +835,14 @@ impl DatabaseManager {
         query: &str,
         limit: u32,
         offset: u32,
+        before: Option<&CursorPosition>,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
         app_name: Option<&str>,
         window_name: Option<&str>, // Add window_name parameter
     ) -> Result<Vec<OCRResult>, sqlx::Error> {
-        let mut sql = r#"
+        let sql = format!(
+            r#"
             SELECT 
                 ocr_text.frame_id,
                 ocr_text.text as ocr_text,
@@ -545,7 +852,10 @@ impl DatabaseManager {
                 frames.offset_index,
                 ocr_text.app_name,
                 ocr_text.ocr_engine,
-                ocr_text.window_name
+                ocr_text.window_name,
+                ocr_text.browser_url,
+                ocr_text.scroll_y,
+                ocr_text.language
             FROM 
                 ocr_text
             JOIN 
@@ -553,44 +863,33 @@ impl DatabaseManager {
             JOIN 
                 video_chunks ON frames.video_chunk_id = video_chunks.id
             WHERE 
-                ocr_text.text LIKE '%' || ?1 || '%' COLLATE NOCASE
+                (ocr_text.text LIKE '%' || ?1 || '%' COLLATE NOCASE{fts})
                 AND ocr_text.text != 'No text found'
                 AND (?2 IS NULL OR frames.timestamp >= ?2)
                 AND (?3 IS NULL OR frames.timestamp <= ?3)
-        "#.to_string();
-    
-        if app_name.is_some() {
-            sql.push_str(" AND ocr_text.app_name = ?6 COLLATE NOCASE");
-        }
-    
-        if window_name.is_some() {
-            sql.push_str(" AND ocr_text.window_name = ?7 COLLATE NOCASE");
-        }
-    
-        sql.push_str(
-            r#"
+                AND video_chunks.integrity IS NOT 'broken'
+                AND (?6 IS NULL OR ocr_text.app_name = ?6 COLLATE NOCASE)
+                AND (?7 IS NULL OR ocr_text.window_name = ?7 COLLATE NOCASE)
+                AND (?8 IS NULL OR frames.timestamp < ?8 OR (frames.timestamp = ?8 AND ocr_text.frame_id < ?9))
             ORDER BY 
-                frames.timestamp DESC
+                frames.timestamp DESC, ocr_text.frame_id DESC
             LIMIT ?4 OFFSET ?5
-            "#,
+        "#,
+            fts = fts_condition(query, "ocr_text_fts", "ocr_text.fts_id")
         );
     
-        let mut query = sqlx::query_as::<_, OCRResult>(&sql)
+        let ocr_results = sqlx::query_as::<_, OCRResult>(&sql)
             .bind(query)
             .bind(start_time)
             .bind(end_time)
             .bind(limit)
-            .bind(offset);
-    
-        if let Some(app_name) = app_name {
-            query = query.bind(app_name);
-        }
-    
-        if let Some(window_name) = window_name {
-            query = query.bind(window_name);
-        }
-    
-        let ocr_results = query.fetch_all(&self.pool).await?;
+            .bind(offset)
+            .bind(app_name)
+            .bind(window_name)
+            .bind(before.map(|position| position.timestamp))
+            .bind(before.map(|position| position.id))
+            .fetch_all(&self.pool)
+            .await?;
         debug!("Fetched OCR results: {:?}", ocr_results);
         Ok(ocr_results)
     }
@@ -600,10 +899,11 @@ impl DatabaseManager {
         query: &str,
         limit: u32,
         offset: u32,
+        before: Option<&CursorPosition>,
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
     ) -> Result<Vec<AudioResult>, sqlx::Error> {
-        sqlx::query_as::<_, AudioResult>(
+        sqlx::query_as::<_, AudioResult>(&format!(
             r#"
             SELECT 
                 audio_transcriptions.audio_chunk_id,
@@ -611,25 +911,31 @@ impl DatabaseManager {
                 audio_transcriptions.timestamp,
                 audio_chunks.file_path,
                 audio_transcriptions.offset_index,
-                audio_transcriptions.transcription_engine
+                audio_transcriptions.transcription_engine,
+                audio_transcriptions.language
             FROM 
                 audio_transcriptions
             JOIN 
                 audio_chunks ON audio_transcriptions.audio_chunk_id = audio_chunks.id
             WHERE 
-                audio_transcriptions.transcription LIKE '%' || ?1 || '%' COLLATE NOCASE
+                (audio_transcriptions.transcription LIKE '%' || ?1 || '%' COLLATE NOCASE{fts})
                 AND (?2 IS NULL OR audio_transcriptions.timestamp >= ?2)
                 AND (?3 IS NULL OR audio_transcriptions.timestamp <= ?3)
+                AND audio_chunks.integrity IS NOT 'broken'
+                AND (?6 IS NULL OR audio_transcriptions.timestamp < ?6 OR (audio_transcriptions.timestamp = ?6 AND audio_transcriptions.audio_chunk_id < ?7))
             ORDER BY 
-                audio_transcriptions.timestamp DESC
+                audio_transcriptions.timestamp DESC, audio_transcriptions.audio_chunk_id DESC
             LIMIT ?4 OFFSET ?5
             "#,
-        )
+            fts = fts_condition(query, "audio_transcriptions_fts", "audio_transcriptions.id")
+        ))
         .bind(query)
         .bind(start_time)
         .bind(end_time)
         .bind(limit)
         .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
         .fetch_all(&self.pool)
         .await
     }
@@ -653,6 +959,82 @@ impl DatabaseManager {
         .await
     }
 
+    /// When the frame was captured, which its thumbnails are named after
+    pub async fn get_frame_timestamp(
+        &self,
+        frame_id: i64,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT timestamp FROM frames WHERE id = ?1")
+            .bind(frame_id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn get_frame_perceptual_hash(&self, frame_id: i64) -> Result<Option<u64>, sqlx::Error> {
+        let hash: Option<Option<i64>> =
+            sqlx::query_scalar("SELECT perceptual_hash FROM frames WHERE id = ?1")
+                .bind(frame_id)
+                .fetch_optional(&self.pool)
+                .await?;
+        Ok(hash.flatten().map(|hash| hash as u64))
+    }
+
+    pub async fn set_frame_perceptual_hash(&self, frame_id: i64, hash: u64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE frames SET perceptual_hash = ?1 WHERE id = ?2")
+            .bind(hash as i64)
+            .bind(frame_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The hashed frames after `after_id`, oldest first
+    pub async fn get_frame_perceptual_hashes(
+        &self,
+        after_id: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, u64)>, sqlx::Error> {
+        let hashes: Vec<(i64, i64)> = sqlx::query_as(
+            "SELECT id, perceptual_hash FROM frames WHERE id > ?1 AND perceptual_hash IS NOT NULL ORDER BY id ASC LIMIT ?2",
+        )
+        .bind(after_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(hashes
+            .into_iter()
+            .map(|(id, hash)| (id, hash as u64))
+            .collect())
+    }
+
+    /// Time, app and window of a frame
+    pub async fn get_frame_window(
+        &self,
+        frame_id: i64,
+    ) -> Result<Option<(DateTime<Utc>, String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT frames.timestamp, COALESCE(ocr_text.app_name, ''), COALESCE(ocr_text.window_name, '')
+            FROM frames
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE frames.id = ?1
+            "#,
+        )
+        .bind(frame_id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// The text read off a frame, its windows' one after the other
+    pub async fn get_frame_ocr_text(&self, frame_id: i64) -> Result<String, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COALESCE(group_concat(text, char(10)), '') FROM ocr_text WHERE frame_id = ?1",
+        )
+        .bind(frame_id)
+        .fetch_one(&self.pool)
+        .await
+    }
+
     pub async fn count_search_results(
         &self,
         query: &str,
@@ -739,14 +1121,18 @@ impl DatabaseManager {
         app_name: Option<&str>,
         window_name: Option<&str>, // Add window_name parameter
     ) -> Result<usize, sqlx::Error> {
-        let mut sql = r#"
+        let mut sql = format!(
+            r#"
             SELECT COUNT(*)
             FROM ocr_text
             JOIN frames ON ocr_text.frame_id = frames.id
-            WHERE text LIKE '%' || ?1 || '%' COLLATE NOCASE
+            WHERE (text LIKE '%' || ?1 || '%' COLLATE NOCASE{fts})
                 AND (?2 IS NULL OR frames.timestamp >= ?2)
                 AND (?3 IS NULL OR frames.timestamp <= ?3)
-        "#.to_string();
+                AND frames.video_chunk_id NOT IN (SELECT id FROM video_chunks WHERE integrity = 'broken')
+        "#,
+            fts = fts_condition(query, "ocr_text_fts", "ocr_text.fts_id")
+        );
 
         if app_name.is_some() {
             sql.push_str(" AND ocr_text.app_name = ?6 COLLATE NOCASE");
@@ -778,15 +1164,17 @@ impl DatabaseManager {
         start_time: Option<DateTime<Utc>>,
         end_time: Option<DateTime<Utc>>,
     ) -> Result<usize, sqlx::Error> {
-        let (count,): (i64,) = sqlx::query_as(
+        let (count,): (i64,) = sqlx::query_as(&format!(
             r#"
             SELECT COUNT(*)
             FROM audio_transcriptions
-            WHERE transcription LIKE '%' || ?1 || '%' COLLATE NOCASE
+            WHERE (transcription LIKE '%' || ?1 || '%' COLLATE NOCASE{fts})
                 AND (?2 IS NULL OR timestamp >= ?2)
                 AND (?3 IS NULL OR timestamp <= ?3)
+                AND audio_chunk_id NOT IN (SELECT id FROM audio_chunks WHERE integrity = 'broken')
             "#,
-        )
+            fts = fts_condition(query, "audio_transcriptions_fts", "audio_transcriptions.id")
+        ))
         .bind(query)
         .bind(start_time)
         .bind(end_time)
@@ -811,6 +1199,75 @@ impl DatabaseManager {
         Ok((latest_frame.map(|f| f.0), latest_audio.map(|a| a.0)))
     }
 
+    /// Texts not tagged with their language yet, oldest first: the rowid and text of the OCR,
+    /// the id and transcription of the audio
+    pub async fn get_unindexed_texts(
+        &self,
+        source: ContentSource,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let sql = match source {
+            ContentSource::Screen => {
+                "SELECT rowid, text FROM ocr_text WHERE language IS NULL ORDER BY frame_id LIMIT ?1"
+            }
+            ContentSource::Audio => {
+                "SELECT id, transcription FROM audio_transcriptions WHERE language IS NULL ORDER BY id LIMIT ?1"
+            }
+        };
+        sqlx::query_as(sql).bind(limit).fetch_all(&self.pool).await
+    }
+
+    /// Tags a text of `get_unindexed_texts` with its language, `indexed_text` goes to its fts
+    /// table. Not searchable by words without it
+    pub async fn index_text(
+        &self,
+        source: ContentSource,
+        id: i64,
+        language: &str,
+        indexed_text: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        match source {
+            ContentSource::Screen => {
+                let fts_id = match indexed_text {
+                    Some(text) => Some(
+                        sqlx::query("INSERT INTO ocr_text_fts (text) VALUES (?1)")
+                            .bind(text)
+                            .execute(&mut *tx)
+                            .await?
+                            .last_insert_rowid(),
+                    ),
+                    None => None,
+                };
+                sqlx::query("UPDATE ocr_text SET language = ?1, fts_id = ?2 WHERE rowid = ?3")
+                    .bind(language)
+                    .bind(fts_id)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            ContentSource::Audio => {
+                sqlx::query("DELETE FROM audio_transcriptions_fts WHERE rowid = ?1")
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+                if let Some(text) = indexed_text {
+                    sqlx::query("INSERT INTO audio_transcriptions_fts (rowid, text) VALUES (?1, ?2)")
+                        .bind(id)
+                        .bind(text)
+                        .execute(&mut *tx)
+                        .await?;
+                }
+                sqlx::query("UPDATE audio_transcriptions SET language = ?1 WHERE id = ?2")
+                    .bind(language)
+                    .bind(id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+        }
+        tx.commit().await
+    }
+
     // Modify the insert_chunked_text method to handle both OCR and audio transcriptions
     pub async fn insert_chunked_text(
         &self,
@@ -822,31 +1279,7 @@ impl DatabaseManager {
         source: ContentSource,
     ) -> Result<(), sqlx::Error> {
         let mut tx = self.pool.begin().await?;
-
-        // Insert or get the text_id
-        let text_id: i64 = sqlx::query_scalar(
-            "INSERT INTO chunked_text_index (text) VALUES (?1) ON CONFLICT(text) DO UPDATE SET text=text RETURNING text_id",
-        )
-        .bind(text)
-        .fetch_one(&mut *tx)
-        .await?;
-
-        // Insert the entry into chunked_text_entries
-        let query = match source {
-            ContentSource::Audio => "INSERT INTO chunked_text_entries (text_id, audio_chunk_id, timestamp, engine, chunking_engine, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            ContentSource::Screen => "INSERT INTO chunked_text_entries (text_id, frame_id, timestamp, engine, chunking_engine, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-        };
-
-        sqlx::query(query)
-            .bind(text_id)
-            .bind(id)
-            .bind(timestamp)
-            .bind(engine)
-            .bind(chunking_engine)
-            .bind(source.to_string())
-            .execute(&mut *tx)
-            .await?;
-
+        insert_chunked_entry(&mut tx, id, text, timestamp, engine, chunking_engine, source).await?;
         tx.commit().await?;
         Ok(())
     }
@@ -979,14 +1412,4582 @@ impl DatabaseManager {
             .map(|_| ())
             .map_err(|e| DatabaseError(e.to_string()))
     }
-}
 
-impl Clone for DatabaseManager {
-    fn clone(&self) -> Self {
-        DatabaseManager {
-            pool: self.pool.clone(),
-        }
+    pub async fn get_audio_timestamps_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT timestamp FROM audio_transcriptions WHERE timestamp > ?1 ORDER BY timestamp ASC",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn insert_meeting_summary(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        summary: &str,
+        decisions: &[String],
+        action_items: &[ActionItem],
+        llm_model: &str,
+        prompt_version: u32,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO meeting_summaries (start_time, end_time, summary, decisions, action_items, llm_model, prompt_version) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(summary)
+        .bind(serde_json::to_string(decisions).unwrap_or_else(|_| "[]".to_string()))
+        .bind(serde_json::to_string(action_items).unwrap_or_else(|_| "[]".to_string()))
+        .bind(llm_model)
+        .bind(prompt_version)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_last_meeting_end_time(&self) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(end_time) FROM meeting_summaries")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Meetings whose summary, calendar event title or attendees contain `query`
+    pub async fn get_meeting_summaries(
+        &self,
+        query: Option<&str>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<MeetingSummary>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, summary, decisions, action_items, llm_model, prompt_version, created_at, calendar_title, calendar_attendees
+            FROM meeting_summaries
+            WHERE (?1 IS NULL
+                OR summary LIKE '%' || ?1 || '%'
+                OR calendar_title LIKE '%' || ?1 || '%'
+                OR calendar_attendees LIKE '%' || ?1 || '%')
+                AND (?4 IS NULL OR start_time < ?4 OR (start_time = ?4 AND id < ?5))
+            ORDER BY start_time DESC, id DESC
+            LIMIT ?2 OFFSET ?3
+            "#,
+        )
+        .bind(query)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(meeting_summary_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_meeting_summary(&self, id: i64) -> Result<Option<MeetingSummary>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, summary, decisions, action_items, llm_model, prompt_version, created_at, calendar_title, calendar_attendees
+            FROM meeting_summaries
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .map(meeting_summary_from_row)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Meetings that started between the two times, oldest first
+    pub async fn get_meeting_summaries_between(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MeetingSummary>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, summary, decisions, action_items, llm_model, prompt_version, created_at, calendar_title, calendar_attendees
+            FROM meeting_summaries
+            WHERE start_time >= ?1 AND start_time < ?2
+            ORDER BY start_time ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(meeting_summary_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_meeting_summaries(&self, query: Option<&str>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM meeting_summaries
+            WHERE ?1 IS NULL
+                OR summary LIKE '%' || ?1 || '%'
+                OR calendar_title LIKE '%' || ?1 || '%'
+                OR calendar_attendees LIKE '%' || ?1 || '%'
+            "#,
+        )
+        .bind(query)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Meetings overlapping the two times that no calendar event was matched to yet
+    pub async fn get_unlabeled_meeting_summaries(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<MeetingSummary>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, summary, decisions, action_items, llm_model, prompt_version, created_at, calendar_title, calendar_attendees
+            FROM meeting_summaries
+            WHERE calendar_title IS NULL AND end_time >= ?1 AND start_time <= ?2
+            ORDER BY start_time ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(meeting_summary_from_row)
+        .fetch_all(&self.pool)
+        .await
     }
+
+    pub async fn set_meeting_calendar_event(
+        &self,
+        id: i64,
+        title: &str,
+        attendees: &[String],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE meeting_summaries SET calendar_title = ?1, calendar_attendees = ?2 WHERE id = ?3",
+        )
+        .bind(title)
+        .bind(serde_json::to_string(attendees).unwrap_or_else(|_| "[]".to_string()))
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Where the worker called `name` stopped, none before its first run
+    pub async fn get_checkpoint(&self, name: &str) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT position FROM processing_checkpoints WHERE name = ?1")
+            .bind(name)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn set_checkpoint(&self, name: &str, position: i64) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO processing_checkpoints (name, position, updated_at) VALUES (?1, ?2, ?3) ON CONFLICT(name) DO UPDATE SET position = excluded.position, updated_at = excluded.updated_at",
+        )
+        .bind(name)
+        .bind(position)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Journals an audio chunk before it is transcribed, so it is transcribed again if the
+    /// server stops first
+    pub async fn insert_pending_transcription(
+        &self,
+        file_path: &str,
+        device: &str,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO pending_transcriptions (file_path, device, recorded_at) VALUES (?1, ?2, ?3)",
+        )
+        .bind(file_path)
+        .bind(device)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The journaled audio chunks as (file path, device), oldest first
+    pub async fn get_pending_transcriptions(&self) -> Result<Vec<(String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT file_path, device FROM pending_transcriptions ORDER BY recorded_at, file_path",
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn remove_pending_transcription(&self, file_path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM pending_transcriptions WHERE file_path = ?1")
+            .bind(file_path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_max_frame_id(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COALESCE(MAX(id), 0) FROM frames")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// The windows of the next `frames` frames with text after `frame_id`, a frame is never
+    /// split across two pages
+    pub async fn get_ocr_text_after_frame(
+        &self,
+        frame_id: i64,
+        frames: u32,
+    ) -> Result<Vec<OCRResult>, sqlx::Error> {
+        sqlx::query_as::<_, OCRResult>(
+            r#"
+            SELECT 
+                ocr_text.frame_id,
+                ocr_text.text as ocr_text,
+                ocr_text.text_json,
+                frames.timestamp,
+                video_chunks.file_path,
+                frames.offset_index,
+                ocr_text.app_name,
+                ocr_text.ocr_engine,
+                ocr_text.window_name,
+                ocr_text.browser_url,
+                ocr_text.scroll_y,
+                ocr_text.language
+            FROM 
+                ocr_text
+            JOIN 
+                frames ON ocr_text.frame_id = frames.id
+            JOIN 
+                video_chunks ON frames.video_chunk_id = video_chunks.id
+            WHERE 
+                ocr_text.frame_id IN (
+                    SELECT DISTINCT frame_id FROM ocr_text
+                    WHERE frame_id > ?1
+                    ORDER BY frame_id ASC
+                    LIMIT ?2
+                )
+            ORDER BY 
+                ocr_text.frame_id ASC
+            "#,
+        )
+        .bind(frame_id)
+        .bind(frames)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn insert_extraction_rule(
+        &self,
+        name: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        kind: ExtractionKind,
+        pattern: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO extraction_rules (name, app_name, window_name, kind, pattern) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(name)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(kind.as_str())
+        .bind(pattern)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_extraction_rules(
+        &self,
+        enabled_only: bool,
+    ) -> Result<Vec<ExtractionRule>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, name, app_name, window_name, kind, pattern, enabled, created_at
+            FROM extraction_rules
+            WHERE (?1 = 0 OR enabled = 1)
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(enabled_only)
+        .map(|row: SqliteRow| {
+            let kind: String = row.get("kind");
+            ExtractionRule {
+                id: row.get("id"),
+                name: row.get("name"),
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+                kind: if kind == "llm" {
+                    ExtractionKind::Llm
+                } else {
+                    ExtractionKind::Regex
+                },
+                pattern: row.get("pattern"),
+                enabled: row.get("enabled"),
+                created_at: row.get("created_at"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn delete_extraction_rule(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM extracted_records WHERE rule_id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let deleted = sqlx::query("DELETE FROM extraction_rules WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    /// Returns None if the rule already extracted the same data
+    pub async fn insert_extracted_record(
+        &self,
+        rule_id: i64,
+        frame_id: i64,
+        timestamp: DateTime<Utc>,
+        app_name: &str,
+        window_name: &str,
+        data: &serde_json::Value,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO extracted_records (rule_id, frame_id, timestamp, app_name, window_name, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .bind(rule_id)
+        .bind(frame_id)
+        .bind(timestamp)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(data.to_string())
+        .execute(&self.pool)
+        .await?;
+        Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+    }
+
+    pub async fn get_extracted_records(
+        &self,
+        rule_id: Option<i64>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<ExtractedRecord>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, rule_id, frame_id, timestamp, app_name, window_name, data
+            FROM extracted_records
+            WHERE (?1 IS NULL OR rule_id = ?1)
+                AND (?4 IS NULL OR timestamp < ?4 OR (timestamp = ?4 AND id < ?5))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?2 OFFSET ?3
+            "#,
+        )
+        .bind(rule_id)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let data: String = row.get("data");
+            ExtractedRecord {
+                id: row.get("id"),
+                rule_id: row.get("rule_id"),
+                frame_id: row.get("frame_id"),
+                timestamp: row.get("timestamp"),
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+                data: serde_json::from_str(&data).unwrap_or_default(),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Records between the two times with the name of their rule, oldest first
+    pub async fn get_named_extracted_records_between(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(String, ExtractedRecord)>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT er.id, er.rule_id, er.frame_id, er.timestamp, er.app_name, er.window_name, er.data, r.name
+            FROM extracted_records er
+            JOIN extraction_rules r ON er.rule_id = r.id
+            WHERE er.timestamp >= ?1 AND er.timestamp < ?2
+            ORDER BY er.timestamp ASC
+            LIMIT ?3
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .map(|row: SqliteRow| {
+            let data: String = row.get("data");
+            (
+                row.get("name"),
+                ExtractedRecord {
+                    id: row.get("id"),
+                    rule_id: row.get("rule_id"),
+                    frame_id: row.get("frame_id"),
+                    timestamp: row.get("timestamp"),
+                    app_name: row.get("app_name"),
+                    window_name: row.get("window_name"),
+                    data: serde_json::from_str(&data).unwrap_or_default(),
+                },
+            )
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_extracted_records(&self, rule_id: Option<i64>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM extracted_records WHERE (?1 IS NULL OR rule_id = ?1)")
+            .bind(rule_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn insert_watch(
+        &self,
+        name: &str,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        kind: WatchKind,
+        pattern: &str,
+        source: WatchSource,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO watches (name, app_name, window_name, kind, pattern, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .bind(name)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(kind.as_str())
+        .bind(pattern)
+        .bind(source.as_str())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_watches(&self, enabled_only: bool) -> Result<Vec<Watch>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, name, app_name, window_name, kind, pattern, source, enabled, created_at
+            FROM watches
+            WHERE (?1 = 0 OR enabled = 1)
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(enabled_only)
+        .map(|row: SqliteRow| {
+            let kind: String = row.get("kind");
+            let source: String = row.get("source");
+            Watch {
+                id: row.get("id"),
+                name: row.get("name"),
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+                kind: if kind == "keywords" {
+                    WatchKind::Keywords
+                } else {
+                    WatchKind::Regex
+                },
+                pattern: row.get("pattern"),
+                source: WatchSource::parse(&source),
+                enabled: row.get("enabled"),
+                created_at: row.get("created_at"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn delete_watch(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM watch_alerts WHERE watch_id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let deleted = sqlx::query("DELETE FROM watches WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    pub async fn insert_watch_alert(&self, alert: &WatchAlert) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO watch_alerts (watch_id, timestamp, source, app_name, window_name, matched_text, excerpt) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(alert.watch_id)
+        .bind(alert.timestamp)
+        .bind(alert.source.as_str())
+        .bind(&alert.app_name)
+        .bind(&alert.window_name)
+        .bind(&alert.matched_text)
+        .bind(&alert.excerpt)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_watch_alerts(
+        &self,
+        watch_id: Option<i64>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<WatchAlert>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT wa.id, wa.watch_id, w.name, wa.timestamp, wa.source, wa.app_name, wa.window_name, wa.matched_text, wa.excerpt
+            FROM watch_alerts wa
+            JOIN watches w ON wa.watch_id = w.id
+            WHERE (?1 IS NULL OR wa.watch_id = ?1)
+                AND (?4 IS NULL OR wa.timestamp < ?4 OR (wa.timestamp = ?4 AND wa.id < ?5))
+            ORDER BY wa.timestamp DESC, wa.id DESC
+            LIMIT ?2 OFFSET ?3
+            "#,
+        )
+        .bind(watch_id)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let source: String = row.get("source");
+            WatchAlert {
+                id: row.get("id"),
+                watch_id: row.get("watch_id"),
+                watch_name: row.get("name"),
+                timestamp: row.get("timestamp"),
+                source: WatchSource::parse(&source),
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+                matched_text: row.get("matched_text"),
+                excerpt: row.get("excerpt"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_watch_alerts(&self, watch_id: Option<i64>) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM watch_alerts WHERE (?1 IS NULL OR watch_id = ?1)")
+            .bind(watch_id)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn insert_llm_usage(
+        &self,
+        source: &str,
+        model: &str,
+        kind: &str,
+        usage: &TokenUsage,
+        cost_usd: f64,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO llm_usage (timestamp, source, model, kind, prompt_tokens, completion_tokens, cost_usd) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(Utc::now())
+        .bind(source)
+        .bind(model)
+        .bind(kind)
+        .bind(usage.prompt_tokens as i64)
+        .bind(usage.completion_tokens as i64)
+        .bind(cost_usd)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_llm_cost_since(&self, since: DateTime<Utc>) -> Result<f64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COALESCE(SUM(cost_usd), 0.0) FROM llm_usage WHERE timestamp >= ?1")
+            .bind(since)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn get_llm_cache(&self, key: &str) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT response FROM llm_cache WHERE key = ?1 AND expires_at > ?2")
+            .bind(key)
+            .bind(Utc::now())
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    pub async fn insert_llm_cache(
+        &self,
+        key: &str,
+        api_url: &str,
+        model: &str,
+        kind: &str,
+        response: &str,
+        expires_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO llm_cache (key, api_url, model, kind, response, created_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(key)
+        .bind(api_url)
+        .bind(model)
+        .bind(kind)
+        .bind(response)
+        .bind(Utc::now())
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Removes the cached answers of a model (all models if None) and the expired ones,
+    /// returns how many entries were removed
+    pub async fn invalidate_llm_cache(&self, model: Option<&str>) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            "DELETE FROM llm_cache WHERE ?1 IS NULL OR model = ?1 OR expires_at <= ?2",
+        )
+        .bind(model)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn count_llm_cache(&self) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM llm_cache WHERE expires_at > ?1")
+            .bind(Utc::now())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Usage aggregated per day, source (task or pipe) and model
+    pub async fn get_llm_usage_per_day(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<LlmUsageSummary>, sqlx::Error> {
+        sqlx::query_as::<_, LlmUsageSummary>(
+            r#"
+            SELECT 
+                DATE(timestamp) as day,
+                source,
+                model,
+                COUNT(*) as calls,
+                SUM(prompt_tokens) as prompt_tokens,
+                SUM(completion_tokens) as completion_tokens,
+                SUM(cost_usd) as cost_usd
+            FROM llm_usage
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+            GROUP BY day, source, model
+            ORDER BY day DESC, cost_usd DESC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// (timestamp, app, window) of the focused window of each frame
+    pub async fn get_focused_windows_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT frames.timestamp, ocr_text.app_name, COALESCE(ocr_text.window_name, '')
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp > ?1 AND ocr_text.focused = 1
+            ORDER BY frames.timestamp ASC
+            "#,
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// (app, window) of the last focused window recorded at most `max_age` before `timestamp`
+    pub async fn get_focused_window_at(
+        &self,
+        timestamp: DateTime<Utc>,
+        max_age: chrono::Duration,
+    ) -> Result<Option<(String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT ocr_text.app_name, COALESCE(ocr_text.window_name, '')
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp <= ?1 AND frames.timestamp >= ?2 AND ocr_text.focused = 1
+            ORDER BY frames.timestamp DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(timestamp)
+        .bind(timestamp - max_age)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn insert_file_event(
+        &self,
+        timestamp: DateTime<Utc>,
+        path: &str,
+        kind: FileEventKind,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO file_events (timestamp, path, kind, app_name, window_name) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(timestamp)
+        .bind(path)
+        .bind(kind.as_str())
+        .bind(app_name)
+        .bind(window_name)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Most recent first. `path` is matched anywhere in the path, case insensitive
+    pub async fn get_file_events(
+        &self,
+        path: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<FileEvent>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, path, kind, app_name, window_name
+            FROM file_events
+            WHERE (?1 IS NULL OR instr(LOWER(path), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR LOWER(app_name) = LOWER(?2))
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+                AND (?7 IS NULL OR timestamp < ?7 OR (timestamp = ?7 AND id < ?8))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(path)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let kind: String = row.get("kind");
+            FileEvent {
+                id: row.get("id"),
+                timestamp: row.get("timestamp"),
+                path: row.get("path"),
+                kind: kind.parse().unwrap_or(FileEventKind::Save),
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_file_events(
+        &self,
+        path: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM file_events
+            WHERE (?1 IS NULL OR instr(LOWER(path), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR LOWER(app_name) = LOWER(?2))
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            "#,
+        )
+        .bind(path)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn insert_window_event(
+        &self,
+        timestamp: DateTime<Utc>,
+        app_name: &str,
+        window_name: &str,
+        kind: WindowEventKind,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO window_events (timestamp, app_name, window_name, kind) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(timestamp)
+        .bind(app_name)
+        .bind(window_name)
+        .bind(kind.as_str())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn end_window_event(&self, id: i64, ended_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE window_events SET ended_at = ?1 WHERE id = ?2")
+            .bind(ended_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Most recent first. `window_name` is matched anywhere in the title, case insensitive.
+    /// Windows still focused at `start_time` are included
+    pub async fn get_window_events(
+        &self,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<WindowEvent>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, ended_at, app_name, window_name, kind
+            FROM window_events
+            WHERE (?1 IS NULL OR LOWER(app_name) = LOWER(?1))
+                AND (?2 IS NULL OR instr(LOWER(window_name), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR ended_at IS NULL OR ended_at >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+                AND (?7 IS NULL OR timestamp < ?7 OR (timestamp = ?7 AND id < ?8))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(app_name)
+        .bind(window_name)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let kind: String = row.get("kind");
+            let timestamp: DateTime<Utc> = row.get("timestamp");
+            let ended_at: Option<DateTime<Utc>> = row.get("ended_at");
+            WindowEvent {
+                id: row.get("id"),
+                timestamp,
+                ended_at,
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+                kind: kind.parse().unwrap_or(WindowEventKind::Focus),
+                duration_secs: ended_at
+                    .map(|ended_at| (ended_at - timestamp).num_milliseconds() as f64 / 1000.0),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_window_events(
+        &self,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM window_events
+            WHERE (?1 IS NULL OR LOWER(app_name) = LOWER(?1))
+                AND (?2 IS NULL OR instr(LOWER(window_name), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR ended_at IS NULL OR ended_at >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            "#,
+        )
+        .bind(app_name)
+        .bind(window_name)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Focus changes from an app to another between the two times, with --window-history
+    pub async fn count_app_switches(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT COUNT(*) FROM window_events WHERE kind = 'focus' AND timestamp >= ?1 AND timestamp < ?2",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn upsert_weekly_metrics(&self, metrics: &WeekMetrics) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO weekly_metrics (week_start, metrics, computed_at) VALUES (?1, ?2, ?3) ON CONFLICT(week_start) DO UPDATE SET metrics = excluded.metrics, computed_at = excluded.computed_at",
+        )
+        .bind(metrics.week_start)
+        .bind(json!(metrics).to_string())
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// The weeks starting from `first` to `last` included, oldest first
+    pub async fn get_weekly_metrics(
+        &self,
+        first: NaiveDate,
+        last: NaiveDate,
+    ) -> Result<Vec<WeekMetrics>, sqlx::Error> {
+        let metrics: Vec<String> = sqlx::query_scalar(
+            "SELECT metrics FROM weekly_metrics WHERE week_start >= ?1 AND week_start <= ?2 ORDER BY week_start ASC",
+        )
+        .bind(first)
+        .bind(last)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(metrics
+            .iter()
+            .filter_map(|metrics| serde_json::from_str(metrics).ok())
+            .collect())
+    }
+
+    /// Returns how many were stored. A command the hooks sent adds its directory and exit code
+    /// to the same one read from the history before, the commands stored already are skipped
+    pub async fn insert_shell_commands(
+        &self,
+        commands: &[NewShellCommand],
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut stored = 0;
+        for command in commands {
+            let result = sqlx::query(
+                r#"
+                INSERT INTO shell_commands (timestamp, ended_at, command, cwd, exit_code, shell, source)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                ON CONFLICT (shell, timestamp, command) DO UPDATE SET
+                    ended_at = COALESCE(excluded.ended_at, ended_at),
+                    cwd = excluded.cwd,
+                    exit_code = excluded.exit_code,
+                    source = excluded.source
+                WHERE excluded.source = 'hook' AND shell_commands.source = 'history'
+                "#,
+            )
+            .bind(command.timestamp)
+            .bind(command.ended_at)
+            .bind(&command.command)
+            .bind(&command.cwd)
+            .bind(command.exit_code)
+            .bind(command.shell.as_str())
+            .bind(command.source.as_str())
+            .execute(&mut *tx)
+            .await?;
+            stored += result.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    pub async fn get_shell_commands(
+        &self,
+        query: Option<&str>,
+        cwd: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<ShellCommand>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, ended_at, command, cwd, exit_code, shell, source
+            FROM shell_commands
+            WHERE (?1 IS NULL OR instr(LOWER(command), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR cwd = ?2 OR cwd LIKE ?2 || '/%')
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+                AND (?7 IS NULL OR timestamp < ?7 OR (timestamp = ?7 AND id < ?8))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(query)
+        .bind(cwd)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let source: String = row.get("source");
+            let timestamp: DateTime<Utc> = row.get("timestamp");
+            let ended_at: Option<DateTime<Utc>> = row.get("ended_at");
+            ShellCommand {
+                id: row.get("id"),
+                timestamp,
+                ended_at,
+                duration_secs: ended_at
+                    .map(|ended_at| (ended_at - timestamp).num_milliseconds() as f64 / 1000.0),
+                command: row.get("command"),
+                cwd: row.get("cwd"),
+                exit_code: row.get("exit_code"),
+                shell: row.get("shell"),
+                source: source.parse().unwrap_or(ShellCommandSource::History),
+                frame_ids: Vec::new(),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_shell_commands(
+        &self,
+        query: Option<&str>,
+        cwd: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM shell_commands
+            WHERE (?1 IS NULL OR instr(LOWER(command), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR cwd = ?2 OR cwd LIKE ?2 || '/%')
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            "#,
+        )
+        .bind(query)
+        .bind(cwd)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// (frame id, app name, window name) of the windows captured between the two times, the
+    /// oldest first
+    pub async fn get_frame_windows_between(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<Vec<(i64, String, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT ocr_text.frame_id, ocr_text.app_name, ocr_text.window_name
+            FROM ocr_text
+            JOIN frames ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp <= ?2
+            ORDER BY frames.timestamp ASC, ocr_text.frame_id ASC
+            "#,
+        )
+        .bind(start)
+        .bind(end)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn insert_window_layout(
+        &self,
+        timestamp: DateTime<Utc>,
+        monitor_id: u32,
+        windows: &[LayoutWindow],
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO window_layouts (timestamp, monitor_id, windows) VALUES (?1, ?2, ?3)",
+        )
+        .bind(timestamp)
+        .bind(monitor_id)
+        .bind(json!(windows).to_string())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// The layout each monitor had at `at`, the latest stored before it
+    pub async fn get_window_layouts_at(
+        &self,
+        at: DateTime<Utc>,
+    ) -> Result<Vec<WindowLayout>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, timestamp, monitor_id, windows
+            FROM window_layouts
+            WHERE id IN (
+                SELECT (
+                    SELECT latest.id FROM window_layouts AS latest
+                    WHERE latest.monitor_id = monitors.monitor_id AND latest.timestamp <= ?1
+                    ORDER BY latest.timestamp DESC, latest.id DESC
+                    LIMIT 1
+                )
+                FROM (SELECT DISTINCT monitor_id FROM window_layouts) AS monitors
+            )
+            ORDER BY monitor_id ASC
+            "#,
+        )
+        .bind(at)
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(rows.into_iter().map(window_layout_from_row).collect())
+    }
+
+    pub async fn get_window_layout(&self, id: i64) -> Result<Option<WindowLayout>, sqlx::Error> {
+        let row = sqlx::query(
+            "SELECT id, timestamp, monitor_id, windows FROM window_layouts WHERE id = ?1",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(row.map(window_layout_from_row))
+    }
+
+    /// The frames that showed text in the region, newest first, with the lines they showed
+    /// there
+    pub async fn get_region_texts(
+        &self,
+        filter: &RegionFilter,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<RegionText>, sqlx::Error> {
+        let (min_x, max_x, min_y, max_y) = region_bounds(&filter.region);
+        let mut texts = sqlx::query(&format!(
+            r#"
+            SELECT frames.id, frames.timestamp, ocr_text.app_name, ocr_text.window_name
+            {REGION_FRAMES}
+                AND (?13 IS NULL OR frames.timestamp < ?13 OR (frames.timestamp = ?13 AND frames.id < ?14))
+            ORDER BY frames.timestamp DESC, frames.id DESC
+            LIMIT ?11 OFFSET ?12
+            "#
+        ))
+        .bind(min_x)
+        .bind(max_x)
+        .bind(min_y)
+        .bind(max_y)
+        .bind(filter.start_time.map(unix_seconds))
+        .bind(filter.end_time.map(unix_seconds))
+        .bind(filter.monitor_id)
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.app_name)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| RegionText {
+            frame_id: row.get("id"),
+            timestamp: row.get("timestamp"),
+            monitor_id: None,
+            app_name: row.get("app_name"),
+            window_name: row.get("window_name"),
+            text: String::new(),
+            boxes: Vec::new(),
+        })
+        .fetch_all(&self.pool)
+        .await?;
+
+        let frame_ids = json!(texts.iter().map(|text| text.frame_id).collect::<Vec<_>>());
+        let rows = sqlx::query(
+            r#"
+            SELECT frame_id, monitor_id, text, confidence, x, y, width, height
+            FROM ocr_boxes
+            WHERE frame_id IN (SELECT value FROM json_each(?1))
+                AND x + width / 2 BETWEEN ?2 AND ?3
+                AND y + height / 2 BETWEEN ?4 AND ?5
+            ORDER BY frame_id, y, x
+            "#,
+        )
+        .bind(frame_ids.to_string())
+        .bind(min_x)
+        .bind(max_x)
+        .bind(min_y)
+        .bind(max_y)
+        .fetch_all(&self.pool)
+        .await?;
+        for row in rows {
+            let frame_id: i64 = row.get("frame_id");
+            let Some(text) = texts.iter_mut().find(|text| text.frame_id == frame_id) else {
+                continue;
+            };
+            text.monitor_id = row.get::<Option<i64>, _>("monitor_id").map(|id| id as u32);
+            text.boxes.push(OcrBox {
+                text: row.get("text"),
+                confidence: row.get("confidence"),
+                rect: Rect {
+                    x: row.get("x"),
+                    y: row.get("y"),
+                    width: row.get("width"),
+                    height: row.get("height"),
+                },
+            });
+        }
+        for text in &mut texts {
+            text.text = text
+                .boxes
+                .iter()
+                .map(|ocr_box| ocr_box.text.as_str())
+                .collect::<Vec<_>>()
+                .join("\n");
+        }
+        Ok(texts)
+    }
+
+    pub async fn count_region_texts(&self, filter: &RegionFilter) -> Result<i64, sqlx::Error> {
+        let (min_x, max_x, min_y, max_y) = region_bounds(&filter.region);
+        sqlx::query_scalar(&format!("SELECT COUNT(*) {REGION_FRAMES}"))
+            .bind(min_x)
+            .bind(max_x)
+            .bind(min_y)
+            .bind(max_y)
+            .bind(filter.start_time.map(unix_seconds))
+            .bind(filter.end_time.map(unix_seconds))
+            .bind(filter.monitor_id)
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.app_name)
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn insert_editor_context(
+        &self,
+        beacon: &EditorBeacon,
+        timestamp: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO editor_contexts (timestamp, ended_at, editor, project, repository, git_branch, file, language)
+            VALUES (?1, ?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+        )
+        .bind(timestamp)
+        .bind(&beacon.editor)
+        .bind(&beacon.project)
+        .bind(&beacon.repository)
+        .bind(&beacon.git_branch)
+        .bind(&beacon.file)
+        .bind(&beacon.language)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn end_editor_context(&self, id: i64, ended_at: DateTime<Utc>) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE editor_contexts SET ended_at = ?1 WHERE id = ?2")
+            .bind(ended_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The context of the editor that ended last
+    pub async fn get_latest_editor_context(
+        &self,
+        editor: &str,
+    ) -> Result<Option<EditorContext>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, ended_at, editor, project, repository, git_branch, file, language
+            FROM editor_contexts
+            WHERE editor = ?1
+            ORDER BY ended_at DESC, id DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(editor)
+        .map(editor_context_from_row)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Most recent first. `query` is matched anywhere in the file, project or repository and
+    /// `repository` and `file` anywhere in theirs, case insensitive. Contexts still going on at
+    /// `start_time` are included
+    pub async fn get_editor_contexts(
+        &self,
+        query: Option<&str>,
+        repository: Option<&str>,
+        file: Option<&str>,
+        git_branch: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<EditorContext>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, ended_at, editor, project, repository, git_branch, file, language
+            FROM editor_contexts
+            WHERE (?1 IS NULL OR instr(LOWER(COALESCE(file, '') || ' ' || COALESCE(project, '') || ' ' || COALESCE(repository, '')), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR instr(LOWER(repository), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR instr(LOWER(file), LOWER(?3)) > 0)
+                AND (?4 IS NULL OR git_branch = ?4)
+                AND (?5 IS NULL OR ended_at >= ?5)
+                AND (?6 IS NULL OR timestamp <= ?6)
+                AND (?9 IS NULL OR timestamp < ?9 OR (timestamp = ?9 AND id < ?10))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?7 OFFSET ?8
+            "#,
+        )
+        .bind(query)
+        .bind(repository)
+        .bind(file)
+        .bind(git_branch)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(editor_context_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_editor_contexts(
+        &self,
+        query: Option<&str>,
+        repository: Option<&str>,
+        file: Option<&str>,
+        git_branch: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM editor_contexts
+            WHERE (?1 IS NULL OR instr(LOWER(COALESCE(file, '') || ' ' || COALESCE(project, '') || ' ' || COALESCE(repository, '')), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR instr(LOWER(repository), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR instr(LOWER(file), LOWER(?3)) > 0)
+                AND (?4 IS NULL OR git_branch = ?4)
+                AND (?5 IS NULL OR ended_at >= ?5)
+                AND (?6 IS NULL OR timestamp <= ?6)
+            "#,
+        )
+        .bind(query)
+        .bind(repository)
+        .bind(file)
+        .bind(git_branch)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn insert_focus_period(
+        &self,
+        start_time: DateTime<Utc>,
+        mode: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO focus_periods (start_time, end_time, mode) VALUES (?1, ?1, ?2)",
+        )
+        .bind(start_time)
+        .bind(mode)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn extend_focus_period(
+        &self,
+        id: i64,
+        end_time: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE focus_periods SET end_time = ?1 WHERE id = ?2")
+            .bind(end_time)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// The periods overlapping the two times, most recent first
+    pub async fn get_focus_periods(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<FocusPeriod>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, mode
+            FROM focus_periods
+            WHERE (?1 IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+                AND (?5 IS NULL OR start_time < ?5 OR (start_time = ?5 AND id < ?6))
+            ORDER BY start_time DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| FocusPeriod {
+            id: row.get("id"),
+            start_time: row.get("start_time"),
+            end_time: row.get("end_time"),
+            mode: row.get("mode"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_focus_periods(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM focus_periods
+            WHERE (?1 IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn insert_timezone_change(
+        &self,
+        timestamp: DateTime<Utc>,
+        timezone: &str,
+        utc_offset_minutes: i32,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO timezone_changes (timestamp, timezone, utc_offset_minutes) VALUES (?1, ?2, ?3)",
+        )
+        .bind(timestamp)
+        .bind(timezone)
+        .bind(utc_offset_minutes)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_latest_timezone_segment(
+        &self,
+    ) -> Result<Option<TimezoneSegment>, sqlx::Error> {
+        Ok(self
+            .get_timezone_segments(None, None, 1, 0, None)
+            .await?
+            .into_iter()
+            .next())
+    }
+
+    /// The segments overlapping the two times, most recent first. Each one ends where the
+    /// next change starts
+    pub async fn get_timezone_segments(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<TimezoneSegment>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, timezone, utc_offset_minutes
+            FROM (
+                SELECT id, timestamp AS start_time, timezone, utc_offset_minutes,
+                    LEAD(timestamp) OVER (ORDER BY timestamp, id) AS end_time
+                FROM timezone_changes
+            )
+            WHERE (?1 IS NULL OR end_time IS NULL OR end_time > ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+                AND (?5 IS NULL OR start_time < ?5 OR (start_time = ?5 AND id < ?6))
+            ORDER BY start_time DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| TimezoneSegment {
+            id: row.get("id"),
+            start_time: row.get("start_time"),
+            end_time: row.get("end_time"),
+            timezone: row.get("timezone"),
+            utc_offset_minutes: row.get("utc_offset_minutes"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_timezone_segments(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM (
+                SELECT timestamp AS start_time,
+                    LEAD(timestamp) OVER (ORDER BY timestamp, id) AS end_time
+                FROM timezone_changes
+            )
+            WHERE (?1 IS NULL OR end_time IS NULL OR end_time > ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// The id of the new row, None when it was stored already
+    pub async fn insert_notification(
+        &self,
+        notification: &OsNotification,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO notifications (timestamp, app_name, title, body, source) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(notification.timestamp)
+        .bind(&notification.app)
+        .bind(&notification.title)
+        .bind(&notification.body)
+        .bind(notification.source.as_str())
+        .execute(&self.pool)
+        .await?;
+        Ok((result.rows_affected() > 0).then(|| result.last_insert_rowid()))
+    }
+
+    pub async fn get_latest_notification_time(
+        &self,
+        source: NotificationSource,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(timestamp) FROM notifications WHERE source = ?1")
+            .bind(source.as_str())
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Most recent first. `query` is matched in the title and the body and `app_name`
+    /// anywhere in the app's name, case insensitive, a bundle id has the app's name in it
+    pub async fn get_notifications(
+        &self,
+        query: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<Notification>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, app_name, title, body, source
+            FROM notifications
+            WHERE (?1 IS NULL OR instr(LOWER(title || ' ' || COALESCE(body, '')), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR instr(LOWER(app_name), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+                AND (?7 IS NULL OR timestamp < ?7 OR (timestamp = ?7 AND id < ?8))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(query)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| Notification {
+            id: row.get("id"),
+            timestamp: row.get("timestamp"),
+            app_name: row.get("app_name"),
+            title: row.get("title"),
+            body: row.get("body"),
+            source: row.get("source"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_notifications(
+        &self,
+        query: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM notifications
+            WHERE (?1 IS NULL OR instr(LOWER(title || ' ' || COALESCE(body, '')), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR instr(LOWER(app_name), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            "#,
+        )
+        .bind(query)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn insert_conversation(
+        &self,
+        title: &str,
+        pipe: Option<&str>,
+        created_at: DateTime<Utc>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO conversations (title, pipe, created_at, updated_at) VALUES (?1, ?2, ?3, ?3)",
+        )
+        .bind(title)
+        .bind(pipe)
+        .bind(created_at)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_conversation(&self, id: i64) -> Result<Option<Conversation>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, title, pipe, created_at, updated_at,
+                (SELECT COUNT(*) FROM conversation_messages WHERE conversation_id = conversations.id) AS message_count
+            FROM conversations
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .map(conversation_from_row)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Most recently continued first. `query` is matched in the title and the messages, case
+    /// insensitive
+    pub async fn get_conversations(
+        &self,
+        query: Option<&str>,
+        pipe: Option<&str>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<Conversation>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, title, pipe, created_at, updated_at,
+                (SELECT COUNT(*) FROM conversation_messages WHERE conversation_id = conversations.id) AS message_count
+            FROM conversations
+            WHERE (?1 IS NULL OR instr(LOWER(title), LOWER(?1)) > 0
+                    OR EXISTS (SELECT 1 FROM conversation_messages
+                        WHERE conversation_id = conversations.id AND instr(LOWER(content), LOWER(?1)) > 0))
+                AND (?2 IS NULL OR pipe = ?2)
+                AND (?5 IS NULL OR updated_at < ?5 OR (updated_at = ?5 AND id < ?6))
+            ORDER BY updated_at DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(query)
+        .bind(pipe)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(conversation_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_conversations(
+        &self,
+        query: Option<&str>,
+        pipe: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM conversations
+            WHERE (?1 IS NULL OR instr(LOWER(title), LOWER(?1)) > 0
+                    OR EXISTS (SELECT 1 FROM conversation_messages
+                        WHERE conversation_id = conversations.id AND instr(LOWER(content), LOWER(?1)) > 0))
+                AND (?2 IS NULL OR pipe = ?2)
+            "#,
+        )
+        .bind(query)
+        .bind(pipe)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn delete_conversation(&self, id: i64) -> Result<bool, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM conversation_messages WHERE conversation_id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+        let deleted = sqlx::query("DELETE FROM conversations WHERE id = ?1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        tx.commit().await?;
+        Ok(deleted > 0)
+    }
+
+    /// Adds the message and moves the conversation to the top of the list
+    pub async fn insert_conversation_message(
+        &self,
+        conversation_id: i64,
+        timestamp: DateTime<Utc>,
+        role: &str,
+        content: &str,
+        citations: &[Citation],
+        tool_calls: &[ToolRun],
+        model: Option<&str>,
+    ) -> Result<i64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let id = sqlx::query(
+            "INSERT INTO conversation_messages (conversation_id, timestamp, role, content, citations, tool_calls, model) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )
+        .bind(conversation_id)
+        .bind(timestamp)
+        .bind(role)
+        .bind(content)
+        .bind(json!(citations).to_string())
+        .bind(json!(tool_calls).to_string())
+        .bind(model)
+        .execute(&mut *tx)
+        .await?
+        .last_insert_rowid();
+        sqlx::query("UPDATE conversations SET updated_at = MAX(updated_at, ?1) WHERE id = ?2")
+            .bind(timestamp)
+            .bind(conversation_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(id)
+    }
+
+    /// In the order they were said
+    pub async fn get_conversation_messages(
+        &self,
+        conversation_id: i64,
+    ) -> Result<Vec<ConversationMessage>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, conversation_id, timestamp, role, content, citations, tool_calls, model
+            FROM conversation_messages
+            WHERE conversation_id = ?1
+            ORDER BY id
+            "#,
+        )
+        .bind(conversation_id)
+        .map(|row: SqliteRow| {
+            let citations: String = row.get("citations");
+            let tool_calls: String = row.get("tool_calls");
+            ConversationMessage {
+                id: row.get("id"),
+                conversation_id: row.get("conversation_id"),
+                timestamp: row.get("timestamp"),
+                role: row.get("role"),
+                content: row.get("content"),
+                citations: serde_json::from_str(&citations).unwrap_or_default(),
+                tool_calls: serde_json::from_str(&tool_calls).unwrap_or_default(),
+                model: row.get("model"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Returns how many were stored, the moves stored already are skipped
+    pub async fn insert_git_events(
+        &self,
+        repository: &str,
+        entries: &[ReflogEntry],
+    ) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let mut stored = 0;
+        for entry in entries {
+            stored += sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO git_events (timestamp, repository, kind, branch, commit_hash, previous_hash, message, author)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+            )
+            .bind(entry.timestamp)
+            .bind(repository)
+            .bind(entry.kind.as_str())
+            .bind(&entry.branch)
+            .bind(&entry.commit)
+            .bind(&entry.previous_commit)
+            .bind(&entry.message)
+            .bind(&entry.author)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        tx.commit().await?;
+        Ok(stored)
+    }
+
+    /// Most recent first. `repository` is matched anywhere in the path of the repository and
+    /// `query` in the message, case insensitive
+    pub async fn get_git_events(
+        &self,
+        query: Option<&str>,
+        repository: Option<&str>,
+        kind: Option<GitEventKind>,
+        branch: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<GitEvent>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, repository, kind, branch, commit_hash, previous_hash, message, author
+            FROM git_events
+            WHERE (?1 IS NULL OR instr(LOWER(message), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR instr(LOWER(repository), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR kind = ?3)
+                AND (?4 IS NULL OR branch = ?4)
+                AND (?5 IS NULL OR timestamp >= ?5)
+                AND (?6 IS NULL OR timestamp <= ?6)
+                AND (?9 IS NULL OR timestamp < ?9 OR (timestamp = ?9 AND id < ?10))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?7 OFFSET ?8
+            "#,
+        )
+        .bind(query)
+        .bind(repository)
+        .bind(kind.map(|kind| kind.as_str()))
+        .bind(branch)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let kind: String = row.get("kind");
+            GitEvent {
+                id: row.get("id"),
+                timestamp: row.get("timestamp"),
+                repository: row.get("repository"),
+                kind: kind.parse().unwrap_or(GitEventKind::Commit),
+                branch: row.get("branch"),
+                commit: row.get("commit_hash"),
+                previous_commit: row.get("previous_hash"),
+                message: row.get("message"),
+                author: row.get("author"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_git_events(
+        &self,
+        query: Option<&str>,
+        repository: Option<&str>,
+        kind: Option<GitEventKind>,
+        branch: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM git_events
+            WHERE (?1 IS NULL OR instr(LOWER(message), LOWER(?1)) > 0)
+                AND (?2 IS NULL OR instr(LOWER(repository), LOWER(?2)) > 0)
+                AND (?3 IS NULL OR kind = ?3)
+                AND (?4 IS NULL OR branch = ?4)
+                AND (?5 IS NULL OR timestamp >= ?5)
+                AND (?6 IS NULL OR timestamp <= ?6)
+            "#,
+        )
+        .bind(query)
+        .bind(repository)
+        .bind(kind.map(|kind| kind.as_str()))
+        .bind(branch)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Last frame recorded at most `max_age` before `timestamp`
+    pub async fn get_frame_id_at(
+        &self,
+        timestamp: DateTime<Utc>,
+        max_age: chrono::Duration,
+    ) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT id FROM frames WHERE timestamp <= ?1 AND timestamp >= ?2 ORDER BY timestamp DESC LIMIT 1",
+        )
+        .bind(timestamp)
+        .bind(timestamp - max_age)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn insert_marker(
+        &self,
+        timestamp: DateTime<Utc>,
+        note: Option<&str>,
+        image_path: Option<&str>,
+        encryption_key_id: Option<u32>,
+        frame_id: Option<i64>,
+        memo_secs: Option<u32>,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO markers (timestamp, note, image_path, encryption_key_id, frame_id, memo_secs) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .bind(timestamp)
+        .bind(note)
+        .bind(image_path)
+        .bind(encryption_key_id)
+        .bind(frame_id)
+        .bind(memo_secs)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_marker(&self, id: i64) -> Result<Option<Marker>, sqlx::Error> {
+        sqlx::query(
+            "SELECT id, timestamp, note, image_path, frame_id, memo_secs, memo FROM markers WHERE id = ?1",
+        )
+        .bind(id)
+        .map(marker_from_row)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Most recent first
+    pub async fn get_markers(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<Marker>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, note, image_path, frame_id, memo_secs, memo
+            FROM markers
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?5 IS NULL OR timestamp < ?5 OR (timestamp = ?5 AND id < ?6))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(marker_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_markers(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM markers
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    pub async fn set_marker_memo(&self, id: i64, memo: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE markers SET memo = ?1 WHERE id = ?2")
+            .bind(memo)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// (id, screenshot) of the markers on forgotten frames, and the ones in the time range
+    /// with the keyword in their note or memo when no app is given, markers have none
+    pub async fn get_markers_to_forget(
+        &self,
+        filter: &ForgetFilter,
+        frame_ids: &[i64],
+    ) -> Result<Vec<(i64, Option<String>)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, image_path
+            FROM markers
+            WHERE frame_id IN (SELECT value FROM json_each(?1))
+                OR (?4 IS NULL
+                    AND (?2 IS NULL OR timestamp >= ?2)
+                    AND (?3 IS NULL OR timestamp <= ?3)
+                    AND (?5 IS NULL OR instr(LOWER(COALESCE(note, '') || COALESCE(memo, '')), LOWER(?5)) > 0))
+            "#,
+        )
+        .bind(json!(frame_ids).to_string())
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.app_name)
+        .bind(&filter.keyword)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn insert_slide_deck(
+        &self,
+        started_at: DateTime<Utc>,
+        monitor_id: u32,
+        app_name: &str,
+        window_name: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO slide_decks (started_at, monitor_id, app_name, window_name) VALUES (?1, ?2, ?3, ?4)",
+        )
+        .bind(started_at)
+        .bind(monitor_id)
+        .bind(app_name)
+        .bind(window_name)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn end_slide_deck(
+        &self,
+        id: i64,
+        ended_at: DateTime<Utc>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE slide_decks SET ended_at = ?1 WHERE id = ?2")
+            .bind(ended_at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_slide(
+        &self,
+        deck_id: i64,
+        position: u32,
+        timestamp: DateTime<Utc>,
+        image_path: Option<&str>,
+        encryption_key_id: Option<u32>,
+        width: u32,
+        height: u32,
+        text: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO slides (deck_id, position, timestamp, image_path, encryption_key_id, width, height, text) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        )
+        .bind(deck_id)
+        .bind(position)
+        .bind(timestamp)
+        .bind(image_path)
+        .bind(encryption_key_id)
+        .bind(width)
+        .bind(height)
+        .bind(text)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_slide_deck(&self, id: i64) -> Result<Option<SlideDeck>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, started_at, ended_at, monitor_id, app_name, window_name,
+                (SELECT COUNT(*) FROM slides WHERE slides.deck_id = slide_decks.id) AS slide_count
+            FROM slide_decks
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .map(slide_deck_from_row)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Most recent first
+    pub async fn get_slide_decks(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<SlideDeck>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, started_at, ended_at, monitor_id, app_name, window_name,
+                (SELECT COUNT(*) FROM slides WHERE slides.deck_id = slide_decks.id) AS slide_count
+            FROM slide_decks
+            WHERE (?1 IS NULL OR started_at >= ?1)
+                AND (?2 IS NULL OR started_at <= ?2)
+                AND (?5 IS NULL OR started_at < ?5 OR (started_at = ?5 AND id < ?6))
+            ORDER BY started_at DESC, id DESC
+            LIMIT ?3 OFFSET ?4
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(slide_deck_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_slide_decks(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM slide_decks
+            WHERE (?1 IS NULL OR started_at >= ?1)
+                AND (?2 IS NULL OR started_at <= ?2)
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// In the order they were shown
+    pub async fn get_slides(&self, deck_id: i64) -> Result<Vec<Slide>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, deck_id, position, timestamp, image_path, width, height, text
+            FROM slides
+            WHERE deck_id = ?1
+            ORDER BY position ASC
+            "#,
+        )
+        .bind(deck_id)
+        .map(slide_from_row)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_slide(&self, id: i64) -> Result<Option<Slide>, sqlx::Error> {
+        sqlx::query(
+            "SELECT id, deck_id, position, timestamp, image_path, width, height, text FROM slides WHERE id = ?1",
+        )
+        .bind(id)
+        .map(slide_from_row)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn insert_activity_session(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        app_name: &str,
+        category: ActivityCategory,
+        confidence: f32,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO activity_sessions (start_time, end_time, app_name, category, confidence) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(app_name)
+        .bind(category.as_str())
+        .bind(confidence)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_last_activity_session_end_time(
+        &self,
+    ) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(end_time) FROM activity_sessions")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    pub async fn get_activity_sessions(
+        &self,
+        category: Option<ActivityCategory>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<ActivitySession>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, app_name, category, confidence, created_at,
+                (
+                    SELECT mode FROM focus_periods
+                    WHERE focus_periods.start_time <= activity_sessions.end_time
+                        AND focus_periods.end_time >= activity_sessions.start_time
+                    ORDER BY focus_periods.start_time
+                    LIMIT 1
+                ) AS focus_mode
+            FROM activity_sessions
+            WHERE (?1 IS NULL OR category = ?1)
+                AND (?2 IS NULL OR end_time >= ?2)
+                AND (?3 IS NULL OR start_time <= ?3)
+                AND (?6 IS NULL OR start_time < ?6 OR (start_time = ?6 AND id < ?7))
+            ORDER BY start_time DESC, id DESC
+            LIMIT ?4 OFFSET ?5
+            "#,
+        )
+        .bind(category.map(|c| c.as_str()))
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let category: String = row.get("category");
+            ActivitySession {
+                id: row.get("id"),
+                start_time: row.get("start_time"),
+                end_time: row.get("end_time"),
+                app_name: row.get("app_name"),
+                category: category.parse().unwrap_or(ActivityCategory::Other),
+                confidence: row.get("confidence"),
+                created_at: row.get("created_at"),
+                focus_mode: row.get("focus_mode"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_activity_sessions(
+        &self,
+        category: Option<ActivityCategory>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM activity_sessions
+            WHERE (?1 IS NULL OR category = ?1)
+                AND (?2 IS NULL OR end_time >= ?2)
+                AND (?3 IS NULL OR start_time <= ?3)
+            "#,
+        )
+        .bind(category.map(|c| c.as_str()))
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Time spent per category, longest first
+    pub async fn get_activity_category_durations(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<CategoryDuration>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT
+                category,
+                COUNT(*) as sessions,
+                SUM((julianday(end_time) - julianday(start_time)) * 86400.0) as duration_secs
+            FROM activity_sessions
+            WHERE (?1 IS NULL OR end_time >= ?1)
+                AND (?2 IS NULL OR start_time <= ?2)
+            GROUP BY category
+            ORDER BY duration_secs DESC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(|row: SqliteRow| {
+            let category: String = row.get("category");
+            CategoryDuration {
+                category: category.parse().unwrap_or(ActivityCategory::Other),
+                sessions: row.get("sessions"),
+                duration_secs: row.get("duration_secs"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// How much text was read on each frame, oldest first
+    pub async fn get_ocr_analytics(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<OcrRow>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT frames.id as frame_id, frames.timestamp, ocr_text.text, ocr_text.app_name,
+                ocr_text.window_name, ocr_text.focused, ocr_text.ocr_engine
+            FROM ocr_text
+            JOIN frames ON frames.id = ocr_text.frame_id
+            WHERE (?1 IS NULL OR frames.timestamp >= ?1)
+                AND (?2 IS NULL OR frames.timestamp <= ?2)
+            ORDER BY frames.timestamp ASC, frames.id ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(|row: SqliteRow| {
+            let text: String = row.get("text");
+            OcrRow {
+                frame_id: row.get("frame_id"),
+                timestamp: row.get("timestamp"),
+                app_name: row.get("app_name"),
+                window_name: row.get("window_name"),
+                focused: row.get::<Option<bool>, _>("focused").unwrap_or(false),
+                ocr_engine: row.get("ocr_engine"),
+                word_count: word_count(&text),
+                char_count: text.chars().count() as i64,
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// How much was said in each transcription, oldest first
+    pub async fn get_transcript_analytics(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<TranscriptRow>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, audio_chunk_id, transcription, transcription_engine
+            FROM audio_transcriptions
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+            ORDER BY timestamp ASC, id ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(|row: SqliteRow| {
+            let transcription: String = row.get("transcription");
+            TranscriptRow {
+                id: row.get("id"),
+                timestamp: row.get("timestamp"),
+                audio_chunk_id: row.get("audio_chunk_id"),
+                transcription_engine: row.get("transcription_engine"),
+                word_count: word_count(&transcription),
+                char_count: transcription.chars().count() as i64,
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Adds the frames and transcriptions up to `until` not counted yet to the hourly totals,
+    /// returns how many were counted. Each one is counted once even if it's kept afterwards
+    pub async fn aggregate_usage(&self, until: DateTime<Utc>) -> Result<u64, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        let since: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT aggregated_until FROM usage_aggregation WHERE id = 1")
+                .fetch_optional(&mut *tx)
+                .await?;
+        if since.is_some_and(|since| since >= until) {
+            return Ok(0);
+        }
+
+        // (frames or transcriptions, words, characters) per hour, source and app
+        let mut totals = BTreeMap::new();
+        let mut add = |timestamp: DateTime<Utc>, source: &'static str, app_name: String, text: &str| {
+            let hour = timestamp.duration_trunc(chrono::Duration::hours(1)).unwrap_or(timestamp);
+            let total: &mut (i64, i64, i64) = totals.entry((hour, source, app_name)).or_default();
+            total.0 += 1;
+            total.1 += word_count(text);
+            total.2 += text.chars().count() as i64;
+        };
+        let frames = sqlx::query(
+            r#"
+            SELECT frames.timestamp, ocr_text.app_name, ocr_text.text
+            FROM ocr_text
+            JOIN frames ON frames.id = ocr_text.frame_id
+            WHERE (?1 IS NULL OR frames.timestamp > ?1) AND frames.timestamp <= ?2
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &frames {
+            let app_name: Option<String> = row.get("app_name");
+            add(row.get("timestamp"), "screen", app_name.unwrap_or_default(), row.get("text"));
+        }
+        let transcriptions = sqlx::query(
+            r#"
+            SELECT timestamp, transcription
+            FROM audio_transcriptions
+            WHERE (?1 IS NULL OR timestamp > ?1) AND timestamp <= ?2
+            "#,
+        )
+        .bind(since)
+        .bind(until)
+        .fetch_all(&mut *tx)
+        .await?;
+        for row in &transcriptions {
+            add(row.get("timestamp"), "audio", String::new(), row.get("transcription"));
+        }
+
+        for ((hour, source, app_name), (count, words, chars)) in &totals {
+            sqlx::query(
+                r#"
+                INSERT INTO usage_aggregates (hour, source, app_name, count, word_count, char_count)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+                ON CONFLICT (hour, source, app_name) DO UPDATE SET
+                    count = count + excluded.count,
+                    word_count = word_count + excluded.word_count,
+                    char_count = char_count + excluded.char_count
+                "#,
+            )
+            .bind(hour)
+            .bind(source)
+            .bind(app_name)
+            .bind(count)
+            .bind(words)
+            .bind(chars)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(
+            "INSERT INTO usage_aggregation (id, aggregated_until) VALUES (1, ?1) ON CONFLICT (id) DO UPDATE SET aggregated_until = excluded.aggregated_until",
+        )
+        .bind(until)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok((frames.len() + transcriptions.len()) as u64)
+    }
+
+    /// Hourly totals between the two times, oldest first
+    pub async fn get_usage_aggregates(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<Vec<UsageRow>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT hour, source, app_name, count, word_count, char_count
+            FROM usage_aggregates
+            WHERE (?1 IS NULL OR hour >= ?1)
+                AND (?2 IS NULL OR hour <= ?2)
+            ORDER BY hour ASC, source ASC, app_name ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(|row: SqliteRow| {
+            let app_name: String = row.get("app_name");
+            UsageRow {
+                hour: row.get("hour"),
+                source: row.get("source"),
+                app_name: (!app_name.is_empty()).then_some(app_name),
+                count: row.get("count"),
+                word_count: row.get("word_count"),
+                char_count: row.get("char_count"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Salt of the backup key, `new_salt` is kept when there is none yet
+    pub async fn backup_salt(&self, new_salt: &[u8]) -> Result<Vec<u8>, sqlx::Error> {
+        sqlx::query("INSERT OR IGNORE INTO backup_key (id, salt) VALUES (1, ?1)")
+            .bind(new_salt)
+            .execute(&self.pool)
+            .await?;
+        sqlx::query_scalar("SELECT salt FROM backup_key WHERE id = 1")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Copy of the database that stays consistent while screenpipe keeps writing
+    pub async fn snapshot_database(&self, path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("VACUUM INTO ?1")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn insert_backup_upload(
+        &self,
+        source: BackupSource,
+        chunk_id: Option<i64>,
+        file_path: &str,
+        object_key: &str,
+        file_size: i64,
+        upload_id: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO backup_uploads (source, chunk_id, file_path, object_key, file_size, upload_id, started_at)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+            "#,
+        )
+        .bind(source.as_str())
+        .bind(chunk_id)
+        .bind(file_path)
+        .bind(object_key)
+        .bind(file_size)
+        .bind(upload_id)
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    /// Uploads started and not completed, oldest first
+    pub async fn get_unfinished_backup_uploads(&self) -> Result<Vec<BackupUpload>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, source, chunk_id, file_path, object_key, file_size, upload_id, parts, started_at
+            FROM backup_uploads
+            WHERE completed_at IS NULL
+            ORDER BY id ASC
+            "#,
+        )
+        .map(|row: SqliteRow| {
+            let source: String = row.get("source");
+            let parts: String = row.get("parts");
+            BackupUpload {
+                id: row.get("id"),
+                source: source.parse().unwrap_or(BackupSource::Database),
+                chunk_id: row.get("chunk_id"),
+                file_path: row.get("file_path"),
+                object_key: row.get("object_key"),
+                file_size: row.get("file_size"),
+                upload_id: row.get("upload_id"),
+                parts: serde_json::from_str(&parts).unwrap_or_default(),
+                started_at: row.get("started_at"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// What the destination gave back for each part uploaded so far
+    pub async fn set_backup_upload_parts(
+        &self,
+        id: i64,
+        parts: &[String],
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE backup_uploads SET parts = ?1 WHERE id = ?2")
+            .bind(serde_json::to_string(parts).unwrap_or_else(|_| "[]".to_string()))
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn complete_backup_upload(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE backup_uploads SET completed_at = ?1 WHERE id = ?2")
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Forgets an upload given up on, its chunk is uploaded again from the start
+    pub async fn delete_backup_upload(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM backup_uploads WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// When the last backup of the database was started, of those completed
+    pub async fn get_last_database_backup(&self) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT MAX(started_at) FROM backup_uploads WHERE source = 'database' AND completed_at IS NOT NULL",
+        )
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Finished chunks never uploaded, oldest first. Broken ones and the ones recorded text
+    /// only, with no file, are left out
+    pub async fn get_chunks_to_back_up(
+        &self,
+        kind: ChunkKind,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let table = kind.table();
+        let source = BackupSource::from(kind);
+        let mut sql = format!(
+            r#"
+            SELECT id, file_path FROM {table}
+            WHERE integrity IS NOT 'broken'
+                AND id NOT IN (SELECT chunk_id FROM backup_uploads WHERE source = ?1 AND chunk_id IS NOT NULL)
+            "#
+        );
+        if kind == ChunkKind::Video {
+            sql.push_str(&format!(
+                " AND id < (SELECT MAX(id) FROM {table}) AND file_path NOT LIKE '%.{}'",
+                TEXT_ONLY_CHUNK_EXTENSION
+            ));
+        }
+        sql.push_str(" ORDER BY id ASC LIMIT ?2");
+        sqlx::query_as(&sql)
+            .bind(source.as_str())
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    /// Where the chunk is now, it's renamed when encrypted. None once it's deleted
+    pub async fn get_chunk_file_path(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+    ) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar(&format!(
+            "SELECT file_path FROM {} WHERE id = ?1",
+            kind.table()
+        ))
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Frames between the two times, oldest first, with the focused app if known
+    pub async fn get_timelapse_frames(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<TimelapseSourceFrame>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT
+                frames.timestamp,
+                frames.offset_index,
+                video_chunks.file_path,
+                COALESCE(
+                    (SELECT ocr_text.app_name FROM ocr_text
+                     WHERE ocr_text.frame_id = frames.id AND ocr_text.focused = 1
+                     LIMIT 1),
+                    ''
+                ) AS app_name
+            FROM frames
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp <= ?2
+                AND video_chunks.integrity IS NOT 'broken'
+            ORDER BY frames.timestamp ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .map(|row: SqliteRow| TimelapseSourceFrame {
+            timestamp: row.get("timestamp"),
+            offset_index: row.get("offset_index"),
+            file_path: row.get("file_path"),
+            app_name: row.get("app_name"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn insert_timelapse_job(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        speed: f64,
+        output_path: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO timelapse_jobs (start_time, end_time, speed, status, output_path) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(speed)
+        .bind(TimelapseStatus::Running.as_str())
+        .bind(output_path)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn finish_timelapse_job(
+        &self,
+        id: i64,
+        status: TimelapseStatus,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE timelapse_jobs SET status = ?1, error = ?2, finished_at = ?3 WHERE id = ?4",
+        )
+        .bind(status.as_str())
+        .bind(error)
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_timelapse_job(&self, id: i64) -> Result<Option<TimelapseJob>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, speed, status, output_path, error, created_at, finished_at
+            FROM timelapse_jobs
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .map(|row: SqliteRow| {
+            let status: String = row.get("status");
+            TimelapseJob {
+                id: row.get("id"),
+                start_time: row.get("start_time"),
+                end_time: row.get("end_time"),
+                speed: row.get("speed"),
+                status: status.parse().unwrap_or(TimelapseStatus::Failed),
+                output_path: row.get("output_path"),
+                error: row.get("error"),
+                created_at: row.get("created_at"),
+                finished_at: row.get("finished_at"),
+            }
+        })
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    /// Frames between the two times, oldest first, with every window recognized on them
+    pub async fn get_clip_frames(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<ClipSourceFrame>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                frames.id,
+                frames.timestamp,
+                frames.offset_index,
+                video_chunks.file_path,
+                ocr_text.app_name,
+                ocr_text.window_name
+            FROM frames
+            JOIN video_chunks ON frames.video_chunk_id = video_chunks.id
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id
+            WHERE frames.timestamp >= ?1 AND frames.timestamp <= ?2
+                AND video_chunks.integrity IS NOT 'broken'
+            ORDER BY frames.timestamp ASC, frames.id ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut frames: Vec<(i64, ClipSourceFrame)> = Vec::new();
+        for row in rows {
+            let id: i64 = row.get("id");
+            if frames.last().map(|(last_id, _)| *last_id) != Some(id) {
+                frames.push((
+                    id,
+                    ClipSourceFrame {
+                        timestamp: row.get("timestamp"),
+                        file_path: row.get("file_path"),
+                        offset_index: row.get("offset_index"),
+                        windows: Vec::new(),
+                    },
+                ));
+            }
+            let app_name: Option<String> = row.get("app_name");
+            let window_name: Option<String> = row.get("window_name");
+            if app_name.is_some() || window_name.is_some() {
+                if let Some((_, frame)) = frames.last_mut() {
+                    frame
+                        .windows
+                        .push((app_name.unwrap_or_default(), window_name.unwrap_or_default()));
+                }
+            }
+        }
+        Ok(frames.into_iter().map(|(_, frame)| frame).collect())
+    }
+
+    pub async fn insert_clip_job(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+        redacted: bool,
+        output_path: &str,
+    ) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            "INSERT INTO clip_jobs (start_time, end_time, redacted, status, output_path) VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(redacted)
+        .bind(ClipStatus::Running.as_str())
+        .bind(output_path)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn finish_clip_job(
+        &self,
+        id: i64,
+        status: ClipStatus,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE clip_jobs SET status = ?1, error = ?2, finished_at = ?3 WHERE id = ?4")
+            .bind(status.as_str())
+            .bind(error)
+            .bind(Utc::now())
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_clip_job(&self, id: i64) -> Result<Option<ClipJob>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, start_time, end_time, redacted, status, output_path, error, created_at, finished_at
+            FROM clip_jobs
+            WHERE id = ?1
+            "#,
+        )
+        .bind(id)
+        .map(|row: SqliteRow| {
+            let status: String = row.get("status");
+            ClipJob {
+                id: row.get("id"),
+                start_time: row.get("start_time"),
+                end_time: row.get("end_time"),
+                redacted: row.get("redacted"),
+                status: status.parse().unwrap_or(ClipStatus::Failed),
+                output_path: row.get("output_path"),
+                error: row.get("error"),
+                created_at: row.get("created_at"),
+                finished_at: row.get("finished_at"),
+            }
+        })
+        .fetch_optional(&self.pool)
+        .await
+    }
+
+    pub async fn insert_audit_entry(&self, entry: &AuditEntry) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO audit_log (timestamp, actor, action, method, path, query, status, range_start, range_end)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            "#,
+        )
+        .bind(entry.timestamp)
+        .bind(&entry.actor)
+        .bind(entry.action.as_str())
+        .bind(&entry.method)
+        .bind(&entry.path)
+        .bind(&entry.query)
+        .bind(entry.status)
+        .bind(entry.range_start)
+        .bind(entry.range_end)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_audit_entries(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        actor: Option<&str>,
+        action: Option<AuditAction>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<AuditEntry>, sqlx::Error> {
+        // entries are appended as they happen, the id alone orders them
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, actor, action, method, path, query, status, range_start, range_end
+            FROM audit_log
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR actor = ?3)
+                AND (?4 IS NULL OR action = ?4)
+                AND (?7 IS NULL OR id < ?7)
+            ORDER BY id DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(actor)
+        .bind(action.map(|action| action.as_str()))
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.id))
+        .map(|row: SqliteRow| {
+            let action: String = row.get("action");
+            AuditEntry {
+                id: row.get("id"),
+                timestamp: row.get("timestamp"),
+                actor: row.get("actor"),
+                action: action.parse().unwrap_or(AuditAction::Query),
+                method: row.get("method"),
+                path: row.get("path"),
+                query: row.get("query"),
+                status: row.get("status"),
+                range_start: row.get("range_start"),
+                range_end: row.get("range_end"),
+            }
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn insert_consent_record(&self, record: &ConsentRecord) -> Result<i64, sqlx::Error> {
+        let id = sqlx::query(
+            r#"
+            INSERT INTO compliance_consents (timestamp, user, organization, policy_hash, accepted)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+        )
+        .bind(record.timestamp)
+        .bind(&record.user)
+        .bind(&record.organization)
+        .bind(&record.policy_hash)
+        .bind(record.accepted)
+        .execute(&self.pool)
+        .await?
+        .last_insert_rowid();
+        Ok(id)
+    }
+
+    pub async fn get_consent_records(&self, limit: u32) -> Result<Vec<ConsentRecord>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT id, timestamp, user, organization, policy_hash, accepted
+            FROM compliance_consents
+            ORDER BY id DESC
+            LIMIT ?1
+            "#,
+        )
+        .bind(limit)
+        .map(|row: SqliteRow| ConsentRecord {
+            id: row.get("id"),
+            timestamp: row.get("timestamp"),
+            user: row.get("user"),
+            organization: row.get("organization"),
+            policy_hash: row.get("policy_hash"),
+            accepted: row.get("accepted"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn count_audit_entries(
+        &self,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        actor: Option<&str>,
+        action: Option<AuditAction>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM audit_log
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR actor = ?3)
+                AND (?4 IS NULL OR action = ?4)
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .bind(actor)
+        .bind(action.map(|action| action.as_str()))
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Stores the records and returns them with their ids
+    pub async fn insert_automation_records(
+        &self,
+        mut records: Vec<AutomationRecord>,
+    ) -> Result<Vec<AutomationRecord>, sqlx::Error> {
+        let mut tx = self.pool.begin().await?;
+        for record in records.iter_mut() {
+            record.id = sqlx::query(
+                r#"
+                INSERT INTO automation_actions (timestamp, pipe, kind, action, app_name, window_name, status, reason)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+                "#,
+            )
+            .bind(record.timestamp)
+            .bind(&record.pipe)
+            .bind(record.action.kind().as_str())
+            .bind(serde_json::to_string(&record.action).unwrap_or_default())
+            .bind(&record.app_name)
+            .bind(&record.window_name)
+            .bind(record.status.as_str())
+            .bind(&record.reason)
+            .execute(&mut *tx)
+            .await?
+            .last_insert_rowid();
+        }
+        tx.commit().await?;
+        Ok(records)
+    }
+
+    /// Most recent first
+    pub async fn get_automation_records(
+        &self,
+        pipe: Option<&str>,
+        status: Option<AutomationStatus>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<AutomationRecord>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, timestamp, pipe, action, app_name, window_name, status, reason
+            FROM automation_actions
+            WHERE (?1 IS NULL OR pipe = ?1)
+                AND (?2 IS NULL OR status = ?2)
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+                AND (?7 IS NULL OR timestamp < ?7 OR (timestamp = ?7 AND id < ?8))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?5 OFFSET ?6
+            "#,
+        )
+        .bind(pipe)
+        .bind(status.map(|status| status.as_str()))
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                let action: String = row.get("action");
+                let status: String = row.get("status");
+                Ok(AutomationRecord {
+                    id: row.get("id"),
+                    timestamp: row.get("timestamp"),
+                    pipe: row.get("pipe"),
+                    action: serde_json::from_str(&action)
+                        .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                    app_name: row.get("app_name"),
+                    window_name: row.get("window_name"),
+                    status: status.parse().unwrap_or(AutomationStatus::Failed),
+                    reason: row.get("reason"),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn count_automation_records(
+        &self,
+        pipe: Option<&str>,
+        status: Option<AutomationStatus>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM automation_actions
+            WHERE (?1 IS NULL OR pipe = ?1)
+                AND (?2 IS NULL OR status = ?2)
+                AND (?3 IS NULL OR timestamp >= ?3)
+                AND (?4 IS NULL OR timestamp <= ?4)
+            "#,
+        )
+        .bind(pipe)
+        .bind(status.map(|status| status.as_str()))
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Most recent first
+    pub async fn get_redactions(
+        &self,
+        source: Option<RedactionSource>,
+        rule: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+        limit: u32,
+        offset: u32,
+        before: Option<&CursorPosition>,
+    ) -> Result<Vec<RedactionRecord>, sqlx::Error> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, timestamp, source, rule, action, app_name, entities, regions, audio_file
+            FROM redactions
+            WHERE (?1 IS NULL OR source = ?1)
+                AND (?2 IS NULL OR rule = ?2)
+                AND (?3 IS NULL OR app_name = ?3 COLLATE NOCASE)
+                AND (?4 IS NULL OR timestamp >= ?4)
+                AND (?5 IS NULL OR timestamp <= ?5)
+                AND (?8 IS NULL OR timestamp < ?8 OR (timestamp = ?8 AND id < ?9))
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?6 OFFSET ?7
+            "#,
+        )
+        .bind(source.map(|source| source.as_str()))
+        .bind(rule)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .bind(limit)
+        .bind(offset)
+        .bind(before.map(|position| position.timestamp))
+        .bind(before.map(|position| position.id))
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|row| {
+                let decode = |e: anyhow::Error| sqlx::Error::Decode(e.into());
+                let source: String = row.get("source");
+                let action: String = row.get("action");
+                let entities: String = row.get("entities");
+                let regions: String = row.get("regions");
+                Ok(RedactionRecord {
+                    id: row.get("id"),
+                    timestamp: row.get("timestamp"),
+                    annotation: RedactionAnnotation {
+                        source: source.parse().map_err(decode)?,
+                        rule: row.get("rule"),
+                        action: action.parse::<PolicyAction>().map_err(decode)?,
+                        app_name: row.get("app_name"),
+                        entities: serde_json::from_str(&entities)
+                            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                        regions: serde_json::from_str(&regions)
+                            .map_err(|e| sqlx::Error::Decode(Box::new(e)))?,
+                    },
+                    audio_file: row.get("audio_file"),
+                })
+            })
+            .collect()
+    }
+
+    pub async fn count_redactions(
+        &self,
+        source: Option<RedactionSource>,
+        rule: Option<&str>,
+        app_name: Option<&str>,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
+    ) -> Result<i64, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT COUNT(*)
+            FROM redactions
+            WHERE (?1 IS NULL OR source = ?1)
+                AND (?2 IS NULL OR rule = ?2)
+                AND (?3 IS NULL OR app_name = ?3 COLLATE NOCASE)
+                AND (?4 IS NULL OR timestamp >= ?4)
+                AND (?5 IS NULL OR timestamp <= ?5)
+            "#,
+        )
+        .bind(source.map(|source| source.as_str()))
+        .bind(rule)
+        .bind(app_name)
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_one(&self.pool)
+        .await
+    }
+
+    /// Chunks without subtitles whose last frame is older than `settled_before`,
+    /// never the chunk still being recorded
+    pub async fn get_video_chunks_pending_subtitles(
+        &self,
+        settled_before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.id, video_chunks.file_path
+            FROM video_chunks
+            WHERE video_chunks.subtitles_muxed = FALSE
+                AND video_chunks.integrity IS NOT 'broken'
+                AND video_chunks.encryption_key_id IS NULL
+                AND video_chunks.id < (SELECT MAX(id) FROM video_chunks)
+                AND (SELECT MAX(frames.timestamp) FROM frames WHERE frames.video_chunk_id = video_chunks.id) < ?1
+            ORDER BY video_chunks.id DESC
+            LIMIT ?2
+            "#,
+        )
+        .bind(settled_before)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn mark_video_chunk_subtitles_muxed(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE video_chunks SET subtitles_muxed = TRUE WHERE id = ?1")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Frames of a chunk in order, with the focused window if known
+    pub async fn get_video_chunk_frames(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<Vec<ChunkFrame>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT
+                frames.timestamp,
+                frames.offset_index,
+                COALESCE(ocr_text.app_name, '') AS app_name,
+                COALESCE(ocr_text.window_name, '') AS window_name
+            FROM frames
+            LEFT JOIN ocr_text ON ocr_text.frame_id = frames.id AND ocr_text.focused = 1
+            WHERE frames.video_chunk_id = ?1
+            GROUP BY frames.id
+            ORDER BY frames.offset_index ASC
+            "#,
+        )
+        .bind(video_chunk_id)
+        .map(|row: SqliteRow| ChunkFrame {
+            timestamp: row.get("timestamp"),
+            offset_index: row.get("offset_index"),
+            app_name: row.get("app_name"),
+            window_name: row.get("window_name"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_transcriptions_between(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<(DateTime<Utc>, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT timestamp, transcription
+            FROM audio_transcriptions
+            WHERE timestamp >= ?1 AND timestamp <= ?2
+            ORDER BY timestamp ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Local days with frames or transcriptions, oldest first
+    pub async fn get_timeline_days(&self) -> Result<Vec<NaiveDate>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT date(timestamp, 'localtime') AS day FROM frames
+            UNION
+            SELECT date(timestamp, 'localtime') AS day FROM audio_transcriptions
+            ORDER BY day ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Frames between the two times, oldest first, with the focused app or an empty name
+    pub async fn get_timeline_frames(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<(i64, DateTime<Utc>, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT
+                frames.id,
+                frames.timestamp,
+                COALESCE(
+                    (SELECT ocr_text.app_name FROM ocr_text
+                     WHERE ocr_text.frame_id = frames.id AND ocr_text.focused = 1
+                     LIMIT 1),
+                    ''
+                ) AS app_name
+            FROM frames
+            WHERE frames.timestamp >= ?1 AND frames.timestamp < ?2
+            ORDER BY frames.timestamp ASC, frames.id ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Transcriptions between the two times with their ids, oldest first
+    pub async fn get_timeline_transcriptions(
+        &self,
+        start_time: DateTime<Utc>,
+        end_time: DateTime<Utc>,
+    ) -> Result<Vec<(i64, DateTime<Utc>, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, timestamp, transcription
+            FROM audio_transcriptions
+            WHERE timestamp >= ?1 AND timestamp < ?2
+            ORDER BY timestamp ASC, id ASC
+            "#,
+        )
+        .bind(start_time)
+        .bind(end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_transcription(&self, id: i64) -> Result<Option<String>, sqlx::Error> {
+        sqlx::query_scalar("SELECT transcription FROM audio_transcriptions WHERE id = ?1")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+    }
+
+    /// Finished chunks the integrity checker hasn't looked at yet, the video chunk
+    /// still being recorded is left out
+    pub async fn get_unchecked_chunks(
+        &self,
+        kind: ChunkKind,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let table = kind.table();
+        let mut sql = format!(
+            "SELECT id, file_path FROM {table} WHERE integrity IS NULL AND encryption_key_id IS NULL"
+        );
+        if kind == ChunkKind::Video {
+            sql.push_str(&format!(" AND id < (SELECT MAX(id) FROM {table})"));
+        }
+        sql.push_str(" ORDER BY id ASC LIMIT ?1");
+        sqlx::query_as(&sql)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn set_chunk_integrity(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+        integrity: ChunkIntegrity,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "UPDATE {} SET integrity = ?1 WHERE id = ?2",
+            kind.table()
+        ))
+        .bind(integrity.as_str())
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Chunks not compacted yet whose last frame is older than `older_than`
+    pub async fn get_video_chunks_to_compact(
+        &self,
+        older_than: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT video_chunks.id, video_chunks.file_path
+            FROM video_chunks
+            WHERE video_chunks.compacted = FALSE
+                AND video_chunks.integrity IS NOT 'broken'
+                AND video_chunks.encryption_key_id IS NULL
+                AND (SELECT MAX(frames.timestamp) FROM frames WHERE frames.video_chunk_id = video_chunks.id) < ?1
+                AND NOT EXISTS (
+                    SELECT 1 FROM markers JOIN frames ON markers.frame_id = frames.id
+                    WHERE frames.video_chunk_id = video_chunks.id
+                )
+            ORDER BY video_chunks.id ASC
+            LIMIT ?2
+            "#,
+        )
+        .bind(older_than)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Points the chunk at its re-encoded file in a single statement, searches see
+    /// either the old or the new file
+    pub async fn mark_video_chunk_compacted(
+        &self,
+        id: i64,
+        file_path: &str,
+        scale: f64,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE video_chunks SET file_path = ?1, scale = scale * ?2, compacted = TRUE WHERE id = ?3",
+        )
+        .bind(file_path)
+        .bind(scale)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Finished plaintext chunks and the ones encrypted with another key than the active one.
+    /// Video chunks settle once their last frame is older than `settled_before`
+    pub async fn get_chunks_to_encrypt(
+        &self,
+        kind: ChunkKind,
+        active_key_id: u32,
+        settled_before: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        let table = kind.table();
+        let settled = match kind {
+            ChunkKind::Video => format!(
+                "id < (SELECT MAX(id) FROM {table}) AND (SELECT MAX(frames.timestamp) FROM frames WHERE frames.video_chunk_id = {table}.id) < ?2"
+            ),
+            ChunkKind::Audio => "timestamp < ?2".to_string(),
+        };
+        let sql = format!(
+            r#"
+            SELECT id, file_path FROM {table}
+            WHERE (encryption_key_id IS NULL AND file_path LIKE '%.mp4' AND {settled})
+                OR encryption_key_id != ?1
+            ORDER BY id ASC
+            LIMIT ?3
+            "#
+        );
+        sqlx::query_as(&sql)
+            .bind(active_key_id)
+            .bind(settled_before)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+    }
+
+    pub async fn set_chunk_encryption(
+        &self,
+        kind: ChunkKind,
+        id: i64,
+        file_path: &str,
+        key_id: u32,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(&format!(
+            "UPDATE {} SET file_path = ?1, encryption_key_id = ?2 WHERE id = ?3",
+            kind.table()
+        ))
+        .bind(file_path)
+        .bind(key_id)
+        .bind(id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Number of chunks per encryption key, None for the plaintext ones
+    pub async fn count_chunks_by_encryption_key(
+        &self,
+    ) -> Result<Vec<(Option<u32>, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT encryption_key_id, COUNT(*) FROM (
+                SELECT encryption_key_id FROM video_chunks
+                UNION ALL
+                SELECT encryption_key_id FROM audio_chunks
+            )
+            GROUP BY encryption_key_id
+            ORDER BY encryption_key_id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Keys of the chunks, of the marker screenshots, of the slides and of the thumbnails
+    pub async fn get_encryption_key_ids_in_use(&self) -> Result<Vec<u32>, sqlx::Error> {
+        let mut key_ids: Vec<u32> = self
+            .count_chunks_by_encryption_key()
+            .await?
+            .into_iter()
+            .filter_map(|(key_id, _)| key_id)
+            .collect();
+        let marker_key_ids: Vec<u32> = sqlx::query_scalar(
+            "SELECT DISTINCT encryption_key_id FROM markers WHERE encryption_key_id IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        key_ids.extend(marker_key_ids);
+        let slide_key_ids: Vec<u32> = sqlx::query_scalar(
+            "SELECT DISTINCT encryption_key_id FROM slides WHERE encryption_key_id IS NOT NULL",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        key_ids.extend(slide_key_ids);
+        let thumbnail_key_ids: Vec<u32> =
+            sqlx::query_scalar("SELECT DISTINCT encryption_key_id FROM thumbnail_keys")
+                .fetch_all(&self.pool)
+                .await?;
+        key_ids.extend(thumbnail_key_ids);
+        key_ids.sort_unstable();
+        key_ids.dedup();
+        Ok(key_ids)
+    }
+
+    pub async fn set_thumbnail_key(&self, path: &str, key_id: u32) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT OR REPLACE INTO thumbnail_keys (path, encryption_key_id) VALUES (?1, ?2)",
+        )
+        .bind(path)
+        .bind(key_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn delete_thumbnail_key(&self, path: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM thumbnail_keys WHERE path = ?1")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Encrypted thumbnails of another key than the active one
+    pub async fn get_thumbnails_to_reencrypt(
+        &self,
+        active_key_id: u32,
+        limit: u32,
+    ) -> Result<Vec<String>, sqlx::Error> {
+        sqlx::query_scalar(
+            "SELECT path FROM thumbnail_keys WHERE encryption_key_id != ?1 ORDER BY path LIMIT ?2",
+        )
+        .bind(active_key_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Marker screenshots or slide images encrypted with another key than the active one
+    pub async fn get_images_to_reencrypt(
+        &self,
+        kind: ImageKind,
+        active_key_id: u32,
+        limit: u32,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(&format!(
+            "SELECT id, image_path FROM {} WHERE image_path IS NOT NULL AND encryption_key_id != ?1 ORDER BY id LIMIT ?2",
+            kind.table()
+        ))
+        .bind(active_key_id)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// The key an image was re-encrypted with, None when the image is gone
+    pub async fn set_image_key(
+        &self,
+        kind: ImageKind,
+        id: i64,
+        key_id: Option<u32>,
+    ) -> Result<(), sqlx::Error> {
+        let gone = if key_id.is_none() {
+            "image_path = NULL, "
+        } else {
+            ""
+        };
+        sqlx::query(&format!(
+            "UPDATE {} SET {}encryption_key_id = ?1 WHERE id = ?2",
+            kind.table(),
+            gone
+        ))
+            .bind(key_id)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Frames in the time range with a window of the app or the keyword in its text or title
+    pub async fn get_frames_to_forget(
+        &self,
+        filter: &ForgetFilter,
+    ) -> Result<Vec<ForgetFrame>, sqlx::Error> {
+        sqlx::query(
+            r#"
+            SELECT frames.id, frames.video_chunk_id, frames.timestamp, video_chunks.file_path
+            FROM frames
+            JOIN video_chunks ON video_chunks.id = frames.video_chunk_id
+            WHERE (?1 IS NULL OR frames.timestamp >= ?1)
+                AND (?2 IS NULL OR frames.timestamp <= ?2)
+                AND ((?3 IS NULL AND ?4 IS NULL) OR EXISTS (
+                    SELECT 1 FROM ocr_text
+                    WHERE ocr_text.frame_id = frames.id
+                        AND (?3 IS NULL OR LOWER(ocr_text.app_name) = LOWER(?3))
+                        AND (?4 IS NULL
+                            OR instr(LOWER(ocr_text.text), LOWER(?4)) > 0
+                            OR instr(LOWER(COALESCE(ocr_text.window_name, '')), LOWER(?4)) > 0)
+                ))
+            ORDER BY frames.id ASC
+            "#,
+        )
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.app_name)
+        .bind(&filter.keyword)
+        .map(|row: SqliteRow| ForgetFrame {
+            id: row.get("id"),
+            video_chunk_id: row.get("video_chunk_id"),
+            timestamp: row.get("timestamp"),
+            file_path: row.get("file_path"),
+        })
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_transcriptions_to_forget(
+        &self,
+        filter: &ForgetFilter,
+    ) -> Result<Vec<i64>, sqlx::Error> {
+        sqlx::query_scalar(
+            r#"
+            SELECT id FROM audio_transcriptions
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR instr(LOWER(transcription), LOWER(?3)) > 0)
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.keyword)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Audio chunks that have no transcription left once these are removed. Chunks without
+    /// any are only matched by a time range alone
+    pub async fn get_audio_chunks_to_forget(
+        &self,
+        transcription_ids: &[i64],
+        filter: &ForgetFilter,
+    ) -> Result<Vec<(i64, String)>, sqlx::Error> {
+        sqlx::query_as(
+            r#"
+            SELECT id, file_path FROM audio_chunks
+            WHERE NOT EXISTS (
+                    SELECT 1 FROM audio_transcriptions
+                    WHERE audio_transcriptions.audio_chunk_id = audio_chunks.id
+                        AND audio_transcriptions.id NOT IN (SELECT value FROM json_each(?1))
+                )
+                AND (
+                    EXISTS (
+                        SELECT 1 FROM audio_transcriptions
+                        WHERE audio_transcriptions.audio_chunk_id = audio_chunks.id
+                    )
+                    OR (?2 AND (?3 IS NULL OR timestamp >= ?3) AND (?4 IS NULL OR timestamp <= ?4))
+                )
+            ORDER BY id ASC
+            "#,
+        )
+        .bind(json!(transcription_ids).to_string())
+        .bind(filter.app_name.is_none() && filter.keyword.is_none())
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    pub async fn get_latest_video_chunk_id(&self) -> Result<Option<i64>, sqlx::Error> {
+        sqlx::query_scalar("SELECT MAX(id) FROM video_chunks")
+            .fetch_one(&self.pool)
+            .await
+    }
+
+    /// Frame ids and offsets of a chunk in order
+    pub async fn get_video_chunk_frame_offsets(
+        &self,
+        video_chunk_id: i64,
+    ) -> Result<Vec<(i64, i64)>, sqlx::Error> {
+        sqlx::query_as(
+            "SELECT id, offset_index FROM frames WHERE video_chunk_id = ?1 ORDER BY offset_index ASC",
+        )
+        .bind(video_chunk_id)
+        .fetch_all(&self.pool)
+        .await
+    }
+
+    /// Deletes the rows of a plan and everything derived from them in one transaction, with
+    /// the freed pages zeroed and the database vacuumed after. A dry run rolls back and only
+    /// counts the rows
+    pub async fn forget(
+        &self,
+        filter: &ForgetFilter,
+        plan: &ForgetPlan,
+        dry_run: bool,
+    ) -> Result<ForgetReport, sqlx::Error> {
+        let frame_ids = json!(plan.frame_ids).to_string();
+        let transcription_ids = json!(plan.transcription_ids).to_string();
+        let video_chunk_ids =
+            json!(plan.video_chunks.iter().map(|(id, _)| *id).collect::<Vec<_>>()).to_string();
+        let audio_chunk_ids =
+            json!(plan.audio_chunks.iter().map(|(id, _)| *id).collect::<Vec<_>>()).to_string();
+        let mut report = ForgetReport {
+            dry_run,
+            ..Default::default()
+        };
+
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("PRAGMA secure_delete = ON")
+            .execute(&mut *conn)
+            .await?;
+        let mut tx = conn.begin().await?;
+
+        // what embeddings were computed from
+        let mut texts: Vec<String> = sqlx::query_scalar(
+            "SELECT text FROM ocr_text WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&frame_ids)
+        .fetch_all(&mut *tx)
+        .await?;
+        texts.extend(
+            sqlx::query_scalar::<_, String>(
+                "SELECT transcription FROM audio_transcriptions WHERE id IN (SELECT value FROM json_each(?1))",
+            )
+            .bind(&transcription_ids)
+            .fetch_all(&mut *tx)
+            .await?,
+        );
+
+        // kept audio chunks only lose the text of the forgotten transcriptions
+        let text_ids: Vec<i64> = sqlx::query_scalar(
+            r#"
+            DELETE FROM chunked_text_entries
+            WHERE frame_id IN (SELECT value FROM json_each(?1))
+                OR audio_chunk_id IN (SELECT value FROM json_each(?2))
+                OR (audio_chunk_id IN (
+                        SELECT audio_chunk_id FROM audio_transcriptions
+                        WHERE id IN (SELECT value FROM json_each(?3))
+                    )
+                    AND (?4 IS NULL OR timestamp >= ?4)
+                    AND (?5 IS NULL OR timestamp <= ?5)
+                    AND (?6 IS NULL OR EXISTS (
+                        SELECT 1 FROM chunked_text_index
+                        WHERE chunked_text_index.text_id = chunked_text_entries.text_id
+                            AND instr(LOWER(chunked_text_index.text), LOWER(?6)) > 0
+                    )))
+            RETURNING text_id
+            "#,
+        )
+        .bind(&frame_ids)
+        .bind(&audio_chunk_ids)
+        .bind(&transcription_ids)
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.keyword)
+        .fetch_all(&mut *tx)
+        .await?;
+        report.text_index_entries = text_ids.len() as u64;
+        // the trigger on chunked_text_index removes them from the fts table
+        texts.extend(
+            sqlx::query_scalar::<_, String>(
+                r#"
+                DELETE FROM chunked_text_index
+                WHERE text_id IN (SELECT value FROM json_each(?1))
+                    AND NOT EXISTS (
+                        SELECT 1 FROM chunked_text_entries
+                        WHERE chunked_text_entries.text_id = chunked_text_index.text_id
+                    )
+                RETURNING text
+                "#,
+            )
+            .bind(json!(text_ids).to_string())
+            .fetch_all(&mut *tx)
+            .await?,
+        );
+
+        report.extracted_records = sqlx::query(
+            "DELETE FROM extracted_records WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&frame_ids)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        report.ocr_texts = sqlx::query(
+            "DELETE FROM ocr_text WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&frame_ids)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        // the trigger on ocr_boxes removes them from the r-tree
+        report.ocr_boxes = sqlx::query(
+            "DELETE FROM ocr_boxes WHERE frame_id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&frame_ids)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        report.frames =
+            sqlx::query("DELETE FROM frames WHERE id IN (SELECT value FROM json_each(?1))")
+                .bind(&frame_ids)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        report.audio_transcriptions = sqlx::query(
+            "DELETE FROM audio_transcriptions WHERE id IN (SELECT value FROM json_each(?1))",
+        )
+        .bind(&transcription_ids)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+        report.audio_chunks_deleted =
+            sqlx::query("DELETE FROM audio_chunks WHERE id IN (SELECT value FROM json_each(?1))")
+                .bind(&audio_chunk_ids)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+        report.video_chunks_deleted =
+            sqlx::query("DELETE FROM video_chunks WHERE id IN (SELECT value FROM json_each(?1))")
+                .bind(&video_chunk_ids)
+                .execute(&mut *tx)
+                .await?
+                .rows_affected();
+
+        for chunk in &plan.rewritten_chunks {
+            for (offset_index, frame_id) in chunk.kept_frame_ids.iter().enumerate() {
+                sqlx::query("UPDATE frames SET offset_index = ?1 WHERE id = ?2")
+                    .bind(offset_index as i64)
+                    .bind(frame_id)
+                    .execute(&mut *tx)
+                    .await?;
+            }
+            // subtitles are muxed again and the new file checked and encrypted
+            sqlx::query(
+                "UPDATE video_chunks SET file_path = ?1, subtitles_muxed = FALSE, integrity = NULL, encryption_key_id = NULL WHERE id = ?2",
+            )
+            .bind(&chunk.file_path)
+            .bind(chunk.id)
+            .execute(&mut *tx)
+            .await?;
+            report.video_chunks_rewritten += 1;
+        }
+
+        // audio has no app, meetings and sessions are matched on what they are made of
+        if filter.app_name.is_none() {
+            report.meeting_summaries = sqlx::query(
+                r#"
+                DELETE FROM meeting_summaries
+                WHERE (?1 IS NULL OR end_time >= ?1)
+                    AND (?2 IS NULL OR start_time <= ?2)
+                    AND (?3 IS NULL OR instr(LOWER(summary || decisions || action_items), LOWER(?3)) > 0)
+                "#,
+            )
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.keyword)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+        if filter.keyword.is_none() && !filter.keep_activity_sessions {
+            report.activity_sessions = sqlx::query(
+                r#"
+                DELETE FROM activity_sessions
+                WHERE (?1 IS NULL OR end_time >= ?1)
+                    AND (?2 IS NULL OR start_time <= ?2)
+                    AND (?3 IS NULL OR LOWER(app_name) = LOWER(?3))
+                "#,
+            )
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.app_name)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        report.window_layouts = sqlx::query(
+            r#"
+            DELETE FROM window_layouts
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR EXISTS (
+                    SELECT 1 FROM json_each(window_layouts.windows)
+                    WHERE LOWER(json_extract(value, '$.app_name')) = LOWER(?3)
+                ))
+                AND (?4 IS NULL OR EXISTS (
+                    SELECT 1 FROM json_each(window_layouts.windows)
+                    WHERE instr(LOWER(json_extract(value, '$.window_name')), LOWER(?4)) > 0
+                ))
+            "#,
+        )
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.app_name)
+        .bind(&filter.keyword)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        let marker_ids = json!(plan.markers.iter().map(|(id, _)| *id).collect::<Vec<_>>()).to_string();
+        report.markers = sqlx::query("DELETE FROM markers WHERE id IN (SELECT value FROM json_each(?1))")
+            .bind(&marker_ids)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+        report.notifications = sqlx::query(
+            r#"
+            DELETE FROM notifications
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR instr(LOWER(app_name), LOWER(?3)) > 0)
+                AND (?4 IS NULL OR instr(LOWER(title || ' ' || COALESCE(body, '')), LOWER(?4)) > 0)
+            "#,
+        )
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.app_name)
+        .bind(&filter.keyword)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        report.file_events = sqlx::query(
+            r#"
+            DELETE FROM file_events
+            WHERE (?1 IS NULL OR timestamp >= ?1)
+                AND (?2 IS NULL OR timestamp <= ?2)
+                AND (?3 IS NULL OR LOWER(app_name) = LOWER(?3))
+                AND (?4 IS NULL OR instr(LOWER(path || COALESCE(window_name, '')), LOWER(?4)) > 0)
+            "#,
+        )
+        .bind(filter.start_time)
+        .bind(filter.end_time)
+        .bind(&filter.app_name)
+        .bind(&filter.keyword)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if filter.app_name.is_none() {
+            report.shell_commands = sqlx::query(
+                r#"
+                DELETE FROM shell_commands
+                WHERE (?1 IS NULL OR timestamp >= ?1)
+                    AND (?2 IS NULL OR timestamp <= ?2)
+                    AND (?3 IS NULL OR instr(LOWER(command || ' ' || COALESCE(cwd, '')), LOWER(?3)) > 0)
+                "#,
+            )
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.keyword)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            report.git_events = sqlx::query(
+                r#"
+                DELETE FROM git_events
+                WHERE (?1 IS NULL OR timestamp >= ?1)
+                    AND (?2 IS NULL OR timestamp <= ?2)
+                    AND (?3 IS NULL OR instr(LOWER(message || ' ' || repository || ' ' || COALESCE(branch, '')), LOWER(?3)) > 0)
+                "#,
+            )
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.keyword)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            report.editor_contexts = sqlx::query(
+                r#"
+                DELETE FROM editor_contexts
+                WHERE (?1 IS NULL OR ended_at >= ?1)
+                    AND (?2 IS NULL OR timestamp <= ?2)
+                    AND (?3 IS NULL OR instr(LOWER(COALESCE(file, '') || ' ' || COALESCE(project, '') || ' ' || COALESCE(repository, '') || ' ' || COALESCE(git_branch, '')), LOWER(?3)) > 0)
+                "#,
+            )
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.keyword)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+
+            report.conversation_messages = sqlx::query(
+                r#"
+                DELETE FROM conversation_messages
+                WHERE (?1 IS NULL OR timestamp >= ?1)
+                    AND (?2 IS NULL OR timestamp <= ?2)
+                    AND (?3 IS NULL OR instr(LOWER(content), LOWER(?3)) > 0)
+                "#,
+            )
+            .bind(filter.start_time)
+            .bind(filter.end_time)
+            .bind(&filter.keyword)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        }
+
+        let models: Vec<(String, String)> = sqlx::query_as(
+            "SELECT DISTINCT api_url, model FROM llm_cache WHERE kind = 'embedding'",
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+        let keys: Vec<String> = models
+            .iter()
+            .flat_map(|(api_url, model)| {
+                texts
+                    .iter()
+                    .map(move |text| cache_key(api_url, model, "embedding", text))
+            })
+            .collect();
+        report.cached_llm_responses = sqlx::query(
+            r#"
+            DELETE FROM llm_cache
+            WHERE key IN (SELECT value FROM json_each(?1))
+                OR (?2 IS NOT NULL AND instr(LOWER(response), LOWER(?2)) > 0)
+            "#,
+        )
+        .bind(json!(keys).to_string())
+        .bind(&filter.keyword)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+
+        if dry_run {
+            tx.rollback().await?;
+            return Ok(report);
+        }
+        tx.commit().await?;
+
+        for fts_table in ["chunked_text_index_fts", "ocr_text_fts", "audio_transcriptions_fts"] {
+            sqlx::query(&format!(
+                "INSERT INTO {}({}) VALUES('optimize')",
+                fts_table, fts_table
+            ))
+            .execute(&mut *conn)
+            .await?;
+        }
+        sqlx::query("PRAGMA secure_delete = OFF")
+            .execute(&mut *conn)
+            .await?;
+        // nothing of the deleted rows is left in the file or the wal
+        sqlx::query("VACUUM").execute(&mut *conn).await?;
+        sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+            .execute(&mut *conn)
+            .await?;
+        Ok(report)
+    }
+}
+
+/// Raw key literal for the SQLCipher `key` pragmas
+fn sqlcipher_key(key: &str) -> String {
+    format!("\"x'{}'\"", key)
+}
+
+pub fn is_plaintext_database(database_path: &str) -> bool {
+    let mut header = [0u8; 16];
+    std::fs::File::open(database_path)
+        .and_then(|mut file| std::io::Read::read_exact(&mut file, &mut header))
+        .is_ok_and(|_| &header == b"SQLite format 3\0")
+}
+
+/// A database file screenpipe doesn't have open, like a restored backup. `key` is None when
+/// it's plaintext
+async fn connect_database_file(
+    database_path: &str,
+    key: Option<&str>,
+) -> Result<SqliteConnection, sqlx::Error> {
+    let mut options = SqliteConnectOptions::from_str(&format!("sqlite:{}", database_path))?;
+    if let Some(key) = key {
+        options = options.pragma("key", sqlcipher_key(key));
+    }
+    let mut conn = options.connect().await?;
+    let tables: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name IN ('frames', 'video_chunks', 'audio_chunks')",
+    )
+    .fetch_one(&mut conn)
+    .await?;
+    if tables != 3 {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} is not a screenpipe database",
+            database_path
+        )));
+    }
+    Ok(conn)
+}
+
+/// What `PRAGMA integrity_check` finds wrong with a database file, nothing when it's sound.
+/// Fails when `key` doesn't open it
+pub async fn check_database_file(
+    database_path: &str,
+    key: Option<&str>,
+) -> Result<Vec<String>, sqlx::Error> {
+    let mut conn = connect_database_file(database_path, key).await?;
+    let problems: Vec<String> = sqlx::query_scalar("PRAGMA integrity_check")
+        .fetch_all(&mut conn)
+        .await?;
+    conn.close().await?;
+    Ok(problems
+        .into_iter()
+        .filter(|problem| problem != "ok")
+        .collect())
+}
+
+/// The chunks a database file points at, as (kind, id, file path)
+pub async fn get_database_file_chunks(
+    database_path: &str,
+    key: Option<&str>,
+) -> Result<Vec<(ChunkKind, i64, String)>, sqlx::Error> {
+    let mut conn = connect_database_file(database_path, key).await?;
+    let mut chunks = Vec::new();
+    for kind in [ChunkKind::Video, ChunkKind::Audio] {
+        let rows: Vec<(i64, String)> = sqlx::query_as(&format!(
+            "SELECT id, file_path FROM {} ORDER BY id ASC",
+            kind.table()
+        ))
+        .fetch_all(&mut conn)
+        .await?;
+        chunks.extend(rows.into_iter().map(|(id, path)| (kind, id, path)));
+    }
+    conn.close().await?;
+    Ok(chunks)
+}
+
+/// Points a chunk of a database file at a plaintext file
+pub async fn set_database_file_chunk_path(
+    database_path: &str,
+    key: Option<&str>,
+    kind: ChunkKind,
+    id: i64,
+    file_path: &str,
+) -> Result<(), sqlx::Error> {
+    let mut conn = connect_database_file(database_path, key).await?;
+    sqlx::query(&format!(
+        "UPDATE {} SET file_path = ?1, encryption_key_id = NULL WHERE id = ?2",
+        kind.table()
+    ))
+    .bind(file_path)
+    .bind(id)
+    .execute(&mut conn)
+    .await?;
+    conn.close().await?;
+    Ok(())
+}
+
+/// The columns holding paths of files under the media directory, as (table, column)
+const MEDIA_PATH_COLUMNS: [(&str, &str); 9] = [
+    ("video_chunks", "file_path"),
+    ("audio_chunks", "file_path"),
+    ("pending_transcriptions", "file_path"),
+    ("backup_uploads", "file_path"),
+    ("markers", "image_path"),
+    ("slides", "image_path"),
+    ("timelapse_jobs", "output_path"),
+    ("clip_jobs", "output_path"),
+    ("thumbnail_keys", "path"),
+];
+
+/// Rewrites the paths of a database file starting with `from` to start with `to` instead, in
+/// one transaction. Returns how many rows were rewritten
+pub async fn rewrite_database_file_paths(
+    database_path: &str,
+    key: Option<&str>,
+    from: &str,
+    to: &str,
+) -> Result<u64, sqlx::Error> {
+    let mut conn = connect_database_file(database_path, key).await?;
+    let mut tx = conn.begin().await?;
+    let mut rewritten = 0;
+    for (table, column) in MEDIA_PATH_COLUMNS {
+        rewritten += sqlx::query(&format!(
+            "UPDATE {table} SET {column} = ?2 || substr({column}, length(?1) + 1) WHERE substr({column}, 1, length(?1)) = ?1"
+        ))
+        .bind(from)
+        .bind(to)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    }
+    tx.commit().await?;
+    conn.close().await?;
+    Ok(rewritten)
+}
+
+/// Copies the database into an encrypted one that then replaces it
+async fn encrypt_plaintext_database(database_path: &str, key: &str) -> Result<(), sqlx::Error> {
+    let encrypted_path = format!("{}.encrypted", database_path);
+    let _ = std::fs::remove_file(&encrypted_path);
+    // lets the attach create the encrypted copy
+    let mut conn = SqliteConnectOptions::from_str(&format!("sqlite:{}", database_path))?
+        .create_if_missing(true)
+        .connect()
+        .await?;
+    sqlx::query("PRAGMA wal_checkpoint(TRUNCATE)")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(&format!(
+        "ATTACH DATABASE '{}' AS encrypted KEY {}",
+        encrypted_path.replace('\'', "''"),
+        sqlcipher_key(key)
+    ))
+    .execute(&mut conn)
+    .await?;
+    sqlx::query("SELECT sqlcipher_export('encrypted')")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query("DETACH DATABASE encrypted")
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+
+    std::fs::rename(&encrypted_path, database_path)?;
+    for suffix in ["-wal", "-shm"] {
+        let _ = std::fs::remove_file(format!("{}{}", database_path, suffix));
+    }
+    Ok(())
+}
+
+/// Moves the database to the new key, nothing to do if it already has it
+async fn rekey_database(
+    database_path: &str,
+    previous_key: &str,
+    key: &str,
+) -> Result<(), sqlx::Error> {
+    let conn = SqliteConnectOptions::from_str(&format!("sqlite:{}", database_path))?
+        .pragma("key", sqlcipher_key(previous_key))
+        .connect()
+        .await;
+    // the previous key doesn't open it, it was re-keyed before
+    let Ok(mut conn) = conn else {
+        return Ok(());
+    };
+    if sqlx::query("SELECT COUNT(*) FROM sqlite_master")
+        .execute(&mut conn)
+        .await
+        .is_err()
+    {
+        return Ok(());
+    }
+    // re-keying needs a rollback journal, wal comes back on the next connection
+    sqlx::query("PRAGMA journal_mode = DELETE")
+        .execute(&mut conn)
+        .await?;
+    sqlx::query(&format!("PRAGMA rekey = {}", sqlcipher_key(key)))
+        .execute(&mut conn)
+        .await?;
+    conn.close().await?;
+    info!("Re-keyed the database after a key rotation");
+    Ok(())
+}
+
+fn editor_context_from_row(row: SqliteRow) -> EditorContext {
+    let timestamp: DateTime<Utc> = row.get("timestamp");
+    let ended_at: DateTime<Utc> = row.get("ended_at");
+    EditorContext {
+        id: row.get("id"),
+        timestamp,
+        ended_at,
+        duration_secs: (ended_at - timestamp).num_milliseconds() as f64 / 1000.0,
+        editor: row.get("editor"),
+        project: row.get("project"),
+        repository: row.get("repository"),
+        git_branch: row.get("git_branch"),
+        file: row.get("file"),
+        language: row.get("language"),
+        frame_ids: Vec::new(),
+    }
+}
+
+fn marker_from_row(row: SqliteRow) -> Marker {
+    Marker {
+        id: row.get("id"),
+        timestamp: row.get("timestamp"),
+        note: row.get("note"),
+        image_path: row.get("image_path"),
+        frame_id: row.get("frame_id"),
+        memo_secs: row.get("memo_secs"),
+        memo: row.get("memo"),
+    }
+}
+
+/// The frames of a region search from ?1 to ?10: the r-tree narrows the boxes down to the
+/// ones overlapping the region in its time range, their exact centers and the frames' own
+/// timestamps decide
+const REGION_FRAMES: &str = r#"
+    FROM frames
+    JOIN ocr_text ON ocr_text.frame_id = frames.id
+    WHERE frames.id IN (
+            SELECT ocr_boxes.frame_id
+            FROM ocr_boxes_rtree
+            JOIN ocr_boxes ON ocr_boxes.id = ocr_boxes_rtree.id
+            WHERE ocr_boxes_rtree.max_x >= ?1 AND ocr_boxes_rtree.min_x <= ?2
+                AND ocr_boxes_rtree.max_y >= ?3 AND ocr_boxes_rtree.min_y <= ?4
+                AND (?5 IS NULL OR ocr_boxes_rtree.max_t >= ?5)
+                AND (?6 IS NULL OR ocr_boxes_rtree.min_t <= ?6)
+                AND ocr_boxes.x + ocr_boxes.width / 2 BETWEEN ?1 AND ?2
+                AND ocr_boxes.y + ocr_boxes.height / 2 BETWEEN ?3 AND ?4
+                AND (?7 IS NULL OR ocr_boxes.monitor_id = ?7)
+        )
+        AND (?8 IS NULL OR frames.timestamp >= ?8)
+        AND (?9 IS NULL OR frames.timestamp <= ?9)
+        AND (?10 IS NULL OR ocr_text.app_name = ?10 COLLATE NOCASE)
+"#;
+
+fn region_bounds(region: &Rect) -> (f64, f64, f64, f64) {
+    (region.x, region.x + region.width, region.y, region.y + region.height)
+}
+
+/// Times in the r-tree, which only stores numbers
+fn unix_seconds(time: DateTime<Utc>) -> f64 {
+    time.timestamp_micros() as f64 / 1_000_000.0
+}
+
+fn window_layout_from_row(row: SqliteRow) -> WindowLayout {
+    let windows: String = row.get("windows");
+    WindowLayout {
+        id: row.get("id"),
+        timestamp: row.get("timestamp"),
+        monitor_id: row.get::<i64, _>("monitor_id") as u32,
+        windows: serde_json::from_str(&windows).unwrap_or_default(),
+    }
+}
+
+fn slide_deck_from_row(row: SqliteRow) -> SlideDeck {
+    SlideDeck {
+        id: row.get("id"),
+        started_at: row.get("started_at"),
+        ended_at: row.get("ended_at"),
+        monitor_id: row.get("monitor_id"),
+        app_name: row.get("app_name"),
+        window_name: row.get("window_name"),
+        slide_count: row.get("slide_count"),
+    }
+}
+
+fn slide_from_row(row: SqliteRow) -> Slide {
+    Slide {
+        id: row.get("id"),
+        deck_id: row.get("deck_id"),
+        position: row.get("position"),
+        timestamp: row.get("timestamp"),
+        image_path: row.get("image_path"),
+        width: row.get("width"),
+        height: row.get("height"),
+        text: row.get("text"),
+    }
+}
+
+fn conversation_from_row(row: SqliteRow) -> Conversation {
+    Conversation {
+        id: row.get("id"),
+        title: row.get("title"),
+        pipe: row.get("pipe"),
+        created_at: row.get("created_at"),
+        updated_at: row.get("updated_at"),
+        message_count: row.get("message_count"),
+    }
+}
+
+fn meeting_summary_from_row(row: SqliteRow) -> MeetingSummary {
+    let decisions: String = row.get("decisions");
+    let action_items: String = row.get("action_items");
+    let calendar_attendees: String = row.get("calendar_attendees");
+    MeetingSummary {
+        id: row.get("id"),
+        start_time: row.get("start_time"),
+        end_time: row.get("end_time"),
+        summary: row.get("summary"),
+        decisions: serde_json::from_str(&decisions).unwrap_or_default(),
+        action_items: serde_json::from_str(&action_items).unwrap_or_default(),
+        llm_model: row.get("llm_model"),
+        prompt_version: row.get("prompt_version"),
+        created_at: row.get("created_at"),
+        calendar_title: row.get("calendar_title"),
+        calendar_attendees: serde_json::from_str(&calendar_attendees).unwrap_or_default(),
+    }
+}
+
+impl Clone for DatabaseManager {
+    fn clone(&self) -> Self {
+        DatabaseManager {
+            pool: self.pool.clone(),
+        }
+    }
+}
+
+/// Chunks of the text for the chunked text index and the engine that made them, the whole
+/// text when chunking fails
+fn chunks_for_index(text: &str) -> (Vec<String>, &'static str) {
+    match text_chunking_simple(text) {
+        Ok(chunks) => (chunks, "local_simple"),
+        Err(e) => {
+            error!("Failed to chunk text: {}", e);
+            (vec![text.to_string()], "No_Chunking")
+        }
+    }
+}
+
+async fn insert_chunked_entry(
+    conn: &mut SqliteConnection,
+    id: i64,
+    text: &str,
+    timestamp: DateTime<Utc>,
+    engine: &str,
+    chunking_engine: &str,
+    source: ContentSource,
+) -> Result<(), sqlx::Error> {
+    // Insert or get the text_id
+    let text_id: i64 = sqlx::query_scalar(
+        "INSERT INTO chunked_text_index (text) VALUES (?1) ON CONFLICT(text) DO UPDATE SET text=text RETURNING text_id",
+    )
+    .bind(text)
+    .fetch_one(&mut *conn)
+    .await?;
+
+    // Insert the entry into chunked_text_entries
+    let query = match source {
+        ContentSource::Audio => "INSERT INTO chunked_text_entries (text_id, audio_chunk_id, timestamp, engine, chunking_engine, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        ContentSource::Screen => "INSERT INTO chunked_text_entries (text_id, frame_id, timestamp, engine, chunking_engine, source) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    };
+
+    sqlx::query(query)
+        .bind(text_id)
+        .bind(id)
+        .bind(timestamp)
+        .bind(engine)
+        .bind(chunking_engine)
+        .bind(source.to_string())
+        .execute(&mut *conn)
+        .await?;
+    Ok(())
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
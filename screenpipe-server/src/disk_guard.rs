@@ -0,0 +1,151 @@
+use crate::forget::{forget, ForgetFilter};
+use crate::DatabaseManager;
+use chrono::Utc;
+use log::{info, warn};
+use screenpipe_core::emit_event;
+use serde::Serialize;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use sysinfo::{DiskExt, System, SystemExt};
+
+/// What is kept while the disk is almost full, the rest is deleted to make room
+const SHORT_RETENTION_DAYS: i64 = 7;
+
+static DISK_LEVEL: AtomicU8 = AtomicU8::new(DiskLevel::Normal as u8);
+
+/// How far recording is degraded to keep the disk from filling up, each level does what
+/// the ones before it do
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskLevel {
+    Normal,
+    /// No full resolution screenshots besides the video, markers only keep their note
+    NoRawFrames,
+    /// New video chunks are encoded at low quality
+    LowBitrate,
+    /// What was recorded more than a week ago is deleted
+    ShortRetention,
+    /// No frame or audio is written until there is room again
+    MediaPaused,
+}
+
+impl DiskLevel {
+    const DEGRADED: [DiskLevel; 4] = [
+        DiskLevel::NoRawFrames,
+        DiskLevel::LowBitrate,
+        DiskLevel::ShortRetention,
+        DiskLevel::MediaPaused,
+    ];
+
+    /// Free space below which the level is reached, a fraction of where degrading starts
+    fn threshold(&self, start: u64) -> u64 {
+        match self {
+            DiskLevel::Normal => u64::MAX,
+            DiskLevel::NoRawFrames => start,
+            DiskLevel::LowBitrate => start / 2,
+            DiskLevel::ShortRetention => start / 5,
+            DiskLevel::MediaPaused => start / 10,
+        }
+    }
+
+    fn from_u8(value: u8) -> Self {
+        DiskLevel::DEGRADED
+            .into_iter()
+            .find(|level| *level as u8 == value)
+            .unwrap_or(DiskLevel::Normal)
+    }
+
+    /// The level for `free` bytes, degrading starts below `start`. A level is only left with
+    /// 10% more than its threshold free, so it doesn't flap around it
+    pub fn for_free_space(free: u64, start: u64, current: DiskLevel) -> Self {
+        let mut level = DiskLevel::Normal;
+        for candidate in DiskLevel::DEGRADED {
+            let threshold = candidate.threshold(start);
+            let threshold = if candidate <= current {
+                threshold + threshold / 10
+            } else {
+                threshold
+            };
+            if free < threshold {
+                level = candidate;
+            }
+        }
+        level
+    }
+}
+
+/// The level set by the guard, Normal when it isn't running
+pub fn disk_level() -> DiskLevel {
+    DiskLevel::from_u8(DISK_LEVEL.load(Ordering::SeqCst))
+}
+
+pub fn set_disk_level(level: DiskLevel) {
+    DISK_LEVEL.store(level as u8, Ordering::SeqCst);
+}
+
+/// Checked by the screen and audio recording before anything is written
+pub fn media_writing_paused() -> bool {
+    disk_level() >= DiskLevel::MediaPaused
+}
+
+/// Bytes available on the disk `dir` is on, None when the disk isn't found
+pub fn free_space(dir: &Path) -> Option<u64> {
+    let dir = dir.canonicalize().unwrap_or_else(|_| dir.to_path_buf());
+    let mut sys = System::new();
+    sys.refresh_disks_list();
+    sys.disks()
+        .iter()
+        .filter(|disk| dir.starts_with(disk.mount_point()))
+        .max_by_key(|disk| disk.mount_point().as_os_str().len())
+        .map(|disk| disk.available_space())
+}
+
+/// Degrades recording as the disk of `data_dir` fills up, from `start` bytes free, and
+/// restores it once there is room again
+pub async fn start_disk_guard(
+    db: Arc<DatabaseManager>,
+    data_dir: PathBuf,
+    start: u64,
+    interval: Duration,
+) {
+    info!(
+        "Disk guard started, degrading recording below {:.1} GB free",
+        start as f64 / 1e9
+    );
+    loop {
+        if let Some(free) = free_space(&data_dir) {
+            let current = disk_level();
+            let level = DiskLevel::for_free_space(free, start, current);
+            if level != current {
+                set_disk_level(level);
+                let free_gb = free as f64 / 1e9;
+                if level == DiskLevel::Normal {
+                    info!("Disk space recovered, {:.1} GB free", free_gb);
+                    emit_event("disk.recovered", json!({"free_bytes": free}));
+                } else {
+                    warn!(
+                        "Only {:.1} GB free, recording degraded to {:?}",
+                        free_gb, level
+                    );
+                    emit_event(
+                        "disk.degraded",
+                        json!({"level": level, "previous": current, "free_bytes": free}),
+                    );
+                }
+            }
+            if level >= DiskLevel::ShortRetention {
+                let filter = ForgetFilter {
+                    end_time: Some(Utc::now() - chrono::Duration::days(SHORT_RETENTION_DAYS)),
+                    ..Default::default()
+                };
+                if let Err(e) = forget(&db, &filter, false).await {
+                    warn!("Failed to delete old data to free disk space: {}", e);
+                }
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
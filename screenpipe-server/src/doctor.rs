@@ -0,0 +1,451 @@
+use crate::disk_guard::free_space;
+use image::{DynamicImage, ImageBuffer, Rgb};
+use screenpipe_core::{discover_accelerators, find_ffmpeg_path};
+use screenpipe_vision::core::perform_ocr;
+use screenpipe_vision::monitor::{get_monitor_by_id, list_monitors};
+use screenpipe_vision::utils::capture_screenshot;
+use screenpipe_vision::OcrEngine;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Below it screenpipe stops being able to store a day of recording
+const MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+const LOW_FREE_SPACE: u64 = 10 * 1024 * 1024 * 1024;
+/// An engine taking longer than this on a blank image is as good as broken
+const OCR_TIMEOUT: Duration = Duration::from_secs(30);
+/// How often the smoke test captures, the default --fps
+const SMOKE_TEST_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Pass,
+    /// Works, but not as well as it could
+    Warn,
+    /// screenpipe won't record until it is fixed
+    Fail,
+    /// Not run, because of an earlier failure or the options
+    Skipped,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorCheck {
+    /// Stable ids like "ffmpeg" or "disk_space", for installers to show their own texts
+    pub name: &'static str,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What the user can do about a warning or failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl DoctorCheck {
+    pub fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        DoctorCheck {
+            name,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            fix: None,
+        }
+    }
+
+    pub fn warn(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        DoctorCheck {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    pub fn fail(name: &'static str, message: impl Into<String>, fix: impl Into<String>) -> Self {
+        DoctorCheck {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            fix: Some(fix.into()),
+        }
+    }
+
+    pub fn skipped(name: &'static str, message: impl Into<String>) -> Self {
+        DoctorCheck {
+            name,
+            status: CheckStatus::Skipped,
+            message: message.into(),
+            fix: None,
+        }
+    }
+}
+
+/// What `--doctor` prints, as one line of json
+#[derive(Debug, Clone, Serialize)]
+pub struct DoctorReport {
+    /// No check failed, warnings included
+    pub passed: bool,
+    pub version: &'static str,
+    pub os: &'static str,
+    pub checks: Vec<DoctorCheck>,
+}
+
+impl DoctorReport {
+    pub fn new(checks: Vec<DoctorCheck>) -> Self {
+        DoctorReport {
+            passed: checks.iter().all(|check| check.status != CheckStatus::Fail),
+            version: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            checks,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DoctorOptions {
+    /// Where the database and media would be stored
+    pub data_dir: PathBuf,
+    pub ocr_engine: OcrEngine,
+    /// The first monitor when not set, like the recording
+    pub monitor_id: Option<u32>,
+    /// The microphone isn't checked with --disable-audio
+    pub audio: bool,
+    /// How long the capture and OCR smoke test runs, not run when zero
+    pub smoke_test: Duration,
+}
+
+/// Every check, in the order an installer would fix them
+pub async fn run_doctor(options: &DoctorOptions) -> DoctorReport {
+    let mut checks = vec![
+        check_data_dir(&options.data_dir),
+        check_disk_space(&options.data_dir),
+        check_ffmpeg().await,
+        check_gpu(),
+    ];
+    let (screen, monitor_id) = check_screen(options.monitor_id).await;
+    checks.push(screen);
+    checks.push(check_microphone(options.audio).await);
+    let ocr = check_ocr_engine(&options.ocr_engine).await;
+    let ocr_works = ocr.status == CheckStatus::Pass;
+    checks.push(ocr);
+    checks.push(match monitor_id {
+        _ if options.smoke_test.is_zero() => DoctorCheck::skipped("smoke_test", "not requested"),
+        Some(monitor_id) if ocr_works => {
+            smoke_test(monitor_id, &options.ocr_engine, options.smoke_test).await
+        }
+        _ => DoctorCheck::skipped("smoke_test", "needs the screen capture and OCR to work"),
+    });
+    DoctorReport::new(checks)
+}
+
+/// The database, media and logs are written there
+pub fn check_data_dir(data_dir: &Path) -> DoctorCheck {
+    let fix = format!(
+        "check the permissions of {} or pick another directory with --data-dir",
+        data_dir.display()
+    );
+    let probe = data_dir.join("data").join(".doctor");
+    let written = std::fs::create_dir_all(data_dir.join("data"))
+        .and_then(|_| std::fs::write(&probe, b"screenpipe"))
+        .and_then(|_| std::fs::remove_file(&probe));
+    match written {
+        Ok(()) => DoctorCheck::pass("data_dir", format!("{} is writable", data_dir.display())),
+        Err(e) => DoctorCheck::fail(
+            "data_dir",
+            format!("{} can't be written: {}", data_dir.display(), e),
+            fix,
+        ),
+    }
+}
+
+/// Free space of the disk the data directory is on
+pub fn check_disk_space(data_dir: &Path) -> DoctorCheck {
+    let Some(available) = free_space(data_dir) else {
+        return DoctorCheck::skipped(
+            "disk_space",
+            format!("the disk of {} wasn't found", data_dir.display()),
+        );
+    };
+    disk_space_check(available)
+}
+
+pub fn disk_space_check(available: u64) -> DoctorCheck {
+    let message = format!("{:.1} GB free", available as f64 / 1e9);
+    let fix = "free some space, or lower the --fps and use --retention-days so less is kept";
+    if available < MIN_FREE_SPACE {
+        DoctorCheck::fail("disk_space", message, fix)
+    } else if available < LOW_FREE_SPACE {
+        DoctorCheck::warn("disk_space", message, fix)
+    } else {
+        DoctorCheck::pass("disk_space", message)
+    }
+}
+
+async fn check_ffmpeg() -> DoctorCheck {
+    let Some(path) = find_ffmpeg_path() else {
+        if cfg!(feature = "native-encoder") {
+            return DoctorCheck::warn(
+                "ffmpeg",
+                "ffmpeg not found, frames are stored as WebP images and audio is disabled",
+                "install ffmpeg or start with --ffmpeg-source auto to download it",
+            );
+        }
+        return DoctorCheck::fail(
+            "ffmpeg",
+            "ffmpeg not found",
+            "install ffmpeg or start with --ffmpeg-source auto to download it",
+        );
+    };
+    let output = tokio::process::Command::new(&path)
+        .arg("-version")
+        .output()
+        .await;
+    match output {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let version = stdout.lines().next().unwrap_or_default();
+            DoctorCheck::pass("ffmpeg", format!("{} ({})", version, path.display()))
+        }
+        Ok(output) => DoctorCheck::fail(
+            "ffmpeg",
+            format!("{} exited with {}", path.display(), output.status),
+            "reinstall ffmpeg or start with --ffmpeg-source pinned",
+        ),
+        Err(e) => DoctorCheck::fail(
+            "ffmpeg",
+            format!("{} can't be run: {}", path.display(), e),
+            "reinstall ffmpeg or start with --ffmpeg-source pinned",
+        ),
+    }
+}
+
+/// Whether the gpus this build runs the local models on can be opened
+fn check_gpu() -> DoctorCheck {
+    let (backend, device) = if cfg!(feature = "cuda") {
+        ("cuda", candle::Device::new_cuda(0))
+    } else if cfg!(feature = "metal") {
+        ("metal", candle::Device::new_metal(0))
+    } else {
+        return DoctorCheck::pass("gpu", "this build runs the local models on the cpu");
+    };
+    match device {
+        Ok(_) => {
+            let accelerators: Vec<String> = discover_accelerators()
+                .iter()
+                .map(|accelerator| accelerator.to_string())
+                .collect();
+            DoctorCheck::pass(
+                "gpu",
+                format!("the local models are scheduled on {}", accelerators.join(", ")),
+            )
+        }
+        Err(e) => DoctorCheck::fail(
+            "gpu",
+            format!(
+                "this build runs on {} but no device can be opened: {}",
+                backend, e
+            ),
+            "update the gpu driver, or use a build without gpu acceleration",
+        ),
+    }
+}
+
+fn screen_permission_fix() -> &'static str {
+    if cfg!(target_os = "macos") {
+        "allow screenpipe in System Settings > Privacy & Security > Screen Recording, then restart it"
+    } else if cfg!(target_os = "linux") {
+        "run screenpipe in the desktop session, with DISPLAY or WAYLAND_DISPLAY set"
+    } else {
+        "run screenpipe in the desktop session of the user"
+    }
+}
+
+/// Id of the monitor recorded, when a screenshot of it can be taken
+async fn check_screen(monitor_id: Option<u32>) -> (DoctorCheck, Option<u32>) {
+    // listing and capturing panic when the screen can't be read
+    let monitor = match monitor_id {
+        Some(id) => tokio::spawn(async move { get_monitor_by_id(id).await }).await,
+        None => tokio::spawn(async { list_monitors().await.into_iter().next() }).await,
+    };
+    let monitor = match monitor {
+        Ok(Some(monitor)) => monitor,
+        Ok(None) if monitor_id.is_some() => {
+            let check = DoctorCheck::fail(
+                "screen_capture",
+                format!("monitor {} not found", monitor_id.unwrap_or_default()),
+                "pick one of --list-monitors with --monitor-id",
+            );
+            return (check, None);
+        }
+        _ => {
+            let check = DoctorCheck::fail(
+                "screen_capture",
+                "no monitor can be read",
+                screen_permission_fix(),
+            );
+            return (check, None);
+        }
+    };
+
+    let image = match tokio::spawn(capture_screenshot(Arc::new(monitor.clone()))).await {
+        Ok(Ok((image, ..))) => image,
+        Ok(Err(e)) => {
+            let check = DoctorCheck::fail(
+                "screen_capture",
+                format!("monitor {} can't be captured: {}", monitor.id(), e),
+                screen_permission_fix(),
+            );
+            return (check, None);
+        }
+        Err(_) => {
+            let check = DoctorCheck::fail(
+                "screen_capture",
+                format!("monitor {} can't be captured", monitor.id()),
+                screen_permission_fix(),
+            );
+            return (check, None);
+        }
+    };
+    let message = format!(
+        "monitor {} captured at {}x{}",
+        monitor.id(),
+        image.width(),
+        image.height()
+    );
+    // without the permission macOS captures the wallpaper or a single color instead of an error
+    let luma = image.to_luma8();
+    let first = luma.pixels().next().copied();
+    let check = if luma.pixels().all(|pixel| Some(*pixel) == first) {
+        DoctorCheck::warn(
+            "screen_capture",
+            format!("{}, but the capture is blank", message),
+            screen_permission_fix(),
+        )
+    } else {
+        DoctorCheck::pass("screen_capture", message)
+    };
+    (check, Some(monitor.id()))
+}
+
+async fn check_microphone(audio: bool) -> DoctorCheck {
+    if !audio {
+        return DoctorCheck::skipped("microphone", "audio is disabled");
+    }
+    let fix = if cfg!(target_os = "macos") {
+        "allow screenpipe in System Settings > Privacy & Security > Microphone, or start with --disable-audio"
+    } else {
+        "connect a microphone, or start with --disable-audio"
+    };
+    // panics without any input device
+    match tokio::task::spawn_blocking(screenpipe_audio::default_input_device).await {
+        Ok(Ok(device)) => DoctorCheck::pass("microphone", format!("{} is available", device)),
+        Ok(Err(e)) => DoctorCheck::fail("microphone", format!("no input device: {}", e), fix),
+        Err(_) => DoctorCheck::fail("microphone", "no input device", fix),
+    }
+}
+
+/// Runs the engine on a blank image, which is enough to know it is installed and answers
+async fn check_ocr_engine(ocr_engine: &OcrEngine) -> DoctorCheck {
+    if matches!(ocr_engine, OcrEngine::Tesseract) {
+        if let Err(e) = rusty_tesseract::get_tesseract_version() {
+            return DoctorCheck::fail(
+                "ocr_engine",
+                format!("tesseract can't be run: {}", e),
+                "install tesseract (brew install tesseract, apt install tesseract-ocr), or pick another --ocr-engine",
+            );
+        }
+    }
+    let image: DynamicImage = ImageBuffer::from_pixel(64, 64, Rgb([255u8, 255, 255])).into();
+    let engine = ocr_engine.clone();
+    let started = Instant::now();
+    let ocr = tokio::time::timeout(
+        OCR_TIMEOUT,
+        tokio::spawn(async move { perform_ocr(&engine, &Arc::new(image)).await }),
+    )
+    .await;
+    let fix = "check the engine's setup, or pick another --ocr-engine";
+    match ocr {
+        Ok(Ok(Ok(_))) => DoctorCheck::pass(
+            "ocr_engine",
+            format!(
+                "{:?} answered in {}ms",
+                ocr_engine,
+                started.elapsed().as_millis()
+            ),
+        ),
+        Ok(Ok(Err(e))) => DoctorCheck::fail("ocr_engine", e.to_string(), fix),
+        Ok(Err(_)) => DoctorCheck::fail("ocr_engine", format!("{:?} crashed", ocr_engine), fix),
+        Err(_) => DoctorCheck::fail(
+            "ocr_engine",
+            format!(
+                "{:?} didn't answer in {}s",
+                ocr_engine,
+                OCR_TIMEOUT.as_secs()
+            ),
+            fix,
+        ),
+    }
+}
+
+/// Captures and reads the screen as the recording would, nothing is stored
+async fn smoke_test(monitor_id: u32, ocr_engine: &OcrEngine, duration: Duration) -> DoctorCheck {
+    let Some(monitor) = get_monitor_by_id(monitor_id).await else {
+        return DoctorCheck::fail(
+            "smoke_test",
+            format!("monitor {} is gone", monitor_id),
+            "run it again with the monitor connected",
+        );
+    };
+    let monitor = Arc::new(monitor);
+    let started = Instant::now();
+    let mut frames = 0u32;
+    let mut characters = 0usize;
+    let mut ocr_time = Duration::ZERO;
+    let mut last_error = None;
+    while started.elapsed() < duration {
+        let tick = Instant::now();
+        match tokio::spawn(capture_screenshot(monitor.clone())).await {
+            Ok(Ok((image, ..))) => {
+                let ocr_started = Instant::now();
+                match perform_ocr(ocr_engine, &Arc::new(image)).await {
+                    Ok((text, _)) => {
+                        frames += 1;
+                        characters += text.trim().chars().count();
+                        ocr_time += ocr_started.elapsed();
+                    }
+                    Err(e) => last_error = Some(format!("OCR failed: {}", e)),
+                }
+            }
+            Ok(Err(e)) => last_error = Some(format!("capture failed: {}", e)),
+            Err(_) => last_error = Some("capture crashed".to_string()),
+        }
+        tokio::time::sleep(SMOKE_TEST_INTERVAL.saturating_sub(tick.elapsed())).await;
+    }
+
+    let elapsed = started.elapsed().as_secs();
+    if frames == 0 {
+        return DoctorCheck::fail(
+            "smoke_test",
+            format!(
+                "no frame captured and read in {}s: {}",
+                elapsed,
+                last_error.unwrap_or_default()
+            ),
+            screen_permission_fix(),
+        );
+    }
+    let message = format!(
+        "{} frames captured and read in {}s, {} characters, {}ms of OCR a frame",
+        frames,
+        elapsed,
+        characters,
+        (ocr_time / frames).as_millis()
+    );
+    if characters == 0 {
+        return DoctorCheck::warn(
+            "smoke_test",
+            format!("{}, no text was read", message),
+            "run it again with some text on the screen, a blank capture means the screen recording permission is missing",
+        );
+    }
+    DoctorCheck::pass("smoke_test", message)
+}
@@ -0,0 +1,142 @@
+use crate::{CapturePause, DatabaseManager};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A beacon this long after the last one of the same context still continues it, the plugins
+/// send one every few seconds while the editor is used and nothing when it's idle
+pub const BEACON_TIMEOUT: ChronoDuration = ChronoDuration::seconds(60);
+/// Frames linked to a context at most, an afternoon in the same file gets many
+pub const MAX_CONTEXT_FRAMES: usize = 10;
+
+/// What an editor plugin sends of the file open in the editor, when it's opened or switched to
+/// and again every few seconds while it's edited
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+pub struct EditorBeacon {
+    /// Like "vscode", "jetbrains" or "neovim"
+    pub editor: String,
+    #[serde(default)]
+    pub project: Option<String>,
+    /// Remote url or root directory of the git repository
+    #[serde(default)]
+    pub repository: Option<String>,
+    #[serde(default)]
+    pub git_branch: Option<String>,
+    /// Path of the file, relative to the project or absolute
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub language: Option<String>,
+    /// When the plugin sent it, now when not given
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl EditorBeacon {
+    /// Without the blank fields, an error when it doesn't say what's open in which editor
+    pub fn validated(self) -> Result<Self> {
+        let non_empty = |value: Option<String>| {
+            value
+                .map(|value| value.trim().to_string())
+                .filter(|value| !value.is_empty())
+        };
+        let beacon = EditorBeacon {
+            editor: self.editor.trim().to_lowercase(),
+            project: non_empty(self.project),
+            repository: non_empty(self.repository),
+            git_branch: non_empty(self.git_branch),
+            file: non_empty(self.file),
+            language: non_empty(self.language),
+            timestamp: self.timestamp,
+        };
+        if beacon.editor.is_empty() {
+            bail!("editor is required");
+        }
+        if beacon.project.is_none() && beacon.repository.is_none() && beacon.file.is_none() {
+            bail!("one of project, repository or file is required");
+        }
+        Ok(beacon)
+    }
+
+    pub fn is_same_context(&self, context: &EditorContext) -> bool {
+        self.editor == context.editor
+            && self.project == context.project
+            && self.repository == context.repository
+            && self.git_branch == context.git_branch
+            && self.file == context.file
+            && self.language == context.language
+    }
+}
+
+/// The time a file was open in an editor, from the first beacon to the last one
+#[derive(Debug, Clone, Serialize)]
+pub struct EditorContext {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub ended_at: DateTime<Utc>,
+    pub duration_secs: f64,
+    pub editor: String,
+    pub project: Option<String>,
+    pub repository: Option<String>,
+    pub git_branch: Option<String>,
+    pub file: Option<String>,
+    pub language: Option<String>,
+    /// Frames captured meanwhile of the editor window showing the file, the first ones
+    pub frame_ids: Vec<i64>,
+}
+
+/// The last part of the path, editors show it in their window title
+fn file_name(path: &str) -> Option<&str> {
+    path.rsplit(['/', '\\'])
+        .next()
+        .filter(|name| !name.is_empty())
+}
+
+/// Continues the current context of the editor or starts another one, unless capture is
+/// paused. Returns the id of the context
+pub async fn record_editor_beacon(
+    db: &DatabaseManager,
+    capture_pause: &CapturePause,
+    beacon: EditorBeacon,
+) -> Result<Option<i64>> {
+    if capture_pause.is_paused() {
+        return Ok(None);
+    }
+    let timestamp = beacon.timestamp.unwrap_or_else(Utc::now);
+    if let Some(current) = db.get_latest_editor_context(&beacon.editor).await? {
+        if beacon.is_same_context(&current) && timestamp - current.ended_at <= BEACON_TIMEOUT {
+            // beacons sent together may arrive out of order
+            db.end_editor_context(current.id, timestamp.max(current.ended_at))
+                .await?;
+            return Ok(Some(current.id));
+        }
+    }
+    Ok(Some(db.insert_editor_context(&beacon, timestamp).await?))
+}
+
+/// The frames captured during each context whose window title shows its file, or its project
+/// when no file was open
+pub async fn link_editor_frames(
+    db: &DatabaseManager,
+    contexts: &mut [EditorContext],
+) -> Result<()> {
+    for context in contexts.iter_mut() {
+        let Some(shown) = context
+            .file
+            .as_deref()
+            .and_then(file_name)
+            .or(context.project.as_deref())
+        else {
+            continue;
+        };
+        context.frame_ids = db
+            .get_frame_windows_between(context.timestamp, context.ended_at)
+            .await?
+            .into_iter()
+            .filter(|(_, _, window_name)| window_name.contains(shown))
+            .map(|(frame_id, _, _)| frame_id)
+            .take(MAX_CONTEXT_FRAMES)
+            .collect();
+    }
+    Ok(())
+}
@@ -0,0 +1,208 @@
+use crate::focus_mode::{do_not_disturb, wait_for_do_not_disturb_end};
+use crate::watchlist::{WatchAlert, WatchSource};
+use crate::DatabaseManager;
+use anyhow::{bail, Result};
+use chrono::{Datelike, Duration as ChronoDuration, Local, NaiveDate, Timelike};
+use log::{debug, error, info, warn};
+use screenpipe_core::subscribe_events;
+use screenpipe_integrations::email::{
+    render_alert, render_digest, send_email, thumbnail_jpeg, EmailAlert, EmailDestination,
+};
+use screenpipe_integrations::vault_export::{local_day_range, VaultNoteSource};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+
+/// How often the time of the digests is looked at
+pub const DIGEST_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// The frame an alert was raised on is stored a few seconds later
+const ALERT_FRAME_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize)]
+struct EmailConfigFile {
+    destinations: Vec<EmailDestination>,
+}
+
+/// Reads the destinations of a `{"destinations": [...]}` file, each one with its SMTP server
+pub fn load_email_destinations(path: &Path) -> Result<Vec<EmailDestination>> {
+    let file: EmailConfigFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut names = HashSet::new();
+    for destination in &file.destinations {
+        destination.validate()?;
+        if !names.insert(destination.name.as_str()) {
+            bail!("two email destinations are named {}", destination.name);
+        }
+    }
+    Ok(file.destinations)
+}
+
+fn digest_checkpoint(destination: &EmailDestination) -> String {
+    format!("email_digest:{}", destination.name)
+}
+
+/// Emails the digest of the day with the frames of its moments
+pub async fn send_digest(
+    db: &DatabaseManager,
+    destination: &EmailDestination,
+    date: NaiveDate,
+) -> Result<()> {
+    let (start, end) = local_day_range(date)?;
+    let content = db.note_content(start, end).await?;
+    let mut thumbnails = HashMap::new();
+    if destination.thumbnails {
+        for frame_id in content.moments.iter().filter_map(|moment| moment.frame_id) {
+            match db.frame_image(frame_id).await {
+                Ok(image) => {
+                    thumbnails.insert(frame_id, thumbnail_jpeg(&image)?);
+                }
+                Err(e) => debug!("Leaving frame {} out of the digest: {}", frame_id, e),
+            }
+        }
+    }
+    send_email(destination, &render_digest(date, &content, thumbnails)).await
+}
+
+/// Sends each destination the digest of the day once its hour has come, once a day
+pub async fn start_email_digests(db: Arc<DatabaseManager>, destinations: Vec<EmailDestination>) {
+    let destinations: Vec<EmailDestination> = destinations
+        .into_iter()
+        .filter(|destination| destination.digest_hour.is_some())
+        .collect();
+    if destinations.is_empty() {
+        return;
+    }
+    info!(
+        "Emailing daily digests to {} destinations",
+        destinations.len()
+    );
+    loop {
+        // sent at the first check after do not disturb ends
+        if do_not_disturb() {
+            tokio::time::sleep(DIGEST_CHECK_INTERVAL).await;
+            continue;
+        }
+        let now = Local::now();
+        let today = now.date_naive();
+        for destination in &destinations {
+            if destination
+                .digest_hour
+                .is_some_and(|hour| now.hour() < hour)
+            {
+                continue;
+            }
+            let checkpoint = digest_checkpoint(destination);
+            let day = today.num_days_from_ce() as i64;
+            match db.get_checkpoint(&checkpoint).await {
+                Ok(Some(sent)) if sent >= day => continue,
+                Ok(_) => {}
+                Err(e) => {
+                    error!("Failed to read when the digest was last emailed: {}", e);
+                    continue;
+                }
+            }
+            match send_digest(&db, destination, today).await {
+                Ok(()) => {
+                    info!("Emailed the digest of {} to {}", today, destination.name);
+                    if let Err(e) = db.set_checkpoint(&checkpoint, day).await {
+                        error!("Failed to record the digest emailed: {}", e);
+                    }
+                }
+                // tried again at the next check
+                Err(e) => warn!("Failed to email the digest: {:#}", e),
+            }
+        }
+        tokio::time::sleep(DIGEST_CHECK_INTERVAL).await;
+    }
+}
+
+/// Emails the alert with the frame it was seen on to the destinations of its watch
+pub async fn send_alert(
+    db: &DatabaseManager,
+    destinations: &[EmailDestination],
+    alert: &WatchAlert,
+) -> Result<usize> {
+    let destinations: Vec<&EmailDestination> = destinations
+        .iter()
+        .filter(|destination| destination.sends_alerts_of(&alert.watch_name))
+        .collect();
+    if destinations.is_empty() {
+        return Ok(0);
+    }
+    let mut thumbnail = None;
+    if alert.source != WatchSource::Audio
+        && destinations
+            .iter()
+            .any(|destination| destination.thumbnails)
+    {
+        if let Some(frame_id) = db
+            .get_frame_id_at(alert.timestamp, ChronoDuration::seconds(1))
+            .await?
+        {
+            match db.frame_image(frame_id).await {
+                Ok(image) => thumbnail = Some((frame_id, thumbnail_jpeg(&image)?)),
+                Err(e) => debug!("Emailing alert {} without its frame: {}", alert.id, e),
+            }
+        }
+    }
+    let email_alert = EmailAlert {
+        watch_name: alert.watch_name.clone(),
+        timestamp: alert.timestamp,
+        app_name: alert.app_name.clone(),
+        window_name: alert.window_name.clone(),
+        matched_text: alert.matched_text.clone(),
+        excerpt: alert.excerpt.clone(),
+    };
+    let mut sent = 0;
+    for destination in destinations {
+        let frame = thumbnail.clone().filter(|_| destination.thumbnails);
+        match send_email(destination, &render_alert(&email_alert, frame)).await {
+            Ok(()) => sent += 1,
+            Err(e) => warn!("Failed to email alert {}: {:#}", alert.id, e),
+        }
+    }
+    Ok(sent)
+}
+
+/// Emails the watch alerts as they're raised, or once do not disturb ends
+pub async fn start_email_alerts(db: Arc<DatabaseManager>, destinations: Vec<EmailDestination>) {
+    if !destinations.iter().any(|destination| destination.alerts) {
+        return;
+    }
+    let destinations = Arc::new(destinations);
+    let mut events = subscribe_events();
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(RecvError::Lagged(missed)) => {
+                warn!("Missed {} events, their alerts aren't emailed", missed);
+                continue;
+            }
+            Err(RecvError::Closed) => return,
+        };
+        if event.name != "watch.alert" {
+            continue;
+        }
+        let alert: WatchAlert = match serde_json::from_value(event.data) {
+            Ok(alert) => alert,
+            Err(e) => {
+                error!("Failed to read a watch alert: {}", e);
+                continue;
+            }
+        };
+        let db = db.clone();
+        let destinations = destinations.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(ALERT_FRAME_DELAY).await;
+            if do_not_disturb() {
+                debug!("Holding alert {} until do not disturb ends", alert.id);
+                wait_for_do_not_disturb_end().await;
+            }
+            if let Err(e) = send_alert(&db, &destinations, &alert).await {
+                warn!("Failed to email alert {}: {}", alert.id, e);
+            }
+        });
+    }
+}
@@ -0,0 +1,573 @@
+use crate::errors::StorageError;
+use crate::integrity::ChunkKind;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use rand::RngCore;
+use screenpipe_core::emit_event;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+
+const KEYCHAIN_SERVICE: &str = "screenpipe";
+const MAGIC: &[u8; 8] = b"SPENC001";
+const HEADER_LEN: usize = MAGIC.len() + 4;
+const NONCE_LEN: usize = 24;
+/// Appended to the path of encrypted chunks
+pub const ENCRYPTED_SUFFIX: &str = ".enc";
+/// Directory of the data directory chunks are decrypted to while they are read
+pub const DECRYPTED_DIR: &str = "decrypted";
+const CHUNKS_PER_RUN: u32 = 20;
+/// Thumbnails are a few KB, many more of them are moved to the active key per run
+const THUMBNAILS_PER_RUN: u32 = 500;
+/// Marker screenshots and slides are full size images
+const IMAGES_PER_RUN: u32 = 50;
+/// Chunks stay plaintext this long after recording so subtitles and integrity checks see
+/// them first
+const SETTLE_TIME: ChronoDuration = ChronoDuration::minutes(10);
+
+/// The images written with `write_media` whose rows keep their path and key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageKind {
+    Marker,
+    Slide,
+}
+
+impl ImageKind {
+    pub fn table(&self) -> &'static str {
+        match self {
+            ImageKind::Marker => "markers",
+            ImageKind::Slide => "slides",
+        }
+    }
+}
+
+/// Keys `open_media` decrypts chunks with, set once they are loaded
+static MEDIA_KEYS: RwLock<Option<Arc<KeyRing>>> = RwLock::new(None);
+/// Where `open_media` decrypts chunks to, set at startup
+static DECRYPTED_MEDIA_DIR: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// What is kept in the keychain, keys are hex
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredKeys {
+    active: u32,
+    /// Old keys stay until no chunk is encrypted with them
+    media: BTreeMap<u32, String>,
+    database: String,
+    /// Set by a rotation until the database is re-keyed at the next start
+    #[serde(default)]
+    previous_database: Option<String>,
+}
+
+impl StoredKeys {
+    fn generate() -> Self {
+        StoredKeys {
+            active: 1,
+            media: BTreeMap::from([(1, random_key())]),
+            database: random_key(),
+            previous_database: None,
+        }
+    }
+}
+
+fn random_key() -> String {
+    let mut key = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut key);
+    key.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn parse_key(hex: &str) -> Result<Key> {
+    // checked before slicing by bytes, which panics in the middle of a multibyte character
+    if hex.len() != 64 || !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        anyhow::bail!("encryption keys are 32 bytes of hex");
+    }
+    let bytes = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()?;
+    Ok(*Key::from_slice(&bytes))
+}
+
+enum KeyStore {
+    /// Entry of the OS keychain, Keychain on macos, the Credential Manager (DPAPI) on
+    /// windows and the secret service on linux
+    Keychain {
+        account: String,
+    },
+    Memory,
+}
+
+impl KeyStore {
+    async fn read(&self) -> Result<Option<StoredKeys>> {
+        let KeyStore::Keychain { account } = self else {
+            return Ok(None);
+        };
+        let account = account.clone();
+        // the keychain apis block
+        let secret = tokio::task::spawn_blocking(move || {
+            match keyring::Entry::new(KEYCHAIN_SERVICE, &account)?.get_password() {
+                Ok(secret) => Ok(Some(secret)),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(e) => Err(e),
+            }
+        })
+        .await??;
+        secret
+            .map(|secret| serde_json::from_str(&secret).map_err(Into::into))
+            .transpose()
+    }
+
+    async fn write(&self, keys: &StoredKeys) -> Result<()> {
+        let KeyStore::Keychain { account } = self else {
+            return Ok(());
+        };
+        let account = account.clone();
+        let secret = serde_json::to_string(keys)?;
+        tokio::task::spawn_blocking(move || {
+            keyring::Entry::new(KEYCHAIN_SERVICE, &account)?.set_password(&secret)
+        })
+        .await??;
+        Ok(())
+    }
+}
+
+/// Encryption keys of the current OS user for one data directory
+pub struct KeyRing {
+    store: KeyStore,
+    keys: RwLock<StoredKeys>,
+}
+
+impl KeyRing {
+    /// Loads the keys from the OS keychain, they are generated and saved on first use
+    pub async fn load(data_dir: &Path) -> Result<Self> {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("USERNAME"))
+            .unwrap_or_else(|_| "default".to_string());
+        let store = KeyStore::Keychain {
+            account: format!("{}@{}", user, data_dir.to_string_lossy()),
+        };
+        let keys = match store.read().await? {
+            Some(keys) => keys,
+            None => {
+                let keys = StoredKeys::generate();
+                store.write(&keys).await?;
+                info!("Generated encryption keys in the OS keychain");
+                keys
+            }
+        };
+        Ok(KeyRing {
+            store,
+            keys: RwLock::new(keys),
+        })
+    }
+
+    /// Keys only kept in memory, data encrypted with them is unreadable once dropped
+    pub fn ephemeral() -> Self {
+        KeyRing {
+            store: KeyStore::Memory,
+            keys: RwLock::new(StoredKeys::generate()),
+        }
+    }
+
+    pub fn active_key_id(&self) -> u32 {
+        self.keys.read().unwrap().active
+    }
+
+    pub fn key_ids(&self) -> Vec<u32> {
+        self.keys.read().unwrap().media.keys().copied().collect()
+    }
+
+    /// Hex key of the database
+    pub fn database_key(&self) -> String {
+        self.keys.read().unwrap().database.clone()
+    }
+
+    /// Key the database is still encrypted with after a rotation
+    pub fn previous_database_key(&self) -> Option<String> {
+        self.keys.read().unwrap().previous_database.clone()
+    }
+
+    /// New keys for media and the database, saved before anything is encrypted with them.
+    /// Chunks are re-encrypted in the background, the database at the next start
+    pub async fn rotate(&self) -> Result<u32> {
+        let mut keys = self.keys.read().unwrap().clone();
+        let id = keys.media.keys().max().copied().unwrap_or(0) + 1;
+        keys.media.insert(id, random_key());
+        keys.active = id;
+        // a rotation before the restart keeps the key the database really has
+        if keys.previous_database.is_none() {
+            keys.previous_database = Some(keys.database.clone());
+        }
+        keys.database = random_key();
+        self.store.write(&keys).await?;
+        *self.keys.write().unwrap() = keys;
+        info!("Rotated encryption keys, key {} is now active", id);
+        Ok(id)
+    }
+
+    pub async fn database_rekeyed(&self) -> Result<()> {
+        let mut keys = self.keys.read().unwrap().clone();
+        keys.previous_database = None;
+        self.store.write(&keys).await?;
+        *self.keys.write().unwrap() = keys;
+        Ok(())
+    }
+
+    /// Drops the old keys no chunk is encrypted with anymore, returns their ids
+    pub async fn retire_unused(&self, in_use: &[u32]) -> Result<Vec<u32>> {
+        let mut keys = self.keys.read().unwrap().clone();
+        let active = keys.active;
+        let retired: Vec<u32> = keys
+            .media
+            .keys()
+            .copied()
+            .filter(|id| *id != active && !in_use.contains(id))
+            .collect();
+        if retired.is_empty() {
+            return Ok(retired);
+        }
+        keys.media.retain(|id, _| !retired.contains(id));
+        self.store.write(&keys).await?;
+        *self.keys.write().unwrap() = keys;
+        Ok(retired)
+    }
+
+    fn cipher(&self, key_id: u32) -> Result<XChaCha20Poly1305> {
+        let keys = self.keys.read().unwrap();
+        let key = keys
+            .media
+            .get(&key_id)
+            .ok_or_else(|| anyhow!("encryption key {} is not in the keychain", key_id))?;
+        Ok(XChaCha20Poly1305::new(&parse_key(key)?))
+    }
+
+    /// Encrypts with the active key, returns its id with the data
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<(u32, Vec<u8>)> {
+        let key_id = self.active_key_id();
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::rngs::OsRng.fill_bytes(&mut nonce);
+        let mut output = Vec::with_capacity(HEADER_LEN + NONCE_LEN + plaintext.len() + 16);
+        output.extend_from_slice(MAGIC);
+        output.extend_from_slice(&key_id.to_be_bytes());
+        // the header is authenticated so the key id can't be swapped
+        let ciphertext = self
+            .cipher(key_id)?
+            .encrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext,
+                    aad: &output[..HEADER_LEN],
+                },
+            )
+            .map_err(|_| anyhow!("failed to encrypt"))?;
+        output.extend_from_slice(&nonce);
+        output.extend_from_slice(&ciphertext);
+        Ok((key_id, output))
+    }
+
+    pub fn decrypt(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let key_id =
+            encrypted_key_id(data).ok_or_else(|| anyhow!("not encrypted by screenpipe"))?;
+        if data.len() < HEADER_LEN + NONCE_LEN {
+            anyhow::bail!("encrypted data is truncated");
+        }
+        let (header, rest) = data.split_at(HEADER_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        self.cipher(key_id)?
+            .decrypt(
+                XNonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: header,
+                },
+            )
+            .map_err(|_| anyhow!("encrypted data is damaged or was changed"))
+    }
+}
+
+/// Id of the key the data was encrypted with, None if it isn't encrypted
+pub fn encrypted_key_id(data: &[u8]) -> Option<u32> {
+    let header = data.get(..HEADER_LEN)?;
+    if &header[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+    Some(u32::from_be_bytes(header[MAGIC.len()..].try_into().ok()?))
+}
+
+/// Keys `open_media` decrypts chunks with
+pub fn set_media_keys(keys: Arc<KeyRing>) {
+    *MEDIA_KEYS.write().unwrap() = Some(keys);
+}
+
+/// Empties the directory `open_media` decrypts chunks to, of what a crash left there, and makes
+/// it private to the user. Done at startup, whether keys are loaded or not
+pub fn prepare_decrypted_dir(data_dir: &Path) -> Result<()> {
+    let dir = data_dir.join(DECRYPTED_DIR);
+    match std::fs::remove_dir_all(&dir) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(anyhow!("failed to empty {}: {}", dir.display(), e))
+        }
+        _ => {}
+    }
+    let mut builder = std::fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder
+        .create(&dir)
+        .map_err(|e| anyhow!("failed to create {}: {}", dir.display(), e))?;
+    *DECRYPTED_MEDIA_DIR.write().unwrap() = Some(dir);
+    Ok(())
+}
+
+/// A chunk ffmpeg can read. Encrypted chunks are decrypted to a file of the decrypted
+/// directory only the user can read, removed once this is dropped
+pub struct MediaFile {
+    path: String,
+    temporary: bool,
+}
+
+impl MediaFile {
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+}
+
+impl Drop for MediaFile {
+    fn drop(&mut self) {
+        if self.temporary {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+pub async fn open_media(file_path: &str) -> Result<MediaFile, StorageError> {
+    let Some(plain_path) = file_path.strip_suffix(ENCRYPTED_SUFFIX) else {
+        return Ok(MediaFile {
+            path: file_path.to_string(),
+            temporary: false,
+        });
+    };
+    let keys = MEDIA_KEYS
+        .read()
+        .unwrap()
+        .clone()
+        .ok_or_else(|| StorageError::MediaLocked(file_path.to_string()))?;
+    let data = tokio::fs::read(file_path)
+        .await
+        .map_err(|e| StorageError::unreadable(file_path, e))?;
+    let plaintext = tokio::task::spawn_blocking(move || keys.decrypt(&data))
+        .await
+        .map_err(|e| StorageError::corrupt(file_path, e))?
+        .map_err(|e| StorageError::corrupt(file_path, e))?;
+
+    let extension = Path::new(plain_path)
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_string())
+        .unwrap_or_else(|| "mp4".to_string());
+    let dir = DECRYPTED_MEDIA_DIR.read().unwrap().clone().ok_or_else(|| {
+        StorageError::unreadable(
+            file_path,
+            std::io::Error::other(
+                "no directory to decrypt to, prepare_decrypted_dir wasn't called",
+            ),
+        )
+    })?;
+    let path = dir
+        .join(format!(
+            "screenpipe-{:016x}.{}",
+            rand::random::<u64>(),
+            extension
+        ))
+        .to_string_lossy()
+        .to_string();
+    let media = MediaFile {
+        path,
+        temporary: true,
+    };
+    write_private(&media.path, &plaintext)
+        .await
+        .map_err(|e| StorageError::unreadable(&media.path, e))?;
+    Ok(media)
+}
+
+/// Writes a file that isn't a chunk, like the screenshot of a marker, encrypted with the
+/// active key when keys are loaded. Returns the path written and the key used
+pub async fn write_media(path: &Path, data: Vec<u8>) -> Result<(String, Option<u32>)> {
+    let path = path.to_string_lossy().to_string();
+    let keys = MEDIA_KEYS.read().unwrap().clone();
+    let Some(keys) = keys else {
+        write_private(&path, &data).await?;
+        return Ok((path, None));
+    };
+    let (key_id, encrypted) = tokio::task::spawn_blocking(move || keys.encrypt(&data)).await??;
+    let path = format!("{}{}", path, ENCRYPTED_SUFFIX);
+    write_private(&path, &encrypted).await?;
+    Ok((path, Some(key_id)))
+}
+
+async fn write_private(path: &str, data: &[u8]) -> std::io::Result<()> {
+    let mut options = tokio::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options.open(path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    Ok(())
+}
+
+/// Encrypts a chunk with the active key, or re-encrypts it after a rotation. The encrypted
+/// file replaces the original only once it's complete and the database points at it
+pub async fn encrypt_chunk(
+    db: &DatabaseManager,
+    keys: &Arc<KeyRing>,
+    kind: ChunkKind,
+    id: i64,
+    file_path: &str,
+) -> Result<()> {
+    let data = tokio::fs::read(file_path).await?;
+    let already_encrypted = file_path.ends_with(ENCRYPTED_SUFFIX);
+    let cipher_keys = Arc::clone(keys);
+    let (key_id, encrypted) = tokio::task::spawn_blocking(move || {
+        if already_encrypted {
+            cipher_keys.encrypt(&cipher_keys.decrypt(&data)?)
+        } else {
+            cipher_keys.encrypt(&data)
+        }
+    })
+    .await??;
+
+    let encrypted_path = if already_encrypted {
+        file_path.to_string()
+    } else {
+        format!("{}{}", file_path, ENCRYPTED_SUFFIX)
+    };
+    let partial_path = format!("{}.partial", encrypted_path);
+    write_private(&partial_path, &encrypted).await?;
+    tokio::fs::rename(&partial_path, &encrypted_path).await?;
+    db.set_chunk_encryption(kind, id, &encrypted_path, key_id)
+        .await?;
+    if !already_encrypted {
+        if let Err(e) = tokio::fs::remove_file(file_path).await {
+            warn!("Failed to remove {} after encrypting it: {}", file_path, e);
+        }
+    }
+    debug!("Encrypted {} with key {}", encrypted_path, key_id);
+    Ok(())
+}
+
+/// Re-encrypts a file of an older key with the active one in place, None when it's gone
+async fn reencrypt_file(keys: &Arc<KeyRing>, file_path: &str) -> Result<Option<u32>> {
+    let data = match tokio::fs::read(file_path).await {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let cipher_keys = Arc::clone(keys);
+    let (key_id, encrypted) =
+        tokio::task::spawn_blocking(move || cipher_keys.encrypt(&cipher_keys.decrypt(&data)?))
+            .await??;
+    let partial_path = format!("{}.partial", file_path);
+    write_private(&partial_path, &encrypted).await?;
+    tokio::fs::rename(&partial_path, file_path).await?;
+    Ok(Some(key_id))
+}
+
+/// Re-encrypts a thumbnail written with an older key with the active one. A thumbnail that
+/// is gone, with its frame forgotten or its chunk moved, no longer holds on to its key
+pub async fn reencrypt_thumbnail(
+    db: &DatabaseManager,
+    keys: &Arc<KeyRing>,
+    file_path: &str,
+) -> Result<()> {
+    match reencrypt_file(keys, file_path).await? {
+        Some(key_id) => db.set_thumbnail_key(file_path, key_id).await?,
+        None => db.delete_thumbnail_key(file_path).await?,
+    }
+    Ok(())
+}
+
+/// Re-encrypts the screenshot of a marker or the image of a slide with the active key. Once
+/// the file is gone the row keeps neither its path nor its key
+pub async fn reencrypt_image(
+    db: &DatabaseManager,
+    keys: &Arc<KeyRing>,
+    kind: ImageKind,
+    id: i64,
+    file_path: &str,
+) -> Result<()> {
+    let key_id = reencrypt_file(keys, file_path).await?;
+    db.set_image_key(kind, id, key_id).await?;
+    Ok(())
+}
+
+/// Encrypts the settled chunks and moves what's on older keys to the active one, then
+/// removes the keys nothing uses anymore
+pub async fn encrypt_pending_chunks(db: &DatabaseManager, keys: &Arc<KeyRing>) -> Result<()> {
+    let settled_before = Utc::now() - SETTLE_TIME;
+    for kind in [ChunkKind::Video, ChunkKind::Audio] {
+        let chunks = db
+            .get_chunks_to_encrypt(kind, keys.active_key_id(), settled_before, CHUNKS_PER_RUN)
+            .await?;
+        for (id, file_path) in chunks {
+            if let Err(e) = encrypt_chunk(db, keys, kind, id, &file_path).await {
+                warn!("Failed to encrypt {}: {}", file_path, e);
+            }
+        }
+    }
+
+    let thumbnails = db
+        .get_thumbnails_to_reencrypt(keys.active_key_id(), THUMBNAILS_PER_RUN)
+        .await?;
+    for file_path in thumbnails {
+        if let Err(e) = reencrypt_thumbnail(db, keys, &file_path).await {
+            warn!("Failed to re-encrypt thumbnail {}: {}", file_path, e);
+        }
+    }
+    for kind in [ImageKind::Marker, ImageKind::Slide] {
+        let images = db
+            .get_images_to_reencrypt(kind, keys.active_key_id(), IMAGES_PER_RUN)
+            .await?;
+        for (id, file_path) in images {
+            if let Err(e) = reencrypt_image(db, keys, kind, id, &file_path).await {
+                warn!("Failed to re-encrypt {}: {}", file_path, e);
+            }
+        }
+    }
+
+    let in_use = db.get_encryption_key_ids_in_use().await?;
+    let retired = keys.retire_unused(&in_use).await?;
+    if !retired.is_empty() {
+        info!("Removed encryption keys {:?} from the keychain", retired);
+        emit_event("encryption.keys_retired", json!({"key_ids": retired}));
+    }
+    Ok(())
+}
+
+/// Periodically encrypts finished chunks and moves the ones on old keys to the active key
+pub async fn start_media_encryptor(
+    db: Arc<DatabaseManager>,
+    keys: Arc<KeyRing>,
+    interval: Duration,
+) {
+    info!(
+        "Media encryptor started, encrypting chunks with key {}",
+        keys.active_key_id()
+    );
+    loop {
+        if let Err(e) = encrypt_pending_chunks(&db, &keys).await {
+            error!("Failed to encrypt chunks: {}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
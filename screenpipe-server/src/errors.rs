@@ -0,0 +1,79 @@
+use axum::http::StatusCode;
+use thiserror::Error;
+
+/// Why something recorded couldn't be read back
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+    #[error("frame {0} not found")]
+    FrameNotFound(i64),
+    /// The chunk was deleted, by the retention or by hand, after the database pointed at it
+    #[error("{0} is gone")]
+    MediaMissing(String),
+    #[error("{0} is encrypted and no keys are loaded")]
+    MediaLocked(String),
+    #[error("failed to read {path}: {source}")]
+    MediaUnreadable {
+        path: String,
+        #[source]
+        source: std::io::Error,
+    },
+    /// Read but not decrypted or decoded
+    #[error("{path} is corrupt: {reason}")]
+    MediaCorrupt { path: String, reason: String },
+    /// Recorded with --text-only or while text only, nothing of the screen was kept
+    #[error("{0} was recorded text only, it has no images")]
+    TextOnly(String),
+    #[error("failed to extract frame {offset_index} of {path}: {reason}")]
+    FrameExtraction {
+        path: String,
+        offset_index: i64,
+        reason: String,
+    },
+}
+
+impl StorageError {
+    /// `MediaMissing` when the file isn't there
+    pub fn unreadable(path: &str, source: std::io::Error) -> Self {
+        if source.kind() == std::io::ErrorKind::NotFound {
+            StorageError::MediaMissing(path.to_string())
+        } else {
+            StorageError::MediaUnreadable {
+                path: path.to_string(),
+                source,
+            }
+        }
+    }
+
+    pub fn corrupt(path: &str, reason: impl ToString) -> Self {
+        StorageError::MediaCorrupt {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        }
+    }
+
+    /// Stable name of the cause, next to the message in the API's errors
+    pub fn code(&self) -> &'static str {
+        match self {
+            StorageError::Database(_) => "database_error",
+            StorageError::FrameNotFound(_) => "frame_not_found",
+            StorageError::MediaMissing(_) => "media_missing",
+            StorageError::MediaLocked(_) => "media_locked",
+            StorageError::MediaUnreadable { .. } => "media_unreadable",
+            StorageError::MediaCorrupt { .. } => "media_corrupt",
+            StorageError::TextOnly(_) => "text_only",
+            StorageError::FrameExtraction { .. } => "frame_extraction_failed",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            StorageError::FrameNotFound(_)
+            | StorageError::MediaMissing(_)
+            | StorageError::TextOnly(_) => StatusCode::NOT_FOUND,
+            StorageError::MediaLocked(_) => StatusCode::LOCKED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use crate::power::background_jobs_paused;
+use crate::{DatabaseManager, LlmService};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{debug, error, info, warn};
+use regex::Regex;
+use screenpipe_core::{emit_event, PromptLibrary};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::{hash_map::Entry, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Frames per run, with all their windows
+const OCR_BATCH_FRAMES: u32 = 100;
+/// The last frame the extraction rules ran on
+const EXTRACTION_CHECKPOINT: &str = "extraction.frame_id";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExtractionKind {
+    /// `pattern` is a regex, named capture groups become the fields of the record
+    Regex,
+    /// `pattern` is a JSON schema the LLM has to fill from the screen text
+    Llm,
+}
+
+impl ExtractionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExtractionKind::Regex => "regex",
+            ExtractionKind::Llm => "llm",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractionRule {
+    pub id: i64,
+    pub name: String,
+    /// Only run on windows of this app (case insensitive), all apps if none
+    pub app_name: Option<String>,
+    /// Only run on windows whose title contains this (case insensitive), all windows if none
+    pub window_name: Option<String>,
+    pub kind: ExtractionKind,
+    pub pattern: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ExtractedRecord {
+    pub id: i64,
+    pub rule_id: i64,
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: String,
+    pub data: Value,
+}
+
+/// Checks the rule can run before storing it
+pub fn validate_extraction_rule(kind: ExtractionKind, pattern: &str) -> Result<()> {
+    match kind {
+        ExtractionKind::Regex => {
+            Regex::new(pattern)?;
+        }
+        ExtractionKind::Llm => {
+            let schema: Value = serde_json::from_str(pattern)?;
+            if !schema.is_object() {
+                anyhow::bail!("json schema must be an object");
+            }
+        }
+    }
+    Ok(())
+}
+
+impl ExtractionRule {
+    pub fn matches_window(&self, app_name: &str, window_name: &str) -> bool {
+        let app_matches = self
+            .app_name
+            .as_ref()
+            .is_none_or(|app| app.eq_ignore_ascii_case(app_name));
+        let window_matches = self
+            .window_name
+            .as_ref()
+            .is_none_or(|window| window_name.to_lowercase().contains(&window.to_lowercase()));
+        app_matches && window_matches
+    }
+}
+
+/// One record per match, with the named capture groups as fields
+/// (or the whole match under `match` if the regex has no named group)
+pub fn extract_with_regex(regex: &Regex, text: &str) -> Vec<Value> {
+    let names: Vec<&str> = regex.capture_names().flatten().collect();
+    regex
+        .captures_iter(text)
+        .map(|captures| {
+            let mut record = Map::new();
+            if names.is_empty() {
+                record.insert("match".to_string(), json!(&captures[0]));
+            }
+            for name in &names {
+                if let Some(value) = captures.name(name) {
+                    record.insert(name.to_string(), json!(value.as_str()));
+                }
+            }
+            Value::Object(record)
+        })
+        .collect()
+}
+
+/// Parses the LLM answer into records, keeping only objects that have the fields required by the schema
+pub fn parse_llm_extraction(content: &str, schema: &Value) -> Vec<Value> {
+    let start = content.find(['[', '{']);
+    let end = content.rfind([']', '}']);
+    let value: Value = match (start, end) {
+        (Some(start), Some(end)) if start <= end => {
+            serde_json::from_str(&content[start..=end]).unwrap_or(Value::Null)
+        }
+        _ => Value::Null,
+    };
+
+    let required: Vec<&str> = schema["required"]
+        .as_array()
+        .map(|r| r.iter().filter_map(|v| v.as_str()).collect())
+        .unwrap_or_default();
+
+    let records = match value {
+        Value::Array(values) => values,
+        Value::Object(_) => vec![value],
+        _ => vec![],
+    };
+    records
+        .into_iter()
+        .filter(|record| {
+            record.is_object() && required.iter().all(|field| !record[*field].is_null())
+        })
+        .collect()
+}
+
+async fn extract_with_llm(
+    llm: &LlmService,
+    prompts: &PromptLibrary,
+    schema: &str,
+    text: &str,
+) -> Result<Vec<Value>> {
+    let schema: Value = serde_json::from_str(schema)?;
+    let messages = prompts
+        .get("extract")
+        .render(&[("schema", &schema.to_string()), ("text", text)]);
+    let answer = llm.chat("extract", &messages).await?;
+    Ok(parse_llm_extraction(&answer.content, &schema))
+}
+
+struct ExtractionWorker {
+    db: Arc<DatabaseManager>,
+    llm: Arc<LlmService>,
+    prompts: Arc<PromptLibrary>,
+    last_frame_id: i64,
+    regexes: HashMap<i64, Regex>,
+    /// Last text sent to the LLM per rule and window, screens rarely change so we avoid re-billing
+    last_llm_texts: HashMap<(i64, String, String), String>,
+}
+
+impl ExtractionWorker {
+    async fn run_once(&mut self) -> Result<()> {
+        let rules = self.db.get_extraction_rules(true).await?;
+        if rules.is_empty() {
+            // nothing to do, don't process the backlog once a rule gets added
+            self.last_frame_id = self.db.get_max_frame_id().await?;
+            return Ok(());
+        }
+
+        let rows = self
+            .db
+            .get_ocr_text_after_frame(self.last_frame_id, OCR_BATCH_FRAMES)
+            .await?;
+        debug!(
+            "Running {} extraction rules on {} windows",
+            rules.len(),
+            rows.len()
+        );
+
+        // the batch is picked up again if it fails part way, the records already inserted
+        // are deduplicated
+        let batch_last_frame_id = rows.iter().map(|row| row.frame_id).max();
+        for row in rows {
+            for rule in rules
+                .iter()
+                .filter(|r| r.matches_window(&row.app_name, &row.window_name))
+            {
+                let records = match rule.kind {
+                    ExtractionKind::Regex => {
+                        let regex = match self.regexes.entry(rule.id) {
+                            Entry::Occupied(entry) => entry.into_mut(),
+                            Entry::Vacant(entry) => entry.insert(Regex::new(&rule.pattern)?),
+                        };
+                        extract_with_regex(regex, &row.ocr_text)
+                    }
+                    ExtractionKind::Llm => {
+                        let key = (rule.id, row.app_name.clone(), row.window_name.clone());
+                        if self.last_llm_texts.get(&key) == Some(&row.ocr_text) {
+                            continue;
+                        }
+                        self.last_llm_texts.insert(key, row.ocr_text.clone());
+                        match extract_with_llm(
+                            &self.llm,
+                            &self.prompts,
+                            &rule.pattern,
+                            &row.ocr_text,
+                        )
+                        .await
+                        {
+                            Ok(records) => records,
+                            Err(e) => {
+                                warn!("LLM extraction failed for rule {}: {}", rule.name, e);
+                                continue;
+                            }
+                        }
+                    }
+                };
+
+                for data in records {
+                    let id = self
+                        .db
+                        .insert_extracted_record(
+                            rule.id,
+                            row.frame_id,
+                            row.timestamp,
+                            &row.app_name,
+                            &row.window_name,
+                            &data,
+                        )
+                        .await?;
+                    // already extracted from a previous frame
+                    let Some(id) = id else { continue };
+
+                    emit_event(
+                        "extraction.record",
+                        json!({
+                            "id": id,
+                            "rule_id": rule.id,
+                            "rule_name": rule.name,
+                            "frame_id": row.frame_id,
+                            "timestamp": row.timestamp,
+                            "app_name": row.app_name,
+                            "window_name": row.window_name,
+                            "data": data,
+                        }),
+                    );
+                }
+            }
+        }
+        if let Some(frame_id) = batch_last_frame_id {
+            self.last_frame_id = self.last_frame_id.max(frame_id);
+        }
+        Ok(())
+    }
+}
+
+/// Periodically runs the enabled extraction rules on the newly recorded screen text
+pub async fn start_extraction_worker(
+    db: Arc<DatabaseManager>,
+    llm: Arc<LlmService>,
+    prompts: Arc<PromptLibrary>,
+    interval: Duration,
+) {
+    // resumes after the last frame it went through before a restart, frames recorded while
+    // it was stopped are not skipped
+    let last_frame_id = match db.get_checkpoint(EXTRACTION_CHECKPOINT).await {
+        Ok(Some(id)) => Ok(id),
+        Ok(None) => db.get_max_frame_id().await,
+        Err(e) => Err(e),
+    };
+    let last_frame_id = match last_frame_id {
+        Ok(id) => id,
+        Err(e) => {
+            error!("Failed to start extraction worker: {}", e);
+            return;
+        }
+    };
+    info!("Extraction worker started");
+
+    let mut worker = ExtractionWorker {
+        db,
+        llm,
+        prompts,
+        last_frame_id,
+        regexes: HashMap::new(),
+        last_llm_texts: HashMap::new(),
+    };
+    loop {
+        if !background_jobs_paused() {
+            let checkpoint = worker.last_frame_id;
+            if let Err(e) = worker.run_once().await {
+                error!("Failed to run extraction rules: {}", e);
+            }
+            if worker.last_frame_id != checkpoint {
+                if let Err(e) = worker
+                    .db
+                    .set_checkpoint(EXTRACTION_CHECKPOINT, worker.last_frame_id)
+                    .await
+                {
+                    warn!("Failed to save extraction checkpoint: {}", e);
+                }
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,228 @@
+use crate::write_coalescer::{PendingWrite, WriteCoalescer, DEFAULT_FLUSH_INTERVAL};
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use notify::event::{AccessKind, AccessMode, CreateKind, ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use screenpipe_core::{channel_stats, emit_event, OverflowPolicy};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+/// Editors write a file in several steps, they make one save
+const DEBOUNCE: ChronoDuration = ChronoDuration::seconds(2);
+/// The focused window is the one of the last frame recorded at most this long before the event
+const MAX_FOCUS_AGE: ChronoDuration = ChronoDuration::seconds(30);
+/// Events waiting for the database, a build or a checkout can send thousands at once
+const EVENTS_CAPACITY: usize = 4096;
+/// Files left by editors and downloads next to the real ones
+const TEMPORARY_SUFFIXES: &[&str] = &[
+    "~",
+    ".swp",
+    ".swx",
+    ".tmp",
+    ".temp",
+    ".partial",
+    ".crdownload",
+    ".part",
+    ".lock",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum FileEventKind {
+    Create,
+    /// Written, or moved in place as editors save atomically
+    Save,
+}
+
+impl FileEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileEventKind::Create => "create",
+            FileEventKind::Save => "save",
+        }
+    }
+
+    fn from_notify(kind: &EventKind) -> Option<Self> {
+        match kind {
+            EventKind::Create(CreateKind::File | CreateKind::Any) => Some(FileEventKind::Create),
+            EventKind::Access(AccessKind::Close(AccessMode::Write))
+            | EventKind::Modify(
+                ModifyKind::Data(_)
+                | ModifyKind::Any
+                | ModifyKind::Name(RenameMode::To | RenameMode::Both),
+            ) => Some(FileEventKind::Save),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for FileEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "create" => Ok(FileEventKind::Create),
+            "save" => Ok(FileEventKind::Save),
+            _ => anyhow::bail!("unknown file event kind: {}", s),
+        }
+    }
+}
+
+/// A file created or saved, with the window that was focused at the time. Reads aren't
+/// reported by the file system watchers of every platform so opening a file isn't an event
+#[derive(Debug, Clone, Serialize)]
+pub struct FileEvent {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub path: String,
+    pub kind: FileEventKind,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+}
+
+/// Hidden files and directories, like .git, and temporary files aren't documents. `path` is
+/// relative to the watched directory, which can be hidden itself
+pub fn is_ignored(path: &Path) -> bool {
+    let hidden = path.components().any(|component| {
+        component
+            .as_os_str()
+            .to_str()
+            .is_some_and(|name| name.starts_with('.') && name != "." && name != "..")
+    });
+    let temporary = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| {
+            // office lock files
+            name.starts_with("~$")
+                || TEMPORARY_SUFFIXES
+                    .iter()
+                    .any(|suffix| name.ends_with(suffix))
+        });
+    hidden || temporary
+}
+
+/// Queues the event with the focused window of the last frame, it is emitted as
+/// "file.activity" once written
+pub async fn record_file_event(
+    db: &DatabaseManager,
+    writes: &WriteCoalescer,
+    timestamp: DateTime<Utc>,
+    path: &Path,
+    kind: FileEventKind,
+) -> Result<()> {
+    let focus = db.get_focused_window_at(timestamp, MAX_FOCUS_AGE).await?;
+    let (app_name, window_name) = focus.unzip();
+    let path = path.to_string_lossy().into_owned();
+    debug!("{} {} in {:?}", kind.as_str(), path, app_name);
+    writes
+        .write(PendingWrite::FileEvent {
+            timestamp,
+            path,
+            kind,
+            app_name,
+            window_name,
+        })
+        .await;
+    Ok(())
+}
+
+pub(crate) fn emit_file_activity(
+    id: i64,
+    timestamp: DateTime<Utc>,
+    path: &str,
+    kind: FileEventKind,
+    app_name: Option<&str>,
+    window_name: Option<&str>,
+) {
+    emit_event(
+        "file.activity",
+        json!({
+            "id": id,
+            "timestamp": timestamp,
+            "path": path,
+            "kind": kind,
+            "app_name": app_name,
+            "window_name": window_name,
+        }),
+    );
+}
+
+/// Records the files created and saved in the directories, recursively. Nothing under the
+/// `excluded` directories is recorded, like screenpipe's own data
+pub async fn start_file_watcher(
+    db: Arc<DatabaseManager>,
+    dirs: Vec<PathBuf>,
+    excluded: Vec<PathBuf>,
+) -> Result<()> {
+    let (tx, mut rx) = mpsc::channel::<notify::Result<Event>>(EVENTS_CAPACITY);
+    let stats = channel_stats("file_activity.events", OverflowPolicy::DropNewest, EVENTS_CAPACITY);
+    let mut watcher = notify::recommended_watcher(move |event| {
+        stats.try_send(&tx, event);
+    })?;
+    for dir in &dirs {
+        if let Err(e) = watcher.watch(dir, RecursiveMode::Recursive) {
+            warn!("Failed to watch {}: {}", dir.display(), e);
+        }
+    }
+    info!("Watching file activity in {} directories", dirs.len());
+    let writes = WriteCoalescer::start(Arc::clone(&db), DEFAULT_FLUSH_INTERVAL);
+
+    let mut last_seen: HashMap<(PathBuf, FileEventKind), DateTime<Utc>> = HashMap::new();
+    while let Some(event) = rx.recv().await {
+        let event = match event {
+            Ok(event) => event,
+            Err(e) => {
+                warn!("File watcher error: {}", e);
+                continue;
+            }
+        };
+        let Some(kind) = FileEventKind::from_notify(&event.kind) else {
+            continue;
+        };
+        // renames give the old path then the new one
+        let paths = match event.kind {
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                event.paths.last().cloned().into_iter().collect()
+            }
+            _ => event.paths,
+        };
+        let now = Utc::now();
+        for path in paths {
+            let relative = dirs
+                .iter()
+                .find_map(|dir| path.strip_prefix(dir).ok())
+                .unwrap_or(&path);
+            if is_ignored(relative)
+                || excluded.iter().any(|dir| path.starts_with(dir))
+                || path.is_dir()
+            {
+                continue;
+            }
+            let key = (path, kind);
+            if last_seen
+                .get(&key)
+                .is_some_and(|seen| now - *seen < DEBOUNCE)
+            {
+                continue;
+            }
+            if let Err(e) = record_file_event(&db, &writes, now, &key.0, kind).await {
+                error!("Failed to record file event: {}", e);
+            }
+            last_seen.insert(key, now);
+        }
+        if last_seen.len() > 10_000 {
+            last_seen.retain(|_, seen| now - *seen < DEBOUNCE);
+        }
+    }
+    // the watcher has to live as long as its events are read
+    drop(watcher);
+    Ok(())
+}
@@ -0,0 +1,227 @@
+//! Do not disturb and the focus modes of the OS. Each stretch of one is kept as a period the
+//! timeline's text, transcriptions and activity sessions are tagged with, and while one is on
+//! the email alerts wait for it to end.
+use crate::DatabaseManager;
+use chrono::{DateTime, Utc};
+use log::{info, warn};
+use screenpipe_core::emit_event;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the state of do not disturb is read
+pub const FOCUS_POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// The name of the periods of platforms without named focus modes
+pub const DO_NOT_DISTURB_MODE: &str = "do_not_disturb";
+
+static DO_NOT_DISTURB: AtomicBool = AtomicBool::new(false);
+
+/// Whether do not disturb or a focus mode was on at the last read, false when they aren't
+/// read
+pub fn do_not_disturb() -> bool {
+    DO_NOT_DISTURB.load(Ordering::SeqCst)
+}
+
+/// Returns once do not disturb is off
+pub async fn wait_for_do_not_disturb_end() {
+    while do_not_disturb() {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FocusPeriod {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    /// The last time it was read on, up to FOCUS_POLL_INTERVAL before it ended
+    pub end_time: DateTime<Utc>,
+    /// Like "Work" or "Sleep" for the focus modes of macOS, DO_NOT_DISTURB_MODE elsewhere
+    pub mode: String,
+}
+
+/// The mode of the period `timestamp` falls in
+pub fn focus_mode_at(periods: &[FocusPeriod], timestamp: DateTime<Utc>) -> Option<String> {
+    periods
+        .iter()
+        .find(|period| period.start_time <= timestamp && timestamp <= period.end_time)
+        .map(|period| period.mode.clone())
+}
+
+/// The focus mode turned on by hand or by a shortcut, from the contents of
+/// ~/Library/DoNotDisturb/DB/Assertions.json, named from ModeConfigurations.json next to it.
+/// The scheduled ones aren't asserted there
+pub fn parse_focus_assertions(
+    assertions: &str,
+    mode_configurations: Option<&str>,
+) -> Option<String> {
+    let assertions: Value = serde_json::from_str(assertions).ok()?;
+    let identifier = assertions["data"]
+        .as_array()?
+        .iter()
+        .filter_map(|store| store["storeAssertionRecords"].as_array())
+        .flatten()
+        .find_map(|record| record["assertionDetails"]["assertionDetailsModeIdentifier"].as_str())?;
+    let name = mode_configurations
+        .and_then(|configurations| serde_json::from_str::<Value>(configurations).ok())
+        .and_then(|configurations| {
+            configurations["data"].as_array()?.iter().find_map(|store| {
+                store["modeConfigurations"][identifier]["mode"]["name"]
+                    .as_str()
+                    .map(str::to_string)
+            })
+        });
+    Some(name.unwrap_or_else(|| DO_NOT_DISTURB_MODE.to_string()))
+}
+
+/// `(<true>,)` as gdbus prints the Inhibited property of the notification server, KDE's and
+/// the other servers implementing the 1.3 spec
+pub fn parse_gdbus_boolean(output: &str) -> Option<bool> {
+    let value = output
+        .trim()
+        .trim_start_matches('(')
+        .trim_end_matches([')', ',']);
+    match value.trim_matches(['<', '>']) {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    }
+}
+
+/// The REG_DWORD `name` of the output of `reg query`
+pub fn parse_reg_dword(output: &str, name: &str) -> Option<u32> {
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != name || fields.next()? != "REG_DWORD" {
+            return None;
+        }
+        u32::from_str_radix(fields.next()?.trim_start_matches("0x"), 16).ok()
+    })
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn read_focus_mode() -> Option<String> {
+    // needs Full Disk Access, without it no focus mode is ever on
+    let db = dirs::home_dir()?.join("Library/DoNotDisturb/DB");
+    let assertions = std::fs::read_to_string(db.join("Assertions.json")).ok()?;
+    let mode_configurations = std::fs::read_to_string(db.join("ModeConfigurations.json")).ok();
+    parse_focus_assertions(&assertions, mode_configurations.as_deref())
+}
+
+#[cfg(target_os = "linux")]
+pub(crate) fn read_focus_mode() -> Option<String> {
+    let output = |program: &str, args: &[&str]| {
+        std::process::Command::new(program)
+            .args(args)
+            .output()
+            .ok()
+            .filter(|output| output.status.success())
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+    };
+    let inhibited = output(
+        "gdbus",
+        &[
+            "call",
+            "--session",
+            "--dest",
+            "org.freedesktop.Notifications",
+            "--object-path",
+            "/org/freedesktop/Notifications",
+            "--method",
+            "org.freedesktop.DBus.Properties.Get",
+            "org.freedesktop.Notifications",
+            "Inhibited",
+        ],
+    )
+    .and_then(|output| parse_gdbus_boolean(&output));
+    // GNOME's server has no Inhibited property, its do not disturb hides the banners
+    let inhibited = inhibited.or_else(|| {
+        output(
+            "gsettings",
+            &["get", "org.gnome.desktop.notifications", "show-banners"],
+        )
+        .and_then(|output| match output.trim() {
+            "false" => Some(true),
+            "true" => Some(false),
+            _ => None,
+        })
+    });
+    inhibited
+        .unwrap_or(false)
+        .then(|| DO_NOT_DISTURB_MODE.to_string())
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn read_focus_mode() -> Option<String> {
+    // Windows 11 turns the toasts off for do not disturb, the value is missing until then
+    let output = std::process::Command::new("reg")
+        .args([
+            "query",
+            r"HKCU\Software\Microsoft\Windows\CurrentVersion\Notifications\Settings",
+            "/v",
+            "NOC_GLOBAL_SETTING_TOASTS_ENABLED",
+        ])
+        .output()
+        .ok()?;
+    let enabled = parse_reg_dword(
+        &String::from_utf8_lossy(&output.stdout),
+        "NOC_GLOBAL_SETTING_TOASTS_ENABLED",
+    )?;
+    (enabled == 0).then(|| DO_NOT_DISTURB_MODE.to_string())
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub(crate) fn read_focus_mode() -> Option<String> {
+    None
+}
+
+/// Moves the end of the current period while its mode stays on, and starts another one and
+/// emits "focus.changed" when the mode changes. Returns the period now on
+pub async fn record_focus_mode(
+    db: &DatabaseManager,
+    current: Option<FocusPeriod>,
+    mode: Option<String>,
+    now: DateTime<Utc>,
+) -> Result<Option<FocusPeriod>, sqlx::Error> {
+    let previous = current.as_ref().map(|period| period.mode.clone());
+    let period = match (current, mode) {
+        (Some(mut period), Some(mode)) if period.mode == mode => {
+            db.extend_focus_period(period.id, now).await?;
+            period.end_time = now;
+            return Ok(Some(period));
+        }
+        (_, Some(mode)) => Some(FocusPeriod {
+            id: db.insert_focus_period(now, &mode).await?,
+            start_time: now,
+            end_time: now,
+            mode,
+        }),
+        (None, None) => return Ok(None),
+        (Some(_), None) => None,
+    };
+    let mode = period.as_ref().map(|period| period.mode.clone());
+    info!("Focus mode changed from {:?} to {:?}", previous, mode);
+    DO_NOT_DISTURB.store(mode.is_some(), Ordering::SeqCst);
+    emit_event(
+        "focus.changed",
+        json!({"do_not_disturb": mode.is_some(), "mode": mode, "previous": previous}),
+    );
+    Ok(period)
+}
+
+/// Reads whether do not disturb or a focus mode is on every `interval`
+pub async fn start_focus_monitor(db: Arc<DatabaseManager>, interval: Duration) {
+    info!("Reading the do not disturb state every {:?}", interval);
+    let mut current = None;
+    loop {
+        let mode = tokio::task::spawn_blocking(read_focus_mode)
+            .await
+            .unwrap_or_default();
+        match record_focus_mode(&db, current.clone(), mode, Utc::now()).await {
+            Ok(period) => current = period,
+            Err(e) => warn!("Failed to record the focus mode: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
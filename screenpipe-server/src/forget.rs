@@ -0,0 +1,369 @@
+use crate::encryption::{open_media, ENCRYPTED_SUFFIX};
+use crate::integrity::decode_errors;
+use crate::text_only::is_text_only_chunk;
+use crate::thumbnails::Thumbnails;
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{info, warn};
+use screenpipe_core::{
+    emit_event, Container, FfmpegCommand, FfmpegInput, FfmpegOutput, VideoCodec,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::{BTreeMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// What to remove, every given condition has to match
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ForgetFilter {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// Frames showing this app, audio is not tied to an app and is left alone
+    pub app_name: Option<String>,
+    /// Case insensitive, in the OCR text, the window title or the transcription
+    pub keyword: Option<String>,
+    /// Activity sessions are left alone, the aggregation only mode keeps them
+    #[serde(skip)]
+    pub keep_activity_sessions: bool,
+}
+
+impl ForgetFilter {
+    /// Blank conditions are dropped
+    pub fn normalized(&self) -> Self {
+        let non_blank = |value: &Option<String>| {
+            value
+                .as_deref()
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        ForgetFilter {
+            start_time: self.start_time,
+            end_time: self.end_time,
+            app_name: non_blank(&self.app_name),
+            keyword: non_blank(&self.keyword),
+            keep_activity_sessions: self.keep_activity_sessions,
+        }
+    }
+
+    /// An empty filter would match everything
+    pub fn is_empty(&self) -> bool {
+        self.start_time.is_none()
+            && self.end_time.is_none()
+            && self.app_name.is_none()
+            && self.keyword.is_none()
+    }
+}
+
+/// Frame matched by a filter
+#[derive(Debug, Clone)]
+pub struct ForgetFrame {
+    pub id: i64,
+    pub video_chunk_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+}
+
+/// Video chunk that keeps some of its frames, re-encoded without the others
+#[derive(Debug, Clone)]
+pub struct RewrittenChunk {
+    pub id: i64,
+    pub original_path: String,
+    pub file_path: String,
+    /// In their order in the new file
+    pub kept_frame_ids: Vec<i64>,
+}
+
+/// Rows and files matched by a filter
+#[derive(Debug, Clone, Default)]
+pub struct ForgetPlan {
+    pub frame_ids: Vec<i64>,
+    pub transcription_ids: Vec<i64>,
+    /// Chunks left without frames or transcriptions, removed with their file
+    pub video_chunks: Vec<(i64, String)>,
+    pub audio_chunks: Vec<(i64, String)>,
+    pub rewritten_chunks: Vec<RewrittenChunk>,
+    /// (id, screenshot) of the markers removed
+    pub markers: Vec<(i64, Option<String>)>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ForgetReport {
+    pub dry_run: bool,
+    pub frames: u64,
+    pub ocr_texts: u64,
+    pub ocr_boxes: u64,
+    pub audio_transcriptions: u64,
+    pub video_chunks_deleted: u64,
+    pub video_chunks_rewritten: u64,
+    pub audio_chunks_deleted: u64,
+    /// Full text search entries
+    pub text_index_entries: u64,
+    pub extracted_records: u64,
+    pub meeting_summaries: u64,
+    pub activity_sessions: u64,
+    /// Files written, matched on their path and the window focused then
+    pub file_events: u64,
+    /// Matched on the command and its directory, they have no app
+    pub shell_commands: u64,
+    /// Matched on the file, project, repository and branch, they have no app either
+    pub editor_contexts: u64,
+    /// Matched on the message, repository and branch
+    pub git_events: u64,
+    /// Matched on their apps, titles and bodies
+    pub notifications: u64,
+    /// Questions and answers of conversations, matched on their text, they have no app
+    pub conversation_messages: u64,
+    pub markers: u64,
+    /// Matched on their apps and window titles
+    pub window_layouts: u64,
+    /// Embeddings and answers cached from the removed text
+    pub cached_llm_responses: u64,
+    /// Files whose frames are gone from the database but could not be rewritten, the chunk
+    /// still being recorded and frames stored as images
+    pub media_kept: Vec<String>,
+}
+
+/// Where the chunk without the forgotten frames is written, next to the original
+pub fn rewritten_path(file_path: &str) -> PathBuf {
+    let path = Path::new(file_path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // unique so a chunk can be rewritten more than once
+    path.with_file_name(format!("{}_{}.mp4", stem, Utc::now().timestamp_millis()))
+}
+
+/// Consecutive offsets as inclusive ranges, keeps the filter expression short
+fn offset_ranges(offsets: &[i64]) -> Vec<(i64, i64)> {
+    let mut offsets = offsets.to_vec();
+    offsets.sort_unstable();
+    offsets.dedup();
+    let mut ranges: Vec<(i64, i64)> = Vec::new();
+    for offset in offsets {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == offset => *end = offset,
+            _ => ranges.push((offset, offset)),
+        }
+    }
+    ranges
+}
+
+/// Re-encodes a chunk without the frames at `dropped` offsets, subtitles are left out
+/// since their cues no longer line up
+pub fn drop_frames_command(input: &str, output: &str, dropped: &[i64]) -> FfmpegCommand {
+    let dropped = offset_ranges(dropped)
+        .iter()
+        .map(|(start, end)| format!("between(n\\,{}\\,{})", start, end))
+        .collect::<Vec<_>>()
+        .join("+");
+    let output = FfmpegOutput::file(output)
+        .arg("-map", "0:v")
+        .video_filter(&format!("select=not({})", dropped))
+        .arg("-fps_mode", "passthrough")
+        .video_codec(VideoCodec::Libx264 {
+            preset: "medium".to_string(),
+            crf: 23,
+        })
+        .pixel_format("yuv420p")
+        .format(Container::Mp4);
+    FfmpegCommand::new()
+        .overwrite()
+        .input(FfmpegInput::file(input))
+        .output(output)
+}
+
+async fn drop_frames(input: &str, output: &str, dropped: &[i64]) -> Result<()> {
+    let media = open_media(input).await?;
+    let result = drop_frames_command(media.path(), output, dropped)
+        .run()
+        .await?;
+    if !result.status.success() {
+        anyhow::bail!(
+            "failed to re-encode {}: {}",
+            input,
+            String::from_utf8_lossy(&result.stderr)
+        );
+    }
+    let errors = decode_errors(output).await?;
+    if !errors.is_empty() {
+        anyhow::bail!("re-encoded {} is not readable: {}", input, errors);
+    }
+    Ok(())
+}
+
+async fn remove_media(file_path: &str) {
+    match tokio::fs::remove_file(file_path).await {
+        Ok(()) => {}
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to remove forgotten media {}: {}", file_path, e),
+    }
+}
+
+/// Removes everything matching the filter from the database and the disk. Partly matched
+/// video chunks are re-encoded before anything is deleted, a failure leaves all data as it
+/// was. A dry run only counts what would be removed
+pub async fn forget(
+    db: &DatabaseManager,
+    filter: &ForgetFilter,
+    dry_run: bool,
+) -> Result<ForgetReport> {
+    let filter = filter.normalized();
+    if filter.is_empty() {
+        anyhow::bail!("the filter needs a time range, an app name or a keyword");
+    }
+
+    let frames = db.get_frames_to_forget(&filter).await?;
+    let transcription_ids = if filter.app_name.is_none() {
+        db.get_transcriptions_to_forget(&filter).await?
+    } else {
+        Vec::new()
+    };
+    let audio_chunks = db
+        .get_audio_chunks_to_forget(&transcription_ids, &filter)
+        .await?;
+    // still being written to
+    let latest_video_chunk = db.get_latest_video_chunk_id().await?;
+
+    let mut chunks: BTreeMap<i64, (String, Vec<i64>)> = BTreeMap::new();
+    for frame in &frames {
+        chunks
+            .entry(frame.video_chunk_id)
+            .or_insert_with(|| (frame.file_path.clone(), Vec::new()))
+            .1
+            .push(frame.id);
+    }
+
+    let frame_ids: Vec<i64> = frames.iter().map(|frame| frame.id).collect();
+    let markers = db.get_markers_to_forget(&filter, &frame_ids).await?;
+    let mut plan = ForgetPlan {
+        frame_ids,
+        transcription_ids,
+        audio_chunks,
+        markers,
+        ..Default::default()
+    };
+    let mut media_kept = Vec::new();
+    for (chunk_id, (file_path, matched)) in chunks {
+        let matched: HashSet<i64> = matched.into_iter().collect();
+        let chunk_frames = db.get_video_chunk_frame_offsets(chunk_id).await?;
+        let all_matched = chunk_frames.iter().all(|(id, _)| matched.contains(id));
+        if Some(chunk_id) == latest_video_chunk {
+            media_kept.push(file_path);
+        } else if all_matched {
+            plan.video_chunks.push((chunk_id, file_path));
+        } else if is_text_only_chunk(&file_path) {
+            // no file, the rows are all there is
+        } else if !file_path
+            .strip_suffix(ENCRYPTED_SUFFIX)
+            .unwrap_or(&file_path)
+            .ends_with(".mp4")
+        {
+            media_kept.push(file_path);
+        } else if !Path::new(&file_path).is_file() {
+            // nothing to rewrite, the rows go with the others
+            warn!(
+                "Video chunk {} is missing, only forgetting its rows",
+                file_path
+            );
+        } else {
+            let (dropped, kept): (Vec<_>, Vec<_>) = chunk_frames
+                .into_iter()
+                .partition(|(id, _)| matched.contains(id));
+            // written in plaintext, encrypted again once the encryptor gets to it
+            let new_path = rewritten_path(file_path.trim_end_matches(ENCRYPTED_SUFFIX))
+                .to_string_lossy()
+                .to_string();
+            if !dry_run {
+                let offsets: Vec<i64> = dropped.iter().map(|(_, offset)| *offset).collect();
+                if let Err(e) = drop_frames(&file_path, &new_path, &offsets).await {
+                    remove_media(&new_path).await;
+                    for chunk in &plan.rewritten_chunks {
+                        remove_media(&chunk.file_path).await;
+                    }
+                    return Err(e);
+                }
+            }
+            plan.rewritten_chunks.push(RewrittenChunk {
+                id: chunk_id,
+                original_path: file_path,
+                file_path: new_path,
+                kept_frame_ids: kept.into_iter().map(|(id, _)| id).collect(),
+            });
+        }
+    }
+
+    let mut report = match db.forget(&filter, &plan, dry_run).await {
+        Ok(report) => report,
+        Err(e) => {
+            if !dry_run {
+                for chunk in &plan.rewritten_chunks {
+                    remove_media(&chunk.file_path).await;
+                }
+            }
+            return Err(e.into());
+        }
+    };
+    report.media_kept = media_kept;
+    if dry_run {
+        return Ok(report);
+    }
+
+    let removed = plan
+        .video_chunks
+        .iter()
+        .chain(plan.audio_chunks.iter())
+        .map(|(_, file_path)| file_path)
+        .chain(
+            plan.rewritten_chunks
+                .iter()
+                .map(|chunk| &chunk.original_path),
+        )
+        .chain(plan.markers.iter().filter_map(|(_, image_path)| image_path.as_ref()));
+    for file_path in removed {
+        remove_media(file_path).await;
+    }
+    // the windows of a capture share its thumbnails, they go with the first one forgotten
+    let captures: HashSet<(&str, DateTime<Utc>)> = frames
+        .iter()
+        .map(|frame| (frame.file_path.as_str(), frame.timestamp))
+        .collect();
+    for (file_path, timestamp) in captures {
+        Thumbnails::of_chunk(file_path).remove(db, timestamp).await;
+    }
+    info!(
+        "Forgot {} frames and {} transcriptions matching {:?}",
+        report.frames, report.audio_transcriptions, filter
+    );
+    emit_event(
+        "data.forgotten",
+        json!({
+            "start_time": filter.start_time,
+            "end_time": filter.end_time,
+            "frames": report.frames,
+            "audio_transcriptions": report.audio_transcriptions,
+            "video_chunks_deleted": report.video_chunks_deleted,
+            "audio_chunks_deleted": report.audio_chunks_deleted,
+        }),
+    );
+    Ok(report)
+}
+
+/// Forgets everything recorded more than `retention` ago, like a forget request ending then
+pub async fn start_retention(db: Arc<DatabaseManager>, retention: ChronoDuration, interval: Duration) {
+    info!("Retention started, keeping {} days", retention.num_days());
+    loop {
+        let filter = ForgetFilter {
+            end_time: Some(Utc::now() - retention),
+            ..Default::default()
+        };
+        if let Err(e) = forget(&db, &filter, false).await {
+            warn!("Failed to delete data past retention: {}", e);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
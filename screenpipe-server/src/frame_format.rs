@@ -0,0 +1,171 @@
+use anyhow::Result;
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::sync::RwLock;
+
+/// Quality of the lossy formats when none is configured, from 1 to 100
+pub const DEFAULT_FRAME_QUALITY: u8 = 80;
+/// From 1 (smallest) to 10 (fastest), fast enough to keep up with the capture
+const AVIF_SPEED: u8 = 8;
+
+/// Images the frames are stored as rather than H.264 video, None until `set_frame_storage`
+static FRAME_STORAGE: RwLock<Option<FrameEncoding>> = RwLock::new(None);
+
+/// Image formats the frames are stored and served as
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameFormat {
+    /// Lossless, the biggest
+    Png,
+    /// Lossless, about half the size of PNG for screens
+    Webp,
+    /// Lossy, the smallest for its fidelity but the slowest to encode
+    Avif,
+    Jpeg,
+}
+
+impl FrameFormat {
+    pub const ALL: [FrameFormat; 4] = [
+        FrameFormat::Png,
+        FrameFormat::Webp,
+        FrameFormat::Avif,
+        FrameFormat::Jpeg,
+    ];
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "png",
+            FrameFormat::Webp => "webp",
+            FrameFormat::Avif => "avif",
+            FrameFormat::Jpeg => "jpg",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            FrameFormat::Png => "image/png",
+            FrameFormat::Webp => "image/webp",
+            FrameFormat::Avif => "image/avif",
+            FrameFormat::Jpeg => "image/jpeg",
+        }
+    }
+
+    pub fn from_content_type(content_type: &str) -> Option<Self> {
+        match content_type {
+            "image/jpg" => Some(FrameFormat::Jpeg),
+            _ => Self::ALL
+                .into_iter()
+                .find(|format| format.content_type() == content_type),
+        }
+    }
+
+    pub fn is_lossless(self) -> bool {
+        matches!(self, FrameFormat::Png | FrameFormat::Webp)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct FrameEncoding {
+    pub format: FrameFormat,
+    /// From 1 to 100, only for AVIF and JPEG
+    pub quality: u8,
+}
+
+impl Default for FrameEncoding {
+    /// What the native encoder always stored frames as
+    fn default() -> Self {
+        FrameEncoding {
+            format: FrameFormat::Webp,
+            quality: DEFAULT_FRAME_QUALITY,
+        }
+    }
+}
+
+impl FrameEncoding {
+    pub fn encode(&self, image: &DynamicImage) -> Result<Vec<u8>> {
+        // WebP, AVIF and JPEG only take 8 bit rgb, screens have no alpha
+        let image = DynamicImage::ImageRgb8(image.to_rgb8());
+        let quality = self.quality.clamp(1, 100);
+        let mut buffer = Vec::new();
+        match self.format {
+            FrameFormat::Png => image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::Png)?,
+            FrameFormat::Webp => {
+                image.write_to(&mut Cursor::new(&mut buffer), ImageFormat::WebP)?
+            }
+            FrameFormat::Avif => image.write_with_encoder(AvifEncoder::new_with_speed_quality(
+                &mut buffer,
+                AVIF_SPEED,
+                quality,
+            ))?,
+            FrameFormat::Jpeg => {
+                image.write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality))?
+            }
+        }
+        Ok(buffer)
+    }
+}
+
+/// Stores the frames as images of `encoding` rather than video, None goes back to video
+pub fn set_frame_storage(encoding: Option<FrameEncoding>) {
+    *FRAME_STORAGE.write().unwrap() = encoding;
+}
+
+pub fn frame_storage() -> Option<FrameEncoding> {
+    *FRAME_STORAGE.read().unwrap()
+}
+
+/// The format out of an `Accept` header that the client prefers, the `stored` one on ties
+/// since it's sent without re-encoding. None when the client takes none of them
+pub fn negotiate_frame_format(accept: Option<&str>, stored: FrameFormat) -> Option<FrameFormat> {
+    let Some(accept) = accept.filter(|accept| !accept.trim().is_empty()) else {
+        return Some(stored);
+    };
+    // (content type, q) of each media range
+    let ranges: Vec<(&str, f32)> = accept
+        .split(',')
+        .filter_map(|range| {
+            let mut parts = range.split(';').map(str::trim);
+            let content_type = parts
+                .next()
+                .filter(|content_type| !content_type.is_empty())?;
+            let q = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse::<f32>().ok())?;
+            Some((content_type, q))
+        })
+        .collect();
+    // the most specific range matching a format gives its q
+    let weight = |format: FrameFormat| {
+        let specific = ranges
+            .iter()
+            .find(|(content_type, _)| FrameFormat::from_content_type(content_type) == Some(format));
+        specific
+            .or_else(|| {
+                ranges
+                    .iter()
+                    .find(|(content_type, _)| *content_type == "image/*")
+            })
+            .or_else(|| {
+                ranges
+                    .iter()
+                    .find(|(content_type, _)| *content_type == "*/*")
+            })
+            .map_or(0.0, |(_, q)| *q)
+    };
+    std::iter::once(stored)
+        .chain(FrameFormat::ALL)
+        .map(|format| (format, weight(format)))
+        .filter(|(_, q)| *q > 0.0)
+        // the first of the best, max_by would keep the last
+        .fold(
+            None,
+            |best: Option<(FrameFormat, f32)>, (format, q)| match best {
+                Some((_, best_q)) if best_q >= q => best,
+                _ => Some((format, q)),
+            },
+        )
+        .map(|(format, _)| format)
+}
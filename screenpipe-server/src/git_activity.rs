@@ -0,0 +1,108 @@
+use crate::pause::PauseLog;
+use crate::{CapturePause, DatabaseManager};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use screenpipe_integrations::git_activity::{git_dir, read_git_activity, GitEventKind};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// How often the reflogs are looked at, git writes them on every commit and checkout
+pub const GIT_ACTIVITY_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GitEvent {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// Path of the repository, as given to --git-repo
+    pub repository: String,
+    pub kind: GitEventKind,
+    pub branch: Option<String>,
+    pub commit: String,
+    pub previous_commit: String,
+    pub message: String,
+    pub author: String,
+}
+
+/// Reads the reflogs of the repositories changed since `modified`, and stores their moves but
+/// the ones made while capture was paused. Returns how many were new
+pub async fn import_git_activity(
+    db: &DatabaseManager,
+    repositories: &[PathBuf],
+    modified: &mut HashMap<PathBuf, SystemTime>,
+    pauses: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> Result<u64> {
+    let mut imported = 0;
+    for repository in repositories {
+        let git_dir = match git_dir(repository) {
+            Ok(git_dir) => git_dir,
+            Err(e) => {
+                debug!("Skipped git activity: {}", e);
+                continue;
+            }
+        };
+        // commits and checkouts all write the reflog of HEAD
+        let Ok(changed) = tokio::fs::metadata(git_dir.join("logs/HEAD"))
+            .await
+            .and_then(|m| m.modified())
+        else {
+            continue;
+        };
+        if modified.get(repository) == Some(&changed) {
+            continue;
+        }
+        let entries: Vec<_> = tokio::task::spawn_blocking(move || read_git_activity(&git_dir))
+            .await??
+            .into_iter()
+            .filter(|entry| {
+                !pauses
+                    .iter()
+                    .any(|(start, end)| entry.timestamp >= *start && entry.timestamp <= *end)
+            })
+            .collect();
+        imported += db
+            .insert_git_events(&repository.display().to_string(), &entries)
+            .await?;
+        modified.insert(repository.clone(), changed);
+        debug!(
+            "Read {} git events of {}",
+            entries.len(),
+            repository.display()
+        );
+    }
+    Ok(imported)
+}
+
+/// Records the commits and branch switches of the repositories as they're made
+pub async fn start_git_activity_watcher(
+    db: Arc<DatabaseManager>,
+    repositories: Vec<PathBuf>,
+    capture_pause: Arc<CapturePause>,
+    interval: Duration,
+) {
+    info!(
+        "Recording git activity of {}",
+        repositories
+            .iter()
+            .map(|repository| repository.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let mut modified = HashMap::new();
+    let mut pause_log = PauseLog::new(interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if pause_log.observe(&capture_pause) {
+            continue;
+        }
+        match import_git_activity(&db, &repositories, &mut modified, pause_log.pauses()).await {
+            Ok(0) => {}
+            Ok(imported) => info!("Recorded {} git events", imported),
+            Err(e) => warn!("Failed to read git activity: {}", e),
+        }
+    }
+}
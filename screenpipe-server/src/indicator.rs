@@ -0,0 +1,182 @@
+use crate::pause::{CapturePause, PauseStatus};
+use anyhow::{bail, Result};
+use log::{info, warn};
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+/// Written by the indicator when it's clicked
+pub const TOGGLE_LINE: &str = "toggle";
+/// How often the pause is looked at, pauses of the API and the schedule change it too
+const INDICATOR_REFRESH: Duration = Duration::from_millis(500);
+const DOT_SIZE: f64 = 12.0;
+const DOT_MARGIN: f64 = 8.0;
+const BORDER_WIDTH: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndicatorStyle {
+    Dot,
+    Border,
+}
+
+impl IndicatorStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndicatorStyle::Dot => "dot",
+            IndicatorStyle::Border => "border",
+        }
+    }
+}
+
+/// What the indicator shows, the server writes it on the indicator's stdin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndicatorState {
+    Capturing,
+    Paused,
+}
+
+impl IndicatorState {
+    pub fn of(status: &PauseStatus) -> Self {
+        if status.paused {
+            IndicatorState::Paused
+        } else {
+            IndicatorState::Capturing
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            IndicatorState::Capturing => "capturing",
+            IndicatorState::Paused => "paused",
+        }
+    }
+}
+
+impl FromStr for IndicatorState {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "capturing" => Ok(IndicatorState::Capturing),
+            "paused" => Ok(IndicatorState::Paused),
+            _ => bail!("unknown indicator state: {}", s),
+        }
+    }
+}
+
+/// The windows of the indicator as (x, y, width, height) in physical pixels, for a monitor at
+/// `position` of `size` physical pixels
+pub fn indicator_rects(
+    style: IndicatorStyle,
+    position: (i32, i32),
+    size: (u32, u32),
+    scale_factor: f64,
+) -> Vec<(i32, i32, u32, u32)> {
+    let (x, y) = position;
+    let (width, height) = size;
+    match style {
+        IndicatorStyle::Dot => {
+            let dot = (DOT_SIZE * scale_factor).round() as u32;
+            let margin = (DOT_MARGIN * scale_factor).round() as i32;
+            vec![(x + width as i32 - margin - dot as i32, y + margin, dot, dot)]
+        }
+        IndicatorStyle::Border => {
+            let border = ((BORDER_WIDTH * scale_factor).round() as u32).max(1);
+            let side = height.saturating_sub(2 * border);
+            vec![
+                (x, y, width, border),
+                (x, y + height as i32 - border as i32, width, border),
+                (x, y + border as i32, border, side),
+                (
+                    x + width as i32 - border as i32,
+                    y + border as i32,
+                    border,
+                    side,
+                ),
+            ]
+        }
+    }
+}
+
+/// Resumes a paused capture or pauses it until resumed
+pub fn toggle_capture(capture_pause: &Arc<CapturePause>) -> PauseStatus {
+    if capture_pause.is_paused() {
+        capture_pause.resume()
+    } else {
+        capture_pause.pause(None)
+    }
+}
+
+/// The native indicator, built next to the screenpipe binary with the `indicator` feature
+fn indicator_path() -> Result<PathBuf> {
+    Ok(std::env::current_exe()?.with_file_name(format!(
+        "screenpipe-indicator{}",
+        std::env::consts::EXE_SUFFIX
+    )))
+}
+
+/// Shows the indicator while screenpipe runs, red while capturing and grey while paused.
+/// Clicking it pauses or resumes capture
+pub async fn start_recording_indicator(capture_pause: Arc<CapturePause>, style: IndicatorStyle) {
+    let path = match indicator_path() {
+        Ok(path) => path,
+        Err(e) => {
+            warn!("Failed to find the recording indicator: {}", e);
+            return;
+        }
+    };
+    let mut child = match Command::new(&path)
+        .arg("--style")
+        .arg(style.as_str())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            warn!(
+                "Failed to show the recording indicator {}, screenpipe has to be built with the indicator feature: {}",
+                path.display(),
+                e
+            );
+            return;
+        }
+    };
+    let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+        return;
+    };
+    let mut clicks = BufReader::new(stdout).lines();
+    let mut refresh = tokio::time::interval(INDICATOR_REFRESH);
+    let mut shown = None;
+    loop {
+        tokio::select! {
+            _ = refresh.tick() => {
+                let state = IndicatorState::of(&capture_pause.status());
+                if shown == Some(state) {
+                    continue;
+                }
+                if let Err(e) = stdin.write_all(format!("{}\n", state.as_str()).as_bytes()).await {
+                    warn!("The recording indicator closed: {}", e);
+                    return;
+                }
+                shown = Some(state);
+            }
+            line = clicks.next_line() => match line {
+                Ok(Some(line)) if line.trim() == TOGGLE_LINE => {
+                    let status = toggle_capture(&capture_pause);
+                    info!("Capture {} from the recording indicator", if status.paused { "paused" } else { "resumed" });
+                }
+                Ok(Some(_)) => {}
+                Ok(None) | Err(_) => {
+                    warn!("The recording indicator closed");
+                    return;
+                }
+            }
+        }
+    }
+}
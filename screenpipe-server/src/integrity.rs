@@ -0,0 +1,164 @@
+use crate::power::background_jobs_paused;
+use crate::text_only::is_text_only_chunk;
+use crate::DatabaseManager;
+use anyhow::Result;
+use log::{debug, error, info, warn};
+use screenpipe_core::{emit_event, Container, FfmpegCommand, FfmpegInput, FfmpegOutput};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CHUNKS_PER_RUN: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkKind {
+    Video,
+    Audio,
+}
+
+impl ChunkKind {
+    pub fn table(&self) -> &'static str {
+        match self {
+            ChunkKind::Video => "video_chunks",
+            ChunkKind::Audio => "audio_chunks",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChunkIntegrity {
+    Ok,
+    /// Decoded with errors and was remuxed, the undamaged part is kept
+    Repaired,
+    /// Missing or not decodable, hidden from searches
+    Broken,
+}
+
+impl ChunkIntegrity {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChunkIntegrity::Ok => "ok",
+            ChunkIntegrity::Repaired => "repaired",
+            ChunkIntegrity::Broken => "broken",
+        }
+    }
+}
+
+/// Errors ffmpeg reported while decoding the whole file, empty if it's intact
+pub async fn decode_errors(file_path: &str) -> Result<String> {
+    let output = FfmpegCommand::new()
+        .global_arg("-v", "error")
+        .input(FfmpegInput::file(file_path))
+        .output(FfmpegOutput::file("-").format(Container::Other("null".to_string())))
+        .run()
+        .await?;
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    if !output.status.success() && stderr.is_empty() {
+        return Ok(format!("ffmpeg exited with {}", output.status));
+    }
+    Ok(stderr)
+}
+
+/// Remuxes what can still be read into a new file and swaps it in if that one decodes cleanly
+pub async fn repair_chunk(file_path: &str) -> Result<bool> {
+    let path = Path::new(file_path);
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_else(|| "mp4".to_string());
+    let repaired_path = path.with_extension(format!("repaired.{}", extension));
+    let repaired = repaired_path.to_string_lossy().to_string();
+
+    let output = FfmpegCommand::new()
+        .overwrite()
+        .input(FfmpegInput::file(file_path).arg("-err_detect", "ignore_err"))
+        .output(
+            FfmpegOutput::file(&repaired)
+                .arg("-map", "0")
+                .arg("-c", "copy"),
+        )
+        .run()
+        .await?;
+
+    let usable = output.status.success()
+        && tokio::fs::metadata(&repaired_path)
+            .await
+            .is_ok_and(|m| m.len() > 0)
+        && decode_errors(&repaired).await?.is_empty();
+    if !usable {
+        let _ = tokio::fs::remove_file(&repaired_path).await;
+        return Ok(false);
+    }
+
+    // rename doesn't replace existing files on windows
+    if cfg!(windows) {
+        tokio::fs::remove_file(path).await?;
+    }
+    tokio::fs::rename(&repaired_path, path).await?;
+    Ok(true)
+}
+
+/// Checks one chunk, repairing it if needed
+pub async fn check_chunk(file_path: &str) -> Result<ChunkIntegrity> {
+    if is_text_only_chunk(file_path) {
+        return Ok(ChunkIntegrity::Ok);
+    }
+    let path = Path::new(file_path);
+    if !path.exists() {
+        debug!("{} is missing", file_path);
+        return Ok(ChunkIntegrity::Broken);
+    }
+    // frames stored as separate images are written one by one, nothing to decode as a whole
+    if path.is_dir() {
+        return Ok(ChunkIntegrity::Ok);
+    }
+
+    let errors = decode_errors(file_path).await?;
+    if errors.is_empty() {
+        return Ok(ChunkIntegrity::Ok);
+    }
+    warn!("{} is damaged: {}", file_path, errors);
+
+    if repair_chunk(file_path).await? {
+        info!("Repaired {}", file_path);
+        Ok(ChunkIntegrity::Repaired)
+    } else {
+        warn!("Could not repair {}, hiding it from searches", file_path);
+        Ok(ChunkIntegrity::Broken)
+    }
+}
+
+async fn check_unchecked_chunks(db: &DatabaseManager) -> Result<()> {
+    for kind in [ChunkKind::Video, ChunkKind::Audio] {
+        let chunks = db.get_unchecked_chunks(kind, CHUNKS_PER_RUN).await?;
+        for (chunk_id, file_path) in chunks {
+            let integrity = check_chunk(&file_path).await?;
+            db.set_chunk_integrity(kind, chunk_id, integrity).await?;
+            if integrity != ChunkIntegrity::Ok {
+                emit_event(
+                    "chunk.integrity",
+                    json!({"kind": kind, "id": chunk_id, "file_path": file_path, "integrity": integrity}),
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Periodically decodes finished chunks, remuxing the damaged ones and marking those
+/// that can't be read, e.g. after a power loss during recording
+pub async fn start_integrity_checker(db: Arc<DatabaseManager>, interval: Duration) {
+    info!("Chunk integrity checker started");
+    loop {
+        if !background_jobs_paused() {
+            if let Err(e) = check_unchecked_chunks(&db).await {
+                error!("Failed to check chunk integrity: {}", e);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,287 @@
+use crate::db::ContentSource;
+use crate::DatabaseManager;
+use log::{debug, error, info};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Stored for the texts too short or too mixed to tell
+pub const UNDETERMINED: &str = "und";
+const INDEX_BATCH_SIZE: u32 = 500;
+/// Latin texts with fewer of their stopwords are undetermined
+const MIN_STOPWORDS: usize = 2;
+
+/// Most frequent short words of the languages written in latin script, none is in two lists
+const STOPWORDS: [(&str, &[&str]); 10] = [
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "of", "to", "with", "this", "that", "you", "for", "was",
+            "have", "not", "it",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "les", "des", "est", "et", "une", "pour", "dans", "avec", "pas", "sur", "qui",
+            "vous", "nous", "au",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "mit", "auf", "ein", "eine", "sie", "ich",
+            "auch", "wir", "zu",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "los", "las", "y", "es", "por", "con", "una", "del", "para", "como", "pero",
+            "muy", "su", "lo",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "gli", "della", "che", "di", "per", "non", "sono", "alla", "uno", "questo", "ma",
+            "nel", "anche", "ho",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "os", "um", "uma", "não", "em", "com", "também", "mas", "seu", "ao", "você", "foi",
+            "são", "isso", "da",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "het", "een", "naar", "van", "niet", "ik", "je", "op", "dat", "zijn", "voor", "maar",
+            "wat", "ook", "er",
+        ],
+    ),
+    (
+        "sv",
+        &[
+            "och", "att", "det", "som", "om", "är", "på", "för", "med", "inte", "jag", "har", "av",
+            "till", "den",
+        ],
+    ),
+    (
+        "pl",
+        &[
+            "nie", "się", "jest", "na", "że", "od", "w", "z", "do", "jak", "tak", "ale", "czy",
+            "mnie", "już",
+        ],
+    ),
+    (
+        "tr",
+        &[
+            "bir", "ve", "bu", "için", "ile", "çok", "ne", "değil", "daha", "ama", "gibi", "ben",
+            "var", "mi", "olarak",
+        ],
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Han,
+    Kana,
+    Hangul,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Thai,
+    Devanagari,
+}
+
+fn script(c: char) -> Option<Script> {
+    Some(match c as u32 {
+        0x41..=0x5A | 0x61..=0x7A | 0xC0..=0x24F | 0x1E00..=0x1EFF => Script::Latin,
+        0x3040..=0x30FF | 0x31F0..=0x31FF | 0xFF66..=0xFF9F => Script::Kana,
+        0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xF900..=0xFAFF | 0x20000..=0x2FA1F => Script::Han,
+        0x1100..=0x11FF | 0x3130..=0x318F | 0xAC00..=0xD7AF => Script::Hangul,
+        0x400..=0x4FF => Script::Cyrillic,
+        0x370..=0x3FF => Script::Greek,
+        0x600..=0x6FF | 0x750..=0x77F => Script::Arabic,
+        0x590..=0x5FF => Script::Hebrew,
+        0xE00..=0xE7F => Script::Thai,
+        0x900..=0x97F => Script::Devanagari,
+        _ => return None,
+    })
+}
+
+/// Written without spaces between words, indexed as bigrams
+pub fn is_cjk(c: char) -> bool {
+    matches!(
+        script(c),
+        Some(Script::Han) | Some(Script::Kana) | Some(Script::Hangul)
+    )
+}
+
+/// ISO 639-1 code of the language `text` is in, `UNDETERMINED` when there's too little of it.
+/// Told by the script, and by the stopwords among the latin ones
+pub fn detect_language(text: &str) -> &'static str {
+    let mut counts: Vec<(Script, usize)> = Vec::new();
+    for script in text.chars().filter_map(script) {
+        match counts.iter_mut().find(|(counted, _)| *counted == script) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((script, 1)),
+        }
+    }
+    let count = |script: Script| {
+        counts
+            .iter()
+            .find(|(counted, _)| *counted == script)
+            .map_or(0, |(_, count)| *count)
+    };
+    let cjk = count(Script::Han) + count(Script::Kana) + count(Script::Hangul);
+    let Some((main, letters)) = counts.iter().copied().max_by_key(|(_, count)| *count) else {
+        return UNDETERMINED;
+    };
+    // a couple of ideographs say more than a couple of latin letters
+    if cjk >= 2 && cjk * 3 >= letters {
+        return if count(Script::Hangul) > 0 && count(Script::Hangul) >= count(Script::Kana) {
+            "ko"
+        } else if count(Script::Kana) > 0 {
+            "ja"
+        } else {
+            "zh"
+        };
+    }
+    if letters < 4 {
+        return UNDETERMINED;
+    }
+    match main {
+        Script::Cyrillic if text.chars().any(|c| "іїєґІЇЄҐ".contains(c)) => "uk",
+        Script::Cyrillic => "ru",
+        Script::Greek => "el",
+        Script::Arabic if text.chars().any(|c| "پچژگ".contains(c)) => "fa",
+        Script::Arabic => "ar",
+        Script::Hebrew => "he",
+        Script::Thai => "th",
+        Script::Devanagari => "hi",
+        Script::Latin => latin_language(text),
+        Script::Han | Script::Kana | Script::Hangul => UNDETERMINED,
+    }
+}
+
+fn latin_language(text: &str) -> &'static str {
+    let words: Vec<String> = text
+        .split(|c: char| !c.is_alphabetic())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    let mut scores: Vec<(&'static str, usize)> = STOPWORDS
+        .iter()
+        .map(|(language, stopwords)| {
+            let hits = words
+                .iter()
+                .filter(|word| stopwords.contains(&word.as_str()))
+                .count();
+            (*language, hits)
+        })
+        .collect();
+    scores.sort_by_key(|(_, hits)| std::cmp::Reverse(*hits));
+    match scores.as_slice() {
+        [(language, best), (_, second), ..] if *best >= MIN_STOPWORDS && best > second => language,
+        _ => UNDETERMINED,
+    }
+}
+
+/// `text` with each run of CJK characters replaced by its overlapping bigrams, what the fts
+/// tables index and are queried with
+pub fn cjk_bigrams(text: &str) -> String {
+    fn flush(run: &mut Vec<char>, output: &mut String) {
+        if run.is_empty() {
+            return;
+        }
+        output.push(' ');
+        if run.len() == 1 {
+            output.push(run[0]);
+        } else {
+            let bigrams: Vec<String> = run.windows(2).map(|pair| pair.iter().collect()).collect();
+            output.push_str(&bigrams.join(" "));
+        }
+        output.push(' ');
+        run.clear();
+    }
+    let mut output = String::with_capacity(text.len() * 2);
+    let mut run = Vec::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut output);
+            output.push(c);
+        }
+    }
+    flush(&mut run, &mut output);
+    output
+}
+
+/// FTS5 query of the words of a search, all of them in any order. None when it has none
+pub fn fts_query(query: &str) -> Option<String> {
+    let terms: Vec<String> = query
+        .split_whitespace()
+        .map(|word| cjk_bigrams(word).trim().replace('"', "\"\""))
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .map(|term| format!("\"{}\"", term))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" "))
+}
+
+/// Tags the texts not indexed yet with their language and adds them to the fts tables,
+/// the number indexed
+pub async fn index_new_texts(db: &DatabaseManager) -> Result<u64, sqlx::Error> {
+    let mut indexed = 0;
+    for source in [ContentSource::Screen, ContentSource::Audio] {
+        loop {
+            let texts = db.get_unindexed_texts(source, INDEX_BATCH_SIZE).await?;
+            for (id, text) in &texts {
+                let language = detect_language(text);
+                let searchable = text.chars().any(char::is_alphanumeric) && text != "No text found";
+                let indexed_text = searchable.then(|| cjk_bigrams(text));
+                db.index_text(source, *id, language, indexed_text.as_deref())
+                    .await?;
+            }
+            indexed += texts.len() as u64;
+            if texts.len() < INDEX_BATCH_SIZE as usize {
+                break;
+            }
+        }
+    }
+    Ok(indexed)
+}
+
+/// Keeps the language of the texts and their fts index up to date, the texts recorded before
+/// the index existed included
+pub async fn start_text_indexer(db: Arc<DatabaseManager>, interval: Duration) {
+    info!("Text language indexer started");
+    loop {
+        match index_new_texts(&db).await {
+            Ok(0) => {}
+            Ok(indexed) => debug!("Indexed the language of {} texts", indexed),
+            Err(e) => error!("Failed to index texts: {}", e),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// SQL condition on `id_column` being in the rowids of `fts_table` matching the words of a
+/// search, to OR with the substring match. Empty when the search has no words
+pub fn fts_condition(query: &str, fts_table: &str, id_column: &str) -> String {
+    match fts_query(query) {
+        Some(fts_query) => format!(
+            " OR {} IN (SELECT rowid FROM {} WHERE {} MATCH '{}')",
+            id_column,
+            fts_table,
+            fts_table,
+            fts_query.replace('\'', "''")
+        ),
+        None => String::new(),
+    }
+}
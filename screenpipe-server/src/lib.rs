@@ -1,20 +1,176 @@
+pub mod activity;
+pub mod aggregation;
+pub mod analytics;
+pub mod app_icons;
+pub mod app_profiles;
+pub mod audit;
+pub mod automation;
+pub mod backup;
+pub mod browser_pages;
+pub mod calendar;
+pub mod capabilities;
 pub mod chunking;
+pub mod compaction;
 pub mod cli;
+pub mod clips;
+pub mod compliance;
+pub mod consumer_redaction;
+pub mod conversations;
+pub mod credentials;
 pub mod core;
 mod db;
+pub mod disk_guard;
+pub mod doctor;
+pub mod editor_context;
+pub mod email;
+pub mod encryption;
+pub mod errors;
+pub mod extraction;
+pub mod file_activity;
 pub mod filtering;
+pub mod focus_mode;
+pub mod git_activity;
+pub mod indicator;
+pub mod forget;
+pub mod frame_format;
+pub mod integrity;
+pub mod language;
+pub mod live_ocr;
+pub mod live_view;
+pub mod local_api;
+pub mod llm;
+pub mod llm_tools;
 pub mod logs;
+pub mod markers;
+pub mod meetings;
+pub mod native_encoder;
+pub mod notifications;
+pub mod pagination;
+pub mod pause;
+pub mod pdf;
 mod plugin;
+pub mod policy;
+pub mod power;
+pub mod profiles;
 mod resource_monitor;
+pub mod relocate;
+pub mod restore;
+pub mod scene;
+pub mod screen_regions;
 mod server;
+pub mod shell_history;
+pub mod similarity;
+pub mod slides;
+pub mod subtitles;
+pub mod test_harness;
+pub mod text_changes;
+pub mod text_only;
+pub mod thumbnails;
+pub mod timelapse;
+pub mod timeline_fs;
+pub mod timezones;
+pub mod tls;
+pub mod trends;
+pub mod user_isolation;
+pub mod vault;
 mod video;
+pub mod watchlist;
+pub mod window_history;
+pub mod window_layout;
+pub mod write_coalescer;
+pub use activity::{
+    start_activity_classifier, ActivityCategory, ActivityClassifier, ActivitySession,
+    CategoryDuration,
+};
+pub use app_icons::{start_icon_capture, AppIcons};
+pub use audit::{AuditAction, AuditEntry};
+pub use automation::{
+    load_automation_grants, Automation, AutomationAction, AutomationGrant, AutomationRecord,
+    AutomationStatus,
+};
+pub use analytics::{analytics_rows, query_analytics, AnalyticsQueryResult};
+pub use calendar::{
+    calendar_feed, focus_blocks, label_meetings, start_calendar_sync, FocusBlock,
+};
+pub use capabilities::{
+    encoding_capabilities, machine_capabilities, set_engine_selection,
+    set_video_encoder_selection, Capabilities, EncoderBackend, EncodingCapabilities,
+    EngineCapability, FeatureCapabilities, VideoEncoderSelection,
+};
 pub use cli::Cli;
+pub use clips::{render_clip, ClipJob, ClipOptions, ClipRenderer, RedactionStyle};
+pub use compliance::{
+    load_compliance_policy, ComplianceMode, CompliancePolicy, ComplianceStatus, ConsentRecord,
+};
+pub use compaction::{start_storage_compactor, CompactionCodec, CompactionOptions};
+pub use consumer_redaction::{Consumer, ConsumerRedaction};
+pub use conversations::{Citation, Conversation, ConversationMessage};
+pub use credentials::{ApiCredentials, Caller};
 pub use core::{start_continuous_recording, RecorderControl};
-pub use db::{ContentType, DatabaseManager, SearchResult};
+pub use db::{
+    check_database_file, get_database_file_chunks, rewrite_database_file_paths,
+    set_database_file_chunk_path, ContentSource, ContentType, DatabaseManager, LlmUsageSummary,
+    SearchResult,
+};
+pub use disk_guard::{disk_level, start_disk_guard, DiskLevel};
+pub use doctor::{run_doctor, CheckStatus, DoctorCheck, DoctorOptions, DoctorReport};
+pub use editor_context::{record_editor_beacon, EditorBeacon, EditorContext};
+pub use email::{load_email_destinations, start_email_alerts, start_email_digests};
+pub use encryption::{prepare_decrypted_dir, set_media_keys, start_media_encryptor, KeyRing};
+pub use errors::StorageError;
+pub use extraction::{start_extraction_worker, ExtractedRecord, ExtractionKind, ExtractionRule};
+pub use file_activity::{record_file_event, start_file_watcher, FileEvent, FileEventKind};
+pub use focus_mode::{do_not_disturb, start_focus_monitor, FocusPeriod};
+pub use forget::{forget, start_retention, ForgetFilter, ForgetReport};
+pub use frame_format::{
+    frame_storage, negotiate_frame_format, set_frame_storage, FrameEncoding, FrameFormat,
+};
+pub use git_activity::{start_git_activity_watcher, GitEvent};
+pub use integrity::{start_integrity_checker, ChunkIntegrity, ChunkKind};
+pub use live_ocr::{LiveOcr, LiveOcrBlock, LiveOcrScreen};
+pub use live_view::LiveView;
+pub use llm::{load_tool_grants, LlmAnswer, LlmService, LlmTool, ToolGrant, ToolRegistry, ToolRun};
+pub use llm_tools::builtin_tools;
 pub use logs::MultiWriter;
+pub use markers::{Marker, MarkerRecorder, MarkerRequest};
+pub use meetings::{start_meeting_summarizer, ActionItem, MeetingSummary};
+pub use notifications::{start_notification_capture, Notification};
+pub use pagination::{Cursor, CursorPosition, Page};
+pub use pause::{CapturePause, PauseLog, PauseStatus};
+pub use pdf::{pdf_document, PdfPage, PdfWord};
+pub use policy::{
+    load_policy_rules, DetectedEntity, PolicyAction, PolicyRule, RedactionAnnotation,
+    RedactionPolicy, RedactionRecord, RedactionSource,
+};
+pub use power::{start_power_monitor, PowerProfile, PowerProfiles, PowerState};
+pub use relocate::{relocate_media, RelocateOptions, RelocateReport};
+pub use profiles::{ActiveProfile, Profile, ProfilesConfig};
 pub use resource_monitor::{ResourceMonitor, RestartSignal};
 pub use server::health_check;
+pub use server::record_compliance_consent;
 pub use server::AppState;
 pub use server::HealthCheckResponse;
 pub use server::Server;
-pub use video::VideoCapture;
+pub use scene::{SceneChange, SceneKind, SceneTracker};
+pub use screen_regions::{OcrBox, RegionFilter, RegionText};
+pub use shell_history::{start_shell_history_import, ShellCommand, ShellCommandSource};
+pub use similarity::{find_similar_frames, perceptual_hash, SimilarFrame};
+pub use slides::{Slide, SlideDeck, SlideRecorder};
+pub use subtitles::start_subtitle_muxer;
+pub use test_harness::{
+    ReplayReport, SyntheticAudio, SyntheticFrame, SyntheticWindow, SyntheticWorkload,
+};
+pub use text_changes::{TextChange, TextChangeTracker};
+pub use text_only::{is_text_only_chunk, set_text_only, text_only};
+pub use thumbnails::{Thumbnails, THUMBNAIL_WIDTHS};
+pub use timelapse::{
+    render_timelapse, TimelapseJob, TimelapseOptions, TimelapseRenderer, TimelapseStatus,
+};
+pub use timezones::{start_timezone_tracker, TimezoneSegment};
+pub use trends::{start_trend_analyzer, WeekMetrics, WeeklyTrends};
+pub use user_isolation::{UserInstance, UserIsolation};
+pub use video::{extract_frame, VideoCapture};
+pub use watchlist::{Watch, WatchAlert, WatchKind, WatchSource, Watchlist};
+pub use window_history::{start_window_history, WindowEvent, WindowEventKind, WindowTracker};
+pub use window_layout::{LayoutRecorder, LayoutWindow, WindowLayout};
+pub use write_coalescer::{PendingWrite, WriteCoalescer};
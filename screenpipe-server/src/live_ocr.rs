@@ -0,0 +1,313 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use futures::Stream;
+use image::DynamicImage;
+use screenpipe_vision::monitor::get_monitor_by_id;
+use screenpipe_vision::{perform_ocr_tesseract_words, CaptureResult, OcrWord};
+use serde::Serialize;
+use std::sync::Arc;
+use tokio::sync::{watch, Mutex};
+
+/// A rectangle in pixels, its top left corner at `x`, `y`
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Where the captured monitor is on the desktop. The frame can have more pixels than the
+/// desktop has points on high dpi screens
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+pub struct MonitorArea {
+    pub id: u32,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub frame_width: u32,
+    pub frame_height: u32,
+}
+
+impl MonitorArea {
+    /// A rectangle of the frame in the coordinates windows are placed in on the desktop
+    pub fn to_screen(&self, rect: Rect) -> Rect {
+        let scale_x = self.width as f64 / self.frame_width.max(1) as f64;
+        let scale_y = self.height as f64 / self.frame_height.max(1) as f64;
+        Rect {
+            x: self.x as f64 + rect.x * scale_x,
+            y: self.y as f64 + rect.y * scale_y,
+            width: rect.width * scale_x,
+            height: rect.height * scale_y,
+        }
+    }
+}
+
+/// Where a monitor whose frames are `frame_size` is on the desktop
+pub async fn monitor_area(monitor_id: u32, frame_size: (u32, u32)) -> MonitorArea {
+    let (frame_width, frame_height) = frame_size;
+    match get_monitor_by_id(monitor_id).await {
+        Some(monitor) => MonitorArea {
+            id: monitor_id,
+            x: monitor.x(),
+            y: monitor.y(),
+            width: monitor.width(),
+            height: monitor.height(),
+            frame_width,
+            frame_height,
+        },
+        // unplugged since the frame was captured
+        None => MonitorArea {
+            id: monitor_id,
+            x: 0,
+            y: 0,
+            width: frame_width,
+            height: frame_height,
+            frame_width,
+            frame_height,
+        },
+    }
+}
+
+/// A word read on the screen
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LiveOcrBlock {
+    pub text: String,
+    pub confidence: f32,
+    /// In pixels of the captured frame
+    pub frame_rect: Rect,
+    /// In desktop coordinates, what an overlay window or a mouse click is placed with
+    pub screen_rect: Rect,
+    /// Words of the same line of the same window have the same number
+    pub line: usize,
+    pub app_name: String,
+    pub window_name: String,
+    pub focused: bool,
+}
+
+/// The words on the screen at the latest captured frame
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct LiveOcrScreen {
+    pub frame_number: u64,
+    pub timestamp: DateTime<Utc>,
+    pub monitor: MonitorArea,
+    pub blocks: Vec<LiveOcrBlock>,
+}
+
+/// A window of the captured frame, private windows are already left out of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct LiveWindow {
+    pub app_name: String,
+    pub window_name: String,
+    pub focused: bool,
+    /// Top left corner in the frame
+    pub position: (i32, i32),
+}
+
+struct LatestFrame {
+    frame_number: u64,
+    timestamp: DateTime<Utc>,
+    size: (u32, u32),
+    windows: Vec<(LiveWindow, Arc<DynamicImage>)>,
+}
+
+/// The words of a window with their place on the screen, numbering its lines from
+/// `first_line`
+pub fn window_blocks(
+    window: &LiveWindow,
+    words: &[OcrWord],
+    monitor: &MonitorArea,
+    first_line: usize,
+) -> Vec<LiveOcrBlock> {
+    let mut line = first_line;
+    let mut previous_line = None;
+    words
+        .iter()
+        .map(|word| {
+            if previous_line.is_some_and(|previous| previous != word.line) {
+                line += 1;
+            }
+            previous_line = Some(word.line);
+            let frame_rect = Rect {
+                x: (window.position.0 + word.left) as f64,
+                y: (window.position.1 + word.top) as f64,
+                width: word.width as f64,
+                height: word.height as f64,
+            };
+            LiveOcrBlock {
+                text: word.text.clone(),
+                confidence: word.confidence,
+                frame_rect,
+                screen_rect: monitor.to_screen(frame_rect),
+                line,
+                app_name: window.app_name.clone(),
+                window_name: window.window_name.clone(),
+                focused: window.focused,
+            }
+        })
+        .collect()
+}
+
+/// The words on the screen as they're captured, with their boxes. The stored OCR text has
+/// no boxes, so the windows of the latest frame are read again when someone asks, once per frame
+pub struct LiveOcr {
+    monitor_id: u32,
+    latest_frame: watch::Sender<Option<Arc<LatestFrame>>>,
+    screen: Mutex<Option<Arc<LiveOcrScreen>>>,
+}
+
+impl LiveOcr {
+    pub fn new(monitor_id: u32) -> Self {
+        let (latest_frame, _) = watch::channel(None);
+        LiveOcr {
+            monitor_id,
+            latest_frame,
+            screen: Mutex::new(None),
+        }
+    }
+
+    /// Called for every captured frame, the images are shared with the recording
+    pub fn publish_frame(&self, frame: &CaptureResult) {
+        let windows = frame
+            .window_ocr_results
+            .iter()
+            .map(|window| {
+                (
+                    LiveWindow {
+                        app_name: window.app_name.clone(),
+                        window_name: window.window_name.clone(),
+                        focused: window.focused,
+                        position: window.position,
+                    },
+                    Arc::clone(&window.image),
+                )
+            })
+            .collect();
+        self.latest_frame.send_replace(Some(Arc::new(LatestFrame {
+            frame_number: frame.frame_number,
+            timestamp: Utc::now(),
+            size: (frame.image.width(), frame.image.height()),
+            windows,
+        })));
+    }
+
+    async fn read(&self, frame: &LatestFrame) -> Result<Arc<LiveOcrScreen>> {
+        // a read already done or going on for this frame is shared
+        let mut screen = self.screen.lock().await;
+        if let Some(screen) = screen.as_ref().filter(|screen| {
+            screen.frame_number == frame.frame_number && screen.timestamp == frame.timestamp
+        }) {
+            return Ok(screen.clone());
+        }
+        let monitor = monitor_area(self.monitor_id, frame.size).await;
+        let windows = frame.windows.clone();
+        let blocks = tokio::task::spawn_blocking(move || -> Result<Vec<LiveOcrBlock>> {
+            let mut blocks: Vec<LiveOcrBlock> = Vec::new();
+            for (window, image) in &windows {
+                let words = perform_ocr_tesseract_words(image)?;
+                let first_line = blocks.last().map_or(0, |block| block.line + 1);
+                blocks.extend(window_blocks(window, &words, &monitor, first_line));
+            }
+            Ok(blocks)
+        })
+        .await??;
+        let read = Arc::new(LiveOcrScreen {
+            frame_number: frame.frame_number,
+            timestamp: frame.timestamp,
+            monitor,
+            blocks,
+        });
+        *screen = Some(read.clone());
+        Ok(read)
+    }
+
+    /// The words of the latest frame, `None` until a frame is captured
+    pub async fn current(&self) -> Result<Option<Arc<LiveOcrScreen>>> {
+        let frame = self.latest_frame.borrow().clone();
+        match frame {
+            Some(frame) => Ok(Some(self.read(&frame).await?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The words of each new frame, frames captured while the previous one is read are skipped
+    pub fn stream(self: &Arc<Self>) -> impl Stream<Item = Result<Arc<LiveOcrScreen>>> {
+        let mut receiver = self.latest_frame.subscribe();
+        receiver.mark_changed();
+        futures::stream::unfold(
+            (self.clone(), receiver),
+            |(live_ocr, mut receiver)| async move {
+                loop {
+                    receiver.changed().await.ok()?;
+                    let frame = receiver.borrow_and_update().clone();
+                    if let Some(frame) = frame {
+                        let screen = live_ocr.read(&frame).await;
+                        return Some((screen, (live_ocr, receiver)));
+                    }
+                }
+            },
+        )
+    }
+}
+
+/// The words as they're read, a line of text for each of their lines
+pub fn blocks_text(blocks: &[LiveOcrBlock]) -> String {
+    let mut text = String::new();
+    let mut previous_line = None;
+    for block in blocks {
+        match previous_line {
+            Some(line) if line == block.line => text.push(' '),
+            Some(_) => text.push('\n'),
+            None => {}
+        }
+        previous_line = Some(block.line);
+        text.push_str(&block.text);
+    }
+    text
+}
+
+/// Whether the line ends a sentence or a title, the next isn't the rest of it
+fn ends_paragraph(line: &str) -> bool {
+    line.ends_with(['.', '!', '?', ':', ';']) || line.chars().count() < 20
+}
+
+/// Text read off the screen made ready to paste: ligatures and runs of spaces undone, lines the
+/// OCR read out of stray marks dropped, and the lines a paragraph was wrapped on joined back,
+/// the words split across them included
+pub fn clean_copied_text(text: &str) -> String {
+    let lines = text.lines().map(|line| {
+        line.replace('ﬁ', "fi")
+            .replace('ﬂ', "fl")
+            .replace('ﬀ', "ff")
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    });
+    let mut cleaned: Vec<String> = Vec::new();
+    for line in lines {
+        let noise = !line.is_empty()
+            && line.chars().count() <= 2
+            && !line.chars().any(char::is_alphanumeric);
+        if noise {
+            continue;
+        }
+        let continues = line.starts_with(|c: char| c.is_lowercase());
+        match cleaned.last_mut() {
+            Some(previous) if line.is_empty() && previous.is_empty() => {}
+            Some(previous) if continues && previous.ends_with('-') => {
+                let before_hyphen = previous[..previous.len() - 1].chars().last();
+                if before_hyphen.is_some_and(char::is_alphabetic) {
+                    previous.pop();
+                }
+                previous.push_str(&line);
+            }
+            Some(previous) if continues && !previous.is_empty() && !ends_paragraph(previous) => {
+                previous.push(' ');
+                previous.push_str(&line);
+            }
+            _ => cleaned.push(line),
+        }
+    }
+    cleaned.join("\n").trim().to_string()
+}
@@ -0,0 +1,341 @@
+use crate::credentials::tokens_match;
+use crate::user_isolation::USER_TOKEN_PARAM;
+use anyhow::Result;
+use image::DynamicImage;
+use log::{debug, error, info, warn};
+use screenpipe_core::{
+    Container, EncoderQuality, FfmpegCommand, FfmpegInput, FfmpegOutput, FfmpegProcess, VideoCodec,
+    VideoEncoder,
+};
+use screenpipe_vision::{CaptureResult, EncodedFrame};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{watch, Mutex};
+use tokio::task::JoinHandle;
+
+pub const LIVE_PLAYLIST: &str = "index.m3u8";
+/// Codec setup of the fragmented MP4 segments, which the player appends first
+const LIVE_INIT_SEGMENT: &str = "init.mp4";
+/// The latest frame is repeated at this rate, the capture fps can be well below one
+const LIVE_FPS: u32 = 2;
+/// ffmpeg has no LL-HLS partial segments, short segments keep the latency at a few seconds
+const SEGMENT_SECONDS: u32 = 1;
+const PLAYLIST_SEGMENTS: u32 = 4;
+/// The encoder stops when nobody asked for the playlist for this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+const PLAYLIST_WAIT: Duration = Duration::from_secs(10);
+
+/// On-demand HLS stream of the screen, the encoder only runs while someone is watching
+pub struct LiveView {
+    dir: PathBuf,
+    token: String,
+    encoder: VideoEncoder,
+    quality: EncoderQuality,
+    latest_frame: watch::Sender<Option<(Arc<DynamicImage>, EncodedFrame)>>,
+    last_viewed: std::sync::Mutex<Instant>,
+    stream: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl LiveView {
+    pub fn new(
+        dir: PathBuf,
+        token: String,
+        encoder: VideoEncoder,
+        quality: EncoderQuality,
+    ) -> Self {
+        let (latest_frame, _) = watch::channel(None);
+        LiveView {
+            dir,
+            token,
+            encoder,
+            quality,
+            latest_frame,
+            last_viewed: std::sync::Mutex::new(Instant::now()),
+            stream: Mutex::new(None),
+        }
+    }
+
+    /// Called for every captured frame, cheap when nobody is watching. The frame's PNG is
+    /// shared with the recording, so a frame repeated or already recorded isn't encoded again
+    pub fn publish_frame(&self, frame: &CaptureResult) {
+        self.latest_frame
+            .send_replace(Some((Arc::clone(&frame.image), frame.png.clone())));
+    }
+
+    pub fn is_authorized(&self, token: Option<&str>) -> bool {
+        token.is_some_and(|token| tokens_match(token, &self.token))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Starts the encoder if needed and waits for the first playlist, its segments carry the
+    /// user's token of a --multi-user server along with the live view one
+    pub async fn playlist(self: &Arc<Self>, user_token: Option<&str>) -> Result<String> {
+        *self.last_viewed.lock().unwrap() = Instant::now();
+
+        {
+            let mut stream = self.stream.lock().await;
+            if stream.as_ref().is_none_or(|task| task.is_finished()) {
+                info!("Starting live view stream");
+                let _ = tokio::fs::remove_dir_all(&self.dir).await;
+                tokio::fs::create_dir_all(&self.dir).await?;
+                *stream = Some(tokio::spawn(Arc::clone(self).run()));
+            }
+        }
+
+        let path = self.dir.join(LIVE_PLAYLIST);
+        let started = Instant::now();
+        loop {
+            if let Ok(playlist) = tokio::fs::read_to_string(&path).await {
+                return Ok(with_token(&playlist, &self.token, user_token));
+            }
+            if started.elapsed() > PLAYLIST_WAIT {
+                anyhow::bail!("live stream did not start, check the ffmpeg logs");
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    fn start_encoder(&self) -> Result<FfmpegProcess> {
+        // fragmented MP4 segments can be appended to a MediaSource as they are, browsers
+        // without native HLS need no transmuxer
+        let segments = self.dir.join("segment_%05d.m4s");
+        let command = FfmpegCommand::new().input(
+            FfmpegInput::pipe()
+                .format(Container::Image2Pipe)
+                .video_codec(VideoCodec::Png)
+                .frame_rate(LIVE_FPS as f64),
+        );
+        let output = FfmpegOutput::file(&self.dir.join(LIVE_PLAYLIST).to_string_lossy())
+            .format(Container::Other("hls".to_string()))
+            .arg("-g", &(LIVE_FPS * SEGMENT_SECONDS).to_string())
+            .arg("-hls_time", &SEGMENT_SECONDS.to_string())
+            .arg("-hls_list_size", &PLAYLIST_SEGMENTS.to_string())
+            .arg("-hls_segment_type", "fmp4")
+            .arg("-hls_fmp4_init_filename", LIVE_INIT_SEGMENT)
+            .arg(
+                "-hls_flags",
+                "delete_segments+independent_segments+omit_endlist+temp_file",
+            )
+            .arg("-hls_segment_filename", &segments.to_string_lossy());
+        self.encoder
+            .encode_to(command, output, self.quality)
+            .spawn("live")
+    }
+
+    async fn run(self: Arc<Self>) {
+        let mut process = match self.start_encoder() {
+            Ok(process) => process,
+            Err(e) => {
+                error!("Failed to start live view encoder: {}", e);
+                return;
+            }
+        };
+        let mut frames = self.latest_frame.subscribe();
+        let mut interval = tokio::time::interval(Duration::from_secs_f64(1.0 / LIVE_FPS as f64));
+
+        loop {
+            interval.tick().await;
+            if self.last_viewed.lock().unwrap().elapsed() > IDLE_TIMEOUT {
+                info!("Nobody is watching, stopping live view stream");
+                break;
+            }
+            if process.has_exited() {
+                warn!(
+                    "Live view encoder died: {}",
+                    process.last_error().unwrap_or_default()
+                );
+                break;
+            }
+
+            let Some((image, png)) = frames.borrow_and_update().clone() else {
+                continue;
+            };
+            let buffer = match tokio::task::spawn_blocking(move || png.png(&image)).await {
+                Ok(Ok(buffer)) => buffer,
+                Ok(Err(e)) => {
+                    error!("Failed to encode live frame: {}", e);
+                    continue;
+                }
+                Err(e) => {
+                    error!("Failed to encode live frame: {}", e);
+                    continue;
+                }
+            };
+            if let Err(e) = process.write(&buffer).await {
+                warn!("Failed to write live frame: {}", e);
+                break;
+            }
+        }
+
+        if let Err(e) = process.finish().await {
+            debug!("Live view encoder exited: {}", e);
+        }
+        let _ = tokio::fs::remove_dir_all(&self.dir).await;
+    }
+}
+
+/// Only the segment names ffmpeg writes, so the route can't read other files
+pub fn is_live_segment(name: &str) -> bool {
+    name == LIVE_INIT_SEGMENT
+        || name.strip_suffix(".m4s").is_some_and(|stem| {
+            !stem.is_empty() && stem.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        })
+}
+
+/// Players can't send headers with segment requests, so the tokens go in their urls
+pub fn with_token(playlist: &str, token: &str, user_token: Option<&str>) -> String {
+    let encode = |value: &str| -> String {
+        value
+            .bytes()
+            .map(|b| {
+                if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+                    (b as char).to_string()
+                } else {
+                    format!("%{:02X}", b)
+                }
+            })
+            .collect()
+    };
+    let mut query = format!("token={}", encode(token));
+    if let Some(user_token) = user_token {
+        query.push_str(&format!("&{}={}", USER_TOKEN_PARAM, encode(user_token)));
+    }
+    playlist
+        .lines()
+        .map(|line| {
+            // the init segment is the URI of a tag
+            let init_segment = line
+                .strip_prefix("#EXT-X-MAP:URI=\"")
+                .and_then(|rest| rest.split_once('"'));
+            if let Some((uri, attributes)) = init_segment {
+                format!("#EXT-X-MAP:URI=\"{}?{}\"{}", uri, query, attributes)
+            } else if line.is_empty() || line.starts_with('#') {
+                line.to_string()
+            } else {
+                format!("{}?{}", line, query)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+pub const LIVE_PAGE: &str = r#"<!doctype html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>screenpipe live</title>
+<style>body{margin:0;background:#000}video{width:100vw;height:100vh}</style>
+</head>
+<body>
+<video id="live" autoplay muted playsinline controls></video>
+<script src="/live/player.js"></script>
+</body>
+</html>
+"#;
+
+/// Plays the stream of the page, natively where the browser has HLS and otherwise by
+/// appending the fragmented MP4 segments to a MediaSource. Served by the server, the page
+/// loads nothing from other origins
+pub const LIVE_PLAYER: &str = r##"(function () {
+  const video = document.getElementById("live");
+  const query = window.location.search;
+  const playlistUrl = "/live/index.m3u8" + query;
+  if (video.canPlayType("application/vnd.apple.mpegurl")) {
+    video.src = playlistUrl;
+    return;
+  }
+  // shown in place of the video, the stream can't be played here
+  const fail = (message) => {
+    const notice = document.createElement("p");
+    notice.style.color = "#fff";
+    notice.style.font = "16px sans-serif";
+    notice.style.padding = "1em";
+    notice.textContent = "Live view can't play here: " + message;
+    video.replaceWith(notice);
+  };
+  if (!window.MediaSource) {
+    fail("this browser has neither HLS nor MediaSource");
+    return;
+  }
+
+  const fetchBytes = async (uri) => {
+    const response = await fetch("/live/" + uri);
+    if (!response.ok) {
+      throw new Error(uri + ": " + response.status);
+    }
+    return new Uint8Array(await response.arrayBuffer());
+  };
+
+  // the codec of the stream, from the avcC box of the init segment, null without one
+  const mimeType = (init) => {
+    for (let i = 4; i + 8 < init.length; i++) {
+      if (init[i] === 0x61 && init[i + 1] === 0x76 && init[i + 2] === 0x63 && init[i + 3] === 0x43) {
+        const profile = Array.from(init.subarray(i + 5, i + 8))
+          .map((byte) => byte.toString(16).padStart(2, "0"))
+          .join("");
+        return 'video/mp4; codecs="avc1.' + profile + '"';
+      }
+    }
+    return null;
+  };
+
+  const appendTo = (buffer, data) =>
+    new Promise((resolve, reject) => {
+      buffer.addEventListener("updateend", resolve, { once: true });
+      buffer.addEventListener("error", reject, { once: true });
+      buffer.appendBuffer(data);
+    });
+
+  const mediaSource = new MediaSource();
+  video.src = URL.createObjectURL(mediaSource);
+  mediaSource.addEventListener("sourceopen", async () => {
+    let buffer = null;
+    const appended = new Set();
+    for (;;) {
+      try {
+        const playlist = (await (await fetch(playlistUrl)).text()).split("\n");
+        if (!buffer) {
+          const map = playlist.find((line) => line.startsWith("#EXT-X-MAP:"));
+          const init = await fetchBytes(map.match(/URI="([^"]+)"/)[1]);
+          const type = mimeType(init);
+          if (!type) {
+            fail("the stream isn't H.264, open it in a browser with native HLS");
+            return;
+          }
+          buffer = mediaSource.addSourceBuffer(type);
+          await appendTo(buffer, init);
+        }
+        for (const uri of playlist.filter((line) => line && !line.startsWith("#"))) {
+          const name = uri.split("?")[0];
+          if (appended.has(name)) {
+            continue;
+          }
+          appended.add(name);
+          await appendTo(buffer, await fetchBytes(uri));
+        }
+        // jump to half a second behind the live edge once more than 3 seconds behind, and
+        // keep only the last 30 seconds buffered
+        if (buffer.buffered.length) {
+          const end = buffer.buffered.end(buffer.buffered.length - 1);
+          if (end - video.currentTime > 3) {
+            video.currentTime = end - 0.5;
+          }
+          if (buffer.buffered.start(0) < end - 30) {
+            await new Promise((resolve) => {
+              buffer.addEventListener("updateend", resolve, { once: true });
+              buffer.remove(0, end - 30);
+            });
+          }
+        }
+      } catch (e) {
+        console.warn("live view:", e);
+      }
+      await new Promise((resolve) => setTimeout(resolve, 1000));
+    }
+  });
+})();
+"##;
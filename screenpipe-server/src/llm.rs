@@ -0,0 +1,531 @@
+use crate::credentials::Caller;
+use crate::DatabaseManager;
+use anyhow::{bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, TimeZone, Utc};
+use log::{debug, info, warn};
+use screenpipe_core::{
+    chat_completion, chat_completion_with_tools, create_embeddings, ChatMessage, LlmConfig,
+    TokenUsage, ToolCall, ToolDefinition,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Rounds of tool calls in a chat, the model has to answer without tools after them
+pub const MAX_TOOL_ROUNDS: usize = 8;
+
+/// USD per million (prompt, completion) tokens, the first matching prefix wins
+const MODEL_PRICES: &[(&str, f64, f64)] = &[
+    ("gpt-4o-mini", 0.15, 0.60),
+    ("gpt-4o", 2.50, 10.00),
+    ("gpt-4-turbo", 10.00, 30.00),
+    ("gpt-4", 30.00, 60.00),
+    ("gpt-3.5-turbo", 0.50, 1.50),
+    ("claude-3-5-sonnet", 3.00, 15.00),
+    ("claude-3-opus", 15.00, 75.00),
+    ("claude-3-haiku", 0.25, 1.25),
+    ("text-embedding-3-small", 0.02, 0.0),
+    ("text-embedding-3-large", 0.13, 0.0),
+];
+
+fn is_local(config: &LlmConfig) -> bool {
+    ["localhost", "127.0.0.1", "0.0.0.0"]
+        .iter()
+        .any(|host| config.api_url.contains(host))
+}
+
+/// Estimated cost in USD, local models and unknown models are free
+pub fn estimate_cost(config: &LlmConfig, usage: &TokenUsage) -> f64 {
+    if is_local(config) {
+        return 0.0;
+    }
+    match MODEL_PRICES
+        .iter()
+        .find(|(prefix, _, _)| config.model.starts_with(prefix))
+    {
+        Some((_, prompt_price, completion_price)) => {
+            (usage.prompt_tokens as f64 * prompt_price
+                + usage.completion_tokens as f64 * completion_price)
+                / 1_000_000.0
+        }
+        None => {
+            debug!(
+                "No known price for model {}, counting it as free",
+                config.model
+            );
+            0.0
+        }
+    }
+}
+
+/// Budgets are reset on the first day of each month (UTC)
+pub fn current_month_start() -> DateTime<Utc> {
+    let now = Utc::now();
+    Utc.with_ymd_and_hms(now.year(), now.month(), 1, 0, 0, 0)
+        .unwrap()
+}
+
+#[derive(Debug, Clone)]
+pub struct LlmAnswer {
+    pub content: String,
+    /// The model that actually answered, can be the local fallback when over budget
+    pub model: String,
+}
+
+/// Something the model can do during a chat, described to it by a JSON schema
+#[async_trait]
+pub trait LlmTool: Send + Sync {
+    fn definition(&self) -> ToolDefinition;
+
+    /// `pipe` is the pipe chatting, None for the user's own chats. The result is sent to the
+    /// model as JSON
+    async fn call(&self, arguments: &Value, pipe: Option<&str>) -> Result<Value>;
+}
+
+/// The tools a pipe may call, by name
+#[derive(Debug, Clone, Deserialize)]
+pub struct ToolGrant {
+    /// As the pipe sends it in the `x-screenpipe-pipe` header, with its token of
+    /// pipe_tokens.json in `x-screenpipe-pipe-token`
+    pub pipe: String,
+    pub tools: HashSet<String>,
+}
+
+#[derive(Deserialize)]
+struct ToolGrantsFile {
+    grants: Vec<ToolGrant>,
+}
+
+/// Reads the grants of a `{"grants": [...]}` file, one per pipe
+pub fn load_tool_grants(path: &Path) -> Result<Vec<ToolGrant>> {
+    let file: ToolGrantsFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    let mut pipes = HashSet::new();
+    for grant in &file.grants {
+        if !pipes.insert(grant.pipe.as_str()) {
+            bail!("two tool grants are for {}", grant.pipe);
+        }
+    }
+    Ok(file.grants)
+}
+
+/// The tools chats can call. The app's chats, with the token of app_token or from the API
+/// socket, get the enabled ones, a pipe only the enabled ones its grant lists, none without a
+/// grant. Other callers get none
+#[derive(Default)]
+pub struct ToolRegistry {
+    tools: BTreeMap<String, Arc<dyn LlmTool>>,
+    /// All of them when None
+    enabled: Option<HashSet<String>>,
+    grants: HashMap<String, HashSet<String>>,
+}
+
+impl ToolRegistry {
+    pub fn register(&mut self, tool: Arc<dyn LlmTool>) {
+        self.tools.insert(tool.definition().name, tool);
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.tools.keys().map(String::as_str).collect()
+    }
+
+    fn check_names<'a>(&self, names: impl IntoIterator<Item = &'a String>) -> Result<()> {
+        for name in names {
+            if !self.tools.contains_key(name) {
+                bail!(
+                    "unknown tool {}, the tools are {}",
+                    name,
+                    self.names().join(", ")
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Only these tools can be called, by anyone
+    pub fn enable(&mut self, names: &[String]) -> Result<()> {
+        self.check_names(names)?;
+        self.enabled = Some(names.iter().cloned().collect());
+        Ok(())
+    }
+
+    pub fn grant(&mut self, grants: Vec<ToolGrant>) -> Result<()> {
+        for grant in grants {
+            self.check_names(&grant.tools)?;
+            self.grants.insert(grant.pipe, grant.tools);
+        }
+        Ok(())
+    }
+
+    /// The tools a chat of the caller can call
+    pub fn allowed(&self, caller: &Caller) -> Vec<Arc<dyn LlmTool>> {
+        let granted = match caller {
+            Caller::App => None,
+            Caller::Pipe(pipe) => Some(self.grants.get(pipe)),
+            Caller::Remote | Caller::Unknown => return Vec::new(),
+        };
+        self.tools
+            .iter()
+            .filter(|(name, _)| self.enabled.as_ref().is_none_or(|e| e.contains(*name)))
+            .filter(|(name, _)| match granted {
+                None => true,
+                Some(grant) => grant.is_some_and(|tools| tools.contains(*name)),
+            })
+            .map(|(_, tool)| Arc::clone(tool))
+            .collect()
+    }
+}
+
+/// A tool called during a chat and what came out of it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolRun {
+    pub id: String,
+    pub name: String,
+    pub arguments: Value,
+    pub result: Option<Value>,
+    /// Sent to the model, which can try again
+    pub error: Option<String>,
+}
+
+impl ToolRun {
+    fn message(&self) -> ChatMessage {
+        let content = match (&self.result, &self.error) {
+            (_, Some(error)) => json!({ "error": error }),
+            (Some(result), None) => result.clone(),
+            (None, None) => Value::Null,
+        };
+        ChatMessage::tool_result(&self.id, content.to_string())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ToolChat {
+    pub answer: LlmAnswer,
+    /// In the order the model called them
+    pub tool_runs: Vec<ToolRun>,
+}
+
+async fn run_tool(tools: &[Arc<dyn LlmTool>], call: &ToolCall, pipe: Option<&str>) -> ToolRun {
+    let tool = tools
+        .iter()
+        .find(|tool| tool.definition().name == call.name);
+    let result = match tool {
+        Some(tool) => tool.call(&call.arguments, pipe).await,
+        None => Err(anyhow::anyhow!("no tool {} can be called here", call.name)),
+    };
+    if let Err(e) = &result {
+        debug!("Tool {} failed: {}", call.name, e);
+    }
+    let (result, error) = match result {
+        Ok(result) => (Some(result), None),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    ToolRun {
+        id: call.id.clone(),
+        name: call.name.clone(),
+        arguments: call.arguments.clone(),
+        result,
+        error,
+    }
+}
+
+/// Entry point for every LLM call of screenpipe, records token usage and enforces the monthly budget
+pub struct LlmService {
+    db: Arc<DatabaseManager>,
+    config: LlmConfig,
+    local_fallback: Option<LlmConfig>,
+    monthly_budget_usd: Option<f64>,
+    /// How long answers are reused for the same model and prompt, no cache if None
+    cache_ttl: Option<Duration>,
+    /// What `embed` sends to the endpoint of `config`, and to the one of `local_fallback`.
+    /// Chat models don't embed
+    embedding_model: Option<String>,
+    fallback_embedding_model: Option<String>,
+}
+
+/// Cache key of a call, the same prompt sent to another model or another endpoint is another
+/// entry
+pub fn cache_key(api_url: &str, model: &str, kind: &str, prompt: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(api_url.as_bytes());
+    hasher.update([0]);
+    hasher.update(model.as_bytes());
+    hasher.update([0]);
+    hasher.update(kind.as_bytes());
+    hasher.update([0]);
+    hasher.update(prompt.as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+impl LlmService {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        config: LlmConfig,
+        local_fallback: Option<LlmConfig>,
+        monthly_budget_usd: Option<f64>,
+        cache_ttl: Option<Duration>,
+    ) -> Self {
+        LlmService {
+            db,
+            config,
+            local_fallback,
+            monthly_budget_usd,
+            cache_ttl,
+            embedding_model: None,
+            fallback_embedding_model: None,
+        }
+    }
+
+    pub fn with_embedding_models(
+        mut self,
+        embedding_model: Option<String>,
+        fallback_embedding_model: Option<String>,
+    ) -> Self {
+        self.embedding_model = embedding_model;
+        self.fallback_embedding_model = fallback_embedding_model;
+        self
+    }
+
+    pub fn model(&self) -> &str {
+        &self.config.model
+    }
+
+    async fn active_config(&self) -> Result<&LlmConfig> {
+        let Some(budget) = self.monthly_budget_usd else {
+            return Ok(&self.config);
+        };
+        if is_local(&self.config) {
+            return Ok(&self.config);
+        }
+
+        let spent = self.db.get_llm_cost_since(current_month_start()).await?;
+        if spent < budget {
+            return Ok(&self.config);
+        }
+
+        match &self.local_fallback {
+            Some(fallback) => {
+                warn!(
+                    "Monthly LLM budget of ${:.2} reached (${:.2} spent), using local model {}",
+                    budget, spent, fallback.model
+                );
+                Ok(fallback)
+            }
+            None => anyhow::bail!(
+                "monthly LLM budget of ${:.2} reached (${:.2} spent) and no local fallback model configured",
+                budget,
+                spent
+            ),
+        }
+    }
+
+    /// The active endpoint with its embedding model
+    async fn embedding_config(&self) -> Result<LlmConfig> {
+        let config = self.active_config().await?;
+        let (model, flag) = if std::ptr::eq(config, &self.config) {
+            (&self.embedding_model, "--llm-embedding-model")
+        } else {
+            (
+                &self.fallback_embedding_model,
+                "--llm-fallback-embedding-model",
+            )
+        };
+        let model = model.clone().ok_or_else(|| {
+            anyhow::anyhow!("no embedding model for {}, set {}", config.api_url, flag)
+        })?;
+        Ok(LlmConfig {
+            model,
+            ..config.clone()
+        })
+    }
+
+    async fn record_usage(&self, source: &str, config: &LlmConfig, kind: &str, usage: TokenUsage) {
+        let cost = estimate_cost(config, &usage);
+        if let Err(e) = self
+            .db
+            .insert_llm_usage(source, &config.model, kind, &usage, cost)
+            .await
+        {
+            warn!("Failed to record LLM usage: {}", e);
+        }
+    }
+
+    async fn cached(&self, key: &str) -> Option<String> {
+        self.cache_ttl?;
+        match self.db.get_llm_cache(key).await {
+            Ok(response) => response,
+            Err(e) => {
+                warn!("Failed to read LLM cache: {}", e);
+                None
+            }
+        }
+    }
+
+    async fn cache(&self, key: &str, config: &LlmConfig, kind: &str, response: &str) {
+        let Some(ttl) = self.cache_ttl else {
+            return;
+        };
+        let expires_at = Utc::now() + chrono::Duration::from_std(ttl).unwrap_or_default();
+        if let Err(e) = self
+            .db
+            .insert_llm_cache(
+                key,
+                &config.api_url,
+                &config.model,
+                kind,
+                response,
+                expires_at,
+            )
+            .await
+        {
+            warn!("Failed to write LLM cache: {}", e);
+        }
+    }
+
+    async fn cached_chat(
+        &self,
+        source: &str,
+        config: &LlmConfig,
+        prompt: &str,
+    ) -> Option<LlmAnswer> {
+        let content = self
+            .cached(&cache_key(&config.api_url, &config.model, "chat", prompt))
+            .await?;
+        debug!("LLM cache hit for {} with {}", source, config.model);
+        Some(LlmAnswer {
+            content,
+            model: config.model.clone(),
+        })
+    }
+
+    /// `source` identifies who is calling (a built-in task or a pipe name) for the usage report
+    pub async fn chat(&self, source: &str, messages: &[ChatMessage]) -> Result<LlmAnswer> {
+        let prompt = serde_json::to_string(messages)?;
+        // a cached answer costs nothing, it's served even once the budget is spent
+        if let Some(answer) = self.cached_chat(source, &self.config, &prompt).await {
+            return Ok(answer);
+        }
+        let config = self.active_config().await?;
+        if !std::ptr::eq(config, &self.config) {
+            if let Some(answer) = self.cached_chat(source, config, &prompt).await {
+                return Ok(answer);
+            }
+        }
+
+        let key = cache_key(&config.api_url, &config.model, "chat", &prompt);
+        let completion = chat_completion(config, messages).await?;
+        self.record_usage(source, config, "chat", completion.usage)
+            .await;
+        self.cache(&key, config, "chat", &completion.content).await;
+
+        Ok(LlmAnswer {
+            content: completion.content,
+            model: config.model.clone(),
+        })
+    }
+
+    /// Lets the model call `tools` until it answers, `pipe` is the pipe chatting. The answers
+    /// aren't cached, the tools see the history as it is now
+    pub async fn chat_with_tools(
+        &self,
+        source: &str,
+        messages: &[ChatMessage],
+        tools: &[Arc<dyn LlmTool>],
+        pipe: Option<&str>,
+    ) -> Result<ToolChat> {
+        let config = self.active_config().await?;
+        let definitions: Vec<ToolDefinition> = tools.iter().map(|tool| tool.definition()).collect();
+        let mut messages = messages.to_vec();
+        let mut tool_runs = Vec::new();
+        for round in 0..=MAX_TOOL_ROUNDS {
+            let offered = if round < MAX_TOOL_ROUNDS {
+                &definitions[..]
+            } else {
+                &[]
+            };
+            let completion = chat_completion_with_tools(config, &messages, offered).await?;
+            self.record_usage(source, config, "chat", completion.usage)
+                .await;
+            if completion.tool_calls.is_empty() {
+                return Ok(ToolChat {
+                    answer: LlmAnswer {
+                        content: completion.content,
+                        model: config.model.clone(),
+                    },
+                    tool_runs,
+                });
+            }
+            info!(
+                "{} called {}",
+                source,
+                completion
+                    .tool_calls
+                    .iter()
+                    .map(|call| call.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+            messages.push(ChatMessage::assistant_with_tool_calls(
+                completion.content,
+                completion.tool_calls.clone(),
+            ));
+            for call in &completion.tool_calls {
+                let run = run_tool(tools, call, pipe).await;
+                messages.push(run.message());
+                tool_runs.push(run);
+            }
+        }
+        bail!(
+            "{} kept calling tools after {} rounds",
+            config.model,
+            MAX_TOOL_ROUNDS
+        )
+    }
+
+    /// Embeddings are cached per input so only the new inputs are sent
+    pub async fn embed(&self, source: &str, inputs: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings: Vec<Option<Vec<f32>>> = Vec::with_capacity(inputs.len());
+        let mut missing = Vec::new();
+        for input in inputs {
+            let cached = match &self.embedding_model {
+                Some(model) => {
+                    let key = cache_key(&self.config.api_url, model, "embedding", input);
+                    self.cached(&key)
+                        .await
+                        .and_then(|e| serde_json::from_str(&e).ok())
+                }
+                None => None,
+            };
+            if cached.is_none() {
+                missing.push(input.clone());
+            }
+            embeddings.push(cached);
+        }
+
+        if !missing.is_empty() {
+            let config = self.embedding_config().await?;
+            let (computed, usage) = create_embeddings(&config, &missing).await?;
+            self.record_usage(source, &config, "embedding", usage).await;
+
+            let mut computed = missing.iter().zip(computed);
+            for embedding in embeddings.iter_mut().filter(|e| e.is_none()) {
+                let Some((input, value)) = computed.next() else {
+                    break;
+                };
+                let key = cache_key(&config.api_url, &config.model, "embedding", input);
+                self.cache(&key, &config, "embedding", &serde_json::to_string(&value)?)
+                    .await;
+                *embedding = Some(value);
+            }
+        }
+
+        Ok(embeddings
+            .into_iter()
+            .map(|e| e.unwrap_or_default())
+            .collect())
+    }
+}
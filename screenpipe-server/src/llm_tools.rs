@@ -0,0 +1,291 @@
+//! The tools chats start with: searching the history, reading a frame, tagging it and
+//! notifying the user. A tag is a marker on the frame, it shows on /markers.
+use crate::focus_mode::do_not_disturb;
+use crate::llm::{LlmTool, ToolRegistry};
+use crate::{ContentType, DatabaseManager, SearchResult};
+use anyhow::{anyhow, bail, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use log::info;
+use screenpipe_core::{emit_event, ToolDefinition};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::sync::Arc;
+
+pub const SEARCH_HISTORY_TOOL: &str = "search_history";
+pub const GET_FRAME_TOOL: &str = "get_frame";
+pub const CREATE_TAG_TOOL: &str = "create_tag";
+pub const SEND_NOTIFICATION_TOOL: &str = "send_notification";
+/// Results of a search given to the model, it can search again with other words
+const MAX_SEARCH_RESULTS: u32 = 20;
+const DEFAULT_SEARCH_RESULTS: u32 = 10;
+/// Of each text given to the model, the screen text of a frame can be pages long
+const MAX_TEXT_CHARS: usize = 2000;
+const MAX_TAG_LEN: usize = 100;
+
+fn arguments<T: DeserializeOwned>(tool: &str, arguments: &Value) -> Result<T> {
+    serde_json::from_value(arguments.clone())
+        .map_err(|e| anyhow!("invalid arguments for {}: {}", tool, e))
+}
+
+fn truncated(text: &str) -> String {
+    match text.char_indices().nth(MAX_TEXT_CHARS) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text.to_string(),
+    }
+}
+
+pub struct SearchHistoryTool {
+    db: Arc<DatabaseManager>,
+}
+
+#[derive(Deserialize)]
+struct SearchHistoryArguments {
+    query: String,
+    #[serde(default)]
+    content_type: ContentType,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    limit: Option<u32>,
+}
+
+#[async_trait]
+impl LlmTool for SearchHistoryTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: SEARCH_HISTORY_TOOL.to_string(),
+            description: "Searches the text that was on the user's screen and what was said \
+                around them, most recent first. Short keyword queries work best."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "query": {"type": "string", "description": "Words to look for"},
+                    "content_type": {"type": "string", "enum": ["all", "ocr", "audio"]},
+                    "start_time": {"type": "string", "format": "date-time"},
+                    "end_time": {"type": "string", "format": "date-time"},
+                    "app_name": {"type": "string", "description": "Only the screen text of this app"},
+                    "limit": {"type": "integer", "minimum": 1, "maximum": MAX_SEARCH_RESULTS},
+                },
+                "required": ["query"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &Value, _pipe: Option<&str>) -> Result<Value> {
+        let arguments: SearchHistoryArguments = self::arguments(SEARCH_HISTORY_TOOL, arguments)?;
+        let limit = arguments
+            .limit
+            .unwrap_or(DEFAULT_SEARCH_RESULTS)
+            .clamp(1, MAX_SEARCH_RESULTS);
+        let results = self
+            .db
+            .search(
+                &arguments.query,
+                arguments.content_type,
+                limit,
+                0,
+                arguments.start_time,
+                arguments.end_time,
+                arguments.app_name.as_deref(),
+                None,
+            )
+            .await?;
+        let results: Vec<Value> = results
+            .into_iter()
+            .map(|result| match result {
+                SearchResult::OCR(ocr) => json!({
+                    "type": "ocr",
+                    "frame_id": ocr.frame_id,
+                    "timestamp": ocr.timestamp,
+                    "app_name": ocr.app_name,
+                    "window_name": ocr.window_name,
+                    "browser_url": ocr.browser_url,
+                    "text": truncated(&ocr.ocr_text),
+                }),
+                SearchResult::Audio(audio) => json!({
+                    "type": "audio",
+                    "audio_chunk_id": audio.audio_chunk_id,
+                    "timestamp": audio.timestamp,
+                    "text": truncated(&audio.transcription),
+                }),
+                SearchResult::FTS(fts) => json!({
+                    "type": "ocr",
+                    "frame_id": fts.frame_id,
+                    "timestamp": fts.frame_timestamp,
+                    "app_name": fts.app_name,
+                    "window_name": fts.window_name,
+                    "text": truncated(&fts.matched_text),
+                }),
+            })
+            .collect();
+        Ok(json!({ "results": results }))
+    }
+}
+
+pub struct GetFrameTool {
+    db: Arc<DatabaseManager>,
+}
+
+#[derive(Deserialize)]
+struct FrameArguments {
+    frame_id: i64,
+}
+
+#[async_trait]
+impl LlmTool for GetFrameTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: GET_FRAME_TOOL.to_string(),
+            description: "Reads a frame of the screen recording: when it was captured, the app \
+                and window in focus and all the text on the screen."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "frame_id": {"type": "integer", "description": "As search_history gives it"},
+                },
+                "required": ["frame_id"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &Value, _pipe: Option<&str>) -> Result<Value> {
+        let FrameArguments { frame_id } = self::arguments(GET_FRAME_TOOL, arguments)?;
+        let (timestamp, app_name, window_name) = self
+            .db
+            .get_frame_window(frame_id)
+            .await?
+            .ok_or_else(|| anyhow!("frame {} not found", frame_id))?;
+        let text = self.db.get_frame_ocr_text(frame_id).await?;
+        Ok(json!({
+            "frame_id": frame_id,
+            "timestamp": timestamp,
+            "app_name": app_name,
+            "window_name": window_name,
+            "text": truncated(&text),
+        }))
+    }
+}
+
+pub struct CreateTagTool {
+    db: Arc<DatabaseManager>,
+}
+
+#[derive(Deserialize)]
+struct CreateTagArguments {
+    frame_id: i64,
+    tag: String,
+}
+
+#[async_trait]
+impl LlmTool for CreateTagTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: CREATE_TAG_TOOL.to_string(),
+            description: "Tags a frame so the user finds the moment again, its video is kept \
+                when the recordings are compacted."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "frame_id": {"type": "integer"},
+                    "tag": {"type": "string", "maxLength": MAX_TAG_LEN},
+                },
+                "required": ["frame_id", "tag"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &Value, pipe: Option<&str>) -> Result<Value> {
+        let CreateTagArguments { frame_id, tag } = self::arguments(CREATE_TAG_TOOL, arguments)?;
+        let tag = tag.trim();
+        if tag.is_empty() || tag.chars().count() > MAX_TAG_LEN {
+            bail!("a tag has 1 to {} characters", MAX_TAG_LEN);
+        }
+        let timestamp = self
+            .db
+            .get_frame_timestamp(frame_id)
+            .await?
+            .ok_or_else(|| anyhow!("frame {} not found", frame_id))?;
+        let id = self
+            .db
+            .insert_marker(timestamp, Some(tag), None, None, Some(frame_id), None)
+            .await?;
+        let marker = self
+            .db
+            .get_marker(id)
+            .await?
+            .ok_or_else(|| anyhow!("marker {} not found", id))?;
+        info!(
+            "Frame {} tagged {} by {}",
+            frame_id,
+            tag,
+            pipe.unwrap_or("a chat")
+        );
+        emit_event("marker.created", json!(marker));
+        Ok(json!({ "marker_id": id, "frame_id": frame_id, "tag": tag }))
+    }
+}
+
+pub struct SendNotificationTool;
+
+#[derive(Deserialize)]
+struct SendNotificationArguments {
+    title: String,
+    #[serde(default)]
+    body: Option<String>,
+}
+
+#[async_trait]
+impl LlmTool for SendNotificationTool {
+    fn definition(&self) -> ToolDefinition {
+        ToolDefinition {
+            name: SEND_NOTIFICATION_TOOL.to_string(),
+            description: "Shows the user a notification, for what they should know now rather \
+                than in the answer."
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "title": {"type": "string"},
+                    "body": {"type": "string"},
+                },
+                "required": ["title"],
+            }),
+        }
+    }
+
+    async fn call(&self, arguments: &Value, pipe: Option<&str>) -> Result<Value> {
+        let SendNotificationArguments { title, body } =
+            self::arguments(SEND_NOTIFICATION_TOOL, arguments)?;
+        // the apps showing them hold them while do not disturb is on
+        let do_not_disturb = do_not_disturb();
+        emit_event(
+            "llm.notification",
+            json!({
+                "title": title,
+                "body": body,
+                "pipe": pipe,
+                "do_not_disturb": do_not_disturb,
+            }),
+        );
+        Ok(json!({ "sent": true, "held_for_do_not_disturb": do_not_disturb }))
+    }
+}
+
+/// The four tools, all of them enabled
+pub fn builtin_tools(db: Arc<DatabaseManager>) -> ToolRegistry {
+    let mut registry = ToolRegistry::default();
+    registry.register(Arc::new(SearchHistoryTool { db: db.clone() }));
+    registry.register(Arc::new(GetFrameTool { db: db.clone() }));
+    registry.register(Arc::new(CreateTagTool { db }));
+    registry.register(Arc::new(SendNotificationTool));
+    registry
+}
@@ -0,0 +1,171 @@
+use anyhow::Result;
+use axum::Router;
+use hyper_util::rt::TokioIo;
+use hyper_util::service::TowerToHyperService;
+use log::{debug, info, warn};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncRead, AsyncWrite};
+
+/// How long to wait before accepting again when accepting a connection failed, e.g. when out
+/// of file descriptors
+#[cfg(any(unix, windows))]
+const ACCEPT_RETRY_DELAY: std::time::Duration = std::time::Duration::from_millis(100);
+
+/// Where the API is served to local processes when --api-socket isn't set
+pub fn default_api_socket(data_dir: &Path) -> PathBuf {
+    #[cfg(windows)]
+    {
+        let _ = data_dir;
+        let user = crate::profiles::login_name().unwrap_or_else(|| "default".to_string());
+        PathBuf::from(format!(r"\\.\pipe\screenpipe-{}", user))
+    }
+    #[cfg(not(windows))]
+    data_dir.join("screenpipe.sock")
+}
+
+pub(crate) async fn serve_connection<S>(stream: S, app: Router)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let service = TowerToHyperService::new(app);
+    if let Err(e) = hyper::server::conn::http1::Builder::new()
+        .serve_connection(TokioIo::new(stream), service)
+        .with_upgrades()
+        .await
+    {
+        debug!("Local API connection closed: {}", e);
+    }
+}
+
+/// Serves the API on a unix socket only the user can open, connections of processes of other
+/// users are closed before reading anything
+#[cfg(unix)]
+pub async fn serve_local_api(path: PathBuf, app: Router) -> Result<()> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+    use tokio::net::UnixListener;
+
+    // left by a previous run
+    if path.exists() {
+        std::fs::remove_file(&path)?;
+    }
+    // bound in a directory only the user can enter and moved in place once restricted, so no
+    // one gets to connect while the socket still has the mode of the umask
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let staging = parent.join(format!(".screenpipe-sock-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&staging);
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("screenpipe.sock");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, &path)?;
+        Ok(listener)
+    });
+    let _ = std::fs::remove_dir_all(&staging);
+    let listener = bound?;
+    let owner = std::fs::metadata(&path)?.uid();
+    info!("Serving the API on {}", path.display());
+
+    loop {
+        let stream = match listener.accept().await {
+            Ok((stream, _)) => stream,
+            Err(e) => {
+                warn!("Failed to accept a local API connection: {}", e);
+                tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                continue;
+            }
+        };
+        match stream.peer_cred() {
+            Ok(peer) if peer.uid() == owner => {
+                tokio::spawn(serve_connection(stream, app.clone()));
+            }
+            Ok(peer) => warn!(
+                "Refused a local API connection of user {} (pid {:?})",
+                peer.uid(),
+                peer.pid()
+            ),
+            Err(e) => warn!("Refused a local API connection without credentials: {}", e),
+        }
+    }
+}
+
+/// Serves the API on a named pipe, only to the processes of the same user on this machine
+#[cfg(windows)]
+pub async fn serve_local_api(path: PathBuf, app: Router) -> Result<()> {
+    use std::os::windows::io::AsRawHandle;
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetNamedPipeClientProcessId(pipe: *mut std::ffi::c_void, client_pid: *mut u32) -> i32;
+    }
+
+    let mut server = ServerOptions::new()
+        .first_pipe_instance(true)
+        .reject_remote_clients(true)
+        .create(&path)?;
+    info!("Serving the API on {}", path.display());
+
+    loop {
+        if let Err(e) = server.connect().await {
+            warn!("Failed to accept a local API connection: {}", e);
+            tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+            continue;
+        }
+        let next = loop {
+            match ServerOptions::new()
+                .reject_remote_clients(true)
+                .create(&path)
+            {
+                Ok(next) => break next,
+                Err(e) => {
+                    warn!("Failed to open the next local API pipe: {}", e);
+                    tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                }
+            }
+        };
+        let connected = std::mem::replace(&mut server, next);
+
+        let mut client_pid = 0u32;
+        // SAFETY: the handle is the connected pipe, owned by `connected` until the end of the
+        // call, and client_pid is a valid u32 to write to
+        let found =
+            unsafe { GetNamedPipeClientProcessId(connected.as_raw_handle(), &mut client_pid) } != 0;
+        if found && same_user(client_pid) {
+            tokio::spawn(serve_connection(connected, app.clone()));
+        } else {
+            warn!(
+                "Refused a local API connection of another user (pid {})",
+                client_pid
+            );
+        }
+    }
+}
+
+#[cfg(windows)]
+fn same_user(pid: u32) -> bool {
+    use sysinfo::{Pid, PidExt, ProcessExt, ProcessRefreshKind, System, SystemExt};
+
+    let mut sys = System::new();
+    let client = Pid::from_u32(pid);
+    let own = Pid::from_u32(std::process::id());
+    let user = ProcessRefreshKind::new().with_user();
+    sys.refresh_process_specifics(client, user);
+    sys.refresh_process_specifics(own, user);
+    match (sys.process(client), sys.process(own)) {
+        (Some(client), Some(own)) => {
+            client.user_id().is_some() && client.user_id() == own.user_id()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+pub async fn serve_local_api(path: PathBuf, _app: Router) -> Result<()> {
+    anyhow::bail!(
+        "the API can't be served on {} on this platform",
+        path.display()
+    )
+}
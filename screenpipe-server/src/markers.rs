@@ -0,0 +1,191 @@
+use crate::disk_guard::{disk_level, DiskLevel};
+use crate::encryption::write_media;
+use crate::text_only::text_only;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use image::{DynamicImage, ImageFormat};
+use log::{debug, error, info, warn};
+use screenpipe_core::emit_event;
+use screenpipe_vision::monitor::get_monitor_by_id;
+use screenpipe_vision::utils::capture_screenshot;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Longest voice memo, what is said after the marker is set
+const MAX_MEMO_SECS: u32 = 120;
+/// Time for the last audio chunk of the memo to be transcribed once it ends
+const TRANSCRIPTION_MARGIN: Duration = Duration::from_secs(30);
+/// The marker points at the last frame recorded at most this long before it
+const MAX_FRAME_AGE: ChronoDuration = ChronoDuration::seconds(30);
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MarkerRequest {
+    #[serde(default)]
+    pub note: Option<String>,
+    /// Seconds of speech after the marker to transcribe as a voice memo
+    #[serde(default)]
+    pub memo_secs: Option<u32>,
+}
+
+impl MarkerRequest {
+    pub fn validate(&self) -> Result<()> {
+        if self.memo_secs.is_some_and(|secs| secs > MAX_MEMO_SECS) {
+            anyhow::bail!("memo_secs can't be more than {}", MAX_MEMO_SECS);
+        }
+        Ok(())
+    }
+}
+
+/// A "remember this" moment. Its frame's video chunk is never compacted
+#[derive(Debug, Clone, Serialize)]
+pub struct Marker {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub note: Option<String>,
+    /// Full resolution screenshot taken when the marker was set, none when the screen isn't
+    /// recorded or capture is paused
+    pub image_path: Option<String>,
+    pub frame_id: Option<i64>,
+    pub memo_secs: Option<u32>,
+    /// Set once the memo is transcribed
+    pub memo: Option<String>,
+}
+
+/// Sets markers for the API and the desktop app's hotkey
+pub struct MarkerRecorder {
+    db: Arc<DatabaseManager>,
+    output_dir: PathBuf,
+    /// None when the screen isn't recorded
+    monitor_id: Option<u32>,
+    audio_chunk_duration: Duration,
+}
+
+impl MarkerRecorder {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        output_dir: PathBuf,
+        monitor_id: Option<u32>,
+        audio_chunk_duration: Duration,
+    ) -> Self {
+        MarkerRecorder {
+            db,
+            output_dir,
+            monitor_id,
+            audio_chunk_duration,
+        }
+    }
+
+    /// Nothing is captured while `paused`, the marker only keeps the note then
+    pub async fn create(&self, request: MarkerRequest, paused: bool) -> Result<Marker> {
+        request.validate()?;
+        let memo_secs = request.memo_secs.filter(|secs| *secs > 0);
+        let note = request
+            .note
+            .map(|note| note.trim().to_string())
+            .filter(|note| !note.is_empty());
+        let timestamp = Utc::now();
+
+        let raw_frames = disk_level() < DiskLevel::NoRawFrames && !text_only();
+        let image_path = match self.monitor_id.filter(|_| !paused && raw_frames) {
+            Some(monitor_id) => match self.save_screenshot(monitor_id, timestamp).await {
+                Ok(saved) => Some(saved),
+                Err(e) => {
+                    warn!("Failed to capture the screen for a marker: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
+        let frame_id = self.db.get_frame_id_at(timestamp, MAX_FRAME_AGE).await?;
+        let id = self
+            .db
+            .insert_marker(
+                timestamp,
+                note.as_deref(),
+                image_path.as_ref().map(|(path, _)| path.as_str()),
+                image_path.as_ref().and_then(|(_, key_id)| *key_id),
+                frame_id,
+                if paused { None } else { memo_secs },
+            )
+            .await?;
+        let marker = self
+            .db
+            .get_marker(id)
+            .await?
+            .ok_or_else(|| anyhow!("marker {} not found", id))?;
+        info!("Marker {} set", id);
+        emit_event("marker.created", json!(marker));
+
+        if let Some(memo_secs) = marker.memo_secs {
+            let db = Arc::clone(&self.db);
+            // what was said is transcribed once its audio chunk ends
+            let wait = Duration::from_secs(memo_secs as u64)
+                + self.audio_chunk_duration
+                + TRANSCRIPTION_MARGIN;
+            tokio::spawn(async move {
+                tokio::time::sleep(wait).await;
+                if let Err(e) = transcribe_memo(&db, id, timestamp, wait).await {
+                    error!("Failed to transcribe the memo of marker {}: {}", id, e);
+                }
+            });
+        }
+        Ok(marker)
+    }
+
+    /// Without the windows left out of the recording
+    async fn save_screenshot(
+        &self,
+        monitor_id: u32,
+        timestamp: DateTime<Utc>,
+    ) -> Result<(String, Option<u32>)> {
+        let monitor = get_monitor_by_id(monitor_id)
+            .await
+            .ok_or_else(|| anyhow!("monitor {} not found", monitor_id))?;
+        // the capture panics when the screen can't be read
+        let (image, ..) = tokio::spawn(capture_screenshot(Arc::new(monitor)))
+            .await
+            .map_err(|_| anyhow!("the screen can't be captured"))??;
+        let png = tokio::task::spawn_blocking(move || encode_png(&image)).await??;
+
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        let path = self.output_dir.join(format!(
+            "marker_{}.png",
+            timestamp.format("%Y-%m-%d_%H-%M-%S%.3f")
+        ));
+        write_media(&path, png).await
+    }
+}
+
+fn encode_png(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut png = Vec::new();
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    Ok(png)
+}
+
+/// The transcriptions made in `window` after the marker. Chunks are transcribed whole so the
+/// memo can start with what was said just before the marker
+pub async fn transcribe_memo(
+    db: &DatabaseManager,
+    id: i64,
+    timestamp: DateTime<Utc>,
+    window: Duration,
+) -> Result<String> {
+    let end = timestamp + ChronoDuration::from_std(window)?;
+    let memo = db
+        .get_transcriptions_between(timestamp, end)
+        .await?
+        .iter()
+        .map(|(_, text)| text.trim())
+        .filter(|text| !text.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    db.set_marker_memo(id, &memo).await?;
+    debug!("Marker {} memo: {}", id, memo);
+    emit_event("marker.memo", json!({"id": id, "memo": memo}));
+    Ok(memo)
+}
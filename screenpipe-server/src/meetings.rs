@@ -0,0 +1,295 @@
+use crate::power::background_jobs_paused;
+use crate::{ContentType, DatabaseManager, LlmService, SearchResult};
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use screenpipe_core::PromptLibrary;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Silence longer than this between two transcriptions ends the meeting
+const MAX_SILENCE_GAP: ChronoDuration = ChronoDuration::minutes(5);
+/// Conversations shorter than this are not considered meetings
+const MIN_MEETING_DURATION: ChronoDuration = ChronoDuration::minutes(5);
+/// How far back we look for meetings when nothing has been summarized yet
+const INITIAL_LOOKBACK: ChronoDuration = ChronoDuration::hours(24);
+/// Rounds a meeting is tried in before giving up on its summary
+const MAX_SUMMARY_ATTEMPTS: u32 = 3;
+const MAX_TIMELINE_ITEMS: u32 = 1000;
+/// Keep the prompt within the context window of small local models
+const MAX_TIMELINE_CHARS: usize = 24_000;
+const MAX_OCR_CHARS_PER_ENTRY: usize = 300;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ActionItem {
+    pub description: String,
+    #[serde(default)]
+    pub owner: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MeetingSummary {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub summary: String,
+    pub decisions: Vec<String>,
+    pub action_items: Vec<ActionItem>,
+    pub llm_model: String,
+    /// Version of the "summarize" prompt template used
+    pub prompt_version: Option<i64>,
+    pub created_at: DateTime<Utc>,
+    /// Title of the calendar event the meeting overlapped, once one was found
+    pub calendar_title: Option<String>,
+    pub calendar_attendees: Vec<String>,
+}
+
+/// What we expect the LLM to answer
+#[derive(Debug, Deserialize, PartialEq)]
+pub struct MeetingSummaryOutput {
+    pub summary: String,
+    #[serde(default)]
+    pub decisions: Vec<String>,
+    #[serde(default)]
+    pub action_items: Vec<ActionItem>,
+}
+
+/// Groups ascending timestamps into sessions separated by more than `max_gap`,
+/// keeping only the sessions lasting at least `min_duration`
+pub fn detect_meeting_sessions(
+    timestamps: &[DateTime<Utc>],
+    max_gap: ChronoDuration,
+    min_duration: ChronoDuration,
+) -> Vec<(DateTime<Utc>, DateTime<Utc>)> {
+    let mut sessions = Vec::new();
+    let mut iter = timestamps.iter();
+    let Some(first) = iter.next() else {
+        return sessions;
+    };
+
+    let (mut start, mut end) = (*first, *first);
+    for timestamp in iter {
+        if *timestamp - end > max_gap {
+            sessions.push((start, end));
+            start = *timestamp;
+        }
+        end = *timestamp;
+    }
+    sessions.push((start, end));
+
+    sessions.retain(|(start, end)| *end - *start >= min_duration);
+    sessions
+}
+
+/// Extracts the JSON object from the LLM answer, models like to wrap it in markdown
+pub fn parse_meeting_summary_output(content: &str) -> Result<MeetingSummaryOutput> {
+    let start = content
+        .find('{')
+        .ok_or_else(|| anyhow::anyhow!("no json object in llm response"))?;
+    let end = content
+        .rfind('}')
+        .ok_or_else(|| anyhow::anyhow!("no json object in llm response"))?;
+    Ok(serde_json::from_str(&content[start..=end])?)
+}
+
+async fn build_timeline(
+    db: &DatabaseManager,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<String> {
+    let mut entries: Vec<(DateTime<Utc>, String)> = Vec::new();
+
+    let audio = db
+        .search(
+            "",
+            ContentType::Audio,
+            MAX_TIMELINE_ITEMS,
+            0,
+            Some(start),
+            Some(end),
+            None,
+            None,
+        )
+        .await?;
+    let ocr = db
+        .search(
+            "",
+            ContentType::OCR,
+            MAX_TIMELINE_ITEMS,
+            0,
+            Some(start),
+            Some(end),
+            None,
+            None,
+        )
+        .await?;
+
+    for result in audio {
+        if let SearchResult::Audio(audio) = result {
+            entries.push((
+                audio.timestamp,
+                format!("[audio] {}", audio.transcription.trim()),
+            ));
+        }
+    }
+
+    // screen text barely changes during a call, only keep it when it does
+    let mut last_screen_text = String::new();
+    let mut ocr: Vec<_> = ocr
+        .into_iter()
+        .filter_map(|r| match r {
+            SearchResult::OCR(ocr) => Some(ocr),
+            _ => None,
+        })
+        .collect();
+    ocr.sort_by_key(|o| o.timestamp);
+    for o in ocr {
+        let text: String = o
+            .ocr_text
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .chars()
+            .take(MAX_OCR_CHARS_PER_ENTRY)
+            .collect();
+        if text.is_empty() || text == last_screen_text {
+            continue;
+        }
+        last_screen_text = text.clone();
+        entries.push((
+            o.timestamp,
+            format!("[screen: {} - {}] {}", o.app_name, o.window_name, text),
+        ));
+    }
+
+    entries.sort_by_key(|(timestamp, _)| *timestamp);
+
+    let mut timeline = String::new();
+    for (timestamp, line) in entries {
+        let line = format!("{} {}\n", timestamp.format("%H:%M:%S"), line);
+        if timeline.len() + line.len() > MAX_TIMELINE_CHARS {
+            break;
+        }
+        timeline.push_str(&line);
+    }
+    Ok(timeline)
+}
+
+async fn summarize_meeting(
+    db: &DatabaseManager,
+    llm: &LlmService,
+    prompts: &PromptLibrary,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<i64> {
+    let timeline = build_timeline(db, start, end).await?;
+    let prompt = prompts.get("summarize");
+    let messages = prompt.render(&[
+        ("start_time", &start.to_rfc3339()),
+        ("end_time", &end.to_rfc3339()),
+        ("timeline", &timeline),
+    ]);
+
+    let answer = llm.chat("summarize", &messages).await?;
+    let output = parse_meeting_summary_output(&answer.content)?;
+
+    let id = db
+        .insert_meeting_summary(
+            start,
+            end,
+            &output.summary,
+            &output.decisions,
+            &output.action_items,
+            &answer.model,
+            prompt.version,
+        )
+        .await?;
+    Ok(id)
+}
+
+/// Meetings whose summary failed, with the rounds they were tried in. Summaries stored after
+/// them move the start of the detection past them, so they are retried from here
+type FailedMeetings = BTreeMap<(DateTime<Utc>, DateTime<Utc>), u32>;
+
+async fn summarize_ended_meetings(
+    db: &DatabaseManager,
+    llm: &LlmService,
+    prompts: &PromptLibrary,
+    failed: &mut FailedMeetings,
+) -> Result<()> {
+    let now = Utc::now();
+    let since = db
+        .get_last_meeting_end_time()
+        .await?
+        .unwrap_or(now - INITIAL_LOOKBACK);
+
+    let timestamps = db.get_audio_timestamps_since(since).await?;
+    let sessions = detect_meeting_sessions(&timestamps, MAX_SILENCE_GAP, MIN_MEETING_DURATION);
+
+    // the failed meetings before `since` aren't detected again
+    let mut meetings: Vec<_> = failed
+        .keys()
+        .filter(|(_, end)| *end <= since)
+        .copied()
+        .collect();
+    for (start, end) in sessions {
+        if now - end <= MAX_SILENCE_GAP {
+            debug!("Meeting started at {} is still ongoing", start);
+            break;
+        }
+        meetings.push((start, end));
+    }
+
+    for (start, end) in meetings {
+        let attempts = failed.get(&(start, end)).copied().unwrap_or(0);
+        if attempts >= MAX_SUMMARY_ATTEMPTS {
+            continue;
+        }
+        info!("Summarizing meeting from {} to {}", start, end);
+        // one meeting the llm fails on doesn't hold back the ones after it
+        match summarize_meeting(db, llm, prompts, start, end).await {
+            Ok(id) => {
+                info!("Stored meeting summary {}", id);
+                failed.remove(&(start, end));
+            }
+            Err(e) if attempts + 1 < MAX_SUMMARY_ATTEMPTS => {
+                warn!(
+                    "Failed to summarize the meeting from {} to {}, retrying it next round: {}",
+                    start, end, e
+                );
+                failed.insert((start, end), attempts + 1);
+            }
+            Err(e) => {
+                error!(
+                    "Failed to summarize the meeting from {} to {} {} times, giving up: {}",
+                    start, end, MAX_SUMMARY_ATTEMPTS, e
+                );
+                failed.insert((start, end), MAX_SUMMARY_ATTEMPTS);
+            }
+        }
+    }
+    // the ones given up on before `since` won't be detected again either
+    failed.retain(|(_, end), attempts| *attempts < MAX_SUMMARY_ATTEMPTS || *end > since);
+    Ok(())
+}
+
+/// Periodically looks for meetings that ended and stores their summary
+pub async fn start_meeting_summarizer(
+    db: Arc<DatabaseManager>,
+    llm: Arc<LlmService>,
+    prompts: Arc<PromptLibrary>,
+    interval: Duration,
+) {
+    info!("Meeting summarizer started using model {}", llm.model());
+    let mut failed = FailedMeetings::new();
+    loop {
+        if !background_jobs_paused() {
+            if let Err(e) = summarize_ended_meetings(&db, &llm, &prompts, &mut failed).await {
+                error!("Failed to summarize meetings: {}", e);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
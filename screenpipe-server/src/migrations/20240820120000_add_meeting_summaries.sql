@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS meeting_summaries (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time TIMESTAMP NOT NULL,
+    end_time TIMESTAMP NOT NULL,
+    summary TEXT NOT NULL,
+    decisions TEXT NOT NULL DEFAULT '[]',
+    action_items TEXT NOT NULL DEFAULT '[]',
+    llm_model TEXT NOT NULL,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_meeting_summaries_end_time ON meeting_summaries(end_time);
@@ -0,0 +1,26 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS extraction_rules (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    app_name TEXT,
+    window_name TEXT,
+    kind TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS extracted_records (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    rule_id INTEGER NOT NULL,
+    frame_id INTEGER NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    app_name TEXT NOT NULL,
+    window_name TEXT NOT NULL,
+    data TEXT NOT NULL,
+    FOREIGN KEY (rule_id) REFERENCES extraction_rules(id) ON DELETE CASCADE,
+    UNIQUE (rule_id, data)
+);
+
+CREATE INDEX IF NOT EXISTS idx_extracted_records_rule_id ON extracted_records(rule_id);
+CREATE INDEX IF NOT EXISTS idx_extracted_records_timestamp ON extracted_records(timestamp);
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE meeting_summaries ADD COLUMN prompt_version INTEGER;
@@ -0,0 +1,2 @@
+-- Add migration script here
+ALTER TABLE video_chunks ADD COLUMN subtitles_muxed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- NULL until the integrity checker looked at the chunk, then 'ok', 'repaired' or 'broken'
+ALTER TABLE video_chunks ADD COLUMN integrity TEXT;
+ALTER TABLE audio_chunks ADD COLUMN integrity TEXT;
@@ -0,0 +1,4 @@
+-- Add migration script here
+ALTER TABLE video_chunks ADD COLUMN compacted BOOLEAN NOT NULL DEFAULT FALSE;
+-- Size of the stored frames relative to the captured screen, for mapping OCR coordinates
+ALTER TABLE video_chunks ADD COLUMN scale REAL NOT NULL DEFAULT 1.0;
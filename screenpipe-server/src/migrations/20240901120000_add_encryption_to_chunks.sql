@@ -0,0 +1,4 @@
+-- Add migration script here
+-- NULL while the chunk is stored in plaintext
+ALTER TABLE video_chunks ADD COLUMN encryption_key_id INTEGER;
+ALTER TABLE audio_chunks ADD COLUMN encryption_key_id INTEGER;
@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS compliance_consents (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    user TEXT NOT NULL,
+    organization TEXT NOT NULL,
+    -- sha256 of the signed policy file the user was shown
+    policy_hash TEXT NOT NULL,
+    accepted BOOLEAN NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_compliance_consents_timestamp ON compliance_consents(timestamp);
+
+-- kept as proof of consent
+CREATE TRIGGER IF NOT EXISTS compliance_consents_no_update
+BEFORE UPDATE ON compliance_consents
+BEGIN
+    SELECT RAISE(ABORT, 'consent records are append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS compliance_consents_no_delete
+BEFORE DELETE ON compliance_consents
+BEGIN
+    SELECT RAISE(ABORT, 'consent records are append-only');
+END;
@@ -0,0 +1,4 @@
+-- Add migration script here
+-- title and attendees of the calendar event the meeting overlapped
+ALTER TABLE meeting_summaries ADD COLUMN calendar_title TEXT;
+ALTER TABLE meeting_summaries ADD COLUMN calendar_attendees TEXT NOT NULL DEFAULT '[]';
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS file_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    path TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    -- focused window when the file was written, NULL when nothing was recorded then
+    app_name TEXT,
+    window_name TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_file_events_timestamp ON file_events(timestamp);
+CREATE INDEX IF NOT EXISTS idx_file_events_path ON file_events(path);
@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS markers (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    note TEXT,
+    -- full resolution screenshot, encrypted with this key when encryption is on
+    image_path TEXT,
+    encryption_key_id INTEGER,
+    -- the chunk of this frame is never compacted
+    frame_id INTEGER,
+    memo_secs INTEGER,
+    memo TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_markers_timestamp ON markers(timestamp);
+CREATE INDEX IF NOT EXISTS idx_markers_frame_id ON markers(frame_id);
@@ -0,0 +1,14 @@
+-- Add migration script here
+-- how far each background worker got, so it resumes there after a restart
+CREATE TABLE IF NOT EXISTS processing_checkpoints (
+    name TEXT PRIMARY KEY,
+    position INTEGER NOT NULL,
+    updated_at TIMESTAMP NOT NULL
+);
+
+-- audio chunks recorded but not transcribed yet, removed in the transaction writing the transcript
+CREATE TABLE IF NOT EXISTS pending_transcriptions (
+    file_path TEXT PRIMARY KEY,
+    device TEXT NOT NULL,
+    recorded_at TIMESTAMP NOT NULL
+);
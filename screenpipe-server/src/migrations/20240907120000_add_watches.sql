@@ -0,0 +1,27 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS watches (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    name TEXT NOT NULL,
+    app_name TEXT,
+    window_name TEXT,
+    kind TEXT NOT NULL,
+    pattern TEXT NOT NULL,
+    source TEXT NOT NULL DEFAULT 'all',
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS watch_alerts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    watch_id INTEGER NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    source TEXT NOT NULL,
+    app_name TEXT,
+    window_name TEXT,
+    matched_text TEXT NOT NULL,
+    excerpt TEXT NOT NULL,
+    FOREIGN KEY (watch_id) REFERENCES watches(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_watch_alerts_watch_id ON watch_alerts(watch_id);
+CREATE INDEX IF NOT EXISTS idx_watch_alerts_timestamp ON watch_alerts(timestamp);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- 64 bit pHash of the window image, for the screenshot similarity search
+ALTER TABLE frames ADD COLUMN perceptual_hash INTEGER;
@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS window_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP,
+    app_name TEXT NOT NULL,
+    window_name TEXT NOT NULL,
+    kind TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_window_events_timestamp ON window_events(timestamp);
+CREATE INDEX IF NOT EXISTS idx_window_events_app_name ON window_events(app_name);
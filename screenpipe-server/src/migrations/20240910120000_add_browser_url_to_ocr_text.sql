@@ -0,0 +1,4 @@
+-- Add migration script here
+-- set when the text of the window was pushed by the browser extension
+ALTER TABLE ocr_text ADD COLUMN browser_url TEXT;
+ALTER TABLE ocr_text ADD COLUMN scroll_y REAL;
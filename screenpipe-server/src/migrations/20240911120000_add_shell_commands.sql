@@ -0,0 +1,15 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS shell_commands (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP,
+    command TEXT NOT NULL,
+    cwd TEXT,
+    exit_code INTEGER,
+    shell TEXT NOT NULL,
+    source TEXT NOT NULL
+);
+
+-- history files are read again and hold the commands the hooks sent, they are stored once
+CREATE UNIQUE INDEX IF NOT EXISTS idx_shell_commands_unique ON shell_commands(shell, timestamp, command);
+CREATE INDEX IF NOT EXISTS idx_shell_commands_timestamp ON shell_commands(timestamp);
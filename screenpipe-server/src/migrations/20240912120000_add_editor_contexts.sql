@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS editor_contexts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP NOT NULL,
+    editor TEXT NOT NULL,
+    project TEXT,
+    repository TEXT,
+    git_branch TEXT,
+    file TEXT,
+    language TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_editor_contexts_timestamp ON editor_contexts(timestamp);
+CREATE INDEX IF NOT EXISTS idx_editor_contexts_editor ON editor_contexts(editor, ended_at);
+CREATE INDEX IF NOT EXISTS idx_editor_contexts_repository ON editor_contexts(repository);
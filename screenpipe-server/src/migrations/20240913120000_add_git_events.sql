@@ -0,0 +1,16 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS git_events (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    repository TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    branch TEXT,
+    commit_hash TEXT NOT NULL,
+    previous_hash TEXT NOT NULL,
+    message TEXT NOT NULL,
+    author TEXT NOT NULL
+);
+
+-- the reflogs are read again whenever they change, each move is stored once
+CREATE UNIQUE INDEX IF NOT EXISTS idx_git_events_unique ON git_events(repository, timestamp, commit_hash, kind);
+CREATE INDEX IF NOT EXISTS idx_git_events_timestamp ON git_events(timestamp);
@@ -0,0 +1,27 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS automation_actions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    pipe TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    action TEXT NOT NULL,
+    app_name TEXT,
+    window_name TEXT,
+    status TEXT NOT NULL,
+    reason TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_automation_actions_timestamp ON automation_actions(timestamp);
+
+-- the trail of the input synthesized for pipes can't be rewritten
+CREATE TRIGGER IF NOT EXISTS automation_actions_no_update
+BEFORE UPDATE ON automation_actions
+BEGIN
+    SELECT RAISE(ABORT, 'automation actions are append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS automation_actions_no_delete
+BEFORE DELETE ON automation_actions
+BEGIN
+    SELECT RAISE(ABORT, 'automation actions are append-only');
+END;
@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS redactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    source TEXT NOT NULL,
+    rule TEXT NOT NULL,
+    action TEXT NOT NULL,
+    app_name TEXT,
+    entities TEXT NOT NULL,
+    regions TEXT NOT NULL,
+    audio_file TEXT
+);
+
+CREATE INDEX IF NOT EXISTS idx_redactions_timestamp ON redactions(timestamp);
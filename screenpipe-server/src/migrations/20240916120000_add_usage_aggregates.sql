@@ -0,0 +1,17 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS usage_aggregates (
+    hour TIMESTAMP NOT NULL,
+    source TEXT NOT NULL,
+    -- empty for audio, part of the key
+    app_name TEXT NOT NULL,
+    count INTEGER NOT NULL,
+    word_count INTEGER NOT NULL,
+    char_count INTEGER NOT NULL,
+    PRIMARY KEY (hour, source, app_name)
+);
+
+-- frames and transcriptions up to this time are counted in usage_aggregates
+CREATE TABLE IF NOT EXISTS usage_aggregation (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    aggregated_until TIMESTAMP NOT NULL
+);
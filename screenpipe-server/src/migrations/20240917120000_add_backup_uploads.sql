@@ -0,0 +1,26 @@
+-- Add migration script here
+-- uploads to the backup destination, the unfinished ones are resumed from the next part
+CREATE TABLE IF NOT EXISTS backup_uploads (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    -- database, video or audio
+    source TEXT NOT NULL,
+    -- the chunk uploaded, NULL for the database
+    chunk_id INTEGER,
+    -- a snapshot for the database, deleted once uploaded
+    file_path TEXT NOT NULL,
+    object_key TEXT NOT NULL,
+    file_size INTEGER NOT NULL,
+    upload_id TEXT NOT NULL,
+    -- json array of what the destination gave back for each part uploaded
+    parts TEXT NOT NULL DEFAULT '[]',
+    started_at TIMESTAMP NOT NULL,
+    completed_at TIMESTAMP
+);
+
+CREATE INDEX IF NOT EXISTS idx_backup_uploads_source_chunk ON backup_uploads(source, chunk_id);
+
+-- salt of the key backups are encrypted with, derived from the passphrase
+CREATE TABLE IF NOT EXISTS backup_key (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    salt BLOB NOT NULL
+);
@@ -0,0 +1,35 @@
+-- Add migration script here
+-- ISO 639-1 code detected by the text indexer, 'und' when it couldn't tell. NULL until indexed
+ALTER TABLE ocr_text ADD COLUMN language TEXT;
+ALTER TABLE audio_transcriptions ADD COLUMN language TEXT;
+-- rowid of the text in ocr_text_fts, ocr_text has no primary key VACUUM keeps
+ALTER TABLE ocr_text ADD COLUMN fts_id INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_ocr_text_fts_id ON ocr_text(fts_id);
+CREATE INDEX IF NOT EXISTS idx_ocr_text_unindexed ON ocr_text(frame_id) WHERE language IS NULL;
+CREATE INDEX IF NOT EXISTS idx_audio_transcriptions_unindexed ON audio_transcriptions(id) WHERE language IS NULL;
+
+-- CJK runs are indexed as overlapping bigrams, the rest is split on unicode word boundaries
+-- with the diacritics removed
+CREATE VIRTUAL TABLE IF NOT EXISTS ocr_text_fts USING fts5(text, tokenize = 'unicode61 remove_diacritics 2');
+-- rowid is the id of the transcription
+CREATE VIRTUAL TABLE IF NOT EXISTS audio_transcriptions_fts USING fts5(text, tokenize = 'unicode61 remove_diacritics 2');
+
+CREATE TRIGGER IF NOT EXISTS ocr_text_fts_ad AFTER DELETE ON ocr_text WHEN old.fts_id IS NOT NULL BEGIN
+  DELETE FROM ocr_text_fts WHERE rowid = old.fts_id;
+END;
+
+-- indexed again by the text indexer
+CREATE TRIGGER IF NOT EXISTS ocr_text_fts_au AFTER UPDATE OF text ON ocr_text BEGIN
+  DELETE FROM ocr_text_fts WHERE rowid = old.fts_id;
+  UPDATE ocr_text SET language = NULL, fts_id = NULL WHERE rowid = new.rowid;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_fts_ad AFTER DELETE ON audio_transcriptions BEGIN
+  DELETE FROM audio_transcriptions_fts WHERE rowid = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS audio_transcriptions_fts_au AFTER UPDATE OF transcription ON audio_transcriptions BEGIN
+  DELETE FROM audio_transcriptions_fts WHERE rowid = old.id;
+  UPDATE audio_transcriptions SET language = NULL WHERE id = new.id;
+END;
@@ -0,0 +1,25 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS slide_decks (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    started_at TIMESTAMP NOT NULL,
+    ended_at TIMESTAMP,
+    monitor_id INTEGER NOT NULL,
+    app_name TEXT NOT NULL,
+    window_name TEXT NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS slides (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    deck_id INTEGER NOT NULL REFERENCES slide_decks(id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    -- jpeg of the slide, encrypted with this key when encryption is on
+    image_path TEXT,
+    encryption_key_id INTEGER,
+    width INTEGER NOT NULL,
+    height INTEGER NOT NULL,
+    text TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_slide_decks_started_at ON slide_decks(started_at);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_slides_deck_position ON slides(deck_id, position);
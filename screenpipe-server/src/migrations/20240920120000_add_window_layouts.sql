@@ -0,0 +1,11 @@
+-- Add migration script here
+-- a row each time the windows of a monitor moved, resized, opened or closed
+CREATE TABLE IF NOT EXISTS window_layouts (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    monitor_id INTEGER NOT NULL,
+    -- json array of the windows in desktop coordinates
+    windows TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_window_layouts_monitor_timestamp ON window_layouts(monitor_id, timestamp);
@@ -0,0 +1,32 @@
+-- Add migration script here
+-- the lines the OCR read in each frame, where they were on the screen
+CREATE TABLE IF NOT EXISTS ocr_boxes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    frame_id INTEGER NOT NULL,
+    monitor_id INTEGER,
+    text TEXT NOT NULL,
+    confidence REAL,
+    -- in pixels of the captured frame
+    x REAL NOT NULL,
+    y REAL NOT NULL,
+    width REAL NOT NULL,
+    height REAL NOT NULL,
+    FOREIGN KEY (frame_id) REFERENCES frames(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ocr_boxes_frame_id ON ocr_boxes(frame_id);
+
+-- the boxes over the screen and the time they were seen, in unix seconds. The r-tree keeps
+-- 32 bit floats rounded outwards, it narrows the search, the exact values are in ocr_boxes
+CREATE VIRTUAL TABLE IF NOT EXISTS ocr_boxes_rtree USING rtree(
+    id,
+    min_x, max_x,
+    min_y, max_y,
+    min_t, max_t
+);
+
+CREATE TRIGGER IF NOT EXISTS ocr_boxes_rtree_delete
+AFTER DELETE ON ocr_boxes
+BEGIN
+    DELETE FROM ocr_boxes_rtree WHERE id = old.id;
+END;
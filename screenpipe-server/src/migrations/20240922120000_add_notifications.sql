@@ -0,0 +1,14 @@
+-- Add migration script here
+-- the notifications the OS showed, toasts are gone before the next frame is captured
+CREATE TABLE IF NOT EXISTS notifications (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    app_name TEXT NOT NULL,
+    title TEXT NOT NULL,
+    body TEXT,
+    source TEXT NOT NULL
+);
+
+-- the databases of the notification centers are read again after a restart, each one is stored once
+CREATE UNIQUE INDEX IF NOT EXISTS idx_notifications_unique ON notifications(source, timestamp, app_name, title);
+CREATE INDEX IF NOT EXISTS idx_notifications_timestamp ON notifications(timestamp);
@@ -0,0 +1,11 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS focus_periods (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    start_time TIMESTAMP NOT NULL,
+    -- the last time it was seen on, moved forward while it lasts
+    end_time TIMESTAMP NOT NULL,
+    mode TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_focus_periods_start_time ON focus_periods(start_time);
+CREATE INDEX IF NOT EXISTS idx_focus_periods_end_time ON focus_periods(end_time);
@@ -0,0 +1,10 @@
+-- Add migration script here
+-- the timezone the system was set to from each change on, the timestamps stay in UTC
+CREATE TABLE IF NOT EXISTS timezone_changes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    timestamp TIMESTAMP NOT NULL,
+    timezone TEXT NOT NULL,
+    utc_offset_minutes INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_timezone_changes_timestamp ON timezone_changes(timestamp);
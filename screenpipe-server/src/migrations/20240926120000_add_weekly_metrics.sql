@@ -0,0 +1,8 @@
+-- Add migration script here
+-- what the time of each ended week went to, compared week over week by /analytics/trends
+CREATE TABLE IF NOT EXISTS weekly_metrics (
+    -- the monday it starts on, in the local timezone
+    week_start DATE PRIMARY KEY,
+    metrics TEXT NOT NULL,
+    computed_at TIMESTAMP NOT NULL
+);
@@ -0,0 +1,9 @@
+-- Add migration script here
+-- the key each encrypted thumbnail was written with, so it's re-encrypted after a rotation
+-- and the key isn't retired before. Plaintext thumbnails aren't listed
+CREATE TABLE IF NOT EXISTS thumbnail_keys (
+    path TEXT PRIMARY KEY,
+    encryption_key_id INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_thumbnail_keys_encryption_key_id ON thumbnail_keys(encryption_key_id);
@@ -0,0 +1,5 @@
+-- Add migration script here
+-- answers are cached per endpoint too, the same model name served elsewhere is another model.
+-- The entries keyed without the endpoint can't be looked up anymore
+DELETE FROM llm_cache;
+ALTER TABLE llm_cache ADD COLUMN api_url TEXT NOT NULL DEFAULT '';
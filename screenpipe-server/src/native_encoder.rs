@@ -0,0 +1,95 @@
+use crate::frame_format::{FrameEncoding, FrameFormat};
+use anyhow::Result;
+use image::{DynamicImage, ImageFormat};
+use screenpipe_core::{Container, FfmpegCommand, FfmpegInput, FfmpegOutput, VideoCodec};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Extension of the chunk directories written without ffmpeg
+pub const NATIVE_CHUNK_EXTENSION: &str = "frames";
+
+/// Stores a video chunk as a directory of images, named after their offset index. Lossless
+/// WebP when ffmpeg isn't available, or the format of --frame-format.
+pub struct NativeChunkWriter {
+    dir: PathBuf,
+    frames: u32,
+    encoding: FrameEncoding,
+}
+
+fn frame_path(dir: &Path, offset_index: i64, format: FrameFormat) -> PathBuf {
+    dir.join(format!("{:06}.{}", offset_index, format.extension()))
+}
+
+impl NativeChunkWriter {
+    pub fn create(dir: &Path, encoding: FrameEncoding) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        Ok(NativeChunkWriter {
+            dir: dir.to_path_buf(),
+            frames: 0,
+            encoding,
+        })
+    }
+
+    pub fn frames(&self) -> u32 {
+        self.frames
+    }
+
+    pub async fn write_frame(&mut self, image: Arc<DynamicImage>) -> Result<()> {
+        let path = frame_path(&self.dir, self.frames as i64, self.encoding.format);
+        let encoding = self.encoding;
+        tokio::task::spawn_blocking(move || {
+            std::fs::write(path, encoding.encode(&image)?)?;
+            Ok::<_, anyhow::Error>(())
+        })
+        .await??;
+        self.frames += 1;
+        Ok(())
+    }
+}
+
+pub fn is_native_chunk(file_path: &str) -> bool {
+    let path = Path::new(file_path);
+    path.is_dir()
+        && path
+            .extension()
+            .is_some_and(|extension| extension == NATIVE_CHUNK_EXTENSION)
+}
+
+/// The image of a frame of a chunk and its format, the format may have changed since the
+/// chunk was started
+pub fn native_frame_file(file_path: &str, offset_index: i64) -> Option<(PathBuf, FrameFormat)> {
+    FrameFormat::ALL.into_iter().find_map(|format| {
+        let path = frame_path(Path::new(file_path), offset_index, format);
+        path.exists().then_some((path, format))
+    })
+}
+
+pub async fn read_native_frame(file_path: &str, offset_index: i64) -> Result<DynamicImage> {
+    let (path, format) = native_frame_file(file_path, offset_index)
+        .ok_or_else(|| anyhow::anyhow!("failed to read frame {} of {}", offset_index, file_path))?;
+    let image = match format {
+        // the image crate only encodes AVIF
+        FrameFormat::Avif => {
+            let output = FfmpegCommand::new()
+                .input(FfmpegInput::file(&path.to_string_lossy()))
+                .output(
+                    FfmpegOutput::pipe()
+                        .frames(1)
+                        .format(Container::Image2Pipe)
+                        .video_codec(VideoCodec::Png),
+                )
+                .run()
+                .await?;
+            if !output.status.success() {
+                anyhow::bail!(
+                    "failed to decode {:?}: {}",
+                    path,
+                    String::from_utf8_lossy(&output.stderr)
+                );
+            }
+            image::load_from_memory_with_format(&output.stdout, ImageFormat::Png)
+        }
+        _ => image::open(&path),
+    };
+    image.map_err(|e| anyhow::anyhow!("failed to read frame {:?}: {}", path, e))
+}
@@ -0,0 +1,302 @@
+use crate::pause::PauseLog;
+use crate::{CapturePause, DatabaseManager};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use screenpipe_core::emit_event;
+use screenpipe_integrations::notifications::{
+    action_center_db, from_filetime, from_mac_absolute_time, notification_center_dbs,
+    parse_notification_plist, parse_toast_payload, to_filetime, to_mac_absolute_time,
+    DBusNotifyReader, NotificationSource, OsNotification,
+};
+use screenpipe_vision::privacy::is_app_allowed;
+use serde::Serialize;
+use serde_json::json;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteRow};
+use sqlx::{ConnectOptions, Row};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+
+/// How often the databases of the notification centers are read, dbus sends them as shown
+pub const NOTIFICATION_POLL_INTERVAL: Duration = Duration::from_secs(5);
+/// dbus-monitor is started again after this long when it stops
+const DBUS_RESTART_DELAY: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Notification {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    /// As the platform names the app: its name on Linux, its bundle id on macOS and its app
+    /// user model id on Windows
+    pub app_name: String,
+    pub title: String,
+    pub body: Option<String>,
+    pub source: String,
+}
+
+/// Stores the notifications but the ones shown while capture was paused and the ones of apps
+/// that aren't captured, and emits "notification.received" for each new one. Returns how
+/// many were new
+pub async fn record_notifications(
+    db: &DatabaseManager,
+    notifications: &[OsNotification],
+    pauses: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> Result<u64> {
+    let mut stored = 0;
+    for notification in notifications {
+        let paused = pauses
+            .iter()
+            .any(|(start, end)| notification.timestamp >= *start && notification.timestamp <= *end);
+        if paused || !is_app_allowed(&notification.app) {
+            continue;
+        }
+        let Some(id) = db.insert_notification(notification).await? else {
+            continue;
+        };
+        stored += 1;
+        debug!("Recorded a notification of {}", notification.app);
+        emit_event(
+            "notification.received",
+            json!(Notification {
+                id,
+                timestamp: notification.timestamp,
+                app_name: notification.app.clone(),
+                title: notification.title.clone(),
+                body: notification.body.clone(),
+                source: notification.source.as_str().to_string(),
+            }),
+        );
+    }
+    Ok(stored)
+}
+
+/// The toasts the action center received after `since`, oldest first
+pub async fn read_action_center(path: &Path, since: DateTime<Utc>) -> Result<Vec<OsNotification>> {
+    let mut connection = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| format!("failed to open {}", path.display()))?;
+    let rows: Vec<SqliteRow> = sqlx::query(
+        r#"
+        SELECT n.ArrivalTime, h.PrimaryId, n.Payload
+        FROM Notification n
+        JOIN NotificationHandler h ON h.RecordId = n.HandlerId
+        WHERE n.Type = 'toast' AND n.ArrivalTime > ?1
+        ORDER BY n.ArrivalTime
+        "#,
+    )
+    .bind(to_filetime(since))
+    .fetch_all(&mut connection)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|row| {
+            let payload: Vec<u8> = row.get("Payload");
+            let (title, body) = parse_toast_payload(&String::from_utf8_lossy(&payload))?;
+            Some(OsNotification {
+                timestamp: from_filetime(row.get("ArrivalTime"))?,
+                app: row.get("PrimaryId"),
+                title,
+                body,
+                source: NotificationSource::ActionCenter,
+            })
+        })
+        .collect())
+}
+
+/// The notifications the notification center delivered after `since`, oldest first. Reading
+/// its database needs Full Disk Access
+pub async fn read_notification_center(
+    path: &Path,
+    since: DateTime<Utc>,
+) -> Result<Vec<OsNotification>> {
+    let mut connection = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .with_context(|| {
+            format!(
+                "failed to open {}, is Full Disk Access granted?",
+                path.display()
+            )
+        })?;
+    let rows: Vec<SqliteRow> = sqlx::query(
+        r#"
+        SELECT r.delivered_date, a.identifier, r.data
+        FROM record r
+        JOIN app a ON a.app_id = r.app_id
+        WHERE r.delivered_date > ?1
+        ORDER BY r.delivered_date
+        "#,
+    )
+    .bind(to_mac_absolute_time(since))
+    .fetch_all(&mut connection)
+    .await?;
+    let mut notifications = Vec::new();
+    for row in rows {
+        let Some(timestamp) = from_mac_absolute_time(row.get("delivered_date")) else {
+            continue;
+        };
+        let data: Vec<u8> = row.get("data");
+        let Some((title, body)) = parse_notification_plist(&plist_to_xml(&data).await?) else {
+            continue;
+        };
+        notifications.push(OsNotification {
+            timestamp,
+            app: row.get("identifier"),
+            title,
+            body,
+            source: NotificationSource::NotificationCenter,
+        });
+    }
+    Ok(notifications)
+}
+
+/// The records are binary plists, plutil converts them
+async fn plist_to_xml(data: &[u8]) -> Result<String> {
+    let mut child = Command::new("plutil")
+        .args(["-convert", "xml1", "-o", "-", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let mut stdin = child.stdin.take().context("no stdin for plutil")?;
+    stdin.write_all(data).await?;
+    drop(stdin);
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        anyhow::bail!("plutil failed to convert a notification record");
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Stores the Notify calls of the session bus as dbus-monitor prints them
+async fn watch_dbus(db: &DatabaseManager, capture_pause: &CapturePause) -> Result<()> {
+    let mut child = Command::new("dbus-monitor")
+        .args([
+            "--session",
+            "interface='org.freedesktop.Notifications',member='Notify'",
+        ])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .context("failed to start dbus-monitor, is it installed?")?;
+    let stdout = child.stdout.take().context("no output of dbus-monitor")?;
+    let mut lines = BufReader::new(stdout).lines();
+    let mut reader = DBusNotifyReader::default();
+    while let Some(line) = lines.next_line().await? {
+        let Some(notification) = reader.push_line(&line) else {
+            continue;
+        };
+        if capture_pause.is_paused() {
+            continue;
+        }
+        if let Err(e) = record_notifications(db, &[notification], &[]).await {
+            warn!("Failed to record a notification: {}", e);
+        }
+    }
+    anyhow::bail!("dbus-monitor stopped")
+}
+
+/// Reads the database of the notification center every `interval`, from the last
+/// notification stored, or from now the first time
+async fn poll_notifications(
+    db: &DatabaseManager,
+    source: NotificationSource,
+    path: &Path,
+    capture_pause: &CapturePause,
+    interval: Duration,
+) {
+    let mut since = match db.get_latest_notification_time(source).await {
+        Ok(latest) => latest.unwrap_or_else(Utc::now),
+        Err(e) => {
+            warn!("Failed to read the last notification: {}", e);
+            Utc::now()
+        }
+    };
+    let mut pause_log = PauseLog::new(interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if pause_log.observe(capture_pause) {
+            continue;
+        }
+        let notifications = match source {
+            NotificationSource::ActionCenter => read_action_center(path, since).await,
+            _ => read_notification_center(path, since).await,
+        };
+        let notifications = match notifications {
+            Ok(notifications) => notifications,
+            Err(e) => {
+                warn!("Failed to read notifications: {:#}", e);
+                continue;
+            }
+        };
+        let Some(last) = notifications.last() else {
+            continue;
+        };
+        since = last.timestamp;
+        match record_notifications(db, &notifications, pause_log.pauses()).await {
+            Ok(0) => {}
+            Ok(stored) => debug!("Recorded {} notifications", stored),
+            Err(e) => warn!("Failed to record notifications: {}", e),
+        }
+    }
+}
+
+/// Where the notification center of the platform keeps them, None for dbus
+fn notification_db(source: NotificationSource) -> Option<PathBuf> {
+    match source {
+        NotificationSource::DBus => None,
+        NotificationSource::ActionCenter => {
+            dirs::data_local_dir().map(|dir| action_center_db(&dir))
+        }
+        NotificationSource::NotificationCenter => {
+            // the per user temporary directory the older versions keep it in
+            let darwin_user_dir = std::process::Command::new("getconf")
+                .arg("DARWIN_USER_DIR")
+                .output()
+                .ok()
+                .map(|output| PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()));
+            notification_center_dbs(&dirs::home_dir()?, darwin_user_dir.as_deref())
+                .into_iter()
+                .find(|db| db.exists())
+        }
+    }
+}
+
+/// Records the notifications the OS shows, as it shows them on Linux and from the database of
+/// the notification center on macOS and Windows
+pub async fn start_notification_capture(
+    db: Arc<DatabaseManager>,
+    capture_pause: Arc<CapturePause>,
+    interval: Duration,
+) {
+    let Some(source) = NotificationSource::current() else {
+        warn!("Notifications can't be read on this platform");
+        return;
+    };
+    if source == NotificationSource::DBus {
+        info!("Recording notifications from the session bus");
+        loop {
+            if let Err(e) = watch_dbus(&db, &capture_pause).await {
+                warn!("Stopped reading notifications: {:#}", e);
+            }
+            tokio::time::sleep(DBUS_RESTART_DELAY).await;
+        }
+    }
+    let Some(path) = notification_db(source) else {
+        warn!("No notification database found, notifications aren't recorded");
+        return;
+    };
+    info!("Recording notifications from {}", path.display());
+    poll_notifications(&db, source, &path, &capture_pause, interval).await;
+}
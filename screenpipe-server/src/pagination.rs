@@ -0,0 +1,85 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Pages asked for with a bigger limit are cut to this many results
+pub const MAX_PAGE_SIZE: u32 = 1000;
+
+/// Sort key of the last item of a page in one source, items are ordered by time then id,
+/// newest first
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CursorPosition {
+    pub timestamp: DateTime<Utc>,
+    pub id: i64,
+}
+
+/// Where a page stopped in each source of its results, like the OCR text and the transcripts
+/// of a search. Opaque to the clients, they send `next_cursor` back as is
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Cursor(BTreeMap<String, CursorPosition>);
+
+impl Cursor {
+    pub fn single(source: &str, position: CursorPosition) -> Self {
+        let mut cursor = Cursor::default();
+        cursor.set(source, position);
+        cursor
+    }
+
+    /// None when the page starts at the newest item of `source`
+    pub fn get(&self, source: &str) -> Option<&CursorPosition> {
+        self.0.get(source)
+    }
+
+    pub fn set(&mut self, source: &str, position: CursorPosition) {
+        self.0.insert(source.to_string(), position);
+    }
+
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(&self.0).unwrap_or_default())
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self> {
+        let json = URL_SAFE_NO_PAD
+            .decode(cursor)
+            .context("cursor is not base64")?;
+        Ok(Cursor(
+            serde_json::from_slice(&json).context("cursor is not valid")?,
+        ))
+    }
+}
+
+/// A page of results, newest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    /// None on the last page
+    pub next_cursor: Option<String>,
+    /// Of every page, new results recorded while paging aren't counted
+    pub total: i64,
+}
+
+impl<T> Page<T> {
+    /// The page of a single source, a full page is followed by another one starting after its
+    /// last item
+    pub fn from_source(
+        data: Vec<T>,
+        limit: u32,
+        total: i64,
+        source: &str,
+        position: impl Fn(&T) -> CursorPosition,
+    ) -> Self {
+        let next_cursor = match data.last() {
+            Some(last) if data.len() >= limit as usize => {
+                Some(Cursor::single(source, position(last)).encode())
+            }
+            _ => None,
+        };
+        Page {
+            data,
+            next_cursor,
+            total,
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use log::info;
+use screenpipe_core::emit_event;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PauseStatus {
+    pub paused: bool,
+    /// Set when the pause ends on its own
+    pub resume_at: Option<DateTime<Utc>>,
+    /// Capture can't be resumed before the user consents to the compliance policy
+    pub awaiting_consent: bool,
+}
+
+/// Privacy pause of all capture, screen and audio stop until resumed
+pub struct CapturePause {
+    paused: Arc<AtomicBool>,
+    resume_at: Mutex<Option<DateTime<Utc>>>,
+    /// Bumped on every pause and resume so an outdated auto resume does nothing
+    generation: AtomicU64,
+    awaiting_consent: AtomicBool,
+}
+
+impl CapturePause {
+    pub fn new() -> Self {
+        CapturePause {
+            paused: Arc::new(AtomicBool::new(false)),
+            resume_at: Mutex::new(None),
+            generation: AtomicU64::new(0),
+            awaiting_consent: AtomicBool::new(false),
+        }
+    }
+
+    /// Flag read by the screen capture loop
+    pub fn flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.paused)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> PauseStatus {
+        PauseStatus {
+            paused: self.is_paused(),
+            resume_at: *self.resume_at.lock().unwrap(),
+            awaiting_consent: self.awaiting_consent.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Pausing again while paused replaces the auto resume
+    pub fn pause(self: &Arc<Self>, duration: Option<Duration>) -> PauseStatus {
+        let generation = self.generation.fetch_add(1, Ordering::SeqCst) + 1;
+        let resume_at = duration.and_then(|duration| {
+            chrono::Duration::from_std(duration)
+                .ok()
+                .map(|duration| Utc::now() + duration)
+        });
+        *self.resume_at.lock().unwrap() = resume_at;
+        self.paused.store(true, Ordering::SeqCst);
+        info!("Capture paused until {:?}", resume_at);
+        emit_event("capture.paused", json!({"resume_at": resume_at}));
+
+        if let Some(duration) = duration {
+            let pause = Arc::clone(self);
+            tokio::spawn(async move {
+                tokio::time::sleep(duration).await;
+                if pause.generation.load(Ordering::SeqCst) == generation {
+                    pause.resume();
+                }
+            });
+        }
+        self.status()
+    }
+
+    /// Does nothing while consent is awaited
+    pub fn resume(&self) -> PauseStatus {
+        if self.awaiting_consent.load(Ordering::SeqCst) {
+            return self.status();
+        }
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.resume_at.lock().unwrap() = None;
+        if self.paused.swap(false, Ordering::SeqCst) {
+            info!("Capture resumed");
+            emit_event("capture.resumed", json!({}));
+        }
+        self.status()
+    }
+
+    /// Stops capture until `give_consent`, pauses and resumes in between don't start it
+    pub fn require_consent(self: &Arc<Self>) -> PauseStatus {
+        self.awaiting_consent.store(true, Ordering::SeqCst);
+        self.pause(None)
+    }
+
+    pub fn give_consent(&self) -> PauseStatus {
+        self.awaiting_consent.store(false, Ordering::SeqCst);
+        self.resume()
+    }
+}
+
+impl Default for CapturePause {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The pauses seen by a task looking at the pause every `interval`, to leave out what happened
+/// during them of the files it reads once capture is resumed
+pub struct PauseLog {
+    interval: Duration,
+    paused_at: Option<DateTime<Utc>>,
+    pauses: Vec<(DateTime<Utc>, DateTime<Utc>)>,
+}
+
+impl PauseLog {
+    pub fn new(interval: Duration) -> Self {
+        PauseLog {
+            interval,
+            paused_at: None,
+            pauses: Vec::new(),
+        }
+    }
+
+    /// Returns whether capture is paused, a pause is logged once it's over
+    pub fn observe(&mut self, capture_pause: &CapturePause) -> bool {
+        if capture_pause.is_paused() {
+            self.paused_at.get_or_insert_with(Utc::now);
+            return true;
+        }
+        if let Some(paused_at) = self.paused_at.take() {
+            // the pause may have started up to an interval before it was seen
+            let since = paused_at - chrono::Duration::from_std(self.interval).unwrap_or_default();
+            self.pauses.push((since, Utc::now()));
+        }
+        false
+    }
+
+    pub fn pauses(&self) -> &[(DateTime<Utc>, DateTime<Utc>)] {
+        &self.pauses
+    }
+}
@@ -0,0 +1,233 @@
+use anyhow::Result;
+use image::codecs::jpeg::JpegEncoder;
+use image::DynamicImage;
+use log::debug;
+use screenpipe_vision::perform_ocr_tesseract_words;
+use std::io::Write;
+
+const JPEG_QUALITY: u8 = 90;
+/// Points per pixel, the pages are at 96 dpi
+const POINTS_PER_PIXEL: f64 = 0.75;
+/// Width of a character of Helvetica out of its size, on average
+const HELVETICA_CHAR_WIDTH: f64 = 0.5;
+/// Of the text laid down the page when the OCR finds no words, in pixels
+const MIN_LINE_HEIGHT: f64 = 8.0;
+
+/// A word of the text layer and its box in the image, in pixels from the top left corner
+#[derive(Debug, Clone, PartialEq)]
+pub struct PdfWord {
+    pub text: String,
+    pub left: f64,
+    pub top: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// A page of the PDF, an image with its words laid invisibly over it so the PDF can be searched
+/// and its text copied
+pub struct PdfPage {
+    pub jpeg: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    pub words: Vec<PdfWord>,
+}
+
+pub(crate) fn encode_jpeg(image: &DynamicImage) -> Result<Vec<u8>> {
+    let mut jpeg = Vec::new();
+    JpegEncoder::new_with_quality(&mut jpeg, JPEG_QUALITY).encode_image(&image.to_rgb8())?;
+    Ok(jpeg)
+}
+
+/// The lines of `text` down the left side of a page, for the images whose words have no boxes
+pub fn text_lines_as_words(text: &str, width: u32, height: u32) -> Vec<PdfWord> {
+    let line_height = (height as f64 / 40.0).max(MIN_LINE_HEIGHT);
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .enumerate()
+        .map(|(index, line)| PdfWord {
+            text: line.to_string(),
+            left: line_height,
+            top: index as f64 * line_height * 1.5,
+            width: line.chars().count() as f64 * line_height * HELVETICA_CHAR_WIDTH,
+            height: line_height,
+        })
+        .take_while(|word| word.top + word.height <= height as f64)
+        .filter(|word| word.left < width as f64)
+        .collect()
+}
+
+/// The words of the image where they are, read again as the stored OCR has no positions.
+/// Falls back to the lines of `text` when the OCR finds none. Blocks while reading
+pub fn text_layer(image: &DynamicImage, text: &str) -> Vec<PdfWord> {
+    let words = match perform_ocr_tesseract_words(image) {
+        Ok(words) => words,
+        Err(e) => {
+            debug!("Failed to read the words of the image again: {}", e);
+            Vec::new()
+        }
+    };
+    if words.is_empty() {
+        return text_lines_as_words(text, image.width(), image.height());
+    }
+    words
+        .into_iter()
+        .filter(|word| word.width > 0 && word.height > 0)
+        .map(|word| PdfWord {
+            text: word.text,
+            left: word.left as f64,
+            top: word.top as f64,
+            width: word.width as f64,
+            height: word.height as f64,
+        })
+        .collect()
+}
+
+/// A PDF string of the latin-1 characters of `text`, the standard fonts have no others
+fn pdf_string(text: &str) -> Vec<u8> {
+    let mut string = vec![b'('];
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => string.extend([b'\\', c as u8]),
+            c if (c as u32) < 0x20 => {}
+            c if (c as u32) <= 0xFF => string.push(c as u32 as u8),
+            _ => string.push(b'?'),
+        }
+    }
+    string.push(b')');
+    string
+}
+
+/// Draws the image over the page and lays each word over its box, stretched to its width, in
+/// render mode 3 so it's selectable but not drawn
+fn page_content(page: &PdfPage) -> Vec<u8> {
+    let (width, height) = (
+        page.width as f64 * POINTS_PER_PIXEL,
+        page.height as f64 * POINTS_PER_PIXEL,
+    );
+    let mut content = format!("q {:.2} 0 0 {:.2} 0 0 cm /Im0 Do Q\n", width, height).into_bytes();
+    let words: Vec<&PdfWord> = page
+        .words
+        .iter()
+        .filter(|word| !word.text.trim().is_empty())
+        .collect();
+    if words.is_empty() {
+        return content;
+    }
+    content.extend_from_slice(b"BT 3 Tr\n");
+    for word in words {
+        let size = word.height * POINTS_PER_PIXEL;
+        let natural_width = word.text.chars().count() as f64 * size * HELVETICA_CHAR_WIDTH;
+        let scale = (word.width * POINTS_PER_PIXEL / natural_width * 100.0).clamp(10.0, 1000.0);
+        // the baseline is a fifth of the box above its bottom, where the descenders end
+        let x = word.left * POINTS_PER_PIXEL;
+        let y = height - (word.top + word.height * 0.8) * POINTS_PER_PIXEL;
+        let _ = write!(
+            content,
+            "/F1 {:.2} Tf {:.2} Tz 1 0 0 1 {:.2} {:.2} Tm ",
+            size, scale, x, y
+        );
+        content.extend(pdf_string(word.text.trim()));
+        content.extend_from_slice(b" Tj\n");
+    }
+    content.extend_from_slice(b"ET\n");
+    content
+}
+
+/// A PDF of the pages, each the size of its image at 96 dpi
+pub fn pdf_document(title: &str, pages: &[PdfPage]) -> Vec<u8> {
+    let mut pdf: Vec<u8> = b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n".to_vec();
+    let mut offsets: Vec<usize> = Vec::new();
+    let mut object = |pdf: &mut Vec<u8>, body: &[u8]| {
+        offsets.push(pdf.len());
+        let _ = writeln!(pdf, "{} 0 obj", offsets.len());
+        pdf.extend_from_slice(body);
+        pdf.extend_from_slice(b"\nendobj\n");
+    };
+    // catalog, pages, font and info, then the page, image and content of each page
+    let page_ids: Vec<usize> = (0..pages.len()).map(|index| 5 + index * 3).collect();
+    object(&mut pdf, b"<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<String> = page_ids.iter().map(|id| format!("{} 0 R", id)).collect();
+    object(
+        &mut pdf,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .as_bytes(),
+    );
+    object(
+        &mut pdf,
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    );
+    let mut info = b"<< /Producer (screenpipe) /Title ".to_vec();
+    info.extend(pdf_string(title));
+    info.extend_from_slice(b" >>");
+    object(&mut pdf, &info);
+
+    for (page, page_id) in pages.iter().zip(&page_ids) {
+        object(
+            &mut pdf,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> /Font << /F1 3 0 R >> >> \
+                 /Contents {} 0 R >>",
+                page.width as f64 * POINTS_PER_PIXEL,
+                page.height as f64 * POINTS_PER_PIXEL,
+                page_id + 1,
+                page_id + 2
+            )
+            .as_bytes(),
+        );
+        let mut image = format!(
+            "<< /Type /XObject /Subtype /Image /Width {} /Height {} /ColorSpace /DeviceRGB \
+             /BitsPerComponent 8 /Filter /DCTDecode /Length {} >>\nstream\n",
+            page.width,
+            page.height,
+            page.jpeg.len()
+        )
+        .into_bytes();
+        image.extend_from_slice(&page.jpeg);
+        image.extend_from_slice(b"\nendstream");
+        object(&mut pdf, &image);
+
+        let content = page_content(page);
+        let mut stream = format!("<< /Length {} >>\nstream\n", content.len()).into_bytes();
+        stream.extend(content);
+        stream.extend_from_slice(b"\nendstream");
+        object(&mut pdf, &stream);
+    }
+
+    let xref = pdf.len();
+    let _ = write!(pdf, "xref\n0 {}\n0000000000 65535 f \n", offsets.len() + 1);
+    for offset in &offsets {
+        let _ = writeln!(pdf, "{:010} 00000 n ", offset);
+    }
+    let _ = write!(
+        pdf,
+        "trailer\n<< /Size {} /Root 1 0 R /Info 4 0 R >>\nstartxref\n{}\n%%EOF\n",
+        offsets.len() + 1,
+        xref
+    );
+    pdf
+}
+
+/// The page of an image with its text layer, read off the async runtime. `jpeg` is the image
+/// already encoded, when it's stored as one
+pub async fn ocr_page(image: DynamicImage, jpeg: Option<Vec<u8>>, text: String) -> Result<PdfPage> {
+    tokio::task::spawn_blocking(move || {
+        let words = text_layer(&image, &text);
+        let jpeg = match jpeg {
+            Some(jpeg) => jpeg,
+            None => encode_jpeg(&image)?,
+        };
+        Ok(PdfPage {
+            jpeg,
+            width: image.width(),
+            height: image.height(),
+            words,
+        })
+    })
+    .await?
+}
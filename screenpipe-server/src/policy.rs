@@ -0,0 +1,657 @@
+use crate::clips::{apply_redaction, text_boxes, RedactionBox, RedactionStyle};
+use anyhow::{bail, Result};
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
+use log::{debug, warn};
+use regex::{Regex, RegexBuilder};
+use screenpipe_core::{redact_matches, PiiEntityKind, PiiMatch, PiiRedactor, SecretKind};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::{perform_ocr_tesseract_words, CaptureResult};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+/// Put instead of the keywords a rule redacts
+const KEYWORD_PLACEHOLDER: &str = "[REDACTED]";
+/// Entity type the keywords of a rule are annotated with
+const KEYWORD_ENTITY: &str = "keyword";
+
+/// What happens to the content a rule matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyAction {
+    /// The frame is neither encoded nor its text stored
+    DropFrame,
+    /// The matched entities and keywords are replaced by placeholders in the stored text
+    RedactText,
+    /// The window is blurred in the stored frame and its text isn't stored
+    BlurRegion,
+    /// Like `redact_text`, and the words are also blurred in the stored frame
+    BlurText,
+    /// The transcription isn't stored and its audio chunk is deleted
+    SkipAudio,
+}
+
+impl PolicyAction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PolicyAction::DropFrame => "drop_frame",
+            PolicyAction::RedactText => "redact_text",
+            PolicyAction::BlurRegion => "blur_region",
+            PolicyAction::BlurText => "blur_text",
+            PolicyAction::SkipAudio => "skip_audio",
+        }
+    }
+}
+
+impl FromStr for PolicyAction {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "drop_frame" => Ok(PolicyAction::DropFrame),
+            "redact_text" => Ok(PolicyAction::RedactText),
+            "blur_region" => Ok(PolicyAction::BlurRegion),
+            "blur_text" => Ok(PolicyAction::BlurText),
+            "skip_audio" => Ok(PolicyAction::SkipAudio),
+            _ => bail!("unknown policy action: {}", s),
+        }
+    }
+}
+
+/// What the pattern and NER detectors find
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DetectedEntity {
+    Email,
+    CreditCard,
+    Ssn,
+    Person,
+    Organization,
+    Location,
+    Misc,
+    /// Card numbers passing the Luhn check, `credit_card` is any 16 digits
+    CardNumber,
+    AwsKey,
+    Jwt,
+    PrivateKey,
+}
+
+impl DetectedEntity {
+    /// Placeholder the detectors label their matches with
+    fn label(&self) -> &'static str {
+        match self {
+            DetectedEntity::Email => "[EMAIL]",
+            DetectedEntity::CreditCard => "[CREDIT_CARD]",
+            DetectedEntity::Ssn => "[SSN]",
+            DetectedEntity::CardNumber => SecretKind::CardNumber.placeholder(),
+            DetectedEntity::AwsKey => SecretKind::AwsKey.placeholder(),
+            DetectedEntity::Jwt => SecretKind::Jwt.placeholder(),
+            DetectedEntity::PrivateKey => SecretKind::PrivateKey.placeholder(),
+            _ => self
+                .ner_kind()
+                .map(|kind| kind.placeholder())
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Entity type of the NER model, `None` for the ones found by patterns
+    pub fn ner_kind(&self) -> Option<PiiEntityKind> {
+        match self {
+            DetectedEntity::Person => Some(PiiEntityKind::Person),
+            DetectedEntity::Organization => Some(PiiEntityKind::Organization),
+            DetectedEntity::Location => Some(PiiEntityKind::Location),
+            DetectedEntity::Misc => Some(PiiEntityKind::Misc),
+            _ => None,
+        }
+    }
+}
+
+/// Entity type of a match, the name of its `DetectedEntity` for the built in detectors
+fn entity_type(label: &str) -> String {
+    if label == KEYWORD_PLACEHOLDER {
+        return KEYWORD_ENTITY.to_string();
+    }
+    label
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .to_lowercase()
+}
+
+impl From<PiiEntityKind> for DetectedEntity {
+    fn from(kind: PiiEntityKind) -> Self {
+        match kind {
+            PiiEntityKind::Person => DetectedEntity::Person,
+            PiiEntityKind::Organization => DetectedEntity::Organization,
+            PiiEntityKind::Location => DetectedEntity::Location,
+            PiiEntityKind::Misc => DetectedEntity::Misc,
+        }
+    }
+}
+
+/// A rule as written in the policy file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyRuleConfig {
+    #[serde(default)]
+    name: String,
+    #[serde(default)]
+    apps: Vec<String>,
+    window_title: Option<String>,
+    url: Option<String>,
+    #[serde(default)]
+    entities: Vec<DetectedEntity>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    action: PolicyAction,
+}
+
+/// Matchers left out match everything, the ones given must all match
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "PolicyRuleConfig")]
+pub struct PolicyRule {
+    pub name: String,
+    /// App names, case insensitive
+    apps: Vec<String>,
+    window_title: Option<Regex>,
+    url: Option<Regex>,
+    /// The text has to contain one of them. A rule redacting text without any
+    /// removes everything the detectors find
+    entities: Vec<DetectedEntity>,
+    keywords: Option<Regex>,
+    pub action: PolicyAction,
+}
+
+fn case_insensitive(pattern: &str) -> Result<Regex> {
+    Ok(RegexBuilder::new(pattern).case_insensitive(true).build()?)
+}
+
+impl TryFrom<PolicyRuleConfig> for PolicyRule {
+    type Error = anyhow::Error;
+
+    fn try_from(config: PolicyRuleConfig) -> Result<Self> {
+        let keywords: Vec<String> = config
+            .keywords
+            .iter()
+            .map(|keyword| keyword.trim())
+            .filter(|keyword| !keyword.is_empty())
+            .map(regex::escape)
+            .collect();
+        Ok(PolicyRule {
+            name: config.name,
+            apps: config.apps,
+            window_title: config
+                .window_title
+                .as_deref()
+                .map(case_insensitive)
+                .transpose()?,
+            url: config.url.as_deref().map(case_insensitive).transpose()?,
+            entities: config.entities,
+            keywords: (!keywords.is_empty())
+                .then(|| case_insensitive(&keywords.join("|")))
+                .transpose()?,
+            action: config.action,
+        })
+    }
+}
+
+impl PolicyRule {
+    /// Applies to the given entities everywhere, the rules of `--redact-pii` and `--blur-secrets`
+    pub fn for_entities(name: &str, entities: Vec<DetectedEntity>, action: PolicyAction) -> Self {
+        PolicyRule {
+            name: name.to_string(),
+            apps: Vec::new(),
+            window_title: None,
+            url: None,
+            entities,
+            keywords: None,
+            action,
+        }
+    }
+
+    /// Applies to the windows of the apps, or with a title matching `window_title`
+    pub fn for_windows(
+        name: &str,
+        apps: Vec<String>,
+        window_title: Option<&str>,
+        action: PolicyAction,
+    ) -> Result<Self> {
+        Ok(PolicyRule {
+            name: name.to_string(),
+            apps,
+            window_title: window_title.map(case_insensitive).transpose()?,
+            url: None,
+            entities: Vec::new(),
+            keywords: None,
+            action,
+        })
+    }
+
+    /// Entity types the NER model has to look for
+    pub fn ner_entities(&self) -> impl Iterator<Item = PiiEntityKind> + '_ {
+        self.entities.iter().filter_map(|entity| entity.ner_kind())
+    }
+
+    fn has_window_matchers(&self) -> bool {
+        !self.apps.is_empty() || self.window_title.is_some() || self.url.is_some()
+    }
+
+    /// URLs aren't captured, the pattern is looked for in the title, where browsers
+    /// usually put the page, and in the text, which has the address bar
+    fn matches_window(&self, app_name: &str, window_name: &str, text: &str) -> bool {
+        let app_matches = self.apps.is_empty()
+            || self
+                .apps
+                .iter()
+                .any(|app| app.eq_ignore_ascii_case(app_name));
+        let title_matches = self
+            .window_title
+            .as_ref()
+            .is_none_or(|title| title.is_match(window_name));
+        let url_matches = self
+            .url
+            .as_ref()
+            .is_none_or(|url| url.is_match(window_name) || url.is_match(text));
+        app_matches && title_matches && url_matches
+    }
+
+    /// Keywords and entities of the text the rule redacts
+    fn redactions(&self, text: &str, pii: &[PiiMatch]) -> (Vec<PiiMatch>, Vec<PiiMatch>) {
+        let keywords = self
+            .keywords
+            .iter()
+            .flat_map(|keywords| keywords.find_iter(text))
+            .map(|m| PiiMatch {
+                start: m.start(),
+                end: m.end(),
+                label: KEYWORD_PLACEHOLDER,
+            })
+            .collect();
+        let entities = pii
+            .iter()
+            .filter(|m| {
+                self.entities.is_empty() || self.entities.iter().any(|e| e.label() == m.label)
+            })
+            .cloned()
+            .collect();
+        (keywords, entities)
+    }
+
+    /// How many of each entity type the rule redacts in the text
+    fn entity_counts(&self, text: &str, pii: &[PiiMatch]) -> BTreeMap<String, usize> {
+        let (keywords, entities) = self.redactions(text, pii);
+        let mut counts = BTreeMap::new();
+        for found in keywords.iter().chain(&entities) {
+            *counts.entry(entity_type(found.label)).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Whether the text has the keywords and entities the rule asks for
+    fn matches_content(&self, text: &str, pii: &[PiiMatch]) -> bool {
+        let (keywords, entities) = self.redactions(text, pii);
+        (self.keywords.is_none() || !keywords.is_empty())
+            && (self.entities.is_empty() || !entities.is_empty())
+    }
+
+    fn needs_detectors(&self) -> bool {
+        !self.entities.is_empty()
+            || matches!(
+                self.action,
+                PolicyAction::RedactText | PolicyAction::BlurText
+            )
+    }
+}
+
+#[derive(Deserialize)]
+struct PolicyFile {
+    rules: Vec<PolicyRule>,
+}
+
+/// Reads rules from a json file like
+/// `{"rules": [{"apps": ["1Password"], "action": "drop_frame"}, {"keywords": ["salary"], ...}]}`
+pub fn load_policy_rules(path: &Path) -> Result<Vec<PolicyRule>> {
+    let file: PolicyFile = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+    Ok(file.rules)
+}
+
+/// What was captured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactionSource {
+    Screen,
+    Audio,
+}
+
+impl RedactionSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RedactionSource::Screen => "screen",
+            RedactionSource::Audio => "audio",
+        }
+    }
+}
+
+impl FromStr for RedactionSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "screen" => Ok(RedactionSource::Screen),
+            "audio" => Ok(RedactionSource::Audio),
+            _ => bail!("unknown redaction source: {}", s),
+        }
+    }
+}
+
+/// What a rule took out of a frame or a transcription, without the content itself, so audits
+/// can tell why something is missing from the record. Window titles are left out too, the
+/// title of a blurred or dropped window can be what the rule hides
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RedactionAnnotation {
+    pub source: RedactionSource,
+    pub rule: String,
+    pub action: PolicyAction,
+    /// App of the window, `None` for transcriptions
+    pub app_name: Option<String>,
+    /// How many of each entity type were found, "keyword" for the rule's keywords
+    pub entities: BTreeMap<String, usize>,
+    /// Blurred regions, in pixels of the frame
+    pub regions: Vec<RedactionBox>,
+}
+
+/// An annotation as stored, frames are matched to it by time and app
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RedactionRecord {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    pub annotation: RedactionAnnotation,
+    pub audio_file: Option<String>,
+}
+
+impl RedactionAnnotation {
+    fn new(
+        source: RedactionSource,
+        rule: &PolicyRule,
+        app_name: Option<&str>,
+        entities: BTreeMap<String, usize>,
+    ) -> Self {
+        RedactionAnnotation {
+            source,
+            rule: rule.name.clone(),
+            action: rule.action,
+            app_name: app_name.map(str::to_string),
+            entities,
+            regions: Vec::new(),
+        }
+    }
+}
+
+/// Region of the frame covered by the window
+fn window_box(window: &WindowOcrResult) -> RedactionBox {
+    let (x, y) = window.position;
+    RedactionBox {
+        x: x.max(0) as u32,
+        y: y.max(0) as u32,
+        width: (window.image.width() as i64 + x.min(0) as i64).max(0) as u32,
+        height: (window.image.height() as i64 + y.min(0) as i64).max(0) as u32,
+    }
+}
+
+/// Moves a box of the window image to the frame, `None` if it is out of it
+fn offset_box(redaction: RedactionBox, (x, y): (i32, i32)) -> Option<RedactionBox> {
+    let left = redaction.x as i64 + x as i64;
+    let top = redaction.y as i64 + y as i64;
+    let width = redaction.width as i64 + left.min(0);
+    let height = redaction.height as i64 + top.min(0);
+    (width > 0 && height > 0).then(|| RedactionBox {
+        x: left.max(0) as u32,
+        y: top.max(0) as u32,
+        width: width as u32,
+        height: height as u32,
+    })
+}
+
+/// Rules applied to what is captured before it is stored
+pub struct RedactionPolicy {
+    rules: Vec<PolicyRule>,
+    detector: Arc<PiiRedactor>,
+    /// App and title of the windows on the last frame, audio rules match against them
+    visible_windows: Mutex<Vec<(String, String)>>,
+}
+
+impl RedactionPolicy {
+    /// The detector can be shared with the consumer redaction, the NER model is big
+    pub fn new(rules: Vec<PolicyRule>, detector: impl Into<Arc<PiiRedactor>>) -> Self {
+        RedactionPolicy {
+            rules,
+            detector: detector.into(),
+            visible_windows: Mutex::new(Vec::new()),
+        }
+    }
+
+    pub fn rules(&self) -> &[PolicyRule] {
+        &self.rules
+    }
+
+    pub fn detector(&self) -> &PiiRedactor {
+        &self.detector
+    }
+
+    fn find_pii(&self, text: &str) -> Vec<PiiMatch> {
+        if self.rules.iter().any(PolicyRule::needs_detectors) {
+            self.detector.find(text)
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Placeholders instead of what the rules match in the text
+    fn redact(&self, text: &str, pii: &[PiiMatch], rules: &[&PolicyRule]) -> String {
+        let matches: Vec<PiiMatch> = rules
+            .iter()
+            .flat_map(|rule| {
+                let (keywords, entities) = rule.redactions(text, pii);
+                keywords.into_iter().chain(entities)
+            })
+            .collect();
+        redact_matches(text, &matches)
+    }
+
+    /// The text of the window and the lines of its json, which hold the same text
+    fn redact_window(&self, window: &mut WindowOcrResult, pii: &[PiiMatch], rules: &[&PolicyRule]) {
+        window.text = self.redact(&window.text, pii, rules);
+        for line in window.text_json.iter_mut() {
+            if let Some(text) = line.get_mut("text") {
+                *text = self.redact(text, &self.find_pii(text), rules);
+            }
+        }
+    }
+
+    /// Where the words each rule matches are in the frame. The OCR output has no word
+    /// boxes so the window is read again, it's wholly blurred if that fails
+    fn word_boxes(
+        &self,
+        window: &WindowOcrResult,
+        rules: &[&PolicyRule],
+    ) -> Vec<Vec<RedactionBox>> {
+        let words = match perform_ocr_tesseract_words(&window.image) {
+            Ok(words) => words,
+            Err(e) => {
+                warn!(
+                    "Failed to locate text in window {}, blurring all of it: {}",
+                    window.window_name, e
+                );
+                return vec![vec![window_box(window)]; rules.len()];
+            }
+        };
+        rules
+            .iter()
+            .map(|rule| {
+                text_boxes(&words, |line| {
+                    let (keywords, entities) = rule.redactions(line, &self.find_pii(line));
+                    keywords.into_iter().chain(entities).collect()
+                })
+                .into_iter()
+                .filter_map(|redaction| offset_box(redaction, window.position))
+                .collect()
+            })
+            .collect()
+    }
+
+    /// The frame as it can be stored, `None` if it has to be dropped.
+    /// Runs the NER model, call it off the async threads
+    pub fn apply_to_frame(&self, frame: CaptureResult) -> Option<CaptureResult> {
+        self.annotate_frame(frame).0
+    }
+
+    /// Like `apply_to_frame`, with what each rule took out of the frame
+    pub fn annotate_frame(
+        &self,
+        mut frame: CaptureResult,
+    ) -> (Option<CaptureResult>, Vec<RedactionAnnotation>) {
+        *self.visible_windows.lock().unwrap() = frame
+            .window_ocr_results
+            .iter()
+            .map(|w| (w.app_name.clone(), w.window_name.clone()))
+            .collect();
+
+        let mut blurred = Vec::new();
+        let mut annotations = Vec::new();
+        let mut windows = Vec::with_capacity(frame.window_ocr_results.len());
+        for mut window in std::mem::take(&mut frame.window_ocr_results) {
+            let pii = self.find_pii(&window.text);
+            let mut region_blurs = Vec::new();
+            let mut redactions = Vec::new();
+            let mut text_blurs = Vec::new();
+            for rule in self.rules.iter().filter(|rule| {
+                rule.matches_window(&window.app_name, &window.window_name, &window.text)
+                    && rule.matches_content(&window.text, &pii)
+            }) {
+                match rule.action {
+                    PolicyAction::DropFrame => {
+                        debug!(
+                            "Rule {} dropped frame {} showing {}",
+                            rule.name, frame.frame_number, window.app_name
+                        );
+                        let annotation = RedactionAnnotation::new(
+                            RedactionSource::Screen,
+                            rule,
+                            Some(&window.app_name),
+                            rule.entity_counts(&window.text, &pii),
+                        );
+                        return (None, vec![annotation]);
+                    }
+                    PolicyAction::BlurRegion => region_blurs.push(rule),
+                    PolicyAction::RedactText => redactions.push(rule),
+                    PolicyAction::BlurText => {
+                        redactions.push(rule);
+                        text_blurs.push(rule);
+                    }
+                    PolicyAction::SkipAudio => {}
+                }
+            }
+            let annotation = |rule: &PolicyRule| {
+                RedactionAnnotation::new(
+                    RedactionSource::Screen,
+                    rule,
+                    Some(&window.app_name),
+                    rule.entity_counts(&window.text, &pii),
+                )
+            };
+
+            // the text of a blurred window isn't stored, the other rules don't matter
+            if !region_blurs.is_empty() {
+                let region = window_box(&window);
+                blurred.push(region);
+                annotations.extend(region_blurs.into_iter().map(|rule| RedactionAnnotation {
+                    regions: vec![region],
+                    ..annotation(rule)
+                }));
+                continue;
+            }
+            let mut text_annotations: Vec<RedactionAnnotation> =
+                redactions.iter().map(|rule| annotation(rule)).collect();
+            if !text_blurs.is_empty() {
+                for (rule, boxes) in text_blurs.iter().zip(self.word_boxes(&window, &text_blurs)) {
+                    blurred.extend(boxes.iter().copied());
+                    if let Some(index) = redactions
+                        .iter()
+                        .position(|other| std::ptr::eq(*other, *rule))
+                    {
+                        text_annotations[index].regions = boxes;
+                    }
+                }
+            }
+            if !redactions.is_empty() {
+                self.redact_window(&mut window, &pii, &redactions);
+            }
+            annotations.extend(text_annotations.into_iter().filter(|annotation| {
+                !annotation.entities.is_empty() || !annotation.regions.is_empty()
+            }));
+            windows.push(window);
+        }
+
+        if !blurred.is_empty() {
+            frame.replace_image(DynamicImage::ImageRgba8(apply_redaction(
+                &frame.image,
+                &blurred,
+                RedactionStyle::Blur,
+            )));
+        }
+        frame.window_ocr_results = windows;
+        (Some(frame), annotations)
+    }
+
+    /// The transcription as it can be stored, `None` if the audio has to be dropped.
+    /// App and title matchers are checked against the windows on screen
+    pub fn apply_to_transcript(&self, transcript: &str) -> Option<String> {
+        self.annotate_transcript(transcript).0
+    }
+
+    /// Like `apply_to_transcript`, with what each rule took out of the transcription
+    pub fn annotate_transcript(
+        &self,
+        transcript: &str,
+    ) -> (Option<String>, Vec<RedactionAnnotation>) {
+        let windows = self.visible_windows.lock().unwrap().clone();
+        let pii = self.find_pii(transcript);
+        let mut redactions = Vec::new();
+        let mut annotations = Vec::new();
+        for rule in &self.rules {
+            let window_matches = !rule.has_window_matchers()
+                || windows
+                    .iter()
+                    .any(|(app_name, window_name)| rule.matches_window(app_name, window_name, ""));
+            if !window_matches || !rule.matches_content(transcript, &pii) {
+                continue;
+            }
+            let annotation = || {
+                RedactionAnnotation::new(
+                    RedactionSource::Audio,
+                    rule,
+                    None,
+                    rule.entity_counts(transcript, &pii),
+                )
+            };
+            match rule.action {
+                PolicyAction::SkipAudio => return (None, vec![annotation()]),
+                PolicyAction::RedactText | PolicyAction::BlurText => {
+                    let annotation = annotation();
+                    if !annotation.entities.is_empty() {
+                        annotations.push(annotation);
+                    }
+                    redactions.push(rule);
+                }
+                PolicyAction::DropFrame | PolicyAction::BlurRegion => {}
+            }
+        }
+        (
+            Some(self.redact(transcript, &pii, &redactions)),
+            annotations,
+        )
+    }
+}
@@ -0,0 +1,308 @@
+use crate::cli::CliOcrEngine;
+use anyhow::{anyhow, Context, Result};
+use clap::ValueEnum;
+use log::info;
+use screenpipe_audio::{set_stt_engine_override, AudioTranscriptionEngine};
+use screenpipe_core::emit_event;
+use screenpipe_vision::{set_capture_override, CaptureOverride, OcrEngine};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// In the screenpipe directory, next to profiles.json
+pub const POWER_PROFILES_FILE: &str = "power_profiles.json";
+
+static BACKGROUND_JOBS_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Checked by the compactor, integrity checker, subtitle muxer and the model jobs before
+/// each run
+pub fn background_jobs_paused() -> bool {
+    BACKGROUND_JOBS_PAUSED.load(Ordering::SeqCst)
+}
+
+/// Where speech is transcribed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SttLocation {
+    /// The whisper model loaded at start
+    Local,
+    /// Deepgram, falling back to whisper when it fails
+    Cloud,
+}
+
+/// How screenpipe records in a power state, unset fields keep what it started with
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PowerProfile {
+    /// Frames per second, never more than --fps
+    #[serde(default)]
+    pub fps: Option<f64>,
+    /// Like --ocr-engine
+    #[serde(default)]
+    pub ocr_engine: Option<String>,
+    #[serde(default)]
+    pub stt: Option<SttLocation>,
+    /// The compactor, integrity checker, subtitle muxer, activity classifier, meeting summaries
+    /// and extraction rules wait
+    #[serde(default)]
+    pub pause_background_jobs: bool,
+}
+
+fn default_low_battery_percent() -> u8 {
+    20
+}
+
+/// What power_profiles.json holds, like
+/// `{"battery": {"fps": 0.5, "stt": "cloud"}, "low_battery_percent": 15}`.
+/// The thermal profile comes first, then low battery, battery and ac
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PowerProfiles {
+    #[serde(default)]
+    pub ac: PowerProfile,
+    #[serde(default = "PowerProfiles::default_battery")]
+    pub battery: PowerProfile,
+    #[serde(default = "PowerProfiles::default_low_battery")]
+    pub low_battery: PowerProfile,
+    /// When the machine is hot enough to be throttled
+    #[serde(default = "PowerProfiles::default_low_battery")]
+    pub thermal: PowerProfile,
+    #[serde(default = "default_low_battery_percent")]
+    pub low_battery_percent: u8,
+}
+
+impl Default for PowerProfiles {
+    fn default() -> Self {
+        PowerProfiles {
+            ac: PowerProfile::default(),
+            battery: PowerProfiles::default_battery(),
+            low_battery: PowerProfiles::default_low_battery(),
+            thermal: PowerProfiles::default_low_battery(),
+            low_battery_percent: default_low_battery_percent(),
+        }
+    }
+}
+
+impl PowerProfiles {
+    fn default_battery() -> PowerProfile {
+        PowerProfile {
+            fps: Some(0.5),
+            pause_background_jobs: true,
+            ..Default::default()
+        }
+    }
+
+    fn default_low_battery() -> PowerProfile {
+        PowerProfile {
+            fps: Some(0.2),
+            pause_background_jobs: true,
+            ..Default::default()
+        }
+    }
+
+    /// The defaults when the file doesn't exist
+    pub fn load(base_dir: &Path) -> Result<Self> {
+        let path = base_dir.join(POWER_PROFILES_FILE);
+        if !path.exists() {
+            return Ok(PowerProfiles::default());
+        }
+        let profiles: PowerProfiles = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("invalid {}", path.display()))?;
+        for (name, profile) in profiles.all() {
+            profile
+                .ocr_engine()
+                .with_context(|| format!("invalid {} profile in {}", name, path.display()))?;
+        }
+        Ok(profiles)
+    }
+
+    fn all(&self) -> [(&'static str, &PowerProfile); 4] {
+        [
+            ("ac", &self.ac),
+            ("battery", &self.battery),
+            ("low_battery", &self.low_battery),
+            ("thermal", &self.thermal),
+        ]
+    }
+
+    /// Name and profile for the power state
+    pub fn select(&self, state: &PowerState) -> (&'static str, &PowerProfile) {
+        let low_battery = state
+            .battery_percent
+            .is_some_and(|percent| percent <= self.low_battery_percent);
+        if state.thermal_pressure {
+            ("thermal", &self.thermal)
+        } else if state.on_battery && low_battery {
+            ("low_battery", &self.low_battery)
+        } else if state.on_battery {
+            ("battery", &self.battery)
+        } else {
+            ("ac", &self.ac)
+        }
+    }
+}
+
+impl PowerProfile {
+    pub fn ocr_engine(&self) -> Result<Option<OcrEngine>> {
+        self.ocr_engine
+            .as_deref()
+            .map(|name| {
+                CliOcrEngine::from_str(name, true)
+                    .map(OcrEngine::from)
+                    .map_err(|_| anyhow!("unknown ocr engine {}", name))
+            })
+            .transpose()
+    }
+
+    /// Applies the profile to the running capture and transcription, `fps` is the one
+    /// screenpipe started with
+    pub fn apply(&self, fps: f64) {
+        let fps = self.fps.map(|profile_fps| profile_fps.min(fps));
+        set_capture_override(CaptureOverride {
+            interval: fps
+                .filter(|fps| *fps > 0.0)
+                .map(|fps| Duration::from_secs_f64(1.0 / fps)),
+            ocr_engine: self.ocr_engine().ok().flatten(),
+        });
+        set_stt_engine_override(self.stt.map(|stt| match stt {
+            SttLocation::Local => AudioTranscriptionEngine::WhisperTiny,
+            SttLocation::Cloud => AudioTranscriptionEngine::Deepgram,
+        }));
+        BACKGROUND_JOBS_PAUSED.store(self.pause_background_jobs, Ordering::SeqCst);
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PowerState {
+    pub on_battery: bool,
+    pub battery_percent: Option<u8>,
+    /// The cpu is hot enough to be throttled
+    pub thermal_pressure: bool,
+}
+
+/// Output of `pmset -g batt`
+pub fn parse_pmset_batt(output: &str) -> (bool, Option<u8>) {
+    let on_battery = output.contains("'Battery Power'");
+    let percent = output
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .find_map(|word| word.strip_suffix('%'))
+        .and_then(|percent| percent.parse().ok());
+    (on_battery, percent)
+}
+
+/// Output of `pmset -g therm`, the cpu is throttled when its speed is limited
+pub fn parse_pmset_therm(output: &str) -> bool {
+    output.lines().any(|line| {
+        line.split_once('=').is_some_and(|(key, value)| {
+            key.trim() == "CPU_Speed_Limit" && value.trim().parse::<u32>().is_ok_and(|v| v < 100)
+        })
+    })
+}
+
+#[cfg(target_os = "macos")]
+pub(crate) fn read_power_state() -> PowerState {
+    let pmset = |arg: &str| {
+        std::process::Command::new("pmset")
+            .args(["-g", arg])
+            .output()
+            .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+            .unwrap_or_default()
+    };
+    let (on_battery, battery_percent) = parse_pmset_batt(&pmset("batt"));
+    PowerState {
+        on_battery,
+        battery_percent,
+        thermal_pressure: parse_pmset_therm(&pmset("therm")),
+    }
+}
+
+/// Temperature of the hottest thermal zone from which laptops throttle
+#[cfg(target_os = "linux")]
+const THERMAL_PRESSURE_MILLIDEGREES: i64 = 90_000;
+
+#[cfg(target_os = "linux")]
+pub(crate) fn read_power_state() -> PowerState {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path)
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let mut state = PowerState::default();
+    let mut on_mains = false;
+    for entry in std::fs::read_dir("/sys/class/power_supply")
+        .into_iter()
+        .flatten()
+        .flatten()
+    {
+        let path = entry.path();
+        match read(&path.join("type")).as_str() {
+            "Mains" => on_mains |= read(&path.join("online")) == "1",
+            "Battery" => {
+                state.battery_percent = read(&path.join("capacity")).parse().ok();
+                state.on_battery |= read(&path.join("status")) == "Discharging";
+            }
+            _ => {}
+        }
+    }
+    state.on_battery &= !on_mains;
+    state.thermal_pressure = std::fs::read_dir("/sys/class/thermal")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| read(&entry.path().join("temp")).parse::<i64>().ok())
+        .any(|temp| temp >= THERMAL_PRESSURE_MILLIDEGREES);
+    state
+}
+
+#[cfg(target_os = "windows")]
+pub(crate) fn read_power_state() -> PowerState {
+    // BatteryStatus 1 is discharging
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-Command",
+            "Get-CimInstance Win32_Battery | Select-Object -First 1 | ForEach-Object { \"$($_.BatteryStatus) $($_.EstimatedChargeRemaining)\" }",
+        ])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).into_owned())
+        .unwrap_or_default();
+    let mut fields = output.split_whitespace();
+    PowerState {
+        on_battery: fields.next() == Some("1"),
+        battery_percent: fields.next().and_then(|percent| percent.parse().ok()),
+        thermal_pressure: false,
+    }
+}
+
+#[cfg(not(any(target_os = "macos", target_os = "linux", target_os = "windows")))]
+pub(crate) fn read_power_state() -> PowerState {
+    PowerState::default()
+}
+
+/// Switches power profiles as the machine goes on and off battery or heats up, `fps` is the
+/// one screenpipe started with
+pub async fn start_power_monitor(profiles: PowerProfiles, fps: f64, interval: Duration) {
+    info!("Power profiles started");
+    let mut current = None;
+    loop {
+        let state = tokio::task::spawn_blocking(read_power_state)
+            .await
+            .unwrap_or_default();
+        let (name, profile) = profiles.select(&state);
+        if current != Some(name) {
+            info!(
+                "Switching to the {} power profile (battery: {}, {:?}%, thermal pressure: {})",
+                name, state.on_battery, state.battery_percent, state.thermal_pressure
+            );
+            profile.apply(fps);
+            emit_event(
+                "power.profile_changed",
+                json!({"profile": name, "previous": current, "state": state}),
+            );
+            current = Some(name);
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
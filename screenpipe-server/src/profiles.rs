@@ -0,0 +1,142 @@
+use crate::policy::{PolicyAction, PolicyRule};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+
+/// In the screenpipe directory, next to the profiles' own directories
+pub const PROFILES_FILE: &str = "profiles.json";
+
+/// A separate store, like "work" or "personal", with what it records and how long it keeps it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Older data is deleted, kept forever if not set
+    #[serde(default)]
+    pub retention_days: Option<u64>,
+    /// Policy file of the profile, relative to the screenpipe directory, like --redaction-policy
+    #[serde(default)]
+    pub redaction_policy: Option<String>,
+    /// Apps never recorded in this profile, case insensitive
+    #[serde(default)]
+    pub blocked_apps: Vec<String>,
+    /// Case insensitive patterns of window titles never recorded, where browsers put the page
+    #[serde(default)]
+    pub blocked_windows: Vec<String>,
+}
+
+impl Profile {
+    /// Frames of the blocked apps and windows are dropped before anything is stored
+    pub fn blocklist_rules(&self, name: &str) -> Result<Vec<PolicyRule>> {
+        let mut rules = Vec::new();
+        if !self.blocked_apps.is_empty() {
+            rules.push(PolicyRule::for_windows(
+                &format!("profile {} blocked apps", name),
+                self.blocked_apps.clone(),
+                None,
+                PolicyAction::DropFrame,
+            )?);
+        }
+        for pattern in &self.blocked_windows {
+            rules.push(PolicyRule::for_windows(
+                &format!("profile {} blocked window {}", name, pattern),
+                Vec::new(),
+                Some(pattern),
+                PolicyAction::DropFrame,
+            )?);
+        }
+        Ok(rules)
+    }
+}
+
+/// What profiles.json holds, like
+/// `{"profiles": {"work": {"retention_days": 365}, "personal": {"blocked_apps": ["Slack"]}},
+/// "sessions": {"alice": "personal"}}`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ProfilesConfig {
+    #[serde(default)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// OS login names and the profile their sessions record to when no --profile is given
+    #[serde(default)]
+    pub sessions: HashMap<String, String>,
+}
+
+impl ProfilesConfig {
+    /// No profiles when the file doesn't exist
+    pub fn load(base_dir: &Path) -> Result<Self> {
+        let path = base_dir.join(PROFILES_FILE);
+        if !path.exists() {
+            return Ok(ProfilesConfig::default());
+        }
+        let config: ProfilesConfig = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("invalid {}", path.display()))?;
+        for name in config.profiles.keys() {
+            validate_profile_name(name)?;
+        }
+        Ok(config)
+    }
+
+    /// The requested profile, or the one bound to the login session. None records to the
+    /// screenpipe directory itself, as without profiles
+    pub fn resolve(
+        &self,
+        requested: Option<&str>,
+        login: Option<&str>,
+    ) -> Result<Option<(String, Profile)>> {
+        let name = match requested {
+            Some(name) => name,
+            None => match login.and_then(|login| self.sessions.get(login)) {
+                Some(name) => name.as_str(),
+                None => return Ok(None),
+            },
+        };
+        let profile = self.profiles.get(name).ok_or_else(|| {
+            anyhow!(
+                "unknown profile {}, add it to {} (profiles: {})",
+                name,
+                PROFILES_FILE,
+                self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+            )
+        })?;
+        Ok(Some((name.to_string(), profile.clone())))
+    }
+}
+
+/// Names are directory names, nothing that could point out of the screenpipe directory
+pub fn validate_profile_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        anyhow::bail!(
+            "invalid profile name {:?}, use letters, digits, - and _",
+            name
+        );
+    }
+    Ok(())
+}
+
+/// Where the profile's database and media are kept
+pub fn profile_dir(base_dir: &Path, name: &str) -> PathBuf {
+    base_dir.join("profiles").join(name)
+}
+
+/// Name of the OS user running screenpipe
+pub fn login_name() -> Option<String> {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .ok()
+        .filter(|name| !name.is_empty())
+}
+
+/// The profile this server records to, for /profiles
+#[derive(Debug, Clone, Serialize)]
+pub struct ActiveProfile {
+    pub name: String,
+    #[serde(flatten)]
+    pub profile: Profile,
+    /// Every profile of profiles.json, switching means restarting with --profile
+    pub available: Vec<String>,
+}
@@ -0,0 +1,346 @@
+//! Moves the recordings of a data directory to another disk, an external SSD say, while
+//! screenpipe keeps recording. The database stays where it is and points at the new paths.
+use crate::db::{
+    check_database_file, get_database_file_chunks, is_plaintext_database,
+    rewrite_database_file_paths,
+};
+use crate::disk_guard::free_space;
+use crate::encryption::KeyRing;
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use log::{info, warn};
+use screenpipe_integrations::reflink::{clone_or_copy, CopyMethod};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// The database screenpipe opens in its data directory
+const DATABASE_FILE: &str = "db.sqlite";
+/// A copy not checked yet, only renamed to the file once its hash is the one of the source
+const PARTIAL_SUFFIX: &str = ".partial";
+
+#[derive(Debug, Clone)]
+pub struct RelocateOptions {
+    /// How long the recorder gets to finish the chunks it was writing to the old directory
+    /// once it's switched over, longer than a video chunk
+    pub settle: Duration,
+    /// Keep the old directory rather than removing it once everything was checked
+    pub keep_old: bool,
+}
+
+impl Default for RelocateOptions {
+    fn default() -> Self {
+        RelocateOptions {
+            settle: Duration::from_secs(90),
+            keep_old: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelocateReport {
+    pub from: String,
+    pub to: String,
+    pub files_copied: u64,
+    pub bytes_copied: u64,
+    /// Of the files copied, the ones sharing the blocks of the old ones, when both directories
+    /// are on one APFS, btrfs, XFS or ReFS volume
+    pub files_cloned: u64,
+    /// Rows of the database pointing at the new directory
+    pub paths_rewritten: u64,
+    /// Where the old directory is kept, with `keep_old`
+    pub previous: Option<String>,
+}
+
+/// Bytes of the files under `dir`
+pub fn media_size(dir: &Path) -> Result<u64> {
+    let mut size = 0;
+    for (_, path) in media_files(dir)? {
+        size += std::fs::metadata(path)?.len();
+    }
+    Ok(size)
+}
+
+/// The files under `dir` with their path relative to it, without the copies left partial
+fn media_files(dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        for entry in std::fs::read_dir(&current)? {
+            let entry = entry?;
+            let path = entry.path();
+            if entry.file_type()?.is_dir() {
+                dirs.push(path);
+            } else if !path.to_string_lossy().ends_with(PARTIAL_SUFFIX) {
+                files.push((path.strip_prefix(dir)?.to_path_buf(), path));
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn sha256_file(path: &Path) -> Result<Vec<u8>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1 << 16];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            return Ok(hasher.finalize().to_vec());
+        }
+        hasher.update(&buffer[..read]);
+    }
+}
+
+/// Copies `source` to `target` through a partial file, renamed once it hashes like the source.
+/// Returns the bytes copied and how
+fn copy_verified(source: &Path, target: &Path) -> Result<(u64, CopyMethod)> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let partial = PathBuf::from(format!("{}{}", target.display(), PARTIAL_SUFFIX));
+    let copied = clone_or_copy(source, &partial)
+        .with_context(|| format!("failed to copy {}", source.display()))?;
+    if sha256_file(source)? != sha256_file(&partial)? {
+        let _ = std::fs::remove_file(&partial);
+        bail!("the copy of {} doesn't match it", source.display());
+    }
+    std::fs::rename(&partial, target)?;
+    Ok(copied)
+}
+
+/// Copies the files of `from` that `to` doesn't have or that changed since they were copied,
+/// as (files, bytes, files cloned)
+fn sync_media(from: &Path, to: &Path) -> Result<(u64, u64, u64)> {
+    let (mut files, mut bytes, mut cloned) = (0, 0, 0);
+    for (relative, source) in media_files(from)? {
+        let target = to.join(&relative);
+        let source_meta = std::fs::metadata(&source)?;
+        // the copies are newer than their source, unless the recorder wrote to it since
+        let up_to_date = std::fs::metadata(&target).is_ok_and(|target_meta| {
+            target_meta.len() == source_meta.len()
+                && match (target_meta.modified(), source_meta.modified()) {
+                    (Ok(copied), Ok(written)) => copied >= written,
+                    _ => false,
+                }
+        });
+        if !up_to_date {
+            let (copied, method) = copy_verified(&source, &target)?;
+            bytes += copied;
+            files += 1;
+            if method == CopyMethod::Cloned {
+                cloned += 1;
+            }
+        }
+    }
+    Ok((files, bytes, cloned))
+}
+
+/// Makes `link` lead to `target`, a junction on Windows where symlinks need admin rights
+#[cfg(unix)]
+fn link_dir(target: &Path, link: &Path) -> Result<()> {
+    std::os::unix::fs::symlink(target, link)?;
+    Ok(())
+}
+
+#[cfg(windows)]
+fn link_dir(target: &Path, link: &Path) -> Result<()> {
+    let status = std::process::Command::new("cmd")
+        .args(["/C", "mklink", "/J"])
+        .arg(link)
+        .arg(target)
+        .stdout(std::process::Stdio::null())
+        .status()?;
+    if !status.success() {
+        bail!("mklink /J {} {} failed", link.display(), target.display());
+    }
+    Ok(())
+}
+
+/// Fails when `to` can't take the recordings of `from`: not empty, inside it, or too small
+fn check_destination(from: &Path, to: &Path) -> Result<()> {
+    let from_meta = std::fs::symlink_metadata(from)
+        .with_context(|| format!("{} has no recordings to move", from.display()))?;
+    if from_meta.file_type().is_symlink() {
+        bail!(
+            "{} already leads to {}, start screenpipe with --media-dir pointing there to move it again",
+            from.display(),
+            std::fs::read_link(from)?.display()
+        );
+    }
+    if !from_meta.is_dir() {
+        bail!("{} is not a directory", from.display());
+    }
+    if to.exists() {
+        if std::fs::read_dir(to)?.next().is_some() {
+            bail!("{} is not empty", to.display());
+        }
+    } else {
+        std::fs::create_dir_all(to)?;
+    }
+    let (from, to) = (from.canonicalize()?, to.canonicalize()?);
+    if to.starts_with(&from) || from.starts_with(&to) {
+        bail!(
+            "{} and {} are inside one another",
+            from.display(),
+            to.display()
+        );
+    }
+    let needed = media_size(&from)?;
+    if let Some(free) = free_space(&to) {
+        if free < needed {
+            bail!(
+                "{} has {:.1} GB free, the recordings take {:.1} GB",
+                to.display(),
+                free as f64 / 1e9,
+                needed as f64 / 1e9
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Removes what's in `dir`, which may be the root of a disk
+fn clear_dir(dir: &Path) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            std::fs::remove_dir_all(path)?;
+        } else {
+            std::fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// The key the database of `data_dir` opens with, None when it's plaintext
+async fn database_key(data_dir: &Path) -> Result<Option<String>> {
+    let database = data_dir.join(DATABASE_FILE);
+    if is_plaintext_database(&database.to_string_lossy()) {
+        return Ok(None);
+    }
+    Ok(Some(KeyRing::load(data_dir).await?.database_key()))
+}
+
+/// Moves the recordings of `data_dir` from the media directory `from` to `to`, while
+/// screenpipe records:
+///
+/// 1. every file is copied to `to` and checked against its hash
+/// 2. `from` is renamed aside and replaced by a link to `to`, so the recorder writes there
+/// 3. once the chunks in flight settled, what changed in the old directory is copied again
+/// 4. the paths of the database are rewritten and every chunk is checked to be in `to`
+/// 5. the old directory is removed
+///
+/// Nothing was lost when it fails, the recordings are in the old directory until the end.
+/// The link stays for the recorder running, restart it with --media-dir `to` to drop it.
+pub async fn relocate_media(
+    data_dir: &Path,
+    from: &Path,
+    to: &Path,
+    options: &RelocateOptions,
+) -> Result<RelocateReport> {
+    check_destination(from, to)?;
+    let to = to.canonicalize()?;
+    let key = database_key(data_dir).await?;
+    let database = data_dir.join(DATABASE_FILE).to_string_lossy().into_owned();
+
+    info!("copying {} to {}", from.display(), to.display());
+    let (source, target) = (from.to_path_buf(), to.clone());
+    let (mut files_copied, mut bytes_copied, mut files_cloned) =
+        tokio::task::spawn_blocking(move || sync_media(&source, &target)).await??;
+
+    let previous = PathBuf::from(format!(
+        "{}.relocated-{}",
+        from.display(),
+        Utc::now().format("%Y%m%d%H%M%S")
+    ));
+    if let Err(e) = std::fs::rename(from, &previous) {
+        // Windows doesn't rename directories with files open, `to` is left empty for a retry
+        clear_dir(&to)?;
+        return Err(anyhow::Error::new(e).context(format!(
+            "failed to switch {} over, stop screenpipe and try again",
+            from.display()
+        )));
+    }
+    if let Err(e) = link_dir(&to, from) {
+        std::fs::rename(&previous, from)?;
+        return Err(e.context(format!(
+            "failed to link {} to {}",
+            from.display(),
+            to.display()
+        )));
+    }
+
+    info!(
+        "switched over, waiting {}s for the chunks in flight",
+        options.settle.as_secs()
+    );
+    tokio::time::sleep(options.settle).await;
+    let (source, target) = (previous.clone(), to.clone());
+    let (files, bytes, cloned) =
+        tokio::task::spawn_blocking(move || sync_media(&source, &target)).await??;
+    files_copied += files;
+    bytes_copied += bytes;
+    files_cloned += cloned;
+
+    let separator = std::path::MAIN_SEPARATOR;
+    let paths_rewritten = rewrite_database_file_paths(
+        &database,
+        key.as_deref(),
+        &format!("{}{}", from.display(), separator),
+        &format!("{}{}", to.display(), separator),
+    )
+    .await?;
+
+    let problems = check_database_file(&database, key.as_deref()).await?;
+    if !problems.is_empty() {
+        bail!(
+            "the database is damaged ({}), the recordings are kept in {}",
+            problems.join(", "),
+            previous.display()
+        );
+    }
+    // chunks already gone before the move, the integrity checker's business, don't count
+    let mut missing = 0;
+    for (_, _, file_path) in get_database_file_chunks(&database, key.as_deref()).await? {
+        let Ok(relative) = Path::new(&file_path).strip_prefix(&to) else {
+            continue;
+        };
+        if !Path::new(&file_path).exists() && previous.join(relative).exists() {
+            warn!("{} wasn't moved", file_path);
+            missing += 1;
+        }
+    }
+    if missing > 0 {
+        bail!(
+            "{} chunks weren't moved, the recordings are kept in {}",
+            missing,
+            previous.display()
+        );
+    }
+
+    let previous = if options.keep_old {
+        Some(previous.to_string_lossy().into_owned())
+    } else {
+        std::fs::remove_dir_all(&previous)?;
+        None
+    };
+    info!(
+        "moved {} files ({:.1} GB, {} cloned) to {}",
+        files_copied,
+        bytes_copied as f64 / 1e9,
+        files_cloned,
+        to.display()
+    );
+    Ok(RelocateReport {
+        from: from.to_string_lossy().into_owned(),
+        to: to.to_string_lossy().into_owned(),
+        files_copied,
+        bytes_copied,
+        files_cloned,
+        paths_rewritten,
+        previous,
+    })
+}
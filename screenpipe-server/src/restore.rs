@@ -0,0 +1,271 @@
+use crate::backup::{
+    chunk_object_key, restore_backup, BACKUP_SUFFIX, DATABASE_PREFIX, SNAPSHOT_TIME_FORMAT,
+};
+use crate::db::{
+    check_database_file, get_database_file_chunks, is_plaintext_database,
+    set_database_file_chunk_path,
+};
+use crate::encryption::{KeyRing, ENCRYPTED_SUFFIX};
+use crate::user_isolation::UserInstance;
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use log::{info, warn};
+use screenpipe_integrations::cloud_backup::BackupClient;
+use serde::Serialize;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// The database screenpipe opens in its data directory
+const DATABASE_FILE: &str = "db.sqlite";
+/// In the data directory, downloads wait there until they are decrypted and checked
+const RESTORE_DIR: &str = "restore";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DatabaseSnapshot {
+    pub object_key: String,
+    pub taken_at: DateTime<Utc>,
+    /// Encrypted, as uploaded
+    pub size: u64,
+}
+
+/// When the snapshot uploaded at `object_key` was taken
+pub fn snapshot_time(object_key: &str) -> Option<DateTime<Utc>> {
+    let name = object_key
+        .strip_prefix(DATABASE_PREFIX)?
+        .strip_suffix(BACKUP_SUFFIX)?
+        .strip_suffix(".sqlite")?;
+    NaiveDateTime::parse_from_str(name, SNAPSHOT_TIME_FORMAT)
+        .ok()
+        .map(|taken_at| taken_at.and_utc())
+}
+
+/// Snapshots of the database at the destination, oldest first
+pub async fn list_snapshots(client: &BackupClient) -> Result<Vec<DatabaseSnapshot>> {
+    let mut snapshots: Vec<DatabaseSnapshot> = client
+        .list_objects(DATABASE_PREFIX)
+        .await?
+        .into_iter()
+        .filter_map(|object| {
+            Some(DatabaseSnapshot {
+                taken_at: snapshot_time(&object.key)?,
+                object_key: object.key,
+                size: object.size,
+            })
+        })
+        .collect();
+    snapshots.sort_by_key(|snapshot| snapshot.taken_at);
+    Ok(snapshots)
+}
+
+/// The last snapshot taken at or before `at`, the last one when not given
+pub fn pick_snapshot(
+    snapshots: &[DatabaseSnapshot],
+    at: Option<DateTime<Utc>>,
+) -> Option<&DatabaseSnapshot> {
+    snapshots
+        .iter()
+        .filter(|snapshot| at.is_none_or(|at| snapshot.taken_at <= at))
+        .max_by_key(|snapshot| snapshot.taken_at)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RestoreOptions {
+    /// Point in time to go back to, the last snapshot when not given
+    pub at: Option<DateTime<Utc>>,
+    /// Only the text layer, the database: the recordings aren't downloaded
+    pub text_only: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RestoreReport {
+    pub snapshot: DatabaseSnapshot,
+    /// Where the database that was replaced is kept
+    pub previous_database: Option<String>,
+    pub media_restored: u64,
+    /// Chunks neither on disk nor in the backup, the integrity checker finds them broken
+    pub media_missing: u64,
+}
+
+/// Downloads `object_key` and decrypts it to `output`, which is only written once the whole
+/// backup decrypted
+async fn download_decrypted(
+    client: &BackupClient,
+    object_key: &str,
+    work_dir: &Path,
+    output: &Path,
+    passphrase: &str,
+) -> Result<u64> {
+    let encrypted = work_dir.join(format!("download{}", BACKUP_SUFFIX));
+    let decrypted = work_dir.join("download.partial");
+    let mut file = tokio::fs::File::create(&encrypted).await?;
+    let downloaded = client.download(object_key, &mut file).await;
+    drop(file);
+    let result = match downloaded {
+        Ok(_) => {
+            let (encrypted, decrypted) = (encrypted.clone(), decrypted.clone());
+            let passphrase = passphrase.to_string();
+            tokio::task::spawn_blocking(move || {
+                let mut input = BufReader::new(std::fs::File::open(encrypted)?);
+                let mut output = BufWriter::new(std::fs::File::create(decrypted)?);
+                let written = restore_backup(&mut input, &mut output, &passphrase)?;
+                output.flush()?;
+                Ok::<_, anyhow::Error>(written)
+            })
+            .await?
+        }
+        Err(e) => Err(e),
+    };
+    let _ = tokio::fs::remove_file(&encrypted).await;
+    match result {
+        Ok(written) => {
+            if let Some(parent) = output.parent() {
+                tokio::fs::create_dir_all(parent).await?;
+            }
+            tokio::fs::rename(&decrypted, output).await?;
+            Ok(written)
+        }
+        Err(e) => {
+            let _ = tokio::fs::remove_file(&decrypted).await;
+            Err(e)
+        }
+    }
+}
+
+/// The key a restored database opens with, None when it's plaintext, and what its integrity
+/// check finds wrong
+async fn check_snapshot(
+    database_path: &str,
+    data_dir: &Path,
+) -> Result<(Option<String>, Vec<String>)> {
+    if is_plaintext_database(database_path) {
+        return Ok((None, check_database_file(database_path, None).await?));
+    }
+    // encrypted by SQLCipher, with the key of the keychain or the one before a rotation
+    let keys = KeyRing::load(data_dir).await?;
+    for key in [Some(keys.database_key()), keys.previous_database_key()]
+        .into_iter()
+        .flatten()
+    {
+        if let Ok(problems) = check_database_file(database_path, Some(&key)).await {
+            return Ok((Some(key), problems));
+        }
+    }
+    bail!("the keychain has no key opening the snapshot, it was encrypted on another machine")
+}
+
+/// Replaces the database of `data_dir` by `restored` in a single rename. The database replaced
+/// is kept next to it with its wal, its path is returned
+pub fn swap_database(restored: &Path, data_dir: &Path, suffix: &str) -> Result<Option<PathBuf>> {
+    let database = data_dir.join(DATABASE_FILE);
+    let mut previous = None;
+    if database.exists() {
+        let kept = data_dir.join(format!("{}.before-restore-{}", DATABASE_FILE, suffix));
+        for extension in ["-wal", "-shm"] {
+            let path = PathBuf::from(format!("{}{}", database.display(), extension));
+            if path.exists() {
+                std::fs::rename(&path, format!("{}{}", kept.display(), extension))?;
+            }
+        }
+        if std::fs::hard_link(&database, &kept).is_err() {
+            std::fs::copy(&database, &kept)?;
+        }
+        previous = Some(kept);
+    }
+    std::fs::rename(restored, &database)?;
+    Ok(previous)
+}
+
+/// Brings the data directory back to a snapshot of the backup, with the recordings it points
+/// at that aren't on disk anymore. screenpipe has to be stopped
+pub async fn restore(
+    client: &BackupClient,
+    passphrase: &str,
+    data_dir: &Path,
+    options: &RestoreOptions,
+) -> Result<RestoreReport> {
+    if let Some(instance) = UserInstance::read(data_dir).filter(UserInstance::is_running) {
+        bail!(
+            "screenpipe is running (pid {}), stop it before restoring",
+            instance.pid
+        );
+    }
+    let snapshots = list_snapshots(client).await?;
+    let snapshot = pick_snapshot(&snapshots, options.at)
+        .cloned()
+        .ok_or_else(|| match options.at {
+            Some(at) => anyhow!("no snapshot of the database was taken before {}", at),
+            None => anyhow!("no snapshot of the database was uploaded"),
+        })?;
+
+    let work_dir = data_dir.join(RESTORE_DIR);
+    tokio::fs::create_dir_all(&work_dir).await?;
+    let restored = work_dir.join(DATABASE_FILE);
+    info!(
+        "Restoring {} taken at {}",
+        snapshot.object_key, snapshot.taken_at
+    );
+    download_decrypted(
+        client,
+        &snapshot.object_key,
+        &work_dir,
+        &restored,
+        passphrase,
+    )
+    .await?;
+    let restored_path = restored.to_string_lossy().to_string();
+    let (key, problems) = check_snapshot(&restored_path, data_dir).await?;
+    if !problems.is_empty() {
+        bail!("the snapshot is damaged: {}", problems.join(", "));
+    }
+
+    let mut media_restored = 0;
+    let mut media_missing = 0;
+    if !options.text_only {
+        let media_dir = data_dir.join("data");
+        for (kind, id, file_path) in
+            get_database_file_chunks(&restored_path, key.as_deref()).await?
+        {
+            if Path::new(&file_path).exists() {
+                continue;
+            }
+            // restored as plaintext, in this data directory which may not be the one backed up
+            let name = Path::new(&file_path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let target = media_dir.join(name.strip_suffix(ENCRYPTED_SUFFIX).unwrap_or(&name));
+            if !target.exists() {
+                let object_key = chunk_object_key(kind, &file_path);
+                if let Err(e) =
+                    download_decrypted(client, &object_key, &work_dir, &target, passphrase).await
+                {
+                    warn!("Chunk {} isn't restored from {}: {}", id, object_key, e);
+                    media_missing += 1;
+                    continue;
+                }
+                media_restored += 1;
+            }
+            set_database_file_chunk_path(
+                &restored_path,
+                key.as_deref(),
+                kind,
+                id,
+                &target.to_string_lossy(),
+            )
+            .await?;
+        }
+    }
+
+    let previous = swap_database(
+        &restored,
+        data_dir,
+        &Utc::now().format(SNAPSHOT_TIME_FORMAT).to_string(),
+    )?;
+    let _ = tokio::fs::remove_dir(&work_dir).await;
+    Ok(RestoreReport {
+        snapshot,
+        previous_database: previous.map(|path| path.to_string_lossy().to_string()),
+        media_restored,
+        media_missing,
+    })
+}
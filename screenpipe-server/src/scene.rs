@@ -0,0 +1,249 @@
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
+use screenpipe_core::emit_event;
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::CaptureResult;
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Mutex;
+
+/// Size of the frames compared, the layout of a screen and not its text
+const THUMBNAIL_WIDTH: u32 = 32;
+const THUMBNAIL_HEIGHT: u32 = 18;
+/// A cell of the thumbnail changed when it's this much brighter or darker, out of 255
+const CELL_CONTRAST: u8 = 32;
+/// Fraction of the cells that changed for the screen to be another scene, scrolling or typing
+/// changes a few of them
+pub const MIN_SCENE_CHANGE: f64 = 0.4;
+/// The windows are laid out differently below this
+pub const MAX_LAYOUT_SIMILARITY: f64 = 0.5;
+/// Fraction of the frame a window presenting slides covers
+const PRESENTATION_COVERAGE: f64 = 0.95;
+/// Apps and titles of the windows presenting slides, lowercase
+const PRESENTATION_APPS: [&str; 6] = [
+    "keynote",
+    "powerpoint",
+    "slide show",
+    "google slides",
+    "impress",
+    "presenter",
+];
+
+/// The monitor the focused window was last on, shared by the capture of every monitor
+static FOCUSED_MONITOR: Mutex<Option<u32>> = Mutex::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SceneKind {
+    /// The focused window is on another monitor than before, or the monitor's resolution
+    /// changed
+    MonitorSwitched,
+    /// A window filling the screen shows slides
+    PresentationStarted,
+    PresentationEnded,
+    /// Other windows or the same ones moved and resized, with most of the screen changed
+    LayoutChanged,
+    /// Another app is focused in the same layout, with most of the screen changed
+    AppSwitched,
+}
+
+impl SceneKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SceneKind::MonitorSwitched => "monitor_switched",
+            SceneKind::PresentationStarted => "presentation_started",
+            SceneKind::PresentationEnded => "presentation_ended",
+            SceneKind::LayoutChanged => "layout_changed",
+            SceneKind::AppSwitched => "app_switched",
+        }
+    }
+}
+
+/// A context switch on a monitor, emitted as "scene.changed"
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SceneChange {
+    pub timestamp: DateTime<Utc>,
+    pub monitor_id: u32,
+    pub kind: SceneKind,
+    /// The focused window after the change, empty when none is
+    pub app_name: String,
+    pub window_name: String,
+    pub previous_app_name: Option<String>,
+    /// Fraction of the screen that changed, 0 to 1
+    pub changed_fraction: f64,
+    /// How much the windows overlap the ones before, 0 to 1
+    pub layout_similarity: f64,
+}
+
+/// A window's app and its top left corner and size
+pub type WindowRect = (String, i64, i64, u32, u32);
+
+struct Scene {
+    width: u32,
+    height: u32,
+    thumbnail: Vec<u8>,
+    windows: Vec<WindowRect>,
+    focused_app: Option<String>,
+    presenting: bool,
+}
+
+fn thumbnail(image: &DynamicImage) -> Vec<u8> {
+    image
+        .thumbnail_exact(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT)
+        .to_luma8()
+        .into_raw()
+}
+
+/// Fraction of the cells of two thumbnails that differ
+pub fn changed_fraction(old: &[u8], new: &[u8]) -> f64 {
+    if old.len() != new.len() || old.is_empty() {
+        return 1.0;
+    }
+    let changed = old
+        .iter()
+        .zip(new)
+        .filter(|(old, new)| old.abs_diff(**new) > CELL_CONTRAST)
+        .count();
+    changed as f64 / old.len() as f64
+}
+
+fn overlap(a: &WindowRect, b: &WindowRect) -> u64 {
+    let width = (a.1 + a.3 as i64).min(b.1 + b.3 as i64) - a.1.max(b.1);
+    let height = (a.2 + a.4 as i64).min(b.2 + b.4 as i64) - a.2.max(b.2);
+    if width <= 0 || height <= 0 {
+        0
+    } else {
+        (width * height) as u64
+    }
+}
+
+/// Area of the windows of `from` covered by a window of the same app in `to`, out of their
+/// whole area
+fn covered(from: &[WindowRect], to: &[WindowRect]) -> f64 {
+    let area: u64 = from.iter().map(|w| w.3 as u64 * w.4 as u64).sum();
+    if area == 0 {
+        return 1.0;
+    }
+    let kept: u64 = from
+        .iter()
+        .map(|window| {
+            to.iter()
+                .filter(|other| other.0 == window.0)
+                .map(|other| overlap(window, other))
+                .max()
+                .unwrap_or(0)
+        })
+        .sum();
+    kept as f64 / area as f64
+}
+
+/// 1 for the same windows at the same places, 0 when none of them is where it was
+pub fn layout_similarity(old: &[WindowRect], new: &[WindowRect]) -> f64 {
+    covered(old, new).min(covered(new, old))
+}
+
+/// The focused window when it fills the frame
+pub(crate) fn fullscreen_window(frame: &CaptureResult) -> Option<&WindowOcrResult> {
+    let frame_area = frame.image.width() as f64 * frame.image.height() as f64;
+    frame.window_ocr_results.iter().find(|window| {
+        let area = window.image.width() as f64 * window.image.height() as f64;
+        window.focused && frame_area > 0.0 && area >= frame_area * PRESENTATION_COVERAGE
+    })
+}
+
+/// The app or the title is one presenting slides
+pub(crate) fn is_presentation_app(window: &WindowOcrResult) -> bool {
+    let names = format!("{} {}", window.app_name, window.window_name).to_lowercase();
+    PRESENTATION_APPS.iter().any(|app| names.contains(app))
+}
+
+fn is_presentation(frame: &CaptureResult) -> bool {
+    fullscreen_window(frame).is_some_and(is_presentation_app)
+}
+
+/// Tells the context switches of a monitor's frames from their small changes and emits
+/// "scene.changed" with what kind of switch it is. The first frame is the baseline
+pub struct SceneTracker {
+    monitor_id: u32,
+    previous: Option<Scene>,
+}
+
+impl SceneTracker {
+    pub fn new(monitor_id: u32) -> Self {
+        Self {
+            monitor_id,
+            previous: None,
+        }
+    }
+
+    pub fn observe(
+        &mut self,
+        frame: &CaptureResult,
+        timestamp: DateTime<Utc>,
+    ) -> Option<SceneChange> {
+        let focused = frame
+            .window_ocr_results
+            .iter()
+            .find(|window| window.focused);
+        let scene = Scene {
+            width: frame.image.width(),
+            height: frame.image.height(),
+            thumbnail: thumbnail(&frame.image),
+            windows: frame
+                .window_ocr_results
+                .iter()
+                .map(|window| {
+                    (
+                        window.app_name.clone(),
+                        window.position.0 as i64,
+                        window.position.1 as i64,
+                        window.image.width(),
+                        window.image.height(),
+                    )
+                })
+                .collect(),
+            focused_app: focused.map(|window| window.app_name.clone()),
+            presenting: is_presentation(frame),
+        };
+        let focus_moved = focused.is_some() && {
+            let mut focused_monitor = FOCUSED_MONITOR.lock().unwrap();
+            let moved = focused_monitor.is_some_and(|monitor| monitor != self.monitor_id);
+            *focused_monitor = Some(self.monitor_id);
+            moved
+        };
+
+        let previous = self.previous.replace(scene);
+        let (previous, scene) = (previous?, self.previous.as_ref()?);
+        let changed = changed_fraction(&previous.thumbnail, &scene.thumbnail);
+        let similarity = layout_similarity(&previous.windows, &scene.windows);
+        let kind =
+            if focus_moved || (previous.width, previous.height) != (scene.width, scene.height) {
+                SceneKind::MonitorSwitched
+            } else if scene.presenting && !previous.presenting {
+                SceneKind::PresentationStarted
+            } else if previous.presenting && !scene.presenting {
+                SceneKind::PresentationEnded
+            } else if changed < MIN_SCENE_CHANGE {
+                return None;
+            } else if similarity < MAX_LAYOUT_SIMILARITY {
+                SceneKind::LayoutChanged
+            } else if scene.focused_app != previous.focused_app {
+                SceneKind::AppSwitched
+            } else {
+                return None;
+            };
+
+        let change = SceneChange {
+            timestamp,
+            monitor_id: self.monitor_id,
+            kind,
+            app_name: focused.map(|w| w.app_name.clone()).unwrap_or_default(),
+            window_name: focused.map(|w| w.window_name.clone()).unwrap_or_default(),
+            previous_app_name: previous.focused_app,
+            changed_fraction: changed,
+            layout_similarity: similarity,
+        };
+        emit_event("scene.changed", json!(change));
+        Some(change)
+    }
+}
@@ -0,0 +1,87 @@
+//! Where on the screen the OCR read each line, kept in an r-tree over the frame's pixels and
+//! time, so the text a region showed over a day can be asked for: a status bar, a ticker
+//! widget, the corner of a dashboard.
+use crate::live_ocr::Rect;
+use chrono::{DateTime, Utc};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::OcrEngine;
+use serde::Serialize;
+
+/// A line the OCR read and its box in the frame
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct OcrBox {
+    pub text: String,
+    /// As the engine reports it, 0 to 100 for Tesseract, 0 to 1 for Apple's
+    pub confidence: Option<f64>,
+    pub rect: Rect,
+}
+
+/// The boxes of a window's text, moved to the frame's pixels. Only Tesseract and Apple's
+/// engine place their text, and the boxes of a window scaled down by its app profile are in
+/// the scaled window's pixels
+pub fn window_boxes(window: &WindowOcrResult, ocr_engine: &OcrEngine) -> Vec<OcrBox> {
+    if window.browser_page.is_some() {
+        return Vec::new();
+    }
+    // Vision's boxes are fractions of the image, from its bottom left corner
+    let normalized = matches!(ocr_engine, OcrEngine::AppleNative);
+    let (image_width, image_height) = (window.image.width() as f64, window.image.height() as f64);
+    window
+        .text_json
+        .iter()
+        .filter_map(|line| {
+            let text = line.get("text")?.trim();
+            if text.is_empty() {
+                return None;
+            }
+            let number = |key: &str| line.get(key)?.parse::<f64>().ok();
+            let (left, top) = (number("left")?, number("top")?);
+            let (width, height) = (number("width")?, number("height")?);
+            if width <= 0.0 || height <= 0.0 {
+                return None;
+            }
+            let (left, top, width, height) = if normalized {
+                (
+                    left * image_width,
+                    (1.0 - top - height) * image_height,
+                    width * image_width,
+                    height * image_height,
+                )
+            } else {
+                (left, top, width, height)
+            };
+            Some(OcrBox {
+                text: text.to_string(),
+                confidence: number("conf").or_else(|| number("confidence")),
+                rect: Rect {
+                    x: window.position.0 as f64 + left,
+                    y: window.position.1 as f64 + top,
+                    width,
+                    height,
+                },
+            })
+        })
+        .collect()
+}
+
+/// The lines whose center is in `region`, of the frames between the two times
+#[derive(Debug, Clone)]
+pub struct RegionFilter {
+    pub region: Rect,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub monitor_id: Option<u32>,
+    pub app_name: Option<String>,
+}
+
+/// What a frame showed in the region, its lines top to bottom
+#[derive(Debug, Clone, Serialize)]
+pub struct RegionText {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub monitor_id: Option<u32>,
+    pub app_name: String,
+    pub window_name: String,
+    pub text: String,
+    pub boxes: Vec<OcrBox>,
+}
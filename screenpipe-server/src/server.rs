@@ -1,16 +1,95 @@
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
-    response::Json as JsonResponse,
-    routing::{get, post},
+    extract::{Form, Path, Query, State},
+    handler::Handler,
+    http::{header, HeaderMap, StatusCode},
+    response::{
+        sse::{Event as SseEvent, KeepAlive, Sse},
+        Html, IntoResponse, Json as JsonResponse, Response,
+    },
+    routing::{delete, get, post},
     serve, Router,
 };
 use crossbeam::queue::SegQueue;
-use screenpipe_vision::monitor::list_monitors;
+use screenpipe_integrations::analytics_export::{write_analytics, AnalyticsFormat, AnalyticsTable};
+use screenpipe_integrations::git_activity::GitEventKind;
+use screenpipe_integrations::shell_history::Shell;
+use screenpipe_vision::monitor::{get_focused_window_title, list_monitors};
+use screenpipe_vision::report_private_windows;
 
-use crate::{ContentType, DatabaseManager, SearchResult};
-use chrono::{DateTime, Utc};
-use log::{debug, error, info};
+use crate::analytics::{
+    analytics_rows, query_analytics, AnalyticsQueryResult, DEFAULT_QUERY_ROWS, DEFAULT_QUERY_WINDOW,
+    MAX_QUERY_ROWS,
+};
+use crate::app_icons::AppIcons;
+use crate::audit::audit_requests;
+use crate::automation::{
+    Automation, AutomationAction, AutomationRecord, AutomationStatus, MAX_ACTIONS_PER_REQUEST,
+};
+use crate::browser_pages::{report_browser_page, BrowserPage, MAX_PAGE_TEXT_LEN};
+use crate::calendar::calendar_feed;
+use crate::capabilities::{machine_capabilities, Capabilities, FeatureCapabilities};
+use crate::consumer_redaction::{redact_responses, Consumer, ConsumerRedaction};
+use crate::credentials::{
+    is_cross_origin, local_peer_is_app, refuse_cross_origin, ApiCredentials, Caller,
+};
+use crate::editor_context::{link_editor_frames, record_editor_beacon, EditorBeacon, EditorContext};
+use crate::encryption::open_media;
+use crate::errors::StorageError;
+use crate::git_activity::GitEvent;
+use crate::notifications::Notification;
+use crate::conversations::{
+    self, chat_history, collect_citations, title_from, Conversation, ConversationMessage,
+};
+use crate::focus_mode::{focus_mode_at, FocusPeriod};
+use crate::timezones::{local_timestamp, TimezoneSegment};
+use screenpipe_integrations::timezone::{parse_timezone, Tz};
+use crate::extraction::validate_extraction_rule;
+use crate::forget::{forget, ForgetFilter, ForgetReport};
+use crate::frame_format::{
+    frame_storage, negotiate_frame_format, FrameEncoding, FrameFormat, DEFAULT_FRAME_QUALITY,
+};
+use crate::native_encoder::native_frame_file;
+use crate::thumbnails::{scale_to_width, thumbnail_level, Thumbnails};
+use crate::local_api::serve_local_api;
+use crate::live_ocr::{blocks_text, clean_copied_text, LiveOcr, LiveOcrScreen, Rect};
+use crate::live_view::{is_live_segment, LiveView, LIVE_PAGE, LIVE_PLAYER};
+use crate::llm::{current_month_start, ToolChat, ToolRegistry, ToolRun};
+use crate::markers::{Marker, MarkerRecorder, MarkerRequest};
+use crate::pagination::{Cursor, CursorPosition, Page, MAX_PAGE_SIZE};
+use crate::policy::{RedactionRecord, RedactionSource};
+use crate::profiles::ActiveProfile;
+use crate::tls::serve_tls;
+use crate::trends::{update_weekly_metrics, weekly_trends, WeeklyTrends, DEFAULT_TREND_WEEKS};
+use crate::user_isolation::{require_user_token, UserIsolation, USER_TOKEN_HEADER};
+use crate::shell_history::{
+    link_terminal_frames, record_hook_command, NewShellCommand, ShellCommand, ShellCommandSource,
+};
+use crate::similarity::{find_similar_frames, frame_hash, perceptual_hash, DEFAULT_MAX_DISTANCE};
+use crate::pdf::{ocr_page, pdf_document, PdfPage};
+use crate::screen_regions::{RegionFilter, RegionText};
+use crate::slides::{Slide, SlideDeck};
+use crate::window_layout::{restore_actions, WindowLayout};
+use crate::watchlist::{compile_watch, Watch, WatchAlert, WatchKind, WatchSource, Watchlist};
+use crate::window_history::WindowEvent;
+use crate::{
+    ActivityCategory, ActivitySession, AuditAction, AuditEntry, CapturePause, CategoryDuration, ClipJob, ClipOptions, ClipRenderer,
+    ComplianceMode, ComplianceStatus, ConsentRecord,
+    ContentType, DatabaseManager, ExtractedRecord, ExtractionKind, ExtractionRule, FileEvent, KeyRing, LlmService,
+    LlmUsageSummary, MeetingSummary, PauseStatus, SearchResult, TimelapseJob, TimelapseOptions,
+    TimelapseRenderer, TimelapseStatus,
+};
+use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use image::DynamicImage;
+use futures::stream::{self, Stream, StreamExt};
+use crate::video::extract_frame;
+use screenpipe_integrations::vault_export::VaultExporter;
+use screenpipe_core::{
+    channel_reports, emit_event, encode_image_for_llm, events_stats, known_model, model_manager,
+    subscribe_events, ChannelReport, ChatMessage, ImageRegion, PromptLibrary,
+    DEFAULT_LLM_IMAGE_MAX_DIMENSION, KNOWN_MODELS, PIPE_HEADER, PIPE_TOKEN_HEADER,
+};
+use chrono::{DateTime, FixedOffset, Local, NaiveDate, Utc};
+use log::{debug, error, info, warn};
 use screenpipe_audio::{
     default_input_device, default_output_device, list_audio_devices, AudioDevice, DeviceControl,
 };
@@ -18,11 +97,14 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::{
     collections::HashMap,
+    convert::Infallible,
     net::SocketAddr,
+    path::PathBuf,
     sync::{atomic::AtomicBool, Arc},
-    time::Duration,
+    time::{Duration, Instant},
 };
 use tokio::net::TcpListener;
+use tokio_rustls::TlsAcceptor;
 use tower_http::trace::TraceLayer;
 use tower_http::{cors::CorsLayer, trace::DefaultMakeSpan};
 
@@ -34,6 +116,34 @@ pub struct AppState {
     pub audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
     pub devices_status: HashMap<AudioDevice, DeviceControl>,
     pub app_start_time: DateTime<Utc>,
+    pub llm: Arc<LlmService>,
+    pub prompts: Arc<PromptLibrary>,
+    pub live_view: Option<Arc<LiveView>>,
+    pub timelapse: Arc<TimelapseRenderer>,
+    pub clips: Arc<ClipRenderer>,
+    pub capture_pause: Arc<CapturePause>,
+    /// None when data is stored in plaintext
+    pub encryption: Option<Arc<KeyRing>>,
+    /// Set when a signed compliance policy is deployed on the machine
+    pub compliance: Option<Arc<ComplianceMode>>,
+    /// What pipes and other consumers get from the API and the event stream
+    pub consumer_redaction: Option<Arc<ConsumerRedaction>>,
+    /// Set with --vault-dir
+    pub vault_export: Option<Arc<VaultExporter>>,
+    pub markers: Arc<MarkerRecorder>,
+    /// None when recording without profiles
+    pub profile: Option<Arc<ActiveProfile>>,
+    /// Reloaded when a watch is created or deleted
+    pub watchlist: Arc<Watchlist>,
+    pub app_icons: Arc<AppIcons>,
+    /// Words on the screen with their boxes, for overlays
+    pub live_ocr: Arc<LiveOcr>,
+    /// Set with --automation-grants
+    pub automation: Option<Arc<Automation>>,
+    /// What the model can call during /ask and /chat, pipes need a grant of --llm-tool-grants
+    pub llm_tools: Arc<ToolRegistry>,
+    /// Tells the app, the pipes and remote clients apart
+    pub credentials: Arc<ApiCredentials>,
 }
 
 // Update the SearchQuery struct
@@ -52,6 +162,10 @@ pub(crate) struct SearchQuery {
     app_name: Option<String>, // Add this line
     #[serde(default)]
     window_name: Option<String>, // Add this line
+    /// IANA timezone the results' local times are given in, the one recorded at each
+    /// result's time when not set
+    #[serde(default)]
+    tz: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -62,23 +176,74 @@ pub(crate) struct PaginationQuery {
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_number_from_string")]
     offset: u32,
+    /// `next_cursor` of the previous page
+    #[serde(default)]
+    cursor: Option<String>,
+}
+
+impl PaginationQuery {
+    fn limit(&self) -> u32 {
+        self.limit.min(MAX_PAGE_SIZE)
+    }
+
+    fn cursor(&self) -> Result<Cursor, (StatusCode, JsonResponse<serde_json::Value>)> {
+        match &self.cursor {
+            Some(cursor) => Cursor::decode(cursor).map_err(|e| {
+                (
+                    StatusCode::BAD_REQUEST,
+                    JsonResponse(json!({"error": format!("Invalid cursor: {}", e)})),
+                )
+            }),
+            None => Ok(Cursor::default()),
+        }
+    }
 }
 
-fn deserialize_number_from_string<'de, D>(deserializer: D) -> Result<u32, D::Error>
+fn deserialize_number_from_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
 where
     D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
 {
     let s: String = serde::Deserialize::deserialize(deserializer)?;
     s.parse().map_err(serde::de::Error::custom)
 }
 
+fn deserialize_optional_number_from_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    let s: Option<String> = serde::Deserialize::deserialize(deserializer)?;
+    s.map(|s| s.parse().map_err(serde::de::Error::custom))
+        .transpose()
+}
+
 // Response structs
 #[derive(Serialize)]
 pub(crate) struct PaginatedResponse<T> {
     data: Vec<T>,
+    next_cursor: Option<String>,
+    took_ms: u64,
     pagination: PaginationInfo,
 }
 
+impl<T> PaginatedResponse<T> {
+    fn new(page: Page<T>, pagination: &PaginationQuery, started: Instant) -> Self {
+        PaginatedResponse {
+            data: page.data,
+            next_cursor: page.next_cursor,
+            took_ms: started.elapsed().as_millis() as u64,
+            pagination: PaginationInfo {
+                limit: pagination.limit(),
+                offset: pagination.offset,
+                total: page.total,
+            },
+        }
+    }
+}
+
 #[derive(Serialize)]
 struct PaginationInfo {
     limit: u32,
@@ -103,6 +268,15 @@ pub(crate) struct OCRContent {
     offset_index: i64,
     app_name: String,
     window_name: String,
+    /// Set when the text was read from the page by the browser extension
+    browser_url: Option<String>,
+    scroll_y: Option<f64>,
+    /// ISO 639-1 code the text was detected in, "und" when it couldn't be told
+    language: Option<String>,
+    /// The focus mode or do not disturb on then
+    focus_mode: Option<String>,
+    /// The timestamp at the local time of the timezone asked for or recorded then
+    local_timestamp: Option<DateTime<FixedOffset>>,
 }
 
 #[derive(Serialize)]
@@ -112,6 +286,9 @@ pub(crate) struct AudioContent {
     timestamp: DateTime<Utc>,
     file_path: String,
     offset_index: i64,
+    language: Option<String>,
+    focus_mode: Option<String>,
+    local_timestamp: Option<DateTime<FixedOffset>>,
 }
 
 #[derive(Serialize)]
@@ -124,6 +301,8 @@ pub(crate) struct FTSContent {
     window_name: String, // Add this field
     file_path: String,
     original_frame_text: Option<String>,
+    focus_mode: Option<String>,
+    local_timestamp: Option<DateTime<FixedOffset>>,
 }
 
 #[derive(Serialize)]
@@ -142,6 +321,27 @@ pub struct MonitorInfo {
 }
 
 // Helper functions
+// sources of the list endpoints in their cursors
+const MEETINGS_CURSOR: &str = "meetings";
+const ACTIVITY_SESSIONS_CURSOR: &str = "activity_sessions";
+const FILE_EVENTS_CURSOR: &str = "file_events";
+const WINDOW_EVENTS_CURSOR: &str = "window_events";
+const SHELL_COMMANDS_CURSOR: &str = "shell_commands";
+const EDITOR_CONTEXTS_CURSOR: &str = "editor_contexts";
+const GIT_EVENTS_CURSOR: &str = "git_events";
+const NOTIFICATIONS_CURSOR: &str = "notifications";
+const CONVERSATIONS_CURSOR: &str = "conversations";
+const FOCUS_PERIODS_CURSOR: &str = "focus_periods";
+const TIMEZONES_CURSOR: &str = "timezones";
+const EXTRACTED_RECORDS_CURSOR: &str = "extracted_records";
+const AUDIT_CURSOR: &str = "audit";
+const AUTOMATION_CURSOR: &str = "automation";
+const MARKERS_CURSOR: &str = "markers";
+const SLIDE_DECKS_CURSOR: &str = "slide_decks";
+const REDACTIONS_CURSOR: &str = "redactions";
+const WATCH_ALERTS_CURSOR: &str = "watch_alerts";
+const REGION_TEXTS_CURSOR: &str = "region_texts";
+
 fn default_limit() -> u32 {
     20
 }
@@ -164,6 +364,7 @@ pub(crate) async fn search(
     JsonResponse<PaginatedResponse<ContentItem>>,
     (StatusCode, JsonResponse<serde_json::Value>),
 > {
+    let started = Instant::now();
     info!(
         "Received search request: query='{}', content_type={:?}, limit={}, offset={}, start_time={:?}, end_time={:?}, app_name={:?}, window_name={:?}",
         query.q.as_deref().unwrap_or(""),
@@ -177,6 +378,9 @@ pub(crate) async fn search(
     );
 
     let query_str = query.q.as_deref().unwrap_or("");
+    let limit = query.pagination.limit();
+    let cursor = query.pagination.cursor()?;
+    let timezone = query.tz.as_deref().map(timezone_param).transpose()?;
 
     // If app_name is specified, force content_type to OCR
     let content_type = if query.app_name.is_some() {
@@ -185,13 +389,14 @@ pub(crate) async fn search(
         query.content_type
     };
 
-    let results = state
+    let (results, next_cursor) = state
         .db
-        .search(
+        .search_after(
             query_str,
             content_type,
-            query.pagination.limit,
+            limit,
             query.pagination.offset,
+            &cursor,
             query.start_time,
             query.end_time,
             query.app_name.as_deref(),
@@ -226,14 +431,110 @@ pub(crate) async fn search(
         })?;
 
     info!("Search completed: found {} results", total);
-    Ok(JsonResponse(PaginatedResponse {
-        data: results.into_iter().map(into_content_item).collect(),
-        pagination: PaginationInfo {
-            limit: query.pagination.limit,
-            offset: query.pagination.offset,
-            total: total as i64,
+    let context = ResultContext::read(&state.db, &results, timezone).await;
+    let next_cursor = (results.len() >= limit as usize).then(|| next_cursor.encode());
+    let page = Page {
+        data: results
+            .into_iter()
+            .map(|result| into_content_item(result, &context))
+            .collect(),
+        next_cursor,
+        total: total as i64,
+    };
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RegionSearchQuery {
+    /// The region in pixels of the captured frame, from its top left corner
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    x: f64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    y: f64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    width: f64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    height: f64,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_optional_number_from_string")]
+    monitor_id: Option<u32>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+/// The text a region of the screen showed, frame by frame
+pub(crate) async fn search_region(
+    Query(query): Query<RegionSearchQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<RegionText>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to search the region: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to search the region: {}", e)})),
+        )
+    };
+    let started = Instant::now();
+    if query.width <= 0.0 || query.height <= 0.0 {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "the region needs a width and a height"})),
+        ));
+    }
+    let cursor = query.pagination.cursor()?;
+    let filter = RegionFilter {
+        region: Rect {
+            x: query.x,
+            y: query.y,
+            width: query.width,
+            height: query.height,
         },
-    }))
+        start_time: query.start_time,
+        end_time: query.end_time,
+        monitor_id: query.monitor_id,
+        app_name: query.app_name.clone(),
+    };
+    let texts = state
+        .db
+        .get_region_texts(
+            &filter,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(REGION_TEXTS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_region_texts(&filter)
+        .await
+        .map_err(internal_error)?;
+    let page = Page::from_source(
+        texts,
+        query.pagination.limit(),
+        total,
+        REGION_TEXTS_CURSOR,
+        |text| CursorPosition {
+            timestamp: text.timestamp,
+            id: text.frame_id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
 }
 
 pub(crate) async fn api_list_audio_devices(
@@ -323,6 +624,26 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
     let app_start_time = state.app_start_time;
     let time_since_start = now.signed_duration_since(app_start_time);
 
+    if state.capture_pause.is_paused() {
+        let status = state.capture_pause.status();
+        let message = match status.resume_at {
+            _ if status.awaiting_consent => {
+                "Capture starts once the user consents to the capture policy.".to_string()
+            }
+            Some(resume_at) => format!("Capture is paused until {}.", resume_at),
+            None => "Capture is paused until it is resumed.".to_string(),
+        };
+        return JsonResponse(HealthCheckResponse {
+            status: "Paused".to_string(),
+            last_frame_timestamp: last_frame,
+            last_audio_timestamp: last_audio,
+            frame_status: "Paused".to_string(),
+            audio_status: "Paused".to_string(),
+            message,
+            verbose_instructions: None,
+        });
+    }
+
     if time_since_start < chrono::Duration::from_std(loading_threshold).unwrap() {
         return JsonResponse(HealthCheckResponse {
             status: "Loading".to_string(),
@@ -388,131 +709,4507 @@ pub async fn health_check(State(state): State<Arc<AppState>>) -> JsonResponse<He
     })
 }
 
-// Helper functions
-fn into_content_item(result: SearchResult) -> ContentItem {
-    match result {
-        SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
-            frame_id: ocr.frame_id,
-            text: ocr.ocr_text,
-            timestamp: ocr.timestamp,
-            file_path: ocr.file_path,
-            offset_index: ocr.offset_index,
-            app_name: ocr.app_name,
-            window_name: ocr.window_name, // Ensure this field is included
-        }),
-        SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
-            chunk_id: audio.audio_chunk_id,
-            transcription: audio.transcription,
-            timestamp: audio.timestamp,
-            file_path: audio.file_path,
-            offset_index: audio.offset_index,
-        }),
-        SearchResult::FTS(fts) => ContentItem::FTS(FTSContent {
-            text_id: fts.text_id,
-            matched_text: fts.matched_text,
-            frame_id: fts.frame_id,
-            timestamp: fts.frame_timestamp,
-            app_name: fts.app_name,
-            window_name: fts.window_name, // Ensure this field is included
-            file_path: fts.video_file_path,
-            original_frame_text: fts.original_frame_text,
-        }),
-    }
-}
-
-pub struct Server {
-    db: Arc<DatabaseManager>,
-    addr: SocketAddr,
-    vision_control: Arc<AtomicBool>,
-    audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+#[derive(Deserialize)]
+pub(crate) struct MeetingsQuery {
+    /// Matched against the summary and the calendar event's title and attendees
+    #[serde(default)]
+    q: Option<String>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
 }
 
-impl Server {
-    pub fn new(
-        db: Arc<DatabaseManager>,
-        addr: SocketAddr,
-        vision_control: Arc<AtomicBool>,
-        audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
-    ) -> Self {
-        Server {
-            db,
-            addr,
-            vision_control,
-            audio_devices_control,
-        }
-    }
-
-    pub async fn start<F>(
-        self,
-        device_status: HashMap<AudioDevice, DeviceControl>,
-        api_plugin: F,
-    ) -> Result<(), std::io::Error>
-    where
-        F: Fn(&axum::http::Request<axum::body::Body>) + Clone + Send + Sync + 'static,
-    {
-        // TODO could init w audio devices
-        let app_state = Arc::new(AppState {
-            db: self.db,
-            vision_control: self.vision_control,
-            audio_devices_control: self.audio_devices_control,
-            devices_status: device_status,
-            app_start_time: Utc::now(),
-        });
-
-        // https://github.com/tokio-rs/console
-        let app = Router::new()
-            .route("/search", get(search))
-            .route("/audio/list", get(api_list_audio_devices))
-            .route("/vision/list", post(api_list_monitors))
-            .route("/health", get(health_check))
-            .layer(ApiPluginLayer::new(api_plugin))
-            .layer(CorsLayer::permissive())
-            .layer(
-                // https://github.com/tokio-rs/axum/blob/main/examples/tracing-aka-logging/src/main.rs
-                TraceLayer::new_for_http()
-                    .make_span_with(DefaultMakeSpan::new().include_headers(true)),
+pub(crate) async fn list_meetings(
+    Query(query): Query<MeetingsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<MeetingSummary>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let started = Instant::now();
+    let MeetingsQuery { q, pagination } = query;
+    let q = q.as_deref().filter(|q| !q.is_empty());
+    let cursor = pagination.cursor()?;
+    let meetings = state
+        .db
+        .get_meeting_summaries(
+            q,
+            pagination.limit(),
+            pagination.offset,
+            cursor.get(MEETINGS_CURSOR),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list meeting summaries: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to list meeting summaries: {}", e)})),
             )
-            .with_state(app_state);
+        })?;
 
-        info!("Starting server on {}", self.addr);
+    let total = state.db.count_meeting_summaries(q).await.map_err(|e| {
+        error!("Failed to count meeting summaries: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to count meeting summaries: {}", e)})),
+        )
+    })?;
 
-        match serve(TcpListener::bind(self.addr).await?, app.into_make_service()).await {
-            Ok(_) => {
-                info!("Server stopped gracefully");
-                Ok(())
-            }
-            Err(e) => {
-                error!("Server error: {}", e);
-                Err(e)
-            }
+    let page = Page::from_source(
+        meetings,
+        pagination.limit(),
+        total,
+        MEETINGS_CURSOR,
+        |meeting| CursorPosition {
+            timestamp: meeting.start_time,
+            id: meeting.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(page, &pagination, started)))
+}
+
+pub(crate) async fn get_meeting(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<MeetingSummary>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match state.db.get_meeting_summary(id).await {
+        Ok(Some(meeting)) => Ok(JsonResponse(meeting)),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("Meeting summary {} not found", id)})),
+        )),
+        Err(e) => {
+            error!("Failed to get meeting summary {}: {}", id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get meeting summary: {}", e)})),
+            ))
         }
     }
 }
 
-// Curl commands for reference:
-// # 1. Basic search query
-// # curl "http://localhost:3030/search?q=test&limit=5&offset=0"
+#[derive(Deserialize)]
+pub(crate) struct ActivitySessionsQuery {
+    #[serde(default)]
+    category: Option<ActivityCategory>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
 
-// # 2. Search with content type filter (OCR)
-// # curl "http://localhost:3030/search?q=test&limit=5&offset=0&content_type=ocr"
+pub(crate) async fn list_activity_sessions(
+    Query(query): Query<ActivitySessionsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<ActivitySession>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list activity sessions: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list activity sessions: {}", e)})),
+        )
+    };
 
-// # 3. Search with content type filter (Audio)
-// # curl "http://localhost:3030/search?q=test&limit=5&offset=0&content_type=audio"
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let sessions = state
+        .db
+        .get_activity_sessions(
+            query.category,
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(ACTIVITY_SESSIONS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
 
-// # 4. Search with pagination
-// # curl "http://localhost:3030/search?q=test&limit=10&offset=20"
+    let total = state
+        .db
+        .count_activity_sessions(query.category, query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
 
-// # 6. Search with no query (should return all results)
-// # curl "http://localhost:3030/search?limit=5&offset=0"
+    let page = Page::from_source(
+        sessions,
+        query.pagination.limit(),
+        total,
+        ACTIVITY_SESSIONS_CURSOR,
+        |session| CursorPosition {
+            timestamp: session.start_time,
+            id: session.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
 
-// # 7. Start a device
-// # curl -X POST "http://localhost:3030/audio/start" -H "Content-Type: application/json" -d '{"device_id": "device1"}'
+#[derive(Deserialize)]
+pub(crate) struct ActivityCategoriesQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
 
-// # 8. Stop a device
-// # curl -X POST "http://localhost:3030/audio/stop" -H "Content-Type: application/json" -d '{"device_id": "device1"}'
+pub(crate) async fn activity_categories(
+    Query(query): Query<ActivityCategoriesQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<CategoryDuration>>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_activity_category_durations(query.start_time, query.end_time)
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to get activity categories: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get activity categories: {}", e)})),
+            )
+        })
+}
 
-// # 9. Get device status
-// # curl "http://localhost:3030/audio/status" -H "Content-Type: application/json" -d '{"device_id": "device1"}'
+#[derive(Deserialize)]
+pub(crate) struct CreateExtractionRuleRequest {
+    name: String,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    window_name: Option<String>,
+    kind: ExtractionKind,
+    /// regex, or the JSON schema (as an object) for llm rules
+    pattern: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CreateWatchRequest {
+    name: String,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    window_name: Option<String>,
+    kind: WatchKind,
+    /// regex, or comma separated keywords
+    pattern: String,
+    #[serde(default)]
+    source: WatchSource,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WatchAlertsQuery {
+    #[serde(default)]
+    watch_id: Option<i64>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FileEventsQuery {
+    /// Part of the path, case insensitive
+    #[serde(default)]
+    path: Option<String>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_file_events(
+    Query(query): Query<FileEventsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<FileEvent>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list file events: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list file events: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let events = state
+        .db
+        .get_file_events(
+            query.path.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(FILE_EVENTS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let total = state
+        .db
+        .count_file_events(
+            query.path.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        events,
+        query.pagination.limit(),
+        total,
+        FILE_EVENTS_CURSOR,
+        |event| CursorPosition {
+            timestamp: event.timestamp,
+            id: event.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WindowLayoutQuery {
+    /// Now when left out
+    #[serde(default)]
+    at: Option<DateTime<Utc>>,
+}
+
+/// Where the windows of each monitor were at a point in time
+pub(crate) async fn get_window_layout_at(
+    Query(query): Query<WindowLayoutQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let at = query.at.unwrap_or_else(Utc::now);
+    let layouts = state.db.get_window_layouts_at(at).await.map_err(|e| {
+        error!("Failed to get the window layout: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to get the window layout: {}", e)})),
+        )
+    })?;
+    Ok(JsonResponse(json!({"at": at, "layouts": layouts})))
+}
+
+async fn find_window_layout(
+    state: &AppState,
+    id: i64,
+) -> Result<WindowLayout, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_window_layout(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get window layout {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get window layout: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("window layout {} not found", id)})),
+            )
+        })
+}
+
+pub(crate) async fn get_window_layout(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<WindowLayout>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    Ok(JsonResponse(find_window_layout(&state, id).await?))
+}
+
+/// Moves the windows of a stored layout back where they were, as `move_window` actions the
+/// pipe of the request needs an `arrange` grant for
+pub(crate) async fn restore_window_layout(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let layout = find_window_layout(&state, id).await?;
+    automate(&state, &headers, restore_actions(&layout)).await
+}
+
+#[derive(Deserialize)]
+pub(crate) struct WindowHistoryQuery {
+    #[serde(default)]
+    app_name: Option<String>,
+    /// Part of the title, case insensitive
+    #[serde(default)]
+    window_name: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn window_history(
+    Query(query): Query<WindowHistoryQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<WindowEvent>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list window history: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list window history: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let events = state
+        .db
+        .get_window_events(
+            query.app_name.as_deref(),
+            query.window_name.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(WINDOW_EVENTS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let total = state
+        .db
+        .count_window_events(
+            query.app_name.as_deref(),
+            query.window_name.as_deref(),
+            query.start_time,
+            query.end_time,
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        events,
+        query.pagination.limit(),
+        total,
+        WINDOW_EVENTS_CURSOR,
+        |event| CursorPosition {
+            timestamp: event.timestamp,
+            id: event.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ShellCommandReport {
+    command: String,
+    shell: String,
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    exit_code: Option<i32>,
+    started_at: DateTime<Utc>,
+    #[serde(default)]
+    ended_at: Option<DateTime<Utc>>,
+}
+
+/// What the hooks of --print-shell-hook send as each command finishes, form encoded as curl can do
+/// it without escaping anything
+pub(crate) async fn report_shell_command(
+    State(state): State<Arc<AppState>>,
+    Form(report): Form<ShellCommandReport>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let shell: Shell = report.shell.parse().map_err(|e: anyhow::Error| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    })?;
+    let command = NewShellCommand {
+        timestamp: report.started_at,
+        ended_at: report.ended_at,
+        command: report.command,
+        cwd: report.cwd.filter(|cwd| !cwd.is_empty()),
+        exit_code: report.exit_code,
+        shell,
+        source: ShellCommandSource::Hook,
+    };
+    let recorded = record_hook_command(&state.db, &state.capture_pause, command)
+        .await
+        .map_err(|e| {
+            error!("Failed to record a shell command: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to record the command: {}", e)})),
+            )
+        })?;
+    Ok(JsonResponse(json!({"success": true, "recorded": recorded})))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ShellCommandsQuery {
+    /// Part of the command, case insensitive
+    #[serde(default)]
+    q: Option<String>,
+    /// The directory the commands ran in, or one of its subdirectories
+    #[serde(default)]
+    cwd: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    /// The commands run up to this frame, the first one is likely what it shows the output of
+    #[serde(default)]
+    frame_id: Option<i64>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_shell_commands(
+    Query(query): Query<ShellCommandsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<ShellCommand>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to list shell commands: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list shell commands: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let end_time = match query.frame_id {
+        Some(frame_id) => {
+            let Some((timestamp, _, _)) = state
+                .db
+                .get_frame_window(frame_id)
+                .await
+                .map_err(|e| internal_error(e.into()))?
+            else {
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    JsonResponse(json!({"error": format!("frame {} not found", frame_id)})),
+                ));
+            };
+            Some(query.end_time.map_or(timestamp, |end_time| end_time.min(timestamp)))
+        }
+        None => query.end_time,
+    };
+
+    let mut commands = state
+        .db
+        .get_shell_commands(
+            query.q.as_deref(),
+            query.cwd.as_deref(),
+            query.start_time,
+            end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(SHELL_COMMANDS_CURSOR),
+        )
+        .await
+        .map_err(|e| internal_error(e.into()))?;
+    link_terminal_frames(&state.db, &mut commands)
+        .await
+        .map_err(internal_error)?;
+
+    let total = state
+        .db
+        .count_shell_commands(
+            query.q.as_deref(),
+            query.cwd.as_deref(),
+            query.start_time,
+            end_time,
+        )
+        .await
+        .map_err(|e| internal_error(e.into()))?;
+
+    let page = Page::from_source(
+        commands,
+        query.pagination.limit(),
+        total,
+        SHELL_COMMANDS_CURSOR,
+        |command| CursorPosition {
+            timestamp: command.timestamp,
+            id: command.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+/// What the editor plugins send of the file open, the beacons of the same file continue the
+/// same context
+pub(crate) async fn report_editor_context(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(beacon): JsonResponse<EditorBeacon>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let beacon = beacon.validated().map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    })?;
+    let id = record_editor_beacon(&state.db, &state.capture_pause, beacon)
+        .await
+        .map_err(|e| {
+            error!("Failed to record an editor context: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to record the context: {}", e)})),
+            )
+        })?;
+    Ok(JsonResponse(json!({"success": true, "recorded": id.is_some(), "id": id})))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct EditorContextsQuery {
+    /// Part of the file, project or repository, case insensitive
+    #[serde(default)]
+    q: Option<String>,
+    /// Part of the remote url or directory of the repository, like its name
+    #[serde(default)]
+    repository: Option<String>,
+    /// Part of the file path
+    #[serde(default)]
+    file: Option<String>,
+    #[serde(default)]
+    git_branch: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_editor_contexts(
+    Query(query): Query<EditorContextsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<EditorContext>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to list editor contexts: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list editor contexts: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let mut contexts = state
+        .db
+        .get_editor_contexts(
+            query.q.as_deref(),
+            query.repository.as_deref(),
+            query.file.as_deref(),
+            query.git_branch.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(EDITOR_CONTEXTS_CURSOR),
+        )
+        .await
+        .map_err(|e| internal_error(e.into()))?;
+    link_editor_frames(&state.db, &mut contexts)
+        .await
+        .map_err(internal_error)?;
+
+    let total = state
+        .db
+        .count_editor_contexts(
+            query.q.as_deref(),
+            query.repository.as_deref(),
+            query.file.as_deref(),
+            query.git_branch.as_deref(),
+            query.start_time,
+            query.end_time,
+        )
+        .await
+        .map_err(|e| internal_error(e.into()))?;
+
+    let page = Page::from_source(
+        contexts,
+        query.pagination.limit(),
+        total,
+        EDITOR_CONTEXTS_CURSOR,
+        |context| CursorPosition {
+            timestamp: context.timestamp,
+            id: context.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AnalyticsQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// A table of the activity sessions, the word counts of the frames or of the transcriptions
+/// as `<table>.csv` or `<table>.parquet`, to be read by pandas or DuckDB
+pub(crate) async fn export_analytics(
+    Path(file): Path<String>,
+    Query(query): Query<AnalyticsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let not_found = |e: anyhow::Error| {
+        (
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    };
+    let (table, format) = file
+        .rsplit_once('.')
+        .ok_or_else(|| not_found(anyhow::anyhow!("expected <table>.csv or <table>.parquet")))?;
+    let table: AnalyticsTable = table.parse().map_err(not_found)?;
+    let format: AnalyticsFormat = format.parse().map_err(not_found)?;
+
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to export {} analytics: {}", table.as_str(), e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to export analytics: {}", e)})),
+        )
+    };
+    let rows = analytics_rows(&state.db, table, query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
+    let data = tokio::task::spawn_blocking(move || write_analytics(&rows, format))
+        .await
+        .map_err(|e| internal_error(e.into()))?
+        .map_err(internal_error)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, format.content_type().to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"{}.{}\"",
+                    table.as_str(),
+                    format.extension()
+                ),
+            ),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TrendsQuery {
+    #[serde(default = "default_trend_weeks")]
+    weeks: u32,
+}
+
+fn default_trend_weeks() -> u32 {
+    DEFAULT_TREND_WEEKS
+}
+
+/// Week over week trends of the last ended weeks and the anomalies of the last one, the
+/// weeks not computed yet are computed first
+pub(crate) async fn get_weekly_trends(
+    Query(query): Query<TrendsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<WeeklyTrends>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let now = Local::now();
+    let trends = async {
+        update_weekly_metrics(&state.db, now).await?;
+        weekly_trends(&state.db, now.date_naive(), query.weeks).await
+    }
+    .await
+    .map_err(|e| {
+        error!("Failed to compute the weekly trends: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to compute the trends: {}", e)})),
+        )
+    })?;
+    Ok(JsonResponse(trends))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AnalyticsQueryRequest {
+    sql: String,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    limit: Option<usize>,
+}
+
+/// Runs a read-only SELECT over the analytics tables: sessions, ocr, transcripts and usage
+pub(crate) async fn run_analytics_query(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<AnalyticsQueryRequest>,
+) -> Result<JsonResponse<AnalyticsQueryResult>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let start_time = payload
+        .start_time
+        .unwrap_or_else(|| payload.end_time.unwrap_or_else(Utc::now) - DEFAULT_QUERY_WINDOW);
+    let limit = payload
+        .limit
+        .unwrap_or(DEFAULT_QUERY_ROWS)
+        .clamp(1, MAX_QUERY_ROWS);
+    let result = query_analytics(
+        &state.db,
+        &payload.sql,
+        Some(start_time),
+        payload.end_time,
+        limit,
+    )
+    .await
+    .map_err(|e| {
+        debug!("Analytics query failed: {}", e);
+        // mistakes in the query are the caller's
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": format!("{:#}", e)})),
+        )
+    })?;
+    Ok(JsonResponse(result))
+}
+
+/// Days of history in the calendar feed when not given
+const DEFAULT_CALENDAR_FEED_DAYS: u32 = 30;
+const MAX_CALENDAR_FEED_DAYS: u32 = 365;
+
+#[derive(Deserialize)]
+pub(crate) struct CalendarFeedQuery {
+    #[serde(default)]
+    days: Option<u32>,
+}
+
+/// The meetings and focus blocks of the last days as an iCalendar feed to subscribe to
+pub(crate) async fn get_calendar_feed(
+    Query(query): Query<CalendarFeedQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let days = query
+        .days
+        .unwrap_or(DEFAULT_CALENDAR_FEED_DAYS)
+        .clamp(1, MAX_CALENDAR_FEED_DAYS);
+    let end = Utc::now();
+    let start = end - chrono::Duration::days(days as i64);
+    let ics = calendar_feed(&state.db, start, end).await.map_err(|e| {
+        error!("Failed to generate the calendar feed: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to generate the calendar feed: {}", e)})),
+        )
+    })?;
+    Ok(([(header::CONTENT_TYPE, "text/calendar; charset=utf-8")], ics).into_response())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct GitEventsQuery {
+    /// Part of the commit subject, case insensitive
+    #[serde(default)]
+    q: Option<String>,
+    /// Part of the path of the repository
+    #[serde(default)]
+    repository: Option<String>,
+    #[serde(default)]
+    kind: Option<GitEventKind>,
+    #[serde(default)]
+    branch: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    /// What happened during this meeting, within the times given
+    #[serde(default)]
+    meeting_id: Option<i64>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_git_events(
+    Query(query): Query<GitEventsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<GitEvent>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to list git events: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list git events: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let (start_time, end_time) = match query.meeting_id {
+        Some(meeting_id) => {
+            let Some(meeting) = state
+                .db
+                .get_meeting_summary(meeting_id)
+                .await
+                .map_err(|e| internal_error(e.into()))?
+            else {
+                return Err((
+                    StatusCode::NOT_FOUND,
+                    JsonResponse(json!({"error": format!("meeting {} not found", meeting_id)})),
+                ));
+            };
+            (
+                Some(query.start_time.map_or(meeting.start_time, |start_time| {
+                    start_time.max(meeting.start_time)
+                })),
+                Some(query.end_time.map_or(meeting.end_time, |end_time| {
+                    end_time.min(meeting.end_time)
+                })),
+            )
+        }
+        None => (query.start_time, query.end_time),
+    };
+
+    let events = state
+        .db
+        .get_git_events(
+            query.q.as_deref(),
+            query.repository.as_deref(),
+            query.kind,
+            query.branch.as_deref(),
+            start_time,
+            end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(GIT_EVENTS_CURSOR),
+        )
+        .await
+        .map_err(|e| internal_error(e.into()))?;
+    let total = state
+        .db
+        .count_git_events(
+            query.q.as_deref(),
+            query.repository.as_deref(),
+            query.kind,
+            query.branch.as_deref(),
+            start_time,
+            end_time,
+        )
+        .await
+        .map_err(|e| internal_error(e.into()))?;
+
+    let page = Page::from_source(
+        events,
+        query.pagination.limit(),
+        total,
+        GIT_EVENTS_CURSOR,
+        |event| CursorPosition {
+            timestamp: event.timestamp,
+            id: event.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct NotificationsQuery {
+    /// Part of the title or the body, case insensitive
+    #[serde(default)]
+    q: Option<String>,
+    /// Part of the app's name, or of its bundle id on macOS
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_notifications(
+    Query(query): Query<NotificationsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<Notification>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list notifications: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list notifications: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let notifications = state
+        .db
+        .get_notifications(
+            query.q.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(NOTIFICATIONS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_notifications(
+            query.q.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+        )
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        notifications,
+        query.pagination.limit(),
+        total,
+        NOTIFICATIONS_CURSOR,
+        |notification| CursorPosition {
+            timestamp: notification.timestamp,
+            id: notification.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FocusPeriodsQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_focus_periods(
+    Query(query): Query<FocusPeriodsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<FocusPeriod>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list focus periods: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list focus periods: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let periods = state
+        .db
+        .get_focus_periods(
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(FOCUS_PERIODS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_focus_periods(query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        periods,
+        query.pagination.limit(),
+        total,
+        FOCUS_PERIODS_CURSOR,
+        |period| CursorPosition {
+            timestamp: period.start_time,
+            id: period.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TimezonesQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_timezones(
+    Query(query): Query<TimezonesQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<TimezoneSegment>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list timezones: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list timezones: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let segments = state
+        .db
+        .get_timezone_segments(
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(TIMEZONES_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_timezone_segments(query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        segments,
+        query.pagination.limit(),
+        total,
+        TIMEZONES_CURSOR,
+        |segment| CursorPosition {
+            timestamp: segment.start_time,
+            id: segment.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ExtractedRecordsQuery {
+    #[serde(default)]
+    rule_id: Option<i64>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_extraction_rules(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<ExtractionRule>>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_extraction_rules(false)
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to list extraction rules: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to list extraction rules: {}", e)})),
+            )
+        })
+}
+
+pub(crate) async fn create_extraction_rule(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<CreateExtractionRuleRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let pattern = match payload.pattern {
+        serde_json::Value::String(pattern) => pattern,
+        schema => schema.to_string(),
+    };
+
+    validate_extraction_rule(payload.kind, &pattern).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": format!("Invalid extraction rule: {}", e)})),
+        )
+    })?;
+
+    let id = state
+        .db
+        .insert_extraction_rule(
+            &payload.name,
+            payload.app_name.as_deref(),
+            payload.window_name.as_deref(),
+            payload.kind,
+            &pattern,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to create extraction rule: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to create extraction rule: {}", e)})),
+            )
+        })?;
+
+    info!("Created extraction rule {} ({})", payload.name, id);
+    Ok(JsonResponse(json!({"id": id})))
+}
+
+pub(crate) async fn delete_extraction_rule(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match state.db.delete_extraction_rule(id).await {
+        Ok(true) => Ok(JsonResponse(json!({"success": true}))),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("Extraction rule {} not found", id)})),
+        )),
+        Err(e) => {
+            error!("Failed to delete extraction rule {}: {}", id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to delete extraction rule: {}", e)})),
+            ))
+        }
+    }
+}
+
+pub(crate) async fn list_watches(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<Vec<Watch>>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_watches(false)
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to list watches: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to list watches: {}", e)})),
+            )
+        })
+}
+
+fn reload_watchlist_error(e: anyhow::Error) -> (StatusCode, JsonResponse<serde_json::Value>) {
+    error!("Failed to reload watches: {}", e);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        JsonResponse(json!({"error": format!("Failed to reload watches: {}", e)})),
+    )
+}
+
+pub(crate) async fn create_watch(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<CreateWatchRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    compile_watch(payload.kind, &payload.pattern).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": format!("Invalid watch: {}", e)})),
+        )
+    })?;
+
+    let id = state
+        .db
+        .insert_watch(
+            &payload.name,
+            payload.app_name.as_deref(),
+            payload.window_name.as_deref(),
+            payload.kind,
+            &payload.pattern,
+            payload.source,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to create watch: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to create watch: {}", e)})),
+            )
+        })?;
+    state.watchlist.reload().await.map_err(reload_watchlist_error)?;
+
+    info!("Created watch {} ({})", payload.name, id);
+    Ok(JsonResponse(json!({"id": id})))
+}
+
+pub(crate) async fn delete_watch(
+    Path(id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match state.db.delete_watch(id).await {
+        Ok(true) => {
+            state.watchlist.reload().await.map_err(reload_watchlist_error)?;
+            Ok(JsonResponse(json!({"success": true})))
+        }
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("Watch {} not found", id)})),
+        )),
+        Err(e) => {
+            error!("Failed to delete watch {}: {}", id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to delete watch: {}", e)})),
+            ))
+        }
+    }
+}
+
+pub(crate) async fn list_watch_alerts(
+    Query(query): Query<WatchAlertsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<WatchAlert>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list watch alerts: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list watch alerts: {}", e)})),
+        )
+    };
+    let alerts = state
+        .db
+        .get_watch_alerts(
+            query.watch_id,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(WATCH_ALERTS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_watch_alerts(query.watch_id)
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        alerts,
+        query.pagination.limit(),
+        total,
+        WATCH_ALERTS_CURSOR,
+        |alert| CursorPosition {
+            timestamp: alert.timestamp,
+            id: alert.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+pub(crate) async fn list_extracted_records(
+    Query(query): Query<ExtractedRecordsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<ExtractedRecord>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let records = state
+        .db
+        .get_extracted_records(
+            query.rule_id,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(EXTRACTED_RECORDS_CURSOR),
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to list extracted records: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to list extracted records: {}", e)})),
+            )
+        })?;
+
+    let total = state
+        .db
+        .count_extracted_records(query.rule_id)
+        .await
+        .map_err(|e| {
+            error!("Failed to count extracted records: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to count extracted records: {}", e)})),
+            )
+        })?;
+
+    let page = Page::from_source(
+        records,
+        query.pagination.limit(),
+        total,
+        EXTRACTED_RECORDS_CURSOR,
+        |record| CursorPosition {
+            timestamp: record.timestamp,
+            id: record.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LlmUsageQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+pub(crate) struct LlmUsageResponse {
+    daily: Vec<LlmUsageSummary>,
+    month_to_date_cost_usd: f64,
+}
+
+pub(crate) async fn llm_usage(
+    Query(query): Query<LlmUsageQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<LlmUsageResponse>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to get llm usage: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to get llm usage: {}", e)})),
+        )
+    };
+
+    let daily = state
+        .db
+        .get_llm_usage_per_day(query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
+
+    let month_to_date_cost_usd = state
+        .db
+        .get_llm_cost_since(current_month_start())
+        .await
+        .map_err(internal_error)?;
+
+    Ok(JsonResponse(LlmUsageResponse {
+        daily,
+        month_to_date_cost_usd,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LlmCacheQuery {
+    /// Only invalidate the answers of this model
+    #[serde(default)]
+    model: Option<String>,
+}
+
+pub(crate) async fn llm_cache_stats(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let entries = state.db.count_llm_cache().await.map_err(|e| {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to count llm cache entries: {}", e)})),
+        )
+    })?;
+    Ok(JsonResponse(json!({"entries": entries})))
+}
+
+pub(crate) async fn invalidate_llm_cache(
+    Query(query): Query<LlmCacheQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let removed = state
+        .db
+        .invalidate_llm_cache(query.model.as_deref())
+        .await
+        .map_err(|e| {
+            error!("Failed to invalidate llm cache: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to invalidate llm cache: {}", e)})),
+            )
+        })?;
+    info!("Invalidated {} llm cache entries", removed);
+    Ok(JsonResponse(json!({"removed": removed})))
+}
+
+/// The models cache, and the models that can be prefetched by name
+pub(crate) async fn list_models() -> JsonResponse<serde_json::Value> {
+    let manager = model_manager();
+    let models = manager.list();
+    let known: Vec<serde_json::Value> = KNOWN_MODELS
+        .iter()
+        .map(|model| {
+            let cached = model.files.iter().all(|file| {
+                models.iter().any(|cached| {
+                    cached.repo == file.repo
+                        && cached.revision == file.revision
+                        && cached.files.iter().any(|f| f.file == file.file)
+                })
+            });
+            json!({"name": model.name, "files": model.files, "cached": cached})
+        })
+        .collect();
+    JsonResponse(json!({
+        "dir": manager.dir(),
+        "quota_bytes": manager.quota_bytes(),
+        "used_bytes": models.iter().map(|model| model.size_bytes).sum::<u64>(),
+        "models": models,
+        "known": known,
+    }))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PrefetchModelRequest {
+    /// One of the known models of GET /models, like whisper-tiny
+    name: String,
+}
+
+pub(crate) async fn prefetch_model(
+    JsonResponse(payload): JsonResponse<PrefetchModelRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let model = known_model(&payload.name).ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("no model is named {}", payload.name)})),
+        )
+    })?;
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to prefetch {}: {}", model.name, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to prefetch {}: {}", model.name, e)})),
+        )
+    };
+    let files = tokio::task::spawn_blocking(move || model_manager().prefetch(model))
+        .await
+        .map_err(|e| internal_error(e.into()))?
+        .map_err(internal_error)?;
+    Ok(JsonResponse(json!({"name": model.name, "files": files})))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RemoveModelRequest {
+    /// `repo@revision`, as GET /models lists them
+    id: String,
+}
+
+pub(crate) async fn remove_model(
+    JsonResponse(payload): JsonResponse<RemoveModelRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let manager = model_manager();
+    if !manager.list().iter().any(|model| model.id == payload.id) {
+        return Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("{} isn't in the models cache", payload.id)})),
+        ));
+    }
+    let freed = manager.remove(&payload.id).map_err(|e| {
+        error!("Failed to remove {}: {}", payload.id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to remove {}: {}", payload.id, e)})),
+        )
+    })?;
+    Ok(JsonResponse(json!({"removed": payload.id, "freed_bytes": freed})))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct DescribeFrameRequest {
+    /// What to look for, e.g. "what does the chart show?"
+    #[serde(default = "default_describe_instruction")]
+    instruction: String,
+    /// Only send this part of the frame to the model
+    #[serde(default)]
+    region: Option<ImageRegion>,
+    #[serde(default = "default_max_dimension")]
+    max_dimension: u32,
+}
+
+fn default_describe_instruction() -> String {
+    "Describe what is on the screen.".to_string()
+}
+
+fn default_max_dimension() -> u32 {
+    DEFAULT_LLM_IMAGE_MAX_DIMENSION
+}
+
+/// The chunk of a frame and the frame's place in it
+async fn recorded_frame(
+    db: &DatabaseManager,
+    frame_id: i64,
+) -> Result<(String, i64), StorageError> {
+    db.get_frame(frame_id)
+        .await?
+        .ok_or(StorageError::FrameNotFound(frame_id))
+}
+
+/// What couldn't be read back, with the `code` of the cause for the clients to branch on
+fn storage_error(e: StorageError) -> (StatusCode, JsonResponse<serde_json::Value>) {
+    error!("{}", e);
+    (
+        e.status(),
+        JsonResponse(json!({"error": e.to_string(), "code": e.code()})),
+    )
+}
+
+type ImageResponse = Result<Response, (StatusCode, JsonResponse<serde_json::Value>)>;
+
+/// The format of the `Accept` header to send an image stored as `stored` in
+fn accepted_format(
+    headers: &HeaderMap,
+    stored: FrameFormat,
+) -> Result<FrameFormat, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let accept = headers
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok());
+    negotiate_frame_format(accept, stored).ok_or_else(|| {
+        (
+            StatusCode::NOT_ACCEPTABLE,
+            JsonResponse(json!({
+                "error": "images are served as image/avif, image/webp, image/png or image/jpeg"
+            })),
+        )
+    })
+}
+
+/// Encodes at the quality of the stored frames, off the async threads since AVIF is slow
+async fn encode_image(image: DynamicImage, format: FrameFormat) -> anyhow::Result<Vec<u8>> {
+    let encoding = FrameEncoding {
+        format,
+        quality: frame_storage().map_or(DEFAULT_FRAME_QUALITY, |stored| stored.quality),
+    };
+    tokio::task::spawn_blocking(move || encoding.encode(&image)).await?
+}
+
+fn image_response(format: FrameFormat, image: Vec<u8>) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, format.content_type()),
+            (header::VARY, "Accept"),
+        ],
+        image,
+    )
+        .into_response()
+}
+
+/// `image` as it's stored, or re-encoded when the client asks for another format
+async fn negotiated_image(headers: &HeaderMap, image: Vec<u8>, stored: FrameFormat) -> ImageResponse {
+    let format = accepted_format(headers, stored)?;
+    if format == stored {
+        return Ok(image_response(format, image));
+    }
+    let encoded = async {
+        let decoded = image::load_from_memory(&image)?;
+        encode_image(decoded, format).await
+    }
+    .await
+    .map_err(|e| {
+        error!("Failed to re-encode an image as {:?}: {}", format, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to encode the image: {}", e)})),
+        )
+    })?;
+    Ok(image_response(format, encoded))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct FrameImageQuery {
+    /// Scales the frame down to about this many pixels wide, for previews
+    #[serde(default)]
+    width: Option<u32>,
+}
+
+/// The image of a recorded frame in the format of the `Accept` header, AVIF, WebP, PNG or
+/// JPEG. Frames stored as images are sent as they are when the client takes their format,
+/// PNG otherwise. With `width`, the smallest thumbnail at least as wide is sent instead.
+pub(crate) async fn get_frame_image(
+    Path(frame_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<FrameImageQuery>,
+    headers: HeaderMap,
+) -> ImageResponse {
+    let (file_path, offset_index) = recorded_frame(&state.db, frame_id)
+        .await
+        .map_err(storage_error)?;
+    if let Some(width) = query.width.filter(|width| *width > 0) {
+        return scaled_frame_image(&state.db, frame_id, &file_path, offset_index, width, &headers)
+            .await;
+    }
+    let stored = native_frame_file(&file_path, offset_index);
+    let format = accepted_format(
+        &headers,
+        stored.as_ref().map_or(FrameFormat::Png, |(_, format)| *format),
+    )?;
+    let encode_error = |e: anyhow::Error| {
+        error!("Failed to encode frame {}: {}", frame_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to encode the frame: {}", e)})),
+        )
+    };
+    if let Some((path, stored_format)) = stored.filter(|(_, stored)| *stored == format) {
+        let image = tokio::fs::read(path)
+            .await
+            .map_err(|e| encode_error(e.into()))?;
+        return Ok(image_response(stored_format, image));
+    }
+    let image = extract_frame(&file_path, offset_index)
+        .await
+        .map_err(storage_error)?;
+    let image = encode_image(image, format).await.map_err(encode_error)?;
+    Ok(image_response(format, image))
+}
+
+/// The thumbnail of the frame at the level for `width`, JPEG unless the client takes none.
+/// Frames recorded before thumbnails, or narrower than the level, are scaled from the full
+/// frame
+async fn scaled_frame_image(
+    db: &DatabaseManager,
+    frame_id: i64,
+    file_path: &str,
+    offset_index: i64,
+    width: u32,
+    headers: &HeaderMap,
+) -> ImageResponse {
+    if let Some(level) = thumbnail_level(width) {
+        let timestamp = db
+            .get_frame_timestamp(frame_id)
+            .await
+            .map_err(|e| storage_error(e.into()))?;
+        if let Some(timestamp) = timestamp {
+            match Thumbnails::of_chunk(file_path).read(timestamp, level).await {
+                Ok(Some(jpeg)) => return negotiated_image(headers, jpeg, FrameFormat::Jpeg).await,
+                Ok(None) => {}
+                Err(e) => warn!("Failed to read the thumbnail of frame {}: {}", frame_id, e),
+            }
+        }
+    }
+    let format = accepted_format(headers, FrameFormat::Jpeg)?;
+    let image = extract_frame(file_path, offset_index)
+        .await
+        .map_err(storage_error)?;
+    // as wide as the thumbnail would have been, the same whether it was written or not
+    let width = thumbnail_level(width).unwrap_or(width);
+    let encoded = async {
+        let scaled = tokio::task::spawn_blocking(move || scale_to_width(&image, width)).await?;
+        encode_image(scaled, format).await
+    }
+    .await
+    .map_err(|e| {
+        error!("Failed to scale frame {}: {}", frame_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to encode the frame: {}", e)})),
+        )
+    })?;
+    Ok(image_response(format, encoded))
+}
+
+/// Sends a recorded frame to the vision model for what OCR can't capture (charts, diagrams, UI state)
+pub(crate) async fn describe_frame(
+    Path(frame_id): Path<i64>,
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<DescribeFrameRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to describe frame {}: {}", frame_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to describe frame: {}", e)})),
+        )
+    };
+
+    let (file_path, offset_index) = recorded_frame(&state.db, frame_id)
+        .await
+        .map_err(storage_error)?;
+    let image = extract_frame(&file_path, offset_index)
+        .await
+        .map_err(storage_error)?;
+    let image_url = encode_image_for_llm(&image, payload.region, payload.max_dimension)
+        .map_err(internal_error)?;
+
+    let mut messages = state
+        .prompts
+        .get("describe")
+        .render(&[("instruction", &payload.instruction)]);
+    if let Some(user_message) = messages.last_mut() {
+        user_message.images.push(image_url);
+    }
+
+    let answer = state
+        .llm
+        .chat("describe", &messages)
+        .await
+        .map_err(internal_error)?;
+
+    Ok(JsonResponse(json!({
+        "frame_id": frame_id,
+        "description": answer.content,
+        "model": answer.model,
+    })))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AskRequest {
+    question: String,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+/// Search results the question starts with, the model searches for more with its tools
+const ASK_CONTEXT_RESULTS: u32 = 10;
+/// Of the text of each result in the context
+const ASK_CONTEXT_CHARS: usize = 1000;
+
+fn context_line(result: &SearchResult) -> String {
+    let (timestamp, source, text) = match result {
+        SearchResult::OCR(ocr) => (
+            ocr.timestamp,
+            format!("{} - {} (frame {})", ocr.app_name, ocr.window_name, ocr.frame_id),
+            &ocr.ocr_text,
+        ),
+        SearchResult::Audio(audio) => (audio.timestamp, "audio".to_string(), &audio.transcription),
+        SearchResult::FTS(fts) => (
+            fts.frame_timestamp,
+            format!("{} - {} (frame {})", fts.app_name, fts.window_name, fts.frame_id),
+            &fts.matched_text,
+        ),
+    };
+    let text: String = text.chars().take(ASK_CONTEXT_CHARS).collect();
+    format!("[{}] {}: {}", timestamp.to_rfc3339(), source, text.trim())
+}
+
+/// Who is asking, from the credentials of the request, and the name the usage is recorded
+/// under: the pipe's, or `task` for the other chats
+fn chat_caller(state: &AppState, headers: &HeaderMap, task: &str) -> (Caller, String) {
+    let caller = state.credentials.caller(headers);
+    let source = caller.pipe().unwrap_or(task).to_string();
+    (caller, source)
+}
+
+async fn run_tool_chat(
+    state: &AppState,
+    headers: &HeaderMap,
+    task: &str,
+    messages: &[ChatMessage],
+) -> Result<ToolChat, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let (caller, source) = chat_caller(state, headers, task);
+    let tools = state.llm_tools.allowed(&caller);
+    state
+        .llm
+        .chat_with_tools(&source, messages, &tools, caller.pipe())
+        .await
+        .map_err(|e| {
+            error!("Failed to answer the {} of {}: {}", task, source, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to answer: {}", e)})),
+            )
+        })
+}
+
+async fn answer_with_tools(
+    state: &AppState,
+    headers: &HeaderMap,
+    task: &str,
+    messages: &[ChatMessage],
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let chat = run_tool_chat(state, headers, task, messages).await?;
+    let tool_calls: &[ToolRun] = &chat.tool_runs;
+    Ok(JsonResponse(json!({
+        "answer": chat.answer.content,
+        "model": chat.answer.model,
+        "tool_calls": tool_calls,
+    })))
+}
+
+/// The results of a search for the question and the context they make for the model
+async fn search_for_question(
+    state: &AppState,
+    question: &str,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> Result<(Vec<SearchResult>, String), (StatusCode, JsonResponse<serde_json::Value>)> {
+    let results = state
+        .db
+        .search(
+            question,
+            ContentType::All,
+            ASK_CONTEXT_RESULTS,
+            0,
+            start_time,
+            end_time,
+            None,
+            None,
+        )
+        .await
+        .map_err(|e| {
+            error!("Failed to search for the question: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to search: {}", e)})),
+            )
+        })?;
+    let context = if results.is_empty() {
+        "Nothing matched the question word for word.".to_string()
+    } else {
+        results.iter().map(context_line).collect::<Vec<_>>().join("\n")
+    };
+    Ok((results, context))
+}
+
+fn empty_question() -> (StatusCode, JsonResponse<serde_json::Value>) {
+    (
+        StatusCode::BAD_REQUEST,
+        JsonResponse(json!({"error": "the question is empty"})),
+    )
+}
+
+/// Answers a question about the history from what a search of it finds, the model can search
+/// further and use the other tools it's allowed
+pub(crate) async fn ask(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<AskRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let question = payload.question.trim();
+    if question.is_empty() {
+        return Err(empty_question());
+    }
+    let (_, context) =
+        search_for_question(&state, question, payload.start_time, payload.end_time).await?;
+    let messages = state
+        .prompts
+        .get("ask")
+        .render(&[("question", question), ("context", &context)]);
+    answer_with_tools(&state, &headers, "ask", &messages).await
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ChatRequest {
+    messages: Vec<ChatMessage>,
+}
+
+/// A chat of a pipe or the user with the model, which calls the tools it's allowed. The
+/// messages are the whole conversation, the last one the user's
+pub(crate) async fn chat(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<ChatRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    if payload.messages.last().map(|message| message.role.as_str()) != Some("user") {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "the last message has to be the user's"})),
+        ));
+    }
+    answer_with_tools(&state, &headers, "chat", &payload.messages).await
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ConversationQuestion {
+    question: String,
+    /// Of a new conversation, the question by default
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+}
+
+async fn find_conversation(
+    state: &AppState,
+    id: i64,
+) -> Result<Conversation, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_conversation(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get conversation {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get conversation: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("conversation {} not found", id)})),
+            )
+        })
+}
+
+async fn conversation_messages(
+    state: &AppState,
+    id: i64,
+) -> Result<Vec<ConversationMessage>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state.db.get_conversation_messages(id).await.map_err(|e| {
+        error!("Failed to get the messages of conversation {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to get the messages: {}", e)})),
+        )
+    })
+}
+
+/// Asks the question with the earlier messages of the conversation and stores both the
+/// question and the answer, nothing is stored when the model fails
+async fn continue_conversation(
+    state: &AppState,
+    headers: &HeaderMap,
+    conversation_id: i64,
+    question: &str,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> Result<ConversationMessage, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let asked_at = Utc::now();
+    let history = conversation_messages(state, conversation_id).await?;
+    let (results, context) = search_for_question(state, question, start_time, end_time).await?;
+    let mut prompt = state
+        .prompts
+        .get("ask")
+        .render(&[("question", question), ("context", &context)]);
+    let question_message = prompt.split_off(1);
+    prompt.extend(chat_history(&history));
+    prompt.extend(question_message);
+
+    let chat = run_tool_chat(state, headers, "ask", &prompt).await?;
+    let citations = collect_citations(&results, &chat.tool_runs);
+    let stored = async {
+        state
+            .db
+            .insert_conversation_message(conversation_id, asked_at, "user", question, &[], &[], None)
+            .await?;
+        let answered_at = Utc::now();
+        let id = state
+            .db
+            .insert_conversation_message(
+                conversation_id,
+                answered_at,
+                "assistant",
+                &chat.answer.content,
+                &citations,
+                &chat.tool_runs,
+                Some(&chat.answer.model),
+            )
+            .await?;
+        Ok::<_, sqlx::Error>((id, answered_at))
+    }
+    .await;
+    let (id, answered_at) = stored.map_err(|e| {
+        error!("Failed to store the answer in conversation {}: {}", conversation_id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to store the answer: {}", e)})),
+        )
+    })?;
+    Ok(ConversationMessage {
+        id,
+        conversation_id,
+        timestamp: answered_at,
+        role: "assistant".to_string(),
+        content: chat.answer.content,
+        citations,
+        tool_calls: chat.tool_runs,
+        model: Some(chat.answer.model),
+    })
+}
+
+/// Starts a conversation with its first question, answered like /ask
+pub(crate) async fn create_conversation(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<ConversationQuestion>,
+) -> Result<(StatusCode, JsonResponse<serde_json::Value>), (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let question = payload.question.trim();
+    if question.is_empty() {
+        return Err(empty_question());
+    }
+    let title = payload
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|title| !title.is_empty())
+        .map(str::to_string)
+        .unwrap_or_else(|| title_from(question));
+    let (caller, _) = chat_caller(&state, &headers, "ask");
+    let id = state
+        .db
+        .insert_conversation(&title, caller.pipe(), Utc::now())
+        .await
+        .map_err(|e| {
+            error!("Failed to start a conversation: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to start the conversation: {}", e)})),
+            )
+        })?;
+    let answer = match continue_conversation(
+        &state,
+        &headers,
+        id,
+        question,
+        payload.start_time,
+        payload.end_time,
+    )
+    .await
+    {
+        Ok(answer) => answer,
+        Err(e) => {
+            // the question can be asked again, without an empty conversation left behind
+            if let Err(e) = state.db.delete_conversation(id).await {
+                warn!("Failed to remove the empty conversation {}: {}", id, e);
+            }
+            return Err(e);
+        }
+    };
+    let conversation = find_conversation(&state, id).await?;
+    Ok((
+        StatusCode::CREATED,
+        JsonResponse(json!({"conversation": conversation, "answer": answer})),
+    ))
+}
+
+/// Asks the next question of a conversation, the model sees the earlier ones and its answers
+pub(crate) async fn add_conversation_message(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<ConversationQuestion>,
+) -> Result<JsonResponse<ConversationMessage>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let question = payload.question.trim();
+    if question.is_empty() {
+        return Err(empty_question());
+    }
+    find_conversation(&state, id).await?;
+    let answer = continue_conversation(
+        &state,
+        &headers,
+        id,
+        question,
+        payload.start_time,
+        payload.end_time,
+    )
+    .await?;
+    Ok(JsonResponse(answer))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ConversationsQuery {
+    /// Part of the title or of a message, case insensitive
+    #[serde(default)]
+    q: Option<String>,
+    /// The conversations a pipe started
+    #[serde(default)]
+    pipe: Option<String>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_conversations(
+    Query(query): Query<ConversationsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<Conversation>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list conversations: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list conversations: {}", e)})),
+        )
+    };
+
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let conversations = state
+        .db
+        .get_conversations(
+            query.q.as_deref(),
+            query.pipe.as_deref(),
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(CONVERSATIONS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_conversations(query.q.as_deref(), query.pipe.as_deref())
+        .await
+        .map_err(internal_error)?;
+
+    let page = Page::from_source(
+        conversations,
+        query.pagination.limit(),
+        total,
+        CONVERSATIONS_CURSOR,
+        |conversation| CursorPosition {
+            timestamp: conversation.updated_at,
+            id: conversation.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+/// The conversation with all its messages, their citations and tool calls
+pub(crate) async fn get_conversation(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let conversation = find_conversation(&state, id).await?;
+    let messages = conversation_messages(&state, id).await?;
+    Ok(JsonResponse(
+        json!({"conversation": conversation, "messages": messages}),
+    ))
+}
+
+pub(crate) async fn delete_conversation(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match state.db.delete_conversation(id).await {
+        Ok(true) => Ok(JsonResponse(json!({"success": true}))),
+        Ok(false) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("conversation {} not found", id)})),
+        )),
+        Err(e) => {
+            error!("Failed to delete conversation {}: {}", id, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to delete conversation: {}", e)})),
+            ))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ConversationExportFormat {
+    #[default]
+    Markdown,
+    Json,
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ConversationExportQuery {
+    #[serde(default)]
+    format: ConversationExportFormat,
+}
+
+/// The conversation as a file to download, markdown or the JSON of /conversations/:id
+pub(crate) async fn export_conversation(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    Query(query): Query<ConversationExportQuery>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let conversation = find_conversation(&state, id).await?;
+    let messages = conversation_messages(&state, id).await?;
+    let (content_type, extension, body) = match query.format {
+        ConversationExportFormat::Markdown => (
+            "text/markdown; charset=utf-8",
+            "md",
+            conversations::to_markdown(&conversation, &messages),
+        ),
+        ConversationExportFormat::Json => (
+            "application/json",
+            "json",
+            json!({"conversation": conversation, "messages": messages}).to_string(),
+        ),
+    };
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"conversation_{}.{}\"", id, extension),
+            ),
+        ],
+        body,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SimilarFramesRequest {
+    /// Base64 encoded screenshot (png, jpeg, ...)
+    #[serde(default)]
+    image: Option<String>,
+    /// Or a recorded frame
+    #[serde(default)]
+    frame_id: Option<i64>,
+    #[serde(default = "default_max_distance")]
+    max_distance: u32,
+    #[serde(default = "default_similar_limit")]
+    limit: usize,
+}
+
+fn default_max_distance() -> u32 {
+    DEFAULT_MAX_DISTANCE
+}
+
+fn default_similar_limit() -> usize {
+    20
+}
+
+/// The past frames that look like a screenshot or a recorded frame, for "when did I see this?"
+pub(crate) async fn similar_frames(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<SimilarFramesRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let started = Instant::now();
+    let bad_request = |message: String| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": message})),
+        )
+    };
+    let internal_error = |e: anyhow::Error| {
+        error!("Failed to find similar frames: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to find similar frames: {}", e)})),
+        )
+    };
+
+    let hash = match (&payload.image, payload.frame_id) {
+        (Some(image), None) => {
+            let bytes = BASE64_STANDARD
+                .decode(image.trim())
+                .map_err(|e| bad_request(format!("image is not base64: {}", e)))?;
+            let image = image::load_from_memory(&bytes)
+                .map_err(|e| bad_request(format!("image can't be decoded: {}", e)))?;
+            perceptual_hash(&image)
+        }
+        (None, Some(frame_id)) => {
+            match state.db.get_frame_window(frame_id).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    return Err((
+                        StatusCode::NOT_FOUND,
+                        JsonResponse(json!({"error": format!("Frame {} not found", frame_id)})),
+                    ))
+                }
+                Err(e) => return Err(internal_error(e.into())),
+            }
+            frame_hash(&state.db, frame_id)
+                .await
+                .map_err(internal_error)?
+        }
+        _ => {
+            return Err(bad_request(
+                "either image or frame_id is required".to_string(),
+            ))
+        }
+    };
+
+    let frames = find_similar_frames(
+        &state.db,
+        hash,
+        payload.max_distance,
+        payload.limit.min(MAX_PAGE_SIZE as usize),
+        payload.frame_id,
+    )
+    .await
+    .map_err(internal_error)?;
+
+    Ok(JsonResponse(json!({
+        "data": frames,
+        "took_ms": started.elapsed().as_millis() as u64,
+    })))
+}
+
+pub(crate) async fn capabilities(State(state): State<Arc<AppState>>) -> JsonResponse<Capabilities> {
+    JsonResponse(machine_capabilities(FeatureCapabilities {
+        encryption: state.encryption.is_some(),
+        consumer_redaction: state.consumer_redaction.is_some(),
+        compliance: state.compliance.is_some(),
+        live_view: state.live_view.is_some(),
+        automation: state.automation.is_some(),
+        vault_export: state.vault_export.is_some(),
+        llm_model: state.llm.model().to_string(),
+        ..Default::default()
+    }))
+}
+
+/// Capacity and drop counters of the channels between capture, OCR, transcription, storage
+/// and the event bus
+pub(crate) async fn list_channels() -> JsonResponse<Vec<ChannelReport>> {
+    JsonResponse(channel_reports())
+}
+
+pub(crate) async fn get_profile(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<ActiveProfile>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match &state.profile {
+        Some(profile) => Ok(JsonResponse(profile.as_ref().clone())),
+        None => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": "no profile, start screenpipe with --profile"})),
+        )),
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LiveOcrQuery {
+    /// Only the words of the focused window
+    #[serde(default)]
+    focused: bool,
+}
+
+/// The words of the screen, redacted for the consumers `--redact-for` names
+async fn live_ocr_value(
+    state: &AppState,
+    headers: &HeaderMap,
+    screen: &LiveOcrScreen,
+    focused: bool,
+) -> serde_json::Value {
+    let mut screen = screen.clone();
+    if focused {
+        screen.blocks.retain(|block| block.focused);
+    }
+    let value = json!(screen);
+    match state
+        .consumer_redaction
+        .as_ref()
+        .filter(|redaction| {
+            redaction.applies_to(Consumer::of_request(&state.credentials, headers))
+        })
+    {
+        Some(redaction) => redaction.redact_owned(value).await,
+        None => value,
+    }
+}
+
+pub(crate) async fn get_live_ocr(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LiveOcrQuery>,
+    headers: HeaderMap,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let screen = state
+        .live_ocr
+        .current()
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("failed to read the screen: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": "no frame captured yet"})),
+            )
+        })?;
+    Ok(JsonResponse(
+        live_ocr_value(&state, &headers, &screen, query.focused).await,
+    ))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct CopyScreenRequest {
+    /// Runs the text through `clean_copied_text`
+    #[serde(default)]
+    clean: bool,
+}
+
+/// The text of the focused window as it's on the screen now, for the desktop app's hotkey to
+/// put on the clipboard. Redacted as `GET /ocr/live` is
+pub(crate) async fn copy_screen_text(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<CopyScreenRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let not_found = |error: &str| (StatusCode::NOT_FOUND, JsonResponse(json!({"error": error})));
+    let screen = state
+        .live_ocr
+        .current()
+        .await
+        .map_err(|e| {
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("failed to read the screen: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| not_found("no frame captured yet"))?;
+    let blocks: Vec<_> = screen
+        .blocks
+        .iter()
+        .filter(|block| block.focused)
+        .cloned()
+        .collect();
+    let Some(window) = blocks.first() else {
+        return Err(not_found("no text in the focused window"));
+    };
+    let mut text = blocks_text(&blocks);
+    if payload.clean {
+        text = clean_copied_text(&text);
+    }
+    let value = json!({
+        "text": text,
+        "app_name": window.app_name,
+        "window_name": window.window_name,
+        "frame_number": screen.frame_number,
+        "timestamp": screen.timestamp,
+        "cleaned": payload.clean,
+    });
+    let value = match state
+        .consumer_redaction
+        .as_ref()
+        .filter(|redaction| {
+            redaction.applies_to(Consumer::of_request(&state.credentials, &headers))
+        })
+    {
+        Some(redaction) => redaction.redact_owned(value).await,
+        None => value,
+    };
+    Ok(JsonResponse(value))
+}
+
+/// Server-sent `ocr` events with the words of each new frame, as `GET /ocr/live` returns them
+pub(crate) async fn stream_live_ocr(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LiveOcrQuery>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let screens = state.live_ocr.stream().then(move |screen| {
+        let state = state.clone();
+        let headers = headers.clone();
+        async move {
+            let event = match screen {
+                Ok(screen) => SseEvent::default().event("ocr").data(
+                    live_ocr_value(&state, &headers, &screen, query.focused)
+                        .await
+                        .to_string(),
+                ),
+                Err(e) => SseEvent::default()
+                    .event("error")
+                    .data(json!({"error": format!("failed to read the screen: {}", e)}).to_string()),
+            };
+            Ok(event)
+        }
+    });
+    Sse::new(screens).keep_alive(KeepAlive::default())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct LiveQuery {
+    token: Option<String>,
+    /// Of a --multi-user server, passed on to the segments
+    user_token: Option<String>,
+}
+
+/// The live view if enabled and the request carries its token, as `?token=` or a bearer header
+fn authorized_live_view(
+    state: &AppState,
+    query: &LiveQuery,
+    headers: &HeaderMap,
+) -> Result<Arc<LiveView>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let live_view = state.live_view.clone().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": "live view is disabled, start screenpipe with --enable-live-view"})),
+        )
+    })?;
+    let bearer = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if !live_view.is_authorized(query.token.as_deref().or(bearer)) {
+        return Err((
+            StatusCode::UNAUTHORIZED,
+            JsonResponse(json!({"error": "invalid or missing live view token"})),
+        ));
+    }
+    Ok(live_view)
+}
+
+pub(crate) async fn live_page(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LiveQuery>,
+    headers: HeaderMap,
+) -> Result<Html<&'static str>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    authorized_live_view(&state, &query, &headers)?;
+    Ok(Html(LIVE_PAGE))
+}
+
+/// The player of the page, it holds no secret and is served to anyone
+pub(crate) async fn live_player() -> Response {
+    (
+        [(header::CONTENT_TYPE, "text/javascript; charset=utf-8")],
+        LIVE_PLAYER,
+    )
+        .into_response()
+}
+
+pub(crate) async fn live_playlist(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LiveQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let live_view = authorized_live_view(&state, &query, &headers)?;
+    let user_token = query.user_token.as_deref().or_else(|| {
+        headers
+            .get(USER_TOKEN_HEADER)
+            .and_then(|value| value.to_str().ok())
+    });
+    let playlist = live_view.playlist(user_token).await.map_err(|e| {
+        error!("Live view failed: {}", e);
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    })?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/vnd.apple.mpegurl"),
+            (header::CACHE_CONTROL, "no-cache"),
+        ],
+        playlist,
+    )
+        .into_response())
+}
+
+pub(crate) async fn live_segment(
+    State(state): State<Arc<AppState>>,
+    Path(segment): Path<String>,
+    Query(query): Query<LiveQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let live_view = authorized_live_view(&state, &query, &headers)?;
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": "segment not found"})),
+        )
+    };
+    if !is_live_segment(&segment) {
+        return Err(not_found());
+    }
+    let data = tokio::fs::read(live_view.dir().join(&segment))
+        .await
+        .map_err(|_| not_found())?;
+    Ok(([(header::CONTENT_TYPE, "video/mp4")], data).into_response())
+}
+
+pub(crate) async fn create_timelapse(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(options): JsonResponse<TimelapseOptions>,
+) -> Result<(StatusCode, JsonResponse<TimelapseJob>), (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    if let Err(e) = options.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        ));
+    }
+    let job = state.timelapse.start(options).await.map_err(|e| {
+        error!("Failed to start timelapse: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to start timelapse: {}", e)})),
+        )
+    })?;
+    Ok((StatusCode::ACCEPTED, JsonResponse(job)))
+}
+
+async fn find_timelapse_job(
+    state: &AppState,
+    id: i64,
+) -> Result<TimelapseJob, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .timelapse
+        .get_job(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get timelapse {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get timelapse: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("timelapse {} not found", id)})),
+            )
+        })
+}
+
+pub(crate) async fn get_timelapse(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<TimelapseJob>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    Ok(JsonResponse(find_timelapse_job(&state, id).await?))
+}
+
+pub(crate) async fn get_timelapse_video(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let job = find_timelapse_job(&state, id).await?;
+    if job.status != TimelapseStatus::Done {
+        return Err((
+            StatusCode::CONFLICT,
+            JsonResponse(json!({"error": format!("timelapse {} is {}", id, job.status.as_str())})),
+        ));
+    }
+    let video = tokio::fs::read(&job.output_path).await.map_err(|e| {
+        error!("Failed to read timelapse {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to read timelapse: {}", e)})),
+        )
+    })?;
+    Ok(([(header::CONTENT_TYPE, "video/mp4")], video).into_response())
+}
+
+pub(crate) async fn create_clip(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(options): JsonResponse<ClipOptions>,
+) -> Result<(StatusCode, JsonResponse<ClipJob>), (StatusCode, JsonResponse<serde_json::Value>)> {
+    if let Err(e) = options.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        ));
+    }
+    let job = state.clips.start(options).await.map_err(|e| {
+        error!("Failed to start clip: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to start clip: {}", e)})),
+        )
+    })?;
+    Ok((StatusCode::ACCEPTED, JsonResponse(job)))
+}
+
+async fn find_clip_job(
+    state: &AppState,
+    id: i64,
+) -> Result<ClipJob, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .clips
+        .get_job(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get clip {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get clip: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("clip {} not found", id)})),
+            )
+        })
+}
+
+pub(crate) async fn get_clip(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<ClipJob>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    Ok(JsonResponse(find_clip_job(&state, id).await?))
+}
+
+pub(crate) async fn get_clip_video(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let job = find_clip_job(&state, id).await?;
+    if job.status != TimelapseStatus::Done {
+        return Err((
+            StatusCode::CONFLICT,
+            JsonResponse(json!({"error": format!("clip {} is {}", id, job.status.as_str())})),
+        ));
+    }
+    let video = tokio::fs::read(&job.output_path).await.map_err(|e| {
+        error!("Failed to read clip {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to read clip: {}", e)})),
+        )
+    })?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "video/mp4".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"clip_{}.mp4\"", id),
+            ),
+        ],
+        video,
+    )
+        .into_response())
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AuditLogQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    actor: Option<String>,
+    #[serde(default)]
+    action: Option<AuditAction>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+/// Every query, export and deletion made through the API, newest first
+pub(crate) async fn list_audit_log(
+    Query(query): Query<AuditLogQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<AuditEntry>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let db_error = |e: sqlx::Error| {
+        error!("Failed to read the audit log: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to read the audit log: {}", e)})),
+        )
+    };
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let entries = state
+        .db
+        .get_audit_entries(
+            query.start_time,
+            query.end_time,
+            query.actor.as_deref(),
+            query.action,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(AUDIT_CURSOR),
+        )
+        .await
+        .map_err(db_error)?;
+    let total = state
+        .db
+        .count_audit_entries(
+            query.start_time,
+            query.end_time,
+            query.actor.as_deref(),
+            query.action,
+        )
+        .await
+        .map_err(db_error)?;
+
+    let page = Page::from_source(
+        entries,
+        query.pagination.limit(),
+        total,
+        AUDIT_CURSOR,
+        |entry| CursorPosition {
+            timestamp: entry.timestamp,
+            id: entry.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AutomationRequest {
+    actions: Vec<AutomationAction>,
+}
+
+/// Synthesizes the actions for the pipe of the request if its grant allows them all
+pub(crate) async fn run_automation(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<AutomationRequest>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    automate(&state, &headers, payload.actions).await
+}
+
+async fn automate(
+    state: &AppState,
+    headers: &HeaderMap,
+    actions: Vec<AutomationAction>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let bad_request =
+        |error: String| (StatusCode::BAD_REQUEST, JsonResponse(json!({"error": error})));
+    let automation = state.automation.clone().ok_or_else(|| {
+        (
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({
+                "error": "automation is off, start screenpipe with --automation-grants"
+            })),
+        )
+    })?;
+    if is_cross_origin(headers) {
+        return Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({"error": "web pages can't synthesize input"})),
+        ));
+    }
+    let Caller::Pipe(pipe) = state.credentials.caller(headers) else {
+        return Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({
+                "error": format!(
+                    "only pipes can synthesize input, with their name in {} and their token in {}",
+                    PIPE_HEADER, PIPE_TOKEN_HEADER
+                )
+            })),
+        ));
+    };
+    if actions.is_empty() || actions.len() > MAX_ACTIONS_PER_REQUEST {
+        return Err(bad_request(format!(
+            "between 1 and {} actions can be asked for at once",
+            MAX_ACTIONS_PER_REQUEST
+        )));
+    }
+    for action in &actions {
+        action.validate().map_err(|e| bad_request(e.to_string()))?;
+    }
+
+    let focused = get_focused_window_title().await;
+    let records = automation
+        .perform(&pipe, actions, focused)
+        .await
+        .map_err(|e| {
+            error!("Failed to synthesize input: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to synthesize input: {}", e)})),
+            )
+        })?;
+    let status = match records.first().map(|record| record.status) {
+        Some(AutomationStatus::Refused) => StatusCode::FORBIDDEN,
+        Some(AutomationStatus::Failed) => StatusCode::INTERNAL_SERVER_ERROR,
+        _ => return Ok(JsonResponse(json!({"actions": records}))),
+    };
+    Err((
+        status,
+        JsonResponse(json!({
+            "error": records.first().and_then(|record| record.reason.clone()),
+            "actions": records,
+        })),
+    ))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct AutomationActionsQuery {
+    #[serde(default)]
+    pipe: Option<String>,
+    #[serde(default)]
+    status: Option<AutomationStatus>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+/// The input asked for by pipes, synthesized or refused
+pub(crate) async fn list_automation_actions(
+    Query(query): Query<AutomationActionsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<AutomationRecord>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let db_error = |e: sqlx::Error| {
+        error!("Failed to list automation actions: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list automation actions: {}", e)})),
+        )
+    };
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let records = state
+        .db
+        .get_automation_records(
+            query.pipe.as_deref(),
+            query.status,
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(AUTOMATION_CURSOR),
+        )
+        .await
+        .map_err(db_error)?;
+    let total = state
+        .db
+        .count_automation_records(
+            query.pipe.as_deref(),
+            query.status,
+            query.start_time,
+            query.end_time,
+        )
+        .await
+        .map_err(db_error)?;
+
+    let page = Page::from_source(
+        records,
+        query.pagination.limit(),
+        total,
+        AUTOMATION_CURSOR,
+        |record| CursorPosition {
+            timestamp: record.timestamp,
+            id: record.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct RedactionsQuery {
+    #[serde(default)]
+    source: Option<RedactionSource>,
+    #[serde(default)]
+    rule: Option<String>,
+    #[serde(default)]
+    app_name: Option<String>,
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+/// What the redaction policy took out of the record, the rule and where but not the content
+pub(crate) async fn list_redactions(
+    Query(query): Query<RedactionsQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<
+    JsonResponse<PaginatedResponse<RedactionRecord>>,
+    (StatusCode, JsonResponse<serde_json::Value>),
+> {
+    let db_error = |e: sqlx::Error| {
+        error!("Failed to list redactions: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list redactions: {}", e)})),
+        )
+    };
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let redactions = state
+        .db
+        .get_redactions(
+            query.source,
+            query.rule.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(REDACTIONS_CURSOR),
+        )
+        .await
+        .map_err(db_error)?;
+    let total = state
+        .db
+        .count_redactions(
+            query.source,
+            query.rule.as_deref(),
+            query.app_name.as_deref(),
+            query.start_time,
+            query.end_time,
+        )
+        .await
+        .map_err(db_error)?;
+
+    let page = Page::from_source(
+        redactions,
+        query.pagination.limit(),
+        total,
+        REDACTIONS_CURSOR,
+        |redaction| CursorPosition {
+            timestamp: redaction.timestamp,
+            id: redaction.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ForgetRequest {
+    #[serde(flatten)]
+    filter: ForgetFilter,
+    #[serde(default)]
+    dry_run: bool,
+}
+
+/// Removes all data matching the filter, a dry run reports what would be removed
+pub(crate) async fn forget_data(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(request): JsonResponse<ForgetRequest>,
+) -> Result<JsonResponse<ForgetReport>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    if request.filter.normalized().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(
+                json!({"error": "the filter needs a time range, an app name or a keyword"}),
+            ),
+        ));
+    }
+    let report = forget(&state.db, &request.filter, request.dry_run)
+        .await
+        .map_err(|e| {
+            error!("Failed to forget data: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to forget data: {}", e)})),
+            )
+        })?;
+    Ok(JsonResponse(report))
+}
+
+#[derive(Serialize)]
+pub(crate) struct EncryptionStatus {
+    enabled: bool,
+    /// False in builds without the sqlcipher feature
+    database_encrypted: bool,
+    active_key_id: Option<u32>,
+    key_ids: Vec<u32>,
+    plaintext_chunks: i64,
+    /// Re-encrypted with the active key in the background
+    chunks_on_old_keys: i64,
+    /// The database gets a rotated key at the next start
+    database_rekey_pending: bool,
+}
+
+async fn encryption_status(
+    state: &AppState,
+) -> Result<EncryptionStatus, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let counts = state
+        .db
+        .count_chunks_by_encryption_key()
+        .await
+        .map_err(|e| {
+            error!("Failed to count encrypted chunks: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to count encrypted chunks: {}", e)})),
+            )
+        })?;
+    let active_key_id = state.encryption.as_ref().map(|keys| keys.active_key_id());
+    let plaintext_chunks = counts
+        .iter()
+        .filter(|(key_id, _)| key_id.is_none())
+        .map(|(_, count)| count)
+        .sum();
+    let chunks_on_old_keys = counts
+        .iter()
+        .filter(|(key_id, _)| key_id.is_some() && *key_id != active_key_id)
+        .map(|(_, count)| count)
+        .sum();
+    Ok(EncryptionStatus {
+        enabled: state.encryption.is_some(),
+        database_encrypted: state.encryption.is_some() && DatabaseManager::supports_encryption(),
+        active_key_id,
+        key_ids: state
+            .encryption
+            .as_ref()
+            .map(|keys| keys.key_ids())
+            .unwrap_or_default(),
+        plaintext_chunks,
+        chunks_on_old_keys,
+        database_rekey_pending: state
+            .encryption
+            .as_ref()
+            .is_some_and(|keys| keys.previous_database_key().is_some()),
+    })
+}
+
+pub(crate) async fn get_encryption_status(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<EncryptionStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    Ok(JsonResponse(encryption_status(&state).await?))
+}
+
+/// Switches to new keys, chunks are re-encrypted in the background and the old keys
+/// removed from the keychain once nothing uses them
+pub(crate) async fn rotate_encryption_keys(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<EncryptionStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let Some(keys) = &state.encryption else {
+        return Err((
+            StatusCode::CONFLICT,
+            JsonResponse(json!({"error": "encryption is not enabled, start with --encrypt-data"})),
+        ));
+    };
+    let key_id = keys.rotate().await.map_err(|e| {
+        error!("Failed to rotate encryption keys: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to rotate encryption keys: {}", e)})),
+        )
+    })?;
+    emit_event("encryption.rotated", json!({"active_key_id": key_id}));
+    Ok(JsonResponse(encryption_status(&state).await?))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PrivateWindowsReport {
+    titles: Vec<String>,
+}
+
+/// Browser extensions report the titles of their private windows, which are then left out
+/// of the capture on top of the ones recognized by their title
+pub(crate) async fn report_private_browser_windows(
+    JsonResponse(payload): JsonResponse<PrivateWindowsReport>,
+) -> JsonResponse<serde_json::Value> {
+    let windows = payload.titles.len();
+    report_private_windows(payload.titles);
+    debug!("Browser extension reported {} private windows", windows);
+    JsonResponse(json!({"success": true}))
+}
+
+/// Browser extensions push the text of the page shown in a window, which the frames of that
+/// window then store instead of their OCR
+pub(crate) async fn push_browser_page(
+    JsonResponse(page): JsonResponse<BrowserPage>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    if page.title.trim().is_empty() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "title is needed to find the window showing the page"})),
+        ));
+    }
+    if page.text.len() > MAX_PAGE_TEXT_LEN {
+        return Err((
+            StatusCode::PAYLOAD_TOO_LARGE,
+            JsonResponse(json!({
+                "error": format!("text is longer than {} bytes", MAX_PAGE_TEXT_LEN)
+            })),
+        ));
+    }
+    debug!("Browser extension pushed {}", page.url);
+    report_browser_page(page);
+    Ok(JsonResponse(json!({"success": true})))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct PauseRequest {
+    /// Capture resumes on its own after this long
+    #[serde(default)]
+    duration_secs: Option<u64>,
+}
+
+pub(crate) async fn capture_pause_status(
+    State(state): State<Arc<AppState>>,
+) -> JsonResponse<PauseStatus> {
+    JsonResponse(state.capture_pause.status())
+}
+
+pub(crate) async fn pause_capture(
+    State(state): State<Arc<AppState>>,
+    payload: Option<JsonResponse<PauseRequest>>,
+) -> Result<JsonResponse<PauseStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let duration_secs = payload.and_then(|JsonResponse(payload)| payload.duration_secs);
+    if duration_secs == Some(0) {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": "duration_secs must be greater than 0"})),
+        ));
+    }
+    Ok(JsonResponse(
+        state
+            .capture_pause
+            .pause(duration_secs.map(Duration::from_secs)),
+    ))
+}
+
+pub(crate) async fn resume_capture(
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PauseStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let status = state.capture_pause.resume();
+    if status.awaiting_consent {
+        return Err((
+            StatusCode::CONFLICT,
+            JsonResponse(
+                json!({"error": "capture starts once the user consents to the capture policy"}),
+            ),
+        ));
+    }
+    Ok(JsonResponse(status))
+}
+
+pub(crate) async fn get_compliance_status(
+    State(state): State<Arc<AppState>>,
+) -> JsonResponse<ComplianceStatus> {
+    JsonResponse(match &state.compliance {
+        Some(compliance) => compliance.status(),
+        None => ComplianceStatus::disabled(),
+    })
+}
+
+#[derive(Deserialize)]
+pub struct ConsentRequest {
+    accepted: bool,
+}
+
+/// The answer to the consent prompt shown at login, only the app showing the prompt can give it
+pub async fn record_compliance_consent(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+    JsonResponse(payload): JsonResponse<ConsentRequest>,
+) -> Result<JsonResponse<ComplianceStatus>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    if is_cross_origin(&headers) || state.credentials.caller(&headers) != Caller::App {
+        return Err((
+            StatusCode::FORBIDDEN,
+            JsonResponse(json!({
+                "error": "consent is given in the app, with the token of app_token in the data directory"
+            })),
+        ));
+    }
+    let Some(compliance) = &state.compliance else {
+        return Err((
+            StatusCode::CONFLICT,
+            JsonResponse(json!({"error": "compliance mode is not enabled"})),
+        ));
+    };
+    if let Err(e) = compliance.record_consent(&state.db, payload.accepted).await {
+        error!("Failed to record consent: {}", e);
+        return Err((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to record consent: {}", e)})),
+        ));
+    }
+    Ok(JsonResponse(compliance.status()))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct ConsentRecordsQuery {
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+pub(crate) async fn list_consent_records(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ConsentRecordsQuery>,
+) -> Result<JsonResponse<Vec<ConsentRecord>>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_consent_records(query.limit)
+        .await
+        .map(JsonResponse)
+        .map_err(|e| {
+            error!("Failed to list consent records: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to list consent records: {}", e)})),
+            )
+        })
+}
+
+fn timezone_param(name: &str) -> Result<Tz, (StatusCode, JsonResponse<serde_json::Value>)> {
+    parse_timezone(name).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        )
+    })
+}
+
+#[derive(Deserialize)]
+pub(crate) struct VaultExportQuery {
+    /// Today if not set
+    date: Option<NaiveDate>,
+    /// IANA timezone the day and its times are in, like the one the day was spent in when
+    /// travelling. The local timezone if not set
+    tz: Option<String>,
+}
+
+/// Writes the daily note of a day into the vault now instead of waiting for the next export
+pub(crate) async fn export_vault_note(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<VaultExportQuery>,
+) -> Result<JsonResponse<serde_json::Value>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let Some(exporter) = &state.vault_export else {
+        return Err((
+            StatusCode::CONFLICT,
+            JsonResponse(json!({"error": "vault export is not enabled, start with --vault-dir"})),
+        ));
+    };
+    let timezone = query.tz.as_deref().map(timezone_param).transpose()?;
+    let date = query.date.unwrap_or_else(|| match &timezone {
+        Some(timezone) => Utc::now().with_timezone(timezone).date_naive(),
+        None => Local::now().date_naive(),
+    });
+    let path = exporter
+        .export_in(state.db.as_ref(), date, timezone)
+        .await
+        .map_err(|e| {
+            error!("Failed to export the daily note of {}: {}", date, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to export the daily note: {}", e)})),
+            )
+        })?;
+    let path = path.to_string_lossy().to_string();
+    emit_event("vault.exported", json!({"date": date, "path": path}));
+    Ok(JsonResponse(json!({"date": date, "path": path})))
+}
+
+/// Sets a "remember this" marker with a screenshot of the screen as it is now
+pub(crate) async fn create_marker(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(request): JsonResponse<MarkerRequest>,
+) -> Result<(StatusCode, JsonResponse<Marker>), (StatusCode, JsonResponse<serde_json::Value>)> {
+    if let Err(e) = request.validate() {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({"error": e.to_string()})),
+        ));
+    }
+    let marker = state
+        .markers
+        .create(request, state.capture_pause.is_paused())
+        .await
+        .map_err(|e| {
+            error!("Failed to set marker: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to set marker: {}", e)})),
+            )
+        })?;
+    Ok((StatusCode::CREATED, JsonResponse(marker)))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct MarkersQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_markers(
+    Query(query): Query<MarkersQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<Marker>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list markers: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list markers: {}", e)})),
+        )
+    };
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let markers = state
+        .db
+        .get_markers(
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(MARKERS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_markers(query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
+    let page = Page::from_source(
+        markers,
+        query.pagination.limit(),
+        total,
+        MARKERS_CURSOR,
+        |marker| CursorPosition {
+            timestamp: marker.timestamp,
+            id: marker.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+async fn find_marker(
+    state: &AppState,
+    id: i64,
+) -> Result<Marker, (StatusCode, JsonResponse<serde_json::Value>)> {
+    state
+        .db
+        .get_marker(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get marker {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get marker: {}", e)})),
+            )
+        })?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("marker {} not found", id)})),
+            )
+        })
+}
+
+pub(crate) async fn get_marker(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<Marker>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    Ok(JsonResponse(find_marker(&state, id).await?))
+}
+
+/// The full resolution screenshot, decrypted when encryption is on
+pub(crate) async fn get_marker_image(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> ImageResponse {
+    let marker = find_marker(&state, id).await?;
+    let Some(image_path) = marker.image_path else {
+        return Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("marker {} has no screenshot", id)})),
+        ));
+    };
+    let image = async {
+        let media = open_media(&image_path).await?;
+        Ok::<_, anyhow::Error>(tokio::fs::read(media.path()).await?)
+    }
+    .await
+    .map_err(|e| {
+        error!("Failed to read the screenshot of marker {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to read the screenshot: {}", e)})),
+        )
+    })?;
+    negotiated_image(&headers, image, FrameFormat::Png).await
+}
+
+/// The icon of an app by its `app_name`, looked up on the machine the first time it is asked for
+pub(crate) async fn get_app_icon(
+    State(state): State<Arc<AppState>>,
+    Path(app_name): Path<String>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    match state.app_icons.icon(&app_name).await {
+        Ok(Some(icon)) => Ok((
+            [
+                (header::CONTENT_TYPE, "image/png"),
+                (header::CACHE_CONTROL, "max-age=86400"),
+            ],
+            icon,
+        )
+            .into_response()),
+        Ok(None) => Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("no icon found for {}", app_name)})),
+        )),
+        Err(e) => {
+            error!("Failed to read the icon of {}: {}", app_name, e);
+            Err((
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to read the icon: {}", e)})),
+            ))
+        }
+    }
+}
+
+/// Server-sent events stream of everything emitted with `screenpipe_core::emit_event`,
+/// redacted for the consumers `--redact-for` names
+pub(crate) async fn stream_events(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<SseEvent, Infallible>>> {
+    let receiver = subscribe_events();
+    let redaction = state
+        .consumer_redaction
+        .clone()
+        .filter(|redaction| {
+            redaction.applies_to(Consumer::of_request(&state.credentials, &headers))
+        });
+    let events = stream::unfold((receiver, redaction), |(mut receiver, redaction)| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(event) => {
+                    let data = match &redaction {
+                        Some(redaction) => redaction.redact_owned(event.data).await,
+                        None => event.data,
+                    };
+                    let sse_event = SseEvent::default()
+                        .event(event.name)
+                        .data(data.to_string());
+                    return Some((Ok(sse_event), (receiver, redaction)));
+                }
+                // slow client, skip what it missed
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(missed)) => {
+                    events_stats().record_dropped(missed);
+                    continue;
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+// Helper functions
+fn result_timestamp(result: &SearchResult) -> DateTime<Utc> {
+    match result {
+        SearchResult::OCR(ocr) => ocr.timestamp,
+        SearchResult::Audio(audio) => audio.timestamp,
+        SearchResult::FTS(fts) => fts.frame_timestamp,
+    }
+}
+
+/// What the search results are tagged with, read for the times of a page of them
+struct ResultContext {
+    focus_periods: Vec<FocusPeriod>,
+    /// The timezone asked for, the segments recorded are used without one
+    timezone: Option<Tz>,
+    timezone_segments: Vec<TimezoneSegment>,
+}
+
+impl ResultContext {
+    async fn read(db: &DatabaseManager, results: &[SearchResult], timezone: Option<Tz>) -> Self {
+        let mut context = ResultContext {
+            focus_periods: Vec::new(),
+            timezone,
+            timezone_segments: Vec::new(),
+        };
+        let times = results.iter().map(result_timestamp);
+        let (Some(first), Some(last)) = (times.clone().min(), times.max()) else {
+            return context;
+        };
+        context.focus_periods = db
+            .get_focus_periods(Some(first), Some(last), u32::MAX, 0, None)
+            .await
+            .unwrap_or_else(|e| {
+                warn!("Failed to read the focus periods of the results: {}", e);
+                Vec::new()
+            });
+        if context.timezone.is_none() {
+            context.timezone_segments = db
+                .get_timezone_segments(Some(first), Some(last), u32::MAX, 0, None)
+                .await
+                .unwrap_or_else(|e| {
+                    warn!("Failed to read the timezones of the results: {}", e);
+                    Vec::new()
+                });
+        }
+        context
+    }
+
+    fn local_timestamp(&self, timestamp: DateTime<Utc>) -> Option<DateTime<FixedOffset>> {
+        match &self.timezone {
+            Some(timezone) => Some(timestamp.with_timezone(timezone).fixed_offset()),
+            None => local_timestamp(&self.timezone_segments, timestamp),
+        }
+    }
+}
+
+fn into_content_item(result: SearchResult, context: &ResultContext) -> ContentItem {
+    let timestamp = result_timestamp(&result);
+    let focus_mode = focus_mode_at(&context.focus_periods, timestamp);
+    let local_timestamp = context.local_timestamp(timestamp);
+    match result {
+        SearchResult::OCR(ocr) => ContentItem::OCR(OCRContent {
+            frame_id: ocr.frame_id,
+            text: ocr.ocr_text,
+            timestamp: ocr.timestamp,
+            file_path: ocr.file_path,
+            offset_index: ocr.offset_index,
+            app_name: ocr.app_name,
+            window_name: ocr.window_name, // Ensure this field is included
+            browser_url: ocr.browser_url,
+            scroll_y: ocr.scroll_y,
+            language: ocr.language,
+            focus_mode,
+            local_timestamp,
+        }),
+        SearchResult::Audio(audio) => ContentItem::Audio(AudioContent {
+            chunk_id: audio.audio_chunk_id,
+            transcription: audio.transcription,
+            timestamp: audio.timestamp,
+            file_path: audio.file_path,
+            offset_index: audio.offset_index,
+            language: audio.language,
+            focus_mode,
+            local_timestamp,
+        }),
+        SearchResult::FTS(fts) => ContentItem::FTS(FTSContent {
+            text_id: fts.text_id,
+            matched_text: fts.matched_text,
+            frame_id: fts.frame_id,
+            timestamp: fts.frame_timestamp,
+            app_name: fts.app_name,
+            window_name: fts.window_name, // Ensure this field is included
+            file_path: fts.video_file_path,
+            original_frame_text: fts.original_frame_text,
+            focus_mode,
+            local_timestamp,
+        }),
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct SlideDecksQuery {
+    #[serde(default)]
+    start_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    end_time: Option<DateTime<Utc>>,
+    #[serde(flatten)]
+    pagination: PaginationQuery,
+}
+
+pub(crate) async fn list_slide_decks(
+    Query(query): Query<SlideDecksQuery>,
+    State(state): State<Arc<AppState>>,
+) -> Result<JsonResponse<PaginatedResponse<SlideDeck>>, (StatusCode, JsonResponse<serde_json::Value>)>
+{
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to list slide decks: {}", e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to list slide decks: {}", e)})),
+        )
+    };
+    let started = Instant::now();
+    let cursor = query.pagination.cursor()?;
+    let decks = state
+        .db
+        .get_slide_decks(
+            query.start_time,
+            query.end_time,
+            query.pagination.limit(),
+            query.pagination.offset,
+            cursor.get(SLIDE_DECKS_CURSOR),
+        )
+        .await
+        .map_err(internal_error)?;
+    let total = state
+        .db
+        .count_slide_decks(query.start_time, query.end_time)
+        .await
+        .map_err(internal_error)?;
+    let page = Page::from_source(
+        decks,
+        query.pagination.limit(),
+        total,
+        SLIDE_DECKS_CURSOR,
+        |deck| CursorPosition {
+            timestamp: deck.started_at,
+            id: deck.id,
+        },
+    );
+    Ok(JsonResponse(PaginatedResponse::new(
+        page,
+        &query.pagination,
+        started,
+    )))
+}
+
+#[derive(Serialize)]
+pub(crate) struct SlideDeckResponse {
+    #[serde(flatten)]
+    deck: SlideDeck,
+    slides: Vec<Slide>,
+}
+
+async fn find_slide_deck(
+    state: &AppState,
+    id: i64,
+) -> Result<SlideDeckResponse, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let internal_error = |e: sqlx::Error| {
+        error!("Failed to get slide deck {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to get slide deck: {}", e)})),
+        )
+    };
+    let deck = state
+        .db
+        .get_slide_deck(id)
+        .await
+        .map_err(internal_error)?
+        .ok_or_else(|| {
+            (
+                StatusCode::NOT_FOUND,
+                JsonResponse(json!({"error": format!("slide deck {} not found", id)})),
+            )
+        })?;
+    let slides = state.db.get_slides(id).await.map_err(internal_error)?;
+    Ok(SlideDeckResponse { deck, slides })
+}
+
+/// A deck with the text of each of its slides
+pub(crate) async fn get_slide_deck(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<JsonResponse<SlideDeckResponse>, (StatusCode, JsonResponse<serde_json::Value>)> {
+    Ok(JsonResponse(find_slide_deck(&state, id).await?))
+}
+
+async fn read_slide_image(slide: &Slide) -> anyhow::Result<Option<Vec<u8>>> {
+    let Some(image_path) = &slide.image_path else {
+        return Ok(None);
+    };
+    let media = open_media(image_path).await?;
+    Ok(Some(tokio::fs::read(media.path()).await?))
+}
+
+pub(crate) async fn get_slide_image(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+    headers: HeaderMap,
+) -> ImageResponse {
+    let not_found = || {
+        (
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("slide {} has no image", id)})),
+        )
+    };
+    let slide = state
+        .db
+        .get_slide(id)
+        .await
+        .map_err(|e| {
+            error!("Failed to get slide {}: {}", id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to get slide: {}", e)})),
+            )
+        })?
+        .ok_or_else(not_found)?;
+    let image = read_slide_image(&slide).await.map_err(|e| {
+        error!("Failed to read the image of slide {}: {}", id, e);
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            JsonResponse(json!({"error": format!("Failed to read the slide: {}", e)})),
+        )
+    })?;
+    let image = image.ok_or_else(not_found)?;
+    negotiated_image(&headers, image, FrameFormat::Jpeg).await
+}
+
+fn pdf_response(filename: &str, pdf: Vec<u8>) -> Response {
+    (
+        [
+            (header::CONTENT_TYPE, "application/pdf".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}\"", filename),
+            ),
+        ],
+        pdf,
+    )
+        .into_response()
+}
+
+/// The slides of a deck as a PDF, a page each, with their words searchable where they are.
+/// Slides stored without an image are left out
+pub(crate) async fn export_slide_deck_pdf(
+    State(state): State<Arc<AppState>>,
+    Path(id): Path<i64>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    let SlideDeckResponse { deck, slides } = find_slide_deck(&state, id).await?;
+    let mut pages = Vec::new();
+    for slide in slides {
+        let page = async {
+            let Some(jpeg) = read_slide_image(&slide).await? else {
+                return Ok(None);
+            };
+            let image = image::load_from_memory(&jpeg)?;
+            anyhow::Ok(Some(ocr_page(image, Some(jpeg), slide.text.clone()).await?))
+        };
+        let page = page.await.map_err(|e| {
+            error!("Failed to read the image of slide {}: {}", slide.id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to read the slides: {}", e)})),
+            )
+        })?;
+        pages.extend(page);
+    }
+    if pages.is_empty() {
+        return Err((
+            StatusCode::NOT_FOUND,
+            JsonResponse(json!({"error": format!("slide deck {} has no slide images", id)})),
+        ));
+    }
+    let title = format!(
+        "{} {}",
+        deck.app_name,
+        deck.started_at.format("%Y-%m-%d %H:%M")
+    );
+    Ok(pdf_response(
+        &format!("slide_deck_{}.pdf", id),
+        pdf_document(&title, &pages),
+    ))
+}
+
+/// Frames exported to a PDF at once, each is read again for the positions of its words
+const MAX_PDF_FRAMES: usize = 200;
+
+#[derive(Deserialize)]
+pub(crate) struct FramesPdfRequest {
+    /// In the order of the pages
+    frame_ids: Vec<i64>,
+    #[serde(default)]
+    title: Option<String>,
+}
+
+/// The frames as a PDF, a page each, with their words laid invisibly where they are so it can
+/// be searched and copied from
+pub(crate) async fn export_frames_pdf(
+    State(state): State<Arc<AppState>>,
+    JsonResponse(payload): JsonResponse<FramesPdfRequest>,
+) -> Result<Response, (StatusCode, JsonResponse<serde_json::Value>)> {
+    if payload.frame_ids.is_empty() || payload.frame_ids.len() > MAX_PDF_FRAMES {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            JsonResponse(json!({
+                "error": format!("frame_ids must have between 1 and {} frames", MAX_PDF_FRAMES)
+            })),
+        ));
+    }
+    let mut pages: Vec<PdfPage> = Vec::with_capacity(payload.frame_ids.len());
+    for frame_id in &payload.frame_ids {
+        let internal_error = |e: anyhow::Error| {
+            error!("Failed to export frame {} to PDF: {}", frame_id, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                JsonResponse(json!({"error": format!("Failed to export frame {}: {}", frame_id, e)})),
+            )
+        };
+        let (file_path, offset_index) = recorded_frame(&state.db, *frame_id)
+            .await
+            .map_err(storage_error)?;
+        let text = state
+            .db
+            .get_frame_ocr_text(*frame_id)
+            .await
+            .map_err(|e| internal_error(e.into()))?;
+        let image = extract_frame(&file_path, offset_index)
+            .await
+            .map_err(storage_error)?;
+        pages.push(ocr_page(image, None, text).await.map_err(internal_error)?);
+    }
+    let title = payload
+        .title
+        .unwrap_or_else(|| format!("screenpipe frames {}", payload.frame_ids[0]));
+    Ok(pdf_response("frames.pdf", pdf_document(&title, &pages)))
+}
+
+pub struct Server {
+    db: Arc<DatabaseManager>,
+    addr: SocketAddr,
+    /// Bound beforehand for a --multi-user server, `addr` is bound otherwise
+    listener: Option<std::net::TcpListener>,
+    vision_control: Arc<AtomicBool>,
+    audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+    llm: Arc<LlmService>,
+    prompts: Arc<PromptLibrary>,
+    live_view: Option<Arc<LiveView>>,
+    timelapse: Arc<TimelapseRenderer>,
+    clips: Arc<ClipRenderer>,
+    capture_pause: Arc<CapturePause>,
+    encryption: Option<Arc<KeyRing>>,
+    compliance: Option<Arc<ComplianceMode>>,
+    consumer_redaction: Option<Arc<ConsumerRedaction>>,
+    vault_export: Option<Arc<VaultExporter>>,
+    markers: Arc<MarkerRecorder>,
+    profile: Option<Arc<ActiveProfile>>,
+    watchlist: Arc<Watchlist>,
+    app_icons: Arc<AppIcons>,
+    live_ocr: Arc<LiveOcr>,
+    automation: Option<Arc<Automation>>,
+    llm_tools: Arc<ToolRegistry>,
+    credentials: Arc<ApiCredentials>,
+    /// Set with --multi-user, requests need the user's token
+    user_isolation: Option<Arc<UserIsolation>>,
+    /// Unix socket or named pipe the API is also served on
+    api_socket: Option<PathBuf>,
+    /// False with --disable-tcp-api
+    serve_tcp: bool,
+    /// Set with --tls, the port serves https only
+    tls: Option<TlsAcceptor>,
+}
+
+impl Server {
+    pub fn new(
+        db: Arc<DatabaseManager>,
+        addr: SocketAddr,
+        listener: Option<std::net::TcpListener>,
+        vision_control: Arc<AtomicBool>,
+        audio_devices_control: Arc<SegQueue<(AudioDevice, DeviceControl)>>,
+        llm: Arc<LlmService>,
+        prompts: Arc<PromptLibrary>,
+        live_view: Option<Arc<LiveView>>,
+        timelapse: Arc<TimelapseRenderer>,
+        clips: Arc<ClipRenderer>,
+        capture_pause: Arc<CapturePause>,
+        encryption: Option<Arc<KeyRing>>,
+        compliance: Option<Arc<ComplianceMode>>,
+        consumer_redaction: Option<Arc<ConsumerRedaction>>,
+        vault_export: Option<Arc<VaultExporter>>,
+        markers: Arc<MarkerRecorder>,
+        profile: Option<Arc<ActiveProfile>>,
+        watchlist: Arc<Watchlist>,
+        app_icons: Arc<AppIcons>,
+        live_ocr: Arc<LiveOcr>,
+        automation: Option<Arc<Automation>>,
+        llm_tools: Arc<ToolRegistry>,
+        credentials: Arc<ApiCredentials>,
+        user_isolation: Option<Arc<UserIsolation>>,
+        api_socket: Option<PathBuf>,
+        serve_tcp: bool,
+        tls: Option<TlsAcceptor>,
+    ) -> Self {
+        Server {
+            db,
+            addr,
+            listener,
+            vision_control,
+            audio_devices_control,
+            llm,
+            prompts,
+            live_view,
+            timelapse,
+            clips,
+            capture_pause,
+            encryption,
+            compliance,
+            consumer_redaction,
+            vault_export,
+            markers,
+            profile,
+            watchlist,
+            app_icons,
+            live_ocr,
+            automation,
+            llm_tools,
+            credentials,
+            user_isolation,
+            api_socket,
+            serve_tcp,
+            tls,
+        }
+    }
+
+    pub async fn start<F>(
+        self,
+        device_status: HashMap<AudioDevice, DeviceControl>,
+        api_plugin: F,
+    ) -> Result<(), std::io::Error>
+    where
+        F: Fn(&axum::http::Request<axum::body::Body>) + Clone + Send + Sync + 'static,
+    {
+        // TODO could init w audio devices
+        let app_state = Arc::new(AppState {
+            db: self.db,
+            vision_control: self.vision_control,
+            audio_devices_control: self.audio_devices_control,
+            devices_status: device_status,
+            app_start_time: Utc::now(),
+            llm: self.llm,
+            prompts: self.prompts,
+            live_view: self.live_view,
+            timelapse: self.timelapse,
+            clips: self.clips,
+            capture_pause: self.capture_pause,
+            encryption: self.encryption,
+            compliance: self.compliance,
+            consumer_redaction: self.consumer_redaction,
+            vault_export: self.vault_export,
+            markers: self.markers,
+            profile: self.profile,
+            watchlist: self.watchlist,
+            app_icons: self.app_icons,
+            live_ocr: self.live_ocr,
+            automation: self.automation,
+            llm_tools: self.llm_tools,
+            credentials: self.credentials,
+        });
+
+        let credentials = app_state.credentials.clone();
+        let guarded =
+            || axum::middleware::from_fn_with_state(credentials.clone(), refuse_cross_origin);
+
+        // https://github.com/tokio-rs/console
+        let app = Router::new()
+            .route("/search", get(search))
+            .route("/search/region", get(search_region))
+            .route("/audio/list", get(api_list_audio_devices))
+            .route("/vision/list", post(api_list_monitors))
+            .route("/health", get(health_check))
+            .route("/meetings", get(list_meetings))
+            .route("/meetings/:id", get(get_meeting))
+            .route("/activity/sessions", get(list_activity_sessions))
+            .route("/activity/categories", get(activity_categories))
+            .route("/files/events", get(list_file_events))
+            .route("/windows/history", get(window_history))
+            .route("/windows/layout", get(get_window_layout_at))
+            .route("/windows/layouts/:id", get(get_window_layout))
+            .route("/windows/layouts/:id/restore", post(restore_window_layout))
+            .route(
+                "/shell/commands",
+                get(list_shell_commands).post(report_shell_command),
+            )
+            .route(
+                "/editor/contexts",
+                get(list_editor_contexts).post(report_editor_context),
+            )
+            .route("/git/events", get(list_git_events))
+            .route("/notifications", get(list_notifications))
+            .route("/focus/periods", get(list_focus_periods))
+            .route("/timezones", get(list_timezones))
+            .route("/calendar.ics", get(get_calendar_feed))
+            .route(
+                "/analytics/query",
+                post(run_analytics_query.layer(guarded())),
+            )
+            .route("/analytics/trends", get(get_weekly_trends))
+            .route("/analytics/:file", get(export_analytics))
+            .route("/apps/:app_name/icon", get(get_app_icon))
+            .route("/markers", get(list_markers).post(create_marker))
+            .route("/markers/:id", get(get_marker))
+            .route("/markers/:id/image", get(get_marker_image))
+            .route("/slides/decks", get(list_slide_decks))
+            .route("/slides/decks/:id", get(get_slide_deck))
+            .route("/slides/decks/:id/pdf", get(export_slide_deck_pdf))
+            .route("/slides/:id/image", get(get_slide_image))
+            .route(
+                "/extraction/rules",
+                get(list_extraction_rules).post(create_extraction_rule),
+            )
+            .route(
+                "/extraction/rules/:id",
+                delete(delete_extraction_rule.layer(guarded())),
+            )
+            .route("/extraction/records", get(list_extracted_records))
+            .route("/watches", get(list_watches).post(create_watch))
+            .route("/watches/:id", delete(delete_watch))
+            .route("/watches/alerts", get(list_watch_alerts))
+            .route("/events", get(stream_events))
+            .route("/capabilities", get(capabilities))
+            .route("/channels", get(list_channels))
+            .route("/profile", get(get_profile))
+            .route("/frames/:id", get(get_frame_image))
+            .route("/frames/:id/describe", post(describe_frame))
+            .route("/ask", post(ask))
+            .route("/chat", post(chat))
+            .route(
+                "/conversations",
+                get(list_conversations).post(create_conversation),
+            )
+            .route(
+                "/conversations/:id",
+                get(get_conversation).delete(delete_conversation),
+            )
+            .route("/conversations/:id/messages", post(add_conversation_message))
+            .route("/conversations/:id/export", get(export_conversation))
+            .route("/frames/similar", post(similar_frames))
+            .route("/frames/pdf", post(export_frames_pdf))
+            .route("/ocr/live", get(get_live_ocr))
+            .route("/ocr/live/stream", get(stream_live_ocr))
+            .route("/ocr/copy", post(copy_screen_text))
+            .route("/live", get(live_page))
+            .route("/live/index.m3u8", get(live_playlist))
+            .route("/live/player.js", get(live_player))
+            .route("/live/:segment", get(live_segment))
+            .route("/timelapse", post(create_timelapse))
+            .route("/timelapse/:id", get(get_timelapse))
+            .route("/timelapse/:id/video", get(get_timelapse_video))
+            .route("/clips", post(create_clip))
+            .route("/clips/:id", get(get_clip))
+            .route("/clips/:id/video", get(get_clip_video))
+            .route("/llm/usage", get(llm_usage))
+            .route(
+                "/capture/pause",
+                get(capture_pause_status).post(pause_capture.layer(guarded())),
+            )
+            .route("/capture/resume", post(resume_capture.layer(guarded())))
+            .route("/audit", get(list_audit_log))
+            .route(
+                "/automation/actions",
+                get(list_automation_actions).post(run_automation),
+            )
+            .route("/redactions", get(list_redactions))
+            .route("/forget", post(forget_data.layer(guarded())))
+            .route("/encryption", get(get_encryption_status))
+            .route(
+                "/encryption/rotate",
+                post(rotate_encryption_keys.layer(guarded())),
+            )
+            .route("/compliance", get(get_compliance_status))
+            .route("/compliance/consent", post(record_compliance_consent))
+            .route("/compliance/consents", get(list_consent_records))
+            .route("/vault/export", post(export_vault_note))
+            .route(
+                "/privacy/private-windows",
+                post(report_private_browser_windows),
+            )
+            .route("/browser/pages", post(push_browser_page))
+            .route(
+                "/llm/cache",
+                get(llm_cache_stats).delete(invalidate_llm_cache),
+            )
+            .route("/models", get(list_models))
+            .route("/models/prefetch", post(prefetch_model))
+            .route("/models/remove", post(remove_model.layer(guarded())))
+            .layer(axum::middleware::from_fn_with_state(
+                app_state.clone(),
+                redact_responses,
+            ))
+            .layer(axum::middleware::from_fn_with_state(
+                app_state.clone(),
+                audit_requests,
+            ))
+            .layer(ApiPluginLayer::new(api_plugin));
+
+        // the socket checks who connects, its clients don't need the user's token and are the
+        // app's
+        let local_api = self.api_socket.map(|path| {
+            let app = app
+                .clone()
+                .layer(axum::middleware::from_fn_with_state(
+                    app_state.credentials.clone(),
+                    local_peer_is_app,
+                ))
+                .layer(TraceLayer::new_for_http())
+                .with_state(app_state.clone());
+            tokio::spawn(serve_local_api(path, app))
+        });
+        if !self.serve_tcp {
+            let Some(local_api) = local_api else {
+                return Err(std::io::Error::other("the API is served neither on tcp nor on a socket"));
+            };
+            return match local_api.await {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(e)) => {
+                    error!("Local API error: {}", e);
+                    Err(std::io::Error::other(e))
+                }
+                Err(e) => Err(std::io::Error::other(e)),
+            };
+        }
+
+        // the other users of the machine are refused before anything runs
+        let app = match self.user_isolation {
+            Some(isolation) => app.layer(axum::middleware::from_fn_with_state(
+                isolation,
+                require_user_token,
+            )),
+            None => app,
+        };
+        let app = app
+            .layer(CorsLayer::permissive())
+            .layer(
+                // https://github.com/tokio-rs/axum/blob/main/examples/tracing-aka-logging/src/main.rs
+                TraceLayer::new_for_http()
+                    .make_span_with(DefaultMakeSpan::new().include_headers(true)),
+            )
+            .with_state(app_state);
+
+        info!("Starting server on {}", self.addr);
+
+        let listener = match self.listener {
+            Some(listener) => {
+                listener.set_nonblocking(true)?;
+                TcpListener::from_std(listener)?
+            }
+            None => TcpListener::bind(self.addr).await?,
+        };
+        let served = match self.tls {
+            Some(acceptor) => {
+                info!("Serving https on {}", self.addr);
+                serve_tls(listener, app, acceptor)
+                    .await
+                    .map_err(std::io::Error::other)
+            }
+            None => serve(listener, app.into_make_service()).await,
+        };
+        match served {
+            Ok(_) => {
+                info!("Server stopped gracefully");
+                Ok(())
+            }
+            Err(e) => {
+                error!("Server error: {}", e);
+                Err(e)
+            }
+        }
+    }
+}
+
+// Curl commands for reference:
+// # 1. Basic search query
+// # curl "http://localhost:3030/search?q=test&limit=5&offset=0"
+
+// # 2. Search with content type filter (OCR)
+// # curl "http://localhost:3030/search?q=test&limit=5&offset=0&content_type=ocr"
+
+// # 3. Search with content type filter (Audio)
+// # curl "http://localhost:3030/search?q=test&limit=5&offset=0&content_type=audio"
+
+// # 4. Search with pagination
+// # curl "http://localhost:3030/search?q=test&limit=10&offset=20"
+
+// # 6. Search with no query (should return all results)
+// # curl "http://localhost:3030/search?limit=5&offset=0"
+
+// # text that showed in the top 40 pixels of monitor 1 over a day, e.g. a status bar
+// # curl "http://localhost:3030/search/region?x=0&y=0&width=1920&height=40&monitor_id=1&start_time=2024-09-21T00:00:00Z&end_time=2024-09-22T00:00:00Z" | jq '.data[] | {timestamp, text}'
+
+// # 7. Start a device
+// # curl -X POST "http://localhost:3030/audio/start" -H "Content-Type: application/json" -d '{"device_id": "device1"}'
+
+// # 8. Stop a device
+// # curl -X POST "http://localhost:3030/audio/stop" -H "Content-Type: application/json" -d '{"device_id": "device1"}'
+
+// # 9. Get device status
+// # curl "http://localhost:3030/audio/status" -H "Content-Type: application/json" -d '{"device_id": "device1"}'
+
+// # list meeting summaries (requires --enable-meeting-summaries)
+// # curl "http://localhost:3030/meetings?limit=5" | jq
+// # curl "http://localhost:3030/meetings?q=sync%20with%20bob" | jq
+// # curl "http://localhost:3030/meetings/1" | jq
+// # sessions tagged by the local activity classifier
+// # curl "http://localhost:3030/activity/sessions?category=coding&limit=10" | jq
+// # time spent per category
+// # curl "http://localhost:3030/activity/categories?start_time=2024-08-25T00:00:00Z" | jq
+
+// # set a "remember this" marker with a full resolution screenshot and a transcription of the next
+// # 30 seconds of speech, its frames are never compacted. The desktop app's hotkey calls this
+// # curl -X POST "http://localhost:3030/markers" -H "Content-Type: application/json" -d '{"note": "pricing idea", "memo_secs": 30}' | jq
+// # curl "http://localhost:3030/markers?start_time=2024-09-05T00:00:00Z" | jq
+// # curl "http://localhost:3030/markers/1/image" -o marker.png
+
+// # files saved in the watched directories (requires --watch-dir) with the app focused then,
+// # the last one before a timestamp found by /search is the document being edited
+// # curl "http://localhost:3030/files/events?end_time=2024-08-25T14:03:00Z&limit=1" | jq
+// # curl "http://localhost:3030/files/events?path=report.docx&app_name=Word" | jq
+
+// # which window was focused when and for how long, to the second, recorded between frames
+// # curl "http://localhost:3030/windows/history?start_time=2024-09-09T09:00:00Z&limit=100" | jq
+// # curl "http://localhost:3030/windows/history?app_name=Chrome&window_name=github" | jq
+// # where the windows were before the laptop was undocked, and putting them back as a pipe
+// # curl "http://localhost:3030/windows/layout?at=2024-09-20T08:55:00Z" | jq '.layouts[] | {id, monitor_id, windows}'
+// # curl -X POST "http://localhost:3030/windows/layouts/12/restore" -H "x-screenpipe-pipe: window-keeper" -H "x-screenpipe-pipe-token: $(jq -r '.["window-keeper"]' ~/.screenpipe/pipe_tokens.json)" | jq
+// # send the commands of zsh (bash, fish) as they finish, with their directory and exit code
+// # with the same --data-dir and --profile as the server, they post to its API socket
+// # echo 'eval "$(screenpipe --print-shell-hook zsh)"' >> ~/.zshrc
+// # curl "http://localhost:3030/shell/commands?q=cargo&cwd=/home/me/project" | jq
+// # the commands that ran before a frame showing an error, with the terminal frames of each one
+// # curl "http://localhost:3030/shell/commands?frame_id=4242&limit=3" | jq
+// # what editor plugins send every few seconds of the file open, and the coding sessions by repository
+// # curl -X POST "http://localhost:3030/editor/contexts" -H "Content-Type: application/json" -d '{"editor": "vscode", "project": "screenpipe", "repository": "github.com/mediar-ai/screenpipe", "git_branch": "main", "file": "screenpipe-server/src/db.rs", "language": "rust"}'
+// # curl "http://localhost:3030/editor/contexts?repository=screenpipe&file=db.rs" | jq
+// # the commits made during a meeting in the repositories of --git-repo
+// # curl "http://localhost:3030/git/events?meeting_id=12&kind=commit" | jq
+// # the notifications of an app recorded with --capture-notifications, gone from the screen long before
+// # curl "http://localhost:3030/notifications?app_name=slack&q=deploy" | jq
+// # when do not disturb or a focus mode was on this week, search results and activity sessions carry focus_mode
+// # curl "http://localhost:3030/focus/periods?start_time=2024-09-16T00:00:00Z" | jq
+// # the timezones the machine was in, search results carry local_timestamp in the one of their time or in ?tz=
+// # curl "http://localhost:3030/timezones" | jq
+// # curl "http://localhost:3030/search?q=boarding&tz=Asia/Tokyo" | jq
+// # the detected meetings and coding focus blocks of the last 30 days, subscribe to
+// # webcal://localhost:3030/calendar.ics from a calendar app, adding ?user_token= with --multi-user
+// # curl "http://localhost:3030/calendar.ics?days=30" -o screenpipe.ics
+// # activity sessions, words read per frame and words spoken per transcription for pandas or DuckDB,
+// # e.g. pd.read_csv("http://localhost:3030/analytics/sessions.csv") or
+// # SELECT * FROM 'http://localhost:3030/analytics/ocr.parquet'
+// # curl "http://localhost:3030/analytics/transcripts.parquet?start_time=2024-09-01T00:00:00Z" -o transcripts.parquet
+// # hourly words read per app, all that's kept of the text with --aggregate-only-after
+// # curl "http://localhost:3030/analytics/usage.csv?start_time=2024-09-01T00:00:00Z"
+// # SQL over the same tables without exporting, of the last 7 days unless start_time is given
+// # curl -X POST "http://localhost:3030/analytics/query" -H "Content-Type: application/json" -d '{"sql": "SELECT app_name, strftime(\'%H\', start_time) AS hour, SUM(duration_secs) / 60 AS minutes FROM sessions GROUP BY 1, 2 ORDER BY 3 DESC"}' | jq
+// # week over week time per category, meetings, context switches and after hours work, with
+// # what's far from the usual weeks, also emitted on /events as "analytics.anomaly"
+// # curl "http://localhost:3030/analytics/trends?weeks=12" | jq
+// # the icon of an app as png, by the app_name of the results
+// # curl "http://localhost:3030/apps/Google%20Chrome/icon" -o chrome.png
+
+// # add an extraction rule, records are stored and emitted on /events as "extraction.record"
+// # curl -X POST "http://localhost:3030/extraction/rules" -H "Content-Type: application/json" -d '{"name": "orders", "app_name": "Salesforce", "kind": "regex", "pattern": "Order #(?P<order_number>\\d+)"}'
+// # curl "http://localhost:3030/extraction/records?rule_id=1" | jq
+// # curl -X POST "http://localhost:3030/watches" -H "Content-Type: application/json" -d '{"name": "mentions", "app_name": "Slack", "kind": "keywords", "pattern": "karol, @karol"}'
+// # curl "http://localhost:3030/watches/alerts?watch_id=1" | jq
+// # curl -N "http://localhost:3030/events"
+// # what a pipe gets, PII and secrets removed unless --redact-for says otherwise
+// # curl -N "http://localhost:3030/events" -H "x-screenpipe-pipe: my-pipe"
+
+// # llm token usage and estimated cost per day, task and model
+// # curl "http://localhost:3030/llm/usage" | jq
+// # what the recording backend supports (ffmpeg or the native fallback)
+// # the profile being recorded to, with its retention and blocklists
+// # curl "http://localhost:3030/profile" | jq
+// # which engines, capture backends, encoders and features this build and machine support
+// # curl "http://localhost:3030/capabilities" | jq
+// # ask the vision model about a frame, optionally cropped to a region
+// # curl "http://localhost:3030/frames/42" -H "Accept: image/avif,image/webp" -o frame.avif
+// # curl "http://localhost:3030/frames/42?width=160" -o thumbnail.jpg
+// # curl -X POST "http://localhost:3030/frames/42/describe" -H "Content-Type: application/json" -d '{"instruction": "What does the chart show?", "region": {"x": 0, "y": 0, "width": 800, "height": 600}}' | jq
+// # ask about the history as the app, the model searches it, reads frames, tags them and notifies with its tools
+// # curl -X POST "http://localhost:3030/ask" -H "Authorization: Bearer $(cat ~/.screenpipe/app_token)" -H "Content-Type: application/json" -d '{"question": "which flight did I book last week?"}' | jq
+// # a pipe's chat, with the tools its grant of --llm-tool-grants allows
+// # curl -X POST "http://localhost:3030/chat" -H "x-screenpipe-pipe: invoice-filer" -H "x-screenpipe-pipe-token: $(jq -r '.["invoice-filer"]' ~/.screenpipe/pipe_tokens.json)" -H "Content-Type: application/json" -d '{"messages": [{"role": "user", "content": "tag the frames where I reviewed an invoice today"}]}' | jq
+// # a conversation kept in the database, continued after a restart and exported with its sources
+// # curl -X POST "http://localhost:3030/conversations" -H "Content-Type: application/json" -d '{"question": "which flight did I book last week?"}' | jq
+// # curl -X POST "http://localhost:3030/conversations/1/messages" -H "Content-Type: application/json" -d '{"question": "and the hotel?"}' | jq
+// # curl "http://localhost:3030/conversations?q=flight" | jq
+// # curl "http://localhost:3030/conversations/1/export" -o conversation.md
+// # when did I see this screen? from a screenshot or a recorded frame
+// # curl -X POST "http://localhost:3030/frames/similar" -H "Content-Type: application/json" -d "{\"image\": \"$(base64 -w0 screenshot.png)\"}" | jq
+// # curl -X POST "http://localhost:3030/frames/similar" -H "Content-Type: application/json" -d '{"frame_id": 42, "max_distance": 6}' | jq
+// # curl -X POST "http://localhost:3030/frames/pdf" -H "Content-Type: application/json" -d '{"frame_ids": [42, 43, 44], "title": "design review"}' -o frames.pdf
+// # invalidate cached llm answers (all, or of one model)
+// # curl -X DELETE "http://localhost:3030/llm/cache?model=gpt-4o" | jq
+// # the local models downloaded, their checksums and what the quota leaves
+// # curl "http://localhost:3030/models" | jq '{used_bytes, quota_bytes, models: [.models[] | {id, size_bytes}]}'
+// # curl -X POST "http://localhost:3030/models/prefetch" -H "Content-Type: application/json" -d '{"name": "whisper-distil-large-v3"}' | jq
+// # curl -X POST "http://localhost:3030/models/remove" -H "Content-Type: application/json" -d '{"id": "openai/whisper-tiny@main"}' | jq
+// # the words on the screen now with their boxes in desktop coordinates, for overlays
+// # curl "http://localhost:3030/ocr/live?focused=true" | jq '.blocks[] | {text, screen_rect}'
+// # and as each frame is captured
+// # curl -N "http://localhost:3030/ocr/live/stream"
+// # the text of the focused window now, cleaned up for pasting
+// # curl -X POST "http://localhost:3030/ocr/copy" -H "Content-Type: application/json" -d '{"clean": true}' | jq -r .text | pbcopy
+// # click and press keys as a pipe (needs --automation-grants) with its token of pipe_tokens.json,
+// # then look at what pipes did
+// # curl -X POST "http://localhost:3030/automation/actions" -H "x-screenpipe-pipe: dismiss-dialog" -H "x-screenpipe-pipe-token: $(jq -r '.["dismiss-dialog"]' ~/.screenpipe/pipe_tokens.json)" -H "Content-Type: application/json" -d '{"actions": [{"type": "click", "x": 640, "y": 412}, {"type": "key", "key": "escape"}]}' | jq
+// # curl "http://localhost:3030/automation/actions?pipe=dismiss-dialog&status=refused" | jq
+// # what the redaction policy blurred, redacted or dropped this morning, without the content
+// # curl "http://localhost:3030/redactions?source=screen&start_time=2024-08-26T08:00:00Z&end_time=2024-08-26T12:00:00Z" | jq
+// # watch the screen live (needs --enable-live-view), open in a browser or play the playlist
+// # open "http://localhost:3030/live?token=<live view token>"
+// # curl -H "Authorization: Bearer <live view token>" "http://localhost:3030/live/index.m3u8"
+// # render the workday at 60x with app and clock captions, then poll the job and download it
+// # curl -X POST "http://localhost:3030/timelapse" -H "Content-Type: application/json" -d '{"start_time": "2024-08-26T08:00:00Z", "end_time": "2024-08-26T17:00:00Z", "speed": 60, "app_names": true, "clock": true}' | jq
+// # curl "http://localhost:3030/timelapse/1" | jq
+// # curl "http://localhost:3030/timelapse/1/video" -o timelapse.mp4
+// # curl -X POST "http://localhost:3030/clips" -H "Content-Type: application/json" -d '{"start_time": "2024-08-30T10:00:00Z", "end_time": "2024-08-30T10:05:00Z", "redact_pii": true, "redact_windows": [{"app_name": "1Password"}], "redaction": "blur"}' | jq
+// # curl "http://localhost:3030/clips/1" | jq
+// # curl "http://localhost:3030/clips/1/video" -o clip.mp4
+// # the slide decks presented on screen, each unique slide once with its text
+// # curl "http://localhost:3030/slides/decks" | jq
+// # curl "http://localhost:3030/slides/decks/1" | jq
+// # curl "http://localhost:3030/slides/decks/1/pdf" -o deck.pdf
+// # what a browser extension sends so its private windows aren't captured, "capture.private_window_excluded" is emitted on /events
+// # curl -X POST "http://localhost:3030/privacy/private-windows" -H "Content-Type: application/json" -d '{"titles": ["Bank of Example - Account"]}'
+// # what a browser extension sends of the tab a window shows, the frames of the window store this text instead of their OCR
+// # curl -X POST "http://localhost:3030/browser/pages" -H "Content-Type: application/json" -d '{"url": "https://example.com/docs", "title": "Docs - Example", "text": "Getting started ...", "browser": "chrome", "scroll_y": 1200}'
+// # pause everything for 10 minutes, without a duration it lasts until resumed
+// # curl -X POST "http://localhost:3030/capture/pause" -H "Content-Type: application/json" -d '{"duration_secs": 600}' | jq
+// # curl "http://localhost:3030/capture/pause" | jq
+// # curl -X POST "http://localhost:3030/capture/resume" | jq
+// # who read, exported or deleted what, filter by actor ("local", "pipe:<file>", "token:<fingerprint>") or action
+// # curl "http://localhost:3030/audit?action=delete&limit=20" | jq
+// # curl "http://localhost:3030/audit?actor=pipe:pipeline.js&start_time=2024-08-31T00:00:00Z" | jq
+// # see what would be removed, then remove everything a keyword shows up in, "data.forgotten" is emitted on /events
+// # curl -X POST "http://localhost:3030/forget" -H "Content-Type: application/json" -d '{"keyword": "project x", "dry_run": true}' | jq
+// # curl -X POST "http://localhost:3030/forget" -H "Content-Type: application/json" -d '{"keyword": "project x"}' | jq
+// # curl -X POST "http://localhost:3030/forget" -H "Content-Type: application/json" -d '{"app_name": "Signal", "start_time": "2024-09-01T08:00:00Z", "end_time": "2024-09-01T12:00:00Z"}' | jq
+// # with --encrypt-data, see how much is still plaintext or on old keys, then switch to new keys
+// # curl "http://localhost:3030/encryption" | jq
+// # curl -X POST "http://localhost:3030/encryption/rotate" | jq
+// # on a machine with a signed compliance policy, the policy and whether the user consented, then the answer to the prompt
+// # curl "http://localhost:3030/compliance" | jq
+// # curl -X POST "http://localhost:3030/compliance/consent" -H "Authorization: Bearer $(cat ~/.screenpipe/app_token)" -H "Content-Type: application/json" -d '{"accepted": true}' | jq
+// # curl "http://localhost:3030/compliance/consents?limit=10" | jq
+// # with --vault-dir, write the daily note of today or another day into the Obsidian or Logseq vault now
+// # curl -X POST "http://localhost:3030/vault/export" | jq
+// # curl -X POST "http://localhost:3030/vault/export?date=2024-09-01" | jq
 
 // list devices
 // # curl "http://localhost:3030/audio/list" | jq
@@ -0,0 +1,224 @@
+use crate::pause::PauseLog;
+use crate::{CapturePause, DatabaseManager};
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, info, warn};
+use screenpipe_integrations::shell_history::{parse_history, HistoryEntry, Shell};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+/// How often the history files are read again
+pub const SHELL_HISTORY_INTERVAL: Duration = Duration::from_secs(30);
+/// Terminal frames this long after a command ended still show its output
+pub const OUTPUT_GRACE: ChronoDuration = ChronoDuration::seconds(5);
+/// Frames linked to a command at most, a long one like a dev server gets many
+pub const MAX_LINKED_FRAMES: usize = 10;
+
+/// Words of the app names of the terminal windows, lowercase
+const TERMINALS: [&str; 16] = [
+    "terminal",
+    "iterm",
+    "iterm2",
+    "alacritty",
+    "kitty",
+    "wezterm",
+    "warp",
+    "konsole",
+    "xterm",
+    "ghostty",
+    "hyper",
+    "tilix",
+    "foot",
+    "terminator",
+    "tabby",
+    "rio",
+];
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ShellCommandSource {
+    /// Sent by the shell hooks as it finished, with its directory and exit code
+    Hook,
+    /// Read from a history file
+    History,
+}
+
+impl ShellCommandSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShellCommandSource::Hook => "hook",
+            ShellCommandSource::History => "history",
+        }
+    }
+}
+
+impl FromStr for ShellCommandSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "hook" => Ok(ShellCommandSource::Hook),
+            "history" => Ok(ShellCommandSource::History),
+            _ => anyhow::bail!("unknown shell command source: {}", s),
+        }
+    }
+}
+
+/// A command to store, the same one read from the history and sent by the hooks is kept once
+#[derive(Debug, Clone, PartialEq)]
+pub struct NewShellCommand {
+    pub timestamp: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub command: String,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    pub shell: Shell,
+    pub source: ShellCommandSource,
+}
+
+impl NewShellCommand {
+    pub fn from_history(shell: Shell, entry: HistoryEntry) -> Self {
+        NewShellCommand {
+            timestamp: entry.timestamp,
+            ended_at: entry
+                .duration_secs
+                .map(|duration| entry.timestamp + ChronoDuration::seconds(duration)),
+            command: entry.command,
+            cwd: None,
+            exit_code: None,
+            shell,
+            source: ShellCommandSource::History,
+        }
+    }
+
+    /// Shells leave the commands typed after a space out of their history, they aren't kept
+    /// either
+    pub fn is_recorded(&self) -> bool {
+        !self.command.trim().is_empty() && !self.command.starts_with(' ')
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ShellCommand {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub duration_secs: Option<f64>,
+    pub command: String,
+    pub cwd: Option<String>,
+    pub exit_code: Option<i32>,
+    pub shell: String,
+    pub source: ShellCommandSource,
+    /// Terminal frames captured while it ran and right after, the first ones
+    pub frame_ids: Vec<i64>,
+}
+
+pub fn is_terminal(app_name: &str) -> bool {
+    let app_name = app_name.to_lowercase();
+    // like WindowsTerminal or gnome-terminal-server
+    app_name.contains("terminal")
+        || app_name
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| TERMINALS.contains(&word))
+}
+
+/// The frames of terminal windows captured while the command ran
+pub async fn link_terminal_frames(
+    db: &DatabaseManager,
+    commands: &mut [ShellCommand],
+) -> Result<()> {
+    for command in commands.iter_mut() {
+        let end = command.ended_at.unwrap_or(command.timestamp) + OUTPUT_GRACE;
+        command.frame_ids = db
+            .get_frame_windows_between(command.timestamp, end)
+            .await?
+            .into_iter()
+            .filter(|(_, app_name, _)| is_terminal(app_name))
+            .map(|(frame_id, _, _)| frame_id)
+            .take(MAX_LINKED_FRAMES)
+            .collect();
+    }
+    Ok(())
+}
+
+/// Stores what the hooks sent, unless capture is paused. Returns whether it was stored
+pub async fn record_hook_command(
+    db: &DatabaseManager,
+    capture_pause: &CapturePause,
+    command: NewShellCommand,
+) -> Result<bool> {
+    if capture_pause.is_paused() || !command.is_recorded() {
+        return Ok(false);
+    }
+    Ok(db.insert_shell_commands(&[command]).await? > 0)
+}
+
+/// Reads the history files changed since `modified`, and stores their commands but the ones
+/// run while capture was paused. Returns how many were new
+pub async fn import_shell_history(
+    db: &DatabaseManager,
+    files: &[(Shell, PathBuf)],
+    modified: &mut HashMap<PathBuf, SystemTime>,
+    pauses: &[(DateTime<Utc>, DateTime<Utc>)],
+) -> Result<u64> {
+    let mut imported = 0;
+    for (shell, path) in files {
+        let Ok(changed) = tokio::fs::metadata(path).await.and_then(|m| m.modified()) else {
+            continue;
+        };
+        if modified.get(path) == Some(&changed) {
+            continue;
+        }
+        // zsh escapes the bytes of some characters, they are left as they are
+        let history = String::from_utf8_lossy(&tokio::fs::read(path).await?).to_string();
+        let commands: Vec<NewShellCommand> = parse_history(*shell, &history)
+            .into_iter()
+            .map(|entry| NewShellCommand::from_history(*shell, entry))
+            .filter(|command| {
+                command.is_recorded()
+                    && !pauses.iter().any(|(start, end)| {
+                        command.timestamp >= *start && command.timestamp <= *end
+                    })
+            })
+            .collect();
+        imported += db.insert_shell_commands(&commands).await?;
+        modified.insert(path.clone(), changed);
+        debug!("Read {} commands of {}", commands.len(), path.display());
+    }
+    Ok(imported)
+}
+
+/// Imports the history files as they are written
+pub async fn start_shell_history_import(
+    db: Arc<DatabaseManager>,
+    files: Vec<(Shell, PathBuf)>,
+    capture_pause: Arc<CapturePause>,
+    interval: Duration,
+) {
+    info!(
+        "Importing shell history from {}",
+        files
+            .iter()
+            .map(|(_, path)| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    let mut modified = HashMap::new();
+    let mut pause_log = PauseLog::new(interval);
+    let mut ticker = tokio::time::interval(interval);
+    loop {
+        ticker.tick().await;
+        if pause_log.observe(&capture_pause) {
+            continue;
+        }
+        match import_shell_history(&db, &files, &mut modified, pause_log.pauses()).await {
+            Ok(0) => {}
+            Ok(imported) => info!("Imported {} shell commands", imported),
+            Err(e) => warn!("Failed to import shell history: {}", e),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::video::extract_frame;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use image::{imageops::FilterType, DynamicImage};
+use serde::Serialize;
+use std::f64::consts::PI;
+
+/// Side of the thumbnail the DCT runs on
+const HASH_SIZE: usize = 32;
+/// Side of the low frequencies kept, one bit each
+const HASH_BITS: usize = 8;
+/// Frames hashed at most this many bits apart are returned by default, a window that was
+/// scrolled or resized a bit stays under it
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+const SCAN_BATCH_SIZE: u32 = 10_000;
+
+/// A past frame that looks like the query, the closest first
+#[derive(Debug, Clone, Serialize)]
+pub struct SimilarFrame {
+    pub frame_id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: String,
+    /// Differing bits of the two hashes, 0 for the same picture
+    pub distance: u32,
+    /// 1 for the same picture
+    pub similarity: f32,
+}
+
+/// pHash of the image: the signs of the lowest frequencies of its DCT. Small changes like
+/// compression, scaling or a blinking cursor only flip a few bits
+pub fn perceptual_hash(image: &DynamicImage) -> u64 {
+    let thumbnail = image
+        .resize_exact(HASH_SIZE as u32, HASH_SIZE as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<f64> = thumbnail.pixels().map(|pixel| pixel[0] as f64).collect();
+
+    let cosines: Vec<f64> = (0..HASH_BITS)
+        .flat_map(|frequency| {
+            (0..HASH_SIZE).map(move |x| {
+                ((2 * x + 1) as f64 * frequency as f64 * PI / (2 * HASH_SIZE) as f64).cos()
+            })
+        })
+        .collect();
+    let mut coefficients = Vec::with_capacity(HASH_BITS * HASH_BITS);
+    for v in 0..HASH_BITS {
+        for u in 0..HASH_BITS {
+            let mut sum = 0.0;
+            for y in 0..HASH_SIZE {
+                let row = &pixels[y * HASH_SIZE..(y + 1) * HASH_SIZE];
+                let cos_y = cosines[v * HASH_SIZE + y];
+                for (x, pixel) in row.iter().enumerate() {
+                    sum += pixel * cosines[u * HASH_SIZE + x] * cos_y;
+                }
+            }
+            coefficients.push(sum);
+        }
+    }
+
+    // the first coefficient is the average brightness, not the picture
+    let mut sorted: Vec<f64> = coefficients[1..].to_vec();
+    sorted.sort_by(f64::total_cmp);
+    let median = sorted[sorted.len() / 2];
+    coefficients
+        .iter()
+        .enumerate()
+        .fold(0u64, |hash, (bit, coefficient)| {
+            if *coefficient > median {
+                hash | (1 << bit)
+            } else {
+                hash
+            }
+        })
+}
+
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+/// The hash of a stored frame, frames recorded before hashes were stored are hashed from
+/// their video once
+pub async fn frame_hash(db: &DatabaseManager, frame_id: i64) -> Result<u64> {
+    if let Some(hash) = db.get_frame_perceptual_hash(frame_id).await? {
+        return Ok(hash);
+    }
+    let (file_path, offset_index) = db
+        .get_frame(frame_id)
+        .await?
+        .ok_or_else(|| anyhow!("frame {} not found", frame_id))?;
+    let image = extract_frame(&file_path, offset_index).await?;
+    let hash = perceptual_hash(&image);
+    db.set_frame_perceptual_hash(frame_id, hash).await?;
+    Ok(hash)
+}
+
+/// The `limit` frames closest to `hash`, at most `max_distance` bits apart
+pub async fn find_similar_frames(
+    db: &DatabaseManager,
+    hash: u64,
+    max_distance: u32,
+    limit: usize,
+    exclude_frame_id: Option<i64>,
+) -> Result<Vec<SimilarFrame>> {
+    let mut similar = Vec::new();
+    let mut after_id = 0;
+    loop {
+        let batch = db
+            .get_frame_perceptual_hashes(after_id, SCAN_BATCH_SIZE)
+            .await?;
+        let Some(last) = batch.last() else {
+            break;
+        };
+        after_id = last.0;
+        similar.extend(
+            batch
+                .into_iter()
+                .filter(|(frame_id, _)| Some(*frame_id) != exclude_frame_id)
+                .map(|(frame_id, frame_hash)| (hash_distance(hash, frame_hash), frame_id))
+                .filter(|(distance, _)| *distance <= max_distance),
+        );
+        // the newest first among equally close frames
+        similar.sort_by_key(|(distance, frame_id)| (*distance, -frame_id));
+        similar.truncate(limit);
+    }
+
+    let mut frames = Vec::with_capacity(similar.len());
+    for (distance, frame_id) in similar {
+        let Some((timestamp, app_name, window_name)) = db.get_frame_window(frame_id).await? else {
+            continue;
+        };
+        frames.push(SimilarFrame {
+            frame_id,
+            timestamp,
+            app_name,
+            window_name,
+            distance,
+            similarity: 1.0 - distance as f32 / 64.0,
+        });
+    }
+    Ok(frames)
+}
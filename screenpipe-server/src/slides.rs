@@ -0,0 +1,254 @@
+use crate::disk_guard::{disk_level, DiskLevel};
+use crate::encryption::write_media;
+use crate::pdf::encode_jpeg;
+use crate::text_only::text_only;
+use crate::scene::{changed_fraction, fullscreen_window, is_presentation_app};
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use image::DynamicImage;
+use log::{error, info};
+use screenpipe_core::emit_event;
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::CaptureResult;
+use serde::Serialize;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// A slide is kept once it's been shown this long, the frames of animations and videos come
+/// faster
+pub const MIN_SLIDE_DURATION: Duration = Duration::seconds(2);
+/// Full screen windows with more lines of text are documents, not slides
+const MAX_SLIDE_LINES: usize = 25;
+/// Size the slides are compared at, small enough that a slide read twice is the same
+const SLIDE_THUMBNAIL_WIDTH: u32 = 64;
+const SLIDE_THUMBNAIL_HEIGHT: u32 = 36;
+/// Slides differing from one of the deck by less than this fraction are that one again
+const SAME_SLIDE: f64 = 0.02;
+
+/// The slides shown in a row by a presentation, stored as they're shown
+#[derive(Debug, Clone, Serialize)]
+pub struct SlideDeck {
+    pub id: i64,
+    pub started_at: DateTime<Utc>,
+    /// None while presented, or when screenpipe stopped during the presentation
+    pub ended_at: Option<DateTime<Utc>>,
+    pub monitor_id: u32,
+    pub app_name: String,
+    /// Of the first slide
+    pub window_name: String,
+    pub slide_count: u32,
+}
+
+/// Each unique slide of a deck once, in the order they were first shown
+#[derive(Debug, Clone, Serialize)]
+pub struct Slide {
+    pub id: i64,
+    pub deck_id: i64,
+    /// From 1
+    pub position: u32,
+    pub timestamp: DateTime<Utc>,
+    /// The slide as JPEG, none when the disk was too full for it
+    pub image_path: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub text: String,
+}
+
+/// A slide shown long enough, to store in the deck being presented
+#[derive(Debug, Clone)]
+pub struct DetectedSlide {
+    pub timestamp: DateTime<Utc>,
+    pub image: Arc<DynamicImage>,
+    pub text: String,
+    pub app_name: String,
+    pub window_name: String,
+    thumbnail: Vec<u8>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SlideAction {
+    /// The first one starts a deck
+    Slide(DetectedSlide),
+    /// The deck isn't presented anymore since then
+    DeckEnded(DateTime<Utc>),
+}
+
+/// A focused window filling the frame, of a presentation app or with no more text than a
+/// slide has
+fn slide_window(frame: &CaptureResult) -> Option<&WindowOcrResult> {
+    let window = fullscreen_window(frame)?;
+    let lines = window
+        .text
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .count();
+    (is_presentation_app(window) || (1..=MAX_SLIDE_LINES).contains(&lines)).then_some(window)
+}
+
+fn normalized_text(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Tells which frames of a monitor are slides. A frame is kept once the next one shows it
+/// stayed on screen for `MIN_SLIDE_DURATION`, and only when the deck doesn't have it already
+#[derive(Default)]
+pub struct SlideDetector {
+    pending: Option<DetectedSlide>,
+    /// Thumbnails and texts of the slides of the deck
+    kept: Vec<(Vec<u8>, String)>,
+    /// App of the deck being presented
+    deck_app: Option<String>,
+}
+
+impl SlideDetector {
+    pub fn observe(&mut self, frame: &CaptureResult, timestamp: DateTime<Utc>) -> Vec<SlideAction> {
+        let mut actions = Vec::new();
+        if let Some(pending) = self.pending.take() {
+            if timestamp - pending.timestamp >= MIN_SLIDE_DURATION {
+                self.keep(pending, &mut actions);
+            }
+        }
+
+        let window = slide_window(frame);
+        let same_deck = match (window, &self.deck_app) {
+            (Some(window), Some(app)) => window.app_name == *app,
+            (_, None) => true,
+            (None, Some(_)) => false,
+        };
+        if !same_deck {
+            if !self.kept.is_empty() {
+                actions.push(SlideAction::DeckEnded(timestamp));
+            }
+            self.kept.clear();
+            self.deck_app = None;
+        }
+        if let Some(window) = window {
+            self.deck_app = Some(window.app_name.clone());
+            self.pending = Some(DetectedSlide {
+                timestamp,
+                image: Arc::clone(&window.image),
+                text: window.text.clone(),
+                app_name: window.app_name.clone(),
+                window_name: window.window_name.clone(),
+                thumbnail: window
+                    .image
+                    .thumbnail_exact(SLIDE_THUMBNAIL_WIDTH, SLIDE_THUMBNAIL_HEIGHT)
+                    .to_luma8()
+                    .into_raw(),
+            });
+        }
+        actions
+    }
+
+    fn keep(&mut self, slide: DetectedSlide, actions: &mut Vec<SlideAction>) {
+        let text = normalized_text(&slide.text);
+        let seen = self.kept.iter().any(|(thumbnail, kept_text)| {
+            changed_fraction(thumbnail, &slide.thumbnail) <= SAME_SLIDE
+                || (!text.is_empty() && *kept_text == text)
+        });
+        if !seen {
+            self.kept.push((slide.thumbnail.clone(), text));
+            actions.push(SlideAction::Slide(slide));
+        }
+    }
+}
+
+/// Stores the slides a monitor's capture shows as decks
+pub struct SlideRecorder {
+    db: Arc<DatabaseManager>,
+    output_dir: PathBuf,
+    monitor_id: u32,
+    detector: SlideDetector,
+    /// Id of the deck presented and its number of slides, none until its first slide
+    deck: Option<(i64, u32)>,
+}
+
+impl SlideRecorder {
+    pub fn new(db: Arc<DatabaseManager>, output_dir: PathBuf, monitor_id: u32) -> Self {
+        SlideRecorder {
+            db,
+            output_dir,
+            monitor_id,
+            detector: SlideDetector::default(),
+            deck: None,
+        }
+    }
+
+    pub async fn observe(&mut self, frame: &CaptureResult, timestamp: DateTime<Utc>) {
+        for action in self.detector.observe(frame, timestamp) {
+            if let Err(e) = self.record(action).await {
+                error!("Failed to store a slide: {}", e);
+            }
+        }
+    }
+
+    async fn record(&mut self, action: SlideAction) -> Result<()> {
+        match action {
+            SlideAction::Slide(slide) => {
+                let (deck_id, position) = match self.deck {
+                    Some((id, count)) => (id, count + 1),
+                    None => {
+                        let id = self
+                            .db
+                            .insert_slide_deck(
+                                slide.timestamp,
+                                self.monitor_id,
+                                &slide.app_name,
+                                &slide.window_name,
+                            )
+                            .await?;
+                        info!("Slide deck {} started in {}", id, slide.app_name);
+                        (id, 1)
+                    }
+                };
+                self.deck = Some((deck_id, position));
+                let image_path = if disk_level() < DiskLevel::NoRawFrames && !text_only() {
+                    let image = Arc::clone(&slide.image);
+                    let jpeg = tokio::task::spawn_blocking(move || encode_jpeg(&image)).await??;
+                    tokio::fs::create_dir_all(&self.output_dir).await?;
+                    let path = self
+                        .output_dir
+                        .join(format!("deck_{}_slide_{}.jpg", deck_id, position));
+                    Some(write_media(&path, jpeg).await?)
+                } else {
+                    None
+                };
+                let id = self
+                    .db
+                    .insert_slide(
+                        deck_id,
+                        position,
+                        slide.timestamp,
+                        image_path.as_ref().map(|(path, _)| path.as_str()),
+                        image_path.as_ref().and_then(|(_, key_id)| *key_id),
+                        slide.image.width(),
+                        slide.image.height(),
+                        &slide.text,
+                    )
+                    .await?;
+                emit_event(
+                    "slides.captured",
+                    json!({
+                        "deck_id": deck_id,
+                        "slide_id": id,
+                        "position": position,
+                        "text": slide.text,
+                    }),
+                );
+            }
+            SlideAction::DeckEnded(ended_at) => {
+                if let Some((id, count)) = self.deck.take() {
+                    self.db.end_slide_deck(id, ended_at).await?;
+                    info!("Slide deck {} ended with {} slides", id, count);
+                    emit_event(
+                        "slides.deck_ended",
+                        json!({"deck_id": id, "slide_count": count}),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
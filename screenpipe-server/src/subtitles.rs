@@ -0,0 +1,252 @@
+use crate::power::background_jobs_paused;
+use crate::video::MAX_FPS;
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use screenpipe_core::{Container, FfmpegCommand, FfmpegInput, FfmpegOutput, VideoCodec};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Chunks are remuxed once their OCR and transcripts had time to land in the database
+const SETTLE_TIME: ChronoDuration = ChronoDuration::minutes(2);
+const CHUNKS_PER_RUN: u32 = 10;
+const MAX_CUE_CHARS: usize = 200;
+
+/// Frame of a video chunk with its focused window
+#[derive(Debug, Clone)]
+pub struct ChunkFrame {
+    pub timestamp: DateTime<Utc>,
+    pub offset_index: i64,
+    pub app_name: String,
+    pub window_name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SubtitleCue {
+    /// Seconds from the start of the chunk
+    pub start: f64,
+    pub end: f64,
+    pub text: String,
+}
+
+/// Position in the chunk of a wall clock time, interpolated between the frames around it.
+/// The capture rarely keeps up with the encoding fps so wall time drifts from video time.
+fn video_time(frames: &[ChunkFrame], time: DateTime<Utc>, fps: f64) -> f64 {
+    let position = |frame: &ChunkFrame| frame.offset_index as f64 / fps;
+    let Some(next) = frames.iter().position(|f| f.timestamp > time) else {
+        return frames
+            .last()
+            .map(|f| position(f) + 1.0 / fps)
+            .unwrap_or(0.0);
+    };
+    if next == 0 {
+        return 0.0;
+    }
+    let (before, after) = (&frames[next - 1], &frames[next]);
+    let span = (after.timestamp - before.timestamp).num_milliseconds() as f64;
+    let progress = if span > 0.0 {
+        (time - before.timestamp).num_milliseconds() as f64 / span
+    } else {
+        0.0
+    };
+    position(before) + progress * (position(after) - position(before))
+}
+
+/// One cue per run of frames showing the same focused window
+pub fn window_cues(frames: &[ChunkFrame], fps: f64) -> Vec<SubtitleCue> {
+    let mut cues: Vec<SubtitleCue> = Vec::new();
+    for frame in frames {
+        if frame.app_name.is_empty() {
+            continue;
+        }
+        let text = if frame.window_name.is_empty() || frame.window_name == frame.app_name {
+            frame.app_name.clone()
+        } else {
+            format!("{} - {}", frame.app_name, frame.window_name)
+        };
+        let start = frame.offset_index as f64 / fps;
+        let end = start + 1.0 / fps;
+        match cues.last_mut() {
+            Some(cue) if cue.text == text => cue.end = end,
+            _ => cues.push(SubtitleCue { start, end, text }),
+        }
+    }
+    cues
+}
+
+/// Transcriptions are stored when their audio chunk ends, so each one covers the
+/// `audio_chunk_duration` before its timestamp
+pub fn transcript_cues(
+    frames: &[ChunkFrame],
+    transcripts: &[(DateTime<Utc>, String)],
+    audio_chunk_duration: ChronoDuration,
+    fps: f64,
+) -> Vec<SubtitleCue> {
+    let (Some(first), Some(last)) = (frames.first(), frames.last()) else {
+        return Vec::new();
+    };
+    transcripts
+        .iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .filter_map(|(timestamp, text)| {
+            let spoken_start = (*timestamp - audio_chunk_duration).max(first.timestamp);
+            let spoken_end = (*timestamp).min(last.timestamp);
+            if spoken_end < spoken_start {
+                return None;
+            }
+            let start = video_time(frames, spoken_start, fps);
+            let end = video_time(frames, spoken_end, fps).max(start + 1.0 / fps);
+            Some(SubtitleCue {
+                start,
+                end,
+                text: text.trim().chars().take(MAX_CUE_CHARS).collect(),
+            })
+        })
+        .collect()
+}
+
+fn srt_timestamp(seconds: f64) -> String {
+    let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+    format!(
+        "{:02}:{:02}:{:02},{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        millis % 1000
+    )
+}
+
+pub fn to_srt(cues: &[SubtitleCue]) -> String {
+    let mut cues = cues.to_vec();
+    cues.sort_by(|a, b| a.start.total_cmp(&b.start));
+    cues.iter()
+        .enumerate()
+        .map(|(i, cue)| {
+            format!(
+                "{}\n{} --> {}\n{}\n",
+                i + 1,
+                srt_timestamp(cue.start),
+                srt_timestamp(cue.end),
+                // a blank line would end the cue
+                cue.text
+                    .lines()
+                    .filter(|line| !line.trim().is_empty())
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Rewrites the chunk with the subtitles as a mov_text track, video is copied untouched
+/// so frame offsets stay valid
+pub async fn mux_subtitles(chunk_path: &str, srt: &str) -> Result<()> {
+    let chunk = Path::new(chunk_path);
+    let srt_path = chunk.with_extension("srt");
+    let muxed_path: PathBuf = chunk.with_extension("subtitled.mp4");
+    tokio::fs::write(&srt_path, srt).await?;
+
+    let output = FfmpegCommand::new()
+        .overwrite()
+        .input(FfmpegInput::file(chunk_path))
+        .input(FfmpegInput::file(&srt_path.to_string_lossy()))
+        .output(
+            FfmpegOutput::file(&muxed_path.to_string_lossy())
+                .arg("-map", "0:v")
+                .arg("-map", "1:s")
+                .video_codec(VideoCodec::Copy)
+                .arg("-c:s", "mov_text")
+                .arg("-metadata:s:s:0", "title=screenpipe")
+                .format(Container::Mp4),
+        )
+        .run()
+        .await;
+    let _ = tokio::fs::remove_file(&srt_path).await;
+    let output = output?;
+    if !output.status.success() {
+        let _ = tokio::fs::remove_file(&muxed_path).await;
+        anyhow::bail!(
+            "failed to mux subtitles into {}: {}",
+            chunk_path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    // rename doesn't replace existing files on windows
+    if cfg!(windows) {
+        tokio::fs::remove_file(chunk).await?;
+    }
+    tokio::fs::rename(&muxed_path, chunk).await?;
+    Ok(())
+}
+
+async fn mux_settled_chunks(
+    db: &DatabaseManager,
+    fps: f64,
+    audio_chunk_duration: ChronoDuration,
+) -> Result<()> {
+    let chunks = db
+        .get_video_chunks_pending_subtitles(Utc::now() - SETTLE_TIME, CHUNKS_PER_RUN)
+        .await?;
+
+    for (chunk_id, file_path) in chunks {
+        // frames stored as images, nothing to mux into
+        if !file_path.ends_with(".mp4") {
+            db.mark_video_chunk_subtitles_muxed(chunk_id).await?;
+            continue;
+        }
+
+        let frames = db.get_video_chunk_frames(chunk_id).await?;
+        let (Some(first), Some(last)) = (frames.first(), frames.last()) else {
+            db.mark_video_chunk_subtitles_muxed(chunk_id).await?;
+            continue;
+        };
+        let transcripts = db
+            .get_transcriptions_between(first.timestamp, last.timestamp + audio_chunk_duration)
+            .await?;
+
+        let mut cues = window_cues(&frames, fps);
+        cues.extend(transcript_cues(
+            &frames,
+            &transcripts,
+            audio_chunk_duration,
+            fps,
+        ));
+
+        if cues.is_empty() {
+            debug!("No subtitles for {}", file_path);
+        } else if let Err(e) = mux_subtitles(&file_path, &to_srt(&cues)).await {
+            // the chunk itself is fine, don't retry forever
+            warn!("{}", e);
+        } else {
+            debug!("Muxed {} subtitles into {}", cues.len(), file_path);
+        }
+        db.mark_video_chunk_subtitles_muxed(chunk_id).await?;
+    }
+    Ok(())
+}
+
+/// Periodically adds a subtitle track of window titles and transcripts to finished chunks
+pub async fn start_subtitle_muxer(
+    db: Arc<DatabaseManager>,
+    fps: f64,
+    audio_chunk_duration: Duration,
+    interval: Duration,
+) {
+    let audio_chunk_duration =
+        ChronoDuration::from_std(audio_chunk_duration).unwrap_or(ChronoDuration::seconds(30));
+    // chunks are encoded at most at this rate
+    let fps = fps.min(MAX_FPS);
+    info!("Subtitle muxer started");
+    loop {
+        if !background_jobs_paused() {
+            if let Err(e) = mux_settled_chunks(&db, fps, audio_chunk_duration).await {
+                error!("Failed to mux subtitles: {}", e);
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
@@ -0,0 +1,372 @@
+//! Replays recorded frames and audio through the recording pipeline, for end to end tests
+//! without a display or a microphone.
+//!
+//! Frames go through the same frame comparison, OCR task and batched writes as a capture, and
+//! audio chunks through the same journal and transcription channel as a recording. The OCR and
+//! transcription engines are a remote worker answering with the text recorded with each
+//! fixture, so the results don't depend on the engines installed on the machine.
+
+use crate::core::{frame_writes, journal_audio_chunks, process_audio_result};
+use crate::text_changes::TextChangeTracker;
+use crate::watchlist::Watchlist;
+use crate::write_coalescer::{PendingWrite, WriteCoalescer, DEFAULT_FLUSH_INTERVAL};
+use crate::DatabaseManager;
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use chrono::Utc;
+use image::DynamicImage;
+use screenpipe_audio::{create_whisper_channel, AudioInput, AudioTranscriptionEngine};
+use screenpipe_integrations::remote_worker::{
+    serve_worker, set_remote_worker, RemoteWorker, WorkHandler, WorkerOptions,
+};
+use screenpipe_vision::{
+    clear_ocr_cache, process_ocr_task, LumaFrame, OcrEngine, MIN_FRAME_DIFFERENCE,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::net::TcpListener;
+use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
+
+/// The worker is process wide, two replays at the same time would answer with each other's text
+static REPLAY: Mutex<()> = Mutex::const_new(());
+
+/// A window of a recorded frame with the text the OCR engine found in it
+#[derive(Clone)]
+pub struct SyntheticWindow {
+    pub app_name: String,
+    pub window_name: String,
+    pub focused: bool,
+    pub image: DynamicImage,
+    pub text: String,
+}
+
+/// A recorded screen, compared with the previous frame like a capture is
+#[derive(Clone)]
+pub struct SyntheticFrame {
+    pub image: DynamicImage,
+    pub windows: Vec<SyntheticWindow>,
+}
+
+/// A recorded audio chunk with its transcript, the file is stored like a recording
+#[derive(Debug, Clone)]
+pub struct SyntheticAudio {
+    pub device: String,
+    pub path: PathBuf,
+    pub transcript: String,
+}
+
+/// The frames and audio chunks of a replay, in the order they were recorded
+#[derive(Clone, Default)]
+pub struct SyntheticWorkload {
+    /// Stored as the video chunk of the frames, no video is encoded
+    pub video_chunk: String,
+    pub frames: Vec<SyntheticFrame>,
+    pub audio: Vec<SyntheticAudio>,
+}
+
+/// What the pipeline did with the workload
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    pub frames: usize,
+    /// Too close to the previous frame, like a static screen
+    pub skipped_frames: usize,
+    pub windows: usize,
+    /// "screen.text_changed" events emitted
+    pub text_changes: usize,
+    /// Matches of the watches stored in the database, in the frames and the transcripts
+    pub alerts: usize,
+    pub transcripts: usize,
+}
+
+/// `workload.json` of a recorded workload, paths are relative to its directory
+#[derive(Deserialize)]
+struct Manifest {
+    #[serde(default)]
+    frames: Vec<ManifestFrame>,
+    #[serde(default)]
+    audio: Vec<ManifestAudio>,
+}
+
+#[derive(Deserialize)]
+struct ManifestFrame {
+    image: PathBuf,
+    #[serde(default)]
+    windows: Vec<ManifestWindow>,
+}
+
+#[derive(Deserialize)]
+struct ManifestWindow {
+    app_name: String,
+    window_name: String,
+    #[serde(default)]
+    focused: bool,
+    image: PathBuf,
+    text: String,
+}
+
+#[derive(Deserialize)]
+struct ManifestAudio {
+    device: String,
+    path: PathBuf,
+    transcript: String,
+}
+
+fn open_image(path: &Path) -> Result<DynamicImage> {
+    image::open(path).with_context(|| format!("failed to open {}", path.display()))
+}
+
+impl SyntheticWorkload {
+    /// Reads the `workload.json` of `dir` and the images it lists
+    pub fn load(dir: &Path) -> Result<Self> {
+        let manifest_path = dir.join("workload.json");
+        let manifest = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("failed to read {}", manifest_path.display()))?;
+        let manifest: Manifest = serde_json::from_str(&manifest)?;
+
+        let mut frames = Vec::new();
+        for frame in manifest.frames {
+            let mut windows = Vec::new();
+            for window in frame.windows {
+                windows.push(SyntheticWindow {
+                    app_name: window.app_name,
+                    window_name: window.window_name,
+                    focused: window.focused,
+                    image: open_image(&dir.join(window.image))?,
+                    text: window.text,
+                });
+            }
+            frames.push(SyntheticFrame {
+                image: open_image(&dir.join(frame.image))?,
+                windows,
+            });
+        }
+        let audio = manifest
+            .audio
+            .into_iter()
+            .map(|audio| SyntheticAudio {
+                device: audio.device,
+                path: dir.join(audio.path),
+                transcript: audio.transcript,
+            })
+            .collect();
+
+        Ok(SyntheticWorkload {
+            video_chunk: dir.join("synthetic.mp4").to_string_lossy().to_string(),
+            frames,
+            audio,
+        })
+    }
+
+    /// Runs the workload through the pipeline into `db`, returns once everything is written
+    pub async fn replay(&self, db: Arc<DatabaseManager>) -> Result<ReplayReport> {
+        let _replay = REPLAY.lock().await;
+        // the same pixels may have other text in this workload
+        clear_ocr_cache();
+        let worker = FixtureWorker::new(self)?;
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        let server = tokio::spawn(serve_worker(
+            listener,
+            Arc::new(worker),
+            WorkerOptions {
+                token: None,
+                max_in_flight: 1,
+                tls: None,
+            },
+        ));
+        set_remote_worker(Some(Arc::new(RemoteWorker::new(&url, None, None)?)));
+
+        let writes = WriteCoalescer::start(Arc::clone(&db), DEFAULT_FLUSH_INTERVAL);
+        let result = self.replay_with(&db, &writes).await;
+        writes.flush().await;
+
+        set_remote_worker(None);
+        server.abort();
+        result
+    }
+
+    async fn replay_with(
+        &self,
+        db: &Arc<DatabaseManager>,
+        writes: &WriteCoalescer,
+    ) -> Result<ReplayReport> {
+        let mut report = ReplayReport::default();
+        let ocr_engine = Arc::new(OcrEngine::Remote);
+        let watchlist = Watchlist::load(Arc::clone(db)).await?;
+        let alerts_before = db.count_watch_alerts(None).await?;
+
+        if !self.frames.is_empty() {
+            writes
+                .write(PendingWrite::VideoChunk {
+                    file_path: self.video_chunk.clone(),
+                })
+                .await;
+        }
+        let (result_tx, mut result_rx) = tokio::sync::mpsc::channel(1);
+        let mut previous_frame: Option<LumaFrame> = None;
+        let mut text_changes = TextChangeTracker::default();
+        for (frame_number, frame) in self.frames.iter().enumerate() {
+            let luma = LumaFrame::new(&frame.image);
+            let difference = match &previous_frame {
+                Some(previous) => previous.difference(&luma)?,
+                None => 1.0,
+            };
+            if difference < MIN_FRAME_DIFFERENCE {
+                report.skipped_frames += 1;
+                continue;
+            }
+            previous_frame = Some(luma);
+
+            let window_images = frame
+                .windows
+                .iter()
+                .map(|window| {
+                    (
+                        Arc::new(window.image.clone()),
+                        window.app_name.clone(),
+                        window.window_name.clone(),
+                        window.focused,
+                        (0, 0),
+                    )
+                })
+                .collect();
+            process_ocr_task(
+                Arc::new(frame.image.clone()),
+                window_images,
+                Vec::new(),
+                frame_number as u64,
+                Instant::now(),
+                result_tx.clone(),
+                false,
+                Arc::clone(&ocr_engine),
+                CancellationToken::new(),
+            )
+            .await?;
+            let captured = result_rx
+                .recv()
+                .await
+                .ok_or_else(|| anyhow!("OCR task stopped"))?;
+            let timestamp = Utc::now();
+            report.text_changes += text_changes.observe(&captured, timestamp).len();
+            watchlist.check_frame(&captured, timestamp).await;
+            let frame_writes = frame_writes(&captured, timestamp, &ocr_engine, None);
+            report.frames += 1;
+            report.windows += frame_writes
+                .iter()
+                .filter(|write| matches!(write, PendingWrite::Ocr { .. }))
+                .count();
+            for write in frame_writes {
+                writes.write(write).await;
+            }
+        }
+
+        if !self.audio.is_empty() {
+            let engine = Arc::new(AudioTranscriptionEngine::Remote);
+            let (whisper_sender, mut whisper_receiver) =
+                create_whisper_channel(Arc::clone(&engine), CancellationToken::new()).await?;
+            let whisper_sender = journal_audio_chunks(Arc::clone(db), whisper_sender);
+            for audio in &self.audio {
+                whisper_sender
+                    .send(AudioInput {
+                        path: audio.path.to_string_lossy().to_string(),
+                        device: audio.device.clone(),
+                    })
+                    .await
+                    .map_err(|_| anyhow!("transcription stopped"))?;
+            }
+            for _ in &self.audio {
+                let result = whisper_receiver
+                    .recv()
+                    .await
+                    .ok_or_else(|| anyhow!("transcription stopped"))?;
+                if let Some(error) = &result.error {
+                    bail!("failed to transcribe {}: {}", result.input.path, error);
+                }
+                process_audio_result(
+                    db,
+                    writes,
+                    result,
+                    None,
+                    Arc::clone(&engine),
+                    None,
+                    Some(&watchlist),
+                )
+                .await?;
+                report.transcripts += 1;
+            }
+        }
+
+        report.alerts = (db.count_watch_alerts(None).await? - alerts_before) as usize;
+        Ok(report)
+    }
+}
+
+/// Answers with the text recorded with the closest window image, and the transcript of the
+/// audio file with the same bytes
+struct FixtureWorker {
+    windows: Vec<(LumaFrame, String)>,
+    transcripts: HashMap<Vec<u8>, String>,
+}
+
+impl FixtureWorker {
+    fn new(workload: &SyntheticWorkload) -> Result<Self> {
+        let windows = workload
+            .frames
+            .iter()
+            .flat_map(|frame| &frame.windows)
+            .map(|window| (LumaFrame::new(&window.image), window.text.clone()))
+            .collect();
+        let mut transcripts = HashMap::new();
+        for audio in &workload.audio {
+            let bytes = std::fs::read(&audio.path)
+                .with_context(|| format!("failed to read {}", audio.path.display()))?;
+            transcripts.insert(bytes, audio.transcript.clone());
+        }
+        Ok(FixtureWorker {
+            windows,
+            transcripts,
+        })
+    }
+}
+
+#[async_trait]
+impl WorkHandler for FixtureWorker {
+    fn can_ocr(&self) -> bool {
+        true
+    }
+
+    fn can_transcribe(&self) -> bool {
+        true
+    }
+
+    async fn ocr(&self, image: DynamicImage) -> Result<(String, String)> {
+        // frames are sent as jpeg, the image is close to a recorded one but not the same
+        let luma = LumaFrame::new(&image);
+        let mut closest: Option<(f64, &str)> = None;
+        for (window, text) in &self.windows {
+            if window.dimensions() != luma.dimensions() {
+                continue;
+            }
+            let difference = window.difference(&luma)?;
+            if closest.is_none_or(|(best, _)| difference < best) {
+                closest = Some((difference, text));
+            }
+        }
+        match closest {
+            Some((_, text)) => Ok((text.to_string(), "[]".to_string())),
+            None => bail!("no recorded window of {}x{}", image.width(), image.height()),
+        }
+    }
+
+    async fn transcribe(&self, audio_path: &Path) -> Result<String> {
+        let bytes = tokio::fs::read(audio_path).await?;
+        self.transcripts
+            .get(&bytes)
+            .cloned()
+            .ok_or_else(|| anyhow!("no recorded transcript for this audio"))
+    }
+}
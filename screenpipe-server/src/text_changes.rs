@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use screenpipe_core::emit_event;
+use screenpipe_vision::CaptureResult;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+
+/// Windows that haven't been seen for the longest are forgotten past this many
+const MAX_TRACKED_WINDOWS: usize = 256;
+
+/// The lines of a window's text that appeared and disappeared since its previous frame
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TextChange {
+    pub timestamp: DateTime<Utc>,
+    pub app_name: String,
+    pub window_name: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+struct WindowText {
+    lines: Vec<String>,
+    last_seen: u64,
+}
+
+/// Compares the OCR text of each window with its previous frame and emits
+/// "screen.text_changed" with the lines that changed. The first frame of a window is its
+/// baseline, nothing is emitted for it
+#[derive(Default)]
+pub struct TextChangeTracker {
+    windows: HashMap<(String, String), WindowText>,
+    frames: u64,
+}
+
+impl TextChangeTracker {
+    pub fn observe(&mut self, frame: &CaptureResult, timestamp: DateTime<Utc>) -> Vec<TextChange> {
+        self.frames += 1;
+        let mut changes = Vec::new();
+        for window in &frame.window_ocr_results {
+            let lines = text_lines(&window.text);
+            let key = (window.app_name.clone(), window.window_name.clone());
+            let previous = self.windows.insert(
+                key,
+                WindowText {
+                    lines: lines.clone(),
+                    last_seen: self.frames,
+                },
+            );
+            let Some(previous) = previous else {
+                continue;
+            };
+            let (added, removed) = diff_lines(&previous.lines, &lines);
+            if added.is_empty() && removed.is_empty() {
+                continue;
+            }
+            let change = TextChange {
+                timestamp,
+                app_name: window.app_name.clone(),
+                window_name: window.window_name.clone(),
+                added,
+                removed,
+            };
+            emit_event("screen.text_changed", json!(change));
+            changes.push(change);
+        }
+
+        while self.windows.len() > MAX_TRACKED_WINDOWS {
+            let oldest = self
+                .windows
+                .iter()
+                .min_by_key(|(_, text)| text.last_seen)
+                .map(|(key, _)| key.clone());
+            match oldest {
+                Some(key) => self.windows.remove(&key),
+                None => break,
+            };
+        }
+        changes
+    }
+}
+
+fn text_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The lines only in `new` and the lines only in `old`, a line repeated more often than
+/// before counts as added. OCR doesn't always read the lines of a window in the same order,
+/// lines that only moved aren't changes
+pub fn diff_lines(old: &[String], new: &[String]) -> (Vec<String>, Vec<String>) {
+    let mut counts: HashMap<&str, i64> = HashMap::new();
+    for line in old {
+        *counts.entry(line).or_default() += 1;
+    }
+    let mut added = Vec::new();
+    for line in new {
+        match counts.get_mut(line.as_str()) {
+            Some(count) if *count > 0 => *count -= 1,
+            _ => added.push(line.clone()),
+        }
+    }
+    let mut removed = Vec::new();
+    for line in old.iter().rev() {
+        if let Some(count) = counts.get_mut(line.as_str()) {
+            if *count > 0 {
+                *count -= 1;
+                removed.push(line.clone());
+            }
+        }
+    }
+    removed.reverse();
+    (added, removed)
+}
@@ -0,0 +1,35 @@
+//! Recording the screen without keeping any image of it. The OCR text, the window metadata
+//! and the transcripts are written as usual, the frames only go to the OCR.
+use chrono::{DateTime, Utc};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Extension of the chunks recorded text only, no file is written for them, their rows only
+/// group the frames like the other chunks
+pub const TEXT_ONLY_CHUNK_EXTENSION: &str = "textonly";
+
+static TEXT_ONLY: AtomicBool = AtomicBool::new(false);
+
+/// No frame, thumbnail, marker screenshot or slide image is written from now on
+pub fn set_text_only(enabled: bool) {
+    TEXT_ONLY.store(enabled, Ordering::SeqCst);
+}
+
+pub fn text_only() -> bool {
+    TEXT_ONLY.load(Ordering::SeqCst)
+}
+
+/// Where a text only chunk started at `time` would be, named like the video chunks
+pub fn text_only_chunk_path(output_path: &str, time: DateTime<Utc>) -> PathBuf {
+    PathBuf::from(output_path).join(format!(
+        "{}.{}",
+        time.format("%Y-%m-%d_%H-%M-%S"),
+        TEXT_ONLY_CHUNK_EXTENSION
+    ))
+}
+
+pub fn is_text_only_chunk(file_path: &str) -> bool {
+    Path::new(file_path)
+        .extension()
+        .is_some_and(|extension| extension == TEXT_ONLY_CHUNK_EXTENSION)
+}
@@ -0,0 +1,138 @@
+//! Small copies of each captured frame, written next to the chunks at capture time, so the
+//! timeline can scrub through hours of 4K recordings without decoding a full frame per step.
+use crate::db::DatabaseManager;
+use crate::encryption::{open_media, write_media, ENCRYPTED_SUFFIX};
+use crate::frame_format::{FrameEncoding, FrameFormat};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use image::DynamicImage;
+use log::warn;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Widths of the pyramid, in pixels, from the timeline strip to the hover preview
+pub const THUMBNAIL_WIDTHS: [u32; 2] = [160, 480];
+/// Thumbnails are looked at in passing, JPEG at this quality is a few KB each
+const THUMBNAIL_QUALITY: u8 = 75;
+/// Directory of the thumbnails in the media directory
+const THUMBNAILS_DIR: &str = "thumbnails";
+
+/// The level of the pyramid to serve for `width`, the smallest at least as wide. None when
+/// the full frame is needed
+pub fn thumbnail_level(width: u32) -> Option<u32> {
+    THUMBNAIL_WIDTHS.into_iter().find(|level| *level >= width)
+}
+
+/// `image` scaled down to `width`, keeping its aspect ratio. Images already as narrow are
+/// left alone
+pub fn scale_to_width(image: &DynamicImage, width: u32) -> DynamicImage {
+    if image.width() <= width {
+        return image.clone();
+    }
+    let height = (image.height() as u64 * width as u64 / image.width() as u64).max(1);
+    image.thumbnail_exact(width, height as u32)
+}
+
+/// The thumbnails of the frames recorded to a media directory, found by the frame's
+/// timestamp, which is all the capture knows of it before the frame gets an id
+#[derive(Debug, Clone)]
+pub struct Thumbnails {
+    dir: PathBuf,
+}
+
+impl Thumbnails {
+    pub fn new(media_dir: &Path) -> Self {
+        Thumbnails {
+            dir: media_dir.join(THUMBNAILS_DIR),
+        }
+    }
+
+    /// The thumbnails of the media directory `file_path` was recorded to, they follow the
+    /// chunks when the recordings are moved
+    pub fn of_chunk(file_path: &str) -> Self {
+        Self::new(Path::new(file_path).parent().unwrap_or(Path::new("")))
+    }
+
+    fn day_dir(&self, timestamp: DateTime<Utc>) -> PathBuf {
+        self.dir.join(timestamp.format("%Y-%m-%d").to_string())
+    }
+
+    fn path(&self, timestamp: DateTime<Utc>, width: u32) -> PathBuf {
+        self.day_dir(timestamp).join(format!(
+            "{}_{}.jpg",
+            timestamp.format("%H-%M-%S%.9f"),
+            width
+        ))
+    }
+
+    /// Writes every level narrower than the frame, each scaled from the one above it, and
+    /// returns how many were written. Encrypted like the markers when media keys are loaded,
+    /// with the key recorded so it's re-encrypted when the key is rotated
+    pub async fn write(
+        &self,
+        db: &DatabaseManager,
+        image: Arc<DynamicImage>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<usize> {
+        let levels = tokio::task::spawn_blocking(move || {
+            let encoding = FrameEncoding {
+                format: FrameFormat::Jpeg,
+                quality: THUMBNAIL_QUALITY,
+            };
+            let mut levels = Vec::new();
+            let mut above: Option<DynamicImage> = None;
+            for width in THUMBNAIL_WIDTHS.into_iter().rev() {
+                if width >= image.width() {
+                    continue;
+                }
+                let scaled = scale_to_width(above.as_ref().unwrap_or(&image), width);
+                levels.push((width, encoding.encode(&scaled)?));
+                above = Some(scaled);
+            }
+            Ok::<_, anyhow::Error>(levels)
+        })
+        .await??;
+        if levels.is_empty() {
+            return Ok(0);
+        }
+        tokio::fs::create_dir_all(self.day_dir(timestamp)).await?;
+        let written = levels.len();
+        for (width, jpeg) in levels {
+            let (path, key_id) = write_media(&self.path(timestamp, width), jpeg).await?;
+            if let Some(key_id) = key_id {
+                db.set_thumbnail_key(&path, key_id).await?;
+            }
+        }
+        Ok(written)
+    }
+
+    /// The JPEG of the frame at `level`, None when it wasn't written
+    pub async fn read(&self, timestamp: DateTime<Utc>, level: u32) -> Result<Option<Vec<u8>>> {
+        let path = self.path(timestamp, level);
+        let encrypted = PathBuf::from(format!("{}{}", path.display(), ENCRYPTED_SUFFIX));
+        let Some(path) = [encrypted, path].into_iter().find(|path| path.exists()) else {
+            return Ok(None);
+        };
+        let media = open_media(&path.to_string_lossy()).await?;
+        Ok(Some(tokio::fs::read(media.path()).await?))
+    }
+
+    /// Removes every level of the frame, and the keys recorded for them
+    pub async fn remove(&self, db: &DatabaseManager, timestamp: DateTime<Utc>) {
+        for width in THUMBNAIL_WIDTHS {
+            let path = self.path(timestamp, width);
+            let encrypted = PathBuf::from(format!("{}{}", path.display(), ENCRYPTED_SUFFIX));
+            for path in [path, encrypted] {
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => warn!("Failed to remove thumbnail {}: {}", path.display(), e),
+                }
+            }
+            let encrypted = format!("{}{}", self.path(timestamp, width).display(), ENCRYPTED_SUFFIX);
+            if let Err(e) = db.delete_thumbnail_key(&encrypted).await {
+                warn!("Failed to remove the key of thumbnail {}: {}", encrypted, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,415 @@
+use crate::encryption::open_media;
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Local, Utc};
+use log::{error, info};
+use screenpipe_core::{
+    Container, EncoderQuality, FfmpegCommand, FfmpegInput, FfmpegOutput, VideoCodec, VideoEncoder,
+};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use crate::native_encoder::{is_native_chunk, read_native_frame};
+
+pub const DEFAULT_TIMELAPSE_SPEED: f64 = 60.0;
+pub const DEFAULT_TIMELAPSE_FPS: f64 = 30.0;
+const MAX_TIMELAPSE_FPS: f64 = 60.0;
+/// Longer pauses between frames (idle, screen locked) are cut down to this
+const MAX_GAP_SECS: f64 = 10.0;
+const CAPTION_FILTER: &str =
+    "fontsize=32:fontcolor=white:box=1:boxcolor=black@0.6:boxborderw=10:x=20:y=h-th-20";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TimelapseStatus {
+    Running,
+    Done,
+    Failed,
+}
+
+impl TimelapseStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TimelapseStatus::Running => "running",
+            TimelapseStatus::Done => "done",
+            TimelapseStatus::Failed => "failed",
+        }
+    }
+}
+
+impl FromStr for TimelapseStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "running" => Ok(TimelapseStatus::Running),
+            "done" => Ok(TimelapseStatus::Done),
+            "failed" => Ok(TimelapseStatus::Failed),
+            _ => anyhow::bail!("unknown timelapse status: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelapseJob {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    pub speed: f64,
+    pub status: TimelapseStatus,
+    pub output_path: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+fn default_speed() -> f64 {
+    DEFAULT_TIMELAPSE_SPEED
+}
+
+fn default_fps() -> f64 {
+    DEFAULT_TIMELAPSE_FPS
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimelapseOptions {
+    pub start_time: DateTime<Utc>,
+    pub end_time: DateTime<Utc>,
+    /// Recorded seconds per second of timelapse
+    #[serde(default = "default_speed")]
+    pub speed: f64,
+    #[serde(default = "default_fps")]
+    pub fps: f64,
+    /// Caption each frame with the focused app
+    #[serde(default)]
+    pub app_names: bool,
+    /// Caption each frame with the local time it was recorded at
+    #[serde(default)]
+    pub clock: bool,
+}
+
+impl TimelapseOptions {
+    pub fn validate(&self) -> Result<()> {
+        if self.end_time <= self.start_time {
+            anyhow::bail!("end_time must be after start_time");
+        }
+        if !self.speed.is_finite() || self.speed <= 0.0 {
+            anyhow::bail!("speed must be positive");
+        }
+        if !self.fps.is_finite() || self.fps <= 0.0 || self.fps > MAX_TIMELAPSE_FPS {
+            anyhow::bail!("fps must be between 0 and {}", MAX_TIMELAPSE_FPS);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TimelapseSourceFrame {
+    pub timestamp: DateTime<Utc>,
+    pub file_path: String,
+    pub offset_index: i64,
+    /// Empty if no focused window was recognized
+    pub app_name: String,
+}
+
+/// Index of the recorded frame shown at each output frame. Pauses longer than
+/// `MAX_GAP_SECS` are shortened so idle time doesn't turn into a still picture.
+pub fn plan_timelapse(timestamps: &[DateTime<Utc>], speed: f64, fps: f64) -> Vec<usize> {
+    if timestamps.is_empty() {
+        return Vec::new();
+    }
+
+    let mut elapsed = Vec::with_capacity(timestamps.len());
+    let mut total = 0.0;
+    elapsed.push(total);
+    for pair in timestamps.windows(2) {
+        let gap = (pair[1] - pair[0]).num_milliseconds() as f64 / 1000.0;
+        total += gap.clamp(0.0, MAX_GAP_SECS);
+        elapsed.push(total);
+    }
+
+    let output_frames = ((total / speed * fps).ceil() as usize).max(1);
+    let mut plan = Vec::with_capacity(output_frames);
+    let mut source = 0;
+    for frame in 0..output_frames {
+        let recorded = frame as f64 / fps * speed;
+        while source + 1 < elapsed.len() && elapsed[source + 1] <= recorded {
+            source += 1;
+        }
+        plan.push(source);
+    }
+    plan
+}
+
+fn caption(frame: &TimelapseSourceFrame, app_names: bool, clock: bool) -> String {
+    let mut parts = Vec::new();
+    if app_names && !frame.app_name.is_empty() {
+        parts.push(frame.app_name.clone());
+    }
+    if clock {
+        parts.push(
+            frame
+                .timestamp
+                .with_timezone(&Local)
+                .format("%H:%M")
+                .to_string(),
+        );
+    }
+    if parts.is_empty() {
+        // drawtext refuses an empty text
+        return " ".to_string();
+    }
+    parts.join(" - ")
+}
+
+/// Escapes text for a drawtext option inside a quoted sendcmd argument
+fn escape_drawtext(text: &str) -> String {
+    text.chars()
+        .filter(|c| !matches!(c, '\'' | '\n' | '\r'))
+        .flat_map(|c| match c {
+            '\\' | ':' | ';' | ',' | '[' | ']' | '=' => vec!['\\', c],
+            c => vec![c],
+        })
+        .collect()
+}
+
+/// sendcmd script switching the drawtext caption whenever it changes
+pub fn caption_script(captions: &[String], fps: f64) -> String {
+    let mut script = String::new();
+    let mut previous: Option<&String> = None;
+    for (frame, caption) in captions.iter().enumerate() {
+        if previous == Some(caption) {
+            continue;
+        }
+        script.push_str(&format!(
+            "{:.3} drawtext reinit 'text={}';\n",
+            frame as f64 / fps,
+            escape_drawtext(caption)
+        ));
+        previous = Some(caption);
+    }
+    script
+}
+
+/// Escapes a path used as a filter option value
+fn escape_filter_path(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "/")
+        .replace(':', "\\:")
+        .replace('\'', "\\'")
+}
+
+/// Decodes all frames of a chunk as `{offset:06}.png` into `dir`
+pub(crate) async fn decode_chunk(file_path: &str, dir: &Path) -> Result<()> {
+    // frames of native chunks are read one by one
+    if is_native_chunk(file_path) {
+        return Ok(());
+    }
+
+    let _ = tokio::fs::remove_dir_all(dir).await;
+    tokio::fs::create_dir_all(dir).await?;
+
+    let media = open_media(file_path).await?;
+    let output = FfmpegCommand::new()
+        .overwrite()
+        .input(FfmpegInput::file(media.path()))
+        .output(
+            FfmpegOutput::file(&dir.join("%06d.png").to_string_lossy())
+                .arg("-fps_mode", "passthrough")
+                .arg("-start_number", "0"),
+        )
+        .run()
+        .await?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "failed to decode {}: {}",
+            file_path,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+/// Frame of a chunk decoded by `decode_chunk` into `chunk_dir`
+pub(crate) async fn read_frame_png(
+    file_path: &str,
+    offset_index: i64,
+    chunk_dir: &Path,
+) -> Result<Vec<u8>> {
+    if is_native_chunk(file_path) {
+        let image = read_native_frame(file_path, offset_index).await?;
+        let mut buffer = Vec::new();
+        image.write_to(
+            &mut std::io::Cursor::new(&mut buffer),
+            image::ImageFormat::Png,
+        )?;
+        return Ok(buffer);
+    }
+
+    Ok(tokio::fs::read(chunk_dir.join(format!("{:06}.png", offset_index))).await?)
+}
+
+/// Renders the recorded frames between the two times into an mp4 at `output`
+pub async fn render_timelapse(
+    db: &DatabaseManager,
+    options: &TimelapseOptions,
+    output: &Path,
+) -> Result<()> {
+    options.validate()?;
+    let frames = db
+        .get_timelapse_frames(options.start_time, options.end_time)
+        .await?;
+    if frames.is_empty() {
+        anyhow::bail!(
+            "no frames recorded between {} and {}",
+            options.start_time,
+            options.end_time
+        );
+    }
+
+    let timestamps: Vec<DateTime<Utc>> = frames.iter().map(|f| f.timestamp).collect();
+    let plan = plan_timelapse(&timestamps, options.speed, options.fps);
+    info!(
+        "Rendering timelapse of {} frames into {} frames at {}x",
+        frames.len(),
+        plan.len(),
+        options.speed
+    );
+
+    let work_dir = output.with_extension("work");
+    tokio::fs::create_dir_all(&work_dir).await?;
+    let result = encode_timelapse(&frames, &plan, options, output, &work_dir).await;
+    let _ = tokio::fs::remove_dir_all(&work_dir).await;
+    result
+}
+
+async fn encode_timelapse(
+    frames: &[TimelapseSourceFrame],
+    plan: &[usize],
+    options: &TimelapseOptions,
+    output: &Path,
+    work_dir: &Path,
+) -> Result<()> {
+    // h264 needs even dimensions
+    let mut filter = "scale=trunc(iw/2)*2:trunc(ih/2)*2".to_string();
+    if options.app_names || options.clock {
+        let captions: Vec<String> = plan
+            .iter()
+            .map(|source| caption(&frames[*source], options.app_names, options.clock))
+            .collect();
+        let script_path = work_dir.join("captions.txt");
+        tokio::fs::write(&script_path, caption_script(&captions, options.fps)).await?;
+        filter.push_str(&format!(
+            ",sendcmd=f='{}',drawtext=text='{}':expansion=none:{}",
+            escape_filter_path(&script_path),
+            escape_drawtext(&captions[0]),
+            CAPTION_FILTER
+        ));
+    }
+
+    let command = FfmpegCommand::new().overwrite().input(
+        FfmpegInput::pipe()
+            .format(Container::Image2Pipe)
+            .video_codec(VideoCodec::Png)
+            .frame_rate(options.fps),
+    );
+    let mut process = VideoEncoder::Software
+        .encode_to(
+            command,
+            FfmpegOutput::file(&output.to_string_lossy()).video_filter(&filter),
+            EncoderQuality::High,
+        )
+        .spawn("timelapse")?;
+
+    let chunk_dir = work_dir.join("chunk");
+    let mut decoded_chunk: Option<&str> = None;
+    let mut last: Option<(usize, Vec<u8>)> = None;
+    for source in plan {
+        let frame = &frames[*source];
+        if last.as_ref().map(|(index, _)| index) != Some(source) {
+            if decoded_chunk != Some(frame.file_path.as_str()) {
+                if let Err(e) = decode_chunk(&frame.file_path, &chunk_dir).await {
+                    error!("{}", e);
+                }
+                decoded_chunk = Some(frame.file_path.as_str());
+            }
+            match read_frame_png(&frame.file_path, frame.offset_index, &chunk_dir).await {
+                Ok(png) => last = Some((*source, png)),
+                // chunk written partially, e.g. ffmpeg crashed, repeat the previous frame
+                Err(e) => error!(
+                    "Frame {} of {} is missing: {}",
+                    frame.offset_index, frame.file_path, e
+                ),
+            }
+        }
+        if let Some((_, png)) = &last {
+            process.write(png).await?;
+        }
+    }
+
+    let status = process.finish().await?;
+    if !status.success() {
+        anyhow::bail!("ffmpeg failed to render the timelapse ({})", status);
+    }
+    Ok(())
+}
+
+/// Runs timelapse jobs in the background, their progress is kept in the database
+pub struct TimelapseRenderer {
+    db: Arc<DatabaseManager>,
+    output_dir: PathBuf,
+}
+
+impl TimelapseRenderer {
+    pub fn new(db: Arc<DatabaseManager>, output_dir: PathBuf) -> Self {
+        TimelapseRenderer { db, output_dir }
+    }
+
+    pub async fn start(&self, options: TimelapseOptions) -> Result<TimelapseJob> {
+        options.validate()?;
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        let output = self.output_dir.join(format!(
+            "timelapse_{}_{}.mp4",
+            options.start_time.format("%Y-%m-%d_%H-%M-%S"),
+            Utc::now().timestamp_millis()
+        ));
+
+        let id = self
+            .db
+            .insert_timelapse_job(
+                options.start_time,
+                options.end_time,
+                options.speed,
+                &output.to_string_lossy(),
+            )
+            .await?;
+
+        let db = Arc::clone(&self.db);
+        tokio::spawn(async move {
+            let (status, error) = match render_timelapse(&db, &options, &output).await {
+                Ok(()) => {
+                    info!("Timelapse {} rendered to {}", id, output.display());
+                    (TimelapseStatus::Done, None)
+                }
+                Err(e) => {
+                    error!("Timelapse {} failed: {}", id, e);
+                    (TimelapseStatus::Failed, Some(e.to_string()))
+                }
+            };
+            if let Err(e) = db.finish_timelapse_job(id, status, error.as_deref()).await {
+                error!("Failed to update timelapse job {}: {}", id, e);
+            }
+        });
+
+        self.db
+            .get_timelapse_job(id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("timelapse job {} not found", id))
+    }
+
+    pub async fn get_job(&self, id: i64) -> Result<Option<TimelapseJob>> {
+        Ok(self.db.get_timelapse_job(id).await?)
+    }
+}
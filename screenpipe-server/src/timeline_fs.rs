@@ -0,0 +1,501 @@
+use crate::video::extract_frame;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, Utc};
+use image::ImageFormat;
+use screenpipe_integrations::vault_export::local_day_range;
+use std::collections::HashMap;
+use std::io::Cursor;
+
+// inodes carry what they point to, so nothing has to be remembered between calls
+const KIND_SHIFT: u32 = 56;
+const YEAR: u64 = 2;
+const MONTH: u64 = 3;
+const DAY: u64 = 4;
+const FRAME: u64 = 5;
+const TRANSCRIPT: u64 = 6;
+
+/// A directory or file of the mounted timeline: /<year>/<month>/<day>/<time>_<app>.png,
+/// with the transcriptions of the day next to the frames as <time>_transcript.txt
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TimelineNode {
+    Root,
+    Year(i32),
+    Month(i32, u32),
+    Day(NaiveDate),
+    Frame(i64),
+    Transcript(i64),
+}
+
+impl TimelineNode {
+    pub fn inode(&self) -> u64 {
+        let tagged = |kind: u64, value: u64| (kind << KIND_SHIFT) | value;
+        match *self {
+            // the root inode is fixed by FUSE
+            TimelineNode::Root => 1,
+            TimelineNode::Year(year) => tagged(YEAR, year as u64),
+            TimelineNode::Month(year, month) => tagged(MONTH, year as u64 * 100 + month as u64),
+            TimelineNode::Day(date) => tagged(
+                DAY,
+                date.year() as u64 * 10_000 + date.month() as u64 * 100 + date.day() as u64,
+            ),
+            TimelineNode::Frame(id) => tagged(FRAME, id as u64),
+            TimelineNode::Transcript(id) => tagged(TRANSCRIPT, id as u64),
+        }
+    }
+
+    pub fn from_inode(inode: u64) -> Option<Self> {
+        if inode == 1 {
+            return Some(TimelineNode::Root);
+        }
+        let value = inode & ((1 << KIND_SHIFT) - 1);
+        match inode >> KIND_SHIFT {
+            YEAR => Some(TimelineNode::Year(value as i32)),
+            MONTH => Some(TimelineNode::Month(
+                (value / 100) as i32,
+                (value % 100) as u32,
+            )),
+            DAY => NaiveDate::from_ymd_opt(
+                (value / 10_000) as i32,
+                (value / 100 % 100) as u32,
+                (value % 100) as u32,
+            )
+            .map(TimelineNode::Day),
+            FRAME => Some(TimelineNode::Frame(value as i64)),
+            TRANSCRIPT => Some(TimelineNode::Transcript(value as i64)),
+            _ => None,
+        }
+    }
+
+    pub fn is_dir(&self) -> bool {
+        !matches!(self, TimelineNode::Frame(_) | TimelineNode::Transcript(_))
+    }
+
+    /// The directory of a directory, files can be in several days so they have none
+    pub fn parent(&self) -> Option<Self> {
+        match *self {
+            TimelineNode::Root | TimelineNode::Year(_) => Some(TimelineNode::Root),
+            TimelineNode::Month(year, _) => Some(TimelineNode::Year(year)),
+            TimelineNode::Day(date) => Some(TimelineNode::Month(date.year(), date.month())),
+            TimelineNode::Frame(_) | TimelineNode::Transcript(_) => None,
+        }
+    }
+}
+
+/// A file of a day directory
+#[derive(Debug, Clone, PartialEq)]
+pub struct TimelineFile {
+    pub name: String,
+    pub node: TimelineNode,
+    pub timestamp: DateTime<Utc>,
+    /// Known for transcriptions, frames are only encoded when opened
+    pub size: Option<u64>,
+}
+
+/// Lowercase letters and digits of the app name, `screen` when no app was focused
+pub fn file_app_name(app_name: &str) -> String {
+    let name: String = app_name
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let name = name
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if name.is_empty() {
+        "screen".to_string()
+    } else {
+        name
+    }
+}
+
+/// The frames and transcriptions of a local day in time order, frames of the same second
+/// get _2, _3... so every name is unique
+pub async fn day_files(db: &DatabaseManager, date: NaiveDate) -> Result<Vec<TimelineFile>> {
+    let (start, end) = local_day_range(date)?;
+    let mut files: Vec<(DateTime<Utc>, String, TimelineNode, Option<u64>)> = db
+        .get_timeline_frames(start, end)
+        .await?
+        .into_iter()
+        .map(|(id, timestamp, app_name)| {
+            let stem = format!(
+                "{}_{}",
+                timestamp.with_timezone(&Local).format("%H:%M:%S"),
+                file_app_name(&app_name)
+            );
+            (timestamp, stem, TimelineNode::Frame(id), None)
+        })
+        .collect();
+    files.extend(
+        db.get_timeline_transcriptions(start, end)
+            .await?
+            .into_iter()
+            .map(|(id, timestamp, transcription)| {
+                let stem = format!(
+                    "{}_transcript",
+                    timestamp.with_timezone(&Local).format("%H:%M:%S")
+                );
+                let size = Some(transcription.len() as u64);
+                (timestamp, stem, TimelineNode::Transcript(id), size)
+            }),
+    );
+    files.sort_by_key(|(timestamp, _, _, _)| *timestamp);
+
+    let mut seen: HashMap<String, usize> = HashMap::new();
+    Ok(files
+        .into_iter()
+        .map(|(timestamp, stem, node, size)| {
+            let extension = match node {
+                TimelineNode::Transcript(_) => "txt",
+                _ => "png",
+            };
+            let count = seen.entry(stem.clone()).or_default();
+            *count += 1;
+            let name = match *count {
+                1 => format!("{}.{}", stem, extension),
+                n => format!("{}_{}.{}", stem, n, extension),
+            };
+            TimelineFile {
+                name,
+                node,
+                timestamp,
+                size,
+            }
+        })
+        .collect())
+}
+
+/// Entries of a directory as (name, node), the days are listed from the database
+pub async fn list_dir(
+    db: &DatabaseManager,
+    node: TimelineNode,
+    days: &[NaiveDate],
+) -> Result<Vec<(String, TimelineNode)>> {
+    let mut entries: Vec<(String, TimelineNode)> = match node {
+        TimelineNode::Root => days
+            .iter()
+            .map(|day| (day.year().to_string(), TimelineNode::Year(day.year())))
+            .collect(),
+        TimelineNode::Year(year) => days
+            .iter()
+            .filter(|day| day.year() == year)
+            .map(|day| {
+                (
+                    format!("{:02}", day.month()),
+                    TimelineNode::Month(year, day.month()),
+                )
+            })
+            .collect(),
+        TimelineNode::Month(year, month) => days
+            .iter()
+            .filter(|day| day.year() == year && day.month() == month)
+            .map(|day| (format!("{:02}", day.day()), TimelineNode::Day(*day)))
+            .collect(),
+        TimelineNode::Day(date) => day_files(db, date)
+            .await?
+            .into_iter()
+            .map(|file| (file.name, file.node))
+            .collect(),
+        TimelineNode::Frame(_) | TimelineNode::Transcript(_) => {
+            return Err(anyhow!("not a directory"))
+        }
+    };
+    entries.dedup_by(|a, b| a.0 == b.0);
+    Ok(entries)
+}
+
+/// What reading the file gives: the frame as png or the transcription as text
+pub async fn read_file(db: &DatabaseManager, node: TimelineNode) -> Result<Vec<u8>> {
+    match node {
+        TimelineNode::Frame(id) => {
+            let (file_path, offset_index) = db
+                .get_frame(id)
+                .await?
+                .ok_or_else(|| anyhow!("frame {} not found", id))?;
+            let image = extract_frame(&file_path, offset_index).await?;
+            let mut png = Vec::new();
+            image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+            Ok(png)
+        }
+        TimelineNode::Transcript(id) => db
+            .get_transcription(id)
+            .await?
+            .map(|text| text.into_bytes())
+            .ok_or_else(|| anyhow!("transcription {} not found", id)),
+        _ => Err(anyhow!("not a file")),
+    }
+}
+
+#[cfg(all(unix, feature = "timeline-fs"))]
+pub use fuse::mount_timeline;
+
+#[cfg(all(unix, feature = "timeline-fs"))]
+mod fuse {
+    use super::{day_files, list_dir, read_file, TimelineFile, TimelineNode};
+    use crate::DatabaseManager;
+    use anyhow::Result;
+    use chrono::{DateTime, Local, NaiveDate};
+    use fuser::{
+        consts::FOPEN_DIRECT_IO, BackgroundSession, FileAttr, FileType, Filesystem, MountOption,
+        ReplyAttr, ReplyData, ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, Request,
+    };
+    use log::{debug, info, warn};
+    use std::collections::HashMap;
+    use std::ffi::OsStr;
+    use std::path::Path;
+    use std::sync::Arc;
+    use std::time::{Duration, SystemTime};
+    use tokio::runtime::Handle;
+
+    /// How long the kernel and we keep listings, today keeps getting new frames
+    const TTL: Duration = Duration::from_secs(10);
+    /// Past this many directories listed, the kernel looks their files up again
+    const MAX_LISTINGS: usize = 16;
+
+    struct Listing {
+        listed_at: DateTime<Local>,
+        entries: Vec<(String, TimelineNode)>,
+        by_name: HashMap<String, TimelineNode>,
+    }
+
+    impl Listing {
+        fn new(entries: Vec<(String, TimelineNode)>) -> Self {
+            Listing {
+                listed_at: Local::now(),
+                by_name: entries.iter().cloned().collect(),
+                entries,
+            }
+        }
+
+        /// Days that ended before they were listed don't change anymore
+        fn is_fresh(&self, node: TimelineNode) -> bool {
+            let ended = matches!(node, TimelineNode::Day(date) if date < self.listed_at.date_naive());
+            ended || (Local::now() - self.listed_at).to_std().unwrap_or_default() < TTL
+        }
+    }
+
+    struct TimelineFs {
+        db: Arc<DatabaseManager>,
+        runtime: Handle,
+        uid: u32,
+        gid: u32,
+        days: Vec<NaiveDate>,
+        listings: HashMap<TimelineNode, Arc<Listing>>,
+        /// Files of the days listed, by inode
+        files: HashMap<u64, TimelineFile>,
+        /// Contents of the open files, by handle
+        open: HashMap<u64, Vec<u8>>,
+        next_handle: u64,
+    }
+
+    impl TimelineFs {
+        fn listing(&mut self, node: TimelineNode) -> Result<Arc<Listing>> {
+            if let Some(listing) = self.listings.get(&node).filter(|l| l.is_fresh(node)) {
+                return Ok(listing.clone());
+            }
+            let entries = match node {
+                TimelineNode::Day(date) => {
+                    let files = self.runtime.block_on(day_files(&self.db, date))?;
+                    let entries = files
+                        .iter()
+                        .map(|file| (file.name.clone(), file.node))
+                        .collect();
+                    self.files
+                        .extend(files.into_iter().map(|file| (file.node.inode(), file)));
+                    entries
+                }
+                _ => {
+                    self.days = self.runtime.block_on(self.db.get_timeline_days())?;
+                    self.runtime.block_on(list_dir(&self.db, node, &self.days))?
+                }
+            };
+            if self.listings.len() >= MAX_LISTINGS {
+                self.listings.clear();
+                self.files.clear();
+            }
+            let listing = Arc::new(Listing::new(entries));
+            self.listings.insert(node, listing.clone());
+            Ok(listing)
+        }
+
+        fn attr(&self, node: TimelineNode) -> FileAttr {
+            let file = self.files.get(&node.inode());
+            let time = file.map_or(SystemTime::UNIX_EPOCH, |file| file.timestamp.into());
+            let (kind, perm, size) = if node.is_dir() {
+                (FileType::Directory, 0o555, 0)
+            } else {
+                (
+                    FileType::RegularFile,
+                    0o444,
+                    file.and_then(|file| file.size).unwrap_or(0),
+                )
+            };
+            FileAttr {
+                ino: node.inode(),
+                size,
+                blocks: size.div_ceil(512),
+                atime: time,
+                mtime: time,
+                ctime: time,
+                crtime: time,
+                kind,
+                perm,
+                nlink: if node.is_dir() { 2 } else { 1 },
+                uid: self.uid,
+                gid: self.gid,
+                rdev: 0,
+                blksize: 512,
+                flags: 0,
+            }
+        }
+    }
+
+    impl Filesystem for TimelineFs {
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            let Some(parent) = TimelineNode::from_inode(parent).filter(TimelineNode::is_dir) else {
+                return reply.error(libc::ENOENT);
+            };
+            match self.listing(parent) {
+                Ok(listing) => match name.to_str().and_then(|name| listing.by_name.get(name)) {
+                    Some(node) => reply.entry(&TTL, &self.attr(*node), 0),
+                    None => reply.error(libc::ENOENT),
+                },
+                Err(e) => {
+                    debug!("Failed to list the timeline: {}", e);
+                    reply.error(libc::EIO)
+                }
+            }
+        }
+
+        fn getattr(&mut self, _req: &Request<'_>, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+            match TimelineNode::from_inode(ino) {
+                Some(node) => reply.attr(&TTL, &self.attr(node)),
+                None => reply.error(libc::ENOENT),
+            }
+        }
+
+        fn readdir(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            mut reply: ReplyDirectory,
+        ) {
+            let Some(node) = TimelineNode::from_inode(ino).filter(TimelineNode::is_dir) else {
+                return reply.error(libc::ENOTDIR);
+            };
+            let listing = match self.listing(node) {
+                Ok(listing) => listing,
+                Err(e) => {
+                    warn!("Failed to list the timeline: {}", e);
+                    return reply.error(libc::EIO);
+                }
+            };
+            let parent = node.parent().unwrap_or(TimelineNode::Root);
+            let entries = [(".", node), ("..", parent)].into_iter().chain(
+                listing
+                    .entries
+                    .iter()
+                    .map(|(name, child)| (name.as_str(), *child)),
+            );
+            for (i, (name, child)) in entries.enumerate().skip(offset as usize) {
+                let kind = if child.is_dir() {
+                    FileType::Directory
+                } else {
+                    FileType::RegularFile
+                };
+                // the offset is the one of the next entry
+                if reply.add(child.inode(), (i + 1) as i64, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        }
+
+        fn open(&mut self, _req: &Request<'_>, ino: u64, _flags: i32, reply: ReplyOpen) {
+            let Some(node) = TimelineNode::from_inode(ino).filter(|node| !node.is_dir()) else {
+                return reply.error(libc::EISDIR);
+            };
+            match self.runtime.block_on(read_file(&self.db, node)) {
+                Ok(data) => {
+                    if let Some(file) = self.files.get_mut(&ino) {
+                        file.size = Some(data.len() as u64);
+                    }
+                    let handle = self.next_handle;
+                    self.next_handle += 1;
+                    self.open.insert(handle, data);
+                    // frames have no size until encoded, reads must not stop at the 0 of stat
+                    reply.opened(handle, FOPEN_DIRECT_IO);
+                }
+                Err(e) => {
+                    warn!("Failed to read {:?} of the timeline: {}", node, e);
+                    reply.error(libc::EIO)
+                }
+            }
+        }
+
+        fn read(
+            &mut self,
+            _req: &Request<'_>,
+            _ino: u64,
+            fh: u64,
+            offset: i64,
+            size: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyData,
+        ) {
+            let Some(data) = self.open.get(&fh) else {
+                return reply.error(libc::EBADF);
+            };
+            let start = (offset.max(0) as usize).min(data.len());
+            let end = (start + size as usize).min(data.len());
+            reply.data(&data[start..end]);
+        }
+
+        fn release(
+            &mut self,
+            _req: &Request<'_>,
+            _ino: u64,
+            fh: u64,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            _flush: bool,
+            reply: ReplyEmpty,
+        ) {
+            self.open.remove(&fh);
+            reply.ok();
+        }
+    }
+
+    /// Mounts the timeline read-only on the directory, it's unmounted when the session is dropped.
+    /// Must be called from the tokio runtime, the database is read on it
+    pub fn mount_timeline(db: Arc<DatabaseManager>, mountpoint: &Path) -> Result<BackgroundSession> {
+        let fs = TimelineFs {
+            db,
+            runtime: Handle::current(),
+            // SAFETY: getuid and getgid always succeed
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            days: Vec::new(),
+            listings: HashMap::new(),
+            files: HashMap::new(),
+            open: HashMap::new(),
+            next_handle: 1,
+        };
+        let session = fuser::spawn_mount2(
+            fs,
+            mountpoint,
+            &[
+                MountOption::RO,
+                MountOption::NoExec,
+                MountOption::FSName("screenpipe".to_string()),
+            ],
+        )?;
+        info!("Timeline mounted on {}", mountpoint.display());
+        Ok(session)
+    }
+}
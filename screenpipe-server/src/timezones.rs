@@ -0,0 +1,96 @@
+//! The timezone the system was in over time, so the history shows each moment at the local
+//! time it happened at, not in the zone of wherever the user is now.
+use crate::DatabaseManager;
+use chrono::{DateTime, FixedOffset, Utc};
+use log::{info, warn};
+use screenpipe_core::emit_event;
+use screenpipe_integrations::timezone::{current_timezone, utc_offset_minutes, Tz};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the system's timezone is looked at, a flight changes it once
+pub const TIMEZONE_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A stretch of time spent in one timezone, at one offset: the change to or from daylight
+/// saving time starts another
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TimezoneSegment {
+    pub id: i64,
+    pub start_time: DateTime<Utc>,
+    /// None for the current one
+    pub end_time: Option<DateTime<Utc>>,
+    /// IANA name, like "Europe/Berlin"
+    pub timezone: String,
+    pub utc_offset_minutes: i32,
+}
+
+impl TimezoneSegment {
+    pub fn contains(&self, timestamp: DateTime<Utc>) -> bool {
+        self.start_time <= timestamp && self.end_time.is_none_or(|end| timestamp < end)
+    }
+
+    pub fn offset(&self) -> Option<FixedOffset> {
+        FixedOffset::east_opt(self.utc_offset_minutes * 60)
+    }
+}
+
+/// `timestamp` at the local time of the segment it falls in, None before the first one
+pub fn local_timestamp(
+    segments: &[TimezoneSegment],
+    timestamp: DateTime<Utc>,
+) -> Option<DateTime<FixedOffset>> {
+    let segment = segments
+        .iter()
+        .find(|segment| segment.contains(timestamp))?;
+    Some(timestamp.with_timezone(&segment.offset()?))
+}
+
+/// Starts a segment when the zone or its offset changed since the last one. Returns whether
+/// it did
+pub async fn record_timezone(
+    db: &DatabaseManager,
+    zone: &Tz,
+    now: DateTime<Utc>,
+) -> Result<bool, sqlx::Error> {
+    let timezone = zone.name();
+    let offset = utc_offset_minutes(zone, now);
+    let previous = db.get_latest_timezone_segment().await?;
+    if previous
+        .as_ref()
+        .is_some_and(|segment| segment.timezone == timezone && segment.utc_offset_minutes == offset)
+    {
+        return Ok(false);
+    }
+    db.insert_timezone_change(now, timezone, offset).await?;
+    info!(
+        "Timezone is now {} (UTC{:+})",
+        timezone,
+        offset as f64 / 60.0
+    );
+    emit_event(
+        "timezone.changed",
+        json!({
+            "timezone": timezone,
+            "utc_offset_minutes": offset,
+            "previous": previous.map(|segment| segment.timezone),
+        }),
+    );
+    Ok(true)
+}
+
+/// Records the timezone at start and each time it changes
+pub async fn start_timezone_tracker(db: Arc<DatabaseManager>, interval: Duration) {
+    loop {
+        match current_timezone() {
+            Some(zone) => {
+                if let Err(e) = record_timezone(&db, &zone, Utc::now()).await {
+                    warn!("Failed to record the timezone: {}", e);
+                }
+            }
+            None => warn!("The system's timezone isn't a known IANA zone, it isn't recorded"),
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
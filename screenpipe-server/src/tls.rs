@@ -0,0 +1,159 @@
+use crate::local_api::serve_connection;
+use crate::user_isolation::write_private_file;
+use anyhow::{Context, Result};
+use axum::Router;
+use chrono::{Datelike, Utc};
+use log::{debug, info, warn};
+use rcgen::{
+    date_time_ymd, BasicConstraints, CertificateParams, CidrSubnet, DnType,
+    ExtendedKeyUsagePurpose, GeneralSubtree, IsCa, KeyPair, KeyUsagePurpose, NameConstraints,
+};
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::{self, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+
+/// macOS and iOS refuse server certificates valid for longer, even from trusted authorities
+const CERTIFICATE_VALIDITY_DAYS: i64 = 825;
+/// The certificate is made again this long before it expires, the authority has to be trusted
+/// again then
+const RENEW_BEFORE_DAYS: i64 = 30;
+const CA_FILE: &str = "ca.pem";
+const CERT_FILE: &str = "cert.pem";
+const KEY_FILE: &str = "key.pem";
+
+/// The certificate generated for localhost and the authority that signed it, the one to trust
+/// in the browser or the OS
+#[derive(Debug, Clone)]
+pub struct LocalCertificate {
+    pub ca: PathBuf,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+/// The certificate in `dir`, generated the first time and when it's about to expire. The key of
+/// the authority is thrown away once the certificate is signed, and its name constraints keep it
+/// to localhost anyway, so trusting it can't let anything else pass as another site
+pub fn local_certificate(dir: &Path) -> Result<LocalCertificate> {
+    let certificate = LocalCertificate {
+        ca: dir.join(CA_FILE),
+        cert: dir.join(CERT_FILE),
+        key: dir.join(KEY_FILE),
+    };
+    let renew_after = Duration::from_secs(
+        ((CERTIFICATE_VALIDITY_DAYS - RENEW_BEFORE_DAYS) * 24 * 60 * 60) as u64,
+    );
+    let is_valid = [&certificate.ca, &certificate.cert, &certificate.key]
+        .iter()
+        .all(|path| path.exists())
+        && std::fs::metadata(&certificate.cert)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .is_some_and(|age| age < renew_after);
+    if is_valid {
+        return Ok(certificate);
+    }
+
+    std::fs::create_dir_all(dir)?;
+    let (ca, cert, key) = generate_certificate()?;
+    std::fs::write(&certificate.ca, ca)?;
+    std::fs::write(&certificate.cert, cert)?;
+    write_private_file(&certificate.key, key.as_bytes())?;
+    info!("Generated a certificate for localhost in {}", dir.display());
+    Ok(certificate)
+}
+
+/// The pem of an authority for localhost, and of the certificate it signed and its key
+fn generate_certificate() -> Result<(String, String, String)> {
+    let now = Utc::now();
+    let expires = now + chrono::Duration::days(CERTIFICATE_VALIDITY_DAYS);
+    let not_before = date_time_ymd(now.year(), now.month() as u8, now.day() as u8);
+    let not_after = date_time_ymd(expires.year(), expires.month() as u8, expires.day() as u8);
+
+    let mut ca_params = CertificateParams::new(Vec::<String>::new())?;
+    ca_params
+        .distinguished_name
+        .push(DnType::CommonName, "screenpipe local authority");
+    ca_params.is_ca = IsCa::Ca(BasicConstraints::Constrained(0));
+    ca_params.key_usages = vec![KeyUsagePurpose::KeyCertSign, KeyUsagePurpose::CrlSign];
+    ca_params.name_constraints = Some(NameConstraints {
+        permitted_subtrees: vec![
+            GeneralSubtree::DnsName("localhost".to_string()),
+            GeneralSubtree::IpAddress(CidrSubnet::from_v4_prefix([127, 0, 0, 0], 8)),
+            GeneralSubtree::IpAddress(CidrSubnet::from_v6_prefix(
+                std::net::Ipv6Addr::LOCALHOST.octets(),
+                128,
+            )),
+        ],
+        excluded_subtrees: Vec::new(),
+    });
+    ca_params.not_before = not_before;
+    ca_params.not_after = not_after;
+    let ca_key = KeyPair::generate()?;
+    let ca = ca_params.self_signed(&ca_key)?;
+
+    let mut params = CertificateParams::new(vec![
+        "localhost".to_string(),
+        "127.0.0.1".to_string(),
+        "::1".to_string(),
+    ])?;
+    params
+        .distinguished_name
+        .push(DnType::CommonName, "localhost");
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+    params.not_before = not_before;
+    params.not_after = not_after;
+    let key = KeyPair::generate()?;
+    let cert = params.signed_by(&key, &ca, &ca_key)?;
+
+    Ok((ca.pem(), cert.pem(), key.serialize_pem()))
+}
+
+/// Reads a pem certificate chain and its key, like the ones of `local_certificate`
+pub fn tls_acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor> {
+    let open = |path: &Path| {
+        std::fs::File::open(path)
+            .map(BufReader::new)
+            .with_context(|| format!("failed to open {}", path.display()))
+    };
+    let certs = rustls_pemfile::certs(&mut open(cert)?)
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .with_context(|| format!("invalid certificate in {}", cert.display()))?;
+    let key = rustls_pemfile::private_key(&mut open(key)?)?
+        .with_context(|| format!("no private key in {}", key.display()))?;
+
+    let mut config =
+        ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()?
+            .with_no_client_auth()
+            .with_single_cert(certs, key)?;
+    config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(config)))
+}
+
+/// Serves the API over https on the listener
+pub async fn serve_tls(listener: TcpListener, app: Router, acceptor: TlsAcceptor) -> Result<()> {
+    loop {
+        let (stream, addr) = match listener.accept().await {
+            Ok(connection) => connection,
+            Err(e) => {
+                // like running out of file descriptors, the next connections may be accepted
+                warn!("Failed to accept a connection: {}", e);
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let app = app.clone();
+        tokio::spawn(async move {
+            match acceptor.accept(stream).await {
+                Ok(stream) => serve_connection(stream, app).await,
+                Err(e) => debug!("TLS handshake with {} failed: {}", addr, e),
+            }
+        });
+    }
+}
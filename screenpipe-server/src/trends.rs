@@ -0,0 +1,462 @@
+//! Week over week trends of how the time is spent, and the measures of the last week far from
+//! the usual. Weeks start on monday, in the local timezone, and are computed once they ended.
+use crate::power::background_jobs_paused;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, TimeZone, Utc, Weekday,
+};
+use log::{error, info};
+use screenpipe_core::emit_event;
+use screenpipe_integrations::vault_export::format_duration;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Weeks before the last one its measures are compared with
+pub const BASELINE_WEEKS: usize = 4;
+/// A measure this many times its usual value, or this many times less, is an anomaly
+pub const ANOMALY_RATIO: f64 = 3.0;
+/// Weeks computed and kept, and the most /analytics/trends returns
+pub const MAX_TREND_WEEKS: u32 = 26;
+pub const DEFAULT_TREND_WEEKS: u32 = 8;
+/// Time for the last sessions of a week to be classified before it is computed
+const WEEK_SETTLE_TIME: ChronoDuration = ChronoDuration::hours(1);
+/// Time outside of these hours, and on weekends, is after hours
+const WORK_START_HOUR: u32 = 9;
+const WORK_END_HOUR: u32 = 18;
+
+/// What the time of a week went to
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WeekMetrics {
+    /// The monday it starts on
+    pub week_start: NaiveDate,
+    pub active_secs: f64,
+    /// Of the activity categories
+    pub category_secs: BTreeMap<String, f64>,
+    pub meetings: u32,
+    pub meeting_secs: f64,
+    /// Apps focused one after the other, recorded with --window-history
+    pub context_switches: u32,
+    /// Per hour active
+    pub switches_per_hour: f64,
+    pub after_hours_secs: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MeasureUnit {
+    Seconds,
+    Count,
+    PerHour,
+}
+
+/// A measure of the last week next to the week before and the usual weeks
+#[derive(Debug, Clone, Serialize)]
+pub struct Trend {
+    pub measure: String,
+    pub unit: MeasureUnit,
+    pub value: f64,
+    pub previous: Option<f64>,
+    /// Relative to the week before, 0.25 for 25% more. None when it had none
+    pub change: Option<f64>,
+    /// Mean of the baseline weeks
+    pub usual: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Anomaly {
+    pub measure: String,
+    pub unit: MeasureUnit,
+    pub value: f64,
+    pub usual: f64,
+    /// Of the value to the usual, None when there is usually none
+    pub ratio: Option<f64>,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WeeklyTrends {
+    /// Oldest first, the weeks nothing was recorded are left out
+    pub weeks: Vec<WeekMetrics>,
+    /// Of the last week
+    pub trends: Vec<Trend>,
+    pub anomalies: Vec<Anomaly>,
+}
+
+struct Measure {
+    name: String,
+    unit: MeasureUnit,
+    value: f64,
+    /// Below this neither the value nor the usual one is worth an anomaly
+    floor: f64,
+}
+
+impl WeekMetrics {
+    fn measures(&self) -> Vec<Measure> {
+        let measure = |name: String, unit, value, floor| Measure {
+            name,
+            unit,
+            value,
+            floor,
+        };
+        let mut measures = vec![measure(
+            "active time".to_string(),
+            MeasureUnit::Seconds,
+            self.active_secs,
+            3600.0,
+        )];
+        measures.extend(self.category_secs.iter().map(|(category, secs)| {
+            measure(format!("{} time", category), MeasureUnit::Seconds, *secs, 3600.0)
+        }));
+        measures.extend([
+            measure(
+                "meetings".to_string(),
+                MeasureUnit::Count,
+                self.meetings as f64,
+                3.0,
+            ),
+            measure(
+                "time in meetings".to_string(),
+                MeasureUnit::Seconds,
+                self.meeting_secs,
+                3600.0,
+            ),
+            measure(
+                "context switches per hour".to_string(),
+                MeasureUnit::PerHour,
+                self.switches_per_hour,
+                5.0,
+            ),
+            measure(
+                "after hours".to_string(),
+                MeasureUnit::Seconds,
+                self.after_hours_secs,
+                3600.0,
+            ),
+        ]);
+        measures
+    }
+
+    fn measure(&self, name: &str) -> f64 {
+        self.measures()
+            .into_iter()
+            .find(|measure| measure.name == name)
+            .map_or(0.0, |measure| measure.value)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.active_secs == 0.0 && self.meetings == 0
+    }
+}
+
+/// The monday of the week of the date
+pub fn week_start(date: NaiveDate) -> NaiveDate {
+    date - ChronoDuration::days(date.weekday().num_days_from_monday() as i64)
+}
+
+fn local_midnight<Z: TimeZone>(date: NaiveDate, zone: &Z) -> Result<DateTime<Utc>> {
+    at_hour(date, 0, zone)
+}
+
+fn at_hour<Z: TimeZone>(date: NaiveDate, hour: u32, zone: &Z) -> Result<DateTime<Utc>> {
+    zone.from_local_datetime(&date.and_hms_opt(hour, 0, 0).unwrap())
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("{} {}:00 doesn't exist in the timezone", date, hour))
+}
+
+fn overlap_secs(
+    (start, end): (DateTime<Utc>, DateTime<Utc>),
+    (from, to): (DateTime<Utc>, DateTime<Utc>),
+) -> f64 {
+    let start = start.max(from);
+    let end = end.min(to);
+    if end <= start {
+        return 0.0;
+    }
+    (end - start).num_milliseconds() as f64 / 1000.0
+}
+
+/// Seconds of the span outside of working hours in the timezone, weekends are after hours
+pub fn after_hours_secs<Z: TimeZone>(start: DateTime<Utc>, end: DateTime<Utc>, zone: &Z) -> f64 {
+    if end <= start {
+        return 0.0;
+    }
+    let mut working_secs = 0.0;
+    let mut date = start.with_timezone(zone).date_naive();
+    let last = end.with_timezone(zone).date_naive();
+    while date <= last {
+        if !matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+            if let (Ok(from), Ok(to)) = (
+                at_hour(date, WORK_START_HOUR, zone),
+                at_hour(date, WORK_END_HOUR, zone),
+            ) {
+                working_secs += overlap_secs((start, end), (from, to));
+            }
+        }
+        date = match date.succ_opt() {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    (end - start).num_milliseconds() as f64 / 1000.0 - working_secs
+}
+
+/// Computes the week starting on the monday, in the timezone
+pub async fn week_metrics<Z: TimeZone>(
+    db: &DatabaseManager,
+    week_start: NaiveDate,
+    zone: &Z,
+) -> Result<WeekMetrics> {
+    let range = (
+        local_midnight(week_start, zone)?,
+        local_midnight(week_start + ChronoDuration::days(7), zone)?,
+    );
+    let mut metrics = WeekMetrics {
+        week_start,
+        ..Default::default()
+    };
+    let sessions = db
+        .get_activity_sessions(None, Some(range.0), Some(range.1), u32::MAX, 0, None)
+        .await?;
+    for session in sessions {
+        let secs = overlap_secs((session.start_time, session.end_time), range);
+        if secs == 0.0 {
+            continue;
+        }
+        metrics.active_secs += secs;
+        *metrics
+            .category_secs
+            .entry(session.category.as_str().to_string())
+            .or_default() += secs;
+        metrics.after_hours_secs += after_hours_secs(
+            session.start_time.max(range.0),
+            session.end_time.min(range.1),
+            zone,
+        );
+    }
+    let meetings = db.get_meeting_summaries_between(range.0, range.1).await?;
+    metrics.meetings = meetings.len() as u32;
+    metrics.meeting_secs = meetings
+        .iter()
+        .map(|meeting| overlap_secs((meeting.start_time, meeting.end_time), range))
+        .sum();
+    metrics.context_switches = db.count_app_switches(range.0, range.1).await? as u32;
+    if metrics.active_secs >= 60.0 {
+        metrics.switches_per_hour = metrics.context_switches as f64 / (metrics.active_secs / 3600.0);
+    }
+    Ok(metrics)
+}
+
+/// Computes the ended weeks of the last `MAX_TREND_WEEKS` that aren't yet, returns the ones
+/// with something recorded. Weeks without anything are computed again next time
+pub async fn update_weekly_metrics(
+    db: &DatabaseManager,
+    now: DateTime<Local>,
+) -> Result<Vec<WeekMetrics>> {
+    let settled = (now - WEEK_SETTLE_TIME).date_naive();
+    // the monday of the last week that ended
+    let last = week_start(settled) - ChronoDuration::weeks(1);
+    let first = last - ChronoDuration::weeks(MAX_TREND_WEEKS as i64 - 1);
+    let stored: Vec<NaiveDate> = db
+        .get_weekly_metrics(first, last)
+        .await?
+        .into_iter()
+        .map(|week| week.week_start)
+        .collect();
+    let mut computed = Vec::new();
+    let mut monday = first;
+    while monday <= last {
+        if !stored.contains(&monday) {
+            let metrics = week_metrics(db, monday, &Local).await?;
+            if !metrics.is_empty() {
+                db.upsert_weekly_metrics(&metrics).await?;
+                computed.push(metrics);
+            }
+        }
+        monday += ChronoDuration::weeks(1);
+    }
+    Ok(computed)
+}
+
+fn format_value(unit: MeasureUnit, value: f64) -> String {
+    match unit {
+        MeasureUnit::Seconds => format_duration(value),
+        MeasureUnit::Count => format!("{}", value.round()),
+        MeasureUnit::PerHour => format!("{:.1}", value),
+    }
+}
+
+fn describe(measure: &Measure, usual: f64) -> String {
+    let value = format_value(measure.unit, measure.value);
+    if usual == 0.0 {
+        return format!("{} {}, usually none", measure.name, value);
+    }
+    if measure.value == 0.0 {
+        return format!(
+            "no {}, usually {}",
+            measure.name,
+            format_value(measure.unit, usual)
+        );
+    }
+    let ratio = measure.value / usual;
+    if ratio >= 1.0 {
+        format!(
+            "{} {}, {:.1}x the usual {}",
+            measure.name,
+            value,
+            ratio,
+            format_value(measure.unit, usual)
+        )
+    } else {
+        format!(
+            "{} {}, {:.1}x less than the usual {}",
+            measure.name,
+            value,
+            1.0 / ratio,
+            format_value(measure.unit, usual)
+        )
+    }
+}
+
+/// The trends of the last of the weeks, oldest first, and its anomalies next to the
+/// `BASELINE_WEEKS` before it. Anomalies need two weeks of baseline
+pub fn compare_weeks(weeks: Vec<WeekMetrics>) -> WeeklyTrends {
+    let Some((last, earlier)) = weeks.split_last() else {
+        return WeeklyTrends::default();
+    };
+    let previous = earlier
+        .last()
+        .filter(|week| week.week_start + ChronoDuration::weeks(1) == last.week_start);
+    let baseline = &earlier[earlier.len().saturating_sub(BASELINE_WEEKS)..];
+    // a category of the weeks before and not of this one went down to nothing
+    let mut last = last.clone();
+    for week in earlier {
+        for category in week.category_secs.keys() {
+            last.category_secs.entry(category.clone()).or_insert(0.0);
+        }
+    }
+
+    let mut trends = Vec::new();
+    let mut anomalies = Vec::new();
+    for measure in last.measures() {
+        let previous = previous.map(|week| week.measure(&measure.name));
+        let usual = (!baseline.is_empty()).then(|| {
+            baseline
+                .iter()
+                .map(|week| week.measure(&measure.name))
+                .sum::<f64>()
+                / baseline.len() as f64
+        });
+        if let Some(usual) = usual.filter(|_| baseline.len() >= 2) {
+            let more = measure.value >= measure.floor && measure.value >= ANOMALY_RATIO * usual;
+            let less = usual >= measure.floor && measure.value * ANOMALY_RATIO <= usual;
+            if more || less {
+                anomalies.push(Anomaly {
+                    measure: measure.name.clone(),
+                    unit: measure.unit,
+                    value: measure.value,
+                    usual,
+                    ratio: (usual > 0.0).then(|| measure.value / usual),
+                    description: describe(&measure, usual),
+                });
+            }
+        }
+        trends.push(Trend {
+            change: previous
+                .filter(|previous| *previous > 0.0)
+                .map(|previous| (measure.value - previous) / previous),
+            measure: measure.name,
+            unit: measure.unit,
+            value: measure.value,
+            previous,
+            usual,
+        });
+    }
+    WeeklyTrends {
+        weeks,
+        trends,
+        anomalies,
+    }
+}
+
+/// The trends of the week before the one starting on `before`, with up to `weeks` weeks
+pub async fn weekly_trends(
+    db: &DatabaseManager,
+    before: NaiveDate,
+    weeks: u32,
+) -> Result<WeeklyTrends> {
+    let weeks = weeks.clamp(1, MAX_TREND_WEEKS);
+    let last = week_start(before) - ChronoDuration::weeks(1);
+    let first = last - ChronoDuration::weeks(weeks.max(BASELINE_WEEKS as u32 + 1) as i64 - 1);
+    let stored = db.get_weekly_metrics(first, last).await?;
+    let mut compared = if stored.last().is_some_and(|week| week.week_start == last) {
+        compare_weeks(stored)
+    } else {
+        // nothing was recorded that week
+        WeeklyTrends {
+            weeks: stored,
+            ..Default::default()
+        }
+    };
+    // the baseline is there even when fewer weeks are asked for
+    let shown = compared.weeks.len().saturating_sub(weeks as usize);
+    compared.weeks.drain(..shown);
+    Ok(compared)
+}
+
+/// Lines of the daily note and digest of a monday about the week before
+pub fn digest_lines(trends: &WeeklyTrends) -> Vec<String> {
+    let mut lines: Vec<String> = trends
+        .anomalies
+        .iter()
+        .map(|anomaly| format!("Unusual: {}", anomaly.description))
+        .collect();
+    for trend in &trends.trends {
+        if trend.value == 0.0 && trend.previous.unwrap_or(0.0) == 0.0 {
+            continue;
+        }
+        let value = format_value(trend.unit, trend.value);
+        lines.push(match trend.change {
+            Some(change) => format!("{}: {} ({:+.0}%)", trend.measure, value, change * 100.0),
+            None => format!("{}: {}", trend.measure, value),
+        });
+    }
+    lines
+}
+
+/// Computes the weeks as they end and emits an `analytics.anomaly` event for each anomaly of
+/// a week just computed
+pub async fn start_trend_analyzer(db: Arc<DatabaseManager>, interval: Duration) {
+    info!("Weekly trend analyzer started");
+    loop {
+        if !background_jobs_paused() {
+            let now = Local::now();
+            let settled = (now - WEEK_SETTLE_TIME).date_naive();
+            match update_weekly_metrics(&db, now).await {
+                Ok(computed) => {
+                    let last = week_start(settled) - ChronoDuration::weeks(1);
+                    if computed.iter().any(|week| week.week_start == last) {
+                        match weekly_trends(&db, settled, DEFAULT_TREND_WEEKS).await {
+                            Ok(trends) => {
+                                for anomaly in trends.anomalies {
+                                    info!("Unusual week of {}: {}", last, anomaly.description);
+                                    emit_event(
+                                        "analytics.anomaly",
+                                        json!({"week_start": last, "anomaly": anomaly}),
+                                    );
+                                }
+                            }
+                            Err(e) => error!("Failed to compare the weeks: {}", e),
+                        }
+                    }
+                }
+                Err(e) => error!("Failed to compute the weekly metrics: {}", e),
+            }
+        }
+        tokio::time::sleep(interval).await;
+    }
+}
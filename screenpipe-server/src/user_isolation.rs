@@ -0,0 +1,183 @@
+use crate::credentials::tokens_match;
+use anyhow::{Context, Result};
+use axum::extract::{Request, State};
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use sysinfo::{PidExt, System, SystemExt};
+
+/// Header the clients of a multi-user server put the user's token in
+pub const USER_TOKEN_HEADER: &str = "x-screenpipe-user-token";
+/// Query parameter for what can't send headers, like video players
+pub const USER_TOKEN_PARAM: &str = "user_token";
+const TOKEN_FILE: &str = "user_token";
+/// The port, pid and user of the running server, for the clients of the user to find it
+pub const INSTANCE_FILE: &str = "instance.json";
+/// Ports tried after the one asked for, when the servers of other users have them
+const PORT_ATTEMPTS: u16 = 100;
+
+/// The server of one OS user on a shared machine. It only answers requests with the user's
+/// token, which is kept in a file only the user can read
+pub struct UserIsolation {
+    pub user: String,
+    token: String,
+}
+
+impl UserIsolation {
+    /// Reads the user's token from the data directory, or creates it. The directory is made
+    /// private to the user first
+    pub fn load(data_dir: &Path, user: &str) -> Result<Self> {
+        restrict_to_owner(data_dir)?;
+        let path = data_dir.join(TOKEN_FILE);
+        let token = match std::fs::read_to_string(&path) {
+            Ok(token) if !token.trim().is_empty() => token.trim().to_string(),
+            _ => {
+                let token: String = rand::thread_rng()
+                    .sample_iter(&rand::distributions::Alphanumeric)
+                    .take(48)
+                    .map(char::from)
+                    .collect();
+                write_private_file(&path, token.as_bytes())?;
+                token
+            }
+        };
+        Ok(UserIsolation {
+            user: user.to_string(),
+            token,
+        })
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn is_authorized(&self, token: Option<&str>) -> bool {
+        token.is_some_and(|token| tokens_match(token, &self.token))
+    }
+}
+
+/// Where the user's history is kept under a data directory shared by several users
+pub fn user_data_dir(base_dir: &Path, user: &str) -> PathBuf {
+    base_dir.join("users").join(user)
+}
+
+/// Only the owner can read, write or list it. Windows keeps the files of a profile private to
+/// its user already
+pub fn restrict_to_owner(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if path.is_dir() { 0o700 } else { 0o600 };
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("failed to restrict {}", path.display()))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// Writes a file only the owner can read. It's created that way, and one that was already
+/// there is restricted before anything is written to it
+pub fn write_private_file(path: &Path, data: &[u8]) -> Result<()> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))
+            .with_context(|| format!("failed to restrict {}", path.display()))?;
+    }
+    file.write_all(data)
+        .with_context(|| format!("failed to write {}", path.display()))?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserInstance {
+    pub user: String,
+    pub pid: u32,
+    pub port: u16,
+    pub started_at: DateTime<Utc>,
+}
+
+impl UserInstance {
+    pub fn read(data_dir: &Path) -> Option<Self> {
+        let instance = std::fs::read_to_string(data_dir.join(INSTANCE_FILE)).ok()?;
+        serde_json::from_str(&instance).ok()
+    }
+
+    pub fn write(&self, data_dir: &Path) -> Result<()> {
+        write_private_file(
+            &data_dir.join(INSTANCE_FILE),
+            serde_json::to_string_pretty(self)?.as_bytes(),
+        )
+    }
+
+    pub fn is_running(&self) -> bool {
+        let pid = sysinfo::Pid::from_u32(self.pid);
+        let mut sys = System::new();
+        sys.refresh_process(pid) && self.pid != std::process::id()
+    }
+}
+
+/// Binds the first port from `preferred` free on localhost, another user's server may have
+/// it. The listener is kept, so the port can't be taken before the server serves on it
+pub fn bind_user_port(preferred: u16) -> Result<std::net::TcpListener> {
+    (preferred..preferred.saturating_add(PORT_ATTEMPTS))
+        .find_map(|port| std::net::TcpListener::bind(("127.0.0.1", port)).ok())
+        .with_context(|| {
+            format!(
+                "no free port in {}..{}",
+                preferred,
+                preferred.saturating_add(PORT_ATTEMPTS)
+            )
+        })
+}
+
+fn request_token(request: &Request) -> Option<String> {
+    if let Some(token) = request
+        .headers()
+        .get(USER_TOKEN_HEADER)
+        .and_then(|value| value.to_str().ok())
+    {
+        return Some(token.to_string());
+    }
+    request.uri().query()?.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == USER_TOKEN_PARAM).then(|| value.to_string())
+    })
+}
+
+/// Refuses the requests without the token of the user the server records, like the ones of
+/// other users of the machine. The live view needs it along with its own token, in the query
+/// of the page, only the player script holding no secret is served without
+pub async fn require_user_token(
+    State(isolation): State<Arc<UserIsolation>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let is_live_player = request.uri().path() == "/live/player.js";
+    if is_live_player || isolation.is_authorized(request_token(&request).as_deref()) {
+        return next.run(request).await;
+    }
+    (
+        StatusCode::FORBIDDEN,
+        Json(json!({
+            "error": format!("this server records the history of {}, send the token of their data directory in {}", isolation.user, USER_TOKEN_HEADER),
+        })),
+    )
+        .into_response()
+}
@@ -0,0 +1,112 @@
+use crate::trends::{digest_lines, update_weekly_metrics, weekly_trends, BASELINE_WEEKS};
+use crate::video::extract_frame;
+use crate::DatabaseManager;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Local, Utc, Weekday};
+use image::DynamicImage;
+use log::warn;
+use screenpipe_integrations::vault_export::{
+    DigestEntry, NoteContent, NoteMeeting, NoteMoment, VaultNoteSource,
+};
+use serde_json::Value;
+
+/// Extracted records in a daily note, each with the frame it was seen on
+const MAX_NOTE_MOMENTS: u32 = 100;
+
+fn record_details(data: &Value) -> Vec<String> {
+    match data {
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(key, value)| match value {
+                Value::String(value) => format!("{}: {}", key, value),
+                value => format!("{}: {}", key, value),
+            })
+            .collect(),
+        Value::String(value) => vec![value.clone()],
+        Value::Null => Vec::new(),
+        value => vec![value.to_string()],
+    }
+}
+
+/// The activity digest, meeting summaries and the records of the extraction rules, tagged
+/// with the rule's name, and on mondays the trends of the week before
+#[async_trait]
+impl VaultNoteSource for DatabaseManager {
+    async fn note_content(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Result<NoteContent> {
+        let digest = self
+            .get_activity_category_durations(Some(start), Some(end))
+            .await?
+            .into_iter()
+            .map(|duration| DigestEntry {
+                label: duration.category.as_str().to_string(),
+                duration_secs: duration.duration_secs,
+            })
+            .collect();
+        let meetings = self
+            .get_meeting_summaries_between(start, end)
+            .await?
+            .into_iter()
+            .map(|meeting| NoteMeeting {
+                start_time: meeting.start_time,
+                end_time: meeting.end_time,
+                title: meeting.calendar_title,
+                attendees: meeting.calendar_attendees,
+                summary: meeting.summary,
+                decisions: meeting.decisions,
+                action_items: meeting
+                    .action_items
+                    .into_iter()
+                    .map(|item| match item.owner {
+                        Some(owner) => format!("{} ({})", item.description, owner),
+                        None => item.description,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let moments = self
+            .get_named_extracted_records_between(start, end, MAX_NOTE_MOMENTS)
+            .await?
+            .into_iter()
+            .map(|(rule_name, record)| NoteMoment {
+                timestamp: record.timestamp,
+                tag: rule_name,
+                app_name: record.app_name,
+                window_name: record.window_name,
+                details: record_details(&record.data),
+                frame_id: Some(record.frame_id),
+            })
+            .collect();
+        let day = start.with_timezone(&Local).date_naive();
+        let last_week = if day.weekday() == Weekday::Mon {
+            let trends = async {
+                update_weekly_metrics(self, Local::now()).await?;
+                weekly_trends(self, day, BASELINE_WEEKS as u32 + 1).await
+            };
+            match trends.await {
+                Ok(trends) => digest_lines(&trends),
+                Err(e) => {
+                    warn!("Leaving the trends of last week out of the note: {}", e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        Ok(NoteContent {
+            digest,
+            meetings,
+            moments,
+            last_week,
+            timezone: None,
+        })
+    }
+
+    async fn frame_image(&self, frame_id: i64) -> Result<DynamicImage> {
+        let (file_path, offset_index) = self
+            .get_frame(frame_id)
+            .await?
+            .ok_or_else(|| anyhow!("frame {} not found", frame_id))?;
+        Ok(extract_frame(&file_path, offset_index).await?)
+    }
+}
@@ -1,21 +1,43 @@
 use chrono::Utc;
-use image::ImageFormat::{self};
+use image::{DynamicImage, ImageFormat};
 use log::{debug, error, info, warn};
-use screenpipe_core::find_ffmpeg_path;
+use screenpipe_core::{
+    channel_stats, emit_event, Container, EncoderQuality, FfmpegCommand, FfmpegInput,
+    FfmpegOutput, FfmpegProcess, OverflowPolicy, VideoCodec, VideoEncoder,
+};
+use crate::browser_pages::merge_browser_pages;
+#[cfg(feature = "native-encoder")]
+use crate::capabilities::{encoder_backend, EncoderBackend};
+use crate::disk_guard::{disk_level, media_writing_paused, DiskLevel};
+use crate::encryption::open_media;
+use crate::errors::StorageError;
+use crate::frame_format::{frame_storage, FrameEncoding};
+use crate::native_encoder::{
+    is_native_chunk, read_native_frame, NativeChunkWriter, NATIVE_CHUNK_EXTENSION,
+};
+use crate::text_only::{is_text_only_chunk, text_only, text_only_chunk_path};
+use crate::write_coalescer::{PendingWrite, WriteCoalescer};
+use crate::{CapturePause, RedactionPolicy};
 use screenpipe_vision::{continuous_capture, CaptureResult, OcrEngine};
+use serde_json::json;
 use std::collections::VecDeque;
 use std::path::PathBuf;
-use std::process::Stdio;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
-use tokio::io::{AsyncBufReadExt, BufReader};
-use tokio::process::{Child, ChildStdin, Command};
 use tokio::sync::mpsc::{channel, Receiver, Sender};
 use tokio::sync::Mutex;
+use tokio_util::sync::CancellationToken;
 
 use std::time::Duration;
 
-const MAX_FPS: f64 = 30.0; // Adjust based on your needs
+pub(crate) const MAX_FPS: f64 = 30.0; // Adjust based on your needs
+const ENCODER_RESTART_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_ENCODER_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+/// Consecutive crashes after which a hardware encoder is replaced by the software one
+const MAX_HARDWARE_ENCODER_RESTARTS: u32 = 3;
+/// Frames from the OCR waiting to be queued, newer ones are dropped while it is full
+const FRAME_CHANNEL_CAPACITY: usize = 32;
+/// Frames the encoder and the database writer can fall behind by before the oldest are dropped
+const MAX_QUEUED_FRAMES: usize = 64;
 
 pub struct VideoCapture {
     frame_queue: Arc<Mutex<VecDeque<CaptureResult>>>,
@@ -31,6 +53,14 @@ impl VideoCapture {
         save_text_files: bool,
         ocr_engine: Arc<OcrEngine>,
         monitor_id: u32,
+        encoder: VideoEncoder,
+        quality: EncoderQuality,
+        redaction_policy: Option<Arc<RedactionPolicy>>,
+        capture_pause: Arc<CapturePause>,
+        // where what the policy redacts is noted, nowhere without a database
+        redaction_writes: Option<WriteCoalescer>,
+        // stops the capture, its OCR and the encoder, the chunk being written is finished first
+        cancel: CancellationToken,
     ) -> Self {
         info!("Starting new video capture");
         let frame_queue = Arc::new(Mutex::new(VecDeque::new()));
@@ -42,7 +72,9 @@ impl VideoCapture {
         let capture_frame_queue = frame_queue.clone();
         let capture_video_frame_queue = video_frame_queue.clone();
         let capture_ocr_frame_queue = ocr_frame_queue.clone();
-        let (result_sender, mut result_receiver) = channel(512);
+        let (result_sender, mut result_receiver) = channel(FRAME_CHANNEL_CAPACITY);
+        let paused = capture_pause.flag();
+        let capture_cancel = cancel.clone();
         let _capture_thread = tokio::spawn(async move {
             continuous_capture(
                 result_sender,
@@ -50,6 +82,8 @@ impl VideoCapture {
                 save_text_files,
                 ocr_engine,
                 monitor_id,
+                paused,
+                capture_cancel,
             )
             .await;
         });
@@ -57,16 +91,73 @@ impl VideoCapture {
         info!("Started capture thread");
 
         // Spawn another thread to handle receiving and queueing the results
+        let queue_cancel = cancel.clone();
         let _queue_thread = tokio::spawn(async move {
-            while let Some(result) = result_receiver.recv().await {
+            let video_stats =
+                channel_stats("video.frames", OverflowPolicy::KeepLatest, MAX_QUEUED_FRAMES);
+            let ocr_stats =
+                channel_stats("ocr.frames", OverflowPolicy::KeepLatest, MAX_QUEUED_FRAMES);
+            let mut excluded_apps = Vec::new();
+            while let Some(mut result) = tokio::select! {
+                result = result_receiver.recv() => result,
+                _ = queue_cancel.cancelled() => None,
+            } {
                 let frame_number = result.frame_number;
+                // OCR of a frame taken right before the pause can finish after it
+                if capture_pause.is_paused() {
+                    debug!("Capture is paused, dropping frame {}", frame_number);
+                    continue;
+                }
+                if media_writing_paused() {
+                    debug!("The disk is almost full, dropping frame {}", frame_number);
+                    continue;
+                }
+                audit_private_windows(&result, &mut excluded_apps);
+                merge_browser_pages(&mut result);
+                // frames are redacted before anything gets to see them
+                let result = match &redaction_policy {
+                    Some(policy) => {
+                        let policy = Arc::clone(policy);
+                        let (result, annotations) = match tokio::task::spawn_blocking(move || {
+                            policy.annotate_frame(result)
+                        })
+                        .await
+                        {
+                            Ok(redacted) => redacted,
+                            Err(e) => {
+                                error!(
+                                    "Failed to apply redaction policy, dropping frame {}: {}",
+                                    frame_number, e
+                                );
+                                continue;
+                            }
+                        };
+                        if let Some(writes) = &redaction_writes {
+                            let timestamp = Utc::now();
+                            for annotation in annotations {
+                                writes
+                                    .write(PendingWrite::Redaction {
+                                        timestamp,
+                                        annotation,
+                                        audio_file: None,
+                                    })
+                                    .await;
+                            }
+                        }
+                        match result {
+                            Some(result) => result,
+                            None => continue,
+                        }
+                    }
+                    None => result,
+                };
                 debug!("Received frame {} for queueing", frame_number);
                 let mut queue = capture_frame_queue.lock().await;
                 let mut video_queue = capture_video_frame_queue.lock().await;
                 let mut ocr_queue = capture_ocr_frame_queue.lock().await;
                 queue.push_back(result.clone());
-                video_queue.push_back(result.clone());
-                ocr_queue.push_back(result);
+                video_stats.push_latest(&mut video_queue, result.clone());
+                ocr_stats.push_latest(&mut ocr_queue, result);
                 debug!("Frame {} pushed to queues. Queue length: {}, Video queue length: {}, OCR queue length: {}", frame_number, queue.len(), video_queue.len(), ocr_queue.len());
 
                 // Clear the old queue after processing
@@ -84,6 +175,9 @@ impl VideoCapture {
                 &output_path,
                 fps,
                 new_chunk_callback_clone,
+                encoder,
+                quality,
+                cancel,
             )
             .await;
         });
@@ -106,33 +200,87 @@ impl VideoCapture {
         Arc::clone(&self.video_frame_queue)
     }
 }
+/// Emits an audit event when private windows start being left out, not on every frame
+fn audit_private_windows(result: &CaptureResult, excluded_apps: &mut Vec<String>) {
+    let mut apps: Vec<String> = result
+        .private_windows
+        .iter()
+        .map(|window| window.app_name.clone())
+        .collect();
+    apps.sort();
+    apps.dedup();
+    if !apps.is_empty() && apps != *excluded_apps {
+        info!("Excluded private windows of {} from capture", apps.join(", "));
+        emit_event(
+            "capture.private_window_excluded",
+            json!({"apps": apps, "windows": result.private_windows.len()}),
+        );
+    }
+    *excluded_apps = apps;
+}
+
 async fn save_frames_as_video(
     frame_queue: &Arc<Mutex<VecDeque<CaptureResult>>>,
     output_path: &str,
     fps: f64,
     new_chunk_callback: Arc<dyn Fn(&str) + Send + Sync>,
+    mut encoder: VideoEncoder,
+    quality: EncoderQuality,
+    cancel: CancellationToken,
 ) {
     debug!("Starting save_frames_as_video function");
+    if text_only() {
+        info!("recording text only, no frames are stored");
+        return save_text_only_chunks(frame_queue, output_path, new_chunk_callback, cancel).await;
+    }
+    if let Some(encoding) = frame_storage() {
+        info!(
+            "storing frames as {:?} images",
+            encoding.format
+        );
+        return save_frames_natively(frame_queue, output_path, new_chunk_callback, encoding, cancel)
+            .await;
+    }
+    #[cfg(feature = "native-encoder")]
+    if encoder_backend() == Some(EncoderBackend::Native) {
+        info!("ffmpeg not available, storing frames with the native encoder");
+        return save_frames_natively(
+            frame_queue,
+            output_path,
+            new_chunk_callback,
+            FrameEncoding::default(),
+            cancel,
+        )
+        .await;
+    }
+
     let frames_per_video = 30; // Adjust this value as needed
     let mut frame_count = 0;
-    let (sender, mut receiver): (Sender<Vec<u8>>, Receiver<Vec<u8>>) = channel(512);
+    let (sender, mut receiver): (Sender<Arc<Vec<u8>>>, Receiver<Arc<Vec<u8>>>) = channel(512);
     let sender = Arc::new(sender);
-    let mut current_ffmpeg: Option<Child> = None;
-    let mut current_stdin: Option<ChildStdin> = None;
+    let encoded_stats = channel_stats("video.encoded_frames", OverflowPolicy::Wait, 512);
+    let mut current_ffmpeg: Option<FfmpegProcess> = None;
+    // consecutive encoder crashes, reset once a chunk is written successfully
+    let mut restarts: u32 = 0;
+
+    while !cancel.is_cancelled() {
+        let encoder_died = current_ffmpeg
+            .as_mut()
+            .is_some_and(|process| process.has_exited());
+        if encoder_died {
+            restart_encoder(&mut current_ffmpeg, &mut restarts).await;
+        }
 
-    loop {
         if frame_count % frames_per_video == 0 || current_ffmpeg.is_none() {
             debug!("Starting new FFmpeg process");
             // Close previous FFmpeg process if exists
-            if let Some(child) = current_ffmpeg.take() {
-                drop(current_stdin.take()); // Ensure stdin is closed
-                let output = child
-                    .wait_with_output()
-                    .await
-                    .expect("ffmpeg process failed");
-                debug!("FFmpeg process exited with status: {}", output.status);
-                if !output.status.success() {
-                    error!("FFmpeg stderr: {}", String::from_utf8_lossy(&output.stderr));
+            if let Some(process) = current_ffmpeg.take() {
+                match process.finish().await {
+                    Ok(status) => {
+                        debug!("FFmpeg process exited with status: {}", status);
+                        restarts = 0;
+                    }
+                    Err(e) => error!("FFmpeg failed to write the video chunk: {}", e),
                 }
             }
 
@@ -142,14 +290,16 @@ async fn save_frames_as_video(
                     debug!("Got first frame for new chunk");
                     break result;
                 }
+                if cancel.is_cancelled() {
+                    return;
+                }
                 tokio::time::sleep(Duration::from_millis(10)).await;
             };
 
             // Encode the first frame
-            let mut buffer = Vec::new();
-            first_frame
-                .image
-                .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
+            let buffer = first_frame
+                .png
+                .png(&first_frame.image)
                 .expect("Failed to encode first frame");
 
             let time = Utc::now();
@@ -164,44 +314,43 @@ async fn save_frames_as_video(
             // Call the callback with the new video chunk file path
             new_chunk_callback(&output_file);
 
-            match start_ffmpeg_process(&output_file, fps).await {
-                Ok(mut child) => {
-                    let mut stdin = child.stdin.take().expect("Failed to open stdin");
-                    let stderr = child.stderr.take().expect("Failed to open stderr");
-                    let stdout = child.stdout.take().expect("Failed to open stdout");
+            if encoder != VideoEncoder::Software && restarts >= MAX_HARDWARE_ENCODER_RESTARTS {
+                warn!(
+                    "{} keeps failing, falling back to software encoding",
+                    encoder.codec_name()
+                );
+                emit_event(
+                    "ffmpeg.encoder_fallback",
+                    json!({"from": encoder, "to": VideoEncoder::Software}),
+                );
+                encoder = VideoEncoder::Software;
+            }
 
+            // smaller chunks while the disk is filling up
+            let quality = if disk_level() >= DiskLevel::LowBitrate {
+                EncoderQuality::Low
+            } else {
+                quality
+            };
+            match start_ffmpeg_process(&output_file, fps, encoder, quality) {
+                Ok(mut process) => {
                     // Write the first frame to FFmpeg
-                    stdin
-                        .write_all(&buffer)
-                        .await
-                        .expect("Failed to write first frame to ffmpeg");
-                    frame_count += 1;
-
-                    // Spawn a task to log FFmpeg's stderr
-                    tokio::spawn(async move {
-                        let reader = BufReader::new(stderr);
-                        let mut lines = reader.lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            debug!("FFmpeg: {}", line);
+                    match process.write(&buffer).await {
+                        Ok(()) => {
+                            frame_count += 1;
+                            current_ffmpeg = Some(process);
+                            debug!("New FFmpeg process started for file: {}", output_file);
                         }
-                    });
-
-                    // Log FFmpeg's stdout
-                    tokio::spawn(async move {
-                        let reader = BufReader::new(stdout);
-                        let mut lines = reader.lines();
-                        while let Ok(Some(line)) = lines.next_line().await {
-                            debug!("FFmpeg: {}", line);
+                        Err(e) => {
+                            error!("Failed to write first frame to ffmpeg: {}", e);
+                            current_ffmpeg = Some(process);
+                            restart_encoder(&mut current_ffmpeg, &mut restarts).await;
                         }
-                    });
-
-                    current_ffmpeg = Some(child);
-                    current_stdin = Some(stdin);
-                    debug!("New FFmpeg process started for file: {}", output_file);
+                    }
                 }
                 Err(e) => {
                     error!("Failed to start FFmpeg process: {}", e);
-                    // Handle the error appropriately, maybe try to restart or exit
+                    restart_encoder(&mut current_ffmpeg, &mut restarts).await;
                 }
             }
         }
@@ -209,16 +358,14 @@ async fn save_frames_as_video(
         if let Some(result) = frame_queue.lock().await.pop_front() {
             debug!("Processing frame in video.rs"); // {}", frame_count + 1
             let sender = Arc::clone(&sender);
+            let encoded_stats = Arc::clone(&encoded_stats);
 
             tokio::spawn(async move {
-                let mut buffer = Vec::new();
-                match result
-                    .image
-                    .write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)
-                {
-                    Ok(_) => {
-                        sender
-                            .send(buffer)
+                // the live view reuses it
+                match result.png.png(&result.image) {
+                    Ok(buffer) => {
+                        encoded_stats
+                            .send(&sender, buffer)
                             .await
                             .expect("Failed to send encoded frame");
                     }
@@ -232,9 +379,10 @@ async fn save_frames_as_video(
 
         // Write encoded frames to FFmpeg
         while let Ok(buffer) = receiver.try_recv() {
-            if let Some(stdin) = current_stdin.as_mut() {
-                if let Err(e) = stdin.write_all(buffer.as_slice()).await {
+            if let Some(process) = current_ffmpeg.as_mut() {
+                if let Err(e) = process.write(buffer.as_slice()).await {
                     error!("Failed to write frame to ffmpeg: {}", e);
+                    restart_encoder(&mut current_ffmpeg, &mut restarts).await;
                     break;
                 }
                 frame_count += 1;
@@ -246,7 +394,7 @@ async fn save_frames_as_video(
                 // Flush every calculated number of frames
                 if frame_count % frames_per_flush == 0 {
                     debug!("Flushing FFmpeg input after {} frames", frames_per_flush);
-                    if let Err(e) = stdin.flush().await {
+                    if let Err(e) = process.flush().await {
                         error!("Failed to flush FFmpeg input: {}", e);
                     }
                 }
@@ -258,11 +406,160 @@ async fn save_frames_as_video(
             tokio::task::yield_now().await;
         }
     }
+
+    // the frames already encoded end the chunk so it can be played
+    if let Some(mut process) = current_ffmpeg.take() {
+        while let Ok(buffer) = receiver.try_recv() {
+            if process.write(buffer.as_slice()).await.is_err() {
+                break;
+            }
+        }
+        if let Err(e) = process.finish().await {
+            error!("FFmpeg failed to write the last video chunk: {}", e);
+        }
+    }
+    debug!("Video encoder stopped");
+}
+
+/// Same chunking as with ffmpeg, but each chunk is a directory of images
+async fn save_frames_natively(
+    frame_queue: &Arc<Mutex<VecDeque<CaptureResult>>>,
+    output_path: &str,
+    new_chunk_callback: Arc<dyn Fn(&str) + Send + Sync>,
+    encoding: FrameEncoding,
+    cancel: CancellationToken,
+) {
+    let frames_per_chunk = 30;
+    let mut writer: Option<NativeChunkWriter> = None;
+
+    while !cancel.is_cancelled() {
+        let Some(result) = frame_queue.lock().await.pop_front() else {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            continue;
+        };
+
+        if writer.as_ref().is_none_or(|w| w.frames() >= frames_per_chunk) {
+            let formatted_time = Utc::now().format("%Y-%m-%d_%H-%M-%S").to_string();
+            let chunk = PathBuf::from(output_path)
+                .join(format!("{}.{}", formatted_time, NATIVE_CHUNK_EXTENSION));
+            match NativeChunkWriter::create(&chunk, encoding) {
+                Ok(new_writer) => {
+                    new_chunk_callback(&chunk.to_string_lossy());
+                    writer = Some(new_writer);
+                }
+                Err(e) => {
+                    error!("Failed to create frames chunk {:?}: {}", chunk, e);
+                    continue;
+                }
+            }
+        }
+
+        if let Some(writer) = writer.as_mut() {
+            if let Err(e) = writer.write_frame(result.image).await {
+                error!("Failed to write frame: {}", e);
+            }
+        }
+    }
 }
 
-use std::env;
+/// Same chunking, but nothing of the frames is written, the chunks only group their rows
+async fn save_text_only_chunks(
+    frame_queue: &Arc<Mutex<VecDeque<CaptureResult>>>,
+    output_path: &str,
+    new_chunk_callback: Arc<dyn Fn(&str) + Send + Sync>,
+    cancel: CancellationToken,
+) {
+    let frames_per_chunk = 30;
+    let mut frames = frames_per_chunk;
+
+    while !cancel.is_cancelled() {
+        if frame_queue.lock().await.pop_front().is_none() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+            continue;
+        }
+        if frames >= frames_per_chunk {
+            let chunk = text_only_chunk_path(output_path, Utc::now());
+            new_chunk_callback(&chunk.to_string_lossy());
+            frames = 0;
+        }
+        frames += 1;
+    }
+}
 
-async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyhow::Error> {
+/// Drops the dead encoder so the next frames go to a new chunk, backing off if it keeps dying
+async fn restart_encoder(current_ffmpeg: &mut Option<FfmpegProcess>, restarts: &mut u32) {
+    let reason = current_ffmpeg
+        .take()
+        .and_then(|process| process.last_error())
+        .unwrap_or_else(|| "unknown error".to_string());
+    let backoff = ENCODER_RESTART_BACKOFF
+        .saturating_mul(2u32.saturating_pow(*restarts))
+        .min(MAX_ENCODER_RESTART_BACKOFF);
+    *restarts += 1;
+
+    warn!(
+        "Video encoder died ({}), restarting in {:?} (attempt {})",
+        reason, backoff, restarts
+    );
+    emit_event(
+        "ffmpeg.restarted",
+        json!({"process": "video", "reason": reason, "attempt": *restarts}),
+    );
+    tokio::time::sleep(backoff).await;
+}
+
+/// Decodes the frame at `offset_index` of a video chunk
+pub async fn extract_frame(
+    file_path: &str,
+    offset_index: i64,
+) -> Result<DynamicImage, StorageError> {
+    let extraction_failed = |reason: String| StorageError::FrameExtraction {
+        path: file_path.to_string(),
+        offset_index,
+        reason,
+    };
+    if is_text_only_chunk(file_path) {
+        return Err(StorageError::TextOnly(file_path.to_string()));
+    }
+    if is_native_chunk(file_path) {
+        return read_native_frame(file_path, offset_index)
+            .await
+            .map_err(|e| extraction_failed(e.to_string()));
+    }
+    if !std::path::Path::new(file_path).exists() {
+        return Err(StorageError::MediaMissing(file_path.to_string()));
+    }
+
+    let media = open_media(file_path).await?;
+    let output = FfmpegCommand::new()
+        .input(FfmpegInput::file(media.path()))
+        .output(
+            FfmpegOutput::pipe()
+                .video_filter(&format!("select=eq(n\\,{})", offset_index))
+                .frames(1)
+                .format(Container::Image2Pipe)
+                .video_codec(VideoCodec::Png),
+        )
+        .run()
+        .await
+        .map_err(|e| extraction_failed(e.to_string()))?;
+
+    if !output.status.success() || output.stdout.is_empty() {
+        return Err(extraction_failed(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    image::load_from_memory_with_format(&output.stdout, ImageFormat::Png)
+        .map_err(|e| extraction_failed(e.to_string()))
+}
+
+fn start_ffmpeg_process(
+    output_file: &str,
+    fps: f64,
+    encoder: VideoEncoder,
+    quality: EncoderQuality,
+) -> Result<FfmpegProcess, anyhow::Error> {
     // Overriding fps with max fps if over the max and warning user
     let fps = if fps > MAX_FPS {
         warn!("Overriding FPS from {} to {}", fps, MAX_FPS);
@@ -271,48 +568,21 @@ async fn start_ffmpeg_process(output_file: &str, fps: f64) -> Result<Child, anyh
         fps
     };
 
-    info!("Starting FFmpeg process for file: {}", output_file);
-    let fps_str = fps.to_string();
-    let mut command = Command::new(find_ffmpeg_path().unwrap());
-    let mut args = vec![
-        "-f",
-        "image2pipe",
-        "-vcodec",
-        "png",
-        "-r",
-        &fps_str,
-        "-i",
-        "-",
-    ];
-
-    if env::consts::OS == "windows" {
-        // TODO switch back to libx264 when ffmpeg is updated in pre_build.js
-        // Use MPEG-4 encoder for Windows
-        args.extend_from_slice(&[
-            "-vcodec",
-            "mpeg4",
-            "-q:v",
-            "5", // Adjust quality (1-31, lower is better)
-            "-preset",
-            "ultrafast",
-        ]);
-    } else {
-        // Use libx264 for other platforms
-        args.extend_from_slice(&["-vcodec", "libx264", "-preset", "ultrafast", "-crf", "23"]);
-    }
-
-    args.extend_from_slice(&["-pix_fmt", "yuv420p", output_file]);
-
-    command
-        .args(&args)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped());
-
-    debug!("FFmpeg command: {:?}", command);
-
-    let child = command.spawn()?;
+    info!(
+        "Starting FFmpeg process for file: {} ({})",
+        output_file,
+        encoder.codec_name()
+    );
+    let command = FfmpegCommand::new().input(
+        FfmpegInput::pipe()
+            .format(Container::Image2Pipe)
+            .video_codec(VideoCodec::Png)
+            .frame_rate(fps),
+    );
+    let process = encoder
+        .encode_to(command, FfmpegOutput::file(output_file), quality)
+        .spawn("video")?;
     debug!("FFmpeg process spawned");
 
-    Ok(child)
+    Ok(process)
 }
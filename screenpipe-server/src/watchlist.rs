@@ -0,0 +1,314 @@
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use regex::Regex;
+use screenpipe_core::emit_event;
+use screenpipe_vision::CaptureResult;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+
+/// The same text of the same window only alerts again after this long, it stays on screen
+/// for many frames
+const ALERT_COOLDOWN: Duration = Duration::from_secs(10 * 60);
+const MAX_EXCERPT_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchKind {
+    /// `pattern` is a regex
+    Regex,
+    /// `pattern` is a comma separated list of keywords, any of them matches (case insensitive)
+    Keywords,
+}
+
+impl WatchKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchKind::Regex => "regex",
+            WatchKind::Keywords => "keywords",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WatchSource {
+    #[default]
+    All,
+    /// The OCR text of the windows
+    Ocr,
+    /// The transcripts
+    Audio,
+}
+
+impl WatchSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WatchSource::All => "all",
+            WatchSource::Ocr => "ocr",
+            WatchSource::Audio => "audio",
+        }
+    }
+
+    pub fn parse(source: &str) -> Self {
+        match source {
+            "ocr" => WatchSource::Ocr,
+            "audio" => WatchSource::Audio,
+            _ => WatchSource::All,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Watch {
+    pub id: i64,
+    pub name: String,
+    /// Only watch windows of this app (case insensitive), all apps if none. Scoped watches
+    /// don't look at the transcripts
+    pub app_name: Option<String>,
+    /// Only watch windows whose title contains this (case insensitive), all windows if none
+    pub window_name: Option<String>,
+    pub kind: WatchKind,
+    pub pattern: String,
+    pub source: WatchSource,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Watch {
+    fn watches(
+        &self,
+        source: WatchSource,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+    ) -> bool {
+        if self.source != WatchSource::All && self.source != source {
+            return false;
+        }
+        let app_matches = match (&self.app_name, app_name) {
+            (None, _) => true,
+            (Some(app), Some(app_name)) => app.eq_ignore_ascii_case(app_name),
+            (Some(_), None) => false,
+        };
+        let window_matches = match (&self.window_name, window_name) {
+            (None, _) => true,
+            (Some(window), Some(window_name)) => {
+                window_name.to_lowercase().contains(&window.to_lowercase())
+            }
+            (Some(_), None) => false,
+        };
+        app_matches && window_matches
+    }
+}
+
+/// A match of a watch, the device is the window of a transcript
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchAlert {
+    pub id: i64,
+    pub watch_id: i64,
+    pub watch_name: String,
+    pub timestamp: DateTime<Utc>,
+    pub source: WatchSource,
+    pub app_name: Option<String>,
+    pub window_name: Option<String>,
+    pub matched_text: String,
+    /// The line the text was found on
+    pub excerpt: String,
+}
+
+/// The regex a watch looks for, keywords match whole words
+pub fn compile_watch(kind: WatchKind, pattern: &str) -> Result<Regex> {
+    match kind {
+        WatchKind::Regex => Ok(Regex::new(pattern)?),
+        WatchKind::Keywords => {
+            let keywords: Vec<String> = pattern
+                .split(',')
+                .map(str::trim)
+                .filter(|keyword| !keyword.is_empty())
+                .map(|keyword| {
+                    let is_word =
+                        |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+                    format!(
+                        "{}{}{}",
+                        if is_word(keyword.chars().next()) {
+                            r"\b"
+                        } else {
+                            ""
+                        },
+                        regex::escape(keyword),
+                        if is_word(keyword.chars().last()) {
+                            r"\b"
+                        } else {
+                            ""
+                        },
+                    )
+                })
+                .collect();
+            if keywords.is_empty() {
+                anyhow::bail!("no keywords");
+            }
+            Ok(Regex::new(&format!("(?i)(?:{})", keywords.join("|")))?)
+        }
+    }
+}
+
+fn excerpt(text: &str, start: usize, end: usize) -> String {
+    let line_start = text[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[end..].find('\n').map_or(text.len(), |i| end + i);
+    let line = text[line_start..line_end].trim();
+    if line.chars().count() <= MAX_EXCERPT_CHARS {
+        return line.to_string();
+    }
+    line.chars().take(MAX_EXCERPT_CHARS).collect::<String>() + "…"
+}
+
+/// The watch, app, window and the text it matched
+type AlertKey = (i64, Option<String>, Option<String>, String);
+
+/// The enabled watches, checked against the text of each captured frame and transcript.
+/// Matches are stored and emitted as "watch.alert"
+pub struct Watchlist {
+    db: Arc<DatabaseManager>,
+    watches: RwLock<Vec<(Watch, Regex)>>,
+    last_alerts: Mutex<HashMap<AlertKey, Instant>>,
+}
+
+impl Watchlist {
+    pub async fn load(db: Arc<DatabaseManager>) -> Result<Arc<Self>> {
+        let watchlist = Arc::new(Watchlist {
+            db,
+            watches: RwLock::new(Vec::new()),
+            last_alerts: Mutex::new(HashMap::new()),
+        });
+        watchlist.reload().await?;
+        Ok(watchlist)
+    }
+
+    /// Picks up the watches created and deleted since it was loaded
+    pub async fn reload(&self) -> Result<()> {
+        let mut watches = Vec::new();
+        for watch in self.db.get_watches(true).await? {
+            match compile_watch(watch.kind, &watch.pattern) {
+                Ok(regex) => watches.push((watch, regex)),
+                Err(e) => error!("Skipping watch {} ({}): {}", watch.name, watch.id, e),
+            }
+        }
+        info!("Watching for {} expressions", watches.len());
+        *self.watches.write().unwrap() = watches;
+        Ok(())
+    }
+
+    pub async fn check_frame(
+        &self,
+        frame: &CaptureResult,
+        timestamp: DateTime<Utc>,
+    ) -> Vec<WatchAlert> {
+        let mut alerts = Vec::new();
+        for window in &frame.window_ocr_results {
+            alerts.extend(
+                self.check(
+                    WatchSource::Ocr,
+                    Some(&window.app_name),
+                    Some(&window.window_name),
+                    &window.text,
+                    timestamp,
+                )
+                .await,
+            );
+        }
+        alerts
+    }
+
+    pub async fn check_transcript(
+        &self,
+        device: &str,
+        transcript: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Vec<WatchAlert> {
+        self.check(
+            WatchSource::Audio,
+            None,
+            Some(device),
+            transcript,
+            timestamp,
+        )
+        .await
+    }
+
+    async fn check(
+        &self,
+        source: WatchSource,
+        app_name: Option<&str>,
+        window_name: Option<&str>,
+        text: &str,
+        timestamp: DateTime<Utc>,
+    ) -> Vec<WatchAlert> {
+        // the device isn't a window, audio is only scoped by source
+        let scope_window = if source == WatchSource::Audio {
+            None
+        } else {
+            window_name
+        };
+        let matches: Vec<(i64, String, String, String)> = self
+            .watches
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|(watch, _)| watch.watches(source, app_name, scope_window))
+            .filter_map(|(watch, regex)| {
+                let found = regex.find(text)?;
+                Some((
+                    watch.id,
+                    watch.name.clone(),
+                    found.as_str().to_string(),
+                    excerpt(text, found.start(), found.end()),
+                ))
+            })
+            .collect();
+
+        let mut alerts = Vec::new();
+        for (watch_id, watch_name, matched_text, excerpt) in matches {
+            let key = (
+                watch_id,
+                app_name.map(str::to_string),
+                window_name.map(str::to_string),
+                matched_text.to_lowercase(),
+            );
+            {
+                let mut last_alerts = self.last_alerts.lock().unwrap();
+                let now = Instant::now();
+                last_alerts.retain(|_, at| now.duration_since(*at) < ALERT_COOLDOWN);
+                if last_alerts.contains_key(&key) {
+                    continue;
+                }
+                last_alerts.insert(key, now);
+            }
+
+            let mut alert = WatchAlert {
+                id: 0,
+                watch_id,
+                watch_name,
+                timestamp,
+                source,
+                app_name: app_name.map(str::to_string),
+                window_name: window_name.map(str::to_string),
+                matched_text,
+                excerpt,
+            };
+            match self.db.insert_watch_alert(&alert).await {
+                Ok(id) => alert.id = id,
+                Err(e) => {
+                    error!("Failed to store alert of watch {}: {}", watch_id, e);
+                    continue;
+                }
+            }
+            emit_event("watch.alert", json!(alert));
+            alerts.push(alert);
+        }
+        alerts
+    }
+}
@@ -0,0 +1,153 @@
+use crate::{CapturePause, DatabaseManager};
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{error, info};
+use screenpipe_core::emit_event;
+use screenpipe_vision::monitor::get_focused_window_title;
+use screenpipe_vision::privacy::{is_app_allowed, is_private_window};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How often the focused window is looked at, OCR frames are much further apart
+pub const DEFAULT_WINDOW_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WindowEventKind {
+    /// Another app's window was focused
+    Focus,
+    /// The focused window changed title, like another tab or document, or another window of
+    /// the same app was focused
+    Title,
+}
+
+impl WindowEventKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            WindowEventKind::Focus => "focus",
+            WindowEventKind::Title => "title",
+        }
+    }
+}
+
+impl FromStr for WindowEventKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "focus" => Ok(WindowEventKind::Focus),
+            "title" => Ok(WindowEventKind::Title),
+            _ => anyhow::bail!("unknown window event kind: {}", s),
+        }
+    }
+}
+
+/// A window that was focused from `timestamp` to `ended_at`, None while it still is or when
+/// screenpipe stopped before it lost focus
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowEvent {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub ended_at: Option<DateTime<Utc>>,
+    pub app_name: String,
+    /// Empty for private windows, their titles are not kept
+    pub window_name: String,
+    pub kind: WindowEventKind,
+    pub duration_secs: Option<f64>,
+}
+
+/// Stores a row each time the focused window changes, and ends the previous one
+#[derive(Default)]
+pub struct WindowTracker {
+    current: Option<(i64, String, String)>,
+}
+
+impl WindowTracker {
+    /// `window` is the app and title of the focused window, None while nothing is recorded,
+    /// like when capture is paused
+    pub async fn observe(
+        &mut self,
+        db: &DatabaseManager,
+        window: Option<(String, String)>,
+        timestamp: DateTime<Utc>,
+    ) -> Result<Option<WindowEvent>> {
+        let unchanged = match (&self.current, &window) {
+            (Some((_, app, title)), Some((app_name, window_name))) => {
+                app == app_name && title == window_name
+            }
+            (None, None) => true,
+            _ => false,
+        };
+        if unchanged {
+            return Ok(None);
+        }
+
+        if let Some((id, _, _)) = &self.current {
+            db.end_window_event(*id, timestamp).await?;
+        }
+        let Some((app_name, window_name)) = window else {
+            self.current = None;
+            return Ok(None);
+        };
+        let kind = match &self.current {
+            Some((_, app, _)) if *app == app_name => WindowEventKind::Title,
+            _ => WindowEventKind::Focus,
+        };
+        let id = db
+            .insert_window_event(timestamp, &app_name, &window_name, kind)
+            .await?;
+        self.current = Some((id, app_name.clone(), window_name.clone()));
+
+        let event = WindowEvent {
+            id,
+            timestamp,
+            ended_at: None,
+            app_name,
+            window_name,
+            kind,
+            duration_secs: None,
+        };
+        emit_event("window.changed", json!(event));
+        Ok(Some(event))
+    }
+}
+
+/// The focused window, as recorded: private windows without their title and nothing for apps
+/// that aren't captured
+fn recorded_window(window: Option<(String, String)>) -> Option<(String, String)> {
+    let (app_name, window_name) = window?;
+    if !is_app_allowed(&app_name) {
+        return None;
+    }
+    if is_private_window(&window_name) {
+        return Some((app_name, String::new()));
+    }
+    Some((app_name, window_name))
+}
+
+/// Records the focus and title changes of the windows between the OCR frames, no image is
+/// captured for them
+pub async fn start_window_history(
+    db: Arc<DatabaseManager>,
+    capture_pause: Arc<CapturePause>,
+    interval: Duration,
+) {
+    info!("Recording window history every {:?}", interval);
+    let mut tracker = WindowTracker::default();
+    let mut ticker = tokio::time::interval(interval);
+    ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    loop {
+        ticker.tick().await;
+        let window = if capture_pause.is_paused() {
+            None
+        } else {
+            recorded_window(get_focused_window_title().await)
+        };
+        if let Err(e) = tracker.observe(&db, window, Utc::now()).await {
+            error!("Failed to record window history: {}", e);
+        }
+    }
+}
@@ -0,0 +1,197 @@
+use crate::automation::AutomationAction;
+use crate::live_ocr::{monitor_area, Rect};
+use crate::DatabaseManager;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use log::{debug, error};
+use screenpipe_vision::CaptureResult;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// A window of a layout, placed in the desktop coordinates `/ocr/live` and the automation use
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayoutWindow {
+    pub app_name: String,
+    pub window_name: String,
+    pub focused: bool,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The windows of a monitor from `timestamp` until its next layout, private ones left out
+#[derive(Debug, Clone, Serialize)]
+pub struct WindowLayout {
+    pub id: i64,
+    pub timestamp: DateTime<Utc>,
+    pub monitor_id: u32,
+    pub windows: Vec<LayoutWindow>,
+}
+
+/// An app and where its window is in the frame, titles and focus change too often to count
+type FrameRect = (String, i32, i32, u32, u32);
+
+fn frame_rects(frame: &CaptureResult) -> Vec<FrameRect> {
+    frame
+        .window_ocr_results
+        .iter()
+        .map(|window| {
+            (
+                window.app_name.clone(),
+                window.position.0,
+                window.position.1,
+                window.image.width(),
+                window.image.height(),
+            )
+        })
+        .collect()
+}
+
+/// Stores the layout of a monitor's windows each time one of them moves, is resized, opens or
+/// closes
+pub struct LayoutRecorder {
+    db: Arc<DatabaseManager>,
+    monitor_id: u32,
+    previous: Option<Vec<FrameRect>>,
+}
+
+impl LayoutRecorder {
+    pub fn new(db: Arc<DatabaseManager>, monitor_id: u32) -> Self {
+        Self {
+            db,
+            monitor_id,
+            previous: None,
+        }
+    }
+
+    pub async fn observe(&mut self, frame: &CaptureResult, timestamp: DateTime<Utc>) {
+        let rects = frame_rects(frame);
+        if self.previous.as_ref() == Some(&rects) {
+            return;
+        }
+        let monitor =
+            monitor_area(self.monitor_id, (frame.image.width(), frame.image.height())).await;
+        let windows: Vec<LayoutWindow> = frame
+            .window_ocr_results
+            .iter()
+            .map(|window| {
+                let rect = monitor.to_screen(Rect {
+                    x: window.position.0 as f64,
+                    y: window.position.1 as f64,
+                    width: window.image.width() as f64,
+                    height: window.image.height() as f64,
+                });
+                LayoutWindow {
+                    app_name: window.app_name.clone(),
+                    window_name: window.window_name.clone(),
+                    focused: window.focused,
+                    x: rect.x.round() as i32,
+                    y: rect.y.round() as i32,
+                    width: rect.width.round() as u32,
+                    height: rect.height.round() as u32,
+                }
+            })
+            .collect();
+        match self
+            .db
+            .insert_window_layout(timestamp, self.monitor_id, &windows)
+            .await
+        {
+            Ok(id) => {
+                debug!(
+                    "Window layout {} of monitor {} has {} windows",
+                    id,
+                    self.monitor_id,
+                    windows.len()
+                );
+                self.previous = Some(rects);
+            }
+            Err(e) => error!("Failed to store the window layout: {}", e),
+        }
+    }
+}
+
+/// The actions putting the windows of a layout back where they were, for a pipe's automation
+/// grant to allow
+pub fn restore_actions(layout: &WindowLayout) -> Vec<AutomationAction> {
+    layout
+        .windows
+        .iter()
+        .filter(|window| window.width > 0 && window.height > 0)
+        .map(|window| AutomationAction::MoveWindow {
+            app_name: window.app_name.clone(),
+            window_name: window.window_name.clone(),
+            x: window.x,
+            y: window.y,
+            width: window.width,
+            height: window.height,
+        })
+        .collect()
+}
+
+/// A string of an AppleScript
+#[cfg(target_os = "macos")]
+fn applescript_string(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Moves and resizes the window of the app with that title, or its first window when none has
+/// it. Blocks until the window manager is done
+pub fn move_window(
+    app_name: &str,
+    window_name: &str,
+    x: i32,
+    y: i32,
+    width: u32,
+    height: u32,
+) -> Result<()> {
+    #[cfg(target_os = "macos")]
+    let output = {
+        let script = format!(
+            "tell application \"System Events\" to tell process {}\n\
+             set matching to windows whose name is {}\n\
+             if matching is {{}} then set matching to {{window 1}}\n\
+             set position of item 1 of matching to {{{}, {}}}\n\
+             set size of item 1 of matching to {{{}, {}}}\n\
+             end tell",
+            applescript_string(app_name),
+            applescript_string(window_name),
+            x,
+            y,
+            width,
+            height
+        );
+        std::process::Command::new("osascript")
+            .arg("-e")
+            .arg(script)
+            .output()?
+    };
+    #[cfg(target_os = "linux")]
+    let output = {
+        let geometry = format!("0,{},{},{},{}", x, y, width, height);
+        let mut command = std::process::Command::new("wmctrl");
+        if window_name.is_empty() {
+            command.args(["-x", "-r", app_name]);
+        } else {
+            command.args(["-r", window_name]);
+        }
+        command.args(["-e", &geometry]).output()?
+    };
+    #[cfg(not(any(target_os = "macos", target_os = "linux")))]
+    {
+        let _ = (app_name, window_name, x, y, width, height);
+        anyhow::bail!("moving windows isn't supported on this platform");
+    }
+    #[cfg(any(target_os = "macos", target_os = "linux"))]
+    {
+        if !output.status.success() {
+            anyhow::bail!(
+                "failed to move the window of {}: {}",
+                app_name,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        Ok(())
+    }
+}
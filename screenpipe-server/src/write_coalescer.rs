@@ -0,0 +1,189 @@
+use crate::file_activity::{emit_file_activity, FileEventKind};
+use crate::screen_regions::OcrBox;
+use crate::{DatabaseManager, RedactionAnnotation};
+use chrono::{DateTime, Utc};
+use log::{debug, error, warn};
+use screenpipe_core::{channel_stats, ChannelStats, OverflowPolicy};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio::sync::oneshot;
+use tokio::time::Instant;
+
+/// How long the first write of a batch waits for others
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+/// A batch is written early once it has this many writes
+const MAX_BATCH_SIZE: usize = 500;
+/// Writes waiting for the flusher, producers wait when it is full, nothing is dropped
+const PENDING_CAPACITY: usize = 4096;
+const MAX_RETRIES: u32 = 3;
+const RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// A row waiting for the next batch, timestamps are taken when it is queued, not written
+#[derive(Debug, Clone, PartialEq)]
+pub enum PendingWrite {
+    /// Frames written after it belong to this chunk
+    VideoChunk { file_path: String },
+    /// A window of a frame, stored as its own frame with the window's text
+    Ocr {
+        timestamp: DateTime<Utc>,
+        text: String,
+        text_json: String,
+        app_name: String,
+        window_name: String,
+        ocr_engine: String,
+        focused: bool,
+        /// Of the window image, for the screenshot similarity search
+        perceptual_hash: Option<u64>,
+        /// Of the page, when the browser extension pushed the text
+        browser_url: Option<String>,
+        scroll_y: Option<f64>,
+    },
+    /// Where the lines of the frame written just before it are on the screen
+    OcrBoxes {
+        monitor_id: Option<u32>,
+        boxes: Vec<OcrBox>,
+    },
+    /// A recorded audio chunk with its transcript, the transcript is left out when empty
+    Transcription {
+        timestamp: DateTime<Utc>,
+        file_path: String,
+        transcription: String,
+        engine: String,
+    },
+    /// What the redaction policy took out of a frame or a transcription
+    Redaction {
+        timestamp: DateTime<Utc>,
+        annotation: RedactionAnnotation,
+        /// The chunk of a transcription, deleted when the audio is skipped
+        audio_file: Option<String>,
+    },
+    /// Emitted as "file.activity" once written
+    FileEvent {
+        timestamp: DateTime<Utc>,
+        path: String,
+        kind: FileEventKind,
+        app_name: Option<String>,
+        window_name: Option<String>,
+    },
+}
+
+enum Queued {
+    Write(PendingWrite),
+    /// Answered once everything queued before it is written
+    Flush(oneshot::Sender<()>),
+}
+
+/// Queues OCR text, transcripts and file events and writes them in one transaction per
+/// interval, instead of a transaction and an fsync for each row
+#[derive(Clone)]
+pub struct WriteCoalescer {
+    sender: Sender<Queued>,
+    stats: Arc<ChannelStats>,
+}
+
+impl WriteCoalescer {
+    /// Spawns the task writing the batches, it stops once every clone is dropped
+    pub fn start(db: Arc<DatabaseManager>, interval: Duration) -> Self {
+        let (sender, receiver) = channel(PENDING_CAPACITY);
+        let stats = channel_stats("db.writes", OverflowPolicy::Wait, PENDING_CAPACITY);
+        tokio::spawn(flush_batches(db, receiver, interval, Arc::clone(&stats)));
+        WriteCoalescer { sender, stats }
+    }
+
+    pub async fn write(&self, write: PendingWrite) {
+        if self
+            .stats
+            .send(&self.sender, Queued::Write(write))
+            .await
+            .is_err()
+        {
+            error!("Database writer stopped, dropping write");
+            self.stats.record_dropped(1);
+        }
+    }
+
+    /// Writes what is queued without waiting for the interval, returns once it is written
+    pub async fn flush(&self) {
+        let (done, written) = oneshot::channel();
+        if self.sender.send(Queued::Flush(done)).await.is_ok() {
+            let _ = written.await;
+        }
+    }
+}
+
+async fn flush_batches(
+    db: Arc<DatabaseManager>,
+    mut receiver: Receiver<Queued>,
+    interval: Duration,
+    stats: Arc<ChannelStats>,
+) {
+    let mut batch = Vec::new();
+    // nothing is written while idle, the interval starts with the first write
+    while let Some(queued) = receiver.recv().await {
+        let mut flush = None;
+        match queued {
+            Queued::Write(write) => batch.push(write),
+            Queued::Flush(done) => flush = Some(done),
+        }
+        let deadline = Instant::now() + interval;
+        while flush.is_none() && batch.len() < MAX_BATCH_SIZE {
+            match tokio::time::timeout_at(deadline, receiver.recv()).await {
+                Ok(Some(Queued::Write(write))) => batch.push(write),
+                Ok(Some(Queued::Flush(done))) => flush = Some(done),
+                Ok(None) | Err(_) => break,
+            }
+        }
+        if !batch.is_empty() {
+            write_batch(&db, &batch, &stats).await;
+            batch.clear();
+        }
+        if let Some(done) = flush {
+            let _ = done.send(());
+        }
+    }
+}
+
+async fn write_batch(db: &DatabaseManager, batch: &[PendingWrite], stats: &ChannelStats) {
+    for attempt in 1..=MAX_RETRIES {
+        match db.insert_batch(batch).await {
+            Ok(ids) => {
+                debug!("Wrote {} rows in one transaction", batch.len());
+                for (write, id) in batch.iter().zip(ids) {
+                    if let PendingWrite::FileEvent {
+                        timestamp,
+                        path,
+                        kind,
+                        app_name,
+                        window_name,
+                    } = write
+                    {
+                        emit_file_activity(
+                            id,
+                            *timestamp,
+                            path,
+                            *kind,
+                            app_name.as_deref(),
+                            window_name.as_deref(),
+                        );
+                    }
+                }
+                return;
+            }
+            Err(e) if attempt < MAX_RETRIES => {
+                warn!(
+                    "Failed to write batch of {} rows (attempt {}/{}): {}",
+                    batch.len(),
+                    attempt,
+                    MAX_RETRIES,
+                    e
+                );
+                tokio::time::sleep(RETRY_BACKOFF * attempt).await;
+            }
+            Err(e) => {
+                error!("Dropping batch of {} rows: {}", batch.len(), e);
+                stats.record_dropped(batch.len() as u64);
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use chrono::{Duration, TimeZone, Utc};
+use screenpipe_server::activity::{classify_by_keywords, detect_app_sessions};
+use screenpipe_server::{ActivityCategory, ActivityClassifier};
+
+#[test]
+fn test_classify_activity_from_app_and_window_names() {
+    let classifier = ActivityClassifier::new().unwrap();
+
+    let cases = [
+        ("Code\nmain.rs - screenpipe", ActivityCategory::Coding),
+        (
+            "Google Chrome\nInbox (3) - Gmail - Google Chrome",
+            ActivityCategory::Email,
+        ),
+        ("zoom.us\nZoom Meeting", ActivityCategory::Meetings),
+        ("Arc\nYouTube - Arc", ActivityCategory::Entertainment),
+        (
+            "Firefox\nWikipedia - Mozilla Firefox",
+            ActivityCategory::Browsing,
+        ),
+    ];
+    for (text, expected) in cases {
+        let (category, confidence) = classifier.classify(text).unwrap();
+        assert_eq!(category, expected, "{}", text);
+        assert!(confidence > 0.4 && confidence <= 1.0);
+        // what's used when the classifier can't run agrees
+        let (category, confidence) = classify_by_keywords(text);
+        assert_eq!(category, expected, "{}", text);
+        assert!(confidence > 0.4 && confidence <= 1.0);
+    }
+}
+
+#[test]
+fn test_classify_unknown_app_is_other() {
+    let classifier = ActivityClassifier::new().unwrap();
+
+    let (category, confidence) = classifier.classify("Finder\nDownloads").unwrap();
+
+    assert_eq!(category, ActivityCategory::Other);
+    assert_eq!(confidence, 0.0);
+    assert_eq!(
+        classify_by_keywords("Finder\nDownloads"),
+        (ActivityCategory::Other, 0.0)
+    );
+}
+
+#[test]
+fn test_detect_app_sessions_splits_on_app_change_and_idle() {
+    let start = Utc.with_ymd_and_hms(2024, 8, 25, 9, 0, 0).unwrap();
+    let window = |secs: i64, app: &str, window: &str| {
+        (
+            start + Duration::seconds(secs),
+            app.to_string(),
+            window.to_string(),
+        )
+    };
+    let windows = vec![
+        window(0, "Code", "main.rs"),
+        window(60, "Code", "db.rs"),
+        window(120, "Code", "main.rs"),
+        window(180, "Slack", "general"),
+        window(240, "Slack", "general"),
+        // away for 20 minutes
+        window(1440, "Slack", "random"),
+    ];
+
+    let sessions = detect_app_sessions(&windows, Duration::minutes(5));
+
+    assert_eq!(sessions.len(), 3);
+    assert_eq!(sessions[0].app_name, "Code");
+    assert_eq!(sessions[0].end_time, start + Duration::seconds(120));
+    assert_eq!(sessions[0].window_names, vec!["main.rs", "db.rs"]);
+    assert_eq!(sessions[1].app_name, "Slack");
+    assert_eq!(sessions[2].start_time, start + Duration::seconds(1440));
+}
@@ -0,0 +1,102 @@
+use chrono::{Duration, Utc};
+use screenpipe_integrations::analytics_export::{AnalyticsRows, AnalyticsTable};
+use screenpipe_server::forget::{forget, ForgetFilter};
+use screenpipe_server::{analytics_rows, ActivityCategory, DatabaseManager};
+use screenpipe_vision::OcrEngine;
+use std::sync::Arc;
+
+async fn recorded_db() -> DatabaseManager {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_video_chunk("test_video.mp4").await.unwrap();
+    for text in ["one two three", "four five"] {
+        let frame_id = db.insert_frame().await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            text,
+            "",
+            "Code",
+            "main.rs",
+            Arc::new(OcrEngine::Tesseract),
+            true,
+        )
+        .await
+        .unwrap();
+    }
+    let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+    db.insert_audio_transcription(audio_chunk_id, "ship it", 0, "whisper")
+        .await
+        .unwrap();
+    db
+}
+
+#[tokio::test]
+async fn test_usage_is_counted_once() {
+    let db = recorded_db().await;
+    let until = Utc::now() + Duration::seconds(1);
+
+    assert_eq!(db.aggregate_usage(until).await.unwrap(), 3);
+    assert_eq!(db.aggregate_usage(until).await.unwrap(), 0);
+
+    let AnalyticsRows::Usage(usage) = analytics_rows(&db, AnalyticsTable::Usage, None, None)
+        .await
+        .unwrap()
+    else {
+        panic!("expected the usage rows");
+    };
+    assert_eq!(usage.len(), 2);
+    let audio = usage.iter().find(|row| row.source == "audio").unwrap();
+    assert_eq!(audio.app_name, None);
+    assert_eq!((audio.count, audio.word_count), (1, 2));
+    let screen = usage.iter().find(|row| row.source == "screen").unwrap();
+    assert_eq!(screen.app_name.as_deref(), Some("Code"));
+    assert_eq!(
+        (screen.count, screen.word_count, screen.char_count),
+        (2, 5, 22)
+    );
+}
+
+#[tokio::test]
+async fn test_raw_data_goes_and_the_aggregates_stay() {
+    let db = recorded_db().await;
+    let start = Utc::now() - Duration::hours(1);
+    db.insert_activity_session(
+        start,
+        start + Duration::minutes(30),
+        "Code",
+        ActivityCategory::Coding,
+        0.8,
+    )
+    .await
+    .unwrap();
+    let until = Utc::now() + Duration::seconds(1);
+    db.aggregate_usage(until).await.unwrap();
+
+    let filter = ForgetFilter {
+        end_time: Some(until),
+        keep_activity_sessions: true,
+        ..Default::default()
+    };
+    let report = forget(&db, &filter, false).await.unwrap();
+
+    assert_eq!(report.frames, 2);
+    assert_eq!(report.audio_transcriptions, 1);
+    assert_eq!(report.activity_sessions, 0);
+    assert!(analytics_rows(&db, AnalyticsTable::Ocr, None, None)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        analytics_rows(&db, AnalyticsTable::Sessions, None, None)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    assert_eq!(
+        analytics_rows(&db, AnalyticsTable::Usage, None, None)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+}
@@ -0,0 +1,131 @@
+use chrono::{Duration, Utc};
+use screenpipe_integrations::analytics_export::{
+    write_analytics, AnalyticsFormat, AnalyticsRows, AnalyticsTable,
+};
+use screenpipe_server::{analytics_rows, query_analytics, ActivityCategory, DatabaseManager};
+use screenpipe_vision::OcrEngine;
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_analytics_tables_count_words_without_the_text() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_video_chunk("test_video.mp4").await.unwrap();
+    let frame_id = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        frame_id,
+        "fn main() {\n    println!(\"hello\");\n}",
+        "",
+        "Code",
+        "main.rs - screenpipe",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+    let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+    db.insert_audio_transcription(audio_chunk_id, "let's ship it on friday", 0, "whisper")
+        .await
+        .unwrap();
+    let start = Utc::now() - Duration::hours(1);
+    db.insert_activity_session(
+        start,
+        start + Duration::minutes(30),
+        "Code",
+        ActivityCategory::Coding,
+        0.8,
+    )
+    .await
+    .unwrap();
+
+    let AnalyticsRows::Ocr(ocr) = analytics_rows(&db, AnalyticsTable::Ocr, None, None)
+        .await
+        .unwrap()
+    else {
+        panic!("expected the ocr rows");
+    };
+    assert_eq!(ocr.len(), 1);
+    assert_eq!(ocr[0].frame_id, frame_id);
+    assert_eq!(ocr[0].word_count, 5);
+    assert!(ocr[0].focused);
+
+    let AnalyticsRows::Transcripts(transcripts) =
+        analytics_rows(&db, AnalyticsTable::Transcripts, None, None)
+            .await
+            .unwrap()
+    else {
+        panic!("expected the transcript rows");
+    };
+    assert_eq!(transcripts[0].word_count, 5);
+
+    let sessions = analytics_rows(&db, AnalyticsTable::Sessions, Some(start), None)
+        .await
+        .unwrap();
+    let csv = String::from_utf8(write_analytics(&sessions, AnalyticsFormat::Csv).unwrap()).unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(
+        lines.next(),
+        Some("id,start_time,end_time,duration_secs,app_name,category,confidence")
+    );
+    assert!(lines.next().unwrap().contains(",1800.0,Code,coding,"));
+
+    let later = analytics_rows(&db, AnalyticsTable::Sessions, Some(Utc::now()), None)
+        .await
+        .unwrap();
+    assert!(later.is_empty());
+}
+
+#[tokio::test]
+async fn test_sql_runs_over_the_analytics_tables_only() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = Utc::now() - Duration::hours(3);
+    for (minutes, app_name) in [(0, "Code"), (40, "Slack"), (60, "Code")] {
+        db.insert_activity_session(
+            start + Duration::minutes(minutes),
+            start + Duration::minutes(minutes + 20),
+            app_name,
+            ActivityCategory::Coding,
+            0.8,
+        )
+        .await
+        .unwrap();
+    }
+
+    let result = query_analytics(
+        &db,
+        "SELECT app_name, ROUND(SUM(duration_secs) / 60) AS minutes FROM sessions \
+         GROUP BY app_name ORDER BY minutes DESC; -- per app",
+        None,
+        None,
+        100,
+    )
+    .await
+    .unwrap();
+    assert_eq!(result.columns, vec!["app_name", "minutes"]);
+    assert_eq!(result.rows[0], vec![json!("Code"), json!(40.0)]);
+    assert!(!result.truncated);
+
+    let result = query_analytics(&db, "SELECT id FROM sessions", None, None, 2)
+        .await
+        .unwrap();
+    assert_eq!(result.rows.len(), 2);
+    assert!(result.truncated);
+
+    for sql in [
+        "DELETE FROM sessions",
+        "SELECT 1; DROP TABLE sessions",
+        "ATTACH DATABASE 'db.sqlite' AS db",
+        "SELECT text FROM ocr_text",
+    ] {
+        assert!(
+            query_analytics(&db, sql, None, None, 100).await.is_err(),
+            "{} was run",
+            sql
+        );
+    }
+    // the ; of a string doesn't end the statement
+    let result = query_analytics(&db, "SELECT 'a;b' AS s", None, None, 100)
+        .await
+        .unwrap();
+    assert_eq!(result.rows, vec![vec![json!("a;b")]]);
+}
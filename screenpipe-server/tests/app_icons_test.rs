@@ -0,0 +1,65 @@
+use image::{DynamicImage, RgbaImage};
+use screenpipe_server::app_icons::{encode_icon, find_desktop_icon, ICON_SIZE};
+use screenpipe_server::AppIcons;
+use std::fs;
+
+fn icon(size: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        size,
+        size,
+        image::Rgba([40, 90, 200, 255]),
+    ))
+}
+
+#[test]
+fn test_finds_the_icon_of_a_desktop_entry() {
+    let dir = tempfile::tempdir().unwrap();
+    let applications = dir.path().join("applications");
+    let icons = dir.path().join("icons");
+    let themed = icons.join("hicolor/128x128/apps");
+    fs::create_dir_all(&applications).unwrap();
+    fs::create_dir_all(&themed).unwrap();
+    fs::write(
+        applications.join("org.gnome.TextEditor.desktop"),
+        "[Desktop Entry]\nName=Text Editor\nExec=gnome-text-editor %U\nIcon=org.gnome.TextEditor\n\n[Desktop Action new-window]\nName=New Window\nIcon=other\n",
+    )
+    .unwrap();
+    fs::write(
+        applications.join("code.desktop"),
+        "[Desktop Entry]\nName=Visual Studio Code\nStartupWMClass=Code\nExec=/usr/share/code/code --unity-launch %F\nIcon=vscode\n",
+    )
+    .unwrap();
+    icon(128)
+        .save(themed.join("org.gnome.TextEditor.png"))
+        .unwrap();
+    icon(64).save(icons.join("vscode.png")).unwrap();
+
+    let application_dirs = vec![applications];
+    let icon_dirs = vec![icons.clone()];
+    let find = |app_name| find_desktop_icon(app_name, &application_dirs, &icon_dirs);
+    assert_eq!(
+        find("gnome-text-editor"),
+        Some(themed.join("org.gnome.TextEditor.png"))
+    );
+    assert_eq!(
+        find("text editor"),
+        Some(themed.join("org.gnome.TextEditor.png"))
+    );
+    // by window class, in the pixmaps
+    assert_eq!(find("Code"), Some(icons.join("vscode.png")));
+    assert_eq!(find("New Window"), None);
+    assert_eq!(find("Slack"), None);
+}
+
+#[tokio::test]
+async fn test_serves_cached_icons() {
+    let dir = tempfile::tempdir().unwrap();
+    let icons = AppIcons::new(dir.path().to_path_buf());
+    let png = encode_icon(&icon(512)).unwrap();
+    let decoded = image::load_from_memory(&png).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (ICON_SIZE, ICON_SIZE));
+
+    fs::write(icons.icon_path("Google Chrome"), &png).unwrap();
+    assert_eq!(icons.icon("Google Chrome").await.unwrap(), Some(png));
+    assert!(icons.icon("no such app 7f3a").await.unwrap().is_none());
+}
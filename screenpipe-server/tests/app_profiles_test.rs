@@ -0,0 +1,32 @@
+use screenpipe_server::app_profiles::{load_app_profiles, APP_PROFILES_FILE};
+
+#[test]
+fn test_load_app_profiles() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(load_app_profiles(dir.path()).unwrap().is_empty());
+
+    let path = dir.path().join(APP_PROFILES_FILE);
+    std::fs::write(
+        &path,
+        r#"[{"app": "Code", "fps": 1, "max_width": 1920}, {"app": "VLC", "fps": 0.0167, "thumbnail_only": true}, {"app": "Notability", "handwriting": true}, {"app": "Signal", "text_only": true}]"#,
+    )
+    .unwrap();
+    let profiles = load_app_profiles(dir.path()).unwrap();
+    assert_eq!(profiles.len(), 4);
+    assert_eq!(profiles[0].max_width, Some(1920));
+    assert!(profiles[1].thumbnail_only);
+    assert!(profiles[2].handwriting && !profiles[0].handwriting);
+    assert!(profiles[3].text_only && !profiles[1].text_only);
+
+    for invalid in [
+        r#"[{"app": "Code", "fps": 0}]"#,
+        r#"[{"app": "Code", "max_width": 0}]"#,
+        r#"[{"app": "Code"}, {"app": "code", "fps": 1}]"#,
+        r#"[{"app": "Code", "quality": "high"}]"#,
+        r#"[{"app": "VLC", "thumbnail_only": true, "handwriting": true}]"#,
+        r#"[{"app": "VLC", "thumbnail_only": true, "text_only": true}]"#,
+    ] {
+        std::fs::write(&path, invalid).unwrap();
+        assert!(load_app_profiles(dir.path()).is_err(), "{}", invalid);
+    }
+}
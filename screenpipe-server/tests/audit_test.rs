@@ -0,0 +1,240 @@
+use axum::body::Body;
+use axum::http::{Method, Request, StatusCode};
+use axum::routing::{delete, get, post};
+use axum::Router;
+use chrono::{TimeZone, Utc};
+use crossbeam::queue::SegQueue;
+use screenpipe_core::{LlmConfig, PromptLibrary, PIPE_HEADER};
+use screenpipe_server::audit::audit_requests;
+use screenpipe_server::{
+    ApiCredentials, AppIcons, AppState, AuditAction, AuditEntry, CapturePause, ClipRenderer, DatabaseManager, LiveOcr, LlmService,
+    MarkerRecorder, TimelapseRenderer, ToolRegistry, Watchlist,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+async fn setup_test_app() -> (Router, Arc<DatabaseManager>) {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let app_state = Arc::new(AppState {
+        db: db.clone(),
+        vision_control: Arc::new(AtomicBool::new(false)),
+        audio_devices_control: Arc::new(SegQueue::new()),
+        devices_status: HashMap::new(),
+        app_start_time: Utc::now(),
+        llm: Arc::new(LlmService::new(
+            db.clone(),
+            LlmConfig::default(),
+            None,
+            None,
+            None,
+        )),
+        prompts: Arc::new(PromptLibrary::default()),
+        live_view: None,
+        timelapse: Arc::new(TimelapseRenderer::new(
+            db.clone(),
+            PathBuf::from("timelapses"),
+        )),
+        clips: Arc::new(ClipRenderer::new(db.clone(), PathBuf::from("clips"))),
+        capture_pause: Arc::new(CapturePause::new()),
+        encryption: None,
+        compliance: None,
+        consumer_redaction: None,
+        vault_export: None,
+        markers: Arc::new(MarkerRecorder::new(
+            db.clone(),
+            PathBuf::from("markers"),
+            None,
+            Duration::from_secs(30),
+        )),
+        profile: None,
+        watchlist: Watchlist::load(db.clone()).await.unwrap(),
+        app_icons: Arc::new(AppIcons::new(PathBuf::from("icons"))),
+        live_ocr: Arc::new(LiveOcr::new(0)),
+        automation: None,
+        llm_tools: Arc::new(ToolRegistry::default()),
+        credentials: Arc::new(ApiCredentials::new("app-token", Vec::new())),
+    });
+
+    let app = Router::new()
+        .route("/search", get(|| async { "results" }))
+        .route("/health", get(|| async { "ok" }))
+        .route("/clips", post(|body: String| async move { body }))
+        .route(
+            "/extraction/rules/:id",
+            delete(|| async { StatusCode::NOT_FOUND }),
+        )
+        .layer(axum::middleware::from_fn_with_state(
+            app_state.clone(),
+            audit_requests,
+        ))
+        .with_state(app_state);
+
+    (app, db)
+}
+
+async fn entries(db: &DatabaseManager) -> Vec<AuditEntry> {
+    db.get_audit_entries(None, None, None, None, 100, 0, None)
+        .await
+        .unwrap()
+}
+
+#[test]
+fn test_audit_action_of_request() {
+    assert_eq!(
+        AuditAction::of_request(&Method::GET, "/search"),
+        Some(AuditAction::Query)
+    );
+    assert_eq!(
+        AuditAction::of_request(&Method::GET, "/clips/1/video"),
+        Some(AuditAction::Export)
+    );
+    assert_eq!(
+        AuditAction::of_request(&Method::GET, "/analytics/ocr.parquet"),
+        Some(AuditAction::Export)
+    );
+    assert_eq!(
+        AuditAction::of_request(&Method::POST, "/analytics/query"),
+        Some(AuditAction::Query)
+    );
+    assert_eq!(
+        AuditAction::of_request(&Method::POST, "/timelapse"),
+        Some(AuditAction::Export)
+    );
+    assert_eq!(
+        AuditAction::of_request(&Method::DELETE, "/llm/cache"),
+        Some(AuditAction::Delete)
+    );
+    assert_eq!(
+        AuditAction::of_request(&Method::POST, "/capture/pause"),
+        Some(AuditAction::Write)
+    );
+    assert_eq!(AuditAction::of_request(&Method::GET, "/health"), None);
+    assert_eq!(AuditAction::of_request(&Method::GET, "/live/0.ts"), None);
+}
+
+#[tokio::test]
+async fn test_requests_are_recorded() {
+    let (app, db) = setup_test_app().await;
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri("/search?q=invoice&token=secret&start_time=2024-08-01T00:00:00Z")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method(Method::POST)
+                .uri("/clips")
+                .header(PIPE_HEADER, "pipeline.js")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    r#"{"start_time": "2024-08-01T10:00:00Z", "end_time": "2024-08-01T11:00:00Z"}"#,
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    app.clone()
+        .oneshot(
+            Request::builder()
+                .method(Method::DELETE)
+                .uri("/extraction/rules/1")
+                .header("authorization", "Bearer secret")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    app.oneshot(
+        Request::builder()
+            .uri("/health")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let entries = entries(&db).await;
+    assert_eq!(entries.len(), 3);
+
+    let deletion = &entries[0];
+    assert_eq!(deletion.action, AuditAction::Delete);
+    assert_eq!(deletion.status, 404);
+    assert!(deletion.actor.starts_with("token:"));
+    assert!(!deletion.actor.contains("secret"));
+
+    let export = &entries[1];
+    assert_eq!(export.action, AuditAction::Export);
+    assert_eq!(export.actor, "pipe:pipeline.js");
+    assert_eq!(
+        export.range_start,
+        Some(Utc.with_ymd_and_hms(2024, 8, 1, 10, 0, 0).unwrap())
+    );
+    assert_eq!(
+        export.range_end,
+        Some(Utc.with_ymd_and_hms(2024, 8, 1, 11, 0, 0).unwrap())
+    );
+
+    let query = &entries[2];
+    assert_eq!(query.action, AuditAction::Query);
+    assert_eq!(query.path, "/search");
+    assert_eq!(
+        query.query.as_deref(),
+        Some("q=invoice&start_time=2024-08-01T00:00:00Z")
+    );
+    // the same token, whether in the query or a header
+    assert_eq!(query.actor, deletion.actor);
+    assert_eq!(
+        query.range_start,
+        Some(Utc.with_ymd_and_hms(2024, 8, 1, 0, 0, 0).unwrap())
+    );
+
+    let deletions = db
+        .get_audit_entries(None, None, None, Some(AuditAction::Delete), 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(deletions.len(), 1);
+    assert_eq!(
+        db.count_audit_entries(None, None, Some("pipe:pipeline.js"), None)
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_audit_log_is_append_only() {
+    let (app, db) = setup_test_app().await;
+    app.oneshot(
+        Request::builder()
+            .uri("/search")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+
+    assert!(sqlx::query("UPDATE audit_log SET actor = 'someone else'")
+        .execute(&db.pool)
+        .await
+        .is_err());
+    assert!(sqlx::query("DELETE FROM audit_log")
+        .execute(&db.pool)
+        .await
+        .is_err());
+    assert_eq!(entries(&db).await.len(), 1);
+}
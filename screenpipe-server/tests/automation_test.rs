@@ -0,0 +1,199 @@
+use anyhow::{bail, Result};
+use screenpipe_server::automation::{AutomationKey, InputDevice, Modifier, MAX_TEXT_LEN};
+use screenpipe_server::{
+    load_automation_grants, Automation, AutomationAction, AutomationStatus, DatabaseManager,
+};
+use std::sync::{Arc, Mutex};
+
+#[derive(Default)]
+struct RecordingDevice {
+    performed: Mutex<Vec<AutomationAction>>,
+}
+
+impl InputDevice for RecordingDevice {
+    fn perform(&self, actions: &[AutomationAction]) -> Result<()> {
+        self.performed.lock().unwrap().extend_from_slice(actions);
+        Ok(())
+    }
+}
+
+struct BrokenDevice;
+
+impl InputDevice for BrokenDevice {
+    fn perform(&self, _actions: &[AutomationAction]) -> Result<()> {
+        bail!("no display")
+    }
+}
+
+fn grants(
+    dir: &std::path::Path,
+    grants: serde_json::Value,
+) -> Result<Vec<screenpipe_server::AutomationGrant>> {
+    let path = dir.join("automation.json");
+    std::fs::write(&path, serde_json::json!({ "grants": grants }).to_string()).unwrap();
+    load_automation_grants(&path)
+}
+
+fn dismiss_grant() -> serde_json::Value {
+    serde_json::json!({
+        "pipe": "dismiss-dialog",
+        "actions": ["click", "key"],
+        "apps": ["Finder"],
+        "max_per_minute": 3,
+    })
+}
+
+fn escape() -> AutomationAction {
+    AutomationAction::Key {
+        key: "escape".to_string(),
+        modifiers: Vec::new(),
+    }
+}
+
+fn finder() -> Option<(String, String)> {
+    Some(("Finder".to_string(), "Update available".to_string()))
+}
+
+#[test]
+fn test_grants_and_actions_are_validated() {
+    let dir = tempfile::tempdir().unwrap();
+    assert_eq!(
+        grants(dir.path(), serde_json::json!([dismiss_grant()]))
+            .unwrap()
+            .len(),
+        1
+    );
+    assert!(grants(
+        dir.path(),
+        serde_json::json!([dismiss_grant(), dismiss_grant()])
+    )
+    .is_err());
+    let mut nothing_allowed = dismiss_grant();
+    nothing_allowed["actions"] = serde_json::json!([]);
+    assert!(grants(dir.path(), serde_json::json!([nothing_allowed])).is_err());
+
+    assert_eq!(
+        "Esc".parse::<AutomationKey>().unwrap(),
+        AutomationKey::Escape
+    );
+    assert_eq!(
+        "f5".parse::<AutomationKey>().unwrap(),
+        AutomationKey::Function(5)
+    );
+    assert_eq!(
+        "a".parse::<AutomationKey>().unwrap(),
+        AutomationKey::Char('a')
+    );
+    assert!("f13".parse::<AutomationKey>().is_err());
+    assert_eq!("cmd".parse::<Modifier>().unwrap(), Modifier::Meta);
+
+    let action: AutomationAction = serde_json::from_value(
+        serde_json::json!({"type": "key", "key": "w", "modifiers": ["ctrl"]}),
+    )
+    .unwrap();
+    assert!(action.validate().is_ok());
+    let action: AutomationAction = serde_json::from_value(
+        serde_json::json!({"type": "key", "key": "w", "modifiers": ["hyper"]}),
+    )
+    .unwrap();
+    assert!(action.validate().is_err());
+    let action = AutomationAction::Type {
+        text: "a".repeat(MAX_TEXT_LEN + 1),
+    };
+    assert!(action.validate().is_err());
+}
+
+#[tokio::test]
+async fn test_only_granted_actions_are_synthesized() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let dir = tempfile::tempdir().unwrap();
+    let device = Arc::new(RecordingDevice::default());
+    let automation = Automation::new(
+        db.clone(),
+        grants(dir.path(), serde_json::json!([dismiss_grant()])).unwrap(),
+        device.clone(),
+    );
+
+    let records = automation
+        .perform("dismiss-dialog", vec![escape()], finder())
+        .await
+        .unwrap();
+    assert_eq!(records[0].status, AutomationStatus::Done);
+    assert_eq!(records[0].app_name.as_deref(), Some("Finder"));
+    assert_eq!(*device.performed.lock().unwrap(), [escape()]);
+
+    let typing = AutomationAction::Type {
+        text: "hello".to_string(),
+    };
+    let records = automation
+        .perform("dismiss-dialog", vec![escape(), typing], finder())
+        .await
+        .unwrap();
+    assert!(records
+        .iter()
+        .all(|record| record.status == AutomationStatus::Refused));
+    assert_eq!(
+        device.performed.lock().unwrap().len(),
+        1,
+        "none of the actions is done"
+    );
+
+    let terminal = Some(("Terminal".to_string(), "zsh".to_string()));
+    let records = automation
+        .perform("dismiss-dialog", vec![escape()], terminal)
+        .await
+        .unwrap();
+    assert_eq!(records[0].status, AutomationStatus::Refused);
+    let records = automation
+        .perform("other-pipe", vec![escape()], finder())
+        .await
+        .unwrap();
+    assert_eq!(records[0].status, AutomationStatus::Refused);
+
+    let records = automation
+        .perform(
+            "dismiss-dialog",
+            vec![escape(), escape(), escape()],
+            finder(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(
+        records[0].status,
+        AutomationStatus::Refused,
+        "3 actions a minute"
+    );
+
+    let trail = db
+        .get_automation_records(None, None, None, None, 100, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(trail.len(), 8);
+    assert_eq!(trail.last().unwrap().action, escape());
+    let refused = db
+        .count_automation_records(None, Some(AutomationStatus::Refused), None, None)
+        .await
+        .unwrap();
+    assert_eq!(refused, 7);
+    assert!(sqlx::query("DELETE FROM automation_actions")
+        .execute(&db.pool)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_failed_input_is_recorded() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let dir = tempfile::tempdir().unwrap();
+    let automation = Automation::new(
+        db.clone(),
+        grants(dir.path(), serde_json::json!([dismiss_grant()])).unwrap(),
+        Arc::new(BrokenDevice),
+    );
+    let records = automation
+        .perform("dismiss-dialog", vec![escape()], finder())
+        .await
+        .unwrap();
+    assert_eq!(records[0].status, AutomationStatus::Failed);
+    assert_eq!(records[0].reason.as_deref(), Some("no display"));
+}
@@ -0,0 +1,261 @@
+use chrono::{TimeZone, Utc};
+use screenpipe_server::backup::{
+    part_count, pbkdf2_sha256, restore_backup, throttle_delay, wait_reason, BackupConfig,
+    BackupKey, BackupSource,
+};
+use screenpipe_server::integrity::ChunkKind;
+use screenpipe_server::power::PowerState;
+use screenpipe_server::restore::{pick_snapshot, snapshot_time, swap_database, DatabaseSnapshot};
+use screenpipe_server::{
+    check_database_file, get_database_file_chunks, set_database_file_chunk_path, DatabaseManager,
+};
+use std::time::Duration;
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn config() -> BackupConfig {
+    serde_json::from_value(serde_json::json!({
+        "destination": {"kind": "webdav", "url": "https://dav.example.com/screenpipe/"},
+        "passphrase_env": "SCREENPIPE_BACKUP_PASSPHRASE",
+        "media": ["audio"],
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_pbkdf2_matches_the_rfc_7914_vectors() {
+    assert_eq!(
+        hex(&pbkdf2_sha256(b"password", b"salt", 1)),
+        "120fb6cffcf8b32c43e7225256c4f837a86548c92ccc35480805987cb70be17b"
+    );
+    assert_eq!(
+        hex(&pbkdf2_sha256(b"password", b"salt", 2)),
+        "ae4d0c95af6b46d32d0adff928f06dd02a303f8ef3c251dfd6e2d85a95474c43"
+    );
+}
+
+#[test]
+fn test_backup_parts_restore_in_order_only() {
+    let key = BackupKey::derive("correct horse", [7; 16]);
+    let parts = [
+        key.encrypt_part(1, false, b"SQLite format 3").unwrap(),
+        key.encrypt_part(2, false, b" and more").unwrap(),
+        key.encrypt_part(3, true, b" pages").unwrap(),
+    ];
+    let backup = parts.concat();
+
+    let mut restored = Vec::new();
+    let written = restore_backup(&mut backup.as_slice(), &mut restored, "correct horse").unwrap();
+    assert_eq!(restored, b"SQLite format 3 and more pages");
+    assert_eq!(written, restored.len() as u64);
+
+    // cut before the last part
+    let truncated = parts[..2].concat();
+    assert!(restore_backup(&mut truncated.as_slice(), &mut Vec::new(), "correct horse").is_err());
+    // parts swapped
+    let swapped = [parts[1].clone(), parts[0].clone(), parts[2].clone()].concat();
+    assert!(restore_backup(&mut swapped.as_slice(), &mut Vec::new(), "correct horse").is_err());
+    assert!(restore_backup(&mut backup.as_slice(), &mut Vec::new(), "wrong horse").is_err());
+    // a length no part has isn't allocated
+    let mut oversized = parts[0].clone();
+    oversized[29..33].copy_from_slice(&u32::MAX.to_be_bytes());
+    assert!(restore_backup(&mut oversized.as_slice(), &mut Vec::new(), "correct horse").is_err());
+}
+
+#[test]
+fn test_uploads_are_throttled_and_wait_for_an_idle_machine_on_ac() {
+    assert_eq!(part_count(0, 8 << 20), 1);
+    assert_eq!(part_count(8 << 20, 8 << 20), 1);
+    assert_eq!(part_count((8 << 20) + 1, 8 << 20), 2);
+
+    // 1 MB is 8000 kilobits, 4 seconds at 2000 kbps
+    assert_eq!(
+        throttle_delay(Some(2000), 1_000_000, Duration::from_secs(1)),
+        Duration::from_secs(3)
+    );
+    assert_eq!(
+        throttle_delay(Some(2000), 1_000_000, Duration::from_secs(5)),
+        Duration::ZERO
+    );
+    assert_eq!(
+        throttle_delay(None, 1_000_000, Duration::ZERO),
+        Duration::ZERO
+    );
+
+    let config = config();
+    let on_ac = PowerState::default();
+    let on_battery = PowerState {
+        on_battery: true,
+        ..Default::default()
+    };
+    assert_eq!(wait_reason(&config, &on_ac, 5.0, false), None);
+    assert_eq!(
+        wait_reason(&config, &on_battery, 5.0, false),
+        Some("on battery")
+    );
+    assert_eq!(
+        wait_reason(&config, &on_ac, 80.0, false),
+        Some("the machine is busy")
+    );
+    assert_eq!(
+        wait_reason(&config, &on_ac, 5.0, true),
+        Some("background jobs are paused")
+    );
+}
+
+#[tokio::test]
+async fn test_chunks_are_backed_up_once_and_uploads_resume() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let first = db.insert_audio_chunk("audio_1.mp4").await.unwrap();
+    let second = db.insert_audio_chunk("audio_2.mp4").await.unwrap();
+    db.insert_video_chunk("video_1.mp4").await.unwrap();
+
+    // the video chunk is still recorded
+    assert!(db
+        .get_chunks_to_back_up(ChunkKind::Video, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        db.get_chunks_to_back_up(ChunkKind::Audio, 10)
+            .await
+            .unwrap(),
+        vec![
+            (first, "audio_1.mp4".to_string()),
+            (second, "audio_2.mp4".to_string())
+        ]
+    );
+
+    let id = db
+        .insert_backup_upload(
+            BackupSource::Audio,
+            Some(first),
+            "audio_1.mp4",
+            "audio/audio_1.mp4.spbk",
+            20 << 20,
+            "upload-1",
+        )
+        .await
+        .unwrap();
+    db.set_backup_upload_parts(id, &["\"etag-1\"".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(
+        db.get_chunks_to_back_up(ChunkKind::Audio, 10)
+            .await
+            .unwrap(),
+        vec![(second, "audio_2.mp4".to_string())]
+    );
+    let unfinished = db.get_unfinished_backup_uploads().await.unwrap();
+    assert_eq!(unfinished.len(), 1);
+    assert_eq!(unfinished[0].source, BackupSource::Audio);
+    assert_eq!(unfinished[0].parts, vec!["\"etag-1\"".to_string()]);
+
+    db.complete_backup_upload(id).await.unwrap();
+    assert!(db.get_unfinished_backup_uploads().await.unwrap().is_empty());
+    assert!(db.get_last_database_backup().await.unwrap().is_none());
+
+    // the salt is made once
+    let salt = db.backup_salt(&[1; 16]).await.unwrap();
+    assert_eq!(db.backup_salt(&[2; 16]).await.unwrap(), salt);
+}
+
+fn snapshot(object_key: &str) -> DatabaseSnapshot {
+    DatabaseSnapshot {
+        object_key: object_key.to_string(),
+        taken_at: snapshot_time(object_key).unwrap(),
+        size: 1000,
+    }
+}
+
+#[test]
+fn test_the_snapshot_before_the_point_in_time_is_restored() {
+    let snapshots = [
+        snapshot("database/2024-09-15T03-00-00Z.sqlite.spbk"),
+        snapshot("database/2024-09-16T03-00-00Z.sqlite.spbk"),
+    ];
+    assert_eq!(
+        snapshot_time("database/2024-09-16T03-00-00Z.sqlite.spbk"),
+        Some(Utc.with_ymd_and_hms(2024, 9, 16, 3, 0, 0).unwrap())
+    );
+    assert_eq!(snapshot_time("audio/audio_1.mp4.spbk"), None);
+
+    let at = |day, hour| Some(Utc.with_ymd_and_hms(2024, 9, day, hour, 0, 0).unwrap());
+    assert_eq!(pick_snapshot(&snapshots, None), Some(&snapshots[1]));
+    assert_eq!(pick_snapshot(&snapshots, at(16, 2)), Some(&snapshots[0]));
+    assert_eq!(pick_snapshot(&snapshots, at(16, 3)), Some(&snapshots[1]));
+    assert_eq!(pick_snapshot(&snapshots, at(14, 12)), None);
+}
+
+#[tokio::test]
+async fn test_a_restored_database_is_checked_and_swapped_in() {
+    let dir = tempfile::tempdir().unwrap();
+    let restored = dir.path().join("restored.sqlite");
+    let restored_path = restored.to_string_lossy().to_string();
+    let db = DatabaseManager::new(&restored_path).await.unwrap();
+    let chunk_id = db
+        .insert_audio_chunk("/old/data/audio_1.mp4.enc")
+        .await
+        .unwrap();
+    db.pool.close().await;
+
+    assert!(check_database_file(&restored_path, None)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(
+        get_database_file_chunks(&restored_path, None)
+            .await
+            .unwrap(),
+        vec![(
+            ChunkKind::Audio,
+            chunk_id,
+            "/old/data/audio_1.mp4.enc".to_string()
+        )]
+    );
+    set_database_file_chunk_path(
+        &restored_path,
+        None,
+        ChunkKind::Audio,
+        chunk_id,
+        "audio_1.mp4",
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        get_database_file_chunks(&restored_path, None)
+            .await
+            .unwrap()[0]
+            .2,
+        "audio_1.mp4"
+    );
+
+    let not_screenpipe = dir.path().join("other.sqlite");
+    std::fs::write(&not_screenpipe, b"").unwrap();
+    assert!(check_database_file(&not_screenpipe.to_string_lossy(), None)
+        .await
+        .is_err());
+
+    let data_dir = dir.path().join("screenpipe");
+    std::fs::create_dir_all(&data_dir).unwrap();
+    std::fs::write(data_dir.join("db.sqlite"), b"current").unwrap();
+    std::fs::write(data_dir.join("db.sqlite-wal"), b"current wal").unwrap();
+    let previous = swap_database(&restored, &data_dir, "2024-09-17T10-00-00Z")
+        .unwrap()
+        .unwrap();
+    assert_eq!(std::fs::read(&previous).unwrap(), b"current");
+    assert_eq!(
+        std::fs::read(format!("{}-wal", previous.display())).unwrap(),
+        b"current wal"
+    );
+    assert!(!data_dir.join("db.sqlite-wal").exists());
+    assert!(!restored.exists());
+    assert!(
+        check_database_file(&data_dir.join("db.sqlite").to_string_lossy(), None)
+            .await
+            .unwrap()
+            .is_empty()
+    );
+}
@@ -0,0 +1,128 @@
+use chrono::Utc;
+use image::{DynamicImage, RgbaImage};
+use screenpipe_server::browser_pages::{
+    browser_page_for_window, is_browser, merge_browser_pages, report_browser_page, BrowserPage,
+};
+use screenpipe_server::{ContentType, DatabaseManager, PendingWrite, SearchResult};
+use screenpipe_vision::core::{BrowserPageSource, WindowOcrResult};
+use screenpipe_vision::CaptureResult;
+use std::sync::Arc;
+use std::time::Instant;
+
+fn window(app_name: &str, window_name: &str) -> WindowOcrResult {
+    WindowOcrResult {
+        window_name: window_name.to_string(),
+        app_name: app_name.to_string(),
+        image: Arc::new(DynamicImage::ImageRgba8(RgbaImage::new(40, 40))),
+        text: "Gettng startd".to_string(),
+        text_json: Vec::new(),
+        focused: true,
+        position: (0, 0),
+        browser_page: None,
+    }
+}
+
+fn page(url: &str, title: &str, text: &str, browser: Option<&str>) -> BrowserPage {
+    BrowserPage {
+        url: url.to_string(),
+        title: title.to_string(),
+        text: text.to_string(),
+        browser: browser.map(str::to_string),
+        scroll_y: Some(1200.0),
+    }
+}
+
+#[test]
+fn test_recognizes_browser_windows() {
+    assert!(is_browser("Google Chrome"));
+    assert!(is_browser("Microsoft Edge"));
+    assert!(is_browser("firefox"));
+    assert!(is_browser("Arc"));
+    assert!(!is_browser("Archive Utility"));
+    assert!(!is_browser("Slack"));
+}
+
+#[test]
+fn test_page_text_replaces_the_ocr_of_its_window() {
+    report_browser_page(page(
+        "https://example.com/docs",
+        "Docs - Example",
+        "old text",
+        Some("chrome"),
+    ));
+    // pushed again after scrolling, the newest one is used
+    report_browser_page(page(
+        "https://example.com/docs",
+        "Docs - Example",
+        "Getting started",
+        Some("chrome"),
+    ));
+    // another browser's tab with the same title
+    assert!(browser_page_for_window("Firefox", "Docs - Example — Mozilla Firefox").is_none());
+    // not a browser
+    assert!(browser_page_for_window("Notes", "Docs - Example").is_none());
+
+    let mut frame = CaptureResult {
+        image: Arc::new(DynamicImage::ImageRgba8(RgbaImage::new(40, 40))),
+        png: Default::default(),
+        frame_number: 1,
+        timestamp: Instant::now(),
+        window_ocr_results: vec![
+            window("Google Chrome", "Docs - Example - Google Chrome"),
+            window("Google Chrome", "Inbox - Google Chrome"),
+        ],
+        private_windows: Vec::new(),
+    };
+    merge_browser_pages(&mut frame);
+    let docs = &frame.window_ocr_results[0];
+    assert_eq!(docs.text, "Getting started");
+    assert_eq!(
+        docs.browser_page,
+        Some(BrowserPageSource {
+            url: "https://example.com/docs".to_string(),
+            scroll_y: Some(1200.0),
+        })
+    );
+    // no page was pushed for it, its OCR is kept
+    let inbox = &frame.window_ocr_results[1];
+    assert_eq!(inbox.text, "Gettng startd");
+    assert!(inbox.browser_page.is_none());
+}
+
+#[tokio::test]
+async fn test_search_returns_the_url_of_pushed_pages() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_batch(&[
+        PendingWrite::VideoChunk {
+            file_path: "chunk.mp4".to_string(),
+        },
+        PendingWrite::Ocr {
+            timestamp: Utc::now(),
+            text: "Getting started".to_string(),
+            text_json: String::new(),
+            app_name: "Google Chrome".to_string(),
+            window_name: "Docs - Example".to_string(),
+            ocr_engine: "BrowserExtension".to_string(),
+            focused: true,
+            perceptual_hash: None,
+            browser_url: Some("https://example.com/docs".to_string()),
+            scroll_y: Some(1200.0),
+        },
+    ])
+    .await
+    .unwrap();
+
+    let results = db
+        .search("started", ContentType::OCR, 10, 0, None, None, None, None)
+        .await
+        .unwrap();
+    let [SearchResult::OCR(result)] = results.as_slice() else {
+        panic!("expected one OCR result, got {:?}", results);
+    };
+    assert_eq!(
+        result.browser_url.as_deref(),
+        Some("https://example.com/docs")
+    );
+    assert_eq!(result.scroll_y, Some(1200.0));
+    assert_eq!(result.ocr_engine, "BrowserExtension");
+}
@@ -0,0 +1,141 @@
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_server::{
+    calendar_feed, focus_blocks, label_meetings, ActivityCategory, ActivitySession, DatabaseManager,
+};
+
+fn ics_time(time: DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+#[tokio::test]
+async fn test_meetings_are_labeled_with_calendar_events() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = Utc::now() - Duration::hours(6);
+    let at = |minutes| start + Duration::minutes(minutes);
+    let sync = db
+        .insert_meeting_summary(
+            at(5),
+            at(40),
+            "Went over the roadmap.",
+            &[],
+            &[],
+            "llama3.2",
+            1,
+        )
+        .await
+        .unwrap();
+    let unplanned = db
+        .insert_meeting_summary(at(180), at(200), "Hallway chat.", &[], &[], "llama3.2", 1)
+        .await
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let calendar = dir.path().join("work.ics");
+    std::fs::write(
+        &calendar,
+        format!(
+            "BEGIN:VCALENDAR\nBEGIN:VEVENT\nUID:1\nSUMMARY:Standup\nDTSTART:{}\nDTEND:{}\nEND:VEVENT\n\
+             BEGIN:VEVENT\nUID:2\nSUMMARY:Sync with Bob\nDTSTART:{}\nDTEND:{}\n\
+             ATTENDEE;CN=Bob:mailto:bob@example.com\nEND:VEVENT\nEND:VCALENDAR\n",
+            ics_time(at(0)),
+            ics_time(at(10)),
+            ics_time(at(10)),
+            ics_time(at(40)),
+        ),
+    )
+    .unwrap();
+    let calendars = vec![calendar.to_string_lossy().to_string()];
+
+    assert_eq!(label_meetings(&db, &calendars).await.unwrap(), 1);
+
+    let meeting = db.get_meeting_summary(sync).await.unwrap().unwrap();
+    assert_eq!(meeting.calendar_title.as_deref(), Some("Sync with Bob"));
+    assert_eq!(meeting.calendar_attendees, vec!["Bob"]);
+    let meeting = db.get_meeting_summary(unplanned).await.unwrap().unwrap();
+    assert!(meeting.calendar_title.is_none());
+
+    // found by the event's title and attendees
+    let found = db
+        .get_meeting_summaries(Some("sync with bob"), 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].id, sync);
+    assert_eq!(db.count_meeting_summaries(Some("Bob")).await.unwrap(), 1);
+
+    // labeled meetings are left alone
+    assert_eq!(label_meetings(&db, &calendars).await.unwrap(), 0);
+}
+
+fn session(
+    id: i64,
+    start: DateTime<Utc>,
+    minutes: i64,
+    app_name: &str,
+    category: ActivityCategory,
+) -> ActivitySession {
+    ActivitySession {
+        id,
+        start_time: start,
+        end_time: start + Duration::minutes(minutes),
+        app_name: app_name.to_string(),
+        category,
+        confidence: 0.9,
+        created_at: start,
+        focus_mode: None,
+    }
+}
+
+#[test]
+fn test_focus_sessions_are_merged_into_blocks() {
+    let start = Utc::now() - Duration::hours(6);
+    let at = |minutes| start + Duration::minutes(minutes);
+    let sessions = vec![
+        session(1, at(0), 20, "Code", ActivityCategory::Coding),
+        // a look at the chat doesn't end the block
+        session(2, at(21), 2, "Slack", ActivityCategory::Other),
+        session(3, at(24), 30, "Terminal", ActivityCategory::Coding),
+        session(4, at(120), 10, "Code", ActivityCategory::Coding),
+        session(5, at(200), 60, "Mail", ActivityCategory::Email),
+    ];
+    let blocks = focus_blocks(&sessions);
+    assert_eq!(blocks.len(), 1, "short and other blocks are left out");
+    assert_eq!(blocks[0].id, 1);
+    assert_eq!((blocks[0].start_time, blocks[0].end_time), (at(0), at(54)));
+    let apps: Vec<&str> = blocks[0].apps.iter().map(|(app, _)| app.as_str()).collect();
+    assert_eq!(apps, vec!["Terminal", "Code"]);
+}
+
+#[tokio::test]
+async fn test_calendar_feed_has_meetings_and_focus_blocks() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = Utc::now() - Duration::hours(6);
+    let at = |minutes| start + Duration::minutes(minutes);
+    let meeting = db
+        .insert_meeting_summary(
+            at(60),
+            at(90),
+            "Went over the roadmap.",
+            &["ship on friday".to_string()],
+            &[],
+            "llama3.2",
+            1,
+        )
+        .await
+        .unwrap();
+    db.set_meeting_calendar_event(meeting, "Planning", &[])
+        .await
+        .unwrap();
+    db.insert_activity_session(at(100), at(160), "Code", ActivityCategory::Coding, 0.9)
+        .await
+        .unwrap();
+
+    let ics = calendar_feed(&db, start, Utc::now()).await.unwrap();
+    assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+    assert!(ics.contains(&format!("UID:meeting-{}@screenpipe", meeting)));
+    assert!(ics.contains("SUMMARY:Planning"));
+    assert!(ics.contains("Decisions:\\n- ship on friday"));
+    assert!(ics.contains("SUMMARY:Focus: Code"));
+    assert!(ics.contains(&format!("DTSTART:{}", ics_time(at(100)))));
+    assert_eq!(ics.matches("BEGIN:VEVENT").count(), 2);
+}
@@ -0,0 +1,36 @@
+use screenpipe_server::{machine_capabilities, set_engine_selection, FeatureCapabilities};
+
+#[test]
+fn test_capabilities_mark_the_engines_in_use() {
+    set_engine_selection("unstructured", Some("whisper-large"), false);
+
+    let capabilities = machine_capabilities(FeatureCapabilities {
+        encryption: true,
+        llm_model: "llama3.1".to_string(),
+        ..Default::default()
+    });
+
+    let in_use = |engines: &[screenpipe_server::EngineCapability]| {
+        engines
+            .iter()
+            .filter(|engine| engine.in_use)
+            .map(|engine| engine.name)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(in_use(&capabilities.ocr_engines), vec!["unstructured"]);
+    assert_eq!(in_use(&capabilities.stt_engines), vec!["whisper-large"]);
+    // no worker to send the work to
+    let remote = capabilities
+        .ocr_engines
+        .iter()
+        .find(|engine| engine.name == "remote")
+        .unwrap();
+    assert!(!remote.available);
+    assert!(remote.note.is_some());
+
+    assert!(capabilities.features.encryption);
+    assert!(!capabilities.features.live_view);
+    assert_eq!(capabilities.features.llm_model, "llama3.1");
+    assert_eq!(capabilities.os, std::env::consts::OS);
+    assert!(!capabilities.capture.audio.is_empty());
+}
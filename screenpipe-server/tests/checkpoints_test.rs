@@ -0,0 +1,53 @@
+use chrono::Utc;
+use screenpipe_server::{DatabaseManager, PendingWrite};
+
+#[tokio::test]
+async fn test_checkpoint_is_overwritten() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    assert_eq!(
+        db.get_checkpoint("extraction.frame_id").await.unwrap(),
+        None
+    );
+
+    db.set_checkpoint("extraction.frame_id", 42).await.unwrap();
+    db.set_checkpoint("extraction.frame_id", 57).await.unwrap();
+    assert_eq!(
+        db.get_checkpoint("extraction.frame_id").await.unwrap(),
+        Some(57)
+    );
+}
+
+#[tokio::test]
+async fn test_pending_transcription_is_removed_with_its_transcript() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_pending_transcription("mic_1.mp4", "MacBook Pro Microphone (input)")
+        .await
+        .unwrap();
+    db.insert_pending_transcription("mic_2.mp4", "MacBook Pro Microphone (input)")
+        .await
+        .unwrap();
+    // journaling the same chunk twice keeps one entry
+    db.insert_pending_transcription("mic_2.mp4", "MacBook Pro Microphone (input)")
+        .await
+        .unwrap();
+
+    db.insert_batch(&[PendingWrite::Transcription {
+        timestamp: Utc::now(),
+        file_path: "mic_1.mp4".to_string(),
+        transcription: "hello".to_string(),
+        engine: "WhisperTiny".to_string(),
+    }])
+    .await
+    .unwrap();
+
+    // the second chunk was never transcribed, it is resumed on the next start
+    assert_eq!(
+        db.get_pending_transcriptions().await.unwrap(),
+        [(
+            "mic_2.mp4".to_string(),
+            "MacBook Pro Microphone (input)".to_string()
+        )]
+    );
+    db.remove_pending_transcription("mic_2.mp4").await.unwrap();
+    assert!(db.get_pending_transcriptions().await.unwrap().is_empty());
+}
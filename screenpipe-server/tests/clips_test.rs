@@ -0,0 +1,116 @@
+use chrono::{Duration, Utc};
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_server::clips::{apply_redaction, pii_boxes, RedactionBox, WindowMatch};
+use screenpipe_server::{ClipOptions, RedactionStyle};
+use screenpipe_vision::OcrWord;
+
+fn word(text: &str, left: i32, line: i32) -> OcrWord {
+    OcrWord {
+        text: text.to_string(),
+        left,
+        top: 100,
+        width: 40,
+        height: 20,
+        confidence: 90.0,
+        line: (1, 1, line),
+    }
+}
+
+#[test]
+fn test_pii_boxes_cover_numbers_split_over_words() {
+    let words = vec![
+        word("card", 0, 1),
+        word("1234", 50, 1),
+        word("5678", 100, 1),
+        word("9012", 150, 1),
+        word("3456", 200, 1),
+        word("hello", 0, 2),
+    ];
+
+    let boxes = pii_boxes(&words);
+
+    assert_eq!(
+        boxes,
+        vec![RedactionBox {
+            x: 46,
+            y: 96,
+            width: 198,
+            height: 28
+        }]
+    );
+}
+
+#[test]
+fn test_pii_boxes_cover_secrets() {
+    let words = vec![
+        word("token:", 0, 1),
+        word("eyJhbGciOiJIUzI1NiJ9.eyJzdWIiOiIxIn0.c2ln", 50, 1),
+        word("4242", 0, 2),
+        word("4242", 50, 2),
+        word("4242", 100, 2),
+        word("4242", 150, 2),
+    ];
+
+    let boxes = pii_boxes(&words);
+
+    assert_eq!(
+        boxes,
+        vec![
+            RedactionBox {
+                x: 46,
+                y: 96,
+                width: 48,
+                height: 28
+            },
+            RedactionBox {
+                x: 0,
+                y: 96,
+                width: 194,
+                height: 28
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_apply_redaction_is_clipped_to_image() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::from_pixel(10, 10, Rgba([255, 255, 255, 255])));
+    let boxes = [RedactionBox {
+        x: 8,
+        y: 8,
+        width: 50,
+        height: 50,
+    }];
+
+    let redacted = apply_redaction(&image, &boxes, RedactionStyle::Black);
+
+    assert_eq!(redacted.get_pixel(9, 9), &Rgba([0, 0, 0, 255]));
+    assert_eq!(redacted.get_pixel(7, 7), &Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+fn test_window_match_and_validation() {
+    let pattern = WindowMatch {
+        app_name: Some("1password".to_string()),
+        window_name: None,
+    };
+    assert!(pattern.matches("1Password 7", "Vault"));
+    assert!(!pattern.matches("Slack", "1password"));
+
+    let start = Utc::now() - Duration::minutes(5);
+    let options = ClipOptions {
+        start_time: start,
+        end_time: start + Duration::minutes(5),
+        redact_pii: false,
+        redact_windows: vec![WindowMatch::default()],
+        redaction: RedactionStyle::Black,
+    };
+    assert!(options.validate().is_err());
+
+    let options = ClipOptions {
+        redact_windows: vec![pattern],
+        ..options
+    };
+    assert!(options.validate().is_ok());
+    assert!(options.is_redacted());
+}
@@ -0,0 +1,40 @@
+use chrono::Duration;
+use screenpipe_server::compaction::{compact_command, compacted_path};
+use screenpipe_server::{CompactionCodec, CompactionOptions};
+use std::path::PathBuf;
+
+fn options(codec: CompactionCodec, scale: f64) -> CompactionOptions {
+    CompactionOptions {
+        older_than: Duration::days(30),
+        codec,
+        scale,
+    }
+}
+
+#[test]
+fn test_compacted_path_is_next_to_original() {
+    assert_eq!(
+        compacted_path("/data/monitor_1_2024-08-01_10-00-00.mp4"),
+        PathBuf::from("/data/monitor_1_2024-08-01_10-00-00_compact.mp4")
+    );
+}
+
+#[test]
+fn test_compact_command_keeps_every_frame() {
+    let args = compact_command("in.mp4", "out.mp4", &options(CompactionCodec::Av1, 1.0)).args();
+
+    let fps_mode = args.iter().position(|a| a == "-fps_mode").unwrap();
+    assert_eq!(args[fps_mode + 1], "passthrough");
+    assert!(args.contains(&"libsvtav1".to_string()));
+    // no scaling at full resolution
+    assert!(!args.iter().any(|a| a.starts_with("scale=")));
+    assert_eq!(args.last().unwrap(), "out.mp4");
+}
+
+#[test]
+fn test_compact_command_scales_to_even_dimensions() {
+    let args = compact_command("in.mp4", "out.mp4", &options(CompactionCodec::H265, 0.5)).args();
+
+    assert!(args.contains(&"scale=trunc(iw*0.5/2)*2:trunc(ih*0.5/2)*2".to_string()));
+    assert!(args.contains(&"libx265".to_string()));
+}
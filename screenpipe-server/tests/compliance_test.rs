@@ -0,0 +1,236 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use crossbeam::queue::SegQueue;
+use ed25519_dalek::{Signer, SigningKey};
+use screenpipe_core::{LlmConfig, PromptLibrary};
+use screenpipe_server::compliance::compliance_policy_to_load;
+use screenpipe_server::{
+    load_compliance_policy, record_compliance_consent, ApiCredentials, AppIcons, AppState,
+    CapturePause, ClipRenderer, ComplianceMode, DatabaseManager, LiveOcr, LlmService,
+    MarkerRecorder, TimelapseRenderer, ToolRegistry, Watchlist,
+};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Duration;
+use tower::ServiceExt;
+
+const POLICY: &str = r#"{
+    "organization": "Acme",
+    "consent_text": "Screens of the apps below are recorded for compliance.",
+    "allowed_apps": ["Slack", "Code"]
+}"#;
+
+fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32])
+}
+
+/// Policy, signature and public key in the layout admins deploy
+fn deploy_policy(dir: &Path, policy: &str, key: &SigningKey) -> PathBuf {
+    let path = dir.join("compliance.json");
+    std::fs::write(&path, policy).unwrap();
+    let signature = key.sign(policy.as_bytes());
+    std::fs::write(
+        dir.join("compliance.json.sig"),
+        STANDARD.encode(signature.to_bytes()),
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("compliance.pub"),
+        STANDARD.encode(key.verifying_key().to_bytes()),
+    )
+    .unwrap();
+    path
+}
+
+#[test]
+fn test_signed_policy_is_loaded() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = deploy_policy(dir.path(), POLICY, &signing_key(1));
+
+    let (policy, hash) = load_compliance_policy(&path, None).unwrap();
+
+    assert_eq!(policy.organization, "Acme");
+    assert_eq!(policy.allowed_apps, vec!["Slack", "Code"]);
+    assert!(!policy.allow_audio);
+    assert_eq!(hash.len(), 64);
+}
+
+#[test]
+fn test_changed_or_foreign_policy_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = deploy_policy(dir.path(), POLICY, &signing_key(1));
+
+    let other_key = STANDARD.encode(signing_key(2).verifying_key().to_bytes());
+    assert!(load_compliance_policy(&path, Some(&other_key)).is_err());
+
+    std::fs::write(&path, POLICY.replace("\"Code\"", "\"Code\", \"Messages\"")).unwrap();
+    assert!(load_compliance_policy(&path, None).is_err());
+
+    std::fs::remove_file(dir.path().join("compliance.json.sig")).unwrap();
+    assert!(load_compliance_policy(&path, None).is_err());
+}
+
+#[test]
+fn test_policy_without_apps_is_rejected() {
+    let dir = tempfile::tempdir().unwrap();
+    let policy = r#"{"organization": "Acme", "consent_text": "", "allowed_apps": []}"#;
+    let path = deploy_policy(dir.path(), policy, &signing_key(1));
+
+    assert!(load_compliance_policy(&path, None).is_err());
+}
+
+#[test]
+fn test_deployed_policy_cant_be_replaced_from_the_command_line() {
+    let system = tempfile::tempdir().unwrap();
+    let own = tempfile::tempdir().unwrap();
+    let system_policy = system.path().join("compliance.json");
+    let own_policy = deploy_policy(own.path(), POLICY, &signing_key(2));
+    let own_policy = own_policy.to_str().unwrap();
+    let own_key = STANDARD.encode(signing_key(2).verifying_key().to_bytes());
+
+    // without a deployed policy the command line picks one, or none
+    assert!(compliance_policy_to_load(&system_policy, None, None)
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        compliance_policy_to_load(&system_policy, Some(own_policy), Some(&own_key)).unwrap(),
+        Some((PathBuf::from(own_policy), Some(own_key.as_str())))
+    );
+
+    deploy_policy(system.path(), POLICY, &signing_key(1));
+    assert_eq!(
+        compliance_policy_to_load(&system_policy, None, None).unwrap(),
+        Some((system_policy.clone(), None))
+    );
+    assert!(compliance_policy_to_load(&system_policy, Some(own_policy), Some(&own_key)).is_err());
+    assert!(compliance_policy_to_load(&system_policy, Some(own_policy), None).is_err());
+    assert!(compliance_policy_to_load(&system_policy, None, Some(&own_key)).is_err());
+    assert_eq!(
+        compliance_policy_to_load(&system_policy, system_policy.to_str(), None).unwrap(),
+        Some((system_policy.clone(), None))
+    );
+}
+
+#[tokio::test]
+async fn test_capture_waits_for_consent() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = deploy_policy(dir.path(), POLICY, &signing_key(1));
+    let (policy, hash) = load_compliance_policy(&path, None).unwrap();
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let pause = Arc::new(CapturePause::new());
+
+    let compliance = ComplianceMode::new(policy, hash.clone(), pause.clone());
+    assert!(pause.is_paused());
+    assert!(pause.resume().awaiting_consent);
+    assert!(pause.is_paused());
+    assert!(!compliance.status().consent_given);
+
+    let status = compliance.record_consent(&db, true).await.unwrap();
+    assert!(!status.paused);
+    assert!(compliance.status().consent_given);
+
+    // declining later stops capture again
+    assert!(compliance.record_consent(&db, false).await.unwrap().paused);
+    assert!(pause.resume().paused);
+
+    let records = db.get_consent_records(10).await.unwrap();
+    assert_eq!(records.len(), 2);
+    assert!(!records[0].accepted);
+    assert!(records[1].accepted);
+    assert_eq!(records[1].policy_hash, hash);
+    assert!(sqlx::query("DELETE FROM compliance_consents")
+        .execute(&db.pool)
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_consent_is_taken_from_the_app_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = deploy_policy(dir.path(), POLICY, &signing_key(1));
+    let (policy, hash) = load_compliance_policy(&path, None).unwrap();
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let pause = Arc::new(CapturePause::new());
+    let compliance = Arc::new(ComplianceMode::new(policy, hash, pause.clone()));
+    // the token the app starts the sidecar with
+    let credentials =
+        ApiCredentials::load(dir.path(), Some("token-of-the-app".to_string()), Vec::new()).unwrap();
+    let app_state = Arc::new(AppState {
+        db: db.clone(),
+        vision_control: Arc::new(AtomicBool::new(false)),
+        audio_devices_control: Arc::new(SegQueue::new()),
+        devices_status: HashMap::new(),
+        app_start_time: Utc::now(),
+        llm: Arc::new(LlmService::new(
+            db.clone(),
+            LlmConfig::default(),
+            None,
+            None,
+            None,
+        )),
+        prompts: Arc::new(PromptLibrary::default()),
+        live_view: None,
+        timelapse: Arc::new(TimelapseRenderer::new(
+            db.clone(),
+            PathBuf::from("timelapses"),
+        )),
+        clips: Arc::new(ClipRenderer::new(db.clone(), PathBuf::from("clips"))),
+        capture_pause: pause.clone(),
+        encryption: None,
+        compliance: Some(compliance.clone()),
+        consumer_redaction: None,
+        vault_export: None,
+        markers: Arc::new(MarkerRecorder::new(
+            db.clone(),
+            PathBuf::from("markers"),
+            None,
+            Duration::from_secs(30),
+        )),
+        profile: None,
+        watchlist: Watchlist::load(db.clone()).await.unwrap(),
+        app_icons: Arc::new(AppIcons::new(PathBuf::from("icons"))),
+        live_ocr: Arc::new(LiveOcr::new(0)),
+        automation: None,
+        llm_tools: Arc::new(ToolRegistry::default()),
+        credentials: Arc::new(credentials),
+    });
+    let app = Router::new()
+        .route("/compliance/consent", post(record_compliance_consent))
+        .with_state(app_state);
+    let consent = |authorization: Option<&str>, origin: Option<&str>| {
+        let mut request = Request::post("/compliance/consent")
+            .header(header::HOST, "localhost:3030")
+            .header(header::CONTENT_TYPE, "application/json");
+        if let Some(authorization) = authorization {
+            request = request.header(header::AUTHORIZATION, authorization);
+        }
+        if let Some(origin) = origin {
+            request = request.header(header::ORIGIN, origin);
+        }
+        request.body(Body::from(r#"{"accepted": true}"#)).unwrap()
+    };
+
+    for request in [
+        consent(None, None),
+        consent(Some("Bearer guess"), None),
+        consent(Some("Bearer token-of-the-app"), Some("https://example.com")),
+    ] {
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+    assert!(!compliance.status().consent_given);
+
+    let response = app
+        .oneshot(consent(Some("Bearer token-of-the-app"), None))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(compliance.status().consent_given);
+    assert!(!pause.is_paused());
+}
@@ -0,0 +1,177 @@
+use chrono::{DateTime, Utc};
+use screenpipe_server::conversations::{chat_history, collect_citations, title_from, to_markdown};
+use screenpipe_server::{forget, Citation, ContentType, DatabaseManager, ForgetFilter, ToolRun};
+use screenpipe_vision::OcrEngine;
+use serde_json::json;
+use std::sync::Arc;
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+fn search_run(frame_id: i64, seconds: i64) -> ToolRun {
+    ToolRun {
+        id: "call_1".to_string(),
+        name: "search_history".to_string(),
+        arguments: json!({"query": "hotel"}),
+        result: Some(json!({"results": [
+            {"type": "ocr", "frame_id": frame_id, "timestamp": at(seconds), "app_name": "Firefox", "text": "Hotel Alma"},
+            {"type": "audio", "audio_chunk_id": 4, "timestamp": at(seconds + 10), "text": "the hotel is booked"},
+        ]})),
+        error: None,
+    }
+}
+
+#[tokio::test]
+async fn test_conversations_are_stored_and_resumed() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let flights = db
+        .insert_conversation("Flights", None, at(1727000000))
+        .await
+        .unwrap();
+    let invoices = db
+        .insert_conversation("Invoices", Some("invoice-filer"), at(1727000100))
+        .await
+        .unwrap();
+
+    let citation = Citation {
+        frame_id: Some(12),
+        audio_chunk_id: None,
+        timestamp: at(1726900000),
+        app_name: Some("Firefox".to_string()),
+    };
+    db.insert_conversation_message(flights, at(1727000200), "user", "which flight?", &[], &[], None)
+        .await
+        .unwrap();
+    db.insert_conversation_message(
+        flights,
+        at(1727000201),
+        "assistant",
+        "LH 1234 to Lisbon",
+        &[citation.clone()],
+        &[search_run(12, 1726900000)],
+        Some("llama3.1"),
+    )
+    .await
+    .unwrap();
+
+    // continued last, so listed first
+    let all = db.get_conversations(None, None, 10, 0, None).await.unwrap();
+    assert_eq!(
+        all.iter().map(|c| c.id).collect::<Vec<_>>(),
+        vec![flights, invoices]
+    );
+    assert_eq!(all[0].updated_at, at(1727000201));
+    assert_eq!(all[0].message_count, 2);
+    let found = db
+        .get_conversations(Some("lisbon"), None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].title, "Flights");
+    assert_eq!(
+        db.count_conversations(None, Some("invoice-filer"))
+            .await
+            .unwrap(),
+        1
+    );
+
+    let messages = db.get_conversation_messages(flights).await.unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].citations, vec![citation]);
+    assert_eq!(messages[1].tool_calls[0].name, "search_history");
+    assert_eq!(messages[1].model.as_deref(), Some("llama3.1"));
+
+    // the model gets the earlier questions and answers
+    let history = chat_history(&messages);
+    assert_eq!(history[0].role, "user");
+    assert_eq!(history[1].role, "assistant");
+    assert_eq!(history[1].content, "LH 1234 to Lisbon");
+    assert!(history[1].tool_calls.is_empty());
+
+    assert!(db.delete_conversation(invoices).await.unwrap());
+    assert!(!db.delete_conversation(invoices).await.unwrap());
+    assert!(db.get_conversation(invoices).await.unwrap().is_none());
+
+    let filter = ForgetFilter {
+        keyword: Some("lisbon".to_string()),
+        ..Default::default()
+    };
+    let report = forget(&db, &filter, false).await.unwrap();
+    assert_eq!(report.conversation_messages, 1);
+    assert_eq!(db.get_conversation_messages(flights).await.unwrap().len(), 1);
+}
+
+#[tokio::test]
+async fn test_answers_cite_the_search_and_what_the_tools_found() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    db.insert_video_chunk("test_video.mp4").await.unwrap();
+    let frame_id = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        frame_id,
+        "Hotel Alma, check-in October 3",
+        "",
+        "Firefox",
+        "booking.com",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+    let results = db
+        .search("hotel", ContentType::OCR, 10, 0, None, None, None, None)
+        .await
+        .unwrap();
+
+    let failed = ToolRun {
+        id: "call_2".to_string(),
+        name: "get_frame".to_string(),
+        arguments: json!({"frame_id": 99}),
+        result: None,
+        error: Some("frame 99 not found".to_string()),
+    };
+    let citations = collect_citations(&results, &[search_run(frame_id, 1727000000), failed]);
+    // the frame of the search is cited once, the audio the model found too
+    assert_eq!(citations.len(), 2);
+    assert_eq!(citations[0].frame_id, Some(frame_id));
+    assert_eq!(citations[0].app_name.as_deref(), Some("Firefox"));
+    assert_eq!(citations[1].audio_chunk_id, Some(4));
+}
+
+#[tokio::test]
+async fn test_conversations_export_to_markdown() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let id = db
+        .insert_conversation(&title_from("where am I staying in Lisbon?"), None, at(1727000000))
+        .await
+        .unwrap();
+    db.insert_conversation_message(id, at(1727000001), "user", "where am I staying in Lisbon?", &[], &[], None)
+        .await
+        .unwrap();
+    let citations = collect_citations(&[], &[search_run(7, 1726900000)]);
+    db.insert_conversation_message(
+        id,
+        at(1727000002),
+        "assistant",
+        "Hotel Alma",
+        &citations,
+        &[search_run(7, 1726900000)],
+        Some("llama3.1"),
+    )
+    .await
+    .unwrap();
+
+    let conversation = db.get_conversation(id).await.unwrap().unwrap();
+    let messages = db.get_conversation_messages(id).await.unwrap();
+    let markdown = to_markdown(&conversation, &messages);
+    assert!(markdown.starts_with("# where am I staying in Lisbon?\n"));
+    assert!(markdown.contains("## You, 2024-09-22T10:13:21+00:00\n\nwhere am I staying in Lisbon?\n"));
+    assert!(markdown.contains("## llama3.1, 2024-09-22T10:13:22+00:00\n\nHotel Alma\n"));
+    assert!(markdown.contains("- search_history {\"query\":\"hotel\"}\n"));
+    assert!(markdown.contains("- frame 7, Firefox at 2024-09-21T06:26:40+00:00\n"));
+    assert!(markdown.contains("- audio chunk 4 at 2024-09-21T06:26:50+00:00\n"));
+
+    let long = "why ".repeat(40);
+    assert_eq!(title_from(&long).chars().count(), 80);
+    assert!(title_from(&long).ends_with('…'));
+}
@@ -0,0 +1,183 @@
+use axum::body::Body;
+use axum::http::{header, HeaderMap, HeaderValue, Request, StatusCode};
+use axum::routing::post;
+use axum::Router;
+use screenpipe_core::{PIPE_HEADER, PIPE_TOKEN_HEADER};
+use screenpipe_server::credentials::{is_cross_origin, refuse_cross_origin, PIPE_TOKENS_FILE};
+use screenpipe_server::{ApiCredentials, Caller};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+fn headers(pairs: &[(&str, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+        headers.insert(
+            header::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(value).unwrap(),
+        );
+    }
+    headers
+}
+
+#[test]
+fn test_callers_are_who_their_credentials_say() {
+    let dir = tempfile::tempdir().unwrap();
+    let credentials =
+        ApiCredentials::load(dir.path(), None, vec!["remote-secret".to_string()]).unwrap();
+    let token = credentials.pipe_token("dismiss-dialog").unwrap();
+    let app = format!("Bearer {}", credentials.app_token());
+
+    assert_eq!(
+        credentials.caller(&headers(&[
+            (PIPE_HEADER, "dismiss-dialog"),
+            (PIPE_TOKEN_HEADER, token.as_str())
+        ])),
+        Caller::Pipe("dismiss-dialog".to_string())
+    );
+    assert_eq!(
+        credentials.caller(&headers(&[("authorization", app.as_str())])),
+        Caller::App
+    );
+    assert_eq!(
+        credentials.caller(&headers(&[("authorization", "Bearer remote-secret")])),
+        Caller::Remote
+    );
+
+    // a name alone, another pipe's token or a made up bearer token are of nobody known
+    assert_eq!(
+        credentials.caller(&headers(&[(PIPE_HEADER, "dismiss-dialog")])),
+        Caller::Unknown
+    );
+    let other = credentials.pipe_token("daily-summary").unwrap();
+    assert_eq!(
+        credentials.caller(&headers(&[
+            (PIPE_HEADER, "dismiss-dialog"),
+            (PIPE_TOKEN_HEADER, other.as_str())
+        ])),
+        Caller::Unknown
+    );
+    assert_eq!(
+        credentials.caller(&headers(&[
+            (PIPE_HEADER, "dismiss-dialog"),
+            ("authorization", app.as_str())
+        ])),
+        Caller::Unknown
+    );
+    assert_eq!(
+        credentials.caller(&headers(&[("authorization", "Bearer guess")])),
+        Caller::Unknown
+    );
+    assert_eq!(credentials.caller(&HeaderMap::new()), Caller::Unknown);
+
+    // the same tokens after a restart
+    let reloaded = ApiCredentials::load(dir.path(), None, Vec::new()).unwrap();
+    assert_eq!(reloaded.app_token(), credentials.app_token());
+    assert_eq!(reloaded.pipe_token("dismiss-dialog").unwrap(), token);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.path().join(PIPE_TOKENS_FILE))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn test_the_app_token_the_app_starts_the_server_with_is_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    ApiCredentials::load(dir.path(), None, Vec::new()).unwrap();
+
+    let credentials =
+        ApiCredentials::load(dir.path(), Some("token-of-the-app".to_string()), Vec::new()).unwrap();
+
+    assert_eq!(
+        credentials.caller(&headers(&[("authorization", "Bearer token-of-the-app")])),
+        Caller::App
+    );
+    // for the clients reading it from the data directory, and the next start without one
+    let reloaded = ApiCredentials::load(dir.path(), None, Vec::new()).unwrap();
+    assert_eq!(reloaded.app_token(), "token-of-the-app");
+}
+
+#[test]
+fn test_requests_of_other_origins_are_told_apart() {
+    assert!(!is_cross_origin(&headers(&[("host", "localhost:3030")])));
+    assert!(!is_cross_origin(&headers(&[
+        ("host", "localhost:3030"),
+        ("origin", "http://localhost:3030")
+    ])));
+    assert!(is_cross_origin(&headers(&[
+        ("host", "localhost:3030"),
+        ("origin", "https://example.com")
+    ])));
+    assert!(is_cross_origin(&headers(&[
+        ("host", "localhost:3030"),
+        ("origin", "http://localhost:8080")
+    ])));
+    assert!(is_cross_origin(&headers(&[
+        ("host", "localhost:3030"),
+        ("origin", "null")
+    ])));
+}
+
+#[tokio::test]
+async fn test_web_pages_of_other_origins_cannot_forget() {
+    let credentials = Arc::new(ApiCredentials::new("app-token", Vec::new()));
+    let app = Router::new().route(
+        "/forget",
+        post(|| async { "forgotten" }).layer(axum::middleware::from_fn_with_state(
+            credentials,
+            refuse_cross_origin,
+        )),
+    );
+    let status = |pairs: &[(&str, &str)]| {
+        let mut request = Request::builder().method("POST").uri("/forget");
+        for (name, value) in pairs {
+            request = request.header(*name, *value);
+        }
+        let app = app.clone();
+        let request = request.body(Body::empty()).unwrap();
+        async move { app.oneshot(request).await.unwrap().status() }
+    };
+
+    // a form on another site, which the permissive CORS doesn't stop
+    assert_eq!(
+        status(&[
+            ("host", "localhost:3030"),
+            ("origin", "https://example.com")
+        ])
+        .await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status(&[
+            ("host", "localhost:3030"),
+            ("origin", "https://example.com"),
+            ("authorization", "Bearer guess")
+        ])
+        .await,
+        StatusCode::FORBIDDEN
+    );
+    // the app's webview, with its token
+    assert_eq!(
+        status(&[
+            ("host", "localhost:3030"),
+            ("origin", "tauri://localhost"),
+            ("authorization", "Bearer app-token")
+        ])
+        .await,
+        StatusCode::OK
+    );
+    // curl and the pages the server serves itself
+    assert_eq!(status(&[("host", "localhost:3030")]).await, StatusCode::OK);
+    assert_eq!(
+        status(&[
+            ("host", "localhost:3030"),
+            ("origin", "http://localhost:3030")
+        ])
+        .await,
+        StatusCode::OK
+    );
+}
@@ -3,7 +3,10 @@ mod tests {
     use std::sync::Arc;
 
     use chrono::Utc;
-    use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+    use screenpipe_server::{
+        ActionItem, ActivityCategory, ChunkIntegrity, ChunkKind, ContentType, DatabaseManager,
+        SearchResult, TimelapseStatus,
+    };
     use screenpipe_vision::OcrEngine;
 
     async fn setup_test_db() -> DatabaseManager {
@@ -320,4 +323,311 @@ mod tests {
             .unwrap();
         assert_eq!(count, 2);
     }
+
+    #[tokio::test]
+    async fn test_insert_and_get_meeting_summary() {
+        let db = setup_test_db().await;
+        assert!(db.get_last_meeting_end_time().await.unwrap().is_none());
+
+        let start_time = Utc::now() - chrono::Duration::minutes(30);
+        let end_time = Utc::now();
+        let id = db
+            .insert_meeting_summary(
+                start_time,
+                end_time,
+                "Discussed the roadmap",
+                &["Focus on audio".to_string()],
+                &[ActionItem {
+                    description: "Fix the tests".to_string(),
+                    owner: Some("Louis".to_string()),
+                }],
+                "llama3.1",
+                1,
+            )
+            .await
+            .unwrap();
+
+        let summary = db.get_meeting_summary(id).await.unwrap().unwrap();
+        assert_eq!(summary.summary, "Discussed the roadmap");
+        assert_eq!(summary.decisions, vec!["Focus on audio"]);
+        assert_eq!(summary.prompt_version, Some(1));
+        assert_eq!(summary.action_items[0].owner.as_deref(), Some("Louis"));
+        assert_eq!(db.count_meeting_summaries(None).await.unwrap(), 1);
+        assert_eq!(db.get_last_meeting_end_time().await.unwrap(), Some(end_time));
+    }
+
+    #[tokio::test]
+    async fn test_focused_windows_and_activity_sessions() {
+        let db = setup_test_db().await;
+        let since = Utc::now() - chrono::Duration::minutes(1);
+        let _ = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let frame_id = db.insert_frame().await.unwrap();
+        for (app, window, focused) in [("Code", "main.rs", true), ("Slack", "general", false)] {
+            db.insert_ocr_text(
+                frame_id,
+                "text",
+                "",
+                app,
+                window,
+                Arc::new(OcrEngine::Tesseract),
+                focused,
+            )
+            .await
+            .unwrap();
+        }
+
+        let windows = db.get_focused_windows_since(since).await.unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].1, "Code");
+        assert_eq!(windows[0].2, "main.rs");
+
+        let start_time = Utc::now() - chrono::Duration::minutes(30);
+        let middle = start_time + chrono::Duration::minutes(20);
+        let end_time = start_time + chrono::Duration::minutes(30);
+        db.insert_activity_session(start_time, middle, "Code", ActivityCategory::Coding, 0.9)
+            .await
+            .unwrap();
+        db.insert_activity_session(middle, end_time, "Zoom", ActivityCategory::Meetings, 0.8)
+            .await
+            .unwrap();
+
+        let sessions = db
+            .get_activity_sessions(Some(ActivityCategory::Coding), None, None, 10, 0, None)
+            .await
+            .unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].app_name, "Code");
+        assert_eq!(
+            db.count_activity_sessions(None, Some(middle + chrono::Duration::minutes(1)), None)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            db.get_last_activity_session_end_time().await.unwrap(),
+            Some(end_time)
+        );
+
+        let durations = db.get_activity_category_durations(None, None).await.unwrap();
+        assert_eq!(durations[0].category, ActivityCategory::Coding);
+        assert!((durations[0].duration_secs - 1200.0).abs() < 1.0);
+        assert!((durations[1].duration_secs - 600.0).abs() < 1.0);
+    }
+
+    #[tokio::test]
+    async fn test_timelapse_frames_and_jobs() {
+        let db = setup_test_db().await;
+        let start_time = Utc::now() - chrono::Duration::minutes(1);
+        let _ = db.insert_video_chunk("test_video.mp4").await.unwrap();
+        let focused_frame = db.insert_frame().await.unwrap();
+        db.insert_ocr_text(
+            focused_frame,
+            "text",
+            "",
+            "Code",
+            "main.rs",
+            Arc::new(OcrEngine::Tesseract),
+            true,
+        )
+        .await
+        .unwrap();
+        let _ = db.insert_frame().await.unwrap();
+
+        let frames = db
+            .get_timelapse_frames(start_time, Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].file_path, "test_video.mp4");
+        assert_eq!(frames[0].app_name, "Code");
+        assert_eq!(frames[1].app_name, "");
+        assert_eq!(frames[1].offset_index, frames[0].offset_index + 1);
+
+        let id = db
+            .insert_timelapse_job(start_time, Utc::now(), 60.0, "timelapse.mp4")
+            .await
+            .unwrap();
+        let job = db.get_timelapse_job(id).await.unwrap().unwrap();
+        assert_eq!(job.status, TimelapseStatus::Running);
+        assert!(job.finished_at.is_none());
+
+        db.finish_timelapse_job(id, TimelapseStatus::Failed, Some("no frames"))
+            .await
+            .unwrap();
+        let job = db.get_timelapse_job(id).await.unwrap().unwrap();
+        assert_eq!(job.status, TimelapseStatus::Failed);
+        assert_eq!(job.error.as_deref(), Some("no frames"));
+        assert!(job.finished_at.is_some());
+        assert!(db.get_timelapse_job(id + 1).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_video_chunks_pending_subtitles() {
+        let db = setup_test_db().await;
+        let first_chunk = db.insert_video_chunk("first.mp4").await.unwrap();
+        let frame_id = db.insert_frame().await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "text",
+            "",
+            "Code",
+            "main.rs",
+            Arc::new(OcrEngine::Tesseract),
+            true,
+        )
+        .await
+        .unwrap();
+        let _ = db.insert_video_chunk("second.mp4").await.unwrap();
+        let _ = db.insert_frame().await.unwrap();
+
+        let frames = db.get_video_chunk_frames(first_chunk).await.unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].app_name, "Code");
+        assert_eq!(frames[0].window_name, "main.rs");
+
+        // the chunk being recorded is never returned
+        let settled_before = Utc::now() + chrono::Duration::minutes(1);
+        let pending = db
+            .get_video_chunks_pending_subtitles(settled_before, 10)
+            .await
+            .unwrap();
+        assert_eq!(pending, vec![(first_chunk, "first.mp4".to_string())]);
+        assert!(db
+            .get_video_chunks_pending_subtitles(Utc::now() - chrono::Duration::minutes(1), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.mark_video_chunk_subtitles_muxed(first_chunk).await.unwrap();
+        assert!(db
+            .get_video_chunks_pending_subtitles(settled_before, 10)
+            .await
+            .unwrap()
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_broken_chunks_hidden_from_search() {
+        let db = setup_test_db().await;
+        let broken_chunk = db.insert_video_chunk("broken.mp4").await.unwrap();
+        let frame_id = db.insert_frame().await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "Hello from a broken chunk",
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+        let _ = db.insert_video_chunk("recording.mp4").await.unwrap();
+        let audio_chunk_id = db.insert_audio_chunk("broken_audio.mp4").await.unwrap();
+        db.insert_audio_transcription(audio_chunk_id, "Hello from broken audio", 0, "")
+            .await
+            .unwrap();
+
+        // the chunk being recorded is never checked
+        let unchecked = db.get_unchecked_chunks(ChunkKind::Video, 10).await.unwrap();
+        assert_eq!(unchecked, vec![(broken_chunk, "broken.mp4".to_string())]);
+        let unchecked = db.get_unchecked_chunks(ChunkKind::Audio, 10).await.unwrap();
+        assert_eq!(unchecked, vec![(audio_chunk_id, "broken_audio.mp4".to_string())]);
+
+        db.set_chunk_integrity(ChunkKind::Video, broken_chunk, ChunkIntegrity::Broken)
+            .await
+            .unwrap();
+        db.set_chunk_integrity(ChunkKind::Audio, audio_chunk_id, ChunkIntegrity::Broken)
+            .await
+            .unwrap();
+
+        assert!(db
+            .get_unchecked_chunks(ChunkKind::Video, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        let results = db
+            .search("Hello", ContentType::All, 100, 0, None, None, None, None)
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+        let count = db
+            .count_search_results("Hello", ContentType::All, None, None, None, None)
+            .await
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[tokio::test]
+    async fn test_compacted_chunk_points_to_new_file() {
+        let db = setup_test_db().await;
+        let chunk_id = db.insert_video_chunk("old.mp4").await.unwrap();
+        let frame_id = db.insert_frame().await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            "Hello, old chunk",
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+
+        let later = Utc::now() + chrono::Duration::minutes(1);
+        let to_compact = db.get_video_chunks_to_compact(later, 10).await.unwrap();
+        assert_eq!(to_compact, vec![(chunk_id, "old.mp4".to_string())]);
+        assert!(db
+            .get_video_chunks_to_compact(Utc::now() - chrono::Duration::days(1), 10)
+            .await
+            .unwrap()
+            .is_empty());
+
+        db.mark_video_chunk_compacted(chunk_id, "old_compact.mp4", 0.5)
+            .await
+            .unwrap();
+
+        assert!(db
+            .get_video_chunks_to_compact(later, 10)
+            .await
+            .unwrap()
+            .is_empty());
+        let (file_path, offset_index) = db.get_frame(frame_id).await.unwrap().unwrap();
+        assert_eq!(file_path, "old_compact.mp4");
+        assert_eq!(offset_index, 0);
+    }
+
+    #[tokio::test]
+    async fn test_clip_frames_list_every_window() {
+        let db = setup_test_db().await;
+        let _ = db.insert_video_chunk("clip.mp4").await.unwrap();
+        let start = Utc::now() - chrono::Duration::seconds(1);
+        let first_frame = db.insert_frame().await.unwrap();
+        for (app, window) in [("Slack", "general"), ("1Password", "Vault")] {
+            db.insert_ocr_text(
+                first_frame,
+                "text",
+                "",
+                app,
+                window,
+                Arc::new(OcrEngine::Tesseract),
+                false,
+            )
+            .await
+            .unwrap();
+        }
+        let _ = db.insert_frame().await.unwrap();
+
+        let frames = db
+            .get_clip_frames(start, Utc::now() + chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].windows.len(), 2);
+        assert_eq!(frames[0].file_path, "clip.mp4");
+        assert!(frames[1].windows.is_empty());
+        assert_eq!(frames[1].offset_index, 1);
+    }
 }
@@ -0,0 +1,27 @@
+use screenpipe_server::DiskLevel;
+
+const GB: u64 = 1_000_000_000;
+const START: u64 = 10 * GB;
+
+#[test]
+fn test_levels_degrade_as_the_disk_fills() {
+    let level = |free| DiskLevel::for_free_space(free, START, DiskLevel::Normal);
+    assert_eq!(level(20 * GB), DiskLevel::Normal);
+    assert_eq!(level(8 * GB), DiskLevel::NoRawFrames);
+    assert_eq!(level(4 * GB), DiskLevel::LowBitrate);
+    assert_eq!(level(GB + GB / 2), DiskLevel::ShortRetention);
+    assert_eq!(level(GB / 2), DiskLevel::MediaPaused);
+}
+
+#[test]
+fn test_levels_are_left_with_some_room() {
+    // just above the 1 GB of the pause, not enough to resume
+    let level = DiskLevel::for_free_space(GB + GB / 20, START, DiskLevel::MediaPaused);
+    assert_eq!(level, DiskLevel::MediaPaused);
+    let level = DiskLevel::for_free_space(GB + GB / 5, START, DiskLevel::MediaPaused);
+    assert_eq!(level, DiskLevel::ShortRetention);
+
+    // everything freed at once
+    let level = DiskLevel::for_free_space(50 * GB, START, DiskLevel::MediaPaused);
+    assert_eq!(level, DiskLevel::Normal);
+}
@@ -0,0 +1,49 @@
+use screenpipe_server::doctor::{check_data_dir, disk_space_check};
+use screenpipe_server::{CheckStatus, DoctorCheck, DoctorReport};
+use serde_json::json;
+
+const GB: u64 = 1024 * 1024 * 1024;
+
+#[test]
+fn test_only_failures_fail_the_report() {
+    let report = DoctorReport::new(vec![
+        DoctorCheck::pass("ffmpeg", "ffmpeg version 7.0"),
+        DoctorCheck::warn("disk_space", "5.0 GB free", "free some space"),
+        DoctorCheck::skipped("microphone", "audio is disabled"),
+    ]);
+    assert!(report.passed);
+
+    let report = DoctorReport::new(vec![
+        DoctorCheck::pass("ffmpeg", "ffmpeg version 7.0"),
+        DoctorCheck::fail("screen_capture", "no monitor can be read", "allow it"),
+    ]);
+    assert!(!report.passed);
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(
+        json["checks"][0],
+        json!({"name": "ffmpeg", "status": "pass", "message": "ffmpeg version 7.0"})
+    );
+    assert_eq!(json["checks"][1]["status"], "fail");
+    assert_eq!(json["checks"][1]["fix"], "allow it");
+}
+
+#[test]
+fn test_disk_space_thresholds() {
+    assert_eq!(disk_space_check(GB / 2).status, CheckStatus::Fail);
+    assert_eq!(disk_space_check(5 * GB).status, CheckStatus::Warn);
+    assert_eq!(disk_space_check(50 * GB).status, CheckStatus::Pass);
+}
+
+#[test]
+fn test_data_dir_must_be_writable() {
+    let dir = tempfile::tempdir().unwrap();
+    let check = check_data_dir(&dir.path().join("screenpipe"));
+    assert_eq!(check.status, CheckStatus::Pass);
+    assert!(!dir.path().join("screenpipe/data/.doctor").exists());
+
+    // a file where the directory should be
+    std::fs::write(dir.path().join("taken"), b"").unwrap();
+    let check = check_data_dir(&dir.path().join("taken"));
+    assert_eq!(check.status, CheckStatus::Fail);
+    assert!(check.fix.unwrap().contains("--data-dir"));
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_server::editor_context::link_editor_frames;
+use screenpipe_server::{
+    record_editor_beacon, CapturePause, DatabaseManager, EditorBeacon, PendingWrite,
+};
+use std::sync::Arc;
+
+fn beacon(file: &str, timestamp: DateTime<Utc>) -> EditorBeacon {
+    EditorBeacon {
+        editor: "VSCode".to_string(),
+        project: Some("screenpipe".to_string()),
+        repository: Some("github.com/mediar-ai/screenpipe".to_string()),
+        git_branch: Some("main".to_string()),
+        file: Some(file.to_string()),
+        language: Some("rust".to_string()),
+        timestamp: Some(timestamp),
+    }
+}
+
+fn ocr(timestamp: DateTime<Utc>, window_name: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp,
+        text: "pub async fn insert_batch".to_string(),
+        text_json: String::new(),
+        app_name: "Code".to_string(),
+        window_name: window_name.to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+#[test]
+fn test_beacons_without_context_are_refused() {
+    let mut blank = beacon("  ", Utc::now());
+    blank.project = None;
+    blank.repository = Some(String::new());
+    assert!(blank.validated().is_err());
+
+    let mut no_editor = beacon("src/db.rs", Utc::now());
+    no_editor.editor = " ".to_string();
+    assert!(no_editor.validated().is_err());
+
+    let valid = beacon("src/db.rs", Utc::now()).validated().unwrap();
+    assert_eq!(valid.editor, "vscode");
+}
+
+#[tokio::test]
+async fn test_beacons_of_the_same_file_continue_its_context() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let pause = Arc::new(CapturePause::new());
+    let started = Utc::now() - Duration::minutes(30);
+    let record = |beacon: EditorBeacon| {
+        let db = &db;
+        let pause = &pause;
+        async move {
+            record_editor_beacon(db, pause, beacon.validated().unwrap())
+                .await
+                .unwrap()
+        }
+    };
+
+    let first = record(beacon("src/db.rs", started)).await.unwrap();
+    assert_eq!(
+        record(beacon("src/db.rs", started + Duration::seconds(20))).await,
+        Some(first)
+    );
+    let other_file = record(beacon("src/server.rs", started + Duration::seconds(40)))
+        .await
+        .unwrap();
+    assert_ne!(other_file, first);
+    // back to it after a while is another context
+    let later = record(beacon("src/server.rs", started + Duration::minutes(10)))
+        .await
+        .unwrap();
+    assert_ne!(later, other_file);
+
+    pause.pause(None);
+    assert_eq!(
+        record(beacon("src/secret.rs", started + Duration::minutes(11))).await,
+        None
+    );
+
+    let contexts = db
+        .get_editor_contexts(None, None, Some("db.rs"), None, None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(contexts.len(), 1);
+    assert_eq!(contexts[0].id, first);
+    assert_eq!(contexts[0].duration_secs, 20.0);
+    assert_eq!(
+        db.count_editor_contexts(Some("screenpipe"), None, None, Some("main"), None, None)
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        db.count_editor_contexts(None, Some("mediar-ai"), None, None, None, None)
+            .await
+            .unwrap(),
+        3
+    );
+    assert_eq!(
+        db.count_editor_contexts(None, None, Some("secret"), None, None, None)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn test_contexts_link_the_frames_showing_their_file() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let started = Utc::now() - Duration::minutes(10);
+    db.insert_batch(&[
+        PendingWrite::VideoChunk {
+            file_path: "chunk.mp4".to_string(),
+        },
+        ocr(started - Duration::seconds(30), "db.rs — screenpipe"),
+        ocr(started + Duration::seconds(5), "db.rs — screenpipe"),
+        ocr(started + Duration::seconds(10), "server.rs — screenpipe"),
+        ocr(started + Duration::seconds(15), "db.rs — screenpipe"),
+    ])
+    .await
+    .unwrap();
+    let pause = Arc::new(CapturePause::new());
+    for seconds in [0, 10, 20] {
+        record_editor_beacon(
+            &db,
+            &pause,
+            beacon(
+                "screenpipe-server/src/db.rs",
+                started + Duration::seconds(seconds),
+            ),
+        )
+        .await
+        .unwrap();
+    }
+
+    let mut contexts = db
+        .get_editor_contexts(Some("db.rs"), None, None, None, None, None, 10, 0, None)
+        .await
+        .unwrap();
+    link_editor_frames(&db, &mut contexts).await.unwrap();
+    let windows = db
+        .get_frame_windows_between(started, started + Duration::seconds(20))
+        .await
+        .unwrap();
+    let file_frames: Vec<i64> = windows
+        .iter()
+        .filter(|(_, _, window_name)| window_name.starts_with("db.rs"))
+        .map(|(frame_id, _, _)| *frame_id)
+        .collect();
+    assert_eq!(file_frames.len(), 2);
+    assert_eq!(contexts[0].frame_ids, file_frames);
+}
@@ -0,0 +1,65 @@
+use chrono::Utc;
+use screenpipe_server::email::send_alert;
+use screenpipe_server::watchlist::{WatchAlert, WatchSource};
+use screenpipe_server::{load_email_destinations, DatabaseManager};
+
+fn write_config(dir: &std::path::Path, destinations: serde_json::Value) -> std::path::PathBuf {
+    let path = dir.join("email.json");
+    std::fs::write(
+        &path,
+        serde_json::json!({ "destinations": destinations }).to_string(),
+    )
+    .unwrap();
+    path
+}
+
+fn destination(name: &str) -> serde_json::Value {
+    serde_json::json!({
+        "name": name,
+        "smtp_host": "smtp.example.com",
+        "from": "me@example.com",
+        "to": ["me@example.com"],
+        "alerts": true,
+        "watches": ["orders"],
+    })
+}
+
+#[test]
+fn test_loads_the_destinations_of_the_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(dir.path(), serde_json::json!([destination("me")]));
+    let destinations = load_email_destinations(&path).unwrap();
+    assert_eq!(destinations.len(), 1);
+    assert_eq!(destinations[0].security.default_port(), 587);
+
+    let path = write_config(
+        dir.path(),
+        serde_json::json!([destination("me"), destination("me")]),
+    );
+    assert!(load_email_destinations(&path).is_err());
+
+    let mut unset_password = destination("me");
+    unset_password["password_env"] = "SCREENPIPE_TEST_UNSET_SMTP_PASSWORD".into();
+    let path = write_config(dir.path(), serde_json::json!([unset_password]));
+    assert!(load_email_destinations(&path).is_err());
+}
+
+#[tokio::test]
+async fn test_alerts_of_other_watches_are_not_emailed() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let path = write_config(dir.path(), serde_json::json!([destination("me")]));
+    let destinations = load_email_destinations(&path).unwrap();
+    let alert = WatchAlert {
+        id: 1,
+        watch_id: 1,
+        watch_name: "mentions".to_string(),
+        timestamp: Utc::now(),
+        source: WatchSource::Ocr,
+        app_name: Some("Slack".to_string()),
+        window_name: Some("#general".to_string()),
+        matched_text: "karol".to_string(),
+        excerpt: "@karol".to_string(),
+    };
+    assert_eq!(send_alert(&db, &destinations, &alert).await.unwrap(), 0);
+}
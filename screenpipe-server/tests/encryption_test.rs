@@ -0,0 +1,277 @@
+use chrono::{Duration, Utc};
+use screenpipe_server::encryption::{
+    encrypt_chunk, encrypt_pending_chunks, encrypted_key_id, open_media, reencrypt_thumbnail,
+};
+use screenpipe_server::{
+    prepare_decrypted_dir, set_media_keys, ChunkKind, DatabaseManager, KeyRing,
+};
+use std::path::Path;
+use std::sync::Arc;
+
+#[test]
+fn test_encrypt_roundtrip() {
+    let keys = KeyRing::ephemeral();
+
+    let (key_id, encrypted) = keys.encrypt(b"frame data").unwrap();
+
+    assert_eq!(key_id, 1);
+    assert_eq!(encrypted_key_id(&encrypted), Some(1));
+    assert!(!encrypted
+        .windows(b"frame data".len())
+        .any(|w| w == b"frame data"));
+    assert_eq!(keys.decrypt(&encrypted).unwrap(), b"frame data");
+    assert_eq!(encrypted_key_id(b"plain mp4 bytes"), None);
+}
+
+#[test]
+fn test_tampered_data_is_rejected() {
+    let keys = KeyRing::ephemeral();
+    let (_, mut encrypted) = keys.encrypt(b"frame data").unwrap();
+
+    let last = encrypted.len() - 1;
+    encrypted[last] ^= 1;
+
+    assert!(keys.decrypt(&encrypted).is_err());
+    assert!(KeyRing::ephemeral()
+        .decrypt(&keys.encrypt(b"frame data").unwrap().1)
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_rotation_keeps_old_keys_until_unused() {
+    let keys = KeyRing::ephemeral();
+    let (_, old) = keys.encrypt(b"before").unwrap();
+    let database_key = keys.database_key();
+
+    assert_eq!(keys.rotate().await.unwrap(), 2);
+
+    let (key_id, _) = keys.encrypt(b"after").unwrap();
+    assert_eq!(key_id, 2);
+    assert_eq!(keys.decrypt(&old).unwrap(), b"before");
+    assert_eq!(keys.previous_database_key(), Some(database_key));
+    assert!(keys.retire_unused(&[1]).await.unwrap().is_empty());
+    assert_eq!(keys.retire_unused(&[]).await.unwrap(), vec![1]);
+    assert_eq!(keys.key_ids(), vec![2]);
+    assert!(keys.decrypt(&old).is_err());
+}
+
+#[tokio::test]
+async fn test_encrypt_chunk_and_read_it_back() {
+    let dir = tempfile::tempdir().unwrap();
+    let file_path = dir.path().join("audio.mp4").to_string_lossy().to_string();
+    std::fs::write(&file_path, b"audio bytes").unwrap();
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let id = db.insert_audio_chunk(&file_path).await.unwrap();
+    sqlx::query("UPDATE audio_chunks SET timestamp = ?1")
+        .bind(Utc::now() - Duration::hours(1))
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let keys = Arc::new(KeyRing::ephemeral());
+
+    let pending = db
+        .get_chunks_to_encrypt(ChunkKind::Audio, 1, Utc::now(), 10)
+        .await
+        .unwrap();
+    assert_eq!(pending, vec![(id, file_path.clone())]);
+
+    encrypt_chunk(&db, &keys, ChunkKind::Audio, id, &file_path)
+        .await
+        .unwrap();
+
+    let encrypted_path = format!("{}.enc", file_path);
+    assert!(!Path::new(&file_path).exists());
+    assert!(db
+        .get_chunks_to_encrypt(ChunkKind::Audio, 1, Utc::now(), 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(db.get_encryption_key_ids_in_use().await.unwrap(), vec![1]);
+
+    // what a crash left decrypted is gone after a restart
+    let decrypted_dir = dir.path().join("decrypted");
+    std::fs::create_dir(&decrypted_dir).unwrap();
+    std::fs::write(decrypted_dir.join("screenpipe-left.mp4"), b"audio bytes").unwrap();
+    prepare_decrypted_dir(dir.path()).unwrap();
+    assert_eq!(std::fs::read_dir(&decrypted_dir).unwrap().count(), 0);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&decrypted_dir)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+    }
+
+    set_media_keys(keys.clone());
+    let media = open_media(&encrypted_path).await.unwrap();
+    let plain_path = media.path().to_string();
+    assert!(Path::new(&plain_path).starts_with(&decrypted_dir));
+    assert_eq!(std::fs::read(&plain_path).unwrap(), b"audio bytes");
+    drop(media);
+    assert!(!Path::new(&plain_path).exists());
+
+    // picked up again to move to the new key
+    keys.rotate().await.unwrap();
+    assert_eq!(
+        db.get_chunks_to_encrypt(ChunkKind::Audio, 2, Utc::now(), 10)
+            .await
+            .unwrap(),
+        vec![(id, encrypted_path)]
+    );
+}
+
+#[tokio::test]
+async fn test_thumbnails_hold_their_key_until_moved_to_the_new_one() {
+    let dir = tempfile::tempdir().unwrap();
+    let file_path = dir.path().join("09-30-00.000000000_160.jpg.enc");
+    let file_path = file_path.to_string_lossy().to_string();
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let keys = Arc::new(KeyRing::ephemeral());
+    let (key_id, encrypted) = keys.encrypt(b"jpeg bytes").unwrap();
+    std::fs::write(&file_path, encrypted).unwrap();
+    db.set_thumbnail_key(&file_path, key_id).await.unwrap();
+
+    keys.rotate().await.unwrap();
+    assert_eq!(db.get_encryption_key_ids_in_use().await.unwrap(), vec![1]);
+    assert_eq!(
+        db.get_thumbnails_to_reencrypt(2, 10).await.unwrap(),
+        vec![file_path.clone()]
+    );
+
+    reencrypt_thumbnail(&db, &keys, &file_path).await.unwrap();
+    assert!(db
+        .get_thumbnails_to_reencrypt(2, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    let in_use = db.get_encryption_key_ids_in_use().await.unwrap();
+    assert_eq!(in_use, vec![2]);
+    assert_eq!(keys.retire_unused(&in_use).await.unwrap(), vec![1]);
+    let data = std::fs::read(&file_path).unwrap();
+    assert_eq!(encrypted_key_id(&data), Some(2));
+    assert_eq!(keys.decrypt(&data).unwrap(), b"jpeg bytes");
+
+    // a thumbnail removed since lets go of its key
+    keys.rotate().await.unwrap();
+    std::fs::remove_file(&file_path).unwrap();
+    reencrypt_thumbnail(&db, &keys, &file_path).await.unwrap();
+    assert!(db.get_encryption_key_ids_in_use().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_marker_and_slide_images_let_go_of_the_rotated_key() {
+    let dir = tempfile::tempdir().unwrap();
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let keys = Arc::new(KeyRing::ephemeral());
+    let write = |name: &str, data: &[u8]| {
+        let path = dir.path().join(name).to_string_lossy().to_string();
+        std::fs::write(&path, keys.encrypt(data).unwrap().1).unwrap();
+        path
+    };
+    let marker_path = write("marker_2024-09-30_09-30-00.000.png.enc", b"png bytes");
+    let marker = db
+        .insert_marker(Utc::now(), None, Some(&marker_path), Some(1), None, None)
+        .await
+        .unwrap();
+    let slide_path = write("deck_1_slide_1.jpg.enc", b"jpeg bytes");
+    let deck = db
+        .insert_slide_deck(Utc::now(), 0, "Keynote", "roadmap.key")
+        .await
+        .unwrap();
+    db.insert_slide(
+        deck,
+        1,
+        Utc::now(),
+        Some(&slide_path),
+        Some(1),
+        1920,
+        1080,
+        "Q4",
+    )
+    .await
+    .unwrap();
+    // the slide image removed since
+    let gone_path = dir.path().join("deck_1_slide_2.jpg.enc");
+    let gone = db
+        .insert_slide(
+            deck,
+            2,
+            Utc::now(),
+            Some(&gone_path.to_string_lossy()),
+            Some(1),
+            1920,
+            1080,
+            "Q1",
+        )
+        .await
+        .unwrap();
+
+    keys.rotate().await.unwrap();
+    assert_eq!(db.get_encryption_key_ids_in_use().await.unwrap(), vec![1]);
+    encrypt_pending_chunks(&db, &keys).await.unwrap();
+
+    assert_eq!(db.get_encryption_key_ids_in_use().await.unwrap(), vec![2]);
+    assert_eq!(keys.key_ids(), vec![2]);
+    for (path, plain) in [
+        (&marker_path, &b"png bytes"[..]),
+        (&slide_path, &b"jpeg bytes"[..]),
+    ] {
+        let data = std::fs::read(path).unwrap();
+        assert_eq!(encrypted_key_id(&data), Some(2));
+        assert_eq!(keys.decrypt(&data).unwrap(), plain);
+    }
+    assert_eq!(
+        db.get_marker(marker).await.unwrap().unwrap().image_path,
+        Some(marker_path)
+    );
+    let (gone_image, gone_key): (Option<String>, Option<u32>) =
+        sqlx::query_as("SELECT image_path, encryption_key_id FROM slides WHERE id = ?1")
+            .bind(gone)
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
+    assert_eq!((gone_image, gone_key), (None, None));
+}
+
+#[cfg(feature = "sqlcipher")]
+#[tokio::test]
+async fn test_plaintext_database_is_encrypted_and_rekeyed() {
+    let dir = tempfile::tempdir().unwrap();
+    let database_path = dir.path().join("db.sqlite").to_string_lossy().to_string();
+    let db = DatabaseManager::new(&database_path).await.unwrap();
+    db.insert_video_chunk("chunk.mp4").await.unwrap();
+    db.pool.close().await;
+    let keys = KeyRing::ephemeral();
+
+    let db = DatabaseManager::new_encrypted(&database_path, &keys.database_key(), None)
+        .await
+        .unwrap();
+    let chunks: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM video_chunks")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(chunks, 1);
+    db.pool.close().await;
+    assert!(!std::fs::read(&database_path)
+        .unwrap()
+        .starts_with(b"SQLite format 3"));
+
+    keys.rotate().await.unwrap();
+    let db = DatabaseManager::new_encrypted(
+        &database_path,
+        &keys.database_key(),
+        keys.previous_database_key().as_deref(),
+    )
+    .await
+    .unwrap();
+    db.pool.close().await;
+    assert!(DatabaseManager::new_encrypted(
+        &database_path,
+        &keys.previous_database_key().unwrap(),
+        None
+    )
+    .await
+    .is_err());
+}
@@ -7,9 +7,14 @@ mod tests {
     use chrono::{Duration, Utc};
     use crossbeam::queue::SegQueue;
     use screenpipe_server::HealthCheckResponse;
-    use screenpipe_server::{health_check, AppState, DatabaseManager};
+    use screenpipe_core::{LlmConfig, PromptLibrary};
+    use screenpipe_server::{
+        health_check, ApiCredentials, AppIcons, AppState, CapturePause, ClipRenderer, DatabaseManager, LiveOcr, LlmService,
+        MarkerRecorder, TimelapseRenderer, ToolRegistry, Watchlist,
+    };
     use screenpipe_vision::OcrEngine; // Adjust this import based on your actual module structure
     use std::collections::HashMap;
+    use std::path::PathBuf;
     use std::sync::atomic::AtomicBool;
     use std::sync::Arc;
     use tower::ServiceExt; // for `oneshot` and `ready`
@@ -22,6 +27,35 @@ mod tests {
             audio_devices_control: Arc::new(SegQueue::new()),
             devices_status: HashMap::new(),
             app_start_time: Utc::now(),
+            llm: Arc::new(LlmService::new(
+                db.clone(),
+                LlmConfig::default(),
+                None,
+                None,
+                None,
+            )),
+            prompts: Arc::new(PromptLibrary::default()),
+            live_view: None,
+            timelapse: Arc::new(TimelapseRenderer::new(db.clone(), PathBuf::from("timelapses"))),
+            clips: Arc::new(ClipRenderer::new(db.clone(), PathBuf::from("clips"))),
+            capture_pause: Arc::new(CapturePause::new()),
+            encryption: None,
+            compliance: None,
+            consumer_redaction: None,
+            vault_export: None,
+            markers: Arc::new(MarkerRecorder::new(
+                db.clone(),
+                PathBuf::from("markers"),
+                None,
+                std::time::Duration::from_secs(30),
+            )),
+            profile: None,
+            watchlist: Watchlist::load(db.clone()).await.unwrap(),
+            app_icons: Arc::new(AppIcons::new(PathBuf::from("icons"))),
+            live_ocr: Arc::new(LiveOcr::new(0)),
+            automation: None,
+            llm_tools: Arc::new(ToolRegistry::default()),
+            credentials: Arc::new(ApiCredentials::new("app-token", Vec::new())),
         });
 
         let app = Router::new()
@@ -58,6 +92,29 @@ mod tests {
         assert!(!health_response.message.is_empty());
     }
 
+    #[tokio::test]
+    async fn test_health_endpoint_while_paused() {
+        let (app, state) = setup_test_app().await;
+        state.capture_pause.pause(None);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/health")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let health_response: HealthCheckResponse = serde_json::from_slice(&body).unwrap();
+
+        assert_eq!(health_response.status, "Paused");
+        assert_eq!(health_response.frame_status, "Paused");
+        assert_eq!(health_response.audio_status, "Paused");
+    }
+
     #[tokio::test]
     async fn test_health_endpoint_after_initialization() {
         let (app, _state) = setup_test_app().await;
@@ -0,0 +1,40 @@
+use axum::http::StatusCode;
+use screenpipe_server::encryption::open_media;
+use screenpipe_server::StorageError;
+
+#[tokio::test]
+async fn test_encrypted_media_without_keys_is_locked() {
+    // no keys are loaded in this test binary
+    let error = match open_media("/nonexistent/2024-01-01_00-00-00.mp4.enc").await {
+        Ok(_) => panic!("an encrypted chunk was opened without keys"),
+        Err(error) => error,
+    };
+    assert!(matches!(error, StorageError::MediaLocked(_)));
+    assert_eq!(error.code(), "media_locked");
+    assert_eq!(error.status(), StatusCode::LOCKED);
+}
+
+#[test]
+fn test_missing_media_is_told_apart_from_unreadable_media() {
+    let missing = StorageError::unreadable(
+        "data/chunk.mp4",
+        std::io::Error::from(std::io::ErrorKind::NotFound),
+    );
+    assert!(matches!(missing, StorageError::MediaMissing(_)));
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+
+    let denied = StorageError::unreadable(
+        "data/chunk.mp4",
+        std::io::Error::from(std::io::ErrorKind::PermissionDenied),
+    );
+    assert_eq!(denied.code(), "media_unreadable");
+    assert_eq!(denied.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert!(std::error::Error::source(&denied).is_some());
+
+    let database = StorageError::from(sqlx::Error::PoolTimedOut);
+    assert_eq!(database.code(), "database_error");
+    assert_eq!(
+        StorageError::FrameNotFound(3).status(),
+        StatusCode::NOT_FOUND
+    );
+}
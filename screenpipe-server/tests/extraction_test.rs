@@ -0,0 +1,110 @@
+use regex::Regex;
+use screenpipe_server::extraction::{extract_with_regex, parse_llm_extraction};
+use screenpipe_server::{DatabaseManager, ExtractionKind, PendingWrite};
+use serde_json::json;
+
+#[test]
+fn test_extract_with_regex_named_groups() {
+    let regex = Regex::new(r"Order #(?P<order_number>\d+) - (?P<customer>\w+)").unwrap();
+    let records = extract_with_regex(&regex, "Order #123 - Alice\nOrder #456 - Bob");
+
+    assert_eq!(
+        records,
+        vec![
+            json!({"order_number": "123", "customer": "Alice"}),
+            json!({"order_number": "456", "customer": "Bob"}),
+        ]
+    );
+}
+
+#[test]
+fn test_extract_with_regex_without_groups() {
+    let regex = Regex::new(r"INV-\d+").unwrap();
+    let records = extract_with_regex(&regex, "paid INV-42 today");
+    assert_eq!(records, vec![json!({"match": "INV-42"})]);
+}
+
+#[test]
+fn test_parse_llm_extraction_filters_required_fields() {
+    let schema = json!({"type": "object", "required": ["order_number"]});
+    let content = r#"```json
+[{"order_number": "123"}, {"customer": "Bob"}]
+```"#;
+    assert_eq!(
+        parse_llm_extraction(content, &schema),
+        vec![json!({"order_number": "123"})]
+    );
+    assert!(parse_llm_extraction("nothing here", &schema).is_empty());
+}
+
+#[tokio::test]
+async fn test_extracted_records_are_deduplicated() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let rule_id = db
+        .insert_extraction_rule("orders", Some("crm"), None, ExtractionKind::Regex, r"\d+")
+        .await
+        .unwrap();
+
+    let data = json!({"match": "123"});
+    let first = db
+        .insert_extracted_record(rule_id, 1, chrono::Utc::now(), "crm", "orders", &data)
+        .await
+        .unwrap();
+    let second = db
+        .insert_extracted_record(rule_id, 2, chrono::Utc::now(), "crm", "orders", &data)
+        .await
+        .unwrap();
+
+    assert!(first.is_some());
+    assert!(second.is_none());
+    assert_eq!(db.count_extracted_records(Some(rule_id)).await.unwrap(), 1);
+
+    let records = db.get_extracted_records(None, 10, 0, None).await.unwrap();
+    assert_eq!(records[0].data, data);
+
+    assert!(db.delete_extraction_rule(rule_id).await.unwrap());
+    assert!(db.get_extraction_rules(false).await.unwrap().is_empty());
+    assert_eq!(db.count_extracted_records(None).await.unwrap(), 0);
+}
+
+fn window(timestamp: chrono::DateTime<chrono::Utc>, window_name: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp,
+        text: "Order #123".to_string(),
+        text_json: String::new(),
+        app_name: "crm".to_string(),
+        window_name: window_name.to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: false,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+#[tokio::test]
+async fn test_frames_are_never_split_across_batches() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = chrono::Utc::now();
+    let mut writes = vec![PendingWrite::VideoChunk {
+        file_path: "chunk.mp4".to_string(),
+    }];
+    for second in 0..3 {
+        let timestamp = start + chrono::Duration::seconds(second);
+        writes.push(window(timestamp, "orders"));
+        writes.push(window(timestamp, "customers"));
+    }
+    db.insert_batch(&writes).await.unwrap();
+
+    let first = db.get_ocr_text_after_frame(0, 2).await.unwrap();
+    assert_eq!(first.len(), 4);
+    let last = first.iter().map(|row| row.frame_id).max().unwrap();
+    let rest = db.get_ocr_text_after_frame(last, 2).await.unwrap();
+    assert_eq!(rest.len(), 2);
+    assert!(rest.iter().all(|row| row.frame_id > last));
+    assert!(db
+        .get_ocr_text_after_frame(rest[0].frame_id, 2)
+        .await
+        .unwrap()
+        .is_empty());
+}
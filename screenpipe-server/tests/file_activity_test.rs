@@ -0,0 +1,78 @@
+use screenpipe_server::file_activity::is_ignored;
+use screenpipe_server::{start_file_watcher, DatabaseManager, FileEventKind};
+use screenpipe_vision::OcrEngine;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[test]
+fn test_temporary_and_hidden_files_are_ignored() {
+    assert!(is_ignored(Path::new("project/.git/index")));
+    assert!(is_ignored(Path::new("notes/.report.md.swp")));
+    assert!(is_ignored(Path::new("docs/~$report.docx")));
+    assert!(is_ignored(Path::new("downloads/video.mp4.crdownload")));
+    assert!(!is_ignored(Path::new("docs/report.docx")));
+}
+
+#[tokio::test]
+async fn test_saved_files_get_the_focused_window() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    db.insert_video_chunk("test_video.mp4").await.unwrap();
+    let frame_id = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        frame_id,
+        "TypeError: undefined",
+        "",
+        "Code",
+        "report.md - notes",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join("screenpipe");
+    std::fs::create_dir(&data_dir).unwrap();
+    tokio::spawn(start_file_watcher(
+        db.clone(),
+        vec![dir.path().to_path_buf()],
+        vec![data_dir.clone()],
+    ));
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    std::fs::write(dir.path().join("report.md"), "# Report").unwrap();
+    std::fs::write(dir.path().join("report.md.swp"), "swap").unwrap();
+    std::fs::write(data_dir.join("db.sqlite"), "data").unwrap();
+
+    let mut events = Vec::new();
+    for _ in 0..50 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        events = db
+            .get_file_events(None, None, None, None, 10, 0, None)
+            .await
+            .unwrap();
+        if events.iter().any(|event| event.kind == FileEventKind::Save) {
+            break;
+        }
+    }
+
+    assert!(!events.is_empty());
+    assert!(events.iter().all(|event| event.path.ends_with("report.md")
+        && event.app_name.as_deref() == Some("Code")
+        && event.window_name.as_deref() == Some("report.md - notes")));
+    // writing the file in several steps is one save
+    assert_eq!(
+        events
+            .iter()
+            .filter(|event| event.kind == FileEventKind::Save)
+            .count(),
+        1
+    );
+    assert_eq!(
+        db.count_file_events(Some("REPORT"), Some("code"), None, None)
+            .await
+            .unwrap(),
+        events.len() as i64
+    );
+}
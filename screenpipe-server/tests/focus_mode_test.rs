@@ -0,0 +1,92 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use screenpipe_server::activity::ActivityCategory;
+use screenpipe_server::focus_mode::{
+    focus_mode_at, parse_focus_assertions, parse_gdbus_boolean, parse_reg_dword, record_focus_mode,
+    DO_NOT_DISTURB_MODE,
+};
+use screenpipe_server::{do_not_disturb, DatabaseManager};
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 9, 23, 9, 0, 0).unwrap() + Duration::seconds(seconds)
+}
+
+#[test]
+fn test_the_focus_mode_is_read_from_the_platform() {
+    let assertions = r#"{"data":[{"storeAssertionRecords":[{"assertionUUID":"5A1C","assertionSource":{"assertionClientIdentifier":"com.apple.controlcenter.focus"},"assertionDetails":{"assertionDetailsModeIdentifier":"com.apple.focus.work","assertionDetailsIdentifier":"com.apple.controlcenter.dnd.manual","assertionDetailsReason":"user-action"},"assertionStartDateTimestamp":748000000}],"header":{"timestamp":748000000}}]}"#;
+    let configurations = r#"{"data":[{"modeConfigurations":{"com.apple.focus.work":{"mode":{"name":"Work","modeIdentifier":"com.apple.focus.work","semanticType":3}},"com.apple.donotdisturb.mode.default":{"mode":{"name":"Do Not Disturb"}}}}]}"#;
+    assert_eq!(
+        parse_focus_assertions(assertions, Some(configurations)),
+        Some("Work".to_string())
+    );
+    // named when the configurations can't be read
+    assert_eq!(
+        parse_focus_assertions(assertions, None),
+        Some(DO_NOT_DISTURB_MODE.to_string())
+    );
+    assert_eq!(
+        parse_focus_assertions(r#"{"data":[{"storeAssertionRecords":[]}]}"#, None),
+        None
+    );
+
+    assert_eq!(parse_gdbus_boolean("(<true>,)\n"), Some(true));
+    assert_eq!(parse_gdbus_boolean("(<false>,)"), Some(false));
+    assert_eq!(parse_gdbus_boolean("Error: no such property"), None);
+
+    let reg = "\r\nHKEY_CURRENT_USER\\Software\\Microsoft\\Windows\\CurrentVersion\\Notifications\\Settings\r\n    NOC_GLOBAL_SETTING_TOASTS_ENABLED    REG_DWORD    0x0\r\n\r\n";
+    assert_eq!(
+        parse_reg_dword(reg, "NOC_GLOBAL_SETTING_TOASTS_ENABLED"),
+        Some(0)
+    );
+    assert_eq!(
+        parse_reg_dword(reg, "NOC_GLOBAL_SETTING_ALLOW_TOASTS"),
+        None
+    );
+}
+
+#[tokio::test]
+async fn test_focus_periods_tag_sessions() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let work = Some("Work".to_string());
+
+    let period = record_focus_mode(&db, None, None, at(0)).await.unwrap();
+    assert_eq!(period, None);
+    let period = record_focus_mode(&db, period, work.clone(), at(10))
+        .await
+        .unwrap();
+    assert!(do_not_disturb());
+    let period = record_focus_mode(&db, period, work.clone(), at(20))
+        .await
+        .unwrap();
+    let period = record_focus_mode(&db, period, work, at(30)).await.unwrap();
+    assert_eq!(period.as_ref().unwrap().start_time, at(10));
+    let period = record_focus_mode(&db, period, Some("Sleep".to_string()), at(40))
+        .await
+        .unwrap();
+    let period = record_focus_mode(&db, period, None, at(50)).await.unwrap();
+    assert_eq!(period, None);
+    assert!(!do_not_disturb());
+
+    let periods = db.get_focus_periods(None, None, 10, 0, None).await.unwrap();
+    assert_eq!(periods.len(), 2);
+    assert_eq!(periods[0].mode, "Sleep");
+    assert_eq!(periods[1].mode, "Work");
+    assert_eq!(periods[1].start_time, at(10));
+    assert_eq!(periods[1].end_time, at(30));
+    assert_eq!(db.count_focus_periods(Some(at(35)), None).await.unwrap(), 1);
+
+    assert_eq!(focus_mode_at(&periods, at(25)), Some("Work".to_string()));
+    assert_eq!(focus_mode_at(&periods, at(35)), None);
+
+    db.insert_activity_session(at(15), at(25), "Code", ActivityCategory::Coding, 0.9)
+        .await
+        .unwrap();
+    db.insert_activity_session(at(60), at(90), "Slack", ActivityCategory::Email, 0.9)
+        .await
+        .unwrap();
+    let sessions = db
+        .get_activity_sessions(None, None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(sessions[0].focus_mode, None);
+    assert_eq!(sessions[1].focus_mode, Some("Work".to_string()));
+}
@@ -0,0 +1,146 @@
+use screenpipe_server::forget::drop_frames_command;
+use screenpipe_server::{forget, DatabaseManager, ForgetFilter};
+use screenpipe_vision::OcrEngine;
+use std::sync::Arc;
+
+async fn insert_frame(db: &DatabaseManager, app_name: &str, text: &str) -> i64 {
+    let frame_id = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        frame_id,
+        text,
+        "",
+        app_name,
+        "window",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+    frame_id
+}
+
+/// An old chunk only showing the keyword next to the chunk being recorded
+async fn setup_db() -> DatabaseManager {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_video_chunk("old.mp4").await.unwrap();
+    insert_frame(&db, "Notes", "plans for Project X").await;
+    insert_frame(&db, "Notes", "project x budget").await;
+    db.insert_video_chunk("current.mp4").await.unwrap();
+    insert_frame(&db, "Signal", "lunch at noon").await;
+    insert_frame(&db, "Notes", "project x is late").await;
+
+    let audio_chunk_id = db.insert_audio_chunk("audio.mp4").await.unwrap();
+    db.insert_audio_transcription(audio_chunk_id, "we talked about project x", 0, "Whisper")
+        .await
+        .unwrap();
+    db.insert_audio_transcription(audio_chunk_id, "see you tomorrow", 1, "Whisper")
+        .await
+        .unwrap();
+    db
+}
+
+async fn count(db: &DatabaseManager, sql: &str) -> i64 {
+    sqlx::query_scalar(sql).fetch_one(&db.pool).await.unwrap()
+}
+
+fn keyword(keyword: &str) -> ForgetFilter {
+    ForgetFilter {
+        keyword: Some(keyword.to_string()),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn test_dry_run_keeps_everything() {
+    let db = setup_db().await;
+
+    let report = forget(&db, &keyword("Project X"), true).await.unwrap();
+
+    assert!(report.dry_run);
+    assert_eq!(report.frames, 3);
+    assert_eq!(report.audio_transcriptions, 1);
+    assert_eq!(report.video_chunks_deleted, 1);
+    assert_eq!(report.audio_chunks_deleted, 0);
+    assert_eq!(report.media_kept, vec!["current.mp4".to_string()]);
+    assert!(report.text_index_entries >= 4);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM frames").await, 4);
+    assert_eq!(
+        count(&db, "SELECT COUNT(*) FROM audio_transcriptions").await,
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_forget_keyword_everywhere() {
+    let db = setup_db().await;
+
+    let report = forget(&db, &keyword("project x"), false).await.unwrap();
+
+    assert_eq!(report.frames, 3);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM frames").await, 1);
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM video_chunks").await, 1);
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM audio_transcriptions WHERE transcription LIKE '%project x%'"
+        )
+        .await,
+        0
+    );
+    // the chunk still holds the other transcription
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM audio_chunks").await, 1);
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM chunked_text_index_fts WHERE chunked_text_index_fts MATCH '\"project x\"'"
+        )
+        .await,
+        0
+    );
+    assert!(count(&db, "SELECT COUNT(*) FROM chunked_text_index_fts").await > 0);
+}
+
+#[tokio::test]
+async fn test_forget_app_leaves_audio() {
+    let db = setup_db().await;
+
+    let filter = ForgetFilter {
+        app_name: Some("signal".to_string()),
+        ..Default::default()
+    };
+    let report = forget(&db, &filter, false).await.unwrap();
+
+    assert_eq!(report.frames, 1);
+    assert_eq!(report.audio_transcriptions, 0);
+    assert_eq!(
+        count(
+            &db,
+            "SELECT COUNT(*) FROM ocr_text WHERE app_name = 'Signal'"
+        )
+        .await,
+        0
+    );
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM frames").await, 3);
+    assert_eq!(
+        count(&db, "SELECT COUNT(*) FROM audio_transcriptions").await,
+        2
+    );
+}
+
+#[tokio::test]
+async fn test_empty_filter_is_rejected() {
+    let db = setup_db().await;
+
+    assert!(forget(&db, &keyword("  "), false).await.is_err());
+    assert_eq!(count(&db, "SELECT COUNT(*) FROM frames").await, 4);
+}
+
+#[test]
+fn test_drop_frames_command_merges_offsets() {
+    let args = drop_frames_command("in.mp4", "out.mp4", &[3, 1, 2, 7]).args();
+
+    assert!(args.contains(&"select=not(between(n\\,1\\,3)+between(n\\,7\\,7))".to_string()));
+    let fps_mode = args.iter().position(|a| a == "-fps_mode").unwrap();
+    assert_eq!(args[fps_mode + 1], "passthrough");
+    assert_eq!(args.last().unwrap(), "out.mp4");
+}
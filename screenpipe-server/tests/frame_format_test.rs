@@ -0,0 +1,68 @@
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use screenpipe_server::{negotiate_frame_format, FrameEncoding, FrameFormat};
+
+fn screen() -> DynamicImage {
+    DynamicImage::ImageRgb8(RgbImage::from_fn(64, 48, |x, y| {
+        Rgb([(x * 4) as u8, (y * 5) as u8, 128])
+    }))
+}
+
+#[test]
+fn test_lossless_formats_keep_every_pixel() {
+    for format in [FrameFormat::Png, FrameFormat::Webp] {
+        assert!(format.is_lossless());
+        let encoded = FrameEncoding { format, quality: 1 }
+            .encode(&screen())
+            .unwrap();
+        let decoded = image::load_from_memory(&encoded).unwrap();
+        assert_eq!(decoded.to_rgb8(), screen().to_rgb8(), "{:?}", format);
+    }
+}
+
+#[test]
+fn test_lossy_formats_follow_the_quality() {
+    let encode = |format, quality| FrameEncoding { format, quality }.encode(&screen()).unwrap();
+
+    let jpeg = encode(FrameFormat::Jpeg, 90);
+    assert_eq!(
+        image::load_from_memory(&jpeg).unwrap().dimensions(),
+        (64, 48)
+    );
+    assert!(encode(FrameFormat::Jpeg, 10).len() < jpeg.len());
+
+    let avif = encode(FrameFormat::Avif, 60);
+    assert_eq!(&avif[4..12], b"ftypavif");
+    assert!(!FrameFormat::Avif.is_lossless());
+}
+
+#[test]
+fn test_the_stored_format_is_served_without_an_accept_header() {
+    assert_eq!(
+        negotiate_frame_format(None, FrameFormat::Webp),
+        Some(FrameFormat::Webp)
+    );
+    assert_eq!(
+        negotiate_frame_format(Some(""), FrameFormat::Png),
+        Some(FrameFormat::Png)
+    );
+}
+
+#[test]
+fn test_the_format_the_client_prefers_is_picked() {
+    let negotiate = |accept| negotiate_frame_format(Some(accept), FrameFormat::Png);
+    assert_eq!(negotiate("image/avif"), Some(FrameFormat::Avif));
+    assert_eq!(
+        negotiate("image/png;q=0.5, image/webp;q=0.9"),
+        Some(FrameFormat::Webp)
+    );
+    assert_eq!(negotiate("image/jpg"), Some(FrameFormat::Jpeg));
+    // what browsers send, the stored format wins the tie
+    assert_eq!(
+        negotiate("image/avif,image/webp,image/apng,image/svg+xml,image/*,*/*;q=0.8"),
+        Some(FrameFormat::Png)
+    );
+    assert_eq!(negotiate("image/*, image/png;q=0"), Some(FrameFormat::Webp));
+    assert_eq!(negotiate("*/*"), Some(FrameFormat::Png));
+    assert_eq!(negotiate("application/json"), None);
+    assert_eq!(negotiate("image/gif, image/png;q=0"), None);
+}
@@ -0,0 +1,116 @@
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_integrations::git_activity::GitEventKind;
+use screenpipe_server::git_activity::import_git_activity;
+use screenpipe_server::DatabaseManager;
+use std::collections::HashMap;
+use std::path::Path;
+
+const ZERO: &str = "0000000000000000000000000000000000000000";
+const A: &str = "1111111111111111111111111111111111111111";
+const B: &str = "2222222222222222222222222222222222222222";
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+fn write_reflog(repository: &Path, lines: &[(&str, &str, i64, &str)]) {
+    let logs = repository.join(".git/logs");
+    std::fs::create_dir_all(&logs).unwrap();
+    let reflog: String = lines
+        .iter()
+        .map(|(previous, commit, time, action)| {
+            format!(
+                "{} {} Ada <ada@example.com> {} +0000\t{}\n",
+                previous, commit, time, action
+            )
+        })
+        .collect();
+    std::fs::write(logs.join("HEAD"), reflog).unwrap();
+}
+
+#[tokio::test]
+async fn test_reflog_moves_are_stored_once() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let repository = dir.path().join("screenpipe");
+    write_reflog(
+        &repository,
+        &[
+            (ZERO, A, 1725000000, "commit (initial): init"),
+            (A, A, 1725000100, "checkout: moving from main to fix/ocr"),
+        ],
+    );
+    let repositories = vec![repository.clone(), dir.path().join("not-a-repo")];
+    let mut modified = HashMap::new();
+
+    assert_eq!(
+        import_git_activity(&db, &repositories, &mut modified, &[])
+            .await
+            .unwrap(),
+        2
+    );
+    // unchanged since
+    assert_eq!(
+        import_git_activity(&db, &repositories, &mut modified, &[])
+            .await
+            .unwrap(),
+        0
+    );
+
+    // committed during a pause, and right after it
+    write_reflog(
+        &repository,
+        &[
+            (ZERO, A, 1725000000, "commit (initial): init"),
+            (A, A, 1725000100, "checkout: moving from main to fix/ocr"),
+            (A, B, 1725000200, "commit: private notes"),
+            (B, A, 1725000300, "commit: fix ocr of dark mode"),
+        ],
+    );
+    modified.clear();
+    let pauses = [(at(1725000150), at(1725000250))];
+    assert_eq!(
+        import_git_activity(&db, &repositories, &mut modified, &pauses)
+            .await
+            .unwrap(),
+        1
+    );
+
+    let commits = db
+        .get_git_events(
+            None,
+            Some("screenpipe"),
+            Some(GitEventKind::Commit),
+            Some("fix/ocr"),
+            None,
+            None,
+            10,
+            0,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(commits.len(), 1);
+    assert_eq!(commits[0].message, "fix ocr of dark mode");
+    assert_eq!(commits[0].author, "Ada");
+    assert_eq!(commits[0].repository, repository.display().to_string());
+    assert_eq!(
+        db.count_git_events(
+            None,
+            None,
+            None,
+            None,
+            Some(at(1725000000)),
+            Some(at(1725000000) + Duration::seconds(150)),
+        )
+        .await
+        .unwrap(),
+        2
+    );
+    assert_eq!(
+        db.count_git_events(Some("PRIVATE"), None, None, None, None, None)
+            .await
+            .unwrap(),
+        0
+    );
+}
@@ -0,0 +1,48 @@
+use screenpipe_server::indicator::{
+    indicator_rects, toggle_capture, IndicatorState, IndicatorStyle,
+};
+use screenpipe_server::CapturePause;
+use std::sync::Arc;
+
+#[test]
+fn test_indicator_state_lines() {
+    for state in [IndicatorState::Capturing, IndicatorState::Paused] {
+        assert_eq!(state.as_str().parse::<IndicatorState>().unwrap(), state);
+    }
+    assert!("recording".parse::<IndicatorState>().is_err());
+}
+
+#[tokio::test]
+async fn test_toggle_capture_follows_pause() {
+    let pause = Arc::new(CapturePause::new());
+    assert_eq!(
+        IndicatorState::of(&pause.status()),
+        IndicatorState::Capturing
+    );
+
+    assert!(toggle_capture(&pause).paused);
+    assert_eq!(IndicatorState::of(&pause.status()), IndicatorState::Paused);
+
+    assert!(!toggle_capture(&pause).paused);
+    assert_eq!(
+        IndicatorState::of(&pause.status()),
+        IndicatorState::Capturing
+    );
+}
+
+#[test]
+fn test_indicator_rects() {
+    let dot = indicator_rects(IndicatorStyle::Dot, (1920, 0), (2560, 1440), 2.0);
+    assert_eq!(dot, vec![(1920 + 2560 - 16 - 24, 16, 24, 24)]);
+
+    let border = indicator_rects(IndicatorStyle::Border, (0, 0), (1920, 1080), 1.0);
+    assert_eq!(
+        border,
+        vec![
+            (0, 0, 1920, 3),
+            (0, 1077, 1920, 3),
+            (0, 3, 3, 1074),
+            (1917, 3, 3, 1074),
+        ]
+    );
+}
@@ -0,0 +1,33 @@
+use screenpipe_server::integrity::check_chunk;
+use screenpipe_server::ChunkIntegrity;
+
+#[tokio::test]
+async fn test_missing_chunk_is_broken() {
+    let integrity = check_chunk("/nonexistent/screenpipe/chunk.mp4")
+        .await
+        .unwrap();
+    assert_eq!(integrity, ChunkIntegrity::Broken);
+}
+
+#[tokio::test]
+async fn test_frame_directory_is_ok() {
+    let dir = std::env::temp_dir().join(format!(
+        "screenpipe_integrity_{}.frames",
+        std::process::id()
+    ));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let integrity = check_chunk(&dir.to_string_lossy()).await.unwrap();
+    assert_eq!(integrity, ChunkIntegrity::Ok);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_integrity_serializes_lowercase() {
+    assert_eq!(
+        serde_json::to_string(&ChunkIntegrity::Repaired).unwrap(),
+        "\"repaired\""
+    );
+    assert_eq!(ChunkIntegrity::Broken.as_str(), "broken");
+}
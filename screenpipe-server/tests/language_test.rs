@@ -0,0 +1,137 @@
+use screenpipe_server::language::{cjk_bigrams, detect_language, fts_query, index_new_texts};
+use screenpipe_server::{ContentType, DatabaseManager, SearchResult};
+use screenpipe_vision::OcrEngine;
+use std::sync::Arc;
+
+#[test]
+fn test_detect_language() {
+    assert_eq!(
+        detect_language("The quick fox is faster than the dog"),
+        "en"
+    );
+    assert_eq!(detect_language("Le café est très bon et pas cher"), "fr");
+    assert_eq!(
+        detect_language("Das ist nicht mein Auto, und die Tür ist auf"),
+        "de"
+    );
+    assert_eq!(detect_language("Nie wiem, czy to jest już gotowe"), "pl");
+    assert_eq!(detect_language("東京タワーの夜景はきれいです"), "ja");
+    assert_eq!(detect_language("北京欢迎你"), "zh");
+    assert_eq!(detect_language("안녕하세요 반갑습니다"), "ko");
+    assert_eq!(detect_language("Привет, как дела?"), "ru");
+    assert_eq!(detect_language("Привіт, як справи? Їжак"), "uk");
+    assert_eq!(detect_language("Καλημέρα σας"), "el");
+    // a file name, a number
+    assert_eq!(detect_language("main.rs"), "und");
+    assert_eq!(detect_language("12:45"), "und");
+}
+
+#[test]
+fn test_cjk_is_indexed_as_bigrams() {
+    assert_eq!(cjk_bigrams("東京タワー").trim(), "東京 京タ タワ ワー");
+    assert_eq!(cjk_bigrams("iPhone東京").trim(), "iPhone 東京");
+    assert_eq!(cjk_bigrams("plain text"), "plain text");
+    assert_eq!(
+        fts_query("東京 tower \"x\"").unwrap(),
+        "\"東京\" \"tower\" \"\"\"x\"\"\""
+    );
+    assert_eq!(fts_query("  ... "), None);
+}
+
+async fn search_ocr(db: &DatabaseManager, query: &str) -> Vec<String> {
+    db.search(query, ContentType::OCR, 100, 0, None, None, None, None)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter_map(|result| match result {
+            SearchResult::OCR(ocr) => Some(ocr.ocr_text),
+            _ => None,
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_non_english_text_is_searchable_once_indexed() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_video_chunk("video.mp4").await.unwrap();
+    for text in ["Le café est très bon", "東京タワーの夜景はきれいです"] {
+        let frame_id = db.insert_frame().await.unwrap();
+        db.insert_ocr_text(
+            frame_id,
+            text,
+            "",
+            "",
+            "",
+            Arc::new(OcrEngine::Tesseract),
+            false,
+        )
+        .await
+        .unwrap();
+    }
+    let audio_chunk_id = db.insert_audio_chunk("audio.mp4").await.unwrap();
+    db.insert_audio_transcription(
+        audio_chunk_id,
+        "La façade de l'hôtel est dans la rue",
+        0,
+        "",
+    )
+    .await
+    .unwrap();
+
+    // only the substring match before
+    assert!(search_ocr(&db, "cafe").await.is_empty());
+    assert_eq!(index_new_texts(&db).await.unwrap(), 3);
+    assert_eq!(index_new_texts(&db).await.unwrap(), 0);
+
+    assert_eq!(search_ocr(&db, "cafe").await, vec!["Le café est très bon"]);
+    assert_eq!(
+        search_ocr(&db, "bon tres").await,
+        vec!["Le café est très bon"]
+    );
+    assert_eq!(
+        search_ocr(&db, "夜景 東京").await,
+        vec!["東京タワーの夜景はきれいです"]
+    );
+    assert!(search_ocr(&db, "京都").await.is_empty());
+    let count = db
+        .count_search_results("cafe", ContentType::OCR, None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+
+    let audio = db
+        .search(
+            "facade hotel",
+            ContentType::Audio,
+            100,
+            0,
+            None,
+            None,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    match audio.as_slice() {
+        [SearchResult::Audio(audio)] => assert_eq!(audio.language.as_deref(), Some("fr")),
+        results => panic!("unexpected results {:?}", results.len()),
+    }
+
+    // edited text is indexed again
+    sqlx::query("UPDATE ocr_text SET text = 'Un thé, pas de café'")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    assert!(search_ocr(&db, "the pas").await.is_empty());
+    index_new_texts(&db).await.unwrap();
+    assert_eq!(search_ocr(&db, "the pas").await.len(), 2);
+    sqlx::query("DELETE FROM ocr_text")
+        .execute(&db.pool)
+        .await
+        .unwrap();
+    let (left,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM ocr_text_fts")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(left, 0);
+}
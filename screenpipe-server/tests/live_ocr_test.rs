@@ -0,0 +1,110 @@
+use screenpipe_server::live_ocr::{
+    blocks_text, clean_copied_text, window_blocks, LiveWindow, MonitorArea, Rect,
+};
+use screenpipe_server::LiveOcr;
+use screenpipe_vision::OcrWord;
+
+fn word(text: &str, left: i32, line: i32) -> OcrWord {
+    OcrWord {
+        text: text.to_string(),
+        left,
+        top: 10 * line,
+        width: 40,
+        height: 8,
+        confidence: 90.0,
+        line: (1, 1, line),
+    }
+}
+
+#[test]
+fn test_words_are_placed_on_the_desktop() {
+    // a retina monitor right of the main one, captured at twice its points
+    let monitor = MonitorArea {
+        id: 2,
+        x: 1440,
+        y: 0,
+        width: 1440,
+        height: 900,
+        frame_width: 2880,
+        frame_height: 1800,
+    };
+    let window = LiveWindow {
+        app_name: "Terminal".to_string(),
+        window_name: "zsh".to_string(),
+        focused: true,
+        position: (200, 100),
+    };
+    let words = [word("cargo", 0, 1), word("test", 50, 1), word("ok", 0, 2)];
+    let blocks = window_blocks(&window, &words, &monitor, 3);
+
+    assert_eq!(
+        blocks[1].frame_rect,
+        Rect {
+            x: 250.0,
+            y: 110.0,
+            width: 40.0,
+            height: 8.0
+        }
+    );
+    assert_eq!(
+        blocks[1].screen_rect,
+        Rect {
+            x: 1565.0,
+            y: 55.0,
+            width: 20.0,
+            height: 4.0
+        }
+    );
+    let lines: Vec<usize> = blocks.iter().map(|block| block.line).collect();
+    assert_eq!(lines, [3, 3, 4]);
+    assert!(blocks.iter().all(|block| block.focused));
+}
+
+#[tokio::test]
+async fn test_nothing_is_read_before_a_frame_is_captured() {
+    assert!(LiveOcr::new(0).current().await.unwrap().is_none());
+}
+
+#[test]
+fn test_copied_text_keeps_the_lines_of_the_screen() {
+    let monitor = MonitorArea {
+        id: 1,
+        x: 0,
+        y: 0,
+        width: 800,
+        height: 600,
+        frame_width: 800,
+        frame_height: 600,
+    };
+    let window = LiveWindow {
+        app_name: "Terminal".to_string(),
+        window_name: "zsh".to_string(),
+        focused: true,
+        position: (0, 0),
+    };
+    let words = [word("cargo", 0, 1), word("test", 50, 1), word("ok", 0, 2)];
+    let blocks = window_blocks(&window, &words, &monitor, 0);
+    assert_eq!(blocks_text(&blocks), "cargo test\nok");
+    assert_eq!(blocks_text(&[]), "");
+}
+
+#[test]
+fn test_copied_text_is_cleaned_for_pasting() {
+    let read = "Release notes\n\
+                \n\
+                \n\
+                The new ﬁlter keeps   the frames of the wind-\n\
+                ows that were focused and drops the\n\
+                others.\n\
+                |\n\
+                Known issues:\n\
+                exports of long decks are slow";
+    assert_eq!(
+        clean_copied_text(read),
+        "Release notes\n\
+         \n\
+         The new filter keeps the frames of the windows that were focused and drops the others.\n\
+         Known issues:\n\
+         exports of long decks are slow"
+    );
+}
@@ -0,0 +1,51 @@
+use screenpipe_core::{EncoderQuality, VideoEncoder};
+use screenpipe_server::live_view::{is_live_segment, with_token, LiveView, LIVE_PAGE, LIVE_PLAYER};
+use std::path::PathBuf;
+
+#[test]
+fn test_live_view_token_check() {
+    let live_view = LiveView::new(
+        PathBuf::from("live"),
+        "s3cret".to_string(),
+        VideoEncoder::Software,
+        EncoderQuality::Medium,
+    );
+
+    assert!(live_view.is_authorized(Some("s3cret")));
+    assert!(!live_view.is_authorized(Some("s3cre")));
+    assert!(!live_view.is_authorized(Some("s3cres")));
+    assert!(!live_view.is_authorized(None));
+}
+
+#[test]
+fn test_live_segment_names() {
+    assert!(is_live_segment("segment_00012.m4s"));
+    assert!(is_live_segment("init.mp4"));
+    assert!(!is_live_segment("../db.sqlite"));
+    assert!(!is_live_segment("..%2Fsegment.m4s"));
+    assert!(!is_live_segment(".m4s"));
+    assert!(!is_live_segment("segment_00012.ts"));
+    assert!(!is_live_segment("index.m3u8"));
+}
+
+#[test]
+fn test_playlist_segments_carry_token() {
+    let playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MAP:URI=\"init.mp4\"\n#EXTINF:1.000000,\nsegment_00001.m4s\n";
+
+    assert_eq!(
+        with_token(playlist, "a b", None),
+        "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MAP:URI=\"init.mp4?token=a%20b\"\n#EXTINF:1.000000,\nsegment_00001.m4s?token=a%20b"
+    );
+    assert_eq!(
+        with_token(playlist, "a", Some("u/1")),
+        "#EXTM3U\n#EXT-X-TARGETDURATION:1\n#EXT-X-MAP:URI=\"init.mp4?token=a&user_token=u%2F1\"\n#EXTINF:1.000000,\nsegment_00001.m4s?token=a&user_token=u%2F1"
+    );
+}
+
+#[test]
+fn test_live_page_loads_nothing_from_other_origins() {
+    assert!(LIVE_PAGE.contains("src=\"/live/player.js\""));
+    for page in [LIVE_PAGE, LIVE_PLAYER] {
+        assert!(!page.contains("http://") && !page.contains("https://"));
+    }
+}
@@ -0,0 +1,220 @@
+use axum::routing::post;
+use axum::{Json, Router};
+use chrono::{Duration, Utc};
+use screenpipe_core::{ChatMessage, LlmConfig, TokenUsage};
+use screenpipe_server::llm::{cache_key, estimate_cost};
+use screenpipe_server::{DatabaseManager, LlmService};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn test_estimate_cost() {
+    let usage = TokenUsage {
+        prompt_tokens: 1_000_000,
+        completion_tokens: 100_000,
+    };
+    let openai = LlmConfig {
+        api_url: "https://api.openai.com/v1".to_string(),
+        api_key: None,
+        model: "gpt-4o-mini".to_string(),
+    };
+    assert!((estimate_cost(&openai, &usage) - 0.21).abs() < 1e-9);
+
+    // local models are free
+    let ollama = LlmConfig {
+        model: "gpt-4o-mini".to_string(),
+        ..LlmConfig::default()
+    };
+    assert_eq!(estimate_cost(&ollama, &usage), 0.0);
+}
+
+#[tokio::test]
+async fn test_llm_usage_aggregation() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let usage = TokenUsage {
+        prompt_tokens: 100,
+        completion_tokens: 10,
+    };
+    db.insert_llm_usage("summarize", "gpt-4o", "chat", &usage, 0.5)
+        .await
+        .unwrap();
+    db.insert_llm_usage("summarize", "gpt-4o", "chat", &usage, 0.25)
+        .await
+        .unwrap();
+    db.insert_llm_usage("my-pipe", "gpt-4o", "chat", &usage, 1.0)
+        .await
+        .unwrap();
+
+    let cost = db
+        .get_llm_cost_since(Utc::now() - Duration::hours(1))
+        .await
+        .unwrap();
+    assert!((cost - 1.75).abs() < 1e-9);
+
+    let daily = db.get_llm_usage_per_day(None, None).await.unwrap();
+    assert_eq!(daily.len(), 2);
+    assert_eq!(daily[0].source, "my-pipe");
+    assert_eq!(daily[1].source, "summarize");
+    assert_eq!(daily[1].calls, 2);
+    assert_eq!(daily[1].prompt_tokens, 200);
+}
+
+#[tokio::test]
+async fn test_llm_cache_ttl_and_invalidation() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let api_url = "https://api.openai.com/v1";
+    let key = cache_key(api_url, "gpt-4o", "chat", "hello");
+    assert_ne!(key, cache_key(api_url, "gpt-4o-mini", "chat", "hello"));
+    assert_ne!(
+        key,
+        cache_key("http://localhost:11434/v1", "gpt-4o", "chat", "hello")
+    );
+
+    db.insert_llm_cache(
+        &key,
+        api_url,
+        "gpt-4o",
+        "chat",
+        "hi!",
+        Utc::now() + Duration::hours(1),
+    )
+    .await
+    .unwrap();
+    let expired_key = cache_key(api_url, "gpt-4o", "chat", "bye");
+    db.insert_llm_cache(
+        &expired_key,
+        api_url,
+        "gpt-4o",
+        "chat",
+        "see you",
+        Utc::now() - Duration::seconds(1),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(
+        db.get_llm_cache(&key).await.unwrap().as_deref(),
+        Some("hi!")
+    );
+    assert!(db.get_llm_cache(&expired_key).await.unwrap().is_none());
+    assert_eq!(db.count_llm_cache().await.unwrap(), 1);
+
+    assert_eq!(
+        db.invalidate_llm_cache(Some("claude-3-haiku"))
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(db.invalidate_llm_cache(Some("gpt-4o")).await.unwrap(), 1);
+    assert!(db.get_llm_cache(&key).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_cached_answers_are_served_over_budget() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let config = LlmConfig {
+        api_url: "https://api.openai.com/v1".to_string(),
+        api_key: None,
+        model: "gpt-4o".to_string(),
+    };
+    let usage = TokenUsage {
+        prompt_tokens: 100,
+        completion_tokens: 10,
+    };
+    db.insert_llm_usage("summarize", "gpt-4o", "chat", &usage, 5.0)
+        .await
+        .unwrap();
+    let messages = vec![ChatMessage::user("hello")];
+    let prompt = serde_json::to_string(&messages).unwrap();
+    db.insert_llm_cache(
+        &cache_key(&config.api_url, &config.model, "chat", &prompt),
+        &config.api_url,
+        &config.model,
+        "chat",
+        "hi!",
+        Utc::now() + Duration::hours(1),
+    )
+    .await
+    .unwrap();
+    let llm = LlmService::new(
+        db,
+        config,
+        None,
+        Some(1.0),
+        Some(std::time::Duration::from_secs(3600)),
+    );
+
+    assert_eq!(
+        llm.chat("summarize", &messages).await.unwrap().content,
+        "hi!"
+    );
+    // anything else would be sent, and the budget is spent
+    assert!(llm
+        .chat("summarize", &[ChatMessage::user("bye")])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_embeddings_are_asked_of_the_embedding_model_once() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let requests: Arc<Mutex<Vec<Value>>> = Arc::default();
+    let recorded = requests.clone();
+    let app = Router::new().route(
+        "/v1/embeddings",
+        post(move |Json(body): Json<Value>| {
+            let recorded = recorded.clone();
+            async move {
+                let data: Vec<Value> = body["input"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|input| json!({"embedding": [input.as_str().unwrap().len(), 1.0]}))
+                    .collect();
+                recorded.lock().unwrap().push(body);
+                Json(json!({"data": data, "usage": {"prompt_tokens": 4, "total_tokens": 4}}))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    let config = LlmConfig {
+        api_url: format!("http://{}/v1", addr),
+        api_key: None,
+        model: "llama3.1".to_string(),
+    };
+    let cache_ttl = Some(std::time::Duration::from_secs(3600));
+
+    // the chat model isn't sent in its place
+    let chat_only = LlmService::new(db.clone(), config.clone(), None, None, cache_ttl);
+    assert!(chat_only
+        .embed("search", &["invoice".to_string()])
+        .await
+        .is_err());
+    assert!(requests.lock().unwrap().is_empty());
+
+    let llm = LlmService::new(db.clone(), config, None, None, cache_ttl)
+        .with_embedding_models(Some("nomic-embed-text".to_string()), None);
+    let first = llm
+        .embed("search", &["invoice".to_string(), "tax".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(first, vec![vec![7.0, 1.0], vec![3.0, 1.0]]);
+    let second = llm
+        .embed("search", &["receipt".to_string(), "invoice".to_string()])
+        .await
+        .unwrap();
+    assert_eq!(second, vec![vec![7.0, 1.0], vec![7.0, 1.0]]);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests
+        .iter()
+        .all(|request| request["model"] == "nomic-embed-text"));
+    // the cached one isn't sent again
+    assert_eq!(requests[1]["input"], json!(["receipt"]));
+    let usage = db.get_llm_usage_per_day(None, None).await.unwrap();
+    assert_eq!(usage[0].model, "nomic-embed-text");
+    assert_eq!(usage[0].calls, 2);
+}
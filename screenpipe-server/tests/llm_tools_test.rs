@@ -0,0 +1,316 @@
+use axum::body::{to_bytes, Body};
+use axum::extract::State;
+use axum::http::{header, HeaderMap, Request};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use screenpipe_core::{parse_tool_calls, ChatMessage, LlmConfig, PIPE_HEADER};
+use screenpipe_server::credentials::{local_peer_is_app, APP_TOKEN_FILE};
+use screenpipe_server::llm_tools::{CREATE_TAG_TOOL, SEARCH_HISTORY_TOOL};
+use screenpipe_server::{
+    builtin_tools, ApiCredentials, Caller, DatabaseManager, LlmService, ToolGrant, ToolRegistry,
+};
+use screenpipe_vision::OcrEngine;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use tower::ServiceExt;
+
+async fn db_with_frame() -> (Arc<DatabaseManager>, i64) {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    db.insert_video_chunk("test_video.mp4").await.unwrap();
+    let frame_id = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        frame_id,
+        "Invoice 2024-117 due on October 31",
+        "",
+        "Preview",
+        "invoice.pdf",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+    (db, frame_id)
+}
+
+fn names(tools: &[Arc<dyn screenpipe_server::LlmTool>]) -> Vec<String> {
+    tools.iter().map(|tool| tool.definition().name).collect()
+}
+
+#[tokio::test]
+async fn test_pipes_only_get_the_enabled_tools_of_their_grant() {
+    let (db, _) = db_with_frame().await;
+    let mut registry = builtin_tools(db);
+    assert_eq!(
+        registry.names(),
+        vec![
+            "create_tag",
+            "get_frame",
+            "search_history",
+            "send_notification"
+        ]
+    );
+    assert!(registry.enable(&["delete_everything".to_string()]).is_err());
+    registry
+        .enable(&[
+            SEARCH_HISTORY_TOOL.to_string(),
+            CREATE_TAG_TOOL.to_string(),
+            "get_frame".to_string(),
+        ])
+        .unwrap();
+    registry
+        .grant(vec![ToolGrant {
+            pipe: "invoice-filer".to_string(),
+            tools: ["search_history", "send_notification"]
+                .into_iter()
+                .map(str::to_string)
+                .collect(),
+        }])
+        .unwrap();
+
+    assert_eq!(
+        names(&registry.allowed(&Caller::App)),
+        vec!["create_tag", "get_frame", "search_history"]
+    );
+    // send_notification is granted but not enabled
+    assert_eq!(
+        names(&registry.allowed(&Caller::Pipe("invoice-filer".to_string()))),
+        vec!["search_history"]
+    );
+    assert!(registry
+        .allowed(&Caller::Pipe("other-pipe".to_string()))
+        .is_empty());
+    // a pipe that leaves out its name or token isn't the app
+    assert!(registry.allowed(&Caller::Unknown).is_empty());
+    assert!(registry.allowed(&Caller::Remote).is_empty());
+}
+
+async fn tools_of(app: Router, request: Request<Body>) -> Vec<String> {
+    let response = app.oneshot(request).await.unwrap();
+    let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+#[tokio::test]
+async fn test_the_app_gets_tools_with_its_token_and_on_the_socket() {
+    let (db, _) = db_with_frame().await;
+    let dir = tempfile::tempdir().unwrap();
+    let credentials = Arc::new(ApiCredentials::load(dir.path(), None, Vec::new()).unwrap());
+    let registry = Arc::new(builtin_tools(db));
+    // the tools /ask and /chat would offer the caller
+    let app = Router::new().route(
+        "/tools",
+        get(
+            |State((credentials, registry)): State<(Arc<ApiCredentials>, Arc<ToolRegistry>)>,
+             headers: HeaderMap| async move {
+                let tools = registry.allowed(&credentials.caller(&headers));
+                Json(names(&tools))
+            },
+        )
+        .with_state((credentials.clone(), registry)),
+    );
+
+    // what the app reads from the data directory and sends
+    let token = std::fs::read_to_string(dir.path().join(APP_TOKEN_FILE)).unwrap();
+    let with_token = tools_of(
+        app.clone(),
+        Request::builder()
+            .uri("/tools")
+            .header(header::AUTHORIZATION, format!("Bearer {}", token))
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(with_token.len(), 4);
+    let without = tools_of(
+        app.clone(),
+        Request::builder()
+            .uri("/tools")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(without.is_empty());
+
+    // the processes of the user on the socket don't need it
+    let socket = app.layer(axum::middleware::from_fn_with_state(
+        credentials,
+        local_peer_is_app,
+    ));
+    let local = tools_of(
+        socket.clone(),
+        Request::builder()
+            .uri("/tools")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert_eq!(local.len(), 4);
+    // but a pipe without its token gets none there either
+    let pipe = tools_of(
+        socket,
+        Request::builder()
+            .uri("/tools")
+            .header(PIPE_HEADER, "invoice-filer")
+            .body(Body::empty())
+            .unwrap(),
+    )
+    .await;
+    assert!(pipe.is_empty());
+}
+
+#[tokio::test]
+async fn test_tools_search_read_and_tag_frames() {
+    let (db, frame_id) = db_with_frame().await;
+    let registry = builtin_tools(db.clone());
+    let tools = registry.allowed(&Caller::App);
+    let tool = |name: &str| {
+        tools
+            .iter()
+            .find(|tool| tool.definition().name == name)
+            .unwrap()
+            .clone()
+    };
+
+    let found = tool("search_history")
+        .call(&json!({"query": "invoice"}), None)
+        .await
+        .unwrap();
+    assert_eq!(found["results"][0]["frame_id"], frame_id);
+    assert_eq!(found["results"][0]["app_name"], "Preview");
+
+    let frame = tool("get_frame")
+        .call(&json!({"frame_id": frame_id}), None)
+        .await
+        .unwrap();
+    assert_eq!(frame["text"], "Invoice 2024-117 due on October 31");
+    assert!(tool("get_frame")
+        .call(&json!({"frame_id": frame_id + 1}), None)
+        .await
+        .is_err());
+
+    let tagged = tool("create_tag")
+        .call(
+            &json!({"frame_id": frame_id, "tag": " invoice due "}),
+            Some("invoice-filer"),
+        )
+        .await
+        .unwrap();
+    assert_eq!(tagged["tag"], "invoice due");
+    let marker = db
+        .get_marker(tagged["marker_id"].as_i64().unwrap())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(marker.frame_id, Some(frame_id));
+    assert_eq!(marker.note.as_deref(), Some("invoice due"));
+    assert!(tool("create_tag")
+        .call(&json!({"frame_id": frame_id, "tag": ""}), None)
+        .await
+        .is_err());
+}
+
+#[test]
+fn test_parses_tool_calls_of_openai_and_ollama() {
+    let openai = json!({"choices": [{"message": {"content": null, "tool_calls": [{
+        "id": "call_abc",
+        "type": "function",
+        "function": {"name": "search_history", "arguments": "{\"query\": \"invoice\"}"},
+    }]}}]});
+    let calls = parse_tool_calls(&openai);
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].id, "call_abc");
+    assert_eq!(calls[0].arguments, json!({"query": "invoice"}));
+
+    // ollama sends the arguments as an object and no id
+    let ollama = json!({"choices": [{"message": {"content": "", "tool_calls": [{
+        "function": {"name": "get_frame", "arguments": {"frame_id": 3}},
+    }]}}]});
+    let calls = parse_tool_calls(&ollama);
+    assert_eq!(calls[0].id, "call_0");
+    assert_eq!(calls[0].arguments, json!({"frame_id": 3}));
+
+    assert!(parse_tool_calls(&json!({"choices": [{"message": {"content": "hi"}}]})).is_empty());
+}
+
+#[tokio::test]
+async fn test_the_model_answers_with_what_its_tools_found() {
+    let (db, frame_id) = db_with_frame().await;
+    let requests: Arc<Mutex<Vec<Value>>> = Arc::default();
+    let recorded = requests.clone();
+    // calls search_history, then answers from its result
+    let app = Router::new().route(
+        "/v1/chat/completions",
+        post(move |Json(body): Json<Value>| {
+            let recorded = recorded.clone();
+            async move {
+                let messages = body["messages"].as_array().unwrap().clone();
+                recorded.lock().unwrap().push(body);
+                let message = match messages.iter().find(|m| m["role"] == "tool") {
+                    None => json!({"role": "assistant", "content": null, "tool_calls": [{
+                        "id": "call_1",
+                        "type": "function",
+                        "function": {"name": "search_history", "arguments": "{\"query\":\"invoice\"}"},
+                    }]}),
+                    Some(result) => {
+                        let result: Value =
+                            serde_json::from_str(result["content"].as_str().unwrap()).unwrap();
+                        json!({"role": "assistant", "content": format!(
+                            "It's due on October 31, frame {}",
+                            result["results"][0]["frame_id"]
+                        )})
+                    }
+                };
+                Json(json!({
+                    "choices": [{"message": message}],
+                    "usage": {"prompt_tokens": 50, "completion_tokens": 10},
+                }))
+            }
+        }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+    let llm = LlmService::new(
+        db.clone(),
+        LlmConfig {
+            api_url: format!("http://{}/v1", addr),
+            api_key: None,
+            model: "llama3.1".to_string(),
+        },
+        None,
+        None,
+        None,
+    );
+    let registry = builtin_tools(db.clone());
+    let chat = llm
+        .chat_with_tools(
+            "ask",
+            &[ChatMessage::user("when is the invoice due?")],
+            &registry.allowed(&Caller::App),
+            None,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(
+        chat.answer.content,
+        format!("It's due on October 31, frame {}", frame_id)
+    );
+    assert_eq!(chat.tool_runs.len(), 1);
+    assert_eq!(chat.tool_runs[0].name, "search_history");
+    assert!(chat.tool_runs[0].error.is_none());
+
+    // every round is recorded
+    let usage = db.get_llm_usage_per_day(None, None).await.unwrap();
+    assert_eq!(usage[0].source, "ask");
+    assert_eq!(usage[0].calls, 2);
+
+    let requests = requests.lock().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0]["tools"].as_array().unwrap().len(), 4);
+    // the model's call and its result are sent back in OpenAI's format
+    let messages = requests[1]["messages"].as_array().unwrap();
+    assert_eq!(messages[1]["tool_calls"][0]["id"], "call_1");
+    assert_eq!(messages[2]["tool_call_id"], "call_1");
+}
@@ -0,0 +1,43 @@
+#![cfg(unix)]
+
+use axum::routing::get;
+use axum::Router;
+use screenpipe_server::local_api::{default_api_socket, serve_local_api};
+use std::os::unix::fs::PermissionsExt;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+#[tokio::test]
+async fn test_serves_the_api_on_a_private_socket() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = default_api_socket(dir.path());
+    assert_eq!(path, dir.path().join("screenpipe.sock"));
+    // left by a crashed run
+    std::fs::write(&path, "").unwrap();
+
+    let app = Router::new().route("/health", get(|| async { "healthy" }));
+    tokio::spawn(serve_local_api(path.clone(), app));
+    let mut stream = None;
+    for _ in 0..50 {
+        if let Ok(connected) = UnixStream::connect(&path).await {
+            stream = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let mut stream = stream.expect("the socket was not served");
+    assert_eq!(
+        std::fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+        0o600
+    );
+
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("healthy"), "{}", response);
+}
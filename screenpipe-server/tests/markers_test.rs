@@ -0,0 +1,107 @@
+use chrono::{Duration as ChronoDuration, Utc};
+use screenpipe_server::markers::transcribe_memo;
+use screenpipe_server::{DatabaseManager, MarkerRecorder, MarkerRequest};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn recorder(db: &Arc<DatabaseManager>) -> MarkerRecorder {
+    MarkerRecorder::new(
+        db.clone(),
+        PathBuf::from("markers"),
+        None,
+        Duration::from_secs(30),
+    )
+}
+
+#[tokio::test]
+async fn test_marker_keeps_its_frame_out_of_compaction() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    db.insert_video_chunk("marked.mp4").await.unwrap();
+    let frame_id = db.insert_frame().await.unwrap();
+
+    let later = Utc::now() + ChronoDuration::minutes(1);
+    assert_eq!(
+        db.get_video_chunks_to_compact(later, 10)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+
+    let marker = recorder(&db)
+        .create(
+            MarkerRequest {
+                note: Some("  pricing idea ".to_string()),
+                memo_secs: None,
+            },
+            false,
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(marker.note.as_deref(), Some("pricing idea"));
+    assert_eq!(marker.frame_id, Some(frame_id));
+    // the screen isn't recorded
+    assert!(marker.image_path.is_none());
+    assert!(db
+        .get_video_chunks_to_compact(later, 10)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(db.count_markers(None, None).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn test_memo_is_what_was_said_after_the_marker() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let markers = recorder(&db);
+    assert!(markers
+        .create(
+            MarkerRequest {
+                note: None,
+                memo_secs: Some(600),
+            },
+            false,
+        )
+        .await
+        .is_err());
+
+    let marker = markers
+        .create(
+            MarkerRequest {
+                note: None,
+                memo_secs: Some(10),
+            },
+            false,
+        )
+        .await
+        .unwrap();
+    assert_eq!(marker.memo_secs, Some(10));
+    assert!(marker.memo.is_none());
+
+    let audio_chunk_id = db.insert_audio_chunk("memo.mp4").await.unwrap();
+    db.insert_audio_transcription(audio_chunk_id, " ask Dana about the invoice ", 0, "whisper")
+        .await
+        .unwrap();
+
+    let memo = transcribe_memo(&db, marker.id, marker.timestamp, Duration::from_secs(60))
+        .await
+        .unwrap();
+    assert_eq!(memo, "ask Dana about the invoice");
+    let marker = db.get_marker(marker.id).await.unwrap().unwrap();
+    assert_eq!(marker.memo.as_deref(), Some("ask Dana about the invoice"));
+
+    // no memo while paused
+    let paused = markers
+        .create(
+            MarkerRequest {
+                note: None,
+                memo_secs: Some(10),
+            },
+            true,
+        )
+        .await
+        .unwrap();
+    assert!(paused.memo_secs.is_none());
+}
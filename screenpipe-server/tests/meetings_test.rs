@@ -0,0 +1,41 @@
+use chrono::{Duration, TimeZone, Utc};
+use screenpipe_server::meetings::{detect_meeting_sessions, parse_meeting_summary_output};
+
+#[test]
+fn test_detect_meeting_sessions_splits_on_silence() {
+    let start = Utc.with_ymd_and_hms(2024, 8, 20, 10, 0, 0).unwrap();
+    // 10 minutes of talking every 30s, a 20 minutes break, then 2 minutes of talking
+    let mut timestamps: Vec<_> = (0..20).map(|i| start + Duration::seconds(30 * i)).collect();
+    let second_start = start + Duration::minutes(30);
+    timestamps.extend((0..4).map(|i| second_start + Duration::seconds(30 * i)));
+
+    let sessions = detect_meeting_sessions(&timestamps, Duration::minutes(5), Duration::minutes(5));
+
+    assert_eq!(
+        sessions.len(),
+        1,
+        "short conversation should not be a meeting"
+    );
+    assert_eq!(sessions[0].0, start);
+    assert_eq!(sessions[0].1, start + Duration::seconds(30 * 19));
+}
+
+#[test]
+fn test_detect_meeting_sessions_empty() {
+    assert!(detect_meeting_sessions(&[], Duration::minutes(5), Duration::minutes(5)).is_empty());
+}
+
+#[test]
+fn test_parse_meeting_summary_output_in_markdown() {
+    let content = r#"Here is the summary:
+```json
+{"summary": "Planned the release", "decisions": ["Ship on friday"], "action_items": [{"description": "Write changelog", "owner": "Louis"}, {"description": "Update docs", "owner": null}]}
+```"#;
+
+    let output = parse_meeting_summary_output(content).unwrap();
+    assert_eq!(output.summary, "Planned the release");
+    assert_eq!(output.decisions, vec!["Ship on friday"]);
+    assert_eq!(output.action_items.len(), 2);
+    assert_eq!(output.action_items[0].owner.as_deref(), Some("Louis"));
+    assert!(output.action_items[1].owner.is_none());
+}
@@ -0,0 +1,60 @@
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use screenpipe_server::native_encoder::{
+    is_native_chunk, native_frame_file, read_native_frame, NativeChunkWriter,
+};
+use screenpipe_server::{FrameEncoding, FrameFormat};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+#[tokio::test]
+async fn test_native_chunk_roundtrip() {
+    let dir = tempdir().unwrap();
+    let chunk = dir.path().join("2024-08-25_12-00-00.frames");
+    let mut writer = NativeChunkWriter::create(&chunk, FrameEncoding::default()).unwrap();
+
+    for shade in [10u8, 200u8] {
+        let image = RgbImage::from_pixel(64, 32, Rgb([shade, shade, shade]));
+        writer
+            .write_frame(Arc::new(DynamicImage::ImageRgb8(image)))
+            .await
+            .unwrap();
+    }
+    assert_eq!(writer.frames(), 2);
+
+    let chunk = chunk.to_str().unwrap();
+    assert!(is_native_chunk(chunk));
+    let frame = read_native_frame(chunk, 1).await.unwrap();
+    assert_eq!(frame.dimensions(), (64, 32));
+    // lossless
+    assert_eq!(frame.to_rgb8().get_pixel(0, 0), &Rgb([200, 200, 200]));
+    assert!(read_native_frame(chunk, 2).await.is_err());
+}
+
+#[tokio::test]
+async fn test_native_chunks_are_written_in_the_configured_format() {
+    let dir = tempdir().unwrap();
+    let chunk = dir.path().join("2024-09-20_12-00-00.frames");
+    let encoding = FrameEncoding {
+        format: FrameFormat::Jpeg,
+        quality: 90,
+    };
+    let mut writer = NativeChunkWriter::create(&chunk, encoding).unwrap();
+    let image = RgbImage::from_pixel(64, 32, Rgb([120, 120, 120]));
+    writer
+        .write_frame(Arc::new(DynamicImage::ImageRgb8(image)))
+        .await
+        .unwrap();
+
+    let chunk = chunk.to_str().unwrap();
+    let (path, format) = native_frame_file(chunk, 0).unwrap();
+    assert_eq!(format, FrameFormat::Jpeg);
+    assert_eq!(path.extension().unwrap(), "jpg");
+    let frame = read_native_frame(chunk, 0).await.unwrap();
+    assert_eq!(frame.dimensions(), (64, 32));
+    let pixel = frame.to_rgb8().get_pixel(10, 10).0;
+    assert!(
+        pixel.iter().all(|channel| channel.abs_diff(120) <= 2),
+        "{:?}",
+        pixel
+    );
+}
@@ -0,0 +1,146 @@
+use chrono::{DateTime, Utc};
+use screenpipe_integrations::notifications::{to_filetime, NotificationSource, OsNotification};
+use screenpipe_server::notifications::{read_action_center, record_notifications};
+use screenpipe_server::{forget, DatabaseManager, ForgetFilter};
+use sqlx::sqlite::SqliteConnectOptions;
+use sqlx::ConnectOptions;
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+fn notification(seconds: i64, app: &str, title: &str, body: Option<&str>) -> OsNotification {
+    OsNotification {
+        timestamp: at(seconds),
+        app: app.to_string(),
+        title: title.to_string(),
+        body: body.map(str::to_string),
+        source: NotificationSource::DBus,
+    }
+}
+
+#[tokio::test]
+async fn test_notifications_are_stored_once() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let notifications = vec![
+        notification(
+            1727000000,
+            "Slack",
+            "Alice in #launch",
+            Some("deploy is green"),
+        ),
+        // shown during a pause
+        notification(1727000100, "Slack", "Bob in #launch", Some("rolling back")),
+        notification(1727000200, "Firefox", "Download finished", None),
+    ];
+    let pauses = vec![(at(1727000050), at(1727000150))];
+
+    assert_eq!(
+        record_notifications(&db, &notifications, &pauses)
+            .await
+            .unwrap(),
+        2
+    );
+    assert_eq!(
+        record_notifications(&db, &notifications, &pauses)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        db.get_latest_notification_time(NotificationSource::DBus)
+            .await
+            .unwrap(),
+        Some(at(1727000200))
+    );
+    assert_eq!(
+        db.get_latest_notification_time(NotificationSource::ActionCenter)
+            .await
+            .unwrap(),
+        None
+    );
+
+    let found = db
+        .get_notifications(Some("DEPLOY"), Some("slack"), None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].title, "Alice in #launch");
+    assert_eq!(found[0].source, "dbus");
+    let all = db
+        .get_notifications(None, None, None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(all[0].app_name, "Firefox");
+    assert_eq!(
+        db.count_notifications(None, None, Some(at(1727000100)), None)
+            .await
+            .unwrap(),
+        1
+    );
+
+    let filter = ForgetFilter {
+        app_name: Some("slack".to_string()),
+        ..Default::default()
+    };
+    let report = forget(&db, &filter, false).await.unwrap();
+    assert_eq!(report.notifications, 1);
+    assert_eq!(
+        db.count_notifications(None, None, None, None)
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_toasts_are_read_from_the_action_center() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("wpndatabase.db");
+    let mut connection = SqliteConnectOptions::new()
+        .filename(&path)
+        .create_if_missing(true)
+        .connect()
+        .await
+        .unwrap();
+    sqlx::query("CREATE TABLE NotificationHandler (RecordId INTEGER PRIMARY KEY, PrimaryId TEXT)")
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    sqlx::query(
+        "CREATE TABLE Notification (Id INTEGER PRIMARY KEY, HandlerId INTEGER, Type TEXT, Payload BLOB, ArrivalTime INTEGER)",
+    )
+    .execute(&mut connection)
+    .await
+    .unwrap();
+    sqlx::query("INSERT INTO NotificationHandler VALUES (1, 'Microsoft.Outlook')")
+        .execute(&mut connection)
+        .await
+        .unwrap();
+    let toasts = [
+        (1727000000, "toast", "<toast><visual><binding><text>Old</text></binding></visual></toast>"),
+        (1727000100, "toast", "<toast><visual><binding><text>Standup moved</text><text>to 10:30</text></binding></visual></toast>"),
+        (1727000200, "tile", "<tile><visual><binding><text>5 unread</text></binding></visual></tile>"),
+    ];
+    for (seconds, kind, payload) in toasts {
+        sqlx::query("INSERT INTO Notification (HandlerId, Type, Payload, ArrivalTime) VALUES (1, ?1, ?2, ?3)")
+            .bind(kind)
+            .bind(payload.as_bytes())
+            .bind(to_filetime(at(seconds)))
+            .execute(&mut connection)
+            .await
+            .unwrap();
+    }
+
+    let notifications = read_action_center(&path, at(1727000000)).await.unwrap();
+    assert_eq!(
+        notifications,
+        vec![OsNotification {
+            timestamp: at(1727000100),
+            app: "Microsoft.Outlook".to_string(),
+            title: "Standup moved".to_string(),
+            body: Some("to 10:30".to_string()),
+            source: NotificationSource::ActionCenter,
+        }]
+    );
+}
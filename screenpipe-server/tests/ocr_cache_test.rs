@@ -0,0 +1,66 @@
+use image::{DynamicImage, Luma};
+use screenpipe_server::{
+    ContentType, DatabaseManager, SearchResult, SyntheticFrame, SyntheticWindow, SyntheticWorkload,
+};
+use screenpipe_vision::ocr_cache_stats;
+use std::sync::Arc;
+
+fn stripes(period: u32, width: u32) -> DynamicImage {
+    let image = image::ImageBuffer::from_fn(width, 200, |x, _| {
+        Luma([if (x / period).is_multiple_of(2) {
+            255u8
+        } else {
+            0
+        }])
+    });
+    DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(image).to_rgb8())
+}
+
+fn window(app_name: &str, image: DynamicImage, text: &str) -> SyntheticWindow {
+    SyntheticWindow {
+        app_name: app_name.to_string(),
+        window_name: format!("{} - main", app_name),
+        focused: false,
+        image,
+        text: text.to_string(),
+    }
+}
+
+#[tokio::test]
+async fn test_unchanged_windows_are_not_ocr_d_again() {
+    let sidebar = stripes(20, 120);
+    let frames = [(10, "fn main() {}"), (30, "fn main() { run() }")]
+        .into_iter()
+        .map(|(period, code)| SyntheticFrame {
+            image: stripes(period, 320),
+            windows: vec![
+                window("Finder", sidebar.clone(), "Desktop\nDocuments"),
+                window("Code", stripes(period, 320), code),
+            ],
+        })
+        .collect();
+    let workload = SyntheticWorkload {
+        video_chunk: "synthetic.mp4".to_string(),
+        frames,
+        audio: Vec::new(),
+    };
+
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let before = ocr_cache_stats();
+    let report = workload.replay(Arc::clone(&db)).await.unwrap();
+    assert_eq!(report.windows, 4);
+    let after = ocr_cache_stats();
+    assert_eq!(after.hits - before.hits, 1);
+    assert_eq!(after.misses - before.misses, 3);
+
+    // the cached text is stored with the second frame like an OCR'd one
+    let sidebars = db
+        .search("Documents", ContentType::OCR, 10, 0, None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(sidebars.len(), 2);
+    assert!(sidebars.iter().all(|result| matches!(
+        result,
+        SearchResult::OCR(ocr) if ocr.ocr_text == "Desktop\nDocuments"
+    )));
+}
@@ -0,0 +1,187 @@
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_server::{
+    ContentType, Cursor, CursorPosition, DatabaseManager, FileEventKind, Page, PendingWrite,
+    SearchResult,
+};
+
+fn ocr(timestamp: DateTime<Utc>, text: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp,
+        text: text.to_string(),
+        text_json: String::new(),
+        app_name: "Notes".to_string(),
+        window_name: "todo".to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+fn transcript(timestamp: DateTime<Utc>, text: &str) -> PendingWrite {
+    PendingWrite::Transcription {
+        timestamp,
+        file_path: format!("{}.mp4", text),
+        transcription: text.to_string(),
+        engine: "WhisperTiny".to_string(),
+    }
+}
+
+fn text_of(result: &SearchResult) -> String {
+    match result {
+        SearchResult::OCR(ocr) => ocr.ocr_text.clone(),
+        SearchResult::Audio(audio) => audio.transcription.clone(),
+        SearchResult::FTS(fts) => fts.matched_text.clone(),
+    }
+}
+
+#[test]
+fn test_cursor_roundtrip() {
+    let mut cursor = Cursor::default();
+    cursor.set(
+        "ocr",
+        CursorPosition {
+            timestamp: Utc::now(),
+            id: 42,
+        },
+    );
+    let encoded = cursor.encode();
+    assert!(encoded
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+    assert_eq!(Cursor::decode(&encoded).unwrap(), cursor);
+    assert!(Cursor::decode("not a cursor").is_err());
+}
+
+#[test]
+fn test_only_full_pages_have_a_next_cursor() {
+    let position = |id: &i64| CursorPosition {
+        timestamp: DateTime::<Utc>::from_timestamp(1_725_000_000, 0).unwrap(),
+        id: *id,
+    };
+    let page = Page::from_source(vec![9, 8, 7], 3, 10, "items", position);
+    let cursor = Cursor::decode(page.next_cursor.as_deref().unwrap()).unwrap();
+    assert_eq!(cursor.get("items").unwrap().id, 7);
+
+    let last = Page::from_source(vec![2, 1], 3, 10, "items", position);
+    assert_eq!(last.next_cursor, None);
+}
+
+#[tokio::test]
+async fn test_search_pages_have_no_gaps_or_duplicates() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = Utc::now() - Duration::hours(1);
+    let mut writes = vec![PendingWrite::VideoChunk {
+        file_path: "chunk.mp4".to_string(),
+    }];
+    for i in 0..7 {
+        writes.push(ocr(
+            start + Duration::seconds(i * 10),
+            &format!("meeting note {}", i),
+        ));
+    }
+    for i in 0..5 {
+        writes.push(transcript(
+            start + Duration::seconds(i * 15),
+            &format!("meeting said {}", i),
+        ));
+    }
+    // two windows of the same frame share their timestamp
+    writes.push(ocr(start + Duration::seconds(30), "meeting note twin"));
+    db.insert_batch(&writes).await.unwrap();
+
+    let mut seen = Vec::new();
+    let mut timestamps = Vec::new();
+    let mut cursor = Cursor::default();
+    loop {
+        let (results, next_cursor) = db
+            .search_after(
+                "meeting",
+                ContentType::All,
+                3,
+                0,
+                &cursor,
+                None,
+                None,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        if seen.is_empty() {
+            // recorded while paging, it is newer than the cursor so it doesn't shift the pages
+            db.insert_batch(&[ocr(Utc::now(), "meeting note late")])
+                .await
+                .unwrap();
+        }
+        for result in &results {
+            seen.push(text_of(result));
+            timestamps.push(match result {
+                SearchResult::OCR(ocr) => ocr.timestamp,
+                SearchResult::Audio(audio) => audio.timestamp,
+                SearchResult::FTS(fts) => fts.frame_timestamp,
+            });
+        }
+        if results.len() < 3 {
+            break;
+        }
+        cursor = next_cursor;
+    }
+
+    assert_eq!(seen.len(), 13);
+    let mut unique = seen.clone();
+    unique.sort();
+    unique.dedup();
+    assert_eq!(unique.len(), 13);
+    assert!(!seen.contains(&"meeting note late".to_string()));
+    assert!(timestamps.windows(2).all(|pair| pair[0] >= pair[1]));
+}
+
+#[tokio::test]
+async fn test_list_resumes_after_the_cursor() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let timestamp = Utc::now();
+    // same time, the id breaks the tie
+    for i in 0..5 {
+        db.insert_file_event(
+            timestamp,
+            &format!("/home/me/{}.md", i),
+            FileEventKind::Save,
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    }
+
+    let first = db
+        .get_file_events(None, None, None, None, 2, 0, None)
+        .await
+        .unwrap();
+    let last = first.last().unwrap();
+    let position = CursorPosition {
+        timestamp: last.timestamp,
+        id: last.id,
+    };
+    let second = db
+        .get_file_events(None, None, None, None, 10, 0, Some(&position))
+        .await
+        .unwrap();
+
+    let paths: Vec<_> = first
+        .iter()
+        .chain(&second)
+        .map(|e| e.path.as_str())
+        .collect();
+    assert_eq!(
+        paths,
+        [
+            "/home/me/4.md",
+            "/home/me/3.md",
+            "/home/me/2.md",
+            "/home/me/1.md",
+            "/home/me/0.md"
+        ]
+    );
+}
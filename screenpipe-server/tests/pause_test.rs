@@ -0,0 +1,44 @@
+use screenpipe_server::CapturePause;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_pause_until_resumed() {
+    let pause = Arc::new(CapturePause::new());
+    let flag = pause.flag();
+
+    let status = pause.pause(None);
+    assert!(status.paused);
+    assert!(status.resume_at.is_none());
+    assert!(flag.load(std::sync::atomic::Ordering::SeqCst));
+
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(pause.is_paused());
+
+    assert!(!pause.resume().paused);
+    assert!(!flag.load(std::sync::atomic::Ordering::SeqCst));
+}
+
+#[tokio::test]
+async fn test_pause_resumes_after_duration() {
+    let pause = Arc::new(CapturePause::new());
+
+    let status = pause.pause(Some(Duration::from_millis(100)));
+    assert!(status.paused);
+    assert!(status.resume_at.is_some());
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(!pause.status().paused);
+    assert!(pause.status().resume_at.is_none());
+}
+
+#[tokio::test]
+async fn test_new_pause_replaces_auto_resume() {
+    let pause = Arc::new(CapturePause::new());
+
+    pause.pause(Some(Duration::from_millis(100)));
+    pause.pause(None);
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(pause.is_paused());
+}
@@ -0,0 +1,84 @@
+use image::{DynamicImage, ImageFormat, RgbImage};
+use screenpipe_server::pdf::text_lines_as_words;
+use screenpipe_server::{pdf_document, PdfPage, PdfWord};
+
+fn jpeg(width: u32, height: u32) -> Vec<u8> {
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(width, height))
+        .write_to(&mut std::io::Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+        .unwrap();
+    jpeg
+}
+
+fn word(text: &str, left: f64, top: f64, width: f64, height: f64) -> PdfWord {
+    PdfWord {
+        text: text.to_string(),
+        left,
+        top,
+        width,
+        height,
+    }
+}
+
+#[test]
+fn test_pdf_has_a_page_per_image() {
+    let pages: Vec<PdfPage> = ["Agenda", "Costs (draft)"]
+        .iter()
+        .map(|text| PdfPage {
+            jpeg: jpeg(64, 36),
+            width: 64,
+            height: 36,
+            words: text_lines_as_words(text, 64, 36),
+        })
+        .collect();
+    let bytes = pdf_document("Keynote", &pages);
+    let pdf = String::from_utf8_lossy(&bytes);
+    assert!(pdf.starts_with("%PDF-1.4"));
+    assert!(pdf.ends_with("%%EOF\n"));
+    assert_eq!(pdf.matches("/Type /Page ").count(), 2);
+    assert!(pdf.contains("/Count 2"));
+    assert!(pdf.contains("/MediaBox [0 0 48.00 27.00]"));
+    assert!(pdf.contains("(Costs \\(draft\\)) Tj"));
+    // the xref points at the objects
+    let xref: usize = pdf
+        .rsplit("startxref\n")
+        .next()
+        .unwrap()
+        .lines()
+        .next()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(bytes[xref..].starts_with(b"xref\n0 11\n"));
+}
+
+#[test]
+fn test_words_are_laid_over_their_boxes() {
+    let page = PdfPage {
+        jpeg: jpeg(400, 200),
+        width: 400,
+        height: 200,
+        // 5 characters of Helvetica at 15pt are 37.5pt wide, the box is twice that
+        words: vec![
+            word("hello", 40.0, 20.0, 100.0, 20.0),
+            word("  ", 0.0, 0.0, 5.0, 5.0),
+        ],
+    };
+    let bytes = pdf_document("frames", &[page]);
+    let pdf = String::from_utf8_lossy(&bytes);
+    assert!(pdf.contains("BT 3 Tr\n/F1 15.00 Tf 200.00 Tz 1 0 0 1 30.00 123.00 Tm (hello) Tj\nET"));
+    assert_eq!(pdf.matches(" Tj").count(), 1);
+}
+
+#[test]
+fn test_text_lines_fit_the_page() {
+    let text = (0..40)
+        .map(|line| format!("line {}", line))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let words = text_lines_as_words(&text, 320, 120);
+    // 8 pixel lines, a line and a half apart
+    assert_eq!(words.len(), 10);
+    assert_eq!(words[1], word("line 1", 8.0, 12.0, 24.0, 8.0));
+    assert!(words.iter().all(|word| word.top + word.height <= 120.0));
+}
@@ -0,0 +1,61 @@
+use screenpipe_server::power::{parse_pmset_batt, parse_pmset_therm, SttLocation};
+use screenpipe_server::{PowerProfiles, PowerState};
+
+#[test]
+fn test_profile_follows_power_state() {
+    let profiles: PowerProfiles = serde_json::from_str(
+        r#"{"battery": {"fps": 0.5, "stt": "cloud"}, "low_battery_percent": 15}"#,
+    )
+    .unwrap();
+    let state = |on_battery, battery_percent, thermal_pressure| PowerState {
+        on_battery,
+        battery_percent,
+        thermal_pressure,
+    };
+
+    assert_eq!(profiles.select(&state(false, Some(10), false)).0, "ac");
+    let (name, profile) = profiles.select(&state(true, Some(40), false));
+    assert_eq!(name, "battery");
+    assert_eq!(profile.stt, Some(SttLocation::Cloud));
+    assert_eq!(
+        profiles.select(&state(true, Some(15), false)).0,
+        "low_battery"
+    );
+    assert_eq!(profiles.select(&state(false, None, true)).0, "thermal");
+
+    // the profiles left out keep their defaults
+    assert_eq!(profiles.low_battery, PowerProfiles::default().low_battery);
+    assert!(profiles.low_battery.pause_background_jobs);
+}
+
+#[test]
+fn test_unknown_ocr_engine_is_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("power_profiles.json"),
+        r#"{"battery": {"ocr_engine": "fastest"}}"#,
+    )
+    .unwrap();
+    let error = PowerProfiles::load(dir.path()).unwrap_err();
+    assert!(format!("{:#}", error).contains("unknown ocr engine fastest"));
+
+    std::fs::write(
+        dir.path().join("power_profiles.json"),
+        r#"{"battery": {"ocr_engine": "tesseract"}}"#,
+    )
+    .unwrap();
+    let profiles = PowerProfiles::load(dir.path()).unwrap();
+    assert!(profiles.battery.ocr_engine().unwrap().is_some());
+}
+
+#[test]
+fn test_pmset_output() {
+    let batt = "Now drawing from 'Battery Power'\n -InternalBattery-0 (id=4653155)\t72%; discharging; 5:12 remaining present: true\n";
+    assert_eq!(parse_pmset_batt(batt), (true, Some(72)));
+    let batt = "Now drawing from 'AC Power'\n -InternalBattery-0 (id=4653155)\t100%; charged; 0:00 remaining present: true\n";
+    assert_eq!(parse_pmset_batt(batt), (false, Some(100)));
+
+    let therm = "Note: No thermal warning level has been recorded\n\tCPU_Scheduler_Limit \t= 100\n\tCPU_Available_CPUs \t= 8\n\tCPU_Speed_Limit \t= 70\n";
+    assert!(parse_pmset_therm(therm));
+    assert!(!parse_pmset_therm(&therm.replace("= 70", "= 100")));
+}
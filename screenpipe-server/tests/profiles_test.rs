@@ -0,0 +1,109 @@
+use image::{DynamicImage, RgbaImage};
+use screenpipe_core::PiiRedactor;
+use screenpipe_server::profiles::{profile_dir, PROFILES_FILE};
+use screenpipe_server::{ProfilesConfig, RedactionPolicy};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::CaptureResult;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Instant;
+
+fn frame(app_name: &str, window_name: &str) -> CaptureResult {
+    let image = Arc::new(DynamicImage::ImageRgba8(RgbaImage::new(40, 40)));
+    CaptureResult {
+        image: image.clone(),
+        png: Default::default(),
+        frame_number: 1,
+        timestamp: Instant::now(),
+        window_ocr_results: vec![WindowOcrResult {
+            window_name: window_name.to_string(),
+            app_name: app_name.to_string(),
+            image,
+            text: "hello".to_string(),
+            text_json: vec![HashMap::from([("text".to_string(), "hello".to_string())])],
+            focused: true,
+            position: (0, 0),
+            browser_page: None,
+        }],
+        private_windows: Vec::new(),
+    }
+}
+
+fn write_profiles(dir: &Path) {
+    std::fs::write(
+        dir.join(PROFILES_FILE),
+        r#"{
+            "profiles": {
+                "work": {"retention_days": 365, "blocked_windows": ["youtube|reddit"]},
+                "personal": {"retention_days": 30, "blocked_apps": ["Slack"]}
+            },
+            "sessions": {"alice": "personal"}
+        }"#,
+    )
+    .unwrap();
+}
+
+#[test]
+fn test_profile_is_the_requested_one_or_the_sessions() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(ProfilesConfig::load(dir.path())
+        .unwrap()
+        .resolve(None, Some("alice"))
+        .unwrap()
+        .is_none());
+
+    write_profiles(dir.path());
+    let profiles = ProfilesConfig::load(dir.path()).unwrap();
+
+    let (name, profile) = profiles.resolve(None, Some("alice")).unwrap().unwrap();
+    assert_eq!(name, "personal");
+    assert_eq!(profile.retention_days, Some(30));
+    let (name, _) = profiles
+        .resolve(Some("work"), Some("alice"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(name, "work");
+    assert!(profiles.resolve(None, Some("bob")).unwrap().is_none());
+    assert!(profiles.resolve(Some("school"), None).is_err());
+
+    assert_eq!(
+        profile_dir(dir.path(), "work"),
+        dir.path().join("profiles").join("work")
+    );
+}
+
+#[test]
+fn test_profile_names_stay_in_the_data_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(PROFILES_FILE),
+        r#"{"profiles": {"../work": {}}}"#,
+    )
+    .unwrap();
+
+    assert!(ProfilesConfig::load(dir.path()).is_err());
+}
+
+#[test]
+fn test_blocked_apps_and_windows_are_not_recorded() {
+    let dir = tempfile::tempdir().unwrap();
+    write_profiles(dir.path());
+    let profiles = ProfilesConfig::load(dir.path()).unwrap();
+    let policy = |name: &str| {
+        RedactionPolicy::new(
+            profiles.profiles[name].blocklist_rules(name).unwrap(),
+            PiiRedactor::default(),
+        )
+    };
+
+    let personal = policy("personal");
+    assert!(personal.apply_to_frame(frame("slack", "general")).is_none());
+    assert!(personal
+        .apply_to_frame(frame("Safari", "YouTube"))
+        .is_some());
+
+    let work = policy("work");
+    assert!(work.apply_to_frame(frame("Safari", "YouTube")).is_none());
+    assert!(work.apply_to_frame(frame("Slack", "general")).is_some());
+}
@@ -0,0 +1,161 @@
+use screenpipe_server::relocate::{media_size, relocate_media, RelocateOptions};
+use screenpipe_server::{get_database_file_chunks, ChunkKind, DatabaseManager};
+use std::path::Path;
+use std::time::Duration;
+
+fn options() -> RelocateOptions {
+    RelocateOptions {
+        settle: Duration::ZERO,
+        keep_old: false,
+    }
+}
+
+#[tokio::test]
+async fn test_recordings_are_moved_and_the_database_rewritten() {
+    let dir = tempfile::tempdir().unwrap();
+    let data_dir = dir.path().join("screenpipe");
+    let from = data_dir.join("data");
+    std::fs::create_dir_all(from.join("monitor_1")).unwrap();
+    std::fs::write(from.join("monitor_1").join("video.mp4"), b"frames").unwrap();
+    std::fs::write(from.join("audio.mp4"), b"speech").unwrap();
+
+    let database = data_dir.join("db.sqlite").to_string_lossy().into_owned();
+    let db = DatabaseManager::new(&database).await.unwrap();
+    let video_id = db
+        .insert_video_chunk(&from.join("monitor_1").join("video.mp4").to_string_lossy())
+        .await
+        .unwrap();
+    let audio_id = db
+        .insert_audio_chunk(&from.join("audio.mp4").to_string_lossy())
+        .await
+        .unwrap();
+    // a path merely sharing the prefix isn't moved
+    let other = format!("{}2/audio.mp4", from.display());
+    let other_id = db.insert_audio_chunk(&other).await.unwrap();
+    db.pool.close().await;
+
+    let to = dir.path().join("ssd").join("screenpipe");
+    let report = relocate_media(&data_dir, &from, &to, &options())
+        .await
+        .unwrap();
+    let to = to.canonicalize().unwrap();
+    assert_eq!(report.files_copied, 2);
+    assert_eq!(report.bytes_copied, 12);
+    assert_eq!(report.paths_rewritten, 2);
+    assert_eq!(report.previous, None);
+
+    assert_eq!(std::fs::read(to.join("audio.mp4")).unwrap(), b"speech");
+    // the recorder still writing to the old path lands on the new disk
+    assert!(std::fs::symlink_metadata(&from)
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert_eq!(
+        std::fs::read(from.join("monitor_1").join("video.mp4")).unwrap(),
+        b"frames"
+    );
+    let leftovers = std::fs::read_dir(dir.path().join("screenpipe"))
+        .unwrap()
+        .filter(|entry| {
+            entry
+                .as_ref()
+                .unwrap()
+                .file_name()
+                .to_string_lossy()
+                .contains(".relocated-")
+        })
+        .count();
+    assert_eq!(leftovers, 0);
+
+    assert_eq!(
+        get_database_file_chunks(&database, None).await.unwrap(),
+        vec![
+            (
+                ChunkKind::Video,
+                video_id,
+                to.join("monitor_1")
+                    .join("video.mp4")
+                    .to_string_lossy()
+                    .into_owned()
+            ),
+            (
+                ChunkKind::Audio,
+                audio_id,
+                to.join("audio.mp4").to_string_lossy().into_owned()
+            ),
+            (ChunkKind::Audio, other_id, other),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn test_the_old_directory_is_kept_when_asked() {
+    let dir = tempfile::tempdir().unwrap();
+    let from = dir.path().join("data");
+    std::fs::create_dir_all(&from).unwrap();
+    std::fs::write(from.join("audio.mp4"), b"speech").unwrap();
+    let database = dir.path().join("db.sqlite").to_string_lossy().into_owned();
+    DatabaseManager::new(&database)
+        .await
+        .unwrap()
+        .pool
+        .close()
+        .await;
+
+    let report = relocate_media(
+        dir.path(),
+        &from,
+        &dir.path().join("moved"),
+        &RelocateOptions {
+            keep_old: true,
+            ..options()
+        },
+    )
+    .await
+    .unwrap();
+    let previous = report.previous.unwrap();
+    assert_eq!(
+        std::fs::read(Path::new(&previous).join("audio.mp4")).unwrap(),
+        b"speech"
+    );
+    assert_eq!(media_size(Path::new(&report.to)).unwrap(), 6);
+}
+
+#[tokio::test]
+async fn test_unfit_destinations_are_refused() {
+    let dir = tempfile::tempdir().unwrap();
+    let from = dir.path().join("data");
+    std::fs::create_dir_all(&from).unwrap();
+    std::fs::write(from.join("audio.mp4"), b"speech").unwrap();
+    let taken = dir.path().join("taken");
+    std::fs::create_dir_all(&taken).unwrap();
+    std::fs::write(taken.join("notes.txt"), b"mine").unwrap();
+
+    let error = relocate_media(dir.path(), &from, &taken, &options())
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("is not empty"), "{}", error);
+    let error = relocate_media(dir.path(), &from, &from.join("inner"), &options())
+        .await
+        .unwrap_err();
+    assert!(
+        error.to_string().contains("inside one another"),
+        "{}",
+        error
+    );
+    let error = relocate_media(
+        dir.path(),
+        &dir.path().join("missing"),
+        &dir.path().join("moved"),
+        &options(),
+    )
+    .await
+    .unwrap_err();
+    assert!(
+        error.to_string().contains("has no recordings to move"),
+        "{}",
+        error
+    );
+    // nothing was touched
+    assert_eq!(std::fs::read(from.join("audio.mp4")).unwrap(), b"speech");
+}
@@ -0,0 +1,150 @@
+use chrono::Utc;
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_core::subscribe_events;
+use screenpipe_server::scene::layout_similarity;
+use screenpipe_server::{SceneKind, SceneTracker};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::CaptureResult;
+use std::sync::Arc;
+use std::time::Instant;
+
+struct Window {
+    app: &'static str,
+    title: &'static str,
+    focused: bool,
+    x: u32,
+    width: u32,
+    shade: u8,
+}
+
+/// A 320x180 screen of windows the whole height, each of one shade
+fn frame(windows: &[Window]) -> CaptureResult {
+    let mut screen = RgbaImage::from_pixel(320, 180, Rgba([40, 40, 40, 255]));
+    let mut results = Vec::new();
+    for window in windows {
+        let pixel = Rgba([window.shade, window.shade, window.shade, 255]);
+        let image = RgbaImage::from_pixel(window.width, 180, pixel);
+        image::imageops::replace(&mut screen, &image, window.x as i64, 0);
+        results.push(WindowOcrResult {
+            window_name: window.title.to_string(),
+            app_name: window.app.to_string(),
+            image: Arc::new(DynamicImage::ImageRgba8(image)),
+            text: String::new(),
+            text_json: Vec::new(),
+            focused: window.focused,
+            position: (window.x as i32, 0),
+            browser_page: None,
+        });
+    }
+    CaptureResult {
+        image: Arc::new(DynamicImage::ImageRgba8(screen)),
+        png: Default::default(),
+        frame_number: 1,
+        timestamp: Instant::now(),
+        window_ocr_results: results,
+        private_windows: Vec::new(),
+    }
+}
+
+fn editor(shade: u8) -> Window {
+    Window {
+        app: "Code",
+        title: "main.rs",
+        focused: true,
+        x: 0,
+        width: 200,
+        shade,
+    }
+}
+
+fn terminal(focused: bool) -> Window {
+    Window {
+        app: "Terminal",
+        title: "zsh",
+        focused,
+        x: 200,
+        width: 120,
+        shade: 20,
+    }
+}
+
+#[test]
+fn test_layout_similarity() {
+    let windows = vec![("Code".to_string(), 0, 0, 200, 100)];
+    assert_eq!(layout_similarity(&windows, &windows), 1.0);
+    let moved = vec![("Code".to_string(), 100, 0, 200, 100)];
+    assert_eq!(layout_similarity(&windows, &moved), 0.5);
+    let other = vec![("Mail".to_string(), 0, 0, 200, 100)];
+    assert_eq!(layout_similarity(&windows, &other), 0.0);
+}
+
+// one test, the monitor the focus was last on is shared
+#[tokio::test]
+async fn test_context_switches_are_told_from_small_changes() {
+    let mut events = subscribe_events();
+    let mut tracker = SceneTracker::new(1);
+    let now = Utc::now();
+    // the baseline, then a small edit
+    assert!(tracker
+        .observe(&frame(&[editor(250), terminal(false)]), now)
+        .is_none());
+    assert!(tracker
+        .observe(&frame(&[editor(245), terminal(false)]), now)
+        .is_none());
+
+    // the focus moves to the terminal, and the editor goes dark
+    let change = tracker
+        .observe(
+            &frame(&[
+                Window {
+                    focused: false,
+                    ..editor(120)
+                },
+                terminal(true),
+            ]),
+            now,
+        )
+        .unwrap();
+    assert_eq!(change.kind, SceneKind::AppSwitched);
+    assert_eq!(change.app_name, "Terminal");
+    assert_eq!(change.previous_app_name.as_deref(), Some("Code"));
+    let event = events.recv().await.unwrap();
+    assert_eq!(event.name, "scene.changed");
+    assert_eq!(event.data["kind"], "app_switched");
+
+    let browser = Window {
+        app: "Firefox",
+        title: "docs",
+        focused: true,
+        x: 0,
+        width: 320,
+        shade: 230,
+    };
+    let change = tracker.observe(&frame(&[browser]), now).unwrap();
+    assert_eq!(change.kind, SceneKind::LayoutChanged);
+    assert!(change.layout_similarity < 0.5);
+
+    let slides = Window {
+        app: "Keynote",
+        title: "Q3 review",
+        focused: true,
+        x: 0,
+        width: 320,
+        shade: 230,
+    };
+    let change = tracker.observe(&frame(&[slides]), now).unwrap();
+    assert_eq!(change.kind, SceneKind::PresentationStarted);
+    let change = tracker
+        .observe(&frame(&[editor(250), terminal(false)]), now)
+        .unwrap();
+    assert_eq!(change.kind, SceneKind::PresentationEnded);
+
+    // the focus is on the other monitor, then back on this one
+    let mut other_monitor = SceneTracker::new(2);
+    assert!(other_monitor.observe(&frame(&[editor(250)]), now).is_none());
+    let change = tracker
+        .observe(&frame(&[editor(250), terminal(false)]), now)
+        .unwrap();
+    assert_eq!(change.kind, SceneKind::MonitorSwitched);
+    assert_eq!(change.monitor_id, 1);
+}
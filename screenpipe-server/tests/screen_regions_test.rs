@@ -0,0 +1,254 @@
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use image::{DynamicImage, RgbImage};
+use screenpipe_server::live_ocr::Rect;
+use screenpipe_server::screen_regions::window_boxes;
+use screenpipe_server::{
+    forget, DatabaseManager, ForgetFilter, OcrBox, PendingWrite, RegionFilter,
+};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::OcrEngine;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn window(text_json: Vec<HashMap<String, String>>) -> WindowOcrResult {
+    WindowOcrResult {
+        window_name: "prices".to_string(),
+        app_name: "Stocks".to_string(),
+        image: Arc::new(DynamicImage::ImageRgb8(RgbImage::new(400, 200))),
+        text: String::new(),
+        text_json,
+        focused: true,
+        position: (100, 50),
+        browser_page: None,
+    }
+}
+
+fn line(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
+
+fn at(second: i64) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 9, 21, 9, 0, 0).unwrap() + Duration::seconds(second)
+}
+
+fn ocr(timestamp: DateTime<Utc>, app_name: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp,
+        text: String::new(),
+        text_json: String::new(),
+        app_name: app_name.to_string(),
+        window_name: "prices".to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+fn ocr_box(text: &str, x: f64, y: f64) -> OcrBox {
+    OcrBox {
+        text: text.to_string(),
+        confidence: Some(90.0),
+        rect: Rect {
+            x,
+            y,
+            width: 60.0,
+            height: 12.0,
+        },
+    }
+}
+
+fn status_bar() -> Rect {
+    Rect {
+        x: 0.0,
+        y: 0.0,
+        width: 1920.0,
+        height: 30.0,
+    }
+}
+
+#[test]
+fn test_boxes_are_moved_to_the_frame() {
+    let window = window(vec![
+        line(&[
+            ("text", "AAPL 227.52"),
+            ("confidence", "91.00"),
+            ("left", "10"),
+            ("top", "20"),
+            ("width", "80"),
+            ("height", "14"),
+        ]),
+        // lines without a box and the empty ones are left out
+        line(&[("text", "MSFT 430.10"), ("confidence", "88.00")]),
+        line(&[
+            ("text", " "),
+            ("left", "0"),
+            ("top", "0"),
+            ("width", "5"),
+            ("height", "5"),
+        ]),
+    ]);
+    let boxes = window_boxes(&window, &OcrEngine::Tesseract);
+    assert_eq!(
+        boxes,
+        vec![OcrBox {
+            text: "AAPL 227.52".to_string(),
+            confidence: Some(91.0),
+            rect: Rect {
+                x: 110.0,
+                y: 70.0,
+                width: 80.0,
+                height: 14.0,
+            },
+        }]
+    );
+
+    // Apple's boxes are fractions of the window from its bottom left corner
+    let window = self::window(vec![line(&[
+        ("text", "AAPL 227.52"),
+        ("conf", "0.5"),
+        ("left", "0.25"),
+        ("top", "0.75"),
+        ("width", "0.5"),
+        ("height", "0.25"),
+    ])]);
+    let boxes = window_boxes(&window, &OcrEngine::AppleNative);
+    assert_eq!(
+        boxes[0].rect,
+        Rect {
+            x: 200.0,
+            y: 50.0,
+            width: 200.0,
+            height: 50.0,
+        }
+    );
+    assert_eq!(boxes[0].confidence, Some(0.5));
+}
+
+#[tokio::test]
+async fn test_the_text_of_a_region_is_found_frame_by_frame() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let ids = db
+        .insert_batch(&[
+            // boxes of a frame written before any chunk have nowhere to go
+            ocr(at(0), "Stocks"),
+            PendingWrite::OcrBoxes {
+                monitor_id: Some(1),
+                boxes: vec![ocr_box("lost", 10.0, 5.0)],
+            },
+            PendingWrite::VideoChunk {
+                file_path: "chunk.mp4".to_string(),
+            },
+            ocr(at(10), "Stocks"),
+            PendingWrite::OcrBoxes {
+                monitor_id: Some(1),
+                boxes: vec![
+                    ocr_box("09:00", 1800.0, 5.0),
+                    ocr_box("AAPL 227.52", 10.0, 5.0),
+                    ocr_box("the body of the page", 10.0, 500.0),
+                ],
+            },
+            ocr(at(20), "Stocks"),
+            PendingWrite::OcrBoxes {
+                monitor_id: Some(1),
+                boxes: vec![ocr_box("AAPL 228.01", 10.0, 5.0)],
+            },
+            ocr(at(20), "Stocks"),
+            PendingWrite::OcrBoxes {
+                monitor_id: Some(2),
+                boxes: vec![ocr_box("other monitor", 10.0, 5.0)],
+            },
+            // its center is below the bar
+            ocr(at(30), "Stocks"),
+            PendingWrite::OcrBoxes {
+                monitor_id: Some(1),
+                boxes: vec![ocr_box("half in", 10.0, 25.0)],
+            },
+        ])
+        .await
+        .unwrap();
+    assert_eq!(ids[1], 0);
+
+    let mut filter = RegionFilter {
+        region: status_bar(),
+        start_time: None,
+        end_time: None,
+        monitor_id: Some(1),
+        app_name: None,
+    };
+    let texts = db.get_region_texts(&filter, 10, 0, None).await.unwrap();
+    let found: Vec<&str> = texts.iter().map(|text| text.text.as_str()).collect();
+    assert_eq!(found, vec!["AAPL 228.01", "AAPL 227.52\n09:00"]);
+    assert_eq!(texts[1].frame_id, ids[3]);
+    assert_eq!(texts[1].monitor_id, Some(1));
+    assert_eq!(texts[1].timestamp, at(10));
+    assert_eq!(texts[1].boxes.len(), 2);
+    assert_eq!(db.count_region_texts(&filter).await.unwrap(), 2);
+
+    filter.start_time = Some(at(5));
+    filter.end_time = Some(at(15));
+    let texts = db.get_region_texts(&filter, 10, 0, None).await.unwrap();
+    assert_eq!(texts.len(), 1);
+    assert_eq!(texts[0].timestamp, at(10));
+
+    filter.monitor_id = None;
+    filter.start_time = None;
+    filter.end_time = None;
+    assert_eq!(db.count_region_texts(&filter).await.unwrap(), 3);
+    filter.app_name = Some("stocks".to_string());
+    assert_eq!(db.count_region_texts(&filter).await.unwrap(), 3);
+    filter.app_name = Some("Notes".to_string());
+    assert_eq!(db.count_region_texts(&filter).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn test_forgotten_frames_take_their_boxes_with_them() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_batch(&[
+        PendingWrite::VideoChunk {
+            file_path: "chunk.mp4".to_string(),
+        },
+        ocr(at(10), "Signal"),
+        PendingWrite::OcrBoxes {
+            monitor_id: Some(1),
+            boxes: vec![ocr_box("a private message", 10.0, 5.0)],
+        },
+        ocr(at(10), "Stocks"),
+        PendingWrite::OcrBoxes {
+            monitor_id: Some(1),
+            boxes: vec![ocr_box("AAPL 227.52", 400.0, 5.0)],
+        },
+        PendingWrite::VideoChunk {
+            file_path: "current.mp4".to_string(),
+        },
+    ])
+    .await
+    .unwrap();
+
+    let filter = ForgetFilter {
+        app_name: Some("Signal".to_string()),
+        ..Default::default()
+    };
+    let report = forget(&db, &filter, false).await.unwrap();
+    assert_eq!(report.ocr_boxes, 1);
+
+    let region = RegionFilter {
+        region: status_bar(),
+        start_time: None,
+        end_time: None,
+        monitor_id: None,
+        app_name: None,
+    };
+    let texts = db.get_region_texts(&region, 10, 0, None).await.unwrap();
+    assert_eq!(texts.len(), 1);
+    assert_eq!(texts[0].text, "AAPL 227.52");
+    let indexed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM ocr_boxes_rtree")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(indexed, 1);
+}
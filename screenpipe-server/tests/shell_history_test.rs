@@ -0,0 +1,160 @@
+use chrono::{DateTime, Duration, Utc};
+use screenpipe_integrations::shell_history::Shell;
+use screenpipe_server::shell_history::{
+    import_shell_history, is_terminal, link_terminal_frames, record_hook_command, NewShellCommand,
+};
+use screenpipe_server::{CapturePause, DatabaseManager, PendingWrite, ShellCommandSource};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+fn at(seconds: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(seconds, 0).unwrap()
+}
+
+fn hook_command(command: &str, timestamp: DateTime<Utc>) -> NewShellCommand {
+    NewShellCommand {
+        timestamp,
+        ended_at: Some(timestamp + Duration::seconds(3)),
+        command: command.to_string(),
+        cwd: Some("/home/me/project".to_string()),
+        exit_code: Some(101),
+        shell: Shell::Zsh,
+        source: ShellCommandSource::Hook,
+    }
+}
+
+fn ocr(timestamp: DateTime<Utc>, app_name: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp,
+        text: "error[E0425]".to_string(),
+        text_json: String::new(),
+        app_name: app_name.to_string(),
+        window_name: "zsh".to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+#[test]
+fn test_recognizes_terminal_windows() {
+    assert!(is_terminal("iTerm2"));
+    assert!(is_terminal("Terminal"));
+    assert!(is_terminal("gnome-terminal-server"));
+    assert!(is_terminal("WindowsTerminal"));
+    assert!(is_terminal("kitty"));
+    assert!(!is_terminal("Slack"));
+}
+
+#[tokio::test]
+async fn test_history_and_hooks_store_each_command_once() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let dir = tempfile::tempdir().unwrap();
+    let history = dir.path().join(".zsh_history");
+    std::fs::write(
+        &history,
+        ": 1725000000:3;cargo test\n: 1725000010:0; export TOKEN=secret\n",
+    )
+    .unwrap();
+    let files = vec![(Shell::Zsh, history.clone())];
+    let mut modified = HashMap::new();
+
+    // the command typed after a space is left out
+    assert_eq!(
+        import_shell_history(&db, &files, &mut modified, &[])
+            .await
+            .unwrap(),
+        1
+    );
+    // unchanged since
+    assert_eq!(
+        import_shell_history(&db, &files, &mut modified, &[])
+            .await
+            .unwrap(),
+        0
+    );
+    // read again, the command is already stored
+    modified.clear();
+    assert_eq!(
+        import_shell_history(&db, &files, &mut modified, &[])
+            .await
+            .unwrap(),
+        0
+    );
+
+    // the hook adds the directory and exit code to the command of the history
+    let pause = Arc::new(CapturePause::new());
+    assert!(
+        record_hook_command(&db, &pause, hook_command("cargo test", at(1725000000)))
+            .await
+            .unwrap()
+    );
+    assert!(
+        !record_hook_command(&db, &pause, hook_command(" ls ~/secret", at(1725000020)))
+            .await
+            .unwrap()
+    );
+    pause.pause(None);
+    assert!(
+        !record_hook_command(&db, &pause, hook_command("git push", at(1725000030)))
+            .await
+            .unwrap()
+    );
+
+    let commands = db
+        .get_shell_commands(None, Some("/home/me"), None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(commands.len(), 1);
+    assert_eq!(commands[0].command, "cargo test");
+    assert_eq!(commands[0].exit_code, Some(101));
+    assert_eq!(commands[0].source, ShellCommandSource::Hook);
+    assert_eq!(commands[0].duration_secs, Some(3.0));
+    assert_eq!(
+        db.count_shell_commands(None, None, None, None)
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_commands_link_the_terminal_frames_of_their_output() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let started = Utc::now() - Duration::minutes(10);
+    db.insert_batch(&[
+        PendingWrite::VideoChunk {
+            file_path: "chunk.mp4".to_string(),
+        },
+        ocr(started - Duration::seconds(30), "iTerm2"),
+        ocr(started + Duration::seconds(1), "iTerm2"),
+        ocr(started + Duration::seconds(1), "Slack"),
+        // the output is still on screen right after
+        ocr(started + Duration::seconds(5), "iTerm2"),
+    ])
+    .await
+    .unwrap();
+    let pause = Arc::new(CapturePause::new());
+    record_hook_command(&db, &pause, hook_command("cargo build", started))
+        .await
+        .unwrap();
+
+    let mut commands = db
+        .get_shell_commands(Some("build"), None, None, None, 10, 0, None)
+        .await
+        .unwrap();
+    link_terminal_frames(&db, &mut commands).await.unwrap();
+    let windows = db
+        .get_frame_windows_between(started, started + Duration::seconds(10))
+        .await
+        .unwrap();
+    let terminal_frames: Vec<i64> = windows
+        .iter()
+        .filter(|(_, app_name, _)| app_name == "iTerm2")
+        .map(|(frame_id, _, _)| *frame_id)
+        .collect();
+    assert_eq!(terminal_frames.len(), 2);
+    assert_eq!(commands[0].frame_ids, terminal_frames);
+}
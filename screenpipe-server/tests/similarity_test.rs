@@ -0,0 +1,104 @@
+use image::{DynamicImage, Luma, Rgb};
+use screenpipe_server::similarity::{hash_distance, DEFAULT_MAX_DISTANCE};
+use screenpipe_server::{
+    find_similar_frames, perceptual_hash, DatabaseManager, SyntheticFrame, SyntheticWindow,
+    SyntheticWorkload,
+};
+use std::sync::Arc;
+
+/// A dark sidebar, a title bar and lines of text on a light page
+fn editor(lines: u32) -> DynamicImage {
+    let image = image::ImageBuffer::from_fn(640, 400, |x, y| {
+        let shade = if x < 120 {
+            40u8
+        } else if y < 30 {
+            90
+        } else if (y - 30) / 20 < lines && (y - 30) % 20 < 8 && x < 600 {
+            20
+        } else {
+            235
+        };
+        Luma([shade])
+    });
+    DynamicImage::ImageLuma8(image)
+}
+
+fn gradient() -> DynamicImage {
+    let image = image::ImageBuffer::from_fn(640, 400, |x, y| Luma([((x + y) % 256) as u8]));
+    DynamicImage::ImageLuma8(image)
+}
+
+fn frame(image: DynamicImage, app_name: &str, window_name: &str) -> SyntheticFrame {
+    let image = DynamicImage::ImageRgb8(image.to_rgb8());
+    SyntheticFrame {
+        image: image.clone(),
+        windows: vec![SyntheticWindow {
+            app_name: app_name.to_string(),
+            window_name: window_name.to_string(),
+            focused: true,
+            image,
+            text: format!("{} - {}", app_name, window_name),
+        }],
+    }
+}
+
+#[test]
+fn test_hash_survives_small_changes() {
+    let original = editor(12);
+    let hash = perceptual_hash(&original);
+
+    let mut brighter = original.to_rgb8();
+    for pixel in brighter.pixels_mut() {
+        *pixel = Rgb(pixel.0.map(|channel| channel.saturating_add(12)));
+    }
+    let scaled = original.resize_exact(1280, 800, image::imageops::FilterType::Nearest);
+
+    assert!(hash_distance(hash, perceptual_hash(&DynamicImage::ImageRgb8(brighter))) <= 2);
+    assert!(hash_distance(hash, perceptual_hash(&scaled)) <= 2);
+    assert!(hash_distance(hash, perceptual_hash(&editor(13))) <= DEFAULT_MAX_DISTANCE);
+    assert!(hash_distance(hash, perceptual_hash(&gradient())) > DEFAULT_MAX_DISTANCE);
+}
+
+#[tokio::test]
+async fn test_finds_recorded_frames_like_a_screenshot() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let workload = SyntheticWorkload {
+        video_chunk: "synthetic.mp4".to_string(),
+        frames: vec![
+            frame(editor(12), "Code", "main.rs"),
+            frame(gradient(), "Preview", "wallpaper.png"),
+            frame(editor(13), "Code", "main.rs"),
+        ],
+        audio: vec![],
+    };
+    let report = workload.replay(Arc::clone(&db)).await.unwrap();
+    assert_eq!(report.frames, 3);
+
+    let screenshot = editor(12).resize_exact(1280, 800, image::imageops::FilterType::Triangle);
+    let similar = find_similar_frames(
+        &db,
+        perceptual_hash(&screenshot),
+        DEFAULT_MAX_DISTANCE,
+        10,
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(similar.len(), 2);
+    assert!(similar.iter().all(|frame| frame.app_name == "Code"));
+    assert!(similar[0].distance <= similar[1].distance);
+    assert!(similar[0].similarity > 0.95);
+
+    // a recorded frame isn't similar to itself
+    let first = similar[0].frame_id;
+    let similar = find_similar_frames(
+        &db,
+        perceptual_hash(&editor(12)),
+        DEFAULT_MAX_DISTANCE,
+        10,
+        Some(first),
+    )
+    .await
+    .unwrap();
+    assert!(similar.iter().all(|frame| frame.frame_id != first));
+}
@@ -0,0 +1,112 @@
+use chrono::{Duration, Utc};
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_server::slides::{SlideAction, SlideDetector};
+use screenpipe_server::{DatabaseManager, SlideRecorder};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::CaptureResult;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A full screen window with a bar as wide as `bar` out of 10, so each slide looks different
+fn slide(app: &str, bar: u32, text: &str) -> CaptureResult {
+    let image = Arc::new(DynamicImage::ImageRgba8(RgbaImage::from_fn(
+        320,
+        180,
+        |x, y| {
+            if y > 60 && y < 120 && x < bar * 32 {
+                Rgba([20, 60, 160, 255])
+            } else {
+                Rgba([250, 250, 250, 255])
+            }
+        },
+    )));
+    CaptureResult {
+        image: image.clone(),
+        png: Default::default(),
+        frame_number: 1,
+        timestamp: Instant::now(),
+        window_ocr_results: vec![WindowOcrResult {
+            window_name: "Q3 review".to_string(),
+            app_name: app.to_string(),
+            image,
+            text: text.to_string(),
+            text_json: Vec::new(),
+            focused: true,
+            position: (0, 0),
+            browser_page: None,
+        }],
+        private_windows: Vec::new(),
+    }
+}
+
+fn kept(actions: &[SlideAction]) -> Vec<String> {
+    actions
+        .iter()
+        .map(|action| match action {
+            SlideAction::Slide(slide) => slide.text.clone(),
+            SlideAction::DeckEnded(_) => "ended".to_string(),
+        })
+        .collect()
+}
+
+#[test]
+fn test_unique_slides_are_kept_once() {
+    let mut detector = SlideDetector::default();
+    let start = Utc::now();
+    let at = |secs: i64| start + Duration::seconds(secs);
+    let mut actions = Vec::new();
+    let frames = [
+        (slide("Preview", 2, "Agenda"), at(0)),
+        // an animation, on screen for less than a second
+        (slide("Preview", 4, "Revenue"), at(10)),
+        (slide("Preview", 5, "Revenue up"), at(11)),
+        (slide("Preview", 8, "Costs"), at(20)),
+        // back to the agenda
+        (slide("Preview", 2, "Agenda"), at(30)),
+        (slide("Preview", 9, "Questions?"), at(40)),
+    ];
+    for (frame, timestamp) in &frames {
+        actions.extend(detector.observe(frame, *timestamp));
+    }
+    // an editor full screen is a document, not a slide
+    let document = (1..40)
+        .map(|line| format!("fn line_{}() {{}}", line))
+        .collect::<Vec<_>>()
+        .join("\n");
+    actions.extend(detector.observe(&slide("Code", 3, &document), at(50)));
+    assert_eq!(
+        kept(&actions),
+        vec!["Agenda", "Revenue up", "Costs", "Questions?", "ended"]
+    );
+}
+
+#[tokio::test]
+async fn test_slides_are_stored_as_a_deck() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let dir = tempfile::tempdir().unwrap();
+    let mut recorder = SlideRecorder::new(Arc::clone(&db), dir.path().join("slides"), 1);
+    let start = Utc::now();
+    recorder
+        .observe(&slide("Keynote", 2, "Agenda"), start)
+        .await;
+    recorder
+        .observe(
+            &slide("Keynote", 6, "Roadmap"),
+            start + Duration::seconds(30),
+        )
+        .await;
+    recorder
+        .observe(&slide("Finder", 0, ""), start + Duration::seconds(60))
+        .await;
+
+    let decks = db.get_slide_decks(None, None, 10, 0, None).await.unwrap();
+    assert_eq!(decks.len(), 1);
+    assert_eq!(decks[0].app_name, "Keynote");
+    assert_eq!(decks[0].slide_count, 2);
+    assert!(decks[0].ended_at.is_some());
+    let slides = db.get_slides(decks[0].id).await.unwrap();
+    let texts: Vec<&str> = slides.iter().map(|slide| slide.text.as_str()).collect();
+    assert_eq!(texts, vec!["Agenda", "Roadmap"]);
+    assert_eq!((slides[1].position, slides[1].width), (2, 320));
+    assert!(std::path::Path::new(slides[0].image_path.as_ref().unwrap()).exists());
+}
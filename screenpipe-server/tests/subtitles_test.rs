@@ -0,0 +1,60 @@
+use chrono::{Duration, TimeZone, Utc};
+use screenpipe_server::subtitles::{to_srt, transcript_cues, window_cues, ChunkFrame};
+
+fn frames() -> Vec<ChunkFrame> {
+    let start = Utc.with_ymd_and_hms(2024, 8, 27, 9, 0, 0).unwrap();
+    // captured every 2s but encoded at 1 fps
+    [
+        ("Code", "main.rs"),
+        ("Code", "main.rs"),
+        ("Slack", "general"),
+        ("", ""),
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, (app, window))| ChunkFrame {
+        timestamp: start + Duration::seconds(i as i64 * 2),
+        offset_index: i as i64,
+        app_name: app.to_string(),
+        window_name: window.to_string(),
+    })
+    .collect()
+}
+
+#[test]
+fn test_window_cues_merge_same_window() {
+    let cues = window_cues(&frames(), 1.0);
+
+    assert_eq!(cues.len(), 2);
+    assert_eq!(cues[0].text, "Code - main.rs");
+    assert_eq!((cues[0].start, cues[0].end), (0.0, 2.0));
+    assert_eq!(cues[1].text, "Slack - general");
+    assert_eq!((cues[1].start, cues[1].end), (2.0, 3.0));
+}
+
+#[test]
+fn test_transcript_cues_map_wall_time_to_video_time() {
+    let frames = frames();
+    let transcripts = vec![(
+        frames[0].timestamp + Duration::seconds(5),
+        " let's ship it ".to_string(),
+    )];
+
+    let cues = transcript_cues(&frames, &transcripts, Duration::seconds(3), 1.0);
+
+    assert_eq!(cues.len(), 1);
+    assert_eq!(cues[0].text, "let's ship it");
+    // spoken from 2s to 5s of wall time, frames 1 to 2.5 of the video
+    assert_eq!((cues[0].start, cues[0].end), (1.0, 2.5));
+}
+
+#[test]
+fn test_to_srt_sorts_and_formats_cues() {
+    let mut cues = window_cues(&frames(), 1.0);
+    cues.reverse();
+
+    assert_eq!(
+        to_srt(&cues),
+        "1\n00:00:00,000 --> 00:00:02,000\nCode - main.rs\n\n2\n00:00:02,000 --> 00:00:03,000\nSlack - general\n"
+    );
+}
@@ -0,0 +1,131 @@
+use image::{DynamicImage, Luma};
+use screenpipe_server::{
+    ContentType, DatabaseManager, ReplayReport, SearchResult, SyntheticAudio, SyntheticFrame,
+    SyntheticWindow, SyntheticWorkload,
+};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Stripes of `period` pixels, across when `horizontal`
+fn stripes(period: u32, horizontal: bool) -> DynamicImage {
+    let image = image::ImageBuffer::from_fn(320, 200, |x, y| {
+        let position = if horizontal { y } else { x };
+        Luma([if (position / period).is_multiple_of(2) {
+            255u8
+        } else {
+            0
+        }])
+    });
+    DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(image).to_rgb8())
+}
+
+fn frame(image: DynamicImage, app_name: &str, text: &str) -> SyntheticFrame {
+    SyntheticFrame {
+        image: image.clone(),
+        windows: vec![SyntheticWindow {
+            app_name: app_name.to_string(),
+            window_name: format!("{} - main", app_name),
+            focused: true,
+            image,
+            text: text.to_string(),
+        }],
+    }
+}
+
+async fn ocr_texts(db: &DatabaseManager, query: &str) -> Vec<(String, String)> {
+    let results = db
+        .search(query, ContentType::OCR, 10, 0, None, None, None, None)
+        .await
+        .unwrap();
+    results
+        .into_iter()
+        .map(|result| match result {
+            SearchResult::OCR(ocr) => (ocr.app_name, ocr.ocr_text),
+            _ => panic!("expected OCR results"),
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_replay_is_searchable() {
+    let dir = tempfile::tempdir().unwrap();
+    let audio_path = dir.path().join("mic_2024-09-06_10-00-00.mp4");
+    std::fs::write(&audio_path, b"recorded audio").unwrap();
+
+    let workload = SyntheticWorkload {
+        video_chunk: "synthetic.mp4".to_string(),
+        frames: vec![
+            frame(stripes(10, true), "Mail", "invoice 4711 is overdue"),
+            // nothing changed on screen
+            frame(stripes(10, true), "Mail", "invoice 4711 is overdue"),
+            frame(stripes(25, false), "Code", "fn parse_invoice"),
+        ],
+        audio: vec![SyntheticAudio {
+            device: "MacBook Pro Microphone (input)".to_string(),
+            path: audio_path.clone(),
+            transcript: "let's move the standup to ten".to_string(),
+        }],
+    };
+
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let report = workload.replay(Arc::clone(&db)).await.unwrap();
+    assert_eq!(
+        report,
+        ReplayReport {
+            frames: 2,
+            skipped_frames: 1,
+            windows: 2,
+            // Mail and Code are different windows
+            text_changes: 0,
+            alerts: 0,
+            transcripts: 1,
+        }
+    );
+
+    assert_eq!(
+        ocr_texts(&db, "overdue").await,
+        [("Mail".to_string(), "invoice 4711 is overdue".to_string())]
+    );
+    assert_eq!(ocr_texts(&db, "parse_invoice").await.len(), 1);
+    let audio = db
+        .search("standup", ContentType::Audio, 10, 0, None, None, None, None)
+        .await
+        .unwrap();
+    assert_eq!(audio.len(), 1);
+    // transcribed, nothing is left to resume
+    assert!(db.get_pending_transcriptions().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_workload_is_loaded_from_its_manifest() {
+    let dir = tempfile::tempdir().unwrap();
+    let save = |name: &str, image: DynamicImage| {
+        image.save(dir.path().join(name)).unwrap();
+    };
+    save("terminal.png", stripes(8, false));
+    save("browser.png", stripes(40, true));
+    std::fs::write(
+        dir.path().join("workload.json"),
+        r#"{
+            "frames": [
+                {"image": "terminal.png", "windows": [{"app_name": "Terminal", "window_name": "zsh", "focused": true, "image": "terminal.png", "text": "cargo test --workspace"}]},
+                {"image": "browser.png", "windows": [{"app_name": "Firefox", "window_name": "Docs", "image": "browser.png", "text": "release checklist"}]}
+            ]
+        }"#,
+    )
+    .unwrap();
+
+    let workload = SyntheticWorkload::load(dir.path()).unwrap();
+    assert_eq!(workload.frames.len(), 2);
+    assert!(workload.audio.is_empty());
+    assert!(Path::new(&workload.video_chunk).starts_with(dir.path()));
+
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let report = workload.replay(Arc::clone(&db)).await.unwrap();
+    assert_eq!(report.windows, 2);
+    assert_eq!(
+        ocr_texts(&db, "checklist").await,
+        [("Firefox".to_string(), "release checklist".to_string())]
+    );
+    assert!(SyntheticWorkload::load(&dir.path().join("missing")).is_err());
+}
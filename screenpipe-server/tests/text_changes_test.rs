@@ -0,0 +1,74 @@
+use image::{DynamicImage, Luma};
+use screenpipe_core::subscribe_events;
+use screenpipe_server::text_changes::diff_lines;
+use screenpipe_server::{DatabaseManager, SyntheticFrame, SyntheticWindow, SyntheticWorkload};
+use std::sync::Arc;
+
+fn lines(text: &str) -> Vec<String> {
+    text.lines().map(str::to_string).collect()
+}
+
+fn terminal(period: u32, text: &str) -> SyntheticFrame {
+    let image = image::ImageBuffer::from_fn(320, 200, |x, _| {
+        Luma([if (x / period).is_multiple_of(2) {
+            255u8
+        } else {
+            0
+        }])
+    });
+    let image = DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(image).to_rgb8());
+    SyntheticFrame {
+        image: image.clone(),
+        windows: vec![SyntheticWindow {
+            app_name: "Terminal".to_string(),
+            window_name: "cargo".to_string(),
+            focused: true,
+            image,
+            text: text.to_string(),
+        }],
+    }
+}
+
+#[test]
+fn test_moved_lines_are_not_changes() {
+    let (added, removed) = diff_lines(
+        &lines("Compiling screenpipe\nwarning: unused import\nwarning: unused import"),
+        &lines("warning: unused import\nCompiling screenpipe\nerror: build failed"),
+    );
+    assert_eq!(added, ["error: build failed"]);
+    assert_eq!(removed, ["warning: unused import"]);
+}
+
+#[tokio::test]
+async fn test_new_text_of_a_window_is_emitted() {
+    let mut events = subscribe_events();
+    let workload = SyntheticWorkload {
+        video_chunk: "synthetic.mp4".to_string(),
+        frames: vec![
+            terminal(10, "$ cargo build\nCompiling screenpipe"),
+            terminal(
+                25,
+                "$ cargo build\nCompiling screenpipe\nerror: build failed",
+            ),
+        ],
+        audio: Vec::new(),
+    };
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let report = workload.replay(db).await.unwrap();
+    // the first frame of the window is the baseline
+    assert_eq!(report.text_changes, 1);
+
+    let event = loop {
+        let event = events.recv().await.unwrap();
+        if event.name == "screen.text_changed" {
+            break event;
+        }
+    };
+    assert_eq!(event.data["app_name"], "Terminal");
+    assert_eq!(event.data["window_name"], "cargo");
+    assert_eq!(
+        event.data["added"],
+        serde_json::json!(["error: build failed"])
+    );
+    assert_eq!(event.data["removed"], serde_json::json!([]));
+}
@@ -0,0 +1,88 @@
+use axum::http::StatusCode;
+use chrono::{TimeZone, Utc};
+use screenpipe_server::integrity::check_chunk;
+use screenpipe_server::text_only::text_only_chunk_path;
+use screenpipe_server::{
+    extract_frame, forget, is_text_only_chunk, ChunkIntegrity, ChunkKind, DatabaseManager,
+    ForgetFilter, PendingWrite, StorageError,
+};
+
+fn ocr(app_name: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp: Utc::now(),
+        text: "meeting notes".to_string(),
+        text_json: String::new(),
+        app_name: app_name.to_string(),
+        window_name: "notes".to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+#[test]
+fn test_text_only_chunks_are_named_like_the_video_chunks() {
+    let time = Utc.with_ymd_and_hms(2024, 10, 3, 8, 15, 0).unwrap();
+    let chunk = text_only_chunk_path("/data", time);
+    assert_eq!(
+        chunk.to_string_lossy(),
+        "/data/2024-10-03_08-15-00.textonly"
+    );
+    assert!(is_text_only_chunk(&chunk.to_string_lossy()));
+    assert!(!is_text_only_chunk("/data/2024-10-03_08-15-00.mp4"));
+    assert!(!is_text_only_chunk("/data/2024-10-03_08-15-00.frames"));
+}
+
+#[tokio::test]
+async fn test_text_only_chunks_have_no_frames_but_are_sound() {
+    let chunk = "/data/2024-10-03_08-15-00.textonly";
+    let error = extract_frame(chunk, 0).await.unwrap_err();
+    assert!(matches!(error, StorageError::TextOnly(_)), "{}", error);
+    assert_eq!(error.code(), "text_only");
+    assert_eq!(error.status(), StatusCode::NOT_FOUND);
+
+    // no file doesn't mean missing
+    assert_eq!(check_chunk(chunk).await.unwrap(), ChunkIntegrity::Ok);
+}
+
+#[tokio::test]
+async fn test_text_only_chunks_are_not_backed_up_and_forget_their_rows() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_batch(&[
+        PendingWrite::VideoChunk {
+            file_path: "old.textonly".to_string(),
+        },
+        ocr("Notes"),
+        ocr("Signal"),
+        PendingWrite::VideoChunk {
+            file_path: "older.mp4".to_string(),
+        },
+        PendingWrite::VideoChunk {
+            file_path: "current.textonly".to_string(),
+        },
+    ])
+    .await
+    .unwrap();
+
+    let chunks = db
+        .get_chunks_to_back_up(ChunkKind::Video, 10)
+        .await
+        .unwrap();
+    let paths: Vec<&str> = chunks.iter().map(|(_, path)| path.as_str()).collect();
+    assert_eq!(paths, vec!["older.mp4"]);
+
+    let filter = ForgetFilter {
+        app_name: Some("Signal".to_string()),
+        ..Default::default()
+    };
+    let report = forget(&db, &filter, false).await.unwrap();
+    assert_eq!(report.frames, 1);
+    assert!(report.media_kept.is_empty());
+    let frames: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM frames")
+        .fetch_one(&db.pool)
+        .await
+        .unwrap();
+    assert_eq!(frames, 1);
+}
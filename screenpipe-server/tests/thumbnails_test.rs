@@ -0,0 +1,122 @@
+use chrono::{DateTime, TimeZone, Utc};
+use image::{DynamicImage, GenericImageView, Rgb, RgbImage};
+use screenpipe_server::thumbnails::{scale_to_width, thumbnail_level};
+use screenpipe_server::{forget, DatabaseManager, ForgetFilter, PendingWrite, Thumbnails};
+use std::sync::Arc;
+use tempfile::tempdir;
+
+fn frame(width: u32, height: u32) -> Arc<DynamicImage> {
+    Arc::new(DynamicImage::ImageRgb8(RgbImage::from_pixel(
+        width,
+        height,
+        Rgb([90, 90, 90]),
+    )))
+}
+
+fn captured_at() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 10, 2, 9, 30, 0).unwrap()
+        + chrono::Duration::nanoseconds(123_456_789)
+}
+
+fn ocr(timestamp: DateTime<Utc>, app_name: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp,
+        text: "quarterly numbers".to_string(),
+        text_json: String::new(),
+        app_name: app_name.to_string(),
+        window_name: "report".to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+#[test]
+fn test_the_smallest_level_at_least_as_wide_is_picked() {
+    assert_eq!(thumbnail_level(100), Some(160));
+    assert_eq!(thumbnail_level(160), Some(160));
+    assert_eq!(thumbnail_level(300), Some(480));
+    assert_eq!(thumbnail_level(1920), None);
+
+    let scaled = scale_to_width(&frame(3840, 2160), 480);
+    assert_eq!(scaled.dimensions(), (480, 270));
+    assert_eq!(scale_to_width(&frame(100, 50), 480).dimensions(), (100, 50));
+}
+
+#[tokio::test]
+async fn test_every_level_narrower_than_the_frame_is_written() {
+    let dir = tempdir().unwrap();
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let thumbnails = Thumbnails::new(dir.path());
+    let timestamp = captured_at();
+
+    assert_eq!(
+        thumbnails
+            .write(&db, frame(1280, 720), timestamp)
+            .await
+            .unwrap(),
+        2
+    );
+    let small = thumbnails.read(timestamp, 160).await.unwrap().unwrap();
+    let small = image::load_from_memory(&small).unwrap();
+    assert_eq!(small.dimensions(), (160, 90));
+    let large = thumbnails.read(timestamp, 480).await.unwrap().unwrap();
+    assert_eq!(
+        image::load_from_memory(&large).unwrap().dimensions(),
+        (480, 270)
+    );
+
+    // a frame narrower than a level is its own preview
+    let later = timestamp + chrono::Duration::seconds(1);
+    assert_eq!(
+        thumbnails.write(&db, frame(320, 200), later).await.unwrap(),
+        1
+    );
+    assert!(thumbnails.read(later, 160).await.unwrap().is_some());
+    assert!(thumbnails.read(later, 480).await.unwrap().is_none());
+}
+
+#[tokio::test]
+async fn test_thumbnails_are_found_from_the_frame_and_forgotten_with_it() {
+    let dir = tempdir().unwrap();
+    let chunk = dir.path().join("2024-10-02_09-30-00.mp4");
+    let chunk = chunk.to_string_lossy().into_owned();
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let timestamp = captured_at();
+    let ids = db
+        .insert_batch(&[
+            PendingWrite::VideoChunk {
+                file_path: chunk.clone(),
+            },
+            ocr(timestamp, "Numbers"),
+            ocr(timestamp, "Slack"),
+            PendingWrite::VideoChunk {
+                file_path: "current.mp4".to_string(),
+            },
+        ])
+        .await
+        .unwrap();
+    let thumbnails = Thumbnails::of_chunk(&chunk);
+    thumbnails
+        .write(&db, frame(1280, 720), timestamp)
+        .await
+        .unwrap();
+
+    // the capture's timestamp comes back to the nanosecond
+    let stored = db.get_frame_timestamp(ids[1]).await.unwrap().unwrap();
+    assert_eq!(stored, timestamp);
+    assert!(thumbnails.read(stored, 160).await.unwrap().is_some());
+
+    // the other window of the capture shares the thumbnail, which shows both
+    let filter = ForgetFilter {
+        app_name: Some("Slack".to_string()),
+        ..Default::default()
+    };
+    forget(&db, &filter, true).await.unwrap();
+    assert!(thumbnails.read(timestamp, 160).await.unwrap().is_some());
+    forget(&db, &filter, false).await.unwrap();
+    assert!(thumbnails.read(timestamp, 160).await.unwrap().is_none());
+    assert!(thumbnails.read(timestamp, 480).await.unwrap().is_none());
+}
@@ -0,0 +1,41 @@
+use chrono::{Duration, TimeZone, Utc};
+use screenpipe_server::timelapse::{caption_script, plan_timelapse};
+
+#[test]
+fn test_plan_timelapse_speeds_up_and_skips_idle_time() {
+    let start = Utc.with_ymd_and_hms(2024, 8, 26, 9, 0, 0).unwrap();
+    // a frame every 2s for a minute, then an hour away from the computer
+    let mut timestamps: Vec<_> = (0..30).map(|i| start + Duration::seconds(i * 2)).collect();
+    timestamps.push(start + Duration::minutes(61));
+
+    let plan = plan_timelapse(&timestamps, 60.0, 10.0);
+
+    // 58s of recording plus the idle hour cut to 10s, at 60x and 10 fps
+    assert_eq!(plan.len(), 12);
+    assert_eq!(plan[0], 0);
+    assert_eq!(plan[1], 3);
+    assert!(plan.windows(2).all(|pair| pair[0] <= pair[1]));
+    assert_eq!(*plan.last().unwrap(), 29);
+}
+
+#[test]
+fn test_plan_timelapse_single_frame() {
+    let now = Utc::now();
+
+    assert_eq!(plan_timelapse(&[now], 60.0, 30.0), vec![0]);
+    assert!(plan_timelapse(&[], 60.0, 30.0).is_empty());
+}
+
+#[test]
+fn test_caption_script_only_emits_changes() {
+    let captions = vec![
+        "Code - 09:00".to_string(),
+        "Code - 09:00".to_string(),
+        "Slack, general - 09:01".to_string(),
+    ];
+
+    assert_eq!(
+        caption_script(&captions, 2.0),
+        "0.000 drawtext reinit 'text=Code - 09\\:00';\n1.000 drawtext reinit 'text=Slack\\, general - 09\\:01';\n"
+    );
+}
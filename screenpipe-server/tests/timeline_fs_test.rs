@@ -0,0 +1,109 @@
+use chrono::{Local, NaiveDate, TimeZone, Utc};
+use screenpipe_server::timeline_fs::{day_files, file_app_name, list_dir, read_file, TimelineNode};
+use screenpipe_server::DatabaseManager;
+use screenpipe_vision::OcrEngine;
+use std::sync::Arc;
+
+#[test]
+fn test_inodes_round_trip() {
+    let date = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+    for node in [
+        TimelineNode::Root,
+        TimelineNode::Year(2024),
+        TimelineNode::Month(2024, 6),
+        TimelineNode::Day(date),
+        TimelineNode::Frame(42),
+        TimelineNode::Transcript(42),
+    ] {
+        assert_eq!(TimelineNode::from_inode(node.inode()), Some(node));
+    }
+    assert_eq!(TimelineNode::Root.inode(), 1);
+    assert_ne!(
+        TimelineNode::Frame(42).inode(),
+        TimelineNode::Transcript(42).inode()
+    );
+    assert_eq!(
+        TimelineNode::Day(date).parent(),
+        Some(TimelineNode::Month(2024, 6))
+    );
+    assert!(!TimelineNode::Frame(42).is_dir());
+}
+
+#[test]
+fn test_app_names_are_usable_in_file_names() {
+    assert_eq!(file_app_name("Google Chrome"), "google-chrome");
+    assert_eq!(file_app_name("Code - Insiders/"), "code-insiders");
+    assert_eq!(file_app_name(""), "screen");
+}
+
+#[tokio::test]
+async fn test_days_list_their_frames_and_transcriptions() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    db.insert_video_chunk("test_video.mp4").await.unwrap();
+    let first = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        first,
+        "hello",
+        "",
+        "Google Chrome",
+        "screenpipe",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+    let second = db.insert_frame().await.unwrap();
+    db.insert_ocr_text(
+        second,
+        "hello",
+        "",
+        "Google Chrome",
+        "screenpipe",
+        Arc::new(OcrEngine::Tesseract),
+        true,
+    )
+    .await
+    .unwrap();
+    let audio_chunk_id = db.insert_audio_chunk("test_audio.mp4").await.unwrap();
+    db.insert_audio_transcription(audio_chunk_id, "let's ship it on friday", 0, "whisper")
+        .await
+        .unwrap();
+
+    let at = Local
+        .with_ymd_and_hms(2024, 6, 12, 14, 30, 5)
+        .unwrap()
+        .with_timezone(&Utc);
+    for table in ["frames", "audio_transcriptions"] {
+        sqlx::query(&format!("UPDATE {} SET timestamp = ?1", table))
+            .bind(at)
+            .execute(&db.pool)
+            .await
+            .unwrap();
+    }
+
+    let date = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+    let files = day_files(&db, date).await.unwrap();
+    let names: Vec<&str> = files.iter().map(|file| file.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "14:30:05_google-chrome.png",
+            "14:30:05_google-chrome_2.png",
+            "14:30:05_transcript.txt"
+        ]
+    );
+    assert_eq!(files[0].node, TimelineNode::Frame(first));
+
+    let days = db.get_timeline_days().await.unwrap();
+    assert_eq!(days, [date]);
+    let years = list_dir(&db, TimelineNode::Root, &days).await.unwrap();
+    assert_eq!(years, [("2024".to_string(), TimelineNode::Year(2024))]);
+    let months = list_dir(&db, TimelineNode::Year(2024), &days)
+        .await
+        .unwrap();
+    assert_eq!(months, [("06".to_string(), TimelineNode::Month(2024, 6))]);
+
+    let transcript = read_file(&db, files[2].node).await.unwrap();
+    assert_eq!(transcript, b"let's ship it on friday");
+    assert_eq!(files[2].size, Some(transcript.len() as u64));
+}
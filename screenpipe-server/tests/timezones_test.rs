@@ -0,0 +1,74 @@
+use chrono::{DateTime, TimeZone, Utc};
+use screenpipe_integrations::timezone::parse_timezone;
+use screenpipe_server::timezones::{local_timestamp, record_timezone};
+use screenpipe_server::DatabaseManager;
+
+fn at(hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 10, 26, hour, 0, 0).unwrap()
+}
+
+#[tokio::test]
+async fn test_a_trip_is_kept_in_segments() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let berlin = parse_timezone("Europe/Berlin").unwrap();
+    let tokyo = parse_timezone("Asia/Tokyo").unwrap();
+
+    assert!(record_timezone(&db, &berlin, at(6)).await.unwrap());
+    assert!(!record_timezone(&db, &berlin, at(7)).await.unwrap());
+    // landed
+    assert!(record_timezone(&db, &tokyo, at(20)).await.unwrap());
+    assert!(!record_timezone(&db, &tokyo, at(21)).await.unwrap());
+
+    let segments = db
+        .get_timezone_segments(None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!(segments[0].timezone, "Asia/Tokyo");
+    assert_eq!(segments[0].end_time, None);
+    assert_eq!(segments[0].utc_offset_minutes, 540);
+    assert_eq!(segments[1].timezone, "Europe/Berlin");
+    assert_eq!(segments[1].end_time, Some(at(20)));
+    assert_eq!(segments[1].utc_offset_minutes, 120);
+    assert_eq!(
+        db.get_latest_timezone_segment().await.unwrap(),
+        Some(segments[0].clone())
+    );
+    assert_eq!(
+        db.count_timezone_segments(Some(at(21)), None)
+            .await
+            .unwrap(),
+        1
+    );
+    assert_eq!(
+        db.count_timezone_segments(None, Some(at(10)))
+            .await
+            .unwrap(),
+        1
+    );
+
+    // each moment at the local time it happened at
+    let boarding = local_timestamp(&segments, at(9)).unwrap();
+    assert_eq!(boarding.to_rfc3339(), "2024-10-26T11:00:00+02:00");
+    let arrival = local_timestamp(&segments, at(22)).unwrap();
+    assert_eq!(arrival.to_rfc3339(), "2024-10-27T07:00:00+09:00");
+    assert_eq!(local_timestamp(&segments, at(5)), None);
+}
+
+#[tokio::test]
+async fn test_daylight_saving_time_starts_a_segment() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let berlin = parse_timezone("Europe/Berlin").unwrap();
+    let before = Utc.with_ymd_and_hms(2024, 10, 27, 0, 0, 0).unwrap();
+    let after = Utc.with_ymd_and_hms(2024, 10, 27, 2, 0, 0).unwrap();
+
+    assert!(record_timezone(&db, &berlin, before).await.unwrap());
+    assert!(record_timezone(&db, &berlin, after).await.unwrap());
+
+    let segments = db
+        .get_timezone_segments(None, None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(segments[0].utc_offset_minutes, 60);
+    assert_eq!(segments[1].utc_offset_minutes, 120);
+}
@@ -0,0 +1,75 @@
+use axum::routing::get;
+use axum::Router;
+use screenpipe_server::tls::{local_certificate, serve_tls, tls_acceptor};
+use std::io::BufReader;
+use std::sync::Arc;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::rustls::pki_types::ServerName;
+use tokio_rustls::rustls::{self, ClientConfig, RootCertStore};
+use tokio_rustls::TlsConnector;
+
+#[test]
+fn test_generates_the_certificate_once() {
+    let dir = tempfile::tempdir().unwrap();
+    let certificate = local_certificate(&dir.path().join("tls")).unwrap();
+    let cert = std::fs::read_to_string(&certificate.cert).unwrap();
+    assert!(cert.starts_with("-----BEGIN CERTIFICATE-----"));
+    assert!(std::fs::read_to_string(&certificate.ca)
+        .unwrap()
+        .starts_with("-----BEGIN CERTIFICATE-----"));
+
+    // kept across restarts, the authority stays trusted
+    let again = local_certificate(&dir.path().join("tls")).unwrap();
+    assert_eq!(std::fs::read_to_string(&again.cert).unwrap(), cert);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&certificate.key)
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[tokio::test]
+async fn test_serves_https_trusted_through_the_local_authority() {
+    let dir = tempfile::tempdir().unwrap();
+    let certificate = local_certificate(dir.path()).unwrap();
+    let acceptor = tls_acceptor(&certificate.cert, &certificate.key).unwrap();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let app = Router::new().route("/health", get(|| async { "healthy" }));
+    tokio::spawn(serve_tls(listener, app, acceptor));
+
+    let mut roots = RootCertStore::empty();
+    let ca = std::fs::File::open(&certificate.ca).unwrap();
+    for cert in rustls_pemfile::certs(&mut BufReader::new(ca)) {
+        roots.add(cert.unwrap()).unwrap();
+    }
+    let config =
+        ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+    let connector = TlsConnector::from(Arc::new(config));
+    let stream = TcpStream::connect(addr).await.unwrap();
+    let mut stream = connector
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    // the server may close without a close_notify
+    let _ = stream.read_to_end(&mut response).await;
+    let response = String::from_utf8_lossy(&response);
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+    assert!(response.ends_with("healthy"), "{}", response);
+}
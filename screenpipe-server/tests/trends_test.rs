@@ -0,0 +1,133 @@
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
+use screenpipe_server::trends::{
+    after_hours_secs, compare_weeks, digest_lines, week_metrics, week_start,
+};
+use screenpipe_server::{ActivityCategory, DatabaseManager, WeekMetrics, WindowEventKind};
+use std::collections::BTreeMap;
+
+fn at(day: u32, hour: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 9, day, hour, 0, 0).unwrap()
+}
+
+fn monday(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 9, day).unwrap()
+}
+
+fn week(day: u32, coding_hours: f64, after_hours: f64) -> WeekMetrics {
+    WeekMetrics {
+        week_start: monday(day),
+        active_secs: coding_hours * 3600.0,
+        category_secs: BTreeMap::from([("coding".to_string(), coding_hours * 3600.0)]),
+        after_hours_secs: after_hours * 3600.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_time_outside_working_hours_and_on_weekends_is_after_hours() {
+    assert_eq!(week_start(monday(19)), monday(16));
+    assert_eq!(week_start(monday(16)), monday(16));
+
+    // tuesday from 5pm to 8pm
+    assert_eq!(after_hours_secs(at(17, 17), at(17, 20), &Utc), 2.0 * 3600.0);
+    // saturday
+    assert_eq!(after_hours_secs(at(21, 10), at(21, 12), &Utc), 2.0 * 3600.0);
+    // friday 5pm to monday 10am: an hour of work on each side
+    assert_eq!(
+        after_hours_secs(at(20, 17), at(23, 10), &Utc),
+        (65.0 - 2.0) * 3600.0
+    );
+}
+
+#[tokio::test]
+async fn test_weeks_are_measured_from_the_sessions_and_window_switches() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let sessions = [
+        (at(17, 10), at(17, 12), ActivityCategory::Coding),
+        (at(17, 19), at(17, 21), ActivityCategory::Coding),
+        (at(21, 10), at(21, 11), ActivityCategory::Entertainment),
+        // started the sunday before, its first hour is another week's
+        (at(15, 23), at(16, 1), ActivityCategory::Coding),
+    ];
+    for (start, end, category) in sessions {
+        db.insert_activity_session(start, end, "app", category, 0.9)
+            .await
+            .unwrap();
+    }
+    for hour in 8..20 {
+        db.insert_window_event(at(18, hour), "app", "window", WindowEventKind::Focus)
+            .await
+            .unwrap();
+    }
+    db.insert_window_event(at(18, 21), "app", "other tab", WindowEventKind::Title)
+        .await
+        .unwrap();
+
+    let metrics = week_metrics(&db, monday(16), &Utc).await.unwrap();
+    assert_eq!(metrics.active_secs, 6.0 * 3600.0);
+    assert_eq!(metrics.category_secs["coding"], 5.0 * 3600.0);
+    assert_eq!(metrics.category_secs["entertainment"], 3600.0);
+    assert_eq!(metrics.after_hours_secs, 4.0 * 3600.0);
+    assert_eq!(metrics.context_switches, 12);
+    assert_eq!(metrics.switches_per_hour, 2.0);
+
+    db.upsert_weekly_metrics(&metrics).await.unwrap();
+    db.upsert_weekly_metrics(&week(9, 1.0, 0.0)).await.unwrap();
+    let stored = db.get_weekly_metrics(monday(9), monday(16)).await.unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[1], metrics);
+    assert!(db
+        .get_weekly_metrics(monday(23), monday(30))
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn test_weeks_far_from_the_usual_are_anomalies() {
+    let weeks = vec![
+        week(2, 10.0, 1.0),
+        week(9, 10.0, 1.0),
+        week(16, 10.0, 1.0),
+        week(23, 10.0, 1.0),
+        week(30, 12.0, 4.0),
+    ];
+    let trends = compare_weeks(weeks);
+    assert_eq!(trends.weeks.len(), 5);
+
+    let coding = trends
+        .trends
+        .iter()
+        .find(|trend| trend.measure == "coding time")
+        .unwrap();
+    assert_eq!(coding.previous, Some(10.0 * 3600.0));
+    assert!((coding.change.unwrap() - 0.2).abs() < 1e-9);
+    assert_eq!(coding.usual, Some(10.0 * 3600.0));
+
+    // 4x the usual after hours, the rest is within 3x
+    assert_eq!(trends.anomalies.len(), 1);
+    assert_eq!(trends.anomalies[0].measure, "after hours");
+    assert_eq!(trends.anomalies[0].ratio, Some(4.0));
+    let lines = digest_lines(&trends);
+    assert_eq!(lines[0], "Unusual: after hours 4h, 4.0x the usual 1h");
+    assert!(lines.contains(&"coding time: 12h (+20%)".to_string()));
+
+    // the time went from a category to one there usually is none of
+    let mut email = week(30, 10.0, 1.0);
+    email.category_secs = BTreeMap::from([("email".to_string(), 10.0 * 3600.0)]);
+    let trends = compare_weeks(vec![week(16, 10.0, 1.0), week(23, 10.0, 1.0), email]);
+    assert_eq!(trends.anomalies.len(), 2);
+    assert_eq!(trends.anomalies[0].measure, "coding time");
+    assert_eq!(trends.anomalies[0].ratio, Some(0.0));
+    assert_eq!(trends.anomalies[1].measure, "email time");
+    assert_eq!(trends.anomalies[1].ratio, None);
+    assert_eq!(
+        trends.anomalies[1].description,
+        "email time 10h, usually none"
+    );
+
+    // a week of baseline isn't enough to tell
+    assert!(compare_weeks(vec![week(23, 10.0, 1.0), week(30, 10.0, 9.0)])
+        .anomalies
+        .is_empty());
+}
@@ -0,0 +1,120 @@
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::routing::get;
+use axum::Router;
+use screenpipe_server::user_isolation::{
+    bind_user_port, require_user_token, user_data_dir, USER_TOKEN_HEADER,
+};
+use screenpipe_server::{UserInstance, UserIsolation};
+use std::sync::Arc;
+use tower::ServiceExt;
+
+#[test]
+fn test_token_is_kept_private_to_the_user() {
+    let base = tempfile::tempdir().unwrap();
+    let dir = user_data_dir(base.path(), "alice");
+    assert_eq!(dir, base.path().join("users").join("alice"));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let isolation = UserIsolation::load(&dir, "alice").unwrap();
+    assert!(isolation.is_authorized(Some(isolation.token())));
+    assert!(!isolation.is_authorized(None));
+    assert!(!isolation.is_authorized(Some("guess")));
+    // the same token after a restart
+    let reloaded = UserIsolation::load(&dir, "alice").unwrap();
+    assert_eq!(reloaded.token(), isolation.token());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        use std::path::Path;
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&dir), 0o700);
+        assert_eq!(mode(&dir.join("user_token")), 0o600);
+    }
+}
+
+#[tokio::test]
+async fn test_refuses_requests_without_the_users_token() {
+    let dir = tempfile::tempdir().unwrap();
+    let isolation = Arc::new(UserIsolation::load(dir.path(), "alice").unwrap());
+    let app = Router::new()
+        .route("/search", get(|| async { "results" }))
+        .route("/live", get(|| async { "live view" }))
+        .route("/live/player.js", get(|| async { "player" }))
+        .layer(axum::middleware::from_fn_with_state(
+            isolation.clone(),
+            require_user_token,
+        ));
+    let status = |request: Request<Body>| {
+        let app = app.clone();
+        async move { app.oneshot(request).await.unwrap().status() }
+    };
+
+    let get = |uri: &str| Request::get(uri).body(Body::empty()).unwrap();
+    assert_eq!(status(get("/search")).await, StatusCode::FORBIDDEN);
+    assert_eq!(
+        status(
+            Request::get("/search")
+                .header(USER_TOKEN_HEADER, "someone else's")
+                .body(Body::empty())
+                .unwrap()
+        )
+        .await,
+        StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status(
+            Request::get("/search")
+                .header(USER_TOKEN_HEADER, isolation.token())
+                .body(Body::empty())
+                .unwrap()
+        )
+        .await,
+        StatusCode::OK
+    );
+    assert_eq!(
+        status(get(&format!(
+            "/search?q=x&user_token={}",
+            isolation.token()
+        )))
+        .await,
+        StatusCode::OK
+    );
+    // the live view checks its own token on top of the user's
+    assert_eq!(status(get("/live?token=live")).await, StatusCode::FORBIDDEN);
+    assert_eq!(
+        status(get(&format!(
+            "/live?token=live&user_token={}",
+            isolation.token()
+        )))
+        .await,
+        StatusCode::OK
+    );
+    assert_eq!(status(get("/live/player.js")).await, StatusCode::OK);
+}
+
+#[test]
+fn test_instance_and_port_of_each_user() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(UserInstance::read(dir.path()).is_none());
+    let instance = UserInstance {
+        user: "alice".to_string(),
+        pid: std::process::id(),
+        port: 3030,
+        started_at: chrono::Utc::now(),
+    };
+    instance.write(dir.path()).unwrap();
+    let read = UserInstance::read(dir.path()).unwrap();
+    assert_eq!(read, instance);
+    // the instance starting is not the one already running
+    assert!(!read.is_running());
+
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = taken.local_addr().unwrap().port();
+    let bound = bind_user_port(port).unwrap();
+    let found = bound.local_addr().unwrap().port();
+    assert!(found > port);
+    // still held by the server starting
+    assert!(std::net::TcpListener::bind(("127.0.0.1", found)).is_err());
+}
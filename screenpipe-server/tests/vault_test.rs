@@ -0,0 +1,61 @@
+use chrono::{Duration, TimeZone, Utc};
+use screenpipe_integrations::vault_export::VaultNoteSource;
+use screenpipe_server::{ActionItem, ActivityCategory, DatabaseManager, ExtractionKind};
+use serde_json::json;
+
+#[tokio::test]
+async fn test_note_content_of_a_day() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = Utc.with_ymd_and_hms(2024, 9, 1, 0, 0, 0).unwrap();
+    let at = |hours| start + Duration::hours(hours);
+    db.insert_activity_session(at(9), at(11), "Code", ActivityCategory::Coding, 0.9)
+        .await
+        .unwrap();
+    db.insert_meeting_summary(
+        at(14),
+        at(15),
+        "Planned the release.",
+        &["Ship on friday".to_string()],
+        &[ActionItem {
+            description: "Write the changelog".to_string(),
+            owner: Some("ana".to_string()),
+        }],
+        "llama3.2",
+        1,
+    )
+    .await
+    .unwrap();
+    // the next day's meeting is left out
+    db.insert_meeting_summary(at(26), at(27), "Retro.", &[], &[], "llama3.2", 1)
+        .await
+        .unwrap();
+    let rule_id = db
+        .insert_extraction_rule("orders", None, None, ExtractionKind::Regex, "Order #(\\d+)")
+        .await
+        .unwrap();
+    db.insert_extracted_record(
+        rule_id,
+        7,
+        at(16),
+        "Salesforce",
+        "Order 1234",
+        &json!({"order_number": "1234"}),
+    )
+    .await
+    .unwrap();
+
+    let content = db.note_content(start, at(24)).await.unwrap();
+
+    assert_eq!(content.digest.len(), 1);
+    assert_eq!(content.digest[0].label, "coding");
+    assert_eq!(content.digest[0].duration_secs.round(), 7200.0);
+    assert_eq!(content.meetings.len(), 1);
+    assert_eq!(
+        content.meetings[0].action_items,
+        vec!["Write the changelog (ana)"]
+    );
+    assert_eq!(content.moments.len(), 1);
+    assert_eq!(content.moments[0].tag, "orders");
+    assert_eq!(content.moments[0].details, vec!["order_number: 1234"]);
+    assert_eq!(content.moments[0].frame_id, Some(7));
+}
@@ -0,0 +1,131 @@
+use image::{DynamicImage, Luma};
+use screenpipe_server::watchlist::compile_watch;
+use screenpipe_server::{
+    DatabaseManager, SyntheticAudio, SyntheticFrame, SyntheticWindow, SyntheticWorkload, WatchKind,
+    WatchSource, Watchlist,
+};
+use std::sync::Arc;
+
+fn stripes(period: u32) -> DynamicImage {
+    let image = image::ImageBuffer::from_fn(320, 200, |x, _| {
+        Luma([if (x / period).is_multiple_of(2) {
+            255u8
+        } else {
+            0
+        }])
+    });
+    DynamicImage::ImageRgb8(DynamicImage::ImageLuma8(image).to_rgb8())
+}
+
+fn frame(period: u32, app_name: &str, window_name: &str, text: &str) -> SyntheticFrame {
+    let image = stripes(period);
+    SyntheticFrame {
+        image: image.clone(),
+        windows: vec![SyntheticWindow {
+            app_name: app_name.to_string(),
+            window_name: window_name.to_string(),
+            focused: true,
+            image,
+            text: text.to_string(),
+        }],
+    }
+}
+
+#[test]
+fn test_keywords_match_whole_words() {
+    let regex = compile_watch(WatchKind::Keywords, "karol, @karol, c++").unwrap();
+    assert!(regex.is_match("thanks Karol!"));
+    assert!(regex.is_match("ping @karol"));
+    assert!(regex.is_match("we need a C++ dev"));
+    assert!(!regex.is_match("karolina says hi"));
+
+    assert!(compile_watch(WatchKind::Keywords, " , ").is_err());
+    assert!(compile_watch(WatchKind::Regex, "build (failed").is_err());
+}
+
+#[tokio::test]
+async fn test_matches_alert_once_in_their_scope() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let mentions = db
+        .insert_watch(
+            "mentions",
+            Some("slack"),
+            None,
+            WatchKind::Keywords,
+            "karol",
+            WatchSource::All,
+        )
+        .await
+        .unwrap();
+    let builds = db
+        .insert_watch(
+            "builds",
+            None,
+            None,
+            WatchKind::Regex,
+            r"build (failed|broken)",
+            WatchSource::Audio,
+        )
+        .await
+        .unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let audio_path = dir.path().join("mic_2024-09-07_10-00-00.mp4");
+    std::fs::write(&audio_path, b"standup audio").unwrap();
+    let workload = SyntheticWorkload {
+        video_chunk: "synthetic.mp4".to_string(),
+        frames: vec![
+            frame(
+                10,
+                "Slack",
+                "#random (muted)",
+                "lunch?\nkarol can you review this",
+            ),
+            // still on screen, already alerted
+            frame(
+                25,
+                "Slack",
+                "#random (muted)",
+                "karol can you review this\nsure",
+            ),
+            // not scoped to the terminal
+            frame(40, "Terminal", "zsh", "git log --author karol"),
+            // screen text, the watch only looks at transcripts
+            frame(15, "Terminal", "cargo", "error: build failed"),
+        ],
+        audio: vec![SyntheticAudio {
+            device: "MacBook Pro Microphone (input)".to_string(),
+            path: audio_path,
+            transcript: "the build failed again last night".to_string(),
+        }],
+    };
+    let report = workload.replay(Arc::clone(&db)).await.unwrap();
+    assert_eq!(report.alerts, 2);
+
+    let alerts = db.get_watch_alerts(None, 10, 0, None).await.unwrap();
+    let by_watch = |watch_id| {
+        alerts
+            .iter()
+            .find(|alert| alert.watch_id == watch_id)
+            .unwrap()
+    };
+    let mention = by_watch(mentions);
+    assert_eq!(mention.watch_name, "mentions");
+    assert_eq!(mention.source, WatchSource::Ocr);
+    assert_eq!(mention.window_name.as_deref(), Some("#random (muted)"));
+    assert_eq!(mention.excerpt, "karol can you review this");
+    let build = by_watch(builds);
+    assert_eq!(build.source, WatchSource::Audio);
+    assert_eq!(build.matched_text, "build failed");
+    assert_eq!(build.app_name, None);
+
+    // deleting the watch drops its alerts and stops it
+    assert!(db.delete_watch(mentions).await.unwrap());
+    let watchlist = Watchlist::load(Arc::clone(&db)).await.unwrap();
+    assert!(watchlist
+        .check_transcript("mic", "karol, the build is broken", chrono::Utc::now())
+        .await
+        .iter()
+        .all(|alert| alert.watch_id == builds));
+    assert_eq!(db.count_watch_alerts(Some(mentions)).await.unwrap(), 0);
+}
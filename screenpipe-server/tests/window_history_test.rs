@@ -0,0 +1,78 @@
+use chrono::{Duration, TimeZone, Utc};
+use screenpipe_server::{DatabaseManager, WindowEventKind, WindowTracker};
+
+fn window(app_name: &str, window_name: &str) -> Option<(String, String)> {
+    Some((app_name.to_string(), window_name.to_string()))
+}
+
+#[tokio::test]
+async fn test_records_focus_and_title_changes() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let start = Utc.with_ymd_and_hms(2024, 9, 9, 9, 0, 0).unwrap();
+    let at = |secs| start + Duration::seconds(secs);
+    let mut tracker = WindowTracker::default();
+
+    let observations = [
+        (0, window("Code", "main.rs")),
+        // polled again, nothing changed
+        (1, window("Code", "main.rs")),
+        (4, window("Code", "lib.rs")),
+        (10, window("Chrome", "Pull requests · GitHub")),
+        // capture paused
+        (12, None),
+        (20, None),
+        (21, window("Chrome", "Pull requests · GitHub")),
+    ];
+    let mut recorded = Vec::new();
+    for (secs, focused) in observations {
+        if let Some(event) = tracker.observe(&db, focused, at(secs)).await.unwrap() {
+            recorded.push(event.kind);
+        }
+    }
+    assert_eq!(
+        recorded,
+        vec![
+            WindowEventKind::Focus,
+            WindowEventKind::Title,
+            WindowEventKind::Focus,
+            WindowEventKind::Focus,
+        ]
+    );
+
+    let history = db
+        .get_window_events(None, None, None, None, 10, 0, None)
+        .await
+        .unwrap();
+    let durations: Vec<(&str, &str, Option<f64>)> = history
+        .iter()
+        .map(|event| {
+            (
+                event.app_name.as_str(),
+                event.window_name.as_str(),
+                event.duration_secs,
+            )
+        })
+        .collect();
+    assert_eq!(
+        durations,
+        vec![
+            ("Chrome", "Pull requests · GitHub", None),
+            ("Chrome", "Pull requests · GitHub", Some(2.0)),
+            ("Code", "lib.rs", Some(6.0)),
+            ("Code", "main.rs", Some(4.0)),
+        ]
+    );
+
+    // the window focused when the range starts is part of it
+    let since = db
+        .get_window_events(Some("code"), None, Some(at(5)), None, 10, 0, None)
+        .await
+        .unwrap();
+    assert_eq!(since.len(), 1);
+    assert_eq!(since[0].window_name, "lib.rs");
+    let titled = db
+        .count_window_events(None, Some("github"), None, Some(at(15)))
+        .await
+        .unwrap();
+    assert_eq!(titled, 1);
+}
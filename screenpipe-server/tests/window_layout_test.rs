@@ -0,0 +1,176 @@
+use anyhow::Result;
+use chrono::{Duration, Utc};
+use image::DynamicImage;
+use screenpipe_server::automation::InputDevice;
+use screenpipe_server::window_layout::restore_actions;
+use screenpipe_server::{
+    load_automation_grants, Automation, AutomationAction, AutomationStatus, DatabaseManager,
+    LayoutRecorder,
+};
+use screenpipe_vision::core::WindowOcrResult;
+use screenpipe_vision::CaptureResult;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A monitor id no machine has, the frames are placed on the desktop as they are
+const MONITOR_ID: u32 = 4242;
+
+fn window(app: &str, title: &str, position: (i32, i32), size: (u32, u32)) -> WindowOcrResult {
+    WindowOcrResult {
+        window_name: title.to_string(),
+        app_name: app.to_string(),
+        image: Arc::new(DynamicImage::new_rgb8(size.0, size.1)),
+        text: String::new(),
+        text_json: Vec::new(),
+        focused: app == "Code",
+        position,
+        browser_page: None,
+    }
+}
+
+fn frame(windows: Vec<WindowOcrResult>) -> CaptureResult {
+    CaptureResult {
+        image: Arc::new(DynamicImage::new_rgb8(1920, 1080)),
+        png: Default::default(),
+        frame_number: 1,
+        timestamp: Instant::now(),
+        window_ocr_results: windows,
+        private_windows: Vec::new(),
+    }
+}
+
+#[tokio::test]
+async fn test_layouts_are_stored_when_windows_move() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let mut recorder = LayoutRecorder::new(Arc::clone(&db), MONITOR_ID);
+    let start = Utc::now();
+    let docked = || {
+        vec![
+            window("Code", "main.rs", (0, 0), (1280, 1080)),
+            window("Slack", "general", (1280, 0), (640, 1080)),
+        ]
+    };
+    recorder.observe(&frame(docked()), start).await;
+    // another channel, the same layout
+    let mut other_channel = docked();
+    other_channel[1].window_name = "random".to_string();
+    recorder
+        .observe(&frame(other_channel), start + Duration::seconds(1))
+        .await;
+    recorder
+        .observe(
+            &frame(vec![window("Code", "main.rs", (0, 0), (1920, 1080))]),
+            start + Duration::seconds(2),
+        )
+        .await;
+
+    let before = db
+        .get_window_layouts_at(start + Duration::milliseconds(1500))
+        .await
+        .unwrap();
+    assert_eq!(before.len(), 1);
+    assert_eq!(before[0].monitor_id, MONITOR_ID);
+    assert_eq!(before[0].timestamp, start);
+    let slack = &before[0].windows[1];
+    assert_eq!(
+        (slack.app_name.as_str(), slack.window_name.as_str()),
+        ("Slack", "general")
+    );
+    assert_eq!(
+        (slack.x, slack.y, slack.width, slack.height),
+        (1280, 0, 640, 1080)
+    );
+
+    let after = db
+        .get_window_layouts_at(start + Duration::seconds(3))
+        .await
+        .unwrap();
+    assert_eq!(after[0].windows.len(), 1);
+    assert!(db
+        .get_window_layouts_at(start - Duration::seconds(1))
+        .await
+        .unwrap()
+        .is_empty());
+    let stored = db.get_window_layout(before[0].id).await.unwrap().unwrap();
+    assert_eq!(stored.windows, before[0].windows);
+}
+
+#[derive(Default)]
+struct RecordingDevice {
+    performed: Mutex<Vec<AutomationAction>>,
+}
+
+impl InputDevice for RecordingDevice {
+    fn perform(&self, actions: &[AutomationAction]) -> Result<()> {
+        self.performed.lock().unwrap().extend_from_slice(actions);
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_restoring_a_layout_needs_the_apps_granted() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let mut recorder = LayoutRecorder::new(Arc::clone(&db), MONITOR_ID);
+    recorder
+        .observe(
+            &frame(vec![
+                window("Code", "main.rs", (0, 0), (1280, 1080)),
+                window("Slack", "general", (1280, 0), (640, 1080)),
+            ]),
+            Utc::now(),
+        )
+        .await;
+    let layout = db
+        .get_window_layouts_at(Utc::now())
+        .await
+        .unwrap()
+        .remove(0);
+    let actions = restore_actions(&layout);
+    assert_eq!(
+        actions[1],
+        AutomationAction::MoveWindow {
+            app_name: "Slack".to_string(),
+            window_name: "general".to_string(),
+            x: 1280,
+            y: 0,
+            width: 640,
+            height: 1080,
+        }
+    );
+    assert!(actions.iter().all(|action| action.validate().is_ok()));
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("automation.json");
+    std::fs::write(
+        &path,
+        serde_json::json!({"grants": [{
+            "pipe": "window-keeper",
+            "actions": ["arrange"],
+            "apps": ["Code"],
+        }]})
+        .to_string(),
+    )
+    .unwrap();
+    let device = Arc::new(RecordingDevice::default());
+    let automation = Automation::new(
+        db.clone(),
+        load_automation_grants(&path).unwrap(),
+        device.clone(),
+    );
+    let focused = Some(("Code".to_string(), "main.rs".to_string()));
+    let refused = automation
+        .perform("window-keeper", actions.clone(), focused.clone())
+        .await
+        .unwrap();
+    assert_eq!(refused[0].status, AutomationStatus::Refused);
+    assert_eq!(
+        refused[0].reason.as_deref(),
+        Some("window-keeper isn't allowed to act on Slack")
+    );
+    let done = automation
+        .perform("window-keeper", actions[..1].to_vec(), focused)
+        .await
+        .unwrap();
+    assert_eq!(done[0].status, AutomationStatus::Done);
+    assert_eq!(*device.performed.lock().unwrap(), actions[..1].to_vec());
+}
@@ -0,0 +1,162 @@
+use chrono::Utc;
+use screenpipe_server::{
+    ContentType, DatabaseManager, FileEventKind, PendingWrite, PolicyAction, RedactionAnnotation,
+    RedactionSource, SearchResult, WriteCoalescer,
+};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+fn ocr(text: &str) -> PendingWrite {
+    PendingWrite::Ocr {
+        timestamp: Utc::now(),
+        text: text.to_string(),
+        text_json: String::new(),
+        app_name: "Code".to_string(),
+        window_name: "main.rs".to_string(),
+        ocr_engine: "Tesseract".to_string(),
+        focused: true,
+        perceptual_hash: None,
+        browser_url: None,
+        scroll_y: None,
+    }
+}
+
+#[tokio::test]
+async fn test_batch_is_written_in_order() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let ids = db
+        .insert_batch(&[
+            ocr("before any chunk"),
+            PendingWrite::VideoChunk {
+                file_path: "first.mp4".to_string(),
+            },
+            ocr("first frame"),
+            ocr("second frame"),
+            PendingWrite::Transcription {
+                timestamp: Utc::now(),
+                file_path: "mic.mp4".to_string(),
+                transcription: "hello from the microphone".to_string(),
+                engine: "WhisperTiny".to_string(),
+            },
+            PendingWrite::FileEvent {
+                timestamp: Utc::now(),
+                path: "/home/me/report.md".to_string(),
+                kind: FileEventKind::Save,
+                app_name: Some("Code".to_string()),
+                window_name: None,
+            },
+        ])
+        .await
+        .unwrap();
+    assert_eq!(ids.len(), 6);
+    // no chunk to put the frame in yet
+    assert_eq!(ids[0], 0);
+
+    let results = db
+        .search("frame", ContentType::OCR, 10, 0, None, None, None, None)
+        .await
+        .unwrap();
+    let mut offsets: Vec<_> = results
+        .iter()
+        .map(|result| match result {
+            SearchResult::OCR(ocr) => (ocr.file_path.clone(), ocr.offset_index),
+            _ => panic!("expected OCR results"),
+        })
+        .collect();
+    offsets.sort();
+    assert_eq!(
+        offsets,
+        [("first.mp4".to_string(), 0), ("first.mp4".to_string(), 1)]
+    );
+    assert_eq!(
+        db.search(
+            "microphone",
+            ContentType::Audio,
+            10,
+            0,
+            None,
+            None,
+            None,
+            None
+        )
+        .await
+        .unwrap()
+        .len(),
+        1
+    );
+    assert_eq!(
+        db.count_file_events(Some("report"), None, None, None)
+            .await
+            .unwrap(),
+        1
+    );
+}
+
+#[tokio::test]
+async fn test_writes_are_flushed_after_the_interval() {
+    let db = Arc::new(DatabaseManager::new("sqlite::memory:").await.unwrap());
+    let writes = WriteCoalescer::start(Arc::clone(&db), Duration::from_millis(200));
+    writes
+        .write(PendingWrite::VideoChunk {
+            file_path: "chunk.mp4".to_string(),
+        })
+        .await;
+    for i in 0..20 {
+        writes.write(ocr(&format!("window {}", i))).await;
+    }
+
+    let count = || async {
+        db.search("window", ContentType::OCR, 100, 0, None, None, None, None)
+            .await
+            .unwrap()
+            .len()
+    };
+    // still waiting for more writes
+    assert_eq!(count().await, 0);
+    tokio::time::sleep(Duration::from_millis(600)).await;
+    assert_eq!(count().await, 20);
+}
+
+#[tokio::test]
+async fn test_redactions_are_stored() {
+    let db = DatabaseManager::new("sqlite::memory:").await.unwrap();
+    let annotation = RedactionAnnotation {
+        source: RedactionSource::Audio,
+        rule: "no-mail".to_string(),
+        action: PolicyAction::RedactText,
+        app_name: None,
+        entities: BTreeMap::from([("email".to_string(), 2)]),
+        regions: Vec::new(),
+    };
+    db.insert_batch(&[PendingWrite::Redaction {
+        timestamp: Utc::now(),
+        annotation: annotation.clone(),
+        audio_file: Some("mic.mp4".to_string()),
+    }])
+    .await
+    .unwrap();
+
+    let redactions = db
+        .get_redactions(
+            Some(RedactionSource::Audio),
+            None,
+            None,
+            None,
+            None,
+            10,
+            0,
+            None,
+        )
+        .await
+        .unwrap();
+    assert_eq!(redactions.len(), 1);
+    assert_eq!(redactions[0].annotation, annotation);
+    assert_eq!(redactions[0].audio_file.as_deref(), Some("mic.mp4"));
+    assert_eq!(
+        db.count_redactions(Some(RedactionSource::Screen), None, None, None, None)
+            .await
+            .unwrap(),
+        0
+    );
+}
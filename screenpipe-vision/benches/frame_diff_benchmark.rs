@@ -0,0 +1,52 @@
+// cargo bench --bench frame_diff_benchmark
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_vision::utils::{compare_images_histogram, compare_images_ssim};
+use screenpipe_vision::LumaFrame;
+
+/// Two frames with a window moved between them
+fn frames(width: u32, height: u32) -> (DynamicImage, DynamicImage) {
+    let frame = |offset: u32| {
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+            if (offset..offset + width / 3).contains(&x) && y > height / 4 {
+                Rgba([250, 250, 250, 255])
+            } else {
+                Rgba([(x % 256) as u8, (y % 256) as u8, 90, 255])
+            }
+        }))
+    };
+    (frame(width / 10), frame(width / 8))
+}
+
+fn criterion_benchmark(c: &mut Criterion) {
+    let mut group = c.benchmark_group("frame_diff");
+    group.sample_size(10);
+
+    for (name, width, height) in [("1080p", 1920, 1080), ("4k", 3840, 2160)] {
+        let (previous, current) = frames(width, height);
+        group.bench_with_input(
+            BenchmarkId::new("full_resolution", name),
+            &(&previous, &current),
+            |b, (previous, current)| {
+                b.iter(|| {
+                    let histogram = compare_images_histogram(previous, current).unwrap();
+                    black_box((histogram + 1.0 - compare_images_ssim(previous, current)) / 2.0)
+                })
+            },
+        );
+        // the previous thumbnail is kept, only the new frame is downscaled
+        let previous = LumaFrame::new(&previous);
+        group.bench_with_input(
+            BenchmarkId::new("luma_thumbnail", name),
+            &(&previous, &current),
+            |b, (previous, current)| {
+                b.iter(|| black_box(previous.difference(&LumaFrame::new(current)).unwrap()))
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, criterion_benchmark);
+criterion_main!(benches);
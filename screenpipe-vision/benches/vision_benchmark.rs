@@ -3,15 +3,19 @@
 // cargo bench --bench vision_benchmark
 // ! not very useful bench
 
+use std::sync::atomic::AtomicBool;
 use std::sync::Arc;
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use screenpipe_vision::{continuous_capture, get_monitor, OcrEngine};
 use tokio::sync::mpsc;
 use tokio::time::Duration;
+use tokio_util::sync::CancellationToken;
 
 async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
     let (result_tx, mut result_rx) = mpsc::channel(100);
+    let cancel = CancellationToken::new();
+    let capture_cancel = cancel.clone();
 
     let capture_handle = tokio::spawn(async move {
         continuous_capture(
@@ -20,6 +24,8 @@ async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
             false,
             Arc::new(OcrEngine::Tesseract),
             get_monitor().await,
+            Arc::new(AtomicBool::new(false)),
+            capture_cancel,
         )
         .await;
     });
@@ -28,6 +34,7 @@ async fn benchmark_continuous_capture(duration_secs: u64) -> f64 {
     tokio::time::sleep(Duration::from_secs(duration_secs)).await;
 
     // Wait for the capture to finish
+    cancel.cancel();
     capture_handle.await.unwrap();
 
     // Count received frames
@@ -0,0 +1,195 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use crate::core::WindowImage;
+use crate::utils::OcrEngine;
+
+/// Width the windows of a thumbnail only app are kept at in the frame
+pub const THUMBNAIL_WIDTH: u32 = 160;
+
+/// How the windows of an app are captured
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppCaptureProfile {
+    /// Name of the app, case insensitive
+    pub app: String,
+    /// Frames per second its windows are captured at, 0.0167 for one a minute. Never more than
+    /// the capture runs at. While it's focused, the frames in between are skipped whole
+    #[serde(default)]
+    pub fps: Option<f64>,
+    /// Width its windows are scaled down to before the OCR, full resolution when not set
+    #[serde(default)]
+    pub max_width: Option<u32>,
+    /// Its windows are kept at `THUMBNAIL_WIDTH` in the frame and not OCR'd
+    #[serde(default)]
+    pub thumbnail_only: bool,
+    /// Its windows are read with the handwriting model whatever the engine of the capture,
+    /// for the note-taking apps written in with a stylus
+    #[serde(default)]
+    pub handwriting: bool,
+    /// Its windows are OCR'd but blacked out in the frame, no image of them is kept
+    #[serde(default)]
+    pub text_only: bool,
+}
+
+impl AppCaptureProfile {
+    fn interval(&self) -> Option<Duration> {
+        self.fps
+            .filter(|fps| *fps > 0.0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps))
+    }
+}
+
+static APP_CAPTURE_PROFILES: RwLock<Vec<AppCaptureProfile>> = RwLock::new(Vec::new());
+
+/// Replaces the profiles the capture applies from its next frame
+pub fn set_app_capture_profiles(profiles: Vec<AppCaptureProfile>) {
+    *APP_CAPTURE_PROFILES.write().unwrap() = profiles;
+}
+
+pub fn app_capture_profile(app_name: &str) -> Option<AppCaptureProfile> {
+    APP_CAPTURE_PROFILES
+        .read()
+        .unwrap()
+        .iter()
+        .find(|profile| profile.app.eq_ignore_ascii_case(app_name))
+        .cloned()
+}
+
+/// The engine the windows of an app are read with
+pub fn window_ocr_engine(app_name: &str, ocr_engine: &Arc<OcrEngine>) -> Arc<OcrEngine> {
+    match app_capture_profile(app_name) {
+        Some(profile) if profile.handwriting => Arc::new(OcrEngine::Handwriting),
+        _ => ocr_engine.clone(),
+    }
+}
+
+/// Lowers the resolution of a window in the frame to `THUMBNAIL_WIDTH`, parts outside of the
+/// frame are cut
+pub fn thumbnail_window(image: &mut DynamicImage, position: (i32, i32), width: u32, height: u32) {
+    let mut output = std::mem::take(image).into_rgba8();
+    let (x, y) = position;
+    let left = x.max(0) as u32;
+    let top = y.max(0) as u32;
+    let right = (x as i64 + width as i64).clamp(0, output.width() as i64) as u32;
+    let bottom = (y as i64 + height as i64).clamp(0, output.height() as i64) as u32;
+    if right > left && bottom > top {
+        let (width, height) = (right - left, bottom - top);
+        if width > THUMBNAIL_WIDTH {
+            let window: RgbaImage =
+                image::imageops::crop_imm(&output, left, top, width, height).to_image();
+            let thumbnail_height = (height as u64 * THUMBNAIL_WIDTH as u64 / width as u64).max(1);
+            let thumbnail = image::imageops::resize(
+                &window,
+                THUMBNAIL_WIDTH,
+                thumbnail_height as u32,
+                FilterType::Triangle,
+            );
+            let window = image::imageops::resize(&thumbnail, width, height, FilterType::Nearest);
+            image::imageops::replace(&mut output, &window, left as i64, top as i64);
+        }
+    }
+    *image = DynamicImage::ImageRgba8(output);
+}
+
+/// Blacks out a window in the frame, parts outside of the frame are cut
+pub fn blank_window(image: &mut DynamicImage, position: (i32, i32), width: u32, height: u32) {
+    let (x, y) = position;
+    let left = x.max(0) as u32;
+    let top = y.max(0) as u32;
+    let right = (x as i64 + width as i64).clamp(0, image.width() as i64) as u32;
+    let bottom = (y as i64 + height as i64).clamp(0, image.height() as i64) as u32;
+    if right <= left || bottom <= top {
+        return;
+    }
+    let mut output = std::mem::take(image).into_rgba8();
+    let black = RgbaImage::from_pixel(right - left, bottom - top, Rgba([0, 0, 0, 255]));
+    image::imageops::replace(&mut output, &black, left as i64, top as i64);
+    *image = DynamicImage::ImageRgba8(output);
+}
+
+/// Applies the app profiles to the frames of one capture loop, it remembers when the windows
+/// of each app were last captured
+#[derive(Debug, Default)]
+pub struct AppSampler {
+    last_captured: HashMap<String, Instant>,
+}
+
+impl AppSampler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn due(&self, app: &str, profile: &AppCaptureProfile, now: Instant) -> bool {
+        match (profile.interval(), self.last_captured.get(app)) {
+            (Some(interval), Some(last)) => now.duration_since(*last) >= interval,
+            _ => true,
+        }
+    }
+
+    /// The windows to OCR, None when the frame is skipped because the app focused isn't due.
+    /// Thumbnail only windows are lowered in `image` and text only ones blacked out, the
+    /// windows OCR'd are scaled to their max width
+    pub fn sample(
+        &mut self,
+        image: &mut DynamicImage,
+        window_images: Vec<WindowImage>,
+        now: Instant,
+    ) -> Option<Vec<WindowImage>> {
+        if APP_CAPTURE_PROFILES.read().unwrap().is_empty() {
+            return Some(window_images);
+        }
+        // decided for the whole frame, an app can have several windows in it
+        let profiles: Vec<Option<(AppCaptureProfile, bool)>> = window_images
+            .iter()
+            .map(|(_, app_name, ..)| {
+                app_capture_profile(app_name).map(|profile| {
+                    let due = self.due(&app_name.to_lowercase(), &profile, now);
+                    (profile, due)
+                })
+            })
+            .collect();
+        let focused_not_due = window_images
+            .iter()
+            .zip(&profiles)
+            .any(|(window, profile)| window.3 && matches!(profile, Some((_, false))));
+        if focused_not_due {
+            return None;
+        }
+
+        let mut sampled = Vec::new();
+        for (window, profile) in window_images.into_iter().zip(profiles) {
+            let Some((profile, due)) = profile else {
+                sampled.push(window);
+                continue;
+            };
+            if !due {
+                continue;
+            }
+            let (window_image, app_name, window_name, focused, position) = window;
+            self.last_captured.insert(app_name.to_lowercase(), now);
+            if profile.thumbnail_only {
+                thumbnail_window(image, position, window_image.width(), window_image.height());
+                continue;
+            }
+            if profile.text_only {
+                blank_window(image, position, window_image.width(), window_image.height());
+            }
+            let window_image = match profile.max_width {
+                Some(max_width) if max_width > 0 && window_image.width() > max_width => {
+                    let height = (window_image.height() as u64 * max_width as u64
+                        / window_image.width() as u64)
+                        .max(1) as u32;
+                    Arc::new(window_image.resize_exact(max_width, height, FilterType::Triangle))
+                }
+                _ => window_image,
+            };
+            sampled.push((window_image, app_name, window_name, focused, position));
+        }
+        Some(sampled)
+    }
+}
@@ -1,7 +1,11 @@
 use clap::Parser;
 use screenpipe_vision::{continuous_capture, monitor::get_default_monitor, OcrEngine};
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
 use tokio::sync::mpsc::channel;
+use tokio_util::sync::CancellationToken;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -25,6 +29,8 @@ async fn main() {
 
     let monitor = get_default_monitor().await;
     let id = monitor.id();
+    let cancel = CancellationToken::new();
+    let capture_cancel = cancel.clone();
 
     let capture_thread = tokio::spawn(async move {
         continuous_capture(
@@ -33,6 +39,8 @@ async fn main() {
             save_text_files,
             Arc::new(OcrEngine::Tesseract),
             id,
+            Arc::new(AtomicBool::new(false)),
+            capture_cancel,
         )
         .await
     });
@@ -52,5 +60,6 @@ async fn main() {
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
 
+    cancel.cancel();
     capture_thread.await.unwrap();
 }
\ No newline at end of file
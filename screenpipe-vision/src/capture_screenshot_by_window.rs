@@ -1,10 +1,16 @@
 use std::sync::Arc;
 use image::DynamicImage;
 use xcap::{Monitor, Window};
+use crate::core::WindowImage;
+use crate::privacy::{is_app_allowed, is_private_window, PrivateWindow};
 
-pub async fn capture_all_visible_windows(monitor: Arc<Monitor>) -> Result<Vec<(DynamicImage, String, String, bool)>, Box<dyn std::error::Error>> {
+/// Images of the windows, and the private windows and the windows of apps that aren't
+/// allowed, which are not captured
+pub async fn capture_all_visible_windows(monitor: Arc<Monitor>) -> Result<(Vec<WindowImage>, Vec<PrivateWindow>, Vec<PrivateWindow>), Box<dyn std::error::Error>> {
     let windows = Window::all()?;
     let mut captured_images = Vec::new();
+    let mut private_windows = Vec::new();
+    let mut blocked_windows = Vec::new();
     let focused_window = get_focused_window(monitor.clone()).await;
 
     for window in windows {
@@ -16,7 +22,27 @@ pub async fn capture_all_visible_windows(monitor: Arc<Monitor>) -> Result<Vec<(D
            && app_name != "Window Server"
            && app_name != "Contexts"
            && !window_name.is_empty() {
-            
+            // top left corner relative to the monitor the frame is captured from
+            let position = (window.x() - monitor.x(), window.y() - monitor.y());
+            if is_private_window(window_name) {
+                private_windows.push(PrivateWindow {
+                    app_name: app_name.to_string(),
+                    position,
+                    width: window.width(),
+                    height: window.height(),
+                });
+                continue;
+            }
+            if !is_app_allowed(app_name) {
+                blocked_windows.push(PrivateWindow {
+                    app_name: app_name.to_string(),
+                    position,
+                    width: window.width(),
+                    height: window.height(),
+                });
+                continue;
+            }
+
             let buffer = window.capture_image()?;
             let image = DynamicImage::ImageRgba8(image::ImageBuffer::from_raw(
                 buffer.width() as u32,
@@ -24,11 +50,11 @@ pub async fn capture_all_visible_windows(monitor: Arc<Monitor>) -> Result<Vec<(D
                 buffer.into_raw(),
             ).unwrap());
 
-            captured_images.push((image, app_name.to_string(), window_name.to_string(), is_focused));
+            captured_images.push((Arc::new(image), app_name.to_string(), window_name.to_string(), is_focused, position));
         }
     }
 
-    Ok(captured_images)
+    Ok((captured_images, private_windows, blocked_windows))
 }
 
 async fn get_focused_window(monitor: Arc<Monitor>) -> Option<Window> {
@@ -1,31 +1,157 @@
-use image::DynamicImage;
+use image::{DynamicImage, ImageFormat, ImageResult};
 use log::{debug, error};
+use screenpipe_core::{channel_stats, OverflowPolicy};
+#[cfg(feature = "cloud")]
+use screenpipe_integrations::remote_worker::perform_ocr_remote;
+#[cfg(feature = "cloud")]
 use screenpipe_integrations::unstructured_ocr::perform_ocr_cloud;
 use serde_json;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::{
     collections::HashMap,
-    sync::Arc,
+    sync::{Arc, OnceLock, RwLock},
     time::{Duration, Instant},
 };
 use tokio::sync::mpsc::Sender;
+use tokio_util::sync::CancellationToken;
 
 #[cfg(target_os = "macos")]
 use crate::apple::perform_ocr_apple;
+use crate::app_profiles::{window_ocr_engine, AppSampler};
+use crate::errors::{CaptureError, OcrError};
+#[cfg(feature = "handwriting")]
+use crate::handwriting::perform_ocr_handwriting;
 use crate::monitor::get_monitor_by_id;
+use crate::ocr_cache::{cache_key, cache_ocr, cached_ocr};
+use crate::privacy::PrivateWindow;
+use crate::replay::record_replay_frame;
+use crate::symbols::{append_symbols, recognize_symbols, symbol_recognition_enabled};
 #[cfg(target_os = "windows")]
 use crate::utils::perform_ocr_windows;
 use crate::utils::OcrEngine;
 use crate::utils::{
     capture_screenshot, compare_with_previous_image, perform_ocr_tesseract, save_text_files,
+    LumaFrame,
 };
 
+/// Frames differing less than this from the last one sent are skipped
+pub const MIN_FRAME_DIFFERENCE: f64 = 0.006;
+
+/// Changes a running capture without restarting it, set by the power profiles
+#[derive(Debug, Clone, Default)]
+pub struct CaptureOverride {
+    /// Used when longer than the interval the capture started with
+    pub interval: Option<Duration>,
+    pub ocr_engine: Option<OcrEngine>,
+}
+
+static CAPTURE_OVERRIDE: RwLock<CaptureOverride> = RwLock::new(CaptureOverride {
+    interval: None,
+    ocr_engine: None,
+});
+
+pub fn set_capture_override(capture_override: CaptureOverride) {
+    *CAPTURE_OVERRIDE.write().unwrap() = capture_override;
+}
+
+/// Which windows of a frame are OCR'd when frames come in faster than the OCR keeps up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcrPriority {
+    /// Every window, the frames the OCR is too slow for are skipped whole
+    AllWindows,
+    /// Only the focused window while the OCR is behind
+    FocusedOnly,
+    /// The focused window, and the other windows of one frame in this many while the OCR is
+    /// behind
+    SampleBackground(u32),
+}
+
+pub const DEFAULT_BACKGROUND_SAMPLE_EVERY: u32 = 5;
+
+static OCR_PRIORITY: RwLock<OcrPriority> = RwLock::new(OcrPriority::SampleBackground(
+    DEFAULT_BACKGROUND_SAMPLE_EVERY,
+));
+
+pub fn set_ocr_priority(priority: OcrPriority) {
+    *OCR_PRIORITY.write().unwrap() = priority;
+}
+
+/// The windows to OCR, focused first. `behind` is the number of frames in a row the OCR was
+/// still busy with the previous one, 0 when it keeps up. Without a focused window the first
+/// one is kept as if it was
+pub fn prioritize_windows(
+    mut window_images: Vec<WindowImage>,
+    priority: OcrPriority,
+    behind: u64,
+) -> Vec<WindowImage> {
+    // stable, the other windows keep their order
+    window_images.sort_by_key(|(_, _, _, focused, _)| !focused);
+    let keep_background = behind == 0
+        || match priority {
+            OcrPriority::AllWindows => true,
+            OcrPriority::FocusedOnly => false,
+            OcrPriority::SampleBackground(every) => behind.is_multiple_of(every.max(1) as u64),
+        };
+    if !keep_background {
+        let focused = window_images.iter().filter(|window| window.3).count();
+        window_images.truncate(focused.max(1));
+    }
+    window_images
+}
+
+/// The interval and engine to capture the next frame with
+fn capture_settings(interval: Duration, ocr_engine: &Arc<OcrEngine>) -> (Duration, Arc<OcrEngine>) {
+    let capture_override = CAPTURE_OVERRIDE.read().unwrap();
+    (
+        capture_override
+            .interval
+            .map_or(interval, |slower| slower.max(interval)),
+        capture_override
+            .ocr_engine
+            .clone()
+            .map_or_else(|| ocr_engine.clone(), Arc::new),
+    )
+}
+
+/// A window captured on its own, with its app, title, whether it's focused and its top left
+/// corner in the frame
+pub type WindowImage = (Arc<DynamicImage>, String, String, bool, (i32, i32));
+
+/// PNG of a frame, encoded once however many consumers write it to ffmpeg
+#[derive(Clone, Default)]
+pub struct EncodedFrame(Arc<OnceLock<Arc<Vec<u8>>>>);
+
+impl EncodedFrame {
+    /// `image` is encoded on the first call, the same bytes are returned after
+    pub fn png(&self, image: &DynamicImage) -> ImageResult<Arc<Vec<u8>>> {
+        if let Some(png) = self.0.get() {
+            return Ok(Arc::clone(png));
+        }
+        let mut buffer = Vec::new();
+        image.write_to(&mut std::io::Cursor::new(&mut buffer), ImageFormat::Png)?;
+        Ok(Arc::clone(self.0.get_or_init(|| Arc::new(buffer))))
+    }
+}
+
+/// Cheap to clone, the images are shared by the queues of the encoder, OCR and live view
 #[derive(Clone)]
 pub struct CaptureResult {
     pub image: Arc<DynamicImage>,
+    /// Of `image`, reset by `replace_image`
+    pub png: EncodedFrame,
     pub frame_number: u64,
     pub timestamp: Instant,
     pub window_ocr_results: Vec<WindowOcrResult>,
+    /// Blacked out of the image and not OCR'd
+    pub private_windows: Vec<PrivateWindow>,
+}
+
+impl CaptureResult {
+    /// For the redactions, the PNG of the previous image is dropped
+    pub fn replace_image(&mut self, image: DynamicImage) {
+        self.image = Arc::new(image);
+        self.png = EncodedFrame::default();
+    }
 }
 
 #[derive(Clone)]
@@ -36,11 +162,24 @@ pub struct WindowOcrResult {
     pub text: String,
     pub text_json: Vec<HashMap<String, String>>,
     pub focused: bool,
+    /// Top left corner of the window in the captured frame
+    pub position: (i32, i32),
+    /// Set when the text is the one the browser extension read from the page, not the OCR
+    pub browser_page: Option<BrowserPageSource>,
+}
+
+/// The page a browser window showed, as the extension reported it
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrowserPageSource {
+    pub url: String,
+    /// Pixels scrolled from the top of the page
+    pub scroll_y: Option<f64>,
 }
 
 pub struct OcrTaskData {
     pub image: Arc<DynamicImage>,
-    pub window_images: Vec<(DynamicImage, String, String, bool)>,
+    pub window_images: Vec<WindowImage>,
+    pub private_windows: Vec<PrivateWindow>,
     pub frame_number: u64,
     pub timestamp: Instant,
     pub result_tx: Sender<CaptureResult>,
@@ -52,25 +191,47 @@ pub async fn continuous_capture(
     save_text_files_flag: bool,
     ocr_engine: Arc<OcrEngine>,
     monitor_id: u32,
+    paused: Arc<AtomicBool>,
+    // stops the capture, and the OCR of the frame in flight
+    cancel: CancellationToken,
 ) {
     debug!(
         "continuous_capture: Starting using monitor: {:?}",
         monitor_id
     );
     let ocr_task_running = Arc::new(AtomicBool::new(false));
+    // frames captured while the OCR was busy since the last one was sent to it
+    let mut frames_while_busy: u64 = 0;
+    // OCR'd frames in a row that had to wait for the OCR
+    let mut behind: u64 = 0;
     let mut frame_counter: u64 = 0;
-    let mut previous_image: Option<Arc<DynamicImage>> = None;
+    let mut previous_frame: Option<LumaFrame> = None;
     let mut max_average: Option<MaxAverageFrame> = None;
     let mut max_avg_value = 0.0;
+    let mut app_sampler = AppSampler::new();
 
-    let monitor = get_monitor_by_id(monitor_id).await.unwrap();
+    let Some(monitor) = get_monitor_by_id(monitor_id).await else {
+        error!("{}", CaptureError::MonitorNotFound(monitor_id));
+        return;
+    };
     let arc_monitor = Arc::new(monitor.clone());
-
-    loop {
+    let base_interval = interval;
+    let base_ocr_engine = ocr_engine;
+
+    while !cancel.is_cancelled() {
+        let (interval, ocr_engine) = capture_settings(base_interval, &base_ocr_engine);
+        if paused.load(Ordering::SeqCst) {
+            // nothing from before the pause is sent once it ends
+            previous_frame = None;
+            max_average = None;
+            max_avg_value = 0.0;
+            sleep_unless_cancelled(interval, &cancel).await;
+            continue;
+        }
         let arc_monitor = arc_monitor.clone();
         let capture_result = match capture_screenshot(arc_monitor).await {
-            Ok((image, window_images, image_hash, _capture_duration)) => {
-                Some((image, window_images, image_hash))
+            Ok((image, window_images, private_windows, image_hash, _capture_duration)) => {
+                Some((image, window_images, private_windows, image_hash))
             }
             Err(e) => {
                 error!("Failed to capture screenshot: {}", e);
@@ -78,10 +239,24 @@ pub async fn continuous_capture(
             }
         };
 
-        if let Some((image, window_images, image_hash)) = capture_result {
+        if let Some((mut image, window_images, private_windows, image_hash)) = capture_result {
+            let Some(window_images) = app_sampler.sample(&mut image, window_images, Instant::now())
+            else {
+                debug!(
+                    "Skipping frame {}, the app focused isn't due by its profile",
+                    frame_counter
+                );
+                frame_counter += 1;
+                sleep_unless_cancelled(interval, &cancel).await;
+                continue;
+            };
+            // shared from here on by the comparison, the best frame and the OCR
+            let image = Arc::new(image);
+            record_replay_frame(monitor_id, &image, &window_images, &private_windows).await;
+            let luma = LumaFrame::new(&image);
             let current_average = match compare_with_previous_image(
-                &previous_image,
-                &image,
+                &previous_frame,
+                &luma,
                 &mut max_average,
                 frame_counter,
                 &mut max_avg_value,
@@ -96,27 +271,31 @@ pub async fn continuous_capture(
             };
 
             // Account for situation when there is no previous image
-            let current_average = if previous_image.is_none() {
+            let current_average = if previous_frame.is_none() {
                 1.0 // Default value to ensure the frame is processed
             } else {
                 current_average
             };
 
-            // Skip the frame if the current average difference is less than 0.006
-            if current_average < 0.006 {
+            if current_average < MIN_FRAME_DIFFERENCE {
                 debug!(
                     "Skipping frame {} due to low average difference: {:.3}",
                     frame_counter, current_average
                 );
                 frame_counter += 1;
-                tokio::time::sleep(interval).await;
+                sleep_unless_cancelled(interval, &cancel).await;
                 continue;
             }
 
+            previous_frame = Some(luma);
+            if ocr_task_running.load(Ordering::SeqCst) {
+                frames_while_busy += 1;
+            }
             if current_average > max_avg_value {
                 max_average = Some(MaxAverageFrame {
-                    image: Arc::new(image.clone()),
-                    window_images: window_images.clone(),
+                    image,
+                    window_images,
+                    private_windows,
                     image_hash,
                     frame_number: frame_counter,
                     timestamp: Instant::now(),
@@ -126,32 +305,47 @@ pub async fn continuous_capture(
                 max_avg_value = current_average;
             }
 
-            previous_image = Some(Arc::new(image.clone()));
-
             if !ocr_task_running.load(Ordering::SeqCst) {
                 if let Some(max_avg_frame) = max_average.take() {
+                    behind = if frames_while_busy > 0 { behind + 1 } else { 0 };
+                    frames_while_busy = 0;
+                    let priority = *OCR_PRIORITY.read().unwrap();
+                    let window_count = max_avg_frame.window_images.len();
+                    let window_images =
+                        prioritize_windows(max_avg_frame.window_images, priority, behind);
+                    if window_images.len() < window_count {
+                        debug!(
+                            "OCR is behind, skipping {} background windows of frame {}",
+                            window_count - window_images.len(),
+                            max_avg_frame.frame_number
+                        );
+                    }
                     let ocr_task_data = OcrTaskData {
-                        image: max_avg_frame.image.clone(),
-                        window_images: max_avg_frame.window_images.clone(),
+                        image: max_avg_frame.image,
+                        window_images,
+                        private_windows: max_avg_frame.private_windows,
                         frame_number: max_avg_frame.frame_number,
                         timestamp: max_avg_frame.timestamp,
-                        result_tx: max_avg_frame.result_tx.clone(),
+                        result_tx: max_avg_frame.result_tx,
                     };
 
                     let ocr_task_running_clone = ocr_task_running.clone();
 
                     ocr_task_running.store(true, Ordering::SeqCst);
                     let ocr_engine_clone = ocr_engine.clone();
+                    let cancel = cancel.clone();
 
                     tokio::spawn(async move {
                         if let Err(e) = process_ocr_task(
                             ocr_task_data.image,
                             ocr_task_data.window_images,
+                            ocr_task_data.private_windows,
                             ocr_task_data.frame_number,
                             ocr_task_data.timestamp,
                             ocr_task_data.result_tx,
                             save_text_files_flag,
                             ocr_engine_clone,
+                            cancel,
                         )
                         .await
                         {
@@ -170,12 +364,22 @@ pub async fn continuous_capture(
         }
 
         frame_counter += 1;
-        tokio::time::sleep(interval).await;
+        sleep_unless_cancelled(interval, &cancel).await;
     }
+    debug!("continuous_capture: Cancelled on monitor {}", monitor_id);
 }
+
+async fn sleep_unless_cancelled(duration: Duration, cancel: &CancellationToken) {
+    tokio::select! {
+        _ = tokio::time::sleep(duration) => {}
+        _ = cancel.cancelled() => {}
+    }
+}
+
 pub struct MaxAverageFrame {
     pub image: Arc<DynamicImage>,
-    pub window_images: Vec<(DynamicImage, String, String, bool)>,
+    pub window_images: Vec<WindowImage>,
+    pub private_windows: Vec<PrivateWindow>,
     pub image_hash: u64,
     pub frame_number: u64,
     pub timestamp: Instant,
@@ -185,13 +389,16 @@ pub struct MaxAverageFrame {
 
 pub async fn process_ocr_task(
     image_arc: Arc<DynamicImage>,
-    window_images: Vec<(DynamicImage, String, String, bool)>,
+    window_images: Vec<WindowImage>,
+    private_windows: Vec<PrivateWindow>,
     frame_number: u64,
     timestamp: Instant,
     result_tx: Sender<CaptureResult>,
     save_text_files_flag: bool,
     ocr_engine: Arc<OcrEngine>,
-) -> Result<(), std::io::Error> {
+    // nothing is sent for a frame cancelled, the windows read so far are thrown away
+    cancel: CancellationToken,
+) -> Result<(), CaptureError> {
     let start_time = Instant::now();
 
     debug!(
@@ -201,32 +408,45 @@ pub async fn process_ocr_task(
 
     // Perform OCR on window images
     let mut window_ocr_results = Vec::new();
-    for (window_image, window_app_name, window_name, focused) in window_images {
-        let window_image_arc = Arc::new(window_image);
-        let (window_text, window_json_output) = match &*ocr_engine {
-            OcrEngine::Unstructured => perform_ocr_cloud(&window_image_arc)
-                .await
-                .map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))?,
-            OcrEngine::Tesseract => perform_ocr_tesseract(&window_image_arc),
-            #[cfg(target_os = "windows")]
-            OcrEngine::WindowsNative => perform_ocr_windows(&window_image_arc).await,
-            #[cfg(target_os = "macos")]
-            OcrEngine::AppleNative => parse_apple_ocr_result(&perform_ocr_apple(&window_image_arc)),
-            _ => {
-                return Err(std::io::Error::new(
-                    std::io::ErrorKind::Other,
-                    "Unsupported OCR engine",
-                ))
+    for (window_image, window_app_name, window_name, focused, position) in window_images {
+        if cancel.is_cancelled() {
+            debug!("OCR of frame {} cancelled", frame_number);
+            return Ok(());
+        }
+        let window_engine = window_ocr_engine(&window_app_name, &ocr_engine);
+        let cache_key = cache_key(&window_engine, &window_image);
+        let (mut window_text, window_json_output) = match cached_ocr(&cache_key) {
+            Some(cached) => cached,
+            None => {
+                let (text, json_output) = tokio::select! {
+                    result = perform_ocr(&window_engine, &window_image) => {
+                        result.map_err(|source| CaptureError::Ocr { frame_number, source })?
+                    }
+                    _ = cancel.cancelled() => {
+                        debug!("OCR of frame {} cancelled", frame_number);
+                        return Ok(());
+                    }
+                };
+                cache_ocr(cache_key, &text, &json_output);
+                (text, json_output)
             }
         };
 
+        let mut text_json = parse_json_output(&window_json_output);
+        if symbol_recognition_enabled() {
+            let symbols = recognize_symbols(&window_image, &window_text);
+            append_symbols(&mut window_text, &mut text_json, &symbols);
+        }
+
         window_ocr_results.push(WindowOcrResult {
             window_name,
             app_name: window_app_name,
-            image: window_image_arc,
+            image: window_image,
             text: window_text,
-            text_json: parse_json_output(&window_json_output),
+            text_json,
             focused,
+            position,
+            browser_page: None,
         });
     }
 
@@ -243,32 +463,64 @@ pub async fn process_ocr_task(
         }
     }
 
+    let windows = window_ocr_results.len();
     let capture_result = CaptureResult {
         image: image_arc,
+        png: EncodedFrame::default(),
         frame_number,
         timestamp,
-        window_ocr_results: window_ocr_results.clone(),
+        window_ocr_results,
+        private_windows,
     };
 
-    if let Err(e) = result_tx.send(capture_result).await {
-        error!("Failed to send OCR result: {}", e);
-        return Err(std::io::Error::new(
-            std::io::ErrorKind::Other,
-            "Failed to send OCR result",
-        ));
+    // a newer frame follows when the consumer catches up
+    let stats = channel_stats(
+        "capture.frames",
+        OverflowPolicy::DropNewest,
+        result_tx.max_capacity(),
+    );
+    if !stats.try_send(&result_tx, capture_result) {
+        return Err(CaptureError::ReceiverDropped);
     }
 
     let duration = start_time.elapsed();
     debug!(
         "OCR task processed frame {} with {} windows in {:?}",
         frame_number,
-        window_ocr_results.len(),
+        windows,
         duration
     );
     Ok(())
 }
 
-fn parse_json_output(json_output: &str) -> Vec<HashMap<String, String>> {
+/// Text and the engine's json output of an image
+pub async fn perform_ocr(
+    ocr_engine: &OcrEngine,
+    image: &Arc<DynamicImage>,
+) -> Result<(String, String), OcrError> {
+    Ok(match ocr_engine {
+        #[cfg(feature = "cloud")]
+        OcrEngine::Unstructured => perform_ocr_cloud(image)
+            .await
+            .map_err(|e| OcrError::engine(ocr_engine, e))?,
+        #[cfg(feature = "cloud")]
+        OcrEngine::Remote => perform_ocr_remote(image)
+            .await
+            .map_err(|e| OcrError::engine(ocr_engine, e))?,
+        OcrEngine::Tesseract => perform_ocr_tesseract(image),
+        #[cfg(feature = "handwriting")]
+        OcrEngine::Handwriting => perform_ocr_handwriting(image)
+            .await
+            .map_err(|e| OcrError::engine(ocr_engine, e))?,
+        #[cfg(target_os = "windows")]
+        OcrEngine::WindowsNative => perform_ocr_windows(image).await,
+        #[cfg(target_os = "macos")]
+        OcrEngine::AppleNative => parse_apple_ocr_result(&perform_ocr_apple(image)),
+        _ => return Err(OcrError::UnsupportedEngine(ocr_engine.clone())),
+    })
+}
+
+pub(crate) fn parse_json_output(json_output: &str) -> Vec<HashMap<String, String>> {
     let parsed_output: Vec<HashMap<String, String>> = serde_json::from_str(json_output)
         .unwrap_or_else(|e| {
             error!("Failed to parse JSON output: {}", e);
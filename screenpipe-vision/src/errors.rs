@@ -0,0 +1,64 @@
+use crate::utils::OcrEngine;
+use thiserror::Error;
+
+/// Why the text of an image couldn't be read
+#[derive(Debug, Error)]
+pub enum OcrError {
+    #[error("the {0:?} OCR engine isn't available on this platform or in this build")]
+    UnsupportedEngine(OcrEngine),
+    /// The engine ran and failed, `message` is what it said
+    #[error("{engine:?} OCR failed: {message}")]
+    Engine { engine: OcrEngine, message: String },
+}
+
+impl OcrError {
+    // only the engines behind a feature can fail this way
+    #[cfg_attr(not(any(feature = "cloud", feature = "handwriting")), allow(dead_code))]
+    pub(crate) fn engine(engine: &OcrEngine, error: impl ToString) -> Self {
+        OcrError::Engine {
+            engine: engine.clone(),
+            message: error.to_string(),
+        }
+    }
+
+    /// Stable name of the cause for the API and the pipes to branch on
+    pub fn code(&self) -> &'static str {
+        match self {
+            OcrError::UnsupportedEngine(_) => "ocr_engine_unsupported",
+            OcrError::Engine { .. } => "ocr_engine_failed",
+        }
+    }
+}
+
+/// Why a frame couldn't be captured, or its OCR result not handed on
+#[derive(Debug, Error)]
+pub enum CaptureError {
+    #[error("monitor {0} not found")]
+    MonitorNotFound(u32),
+    #[error("failed to capture monitor {monitor_id}: {source}")]
+    Screenshot {
+        monitor_id: u32,
+        #[source]
+        source: xcap::XCapError,
+    },
+    #[error("failed to read the text of frame {frame_number}: {source}")]
+    Ocr {
+        frame_number: u64,
+        #[source]
+        source: OcrError,
+    },
+    /// Nothing takes the captured frames anymore
+    #[error("the receiver of the captured frames was dropped")]
+    ReceiverDropped,
+}
+
+impl CaptureError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CaptureError::MonitorNotFound(_) => "monitor_not_found",
+            CaptureError::Screenshot { .. } => "screen_capture_failed",
+            CaptureError::Ocr { source, .. } => source.code(),
+            CaptureError::ReceiverDropped => "capture_receiver_dropped",
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::{Error as E, Result};
+use candle::{DType, Device, Tensor};
+use candle_nn::{ops::softmax, VarBuilder};
+use candle_transformers::models::{trocr, vit};
+use image::imageops::FilterType;
+use image::DynamicImage;
+use log::{debug, error, info};
+use screenpipe_core::{model_device, AcceleratorLease, LocalModel, TROCR_HANDWRITTEN};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokenizers::Tokenizer;
+
+use crate::symbols::ink_mask;
+
+/// Rows of ink this close are the same line of writing
+const MAX_LINE_GAP: u32 = 4;
+/// Bands of ink less tall are the rules of the page, not writing
+const MIN_LINE_HEIGHT: u32 = 8;
+/// Blank kept around the ink of a line, the model reads lines cropped loosely
+const LINE_MARGIN: u32 = 4;
+/// Lines read per window, a page of notes
+const MAX_LINES: usize = 40;
+/// Tokens decoded per line
+const MAX_LINE_TOKENS: usize = 96;
+/// Megabytes the weights take in f32
+const MODEL_MEMORY_MB: u64 = 1400;
+/// Wait before loading the model again after it failed to load, doubled on each failure
+const LOAD_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+const MAX_LOAD_RETRY_BACKOFF: Duration = Duration::from_secs(3600);
+
+/// The model reads one line at a time, the lines of a window are found first
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandwritingLine {
+    pub left: u32,
+    pub top: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// The lines of writing of an image, top to bottom, each cropped to its ink
+pub fn handwriting_lines(image: &DynamicImage) -> Vec<HandwritingLine> {
+    let (mask, width, height) = ink_mask(image);
+    let row_has_ink = |y: usize| mask[y * width..(y + 1) * width].iter().any(|ink| *ink);
+    let mut bands: Vec<(u32, u32)> = Vec::new();
+    for y in (0..height).filter(|y| row_has_ink(*y)) {
+        let y = y as u32;
+        match bands.last_mut() {
+            Some((_, bottom)) if y - *bottom <= MAX_LINE_GAP => *bottom = y,
+            _ => bands.push((y, y)),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (top, bottom) in bands {
+        if bottom - top + 1 < MIN_LINE_HEIGHT {
+            continue;
+        }
+        let rows = &mask[top as usize * width..(bottom as usize + 1) * width];
+        let column_has_ink = |x: usize| rows.iter().skip(x).step_by(width).any(|ink| *ink);
+        let Some(left) = (0..width).find(|x| column_has_ink(*x)) else {
+            continue;
+        };
+        let right = (0..width)
+            .rev()
+            .find(|x| column_has_ink(*x))
+            .unwrap_or(left);
+        let left = (left as u32).saturating_sub(LINE_MARGIN);
+        let top = top.saturating_sub(LINE_MARGIN);
+        let right = (right as u32 + LINE_MARGIN).min(width as u32 - 1);
+        let bottom = (bottom + LINE_MARGIN).min(height as u32 - 1);
+        lines.push(HandwritingLine {
+            left,
+            top,
+            width: right - left + 1,
+            height: bottom - top + 1,
+        });
+        if lines.len() == MAX_LINES {
+            break;
+        }
+    }
+    lines
+}
+
+#[derive(Debug, Clone, serde::Deserialize)]
+struct Config {
+    encoder: vit::Config,
+    decoder: trocr::TrOCRConfig,
+}
+
+pub struct HandwritingModel {
+    model: trocr::TrOCRModel,
+    tokenizer: Tokenizer,
+    device: Device,
+    config: Config,
+    _lease: AcceleratorLease,
+}
+
+impl HandwritingModel {
+    /// Downloads the TrOCR handwritten model the first time
+    pub fn new() -> Result<Self> {
+        let (device, lease) = model_device(LocalModel::Ocr, MODEL_MEMORY_MB);
+        info!("Loading the handwriting model on {}", lease.accelerator);
+        let config: Config = serde_json::from_str(&std::fs::read_to_string(
+            TROCR_HANDWRITTEN.file("config.json")?,
+        )?)?;
+        let weights = TROCR_HANDWRITTEN.file("model.safetensors")?;
+        let tokenizer = TROCR_HANDWRITTEN.file("tokenizer.json")?;
+        let tokenizer = Tokenizer::from_file(tokenizer).map_err(E::msg)?;
+        let vb = unsafe { VarBuilder::from_mmaped_safetensors(&[weights], DType::F32, &device)? };
+        let model = trocr::TrOCRModel::new(&config.encoder, &config.decoder, vb)?;
+        debug!("Handwriting model loaded");
+        Ok(Self {
+            model,
+            tokenizer,
+            device,
+            config,
+            _lease: lease,
+        })
+    }
+
+    /// The image scaled to the input of the encoder, normalized to -1..1
+    fn pixels(&self, line: &DynamicImage) -> Result<Tensor> {
+        let size = self.config.encoder.image_size as u32;
+        let rgb = line
+            .resize_exact(size, size, FilterType::Triangle)
+            .to_rgb8();
+        let pixels = Tensor::from_vec(
+            rgb.into_raw(),
+            (size as usize, size as usize, 3),
+            &self.device,
+        )?
+        .permute((2, 0, 1))?
+        .to_dtype(DType::F32)?;
+        Ok(((pixels / 127.5)? - 1.0)?.unsqueeze(0)?)
+    }
+
+    /// The text of a line of writing, and how sure the model is of it out of 100
+    pub fn read_line(&mut self, line: &DynamicImage) -> Result<(String, f32)> {
+        let pixels = self.pixels(line)?;
+        let encoder_xs = self.model.encoder().forward(&pixels)?;
+        let mut tokens = vec![self.config.decoder.decoder_start_token_id];
+        let mut confidence = 0.0;
+        for index in 0..MAX_LINE_TOKENS {
+            // the decoder caches what it's already seen
+            let start = if index == 0 { 0 } else { tokens.len() - 1 };
+            let input = Tensor::new(&tokens[start..], &self.device)?.unsqueeze(0)?;
+            let logits = self.model.decode(&input, &encoder_xs, start)?.squeeze(0)?;
+            let logits = logits.get(logits.dim(0)? - 1)?;
+            let token = logits.argmax(0)?.to_scalar::<u32>()?;
+            if token == self.config.decoder.eos_token_id {
+                break;
+            }
+            confidence += softmax(&logits, 0)?
+                .get(token as usize)?
+                .to_scalar::<f32>()?;
+            tokens.push(token);
+        }
+        self.model.reset_kv_cache();
+        let read = tokens.len() - 1;
+        let text = self.tokenizer.decode(&tokens[1..], true).map_err(E::msg)?;
+        let confidence = if read == 0 {
+            0.0
+        } else {
+            confidence / read as f32 * 100.0
+        };
+        Ok((text.trim().to_string(), confidence))
+    }
+}
+
+struct LoadedModel {
+    model: Option<HandwritingModel>,
+    failures: u32,
+    /// No new attempt at loading the model before then
+    retry_at: Option<Instant>,
+}
+
+/// Loaded on the first window read with it. A failed load, like a download cut short, is
+/// tried again after a backoff instead of leaving handwriting off until a restart
+static HANDWRITING_MODEL: Mutex<LoadedModel> = Mutex::new(LoadedModel {
+    model: None,
+    failures: 0,
+    retry_at: None,
+});
+
+fn read_handwriting(image: &DynamicImage) -> Result<(String, String)> {
+    let mut loaded = HANDWRITING_MODEL.lock().unwrap();
+    if loaded.model.is_none() {
+        if loaded
+            .retry_at
+            .is_some_and(|retry_at| Instant::now() < retry_at)
+        {
+            return Err(E::msg("the handwriting model isn't loaded"));
+        }
+        match HandwritingModel::new() {
+            Ok(model) => {
+                loaded.model = Some(model);
+                loaded.failures = 0;
+                loaded.retry_at = None;
+            }
+            Err(e) => {
+                let backoff = LOAD_RETRY_BACKOFF
+                    .saturating_mul(2u32.saturating_pow(loaded.failures))
+                    .min(MAX_LOAD_RETRY_BACKOFF);
+                loaded.failures += 1;
+                loaded.retry_at = Some(Instant::now() + backoff);
+                error!(
+                    "Failed to load the handwriting model, trying again in {:?}: {}",
+                    backoff, e
+                );
+                return Err(e);
+            }
+        }
+    }
+    let Some(model) = loaded.model.as_mut() else {
+        return Err(E::msg("the handwriting model isn't loaded"));
+    };
+    let mut text_lines = Vec::new();
+    let mut json_lines: Vec<HashMap<String, String>> = Vec::new();
+    for (index, line) in handwriting_lines(image).into_iter().enumerate() {
+        let crop = image.crop_imm(line.left, line.top, line.width, line.height);
+        let (text, confidence) = model.read_line(&crop)?;
+        if text.is_empty() {
+            continue;
+        }
+        json_lines.push(HashMap::from([
+            ("text".to_string(), text.clone()),
+            ("confidence".to_string(), format!("{:.2}", confidence)),
+            (
+                "line_position".to_string(),
+                format!("handwriting_line{}", index + 1),
+            ),
+        ]));
+        text_lines.push(text);
+    }
+    Ok((text_lines.join("\n"), serde_json::to_string(&json_lines)?))
+}
+
+/// Text and json output of an image of handwriting, read line by line with TrOCR off the
+/// async runtime
+pub async fn perform_ocr_handwriting(image: &Arc<DynamicImage>) -> Result<(String, String)> {
+    let image = image.clone();
+    tokio::task::spawn_blocking(move || read_handwriting(&image)).await?
+}
@@ -1,10 +1,36 @@
 #[cfg(target_os = "macos")]
 pub mod apple;
+pub mod app_profiles;
 pub mod core;
+pub mod errors;
+#[cfg(feature = "handwriting")]
+pub mod handwriting;
 pub mod monitor;
+pub mod ocr_cache;
+pub mod privacy;
+pub mod replay;
+pub mod symbols;
 pub mod utils;
 #[cfg(target_os = "macos")]
 pub use apple::perform_ocr_apple;
-pub use core::{continuous_capture, process_ocr_task, CaptureResult};
-pub use utils::{perform_ocr_tesseract, OcrEngine};
+pub use app_profiles::{
+    set_app_capture_profiles, window_ocr_engine, AppCaptureProfile, AppSampler,
+};
+pub use core::{
+    continuous_capture, perform_ocr, prioritize_windows, process_ocr_task, set_capture_override,
+    set_ocr_priority, CaptureOverride, CaptureResult, EncodedFrame, OcrPriority, WindowImage,
+    DEFAULT_BACKGROUND_SAMPLE_EVERY, MIN_FRAME_DIFFERENCE,
+};
+pub use errors::{CaptureError, OcrError};
+#[cfg(feature = "handwriting")]
+pub use handwriting::{handwriting_lines, perform_ocr_handwriting, HandwritingLine};
+pub use ocr_cache::{clear_ocr_cache, ocr_cache_stats, OcrCacheStats};
+pub use privacy::{report_private_windows, set_allowed_apps, PrivateWindow};
+pub use symbols::{recognize_symbols, set_symbol_recognition};
+pub use replay::{
+    replay_bundle, start_replay_recording, ReplayOptions, ReplayRecorder, ReplayReport,
+};
+pub use utils::{
+    perform_ocr_tesseract, perform_ocr_tesseract_words, LumaFrame, OcrEngine, OcrWord,
+};
 pub mod capture_screenshot_by_window;
\ No newline at end of file
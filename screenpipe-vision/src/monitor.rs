@@ -32,6 +32,19 @@ fn is_significant_size(window: &Window, monitor: &Monitor) -> bool {
     ratio > 0.2 && ratio <= 1.0
 }
 
+/// App and title of the window in front, on any monitor. Only asks for the windows, nothing
+/// is captured
+pub async fn get_focused_window_title() -> Option<(String, String)> {
+    Window::all().ok()?.into_iter().find_map(|w| {
+        let app_name = w.app_name();
+        (!w.is_minimized()
+            && app_name != "Window Server"
+            && app_name != "Contexts"
+            && !w.title().is_empty())
+        .then(|| (app_name.to_string(), w.title().to_string()))
+    })
+}
+
 pub async fn get_windows_for_monitor(monitor: Monitor) -> Vec<Window> {
     Window::all()
         .unwrap_or_default()
@@ -41,7 +54,7 @@ pub async fn get_windows_for_monitor(monitor: Monitor) -> Vec<Window> {
 }
 
 pub async fn list_monitors() -> Vec<Monitor> {
-    let monitors = Monitor::all().unwrap();
+    let monitors = Monitor::all().unwrap_or_default();
     return monitors.iter().map(|m| m.clone()).collect();
 }
 
@@ -0,0 +1,114 @@
+use crate::utils::{calculate_hash, OcrEngine};
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// A window image OCR'd longer ago than this is OCR'd again
+const OCR_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+/// The least recently used windows are forgotten past this many
+const OCR_CACHE_CAPACITY: usize = 512;
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub(crate) struct CacheKey {
+    image_hash: u64,
+    width: u32,
+    height: u32,
+    /// Engines don't find the same text
+    engine: String,
+}
+
+struct CacheEntry {
+    text: String,
+    json_output: String,
+    ocr_at: Instant,
+    used_at: Instant,
+}
+
+/// The OCR engine's output for the exact same pixels: sidebars, toolbars and windows that
+/// didn't change since the previous frame
+static OCR_CACHE: Mutex<Option<HashMap<CacheKey, CacheEntry>>> = Mutex::new(None);
+static HITS: AtomicU64 = AtomicU64::new(0);
+static MISSES: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OcrCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+pub(crate) fn cache_key(ocr_engine: &OcrEngine, image: &DynamicImage) -> CacheKey {
+    CacheKey {
+        image_hash: calculate_hash(image),
+        width: image.width(),
+        height: image.height(),
+        engine: format!("{:?}", ocr_engine),
+    }
+}
+
+/// The text and json output of the last OCR of the same image, if recent
+pub(crate) fn cached_ocr(key: &CacheKey) -> Option<(String, String)> {
+    let mut cache = OCR_CACHE.lock().unwrap();
+    let cached = cache.as_mut().and_then(|entries| {
+        let entry = entries.get_mut(key)?;
+        if entry.ocr_at.elapsed() > OCR_CACHE_TTL {
+            entries.remove(key);
+            return None;
+        }
+        entry.used_at = Instant::now();
+        Some((entry.text.clone(), entry.json_output.clone()))
+    });
+    match cached {
+        Some(_) => HITS.fetch_add(1, Ordering::Relaxed),
+        None => MISSES.fetch_add(1, Ordering::Relaxed),
+    };
+    cached
+}
+
+pub(crate) fn cache_ocr(key: CacheKey, text: &str, json_output: &str) {
+    let mut cache = OCR_CACHE.lock().unwrap();
+    let entries = cache.get_or_insert_with(HashMap::new);
+    let now = Instant::now();
+    entries.insert(
+        key,
+        CacheEntry {
+            text: text.to_string(),
+            json_output: json_output.to_string(),
+            ocr_at: now,
+            used_at: now,
+        },
+    );
+    if entries.len() > OCR_CACHE_CAPACITY {
+        entries.retain(|_, entry| entry.ocr_at.elapsed() <= OCR_CACHE_TTL);
+    }
+    while entries.len() > OCR_CACHE_CAPACITY {
+        let Some(oldest) = entries
+            .iter()
+            .min_by_key(|(_, entry)| entry.used_at)
+            .map(|(key, _)| key.clone())
+        else {
+            break;
+        };
+        entries.remove(&oldest);
+    }
+}
+
+pub fn ocr_cache_stats() -> OcrCacheStats {
+    OcrCacheStats {
+        hits: HITS.load(Ordering::Relaxed),
+        misses: MISSES.load(Ordering::Relaxed),
+        entries: OCR_CACHE
+            .lock()
+            .unwrap()
+            .as_ref()
+            .map_or(0, |entries| entries.len()),
+    }
+}
+
+/// Forgets every cached result, for when the same pixels should be read again, like after
+/// switching to another engine's worker
+pub fn clear_ocr_cache() {
+    *OCR_CACHE.lock().unwrap() = None;
+}
@@ -0,0 +1,104 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, RwLock};
+
+/// What browsers put in the title of their private windows
+const PRIVATE_TITLE_MARKERS: [&str; 5] = [
+    "incognito",
+    "inprivate",
+    "private browsing",
+    "private window",
+    // brave
+    "(private)",
+];
+
+/// Titles of the private windows browser extensions told us about
+static REPORTED_PRIVATE_TITLES: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Apps whose windows are captured, all of them when not set
+static ALLOWED_APPS: RwLock<Option<Vec<String>>> = RwLock::new(None);
+
+/// Window left out of the capture, its title is not kept
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PrivateWindow {
+    pub app_name: String,
+    /// Top left corner in the captured frame
+    pub position: (i32, i32),
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Replaces what extensions reported before. Extensions know which of their windows are
+/// private and report the title of their active tab, which the window title contains
+pub fn report_private_windows(titles: Vec<String>) {
+    *REPORTED_PRIVATE_TITLES.lock().unwrap() = titles
+        .into_iter()
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty())
+        .collect();
+}
+
+pub fn is_private_window(window_name: &str) -> bool {
+    let title = window_name.to_lowercase();
+    PRIVATE_TITLE_MARKERS
+        .iter()
+        .any(|marker| title.contains(marker))
+        || REPORTED_PRIVATE_TITLES
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|reported| window_name.contains(reported.as_str()))
+}
+
+/// Restricts capture to the windows of these apps, case insensitive. The rest of the
+/// screen is blacked out
+pub fn set_allowed_apps(apps: Option<Vec<String>>) {
+    *ALLOWED_APPS.write().unwrap() = apps;
+}
+
+pub fn has_app_allowlist() -> bool {
+    ALLOWED_APPS.read().unwrap().is_some()
+}
+
+pub fn is_app_allowed(app_name: &str) -> bool {
+    match &*ALLOWED_APPS.read().unwrap() {
+        Some(apps) => apps.iter().any(|app| app.eq_ignore_ascii_case(app_name)),
+        None => true,
+    }
+}
+
+/// Blacks out everything but the windows, `(position, width, height)` in the frame
+pub fn keep_only(image: &mut DynamicImage, windows: &[((i32, i32), u32, u32)]) {
+    // taken, not copied, when the capture is already rgba
+    let original = std::mem::take(image).into_rgba8();
+    let mut output =
+        RgbaImage::from_pixel(original.width(), original.height(), Rgba([0, 0, 0, 255]));
+    for &((x, y), width, height) in windows {
+        let left = x.max(0) as u32;
+        let top = y.max(0) as u32;
+        let right = (x as i64 + width as i64).clamp(0, original.width() as i64) as u32;
+        let bottom = (y as i64 + height as i64).clamp(0, original.height() as i64) as u32;
+        if right <= left || bottom <= top {
+            continue;
+        }
+        let window = image::imageops::crop_imm(&original, left, top, right - left, bottom - top);
+        image::imageops::replace(&mut output, &*window, left as i64, top as i64);
+    }
+    *image = DynamicImage::ImageRgba8(output);
+}
+
+/// Blacks out the windows in the frame, parts outside of it are cut
+pub fn black_out(image: &mut DynamicImage, windows: &[PrivateWindow]) {
+    let mut output = std::mem::take(image).into_rgba8();
+    for window in windows {
+        let (x, y) = window.position;
+        let width = (window.width as i64 + x.min(0) as i64).max(0) as u32;
+        let height = (window.height as i64 + y.min(0) as i64).max(0) as u32;
+        if width == 0 || height == 0 {
+            continue;
+        }
+        let patch = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 255]));
+        image::imageops::replace(&mut output, &patch, x.max(0) as i64, y.max(0) as i64);
+    }
+    *image = DynamicImage::ImageRgba8(output);
+}
@@ -0,0 +1,306 @@
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use image::{DynamicImage, ImageFormat};
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::core::{
+    parse_json_output, perform_ocr, prioritize_windows, OcrPriority, WindowImage,
+    MIN_FRAME_DIFFERENCE,
+};
+use crate::privacy::PrivateWindow;
+use crate::utils::{LumaFrame, OcrEngine};
+
+/// Of a bundle, next to the frames it lists
+pub const REPLAY_MANIFEST: &str = "manifest.json";
+/// Bumped when the manifest changes in a way older replays can't read
+pub const REPLAY_BUNDLE_VERSION: u32 = 1;
+
+/// What the capture of one monitor returned, frame after frame, before any diffing or OCR
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayManifest {
+    pub version: u32,
+    pub monitor_id: u32,
+    pub recorded_at: DateTime<Utc>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    /// Since the first frame of the bundle
+    pub offset_ms: u64,
+    /// PNG of the whole frame, relative to the bundle, private windows already blacked out
+    pub image: String,
+    pub windows: Vec<ReplayWindow>,
+    pub private_windows: Vec<PrivateWindow>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReplayWindow {
+    pub app_name: String,
+    pub window_name: String,
+    pub focused: bool,
+    /// Top left corner of the window in the frame
+    pub position: (i32, i32),
+    /// PNG of the window, relative to the bundle
+    pub image: String,
+}
+
+/// Writes the frames of a capture to a bundle `screenpipe-replay` feeds back to the pipeline
+pub struct ReplayRecorder {
+    dir: PathBuf,
+    started: Instant,
+    manifest: ReplayManifest,
+}
+
+impl ReplayRecorder {
+    /// Fails when `dir` already holds a bundle
+    pub fn create(dir: &Path, monitor_id: u32) -> Result<Self> {
+        if dir.join(REPLAY_MANIFEST).exists() {
+            bail!("{} already holds a replay bundle", dir.display());
+        }
+        std::fs::create_dir_all(dir.join("frames"))?;
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            started: Instant::now(),
+            manifest: ReplayManifest {
+                version: REPLAY_BUNDLE_VERSION,
+                monitor_id,
+                recorded_at: Utc::now(),
+                frames: Vec::new(),
+            },
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn frames(&self) -> usize {
+        self.manifest.frames.len()
+    }
+
+    /// The manifest is rewritten after each frame, what was recorded before a crash replays
+    pub fn record(
+        &mut self,
+        image: &DynamicImage,
+        window_images: &[WindowImage],
+        private_windows: &[PrivateWindow],
+    ) -> Result<()> {
+        let number = self.manifest.frames.len() + 1;
+        let frame_image = format!("frames/{:06}.png", number);
+        image.save_with_format(self.dir.join(&frame_image), ImageFormat::Png)?;
+        let mut windows = Vec::new();
+        for (index, (window_image, app_name, window_name, focused, position)) in
+            window_images.iter().enumerate()
+        {
+            let path = format!("frames/{:06}-{}.png", number, index);
+            window_image.save_with_format(self.dir.join(&path), ImageFormat::Png)?;
+            windows.push(ReplayWindow {
+                app_name: app_name.clone(),
+                window_name: window_name.clone(),
+                focused: *focused,
+                position: *position,
+                image: path,
+            });
+        }
+        self.manifest.frames.push(ReplayFrame {
+            offset_ms: self.started.elapsed().as_millis() as u64,
+            image: frame_image,
+            windows,
+            private_windows: private_windows.to_vec(),
+        });
+        std::fs::write(
+            self.dir.join(REPLAY_MANIFEST),
+            serde_json::to_vec_pretty(&self.manifest)?,
+        )?;
+        Ok(())
+    }
+}
+
+struct ReplayRecording {
+    recorder: ReplayRecorder,
+    until: Instant,
+}
+
+/// Taken out while a frame is written, the capture loop of the monitor is the only one using it
+static REPLAY_RECORDING: Mutex<Option<ReplayRecording>> = Mutex::new(None);
+
+/// The frames `monitor_id` captures during `duration` are recorded to a bundle in `dir`
+pub fn start_replay_recording(dir: &Path, monitor_id: u32, duration: Duration) -> Result<()> {
+    let recorder = ReplayRecorder::create(dir, monitor_id)?;
+    *REPLAY_RECORDING.lock().unwrap() = Some(ReplayRecording {
+        recorder,
+        until: Instant::now() + duration,
+    });
+    Ok(())
+}
+
+/// Called by the capture with what it got, before the frame is compared to the previous one
+pub(crate) async fn record_replay_frame(
+    monitor_id: u32,
+    image: &Arc<DynamicImage>,
+    window_images: &[WindowImage],
+    private_windows: &[PrivateWindow],
+) {
+    let mut recording = {
+        let mut current = REPLAY_RECORDING.lock().unwrap();
+        match current.as_ref() {
+            Some(recording) if recording.recorder.manifest.monitor_id == monitor_id => {
+                current.take().unwrap()
+            }
+            _ => return,
+        }
+    };
+    if Instant::now() >= recording.until {
+        info!(
+            "Replay bundle of {} frames written to {}",
+            recording.recorder.frames(),
+            recording.recorder.dir().display()
+        );
+        return;
+    }
+    let image = Arc::clone(image);
+    let window_images = window_images.to_vec();
+    let private_windows = private_windows.to_vec();
+    let written = tokio::task::spawn_blocking(move || {
+        let result = recording
+            .recorder
+            .record(&image, &window_images, &private_windows);
+        (recording, result)
+    })
+    .await;
+    match written {
+        Ok((recording, Ok(()))) => *REPLAY_RECORDING.lock().unwrap() = Some(recording),
+        Ok((recording, Err(e))) => error!(
+            "Replay recording to {} stopped: {}",
+            recording.recorder.dir().display(),
+            e
+        ),
+        Err(e) => error!("Replay recording stopped: {}", e),
+    }
+}
+
+pub fn load_replay_manifest(dir: &Path) -> Result<ReplayManifest> {
+    let path = dir.join(REPLAY_MANIFEST);
+    let manifest: ReplayManifest = serde_json::from_slice(
+        &std::fs::read(&path).with_context(|| format!("failed to read {}", path.display()))?,
+    )
+    .with_context(|| format!("{} is not a replay manifest", path.display()))?;
+    if manifest.version > REPLAY_BUNDLE_VERSION {
+        bail!(
+            "the bundle is version {}, this replay reads up to {}",
+            manifest.version,
+            REPLAY_BUNDLE_VERSION
+        );
+    }
+    Ok(manifest)
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    /// OCR'd with this engine, only the frame diffing is replayed when not given
+    pub ocr_engine: Option<OcrEngine>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayReport {
+    pub monitor_id: u32,
+    pub recorded_at: DateTime<Utc>,
+    pub ocr_engine: Option<String>,
+    pub frames: Vec<ReplayedFrame>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayedFrame {
+    pub image: String,
+    pub offset_ms: u64,
+    /// From the last frame that wasn't skipped, 1 for the first one
+    pub difference: f64,
+    /// Below `MIN_FRAME_DIFFERENCE`, the capture doesn't OCR it
+    pub skipped: bool,
+    /// Focused first, in the order the capture OCRs them
+    pub windows: Vec<ReplayedWindow>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ReplayedWindow {
+    pub app_name: String,
+    pub window_name: String,
+    pub focused: bool,
+    pub text: Option<String>,
+    pub text_json: Option<Vec<HashMap<String, String>>>,
+}
+
+fn open_image(dir: &Path, path: &str) -> Result<DynamicImage> {
+    image::open(dir.join(path)).with_context(|| format!("failed to open {} of the bundle", path))
+}
+
+/// Feeds the frames of a bundle through the diffing and the OCR of the capture. Unlike the
+/// capture, every frame is taken as if the OCR kept up and nothing comes from the OCR cache,
+/// the same bundle gives the same report while the pipeline doesn't change
+pub async fn replay_bundle(dir: &Path, options: &ReplayOptions) -> Result<ReplayReport> {
+    let manifest = load_replay_manifest(dir)?;
+    let mut previous_frame: Option<LumaFrame> = None;
+    let mut frames = Vec::new();
+    for frame in &manifest.frames {
+        let luma = LumaFrame::new(&open_image(dir, &frame.image)?);
+        let difference = match &previous_frame {
+            Some(previous) => previous.difference(&luma)?,
+            None => 1.0,
+        };
+        let skipped = difference < MIN_FRAME_DIFFERENCE;
+        let mut windows = Vec::new();
+        if !skipped {
+            previous_frame = Some(luma);
+            let mut window_images = Vec::new();
+            for window in &frame.windows {
+                window_images.push((
+                    Arc::new(open_image(dir, &window.image)?),
+                    window.app_name.clone(),
+                    window.window_name.clone(),
+                    window.focused,
+                    window.position,
+                ));
+            }
+            for (image, app_name, window_name, focused, _) in
+                prioritize_windows(window_images, OcrPriority::AllWindows, 0)
+            {
+                let (text, text_json) = match &options.ocr_engine {
+                    Some(engine) => {
+                        let (text, json_output) = perform_ocr(engine, &image).await?;
+                        (Some(text), Some(parse_json_output(&json_output)))
+                    }
+                    None => (None, None),
+                };
+                windows.push(ReplayedWindow {
+                    app_name,
+                    window_name,
+                    focused,
+                    text,
+                    text_json,
+                });
+            }
+        }
+        frames.push(ReplayedFrame {
+            image: frame.image.clone(),
+            offset_ms: frame.offset_ms,
+            difference,
+            skipped,
+            windows,
+        });
+    }
+    Ok(ReplayReport {
+        monitor_id: manifest.monitor_id,
+        recorded_at: manifest.recorded_at,
+        ocr_engine: options
+            .ocr_engine
+            .as_ref()
+            .map(|engine| format!("{:?}", engine)),
+        frames,
+    })
+}
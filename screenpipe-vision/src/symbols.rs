@@ -0,0 +1,343 @@
+use image::DynamicImage;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SYMBOL_RECOGNITION: AtomicBool = AtomicBool::new(false);
+
+/// What the OCR reads in place of symbols, the longest first so `<->` isn't read as `<-`.
+/// Emoticons only count as a word of their own
+const SYMBOL_ALIASES: [(&str, &str, bool); 22] = [
+    ("<=>", "⇔", false),
+    ("<->", "↔", false),
+    ("-->", "→", false),
+    ("<--", "←", false),
+    ("+/-", "±", false),
+    ("=/=", "≠", false),
+    ("->", "→", false),
+    ("<-", "←", false),
+    ("=>", "⇒", false),
+    ("<=", "≤", false),
+    (">=", "≥", false),
+    ("!=", "≠", false),
+    ("~=", "≈", false),
+    ("+-", "±", false),
+    ("sqrt", "√", false),
+    (":-)", "🙂", true),
+    (":)", "🙂", true),
+    (":(", "🙁", true),
+    (":D", "😀", true),
+    (";)", "😉", true),
+    ("<3", "❤", true),
+    ("(y)", "👍", true),
+];
+
+/// Ink darker or lighter than the background by this much, out of 255
+const INK_CONTRAST: u8 = 80;
+/// Glyphs are looked for on a mask pooled down to at most this many pixels wide and tall, a
+/// window of a 4K screen is pooled 3 or 4 to 1
+const DETECTION_SIZE: usize = 640;
+/// Pixels this far from gray have a color of their own, out of 255
+const MIN_SATURATION: u8 = 90;
+/// Sides of an emoji, in pixels of the pooled mask
+const MIN_EMOJI_SIDE: usize = 6;
+/// Components with fewer pixels are noise
+const MIN_GLYPH_PIXELS: usize = 20;
+/// Long side of an arrow, in pixels
+const MIN_ARROW_LENGTH: usize = 12;
+/// An arrow is at least this many times longer than its head is wide
+const MIN_ARROW_ASPECT: f32 = 2.5;
+
+pub fn set_symbol_recognition(enabled: bool) {
+    SYMBOL_RECOGNITION.store(enabled, Ordering::SeqCst);
+}
+
+pub fn symbol_recognition_enabled() -> bool {
+    SYMBOL_RECOGNITION.load(Ordering::SeqCst)
+}
+
+/// Symbols the OCR spelled out with other characters, in the order they're read
+pub fn symbols_in_text(text: &str) -> Vec<&'static str> {
+    let mut symbols = Vec::new();
+    for word in text.split_whitespace() {
+        if let Some((_, symbol, _)) = SYMBOL_ALIASES
+            .iter()
+            .find(|(alias, _, whole_word)| *whole_word && word == *alias)
+        {
+            symbols.push(*symbol);
+            continue;
+        }
+        let mut rest = word;
+        while !rest.is_empty() {
+            match SYMBOL_ALIASES
+                .iter()
+                .find(|(alias, _, whole_word)| !whole_word && rest.starts_with(alias))
+            {
+                Some((alias, symbol, _)) => {
+                    symbols.push(*symbol);
+                    rest = &rest[alias.len()..];
+                }
+                None => {
+                    let next = rest.chars().next().map_or(1, char::len_utf8);
+                    rest = &rest[next..];
+                }
+            }
+        }
+    }
+    symbols
+}
+
+/// Pixels that differ from the background, the most frequent brightness, row after row
+pub(crate) fn ink_mask(image: &DynamicImage) -> (Vec<bool>, usize, usize) {
+    let luma = image.to_luma8();
+    let (width, height) = (luma.width() as usize, luma.height() as usize);
+    let mut histogram = [0usize; 256];
+    for pixel in luma.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    let background = (0..256).max_by_key(|value| histogram[*value]).unwrap_or(0) as u8;
+    let mask = luma
+        .pixels()
+        .map(|pixel| pixel.0[0].abs_diff(background) > INK_CONTRAST)
+        .collect();
+    (mask, width, height)
+}
+
+/// A block of the pooled mask is set when any of its pixels is, so thin strokes survive, or
+/// with `solid` when most of them are, so filled shapes keep their outline
+fn pooled(mask: Vec<bool>, width: usize, height: usize, solid: bool) -> (Vec<bool>, usize, usize) {
+    let scale = width.max(height).div_ceil(DETECTION_SIZE).max(1);
+    if scale == 1 {
+        return (mask, width, height);
+    }
+    let (pooled_width, pooled_height) = (width.div_ceil(scale), height.div_ceil(scale));
+    let mut counts = vec![0usize; pooled_width * pooled_height];
+    for (index, _) in mask.iter().enumerate().filter(|(_, ink)| **ink) {
+        let (x, y) = (index % width, index / width);
+        counts[(y / scale) * pooled_width + x / scale] += 1;
+    }
+    let min_count = if solid { scale * scale / 2 } else { 1 };
+    let pooled = counts.into_iter().map(|count| count >= min_count).collect();
+    (pooled, pooled_width, pooled_height)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GlyphColor {
+    Yellow,
+    Red,
+}
+
+/// The colors emoji stand out with from text, which is black, white or gray
+fn glyph_color(r: u8, g: u8, b: u8) -> Option<GlyphColor> {
+    if r.max(g).max(b) - r.min(g).min(b) < MIN_SATURATION {
+        return None;
+    }
+    if r > 180 && g > 150 && b < 110 {
+        Some(GlyphColor::Yellow)
+    } else if r > 150 && g < 90 && b < 110 {
+        Some(GlyphColor::Red)
+    } else {
+        None
+    }
+}
+
+/// Bounding box and pixels of each 8-connected group of ink
+fn components(mask: &[bool], width: usize, height: usize) -> Vec<Vec<(usize, usize)>> {
+    let mut seen = vec![false; mask.len()];
+    let mut found = Vec::new();
+    for start in 0..mask.len() {
+        if !mask[start] || seen[start] {
+            continue;
+        }
+        seen[start] = true;
+        let mut stack = vec![start];
+        let mut pixels = Vec::new();
+        while let Some(index) = stack.pop() {
+            let (x, y) = (index % width, index / width);
+            pixels.push((x, y));
+            for dy in -1i64..=1 {
+                for dx in -1i64..=1 {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if nx < 0 || ny < 0 || nx >= width as i64 || ny >= height as i64 {
+                        continue;
+                    }
+                    let neighbour = ny as usize * width + nx as usize;
+                    if mask[neighbour] && !seen[neighbour] {
+                        seen[neighbour] = true;
+                        stack.push(neighbour);
+                    }
+                }
+            }
+        }
+        if pixels.len() >= MIN_GLYPH_PIXELS {
+            found.push(pixels);
+        }
+    }
+    found
+}
+
+/// Towards the end or the start for a component along x, from how tall its ink is at each x:
+/// a thin shaft with a head tapering to its tip at one end
+fn arrow_direction(extents: &[usize]) -> Option<bool> {
+    let length = extents.len();
+    let head = *extents.iter().max()?;
+    if length < MIN_ARROW_LENGTH || (length as f32) < head as f32 * MIN_ARROW_ASPECT {
+        return None;
+    }
+    let mut middle: Vec<usize> = extents[length / 3..length * 2 / 3].to_vec();
+    middle.sort_unstable();
+    let shaft = middle[middle.len() / 2].max(1);
+    if head < shaft * 3 {
+        return None;
+    }
+    let head_at = extents.iter().position(|extent| *extent == head)?;
+    let towards_end = head_at >= length / 2;
+    // the head is within the last quarter, the ink narrows towards the tip of it
+    let (head_zone, tip) = if towards_end {
+        (head_at >= length * 3 / 4, extents[length - 1])
+    } else {
+        (head_at < length / 4, extents[0])
+    };
+    let other_end = if towards_end {
+        &extents[..length / 4]
+    } else {
+        &extents[length * 3 / 4..]
+    };
+    let other_end_wide = other_end.iter().any(|extent| *extent * 2 > head);
+    (head_zone && tip * 2 <= head && !other_end_wide).then_some(towards_end)
+}
+
+fn bounding_box(pixels: &[(usize, usize)]) -> (usize, usize, usize, usize) {
+    pixels.iter().fold(
+        (usize::MAX, usize::MAX, 0, 0),
+        |(min_x, min_y, max_x, max_y), (x, y)| {
+            (min_x.min(*x), min_y.min(*y), max_x.max(*x), max_y.max(*y))
+        },
+    )
+}
+
+/// Arrows drawn or typed in the image, which the OCR leaves out or reads as dashes. Typed
+/// arrows of small text are lost in the pooling of large windows
+pub fn arrows_in_image(image: &DynamicImage) -> Vec<&'static str> {
+    let (mask, width, height) = ink_mask(image);
+    let (mask, width, height) = pooled(mask, width, height, false);
+    let mut arrows = Vec::new();
+    for pixels in components(&mask, width, height) {
+        let (min_x, min_y, max_x, max_y) = bounding_box(&pixels);
+        let (box_width, box_height) = (max_x - min_x + 1, max_y - min_y + 1);
+        // the frame of a window or a table, not a glyph
+        if box_width * 2 > width && box_height * 2 > height {
+            continue;
+        }
+        // from the first to the last pixel of ink, an open head is as wide as a filled one
+        let mut columns = vec![(usize::MAX, 0); box_width];
+        let mut rows = vec![(usize::MAX, 0); box_height];
+        for (x, y) in &pixels {
+            let column = &mut columns[x - min_x];
+            *column = (column.0.min(*y), column.1.max(*y));
+            let row = &mut rows[y - min_y];
+            *row = (row.0.min(*x), row.1.max(*x));
+        }
+        let extents = |spans: Vec<(usize, usize)>| -> Vec<usize> {
+            spans
+                .into_iter()
+                .map(|(first, last)| if first > last { 0 } else { last - first + 1 })
+                .collect()
+        };
+        let arrow = if box_width >= box_height {
+            arrow_direction(&extents(columns)).map(|right| if right { "→" } else { "←" })
+        } else {
+            arrow_direction(&extents(rows)).map(|down| if down { "↓" } else { "↑" })
+        };
+        arrows.extend(arrow);
+    }
+    arrows
+}
+
+/// The emoji of a colored glyph: a yellow disc is a face, a red shape with two lobes at the
+/// top and a point at the bottom is a heart
+fn emoji_of(pixels: &[(usize, usize)], color: GlyphColor) -> Option<&'static str> {
+    let (min_x, min_y, max_x, max_y) = bounding_box(pixels);
+    let (box_width, box_height) = (max_x - min_x + 1, max_y - min_y + 1);
+    if box_width.min(box_height) < MIN_EMOJI_SIDE
+        || box_width * 4 < box_height * 3
+        || box_height * 4 < box_width * 3
+    {
+        return None;
+    }
+    let fill = pixels.len() as f32 / (box_width * box_height) as f32;
+    match color {
+        GlyphColor::Yellow => (0.55..=0.9).contains(&fill).then_some("🙂"),
+        GlyphColor::Red => {
+            // the dip between the lobes, the top of the middle column is below the top
+            let center = min_x + box_width / 2;
+            let center_top = pixels
+                .iter()
+                .filter(|(x, _)| *x == center)
+                .map(|(_, y)| *y)
+                .min()?;
+            let notched = (center_top - min_y) * 10 >= box_height;
+            let tip = pixels
+                .iter()
+                .filter(|(_, y)| *y == max_y)
+                .fold((usize::MAX, 0), |(first, last), (x, _)| {
+                    (first.min(*x), last.max(*x))
+                });
+            let pointed = (tip.1 - tip.0 + 1) * 3 <= box_width;
+            ((0.5..=0.85).contains(&fill) && notched && pointed).then_some("❤")
+        }
+    }
+}
+
+/// Emoji in the image, which the OCR leaves out or reads as stray letters
+pub fn emoji_in_image(image: &DynamicImage) -> Vec<&'static str> {
+    let rgb = image.to_rgb8();
+    let (width, height) = (rgb.width() as usize, rgb.height() as usize);
+    let mut emoji = Vec::new();
+    for color in [GlyphColor::Yellow, GlyphColor::Red] {
+        let mask = rgb
+            .pixels()
+            .map(|pixel| glyph_color(pixel.0[0], pixel.0[1], pixel.0[2]) == Some(color))
+            .collect();
+        let (mask, pooled_width, pooled_height) = pooled(mask, width, height, true);
+        for pixels in components(&mask, pooled_width, pooled_height) {
+            emoji.extend(emoji_of(&pixels, color));
+        }
+    }
+    emoji
+}
+
+/// Symbols of the image and its text that the text doesn't already have, each once
+pub fn recognize_symbols(image: &DynamicImage, text: &str) -> Vec<&'static str> {
+    let mut symbols: Vec<&'static str> = Vec::new();
+    for symbol in symbols_in_text(text)
+        .into_iter()
+        .chain(arrows_in_image(image))
+        .chain(emoji_in_image(image))
+    {
+        if !text.contains(symbol) && !symbols.contains(&symbol) {
+            symbols.push(symbol);
+        }
+    }
+    symbols
+}
+
+/// Adds the symbols as a last line of the text, so what the OCR read is kept, and as a line of
+/// the json output
+pub fn append_symbols(
+    text: &mut String,
+    text_json: &mut Vec<HashMap<String, String>>,
+    symbols: &[&str],
+) {
+    if symbols.is_empty() {
+        return;
+    }
+    let line = symbols.join(" ");
+    if !text.is_empty() {
+        text.push('\n');
+    }
+    text.push_str(&line);
+    text_json.push(HashMap::from([
+        ("text".to_string(), line),
+        ("line_position".to_string(), "symbols".to_string()),
+    ]));
+}
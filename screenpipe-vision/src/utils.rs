@@ -1,10 +1,13 @@
 use crate::capture_screenshot_by_window::capture_all_visible_windows;
-use crate::core::MaxAverageFrame;
-use image::DynamicImage;
+use crate::core::{MaxAverageFrame, WindowImage};
+use crate::errors::CaptureError;
+use crate::privacy::{black_out, has_app_allowlist, keep_only, PrivateWindow};
+use image::{DynamicImage, GrayImage};
 use image_compare::{Algorithm, Metric, Similarity};
 use log::{debug, error};
 use rusty_tesseract::{Args, DataOutput, Image};
 use serde_json;
+use std::borrow::Cow;
 use std::collections::HashMap;
 use std::fs::{self, File};
 use std::hash::{DefaultHasher, Hash, Hasher};
@@ -20,6 +23,10 @@ pub enum OcrEngine {
     Tesseract,
     WindowsNative,
     AppleNative,
+    /// A worker on another machine, see `--remote-worker`
+    Remote,
+    /// TrOCR trained on handwriting, for the notes written with a stylus
+    Handwriting,
 }
 
 impl Default for OcrEngine {
@@ -52,6 +59,72 @@ pub fn compare_images_ssim(image1: &DynamicImage, image2: &DynamicImage) -> f64
     result.score
 }
 
+/// Frames are compared on a grayscale thumbnail this many times smaller on each side
+pub const FRAME_DIFF_DOWNSCALE: u32 = 4;
+
+/// Luma thumbnail of a frame, computed once per capture and kept for comparing the next one
+#[derive(Debug, Clone)]
+pub struct LumaFrame(GrayImage);
+
+impl LumaFrame {
+    /// Averages each block of pixels in a single pass over the RGBA bytes, without converting
+    /// the full frame to grayscale first. Small images are kept at full size
+    pub fn new(image: &DynamicImage) -> Self {
+        let rgba = match image {
+            DynamicImage::ImageRgba8(rgba) => Cow::Borrowed(rgba),
+            other => Cow::Owned(other.to_rgba8()),
+        };
+        let (width, height) = rgba.dimensions();
+        let scale = if width.min(height) >= FRAME_DIFF_DOWNSCALE * 16 {
+            FRAME_DIFF_DOWNSCALE
+        } else {
+            1
+        };
+        let (thumb_width, thumb_height) = (width / scale, height / scale);
+        let row_bytes = width as usize * 4;
+        let block_bytes = scale as usize * 4;
+        let mut pixels = Vec::with_capacity(thumb_width as usize * thumb_height as usize);
+        let mut sums = vec![0u32; thumb_width as usize];
+        for rows in rgba
+            .as_raw()
+            .chunks_exact(row_bytes * scale as usize)
+            .take(thumb_height as usize)
+        {
+            sums.fill(0);
+            for row in rows.chunks_exact(row_bytes) {
+                // fixed size chunks so the compiler can vectorize the inner loops
+                for (sum, block) in sums.iter_mut().zip(row.chunks_exact(block_bytes)) {
+                    *sum += block
+                        .chunks_exact(4)
+                        .map(|px| 77 * px[0] as u32 + 150 * px[1] as u32 + 29 * px[2] as u32)
+                        .sum::<u32>();
+                }
+            }
+            let divisor = scale * scale * 256;
+            pixels.extend(sums.iter().map(|sum| (sum / divisor) as u8));
+        }
+        LumaFrame(
+            GrayImage::from_raw(thumb_width, thumb_height, pixels)
+                .expect("thumbnail has one byte per pixel"),
+        )
+    }
+
+    pub fn dimensions(&self) -> (u32, u32) {
+        self.0.dimensions()
+    }
+
+    /// Mean of the histogram distance and the SSIM dissimilarity, 0 for identical frames
+    pub fn difference(&self, other: &LumaFrame) -> anyhow::Result<f64> {
+        let histogram_diff =
+            image_compare::gray_similarity_histogram(Metric::Hellinger, &self.0, &other.0)
+                .map_err(|e| anyhow::anyhow!("Failed to compare images: {}", e))?;
+        let ssim =
+            image_compare::gray_similarity_structure(&Algorithm::MSSIMSimple, &self.0, &other.0)
+                .map_err(|e| anyhow::anyhow!("Failed to compare images: {}", e))?;
+        Ok((histogram_diff + 1.0 - ssim.score) / 2.0)
+    }
+}
+
 pub fn perform_ocr_tesseract(image: &DynamicImage) -> (String, String) {
     let args = Args {
         lang: "eng".to_string(),
@@ -74,6 +147,47 @@ pub fn perform_ocr_tesseract(image: &DynamicImage) -> (String, String) {
     (text, json_output)
 }
 
+/// Word found by tesseract with its box in image pixels
+#[derive(Debug, Clone, PartialEq)]
+pub struct OcrWord {
+    pub text: String,
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub confidence: f32,
+    /// Block, paragraph and line number, the same for words on the same line
+    pub line: (i32, i32, i32),
+}
+
+pub fn perform_ocr_tesseract_words(image: &DynamicImage) -> anyhow::Result<Vec<OcrWord>> {
+    let args = Args {
+        lang: "eng".to_string(),
+        config_variables: HashMap::from([("tessedit_create_tsv".into(), "1".into())]),
+        dpi: Some(600),
+        psm: Some(1),
+        oem: Some(1),
+    };
+    let ocr_image = Image::from_dynamic_image(image).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let data_output =
+        rusty_tesseract::image_to_data(&ocr_image, &args).map_err(|e| anyhow::anyhow!("{}", e))?;
+
+    Ok(data_output
+        .data
+        .into_iter()
+        .filter(|record| record.word_num > 0 && !record.text.trim().is_empty())
+        .map(|record| OcrWord {
+            text: record.text,
+            left: record.left,
+            top: record.top,
+            width: record.width,
+            height: record.height,
+            confidence: record.conf,
+            line: (record.block_num, record.par_num, record.line_num),
+        })
+        .collect())
+}
+
 fn data_output_to_text(data_output: &DataOutput) -> String {
     let mut text = String::new();
     for record in &data_output.data {
@@ -93,6 +207,8 @@ fn data_output_to_json(data_output: &DataOutput) -> String {
     let mut current_conf = 0.0;
     let mut word_count = 0;
     let mut last_word_num = 0;
+    // left, top, right and bottom of the words of the line, in image pixels
+    let mut current_box: Option<(i32, i32, i32, i32)> = None;
 
     for record in &data_output.data {
         if record.word_num == 0 {
@@ -112,6 +228,7 @@ fn data_output_to_json(data_output: &DataOutput) -> String {
                         record.line_num
                     ),
                 );
+                insert_line_box(&mut line_data, current_box.take());
                 lines.push(line_data);
                 current_line.clear();
                 current_conf = 0.0;
@@ -125,6 +242,12 @@ fn data_output_to_json(data_output: &DataOutput) -> String {
             current_line.push_str(&record.text);
             current_conf += record.conf;
             word_count += 1;
+            let (left, top) = (record.left, record.top);
+            let (right, bottom) = (left + record.width, top + record.height);
+            current_box = Some(match current_box {
+                Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+                None => (left, top, right, bottom),
+            });
         }
         last_word_num = record.word_num;
     }
@@ -133,53 +256,84 @@ fn data_output_to_json(data_output: &DataOutput) -> String {
         let mut line_data = HashMap::new();
         line_data.insert("text".to_string(), current_line);
         line_data.insert("confidence".to_string(), format!("{:.2}", avg_conf));
+        insert_line_box(&mut line_data, current_box);
         lines.push(line_data);
     }
 
     serde_json::to_string_pretty(&lines).unwrap()
 }
 
+/// Where the line is in the image, in pixels, under the keys the other engines use
+fn insert_line_box(line_data: &mut HashMap<String, String>, line_box: Option<(i32, i32, i32, i32)>) {
+    let Some((left, top, right, bottom)) = line_box else {
+        return;
+    };
+    line_data.insert("left".to_string(), left.to_string());
+    line_data.insert("top".to_string(), top.to_string());
+    line_data.insert("width".to_string(), (right - left).to_string());
+    line_data.insert("height".to_string(), (bottom - top).to_string());
+}
+
 pub async fn capture_screenshot(
     monitor: Arc<Monitor>,
 ) -> Result<
     (
         DynamicImage,
-        Vec<(DynamicImage, String, String, bool)>,
+        Vec<WindowImage>,
+        Vec<PrivateWindow>,
         u64,
         Duration,
     ),
-    anyhow::Error,
+    CaptureError,
 > {
     let capture_start = Instant::now();
-    let buffer = monitor.capture_image().unwrap();
-    let image = DynamicImage::ImageRgba8(buffer);
-    let image_hash = calculate_hash(&image);
-    let capture_duration = capture_start.elapsed();
+    let buffer = monitor
+        .capture_image()
+        .map_err(|source| CaptureError::Screenshot {
+            monitor_id: monitor.id(),
+            source,
+        })?;
+    let mut image = DynamicImage::ImageRgba8(buffer);
 
     // Capture all visible windows
-    let window_images = capture_all_visible_windows(monitor.clone())
-        .await
-        .unwrap_or_default();
+    let (window_images, private_windows, blocked_windows) =
+        capture_all_visible_windows(monitor.clone())
+            .await
+            .unwrap_or_default();
+    if has_app_allowlist() {
+        // the desktop and the windows we can't list are left out too
+        let allowed: Vec<_> = window_images
+            .iter()
+            .map(|(window, _, _, _, position)| (*position, window.width(), window.height()))
+            .collect();
+        keep_only(&mut image, &allowed);
+        // on top of an allowed window
+        black_out(&mut image, &blocked_windows);
+    }
+    if !private_windows.is_empty() {
+        debug!("Excluding {} private windows from capture", private_windows.len());
+        black_out(&mut image, &private_windows);
+    }
+    let image_hash = calculate_hash(&image);
+    let capture_duration = capture_start.elapsed();
 
-    Ok((image, window_images, image_hash, capture_duration))
+    Ok((image, window_images, private_windows, image_hash, capture_duration))
 }
 
 pub async fn compare_with_previous_image(
-    previous_image: &Option<Arc<DynamicImage>>,
-    current_image: &DynamicImage,
+    previous_frame: &Option<LumaFrame>,
+    current_frame: &LumaFrame,
     max_average: &mut Option<MaxAverageFrame>,
     frame_number: u64,
     max_avg_value: &mut f64,
 ) -> anyhow::Result<f64> {
     let mut current_average = 0.0;
-    if let Some(prev_image) = previous_image {
-        let histogram_diff = compare_images_histogram(prev_image, current_image)?;
-        let ssim_diff = 1.0 - compare_images_ssim(prev_image, current_image);
-        current_average = (histogram_diff + ssim_diff) / 2.0;
+    if let Some(prev_frame) = previous_frame {
+        current_average = prev_frame.difference(current_frame)?;
         let max_avg_frame_number = max_average.as_ref().map_or(0, |frame| frame.frame_number);
         debug!(
-            "Frame {}: Histogram diff: {:.3}, SSIM diff: {:.3}, Current Average: {:.3}, Max_avr: {:.3} Fr: {}",
-            frame_number, histogram_diff, ssim_diff, current_average, *max_avg_value, max_avg_frame_number
+            "Frame {}: Current Average: {:.3}, Max_avr: {:.3} Fr: {}",
+            frame_number, current_average, *max_avg_value, max_avg_frame_number
         );
     } else {
         debug!("No previous image to compare for frame {}", frame_number);
@@ -0,0 +1,128 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use screenpipe_vision::{
+    set_app_capture_profiles, window_ocr_engine, AppCaptureProfile, AppSampler, OcrEngine,
+    WindowImage,
+};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+fn window(app: &str, focused: bool, width: u32) -> WindowImage {
+    let image = RgbaImage::from_fn(width, 100, |x, _| Rgba([(x % 2 * 255) as u8, 0, 0, 255]));
+    (
+        Arc::new(DynamicImage::ImageRgba8(image)),
+        app.to_string(),
+        format!("{} window", app),
+        focused,
+        (0, 0),
+    )
+}
+
+fn apps(windows: &[WindowImage]) -> Vec<&str> {
+    windows.iter().map(|window| window.1.as_str()).collect()
+}
+
+// one test, the profiles are global
+#[test]
+fn test_app_profiles_sample_and_crop_windows() {
+    set_app_capture_profiles(vec![
+        AppCaptureProfile {
+            app: "code".to_string(),
+            fps: Some(1.0),
+            max_width: Some(400),
+            thumbnail_only: false,
+            handwriting: false,
+            text_only: false,
+        },
+        AppCaptureProfile {
+            app: "VLC".to_string(),
+            fps: Some(1.0 / 60.0),
+            max_width: None,
+            thumbnail_only: true,
+            handwriting: false,
+            text_only: false,
+        },
+        AppCaptureProfile {
+            app: "Notability".to_string(),
+            fps: None,
+            max_width: None,
+            thumbnail_only: false,
+            handwriting: true,
+            text_only: false,
+        },
+    ]);
+    let mut sampler = AppSampler::new();
+    let start = Instant::now();
+    let mut frame = DynamicImage::ImageRgba8(RgbaImage::from_fn(1000, 200, |x, _| {
+        Rgba([(x % 2 * 255) as u8, 0, 0, 255])
+    }));
+    let windows = || {
+        vec![
+            window("Code", true, 800),
+            window("VLC", false, 1000),
+            window("Terminal", false, 300),
+        ]
+    };
+
+    let sampled = sampler.sample(&mut frame, windows(), start).unwrap();
+    // not OCR'd, lowered in the frame
+    assert_eq!(apps(&sampled), vec!["Code", "Terminal"]);
+    assert_eq!(sampled[0].0.dimensions(), (400, 50));
+    assert_eq!(sampled[1].0.dimensions(), (300, 100));
+    assert_eq!(frame.dimensions(), (1000, 200));
+    // the stripes of the window are averaged away, below it they aren't
+    assert_eq!(frame.get_pixel(0, 50), frame.get_pixel(1, 50));
+    assert_ne!(frame.get_pixel(0, 150), frame.get_pixel(1, 150));
+
+    // Code is focused and not due, the frame is skipped whole
+    assert!(sampler
+        .sample(&mut frame, windows(), start + Duration::from_millis(500))
+        .is_none());
+
+    let mut unfocused = windows();
+    unfocused[0].3 = false;
+    unfocused[2].3 = true;
+    let sampled = sampler
+        .sample(&mut frame, unfocused, start + Duration::from_millis(600))
+        .unwrap();
+    assert_eq!(apps(&sampled), vec!["Terminal"]);
+
+    let sampled = sampler
+        .sample(&mut frame, windows(), start + Duration::from_secs(2))
+        .unwrap();
+    assert_eq!(apps(&sampled), vec!["Code", "Terminal"]);
+
+    let engine = Arc::new(OcrEngine::Tesseract);
+    assert!(matches!(
+        *window_ocr_engine("notability", &engine),
+        OcrEngine::Handwriting
+    ));
+    assert!(matches!(
+        *window_ocr_engine("Code", &engine),
+        OcrEngine::Tesseract
+    ));
+
+    set_app_capture_profiles(vec![AppCaptureProfile {
+        app: "Signal".to_string(),
+        fps: None,
+        max_width: None,
+        thumbnail_only: false,
+        handwriting: false,
+        text_only: true,
+    }]);
+    let mut signal = window("Signal", true, 300);
+    signal.4 = (500, 100);
+    let sampled = sampler
+        .sample(&mut frame, vec![signal], start + Duration::from_secs(2))
+        .unwrap();
+    // still read, but gone from the frame
+    assert_eq!(apps(&sampled), vec!["Signal"]);
+    assert_eq!(sampled[0].0.dimensions(), (300, 100));
+    assert_eq!(frame.get_pixel(600, 150), Rgba([0, 0, 0, 255]));
+    assert_ne!(frame.get_pixel(0, 150), frame.get_pixel(1, 150));
+
+    set_app_capture_profiles(Vec::new());
+    let sampled = sampler
+        .sample(&mut frame, windows(), start + Duration::from_secs(3))
+        .unwrap();
+    assert_eq!(sampled.len(), 3);
+}
@@ -0,0 +1,70 @@
+use image::DynamicImage;
+use screenpipe_vision::{perform_ocr, process_ocr_task, CaptureError, OcrEngine, OcrError};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+#[cfg(not(target_os = "windows"))]
+#[tokio::test]
+async fn test_engines_of_other_platforms_are_unsupported() {
+    let image = Arc::new(DynamicImage::new_rgb8(8, 8));
+    let error = perform_ocr(&OcrEngine::WindowsNative, &image)
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        error,
+        OcrError::UnsupportedEngine(OcrEngine::WindowsNative)
+    ));
+    assert_eq!(error.code(), "ocr_engine_unsupported");
+}
+
+#[cfg(not(feature = "cloud"))]
+#[tokio::test]
+async fn test_cloud_engines_are_unsupported_without_the_feature() {
+    let image = Arc::new(DynamicImage::new_rgb8(8, 8));
+    for engine in [OcrEngine::Unstructured, OcrEngine::Remote] {
+        let error = perform_ocr(&engine, &image).await.unwrap_err();
+        assert_eq!(error.code(), "ocr_engine_unsupported");
+    }
+}
+
+#[tokio::test]
+async fn test_a_dropped_receiver_is_told_apart() {
+    let (result_tx, result_rx) = mpsc::channel(1);
+    drop(result_rx);
+
+    let error = process_ocr_task(
+        Arc::new(DynamicImage::new_rgb8(8, 8)),
+        Vec::new(),
+        Vec::new(),
+        1,
+        Instant::now(),
+        result_tx,
+        false,
+        Arc::new(OcrEngine::Tesseract),
+        CancellationToken::new(),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(error, CaptureError::ReceiverDropped));
+    assert_eq!(error.code(), "capture_receiver_dropped");
+}
+
+#[test]
+fn test_capture_errors_keep_the_ocr_cause() {
+    let error = CaptureError::Ocr {
+        frame_number: 7,
+        source: OcrError::Engine {
+            engine: OcrEngine::Remote,
+            message: "no worker".to_string(),
+        },
+    };
+    assert_eq!(error.code(), "ocr_engine_failed");
+    assert_eq!(
+        error.to_string(),
+        "failed to read the text of frame 7: Remote OCR failed: no worker"
+    );
+    let source = std::error::Error::source(&error).unwrap();
+    assert_eq!(source.to_string(), "Remote OCR failed: no worker");
+}
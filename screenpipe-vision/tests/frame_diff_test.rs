@@ -0,0 +1,45 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_vision::LumaFrame;
+
+fn desktop(width: u32, height: u32, window_x: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| {
+        if (window_x..window_x + width / 3).contains(&x) && y > height / 4 {
+            Rgba([250, 250, 250, 255])
+        } else {
+            Rgba([(x % 256) as u8, (y % 256) as u8, 90, 255])
+        }
+    }))
+}
+
+#[test]
+fn test_frames_are_compared_downscaled() {
+    let frame = LumaFrame::new(&desktop(1920, 1080, 100));
+    assert_eq!(frame.dimensions(), (480, 270));
+    // too small to lose detail to downscaling
+    assert_eq!(LumaFrame::new(&desktop(40, 30, 0)).dimensions(), (40, 30));
+}
+
+#[test]
+fn test_difference_of_frames() {
+    let previous = LumaFrame::new(&desktop(1920, 1080, 100));
+    let same = LumaFrame::new(&desktop(1920, 1080, 100));
+    let moved = LumaFrame::new(&desktop(1920, 1080, 400));
+
+    assert!(previous.difference(&same).unwrap() < 1e-9);
+    // above what continuous_capture skips
+    assert!(previous.difference(&moved).unwrap() > 0.006);
+    // another monitor resolution
+    assert!(previous
+        .difference(&LumaFrame::new(&desktop(1280, 720, 100)))
+        .is_err());
+}
+
+#[test]
+fn test_other_pixel_formats_match_rgba() {
+    let rgba = desktop(256, 128, 10);
+    let rgb = DynamicImage::ImageRgb8(rgba.to_rgb8());
+    let difference = LumaFrame::new(&rgba)
+        .difference(&LumaFrame::new(&rgb))
+        .unwrap();
+    assert!(difference < 1e-9);
+}
@@ -0,0 +1,34 @@
+use image::{DynamicImage, RgbaImage};
+use screenpipe_vision::{CaptureResult, EncodedFrame};
+use std::sync::Arc;
+use std::time::Instant;
+
+#[test]
+fn test_frame_is_encoded_once() {
+    let image = DynamicImage::ImageRgba8(RgbaImage::new(64, 48));
+    let encoded = EncodedFrame::default();
+
+    let png = encoded.png(&image).unwrap();
+    // clones share the encoding, as the queues of a frame do
+    assert!(Arc::ptr_eq(&png, &encoded.clone().png(&image).unwrap()));
+    let decoded = image::load_from_memory(&png).unwrap();
+    assert_eq!((decoded.width(), decoded.height()), (64, 48));
+}
+
+#[test]
+fn test_replaced_image_is_encoded_again() {
+    let mut frame = CaptureResult {
+        image: Arc::new(DynamicImage::ImageRgba8(RgbaImage::new(64, 48))),
+        png: EncodedFrame::default(),
+        frame_number: 1,
+        timestamp: Instant::now(),
+        window_ocr_results: Vec::new(),
+        private_windows: Vec::new(),
+    };
+    let before = frame.png.png(&frame.image).unwrap();
+
+    frame.replace_image(DynamicImage::ImageRgba8(RgbaImage::new(32, 24)));
+    let after = frame.png.png(&frame.image).unwrap();
+    assert!(!Arc::ptr_eq(&before, &after));
+    assert_eq!(image::load_from_memory(&after).unwrap().width(), 32);
+}
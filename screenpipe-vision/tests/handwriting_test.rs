@@ -0,0 +1,41 @@
+#![cfg(feature = "handwriting")]
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_vision::{handwriting_lines, HandwritingLine};
+
+#[test]
+fn test_handwriting_lines_are_found_top_to_bottom() {
+    let page = RgbaImage::from_fn(400, 200, |x, y| {
+        // a line of writing, a rule of the page, and a second line with a gap inside a letter
+        let first_line = (30..60).contains(&y) && (50..300).contains(&x) && x % 7 < 4;
+        let rule = y == 90 && (10..390).contains(&x);
+        let second_line =
+            ((120..135).contains(&y) || (138..150).contains(&y)) && (100..200).contains(&x);
+        if first_line || rule || second_line {
+            Rgba([20, 20, 60, 255])
+        } else {
+            Rgba([250, 250, 245, 255])
+        }
+    });
+    let lines = handwriting_lines(&DynamicImage::ImageRgba8(page));
+    assert_eq!(
+        lines,
+        vec![
+            HandwritingLine {
+                left: 46,
+                top: 26,
+                width: 256,
+                height: 38,
+            },
+            HandwritingLine {
+                left: 96,
+                top: 116,
+                width: 108,
+                height: 38,
+            },
+        ]
+    );
+
+    let blank = RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255]));
+    assert!(handwriting_lines(&DynamicImage::ImageRgba8(blank)).is_empty());
+}
@@ -0,0 +1,37 @@
+use image::DynamicImage;
+use screenpipe_vision::{process_ocr_task, OcrEngine};
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+#[tokio::test]
+async fn test_cancelled_ocr_sends_nothing() {
+    let image = Arc::new(DynamicImage::new_rgb8(64, 64));
+    let (result_tx, mut result_rx) = mpsc::channel(1);
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+
+    let result = process_ocr_task(
+        Arc::clone(&image),
+        vec![(
+            image,
+            "Code".to_string(),
+            "main.rs".to_string(),
+            true,
+            (0, 0),
+        )],
+        Vec::new(),
+        1,
+        Instant::now(),
+        result_tx,
+        false,
+        Arc::new(OcrEngine::Tesseract),
+        cancel,
+    )
+    .await;
+
+    assert!(result.is_ok());
+    // the sender is gone with the task, without a frame
+    assert!(result_rx.recv().await.is_none());
+}
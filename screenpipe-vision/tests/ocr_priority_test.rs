@@ -0,0 +1,53 @@
+use image::DynamicImage;
+use screenpipe_vision::{prioritize_windows, OcrPriority, WindowImage};
+use std::sync::Arc;
+
+fn windows() -> Vec<WindowImage> {
+    ["Slack", "Code", "Finder"]
+        .iter()
+        .map(|app| {
+            (
+                Arc::new(DynamicImage::new_rgb8(4, 4)),
+                app.to_string(),
+                format!("{} - main", app),
+                *app == "Code",
+                (0, 0),
+            )
+        })
+        .collect()
+}
+
+fn apps(windows: &[WindowImage]) -> Vec<&str> {
+    windows.iter().map(|window| window.1.as_str()).collect()
+}
+
+#[test]
+fn test_focused_window_is_ocr_d_first() {
+    let kept = prioritize_windows(windows(), OcrPriority::FocusedOnly, 0);
+    // the OCR keeps up, nothing is dropped
+    assert_eq!(apps(&kept), ["Code", "Slack", "Finder"]);
+}
+
+#[test]
+fn test_background_windows_are_dropped_while_behind() {
+    let kept = prioritize_windows(windows(), OcrPriority::FocusedOnly, 3);
+    assert_eq!(apps(&kept), ["Code"]);
+
+    let kept = prioritize_windows(windows(), OcrPriority::AllWindows, 3);
+    assert_eq!(kept.len(), 3);
+
+    let sampled: Vec<usize> = (1..=6)
+        .map(|behind| prioritize_windows(windows(), OcrPriority::SampleBackground(3), behind).len())
+        .collect();
+    assert_eq!(sampled, [1, 1, 3, 1, 1, 3]);
+}
+
+#[test]
+fn test_a_window_is_kept_without_focus() {
+    let unfocused: Vec<WindowImage> = windows()
+        .into_iter()
+        .map(|(image, app, window, _, position)| (image, app, window, false, position))
+        .collect();
+    let kept = prioritize_windows(unfocused, OcrPriority::FocusedOnly, 1);
+    assert_eq!(apps(&kept), ["Slack"]);
+}
@@ -0,0 +1,71 @@
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use screenpipe_vision::privacy::{black_out, is_app_allowed, is_private_window, keep_only};
+use screenpipe_vision::{report_private_windows, set_allowed_apps, PrivateWindow};
+
+#[test]
+fn test_private_title_markers() {
+    assert!(is_private_window("New Tab - Google Chrome (Incognito)"));
+    assert!(is_private_window("Bing - InPrivate - Microsoft Edge"));
+    assert!(is_private_window("Mozilla Firefox Private Browsing"));
+    assert!(is_private_window("Search - Brave (Private)"));
+    assert!(!is_private_window("GitHub - Google Chrome"));
+}
+
+#[test]
+fn test_reported_private_windows() {
+    assert!(!is_private_window("Bank statement - Arc"));
+
+    report_private_windows(vec!["Bank statement".to_string(), "  ".to_string()]);
+    assert!(is_private_window("Bank statement - Arc"));
+    assert!(!is_private_window("Docs - Arc"));
+
+    // a new report replaces the previous one
+    report_private_windows(Vec::new());
+    assert!(!is_private_window("Bank statement - Arc"));
+}
+
+#[test]
+fn test_black_out() {
+    let mut image =
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255])));
+
+    black_out(
+        &mut image,
+        &[PrivateWindow {
+            app_name: "Google Chrome".to_string(),
+            position: (-10, 50),
+            width: 40,
+            height: 80,
+        }],
+    );
+
+    assert_eq!(image.get_pixel(0, 50), Rgba([0, 0, 0, 255]));
+    assert_eq!(image.get_pixel(29, 99), Rgba([0, 0, 0, 255]));
+    assert_eq!(image.get_pixel(30, 99), Rgba([255, 255, 255, 255]));
+    assert_eq!(image.get_pixel(0, 49), Rgba([255, 255, 255, 255]));
+}
+
+#[test]
+fn test_app_allowlist() {
+    assert!(is_app_allowed("Slack"));
+
+    set_allowed_apps(Some(vec!["Slack".to_string(), "Code".to_string()]));
+    assert!(is_app_allowed("slack"));
+    assert!(!is_app_allowed("Messages"));
+
+    set_allowed_apps(None);
+    assert!(is_app_allowed("Messages"));
+}
+
+#[test]
+fn test_keep_only() {
+    let mut image =
+        DynamicImage::ImageRgba8(RgbaImage::from_pixel(100, 100, Rgba([255, 255, 255, 255])));
+
+    keep_only(&mut image, &[((60, -10), 60, 30)]);
+
+    assert_eq!(image.get_pixel(60, 0), Rgba([255, 255, 255, 255]));
+    assert_eq!(image.get_pixel(99, 19), Rgba([255, 255, 255, 255]));
+    assert_eq!(image.get_pixel(99, 20), Rgba([0, 0, 0, 255]));
+    assert_eq!(image.get_pixel(59, 0), Rgba([0, 0, 0, 255]));
+}
@@ -0,0 +1,90 @@
+use image::{DynamicImage, Rgba, RgbaImage};
+use screenpipe_vision::replay::load_replay_manifest;
+use screenpipe_vision::{replay_bundle, PrivateWindow, ReplayOptions, ReplayRecorder};
+use std::sync::Arc;
+
+fn desktop(window_x: u32) -> DynamicImage {
+    DynamicImage::ImageRgba8(RgbaImage::from_fn(320, 200, |x, y| {
+        if (window_x..window_x + 100).contains(&x) && y > 50 {
+            Rgba([250, 250, 250, 255])
+        } else {
+            Rgba([(x % 256) as u8, (y % 256) as u8, 90, 255])
+        }
+    }))
+}
+
+fn window() -> Arc<DynamicImage> {
+    Arc::new(DynamicImage::ImageRgba8(RgbaImage::from_pixel(
+        40,
+        30,
+        Rgba([250, 250, 250, 255]),
+    )))
+}
+
+#[tokio::test]
+async fn test_recorded_frames_replay_through_the_diffing() {
+    let dir = tempfile::tempdir().unwrap();
+    let bundle = dir.path().join("bundle");
+    let mut recorder = ReplayRecorder::create(&bundle, 2).unwrap();
+    let windows = vec![
+        (
+            window(),
+            "Terminal".to_string(),
+            "zsh".to_string(),
+            false,
+            (0, 0),
+        ),
+        (
+            window(),
+            "Code".to_string(),
+            "main.rs".to_string(),
+            true,
+            (100, 50),
+        ),
+    ];
+    let private = vec![PrivateWindow {
+        app_name: "Firefox".to_string(),
+        position: (10, 10),
+        width: 50,
+        height: 20,
+    }];
+    recorder.record(&desktop(10), &windows, &private).unwrap();
+    recorder.record(&desktop(10), &windows, &[]).unwrap();
+    recorder.record(&desktop(200), &windows, &[]).unwrap();
+    assert_eq!(recorder.frames(), 3);
+
+    let manifest = load_replay_manifest(&bundle).unwrap();
+    assert_eq!(manifest.monitor_id, 2);
+    assert_eq!(manifest.frames.len(), 3);
+    assert_eq!(manifest.frames[0].private_windows, private);
+    assert_eq!(manifest.frames[0].windows[1].window_name, "main.rs");
+    assert!(bundle.join(&manifest.frames[2].windows[0].image).exists());
+    // a bundle isn't recorded over
+    assert!(ReplayRecorder::create(&bundle, 2).is_err());
+
+    let report = replay_bundle(&bundle, &ReplayOptions::default())
+        .await
+        .unwrap();
+    let skipped: Vec<bool> = report.frames.iter().map(|frame| frame.skipped).collect();
+    assert_eq!(skipped, vec![false, true, false]);
+    assert_eq!(report.frames[0].difference, 1.0);
+    assert!(report.frames[2].difference > screenpipe_vision::MIN_FRAME_DIFFERENCE);
+    assert!(report.frames[1].windows.is_empty());
+    // focused first, as the capture OCRs them
+    let apps: Vec<&str> = report.frames[2]
+        .windows
+        .iter()
+        .map(|window| window.app_name.as_str())
+        .collect();
+    assert_eq!(apps, vec!["Code", "Terminal"]);
+    assert!(report.frames[2].windows[0].text.is_none());
+
+    // deterministic
+    let again = replay_bundle(&bundle, &ReplayOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        serde_json::to_string(&report).unwrap(),
+        serde_json::to_string(&again).unwrap()
+    );
+}
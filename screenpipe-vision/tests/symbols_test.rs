@@ -0,0 +1,153 @@
+use image::{DynamicImage, Luma, Rgba, RgbaImage};
+use screenpipe_vision::recognize_symbols;
+use screenpipe_vision::symbols::{
+    append_symbols, arrows_in_image, emoji_in_image, symbols_in_text,
+};
+
+fn whiteboard() -> RgbaImage {
+    RgbaImage::from_pixel(200, 120, Rgba([245, 245, 245, 255]))
+}
+
+fn ink(image: &mut RgbaImage, x: u32, y: u32) {
+    if x < image.width() && y < image.height() {
+        image.put_pixel(x, y, Rgba([20, 20, 30, 255]));
+    }
+}
+
+/// A line arrow pointing right, `--->`, with an open head
+fn draw_right_arrow(image: &mut RgbaImage, x: u32, y: u32, length: u32) {
+    for dx in 0..length {
+        ink(image, x + dx, y);
+        ink(image, x + dx, y + 1);
+    }
+    for step in 0..8 {
+        let tip = x + length - 1;
+        ink(image, tip - step, y - step);
+        ink(image, tip - step, y + 1 + step);
+    }
+}
+
+/// A yellow face with dark eyes, as emoji are drawn
+fn draw_face(image: &mut RgbaImage, cx: i32, cy: i32, radius: i32) {
+    for y in -radius..=radius {
+        for x in -radius..=radius {
+            let eye = ((x + radius / 2).abs() <= 1 || (x - radius / 2).abs() <= 1)
+                && (y + radius / 3).abs() <= 1;
+            if x * x + y * y <= radius * radius {
+                let color = if eye {
+                    [40, 30, 20, 255]
+                } else {
+                    [255, 204, 77, 255]
+                };
+                image.put_pixel((cx + x) as u32, (cy + y) as u32, Rgba(color));
+            }
+        }
+    }
+}
+
+/// A red heart, (x² + y² - 1)³ ≤ x²y³
+fn draw_heart(image: &mut RgbaImage, left: u32, top: u32, size: u32) {
+    for py in 0..size {
+        for px in 0..size {
+            let half = size as f32 / 2.0;
+            let x = (px as f32 - half + 0.5) / half * 1.2;
+            let y = -(py as f32 - half + 0.5) / half * 1.2 + 0.15;
+            if (x * x + y * y - 1.0).powi(3) - x * x * y.powi(3) <= 0.0 {
+                image.put_pixel(left + px, top + py, Rgba([221, 46, 68, 255]));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_symbols_spelled_by_the_ocr() {
+    assert_eq!(
+        symbols_in_text("a -> b <=> c <-> d x<=y"),
+        vec!["→", "⇔", "↔", "≤"]
+    );
+    assert_eq!(symbols_in_text("i = n(n+1)/2 :) ok"), vec!["🙂"]);
+    // greek letters are text of their own, not operators
+    assert!(symbols_in_text("Σ-algebra, Π types").is_empty());
+    // emoticons count as words of their own, not inside code
+    assert!(symbols_in_text("f(x:(int))").is_empty());
+}
+
+#[test]
+fn test_arrows_drawn_on_a_whiteboard() {
+    let mut image = whiteboard();
+    draw_right_arrow(&mut image, 20, 30, 60);
+    // a plain line and a letter T aren't arrows
+    for dx in 0..60 {
+        ink(&mut image, 20 + dx, 90);
+    }
+    for dx in 0..12 {
+        ink(&mut image, 150 + dx, 20);
+    }
+    for dy in 0..16 {
+        ink(&mut image, 156, 20 + dy);
+    }
+    let image = DynamicImage::ImageRgba8(image);
+    assert_eq!(arrows_in_image(&image), vec!["→"]);
+
+    let flipped = image.fliph();
+    assert_eq!(arrows_in_image(&flipped), vec!["←"]);
+    let rotated = image.rotate90();
+    assert_eq!(arrows_in_image(&rotated), vec!["↓"]);
+    // dark mode
+    let inverted = {
+        let mut inverted = image.clone();
+        inverted.invert();
+        inverted
+    };
+    assert_eq!(arrows_in_image(&inverted), vec!["→"]);
+    let gray = DynamicImage::ImageLuma8(image::ImageBuffer::from_pixel(50, 50, Luma([0u8])));
+    assert!(arrows_in_image(&gray).is_empty());
+}
+
+#[test]
+fn test_emoji_in_a_chat() {
+    let mut image = whiteboard();
+    draw_face(&mut image, 40, 60, 12);
+    draw_heart(&mut image, 100, 45, 30);
+    // a red badge and a yellow highlighted line aren't emoji
+    for y in 10..30 {
+        for x in 160..180 {
+            image.put_pixel(x, y, Rgba([221, 46, 68, 255]));
+        }
+    }
+    for y in 95..109 {
+        for x in 20..180 {
+            image.put_pixel(x, y, Rgba([255, 230, 80, 255]));
+        }
+    }
+    let image = DynamicImage::ImageRgba8(image);
+    assert_eq!(emoji_in_image(&image), vec!["🙂", "❤"]);
+    // upside down, a heart is a drop
+    assert_eq!(emoji_in_image(&image.flipv()), vec!["🙂"]);
+
+    // a window of a 4K screen is pooled down and the emoji of its text are still found
+    let mut large = RgbaImage::from_pixel(3200, 1800, Rgba([245, 245, 245, 255]));
+    draw_face(&mut large, 400, 300, 24);
+    draw_heart(&mut large, 900, 280, 48);
+    let large = DynamicImage::ImageRgba8(large);
+    assert_eq!(emoji_in_image(&large), vec!["🙂", "❤"]);
+}
+
+#[test]
+fn test_recognized_symbols_are_appended() {
+    let mut image = whiteboard();
+    draw_right_arrow(&mut image, 20, 30, 60);
+    draw_heart(&mut image, 120, 60, 30);
+    let image = DynamicImage::ImageRgba8(image);
+
+    // the arrow the OCR spelled and the one drawn are the same symbol
+    let symbols = recognize_symbols(&image, "input -> output <3");
+    assert_eq!(symbols, vec!["→", "❤"]);
+    assert!(recognize_symbols(&image, "input → output").is_empty());
+
+    let mut text = "input -> output <3".to_string();
+    let mut text_json = Vec::new();
+    append_symbols(&mut text, &mut text_json, &symbols);
+    assert_eq!(text, "input -> output <3\n→ ❤");
+    assert_eq!(text_json[0]["text"], "→ ❤");
+}
@@ -3,6 +3,7 @@
 mod tests {
     use screenpipe_vision::monitor::get_default_monitor;
     use screenpipe_vision::{process_ocr_task, OcrEngine};
+    use std::sync::atomic::AtomicBool;
     use std::sync::Arc;
     use std::{path::PathBuf, time::Instant};
     use tokio::sync::mpsc;
@@ -10,6 +11,7 @@ mod tests {
     use screenpipe_vision::{continuous_capture, CaptureResult};
     use std::time::Duration;
     use tokio::time::timeout;
+    use tokio_util::sync::CancellationToken;
 
     #[cfg(target_os = "windows")]
     #[tokio::test]
@@ -29,20 +31,23 @@ mod tests {
         let app_name = "test_app".to_string();
 
         let window_images = vec![(
-            image.clone(),
+            Arc::new(image.clone()),
             "test_app".to_string(),
             "test_window".to_string(),
             true,
+            (0, 0),
         )];
 
         let result = process_ocr_task(
             image_arc,
             window_images,
+            Vec::new(),
             frame_number,
             timestamp,
             tx,
             false,
             ocr_engine,
+            CancellationToken::new(),
         )
         .await;
 
@@ -71,6 +76,8 @@ mod tests {
             save_text_files_flag,
             ocr_engine,
             monitor,
+            Arc::new(AtomicBool::new(false)),
+            CancellationToken::new(),
         ));
 
         // Wait for a short duration to allow some captures to occur